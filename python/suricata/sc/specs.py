@@ -166,6 +166,12 @@ argsd = {
             "required": 1,
         },
     ],
+    "app-layer-state": [
+        {
+            "name": "protocol",
+            "required": 0,
+        },
+    ],
     "dataset-add": [
         {
             "name": "setname",
@@ -0,0 +1,164 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+extern crate proc_macro;
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{self, parse_macro_input, DeriveInput};
+
+pub fn derive_eve_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut stmts = Vec::new();
+
+    match input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(ref fields),
+            ..
+        }) => {
+            for field in fields.named.iter() {
+                let opts = parse_field_attrs(&field.attrs);
+                if opts.skip {
+                    continue;
+                }
+                let ident = field.ident.as_ref().unwrap();
+                let key = match opts.rename {
+                    Some(key) => key,
+                    None => ident.to_string().trim_start_matches("r#").to_string(),
+                };
+                if opts.hex {
+                    stmts.push(quote! {
+                        crate::jsonbuilder::EveHex::set_eve_hex(&self.#ident, js, #key)?;
+                    });
+                } else {
+                    stmts.push(quote! {
+                        crate::jsonbuilder::EveValue::set_eve(&self.#ident, js, #key)?;
+                    });
+                }
+            }
+        }
+        _ => panic!("EveSerialize can only be derived for structs with named fields"),
+    }
+
+    let expanded = quote! {
+        impl #impl_generics crate::jsonbuilder::EveSerialize for #name #ty_generics #where_clause {
+            fn serialize_eve(
+                &self, js: &mut crate::jsonbuilder::JsonBuilder,
+            ) -> Result<(), crate::jsonbuilder::JsonError> {
+                #( #stmts )*
+                Ok(())
+            }
+        }
+
+        impl #impl_generics crate::jsonbuilder::EveValue for #name #ty_generics #where_clause {
+            fn set_eve(
+                &self, js: &mut crate::jsonbuilder::JsonBuilder, key: &str,
+            ) -> Result<(), crate::jsonbuilder::JsonError> {
+                js.open_object(key)?;
+                crate::jsonbuilder::EveSerialize::serialize_eve(self, js)?;
+                js.close()?;
+                Ok(())
+            }
+        }
+
+        impl #impl_generics crate::jsonbuilder::EveArrayValue for #name #ty_generics #where_clause {
+            fn append_eve(
+                &self, js: &mut crate::jsonbuilder::JsonBuilder,
+            ) -> Result<(), crate::jsonbuilder::JsonError> {
+                js.start_object()?;
+                crate::jsonbuilder::EveSerialize::serialize_eve(self, js)?;
+                js.close()?;
+                Ok(())
+            }
+        }
+    };
+
+    proc_macro::TokenStream::from(expanded)
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct FieldOpts {
+    rename: Option<String>,
+    hex: bool,
+    skip: bool,
+}
+
+/// Parse the `#[eve(...)]` attributes of a field.
+fn parse_field_attrs(attrs: &[syn::Attribute]) -> FieldOpts {
+    let mut opts = FieldOpts::default();
+    for attr in attrs {
+        if !attr.path.is_ident("eve") {
+            continue;
+        }
+        match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => {
+                for nested in list.nested.iter() {
+                    match nested {
+                        syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("hex") => {
+                            opts.hex = true;
+                        }
+                        syn::NestedMeta::Meta(syn::Meta::Path(path)) if path.is_ident("skip") => {
+                            opts.skip = true;
+                        }
+                        syn::NestedMeta::Meta(syn::Meta::NameValue(nv))
+                            if nv.path.is_ident("rename") =>
+                        {
+                            match nv.lit {
+                                syn::Lit::Str(ref s) => {
+                                    opts.rename = Some(s.value());
+                                }
+                                _ => panic!("eve rename expects a string"),
+                            }
+                        }
+                        _ => panic!("unsupported eve attribute"),
+                    }
+                }
+            }
+            _ => panic!("eve attribute expects a list, e.g. #[eve(skip)]"),
+        }
+    }
+    opts
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_field_attrs() {
+        let attrs: Vec<syn::Attribute> = vec![syn::parse_quote!(#[doc = "field"])];
+        assert_eq!(parse_field_attrs(&attrs), FieldOpts::default());
+
+        let attrs: Vec<syn::Attribute> = vec![
+            syn::parse_quote!(#[eve(rename = "name")]),
+            syn::parse_quote!(#[eve(hex)]),
+        ];
+        assert_eq!(
+            parse_field_attrs(&attrs),
+            FieldOpts {
+                rename: Some("name".to_string()),
+                hex: true,
+                skip: false,
+            }
+        );
+
+        let attrs: Vec<syn::Attribute> = vec![syn::parse_quote!(#[eve(skip)])];
+        assert!(parse_field_attrs(&attrs).skip);
+    }
+}
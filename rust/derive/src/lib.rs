@@ -20,6 +20,7 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 
 mod applayerevent;
+mod eve;
 
 /// The `AppLayerEvent` derive macro generates a `AppLayerEvent` trait
 /// implementation for enums that define AppLayerEvents.
@@ -40,3 +41,32 @@ mod applayerevent;
 pub fn derive_app_layer_event(input: TokenStream) -> TokenStream {
     applayerevent::derive_app_layer_event(input)
 }
+
+/// The `EveSerialize` derive macro generates the code to log a struct,
+/// usually a transaction, to EVE with a `JsonBuilder`.
+///
+/// Each field is logged with its name as key, using the
+/// `jsonbuilder::EveValue` implementation of its type. `None` options
+/// and empty vectors are not logged. Nested structs must derive
+/// `EveSerialize` as well and are logged as objects.
+///
+/// Fields can be annotated with:
+///
+/// - `#[eve(rename = "name")]` to log the field under another key
+/// - `#[eve(hex)]` to log bytes as a hex string
+/// - `#[eve(skip)]` to not log the field
+///
+/// Example usage:
+///
+/// #[derive(EveSerialize)]
+/// struct Transaction {
+///     #[eve(skip)]
+///     tx_id: u64,
+///     request: Option<String>,
+///     #[eve(rename = "id", hex)]
+///     session_id: Vec<u8>,
+/// }
+#[proc_macro_derive(EveSerialize, attributes(eve))]
+pub fn derive_eve_serialize(input: TokenStream) -> TokenStream {
+    eve::derive_eve_serialize(input)
+}
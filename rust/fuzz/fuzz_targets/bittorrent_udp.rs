@@ -0,0 +1,6 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    suricata_rust::fuzz::fuzz_parser("bittorrent_udp", data);
+});
@@ -24,18 +24,37 @@ use crate::applayer;
 use std::os::raw::{c_void,c_char,c_int};
 use crate::core::SC;
 use std::ffi::CStr;
+use std::collections::{BTreeMap,HashMap};
+use std::hash::Hash;
+use crate::common::static_cstr;
+
+/// Payload logging as configured for the protocol.
+pub const TX_PAYLOAD_LOG_DEFAULT: u8 = 0;
+/// Don't log the payloads of the transaction.
+pub const TX_PAYLOAD_LOG_NONE: u8 = 1;
+/// Log the payloads of the transaction in full.
+pub const TX_PAYLOAD_LOG_FULL: u8 = 2;
+
+// Values of enum ConfigAction.
+const CONFIG_ACTION_UNSET: c_int = 0;
 
 #[repr(C)]
-#[derive(Default, Debug,PartialEq)]
+#[derive(Default, Debug,PartialEq,Clone,Copy)]
 pub struct AppLayerTxConfig {
     /// config: log flags
     log_flags: u8,
+    /// config: payload logging verbosity, one of TX_PAYLOAD_LOG_*
+    payload_log: u8,
+    /// config: number of bytes of the tx buffers to inspect, 0 for all
+    inspect_depth: u32,
 }
 
 impl AppLayerTxConfig {
     pub fn new() -> Self {
         Self {
             log_flags: 0,
+            payload_log: TX_PAYLOAD_LOG_DEFAULT,
+            inspect_depth: 0,
         }
     }
 
@@ -48,6 +67,62 @@ impl AppLayerTxConfig {
     pub fn get_log_flags(&self) -> u8 {
         self.log_flags
     }
+
+    pub fn set_payload_log(&mut self, payload_log: u8) {
+        self.payload_log = payload_log;
+    }
+    pub fn get_payload_log(&self) -> u8 {
+        self.payload_log
+    }
+    /// Check if the payloads of the transaction should be logged, given
+    /// whether the protocol logs them by default.
+    pub fn log_payload(&self, default: bool) -> bool {
+        match self.payload_log {
+            TX_PAYLOAD_LOG_NONE => false,
+            TX_PAYLOAD_LOG_FULL => true,
+            _ => default,
+        }
+    }
+
+    pub fn set_inspect_depth(&mut self, depth: u32) {
+        self.inspect_depth = depth;
+    }
+    pub fn get_inspect_depth(&self) -> u32 {
+        self.inspect_depth
+    }
+    /// Limit a buffer to the inspection depth.
+    pub fn inspect_limit<'a>(&self, buf: &'a [u8]) -> &'a [u8] {
+        let depth = self.inspect_depth as usize;
+        if depth > 0 && buf.len() > depth {
+            &buf[..depth]
+        } else {
+            buf
+        }
+    }
+
+    /// Apply a config passed to `apply_tx_config`: with the set mode the
+    /// log flags are added and the other values are set if not default,
+    /// with the unset mode the log flags are removed and the values that
+    /// are not default are reset.
+    pub fn apply(&mut self, mode: c_int, config: &AppLayerTxConfig) {
+        if mode == CONFIG_ACTION_UNSET {
+            self.log_flags &= !config.log_flags;
+            if config.payload_log != TX_PAYLOAD_LOG_DEFAULT {
+                self.payload_log = TX_PAYLOAD_LOG_DEFAULT;
+            }
+            if config.inspect_depth != 0 {
+                self.inspect_depth = 0;
+            }
+        } else {
+            self.log_flags |= config.log_flags;
+            if config.payload_log != TX_PAYLOAD_LOG_DEFAULT {
+                self.payload_log = config.payload_log;
+            }
+            if config.inspect_depth != 0 {
+                self.inspect_depth = config.inspect_depth;
+            }
+        }
+    }
 }
 
 #[repr(C)]
@@ -67,6 +142,11 @@ pub struct AppLayerTxData {
     /// detection engine flags for use by detection engine
     detect_flags_ts: u64,
     detect_flags_tc: u64,
+
+    /// time of the request and response data, in milliseconds since the
+    /// epoch, 0 if not recorded
+    ts_request_ms: u64,
+    ts_response_ms: u64,
 }
 
 impl AppLayerTxData {
@@ -79,6 +159,8 @@ impl AppLayerTxData {
             files_stored: 0,
             detect_flags_ts: 0,
             detect_flags_tc: 0,
+            ts_request_ms: 0,
+            ts_response_ms: 0,
         }
     }
     pub fn init_files_opened(&mut self) {
@@ -87,6 +169,63 @@ impl AppLayerTxData {
     pub fn incr_files_opened(&mut self) {
         self.files_opened += 1;
     }
+
+    /// Return the flags of the tx loggers that have logged the
+    /// transaction. The C side reads and updates `logged.flags`
+    /// directly.
+    pub fn logged(&self) -> u64 {
+        self.logged.get()
+    }
+
+    pub fn set_logged(&mut self, bits: u64) {
+        self.logged.set(bits);
+    }
+
+    /// Check if the tx logger with the given id has logged the
+    /// transaction.
+    pub fn is_logged(&self, logger_id: u32) -> bool {
+        self.logged.get() & (1u64 << logger_id) != 0
+    }
+
+    /// Record the time of the last packet of the flow as the time of the
+    /// data of the transaction seen in `direction`. Timestamps are only
+    /// recorded for parsers calling this from their parse functions.
+    pub fn update_timestamp(&mut self, flow: &Flow, direction: core::Direction) {
+        let ts = flow.get_last_time();
+        let ts_ms = ts.as_secs() * 1000 + ts.subsec_millis() as u64;
+        self.set_timestamp_ms(direction, ts_ms);
+    }
+
+    /// Set the time of the data seen in `direction`. The request time is
+    /// the time of the first request data, the response time the time of
+    /// the last response data.
+    pub fn set_timestamp_ms(&mut self, direction: core::Direction, ts_ms: u64) {
+        match direction {
+            core::Direction::ToServer => {
+                if self.ts_request_ms == 0 {
+                    self.ts_request_ms = ts_ms;
+                }
+            }
+            core::Direction::ToClient => {
+                self.ts_response_ms = ts_ms;
+            }
+        }
+    }
+
+    pub fn request_timestamp_ms(&self) -> Option<u64> {
+        if self.ts_request_ms > 0 { Some(self.ts_request_ms) } else { None }
+    }
+
+    pub fn response_timestamp_ms(&self) -> Option<u64> {
+        if self.ts_response_ms > 0 { Some(self.ts_response_ms) } else { None }
+    }
+
+    /// Time between the request and the response, in milliseconds.
+    pub fn response_time_ms(&self) -> Option<u64> {
+        let request = self.request_timestamp_ms()?;
+        let response = self.response_timestamp_ms()?;
+        Some(response.saturating_sub(request))
+    }
 }
 
 #[macro_export]
@@ -135,6 +274,24 @@ impl AppLayerResult {
             needed: needed,
         };
     }
+    /// parser needs more data, after consuming 'consumed' bytes of an
+    /// input of 'input_len' bytes. 'needed' is the number of bytes the
+    /// parser wants from 'consumed' on. The invariants documented on
+    /// incomplete() are checked in debug builds, and enforced otherwise:
+    /// needed is raised to ask for at least one more byte than is left.
+    pub fn incomplete_from(input_len: usize, consumed: usize, needed: usize) -> Self {
+        debug_assert!(consumed <= input_len,
+                      "consumed {} more than input len {}", consumed, input_len);
+        debug_assert!(consumed.saturating_add(needed) > input_len,
+                      "consumed {} + needed {} not more than input len {}",
+                      consumed, needed, input_len);
+        let consumed = std::cmp::min(consumed, input_len);
+        let needed = std::cmp::max(needed, input_len - consumed + 1);
+        if input_len > std::u32::MAX as usize || needed > std::u32::MAX as usize {
+            return Self::err();
+        }
+        Self::incomplete(consumed as u32, needed as u32)
+    }
 
     pub fn is_ok(self) -> bool {
         self.status == 0
@@ -246,6 +403,10 @@ pub struct RustParser {
     /// Function to handle the end of data coming on one of the sides
     /// due to the stream reaching its 'depth' limit.
     pub truncate: Option<TruncateFn>,
+
+    /// Function called periodically with the current time (seconds) so
+    /// the state can expire transactions that will never complete.
+    pub state_purge: Option<StatePurgeFn>,
 }
 
 /// Create a slice, given a buffer and a length
@@ -256,12 +417,105 @@ macro_rules! build_slice {
     ($buf:ident, $len:expr) => ( std::slice::from_raw_parts($buf, $len) );
 }
 
-/// Cast pointer to a variable, as a mutable reference to an object
+/// Marker for the types that are passed to C as opaque pointers, and so
+/// may be cast back from a raw pointer with `cast_mut`.
 ///
-/// UNSAFE !
+/// # Safety
+///
+/// Only implement this for types that are handed to C as a pointer to
+/// the type itself, such as parser states and transactions.
+pub unsafe trait FfiType {}
+
+/// Cast a pointer received from C back to a mutable reference to the
+/// object it was created from.
+///
+/// # Safety
+///
+/// The pointer must point to a live `T`, and no other reference to it
+/// may be in use for the lifetime of the result. A null pointer is
+/// caught in debug builds only.
+#[inline]
+pub unsafe fn cast_mut<'a, T: FfiType, P>(ptr: *const P) -> &'a mut T {
+    debug_assert!(!ptr.is_null(), "null pointer");
+    &mut *(ptr as *mut T)
+}
+
+/// A parser state that can be told its stream depth was reached in a
+/// direction, so no more data will be parsed in it.
+pub trait Truncate {
+    /// Mark the open transactions waiting for data in `direction` as
+    /// complete, so they can be logged and freed.
+    fn truncate(&mut self, direction: core::Direction);
+}
+
+/// Generic `truncate` callback for states implementing `Truncate`, to
+/// register as `truncate: Some(applayer::state_truncate::<MyState>)`.
+pub unsafe extern "C" fn state_truncate<S: Truncate + FfiType>(state: *mut c_void, direction: u8) {
+    let state: &mut S = cast_mut(state);
+    state.truncate(core::Direction::from(direction));
+}
+
+/// Result of a probing parser.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProbeResult {
+    /// The data matches the protocol, in the direction it was seen in.
+    Found(AppProto),
+    /// Not enough data to decide yet.
+    Unknown,
+    /// The data does not match the protocol.
+    Failed,
+    /// The data matches the protocol, but belongs to the opposite
+    /// direction (e.g. a response seen first).
+    Flipped(AppProto),
+}
+
+impl ProbeResult {
+    /// Return `Found` if the data belongs to `direction`, the direction it
+    /// was seen in, or `Flipped` if it belongs to `actual` and that is the
+    /// opposite direction.
+    pub fn with_direction(alproto: AppProto, direction: core::Direction,
+                          actual: core::Direction) -> ProbeResult {
+        if direction == actual {
+            ProbeResult::Found(alproto)
+        } else {
+            ProbeResult::Flipped(alproto)
+        }
+    }
+
+    /// Convert to the value returned to the C probing code, setting
+    /// `rdir` to the reversed direction for `Flipped` results.
+    pub fn to_c(self, direction: u8, rdir: Option<&mut u8>) -> AppProto {
+        match self {
+            ProbeResult::Found(alproto) => alproto,
+            ProbeResult::Unknown => core::ALPROTO_UNKNOWN,
+            ProbeResult::Failed => unsafe { core::ALPROTO_FAILED },
+            ProbeResult::Flipped(alproto) => {
+                if let Some(rdir) = rdir {
+                    *rdir = core::Direction::from(direction).reverse().into();
+                }
+                alproto
+            }
+        }
+    }
+}
+
+/// Export a safe probing function `fn(&[u8], Direction) -> ProbeResult`
+/// as a probing parser with the C ABI.
 #[macro_export]
-macro_rules! cast_pointer {
-    ($ptr:ident, $ty:ty) => ( &mut *($ptr as *mut $ty) );
+macro_rules! export_probe {
+    ($name:ident, $probe:expr) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(
+            _flow: *const $crate::core::Flow, direction: u8, input: *const u8, input_len: u32,
+            rdir: *mut u8,
+        ) -> $crate::core::AppProto {
+            if input.is_null() {
+                return $crate::applayer::ProbeResult::Unknown.to_c(direction, rdir.as_mut());
+            }
+            let slice = std::slice::from_raw_parts(input, input_len as usize);
+            $probe(slice, $crate::core::Direction::from(direction)).to_c(direction, rdir.as_mut())
+        }
+    };
 }
 
 pub type ParseFn      = unsafe extern "C" fn (flow: *const Flow,
@@ -296,6 +550,7 @@ pub type GetTxIteratorFn    = unsafe extern "C" fn (ipproto: u8, alproto: AppPro
 pub type GetTxDataFn = unsafe extern "C" fn(*mut c_void) -> *mut AppLayerTxData;
 pub type ApplyTxConfigFn = unsafe extern "C" fn (*mut c_void, *mut c_void, c_int, AppLayerTxConfig);
 pub type TruncateFn = unsafe extern "C" fn (*mut c_void, u8);
+pub type StatePurgeFn = unsafe extern "C" fn (*mut c_void, u64);
 
 
 // Defined in app-layer-register.h
@@ -304,8 +559,25 @@ extern {
     pub fn AppLayerRegisterParserAlias(parser_name: *const c_char, alias_name: *const c_char);
 }
 
+/// Register the alternative names a parser is known by in the
+/// configuration and in rules, e.g. "ikev2" for "ike".
+///
+/// The C side keeps the pointers to the names, so they come from
+/// `static_cstr`.
+pub unsafe fn register_parser_aliases(parser: &RustParser, aliases: &[&str]) {
+    for alias in aliases {
+        let alias = static_cstr(alias);
+        if !alias.is_null() {
+            AppLayerRegisterParserAlias(parser.name, alias);
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 pub unsafe fn AppLayerRegisterParser(parser: *const RustParser, alproto: AppProto) -> c_int {
+    if let Some(parser) = parser.as_ref() {
+        crate::eventmap::register(parser);
+    }
     (SC.unwrap().AppLayerRegisterParser)(parser, alproto)
 }
 
@@ -322,8 +594,12 @@ extern {
                                                      pattern: *const c_char, depth: u16,
                                                      offset: u16, direction: u8, ppfn: ProbeFn,
                                                      pp_min_depth: u16, pp_max_depth: u16) -> c_int;
+    pub fn AppLayerProtoDetectPMRegisterPatternCI(ipproto: u8, alproto: AppProto,
+                                                  pattern: *const c_char, depth: u16,
+                                                  offset: u16, direction: u8) -> c_int;
     pub fn AppLayerProtoDetectConfProtoDetectionEnabled(ipproto: *const c_char, proto: *const c_char) -> c_int;
     pub fn AppLayerProtoDetectConfProtoDetectionEnabledDefault(ipproto: *const c_char, proto: *const c_char, default: bool) -> c_int;
+    pub fn AppLayerRequestProtocolChange(flow: *const Flow, dp: u16, expect_proto: AppProto);
 }
 
 // Defined in app-layer-parser.h
@@ -353,11 +629,55 @@ extern {
     pub fn AppLayerParserRegisterOptionFlags(ipproto: u8, alproto: AppProto, flags: u32);
 }
 
+/// Check if the parser state `pstate`, as passed to the parse functions,
+/// has seen the end of the stream in `direction`.
+pub unsafe fn parser_state_is_eof(pstate: *mut c_void, direction: core::Direction) -> bool {
+    if pstate.is_null() {
+        return false;
+    }
+    let flag = match direction {
+        core::Direction::ToServer => APP_LAYER_PARSER_EOF_TS,
+        core::Direction::ToClient => APP_LAYER_PARSER_EOF_TC,
+    };
+    AppLayerParserStateIssetFlag(pstate, flag) > 0
+}
+
+/// Stop the inspection of the flow of the parser state `pstate`, and
+/// have it bypassed if bypass is enabled. For use once the rest of the
+/// flow is of no interest, such as after the switch to an encrypted
+/// channel. The parser is not called for the flow anymore.
+pub unsafe fn parser_state_set_bypass_ready(pstate: *mut c_void) {
+    if pstate.is_null() {
+        return;
+    }
+    AppLayerParserStateSetFlag(pstate,
+                               APP_LAYER_PARSER_NO_INSPECTION
+                               | APP_LAYER_PARSER_NO_REASSEMBLY
+                               | APP_LAYER_PARSER_BYPASS_READY);
+}
+
+/// Set the stream reassembly depth of a protocol, 0 for unlimited.
+pub fn set_stream_depth(ipproto: u8, alproto: AppProto, stream_depth: u32) {
+    unsafe {
+        AppLayerParserSetStreamDepth(ipproto, alproto, stream_depth);
+    }
+}
+
+/// Have the data of `flow` reassembled and inspected by the raw stream
+/// inspection in `direction` now, instead of at the next chunk size.
+pub fn trigger_raw_stream_reassembly(flow: *const Flow, direction: core::Direction) {
+    let direction = match direction {
+        core::Direction::ToServer => core::STREAM_TOSERVER,
+        core::Direction::ToClient => core::STREAM_TOCLIENT,
+    };
+    core::sc_app_layer_parser_trigger_raw_stream_reassembly(flow, direction as i32);
+}
+
 #[repr(C)]
 pub struct AppLayerGetTxIterTuple {
-    tx_ptr: *mut std::os::raw::c_void,
-    tx_id: u64,
-    has_next: bool,
+    pub tx_ptr: *mut std::os::raw::c_void,
+    pub tx_id: u64,
+    pub has_next: bool,
 }
 
 impl AppLayerGetTxIterTuple {
@@ -377,7 +697,7 @@ impl AppLayerGetTxIterTuple {
 #[repr(C)]
 #[derive(Default, Debug,PartialEq)]
 pub struct LoggerFlags {
-    flags: u32,
+    flags: u64,
 }
 
 impl LoggerFlags {
@@ -386,33 +706,80 @@ impl LoggerFlags {
         Default::default()
     }
 
-    pub fn get(&self) -> u32 {
+    pub fn get(&self) -> u64 {
         self.flags
     }
 
-    pub fn set(&mut self, bits: u32) {
+    pub fn set(&mut self, bits: u64) {
         self.flags = bits;
     }
 
 }
 
+/// DetectStateHolder owns the DetectEngineState of a transaction. The
+/// state is allocated by the detection engine and handed to the
+/// transaction through the `set_de_state` callback, and freed when the
+/// holder is dropped together with its transaction.
+#[derive(Debug,PartialEq)]
+pub struct DetectStateHolder {
+    state: Option<*mut core::DetectEngineState>,
+}
+
+impl Default for DetectStateHolder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DetectStateHolder {
+    pub fn new() -> Self {
+        Self { state: None }
+    }
+
+    /// Return the detection state, or NULL if none was set.
+    pub fn get(&self) -> *mut core::DetectEngineState {
+        match self.state {
+            Some(ds) => ds,
+            None => std::ptr::null_mut(),
+        }
+    }
+
+    /// Take ownership of a detection state, freeing any previous one.
+    pub fn set(&mut self, state: *mut core::DetectEngineState) {
+        if let Some(old) = self.state {
+            if old != state {
+                core::sc_detect_engine_state_free(old);
+            }
+        }
+        self.state = Some(state);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.state.is_some()
+    }
+}
+
+impl Drop for DetectStateHolder {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.take() {
+            core::sc_detect_engine_state_free(state);
+        }
+    }
+}
+
 /// Export a function to get the DetectEngineState on a struct.
+///
+/// The struct is expected to hold its state in a `de_state` field
+/// of type `DetectStateHolder`.
 #[macro_export]
 macro_rules!export_tx_get_detect_state {
     ($name:ident, $type:ty) => (
         #[no_mangle]
         pub unsafe extern "C" fn $name(tx: *mut std::os::raw::c_void)
-            -> *mut core::DetectEngineState
+            -> *mut crate::core::DetectEngineState
         {
-            let tx = cast_pointer!(tx, $type);
-            match tx.de_state {
-                Some(ds) => {
-                    return ds;
-                },
-                None => {
-                    return std::ptr::null_mut();
-                }
-            }
+            let tx: &mut $type = $crate::applayer::cast_mut(tx);
+            tx.de_state.get()
         }
     )
 }
@@ -423,15 +790,25 @@ macro_rules!export_tx_set_detect_state {
     ($name:ident, $type:ty) => (
         #[no_mangle]
         pub unsafe extern "C" fn $name(tx: *mut std::os::raw::c_void,
-                de_state: &mut core::DetectEngineState) -> std::os::raw::c_int
+                de_state: &mut crate::core::DetectEngineState) -> std::os::raw::c_int
         {
-            let tx = cast_pointer!(tx, $type);
-            tx.de_state = Some(de_state);
+            let tx: &mut $type = $crate::applayer::cast_mut(tx);
+            tx.de_state.set(de_state);
             0
         }
     )
 }
 
+/// Export both the get and set DetectEngineState functions for a
+/// transaction type holding a `DetectStateHolder` in `de_state`.
+#[macro_export]
+macro_rules!export_tx_detect_state {
+    ($get_name:ident, $set_name:ident, $type:ty) => (
+        export_tx_get_detect_state!($get_name, $type);
+        export_tx_set_detect_state!($set_name, $type);
+    )
+}
+
 /// AppLayerEvent trait that will be implemented on enums that
 /// derive AppLayerEvent.
 pub trait AppLayerEvent {
@@ -507,3 +884,229 @@ pub unsafe fn get_event_info_by_id<T: AppLayerEvent>(
     }
     return -1;
 }
+
+/// PairingTable matches responses to the pending request transaction
+/// they belong to, using a protocol specific key such as a packet id,
+/// a transaction id (xid) or a message id.
+///
+/// Each pending entry records the transaction id and the time (in
+/// seconds) the request was seen. Entries are removed when the response
+/// is paired, when they time out, or when they are evicted because the
+/// table is full. A `max_pending` or `timeout` of 0 disables the
+/// respective limit.
+///
+/// Parsers logging every message as a transaction of its own, like DHCP,
+/// NTP and IKE, have no responses to pair and do not use it.
+#[derive(Debug)]
+pub struct PairingTable<K> {
+    by_key: HashMap<K, u64>,
+    by_tx: BTreeMap<u64, (K, u64)>,
+    max_pending: usize,
+    timeout: u64,
+}
+
+impl<K: Eq + Hash + Clone> Default for PairingTable<K> {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl<K: Eq + Hash + Clone> PairingTable<K> {
+    pub fn new(max_pending: usize, timeout: u64) -> Self {
+        Self {
+            by_key: HashMap::new(),
+            by_tx: BTreeMap::new(),
+            max_pending: max_pending,
+            timeout: timeout,
+        }
+    }
+
+    pub fn set_max_pending(&mut self, max_pending: usize) {
+        self.max_pending = max_pending;
+    }
+
+    pub fn set_timeout(&mut self, timeout: u64) {
+        self.timeout = timeout;
+    }
+
+    /// Number of requests waiting for a response.
+    pub fn len(&self) -> usize {
+        self.by_key.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_key.is_empty()
+    }
+
+    /// Register transaction `tx_id` as waiting for a response with `key`.
+    ///
+    /// Returns the ids of the transactions that are no longer pending
+    /// as a result: a previous request using the same key, and the
+    /// oldest requests if the table exceeded `max_pending`.
+    pub fn insert(&mut self, key: K, tx_id: u64, ts: u64) -> Vec<u64> {
+        let mut dropped = Vec::new();
+        if let Some(old) = self.by_key.insert(key.clone(), tx_id) {
+            self.by_tx.remove(&old);
+            dropped.push(old);
+        }
+        self.by_tx.insert(tx_id, (key, ts));
+        if self.max_pending > 0 {
+            while self.by_tx.len() > self.max_pending {
+                let oldest = match self.by_tx.keys().next() {
+                    Some(id) => *id,
+                    None => break,
+                };
+                self.remove_tx(oldest);
+                dropped.push(oldest);
+            }
+        }
+        dropped
+    }
+
+    /// Return the transaction id waiting for `key`, leaving it pending.
+    pub fn get(&self, key: &K) -> Option<u64> {
+        self.by_key.get(key).cloned()
+    }
+
+    /// Return the transaction id waiting for `key` and remove it from
+    /// the table, as the response completes the exchange.
+    pub fn take(&mut self, key: &K) -> Option<u64> {
+        let tx_id = self.by_key.remove(key)?;
+        self.by_tx.remove(&tx_id);
+        Some(tx_id)
+    }
+
+    /// Forget about a transaction, for example because it was freed.
+    pub fn remove_tx(&mut self, tx_id: u64) -> bool {
+        if let Some((key, _)) = self.by_tx.remove(&tx_id) {
+            self.by_key.remove(&key);
+            return true;
+        }
+        false
+    }
+
+    /// Set the time of the requests registered with a time of 0, for
+    /// callers that only learn the current time after registering them.
+    pub fn set_unknown_ts(&mut self, ts: u64) {
+        for (_, entry_ts) in self.by_tx.values_mut() {
+            if *entry_ts == 0 {
+                *entry_ts = ts;
+            }
+        }
+    }
+
+    /// Remove and return the ids of all transactions that have been
+    /// waiting for longer than the timeout at time `now`.
+    pub fn expire(&mut self, now: u64) -> Vec<u64> {
+        if self.timeout == 0 {
+            return Vec::new();
+        }
+        let timeout = self.timeout;
+        let expired: Vec<u64> = self.by_tx.iter()
+            .filter(|(_, (_, ts))| now.saturating_sub(*ts) > timeout)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in &expired {
+            self.remove_tx(*id);
+        }
+        expired
+    }
+
+    pub fn clear(&mut self) {
+        self.by_key.clear();
+        self.by_tx.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pairing_table_take() {
+        let mut table: PairingTable<u16> = PairingTable::new(0, 0);
+        assert!(table.insert(1, 10, 0).is_empty());
+        assert!(table.insert(2, 11, 0).is_empty());
+        assert_eq!(table.get(&1), Some(10));
+        assert_eq!(table.take(&1), Some(10));
+        assert_eq!(table.take(&1), None);
+        assert_eq!(table.len(), 1);
+        assert!(table.remove_tx(11));
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_pairing_table_key_reuse() {
+        let mut table: PairingTable<u16> = PairingTable::new(0, 0);
+        table.insert(1, 10, 0);
+        assert_eq!(table.insert(1, 12, 0), vec![10]);
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.take(&1), Some(12));
+    }
+
+    #[test]
+    fn test_pairing_table_limit() {
+        let mut table: PairingTable<u32> = PairingTable::new(2, 0);
+        table.insert(1, 1, 0);
+        table.insert(2, 2, 0);
+        assert_eq!(table.insert(3, 3, 0), vec![1]);
+        assert_eq!(table.get(&1), None);
+        assert_eq!(table.get(&3), Some(3));
+    }
+
+    #[test]
+    fn test_pairing_table_expire() {
+        let mut table: PairingTable<u32> = PairingTable::new(0, 10);
+        table.insert(1, 1, 100);
+        table.insert(2, 2, 105);
+        assert!(table.expire(110).is_empty());
+        assert_eq!(table.expire(111), vec![1]);
+        assert_eq!(table.expire(120), vec![2]);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_probe_result_to_c() {
+        let mut rdir: u8 = 0;
+        assert_eq!(ProbeResult::Found(7).to_c(core::STREAM_TOSERVER, Some(&mut rdir)), 7);
+        assert_eq!(rdir, 0);
+        assert_eq!(ProbeResult::Unknown.to_c(core::STREAM_TOSERVER, Some(&mut rdir)),
+                   core::ALPROTO_UNKNOWN);
+        assert_eq!(rdir, 0);
+
+        let result = ProbeResult::with_direction(7, core::Direction::ToClient,
+                                                 core::Direction::ToServer);
+        assert_eq!(result, ProbeResult::Flipped(7));
+        assert_eq!(result.to_c(core::STREAM_TOCLIENT, Some(&mut rdir)), 7);
+        assert_eq!(rdir, core::STREAM_TOSERVER);
+    }
+
+    #[test]
+    fn test_result_incomplete_from() {
+        assert_eq!(AppLayerResult::incomplete_from(10, 4, 7),
+                   AppLayerResult::incomplete(4, 7));
+        assert_eq!(AppLayerResult::incomplete_from(10, 10, 1),
+                   AppLayerResult::incomplete(10, 1));
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(debug_assertions)]
+    fn test_result_incomplete_from_needed_too_small() {
+        AppLayerResult::incomplete_from(10, 4, 6);
+    }
+
+    #[test]
+    fn test_tx_data_timestamps() {
+        let mut tx_data = AppLayerTxData::new();
+        assert_eq!(tx_data.response_time_ms(), None);
+        tx_data.set_timestamp_ms(core::Direction::ToServer, 1000);
+        tx_data.set_timestamp_ms(core::Direction::ToServer, 1100);
+        assert_eq!(tx_data.request_timestamp_ms(), Some(1000));
+        assert_eq!(tx_data.response_time_ms(), None);
+        tx_data.set_timestamp_ms(core::Direction::ToClient, 1200);
+        tx_data.set_timestamp_ms(core::Direction::ToClient, 1250);
+        assert_eq!(tx_data.response_timestamp_ms(), Some(1250));
+        assert_eq!(tx_data.response_time_ms(), Some(250));
+    }
+}
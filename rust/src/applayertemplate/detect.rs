@@ -0,0 +1,122 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Sticky buffers on the request and the response of a transaction.
+
+use super::template::{TemplateTransaction, ALPROTO_TEMPLATE};
+use crate::applayer::cast_mut;
+/* TEMPLATE_START_REMOVE */
+use crate::core::ALPROTO_UNKNOWN;
+/* TEMPLATE_END_REMOVE */
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_TEMPLATE_REQUEST_BUFFER_ID: c_int = 0;
+static mut G_TEMPLATE_RESPONSE_BUFFER_ID: c_int = 0;
+
+unsafe fn set_buffer(data: &Option<String>, buf: *mut *const u8, len: *mut u32) -> bool {
+    match data {
+        Some(data) => {
+            *buf = data.as_ptr();
+            *len = data.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn template_tx_get_request(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut TemplateTransaction = cast_mut(tx);
+    set_buffer(&tx.request, buf, len)
+}
+
+unsafe extern "C" fn template_tx_get_response(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut TemplateTransaction = cast_mut(tx);
+    set_buffer(&tx.response, buf, len)
+}
+
+unsafe extern "C" fn template_request_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, template_tx_get_request)
+}
+
+unsafe extern "C" fn template_response_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, template_tx_get_response)
+}
+
+unsafe extern "C" fn template_request_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_TEMPLATE, G_TEMPLATE_REQUEST_BUFFER_ID)
+}
+
+unsafe extern "C" fn template_response_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_TEMPLATE, G_TEMPLATE_RESPONSE_BUFFER_ID)
+}
+
+/// Register the Template keywords.
+pub unsafe fn register_keywords() {
+    /* TEMPLATE_START_REMOVE */
+    // The parser is only registered if enabled in config.
+    if ALPROTO_TEMPLATE == ALPROTO_UNKNOWN {
+        return;
+    }
+    /* TEMPLATE_END_REMOVE */
+    let kw = DetectKeyword {
+        name: "template.request",
+        desc: "sticky buffer to match on the Template requests",
+        url: "/rules/template-keywords.html#template-request",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: template_request_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_TEMPLATE_REQUEST_BUFFER_ID = helper_buffer_mpm_register(
+            "template.request", "template request", ALPROTO_TEMPLATE, false, true, 0,
+            template_request_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "template.response",
+        desc: "sticky buffer to match on the Template responses",
+        url: "/rules/template-keywords.html#template-response",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: template_response_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_TEMPLATE_RESPONSE_BUFFER_ID = helper_buffer_mpm_register(
+            "template.response", "template response", ALPROTO_TEMPLATE, true, false, 1,
+            template_response_get_data);
+    }
+}
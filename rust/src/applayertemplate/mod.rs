@@ -1,4 +1,4 @@
-/* Copyright (C) 2018 Open Information Security Foundation
+/* Copyright (C) 2018-2021 Open Information Security Foundation
  *
  * You can copy, redistribute or modify this Program under the terms of
  * the GNU General Public License version 2 as published by the Free
@@ -15,6 +15,12 @@
  * 02110-1301, USA.
  */
 
+//! Template application layer, parser, logger and detection module.
+//!
+//! The messages are a length, a colon and the text. The transactions
+//! are a request and its response.
+
+pub mod detect;
 pub mod template;
 mod parser;
 /* TEMPLATE_START_REMOVE */
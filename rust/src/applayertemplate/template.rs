@@ -1,4 +1,4 @@
-/* Copyright (C) 2018-2020 Open Information Security Foundation
+/* Copyright (C) 2018-2021 Open Information Security Foundation
  *
  * You can copy, redistribute or modify this Program under the terms of
  * the GNU General Public License version 2 as published by the Free
@@ -15,239 +15,225 @@
  * 02110-1301, USA.
  */
 
-use std;
-use crate::core::{self, ALPROTO_UNKNOWN, AppProto, Flow, IPPROTO_TCP};
+use super::parser;
 use crate::applayer::{self, *};
-use std::ffi::CString;
+/* TEMPLATE_START_REMOVE */
+use crate::conf::conf_get_app_layer_node;
+/* TEMPLATE_END_REMOVE */
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
 use nom;
-use super::parser;
+use std::ffi::CString;
 
-static mut ALPROTO_TEMPLATE: AppProto = ALPROTO_UNKNOWN;
+declare_counters!(template_stats, "app_layer.template", {
+    requests,
+    responses,
+    anomalies,
+});
 
 #[derive(AppLayerEvent)]
-enum TemplateEvent {}
+pub enum TemplateEvent {
+    MalformedData,
+    MessageTooLong,
+    UnsolicitedResponse,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static TEMPLATE_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static TEMPLATE_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum length of a message, with its length prefix. Longer messages
+/// are not buffered.
+const TEMPLATE_MAX_MESSAGE_LEN: usize = 65536;
+
+/// Number of bytes the probing parser looks into for the length prefix.
+const TEMPLATE_PROBE_LEN: usize = 10;
 
+pub static mut ALPROTO_TEMPLATE: AppProto = ALPROTO_UNKNOWN;
+
+/// A request and its response.
+#[derive(Debug)]
 pub struct TemplateTransaction {
-    tx_id: u64,
+    id: u64,
     pub request: Option<String>,
     pub response: Option<String>,
-
-    de_state: Option<*mut core::DetectEngineState>,
+    de_state: DetectStateHolder,
     events: *mut core::AppLayerDecoderEvents,
-    tx_data: AppLayerTxData,
+    tx_data: applayer::AppLayerTxData,
 }
 
+unsafe impl FfiType for TemplateTransaction {}
+
 impl TemplateTransaction {
-    pub fn new() -> TemplateTransaction {
+    fn new(id: u64) -> TemplateTransaction {
         TemplateTransaction {
-            tx_id: 0,
+            id,
             request: None,
             response: None,
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
-            tx_data: AppLayerTxData::new(),
+            tx_data: applayer::AppLayerTxData::new(),
         }
     }
 
-    pub fn free(&mut self) {
-        if self.events != std::ptr::null_mut() {
-            core::sc_app_layer_decoder_events_free_events(&mut self.events);
-        }
-        if let Some(state) = self.de_state {
-            core::sc_detect_engine_state_free(state);
-        }
+    fn set_event(&mut self, event: TemplateEvent) {
+        stats_incr!(template_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
     }
 }
 
 impl Drop for TemplateTransaction {
     fn drop(&mut self) {
-        self.free();
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for TemplateTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = TemplateEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
     }
 }
 
 pub struct TemplateState {
-    tx_id: u64,
-    transactions: Vec<TemplateTransaction>,
+    transactions: TxTable<TemplateTransaction>,
     request_gap: bool,
     response_gap: bool,
 }
 
+unsafe impl FfiType for TemplateState {}
+
+impl txtable::State for TemplateState {
+    type Tx = TemplateTransaction;
+
+    fn tx_table(&self) -> &TxTable<TemplateTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<TemplateTransaction> {
+        &mut self.transactions
+    }
+}
+
 impl TemplateState {
-    pub fn new() -> Self {
-        Self {
-            tx_id: 0,
-            transactions: Vec::new(),
+    pub fn new() -> TemplateState {
+        TemplateState {
+            transactions: TxTable::with_limit(&TEMPLATE_TX_LIMIT).with_gauges(&TEMPLATE_GAUGES),
             request_gap: false,
             response_gap: false,
         }
     }
 
-    // Free a transaction by ID.
     fn free_tx(&mut self, tx_id: u64) {
-        let len = self.transactions.len();
-        let mut found = false;
-        let mut index = 0;
-        for i in 0..len {
-            let tx = &self.transactions[i];
-            if tx.tx_id == tx_id + 1 {
-                found = true;
-                index = i;
-                break;
-            }
-        }
-        if found {
-            self.transactions.remove(index);
-        }
+        self.transactions.free(tx_id);
     }
 
-    pub fn get_tx(&mut self, tx_id: u64) -> Option<&TemplateTransaction> {
-        for tx in &mut self.transactions {
-            if tx.tx_id == tx_id + 1 {
-                return Some(tx);
-            }
-        }
-        return None;
+    fn new_tx(&mut self) -> TemplateTransaction {
+        TemplateTransaction::new(self.transactions.next_id())
     }
 
-    fn new_tx(&mut self) -> TemplateTransaction {
-        let mut tx = TemplateTransaction::new();
-        self.tx_id += 1;
-        tx.tx_id = self.tx_id;
-        return tx;
+    fn set_event(&mut self, event: TemplateEvent) {
+        let mut tx = self.new_tx();
+        tx.set_event(event);
+        self.transactions.push(tx);
     }
 
+    /// The oldest transaction still waiting for its response.
     fn find_request(&mut self) -> Option<&mut TemplateTransaction> {
-        for tx in &mut self.transactions {
-            if tx.response.is_none() {
-                return Some(tx);
-            }
-        }
-        None
+        self.transactions.iter_mut()
+            .find(|tx| tx.request.is_some() && tx.response.is_none())
     }
 
-    fn parse_request(&mut self, input: &[u8]) -> AppLayerResult {
-        // We're not interested in empty requests.
-        if input.len() == 0 {
-            return AppLayerResult::ok();
+    fn handle_message(&mut self, message: String, direction: Direction) {
+        SCLogDebug!("{:?} message {:?}", direction, message);
+        if direction == Direction::ToServer {
+            stats_incr!(template_stats::requests);
+            let mut tx = self.new_tx();
+            tx.request = Some(message);
+            self.transactions.push(tx);
+            return;
         }
-
-        // If there was gap, check we can sync up again.
-        if self.request_gap {
-            if probe(input).is_err() {
-                // The parser now needs to decide what to do as we are not in sync.
-                // For this template, we'll just try again next time.
-                return AppLayerResult::ok();
-            }
-
-            // It looks like we're in sync with a message header, clear gap
-            // state and keep parsing.
-            self.request_gap = false;
+        stats_incr!(template_stats::responses);
+        if let Some(tx) = self.find_request() {
+            tx.response = Some(message);
+            return;
         }
-
-        let mut start = input;
-        while start.len() > 0 {
-            match parser::parse_message(start) {
-                Ok((rem, request)) => {
-                    start = rem;
-
-                    SCLogNotice!("Request: {}", request);
-                    let mut tx = self.new_tx();
-                    tx.request = Some(request);
-                    self.transactions.push(tx);
-                },
-                Err(nom::Err::Incomplete(_)) => {
-                    // Not enough data. This parser doesn't give us a good indication
-                    // of how much data is missing so just ask for one more byte so the
-                    // parse is called as soon as more data is received.
-                    let consumed = input.len() - start.len();
-                    let needed = start.len() + 1;
-                    return AppLayerResult::incomplete(consumed as u32, needed as u32);
-                },
-                Err(_) => {
-                    return AppLayerResult::err();
-                },
-            }
-        }
-
-        // Input was fully consumed.
-        return AppLayerResult::ok();
+        let mut tx = self.new_tx();
+        tx.response = Some(message);
+        tx.set_event(TemplateEvent::UnsolicitedResponse);
+        self.transactions.push(tx);
     }
 
-    fn parse_response(&mut self, input: &[u8]) -> AppLayerResult {
-        // We're not interested in empty responses.
-        if input.len() == 0 {
+    fn parse(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
+        // We're not interested in empty messages.
+        if input.is_empty() {
             return AppLayerResult::ok();
         }
 
-        if self.response_gap {
+        // After a gap, resume at the next message that looks like one.
+        let gap = if direction == Direction::ToServer {
+            &mut self.request_gap
+        } else {
+            &mut self.response_gap
+        };
+        if *gap {
             if probe(input).is_err() {
-                // The parser now needs to decide what to do as we are not in sync.
-                // For this template, we'll just try again next time.
+                // Not in sync yet: drop the data and try again with the
+                // next segment.
                 return AppLayerResult::ok();
             }
-
-            // It looks like we're in sync with a message header, clear gap
-            // state and keep parsing.
-            self.response_gap = false;
+            *gap = false;
         }
+
         let mut start = input;
-        while start.len() > 0 {
+        while !start.is_empty() {
             match parser::parse_message(start) {
-                Ok((rem, response)) => {
+                Ok((rem, message)) => {
                     start = rem;
-
-                    match self.find_request() {
-                        Some(tx) => {
-                            tx.response = Some(response);
-                            SCLogNotice!("Found response for request:");
-                            SCLogNotice!("- Request: {:?}", tx.request);
-                            SCLogNotice!("- Response: {:?}", tx.response);
-                        }
-                        None => {}
-                    }
+                    self.handle_message(message, direction);
                 }
                 Err(nom::Err::Incomplete(_)) => {
+                    if start.len() >= TEMPLATE_MAX_MESSAGE_LEN {
+                        self.set_event(TemplateEvent::MessageTooLong);
+                        return AppLayerResult::err();
+                    }
+                    // The parser doesn't tell how much data is missing, so
+                    // ask for one more byte.
                     let consumed = input.len() - start.len();
-                    let needed = start.len() + 1;
-                    return AppLayerResult::incomplete(consumed as u32, needed as u32);
+                    return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                           start.len() + 1);
                 }
                 Err(_) => {
+                    self.set_event(TemplateEvent::MalformedData);
                     return AppLayerResult::err();
                 }
             }
         }
 
-        // All input was fully consumed.
-        return AppLayerResult::ok();
+        AppLayerResult::ok()
     }
 
-    fn tx_iterator(
-        &mut self,
-        min_tx_id: u64,
-        state: &mut u64,
-    ) -> Option<(&TemplateTransaction, u64, bool)> {
-        let mut index = *state as usize;
-        let len = self.transactions.len();
-
-        while index < len {
-            let tx = &self.transactions[index];
-            if tx.tx_id < min_tx_id + 1 {
-                index += 1;
-                continue;
-            }
-            *state = index as u64;
-            return Some((tx, tx.tx_id - 1, (len - index) > 1));
+    fn on_gap(&mut self, direction: Direction) {
+        if direction == Direction::ToServer {
+            self.request_gap = true;
+        } else {
+            self.response_gap = true;
         }
-
-        return None;
-    }
-
-    fn on_request_gap(&mut self, _size: u32) {
-        self.request_gap = true;
-    }
-
-    fn on_response_gap(&mut self, _size: u32) {
-        self.response_gap = true;
     }
 }
 
@@ -257,7 +243,7 @@ impl TemplateState {
 /// as a string followed by a ':', we look at up to the first 10
 /// characters for that pattern.
 fn probe(input: &[u8]) -> nom::IResult<&[u8], ()> {
-    let size = std::cmp::min(10, input.len());
+    let size = std::cmp::min(TEMPLATE_PROBE_LEN, input.len());
     let (rem, prefix) = nom::bytes::complete::take(size)(input)?;
     nom::sequence::terminated(
         nom::bytes::complete::take_while1(nom::character::is_digit),
@@ -266,41 +252,26 @@ fn probe(input: &[u8]) -> nom::IResult<&[u8], ()> {
     Ok((rem, ()))
 }
 
-// C exports.
-
-export_tx_get_detect_state!(
-    rs_template_tx_get_detect_state,
-    TemplateTransaction
-);
-export_tx_set_detect_state!(
-    rs_template_tx_set_detect_state,
-    TemplateTransaction
-);
-
-/// C entry point for a probing parser.
-#[no_mangle]
-pub unsafe extern "C" fn rs_template_probing_parser(
-    _flow: *const Flow,
-    _direction: u8,
-    input: *const u8,
-    input_len: u32,
-    _rdir: *mut u8
-) -> AppProto {
+/// Probe for a length prefix. Requests and responses look the same, so
+/// the direction the data is seen in is kept.
+fn template_probe(input: &[u8], _direction: Direction) -> ProbeResult {
     // Need at least 2 bytes.
-    if input_len > 1 && input != std::ptr::null_mut() {
-        let slice = build_slice!(input, input_len as usize);
-        if probe(slice).is_ok() {
-            return ALPROTO_TEMPLATE;
-        }
+    if input.len() < 2 {
+        return ProbeResult::Unknown;
+    }
+    if probe(input).is_ok() {
+        return ProbeResult::Found(unsafe { ALPROTO_TEMPLATE });
     }
-    return ALPROTO_UNKNOWN;
+    ProbeResult::Failed
 }
 
+export_probe!(rs_template_probing_parser, template_probe);
+
 #[no_mangle]
-pub extern "C" fn rs_template_state_new(_orig_state: *mut std::os::raw::c_void, _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+pub extern "C" fn rs_template_state_new(_orig_state: *mut std::os::raw::c_void,
+                                        _orig_proto: AppProto) -> *mut std::os::raw::c_void {
     let state = TemplateState::new();
-    let boxed = Box::new(state);
-    return Box::into_raw(boxed) as *mut std::os::raw::c_void;
+    Box::into_raw(Box::new(state)) as *mut _
 }
 
 #[no_mangle]
@@ -309,196 +280,110 @@ pub unsafe extern "C" fn rs_template_state_free(state: *mut std::os::raw::c_void
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn rs_template_state_tx_free(
-    state: *mut std::os::raw::c_void,
-    tx_id: u64,
-) {
-    let state = cast_pointer!(state, TemplateState);
+pub unsafe extern "C" fn rs_template_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut TemplateState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_template_parse_request(
-    _flow: *const Flow,
-    state: *mut std::os::raw::c_void,
-    pstate: *mut std::os::raw::c_void,
-    input: *const u8,
-    input_len: u32,
-    _data: *const std::os::raw::c_void,
-    _flags: u8,
-) -> AppLayerResult {
-    let eof = if AppLayerParserStateIssetFlag(pstate, APP_LAYER_PARSER_EOF_TS) > 0 {
-        true
-    } else {
-        false
-    };
-
-    if eof {
+unsafe fn parse(state: *mut std::os::raw::c_void, pstate: *mut std::os::raw::c_void,
+                input: *const u8, input_len: u32, direction: Direction) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, direction) {
         // If needed, handle EOF, or pass it into the parser.
         return AppLayerResult::ok();
     }
-
-    let state = cast_pointer!(state, TemplateState);
-
-    if input == std::ptr::null_mut() && input_len > 0 {
-        // Here we have a gap signaled by the input being null, but a greater
-        // than 0 input_len which provides the size of the gap.
-        state.on_request_gap(input_len);
-        AppLayerResult::ok()
-    } else {
-        let buf = build_slice!(input, input_len as usize);
-        state.parse_request(buf)
+    let state: &mut TemplateState = cast_mut(state);
+    if input.is_null() && input_len > 0 {
+        // A gap, signaled by the input being null, but a greater than 0
+        // input_len giving the size of the gap.
+        state.on_gap(direction);
+        return AppLayerResult::ok();
     }
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, direction)
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn rs_template_parse_response(
-    _flow: *const Flow,
-    state: *mut std::os::raw::c_void,
-    pstate: *mut std::os::raw::c_void,
-    input: *const u8,
-    input_len: u32,
-    _data: *const std::os::raw::c_void,
-    _flags: u8,
-) -> AppLayerResult {
-    let _eof = if AppLayerParserStateIssetFlag(pstate, APP_LAYER_PARSER_EOF_TC) > 0 {
-        true
-    } else {
-        false
-    };
-    let state = cast_pointer!(state, TemplateState);
+pub unsafe extern "C" fn rs_template_parse_request(_flow: *const Flow,
+                                                   state: *mut std::os::raw::c_void,
+                                                   pstate: *mut std::os::raw::c_void,
+                                                   input: *const u8,
+                                                   input_len: u32,
+                                                   _data: *const std::os::raw::c_void,
+                                                   _flags: u8) -> AppLayerResult {
+    parse(state, pstate, input, input_len, Direction::ToServer)
+}
 
-    if input == std::ptr::null_mut() && input_len > 0 {
-        // Here we have a gap signaled by the input being null, but a greater
-        // than 0 input_len which provides the size of the gap.
-        state.on_response_gap(input_len);
-        AppLayerResult::ok()
-    } else {
-        let buf = build_slice!(input, input_len as usize);
-        state.parse_response(buf)
-    }
+#[no_mangle]
+pub unsafe extern "C" fn rs_template_parse_response(_flow: *const Flow,
+                                                    state: *mut std::os::raw::c_void,
+                                                    pstate: *mut std::os::raw::c_void,
+                                                    input: *const u8,
+                                                    input_len: u32,
+                                                    _data: *const std::os::raw::c_void,
+                                                    _flags: u8) -> AppLayerResult {
+    parse(state, pstate, input, input_len, Direction::ToClient)
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn rs_template_state_get_tx(
-    state: *mut std::os::raw::c_void,
-    tx_id: u64,
-) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, TemplateState);
-    match state.get_tx(tx_id) {
-        Some(tx) => {
-            return tx as *const _ as *mut _;
-        }
-        None => {
-            return std::ptr::null_mut();
-        }
+pub unsafe extern "C" fn rs_template_state_get_tx(state: *mut std::os::raw::c_void,
+                                                  tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut TemplateState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
     }
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn rs_template_state_get_tx_count(
-    state: *mut std::os::raw::c_void,
-) -> u64 {
-    let state = cast_pointer!(state, TemplateState);
-    return state.tx_id;
+pub unsafe extern "C" fn rs_template_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut TemplateState = cast_mut(state);
+    state.transactions.tx_count()
 }
 
+/// A transaction is complete once it has its response, in both
+/// directions.
 #[no_mangle]
-pub unsafe extern "C" fn rs_template_tx_get_alstate_progress(
-    tx: *mut std::os::raw::c_void,
-    _direction: u8,
-) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, TemplateTransaction);
-
-    // Transaction is done if we have a response.
+pub unsafe extern "C" fn rs_template_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                             _direction: u8) -> std::os::raw::c_int {
+    let tx: &mut TemplateTransaction = cast_mut(tx);
     if tx.response.is_some() {
         return 1;
     }
-    return 0;
+    0
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_template_state_get_events(
-    tx: *mut std::os::raw::c_void
-) -> *mut core::AppLayerDecoderEvents {
-    let tx = cast_pointer!(tx, TemplateTransaction);
-    return tx.events;
-}
+export_tx_detect_state!(rs_template_state_get_tx_detect_state,
+                        rs_template_state_set_tx_detect_state, TemplateTransaction);
 
 #[no_mangle]
-pub unsafe extern "C" fn rs_template_state_get_tx_iterator(
-    _ipproto: u8,
-    _alproto: AppProto,
-    state: *mut std::os::raw::c_void,
-    min_tx_id: u64,
-    _max_tx_id: u64,
-    istate: &mut u64,
-) -> applayer::AppLayerGetTxIterTuple {
-    let state = cast_pointer!(state, TemplateState);
-    match state.tx_iterator(min_tx_id, istate) {
-        Some((tx, out_tx_id, has_next)) => {
-            let c_tx = tx as *const _ as *mut _;
-            let ires = applayer::AppLayerGetTxIterTuple::with_values(
-                c_tx,
-                out_tx_id,
-                has_next,
-            );
-            return ires;
-        }
-        None => {
-            return applayer::AppLayerGetTxIterTuple::not_found();
-        }
-    }
+pub unsafe extern "C" fn rs_template_state_get_events(tx: *mut std::os::raw::c_void)
+                                                      -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut TemplateTransaction = cast_mut(tx);
+    tx.events
 }
 
-/// Get the request buffer for a transaction from C.
-///
-/// No required for parsing, but an example function for retrieving a
-/// pointer to the request buffer from C for detection.
-#[no_mangle]
-pub unsafe extern "C" fn rs_template_get_request_buffer(
-    tx: *mut std::os::raw::c_void,
-    buf: *mut *const u8,
-    len: *mut u32,
-) -> u8
-{
-    let tx = cast_pointer!(tx, TemplateTransaction);
-    if let Some(ref request) = tx.request {
-        if request.len() > 0 {
-            *len = request.len() as u32;
-            *buf = request.as_ptr();
-            return 1;
-        }
-    }
-    return 0;
-}
+export_tx_data_get!(rs_template_get_tx_data, TemplateTransaction);
 
-/// Get the response buffer for a transaction from C.
-#[no_mangle]
-pub unsafe extern "C" fn rs_template_get_response_buffer(
-    tx: *mut std::os::raw::c_void,
-    buf: *mut *const u8,
-    len: *mut u32,
-) -> u8
-{
-    let tx = cast_pointer!(tx, TemplateTransaction);
-    if let Some(ref response) = tx.response {
-        if response.len() > 0 {
-            *len = response.len() as u32;
-            *buf = response.as_ptr();
-            return 1;
-        }
-    }
-    return 0;
+fn template_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    TEMPLATE_GAUGES.log(js)?;
+    js.set_uint("events", template_stats::anomalies::COUNTER.get())?;
+    Ok(())
 }
 
-export_tx_data_get!(rs_template_get_tx_data, TemplateTransaction);
-
 // Parser name as a C style string.
-const PARSER_NAME: &'static [u8] = b"template-rust\0";
+const PARSER_NAME: &[u8] = b"template-rust\0";
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_template_register_parser() {
+    /* TEMPLATE_START_REMOVE */
+    // Only register if enabled in config.
+    if conf_get_app_layer_node("template-rust").is_none() {
+        return;
+    }
+    /* TEMPLATE_END_REMOVE */
+    template_stats::register();
+    TEMPLATE_TX_LIMIT.configure("template-rust", txtable::DEFAULT_MAX_TX);
+    introspect::register("template-rust", template_introspect);
     let default_port = CString::new("[7000]").unwrap();
     let parser = RustParser {
         name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
@@ -518,46 +403,51 @@ pub unsafe extern "C" fn rs_template_register_parser() {
         tx_comp_st_ts: 1,
         tx_comp_st_tc: 1,
         tx_get_progress: rs_template_tx_get_alstate_progress,
-        get_de_state: rs_template_tx_get_detect_state,
-        set_de_state: rs_template_tx_set_detect_state,
+        get_de_state: rs_template_state_get_tx_detect_state,
+        set_de_state: rs_template_state_set_tx_detect_state,
         get_events: Some(rs_template_state_get_events),
         get_eventinfo: Some(TemplateEvent::get_event_info),
-        get_eventinfo_byid : Some(TemplateEvent::get_event_info_by_id),
+        get_eventinfo_byid: Some(TemplateEvent::get_event_info_by_id),
         localstorage_new: None,
         localstorage_free: None,
         get_files: None,
-        get_tx_iterator: Some(rs_template_state_get_tx_iterator),
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<TemplateState>),
         get_tx_data: rs_template_get_tx_data,
         apply_tx_config: None,
         flags: APP_LAYER_PARSER_OPT_ACCEPT_GAPS,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
-
-    if AppLayerProtoDetectConfProtoDetectionEnabled(
-        ip_proto_str.as_ptr(),
-        parser.name,
-    ) != 0
-    {
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
         let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
         ALPROTO_TEMPLATE = alproto;
-        if AppLayerParserConfParserEnabled(
-            ip_proto_str.as_ptr(),
-            parser.name,
-        ) != 0
-        {
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
             let _ = AppLayerRegisterParser(&parser, alproto);
         }
-        SCLogNotice!("Rust template parser registered.");
+        SCLogDebug!("Rust template parser registered.");
     } else {
-        SCLogNotice!("Protocol detector and parser disabled for TEMPLATE.");
+        SCLogDebug!("Protocol detector and parser disabled for TEMPLATE.");
     }
 }
 
 #[cfg(test)]
-mod test {
+mod tests {
     use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_template_state_new,
+            state_free: rs_template_state_free,
+            parse_ts: rs_template_parse_request,
+            parse_tc: rs_template_parse_response,
+            get_tx_count: rs_template_state_get_tx_count,
+            get_tx: rs_template_state_get_tx,
+            tx_free: rs_template_state_tx_free,
+        })
+    }
 
     #[test]
     fn test_probe() {
@@ -567,26 +457,82 @@ mod test {
         assert!(probe(b"0123456789:").is_err());
     }
 
+    #[test]
+    fn test_template_probe() {
+        unsafe {
+            ALPROTO_TEMPLATE = 42;
+        }
+        assert_eq!(template_probe(b"12:Hello World!", Direction::ToServer),
+                   ProbeResult::Found(42));
+        assert_eq!(template_probe(b"1", Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(template_probe(b"GET / HTTP/1.1\r\n", Direction::ToServer),
+                   ProbeResult::Failed);
+    }
+
     #[test]
     fn test_incomplete() {
         let mut state = TemplateState::new();
         let buf = b"5:Hello3:bye";
 
-        let r = state.parse_request(&buf[0..0]);
+        let r = state.parse(&buf[0..0], Direction::ToServer);
         assert_eq!(r, AppLayerResult{ status: 0, consumed: 0, needed: 0});
 
-        let r = state.parse_request(&buf[0..1]);
+        let r = state.parse(&buf[0..1], Direction::ToServer);
         assert_eq!(r, AppLayerResult{ status: 1, consumed: 0, needed: 2});
 
-        let r = state.parse_request(&buf[0..2]);
+        let r = state.parse(&buf[0..2], Direction::ToServer);
         assert_eq!(r, AppLayerResult{ status: 1, consumed: 0, needed: 3});
 
         // This is the first message and only the first message.
-        let r = state.parse_request(&buf[0..7]);
+        let r = state.parse(&buf[0..7], Direction::ToServer);
         assert_eq!(r, AppLayerResult{ status: 0, consumed: 0, needed: 0});
 
         // The first message and a portion of the second.
-        let r = state.parse_request(&buf[0..9]);
+        let r = state.parse(&buf[0..9], Direction::ToServer);
         assert_eq!(r, AppLayerResult{ status: 1, consumed: 7, needed: 3});
     }
+
+    #[test]
+    fn test_template_session() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"12:Hello World!4:Bye."));
+        // a response split over two segments
+        assert!(h.feed_segments(Direction::ToClient, b"12:Hello World!", 6));
+        assert_eq!(h.tx_count(), 2);
+        let tx = h.get_tx::<TemplateTransaction>(0).unwrap();
+        assert_eq!(tx.request, Some("Hello World!".to_string()));
+        assert_eq!(tx.response, Some("Hello World!".to_string()));
+        let tx = h.get_tx::<TemplateTransaction>(1).unwrap();
+        assert_eq!(tx.request, Some("Bye.".to_string()));
+        assert!(tx.response.is_none());
+    }
+
+    #[test]
+    fn test_template_unsolicited_response() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"3:bye"));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<TemplateTransaction>(0).unwrap();
+        assert!(tx.request.is_none());
+        assert_eq!(tx.response, Some("bye".to_string()));
+    }
+
+    /* TEMPLATE_START_REMOVE */
+    #[test]
+    fn test_template_pcap() {
+        let payloads = crate::testing::pcap_payloads(include_bytes!("template.pcap"), 7000).unwrap();
+        let mut h = harness();
+        assert!(h.replay(&payloads));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<TemplateTransaction>(0).unwrap();
+        assert_eq!(tx.request, Some("Hello World!".to_string()));
+        assert_eq!(tx.response, Some("Bye".to_string()));
+    }
+    /* TEMPLATE_END_REMOVE */
+
+    #[test]
+    fn test_template_malformed() {
+        let mut h = harness();
+        assert!(!h.feed(Direction::ToServer, b"abc:Hello"));
+    }
 }
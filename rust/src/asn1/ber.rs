@@ -0,0 +1,378 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! BER and DER elements (X.690), and a walker checking them against
+//! constraints.
+//!
+//! The walker goes through the elements of a buffer depth first, without
+//! decoding their values, and calls a visitor on each of them. It stops
+//! at the first element violating the `BerConstraints`, so a parser can
+//! refuse an encoding before handing it to a decoder, and the `asn1`
+//! keyword can match on it.
+
+/// Hard limit on the nesting depth of the elements, whatever the
+/// constraints, as the walker recurses into constructed elements.
+pub const BER_MAX_DEPTH: usize = 256;
+
+pub const BER_CLASS_UNIVERSAL: u8 = 0;
+pub const BER_CLASS_APPLICATION: u8 = 1;
+pub const BER_CLASS_CONTEXT: u8 = 2;
+pub const BER_CLASS_PRIVATE: u8 = 3;
+
+pub const BER_TAG_BIT_STRING: u32 = 3;
+pub const BER_TAG_REAL: u32 = 9;
+
+/// The identifier and length octets of an element.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BerHeader {
+    pub class: u8,
+    pub constructed: bool,
+    pub tag: u32,
+    /// The length of the content, None for the indefinite form.
+    pub len: Option<u64>,
+    /// Whether the tag and the length are encoded in their shortest
+    /// form, as required by DER.
+    pub minimal: bool,
+}
+
+/// The constraints an encoding violates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BerViolation {
+    /// The elements are nested deeper than allowed.
+    MaxDepth,
+    /// The length of an element is over the limit.
+    OversizeLength,
+    /// An element has an indefinite length, which DER forbids.
+    IndefiniteLength,
+    /// A tag or length is not in its shortest form, which DER requires.
+    NonMinimalEncoding,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BerError {
+    /// More data is needed to complete the element.
+    Incomplete,
+    /// The encoding is invalid.
+    Malformed,
+    /// More elements than allowed were walked.
+    TooManyElements,
+    Violation(BerViolation),
+}
+
+/// Constraints on the elements walked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BerConstraints {
+    /// Maximum nesting depth, the outermost elements being at depth 1.
+    /// It is capped to `BER_MAX_DEPTH`.
+    pub max_depth: usize,
+    /// Maximum length of the content of an element.
+    pub max_length: Option<u64>,
+    /// Whether the encoding must be DER: definite lengths, and tags and
+    /// lengths in their shortest form.
+    pub der: bool,
+}
+
+impl Default for BerConstraints {
+    fn default() -> BerConstraints {
+        BerConstraints {
+            max_depth: BER_MAX_DEPTH,
+            max_length: None,
+            der: false,
+        }
+    }
+}
+
+/// An element, as given to the visitor of `ber_walk`.
+#[derive(Debug)]
+pub struct BerElement<'a> {
+    pub header: BerHeader,
+    /// The nesting depth, 1 for the outermost elements.
+    pub depth: usize,
+    /// The content octets. Empty for the constructed elements of
+    /// indefinite length, whose elements are walked next.
+    pub content: &'a [u8],
+}
+
+/// Parse the identifier and length octets of an element.
+pub fn ber_header(input: &[u8]) -> Result<(&[u8], BerHeader), BerError> {
+    let (&id, mut rest) = input.split_first().ok_or(BerError::Incomplete)?;
+    let mut minimal = true;
+    let tag = if id & 0x1f != 0x1f {
+        (id & 0x1f) as u32
+    } else {
+        // high tag number form: base 128, most significant first
+        let mut tag: u32 = 0;
+        let mut first = true;
+        loop {
+            let (&b, r) = rest.split_first().ok_or(BerError::Incomplete)?;
+            rest = r;
+            if first && b == 0x80 {
+                minimal = false;
+            }
+            first = false;
+            if tag >> 25 != 0 {
+                return Err(BerError::Malformed);
+            }
+            tag = (tag << 7) | (b & 0x7f) as u32;
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        if tag < 0x1f {
+            minimal = false;
+        }
+        tag
+    };
+    let (&first, mut rest) = rest.split_first().ok_or(BerError::Incomplete)?;
+    let len = if first & 0x80 == 0 {
+        Some(first as u64)
+    } else if first == 0x80 {
+        None
+    } else {
+        let n = (first & 0x7f) as usize;
+        // 0x7f is reserved
+        if n > 8 {
+            return Err(BerError::Malformed);
+        }
+        if rest.len() < n {
+            return Err(BerError::Incomplete);
+        }
+        let (bytes, r) = rest.split_at(n);
+        rest = r;
+        if bytes[0] == 0 {
+            minimal = false;
+        }
+        let len = bytes.iter().fold(0u64, |len, b| (len << 8) | *b as u64);
+        if len < 0x80 {
+            minimal = false;
+        }
+        Some(len)
+    };
+    let header = BerHeader {
+        class: id >> 6,
+        constructed: id & 0x20 != 0,
+        tag,
+        len,
+        minimal,
+    };
+    Ok((rest, header))
+}
+
+struct Walk<'c> {
+    constraints: &'c BerConstraints,
+    max_elements: usize,
+    elements: usize,
+}
+
+impl<'c> Walk<'c> {
+    /// Walk the element at the start of `input`, and its elements if it
+    /// is constructed. `bounded` is set inside of an element of definite
+    /// length, where missing data is an error. Returns the data after
+    /// the element, and the result of the visitor if it stopped.
+    fn element<'a, R, F>(
+        &mut self, input: &'a [u8], depth: usize, bounded: bool, visit: &mut F,
+    ) -> Result<(&'a [u8], Option<R>), BerError>
+    where
+        F: FnMut(&BerElement<'a>) -> Option<R>,
+    {
+        let truncated = if bounded { BerError::Malformed } else { BerError::Incomplete };
+        let (rest, header) = match ber_header(input) {
+            Err(BerError::Incomplete) => return Err(truncated),
+            r => r?,
+        };
+        self.elements += 1;
+        if self.max_elements > 0 && self.elements > self.max_elements {
+            return Err(BerError::TooManyElements);
+        }
+        if depth > std::cmp::min(self.constraints.max_depth, BER_MAX_DEPTH) {
+            return Err(BerError::Violation(BerViolation::MaxDepth));
+        }
+        if self.constraints.der && !header.minimal {
+            return Err(BerError::Violation(BerViolation::NonMinimalEncoding));
+        }
+        match header.len {
+            Some(len) => {
+                if let Some(max_length) = self.constraints.max_length {
+                    if len > max_length {
+                        return Err(BerError::Violation(BerViolation::OversizeLength));
+                    }
+                }
+                if len > rest.len() as u64 {
+                    return Err(truncated);
+                }
+                let (content, rest) = rest.split_at(len as usize);
+                let element = BerElement { header, depth, content };
+                if let Some(r) = visit(&element) {
+                    return Ok((rest, Some(r)));
+                }
+                if header.constructed {
+                    let mut content = content;
+                    while !content.is_empty() {
+                        let (r, stop) = self.element(content, depth + 1, true, visit)?;
+                        if stop.is_some() {
+                            return Ok((rest, stop));
+                        }
+                        content = r;
+                    }
+                }
+                Ok((rest, None))
+            }
+            None => {
+                if self.constraints.der {
+                    return Err(BerError::Violation(BerViolation::IndefiniteLength));
+                }
+                // only constructed elements can have an indefinite length
+                if !header.constructed {
+                    return Err(BerError::Malformed);
+                }
+                let element = BerElement { header, depth, content: &[] };
+                if let Some(r) = visit(&element) {
+                    return Ok((rest, Some(r)));
+                }
+                // the elements run up to the end-of-contents octets
+                let mut content = rest;
+                loop {
+                    if content.starts_with(&[0, 0]) {
+                        return Ok((&content[2..], None));
+                    }
+                    if content.is_empty() {
+                        return Err(truncated);
+                    }
+                    let (r, stop) = self.element(content, depth + 1, bounded, visit)?;
+                    if stop.is_some() {
+                        return Ok((r, stop));
+                    }
+                    content = r;
+                }
+            }
+        }
+    }
+}
+
+/// Walk the elements of `input`, depth first, calling `visit` on each of
+/// them until it returns a result. At most `max_elements` elements are
+/// walked, 0 for no limit.
+///
+/// Returns the result of the visitor if it stopped the walk, None if all
+/// the elements were walked, or an error on the first element that is
+/// invalid, incomplete or violates the constraints.
+pub fn ber_walk<'a, R, F>(
+    input: &'a [u8], constraints: &BerConstraints, max_elements: usize, mut visit: F,
+) -> Result<Option<R>, BerError>
+where
+    F: FnMut(&BerElement<'a>) -> Option<R>,
+{
+    let mut walk = Walk {
+        constraints,
+        max_elements,
+        elements: 0,
+    };
+    let mut rest = input;
+    while !rest.is_empty() {
+        let (r, stop) = walk.element(rest, 1, false, &mut visit)?;
+        if stop.is_some() {
+            return Ok(stop);
+        }
+        rest = r;
+    }
+    Ok(None)
+}
+
+/// Check the elements of a message against `constraints`, for parsers
+/// to call before decoding it.
+pub fn ber_validate(input: &[u8], constraints: &BerConstraints) -> Result<(), BerError> {
+    ber_walk(input, constraints, 0, |_| None::<()>).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depths(input: &[u8], constraints: &BerConstraints) -> Result<Vec<usize>, BerError> {
+        let mut depths = Vec::new();
+        ber_walk(input, constraints, 0, |element| {
+            depths.push(element.depth);
+            None::<()>
+        })?;
+        Ok(depths)
+    }
+
+    #[test]
+    fn test_ber_header() {
+        assert_eq!(ber_header(&[0x30, 0x05]).unwrap().1,
+                   BerHeader { class: 0, constructed: true, tag: 16, len: Some(5),
+                               minimal: true });
+        assert_eq!(ber_header(&[0x63, 0x82, 0x01, 0x00]).unwrap().1,
+                   BerHeader { class: 1, constructed: true, tag: 3, len: Some(256),
+                               minimal: true });
+        assert_eq!(ber_header(&[0x30, 0x80]).unwrap().1.len, None);
+        // high tag number
+        assert_eq!(ber_header(&[0x9f, 0x81, 0x00, 0x00]).unwrap().1,
+                   BerHeader { class: 2, constructed: false, tag: 128, len: Some(0),
+                               minimal: true });
+        // non minimal tag and length
+        assert!(!ber_header(&[0x1f, 0x01, 0x00]).unwrap().1.minimal);
+        assert!(!ber_header(&[0x04, 0x81, 0x05]).unwrap().1.minimal);
+        assert!(!ber_header(&[0x04, 0x82, 0x00, 0x80]).unwrap().1.minimal);
+        assert_eq!(ber_header(&[0x30, 0x82, 0x01]), Err(BerError::Incomplete));
+        assert_eq!(ber_header(&[0x30, 0x89]), Err(BerError::Malformed));
+    }
+
+    #[test]
+    fn test_ber_walk() {
+        // SEQUENCE { INTEGER 1, SEQUENCE { OCTET STRING "a" } }
+        let input = &[0x30, 0x08, 0x02, 0x01, 0x01, 0x30, 0x03, 0x04, 0x01, b'a'];
+        let constraints = BerConstraints::default();
+        assert_eq!(depths(input, &constraints), Ok(vec![1, 2, 2, 3]));
+        // the same with indefinite lengths
+        let input = &[0x30, 0x80, 0x02, 0x01, 0x01, 0x30, 0x80, 0x04, 0x01, b'a', 0, 0, 0, 0];
+        assert_eq!(depths(input, &constraints), Ok(vec![1, 2, 2, 3]));
+        assert_eq!(depths(&input[..12], &constraints), Err(BerError::Incomplete));
+        // an element running past the one containing it
+        assert_eq!(depths(&[0x30, 0x03, 0x04, 0x05, b'a'], &constraints),
+                   Err(BerError::Malformed));
+        // primitive element of indefinite length
+        assert_eq!(depths(&[0x04, 0x80, 0, 0], &constraints), Err(BerError::Malformed));
+    }
+
+    #[test]
+    fn test_ber_constraints() {
+        let input = &[0x30, 0x08, 0x02, 0x01, 0x01, 0x30, 0x03, 0x04, 0x01, b'a'];
+        let constraints = BerConstraints { max_depth: 2, ..Default::default() };
+        assert_eq!(ber_validate(input, &constraints),
+                   Err(BerError::Violation(BerViolation::MaxDepth)));
+        let constraints = BerConstraints { max_length: Some(7), ..Default::default() };
+        assert_eq!(ber_validate(input, &constraints),
+                   Err(BerError::Violation(BerViolation::OversizeLength)));
+        let constraints = BerConstraints { der: true, ..Default::default() };
+        assert_eq!(ber_validate(input, &constraints), Ok(()));
+        assert_eq!(ber_validate(&[0x30, 0x80, 0x02, 0x01, 0x01, 0, 0], &constraints),
+                   Err(BerError::Violation(BerViolation::IndefiniteLength)));
+        assert_eq!(ber_validate(&[0x04, 0x81, 0x01, b'a'], &constraints),
+                   Err(BerError::Violation(BerViolation::NonMinimalEncoding)));
+
+        // the walk is bounded whatever the constraints
+        let mut nested = Vec::new();
+        for _ in 0..=BER_MAX_DEPTH {
+            nested.extend_from_slice(&[0x30, 0x80]);
+        }
+        assert_eq!(ber_validate(&nested, &BerConstraints::default()),
+                   Err(BerError::Violation(BerViolation::MaxDepth)));
+        assert_eq!(ber_walk(input, &BerConstraints::default(), 3, |_| None::<()>),
+                   Err(BerError::TooManyElements));
+    }
+}
@@ -15,22 +15,24 @@
  * 02110-1301, USA.
  */
 
-use der_parser::ber::{parse_ber_recursive, BerObject, BerObjectContent, BerTag};
 use std::convert::TryFrom;
 
+pub mod ber;
 mod parse_rules;
+use ber::{
+    ber_walk, BerConstraints, BerElement, BerError, BerViolation, BER_CLASS_UNIVERSAL,
+    BER_MAX_DEPTH, BER_TAG_BIT_STRING, BER_TAG_REAL,
+};
 use parse_rules::DetectAsn1Data;
 
-/// Container for parsed Asn1 objects
+/// Asn1 encoded data, from the offset given in the keyword options
 #[derive(Debug)]
-pub struct Asn1<'a>(Vec<BerObject<'a>>);
+pub struct Asn1<'a>(&'a [u8]);
 
 /// Errors possible during decoding of Asn1
 #[derive(Debug)]
 enum Asn1DecodeError {
     InvalidKeywordParameter,
-    MaxFrames,
-    BerError(nom::Err<der_parser::error::BerError>),
 }
 
 /// Enumeration of Asn1 checks
@@ -40,127 +42,79 @@ enum Asn1Check {
     BitstringOverflow,
     DoubleOverflow,
     MaxDepth,
+    IndefiniteLength,
+    NonMinimalEncoding,
 }
 
 impl<'a> Asn1<'a> {
-    /// Checks each BerObject contained in self with the provided detection
-    /// data, returns the first successful match if one occurs
+    /// Walks the elements with the provided detection data, returns the
+    /// first successful match if one occurs
     fn check(&self, ad: &DetectAsn1Data) -> Option<Asn1Check> {
-        for obj in &self.0 {
-            let res = Asn1::check_object_recursive(obj, ad, ad.max_frames as usize);
-            if res.is_some() {
-                return res;
-            }
+        let constraints = BerConstraints {
+            max_depth: ad.max_depth.map_or(BER_MAX_DEPTH, |v| v as usize),
+            max_length: ad.oversize_length.map(u64::from),
+            der: ad.der,
+        };
+
+        match ber_walk(self.0, &constraints, ad.max_frames as usize, |element| {
+            Asn1::check_element(element, ad)
+        }) {
+            Ok(res) => res,
+            Err(BerError::Violation(violation)) => match violation {
+                BerViolation::MaxDepth => {
+                    // only a match if asked for, the walk is bounded anyway
+                    if ad.max_depth.is_some() {
+                        Some(Asn1Check::MaxDepth)
+                    } else {
+                        None
+                    }
+                }
+                BerViolation::OversizeLength => Some(Asn1Check::OversizeLength),
+                BerViolation::IndefiniteLength => Some(Asn1Check::IndefiniteLength),
+                BerViolation::NonMinimalEncoding => Some(Asn1Check::NonMinimalEncoding),
+            },
+            // silent error as this could fail
+            // on non-asn1 or fragmented packets
+            Err(_) => None,
         }
-
-        None
     }
 
-    fn check_object_recursive(
-        obj: &BerObject, ad: &DetectAsn1Data, max_depth: usize,
-    ) -> Option<Asn1Check> {
-        // Check stack depth
-        if max_depth == 0 {
-            return Some(Asn1Check::MaxDepth);
-        }
-
-        // Check current object
-        let res = Asn1::check_object(obj, ad);
-        if res.is_some() {
-            return res;
-        }
-
-        // Check sub-nodes
-        for node in obj.ref_iter() {
-            let res = Asn1::check_object_recursive(node, ad, max_depth - 1);
-            if res.is_some() {
-                return res;
-            }
-        }
-
-        None
-    }
-
-    /// Checks a BerObject and subnodes against the Asn1 checks
-    fn check_object(obj: &BerObject, ad: &DetectAsn1Data) -> Option<Asn1Check> {
-        // oversize_length will check if a node has a length greater than
-        // the user supplied length
-        if let Some(oversize_length) = ad.oversize_length {
-            if obj.header.len > oversize_length as u64
-                || obj.content.as_slice().unwrap_or(&[]).len() > oversize_length as usize
-            {
-                return Some(Asn1Check::OversizeLength);
-            }
+    /// Checks an element against the Asn1 checks on its content, the
+    /// constraints on its header being checked by the walker
+    fn check_element(element: &BerElement, ad: &DetectAsn1Data) -> Option<Asn1Check> {
+        let header = &element.header;
+        if header.class != BER_CLASS_UNIVERSAL || header.constructed {
+            return None;
         }
+        let data = element.content;
 
         // bitstring_overflow check a malformed option where the number of bits
         // to ignore is greater than the length decoded (in bits)
         if ad.bitstring_overflow
-            && (obj.header.is_universal()
-                && obj.header.tag == BerTag::BitString
-                && obj.header.is_primitive())
+            && header.tag == BER_TAG_BIT_STRING
+            && !data.is_empty()
+            && data[0] as u64 > (data.len() as u64).saturating_mul(8)
         {
-            if let BerObjectContent::BitString(bits, _v) = &obj.content {
-                if obj.header.len > 0
-                    && *bits as u64 > obj.header.len.saturating_mul(8)
-                {
-                    return Some(Asn1Check::BitstringOverflow);
-                }
-            }
+            return Some(Asn1Check::BitstringOverflow);
         }
 
         // double_overflow checks a known issue that affects the MSASN1 library
         // when decoding double/real types. If the encoding is ASCII,
         // and the buffer is greater than 256, the array is overflown
         if ad.double_overflow
-            && (obj.header.is_universal()
-                && obj.header.tag == BerTag::RealType
-                && obj.header.is_primitive())
+            && header.tag == BER_TAG_REAL
+            && !data.is_empty()
+            && data[0] & 0xC0 == 0
+            && data.len() > 256
         {
-            if let Ok(data) = obj.content.as_slice() {
-                if obj.header.len > 0
-                    && !data.is_empty()
-                    && data[0] & 0xC0 == 0
-                    && (obj.header.len > 256 || data.len() > 256)
-                {
-                    return Some(Asn1Check::DoubleOverflow);
-                }
-            }
+            return Some(Asn1Check::DoubleOverflow);
         }
 
         None
     }
 
-    fn from_slice(input: &'a [u8], ad: &DetectAsn1Data) -> Result<Asn1<'a>, Asn1DecodeError> {
-        let mut results = Vec::new();
-        let mut rest = input;
-
-        // while there's data to process
-        while !rest.is_empty() {
-            let max_depth = ad.max_frames as usize;
-
-            if results.len() >= max_depth {
-                return Err(Asn1DecodeError::MaxFrames);
-            }
-
-            let res = parse_ber_recursive(rest, max_depth);
-
-            match res {
-                Ok((new_rest, obj)) => {
-                    results.push(obj);
-
-                    rest = new_rest;
-                }
-                // If there's an error, bail
-                Err(_) => {
-                    // silent error as this could fail
-                    // on non-asn1 or fragmented packets
-                    break;
-                }
-            }
-        }
-
-        Ok(Asn1(results))
+    fn from_slice(input: &'a [u8]) -> Asn1<'a> {
+        Asn1(input)
     }
 }
 
@@ -200,11 +154,11 @@ fn asn1_decode<'a>(
     // Get slice from buffer at offset
     let slice = &buffer[offset as usize..];
 
-    Asn1::from_slice(slice, ad)
+    Ok(Asn1::from_slice(slice))
 }
 
-/// Attempt to parse a Asn1 object from input, and return a pointer
-/// to the parsed object if successful, null on failure
+/// Apply the offset of the asn1 keyword options to input, and return a
+/// pointer to the Asn1 data to check if successful, null on failure
 ///
 /// # Safety
 ///
@@ -247,6 +201,8 @@ pub unsafe extern "C" fn rs_asn1_free(ptr: *mut Asn1) {
 ///   - oversize_length
 ///   - bitstring_overflow
 ///   - double_overflow
+///   - max_depth
+///   - der
 ///
 /// # Safety
 ///
@@ -275,12 +231,6 @@ impl From<std::num::TryFromIntError> for Asn1DecodeError {
     }
 }
 
-impl From<nom::Err<der_parser::error::BerError>> for Asn1DecodeError {
-    fn from(e: nom::Err<der_parser::error::BerError>) -> Asn1DecodeError {
-        Asn1DecodeError::BerError(e)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,6 +246,9 @@ mod tests {
                     \x31\x1F\x61\x11\x1A\x05Susan\x1A\x01B\x1A\x05 \
                     Jones\xA0\x0A\x43\x0819590717";
 
+    // SEQUENCE { INTEGER 1, SEQUENCE { OCTET STRING "a" } }, 3 levels deep
+    static NESTED: &[u8] = b"\x30\x08\x02\x01\x01\x30\x03\x04\x01a";
+
     /// Ensure that the checks work when they should
     #[test_case("oversize_length 132 absolute_offset 0", ASN1_A3, DetectAsn1Data {
             oversize_length: Some(132),
@@ -369,6 +322,37 @@ mod tests {
             absolute_offset: Some(0),
             ..Default::default()
         }, None; "Test double_overflow rule (non-match)" )]
+    #[test_case("max_depth 2 absolute_offset 0", NESTED, DetectAsn1Data {
+            max_depth: Some(2),
+            absolute_offset: Some(0),
+            ..Default::default()
+        }, Some(Asn1Check::MaxDepth); "Test max_depth rule (match)" )]
+    #[test_case("max_depth 3 absolute_offset 0", NESTED, DetectAsn1Data {
+            max_depth: Some(3),
+            absolute_offset: Some(0),
+            ..Default::default()
+        }, None; "Test max_depth rule (non-match)" )]
+    #[test_case("der absolute_offset 0",
+        /* sequence of indefinite length, holding an integer */
+        b"\x30\x80\x02\x01\x01\x00\x00",
+        DetectAsn1Data {
+            der: true,
+            absolute_offset: Some(0),
+            ..Default::default()
+        }, Some(Asn1Check::IndefiniteLength); "Test der rule on indefinite length (match)" )]
+    #[test_case("der absolute_offset 0",
+        /* octet string, its length of 1 in the long form */
+        b"\x04\x81\x01a",
+        DetectAsn1Data {
+            der: true,
+            absolute_offset: Some(0),
+            ..Default::default()
+        }, Some(Asn1Check::NonMinimalEncoding); "Test der rule on long form length (match)" )]
+    #[test_case("der absolute_offset 0", NESTED, DetectAsn1Data {
+            der: true,
+            absolute_offset: Some(0),
+            ..Default::default()
+        }, None; "Test der rule (non-match)" )]
     fn test_checks(
         rule: &str, asn1_buf: &'static [u8], expected_data: DetectAsn1Data,
         expected_check: Option<Asn1Check>,
@@ -378,7 +362,7 @@ mod tests {
         assert_eq!(expected_data, ad);
 
         // Decode
-        let asn1 = Asn1::from_slice(asn1_buf, &ad).unwrap();
+        let asn1 = Asn1::from_slice(asn1_buf);
 
         // Run checks
         let result = asn1.check(&ad);
@@ -24,6 +24,8 @@ use nom::IResult;
 use std::ffi::CStr;
 use std::os::raw::c_char;
 
+use super::ber::BER_MAX_DEPTH;
+
 const ASN1_DEFAULT_MAX_FRAMES: u16 = 30;
 
 /// Parse the asn1 keyword and return a pointer to a `DetectAsn1Data`
@@ -89,6 +91,8 @@ pub struct DetectAsn1Data {
     pub oversize_length: Option<u32>,
     pub absolute_offset: Option<u16>,
     pub relative_offset: Option<i32>,
+    pub max_depth: Option<u16>,
+    pub der: bool,
     pub max_frames: u16,
 }
 
@@ -100,6 +104,8 @@ impl Default for DetectAsn1Data {
             oversize_length: None,
             absolute_offset: None,
             relative_offset: None,
+            max_depth: None,
+            der: false,
             max_frames: ASN1_DEFAULT_MAX_FRAMES,
         }
     }
@@ -157,6 +163,18 @@ pub(super) fn asn1_parse_rule(input: &str) -> IResult<&str, DetectAsn1Data> {
         )(i)
     }
 
+    fn max_depth(i: &str) -> IResult<&str, (&str, u16)> {
+        separated_pair(
+            tag("max_depth"),
+            multispace1,
+            verify(parse_u16_number, |v| *v > 0 && *v as usize <= BER_MAX_DEPTH),
+        )(i)
+    }
+
+    fn der(i: &str) -> IResult<&str, &str> {
+        tag("der")(i)
+    }
+
     let mut data = DetectAsn1Data::default();
 
     let mut rest = input;
@@ -172,6 +190,8 @@ pub(super) fn asn1_parse_rule(input: &str) -> IResult<&str, DetectAsn1Data> {
                 oversize_length,
                 absolute_offset,
                 relative_offset,
+                max_depth,
+                der,
                 _,
             ),
         ) = tuple((
@@ -181,6 +201,8 @@ pub(super) fn asn1_parse_rule(input: &str) -> IResult<&str, DetectAsn1Data> {
             opt(oversize_length),
             opt(absolute_offset),
             opt(relative_offset),
+            opt(max_depth),
+            opt(der),
             opt(alt((multispace1, tag(",")))),
         ))(rest)?;
 
@@ -194,6 +216,10 @@ pub(super) fn asn1_parse_rule(input: &str) -> IResult<&str, DetectAsn1Data> {
             data.absolute_offset = Some(v);
         } else if let Some((_, v)) = relative_offset {
             data.relative_offset = Some(v);
+        } else if let Some((_, v)) = max_depth {
+            data.max_depth = Some(v);
+        } else if der.is_some() {
+            data.der = true;
         } else {
             return Err(nom::Err::Error(nom::error::make_error(
                 rest,
@@ -269,6 +295,27 @@ mod tests {
     #[test_case("relative_offset",
         DetectAsn1Data::default() => panics "Error((\"relative_offset\", Verify))";
         "check that we fail if the needed arg relative_offset is not given")]
+    // Test max_depth
+    #[test_case("max_depth 8",
+        DetectAsn1Data { max_depth: Some(8), ..Default::default()};
+        "check that we parse max_depth correctly")]
+    #[test_case("max_depth 0",
+        DetectAsn1Data::default() => panics "Error((\"max_depth 0\", Verify))";
+        "check under lower bound on max_depth")]
+    #[test_case("max_depth 256",
+        DetectAsn1Data { max_depth: Some(256), ..Default::default()};
+        "check upper bound on max_depth")]
+    #[test_case("max_depth 257",
+        DetectAsn1Data::default() => panics "Error((\"max_depth 257\", Verify))";
+        "check over upper bound on max_depth")]
+    // Test der
+    #[test_case("der",
+        DetectAsn1Data { der: true, ..Default::default()};
+        "check that we parse der correctly")]
+    #[test_case("der, max_depth 4 absolute_offset 0",
+        DetectAsn1Data { der: true, max_depth: Some(4), absolute_offset: Some(0),
+            ..Default::default()};
+        "check for combinations of der with other keywords")]
     // Test bitstring_overflow
     #[test_case("bitstring_overflow",
         DetectAsn1Data { bitstring_overflow: true, ..Default::default()};
@@ -0,0 +1,847 @@
+/* Copyright (C) 2024 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::common::to_hex;
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP, IPPROTO_UDP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(bittorrent_stats, "app_layer.bittorrent", {
+    handshakes,
+    extended_handshakes,
+    peer_messages,
+    dht_queries,
+    dht_responses,
+    dht_errors,
+    utp_packets,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum BitTorrentEvent {
+    MalformedHandshake,
+    MalformedMessage,
+    InfoHashMismatch,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static BITTORRENT_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static BITTORRENT_GAUGES: StateGauges = StateGauges::new();
+
+/// Largest message of the peer wire protocol. Pieces are requested by
+/// blocks of 16 KiB, the largest messages are the bitfields of torrents
+/// with many pieces.
+const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+/// Largest extended handshake that is buffered to be parsed. Larger
+/// ones are skipped.
+const MAX_EXTENDED_LEN: usize = 16384;
+
+/// Maximum number of DHT queries waiting for a response per flow.
+const MAX_PENDING_QUERIES: usize = 64;
+
+pub static mut ALPROTO_BITTORRENT: AppProto = ALPROTO_UNKNOWN;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    Tcp,
+    Utp,
+    Dht,
+}
+
+impl Transport {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Transport::Tcp => "tcp",
+            Transport::Utp => "utp",
+            Transport::Dht => "dht",
+        }
+    }
+}
+
+/// The handshakes of one side of a peer connection.
+#[derive(Debug)]
+pub struct Peer {
+    pub handshake: Handshake,
+    pub extended: Option<ExtendedHandshake>,
+}
+
+#[derive(Debug)]
+pub struct BitTorrentTransaction {
+    id: u64,
+    pub transport: Transport,
+    /// The peer connection handshakes, of the side opening the
+    /// connection and of the other one.
+    pub client: Option<Peer>,
+    pub server: Option<Peer>,
+    /// A DHT query, and its response or error.
+    pub dht_query: Option<DhtMessage>,
+    pub dht_response: Option<DhtMessage>,
+    /// The info hash in hex, of the handshake of the client or of the
+    /// DHT query.
+    pub info_hash: Option<String>,
+    pub complete: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for BitTorrentTransaction {}
+
+impl BitTorrentTransaction {
+    fn new(id: u64, transport: Transport) -> BitTorrentTransaction {
+        BitTorrentTransaction {
+            id,
+            transport,
+            client: None,
+            server: None,
+            dht_query: None,
+            dht_response: None,
+            info_hash: None,
+            complete: true,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: BitTorrentEvent) {
+        stats_incr!(bittorrent_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    fn peer_mut(&mut self, direction: Direction) -> &mut Option<Peer> {
+        if direction == Direction::ToServer {
+            &mut self.client
+        } else {
+            &mut self.server
+        }
+    }
+}
+
+impl Drop for BitTorrentTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for BitTorrentTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = BitTorrentEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+/// The peer wire stream of one direction.
+#[derive(Debug, Default)]
+struct PeerStream {
+    handshake: bool,
+    /// Set once the extended handshake was seen, or the peers moved on
+    /// to exchanging pieces without one.
+    extended_done: bool,
+    /// Bytes of the current message still to skip.
+    skip: usize,
+}
+
+pub struct BitTorrentState {
+    transactions: TxTable<BitTorrentTransaction>,
+    /// The DHT queries waiting for a response, by whether they were
+    /// sent to the server and their transaction id: both nodes send
+    /// queries.
+    pending: PairingTable<(bool, Vec<u8>)>,
+    streams: [PeerStream; 2],
+    /// The transaction of the peer connection.
+    peer_tx: Option<u64>,
+    /// Set once a malformed message was seen: the event is only raised
+    /// once per flow.
+    malformed: bool,
+}
+
+unsafe impl FfiType for BitTorrentState {}
+
+impl txtable::State for BitTorrentState {
+    type Tx = BitTorrentTransaction;
+
+    fn tx_table(&self) -> &TxTable<BitTorrentTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<BitTorrentTransaction> {
+        &mut self.transactions
+    }
+}
+
+fn stream_index(direction: Direction) -> usize {
+    if direction == Direction::ToServer {
+        0
+    } else {
+        1
+    }
+}
+
+impl BitTorrentState {
+    pub fn new() -> BitTorrentState {
+        BitTorrentState {
+            transactions: TxTable::with_limit(&BITTORRENT_TX_LIMIT)
+                .with_gauges(&BITTORRENT_GAUGES),
+            pending: PairingTable::new(MAX_PENDING_QUERIES, 0),
+            streams: Default::default(),
+            peer_tx: None,
+            malformed: false,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+        self.pending.remove_tx(tx_id + 1);
+    }
+
+    fn find_tx(&mut self, id: u64) -> Option<&mut BitTorrentTransaction> {
+        self.transactions.iter_mut().find(|tx| tx.id == id)
+    }
+
+    fn new_tx(&mut self, transport: Transport) -> BitTorrentTransaction {
+        BitTorrentTransaction::new(self.transactions.next_id(), transport)
+    }
+
+    fn set_malformed(&mut self, transport: Transport, event: BitTorrentEvent) {
+        if self.malformed {
+            return;
+        }
+        self.malformed = true;
+        let mut tx = self.new_tx(transport);
+        tx.set_event(event);
+        self.transactions.push(tx);
+    }
+
+    fn peer_tx_mut(&mut self) -> Option<&mut BitTorrentTransaction> {
+        let id = self.peer_tx?;
+        self.find_tx(id)
+    }
+
+    /// The peer connection is logged once both sides are past their
+    /// handshakes: the extended handshakes are only sent when both
+    /// sides support the extension protocol.
+    fn update_peer_complete(&mut self) {
+        let streams = &self.streams;
+        let tx = match self.peer_tx {
+            Some(id) => match self.transactions.iter_mut().find(|tx| tx.id == id) {
+                Some(tx) => tx,
+                None => return,
+            },
+            None => return,
+        };
+        let extended = match (&tx.client, &tx.server) {
+            (Some(client), Some(server)) => {
+                client.handshake.has_extension_protocol()
+                    && server.handshake.has_extension_protocol()
+            }
+            _ => return,
+        };
+        tx.complete = !extended || (streams[0].extended_done && streams[1].extended_done);
+    }
+
+    fn handle_handshake(&mut self, direction: Direction, handshake: Handshake, transport: Transport) {
+        stats_incr!(bittorrent_stats::handshakes);
+        self.streams[stream_index(direction)].handshake = true;
+        if self.peer_tx_mut().is_none() {
+            let mut tx = self.new_tx(transport);
+            tx.complete = false;
+            self.peer_tx = Some(tx.id);
+            self.transactions.push(tx);
+        }
+        let tx = match self.peer_tx_mut() {
+            Some(tx) => tx,
+            None => return,
+        };
+        let other = match direction {
+            Direction::ToServer => &tx.server,
+            Direction::ToClient => &tx.client,
+        };
+        let mismatch = match other {
+            Some(peer) => peer.handshake.info_hash != handshake.info_hash,
+            None => false,
+        };
+        if tx.info_hash.is_none() || direction == Direction::ToServer {
+            tx.info_hash = Some(to_hex(&handshake.info_hash));
+        }
+        *tx.peer_mut(direction) = Some(Peer { handshake, extended: None });
+        if mismatch {
+            tx.set_event(BitTorrentEvent::InfoHashMismatch);
+        }
+        self.update_peer_complete();
+    }
+
+    fn handle_extended(&mut self, direction: Direction, payload: &[u8]) {
+        match parse_extended(payload) {
+            Ok(Some(extended)) => {
+                stats_incr!(bittorrent_stats::extended_handshakes);
+                self.streams[stream_index(direction)].extended_done = true;
+                if let Some(tx) = self.peer_tx_mut() {
+                    if let Some(ref mut peer) = tx.peer_mut(direction) {
+                        peer.extended = Some(extended);
+                    }
+                }
+                self.update_peer_complete();
+            }
+            Ok(None) => {}
+            Err(_) => self.set_malformed(Transport::Tcp, BitTorrentEvent::MalformedMessage),
+        }
+    }
+
+    /// Parse the handshake and the messages of a peer connection over
+    /// TCP. Only the extended handshakes are buffered, the payload of
+    /// the other messages is skipped.
+    fn parse_tcp(&mut self, direction: Direction, input: &[u8]) -> AppLayerResult {
+        let idx = stream_index(direction);
+        let mut start = input;
+        while !start.is_empty() {
+            let consumed = input.len() - start.len();
+            if !self.streams[idx].handshake {
+                match parse_handshake(start) {
+                    Ok((rest, handshake)) => {
+                        self.handle_handshake(direction, handshake, Transport::Tcp);
+                        start = rest;
+                        continue;
+                    }
+                    Err(ParseError::Incomplete) => {
+                        return AppLayerResult::incomplete_from(input.len(), consumed, HANDSHAKE_LEN);
+                    }
+                    Err(ParseError::Invalid) => {
+                        self.set_malformed(Transport::Tcp, BitTorrentEvent::MalformedHandshake);
+                        return AppLayerResult::err();
+                    }
+                }
+            }
+            if self.streams[idx].skip > 0 {
+                let n = std::cmp::min(self.streams[idx].skip, start.len());
+                self.streams[idx].skip -= n;
+                start = &start[n..];
+                continue;
+            }
+            let header = match parse_message_header(start) {
+                Ok(header) => header,
+                Err(_) => return AppLayerResult::incomplete_from(input.len(), consumed, 5),
+            };
+            let id = match header.id {
+                Some(id) => id,
+                None => {
+                    // keep-alive
+                    start = &start[4..];
+                    continue;
+                }
+            };
+            if header.len > MAX_MESSAGE_LEN {
+                self.set_malformed(Transport::Tcp, BitTorrentEvent::MalformedMessage);
+                return AppLayerResult::err();
+            }
+            stats_incr!(bittorrent_stats::peer_messages);
+            if id == MSG_EXTENDED && header.len <= MAX_EXTENDED_LEN {
+                if start.len() < 4 + header.len {
+                    return AppLayerResult::incomplete_from(input.len(), consumed, 4 + header.len);
+                }
+                self.handle_extended(direction, &start[5..4 + header.len]);
+                start = &start[4 + header.len..];
+                continue;
+            }
+            if (id == MSG_REQUEST || id == MSG_PIECE || id == MSG_CANCEL)
+                && !self.streams[idx].extended_done
+            {
+                self.streams[idx].extended_done = true;
+                self.update_peer_complete();
+            }
+            self.streams[idx].skip = header.len - 1;
+            start = &start[5..];
+        }
+        AppLayerResult::ok()
+    }
+
+    fn handle_dht(&mut self, direction: Direction, message: DhtMessage) {
+        match message.body {
+            DhtBody::Query(ref query) => {
+                stats_incr!(bittorrent_stats::dht_queries);
+                let mut tx = self.new_tx(Transport::Dht);
+                tx.info_hash = query.info_hash.as_ref().map(|h| to_hex(h));
+                tx.complete = false;
+                let key = (direction.is_to_server(), message.transaction_id.clone());
+                for id in self.pending.insert(key, tx.id, 0) {
+                    if let Some(tx) = self.find_tx(id) {
+                        tx.complete = true;
+                    }
+                }
+                tx.dht_query = Some(message);
+                self.transactions.push(tx);
+            }
+            DhtBody::Response(_) | DhtBody::Error(_) => {
+                if let DhtBody::Error(_) = message.body {
+                    stats_incr!(bittorrent_stats::dht_errors);
+                } else {
+                    stats_incr!(bittorrent_stats::dht_responses);
+                }
+                let key = (direction.is_to_client(), message.transaction_id.clone());
+                if let Some(id) = self.pending.take(&key) {
+                    if let Some(tx) = self.find_tx(id) {
+                        tx.dht_response = Some(message);
+                        tx.complete = true;
+                        return;
+                    }
+                }
+                // the query was missed
+                let mut tx = self.new_tx(Transport::Dht);
+                tx.dht_response = Some(message);
+                self.transactions.push(tx);
+            }
+        }
+    }
+
+    /// Only the handshake is looked for in the uTP packets: the stream
+    /// is not reassembled.
+    fn handle_utp(&mut self, direction: Direction, header: UtpHeader, payload: &[u8]) {
+        stats_incr!(bittorrent_stats::utp_packets);
+        let idx = stream_index(direction);
+        if header.packet_type != UTP_ST_DATA || self.streams[idx].handshake {
+            return;
+        }
+        if let Ok((_, handshake)) = parse_handshake(payload) {
+            self.streams[idx].extended_done = true;
+            self.handle_handshake(direction, handshake, Transport::Utp);
+        }
+    }
+
+    /// Parse a datagram, a DHT message or a uTP packet as both share the
+    /// UDP port of the clients.
+    fn parse_udp(&mut self, direction: Direction, input: &[u8]) -> AppLayerResult {
+        if input.first() == Some(&b'd') {
+            match parse_dht_message(input) {
+                Ok(message) => self.handle_dht(direction, message),
+                Err(_) => self.set_malformed(Transport::Dht, BitTorrentEvent::MalformedMessage),
+            }
+        } else if let Some((header, payload)) = parse_utp(input) {
+            self.handle_utp(direction, header, payload);
+        } else {
+            self.set_malformed(Transport::Dht, BitTorrentEvent::MalformedMessage);
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for the handshake, sent first by both peers.
+fn bittorrent_probe_tcp(input: &[u8], _direction: Direction) -> ProbeResult {
+    let n = std::cmp::min(input.len(), HANDSHAKE_PROTOCOL.len());
+    if input[..n] != HANDSHAKE_PROTOCOL[..n] {
+        return ProbeResult::Failed;
+    }
+    if n < HANDSHAKE_PROTOCOL.len() {
+        return ProbeResult::Unknown;
+    }
+    ProbeResult::Found(unsafe { ALPROTO_BITTORRENT })
+}
+
+/// Probe for a DHT message, or for the SYN opening a uTP connection.
+fn bittorrent_probe_udp(input: &[u8], direction: Direction) -> ProbeResult {
+    let alproto = unsafe { ALPROTO_BITTORRENT };
+    if input.first() == Some(&b'd') {
+        return match parse_dht_message(input) {
+            Ok(DhtMessage { body: DhtBody::Query(_), .. }) => {
+                ProbeResult::with_direction(alproto, direction, Direction::ToServer)
+            }
+            Ok(_) => ProbeResult::with_direction(alproto, direction, Direction::ToClient),
+            Err(_) => ProbeResult::Failed,
+        };
+    }
+    match parse_utp(input) {
+        Some((header, payload)) => {
+            if header.packet_type == UTP_ST_SYN && payload.is_empty() {
+                ProbeResult::with_direction(alproto, direction, Direction::ToServer)
+            } else {
+                ProbeResult::Failed
+            }
+        }
+        None => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_bittorrent_probing_parser_tcp, bittorrent_probe_tcp);
+export_probe!(rs_bittorrent_probing_parser_udp, bittorrent_probe_udp);
+
+#[no_mangle]
+pub extern "C" fn rs_bittorrent_state_new(_orig_state: *mut std::os::raw::c_void,
+                                          _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = BitTorrentState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut BitTorrentState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut BitTorrentState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_parse_request_udp(_flow: *const Flow,
+                                                         state: *mut std::os::raw::c_void,
+                                                         _pstate: *mut std::os::raw::c_void,
+                                                         input: *const u8,
+                                                         input_len: u32,
+                                                         _data: *const std::os::raw::c_void,
+                                                         _flags: u8) -> AppLayerResult {
+    let state: &mut BitTorrentState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse_udp(Direction::ToServer, buf)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_parse_response_udp(_flow: *const Flow,
+                                                          state: *mut std::os::raw::c_void,
+                                                          _pstate: *mut std::os::raw::c_void,
+                                                          input: *const u8,
+                                                          input_len: u32,
+                                                          _data: *const std::os::raw::c_void,
+                                                          _flags: u8) -> AppLayerResult {
+    let state: &mut BitTorrentState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse_udp(Direction::ToClient, buf)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_parse_request_tcp(_flow: *const Flow,
+                                                         state: *mut std::os::raw::c_void,
+                                                         pstate: *mut std::os::raw::c_void,
+                                                         input: *const u8,
+                                                         input_len: u32,
+                                                         _data: *const std::os::raw::c_void,
+                                                         _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut BitTorrentState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse_tcp(Direction::ToServer, buf)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_parse_response_tcp(_flow: *const Flow,
+                                                          state: *mut std::os::raw::c_void,
+                                                          pstate: *mut std::os::raw::c_void,
+                                                          input: *const u8,
+                                                          input_len: u32,
+                                                          _data: *const std::os::raw::c_void,
+                                                          _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut BitTorrentState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse_tcp(Direction::ToClient, buf)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_state_get_tx(state: *mut std::os::raw::c_void,
+                                                    tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut BitTorrentState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut BitTorrentState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// A peer connection is complete past the handshakes, a DHT query once
+/// answered.
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                               _direction: u8) -> std::os::raw::c_int {
+    let tx: &mut BitTorrentTransaction = cast_mut(tx);
+    tx.complete as std::os::raw::c_int
+}
+
+export_tx_detect_state!(rs_bittorrent_state_get_tx_detect_state,
+                        rs_bittorrent_state_set_tx_detect_state, BitTorrentTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_state_get_events(tx: *mut std::os::raw::c_void)
+                                                        -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut BitTorrentTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_bittorrent_get_tx_data, BitTorrentTransaction);
+
+fn bittorrent_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    BITTORRENT_GAUGES.log(js)?;
+    js.set_uint("events", bittorrent_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+fn register_pattern_probe() -> i8 {
+    let pattern = b"\x13BitTorrent protocol\0";
+    for direction in &[core::STREAM_TOSERVER, core::STREAM_TOCLIENT] {
+        unsafe {
+            if AppLayerProtoDetectPMRegisterPatternCSwPP(IPPROTO_TCP as u8, ALPROTO_BITTORRENT,
+                                                         pattern.as_ptr() as *const std::os::raw::c_char,
+                                                         HANDSHAKE_PROTOCOL.len() as u16, 0, *direction,
+                                                         rs_bittorrent_probing_parser_tcp, 0, 0) < 0 {
+                SCLogDebug!("AppLayerProtoDetectPMRegisterPatternCSwPP FAILED");
+                return -1;
+            }
+        }
+    }
+    0
+}
+
+const PARSER_NAME: &[u8] = b"bittorrent\0";
+
+fn parser(ipproto: i32) -> RustParser {
+    let tcp = ipproto == IPPROTO_TCP;
+    RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: std::ptr::null(),
+        ipproto,
+        probe_ts: Some(if tcp { rs_bittorrent_probing_parser_tcp } else { rs_bittorrent_probing_parser_udp }),
+        probe_tc: Some(if tcp { rs_bittorrent_probing_parser_tcp } else { rs_bittorrent_probing_parser_udp }),
+        min_depth: 0,
+        max_depth: if tcp { HANDSHAKE_PROTOCOL.len() as u16 } else { 0 },
+        state_new: rs_bittorrent_state_new,
+        state_free: rs_bittorrent_state_free,
+        tx_free: rs_bittorrent_state_tx_free,
+        parse_ts: if tcp { rs_bittorrent_parse_request_tcp } else { rs_bittorrent_parse_request_udp },
+        parse_tc: if tcp { rs_bittorrent_parse_response_tcp } else { rs_bittorrent_parse_response_udp },
+        get_tx_count: rs_bittorrent_state_get_tx_count,
+        get_tx: rs_bittorrent_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_bittorrent_tx_get_alstate_progress,
+        get_de_state: rs_bittorrent_state_get_tx_detect_state,
+        set_de_state: rs_bittorrent_state_set_tx_detect_state,
+        get_events: Some(rs_bittorrent_state_get_events),
+        get_eventinfo: Some(BitTorrentEvent::get_event_info),
+        get_eventinfo_byid: Some(BitTorrentEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<BitTorrentState>),
+        get_tx_data: rs_bittorrent_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<BitTorrentState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    }
+}
+
+unsafe fn register(ipproto: i32, ip_proto_str: &str) {
+    let default_port = CString::new("[6881:6889]").unwrap();
+    let mut parser = parser(ipproto);
+    parser.default_port = default_port.as_ptr();
+
+    let ip_proto_str = CString::new(ip_proto_str).unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_BITTORRENT = alproto;
+        if ipproto == IPPROTO_TCP && register_pattern_probe() < 0 {
+            return;
+        }
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust bittorrent parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for BitTorrent.");
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_tcp_register_parser() {
+    bittorrent_stats::register();
+    BITTORRENT_TX_LIMIT.configure("bittorrent", txtable::DEFAULT_MAX_TX);
+    introspect::register("bittorrent", bittorrent_introspect);
+    register(IPPROTO_TCP, "tcp");
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_bittorrent_udp_register_parser() {
+    register(IPPROTO_UDP, "udp");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness(tcp: bool) -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_bittorrent_state_new,
+            state_free: rs_bittorrent_state_free,
+            parse_ts: if tcp { rs_bittorrent_parse_request_tcp } else { rs_bittorrent_parse_request_udp },
+            parse_tc: if tcp { rs_bittorrent_parse_response_tcp } else { rs_bittorrent_parse_response_udp },
+            get_tx_count: rs_bittorrent_state_get_tx_count,
+            get_tx: rs_bittorrent_state_get_tx,
+            tx_free: rs_bittorrent_state_tx_free,
+        })
+    }
+
+    fn handshake(reserved: &[u8], info_hash: u8, peer_id: &[u8]) -> Vec<u8> {
+        let mut v = HANDSHAKE_PROTOCOL.to_vec();
+        v.extend_from_slice(reserved);
+        v.extend_from_slice(&[info_hash; 20]);
+        v.extend_from_slice(peer_id);
+        v
+    }
+
+    const EXTENSIONS: &[u8] = b"\x00\x00\x00\x00\x00\x10\x00\x05";
+
+    fn extended_handshake(version: &str) -> Vec<u8> {
+        let dict = format!("d1:md6:ut_pexi1ee1:v{}:{}e", version.len(), version);
+        let mut v = vec![0, 0, 0, (dict.len() + 2) as u8, MSG_EXTENDED, 0];
+        v.extend_from_slice(dict.as_bytes());
+        v
+    }
+
+    #[test]
+    fn test_bittorrent_tcp_handshakes() {
+        let mut h = harness(true);
+        let mut client = handshake(EXTENSIONS, 0xaa, b"-qB4250-abcdefghijkl");
+        client.extend_from_slice(&extended_handshake("qBittorrent/4.2.5"));
+        assert!(h.feed_segments(Direction::ToServer, &client, 7));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<BitTorrentTransaction>(0).unwrap();
+        assert_eq!(tx.transport, Transport::Tcp);
+        assert_eq!(tx.info_hash, Some("aa".repeat(20)));
+        assert_eq!(tx.client.as_ref().unwrap().extended.as_ref().unwrap().version,
+                   Some(b"qBittorrent/4.2.5".to_vec()));
+        assert!(!tx.complete);
+
+        let mut server = handshake(EXTENSIONS, 0xaa, b"-TR2940-mnopqrstuvwx");
+        // a bitfield, and a have split over the segments
+        server.extend_from_slice(b"\x00\x00\x00\x03\x05\xff\xf0");
+        server.extend_from_slice(b"\x00\x00\x00\x05\x04\x00\x00");
+        assert!(h.feed(Direction::ToServer, b"\x00\x00\x00\x00"));
+        assert!(h.feed(Direction::ToClient, &server));
+        assert!(!h.get_tx::<BitTorrentTransaction>(0).unwrap().complete);
+        assert!(h.feed(Direction::ToClient, b"\x00\x2a"));
+        let mut rest = extended_handshake("Transmission 2.94");
+        rest.extend_from_slice(b"\x00\x00\x00\x01\x02");
+        assert!(h.feed(Direction::ToClient, &rest));
+        let tx = h.get_tx::<BitTorrentTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.server.as_ref().unwrap().handshake.client(), Some(&b"TR2940"[..]));
+        assert_eq!(h.tx_count(), 1);
+        assert_eq!(h.pending(Direction::ToClient), 0);
+    }
+
+    #[test]
+    fn test_bittorrent_tcp_no_extensions() {
+        let mut h = harness(true);
+        assert!(h.feed(Direction::ToServer, &handshake(&[0; 8], 0x01, &[b'A'; 20])));
+        let mut server = handshake(&[0; 8], 0x02, &[b'B'; 20]);
+        // a message larger than any the protocol uses
+        server.extend_from_slice(b"\x7f\x00\x00\x00\x07");
+        assert!(!h.feed(Direction::ToClient, &server));
+        let tx = h.get_tx::<BitTorrentTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.info_hash, Some("01".repeat(20)));
+        assert_eq!(h.tx_count(), 2);
+    }
+
+    #[test]
+    fn test_bittorrent_dht() {
+        let mut h = harness(false);
+        let query = b"d1:ad2:id20:abcdefghij01234567899:info_hash20:mnopqrstuvwxyz123456e\
+            1:q9:get_peers1:t2:aa1:y1:qe";
+        assert!(h.feed(Direction::ToServer, query));
+        // the other node queries too, with the same transaction id
+        assert!(h.feed(Direction::ToClient, b"d1:ad2:id20:mnopqrstuvwxyz123456e1:q4:ping1:t2:aa1:y1:qe"));
+        assert_eq!(h.tx_count(), 2);
+        let tx = h.get_tx::<BitTorrentTransaction>(0).unwrap();
+        assert_eq!(tx.info_hash, Some("6d6e6f707172737475767778797a313233343536".to_string()));
+        assert!(!tx.complete);
+
+        assert!(h.feed(Direction::ToClient, b"d1:rd2:id20:mnopqrstuvwxyz1234565:token8:aoeusnth\
+            6:valuesl6:axje.uee1:t2:aa1:y1:re"));
+        assert_eq!(h.tx_count(), 2);
+        let tx = h.get_tx::<BitTorrentTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert!(tx.dht_response.is_some());
+        assert!(!h.get_tx::<BitTorrentTransaction>(1).unwrap().complete);
+
+        // a response to a query that was not seen
+        assert!(h.feed(Direction::ToClient, b"d1:rd2:id20:mnopqrstuvwxyz123456e1:t2:zz1:y1:re"));
+        assert_eq!(h.tx_count(), 3);
+        assert!(h.get_tx::<BitTorrentTransaction>(2).unwrap().dht_query.is_none());
+    }
+
+    #[test]
+    fn test_bittorrent_utp() {
+        let mut h = harness(false);
+        let syn = b"\x41\x00\x30\x39\x00\x00\x00\x01\x00\x00\x00\x00\x00\x10\x00\x00\x00\x01\x00\x00";
+        assert!(h.feed(Direction::ToServer, syn));
+        assert_eq!(h.tx_count(), 0);
+        let mut data = syn.to_vec();
+        data[0] = 0x01;
+        data.extend_from_slice(&handshake(&[0; 8], 0x33, &[b'C'; 20]));
+        assert!(h.feed(Direction::ToServer, &data));
+        let tx = h.get_tx::<BitTorrentTransaction>(0).unwrap();
+        assert_eq!(tx.transport, Transport::Utp);
+        assert!(tx.client.is_some());
+        assert!(!tx.complete);
+    }
+
+    #[test]
+    fn test_bittorrent_probe() {
+        unsafe {
+            ALPROTO_BITTORRENT = 42;
+        }
+        let hs = handshake(&[0; 8], 0x01, &[b'A'; 20]);
+        assert_eq!(bittorrent_probe_tcp(&hs, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(bittorrent_probe_tcp(&hs[..10], Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(bittorrent_probe_tcp(b"GET / HTTP/1.1\r\n", Direction::ToServer), ProbeResult::Failed);
+        let ping = b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe";
+        assert_eq!(bittorrent_probe_udp(ping, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(bittorrent_probe_udp(b"d1:rd2:id20:abcdefghij0123456789e1:t2:aa1:y1:re",
+                                        Direction::ToServer), ProbeResult::Flipped(42));
+        let syn = b"\x41\x00\x30\x39\x00\x00\x00\x01\x00\x00\x00\x00\x00\x10\x00\x00\x00\x01\x00\x00";
+        assert_eq!(bittorrent_probe_udp(syn, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(bittorrent_probe_udp(b"d5:helloe", Direction::ToServer), ProbeResult::Failed);
+        assert_eq!(bittorrent_probe_udp(&syn[..19], Direction::ToServer), ProbeResult::Failed);
+    }
+}
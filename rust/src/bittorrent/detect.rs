@@ -0,0 +1,79 @@
+/* Copyright (C) 2024 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Sticky buffer on the info hash of the torrent shared by the peers
+//! or looked up in the DHT.
+
+use super::bittorrent::{BitTorrentTransaction, ALPROTO_BITTORRENT};
+use crate::applayer::cast_mut;
+use crate::core::ALPROTO_UNKNOWN;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_BITTORRENT_INFO_HASH_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn bittorrent_tx_get_info_hash(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut BitTorrentTransaction = cast_mut(tx);
+    match tx.info_hash {
+        Some(ref info_hash) => {
+            *buf = info_hash.as_ptr();
+            *len = info_hash.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn bittorrent_info_hash_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, bittorrent_tx_get_info_hash)
+}
+
+unsafe extern "C" fn bittorrent_info_hash_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_BITTORRENT, G_BITTORRENT_INFO_HASH_BUFFER_ID)
+}
+
+/// Register the BitTorrent keywords.
+pub unsafe fn register_keywords() {
+    // The parser is only registered if enabled in config.
+    if ALPROTO_BITTORRENT == ALPROTO_UNKNOWN {
+        return;
+    }
+    let kw = DetectKeyword {
+        name: "bittorrent.info_hash",
+        desc: "sticky buffer to match on the BitTorrent info hash, in lowercase hex",
+        url: "/rules/bittorrent-keywords.html#bittorrent-info-hash",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: bittorrent_info_hash_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_BITTORRENT_INFO_HASH_BUFFER_ID = helper_buffer_mpm_register(
+            "bittorrent.info_hash", "bittorrent info hash", ALPROTO_BITTORRENT, true, true, 0,
+            bittorrent_info_hash_get_data);
+    }
+}
@@ -0,0 +1,158 @@
+/* Copyright (C) 2024 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::bittorrent::{BitTorrentTransaction, Peer};
+use super::parser::{DhtBody, DhtMessage};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log_peer(js: &mut JsonBuilder, name: &str, peer: &Peer) -> Result<(), JsonError> {
+    js.open_object(name)?;
+    js.set_hex("info_hash", &peer.handshake.info_hash)?;
+    js.set_hex("peer_id", &peer.handshake.peer_id)?;
+    if let Some(client) = peer.handshake.client() {
+        js.set_string_from_bytes("client", client)?;
+    }
+    let extensions = peer.handshake.extensions();
+    if !extensions.is_empty() {
+        js.open_array("extensions")?;
+        for extension in extensions {
+            js.append_string(extension)?;
+        }
+        js.close()?;
+    }
+    if let Some(ref extended) = peer.extended {
+        if let Some(ref version) = extended.version {
+            js.set_string_from_bytes("version", version)?;
+        }
+        if !extended.messages.is_empty() {
+            js.open_array("extension_messages")?;
+            for message in &extended.messages {
+                js.append_string_from_bytes(message)?;
+            }
+            js.close()?;
+        }
+        if let Some(port) = extended.port {
+            js.set_uint("port", port as u64)?;
+        }
+    }
+    js.close()?;
+    Ok(())
+}
+
+fn log_dht(js: &mut JsonBuilder, message: &DhtMessage) -> Result<(), JsonError> {
+    match message.body {
+        DhtBody::Query(ref query) => {
+            js.open_object("query")?;
+            js.set_string_from_bytes("method", &query.method)?;
+            if let Some(ref id) = query.id {
+                js.set_hex("id", id)?;
+            }
+            if let Some(ref info_hash) = query.info_hash {
+                js.set_hex("info_hash", info_hash)?;
+            }
+            if let Some(ref target) = query.target {
+                js.set_hex("target", target)?;
+            }
+            if let Some(port) = query.port {
+                js.set_uint("port", port as u64)?;
+            }
+            if query.implied_port {
+                js.set_bool("implied_port", true)?;
+            }
+            if let Some(ref token) = query.token {
+                js.set_hex("token", token)?;
+            }
+            js.close()?;
+        }
+        DhtBody::Response(ref response) => {
+            js.open_object("response")?;
+            if let Some(ref id) = response.id {
+                js.set_hex("id", id)?;
+            }
+            if !response.nodes.is_empty() {
+                js.open_array("nodes")?;
+                for node in &response.nodes {
+                    js.start_object()?;
+                    js.set_hex("id", &node.id)?;
+                    js.set_string("ip", &node.ip.to_string())?;
+                    js.set_uint("port", node.port as u64)?;
+                    js.close()?;
+                }
+                js.close()?;
+            }
+            if !response.values.is_empty() {
+                js.open_array("values")?;
+                for peer in &response.values {
+                    js.start_object()?;
+                    js.set_string("ip", &peer.ip.to_string())?;
+                    js.set_uint("port", peer.port as u64)?;
+                    js.close()?;
+                }
+                js.close()?;
+            }
+            if let Some(ref token) = response.token {
+                js.set_hex("token", token)?;
+            }
+            js.close()?;
+        }
+        DhtBody::Error(ref error) => {
+            js.open_object("error")?;
+            if error.code >= 0 {
+                js.set_uint("code", error.code as u64)?;
+            }
+            js.set_string_from_bytes("message", &error.message)?;
+            js.close()?;
+        }
+    }
+    Ok(())
+}
+
+fn log(tx: &BitTorrentTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("bittorrent")?;
+    js.set_string("transport", tx.transport.to_str())?;
+    if let Some(ref info_hash) = tx.info_hash {
+        js.set_string("info_hash", info_hash)?;
+    }
+    if let Some(ref peer) = tx.client {
+        log_peer(js, "client", peer)?;
+    }
+    if let Some(ref peer) = tx.server {
+        log_peer(js, "server", peer)?;
+    }
+    let first = tx.dht_query.as_ref().or(tx.dht_response.as_ref());
+    if let Some(first) = first {
+        js.open_object("dht")?;
+        js.set_hex("transaction_id", &first.transaction_id)?;
+        if let Some(ref version) = first.version {
+            js.set_hex("version", version)?;
+        }
+        if let Some(ref query) = tx.dht_query {
+            log_dht(js, query)?;
+        }
+        if let Some(ref response) = tx.dht_response {
+            log_dht(js, response)?;
+        }
+        js.close()?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_bittorrent_to_json(tx: &mut BitTorrentTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
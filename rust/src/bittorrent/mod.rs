@@ -0,0 +1,31 @@
+/* Copyright (C) 2024 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! BitTorrent application layer, parser and logger module.
+//!
+//! Over TCP, the transaction is the peer connection: the handshakes of
+//! both peers, and their extended handshakes if they support the
+//! extension protocol. The pieces exchanged afterwards are skipped.
+//!
+//! Over UDP, the transactions are a DHT query and its response, and
+//! the peer connections over uTP of which only the handshakes are
+//! looked for.
+
+pub mod bittorrent;
+pub mod detect;
+pub mod logger;
+pub mod parser;
@@ -0,0 +1,598 @@
+/* Copyright (C) 2024 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! BitTorrent decoding: the handshake and messages of the peer wire
+//! protocol (BEP 3, BEP 10), the bencoded KRPC messages of the DHT
+//! (BEP 5) and the header of uTP (BEP 29).
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The protocol string of the handshake, with its length prefix.
+pub const HANDSHAKE_PROTOCOL: &[u8] = b"\x13BitTorrent protocol";
+
+/// The protocol string, the reserved bytes, the info hash and the peer id.
+pub const HANDSHAKE_LEN: usize = 68;
+
+/// Length of info hashes, node ids and peer ids.
+pub const ID_LEN: usize = 20;
+
+/// Message id of the extension protocol (BEP 10).
+pub const MSG_EXTENDED: u8 = 20;
+
+/// Messages requesting or carrying pieces: once they are exchanged the
+/// peers are past the start of the connection.
+pub const MSG_REQUEST: u8 = 6;
+pub const MSG_PIECE: u8 = 7;
+pub const MSG_CANCEL: u8 = 8;
+
+/// Nesting allowed in bencoded data. KRPC messages and extended
+/// handshakes use 3 levels at most.
+const BENCODE_MAX_DEPTH: usize = 8;
+
+pub const UTP_ST_DATA: u8 = 0;
+pub const UTP_ST_FIN: u8 = 1;
+pub const UTP_ST_STATE: u8 = 2;
+pub const UTP_ST_RESET: u8 = 3;
+pub const UTP_ST_SYN: u8 = 4;
+
+const UTP_HEADER_LEN: usize = 20;
+const UTP_VERSION: u8 = 1;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    Incomplete,
+    Invalid,
+}
+
+/// Bencoded value, borrowing the strings from the input.
+#[derive(Debug, PartialEq)]
+pub enum BValue<'a> {
+    Int(i64),
+    Bytes(&'a [u8]),
+    List(Vec<BValue<'a>>),
+    Dict(Vec<(&'a [u8], BValue<'a>)>),
+}
+
+impl<'a> BValue<'a> {
+    pub fn get(&self, key: &[u8]) -> Option<&BValue<'a>> {
+        match *self {
+            BValue::Dict(ref entries) => entries.iter().find(|(k, _)| *k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            BValue::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match *self {
+            BValue::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+}
+
+fn bencode_int(input: &[u8], end: u8) -> Result<(&[u8], i64), ParseError> {
+    let pos = match input.iter().position(|c| *c == end) {
+        Some(pos) => pos,
+        // 20 bytes hold any i64 with its sign
+        None if input.len() < 21 => return Err(ParseError::Incomplete),
+        None => return Err(ParseError::Invalid),
+    };
+    let digits = &input[..pos];
+    let (negative, digits) = match digits.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, digits),
+    };
+    if digits.is_empty() || digits.len() > 19 || !digits.iter().all(u8::is_ascii_digit) {
+        return Err(ParseError::Invalid);
+    }
+    let mut value: i64 = 0;
+    for d in digits {
+        value = value
+            .checked_mul(10)
+            .and_then(|v| v.checked_add((d - b'0') as i64))
+            .ok_or(ParseError::Invalid)?;
+    }
+    Ok((&input[pos + 1..], if negative { -value } else { value }))
+}
+
+fn bencode_value(input: &[u8], depth: usize) -> Result<(&[u8], BValue), ParseError> {
+    let first = *input.first().ok_or(ParseError::Incomplete)?;
+    match first {
+        b'i' => {
+            let (rest, value) = bencode_int(&input[1..], b'e')?;
+            Ok((rest, BValue::Int(value)))
+        }
+        b'0'..=b'9' => {
+            let (rest, len) = bencode_int(input, b':')?;
+            let len = len as usize;
+            if rest.len() < len {
+                return Err(ParseError::Incomplete);
+            }
+            Ok((&rest[len..], BValue::Bytes(&rest[..len])))
+        }
+        b'l' | b'd' => {
+            if depth == 0 {
+                return Err(ParseError::Invalid);
+            }
+            let mut rest = &input[1..];
+            let mut items = Vec::new();
+            let mut entries = Vec::new();
+            loop {
+                match rest.first() {
+                    None => return Err(ParseError::Incomplete),
+                    Some(b'e') => {
+                        rest = &rest[1..];
+                        break;
+                    }
+                    _ => {}
+                }
+                if first == b'd' {
+                    let (r, key) = bencode_value(rest, 0)?;
+                    let key = key.as_bytes().ok_or(ParseError::Invalid)?;
+                    let (r, value) = bencode_value(r, depth - 1)?;
+                    entries.push((key, value));
+                    rest = r;
+                } else {
+                    let (r, value) = bencode_value(rest, depth - 1)?;
+                    items.push(value);
+                    rest = r;
+                }
+            }
+            if first == b'd' {
+                Ok((rest, BValue::Dict(entries)))
+            } else {
+                Ok((rest, BValue::List(items)))
+            }
+        }
+        _ => Err(ParseError::Invalid),
+    }
+}
+
+/// Parse one bencoded value, returning the data following it.
+pub fn parse_bencode(input: &[u8]) -> Result<(&[u8], BValue), ParseError> {
+    bencode_value(input, BENCODE_MAX_DEPTH)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Handshake {
+    pub reserved: [u8; 8],
+    pub info_hash: [u8; ID_LEN],
+    pub peer_id: [u8; ID_LEN],
+}
+
+impl Handshake {
+    /// The extensions announced by the reserved bits.
+    pub fn extensions(&self) -> Vec<&'static str> {
+        let mut extensions = Vec::new();
+        if self.reserved[0] & 0x80 != 0 {
+            extensions.push("azureus_messaging");
+        }
+        if self.reserved[5] & 0x10 != 0 {
+            extensions.push("extension_protocol");
+        }
+        if self.reserved[7] & 0x01 != 0 {
+            extensions.push("dht");
+        }
+        if self.reserved[7] & 0x04 != 0 {
+            extensions.push("fast");
+        }
+        if self.reserved[7] & 0x10 != 0 {
+            extensions.push("v2_upgrade");
+        }
+        extensions
+    }
+
+    pub fn has_extension_protocol(&self) -> bool {
+        self.reserved[5] & 0x10 != 0
+    }
+
+    /// The client and version of an Azureus style peer id, like
+    /// "-qB4250-".
+    pub fn client(&self) -> Option<&[u8]> {
+        let prefix = &self.peer_id[..8];
+        if prefix[0] == b'-' && prefix[7] == b'-'
+            && prefix[1..7].iter().all(u8::is_ascii_alphanumeric)
+        {
+            return Some(&prefix[1..7]);
+        }
+        None
+    }
+}
+
+/// Parse a handshake. The protocol string is checked before the whole
+/// handshake is available, so a stream not starting with it is rejected
+/// early.
+pub fn parse_handshake(input: &[u8]) -> Result<(&[u8], Handshake), ParseError> {
+    let n = std::cmp::min(input.len(), HANDSHAKE_PROTOCOL.len());
+    if input[..n] != HANDSHAKE_PROTOCOL[..n] {
+        return Err(ParseError::Invalid);
+    }
+    if input.len() < HANDSHAKE_LEN {
+        return Err(ParseError::Incomplete);
+    }
+    let mut handshake = Handshake {
+        reserved: [0; 8],
+        info_hash: [0; ID_LEN],
+        peer_id: [0; ID_LEN],
+    };
+    handshake.reserved.copy_from_slice(&input[20..28]);
+    handshake.info_hash.copy_from_slice(&input[28..48]);
+    handshake.peer_id.copy_from_slice(&input[48..68]);
+    Ok((&input[HANDSHAKE_LEN..], handshake))
+}
+
+/// The header of a message of the peer wire protocol: its length and
+/// id. Keep-alives have no id.
+#[derive(Debug, PartialEq)]
+pub struct MessageHeader {
+    pub len: usize,
+    pub id: Option<u8>,
+}
+
+pub fn parse_message_header(input: &[u8]) -> Result<MessageHeader, ParseError> {
+    if input.len() < 4 {
+        return Err(ParseError::Incomplete);
+    }
+    let len = (input[0] as usize) << 24 | (input[1] as usize) << 16
+        | (input[2] as usize) << 8 | input[3] as usize;
+    if len == 0 {
+        return Ok(MessageHeader { len, id: None });
+    }
+    match input.get(4) {
+        Some(id) => Ok(MessageHeader { len, id: Some(*id) }),
+        None => Err(ParseError::Incomplete),
+    }
+}
+
+/// The extended handshake (BEP 10), following the handshake when both
+/// peers support the extension protocol.
+#[derive(Debug, Default, PartialEq)]
+pub struct ExtendedHandshake {
+    /// The client name and version, "v".
+    pub version: Option<Vec<u8>>,
+    /// The extension messages supported, the keys of "m".
+    pub messages: Vec<Vec<u8>>,
+    /// The TCP listen port, "p".
+    pub port: Option<u16>,
+}
+
+/// Parse the payload of an extended message, after its id byte. Returns
+/// None for the extension messages other than the handshake.
+pub fn parse_extended(payload: &[u8]) -> Result<Option<ExtendedHandshake>, ParseError> {
+    let (&ext_id, rest) = payload.split_first().ok_or(ParseError::Invalid)?;
+    if ext_id != 0 {
+        return Ok(None);
+    }
+    let dict = match parse_bencode(rest) {
+        Ok((_, dict @ BValue::Dict(_))) => dict,
+        _ => return Err(ParseError::Invalid),
+    };
+    let mut handshake = ExtendedHandshake::default();
+    handshake.version = dict.get(b"v").and_then(BValue::as_bytes).map(|v| v.to_vec());
+    if let Some(BValue::Dict(ref entries)) = dict.get(b"m") {
+        handshake.messages = entries.iter().map(|(k, _)| k.to_vec()).collect();
+    }
+    handshake.port = dict.get(b"p").and_then(BValue::as_int).and_then(port_from_int);
+    Ok(Some(handshake))
+}
+
+fn port_from_int(port: i64) -> Option<u16> {
+    if port > 0 && port <= 0xffff {
+        Some(port as u16)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DhtNode {
+    pub id: Vec<u8>,
+    pub ip: std::net::IpAddr,
+    pub port: u16,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DhtPeer {
+    pub ip: std::net::IpAddr,
+    pub port: u16,
+}
+
+/// The arguments of a query. Only the ones of the BEP 5 queries are
+/// kept.
+#[derive(Debug, Default, PartialEq)]
+pub struct DhtQuery {
+    pub method: Vec<u8>,
+    pub id: Option<Vec<u8>>,
+    pub info_hash: Option<Vec<u8>>,
+    pub target: Option<Vec<u8>>,
+    pub port: Option<u16>,
+    pub implied_port: bool,
+    pub token: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DhtResponse {
+    pub id: Option<Vec<u8>>,
+    pub nodes: Vec<DhtNode>,
+    pub values: Vec<DhtPeer>,
+    pub token: Option<Vec<u8>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DhtError {
+    pub code: i64,
+    pub message: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DhtBody {
+    Query(DhtQuery),
+    Response(DhtResponse),
+    Error(DhtError),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DhtMessage {
+    pub transaction_id: Vec<u8>,
+    /// The client version, "v".
+    pub version: Option<Vec<u8>>,
+    pub body: DhtBody,
+}
+
+fn opt_bytes(dict: &BValue, key: &[u8]) -> Option<Vec<u8>> {
+    dict.get(key).and_then(BValue::as_bytes).map(|v| v.to_vec())
+}
+
+fn compact_peer(b: &[u8]) -> Option<DhtPeer> {
+    let (ip, port) = match b.len() {
+        6 => {
+            let ip = Ipv4Addr::new(b[0], b[1], b[2], b[3]);
+            (ip.into(), &b[4..])
+        }
+        18 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&b[..16]);
+            (Ipv6Addr::from(octets).into(), &b[16..])
+        }
+        _ => return None,
+    };
+    Some(DhtPeer { ip, port: (port[0] as u16) << 8 | port[1] as u16 })
+}
+
+/// The compact node infos: the node id followed by a compact peer, of
+/// an IPv4 address in "nodes" and of an IPv6 address in "nodes6".
+fn compact_nodes(b: &[u8], addr_len: usize, nodes: &mut Vec<DhtNode>) -> bool {
+    let len = ID_LEN + addr_len;
+    if b.len() % len != 0 {
+        return false;
+    }
+    for chunk in b.chunks(len) {
+        if let Some(peer) = compact_peer(&chunk[ID_LEN..]) {
+            nodes.push(DhtNode { id: chunk[..ID_LEN].to_vec(), ip: peer.ip, port: peer.port });
+        }
+    }
+    true
+}
+
+/// Parse a KRPC message of the DHT.
+pub fn parse_dht_message(input: &[u8]) -> Result<DhtMessage, ParseError> {
+    let dict = match parse_bencode(input) {
+        Ok((_, dict @ BValue::Dict(_))) => dict,
+        Ok(_) | Err(ParseError::Incomplete) => return Err(ParseError::Invalid),
+        Err(e) => return Err(e),
+    };
+    let transaction_id = opt_bytes(&dict, b"t").ok_or(ParseError::Invalid)?;
+    let version = opt_bytes(&dict, b"v");
+    let body = match dict.get(b"y").and_then(BValue::as_bytes) {
+        Some(b"q") => {
+            let method = opt_bytes(&dict, b"q").ok_or(ParseError::Invalid)?;
+            let args = dict.get(b"a").ok_or(ParseError::Invalid)?;
+            DhtBody::Query(DhtQuery {
+                method,
+                id: opt_bytes(args, b"id"),
+                info_hash: opt_bytes(args, b"info_hash"),
+                target: opt_bytes(args, b"target"),
+                port: args.get(b"port").and_then(BValue::as_int).and_then(port_from_int),
+                implied_port: args.get(b"implied_port").and_then(BValue::as_int).unwrap_or(0) != 0,
+                token: opt_bytes(args, b"token"),
+            })
+        }
+        Some(b"r") => {
+            let values = dict.get(b"r").ok_or(ParseError::Invalid)?;
+            let mut response = DhtResponse {
+                id: opt_bytes(values, b"id"),
+                token: opt_bytes(values, b"token"),
+                ..Default::default()
+            };
+            if let Some(nodes) = values.get(b"nodes").and_then(BValue::as_bytes) {
+                if !compact_nodes(nodes, 6, &mut response.nodes) {
+                    return Err(ParseError::Invalid);
+                }
+            }
+            if let Some(nodes) = values.get(b"nodes6").and_then(BValue::as_bytes) {
+                if !compact_nodes(nodes, 18, &mut response.nodes) {
+                    return Err(ParseError::Invalid);
+                }
+            }
+            if let Some(BValue::List(ref peers)) = values.get(b"values") {
+                response.values = peers
+                    .iter()
+                    .filter_map(BValue::as_bytes)
+                    .filter_map(compact_peer)
+                    .collect();
+            }
+            DhtBody::Response(response)
+        }
+        Some(b"e") => match dict.get(b"e") {
+            Some(BValue::List(ref e)) if e.len() == 2 => DhtBody::Error(DhtError {
+                code: e[0].as_int().ok_or(ParseError::Invalid)?,
+                message: e[1].as_bytes().ok_or(ParseError::Invalid)?.to_vec(),
+            }),
+            _ => return Err(ParseError::Invalid),
+        },
+        _ => return Err(ParseError::Invalid),
+    };
+    Ok(DhtMessage { transaction_id, version, body })
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UtpHeader {
+    pub packet_type: u8,
+    pub connection_id: u16,
+    pub seq_nr: u16,
+    pub ack_nr: u16,
+}
+
+/// Parse the header of a uTP packet and its extensions, returning the
+/// payload.
+pub fn parse_utp(input: &[u8]) -> Option<(UtpHeader, &[u8])> {
+    if input.len() < UTP_HEADER_LEN {
+        return None;
+    }
+    let packet_type = input[0] >> 4;
+    if input[0] & 0x0f != UTP_VERSION || packet_type > UTP_ST_SYN {
+        return None;
+    }
+    let header = UtpHeader {
+        packet_type,
+        connection_id: (input[2] as u16) << 8 | input[3] as u16,
+        seq_nr: (input[16] as u16) << 8 | input[17] as u16,
+        ack_nr: (input[18] as u16) << 8 | input[19] as u16,
+    };
+    let mut extension = input[1];
+    let mut rest = &input[UTP_HEADER_LEN..];
+    while extension != 0 {
+        // 1 is the selective ack, 2 the extension bits: anything else
+        // is not uTP
+        if extension > 2 || rest.len() < 2 {
+            return None;
+        }
+        let len = rest[1] as usize;
+        if rest.len() < 2 + len {
+            return None;
+        }
+        extension = rest[0];
+        rest = &rest[2 + len..];
+    }
+    Some((header, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bencode() {
+        let (rest, value) = parse_bencode(b"d3:bar4:spam3:fooi-42e4:listl1:ai0eee!").unwrap();
+        assert_eq!(rest, b"!");
+        assert_eq!(value.get(b"bar"), Some(&BValue::Bytes(b"spam")));
+        assert_eq!(value.get(b"foo"), Some(&BValue::Int(-42)));
+        assert_eq!(value.get(b"list"), Some(&BValue::List(vec![BValue::Bytes(b"a"), BValue::Int(0)])));
+        assert_eq!(parse_bencode(b"d3:bar4:sp"), Err(ParseError::Incomplete));
+        assert_eq!(parse_bencode(b"i12x"), Err(ParseError::Incomplete));
+        assert_eq!(parse_bencode(b"di1ei2ee"), Err(ParseError::Invalid));
+        assert_eq!(parse_bencode(b"i99999999999999999999e"), Err(ParseError::Invalid));
+        assert_eq!(parse_bencode(b"lllllllllleeeeeeeeee"), Err(ParseError::Invalid));
+    }
+
+    #[test]
+    fn test_dht_get_peers() {
+        let query = b"d1:ad2:id20:abcdefghij01234567899:info_hash20:mnopqrstuvwxyz123456e\
+            1:q9:get_peers1:t2:aa1:y1:qe";
+        let msg = parse_dht_message(query).unwrap();
+        assert_eq!(msg.transaction_id, b"aa");
+        match msg.body {
+            DhtBody::Query(q) => {
+                assert_eq!(q.method, b"get_peers");
+                assert_eq!(q.info_hash.unwrap(), b"mnopqrstuvwxyz123456");
+            }
+            _ => panic!("not a query"),
+        }
+
+        let response = b"d1:rd2:id20:abcdefghij01234567895:token8:aoeusnth6:valuesl6:\
+            axje.u6:idhtnmee1:t2:aa1:y1:re";
+        let msg = parse_dht_message(response).unwrap();
+        match msg.body {
+            DhtBody::Response(r) => {
+                assert_eq!(r.token.unwrap(), b"aoeusnth");
+                assert_eq!(r.values.len(), 2);
+                assert_eq!(r.values[0].ip, std::net::IpAddr::from([b'a', b'x', b'j', b'e']));
+                assert_eq!(r.values[0].port, 0x2e75);
+            }
+            _ => panic!("not a response"),
+        }
+
+        let msg = parse_dht_message(b"d1:eli201e23:A Generic Error Ocurrede1:t2:aa1:y1:ee").unwrap();
+        assert_eq!(msg.body, DhtBody::Error(DhtError { code: 201, message: b"A Generic Error Ocurred".to_vec() }));
+
+        assert_eq!(parse_dht_message(b"d1:t2:aa1:y1:xe"), Err(ParseError::Invalid));
+        // truncated datagrams are not completed later
+        assert_eq!(parse_dht_message(&query[..20]), Err(ParseError::Invalid));
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut input = HANDSHAKE_PROTOCOL.to_vec();
+        input.extend_from_slice(b"\x00\x00\x00\x00\x00\x10\x00\x05");
+        input.extend_from_slice(&[0xaa; 20]);
+        input.extend_from_slice(b"-qB4250-abcdefghijkl");
+        assert_eq!(parse_handshake(&input[..30]), Err(ParseError::Incomplete));
+        assert_eq!(parse_handshake(b"\x13BitTorrent protocoX"), Err(ParseError::Invalid));
+        let (rest, handshake) = parse_handshake(&input).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(handshake.info_hash, [0xaa; 20]);
+        assert_eq!(handshake.client(), Some(&b"qB4250"[..]));
+        assert_eq!(handshake.extensions(), vec!["extension_protocol", "dht", "fast"]);
+    }
+
+    #[test]
+    fn test_extended_handshake() {
+        let payload = b"\x00d1:md11:ut_metadatai2e6:ut_pexi1ee1:pi6881e1:v12:uTorrent 1.2e";
+        let handshake = parse_extended(payload).unwrap().unwrap();
+        assert_eq!(handshake.version, Some(b"uTorrent 1.2".to_vec()));
+        assert_eq!(handshake.messages, vec![b"ut_metadata".to_vec(), b"ut_pex".to_vec()]);
+        assert_eq!(handshake.port, Some(6881));
+        assert_eq!(parse_extended(b"\x02d8:msg_typei0ee"), Ok(None));
+        assert_eq!(parse_extended(b"\x00junk"), Err(ParseError::Invalid));
+    }
+
+    #[test]
+    fn test_utp() {
+        let syn = b"\x41\x00\x30\x39\x00\x00\x00\x01\x00\x00\x00\x00\x00\x10\x00\x00\x00\x01\x00\x00";
+        let (header, payload) = parse_utp(syn).unwrap();
+        assert_eq!(header.packet_type, UTP_ST_SYN);
+        assert_eq!(header.connection_id, 12345);
+        assert!(payload.is_empty());
+        // with a selective ack extension
+        let mut data = syn.to_vec();
+        data[0] = 0x01;
+        data[1] = 1;
+        data.extend_from_slice(b"\x00\x04\xff\xff\xff\xffpayload");
+        let (header, payload) = parse_utp(&data).unwrap();
+        assert_eq!(header.packet_type, UTP_ST_DATA);
+        assert_eq!(payload, b"payload");
+        data[1] = 7;
+        assert_eq!(parse_utp(&data), None);
+        assert_eq!(parse_utp(b"d1:ad2:id20:abcdefghij0123"), None);
+    }
+}
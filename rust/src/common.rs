@@ -1,5 +1,10 @@
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::os::raw::c_char;
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use digest::Digest;
+use sha2::Sha256;
 
 #[macro_export]
 macro_rules! take_until_and_consume (
@@ -60,6 +65,36 @@ pub fn rust_string_to_c(s: String) -> *mut c_char {
         .unwrap_or(std::ptr::null_mut())
 }
 
+lazy_static! {
+    static ref STATIC_CSTRINGS: Mutex<HashMap<String, CString>> = Mutex::new(HashMap::new());
+}
+
+/// Return a C string for `s` that is valid for the lifetime of the
+/// process, for the C APIs that keep the pointer they are given, such as
+/// the keyword, buffer and alias registration. Each string is allocated
+/// once, later calls with the same string return the same pointer.
+///
+/// Returns NULL if `s` contains a nul byte.
+pub fn static_cstr(s: &str) -> *const c_char {
+    let mut cache = match STATIC_CSTRINGS.lock() {
+        Ok(cache) => cache,
+        Err(_) => return std::ptr::null(),
+    };
+    if let Some(cs) = cache.get(s) {
+        return cs.as_ptr();
+    }
+    match CString::new(s) {
+        Ok(cs) => {
+            // The heap buffer of the CString does not move when the map
+            // grows, so the pointer stays valid.
+            let ptr = cs.as_ptr();
+            cache.insert(s.to_string(), cs);
+            ptr
+        }
+        Err(_) => std::ptr::null(),
+    }
+}
+
 /// Free a CString allocated by Rust (for ex. using `rust_string_to_c`)
 ///
 /// # Safety
@@ -81,3 +116,12 @@ pub fn to_hex(input: &[u8]) -> String {
         |b| vec![char::from(CHARS[(b >>  4) as usize]), char::from(CHARS[(b & 0xf) as usize])]
     ).flatten().collect();
 }
+
+/// The truncated SHA-256 of a part of a JA4 style fingerprint, or zeros
+/// if there is nothing to hash.
+pub fn ja4_hash(part: &str) -> String {
+    if part.is_empty() {
+        return "000000000000".to_string();
+    }
+    format!("{:x}", Sha256::digest(part.as_bytes()))[..12].to_string()
+}
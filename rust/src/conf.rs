@@ -30,6 +30,8 @@ use nom::{
 
 extern {
     fn ConfGet(key: *const c_char, res: *mut *const c_char) -> i8;
+    fn ConfGetNode(key: *const c_char) -> *const c_void;
+    fn ConfNodeLookupChild(node: *const c_void, key: *const c_char) -> *const c_void;
     fn ConfGetChildValue(conf: *const c_void, key: *const c_char,
                          vptr: *mut *const c_char) -> i8;
     fn ConfGetChildValueBool(conf: *const c_void, key: *const c_char,
@@ -77,6 +79,54 @@ pub fn conf_get_bool(key: &str) -> bool {
     return false;
 }
 
+// Return the value of key as an unsigned integer. An invalid value is
+// logged and treated as not set.
+pub fn conf_get_u64(key: &str) -> Option<u64> {
+    parse_u64(key, conf_get(key)?)
+}
+
+// Return the value of key as a size in bytes, like "10mb". An invalid
+// value is logged and treated as not set.
+pub fn conf_get_memval(key: &str) -> Option<u64> {
+    parse_memval(key, conf_get(key)?)
+}
+
+// Return the node for key.
+pub fn conf_get_node(key: &str) -> Option<ConfNode> {
+    let s = CString::new(key).unwrap();
+    let node = unsafe { ConfGetNode(s.as_ptr()) };
+    if node.is_null() {
+        return None;
+    }
+    Some(ConfNode::wrap(node))
+}
+
+// Return the configuration node of an app-layer protocol, that is
+// app-layer.protocols.<proto>.
+pub fn conf_get_app_layer_node(proto: &str) -> Option<ConfNode> {
+    conf_get_node(&format!("app-layer.protocols.{}", proto))
+}
+
+fn parse_u64(key: &str, val: &str) -> Option<u64> {
+    match val.trim().parse::<u64>() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            SCLogError!("Invalid value for {}: {}", key, val);
+            None
+        }
+    }
+}
+
+fn parse_memval(key: &str, val: &str) -> Option<u64> {
+    match get_memval(val) {
+        Ok(v) => Some(v),
+        Err(_) => {
+            SCLogError!("Invalid size for {}: {}", key, val);
+            None
+        }
+    }
+}
+
 /// Wrap a Suricata ConfNode and expose some of its methods with a
 /// Rust friendly interface.
 pub struct ConfNode {
@@ -132,6 +182,48 @@ impl ConfNode {
         return false;
     }
 
+    /// Return the child node for key.
+    pub fn get_child_node(&self, key: &str) -> Option<ConfNode> {
+        let s = CString::new(key).unwrap();
+        let node = unsafe { ConfNodeLookupChild(self.conf, s.as_ptr()) };
+        if node.is_null() {
+            return None;
+        }
+        Some(ConfNode::wrap(node))
+    }
+
+    /// Return the value of key as an unsigned integer. An invalid value
+    /// is logged and treated as not set.
+    pub fn get_child_u64(&self, key: &str) -> Option<u64> {
+        parse_u64(key, self.get_child_value(key)?)
+    }
+
+    /// Return the value of key as a size in bytes, like "10mb". An
+    /// invalid value is logged and treated as not set.
+    pub fn get_child_memval(&self, key: &str) -> Option<u64> {
+        parse_memval(key, self.get_child_value(key)?)
+    }
+
+    /// Return the values of the sequence key. A scalar value is
+    /// returned as a list of one element.
+    pub fn get_child_string_list(&self, key: &str) -> Vec<String> {
+        let mut list = Vec::new();
+        if let Some(node) = self.get_child_node(key) {
+            // sequence items are children named after their index
+            let mut i = 0;
+            while let Some(val) = node.get_child_value(&i.to_string()) {
+                list.push(val.to_string());
+                i += 1;
+            }
+            if list.is_empty() {
+                if let Some(val) = self.get_child_value(key) {
+                    list.push(val.to_string());
+                }
+            }
+        }
+        list
+    }
+
 }
 
 const BYTE: u64       = 1;
@@ -19,6 +19,7 @@
 
 use std;
 use crate::filecontainer::*;
+use crate::applayer::FfiType;
 
 /// Opaque C types.
 pub enum DetectEngineState {}
@@ -39,6 +40,65 @@ pub const STREAM_GAP:      u8 = 0x10;
 pub const STREAM_DEPTH:    u8 = 0x20;
 pub const STREAM_MIDSTREAM:u8 = 0x40;
 
+/// Direction of the data in a flow.
+///
+/// The C side passes the direction as part of the STREAM_* flags, so the
+/// FFI functions keep taking a `u8` and convert it with `Direction::from`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    ToServer,
+    ToClient,
+}
+
+impl Direction {
+    pub fn is_to_server(self) -> bool {
+        self == Direction::ToServer
+    }
+
+    pub fn is_to_client(self) -> bool {
+        self == Direction::ToClient
+    }
+
+    /// Return the opposite direction.
+    pub fn reverse(self) -> Direction {
+        match self {
+            Direction::ToServer => Direction::ToClient,
+            Direction::ToClient => Direction::ToServer,
+        }
+    }
+}
+
+impl Default for Direction {
+    fn default() -> Self {
+        Direction::ToServer
+    }
+}
+
+impl From<u8> for Direction {
+    /// Convert STREAM_* flags to a direction. The flags are expected to
+    /// have exactly one of STREAM_TOSERVER and STREAM_TOCLIENT set.
+    fn from(flags: u8) -> Self {
+        debug_assert!(
+            (flags & (STREAM_TOSERVER | STREAM_TOCLIENT)) == STREAM_TOSERVER
+                || (flags & (STREAM_TOSERVER | STREAM_TOCLIENT)) == STREAM_TOCLIENT
+        );
+        if flags & STREAM_TOCLIENT != 0 {
+            Direction::ToClient
+        } else {
+            Direction::ToServer
+        }
+    }
+}
+
+impl From<Direction> for u8 {
+    fn from(direction: Direction) -> u8 {
+        match direction {
+            Direction::ToServer => STREAM_TOSERVER,
+            Direction::ToClient => STREAM_TOCLIENT,
+        }
+    }
+}
+
 // Application layer protocol identifiers (app-layer-protos.h)
 pub type AppProto = u16;
 
@@ -90,6 +150,8 @@ pub type DetectEngineStateFreeFunc =
 
 pub type AppLayerParserTriggerRawStreamReassemblyFunc =
     extern "C" fn (flow: *const Flow, direction: i32);
+pub type AppLayerRequestProtocolTLSUpgradeFunc =
+    extern "C" fn (flow: *const Flow);
 pub type AppLayerDecoderEventsSetEventRawFunc =
     extern "C" fn (events: *mut *mut AppLayerDecoderEvents,
                    event: u8);
@@ -154,6 +216,7 @@ pub struct SuricataContext {
     AppLayerDecoderEventsSetEventRaw: AppLayerDecoderEventsSetEventRawFunc,
     AppLayerDecoderEventsFreeEvents: AppLayerDecoderEventsFreeEventsFunc,
     pub AppLayerParserTriggerRawStreamReassembly: AppLayerParserTriggerRawStreamReassemblyFunc,
+    pub AppLayerRequestProtocolTLSUpgrade: AppLayerRequestProtocolTLSUpgradeFunc,
 
     pub HttpRangeFreeBlock: SCHttpRangeFreeBlock,
     pub HTPFileCloseHandleRange: SCHTPFileCloseHandleRange,
@@ -215,6 +278,16 @@ pub fn sc_app_layer_parser_trigger_raw_stream_reassembly(flow: *const Flow, dire
     }
 }
 
+/// AppLayerRequestProtocolTLSUpgrade wrapper, for the STARTTLS
+/// commands: the flow is handed over to the TLS parser.
+pub fn sc_app_layer_request_protocol_tls_upgrade(flow: *const Flow) {
+    unsafe {
+        if let Some(c) = SC {
+            (c.AppLayerRequestProtocolTLSUpgrade)(flow);
+        }
+    }
+}
+
 /// AppLayerDecoderEventsSetEventRaw wrapper.
 pub fn sc_app_layer_decoder_events_set_event_raw(
     events: *mut *mut AppLayerDecoderEvents, event: u8)
@@ -222,6 +295,9 @@ pub fn sc_app_layer_decoder_events_set_event_raw(
     unsafe {
         if let Some(c) = SC {
             (c.AppLayerDecoderEventsSetEventRaw)(events, event);
+        } else {
+            #[cfg(test)]
+            crate::testing::set_event_raw(events, event);
         }
     }
 }
@@ -233,6 +309,9 @@ pub fn sc_app_layer_decoder_events_free_events(
     unsafe {
         if let Some(c) = SC {
             (c.AppLayerDecoderEventsFreeEvents)(events);
+        } else {
+            #[cfg(test)]
+            crate::testing::free_events(events);
         }
     }
 }
@@ -240,6 +319,8 @@ pub fn sc_app_layer_decoder_events_free_events(
 /// Opaque flow type (defined in C)
 pub enum Flow {}
 
+unsafe impl FfiType for Flow {}
+
 /// Extern functions operating on Flow.
 extern {
     pub fn FlowGetLastTimeAsParts(flow: &Flow, secs: *mut u64, usecs: *mut u64);
@@ -253,7 +334,7 @@ impl Flow {
 
     /// Return the time of the last flow update as a `Duration`
     /// since the epoch.
-    pub fn get_last_time(&mut self) -> std::time::Duration {
+    pub fn get_last_time(&self) -> std::time::Duration {
         unsafe {
             let mut secs: u64 = 0;
             let mut usecs: u64 = 0;
@@ -182,10 +182,22 @@ pub struct DCERPCTransaction {
     pub resp_cmd: u8,
     pub activityuuid: Vec<u8>,
     pub seqnum: u32,
+    /// interface of the request, from the BINDACK for connection
+    /// oriented DCERPC or from the PDU header for connectionless
+    pub if_uuid: Vec<u8>,
+    pub if_version: u16,
+    pub if_versionminor: u16,
+    /// string form of if_uuid, for the dcerpc.interface buffer
+    pub if_uuid_str: String,
+    /// last fragment number seen, connectionless only
+    pub fragnum_ts: u16,
+    pub fragnum_tc: u16,
     pub tx_data: AppLayerTxData,
-    pub de_state: Option<*mut core::DetectEngineState>,
+    pub de_state: DetectStateHolder,
 }
 
+unsafe impl FfiType for DCERPCTransaction {}
+
 impl DCERPCTransaction {
     pub fn new() -> Self {
         return Self {
@@ -194,21 +206,14 @@ impl DCERPCTransaction {
             req_cmd: DCERPC_TYPE_REQUEST,
             resp_cmd: DCERPC_TYPE_RESPONSE,
             activityuuid: Vec::new(),
+            if_uuid: Vec::new(),
+            if_uuid_str: String::new(),
             tx_data: AppLayerTxData::new(),
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             ..Default::default()
         }
     }
 
-    pub fn free(&mut self) {
-        match self.de_state {
-            Some(state) => {
-                sc_detect_engine_state_free(state);
-            }
-            _ => {}
-        }
-    }
-
     pub fn get_req_ctxid(&self) -> u16 {
         self.ctxid
     }
@@ -224,11 +229,24 @@ impl DCERPCTransaction {
     pub fn get_endianness(&self) -> u8 {
         self.endianness
     }
-}
 
-impl Drop for DCERPCTransaction {
-    fn drop(&mut self) {
-        self.free();
+    /// Get the interface uuid and version of the request, if known.
+    pub fn get_iface(&self) -> Option<(&[u8], u16, u16)> {
+        if self.if_uuid.is_empty() {
+            return None;
+        }
+        Some((&self.if_uuid[..], self.if_version, self.if_versionminor))
+    }
+
+    /// Set the interface of the request.
+    pub fn set_iface(&mut self, uuid: Vec<u8>, version: u16, versionminor: u16) {
+        self.if_uuid_str = match uuid::Uuid::from_slice(&uuid) {
+            Ok(u) => u.to_hyphenated().to_string(),
+            Err(_) => String::new(),
+        };
+        self.if_uuid = uuid;
+        self.if_version = version;
+        self.if_versionminor = versionminor;
     }
 }
 
@@ -334,6 +352,8 @@ pub struct DCERPCState {
     pub flow: Option<*const core::Flow>,
 }
 
+unsafe impl FfiType for DCERPCState {}
+
 impl DCERPCState {
     pub fn new() -> Self {
         return Self {
@@ -505,6 +525,15 @@ impl DCERPCState {
         None
     }
 
+    /// Get the accepted interface for a presentation context id.
+    pub fn get_iface_for_ctxid(&self, ctxid: u16) -> Option<&DCERPCUuidEntry> {
+        match self.bindack {
+            Some(ref bindack) => bindack.accepted_uuid_list.iter()
+                .find(|uuidentry| uuidentry.ctxid == ctxid && uuidentry.result == 0),
+            None => None,
+        }
+    }
+
     /// Find the transaction as per call ID defined in header. If the tx is not
     /// found, create one.
     ///
@@ -885,6 +914,8 @@ impl DCERPCState {
             Ok((leftover_input, request)) => {
                 let call_id = self.get_hdr_call_id().unwrap_or(0);
                 let hdr_type = self.get_hdr_type().unwrap_or(0);
+                let iface = self.get_iface_for_ctxid(request.ctxid)
+                    .map(|u| (u.uuid.to_vec(), u.version, u.versionminor));
                 let mut transaction = self.get_tx_by_call_id(call_id, core::STREAM_TOSERVER);
                 match transaction {
                     Some(ref mut tx) => {
@@ -892,6 +923,9 @@ impl DCERPCState {
                         tx.ctxid = request.ctxid;
                         tx.opnum = request.opnum;
                         tx.first_request_seen = request.first_request_seen;
+                        if let Some((uuid, version, versionminor)) = iface {
+                            tx.set_iface(uuid, version, versionminor);
+                        }
                     }
                     None => {
                         let mut tx = self.create_tx(call_id);
@@ -899,6 +933,9 @@ impl DCERPCState {
                         tx.ctxid = request.ctxid;
                         tx.opnum = request.opnum;
                         tx.first_request_seen = request.first_request_seen;
+                        if let Some((uuid, version, versionminor)) = iface {
+                            tx.set_iface(uuid, version, versionminor);
+                        }
                         self.transactions.push(tx);
                     }
                 }
@@ -1148,7 +1185,7 @@ pub unsafe extern "C" fn rs_dcerpc_parse_request(
     flow: *const core::Flow, state: *mut std::os::raw::c_void, _pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, DCERPCState);
+    let state: &mut DCERPCState = cast_mut(state);
     SCLogDebug!("Handling request: input {:p} input_len {} flags {:x} EOF {}",
             input, input_len, flags, flags & core::STREAM_EOF != 0);
     if flags & core::STREAM_EOF != 0 && input_len == 0 {
@@ -1171,7 +1208,7 @@ pub unsafe extern "C" fn rs_dcerpc_parse_response(
     flow: *const core::Flow, state: *mut std::os::raw::c_void, _pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, DCERPCState);
+    let state: &mut DCERPCState = cast_mut(state);
     if flags & core::STREAM_EOF != 0 && input_len == 0 {
         return AppLayerResult::ok();
     }
@@ -1203,14 +1240,14 @@ pub extern "C" fn rs_dcerpc_state_free(state: *mut std::os::raw::c_void) {
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_dcerpc_state_transaction_free(state: *mut std::os::raw::c_void, tx_id: u64) {
-    let dce_state = cast_pointer!(state, DCERPCState);
+    let dce_state: &mut DCERPCState = cast_mut(state);
     SCLogDebug!("freeing tx {}", tx_id as u64);
     dce_state.free_tx(tx_id);
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_dcerpc_state_trunc(state: *mut std::os::raw::c_void, direction: u8) {
-    let dce_state = cast_pointer!(state, DCERPCState);
+    let dce_state: &mut DCERPCState = cast_mut(state);
     if direction & core::STREAM_TOSERVER != 0 {
         dce_state.ts_ssn_trunc = true;
         for tx in &mut dce_state.transactions {
@@ -1232,31 +1269,13 @@ pub unsafe extern "C" fn rs_dcerpc_state_trunc(state: *mut std::os::raw::c_void,
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_dcerpc_get_tx_detect_state(
-    vtx: *mut std::os::raw::c_void,
-) -> *mut core::DetectEngineState {
-    let dce_tx = cast_pointer!(vtx, DCERPCTransaction);
-    match dce_tx.de_state {
-        Some(ds) => ds,
-        None => std::ptr::null_mut(),
-    }
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_dcerpc_set_tx_detect_state(
-    vtx: *mut std::os::raw::c_void, de_state: &mut core::DetectEngineState,
-) -> std::os::raw::c_int {
-    let dce_tx = cast_pointer!(vtx, DCERPCTransaction);
-    dce_tx.de_state = Some(de_state);
-    0
-}
+export_tx_detect_state!(rs_dcerpc_get_tx_detect_state, rs_dcerpc_set_tx_detect_state, DCERPCTransaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_dcerpc_get_tx(
     vtx: *mut std::os::raw::c_void, tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let dce_state = cast_pointer!(vtx, DCERPCState);
+    let dce_state: &mut DCERPCState = cast_mut(vtx);
     match dce_state.get_tx(tx_id) {
         Some(tx) => tx as *const _ as *mut _,
         None => std::ptr::null_mut(),
@@ -1265,14 +1284,14 @@ pub unsafe extern "C" fn rs_dcerpc_get_tx(
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_dcerpc_get_tx_cnt(vtx: *mut std::os::raw::c_void) -> u64 {
-    let dce_state = cast_pointer!(vtx, DCERPCState);
+    let dce_state: &mut DCERPCState = cast_mut(vtx);
     dce_state.tx_id
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_dcerpc_get_alstate_progress(tx: *mut std::os::raw::c_void, direction: u8
                                                  )-> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, DCERPCTransaction);
+    let tx: &mut DCERPCTransaction = cast_mut(tx);
     if direction == core::STREAM_TOSERVER && tx.req_done {
         SCLogDebug!("tx {} TOSERVER progress 1 => {:?}", tx.call_id, tx);
         return 1;
@@ -1289,7 +1308,7 @@ pub unsafe extern "C" fn rs_dcerpc_get_tx_data(
     tx: *mut std::os::raw::c_void)
     -> *mut AppLayerTxData
 {
-    let tx = cast_pointer!(tx, DCERPCTransaction);
+    let tx: &mut DCERPCTransaction = cast_mut(tx);
     return &mut tx.tx_data;
 }
 
@@ -1407,6 +1426,7 @@ pub unsafe extern "C" fn rs_dcerpc_register_parser() {
         apply_tx_config: None,
         flags: APP_LAYER_PARSER_OPT_ACCEPT_GAPS,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
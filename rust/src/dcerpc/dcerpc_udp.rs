@@ -18,7 +18,10 @@
 use crate::applayer::*;
 use crate::core;
 use crate::dcerpc::dcerpc::{
-    DCERPCTransaction, DCERPC_TYPE_REQUEST, DCERPC_TYPE_RESPONSE, PFCL1_FRAG, PFCL1_LASTFRAG,
+    DCERPCTransaction, DCERPC_TYPE_REQUEST, DCERPC_TYPE_RESPONSE, DCERPC_TYPE_FAULT,
+    DCERPC_TYPE_REJECT, DCERPC_TYPE_PING, DCERPC_TYPE_WORKING, DCERPC_TYPE_NOCALL,
+    DCERPC_TYPE_ACK, DCERPC_TYPE_CL_CANCEL, DCERPC_TYPE_FACK, DCERPC_TYPE_CANCEL_ACK,
+    PFCL1_FRAG, PFCL1_LASTFRAG,
     rs_dcerpc_get_alstate_progress, ALPROTO_DCERPC, PARSER_NAME,
 };
 use std;
@@ -57,6 +60,8 @@ pub struct DCERPCUDPState {
     pub transactions: Vec<DCERPCTransaction>,
 }
 
+unsafe impl FfiType for DCERPCUDPState {}
+
 impl DCERPCUDPState {
     pub fn new() -> Self {
         Default::default()
@@ -68,6 +73,10 @@ impl DCERPCUDPState {
         tx.endianness = hdr.drep[0] & 0x10;
         tx.activityuuid = hdr.activityuuid.to_vec();
         tx.seqnum = hdr.seqnum;
+        tx.opnum = hdr.opnum;
+        // the major version is in the low 16 bits of if_vers
+        tx.set_iface(hdr.interfaceuuid.to_vec(), (hdr.if_vers & 0xffff) as u16,
+                     (hdr.if_vers >> 16) as u16);
         self.tx_id += 1;
         tx
     }
@@ -117,7 +126,7 @@ impl DCERPCUDPState {
         for tx in &mut self.transactions {
             if tx.seqnum == hdr.seqnum && tx.activityuuid == hdr.activityuuid {
                 if (hdr.pkt_type == DCERPC_TYPE_REQUEST && !tx.req_done) ||
-                   (hdr.pkt_type == DCERPC_TYPE_RESPONSE && !tx.resp_done) {
+                   (is_response_type(hdr.pkt_type) && !tx.resp_done) {
                     SCLogDebug!("found tx id {}, last tx_id {}, {} {}", tx.id, self.tx_id, tx.seqnum, tx.activityuuid[0]);
                     return Some(tx);
                 }
@@ -127,9 +136,10 @@ impl DCERPCUDPState {
     }
 
     pub fn handle_fragment_data(&mut self, hdr: &DCERPCHdrUdp, input: &[u8]) -> bool {
-        if hdr.pkt_type != DCERPC_TYPE_REQUEST && hdr.pkt_type != DCERPC_TYPE_RESPONSE {
-            SCLogDebug!("Unrecognized packet type");
-            return false;
+        if hdr.pkt_type != DCERPC_TYPE_REQUEST && !is_response_type(hdr.pkt_type) {
+            // PING, WORKING, NOCALL, ACK, FACK and cancels carry no stub data
+            SCLogDebug!("Ignoring packet type {}", hdr.pkt_type);
+            return is_control_type(hdr.pkt_type);
         }
 
         let mut otx = self.find_incomplete_tx(hdr);
@@ -143,27 +153,39 @@ impl DCERPCUDPState {
         if let Some(tx) = otx {
             let done = (hdr.flags1 & PFCL1_FRAG) == 0 || (hdr.flags1 & PFCL1_LASTFRAG) != 0;
 
-            match hdr.pkt_type {
-                DCERPC_TYPE_REQUEST => {
-                    tx.stub_data_buffer_ts.extend_from_slice(input);
-                    tx.frag_cnt_ts += 1;
-                    if done {
-                        tx.req_done = true;
-                    }
+            if hdr.pkt_type == DCERPC_TYPE_REQUEST {
+                // a retransmitted fragment is only accounted once
+                if tx.frag_cnt_ts > 0 && hdr.fragnum <= tx.fragnum_ts {
+                    SCLogDebug!("duplicate request fragment {}", hdr.fragnum);
                     return true;
                 }
-                DCERPC_TYPE_RESPONSE => {
-                    tx.stub_data_buffer_tc.extend_from_slice(input);
-                    tx.frag_cnt_tc += 1;
-                    if done {
-                        tx.resp_done = true;
-                    }
+                if hdr.fragnum == 0 {
+                    tx.first_request_seen = 1;
+                }
+                tx.req_cmd = hdr.pkt_type;
+                tx.fragnum_ts = hdr.fragnum;
+                tx.stub_data_buffer_ts.extend_from_slice(input);
+                tx.frag_cnt_ts += 1;
+                if done {
+                    tx.req_done = true;
+                }
+            } else {
+                if tx.frag_cnt_tc > 0 && hdr.fragnum <= tx.fragnum_tc {
+                    SCLogDebug!("duplicate response fragment {}", hdr.fragnum);
                     return true;
                 }
-                _ => {
-                    // unreachable
+                tx.resp_cmd = hdr.pkt_type;
+                tx.fragnum_tc = hdr.fragnum;
+                if hdr.pkt_type == DCERPC_TYPE_RESPONSE {
+                    tx.stub_data_buffer_tc.extend_from_slice(input);
+                }
+                tx.frag_cnt_tc += 1;
+                // FAULT and REJECT end the call
+                if done || hdr.pkt_type != DCERPC_TYPE_RESPONSE {
+                    tx.resp_done = true;
                 }
             }
+            return true;
         }
         return false; // unreachable
     }
@@ -209,7 +231,7 @@ pub unsafe extern "C" fn rs_dcerpc_udp_parse(
     _flow: *const core::Flow, state: *mut std::os::raw::c_void, _pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, DCERPCUDPState);
+    let state: &mut DCERPCUDPState = cast_mut(state);
     if input_len > 0 && input != std::ptr::null_mut() {
         let buf = build_slice!(input, input_len as usize);
         return state.handle_input_data(buf);
@@ -233,37 +255,19 @@ pub extern "C" fn rs_dcerpc_udp_state_new(_orig_state: *mut std::os::raw::c_void
 pub unsafe extern "C" fn rs_dcerpc_udp_state_transaction_free(
     state: *mut std::os::raw::c_void, tx_id: u64,
 ) {
-    let dce_state = cast_pointer!(state, DCERPCUDPState);
+    let dce_state: &mut DCERPCUDPState = cast_mut(state);
     SCLogDebug!("freeing tx {}", tx_id as u64);
     dce_state.free_tx(tx_id);
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_dcerpc_udp_get_tx_detect_state(
-    vtx: *mut std::os::raw::c_void,
-) -> *mut core::DetectEngineState {
-    let dce_state = cast_pointer!(vtx, DCERPCTransaction);
-    match dce_state.de_state {
-        Some(ds) => ds,
-        None => std::ptr::null_mut(),
-    }
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_dcerpc_udp_set_tx_detect_state(
-    vtx: *mut std::os::raw::c_void, de_state: &mut core::DetectEngineState,
-) -> std::os::raw::c_int {
-    let dce_state = cast_pointer!(vtx, DCERPCTransaction);
-    dce_state.de_state = Some(de_state);
-    0
-}
+export_tx_detect_state!(rs_dcerpc_udp_get_tx_detect_state, rs_dcerpc_udp_set_tx_detect_state, DCERPCTransaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_dcerpc_udp_get_tx_data(
     tx: *mut std::os::raw::c_void)
     -> *mut AppLayerTxData
 {
-    let tx = cast_pointer!(tx, DCERPCTransaction);
+    let tx: &mut DCERPCTransaction = cast_mut(tx);
     return &mut tx.tx_data;
 }
 
@@ -271,7 +275,7 @@ pub unsafe extern "C" fn rs_dcerpc_udp_get_tx_data(
 pub unsafe extern "C" fn rs_dcerpc_udp_get_tx(
     state: *mut std::os::raw::c_void, tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let dce_state = cast_pointer!(state, DCERPCUDPState);
+    let dce_state: &mut DCERPCUDPState = cast_mut(state);
     match dce_state.get_tx(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -284,10 +288,24 @@ pub unsafe extern "C" fn rs_dcerpc_udp_get_tx(
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_dcerpc_udp_get_tx_cnt(vtx: *mut std::os::raw::c_void) -> u64 {
-    let dce_state = cast_pointer!(vtx, DCERPCUDPState);
+    let dce_state: &mut DCERPCUDPState = cast_mut(vtx);
     dce_state.tx_id
 }
 
+fn is_response_type(pkt_type: u8) -> bool {
+    pkt_type == DCERPC_TYPE_RESPONSE || pkt_type == DCERPC_TYPE_FAULT ||
+        pkt_type == DCERPC_TYPE_REJECT
+}
+
+/// Connectionless PDU types that are part of a call but carry no data.
+fn is_control_type(pkt_type: u8) -> bool {
+    match pkt_type {
+        DCERPC_TYPE_PING | DCERPC_TYPE_WORKING | DCERPC_TYPE_NOCALL | DCERPC_TYPE_ACK |
+        DCERPC_TYPE_CL_CANCEL | DCERPC_TYPE_FACK | DCERPC_TYPE_CANCEL_ACK => true,
+        _ => false,
+    }
+}
+
 /// Probe input to see if it looks like DCERPC.
 fn probe(input: &[u8]) -> (bool, bool) {
     match parser::parse_dcerpc_udp_header(input) {
@@ -372,6 +390,7 @@ pub unsafe extern "C" fn rs_dcerpc_udp_register_parser() {
         apply_tx_config: None,
         flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("udp").unwrap();
@@ -381,6 +400,15 @@ pub unsafe extern "C" fn rs_dcerpc_udp_register_parser() {
         if register_pattern_probe() < 0 {
             return;
         }
+        // also probe the endpoint mapper port, the pattern is short
+        let default_port = CString::new("135").unwrap();
+        if AppLayerProtoDetectPPParseConfPorts(ip_proto_str.as_ptr(), core::IPPROTO_UDP as u8,
+                parser.name, ALPROTO_DCERPC, 0, DCERPC_UDP_HDR_LEN as u16,
+                rs_dcerpc_probe_udp, rs_dcerpc_probe_udp) == 0 {
+            AppLayerProtoDetectPPRegister(core::IPPROTO_UDP as u8, default_port.as_ptr(),
+                ALPROTO_DCERPC, 0, DCERPC_UDP_HDR_LEN as u16, core::STREAM_TOSERVER,
+                rs_dcerpc_probe_udp, rs_dcerpc_probe_udp);
+        }
         if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
             let _ = AppLayerRegisterParser(&parser, alproto);
         }
@@ -393,6 +421,7 @@ pub unsafe extern "C" fn rs_dcerpc_udp_register_parser() {
 #[cfg(test)]
 mod tests {
     use crate::applayer::AppLayerResult;
+    use crate::dcerpc::dcerpc::DCERPC_TYPE_PING;
     use crate::dcerpc::dcerpc_udp::DCERPCUDPState;
     use crate::dcerpc::parser;
 
@@ -571,4 +600,33 @@ mod tests {
             dcerpcudp_state.transactions[0].stub_data_buffer_ts.len()
         );
     }
+
+    #[test]
+    fn test_handle_input_data_udp_iface() {
+        let mut request: Vec<u8> = vec![
+            0x04, 0x00, 0x08, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xb8, 0x4a, 0x9f, 0x4d,
+            0x1c, 0x7d, 0xcf, 0x11, 0x86, 0x1e, 0x00, 0x20, 0xaf, 0x6e, 0x7c, 0x57, 0x86, 0xc2,
+            0x37, 0x67, 0xf7, 0x1e, 0xd1, 0x11, 0xbc, 0xd9, 0x00, 0x60, 0x97, 0x92, 0xd2, 0x6c,
+            0x79, 0xbe, 0x01, 0x34, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0xff, 0xff, 0xff, 0xff, 0x68, 0x00, 0x00, 0x00, 0x0a, 0x00,
+        ];
+        // header announces a 104 byte body
+        request.extend_from_slice(&[0x00; 104]);
+        let (_, header) = parser::parse_dcerpc_udp_header(&request).unwrap();
+        let mut dcerpcudp_state = DCERPCUDPState::new();
+        assert_eq!(AppLayerResult::ok(), dcerpcudp_state.handle_input_data(&request));
+        assert_eq!(1, dcerpcudp_state.transactions.len());
+        let tx = &dcerpcudp_state.transactions[0];
+        assert_eq!(1, tx.get_first_req_seen());
+        assert_eq!(header.opnum, tx.get_req_opnum());
+        let (uuid, version, _) = tx.get_iface().unwrap();
+        assert_eq!(&header.interfaceuuid[..], uuid);
+        assert_eq!((header.if_vers & 0xffff) as u16, version);
+
+        // a PING for the same activity is accepted without a new tx
+        request[1] = DCERPC_TYPE_PING;
+        assert_eq!(AppLayerResult::ok(), dcerpcudp_state.handle_input_data(&request));
+        assert_eq!(1, dcerpcudp_state.transactions.len());
+    }
 }
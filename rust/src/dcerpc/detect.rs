@@ -16,11 +16,16 @@
  */
 
 use super::dcerpc::{
-    DCERPCState, DCERPCTransaction, DCERPC_TYPE_REQUEST, DCERPC_TYPE_RESPONSE,
+    DCERPCState, DCERPCTransaction, ALPROTO_DCERPC, DCERPC_TYPE_REQUEST, DCERPC_TYPE_RESPONSE,
     DCERPC_UUID_ENTRY_FLAG_FF,
 };
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
 use std::ffi::CStr;
-use std::os::raw::{c_char, c_void};
+use std::os::raw::{c_char, c_int, c_void};
 use uuid::Uuid;
 
 pub const DETECT_DCE_IFACE_OP_NONE: u8 = 0;
@@ -268,6 +273,27 @@ pub extern "C" fn rs_dcerpc_iface_match(
     return match_backuuid(tx, state, if_data);
 }
 
+/// Connectionless variant: every PDU carries the interface, so match on
+/// what the transaction recorded instead of the bind state.
+#[no_mangle]
+pub extern "C" fn rs_dcerpc_udp_iface_match(
+    tx: &mut DCERPCTransaction, if_data: &mut DCEIfaceData,
+) -> u8 {
+    if tx.get_first_req_seen() == 0 {
+        return 0;
+    }
+    match tx.get_iface() {
+        Some((uuid, version, _)) => {
+            if uuid == &if_data.if_uuid[..] && match_iface_version(version, if_data) {
+                1
+            } else {
+                0
+            }
+        }
+        None => 0,
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn rs_dcerpc_iface_parse(carg: *const c_char) -> *mut c_void {
     if carg.is_null() {
@@ -340,6 +366,51 @@ pub unsafe extern "C" fn rs_dcerpc_opnum_free(ptr: *mut c_void) {
     }
 }
 
+static mut G_DCERPC_INTERFACE_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn dcerpc_tx_get_interface(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut DCERPCTransaction = cast_mut(tx);
+    if tx.first_request_seen == 0 || tx.if_uuid_str.is_empty() {
+        return false;
+    }
+    *buf = tx.if_uuid_str.as_ptr();
+    *len = tx.if_uuid_str.len() as u32;
+    true
+}
+
+unsafe extern "C" fn dcerpc_interface_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, dcerpc_tx_get_interface)
+}
+
+unsafe extern "C" fn dcerpc_interface_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_DCERPC, G_DCERPC_INTERFACE_BUFFER_ID)
+}
+
+/// Register the DCERPC keywords implemented in Rust.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "dcerpc.interface",
+        desc: "sticky buffer to match on the interface UUID of a DCERPC request",
+        url: "/rules/dcerpc-keywords.html#dcerpc-interface",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: dcerpc_interface_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_DCERPC_INTERFACE_BUFFER_ID = helper_buffer_mpm_register(
+            "dcerpc.interface", "dcerpc interface uuid", ALPROTO_DCERPC, false, true, 1,
+            dcerpc_interface_get_data);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -517,4 +588,15 @@ mod test {
         let opnum_data = parse_opnum_data(arg);
         assert_eq!(true, opnum_data.is_err());
     }
+
+    #[test]
+    fn test_tx_interface_string() {
+        let mut iface_data = parse_iface_data("367abb81-9844-35f1-ad32-98f038001003,=2").unwrap();
+        let mut tx = DCERPCTransaction::new();
+        tx.set_iface(iface_data.if_uuid.clone(), 2, 0);
+        assert_eq!("367abb81-9844-35f1-ad32-98f038001003", tx.if_uuid_str);
+
+        tx.first_request_seen = 1;
+        assert_eq!(1, rs_dcerpc_udp_iface_match(&mut tx, &mut iface_data));
+    }
 }
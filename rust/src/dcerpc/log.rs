@@ -20,6 +20,17 @@ use crate::dcerpc::dcerpc::*;
 use crate::dcerpc::dcerpc_udp::*;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 
+fn log_dcerpc_iface(jsb: &mut JsonBuilder, tx: &DCERPCTransaction) -> Result<(), JsonError> {
+    if !tx.if_uuid_str.is_empty() {
+        jsb.open_object("interface")?;
+        jsb.set_string("uuid", &tx.if_uuid_str)?;
+        let vstr = format!("{}.{}", tx.if_version, tx.if_versionminor);
+        jsb.set_string("version", &vstr)?;
+        jsb.close()?;
+    }
+    Ok(())
+}
+
 fn log_dcerpc_header_tcp(
     jsb: &mut JsonBuilder, state: &DCERPCState, tx: &DCERPCTransaction,
 ) -> Result<(), JsonError> {
@@ -31,6 +42,7 @@ fn log_dcerpc_header_tcp(
                 jsb.set_uint("opnum", tx.opnum as u64)?;
                 jsb.set_uint("frag_cnt", tx.frag_cnt_ts as u64)?;
                 jsb.set_uint("stub_data_size", tx.stub_data_buffer_ts.len() as u64)?;
+                log_dcerpc_iface(jsb, tx)?;
                 jsb.close()?;
             }
             DCERPC_TYPE_BIND => match &state.bind {
@@ -91,6 +103,7 @@ fn log_dcerpc_header_udp(
                 jsb.set_uint("opnum", tx.opnum as u64)?;
                 jsb.set_uint("frag_cnt", tx.frag_cnt_ts as u64)?;
                 jsb.set_uint("stub_data_size", tx.stub_data_buffer_ts.len() as u64)?;
+                log_dcerpc_iface(jsb, tx)?;
                 jsb.close()?;
             }
             _ => {}
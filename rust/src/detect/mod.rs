@@ -0,0 +1,275 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Helpers to implement detection keywords in Rust.
+//!
+//! Keywords are registered from `rs_detect_register_keywords`, which is
+//! called during the keyword setup, after the app-layer parsers have
+//! been registered. Two kinds of keywords are supported:
+//!
+//! - sticky buffers: registered with `helper_buffer_mpm_register`, with a
+//!   `GetData` callback using `helper_get_data` to fill the inspection
+//!   buffer from the transaction, and a setup callback using
+//!   `helper_setup_sticky_buffer`.
+//! - multi buffers, sticky buffers for which a transaction can have
+//!   several buffers: registered with `helper_multi_buffer_mpm_register`,
+//!   with a `DetectGetMultiBufFn` callback returning the buffers by index.
+//! - keywords matching on the transaction, like integer keywords:
+//!   registered with `helper_buffer_register`, with a setup callback
+//!   parsing the rule option and passing the context to
+//!   `helper_setup_tx_match`, and an `AppLayerTxMatch` callback.
+//!
+//! The names and descriptions are referenced by the engine for its whole
+//! lifetime, so they are passed as `static_cstr` strings.
+
+pub mod uint;
+
+use std::os::raw::{c_char, c_int, c_void};
+
+use crate::common::static_cstr;
+use crate::core::AppProto;
+
+pub const SIGMATCH_NOOPT: u16 = 1; // BIT_U16(0) in detect.h
+pub const SIGMATCH_QUOTES_OPTIONAL: u16 = 0x20; // BIT_U16(5) in detect.h
+pub const SIGMATCH_INFO_STICKY_BUFFER: u16 = 0x200; // BIT_U16(9) in detect.h
+
+/// Keyword setup callback: (de_ctx, signature, option) -> 0 or -1.
+pub type DetectSetupFn =
+    unsafe extern "C" fn(de: *mut c_void, s: *mut c_void, raw: *const c_char) -> c_int;
+/// Keyword context free callback: (de_ctx, ctx).
+pub type DetectFreeFn = unsafe extern "C" fn(de: *mut c_void, ctx: *mut c_void);
+/// Transaction match callback:
+/// (det_ctx, flow, flags, state, tx, signature, ctx) -> 1 on match.
+pub type DetectTxMatchFn = unsafe extern "C" fn(
+    det_ctx: *mut c_void, f: *mut c_void, flags: u8, state: *mut c_void, tx: *mut c_void,
+    s: *const c_void, ctx: *const c_void,
+) -> c_int;
+/// Inspection buffer callback, the InspectionBufferGetDataPtr of the
+/// engine: (det_ctx, transforms, flow, flow_flags, tx, list_id) -> buffer.
+pub type DetectGetDataFn = unsafe extern "C" fn(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void;
+/// Callback returning the buffer of a transaction for a direction.
+pub type DetectGetBufFn =
+    unsafe extern "C" fn(tx: *mut c_void, flow_flags: u8, buf: *mut *const u8, len: *mut u32) -> bool;
+/// Callback returning the buffer number `local_id` of a transaction for a
+/// direction, or false if there is no such buffer.
+pub type DetectGetMultiBufFn = unsafe extern "C" fn(
+    tx: *mut c_void, flow_flags: u8, local_id: u32, buf: *mut *const u8, len: *mut u32,
+) -> bool;
+
+/// Keyword table entry, copied into the sigmatch_table by
+/// DetectHelperKeywordRegister.
+#[repr(C)]
+#[allow(non_snake_case)]
+pub struct SCSigTableElmt {
+    pub name: *const c_char,
+    pub desc: *const c_char,
+    pub url: *const c_char,
+    pub flags: u16,
+    pub Setup: unsafe extern "C" fn(de: *mut c_void, s: *mut c_void, raw: *const c_char) -> c_int,
+    pub Free: Option<unsafe extern "C" fn(de: *mut c_void, ctx: *mut c_void)>,
+    pub AppLayerTxMatch: Option<
+        unsafe extern "C" fn(
+            det_ctx: *mut c_void, f: *mut c_void, flags: u8, state: *mut c_void,
+            tx: *mut c_void, s: *const c_void, ctx: *const c_void,
+        ) -> c_int,
+    >,
+}
+
+/// Description of a keyword implemented in Rust.
+pub struct DetectKeyword {
+    pub name: &'static str,
+    pub desc: &'static str,
+    pub url: &'static str,
+    pub flags: u16,
+    pub setup: DetectSetupFn,
+    pub free: Option<DetectFreeFn>,
+    pub tx_match: Option<DetectTxMatchFn>,
+}
+
+extern {
+    fn DetectHelperKeywordRegister(kw: *const SCSigTableElmt) -> c_int;
+    fn DetectHelperBufferRegister(
+        name: *const c_char, alproto: AppProto, toclient: bool, toserver: bool, progress: c_int,
+    ) -> c_int;
+    fn DetectHelperBufferMpmRegister(
+        name: *const c_char, desc: *const c_char, alproto: AppProto, toclient: bool,
+        toserver: bool, progress: c_int, get_data: DetectGetDataFn,
+    ) -> c_int;
+    fn DetectHelperMultiBufferMpmRegister(
+        name: *const c_char, desc: *const c_char, alproto: AppProto, toclient: bool,
+        toserver: bool, progress: c_int, get_buf: DetectGetMultiBufFn,
+    ) -> c_int;
+    fn DetectHelperGetData(
+        det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+        tx: *mut c_void, list_id: c_int, get_buf: DetectGetBufFn,
+    ) -> *mut c_void;
+    fn DetectHelperSigMatchAppend(
+        s: *mut c_void, kw_id: u16, ctx: *mut c_void, list_id: c_int,
+    ) -> c_int;
+    fn DetectBufferSetActiveList(s: *mut c_void, list_id: c_int) -> c_int;
+    fn DetectSignatureSetAppProto(s: *mut c_void, alproto: AppProto) -> c_int;
+}
+
+/// Register a keyword. Returns the keyword id, or None if it could not be
+/// registered. Running out of slots in the sigmatch_table is a fatal
+/// error of the engine, see DETECT_TBLSIZE_DYNAMIC.
+pub fn helper_keyword_register(kw: &DetectKeyword) -> Option<u16> {
+    let elmt = SCSigTableElmt {
+        name: static_cstr(kw.name),
+        desc: static_cstr(kw.desc),
+        url: static_cstr(kw.url),
+        flags: kw.flags,
+        Setup: kw.setup,
+        Free: kw.free,
+        AppLayerTxMatch: kw.tx_match,
+    };
+    let id = unsafe { DetectHelperKeywordRegister(&elmt) };
+    if id < 0 {
+        return None;
+    }
+    Some(id as u16)
+}
+
+/// Register the list for a keyword matching on transactions of `alproto`
+/// through its `tx_match` callback, once the transaction reached
+/// `progress`. Returns the list id.
+pub fn helper_buffer_register(
+    name: &str, alproto: AppProto, toclient: bool, toserver: bool, progress: i32,
+) -> c_int {
+    unsafe { DetectHelperBufferRegister(static_cstr(name), alproto, toclient, toserver, progress) }
+}
+
+/// Register a sticky buffer for transactions of `alproto` with inspection
+/// and mpm engines, using `get_data` to get the buffer once the
+/// transaction reached `progress`. Returns the list id.
+pub fn helper_buffer_mpm_register(
+    name: &str, desc: &str, alproto: AppProto, toclient: bool, toserver: bool, progress: i32,
+    get_data: DetectGetDataFn,
+) -> c_int {
+    unsafe {
+        DetectHelperBufferMpmRegister(
+            static_cstr(name),
+            static_cstr(desc),
+            alproto,
+            toclient,
+            toserver,
+            progress,
+            get_data,
+        )
+    }
+}
+
+/// Register a multi buffer for transactions of `alproto` with inspection
+/// and mpm engines, using `get_buf` to get the buffers once the
+/// transaction reached `progress`. Returns the list id.
+pub fn helper_multi_buffer_mpm_register(
+    name: &str, desc: &str, alproto: AppProto, toclient: bool, toserver: bool, progress: i32,
+    get_buf: DetectGetMultiBufFn,
+) -> c_int {
+    unsafe {
+        DetectHelperMultiBufferMpmRegister(
+            static_cstr(name),
+            static_cstr(desc),
+            alproto,
+            toclient,
+            toserver,
+            progress,
+            get_buf,
+        )
+    }
+}
+
+/// Implementation of a `DetectGetDataFn`, setting up the inspection
+/// buffer from the data returned by `get_buf`.
+pub unsafe fn helper_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int, get_buf: DetectGetBufFn,
+) -> *mut c_void {
+    DetectHelperGetData(det_ctx, transforms, f, flow_flags, tx, list_id, get_buf)
+}
+
+/// Setup of a sticky buffer keyword: set the app-layer protocol of the
+/// signature and make `list_id` the active list.
+pub unsafe fn helper_setup_sticky_buffer(s: *mut c_void, alproto: AppProto, list_id: c_int) -> c_int {
+    if DetectSignatureSetAppProto(s, alproto) != 0 {
+        return -1;
+    }
+    if DetectBufferSetActiveList(s, list_id) < 0 {
+        return -1;
+    }
+    0
+}
+
+/// Setup of a keyword matching on transactions: set the app-layer
+/// protocol of the signature and append a match of keyword `kw_id` with
+/// context `ctx` to `list_id`. The context is freed by
+/// `helper_free_ctx::<T>` which should be the `free` callback of the
+/// keyword.
+pub unsafe fn helper_setup_tx_match<T>(
+    s: *mut c_void, alproto: AppProto, kw_id: u16, list_id: c_int, ctx: T,
+) -> c_int {
+    if DetectSignatureSetAppProto(s, alproto) != 0 {
+        return -1;
+    }
+    let ctx = Box::into_raw(Box::new(ctx)) as *mut c_void;
+    if DetectHelperSigMatchAppend(s, kw_id, ctx, list_id) < 0 {
+        std::mem::drop(Box::from_raw(ctx as *mut T));
+        return -1;
+    }
+    0
+}
+
+/// Free a context set up by `helper_setup_tx_match`.
+pub unsafe fn helper_free_ctx<T>(ctx: *mut c_void) {
+    if !ctx.is_null() {
+        std::mem::drop(Box::from_raw(ctx as *mut T));
+    }
+}
+
+/// Register the keywords implemented in Rust. Called from SigTableSetup.
+#[no_mangle]
+pub unsafe extern "C" fn rs_detect_register_keywords() {
+    // app-layer modules register their keywords here
+    crate::applayertemplate::detect::register_keywords();
+    crate::bittorrent::detect::register_keywords();
+    crate::dcerpc::detect::register_keywords();
+    crate::dns::detect::register_keywords();
+    crate::dtls::detect::register_keywords();
+    crate::ftp::detect::register_keywords();
+    crate::icap::detect::register_keywords();
+    crate::irc::detect::register_keywords();
+    crate::kafka::detect::register_keywords();
+    crate::ldap::detect::register_keywords();
+    crate::llmnr::detect::register_keywords();
+    crate::modbus::detect::register_keywords();
+    crate::mqtt::detect::register_keywords();
+    crate::nbns::detect::register_keywords();
+    crate::nfs::detect::register_keywords();
+    crate::quic::detect::register_keywords();
+    crate::rdp::detect::register_keywords();
+    crate::rfb::detect::register_keywords();
+    crate::sip::detect::register_keywords();
+    crate::smb::detect::register_keywords();
+    crate::snmp::detect::register_keywords();
+    crate::ssh::detect::register_keywords();
+    crate::stratum::detect::register_keywords();
+    crate::telnet::detect::register_keywords();
+    crate::xmpp::detect::register_keywords();
+}
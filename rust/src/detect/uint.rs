@@ -0,0 +1,127 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Integer keyword options, with the same syntax as the C
+//! detect-engine-uint: `N`, `=N`, `<N`, `<=N`, `>N`, `>=N` and `N-M`,
+//! the range being exclusive.
+
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DetectUintMode {
+    Equal,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Range,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DetectUintData<T> {
+    pub arg1: T,
+    pub arg2: T,
+    pub mode: DetectUintMode,
+}
+
+fn parse_value<T: FromStr>(s: &str) -> Option<T> {
+    let s = s.trim();
+    if s.is_empty() || !s.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse::<T>().ok()
+}
+
+/// Parse an integer keyword option.
+pub fn detect_parse_uint<T>(s: &str) -> Option<DetectUintData<T>>
+    where T: FromStr + PartialOrd + Copy + Default
+{
+    let s = s.trim();
+    let (mode, value) = if s.starts_with("<=") {
+        (DetectUintMode::Lte, &s[2..])
+    } else if s.starts_with(">=") {
+        (DetectUintMode::Gte, &s[2..])
+    } else if s.starts_with('<') {
+        (DetectUintMode::Lt, &s[1..])
+    } else if s.starts_with('>') {
+        (DetectUintMode::Gt, &s[1..])
+    } else if s.starts_with('=') {
+        (DetectUintMode::Equal, &s[1..])
+    } else if let Some(idx) = s.find('-') {
+        let arg1 = parse_value(&s[..idx])?;
+        let arg2 = parse_value(&s[idx + 1..])?;
+        if arg1 >= arg2 {
+            SCLogError!("Invalid range: {}", s);
+            return None;
+        }
+        return Some(DetectUintData { arg1, arg2, mode: DetectUintMode::Range });
+    } else {
+        (DetectUintMode::Equal, s)
+    };
+    let arg1 = parse_value(value)?;
+    Some(DetectUintData { arg1, arg2: T::default(), mode })
+}
+
+/// Match a value against a parsed integer keyword option.
+pub fn detect_match_uint<T: PartialOrd + Copy>(ctx: &DetectUintData<T>, val: T) -> bool {
+    match ctx.mode {
+        DetectUintMode::Equal => val == ctx.arg1,
+        DetectUintMode::Lt => val < ctx.arg1,
+        DetectUintMode::Lte => val <= ctx.arg1,
+        DetectUintMode::Gt => val > ctx.arg1,
+        DetectUintMode::Gte => val >= ctx.arg1,
+        DetectUintMode::Range => val > ctx.arg1 && val < ctx.arg2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_parse_uint() {
+        let ctx = detect_parse_uint::<u8>(" 3 ").unwrap();
+        assert_eq!(ctx.mode, DetectUintMode::Equal);
+        assert_eq!(ctx.arg1, 3);
+        let ctx = detect_parse_uint::<u32>("<=10").unwrap();
+        assert_eq!(ctx.mode, DetectUintMode::Lte);
+        let ctx = detect_parse_uint::<u32>("> 10").unwrap();
+        assert_eq!(ctx.mode, DetectUintMode::Gt);
+        let ctx = detect_parse_uint::<u16>("1-10").unwrap();
+        assert_eq!(ctx, DetectUintData { arg1: 1, arg2: 10, mode: DetectUintMode::Range });
+
+        assert!(detect_parse_uint::<u8>("256").is_none());
+        assert!(detect_parse_uint::<u8>("10-1").is_none());
+        assert!(detect_parse_uint::<u8>("-1").is_none());
+        assert!(detect_parse_uint::<u8>("<").is_none());
+        assert!(detect_parse_uint::<u8>("a").is_none());
+    }
+
+    #[test]
+    fn test_detect_match_uint() {
+        let ctx = detect_parse_uint::<u32>("1-10").unwrap();
+        assert!(!detect_match_uint(&ctx, 1));
+        assert!(detect_match_uint(&ctx, 5));
+        assert!(!detect_match_uint(&ctx, 10));
+        let ctx = detect_parse_uint::<u32>(">=10").unwrap();
+        assert!(detect_match_uint(&ctx, 10));
+        assert!(!detect_match_uint(&ctx, 9));
+        let ctx = detect_parse_uint::<u32>("7").unwrap();
+        assert!(detect_match_uint(&ctx, 7));
+        assert!(!detect_match_uint(&ctx, 8));
+    }
+}
@@ -18,7 +18,7 @@
 use crate::applayer::{self, *};
 use crate::core;
 use crate::core::{ALPROTO_UNKNOWN, AppProto, Flow, IPPROTO_UDP};
-use crate::core::{sc_detect_engine_state_free, sc_app_layer_decoder_events_free_events};
+use crate::core::sc_app_layer_decoder_events_free_events;
 use crate::dhcp::parser::*;
 use std;
 use std::ffi::CString;
@@ -27,6 +27,20 @@ static mut ALPROTO_DHCP: AppProto = ALPROTO_UNKNOWN;
 
 static DHCP_MIN_FRAME_LEN: u32 = 232;
 
+declare_counters!(dhcp_stats, "app_layer.dhcp", {
+    discover,
+    offer,
+    request,
+    decline,
+    ack,
+    nak,
+    release,
+    inform,
+    unknown,
+    malformed,
+    anomalies,
+});
+
 pub const BOOTP_REQUEST: u8 = 1;
 pub const BOOTP_REPLY: u8 = 2;
 
@@ -79,17 +93,19 @@ pub enum DHCPEvent {
 pub struct DHCPTransaction {
     tx_id: u64,
     pub message: DHCPMessage,
-    de_state: Option<*mut core::DetectEngineState>,
+    de_state: DetectStateHolder,
     events: *mut core::AppLayerDecoderEvents,
     tx_data: applayer::AppLayerTxData,
 }
 
+unsafe impl FfiType for DHCPTransaction {}
+
 impl DHCPTransaction {
     pub fn new(id: u64, message: DHCPMessage) -> DHCPTransaction {
         DHCPTransaction {
             tx_id: id,
             message: message,
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: applayer::AppLayerTxData::new(),
         }
@@ -99,12 +115,6 @@ impl DHCPTransaction {
         if self.events != std::ptr::null_mut() {
             sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        match self.de_state {
-            Some(state) => {
-                sc_detect_engine_state_free(state);
-            }
-            _ => {}
-        }
     }
 
 }
@@ -115,8 +125,7 @@ impl Drop for DHCPTransaction {
     }
 }
 
-export_tx_get_detect_state!(rs_dhcp_tx_get_detect_state, DHCPTransaction);
-export_tx_set_detect_state!(rs_dhcp_tx_set_detect_state, DHCPTransaction);
+export_tx_detect_state!(rs_dhcp_tx_get_detect_state, rs_dhcp_tx_set_detect_state, DHCPTransaction);
 
 #[derive(Default)]
 pub struct DHCPState {
@@ -129,6 +138,8 @@ pub struct DHCPState {
     events: u16,
 }
 
+unsafe impl FfiType for DHCPState {}
+
 impl DHCPState {
     pub fn new() -> Self {
         Default::default()
@@ -137,6 +148,7 @@ impl DHCPState {
     pub fn parse(&mut self, input: &[u8]) -> bool {
         match dhcp_parse(input) {
             Ok((_, message)) => {
+                DHCPState::count_msg(&message);
                 let malformed_options = message.malformed_options;
                 let truncated_options = message.truncated_options;
                 self.tx_id += 1;
@@ -151,11 +163,35 @@ impl DHCPState {
                 return true;
             }
             _ => {
+                stats_incr!(dhcp_stats::malformed);
                 return false;
             }
         }
     }
 
+    fn count_msg(message: &DHCPMessage) {
+        let mut msg_type = None;
+        for option in &message.options {
+            if let DHCPOptionWrapper::Generic(ref generic) = option.option {
+                if option.code == DHCP_OPT_TYPE && generic.data.len() > 0 {
+                    msg_type = Some(generic.data[0]);
+                    break;
+                }
+            }
+        }
+        match msg_type {
+            Some(DHCP_TYPE_DISCOVER) => stats_incr!(dhcp_stats::discover),
+            Some(DHCP_TYPE_OFFER) => stats_incr!(dhcp_stats::offer),
+            Some(DHCP_TYPE_REQUEST) => stats_incr!(dhcp_stats::request),
+            Some(DHCP_TYPE_DECLINE) => stats_incr!(dhcp_stats::decline),
+            Some(DHCP_TYPE_ACK) => stats_incr!(dhcp_stats::ack),
+            Some(DHCP_TYPE_NAK) => stats_incr!(dhcp_stats::nak),
+            Some(DHCP_TYPE_RELEASE) => stats_incr!(dhcp_stats::release),
+            Some(DHCP_TYPE_INFORM) => stats_incr!(dhcp_stats::inform),
+            _ => stats_incr!(dhcp_stats::unknown),
+        }
+    }
+
     pub fn get_tx(&mut self, tx_id: u64) -> Option<&DHCPTransaction> {
         for tx in &mut self.transactions {
             if tx.tx_id == tx_id + 1 {
@@ -184,6 +220,7 @@ impl DHCPState {
 
     fn set_event(&mut self, event: DHCPEvent) {
         if let Some(tx) = self.transactions.last_mut() {
+            stats_incr!(dhcp_stats::anomalies);
             core::sc_app_layer_decoder_events_set_event_raw(
                 &mut tx.events, event as u8);
             self.events += 1;
@@ -242,7 +279,7 @@ pub extern "C" fn rs_dhcp_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void
 #[no_mangle]
 pub unsafe extern "C" fn rs_dhcp_state_get_tx(state: *mut std::os::raw::c_void,
                                        tx_id: u64) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, DHCPState);
+    let state: &mut DHCPState = cast_mut(state);
     match state.get_tx(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -255,7 +292,7 @@ pub unsafe extern "C" fn rs_dhcp_state_get_tx(state: *mut std::os::raw::c_void,
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_dhcp_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
-    let state = cast_pointer!(state, DHCPState);
+    let state: &mut DHCPState = cast_mut(state);
     return state.tx_id;
 }
 
@@ -267,7 +304,7 @@ pub unsafe extern "C" fn rs_dhcp_parse(_flow: *const core::Flow,
                                 input_len: u32,
                                 _data: *const std::os::raw::c_void,
                                 _flags: u8) -> AppLayerResult {
-    let state = cast_pointer!(state, DHCPState);
+    let state: &mut DHCPState = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
     if state.parse(buf) {
         return AppLayerResult::ok();
@@ -280,7 +317,7 @@ pub unsafe extern "C" fn rs_dhcp_state_tx_free(
     state: *mut std::os::raw::c_void,
     tx_id: u64)
 {
-    let state = cast_pointer!(state, DHCPState);
+    let state: &mut DHCPState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -300,7 +337,7 @@ pub unsafe extern "C" fn rs_dhcp_state_free(state: *mut std::os::raw::c_void) {
 pub unsafe extern "C" fn rs_dhcp_state_get_events(tx: *mut std::os::raw::c_void)
                                            -> *mut core::AppLayerDecoderEvents
 {
-    let tx = cast_pointer!(tx, DHCPTransaction);
+    let tx: &mut DHCPTransaction = cast_mut(tx);
     return tx.events;
 }
 
@@ -314,7 +351,7 @@ pub unsafe extern "C" fn rs_dhcp_state_get_tx_iterator(
     istate: &mut u64)
     -> applayer::AppLayerGetTxIterTuple
 {
-    let state = cast_pointer!(state, DHCPState);
+    let state: &mut DHCPState = cast_mut(state);
     match state.get_tx_iterator(min_tx_id, istate) {
         Some((tx, out_tx_id, has_next)) => {
             let c_tx = tx as *const _ as *mut _;
@@ -335,6 +372,7 @@ const PARSER_NAME: &'static [u8] = b"dhcp\0";
 #[no_mangle]
 pub unsafe extern "C" fn rs_dhcp_register_parser() {
     SCLogDebug!("Registering DHCP parser.");
+    dhcp_stats::register();
     let ports = CString::new("[67,68]").unwrap();
     let parser = RustParser {
         name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
@@ -367,6 +405,7 @@ pub unsafe extern "C" fn rs_dhcp_register_parser() {
         apply_tx_config    : None,
         flags              : APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate           : None,
+        state_purge        : None,
     };
 
     let ip_proto_str = CString::new("udp").unwrap();
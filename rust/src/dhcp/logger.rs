@@ -18,6 +18,7 @@
 use std;
 use std::os::raw::c_void;
 
+use crate::applayer::{cast_mut, FfiType};
 use crate::dhcp::dhcp::*;
 use crate::dhcp::parser::{DHCPOptionWrapper,DHCPOptGeneric};
 use crate::dns::log::dns_print_addr;
@@ -28,6 +29,8 @@ pub struct DHCPLogger {
     extended: bool,
 }
 
+unsafe impl FfiType for DHCPLogger {}
+
 impl DHCPLogger {
     
     pub fn new(conf: ConfNode) -> Self {
@@ -271,8 +274,8 @@ pub unsafe extern "C" fn rs_dhcp_logger_free(logger: *mut std::os::raw::c_void)
 pub unsafe extern "C" fn rs_dhcp_logger_log(logger: *mut std::os::raw::c_void,
                                      tx: *mut std::os::raw::c_void,
                                      js: &mut JsonBuilder) -> bool {
-    let logger = cast_pointer!(logger, DHCPLogger);
-    let tx = cast_pointer!(tx, DHCPTransaction);
+    let logger: &mut DHCPLogger = cast_mut(logger);
+    let tx: &mut DHCPTransaction = cast_mut(tx);
     logger.log(tx, js).is_ok()
 }
 
@@ -280,7 +283,7 @@ pub unsafe extern "C" fn rs_dhcp_logger_log(logger: *mut std::os::raw::c_void,
 pub unsafe extern "C" fn rs_dhcp_logger_do_log(logger: *mut std::os::raw::c_void,
                                         tx: *mut std::os::raw::c_void)
                                         -> bool {
-    let logger = cast_pointer!(logger, DHCPLogger);
-    let tx = cast_pointer!(tx, DHCPTransaction);
+    let logger: &mut DHCPLogger = cast_mut(logger);
+    let tx: &mut DHCPTransaction = cast_mut(tx);
     logger.do_log(tx)
 }
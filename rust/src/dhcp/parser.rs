@@ -18,6 +18,7 @@
 use std::cmp::min;
 
 use crate::dhcp::dhcp::*;
+use crate::parsers_util::length_data_be_u8;
 use nom::IResult;
 use nom::combinator::rest;
 use nom::number::streaming::{be_u8, be_u16, be_u32};
@@ -159,8 +160,7 @@ named!(pub parse_address_time_option<DHCPOption>,
 named!(pub parse_generic_option<DHCPOption>,
        do_parse!(
            code: be_u8 >>
-           len: be_u8 >>
-           data: take!(len) >> (
+           data: length_data_be_u8 >> (
                DHCPOption{
                    code: code,
                    data: None,
@@ -236,6 +236,7 @@ pub fn dhcp_parse(input: &[u8]) -> IResult<&[u8], DHCPMessage> {
 #[cfg(test)]
 mod tests {
     use crate::dhcp::dhcp::*;
+use crate::parsers_util::length_data_be_u8;
     use crate::dhcp::parser::*;
 
     #[test]
@@ -15,10 +15,21 @@
  * 02110-1301, USA.
  */
 
-use super::dns::DNSTransaction;
+use super::dns::{
+    DNSAnswerEntry, DNSQueryEntry, DNSRData, DNSResponse, DNSTransaction, ALPROTO_DNS,
+};
+use super::features::{dns_query_features, DNSQueryFeatures};
+use crate::applayer::cast_mut;
 use crate::core;
+use crate::detect::uint::{detect_match_uint, detect_parse_uint, DetectUintData};
+use crate::detect::{
+    helper_buffer_register, helper_free_ctx, helper_keyword_register,
+    helper_multi_buffer_mpm_register, helper_setup_sticky_buffer, helper_setup_tx_match,
+    DetectGetMultiBufFn, DetectKeyword, DetectSetupFn, DetectTxMatchFn,
+    SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
 use std::ffi::CStr;
-use std::os::raw::{c_char, c_void};
+use std::os::raw::{c_char, c_int, c_void};
 
 #[derive(Debug, PartialEq)]
 pub struct DetectDnsOpcode {
@@ -120,6 +131,282 @@ pub unsafe extern "C" fn rs_dns_detect_opcode_free(ptr: *mut c_void) {
     }
 }
 
+static mut G_DNS_NS_BUFFER_ID: c_int = 0;
+static mut G_DNS_SOA_MNAME_BUFFER_ID: c_int = 0;
+static mut G_DNS_SOA_RNAME_BUFFER_ID: c_int = 0;
+
+/// Records of the answer and authority sections of a response.
+fn response_records(response: &DNSResponse) -> impl Iterator<Item = &DNSAnswerEntry> {
+    response.answers.iter().chain(response.authorities.iter())
+}
+
+fn response_ns(response: &DNSResponse, local_id: u32) -> Option<&[u8]> {
+    response_records(response)
+        .filter_map(|answer| match &answer.data {
+            DNSRData::NS(name) => Some(name.as_slice()),
+            _ => None,
+        })
+        .nth(local_id as usize)
+}
+
+fn response_soa_mname(response: &DNSResponse, local_id: u32) -> Option<&[u8]> {
+    response_records(response)
+        .filter_map(|answer| match &answer.data {
+            DNSRData::SOA(soa) => Some(soa.mname.as_slice()),
+            _ => None,
+        })
+        .nth(local_id as usize)
+}
+
+fn response_soa_rname(response: &DNSResponse, local_id: u32) -> Option<&[u8]> {
+    response_records(response)
+        .filter_map(|answer| match &answer.data {
+            DNSRData::SOA(soa) => Some(soa.rname.as_slice()),
+            _ => None,
+        })
+        .nth(local_id as usize)
+}
+
+unsafe fn dns_tx_get_response_buffer(
+    tx: *mut c_void, local_id: u32, buf: *mut *const u8, len: *mut u32,
+    get: fn(&DNSResponse, u32) -> Option<&[u8]>,
+) -> bool {
+    let tx: &mut DNSTransaction = cast_mut(tx);
+    if let Some(response) = &tx.response {
+        if let Some(value) = get(response, local_id) {
+            *buf = value.as_ptr();
+            *len = value.len() as u32;
+            return true;
+        }
+    }
+    false
+}
+
+unsafe extern "C" fn dns_tx_get_ns(
+    tx: *mut c_void, _flow_flags: u8, local_id: u32, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    dns_tx_get_response_buffer(tx, local_id, buf, len, response_ns)
+}
+
+unsafe extern "C" fn dns_tx_get_soa_mname(
+    tx: *mut c_void, _flow_flags: u8, local_id: u32, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    dns_tx_get_response_buffer(tx, local_id, buf, len, response_soa_mname)
+}
+
+unsafe extern "C" fn dns_tx_get_soa_rname(
+    tx: *mut c_void, _flow_flags: u8, local_id: u32, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    dns_tx_get_response_buffer(tx, local_id, buf, len, response_soa_rname)
+}
+
+unsafe extern "C" fn dns_ns_setup(_de: *mut c_void, s: *mut c_void, _raw: *const c_char) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_DNS, G_DNS_NS_BUFFER_ID)
+}
+
+unsafe extern "C" fn dns_soa_mname_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_DNS, G_DNS_SOA_MNAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn dns_soa_rname_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_DNS, G_DNS_SOA_RNAME_BUFFER_ID)
+}
+
+unsafe fn register_response_buffer(
+    name: &'static str, desc: &'static str, url: &'static str, buffer_desc: &'static str,
+    setup: unsafe extern "C" fn(*mut c_void, *mut c_void, *const c_char) -> c_int,
+    get_buf: DetectGetMultiBufFn,
+) -> c_int {
+    let kw = DetectKeyword {
+        name,
+        desc,
+        url,
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_none() {
+        return 0;
+    }
+    helper_multi_buffer_mpm_register(name, buffer_desc, ALPROTO_DNS, true, false, 1, get_buf)
+}
+
+static mut G_DNS_QUERY_ENTROPY_KW_ID: u16 = 0;
+static mut G_DNS_QUERY_ENTROPY_BUFFER_ID: c_int = 0;
+static mut G_DNS_QUERY_DIGIT_RATIO_KW_ID: u16 = 0;
+static mut G_DNS_QUERY_DIGIT_RATIO_BUFFER_ID: c_int = 0;
+static mut G_DNS_QUERY_LABEL_LENGTH_KW_ID: u16 = 0;
+static mut G_DNS_QUERY_LABEL_LENGTH_BUFFER_ID: c_int = 0;
+
+/// Queries of the message of the transaction in the direction of `flags`.
+fn tx_queries(tx: &DNSTransaction, flags: u8) -> &[DNSQueryEntry] {
+    if flags & core::STREAM_TOSERVER != 0 {
+        if let Some(request) = &tx.request {
+            return &request.queries;
+        }
+    } else if let Some(response) = &tx.response {
+        return &response.queries;
+    }
+    &[]
+}
+
+/// Match if the features of any query name match.
+unsafe fn query_features_match(
+    tx: *mut c_void, flags: u8, ctx: *const c_void,
+    get: fn(&DNSQueryFeatures) -> u32,
+) -> c_int {
+    let tx: &mut DNSTransaction = cast_mut(tx);
+    let ctx = &*(ctx as *const DetectUintData<u32>);
+    for query in tx_queries(tx, flags) {
+        if detect_match_uint(ctx, get(&dns_query_features(&query.name))) {
+            return 1;
+        }
+    }
+    0
+}
+
+unsafe fn query_features_setup(
+    s: *mut c_void, raw: *const c_char, kw_id: u16, list_id: c_int,
+) -> c_int {
+    if raw.is_null() {
+        return -1;
+    }
+    let ctx = match CStr::from_ptr(raw).to_str().ok().and_then(detect_parse_uint::<u32>) {
+        Some(ctx) => ctx,
+        None => return -1,
+    };
+    helper_setup_tx_match(s, ALPROTO_DNS, kw_id, list_id, ctx)
+}
+
+unsafe extern "C" fn dns_query_entropy_match(
+    _det_ctx: *mut c_void, _f: *mut c_void, flags: u8, _state: *mut c_void, tx: *mut c_void,
+    _s: *const c_void, ctx: *const c_void,
+) -> c_int {
+    query_features_match(tx, flags, ctx, |features| features.entropy)
+}
+
+unsafe extern "C" fn dns_query_entropy_setup(
+    _de: *mut c_void, s: *mut c_void, raw: *const c_char,
+) -> c_int {
+    query_features_setup(
+        s, raw, G_DNS_QUERY_ENTROPY_KW_ID, G_DNS_QUERY_ENTROPY_BUFFER_ID)
+}
+
+unsafe extern "C" fn dns_query_digit_ratio_match(
+    _det_ctx: *mut c_void, _f: *mut c_void, flags: u8, _state: *mut c_void, tx: *mut c_void,
+    _s: *const c_void, ctx: *const c_void,
+) -> c_int {
+    query_features_match(tx, flags, ctx, |features| features.digit_ratio as u32)
+}
+
+unsafe extern "C" fn dns_query_digit_ratio_setup(
+    _de: *mut c_void, s: *mut c_void, raw: *const c_char,
+) -> c_int {
+    query_features_setup(
+        s, raw, G_DNS_QUERY_DIGIT_RATIO_KW_ID, G_DNS_QUERY_DIGIT_RATIO_BUFFER_ID)
+}
+
+unsafe extern "C" fn dns_query_label_length_match(
+    _det_ctx: *mut c_void, _f: *mut c_void, flags: u8, _state: *mut c_void, tx: *mut c_void,
+    _s: *const c_void, ctx: *const c_void,
+) -> c_int {
+    query_features_match(tx, flags, ctx, |features| features.label_length as u32)
+}
+
+unsafe extern "C" fn dns_query_label_length_setup(
+    _de: *mut c_void, s: *mut c_void, raw: *const c_char,
+) -> c_int {
+    query_features_setup(
+        s, raw, G_DNS_QUERY_LABEL_LENGTH_KW_ID, G_DNS_QUERY_LABEL_LENGTH_BUFFER_ID)
+}
+
+unsafe extern "C" fn dns_query_features_free(_de: *mut c_void, ctx: *mut c_void) {
+    helper_free_ctx::<DetectUintData<u32>>(ctx);
+}
+
+/// Register an integer keyword on the features of the query names,
+/// returning the keyword and list ids.
+unsafe fn register_query_features_keyword(
+    name: &'static str, desc: &'static str, url: &'static str, setup: DetectSetupFn,
+    tx_match: DetectTxMatchFn,
+) -> Option<(u16, c_int)> {
+    let kw = DetectKeyword {
+        name,
+        desc,
+        url,
+        flags: 0,
+        setup,
+        free: Some(dns_query_features_free),
+        tx_match: Some(tx_match),
+    };
+    let kw_id = helper_keyword_register(&kw)?;
+    let list_id = helper_buffer_register(name, ALPROTO_DNS, true, true, 1);
+    Some((kw_id, list_id))
+}
+
+/// Register the DNS keywords implemented in Rust.
+pub unsafe fn register_keywords() {
+    if let Some((kw_id, list_id)) = register_query_features_keyword(
+        "dns.query.entropy",
+        "match on the entropy of the DNS query names, in hundredths of bits per character",
+        "/rules/dns-keywords.html#dns-query-entropy",
+        dns_query_entropy_setup,
+        dns_query_entropy_match,
+    ) {
+        G_DNS_QUERY_ENTROPY_KW_ID = kw_id;
+        G_DNS_QUERY_ENTROPY_BUFFER_ID = list_id;
+    }
+    if let Some((kw_id, list_id)) = register_query_features_keyword(
+        "dns.query.digit_ratio",
+        "match on the percentage of digits in the DNS query names",
+        "/rules/dns-keywords.html#dns-query-digit-ratio",
+        dns_query_digit_ratio_setup,
+        dns_query_digit_ratio_match,
+    ) {
+        G_DNS_QUERY_DIGIT_RATIO_KW_ID = kw_id;
+        G_DNS_QUERY_DIGIT_RATIO_BUFFER_ID = list_id;
+    }
+    if let Some((kw_id, list_id)) = register_query_features_keyword(
+        "dns.query.label_length",
+        "match on the length of the longest label of the DNS query names",
+        "/rules/dns-keywords.html#dns-query-label-length",
+        dns_query_label_length_setup,
+        dns_query_label_length_match,
+    ) {
+        G_DNS_QUERY_LABEL_LENGTH_KW_ID = kw_id;
+        G_DNS_QUERY_LABEL_LENGTH_BUFFER_ID = list_id;
+    }
+    G_DNS_NS_BUFFER_ID = register_response_buffer(
+        "dns.ns",
+        "sticky buffer to match on the name servers of NS records in DNS responses",
+        "/rules/dns-keywords.html#dns-ns",
+        "dns ns name",
+        dns_ns_setup,
+        dns_tx_get_ns,
+    );
+    G_DNS_SOA_MNAME_BUFFER_ID = register_response_buffer(
+        "dns.soa.mname",
+        "sticky buffer to match on the primary name server of SOA records in DNS responses",
+        "/rules/dns-keywords.html#dns-soa-mname",
+        "dns soa mname",
+        dns_soa_mname_setup,
+        dns_tx_get_soa_mname,
+    );
+    G_DNS_SOA_RNAME_BUFFER_ID = register_response_buffer(
+        "dns.soa.rname",
+        "sticky buffer to match on the mailbox of SOA records in DNS responses",
+        "/rules/dns-keywords.html#dns-soa-rname",
+        "dns soa rname",
+        dns_soa_rname_setup,
+        dns_tx_get_soa_rname,
+    );
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -206,4 +493,26 @@ mod test {
             false
         );
     }
+
+    #[test]
+    fn test_response_ns_soa() {
+        use crate::dns::parser::dns_parse_response;
+
+        // NXDOMAIN response with the SOA in the authority section.
+        let pkt: &[u8] = &[
+            0x8d, 0xd5, 0x81, 0x83, 0x00, 0x01, 0x00, 0x00, /* ........ */
+            0x00, 0x01, 0x00, 0x00, 0x03, 0x63, 0x6f, 0x6d, /* .....com */
+            0x00, 0x00, 0x01, 0x00, 0x01, 0xc0, 0x0c, 0x00, /* ........ */
+            0x06, 0x00, 0x01, 0x00, 0x00, 0x03, 0x84, 0x00, /* ........ */
+            0x1c, 0x01, 0x61, 0xc0, 0x0c, 0x01, 0x62, 0xc0, /* ..a...b. */
+            0x0c, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x0e, /* ........ */
+            0x10, 0x00, 0x00, 0x02, 0x58, 0x00, 0x09, 0x3a, /* ....X..: */
+            0x80, 0x00, 0x00, 0x03, 0x84, /* ..... */
+        ];
+        let (_, response) = dns_parse_response(pkt).unwrap();
+        assert_eq!(response_soa_mname(&response, 0), Some(&b"a.com"[..]));
+        assert_eq!(response_soa_rname(&response, 0), Some(&b"b.com"[..]));
+        assert_eq!(response_soa_mname(&response, 1), None);
+        assert_eq!(response_ns(&response, 0), None);
+    }
 }
@@ -23,7 +23,7 @@ use std::collections::HashMap;
 use std::collections::VecDeque;
 
 use crate::applayer::*;
-use crate::core::{self, AppProto, ALPROTO_UNKNOWN, IPPROTO_UDP, IPPROTO_TCP};
+use crate::core::{self, AppProto, Direction, ALPROTO_UNKNOWN, IPPROTO_UDP, IPPROTO_TCP};
 use crate::dns::parser;
 
 use nom::IResult;
@@ -113,7 +113,7 @@ pub const DNS_RCODE_BADALG:   u16 = 21;
 pub const DNS_RCODE_BADTRUNC: u16 = 22;
 
 
-static mut ALPROTO_DNS: AppProto = ALPROTO_UNKNOWN;
+pub static mut ALPROTO_DNS: AppProto = ALPROTO_UNKNOWN;
 
 #[derive(Debug, PartialEq, AppLayerEvent)]
 pub enum DNSEvent {
@@ -224,6 +224,7 @@ pub struct DNSResponse {
     pub queries: Vec<DNSQueryEntry>,
     pub answers: Vec<DNSAnswerEntry>,
     pub authorities: Vec<DNSAnswerEntry>,
+    pub additionals: Vec<DNSAnswerEntry>,
 }
 
 #[derive(Debug)]
@@ -231,11 +232,13 @@ pub struct DNSTransaction {
     pub id: u64,
     pub request: Option<DNSRequest>,
     pub response: Option<DNSResponse>,
-    pub de_state: Option<*mut core::DetectEngineState>,
+    pub de_state: DetectStateHolder,
     pub events: *mut core::AppLayerDecoderEvents,
     pub tx_data: AppLayerTxData,
 }
 
+unsafe impl FfiType for DNSTransaction {}
+
 impl DNSTransaction {
 
     pub fn new() -> Self {
@@ -243,7 +246,7 @@ impl DNSTransaction {
             id: 0,
             request: None,
             response: None,
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: AppLayerTxData::new(),
         }
@@ -253,12 +256,6 @@ impl DNSTransaction {
         if self.events != std::ptr::null_mut() {
             core::sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        match self.de_state {
-            Some(state) => {
-                core::sc_detect_engine_state_free(state);
-            }
-            None => { },
-        }
     }
 
     /// Get the DNS transactions ID (not the internal tracking ID).
@@ -336,6 +333,8 @@ pub struct DNSState {
     gap: bool,
 }
 
+unsafe impl FfiType for DNSState {}
+
 impl DNSState {
 
     pub fn new() -> Self {
@@ -666,7 +665,7 @@ pub extern "C" fn rs_dns_state_free(state: *mut std::os::raw::c_void) {
 pub unsafe extern "C" fn rs_dns_state_tx_free(state: *mut std::os::raw::c_void,
                                        tx_id: u64)
 {
-    let state = cast_pointer!(state, DNSState);
+    let state: &mut DNSState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -680,7 +679,7 @@ pub unsafe extern "C" fn rs_dns_parse_request(_flow: *const core::Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8)
                                        -> AppLayerResult {
-    let state = cast_pointer!(state, DNSState);
+    let state: &mut DNSState = cast_mut(state);
     let buf = std::slice::from_raw_parts(input, input_len as usize);
     if state.parse_request(buf) {
         AppLayerResult::ok()
@@ -698,7 +697,7 @@ pub unsafe extern "C" fn rs_dns_parse_response(_flow: *const core::Flow,
                                         _data: *const std::os::raw::c_void,
                                         _flags: u8)
                                         -> AppLayerResult {
-    let state = cast_pointer!(state, DNSState);
+    let state: &mut DNSState = cast_mut(state);
     let buf = std::slice::from_raw_parts(input, input_len as usize);
     if state.parse_response(buf) {
         AppLayerResult::ok()
@@ -717,7 +716,7 @@ pub unsafe extern "C" fn rs_dns_parse_request_tcp(_flow: *const core::Flow,
                                            _data: *const std::os::raw::c_void,
                                            _flags: u8)
                                            -> AppLayerResult {
-    let state = cast_pointer!(state, DNSState);
+    let state: &mut DNSState = cast_mut(state);
     if input_len > 0 {
         if input != std::ptr::null_mut() {
             let buf = std::slice::from_raw_parts(input, input_len as usize);
@@ -737,7 +736,7 @@ pub unsafe extern "C" fn rs_dns_parse_response_tcp(_flow: *const core::Flow,
                                             _data: *const std::os::raw::c_void,
                                             _flags: u8)
                                             -> AppLayerResult {
-    let state = cast_pointer!(state, DNSState);
+    let state: &mut DNSState = cast_mut(state);
     if input_len > 0 {
         if input != std::ptr::null_mut() {
             let buf = std::slice::from_raw_parts(input, input_len as usize);
@@ -763,7 +762,7 @@ pub extern "C" fn rs_dns_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void,
 pub unsafe extern "C" fn rs_dns_state_get_tx_count(state: *mut std::os::raw::c_void)
                                             -> u64
 {
-    let state = cast_pointer!(state, DNSState);
+    let state: &mut DNSState = cast_mut(state);
     SCLogDebug!("rs_dns_state_get_tx_count: returning {}", state.tx_id);
     return state.tx_id;
 }
@@ -773,7 +772,7 @@ pub unsafe extern "C" fn rs_dns_state_get_tx(state: *mut std::os::raw::c_void,
                                       tx_id: u64)
                                       -> *mut std::os::raw::c_void
 {
-    let state = cast_pointer!(state, DNSState);
+    let state: &mut DNSState = cast_mut(state);
     match state.get_tx(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -794,37 +793,13 @@ pub extern "C" fn rs_dns_tx_is_response(tx: &mut DNSTransaction) -> bool {
     tx.response.is_some()
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_dns_state_set_tx_detect_state(
-    tx: *mut std::os::raw::c_void,
-    de_state: &mut core::DetectEngineState) -> std::os::raw::c_int
-{
-    let tx = cast_pointer!(tx, DNSTransaction);
-    tx.de_state = Some(de_state);
-    return 0;
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_dns_state_get_tx_detect_state(
-    tx: *mut std::os::raw::c_void)
-    -> *mut core::DetectEngineState
-{
-    let tx = cast_pointer!(tx, DNSTransaction);
-    match tx.de_state {
-        Some(ds) => {
-            return ds;
-        },
-        None => {
-            return std::ptr::null_mut();
-        }
-    }
-}
+export_tx_detect_state!(rs_dns_state_get_tx_detect_state, rs_dns_state_set_tx_detect_state, DNSTransaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_dns_state_get_events(tx: *mut std::os::raw::c_void)
                                           -> *mut core::AppLayerDecoderEvents
 {
-    let tx = cast_pointer!(tx, DNSTransaction);
+    let tx: &mut DNSTransaction = cast_mut(tx);
     return tx.events;
 }
 
@@ -833,7 +808,7 @@ pub unsafe extern "C" fn rs_dns_state_get_tx_data(
     tx: *mut std::os::raw::c_void)
     -> *mut AppLayerTxData
 {
-    let tx = cast_pointer!(tx, DNSTransaction);
+    let tx: &mut DNSTransaction = cast_mut(tx);
     return &mut tx.tx_data;
 }
 
@@ -894,66 +869,51 @@ pub unsafe extern "C" fn rs_dns_tx_get_query_rrtype(tx: &mut DNSTransaction,
     return 0;
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_dns_probe(
-    _flow: *const core::Flow,
-    _dir: u8,
-    input: *const u8,
-    len: u32,
-    rdir: *mut u8,
-) -> AppProto {
-    if len == 0 || len < std::mem::size_of::<DNSHeader>() as u32 {
-        return core::ALPROTO_UNKNOWN;
+/// Probing parser for DNS over UDP.
+fn dns_probe_udp(input: &[u8], direction: Direction) -> ProbeResult {
+    if input.len() < std::mem::size_of::<DNSHeader>() {
+        return ProbeResult::Unknown;
     }
-    let slice: &[u8] = std::slice::from_raw_parts(input as *mut u8, len as usize);
-    let (is_dns, is_request, _) = probe(slice, slice.len());
+    let (is_dns, is_request, _) = probe(input, input.len());
     if is_dns {
-        let dir = if is_request {
-            core::STREAM_TOSERVER
+        let actual = if is_request {
+            Direction::ToServer
         } else {
-            core::STREAM_TOCLIENT
+            Direction::ToClient
         };
-        *rdir = dir;
-        return ALPROTO_DNS;
+        return ProbeResult::with_direction(unsafe { ALPROTO_DNS }, direction, actual);
     }
-    return 0;
+    return ProbeResult::Unknown;
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_dns_probe_tcp(
-    _flow: *const core::Flow,
-    direction: u8,
-    input: *const u8,
-    len: u32,
-    rdir: *mut u8
-) -> AppProto {
-    if len == 0 || len < std::mem::size_of::<DNSHeader>() as u32 + 2 {
-        return core::ALPROTO_UNKNOWN;
+/// Probing parser for DNS over TCP.
+fn dns_probe_tcp(input: &[u8], direction: Direction) -> ProbeResult {
+    if input.len() < std::mem::size_of::<DNSHeader>() + 2 {
+        return ProbeResult::Unknown;
     }
-    let slice: &[u8] = std::slice::from_raw_parts(input as *mut u8, len as usize);
     //is_incomplete is checked by caller
-    let (is_dns, is_request, _) = probe_tcp(slice);
+    let (is_dns, is_request, _) = probe_tcp(input);
     if is_dns {
-        let dir = if is_request {
-            core::STREAM_TOSERVER
+        let actual = if is_request {
+            Direction::ToServer
         } else {
-            core::STREAM_TOCLIENT
+            Direction::ToClient
         };
-        if direction & (core::STREAM_TOSERVER|core::STREAM_TOCLIENT) != dir {
-            *rdir = dir;
-        }
-        return ALPROTO_DNS;
+        return ProbeResult::with_direction(unsafe { ALPROTO_DNS }, direction, actual);
     }
-    return 0;
+    return ProbeResult::Unknown;
 }
 
+export_probe!(rs_dns_probe, dns_probe_udp);
+export_probe!(rs_dns_probe_tcp, dns_probe_tcp);
+
 #[no_mangle]
 pub unsafe extern "C" fn rs_dns_apply_tx_config(
     _state: *mut std::os::raw::c_void, _tx: *mut std::os::raw::c_void,
     _mode: std::os::raw::c_int, config: AppLayerTxConfig
 ) {
-    let tx = cast_pointer!(_tx, DNSTransaction);
-    let state = cast_pointer!(_state, DNSState);
+    let tx: &mut DNSTransaction = cast_mut(_tx);
+    let state: &mut DNSState = cast_mut(_state);
     if let Some(request) = &tx.request {
         if state.config.is_none() {
             state.config = Some(ConfigTracker::new());
@@ -998,6 +958,7 @@ pub unsafe extern "C" fn rs_dns_udp_register_parser() {
         apply_tx_config: Some(rs_dns_apply_tx_config),
         flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("udp").unwrap();
@@ -1044,6 +1005,7 @@ pub unsafe extern "C" fn rs_dns_tcp_register_parser() {
         apply_tx_config: Some(rs_dns_apply_tx_config),
         flags: APP_LAYER_PARSER_OPT_ACCEPT_GAPS | APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
@@ -0,0 +1,90 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Lexical features of queried names, for DGA heuristics.
+//!
+//! The features are computed on the name as logged, without the dots
+//! separating the labels, ASCII letters being case folded.
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DNSQueryFeatures {
+    /// Shannon entropy of the characters, in hundredths of bits per
+    /// character.
+    pub entropy: u32,
+    /// Percentage of the characters that are digits.
+    pub digit_ratio: u8,
+    /// Length of the longest label.
+    pub label_length: u8,
+}
+
+pub fn dns_query_features(name: &[u8]) -> DNSQueryFeatures {
+    let mut counts = [0u32; 256];
+    let mut total = 0u32;
+    let mut digits = 0u32;
+    let mut label_length = 0usize;
+    for label in name.split(|&c| c == b'.') {
+        if label.len() > label_length {
+            label_length = label.len();
+        }
+        for &c in label {
+            counts[c.to_ascii_lowercase() as usize] += 1;
+            if c.is_ascii_digit() {
+                digits += 1;
+            }
+            total += 1;
+        }
+    }
+    if total == 0 {
+        return DNSQueryFeatures::default();
+    }
+
+    let mut entropy = 0.0f64;
+    for &count in counts.iter().filter(|&&count| count > 0) {
+        let p = count as f64 / total as f64;
+        entropy -= p * p.log2();
+    }
+    DNSQueryFeatures {
+        entropy: (entropy * 100.0).round() as u32,
+        digit_ratio: (digits * 100 / total) as u8,
+        // labels are at most 63 bytes on the wire, but parsed names
+        // are not checked
+        label_length: std::cmp::min(label_length, 255) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dns_query_features() {
+        assert_eq!(dns_query_features(b""), DNSQueryFeatures::default());
+
+        // 4 distinct characters with the same frequency: 2 bits
+        let features = dns_query_features(b"abcd.ABCD");
+        assert_eq!(features.entropy, 200);
+        assert_eq!(features.digit_ratio, 0);
+        assert_eq!(features.label_length, 4);
+
+        let features = dns_query_features(b"aaaa");
+        assert_eq!(features.entropy, 0);
+
+        let features = dns_query_features(b"x1y2z3w4.example.com");
+        assert_eq!(features.digit_ratio, 22);
+        assert_eq!(features.label_length, 8);
+    }
+}
@@ -21,6 +21,7 @@ use std::collections::HashMap;
 
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 use crate::dns::dns::*;
+use crate::dns::features::dns_query_features;
 
 pub const LOG_QUERIES    : u64 = BIT_U64!(0);
 pub const LOG_ANSWER     : u64 = BIT_U64!(1);
@@ -87,9 +88,12 @@ pub const LOG_URI        : u64 = BIT_U64!(59);
 pub const LOG_FORMAT_GROUPED  : u64 = BIT_U64!(60);
 pub const LOG_FORMAT_DETAILED : u64 = BIT_U64!(61);
 
+pub const LOG_AUTHORITIES     : u64 = BIT_U64!(62);
+pub const LOG_ADDITIONALS     : u64 = BIT_U64!(63);
+
 fn dns_log_rrtype_enabled(rtype: u16, flags: u64) -> bool
 {
-    if flags == !0 {
+    if flags | LOG_AUTHORITIES | LOG_ADDITIONALS == !0 {
         return true;
     }
 
@@ -443,7 +447,8 @@ fn dns_log_srv(srv: &DNSRDataSRV) -> Result<JsonBuilder, JsonError>
     return Ok(js);
 }
 
-fn dns_log_json_answer_detail(answer: &DNSAnswerEntry) -> Result<JsonBuilder, JsonError>
+/// Log a resource record as an object, with its rdata when known.
+pub fn dns_log_json_answer_detail(answer: &DNSAnswerEntry) -> Result<JsonBuilder, JsonError>
 {
     let mut jsa = JsonBuilder::new_object();
 
@@ -598,7 +603,7 @@ fn dns_log_json_answer(js: &mut JsonBuilder, response: &DNSResponse, flags: u64)
 
     }
 
-    if response.authorities.len() > 0 {
+    if flags & LOG_AUTHORITIES != 0 && response.authorities.len() > 0 {
         js.open_array("authorities")?;
         for auth in &response.authorities {
             let auth_detail = dns_log_json_answer_detail(auth)?;
@@ -607,6 +612,18 @@ fn dns_log_json_answer(js: &mut JsonBuilder, response: &DNSResponse, flags: u64)
         js.close()?;
     }
 
+    // The OPT pseudo-record of EDNS is not logged.
+    let mut additionals = response.additionals.iter()
+        .filter(|additional| additional.rrtype != DNS_RECORD_TYPE_OPT)
+        .peekable();
+    if flags & LOG_ADDITIONALS != 0 && additionals.peek().is_some() {
+        js.open_array("additionals")?;
+        for additional in additionals {
+            js.append_object(&dns_log_json_answer_detail(additional)?)?;
+        }
+        js.close()?;
+    }
+
     Ok(())
 }
 
@@ -626,6 +643,12 @@ fn dns_log_query(tx: &mut DNSTransaction,
                 jb.set_string_from_bytes("rrname", &query.name)?;
                 jb.set_string("rrtype", &dns_rrtype_string(query.rrtype))?;
                 jb.set_uint("tx_id", tx.id - 1)?;
+                let features = dns_query_features(&query.name);
+                jb.open_object("features")?;
+                jb.set_float("entropy", features.entropy as f64 / 100.0)?;
+                jb.set_uint("digit_ratio", features.digit_ratio as u64)?;
+                jb.set_uint("label_length", features.label_length as u64)?;
+                jb.close()?;
                 return Ok(true);
             }
         }
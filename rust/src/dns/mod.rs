@@ -19,6 +19,7 @@ pub mod parser;
 pub mod dns;
 pub mod log;
 pub mod detect;
+pub mod features;
 
 #[cfg(feature = "lua")]
 pub mod lua;
@@ -181,13 +181,19 @@ fn dns_parse_answer<'a>(slice: &'a [u8], message: &'a [u8], count: usize)
                         1
                     }
                 };
-                let result: IResult<&'a [u8], Vec<DNSRData>> =
+                // many_m_n! fails on a parser that consumes nothing, so
+                // empty data, as in the EDNS OPT record, is parsed once.
+                let result: IResult<&'a [u8], Vec<DNSRData>> = if data.is_empty() {
+                    dns_parse_rdata(data, message, rrtype)
+                        .map(|(rem, rdata)| (rem, vec![rdata]))
+                } else {
                     do_parse!(
                         data,
                         rdata: many_m_n!(1, n,
                                          complete!(call!(dns_parse_rdata, message, rrtype)))
                             >> (rdata)
-                    );
+                    )
+                };
                 match result {
                     Ok((_, rdatas)) => {
                         for rdata in rdatas {
@@ -213,9 +219,12 @@ fn dns_parse_answer<'a>(slice: &'a [u8], message: &'a [u8], count: usize)
 
 
 /// Parse a DNS response.
+///
+/// The additional records are optional: a response whose additional
+/// section can not be parsed is returned without them.
 pub fn dns_parse_response<'a>(slice: &'a [u8])
                               -> IResult<&[u8], DNSResponse> {
-    do_parse!(
+    let (rem, mut response) = do_parse!(
         slice,
         header: dns_parse_header
             >> queries: count!(
@@ -230,9 +239,18 @@ pub fn dns_parse_response<'a>(slice: &'a [u8])
                     queries: queries,
                     answers: answers,
                     authorities: authorities,
+                    additionals: Vec::new(),
                 }
             )
-    )
+    )?;
+    let additional_rr = response.header.additional_rr as usize;
+    match dns_parse_answer(rem, slice, additional_rr) {
+        Ok((rem, additionals)) => {
+            response.additionals = additionals;
+            Ok((rem, response))
+        }
+        Err(_) => Ok((rem, response)),
+    }
 }
 
 /// Parse a single DNS query.
@@ -662,9 +680,10 @@ mod tests {
         match res {
             Ok((rem, response)) => {
 
-                // For now we have some remainder data as there is an
-                // additional record type we don't parse yet.
-                assert!(rem.len() > 0);
+                // The additional record is the EDNS OPT pseudo-record.
+                assert_eq!(rem.len(), 0);
+                assert_eq!(response.additionals.len(), 1);
+                assert_eq!(response.additionals[0].rrtype, DNS_RECORD_TYPE_OPT);
 
                 assert_eq!(response.header, DNSHeader{
                     tx_id: 0x8295,
@@ -701,6 +720,33 @@ mod tests {
     }
 
 
+    #[test]
+    fn test_dns_parse_response_truncated_additional() {
+        // The response of test_dns_parse_response_nxdomain_soa, cut in
+        // the additional record.
+        let pkt: &[u8] = &[
+                        0x82, 0x95, 0x81, 0x83, 0x00, 0x01, /* j....... */
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x03, 0x64, /* .......d */
+            0x6e, 0x65, 0x04, 0x6f, 0x69, 0x73, 0x66, 0x03, /* ne.oisf. */
+            0x6e, 0x65, 0x74, 0x00, 0x00, 0x01, 0x00, 0x01, /* net..... */
+            0xc0, 0x10, 0x00, 0x06, 0x00, 0x01, 0x00, 0x00, /* ........ */
+            0x03, 0x83, 0x00, 0x45, 0x06, 0x6e, 0x73, 0x2d, /* ...E.ns- */
+            0x31, 0x31, 0x30, 0x09, 0x61, 0x77, 0x73, 0x64, /* 110.awsd */
+            0x6e, 0x73, 0x2d, 0x31, 0x33, 0x03, 0x63, 0x6f, /* ns-13.co */
+            0x6d, 0x00, 0x11, 0x61, 0x77, 0x73, 0x64, 0x6e, /* m..awsdn */
+            0x73, 0x2d, 0x68, 0x6f, 0x73, 0x74, 0x6d, 0x61, /* s-hostma */
+            0x73, 0x74, 0x65, 0x72, 0x06, 0x61, 0x6d, 0x61, /* ster.ama */
+            0x7a, 0x6f, 0x6e, 0xc0, 0x3b, 0x00, 0x00, 0x00, /* zon.;... */
+            0x01, 0x00, 0x00, 0x1c, 0x20, 0x00, 0x00, 0x03, /* .... ... */
+            0x84, 0x00, 0x12, 0x75, 0x00, 0x00, 0x01, 0x51, /* ...u...Q */
+            0x80, 0x00, 0x00, 0x29, 0x02, 0x00,             /* ...).. */
+        ];
+
+        let (_, response) = dns_parse_response(pkt).unwrap();
+        assert_eq!(response.authorities.len(), 1);
+        assert!(response.additionals.is_empty());
+    }
+
     #[test]
     fn test_dns_parse_response_null() {
         // DNS response with a NULL record from
@@ -0,0 +1,72 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Buffer on the server name of the ClientHello, like tls.sni.
+
+use super::dtls::{DtlsTransaction, ALPROTO_DTLS};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_DTLS_SNI_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn dtls_tx_get_sni(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut DtlsTransaction = cast_mut(tx);
+    match tx.client_hello.as_ref().and_then(|hello| hello.sni.as_ref()) {
+        Some(sni) => {
+            *buf = sni.as_ptr();
+            *len = sni.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn dtls_sni_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, dtls_tx_get_sni)
+}
+
+unsafe extern "C" fn dtls_sni_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_DTLS, G_DTLS_SNI_BUFFER_ID)
+}
+
+/// Register the DTLS keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "dtls.sni",
+        desc: "sticky buffer to match on the server name of the DTLS ClientHello",
+        url: "/rules/dtls-keywords.html#dtls-sni",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: dtls_sni_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_DTLS_SNI_BUFFER_ID = helper_buffer_mpm_register(
+            "dtls.sni", "dtls server name", ALPROTO_DTLS, false, true, 1, dtls_sni_get_data);
+    }
+}
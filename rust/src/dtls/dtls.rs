@@ -0,0 +1,650 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_UDP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::quic::fingerprint::{ClientHello, ServerHello};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use crate::x509::Certificate;
+use std::ffi::CString;
+
+declare_counters!(dtls_stats, "app_layer.dtls", {
+    handshakes,
+    hello_verify_requests,
+    certificates,
+    alerts,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum DtlsEvent {
+    InvalidRecord,
+    InvalidHandshake,
+    HandshakeTooLarge,
+    InvalidCertificate,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static DTLS_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static DTLS_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum length of a reassembled handshake message. Certificate
+/// chains are the largest.
+const DTLS_MAX_HANDSHAKE_LEN: usize = 65536;
+
+/// Maximum number of fragments of a message received ahead of the
+/// reassembled data.
+const DTLS_MAX_HANDSHAKE_FRAGMENTS: usize = 64;
+
+/// Length of a record header followed by a handshake header.
+const DTLS_PROBE_LEN: usize = 25;
+
+pub static mut ALPROTO_DTLS: AppProto = ALPROTO_UNKNOWN;
+
+#[derive(Debug)]
+pub struct DtlsTransaction {
+    id: u64,
+    /// The version of the first record of the handshake.
+    pub record_version: u16,
+    pub client_hello: Option<ClientHello>,
+    pub server_hello: Option<ServerHello>,
+    /// Set when the server asked the client to prove its address with a
+    /// cookie.
+    pub hello_verify: bool,
+    /// The certificate chain of the server.
+    pub certificates: Vec<Certificate>,
+    /// The level and description of an alert sent in the clear.
+    pub alert: Option<(u8, u8)>,
+    /// Set once the rest of the handshake of the server is encrypted.
+    pub complete: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for DtlsTransaction {}
+
+impl DtlsTransaction {
+    fn new(id: u64, record_version: u16) -> DtlsTransaction {
+        DtlsTransaction {
+            id,
+            record_version,
+            client_hello: None,
+            server_hello: None,
+            hello_verify: false,
+            certificates: Vec::new(),
+            alert: None,
+            complete: false,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: DtlsEvent) {
+        stats_incr!(dtls_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    /// The version negotiated by the server.
+    pub fn version(&self) -> Option<u16> {
+        self.server_hello
+            .as_ref()
+            .map(|sh| sh.selected_version.unwrap_or(sh.version))
+    }
+}
+
+impl Drop for DtlsTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for DtlsTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = DtlsEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+/// Reassembly of the handshake messages of a direction. The messages
+/// are numbered; their fragments may be split over records and
+/// datagrams, and are retransmitted when lost.
+#[derive(Default)]
+struct HandshakeReassembly {
+    next_seq: u16,
+    msg_type: u8,
+    length: usize,
+    /// Data received in order, from offset 0.
+    data: Vec<u8>,
+    /// Fragments received ahead of `data`.
+    fragments: Vec<(usize, Vec<u8>)>,
+}
+
+impl HandshakeReassembly {
+    fn reset(&mut self, message_seq: u16, msg_type: u8, length: usize) {
+        self.next_seq = message_seq;
+        self.msg_type = msg_type;
+        self.length = length;
+        self.data.clear();
+        self.fragments.clear();
+    }
+
+    /// Add a fragment, returning the type and body of the message it
+    /// completes, if any.
+    fn add(&mut self, fragment: &HandshakeFragment)
+           -> Result<Option<(u8, Vec<u8>)>, DtlsEvent> {
+        let length = fragment.length as usize;
+        if fragment.message_seq < self.next_seq {
+            // a retransmission of a message already reassembled
+            return Ok(None);
+        }
+        if length > DTLS_MAX_HANDSHAKE_LEN {
+            return Err(DtlsEvent::HandshakeTooLarge);
+        }
+        let started = !self.data.is_empty() || !self.fragments.is_empty();
+        if fragment.message_seq > self.next_seq || !started {
+            // the previous messages were missed
+            self.reset(fragment.message_seq, fragment.msg_type, length);
+        } else if fragment.msg_type != self.msg_type || length != self.length {
+            return Err(DtlsEvent::InvalidHandshake);
+        }
+        if self.fragments.len() >= DTLS_MAX_HANDSHAKE_FRAGMENTS {
+            return Err(DtlsEvent::HandshakeTooLarge);
+        }
+        self.fragments.push((fragment.fragment_offset as usize, fragment.data.to_vec()));
+        self.fragments.sort_by_key(|(offset, _)| *offset);
+        let mut ahead = Vec::new();
+        for (offset, data) in self.fragments.drain(..) {
+            if offset > self.data.len() {
+                ahead.push((offset, data));
+            } else if offset + data.len() > self.data.len() {
+                let skip = self.data.len() - offset;
+                self.data.extend_from_slice(&data[skip..]);
+            }
+        }
+        self.fragments = ahead;
+        if self.data.len() < self.length {
+            return Ok(None);
+        }
+        let message = (self.msg_type, std::mem::replace(&mut self.data, Vec::new()));
+        self.fragments.clear();
+        self.next_seq = self.next_seq.wrapping_add(1);
+        Ok(Some(message))
+    }
+}
+
+pub struct DtlsState {
+    transactions: TxTable<DtlsTransaction>,
+    handshake_ts: HandshakeReassembly,
+    handshake_tc: HandshakeReassembly,
+}
+
+unsafe impl FfiType for DtlsState {}
+
+impl txtable::State for DtlsState {
+    type Tx = DtlsTransaction;
+
+    fn tx_table(&self) -> &TxTable<DtlsTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<DtlsTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl DtlsState {
+    pub fn new() -> DtlsState {
+        DtlsState {
+            transactions: TxTable::with_limit(&DTLS_TX_LIMIT).with_gauges(&DTLS_GAUGES),
+            handshake_ts: HandshakeReassembly::default(),
+            handshake_tc: HandshakeReassembly::default(),
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    /// The handshake in progress, a new one if the last one is complete.
+    fn current_tx(&mut self, record_version: u16) -> &mut DtlsTransaction {
+        let start = match self.transactions.last_mut() {
+            Some(tx) => tx.complete,
+            None => true,
+        };
+        if start {
+            stats_incr!(dtls_stats::handshakes);
+            let tx = DtlsTransaction::new(self.transactions.next_id(), record_version);
+            self.transactions.push(tx);
+        }
+        self.transactions.last_mut().unwrap()
+    }
+
+    fn handle_message(&mut self, record_version: u16, msg_type: u8, body: &[u8],
+                      direction: Direction) {
+        let tx = self.current_tx(record_version);
+        match (msg_type, direction) {
+            (HANDSHAKE_CLIENT_HELLO, Direction::ToServer) => match parse_client_hello(body) {
+                // the hello is sent again with the cookie of the server
+                Ok((_, (hello, _cookie))) => tx.client_hello = Some(hello),
+                Err(_) => tx.set_event(DtlsEvent::InvalidHandshake),
+            },
+            (HANDSHAKE_HELLO_VERIFY_REQUEST, Direction::ToClient) => {
+                stats_incr!(dtls_stats::hello_verify_requests);
+                tx.hello_verify = true;
+            }
+            (HANDSHAKE_SERVER_HELLO, Direction::ToClient) => match parse_server_hello(body) {
+                Ok((_, hello)) => {
+                    tx.server_hello = Some(hello);
+                    // the rest of the handshake of DTLS 1.3 is encrypted
+                    if tx.version() == Some(DTLS_1_3) {
+                        tx.complete = true;
+                    }
+                }
+                Err(_) => tx.set_event(DtlsEvent::InvalidHandshake),
+            },
+            (HANDSHAKE_CERTIFICATE, Direction::ToClient) => match parse_certificates(body) {
+                Ok((_, chain)) => {
+                    for der in chain {
+                        stats_incr!(dtls_stats::certificates);
+                        match Certificate::parse(der) {
+                            Ok(cert) => tx.certificates.push(cert),
+                            Err(_) => tx.set_event(DtlsEvent::InvalidCertificate),
+                        }
+                    }
+                }
+                Err(_) => tx.set_event(DtlsEvent::InvalidHandshake),
+            },
+            _ => {}
+        }
+    }
+
+    fn handle_handshake(&mut self, record: &Record, direction: Direction) -> bool {
+        let fragments = match parse_handshake_fragments(record.fragment) {
+            Ok((rem, fragments)) if rem.is_empty() => fragments,
+            _ => return false,
+        };
+        for fragment in fragments {
+            let reassembly = match direction {
+                Direction::ToServer => &mut self.handshake_ts,
+                Direction::ToClient => &mut self.handshake_tc,
+            };
+            match reassembly.add(&fragment) {
+                Ok(Some((msg_type, body))) => {
+                    self.handle_message(record.version, msg_type, &body, direction);
+                }
+                Ok(None) => {}
+                Err(event) => {
+                    reassembly.reset(fragment.message_seq.wrapping_add(1), 0, 0);
+                    self.current_tx(record.version).set_event(event);
+                }
+            }
+        }
+        true
+    }
+
+    fn handle_record(&mut self, record: &Record, direction: Direction) -> bool {
+        if record.epoch != 0 || record.content_type == CONTENT_APPLICATION_DATA {
+            // keyed: the handshake of this side is over
+            if direction == Direction::ToClient {
+                if let Some(tx) = self.transactions.last_mut() {
+                    tx.complete = true;
+                }
+            }
+            return true;
+        }
+        match record.content_type {
+            CONTENT_HANDSHAKE => self.handle_handshake(record, direction),
+            CONTENT_CHANGE_CIPHER_SPEC => {
+                if direction == Direction::ToClient {
+                    if let Some(tx) = self.transactions.last_mut() {
+                        tx.complete = true;
+                    }
+                }
+                true
+            }
+            CONTENT_ALERT if record.fragment.len() == 2 => {
+                stats_incr!(dtls_stats::alerts);
+                let tx = self.current_tx(record.version);
+                tx.alert = Some((record.fragment[0], record.fragment[1]));
+                // a fatal alert ends the handshake
+                if record.fragment[0] == 2 {
+                    tx.complete = true;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Parse the records of a datagram.
+    fn parse(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
+        let mut current = input;
+        while !current.is_empty() {
+            if is_unified_header(current[0]) {
+                // the rest of the datagram is DTLS 1.3 ciphertext
+                break;
+            }
+            let (rem, record) = match parse_record(current) {
+                Ok((rem, record)) => (rem, record),
+                Err(_) => {
+                    self.current_tx(DTLS_1_0).set_event(DtlsEvent::InvalidRecord);
+                    break;
+                }
+            };
+            if !is_dtls_version(record.version) || !self.handle_record(&record, direction) {
+                self.current_tx(record.version).set_event(DtlsEvent::InvalidRecord);
+                break;
+            }
+            current = rem;
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for the first fragment of a hello in the first record of a
+/// flow.
+fn dtls_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    if input.len() < DTLS_PROBE_LEN {
+        return ProbeResult::Unknown;
+    }
+    let version = u16::from_be_bytes([input[1], input[2]]);
+    let epoch = u16::from_be_bytes([input[3], input[4]]);
+    let fragment_offset = &input[19..22];
+    if input[0] != CONTENT_HANDSHAKE || !is_dtls_version(version) || epoch != 0
+        || fragment_offset != [0, 0, 0] {
+        return ProbeResult::Failed;
+    }
+    let actual = match input[13] {
+        HANDSHAKE_CLIENT_HELLO => Direction::ToServer,
+        HANDSHAKE_SERVER_HELLO | HANDSHAKE_HELLO_VERIFY_REQUEST => Direction::ToClient,
+        _ => return ProbeResult::Failed,
+    };
+    ProbeResult::with_direction(unsafe { ALPROTO_DTLS }, direction, actual)
+}
+
+export_probe!(rs_dtls_probing_parser, dtls_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_dtls_state_new(_orig_state: *mut std::os::raw::c_void,
+                                    _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = DtlsState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_dtls_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut DtlsState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_dtls_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut DtlsState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_dtls_parse_request(_flow: *const Flow,
+                                               state: *mut std::os::raw::c_void,
+                                               _pstate: *mut std::os::raw::c_void,
+                                               input: *const u8,
+                                               input_len: u32,
+                                               _data: *const std::os::raw::c_void,
+                                               _flags: u8) -> AppLayerResult {
+    let state: &mut DtlsState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_dtls_parse_response(_flow: *const Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                _pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    let state: &mut DtlsState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_dtls_state_get_tx(state: *mut std::os::raw::c_void,
+                                              tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut DtlsState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_dtls_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut DtlsState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// The client side is done with its hello, the handshake once the rest
+/// of the one of the server is encrypted.
+#[no_mangle]
+pub unsafe extern "C" fn rs_dtls_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                         direction: u8) -> std::os::raw::c_int {
+    let tx: &mut DtlsTransaction = cast_mut(tx);
+    if tx.complete || (direction & core::STREAM_TOSERVER != 0 && tx.client_hello.is_some()) {
+        1
+    } else {
+        0
+    }
+}
+
+export_tx_detect_state!(rs_dtls_state_get_tx_detect_state,
+                        rs_dtls_state_set_tx_detect_state, DtlsTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_dtls_state_get_events(tx: *mut std::os::raw::c_void)
+                                                  -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut DtlsTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_dtls_get_tx_data, DtlsTransaction);
+
+fn dtls_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    DTLS_GAUGES.log(js)?;
+    js.set_uint("events", dtls_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+const PARSER_NAME: &[u8] = b"dtls\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_dtls_register_parser() {
+    dtls_stats::register();
+    DTLS_TX_LIMIT.configure("dtls", txtable::DEFAULT_MAX_TX);
+    introspect::register("dtls", dtls_introspect);
+    let default_port = CString::new("[443,4433,5684]").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_UDP,
+        probe_ts: Some(rs_dtls_probing_parser),
+        probe_tc: Some(rs_dtls_probing_parser),
+        min_depth: 0,
+        max_depth: DTLS_PROBE_LEN as u16,
+        state_new: rs_dtls_state_new,
+        state_free: rs_dtls_state_free,
+        tx_free: rs_dtls_state_tx_free,
+        parse_ts: rs_dtls_parse_request,
+        parse_tc: rs_dtls_parse_response,
+        get_tx_count: rs_dtls_state_get_tx_count,
+        get_tx: rs_dtls_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_dtls_tx_get_alstate_progress,
+        get_de_state: rs_dtls_state_get_tx_detect_state,
+        set_de_state: rs_dtls_state_set_tx_detect_state,
+        get_events: Some(rs_dtls_state_get_events),
+        get_eventinfo: Some(DtlsEvent::get_event_info),
+        get_eventinfo_byid: Some(DtlsEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<DtlsState>),
+        get_tx_data: rs_dtls_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<DtlsState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("udp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_DTLS = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust dtls parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for DTLS.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dtls::parser::tests::{client_hello_fragment, handshake_fragment, record};
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_dtls_state_new,
+            state_free: rs_dtls_state_free,
+            parse_ts: rs_dtls_parse_request,
+            parse_tc: rs_dtls_parse_response,
+            get_tx_count: rs_dtls_state_get_tx_count,
+            get_tx: rs_dtls_state_get_tx,
+            tx_free: rs_dtls_state_tx_free,
+        })
+    }
+
+    fn server_hello_body(version: u16) -> Vec<u8> {
+        let mut body = version.to_be_bytes().to_vec();
+        body.extend_from_slice(&[0xa5; 32]);
+        body.extend_from_slice(&[0x00, 0xc0, 0x2b, 0x00]);
+        body
+    }
+
+    #[test]
+    fn test_dtls_handshake() {
+        let mut h = harness();
+        let hello = record(CONTENT_HANDSHAKE, 0, &client_hello_fragment(0, b""));
+        assert!(h.feed(Direction::ToServer, &hello));
+        // retransmitted
+        assert!(h.feed(Direction::ToServer, &hello));
+        let verify = handshake_fragment(HANDSHAKE_HELLO_VERIFY_REQUEST, 0, b"\xfe\xff\x02\xc0\x0c",
+                                        0, 5);
+        assert!(h.feed(Direction::ToClient, &record(CONTENT_HANDSHAKE, 0, &verify)));
+        let hello = record(CONTENT_HANDSHAKE, 0, &client_hello_fragment(1, b"\xc0\x0c"));
+        assert!(h.feed(Direction::ToServer, &hello));
+
+        // the ServerHello in a record, the Certificate split over two
+        let server_hello = server_hello_body(DTLS_1_2);
+        let mut datagram = record(CONTENT_HANDSHAKE, 0,
+                                  &handshake_fragment(HANDSHAKE_SERVER_HELLO, 1, &server_hello,
+                                                      0, server_hello.len()));
+        let certificate = b"\x00\x00\x05\x00\x00\x02\x30\x00";
+        datagram.extend(record(CONTENT_HANDSHAKE, 0,
+                               &handshake_fragment(HANDSHAKE_CERTIFICATE, 2, certificate, 0, 4)));
+        assert!(h.feed(Direction::ToClient, &datagram));
+        let tx = h.get_tx::<DtlsTransaction>(0).unwrap();
+        assert_eq!(tx.version(), Some(DTLS_1_2));
+        assert!(tx.certificates.is_empty());
+        assert!(h.feed(Direction::ToClient,
+                       &record(CONTENT_HANDSHAKE, 0,
+                               &handshake_fragment(HANDSHAKE_CERTIFICATE, 2, certificate, 4, 4))));
+        assert!(h.feed(Direction::ToClient, &record(CONTENT_CHANGE_CIPHER_SPEC, 0, b"\x01")));
+
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<DtlsTransaction>(0).unwrap();
+        assert!(tx.hello_verify);
+        let hello = tx.client_hello.as_ref().unwrap();
+        assert_eq!(hello.sni, Some(b"example.com".to_vec()));
+        assert!(tx.complete);
+        // the DER of the certificate is not valid
+        assert!(tx.certificates.is_empty());
+    }
+
+    #[test]
+    fn test_dtls_reassembly() {
+        let mut reassembly = HandshakeReassembly::default();
+        let body = b"abcdefgh";
+        let second = handshake_fragment(HANDSHAKE_CERTIFICATE, 0, body, 4, 4);
+        let (_, second) = parse_handshake_fragment(&second).unwrap();
+        assert_eq!(reassembly.add(&second).ok(), Some(None));
+        let first = handshake_fragment(HANDSHAKE_CERTIFICATE, 0, body, 0, 6);
+        let (_, first) = parse_handshake_fragment(&first).unwrap();
+        assert_eq!(reassembly.add(&first).ok(), Some(Some((HANDSHAKE_CERTIFICATE, body.to_vec()))));
+        // a retransmission of the message
+        assert_eq!(reassembly.add(&first).ok(), Some(None));
+
+        let mismatch = handshake_fragment(HANDSHAKE_SERVER_HELLO, 1, body, 0, 2);
+        let (_, mismatch) = parse_handshake_fragment(&mismatch).unwrap();
+        assert_eq!(reassembly.add(&mismatch).ok(), Some(None));
+        let other = handshake_fragment(HANDSHAKE_CERTIFICATE, 1, body, 2, 2);
+        let (_, other) = parse_handshake_fragment(&other).unwrap();
+        match reassembly.add(&other) {
+            Err(DtlsEvent::InvalidHandshake) => {}
+            _ => panic!("expected an invalid handshake"),
+        }
+    }
+
+    #[test]
+    fn test_dtls_probe() {
+        unsafe {
+            ALPROTO_DTLS = 42;
+        }
+        let hello = record(CONTENT_HANDSHAKE, 0, &client_hello_fragment(0, b""));
+        assert_eq!(dtls_probe(&hello, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(dtls_probe(&hello, Direction::ToClient), ProbeResult::Flipped(42));
+        assert_eq!(dtls_probe(&hello[..10], Direction::ToServer), ProbeResult::Unknown);
+        let data = record(CONTENT_APPLICATION_DATA, 1, &[0; 32]);
+        assert_eq!(dtls_probe(&data, Direction::ToServer), ProbeResult::Failed);
+        // a QUIC Initial packet
+        let mut quic = vec![0xc3, 0x00, 0x00, 0x00, 0x01];
+        quic.extend_from_slice(&[0; 32]);
+        assert_eq!(dtls_probe(&quic, Direction::ToServer), ProbeResult::Failed);
+    }
+}
@@ -0,0 +1,68 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::dtls::DtlsTransaction;
+use super::parser::version_name;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::quic::fingerprint::ja3_hash;
+use crate::x509::log_certificates;
+
+fn log_ja3(js: &mut JsonBuilder, key: &str, ja3: &str) -> Result<(), JsonError> {
+    js.open_object(key)?;
+    js.set_string("string", ja3)?;
+    js.set_string("hash", &ja3_hash(ja3))?;
+    js.close()?;
+    Ok(())
+}
+
+fn log(tx: &DtlsTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("dtls")?;
+    let version = tx.version().unwrap_or(tx.record_version);
+    js.set_string("version", version_name(version))?;
+    if let Some(ref hello) = tx.client_hello {
+        if let Some(ref sni) = hello.sni {
+            js.set_string_from_bytes("sni", sni)?;
+        }
+        if !hello.alpn.is_empty() {
+            js.open_array("alpn")?;
+            for protocol in &hello.alpn {
+                js.append_string_from_bytes(protocol)?;
+            }
+            js.close()?;
+        }
+        log_ja3(js, "ja3", &hello.ja3())?;
+    }
+    js.set_bool("hello_verify", tx.hello_verify)?;
+    if let Some(ref hello) = tx.server_hello {
+        js.set_string("cipher_suite", &format!("{:04x}", hello.cipher))?;
+        log_ja3(js, "ja3s", &hello.ja3s())?;
+    }
+    log_certificates(js, "certificates", &tx.certificates)?;
+    if let Some((level, description)) = tx.alert {
+        js.open_object("alert")?;
+        js.set_uint("level", level as u64)?;
+        js.set_uint("description", description as u64)?;
+        js.close()?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_dtls_to_json(tx: &mut DtlsTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
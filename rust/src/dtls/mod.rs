@@ -0,0 +1,28 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! DTLS application layer, parser and logger module.
+//!
+//! A transaction is a handshake, from the hello of the client until the
+//! rest of the handshake of the server is encrypted. The hellos, the
+//! certificate chain of the server and the alerts sent in the clear are
+//! logged like the ones of TLS.
+
+pub mod detect;
+pub mod dtls;
+pub mod logger;
+pub mod parser;
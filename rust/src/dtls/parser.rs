@@ -0,0 +1,232 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! DTLS record layer and handshake decoding, RFC 6347. The records of
+//! epoch 0 are in the clear; the hellos differ from the ones of TLS by
+//! the cookie of the ClientHello.
+
+use crate::parsers_util::{length_data_be_u16, length_data_be_u8};
+use crate::quic::fingerprint::{client_hello, server_hello, ClientHello, ServerHello};
+use nom::bytes::streaming::take;
+use nom::combinator::complete;
+use nom::error::ErrorKind;
+use nom::multi::many0;
+use nom::number::streaming::{be_u16, be_u24, be_u8};
+use nom::{Err, IResult};
+
+pub const CONTENT_CHANGE_CIPHER_SPEC: u8 = 20;
+pub const CONTENT_ALERT: u8 = 21;
+pub const CONTENT_HANDSHAKE: u8 = 22;
+pub const CONTENT_APPLICATION_DATA: u8 = 23;
+
+pub const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+pub const HANDSHAKE_SERVER_HELLO: u8 = 2;
+pub const HANDSHAKE_HELLO_VERIFY_REQUEST: u8 = 3;
+pub const HANDSHAKE_CERTIFICATE: u8 = 11;
+
+pub const DTLS_1_0: u16 = 0xfeff;
+pub const DTLS_1_2: u16 = 0xfefd;
+pub const DTLS_1_3: u16 = 0xfefc;
+
+pub fn version_name(version: u16) -> &'static str {
+    match version {
+        DTLS_1_0 => "DTLS 1.0",
+        DTLS_1_2 => "DTLS 1.2",
+        DTLS_1_3 => "DTLS 1.3",
+        _ => "unknown",
+    }
+}
+
+/// The DTLS versions are the one's complement of the TLS ones, 0xfe00
+/// and up.
+pub fn is_dtls_version(version: u16) -> bool {
+    version >> 8 == 0xfe
+}
+
+/// The ciphertexts of DTLS 1.3 have a unified header, starting with the
+/// bits 001.
+pub fn is_unified_header(first: u8) -> bool {
+    first & 0xe0 == 0x20
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Record<'a> {
+    pub content_type: u8,
+    pub version: u16,
+    pub epoch: u16,
+    pub sequence: u64,
+    pub fragment: &'a [u8],
+}
+
+pub fn parse_record(i: &[u8]) -> IResult<&[u8], Record> {
+    let (i, content_type) = be_u8(i)?;
+    let (i, version) = be_u16(i)?;
+    let (i, epoch) = be_u16(i)?;
+    let (i, seq) = take(6usize)(i)?;
+    let sequence = seq.iter().fold(0u64, |acc, &b| acc << 8 | b as u64);
+    let (i, fragment) = length_data_be_u16(i)?;
+    Ok((i, Record { content_type, version, epoch, sequence, fragment }))
+}
+
+/// A fragment of a handshake message.
+#[derive(Debug, PartialEq)]
+pub struct HandshakeFragment<'a> {
+    pub msg_type: u8,
+    /// The length of the whole message.
+    pub length: u32,
+    pub message_seq: u16,
+    pub fragment_offset: u32,
+    pub data: &'a [u8],
+}
+
+pub fn parse_handshake_fragment(i: &[u8]) -> IResult<&[u8], HandshakeFragment> {
+    let (i, msg_type) = be_u8(i)?;
+    let (i, length) = be_u24(i)?;
+    let (i, message_seq) = be_u16(i)?;
+    let (i, fragment_offset) = be_u24(i)?;
+    let (i, fragment_length) = be_u24(i)?;
+    if fragment_offset as u64 + fragment_length as u64 > length as u64 {
+        return Err(Err::Error((i, ErrorKind::Verify)));
+    }
+    let (i, data) = take(fragment_length)(i)?;
+    Ok((i, HandshakeFragment { msg_type, length, message_seq, fragment_offset, data }))
+}
+
+/// Parse the handshake fragments of a record.
+pub fn parse_handshake_fragments(i: &[u8]) -> IResult<&[u8], Vec<HandshakeFragment>> {
+    many0(complete(parse_handshake_fragment))(i)
+}
+
+fn cipher_suites(i: &[u8]) -> IResult<&[u8], Vec<u16>> {
+    let (i, data) = length_data_be_u16(i)?;
+    let (_, ciphers) = many0(complete(be_u16))(data)?;
+    Ok((i, ciphers))
+}
+
+/// Parse the body of a ClientHello, with its cookie.
+pub fn parse_client_hello(i: &[u8]) -> IResult<&[u8], (ClientHello, Vec<u8>)> {
+    let (i, version) = be_u16(i)?;
+    let (i, _random) = take(32usize)(i)?;
+    let (i, _session_id) = length_data_be_u8(i)?;
+    let (i, cookie) = length_data_be_u8(i)?;
+    let (i, ciphers) = cipher_suites(i)?;
+    let (i, _compression) = length_data_be_u8(i)?;
+    let (i, ext) = if i.is_empty() { (i, &i[..0]) } else { length_data_be_u16(i)? };
+    Ok((i, (client_hello(version, ciphers, ext), cookie.to_vec())))
+}
+
+/// Parse the body of a ServerHello, the same as in TLS.
+pub fn parse_server_hello(i: &[u8]) -> IResult<&[u8], ServerHello> {
+    let (i, version) = be_u16(i)?;
+    let (i, _random) = take(32usize)(i)?;
+    let (i, _session_id) = length_data_be_u8(i)?;
+    let (i, cipher) = be_u16(i)?;
+    let (i, _compression) = be_u8(i)?;
+    let (i, ext) = if i.is_empty() { (i, &i[..0]) } else { length_data_be_u16(i)? };
+    Ok((i, server_hello(version, cipher, ext)))
+}
+
+fn certificate(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (i, len) = be_u24(i)?;
+    take(len)(i)
+}
+
+/// Parse the body of a Certificate message into the DER encoded
+/// certificates of the chain.
+pub fn parse_certificates(i: &[u8]) -> IResult<&[u8], Vec<&[u8]>> {
+    let (i, len) = be_u24(i)?;
+    let (i, list) = take(len)(i)?;
+    let (_, certs) = many0(complete(certificate))(list)?;
+    Ok((i, certs))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// A ClientHello with a cookie, a cipher and an SNI extension, in a
+    /// handshake fragment.
+    pub fn client_hello_fragment(message_seq: u16, cookie: &[u8]) -> Vec<u8> {
+        let mut body = vec![0xfe, 0xfd];
+        body.extend_from_slice(&[0x5a; 32]);
+        body.push(0);
+        body.push(cookie.len() as u8);
+        body.extend_from_slice(cookie);
+        body.extend_from_slice(&[0x00, 0x02, 0xc0, 0x2b, 0x01, 0x00]);
+        let sni = b"\x00\x00\x00\x10\x00\x0e\x00\x00\x0bexample.com";
+        body.extend_from_slice(&[0x00, sni.len() as u8]);
+        body.extend_from_slice(sni);
+        handshake_fragment(HANDSHAKE_CLIENT_HELLO, message_seq, &body, 0, body.len())
+    }
+
+    pub fn handshake_fragment(msg_type: u8, message_seq: u16, body: &[u8], offset: usize,
+                              len: usize) -> Vec<u8> {
+        let mut fragment = vec![msg_type, 0, (body.len() >> 8) as u8, body.len() as u8];
+        fragment.extend_from_slice(&message_seq.to_be_bytes());
+        fragment.extend_from_slice(&[0, (offset >> 8) as u8, offset as u8]);
+        fragment.extend_from_slice(&[0, (len >> 8) as u8, len as u8]);
+        fragment.extend_from_slice(&body[offset..offset + len]);
+        fragment
+    }
+
+    pub fn record(content_type: u8, epoch: u16, fragment: &[u8]) -> Vec<u8> {
+        let mut record = vec![content_type, 0xfe, 0xfd];
+        record.extend_from_slice(&epoch.to_be_bytes());
+        record.extend_from_slice(&[0, 0, 0, 0, 0, 1]);
+        record.extend_from_slice(&(fragment.len() as u16).to_be_bytes());
+        record.extend_from_slice(fragment);
+        record
+    }
+
+    #[test]
+    fn test_parse_record() {
+        let data = record(CONTENT_HANDSHAKE, 0, &client_hello_fragment(1, b"\xc0\x0c"));
+        let (rem, rec) = parse_record(&data).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(rec.content_type, CONTENT_HANDSHAKE);
+        assert_eq!(rec.version, DTLS_1_2);
+        assert_eq!(rec.sequence, 1);
+
+        let (_, fragments) = parse_handshake_fragments(rec.fragment).unwrap();
+        assert_eq!(fragments.len(), 1);
+        let fragment = &fragments[0];
+        assert_eq!(fragment.message_seq, 1);
+        assert_eq!(fragment.length as usize, fragment.data.len());
+
+        let (_, (hello, cookie)) = parse_client_hello(fragment.data).unwrap();
+        assert_eq!(cookie, b"\xc0\x0c");
+        assert_eq!(hello.version, DTLS_1_2);
+        assert_eq!(hello.ciphers, vec![0xc02b]);
+        assert_eq!(hello.sni, Some(b"example.com".to_vec()));
+
+        assert!(parse_record(&data[..10]).is_err());
+    }
+
+    #[test]
+    fn test_parse_handshake_fragment_overflow() {
+        // a fragment going past the length of its message
+        let fragment = b"\x01\x00\x00\x04\x00\x00\x00\x00\x02\x00\x00\x04\x00\x00\x00\x00";
+        assert!(parse_handshake_fragment(fragment).is_err());
+    }
+
+    #[test]
+    fn test_parse_certificates() {
+        let body = b"\x00\x00\x0a\x00\x00\x02\x30\x00\x00\x00\x02\x30\x01";
+        let (_, certs) = parse_certificates(body).unwrap();
+        assert_eq!(certs, vec![&b"\x30\x00"[..], &b"\x30\x01"[..]]);
+    }
+}
@@ -0,0 +1,194 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Map of the app-layer events of the Rust parsers.
+//!
+//! The events of every parser registered with `AppLayerRegisterParser`
+//! are enumerated through its `get_eventinfo_byid` function. The map can
+//! be dumped as JSON, or as `app-layer-event` rule stubs, and is checked
+//! for events sharing a name at registration, as such events can't be
+//! told apart by the rules.
+
+use crate::applayer::{GetEventInfoByIdFn, RustParser};
+use crate::core::{AppLayerEventType, APP_LAYER_EVENT_TYPE_PACKET};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use lazy_static::lazy_static;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::sync::Mutex;
+
+/// Event ids are stored as an u8 by the decoder events.
+const MAX_EVENT_ID: c_int = 255;
+
+#[derive(Debug, PartialEq)]
+pub struct EventInfo {
+    pub id: i32,
+    pub name: String,
+    pub event_type: AppLayerEventType,
+}
+
+lazy_static! {
+    static ref PROTOCOLS: Mutex<Vec<(String, Vec<EventInfo>)>> = Mutex::new(Vec::new());
+}
+
+/// Enumerate the events of a parser with its `get_eventinfo_byid`
+/// function.
+pub fn enumerate(by_id: GetEventInfoByIdFn) -> Vec<EventInfo> {
+    let mut events = Vec::new();
+    for id in 0..=MAX_EVENT_ID {
+        let mut name: *const c_char = std::ptr::null();
+        let mut event_type: AppLayerEventType = 0;
+        if unsafe { by_id(id, &mut name, &mut event_type) } != 0 || name.is_null() {
+            continue;
+        }
+        let name = unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned();
+        events.push(EventInfo { id, name, event_type });
+    }
+    events
+}
+
+/// Return the names shared by several events.
+pub fn collisions(events: &[EventInfo]) -> Vec<&str> {
+    let mut names = Vec::new();
+    for (i, event) in events.iter().enumerate() {
+        if events[..i].iter().any(|e| e.name == event.name)
+            && !names.contains(&event.name.as_str()) {
+            names.push(event.name.as_str());
+        }
+    }
+    names
+}
+
+fn add(proto: &str, events: Vec<EventInfo>) {
+    for name in collisions(&events) {
+        SCLogError!("{}: several app-layer events named {}", proto, name);
+    }
+    if let Ok(mut protocols) = PROTOCOLS.lock() {
+        if !protocols.iter().any(|(p, _)| p == proto) {
+            protocols.push((proto.to_string(), events));
+        }
+    }
+}
+
+/// Add the events of a parser being registered. Parsers registered for
+/// several IP protocols are added once.
+pub fn register(parser: &RustParser) {
+    let by_id = match parser.get_eventinfo_byid {
+        Some(by_id) => by_id,
+        None => return,
+    };
+    if parser.name.is_null() {
+        return;
+    }
+    let proto = unsafe { CStr::from_ptr(parser.name) }.to_string_lossy().into_owned();
+    add(&proto, enumerate(by_id));
+}
+
+/// Dump the map as a JSON object with an array of events per protocol.
+pub fn to_json() -> Result<JsonBuilder, JsonError> {
+    let mut js = JsonBuilder::new_object();
+    if let Ok(protocols) = PROTOCOLS.lock() {
+        for (proto, events) in protocols.iter() {
+            js.open_array(proto)?;
+            for event in events {
+                js.start_object()?;
+                js.set_uint("id", event.id as u64)?;
+                js.set_string("name", &event.name)?;
+                let event_type = if event.event_type == APP_LAYER_EVENT_TYPE_PACKET {
+                    "packet"
+                } else {
+                    "transaction"
+                };
+                js.set_string("type", event_type)?;
+                js.close()?;
+            }
+            js.close()?;
+        }
+    }
+    js.close()?;
+    Ok(js)
+}
+
+/// Generate an `app-layer-event` rule per event, with sids starting at
+/// `sid_base`.
+pub fn rule_stubs(sid_base: u32) -> String {
+    let mut rules = String::new();
+    let mut sid = sid_base;
+    if let Ok(protocols) = PROTOCOLS.lock() {
+        for (proto, events) in protocols.iter() {
+            for event in events {
+                rules.push_str(&format!(
+                    "alert {} any any -> any any (msg:\"SURICATA {} {}\"; \
+                     app-layer-event:{}.{}; classtype:protocol-command-decode; \
+                     sid:{}; rev:1;)\n",
+                    proto, proto.to_uppercase(), event.name.replace('_', " "),
+                    proto, event.name, sid));
+                sid += 1;
+            }
+        }
+    }
+    rules
+}
+
+/// Return the events of all the registered Rust parsers as a JSON object,
+/// to be freed with `jb_free`. Returns NULL on error.
+#[no_mangle]
+pub extern "C" fn rs_app_layer_event_map() -> *mut JsonBuilder {
+    match to_json() {
+        Ok(js) => Box::into_raw(Box::new(js)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::applayer::AppLayerEvent;
+    use crate::core::APP_LAYER_EVENT_TYPE_TRANSACTION;
+
+    #[derive(AppLayerEvent)]
+    enum TestEvent {
+        MalformedData,
+        InvalidHeader,
+    }
+
+    #[test]
+    fn test_enumerate() {
+        let events = enumerate(TestEvent::get_event_info_by_id);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name, "malformed_data");
+        assert_eq!(events[1].id, 1);
+        assert_eq!(events[1].event_type, APP_LAYER_EVENT_TYPE_TRANSACTION);
+        assert!(collisions(&events).is_empty());
+
+        add("test-eventmap", events);
+        assert!(rule_stubs(1000).contains(
+            "app-layer-event:test-eventmap.invalid_header; \
+             classtype:protocol-command-decode; sid:1001; rev:1;)"));
+    }
+
+    #[test]
+    fn test_collisions() {
+        let event = |id, name: &str| EventInfo {
+            id,
+            name: name.to_string(),
+            event_type: 1,
+        };
+        let events = vec![event(0, "a"), event(1, "b"), event(2, "a"), event(3, "a")];
+        assert_eq!(collisions(&events), vec!["a"]);
+    }
+}
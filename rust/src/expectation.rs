@@ -0,0 +1,104 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Expected flows, see app-layer-expectation.c.
+//!
+//! A parser learning about a parallel flow, such as a data channel, can
+//! declare it with `create`. The next flow between the same hosts and
+//! matching the ports is then assigned the app-layer protocol of the
+//! expectation, and gets the data of the expectation attached, for its
+//! parser to `take` it.
+//!
+//! ```ignore
+//! // in the parser of the control flow
+//! expectation::create(flow, STREAM_TOSERVER, 0, port, ALPROTO_X, Transfer { .. });
+//!
+//! // in the parser of the expected flow
+//! if let Some(transfer) = expectation::take::<Transfer>(flow) { .. }
+//! ```
+
+use crate::core::{AppProto, Flow};
+use crate::flowstorage::{FlowGetStorageById, FlowSetStorageById, FlowStorageId};
+use std::os::raw::{c_int, c_void};
+
+extern {
+    fn AppLayerExpectationCreate(f: *mut Flow, direction: c_int, src: u16, dst: u16,
+                                 alproto: AppProto, data: *mut c_void) -> c_int;
+    fn AppLayerExpectationGetFlowId() -> FlowStorageId;
+}
+
+/// Expectation data as laid out for the C side, which frees it through
+/// the function at its start.
+#[repr(C)]
+struct Expectation<T> {
+    free: unsafe extern "C" fn(*mut c_void),
+    data: T,
+}
+
+unsafe extern "C" fn expectation_free<T>(ptr: *mut c_void) {
+    std::mem::drop(Box::from_raw(ptr as *mut Expectation<T>));
+}
+
+/// Declare a flow expected between the hosts of `flow`, from port `src`
+/// to port `dst` (0 for any), carrying data in `direction`
+/// (STREAM_TOSERVER and/or STREAM_TOCLIENT) of the new flow. The new
+/// flow gets `alproto` and `data`. Returns false if the expectation
+/// could not be created.
+pub fn create<T>(flow: *const Flow, direction: u8, src: u16, dst: u16, alproto: AppProto,
+                 data: T) -> bool {
+    if flow.is_null() {
+        return false;
+    }
+    let expectation = Box::into_raw(Box::new(Expectation {
+        free: expectation_free::<T>,
+        data,
+    }));
+    let r = unsafe {
+        AppLayerExpectationCreate(flow as *mut Flow, direction as c_int, src, dst, alproto,
+                                  expectation as *mut c_void)
+    };
+    if r < 0 {
+        // not taken over by the C side
+        std::mem::drop(unsafe { Box::from_raw(expectation) });
+        return false;
+    }
+    true
+}
+
+/// Take the data of the expectation `flow` was created from.
+///
+/// # Safety
+///
+/// The data must have been attached with `create::<T>`, which is the
+/// case when the expectation was created by the parser of the app-layer
+/// protocol of `flow` with the same type.
+pub unsafe fn take<T>(flow: *const Flow) -> Option<T> {
+    if flow.is_null() {
+        return None;
+    }
+    let id = AppLayerExpectationGetFlowId();
+    if id.id < 0 {
+        return None;
+    }
+    let ptr = FlowGetStorageById(flow as *mut Flow, id);
+    if ptr.is_null() {
+        return None;
+    }
+    FlowSetStorageById(flow as *mut Flow, id, std::ptr::null_mut());
+    let expectation = Box::from_raw(ptr as *mut Expectation<T>);
+    Some(expectation.data)
+}
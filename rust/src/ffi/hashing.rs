@@ -19,10 +19,20 @@ use digest::Digest;
 use md5::Md5;
 use sha1::Sha1;
 use sha2::Sha256;
+use sha3::Sha3_256;
 use std::os::raw::c_char;
+use crate::fuzzyhash::ssdeep::Ssdeep;
+use crate::fuzzyhash::tlsh::{Tlsh, TLSH_HEX_LEN};
 
 pub const SC_SHA1_LEN: usize = 20;
 pub const SC_SHA256_LEN: usize = 32;
+pub const SC_SHA3_256_LEN: usize = 32;
+
+// Buffer size for a ssdeep digest string, including the trailing NUL.
+pub const SC_SSDEEP_MAX_LEN: usize = 148;
+
+// Length of a TLSH hex string, including the trailing NUL.
+pub const SC_TLSH_LEN: usize = TLSH_HEX_LEN + 1;
 
 // Length of a MD5 hex string, not including a trailing NUL.
 pub const SC_MD5_HEX_LEN: usize = 32;
@@ -211,6 +221,114 @@ pub unsafe extern "C" fn SCMd5HashBufferToHex(
     output[output.len() - 1] = 0;
 }
 
+// Start of SHA3-256 C bindings.
+
+pub struct SCSha3_256(Sha3_256);
+
+#[no_mangle]
+pub extern "C" fn SCSha3_256New() -> *mut SCSha3_256 {
+    let hasher = Box::new(SCSha3_256(Sha3_256::new()));
+    Box::into_raw(hasher)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCSha3_256Update(hasher: &mut SCSha3_256, bytes: *const u8, len: u32) {
+    update(&mut hasher.0, bytes, len);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCSha3_256Finalize(hasher: &mut SCSha3_256, out: *mut u8, len: u32) {
+    let hasher: Box<SCSha3_256> = Box::from_raw(hasher);
+    finalize(hasher.0, out, len);
+}
+
+/// Free an unfinalized Sha3-256 context.
+#[no_mangle]
+pub unsafe extern "C" fn SCSha3_256Free(hasher: &mut SCSha3_256) {
+    // Drop.
+    let _: Box<SCSha3_256> = Box::from_raw(hasher);
+}
+
+// Start of ssdeep C bindings.
+
+pub struct SCSsdeep(Ssdeep);
+
+#[no_mangle]
+pub extern "C" fn SCSsdeepNew() -> *mut SCSsdeep {
+    let hasher = Box::new(SCSsdeep(Ssdeep::new()));
+    Box::into_raw(hasher)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCSsdeepUpdate(hasher: &mut SCSsdeep, bytes: *const u8, len: u32) {
+    let data = std::slice::from_raw_parts(bytes, len as usize);
+    hasher.0.update(data);
+}
+
+/// Finalize the ssdeep context to a NUL terminated digest string.
+///
+/// Consumes the hash context. Returns false if no digest could be
+/// produced or it does not fit in the output buffer.
+#[no_mangle]
+pub unsafe extern "C" fn SCSsdeepFinalize(hasher: &mut SCSsdeep, out: *mut c_char, len: u32) -> bool {
+    let hasher: Box<SCSsdeep> = Box::from_raw(hasher);
+    copy_digest_string(hasher.0.finalize(), out, len)
+}
+
+/// Free an unfinalized ssdeep context.
+#[no_mangle]
+pub unsafe extern "C" fn SCSsdeepFree(hasher: &mut SCSsdeep) {
+    // Drop.
+    let _: Box<SCSsdeep> = Box::from_raw(hasher);
+}
+
+// Start of TLSH C bindings.
+
+pub struct SCTlsh(Tlsh);
+
+#[no_mangle]
+pub extern "C" fn SCTlshNew() -> *mut SCTlsh {
+    let hasher = Box::new(SCTlsh(Tlsh::new()));
+    Box::into_raw(hasher)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn SCTlshUpdate(hasher: &mut SCTlsh, bytes: *const u8, len: u32) {
+    let data = std::slice::from_raw_parts(bytes, len as usize);
+    hasher.0.update(data);
+}
+
+/// Finalize the TLSH context to a NUL terminated hex string.
+///
+/// Consumes the hash context. Returns false if the data was too short
+/// or too uniform for a valid hash.
+#[no_mangle]
+pub unsafe extern "C" fn SCTlshFinalize(hasher: &mut SCTlsh, out: *mut c_char, len: u32) -> bool {
+    let hasher: Box<SCTlsh> = Box::from_raw(hasher);
+    copy_digest_string(hasher.0.finalize(), out, len)
+}
+
+/// Free an unfinalized TLSH context.
+#[no_mangle]
+pub unsafe extern "C" fn SCTlshFree(hasher: &mut SCTlsh) {
+    // Drop.
+    let _: Box<SCTlsh> = Box::from_raw(hasher);
+}
+
+unsafe fn copy_digest_string(digest: Option<String>, out: *mut c_char, len: u32) -> bool {
+    let digest = match digest {
+        Some(digest) => digest,
+        None => return false,
+    };
+    if digest.len() >= len as usize {
+        return false;
+    }
+    let output = std::slice::from_raw_parts_mut(out as *mut u8, len as usize);
+    output[..digest.len()].copy_from_slice(digest.as_bytes());
+    output[digest.len()] = 0;
+    return true;
+}
+
 // Functions that are generic over Digest. For the most part the C bindings are
 // just wrappers around these.
 
@@ -18,16 +18,21 @@
 use std::ptr;
 use std::os::raw::{c_void};
 
+use crate::applayer::{cast_mut, FfiType};
 use crate::core::*;
 
 // Defined in util-file.h
 extern {
     pub fn FileFlowToFlags(flow: *const Flow, flags: u8) -> u16;
 }
+pub const FILE_TRUNCATED:     u16 = BIT_U16!(0);
 pub const FILE_USE_DETECT:    u16 = BIT_U16!(13);
 
 
 // Generic file structure, so it can be used by different protocols
+//
+// The containers are handed to the engine through `get_files`, and
+// their files are freed when the `Files` are dropped with the state.
 #[derive(Debug, Default)]
 pub struct Files {
     pub files_ts: FileContainer,
@@ -45,6 +50,76 @@ impl Files {
             (&mut self.files_tc, self.flags_tc)
         }
     }
+
+    /// Return the container for a direction.
+    pub fn container(&mut self, direction: Direction) -> &mut FileContainer {
+        match direction {
+            Direction::ToServer => &mut self.files_ts,
+            Direction::ToClient => &mut self.files_tc,
+        }
+    }
+
+    /// Set the flags for the files opened in a direction.
+    pub fn set_flags(&mut self, direction: Direction, flags: u16) {
+        SCLogDebug!("direction: {:?}, flags: {}", direction, flags);
+        match direction {
+            Direction::ToServer => self.flags_ts = flags,
+            Direction::ToClient => self.flags_tc = flags,
+        }
+    }
+
+    /// Set the flags for the files opened in a direction from the flow
+    /// settings. To be called by the parsers before handling new data.
+    pub unsafe fn update_flags(&mut self, flow: *const Flow, direction: Direction) {
+        if flow.is_null() {
+            return;
+        }
+        let flags = FileFlowToFlags(flow, direction.into());
+        self.set_flags(direction, flags | FILE_USE_DETECT);
+    }
+
+    /// Open a new file, identified by `track_id` in later calls.
+    pub fn open(&mut self, cfg: &'static SuricataFileContext, direction: Direction,
+            track_id: u32, name: &[u8]) -> bool {
+        let (files, flags) = self.get(direction.into());
+        files.file_open(cfg, &track_id, name, flags) == 0
+    }
+
+    /// Append data to an open file.
+    pub fn append(&mut self, direction: Direction, track_id: u32, data: &[u8]) -> bool {
+        self.container(direction).file_append(&track_id, data, false) == 0
+    }
+
+    /// Close a file once all its data has been appended.
+    pub fn close(&mut self, direction: Direction, track_id: u32) -> bool {
+        let (files, flags) = self.get(direction.into());
+        files.file_close(&track_id, flags) == 0
+    }
+
+    /// Close a file of which data is missing.
+    pub fn close_truncated(&mut self, direction: Direction, track_id: u32) -> bool {
+        let (files, flags) = self.get(direction.into());
+        files.file_close(&track_id, flags | FILE_TRUNCATED) == 0
+    }
+
+    /// Set the transaction id of the last opened file.
+    pub fn set_tx(&mut self, direction: Direction, tx_id: u64) {
+        self.container(direction).file_set_txid_on_last_file(tx_id);
+    }
+}
+
+/// A parser state storing its files in `Files`.
+pub trait FilesState {
+    fn files(&mut self) -> &mut Files;
+}
+
+/// `get_files` function for any parser state implementing `FilesState`,
+/// to be registered as `get_files: Some(state_get_files::<S>)`.
+pub unsafe extern "C" fn state_get_files<S: FilesState + FfiType>(
+    state: *mut c_void, direction: u8) -> *mut FileContainer
+{
+    let state: &mut S = cast_mut(state);
+    state.files().container(direction.into()) as *mut FileContainer
 }
 
 pub struct File;
@@ -0,0 +1,159 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Typed access to the flow storage.
+//!
+//! A parser can attach an object to a flow outside of its app-layer
+//! state, for example to share it with a C detection module, by
+//! registering a `FlowStorage` from its register function. Registration
+//! has to happen before the storage API is finalized, which is the case
+//! for the parser register functions:
+//!
+//! ```ignore
+//! static NTP_PEERS: FlowStorage<NTPPeers> = FlowStorage::new();
+//!
+//! // in the parser registration function
+//! NTP_PEERS.register("ntp-peers");
+//!
+//! // in the parser
+//! let peers = NTP_PEERS.get_or_insert_with(flow, NTPPeers::new);
+//! ```
+//!
+//! The object is freed with the flow.
+
+use crate::common::static_cstr;
+use crate::core::Flow;
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct FlowStorageId {
+    pub(crate) id: c_int,
+}
+
+extern {
+    fn FlowStorageRegister(name: *const c_char, size: c_uint,
+                           alloc: Option<unsafe extern "C" fn(c_uint) -> *mut c_void>,
+                           free: Option<unsafe extern "C" fn(*mut c_void)>) -> FlowStorageId;
+    pub(crate) fn FlowGetStorageById(flow: *mut Flow, id: FlowStorageId) -> *mut c_void;
+    pub(crate) fn FlowSetStorageById(flow: *mut Flow, id: FlowStorageId, ptr: *mut c_void) -> c_int;
+}
+
+unsafe extern "C" fn flow_storage_free<T>(ptr: *mut c_void) {
+    if !ptr.is_null() {
+        std::mem::drop(Box::from_raw(ptr as *mut T));
+    }
+}
+
+/// A flow storage slot holding a `T` per flow.
+pub struct FlowStorage<T> {
+    /// Id of the storage, -1 until registered.
+    id: AtomicI32,
+    _marker: PhantomData<T>,
+}
+
+impl<T> FlowStorage<T> {
+    pub const fn new() -> FlowStorage<T> {
+        FlowStorage {
+            id: AtomicI32::new(-1),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Send> FlowStorage<T> {
+    /// Register the storage under `name`. Returns false if it could not
+    /// be registered, in which case the storage stays empty.
+    pub fn register(&self, name: &str) -> bool {
+        let id = unsafe {
+            FlowStorageRegister(static_cstr(name), std::mem::size_of::<*mut c_void>() as c_uint,
+                                None, Some(flow_storage_free::<T>))
+        };
+        if id.id < 0 {
+            SCLogError!("failed to register flow storage {}", name);
+            return false;
+        }
+        self.id.store(id.id, Ordering::Relaxed);
+        true
+    }
+
+    fn id(&self) -> Option<FlowStorageId> {
+        let id = self.id.load(Ordering::Relaxed);
+        if id < 0 {
+            None
+        } else {
+            Some(FlowStorageId { id })
+        }
+    }
+
+    fn ptr(&self, flow: &Flow) -> *mut T {
+        match self.id() {
+            Some(id) => unsafe {
+                FlowGetStorageById(flow as *const Flow as *mut Flow, id) as *mut T
+            },
+            None => std::ptr::null_mut(),
+        }
+    }
+
+    pub fn get<'a>(&self, flow: &'a Flow) -> Option<&'a T> {
+        unsafe { self.ptr(flow).as_ref() }
+    }
+
+    pub fn get_mut<'a>(&self, flow: &'a mut Flow) -> Option<&'a mut T> {
+        unsafe { self.ptr(flow).as_mut() }
+    }
+
+    /// Store `value` in the flow, freeing the value stored before.
+    /// Returns false if the storage is not registered.
+    pub fn set(&self, flow: &mut Flow, value: T) -> bool {
+        let _ = self.take(flow);
+        match self.id() {
+            Some(id) => {
+                let ptr = Box::into_raw(Box::new(value));
+                unsafe { FlowSetStorageById(flow, id, ptr as *mut c_void) };
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the value stored in the flow and return it.
+    pub fn take(&self, flow: &mut Flow) -> Option<T> {
+        let ptr = self.ptr(flow);
+        if ptr.is_null() {
+            return None;
+        }
+        let id = self.id()?;
+        unsafe {
+            FlowSetStorageById(flow, id, std::ptr::null_mut());
+            Some(*Box::from_raw(ptr))
+        }
+    }
+
+    /// Get the value stored in the flow, storing the result of `f` first
+    /// if there is none. Returns None if the storage is not registered.
+    pub fn get_or_insert_with<'a, F>(&self, flow: &'a mut Flow, f: F) -> Option<&'a mut T>
+        where F: FnOnce() -> T
+    {
+        if self.ptr(flow).is_null() && !self.set(flow, f()) {
+            return None;
+        }
+        self.get_mut(flow)
+    }
+}
@@ -0,0 +1,115 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Buffers on the commands of the client: the upper cased verb, and
+//! its argument.
+
+use super::ftp::{FtpTransaction, ALPROTO_FTP};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_FTP_COMMAND_BUFFER_ID: c_int = 0;
+static mut G_FTP_COMMAND_DATA_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn ftp_tx_get_command(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut FtpTransaction = cast_mut(tx);
+    match tx.command {
+        Some(ref command) => {
+            *buf = command.as_ptr();
+            *len = command.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn ftp_tx_get_command_data(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut FtpTransaction = cast_mut(tx);
+    if tx.command.is_none() || tx.argument.is_empty() {
+        return false;
+    }
+    *buf = tx.argument.as_ptr();
+    *len = tx.argument.len() as u32;
+    true
+}
+
+unsafe extern "C" fn ftp_command_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ftp_tx_get_command)
+}
+
+unsafe extern "C" fn ftp_command_data_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ftp_tx_get_command_data)
+}
+
+unsafe extern "C" fn ftp_command_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_FTP, G_FTP_COMMAND_BUFFER_ID)
+}
+
+unsafe extern "C" fn ftp_command_data_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_FTP, G_FTP_COMMAND_DATA_BUFFER_ID)
+}
+
+/// Register the FTP keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "ftp.command",
+        desc: "sticky buffer to match on the FTP command",
+        url: "/rules/ftp-keywords.html#ftp-command",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ftp_command_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_FTP_COMMAND_BUFFER_ID = helper_buffer_mpm_register(
+            "ftp.command", "ftp command", ALPROTO_FTP, false, true, 1,
+            ftp_command_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "ftp.command_data",
+        desc: "sticky buffer to match on the argument of the FTP command",
+        url: "/rules/ftp-keywords.html#ftp-command-data",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ftp_command_data_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_FTP_COMMAND_DATA_BUFFER_ID = helper_buffer_mpm_register(
+            "ftp.command_data", "ftp command data", ALPROTO_FTP, false, true, 1,
+            ftp_command_data_get_data);
+    }
+}
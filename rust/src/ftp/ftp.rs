@@ -0,0 +1,824 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use super::{ftp_epsv_response, ftp_pasv_response};
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+use std::net::Ipv4Addr;
+use std::os::raw::c_int;
+
+declare_counters!(ftp_stats, "app_layer.ftp", {
+    commands,
+    replies,
+    transfers,
+    tls_upgrades,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum FtpEvent {
+    CleartextCredentials,
+    LineTooLong,
+    InvalidPortCommand,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static FTP_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static FTP_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum length of a command or reply line. Longer lines are
+/// discarded up to their end.
+const FTP_MAX_LINE_LEN: usize = 4096;
+
+/// Maximum number of reply lines kept per transaction.
+const FTP_MAX_REPLY_LINES: usize = 64;
+
+pub static mut ALPROTO_FTP: AppProto = ALPROTO_UNKNOWN;
+
+// Defined in app-layer-ftp.h
+extern {
+    fn FTPDataExpectationCreate(flow: *const Flow, direction: u8, dyn_port: u16,
+                                file_name: *const u8, file_name_len: u32,
+                                store: bool) -> c_int;
+}
+
+#[derive(Debug)]
+pub struct FtpReplyLine {
+    /// The code the line starts with, if any.
+    pub code: Option<u16>,
+    pub text: Vec<u8>,
+}
+
+/// A command and its reply. The greeting of the server is a
+/// transaction without a command.
+#[derive(Debug)]
+pub struct FtpTransaction {
+    id: u64,
+    pub command: Option<String>,
+    pub argument: Vec<u8>,
+    pub replies: Vec<FtpReplyLine>,
+    /// The data connection port set up by a PORT, EPRT, PASV or EPSV
+    /// command.
+    pub dyn_port: Option<u16>,
+    /// Whether the client opens the data connection (PORT and EPRT).
+    pub active: bool,
+    /// The address of a PORT command.
+    pub port_address: Option<Ipv4Addr>,
+    /// Set once the final reply is received.
+    pub done: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for FtpTransaction {}
+
+impl FtpTransaction {
+    fn new(id: u64, command: Option<String>, argument: &[u8]) -> FtpTransaction {
+        FtpTransaction {
+            id,
+            command,
+            argument: argument.to_vec(),
+            replies: Vec::new(),
+            dyn_port: None,
+            active: false,
+            port_address: None,
+            done: false,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: FtpEvent) {
+        stats_incr!(ftp_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    fn is_command(&self, verb: &str) -> bool {
+        match self.command {
+            Some(ref command) => command == verb,
+            None => false,
+        }
+    }
+}
+
+impl Drop for FtpTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for FtpTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = FtpEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+pub struct FtpState {
+    transactions: TxTable<FtpTransaction>,
+    /// The user of the last USER command.
+    user: Option<Vec<u8>>,
+    /// The data connection port negotiated for the next transfer.
+    dyn_port: Option<u16>,
+    active: bool,
+    /// The code of the multiline reply being received.
+    multiline: Option<u16>,
+    /// Set while the rest of an overlong line is discarded, per
+    /// direction.
+    discard_ts: bool,
+    discard_tc: bool,
+}
+
+unsafe impl FfiType for FtpState {}
+
+impl txtable::State for FtpState {
+    type Tx = FtpTransaction;
+
+    fn tx_table(&self) -> &TxTable<FtpTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<FtpTransaction> {
+        &mut self.transactions
+    }
+}
+
+/// Whether a user name is one of the usual anonymous logins, for which
+/// the password is not a secret.
+fn is_anonymous(user: &[u8]) -> bool {
+    user.eq_ignore_ascii_case(b"anonymous") || user.eq_ignore_ascii_case(b"ftp")
+}
+
+impl FtpState {
+    pub fn new() -> FtpState {
+        FtpState {
+            transactions: TxTable::with_limit(&FTP_TX_LIMIT).with_gauges(&FTP_GAUGES),
+            user: None,
+            dyn_port: None,
+            active: false,
+            multiline: None,
+            discard_ts: false,
+            discard_tc: false,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    /// Expect the data connection of a RETR or STOR, on the port
+    /// negotiated by the last PORT, EPRT, PASV or EPSV command.
+    fn expect_transfer(&mut self, flow: *const Flow, store: bool, file_name: &[u8]) {
+        let dyn_port = match self.dyn_port.take() {
+            Some(port) => port,
+            None => return,
+        };
+        let active = std::mem::replace(&mut self.active, false);
+        if file_name.is_empty() || flow.is_null() {
+            return;
+        }
+        // the direction the file is sent in on the data connection, which
+        // is opened by the server in active mode
+        let direction = if active == store {
+            core::STREAM_TOCLIENT
+        } else {
+            core::STREAM_TOSERVER
+        };
+        let ret = unsafe {
+            FTPDataExpectationCreate(flow, direction, dyn_port, file_name.as_ptr(),
+                                     file_name.len() as u32, store)
+        };
+        if ret == 0 {
+            stats_incr!(ftp_stats::transfers);
+        }
+        SCLogDebug!("expectation on port {} {}", dyn_port,
+                    if ret == 0 { "created" } else { "failed" });
+    }
+
+    fn handle_command(&mut self, flow: *const Flow, line: &[u8]) {
+        let command = match parse_command(line) {
+            Some(command) => command,
+            None => {
+                SCLogDebug!("not a command: {:?}", line);
+                return;
+            }
+        };
+        stats_incr!(ftp_stats::commands);
+        let mut tx = FtpTransaction::new(self.transactions.next_id(),
+                                         Some(command.verb.clone()), command.argument);
+        match command.verb.as_str() {
+            "USER" => {
+                self.user = Some(command.argument.to_vec());
+            }
+            "PASS" => {
+                let anonymous = match self.user {
+                    Some(ref user) => is_anonymous(user),
+                    None => false,
+                };
+                if !command.argument.is_empty() && !anonymous {
+                    tx.set_event(FtpEvent::CleartextCredentials);
+                }
+            }
+            "PORT" => match parse_port_argument(command.argument) {
+                Some((address, _)) => tx.port_address = Some(address),
+                None => tx.set_event(FtpEvent::InvalidPortCommand),
+            },
+            "EPRT" => {
+                if parse_eprt_argument(command.argument).is_none() {
+                    tx.set_event(FtpEvent::InvalidPortCommand);
+                }
+            }
+            "RETR" | "STOR" => {
+                self.expect_transfer(flow, command.verb == "STOR", command.argument);
+            }
+            _ => {}
+        }
+        self.transactions.push(tx);
+    }
+
+    /// Apply the final reply to a command.
+    fn handle_final_reply(&mut self, flow: *const Flow, code: u16, line: &[u8]) {
+        let tx = match self.transactions.iter_mut().find(|tx| !tx.done) {
+            Some(tx) => tx,
+            None => return,
+        };
+        let success = code >= 200 && code < 300;
+        if tx.is_command("AUTH") && code == 234 {
+            if tx.argument.eq_ignore_ascii_case(b"TLS") || tx.argument.eq_ignore_ascii_case(b"SSL") {
+                stats_incr!(ftp_stats::tls_upgrades);
+                core::sc_app_layer_request_protocol_tls_upgrade(flow);
+            }
+        } else if (tx.is_command("PORT") || tx.is_command("EPRT")) && success {
+            let port = if tx.is_command("PORT") {
+                parse_port_argument(&tx.argument).map(|(_, port)| port)
+            } else {
+                parse_eprt_argument(&tx.argument)
+            };
+            if let Some(port) = port {
+                SCLogDebug!("FTP active mode: dynamic port {}", port);
+                tx.dyn_port = Some(port);
+                tx.active = true;
+                self.dyn_port = Some(port);
+                self.active = true;
+            }
+        } else if tx.is_command("PASV") && code == 227 {
+            if let Ok((_, port)) = ftp_pasv_response(line) {
+                SCLogDebug!("FTP passive mode (v4): dynamic port {}", port);
+                tx.dyn_port = Some(port);
+                self.dyn_port = Some(port);
+                self.active = false;
+            }
+        } else if tx.is_command("EPSV") && code == 229 {
+            if let Ok((_, port)) = ftp_epsv_response(line) {
+                SCLogDebug!("FTP passive mode (v6): dynamic port {}", port);
+                tx.dyn_port = Some(port);
+                self.dyn_port = Some(port);
+                self.active = false;
+            }
+        }
+        // a preliminary reply keeps the command open for the next one
+        if !is_preliminary(code) {
+            tx.done = true;
+        }
+    }
+
+    fn handle_reply(&mut self, flow: *const Flow, line: &[u8]) {
+        let (code, text, last) = match (parse_reply_line(line), self.multiline) {
+            (ReplyLine::Code { code, last, text }, Some(multiline)) => {
+                (Some(code), text, last && code == multiline)
+            }
+            (ReplyLine::Code { code, last, text }, None) => {
+                if !last {
+                    self.multiline = Some(code);
+                }
+                (Some(code), text, last)
+            }
+            (ReplyLine::Text(text), multiline) => (None, text, multiline.is_none()),
+        };
+        if !self.transactions.iter().any(|tx| !tx.done) {
+            // the greeting, or a reply without a command
+            let tx = FtpTransaction::new(self.transactions.next_id(), None, &[]);
+            self.transactions.push(tx);
+        }
+        if let Some(tx) = self.transactions.iter_mut().find(|tx| !tx.done) {
+            if tx.replies.len() < FTP_MAX_REPLY_LINES {
+                tx.replies.push(FtpReplyLine { code, text: text.to_vec() });
+            }
+        }
+        if last {
+            stats_incr!(ftp_stats::replies);
+            self.multiline = None;
+            match code {
+                Some(code) => self.handle_final_reply(flow, code, line),
+                None => {
+                    if let Some(tx) = self.transactions.iter_mut().find(|tx| !tx.done) {
+                        tx.done = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_line(&mut self, flow: *const Flow, line: &[u8], direction: Direction) {
+        let line = match line.last() {
+            Some(b'\r') => &line[..line.len() - 1],
+            _ => line,
+        };
+        match direction {
+            Direction::ToServer => {
+                if !line.is_empty() {
+                    self.handle_command(flow, line);
+                }
+            }
+            Direction::ToClient => self.handle_reply(flow, line),
+        }
+    }
+
+    /// Flag an overlong line on the current transaction, or on a new one
+    /// if there is none.
+    fn set_line_too_long(&mut self, direction: Direction) {
+        if direction == Direction::ToClient {
+            if let Some(tx) = self.transactions.iter_mut().find(|tx| !tx.done) {
+                tx.set_event(FtpEvent::LineTooLong);
+                return;
+            }
+        }
+        let mut tx = FtpTransaction::new(self.transactions.next_id(), None, &[]);
+        tx.done = true;
+        tx.set_event(FtpEvent::LineTooLong);
+        self.transactions.push(tx);
+    }
+
+    fn parse(&mut self, flow: *const Flow, input: &[u8], direction: Direction) -> AppLayerResult {
+        let mut current = input;
+        while !current.is_empty() {
+            let discard = match direction {
+                Direction::ToServer => &mut self.discard_ts,
+                Direction::ToClient => &mut self.discard_tc,
+            };
+            match current.iter().position(|&b| b == b'\n') {
+                Some(pos) if *discard => {
+                    *discard = false;
+                    current = &current[pos + 1..];
+                }
+                Some(pos) => {
+                    self.handle_line(flow, &current[..pos], direction);
+                    current = &current[pos + 1..];
+                }
+                None if *discard => {
+                    return AppLayerResult::ok();
+                }
+                None => {
+                    if current.len() >= FTP_MAX_LINE_LEN {
+                        *discard = true;
+                        self.set_line_too_long(direction);
+                        return AppLayerResult::ok();
+                    }
+                    let consumed = input.len() - current.len();
+                    return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                           current.len() + 1);
+                }
+            }
+        }
+        AppLayerResult::ok()
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn rs_ftp_state_new(_orig_state: *mut std::os::raw::c_void,
+                                   _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = FtpState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut FtpState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut FtpState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_parse_request(flow: *const Flow,
+                                              state: *mut std::os::raw::c_void,
+                                              pstate: *mut std::os::raw::c_void,
+                                              input: *const u8,
+                                              input_len: u32,
+                                              _data: *const std::os::raw::c_void,
+                                              _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut FtpState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(flow, buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_parse_response(flow: *const Flow,
+                                               state: *mut std::os::raw::c_void,
+                                               pstate: *mut std::os::raw::c_void,
+                                               input: *const u8,
+                                               input_len: u32,
+                                               _data: *const std::os::raw::c_void,
+                                               _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut FtpState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(flow, buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_state_get_tx(state: *mut std::os::raw::c_void,
+                                             tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut FtpState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut FtpState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// A command is complete once parsed, its reply once the final reply
+/// is received.
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                        direction: u8) -> std::os::raw::c_int {
+    let tx: &mut FtpTransaction = cast_mut(tx);
+    if Direction::from(direction) == Direction::ToServer || tx.done {
+        1
+    } else {
+        0
+    }
+}
+
+/// The address of the PORT command of a transaction, in network byte
+/// order, for the ftpbounce keyword.
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_tx_get_port_ipv4(tx: &FtpTransaction, address: *mut u32) -> bool {
+    match tx.port_address {
+        Some(port_address) => {
+            *address = u32::from_ne_bytes(port_address.octets());
+            true
+        }
+        None => false,
+    }
+}
+
+export_tx_detect_state!(rs_ftp_state_get_tx_detect_state,
+                        rs_ftp_state_set_tx_detect_state, FtpTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_state_get_events(tx: *mut std::os::raw::c_void)
+                                                 -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut FtpTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_ftp_get_tx_data, FtpTransaction);
+
+fn ftp_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    FTP_GAUGES.log(js)?;
+    js.set_uint("events", ftp_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+fn register_patterns() -> i8 {
+    let patterns: &[(&[u8], u16, u8)] = &[
+        (b"220 (\0", 5, core::STREAM_TOCLIENT),
+        (b"FEAT\0", 4, core::STREAM_TOSERVER),
+        (b"USER \0", 5, core::STREAM_TOSERVER),
+        (b"PASS \0", 5, core::STREAM_TOSERVER),
+        (b"PORT \0", 5, core::STREAM_TOSERVER),
+    ];
+    for (pattern, depth, direction) in patterns {
+        unsafe {
+            if AppLayerProtoDetectPMRegisterPatternCI(IPPROTO_TCP as u8, ALPROTO_FTP,
+                                                      pattern.as_ptr() as *const std::os::raw::c_char,
+                                                      *depth, 0, *direction) < 0 {
+                SCLogDebug!("AppLayerProtoDetectPMRegisterPatternCI FAILED");
+                return -1;
+            }
+        }
+    }
+    0
+}
+
+const PARSER_NAME: &[u8] = b"ftp\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ftp_register_parser() {
+    ftp_stats::register();
+    FTP_TX_LIMIT.configure("ftp", txtable::DEFAULT_MAX_TX);
+    introspect::register("ftp", ftp_introspect);
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: std::ptr::null(),
+        ipproto: IPPROTO_TCP,
+        probe_ts: None,
+        probe_tc: None,
+        min_depth: 0,
+        max_depth: 0,
+        state_new: rs_ftp_state_new,
+        state_free: rs_ftp_state_free,
+        tx_free: rs_ftp_state_tx_free,
+        parse_ts: rs_ftp_parse_request,
+        parse_tc: rs_ftp_parse_response,
+        get_tx_count: rs_ftp_state_get_tx_count,
+        get_tx: rs_ftp_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_ftp_tx_get_alstate_progress,
+        get_de_state: rs_ftp_state_get_tx_detect_state,
+        set_de_state: rs_ftp_state_set_tx_detect_state,
+        get_events: Some(rs_ftp_state_get_events),
+        get_eventinfo: Some(FtpEvent::get_event_info),
+        get_eventinfo_byid: Some(FtpEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<FtpState>),
+        get_tx_data: rs_ftp_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<FtpState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_FTP = alproto;
+        if register_patterns() < 0 {
+            return;
+        }
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust ftp parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for FTP.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{decoder_events, ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_ftp_state_new,
+            state_free: rs_ftp_state_free,
+            parse_ts: rs_ftp_parse_request,
+            parse_tc: rs_ftp_parse_response,
+            get_tx_count: rs_ftp_state_get_tx_count,
+            get_tx: rs_ftp_state_get_tx,
+            tx_free: rs_ftp_state_tx_free,
+        })
+    }
+
+    #[test]
+    fn test_ftp_port_one_chunk() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"PORT 192,168,1,1,0,80\r\n"));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<FtpTransaction>(0).unwrap();
+        assert!(tx.is_command("PORT"));
+        assert_eq!(tx.argument, b"192,168,1,1,0,80");
+        assert_eq!(tx.port_address, Some(Ipv4Addr::new(192, 168, 1, 1)));
+    }
+
+    #[test]
+    fn test_ftp_port_split() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"POR"));
+        assert!(h.feed(Direction::ToServer, b"T 192,168,1"));
+        assert!(h.feed(Direction::ToServer, b"1,1,10,20\r\n"));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<FtpTransaction>(0).unwrap();
+        assert!(tx.is_command("PORT"));
+        assert_eq!(tx.port_address, Some(Ipv4Addr::new(192, 168, 11, 1)));
+    }
+
+    #[test]
+    fn test_ftp_incomplete_command() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"PORT"));
+        assert_eq!(h.tx_count(), 0);
+        assert_eq!(h.pending(Direction::ToServer), 4);
+        assert!(h.feed(Direction::ToServer, b"\r\n"));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<FtpTransaction>(0).unwrap();
+        assert!(tx.is_command("PORT"));
+        assert_eq!(tx.port_address, None);
+    }
+
+    #[test]
+    fn test_ftp_incomplete_command_split() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"PO"));
+        assert_eq!(h.tx_count(), 0);
+        assert!(h.feed(Direction::ToServer, b"RT\r\n"));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<FtpTransaction>(0).unwrap();
+        assert!(tx.is_command("PORT"));
+        assert_eq!(tx.port_address, None);
+        assert_eq!(decoder_events(tx.events), vec![FtpEvent::InvalidPortCommand as u8]);
+    }
+
+    #[test]
+    fn test_ftp_byte_by_byte() {
+        let mut h = harness();
+        assert!(h.feed_segments(Direction::ToServer, b"PORT 1,2,3,4,5,6\r\n", 1));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<FtpTransaction>(0).unwrap();
+        assert_eq!(tx.port_address, Some(Ipv4Addr::new(1, 2, 3, 4)));
+    }
+
+    #[test]
+    fn test_ftp_retr_without_filename() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"PORT 192,168,1,1,0,80\r\n"));
+        assert!(h.feed(Direction::ToClient, b"200 PORT command successful\r\n"));
+        assert_eq!(h.state::<FtpState>().dyn_port, Some(80));
+        assert!(h.feed(Direction::ToServer, b"RETR\r\n"));
+        assert_eq!(h.tx_count(), 2);
+        let tx = h.get_tx::<FtpTransaction>(1).unwrap();
+        assert!(tx.is_command("RETR"));
+        // the port is used up even without a file to transfer
+        assert_eq!(h.state::<FtpState>().dyn_port, None);
+    }
+
+    #[test]
+    fn test_ftp_stor_without_filename() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"PORT 192,168,1,1,0,80\r\n"));
+        assert!(h.feed(Direction::ToClient, b"200 PORT command successful\r\n"));
+        assert!(h.feed(Direction::ToServer, b"STOR\r\n"));
+        assert_eq!(h.tx_count(), 2);
+        let tx = h.get_tx::<FtpTransaction>(1).unwrap();
+        assert!(tx.is_command("STOR"));
+        assert!(tx.argument.is_empty());
+        assert_eq!(h.state::<FtpState>().dyn_port, None);
+    }
+
+    #[test]
+    fn test_ftp_pipelining() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"USER anonymous\r\nPASS guest\r\nSYST\r\nTYPE I\r\n"));
+        assert_eq!(h.tx_count(), 4);
+        assert!(!h.get_tx::<FtpTransaction>(0).unwrap().done);
+        assert!(h.feed(Direction::ToClient,
+                       b"331 Password required\r\n230 Logged in\r\n215 UNIX Type: L8\r\n"));
+        assert!(!h.get_tx::<FtpTransaction>(3).unwrap().done);
+        assert!(h.feed(Direction::ToClient, b"200 Switching to Binary mode.\r\n"));
+        assert_eq!(h.tx_count(), 4);
+        let codes: Vec<Option<u16>> = (0..4)
+            .map(|id| h.get_tx::<FtpTransaction>(id).unwrap())
+            .map(|tx| {
+                assert!(tx.done);
+                assert_eq!(tx.replies.len(), 1);
+                tx.replies[0].code
+            })
+            .collect();
+        assert_eq!(codes, vec![Some(331), Some(230), Some(215), Some(200)]);
+        assert!(h.get_tx::<FtpTransaction>(2).unwrap().is_command("SYST"));
+    }
+
+    #[test]
+    fn test_ftp_epsv() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"EPSV\r\n"));
+        assert!(h.feed(Direction::ToClient,
+                       b"229 Entering Extended Passive Mode (|||48758|)\r\n"));
+        let tx = h.get_tx::<FtpTransaction>(0).unwrap();
+        assert_eq!(tx.dyn_port, Some(48758));
+        assert!(!tx.active);
+        assert_eq!(h.state::<FtpState>().dyn_port, Some(48758));
+        assert!(h.feed(Direction::ToServer, b"STOR upload.bin\r\n"));
+        assert_eq!(h.state::<FtpState>().dyn_port, None);
+    }
+
+    #[test]
+    fn test_ftp_port_rejected() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"PORT 192,168,1,1,0,80\r\n"));
+        assert!(h.feed(Direction::ToClient, b"500 Illegal PORT command\r\n"));
+        let tx = h.get_tx::<FtpTransaction>(0).unwrap();
+        assert!(tx.done);
+        assert_eq!(tx.dyn_port, None);
+        assert_eq!(h.state::<FtpState>().dyn_port, None);
+    }
+
+    #[test]
+    fn test_ftp_eprt() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"EPRT |2|2001:db8::1|5282|\r\n"));
+        assert!(h.feed(Direction::ToClient, b"200 EPRT command successful\r\n"));
+        let tx = h.get_tx::<FtpTransaction>(0).unwrap();
+        assert_eq!(tx.dyn_port, Some(5282));
+        assert!(tx.active);
+        assert!(tx.events.is_null());
+        assert!(h.state::<FtpState>().active);
+    }
+
+    #[test]
+    fn test_ftp_dialogue() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220-Welcome\r\n to the server\r\n220 Ready\r\n"));
+        assert!(h.feed(Direction::ToServer, b"USER alice\r\nPASS secret\r\n"));
+        assert!(h.feed(Direction::ToClient, b"331 Password required\r\n230 Logged in\r\n"));
+        assert!(h.feed(Direction::ToServer, b"PASV\r\n"));
+        assert!(h.feed(Direction::ToClient,
+                       b"227 Entering Passive Mode (192,0,2,1,221,243).\r\n"));
+        assert!(h.feed(Direction::ToServer, b"RETR file.txt\r\n"));
+        assert!(h.feed(Direction::ToClient, b"150 Opening data connection\r\n"));
+        assert!(!h.get_tx::<FtpTransaction>(4).unwrap().done);
+        assert!(h.feed(Direction::ToClient, b"226 Transfer complete\r\n"));
+        assert_eq!(h.tx_count(), 5);
+
+        let greeting = h.get_tx::<FtpTransaction>(0).unwrap();
+        assert_eq!(greeting.command, None);
+        assert_eq!(greeting.replies.len(), 3);
+        assert!(greeting.done);
+
+        let pass = h.get_tx::<FtpTransaction>(2).unwrap();
+        assert_eq!(pass.replies[0].code, Some(230));
+        assert!(!pass.events.is_null());
+
+        let pasv = h.get_tx::<FtpTransaction>(3).unwrap();
+        assert_eq!(pasv.dyn_port, Some(56819));
+        assert!(!pasv.active);
+
+        let retr = h.get_tx::<FtpTransaction>(4).unwrap();
+        assert_eq!(retr.argument, b"file.txt");
+        assert_eq!(retr.replies.len(), 2);
+        assert!(retr.done);
+        assert_eq!(h.state::<FtpState>().dyn_port, None);
+    }
+
+    #[test]
+    fn test_ftp_anonymous_password() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"USER anonymous\r\nPASS guest@example.com\r\n"));
+        assert!(h.get_tx::<FtpTransaction>(1).unwrap().events.is_null());
+    }
+
+    #[test]
+    fn test_ftp_line_too_long() {
+        let mut h = harness();
+        let mut input = b"SITE ".to_vec();
+        input.extend(std::iter::repeat(b'A').take(FTP_MAX_LINE_LEN));
+        assert!(h.feed(Direction::ToServer, &input));
+        assert!(h.feed(Direction::ToServer, b"AAAA\r\nNOOP\r\n"));
+        assert_eq!(h.tx_count(), 2);
+        let tx = h.get_tx::<FtpTransaction>(1).unwrap();
+        assert!(tx.is_command("NOOP"));
+    }
+}
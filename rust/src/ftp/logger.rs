@@ -0,0 +1,55 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::ftp::FtpTransaction;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+/// Log the fields of a transaction into the "ftp" object opened by the
+/// caller.
+fn log(tx: &FtpTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    if let Some(ref command) = tx.command {
+        js.set_string("command", command)?;
+        if !tx.argument.is_empty() {
+            js.set_string_from_bytes("command_data", &tx.argument)?;
+        }
+    }
+    if tx.replies.iter().any(|reply| reply.code.is_some()) {
+        js.open_array("completion_code")?;
+        for code in tx.replies.iter().filter_map(|reply| reply.code) {
+            js.append_string(&format!("{:03}", code))?;
+        }
+        js.close()?;
+    }
+    if !tx.replies.is_empty() {
+        js.open_array("reply")?;
+        for reply in &tx.replies {
+            js.append_string_from_bytes(&reply.text)?;
+        }
+        js.close()?;
+    }
+    if let Some(port) = tx.dyn_port {
+        js.set_uint("dynamic_port", port as u64)?;
+        js.set_string("mode", if tx.active { "active" } else { "passive" })?;
+    }
+    js.set_string("reply_received", if tx.done { "yes" } else { "no" })?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_ftp_log_json(tx: &mut FtpTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
@@ -17,6 +17,11 @@
 
 extern crate nom;
 
+pub mod detect;
+pub mod ftp;
+pub mod logger;
+pub mod parser;
+
 use nom::character::complete::{digit1, multispace0};
 use std::str;
 use std;
@@ -75,41 +80,6 @@ named!(pub ftp_pasv_response<u16>,
 );
 
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_ftp_active_port(input: *const u8, len: u32) -> u16 {
-    let buf = build_slice!(input, len as usize);
-    match ftp_active_port(buf) {
-        Ok((_, dport)) => {
-            return dport;
-        },
-        Err(nom::Err::Incomplete(_)) => {
-            SCLogDebug!("port incomplete: '{:?}'", buf);
-        },
-        Err(_) => {
-            SCLogDebug!("port error on '{:?}'", buf);
-        },
-    }
-    return 0;
-}
-
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_ftp_pasv_response(input: *const u8, len: u32) -> u16 {
-    let buf = std::slice::from_raw_parts(input, len as usize);
-    match ftp_pasv_response(buf) {
-        Ok((_, dport)) => {
-            return dport;
-        },
-        Err(nom::Err::Incomplete(_)) => {
-            SCLogDebug!("pasv incomplete: '{:?}'", String::from_utf8_lossy(buf));
-        },
-        Err(_) => {
-            SCLogDebug!("pasv error on '{:?}'", String::from_utf8_lossy(buf));
-        },
-    }
-    return 0;
-}
-
 // 229 Entering Extended Passive Mode (|||48758|).
 named!(pub ftp_epsv_response<u16>,
        do_parse!(
@@ -139,41 +109,6 @@ named!(pub ftp_active_eprt<u16>,
         )
 );
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_ftp_active_eprt(input: *const u8, len: u32) -> u16 {
-    let buf = build_slice!(input, len as usize);
-    match ftp_active_eprt(buf) {
-        Ok((_, dport)) => {
-            return dport;
-        },
-        Err(nom::Err::Incomplete(_)) => {
-            SCLogDebug!("eprt incomplete: '{:?}'", String::from_utf8_lossy(buf));
-        },
-        Err(_) => {
-            SCLogDebug!("epsv incomplete: '{:?}'", String::from_utf8_lossy(buf));
-        },
-
-    }
-    return 0;
-}
-#[no_mangle]
-pub unsafe extern "C" fn rs_ftp_epsv_response(input: *const u8, len: u32) -> u16 {
-    let buf = std::slice::from_raw_parts(input, len as usize);
-    match ftp_epsv_response(buf) {
-        Ok((_, dport)) => {
-            return dport;
-        },
-        Err(nom::Err::Incomplete(_)) => {
-            SCLogDebug!("epsv incomplete: '{:?}'", String::from_utf8_lossy(buf));
-        },
-        Err(_) => {
-            SCLogDebug!("epsv incomplete: '{:?}'", String::from_utf8_lossy(buf));
-        },
-
-    }
-    return 0;
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
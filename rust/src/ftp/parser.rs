@@ -0,0 +1,168 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Lines of the FTP control connection (RFC 959). A command is a case
+//! insensitive verb, followed by a space and its argument. A reply
+//! starts with a three digit code followed by a space, or by a hyphen
+//! for the first line of a multiline reply, which ends with a line
+//! starting with the same code and a space.
+
+use std::net::Ipv4Addr;
+
+/// Telnet "interpret as command" byte, sent with the IP and DM
+/// commands ahead of an ABOR (RFC 959, section 4.1.3).
+const TELNET_IAC: u8 = 0xff;
+
+#[derive(Debug, PartialEq)]
+pub struct Command<'a> {
+    /// The verb, in upper case.
+    pub verb: String,
+    pub argument: &'a [u8],
+}
+
+/// Parse a command line, without its line ending. Returns None if the
+/// line does not start with a verb.
+pub fn parse_command(mut line: &[u8]) -> Option<Command> {
+    while line.len() >= 2 && line[0] == TELNET_IAC {
+        line = &line[2..];
+    }
+    let end = line.iter().position(|&b| b == b' ').unwrap_or(line.len());
+    let verb = &line[..end];
+    if verb.is_empty() || !verb.iter().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+    let argument: &[u8] = if end < line.len() { &line[end + 1..] } else { &[] };
+    Some(Command {
+        verb: String::from_utf8_lossy(verb).to_ascii_uppercase(),
+        argument,
+    })
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplyLine<'a> {
+    /// A line starting with a code: the last line of a reply if the code
+    /// is followed by a space, else the first line of a multiline reply.
+    Code { code: u16, last: bool, text: &'a [u8] },
+    /// A line of a multiline reply, or a malformed reply.
+    Text(&'a [u8]),
+}
+
+/// Parse a reply line, without its line ending.
+pub fn parse_reply_line(line: &[u8]) -> ReplyLine {
+    if line.len() < 3 || !line[..3].iter().all(|b| b.is_ascii_digit()) {
+        return ReplyLine::Text(line);
+    }
+    let code = line[..3].iter().fold(0u16, |code, b| code * 10 + (b - b'0') as u16);
+    match line.get(3) {
+        None => ReplyLine::Code { code, last: true, text: &[] },
+        Some(b' ') => ReplyLine::Code { code, last: true, text: &line[4..] },
+        Some(b'-') => ReplyLine::Code { code, last: false, text: &line[4..] },
+        Some(_) => ReplyLine::Text(line),
+    }
+}
+
+/// Whether a reply code is a positive preliminary reply, after which
+/// the command gets another reply.
+pub fn is_preliminary(code: u16) -> bool {
+    code >= 100 && code < 200
+}
+
+/// The address and port of the argument of a PORT command,
+/// "h1,h2,h3,h4,p1,p2".
+pub fn parse_port_argument(argument: &[u8]) -> Option<(Ipv4Addr, u16)> {
+    let argument = std::str::from_utf8(argument).ok()?;
+    let mut fields = [0u8; 6];
+    let mut values = argument.trim().split(',');
+    for field in fields.iter_mut() {
+        *field = values.next()?.trim().parse().ok()?;
+    }
+    if values.next().is_some() {
+        return None;
+    }
+    let address = Ipv4Addr::new(fields[0], fields[1], fields[2], fields[3]);
+    Some((address, ((fields[4] as u16) << 8) | fields[5] as u16))
+}
+
+/// The port of the argument of an EPRT command (RFC 2428),
+/// "|proto|address|port|", where any printable character may be used
+/// as the delimiter.
+pub fn parse_eprt_argument(argument: &[u8]) -> Option<u16> {
+    let argument = std::str::from_utf8(argument).ok()?.trim();
+    let delimiter = argument.chars().next()?;
+    let fields: Vec<&str> = argument.split(delimiter).collect();
+    // the leading and trailing delimiters give empty fields
+    if fields.len() != 5 || !fields[0].is_empty() || !fields[4].is_empty() {
+        return None;
+    }
+    fields[3].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command(b"retr /pub/file.txt"), Some(Command {
+            verb: "RETR".to_string(),
+            argument: b"/pub/file.txt",
+        }));
+        assert_eq!(parse_command(b"PASV"), Some(Command {
+            verb: "PASV".to_string(),
+            argument: b"",
+        }));
+        // ABOR preceded by the telnet IP and DM commands
+        assert_eq!(parse_command(b"\xff\xf4\xff\xf2ABOR"), Some(Command {
+            verb: "ABOR".to_string(),
+            argument: b"",
+        }));
+        assert_eq!(parse_command(b""), None);
+        assert_eq!(parse_command(b" USER"), None);
+        assert_eq!(parse_command(b"227 Entering Passive Mode"), None);
+    }
+
+    #[test]
+    fn test_parse_reply_line() {
+        assert_eq!(parse_reply_line(b"230 Login successful."),
+                   ReplyLine::Code { code: 230, last: true, text: b"Login successful." });
+        assert_eq!(parse_reply_line(b"220-Welcome"),
+                   ReplyLine::Code { code: 220, last: false, text: b"Welcome" });
+        assert_eq!(parse_reply_line(b"200"), ReplyLine::Code { code: 200, last: true, text: b"" });
+        assert_eq!(parse_reply_line(b" 21 files"), ReplyLine::Text(b" 21 files"));
+        assert_eq!(parse_reply_line(b"2001:db8::1"), ReplyLine::Text(b"2001:db8::1"));
+        assert!(is_preliminary(150));
+        assert!(!is_preliminary(226));
+    }
+
+    #[test]
+    fn test_parse_port_argument() {
+        assert_eq!(parse_port_argument(b"192,168,0,13,234,10"),
+                   Some((Ipv4Addr::new(192, 168, 0, 13), 59914)));
+        assert_eq!(parse_port_argument(b"192,168,0"), None);
+        assert_eq!(parse_port_argument(b"192,168,0,256,234,10"), None);
+        assert_eq!(parse_port_argument(b"192,168,0,13,234,10,1"), None);
+    }
+
+    #[test]
+    fn test_parse_eprt_argument() {
+        assert_eq!(parse_eprt_argument(b"|2|2a01:e34:ee97:b130:8c3e:45ea:5ac6:e301|41813|"),
+                   Some(41813));
+        assert_eq!(parse_eprt_argument(b"!1!192.0.2.1!2049!"), Some(2049));
+        assert_eq!(parse_eprt_argument(b"|1|192.0.2.1|2049"), None);
+        assert_eq!(parse_eprt_argument(b""), None);
+    }
+}
@@ -0,0 +1,288 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Common driver for the fuzz targets in `rust/fuzz`, built with the
+//! `fuzz` feature.
+//!
+//! The fuzz input is a sequence of records, each made of a control byte,
+//! a 16 bit big endian length and the data. The top bit of the control
+//! byte selects the direction (set for to client), the other bits the
+//! segment size the data is passed to the parser with, 0 meaning all at
+//! once. For UDP parsers each record is a datagram. This lets the fuzzer
+//! split the data and interleave the directions arbitrarily.
+//!
+//! The invariants on the parser results are checked by `ParserHarness`.
+//!
+//! The SMB, NFS and HTTP/2 parsers need a flow and the C file API, so
+//! they are not covered.
+
+use crate::core::Direction;
+use crate::testing::{ParserFns, ParserHarness};
+
+/// Number of transactions kept before the oldest ones are freed.
+const MAX_LIVE_TXS: u64 = 8;
+
+/// Return the parser functions of the parser with the given name, and
+/// whether the parser is for UDP.
+fn parser(name: &str) -> Option<(ParserFns, bool)> {
+    use crate::applayertemplate::template;
+    use crate::bittorrent::bittorrent;
+    use crate::dcerpc::{dcerpc, dcerpc_udp};
+    use crate::dhcp::dhcp;
+    use crate::dns::dns;
+    use crate::dtls::dtls;
+    use crate::ftp::ftp;
+    use crate::icap::icap;
+    use crate::ike::ike;
+    use crate::irc::irc;
+    use crate::kafka::kafka;
+    use crate::krb::krb5;
+    use crate::ldap::ldap;
+    use crate::llmnr::llmnr;
+    use crate::modbus::modbus;
+    use crate::mqtt::mqtt;
+    use crate::nbns::nbns;
+    use crate::openvpn::openvpn;
+    use crate::proxy::proxy;
+    use crate::ntp::ntp;
+    use crate::quic::quic;
+    use crate::rdp::rdp;
+    use crate::rfb::rfb;
+    use crate::rtp::rtp;
+    use crate::sip::sip;
+    use crate::smtp::smtp;
+    use crate::snmp::snmp;
+    use crate::ssh::ssh;
+    use crate::stratum::stratum;
+    use crate::telnet::telnet;
+    use crate::xmpp::xmpp;
+
+    macro_rules! fns {
+        ($new:path, $free:path, $ts:path, $tc:path, $cnt:path, $get:path, $tx_free:path) => {
+            ParserFns {
+                state_new: $new,
+                state_free: $free,
+                parse_ts: $ts,
+                parse_tc: $tc,
+                get_tx_count: $cnt,
+                get_tx: $get,
+                tx_free: $tx_free,
+            }
+        };
+    }
+
+    let parser = match name {
+        "bittorrent" => (fns!(bittorrent::rs_bittorrent_state_new,
+                bittorrent::rs_bittorrent_state_free, bittorrent::rs_bittorrent_parse_request_tcp,
+                bittorrent::rs_bittorrent_parse_response_tcp,
+                bittorrent::rs_bittorrent_state_get_tx_count, bittorrent::rs_bittorrent_state_get_tx,
+                bittorrent::rs_bittorrent_state_tx_free), false),
+        "bittorrent_udp" => (fns!(bittorrent::rs_bittorrent_state_new,
+                bittorrent::rs_bittorrent_state_free, bittorrent::rs_bittorrent_parse_request_udp,
+                bittorrent::rs_bittorrent_parse_response_udp,
+                bittorrent::rs_bittorrent_state_get_tx_count, bittorrent::rs_bittorrent_state_get_tx,
+                bittorrent::rs_bittorrent_state_tx_free), true),
+        "dcerpc" => (fns!(dcerpc::rs_dcerpc_state_new, dcerpc::rs_dcerpc_state_free,
+                dcerpc::rs_dcerpc_parse_request, dcerpc::rs_dcerpc_parse_response,
+                dcerpc::rs_dcerpc_get_tx_cnt, dcerpc::rs_dcerpc_get_tx,
+                dcerpc::rs_dcerpc_state_transaction_free), false),
+        "dcerpc_udp" => (fns!(dcerpc_udp::rs_dcerpc_udp_state_new,
+                dcerpc_udp::rs_dcerpc_udp_state_free, dcerpc_udp::rs_dcerpc_udp_parse,
+                dcerpc_udp::rs_dcerpc_udp_parse, dcerpc_udp::rs_dcerpc_udp_get_tx_cnt,
+                dcerpc_udp::rs_dcerpc_udp_get_tx,
+                dcerpc_udp::rs_dcerpc_udp_state_transaction_free), true),
+        "dhcp" => (fns!(dhcp::rs_dhcp_state_new, dhcp::rs_dhcp_state_free,
+                dhcp::rs_dhcp_parse, dhcp::rs_dhcp_parse, dhcp::rs_dhcp_state_get_tx_count,
+                dhcp::rs_dhcp_state_get_tx, dhcp::rs_dhcp_state_tx_free), true),
+        "dns" => (fns!(dns::rs_dns_state_new, dns::rs_dns_state_free,
+                dns::rs_dns_parse_request, dns::rs_dns_parse_response,
+                dns::rs_dns_state_get_tx_count, dns::rs_dns_state_get_tx,
+                dns::rs_dns_state_tx_free), true),
+        "dns_tcp" => (fns!(dns::rs_dns_state_new, dns::rs_dns_state_free,
+                dns::rs_dns_parse_request_tcp, dns::rs_dns_parse_response_tcp,
+                dns::rs_dns_state_get_tx_count, dns::rs_dns_state_get_tx,
+                dns::rs_dns_state_tx_free), false),
+        "dtls" => (fns!(dtls::rs_dtls_state_new, dtls::rs_dtls_state_free,
+                dtls::rs_dtls_parse_request, dtls::rs_dtls_parse_response,
+                dtls::rs_dtls_state_get_tx_count, dtls::rs_dtls_state_get_tx,
+                dtls::rs_dtls_state_tx_free), true),
+        "ftp" => (fns!(ftp::rs_ftp_state_new, ftp::rs_ftp_state_free,
+                ftp::rs_ftp_parse_request, ftp::rs_ftp_parse_response,
+                ftp::rs_ftp_state_get_tx_count, ftp::rs_ftp_state_get_tx,
+                ftp::rs_ftp_state_tx_free), false),
+        "icap" => (fns!(icap::rs_icap_state_new, icap::rs_icap_state_free,
+                icap::rs_icap_parse_request, icap::rs_icap_parse_response,
+                icap::rs_icap_state_get_tx_count, icap::rs_icap_state_get_tx,
+                icap::rs_icap_state_tx_free), false),
+        "ike" => (fns!(ike::rs_ike_state_new, ike::rs_ike_state_free,
+                ike::rs_ike_parse_request, ike::rs_ike_parse_response,
+                ike::rs_ike_state_get_tx_count, ike::rs_ike_state_get_tx,
+                ike::rs_ike_state_tx_free), true),
+        "irc" => (fns!(irc::rs_irc_state_new, irc::rs_irc_state_free,
+                irc::rs_irc_parse_request, irc::rs_irc_parse_response,
+                irc::rs_irc_state_get_tx_count, irc::rs_irc_state_get_tx,
+                irc::rs_irc_state_tx_free), false),
+        "kafka" => (fns!(kafka::rs_kafka_state_new, kafka::rs_kafka_state_free,
+                kafka::rs_kafka_parse_request, kafka::rs_kafka_parse_response,
+                kafka::rs_kafka_state_get_tx_count, kafka::rs_kafka_state_get_tx,
+                kafka::rs_kafka_state_tx_free), false),
+        "krb5" => (fns!(krb5::rs_krb5_state_new, krb5::rs_krb5_state_free,
+                krb5::rs_krb5_parse_request, krb5::rs_krb5_parse_response,
+                krb5::rs_krb5_state_get_tx_count, krb5::rs_krb5_state_get_tx,
+                krb5::rs_krb5_state_tx_free), true),
+        "krb5_tcp" => (fns!(krb5::rs_krb5_state_new, krb5::rs_krb5_state_free,
+                krb5::rs_krb5_parse_request_tcp, krb5::rs_krb5_parse_response_tcp,
+                krb5::rs_krb5_state_get_tx_count, krb5::rs_krb5_state_get_tx,
+                krb5::rs_krb5_state_tx_free), false),
+        "ldap" => (fns!(ldap::rs_ldap_state_new, ldap::rs_ldap_state_free,
+                ldap::rs_ldap_parse_request, ldap::rs_ldap_parse_response,
+                ldap::rs_ldap_state_get_tx_count, ldap::rs_ldap_state_get_tx,
+                ldap::rs_ldap_state_tx_free), false),
+        "llmnr" => (fns!(llmnr::rs_llmnr_state_new, llmnr::rs_llmnr_state_free,
+                llmnr::rs_llmnr_parse_request, llmnr::rs_llmnr_parse_response,
+                llmnr::rs_llmnr_state_get_tx_count, llmnr::rs_llmnr_state_get_tx,
+                llmnr::rs_llmnr_state_tx_free), true),
+        "modbus" => (fns!(modbus::rs_modbus_state_new, modbus::rs_modbus_state_free,
+                modbus::rs_modbus_parse_request, modbus::rs_modbus_parse_response,
+                modbus::rs_modbus_state_get_tx_count, modbus::rs_modbus_state_get_tx,
+                modbus::rs_modbus_state_tx_free), false),
+        "mqtt" => (fns!(mqtt::rs_mqtt_state_new, mqtt::rs_mqtt_state_free,
+                mqtt::rs_mqtt_parse_request, mqtt::rs_mqtt_parse_response,
+                mqtt::rs_mqtt_state_get_tx_count, mqtt::rs_mqtt_state_get_tx,
+                mqtt::rs_mqtt_state_tx_free), false),
+        "nbns" => (fns!(nbns::rs_nbns_state_new, nbns::rs_nbns_state_free,
+                nbns::rs_nbns_parse_request, nbns::rs_nbns_parse_response,
+                nbns::rs_nbns_state_get_tx_count, nbns::rs_nbns_state_get_tx,
+                nbns::rs_nbns_state_tx_free), true),
+        "ntp" => (fns!(ntp::rs_ntp_state_new, ntp::rs_ntp_state_free,
+                ntp::rs_ntp_parse_request, ntp::rs_ntp_parse_response,
+                ntp::rs_ntp_state_get_tx_count, ntp::rs_ntp_state_get_tx,
+                ntp::rs_ntp_state_tx_free), true),
+        "openvpn" => (fns!(openvpn::rs_openvpn_state_new, openvpn::rs_openvpn_state_free,
+                openvpn::rs_openvpn_parse, openvpn::rs_openvpn_parse,
+                openvpn::rs_openvpn_state_get_tx_count, openvpn::rs_openvpn_state_get_tx,
+                openvpn::rs_openvpn_state_tx_free), true),
+        "openvpn_tcp" => (fns!(openvpn::rs_openvpn_state_new, openvpn::rs_openvpn_state_free,
+                openvpn::rs_openvpn_parse_request_tcp, openvpn::rs_openvpn_parse_response_tcp,
+                openvpn::rs_openvpn_state_get_tx_count, openvpn::rs_openvpn_state_get_tx,
+                openvpn::rs_openvpn_state_tx_free), false),
+        "proxy" => (fns!(proxy::rs_proxy_state_new, proxy::rs_proxy_state_free,
+                proxy::rs_proxy_parse_request, proxy::rs_proxy_parse_response,
+                proxy::rs_proxy_state_get_tx_count, proxy::rs_proxy_state_get_tx,
+                proxy::rs_proxy_state_tx_free), false),
+        "quic" => (fns!(quic::rs_quic_state_new, quic::rs_quic_state_free,
+                quic::rs_quic_parse_request, quic::rs_quic_parse_response,
+                quic::rs_quic_state_get_tx_count, quic::rs_quic_state_get_tx,
+                quic::rs_quic_state_tx_free), true),
+        "rdp" => (fns!(rdp::rs_rdp_state_new, rdp::rs_rdp_state_free,
+                rdp::rs_rdp_parse_ts, rdp::rs_rdp_parse_tc,
+                rdp::rs_rdp_state_get_tx_count, rdp::rs_rdp_state_get_tx,
+                rdp::rs_rdp_state_tx_free), false),
+        "rfb" => (fns!(rfb::rs_rfb_state_new, rfb::rs_rfb_state_free,
+                rfb::rs_rfb_parse_request, rfb::rs_rfb_parse_response,
+                rfb::rs_rfb_state_get_tx_count, rfb::rs_rfb_state_get_tx,
+                rfb::rs_rfb_state_tx_free), false),
+        "rtp" => (fns!(rtp::rs_rtp_state_new, rtp::rs_rtp_state_free,
+                rtp::rs_rtp_parse_request, rtp::rs_rtp_parse_response,
+                rtp::rs_rtp_state_get_tx_count, rtp::rs_rtp_state_get_tx,
+                rtp::rs_rtp_state_tx_free), true),
+        "sip" => (fns!(sip::rs_sip_state_new, sip::rs_sip_state_free,
+                sip::rs_sip_parse_request, sip::rs_sip_parse_response,
+                sip::rs_sip_state_get_tx_count, sip::rs_sip_state_get_tx,
+                sip::rs_sip_state_tx_free), true),
+        "sip_tcp" => (fns!(sip::rs_sip_state_new, sip::rs_sip_state_free,
+                sip::rs_sip_parse_request_tcp, sip::rs_sip_parse_response_tcp,
+                sip::rs_sip_state_get_tx_count, sip::rs_sip_state_get_tx,
+                sip::rs_sip_state_tx_free), false),
+        "smtp" => (fns!(smtp::rs_smtp_state_new, smtp::rs_smtp_state_free,
+                smtp::rs_smtp_parse_request, smtp::rs_smtp_parse_response,
+                smtp::rs_smtp_state_get_tx_count, smtp::rs_smtp_state_get_tx,
+                smtp::rs_smtp_state_tx_free), false),
+        "snmp" => (fns!(snmp::rs_snmp_state_new, snmp::rs_snmp_state_free,
+                snmp::rs_snmp_parse_request, snmp::rs_snmp_parse_response,
+                snmp::rs_snmp_state_get_tx_count, snmp::rs_snmp_state_get_tx,
+                snmp::rs_snmp_state_tx_free), true),
+        "ssh" => (fns!(ssh::rs_ssh_state_new, ssh::rs_ssh_state_free,
+                ssh::rs_ssh_parse_request, ssh::rs_ssh_parse_response,
+                ssh::rs_ssh_state_get_tx_count, ssh::rs_ssh_state_get_tx,
+                ssh::rs_ssh_state_tx_free), false),
+        "stratum" => (fns!(stratum::rs_stratum_state_new, stratum::rs_stratum_state_free,
+                stratum::rs_stratum_parse_request, stratum::rs_stratum_parse_response,
+                stratum::rs_stratum_state_get_tx_count, stratum::rs_stratum_state_get_tx,
+                stratum::rs_stratum_state_tx_free), false),
+        "telnet" => (fns!(telnet::rs_telnet_state_new, telnet::rs_telnet_state_free,
+                telnet::rs_telnet_parse_request, telnet::rs_telnet_parse_response,
+                telnet::rs_telnet_state_get_tx_count, telnet::rs_telnet_state_get_tx,
+                telnet::rs_telnet_state_tx_free), false),
+        "template" => (fns!(template::rs_template_state_new, template::rs_template_state_free,
+                template::rs_template_parse_request, template::rs_template_parse_response,
+                template::rs_template_state_get_tx_count, template::rs_template_state_get_tx,
+                template::rs_template_state_tx_free), false),
+        "xmpp" => (fns!(xmpp::rs_xmpp_state_new, xmpp::rs_xmpp_state_free,
+                xmpp::rs_xmpp_parse_request, xmpp::rs_xmpp_parse_response,
+                xmpp::rs_xmpp_state_get_tx_count, xmpp::rs_xmpp_state_get_tx,
+                xmpp::rs_xmpp_state_tx_free), false),
+        _ => return None,
+    };
+    Some(parser)
+}
+
+/// Run the fuzz input `data` through the parser with the given name.
+pub fn fuzz_parser(name: &str, data: &[u8]) {
+    let (fns, udp) = match parser(name) {
+        Some(parser) => parser,
+        None => panic!("no parser named {}", name),
+    };
+    let mut harness = ParserHarness::new(fns);
+    let mut next_free = 0;
+    let mut rem = data;
+    while rem.len() >= 3 {
+        let ctrl = rem[0];
+        let len = std::cmp::min(u16::from_be_bytes([rem[1], rem[2]]) as usize, rem.len() - 3);
+        let record = &rem[3..3 + len];
+        rem = &rem[3 + len..];
+
+        let direction = if ctrl & 0x80 != 0 {
+            Direction::ToClient
+        } else {
+            Direction::ToServer
+        };
+        let segment_size = (ctrl & 0x7f) as usize;
+        let ok = if udp || segment_size == 0 {
+            harness.feed(direction, record)
+        } else {
+            harness.feed_segments(direction, record, segment_size)
+        };
+        if !ok {
+            // the engine stops parsing after an error
+            break;
+        }
+
+        // Walk the transactions and free the oldest ones, like the
+        // inspection and logging would.
+        let count = harness.tx_count();
+        while count.saturating_sub(next_free) > MAX_LIVE_TXS {
+            if harness.get_tx::<u8>(next_free).is_some() {
+                harness.free_tx(next_free);
+            }
+            next_free += 1;
+        }
+        for tx_id in next_free..count {
+            let _ = harness.get_tx::<u8>(tx_id);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+/* Copyright (C) 2024 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Streaming ssdeep (spamsum) context triggered piecewise hash.
+//!
+//! This follows the libfuzzy engine: a rolling hash over a 7 byte window
+//! triggers the emission of a base64 character from a FNV style piece
+//! hash, for a ladder of block sizes kept in parallel until the total
+//! size is known. The digest is `blocksize:hash1:hash2`.
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCKSIZE: u64 = 3;
+const HASH_PRIME: u32 = 0x0100_0193;
+const HASH_INIT: u32 = 0x2802_1967;
+const NUM_BLOCKHASHES: usize = 31;
+const SPAMSUM_LENGTH: usize = 64;
+
+const B64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn block_size(index: usize) -> u64 {
+    MIN_BLOCKSIZE << index
+}
+
+fn sum_hash(c: u8, h: u32) -> u32 {
+    h.wrapping_mul(HASH_PRIME) ^ c as u32
+}
+
+#[derive(Default)]
+struct RollState {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl RollState {
+    fn hash(&mut self, c: u8) {
+        let c32 = c as u32;
+        self.h2 = self.h2.wrapping_sub(self.h1);
+        self.h2 = self.h2.wrapping_add((ROLLING_WINDOW as u32).wrapping_mul(c32));
+        self.h1 = self.h1.wrapping_add(c32);
+        self.h1 = self.h1.wrapping_sub(self.window[self.n] as u32);
+        self.window[self.n] = c;
+        self.n = (self.n + 1) % ROLLING_WINDOW;
+        self.h3 = (self.h3 << 5) ^ c32;
+    }
+
+    fn sum(&self) -> u32 {
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct BlockHash {
+    h: u32,
+    halfh: u32,
+    /// Emitted characters; `digest[dlen]` holds the pending character
+    /// once the hash is full, 0 otherwise.
+    digest: [u8; SPAMSUM_LENGTH],
+    halfdigest: u8,
+    dlen: usize,
+}
+
+impl Default for BlockHash {
+    fn default() -> Self {
+        BlockHash {
+            h: HASH_INIT,
+            halfh: HASH_INIT,
+            digest: [0; SPAMSUM_LENGTH],
+            halfdigest: 0,
+            dlen: 0,
+        }
+    }
+}
+
+/// Incremental ssdeep hasher.
+pub struct Ssdeep {
+    bhstart: usize,
+    bhend: usize,
+    bh: [BlockHash; NUM_BLOCKHASHES],
+    total_size: u64,
+    roll: RollState,
+}
+
+impl Default for Ssdeep {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ssdeep {
+    pub fn new() -> Ssdeep {
+        Ssdeep {
+            bhstart: 0,
+            bhend: 1,
+            bh: [BlockHash::default(); NUM_BLOCKHASHES],
+            total_size: 0,
+            roll: RollState::default(),
+        }
+    }
+
+    fn try_fork_blockhash(&mut self) {
+        if self.bhend >= NUM_BLOCKHASHES {
+            return;
+        }
+        let prev = self.bh[self.bhend - 1];
+        self.bh[self.bhend] = BlockHash {
+            h: prev.h,
+            halfh: prev.halfh,
+            ..BlockHash::default()
+        };
+        self.bhend += 1;
+    }
+
+    fn try_reduce_blockhash(&mut self) {
+        if self.bhend - self.bhstart < 2 {
+            return;
+        }
+        if block_size(self.bhstart) * SPAMSUM_LENGTH as u64 >= self.total_size {
+            // Still need the smaller block size.
+            return;
+        }
+        if self.bh[self.bhstart + 1].dlen < SPAMSUM_LENGTH / 2 {
+            // The next block size would not give a long enough hash.
+            return;
+        }
+        self.bhstart += 1;
+    }
+
+    fn step(&mut self, c: u8) {
+        self.roll.hash(c);
+        let h = self.roll.sum() as u64;
+
+        for bh in &mut self.bh[self.bhstart..self.bhend] {
+            bh.h = sum_hash(c, bh.h);
+            bh.halfh = sum_hash(c, bh.halfh);
+        }
+
+        let mut i = self.bhstart;
+        while i < self.bhend {
+            // With a ladder of doubling block sizes, once the trigger
+            // misses one it misses all the larger ones too.
+            if h % block_size(i) != block_size(i) - 1 {
+                break;
+            }
+            if self.bh[i].dlen == 0 {
+                self.try_fork_blockhash();
+            }
+            let bh = &mut self.bh[i];
+            bh.digest[bh.dlen] = B64[(bh.h % 64) as usize];
+            bh.halfdigest = B64[(bh.halfh % 64) as usize];
+            if bh.dlen < SPAMSUM_LENGTH - 1 {
+                bh.dlen += 1;
+                bh.digest[bh.dlen] = 0;
+                bh.h = HASH_INIT;
+                if bh.dlen < SPAMSUM_LENGTH / 2 {
+                    bh.halfh = HASH_INIT;
+                    bh.halfdigest = 0;
+                }
+            } else {
+                self.try_reduce_blockhash();
+            }
+            i += 1;
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.total_size += data.len() as u64;
+        for c in data {
+            self.step(*c);
+        }
+    }
+
+    /// Produce the digest string. Returns None if the input was too
+    /// large for the largest block size.
+    pub fn finalize(&self) -> Option<String> {
+        let h = self.roll.sum();
+        let mut bi = self.bhstart;
+        while block_size(bi) * (SPAMSUM_LENGTH as u64) < self.total_size {
+            bi += 1;
+            if bi >= NUM_BLOCKHASHES {
+                return None;
+            }
+        }
+        // Adapt the guess to the block hashes that actually exist and
+        // are long enough.
+        while bi >= self.bhend {
+            bi -= 1;
+        }
+        while bi > self.bhstart && self.bh[bi].dlen < SPAMSUM_LENGTH / 2 {
+            bi -= 1;
+        }
+
+        let mut out = format!("{}:", block_size(bi)).into_bytes();
+        let bh = &self.bh[bi];
+        out.extend_from_slice(&bh.digest[..bh.dlen]);
+        if h != 0 {
+            out.push(B64[(bh.h % 64) as usize]);
+        } else if bh.digest[bh.dlen] != 0 {
+            out.push(bh.digest[bh.dlen]);
+        }
+        out.push(b':');
+
+        if bi < self.bhend - 1 {
+            let bh = &self.bh[bi + 1];
+            let len = std::cmp::min(bh.dlen, SPAMSUM_LENGTH / 2 - 1);
+            out.extend_from_slice(&bh.digest[..len]);
+            if h != 0 {
+                out.push(B64[(bh.halfh % 64) as usize]);
+            } else if bh.halfdigest != 0 {
+                out.push(bh.halfdigest);
+            }
+        } else if h != 0 {
+            out.push(B64[(self.bh[bi].h % 64) as usize]);
+        }
+
+        // Only ever contains ASCII.
+        String::from_utf8(out).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn hash(data: &[u8]) -> String {
+        let mut ctx = Ssdeep::new();
+        ctx.update(data);
+        ctx.finalize().unwrap()
+    }
+
+    #[test]
+    fn test_ssdeep_empty() {
+        assert_eq!(hash(b""), "3::");
+    }
+
+    #[test]
+    fn test_ssdeep_streaming() {
+        let data = sample(100_000, 1);
+        let mut ctx = Ssdeep::new();
+        for chunk in data.chunks(1337) {
+            ctx.update(chunk);
+        }
+        assert_eq!(ctx.finalize().unwrap(), hash(&data));
+    }
+
+    #[test]
+    fn test_ssdeep_shape() {
+        let digest = hash(&sample(100_000, 2));
+        let parts: Vec<&str> = digest.split(':').collect();
+        assert_eq!(parts.len(), 3);
+        let bs: u64 = parts[0].parse().unwrap();
+        assert_eq!(bs % MIN_BLOCKSIZE, 0);
+        assert!(parts[1].len() >= SPAMSUM_LENGTH / 2 && parts[1].len() <= SPAMSUM_LENGTH);
+        assert!(parts[2].len() <= SPAMSUM_LENGTH / 2);
+    }
+
+    #[test]
+    fn test_ssdeep_similar() {
+        let data = sample(50_000, 3);
+        let mut modified = data.clone();
+        modified[25_000] ^= 0xff;
+        let a = hash(&data);
+        let b = hash(&modified);
+        assert_ne!(a, b);
+        let a: Vec<&str> = a.split(':').collect();
+        let b: Vec<&str> = b.split(':').collect();
+        assert_eq!(a[0], b[0]);
+        // A single flipped byte only touches the piece around it.
+        let prefix = a[1].bytes().zip(b[1].bytes()).take_while(|(x, y)| x == y).count();
+        assert!(prefix >= a[1].len() / 3);
+    }
+}
@@ -0,0 +1,240 @@
+/* Copyright (C) 2024 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Streaming TLSH locality sensitive hash.
+//!
+//! The standard configuration: 128 buckets, a one byte checksum and a
+//! five byte sliding window, rendered as the "T1" versioned hex digest.
+
+const SLIDING_WND_SIZE: usize = 5;
+const BUCKETS: usize = 256;
+const EFF_BUCKETS: usize = 128;
+const CODE_SIZE: usize = EFF_BUCKETS / 4;
+
+/// Below this there are not enough triplets for a meaningful hash.
+pub const TLSH_MIN_DATA_LENGTH: u64 = 50;
+
+/// Length of the hex digest, including the "T1" prefix.
+pub const TLSH_HEX_LEN: usize = 2 + 2 * (3 + CODE_SIZE);
+
+#[rustfmt::skip]
+const V_TABLE: [u8; 256] = [
+    1, 87, 49, 12, 176, 178, 102, 166, 121, 193, 6, 84, 249, 230, 44, 163,
+    14, 197, 213, 181, 161, 85, 218, 80, 64, 239, 24, 226, 236, 142, 38, 200,
+    110, 177, 104, 103, 141, 253, 255, 50, 77, 101, 81, 18, 45, 96, 31, 222,
+    25, 107, 190, 70, 86, 237, 240, 34, 72, 242, 20, 214, 244, 227, 149, 235,
+    97, 234, 57, 22, 60, 250, 82, 175, 208, 5, 127, 199, 111, 62, 135, 248,
+    174, 169, 211, 58, 66, 154, 106, 195, 245, 171, 17, 187, 182, 179, 0, 243,
+    132, 56, 148, 75, 128, 133, 158, 100, 130, 126, 91, 13, 153, 246, 216, 219,
+    119, 68, 223, 78, 83, 88, 201, 99, 122, 11, 92, 32, 136, 114, 52, 10,
+    138, 30, 48, 183, 156, 35, 61, 26, 143, 74, 251, 94, 129, 162, 63, 152,
+    170, 7, 115, 167, 241, 206, 3, 150, 55, 59, 151, 220, 90, 53, 23, 131,
+    125, 173, 15, 238, 79, 95, 89, 16, 105, 137, 225, 224, 217, 160, 37, 123,
+    118, 73, 2, 157, 46, 116, 9, 145, 134, 228, 207, 212, 202, 215, 69, 229,
+    27, 188, 67, 124, 168, 252, 42, 4, 29, 108, 21, 247, 19, 205, 39, 203,
+    233, 40, 186, 147, 198, 192, 155, 33, 164, 191, 98, 204, 165, 180, 117, 76,
+    140, 36, 210, 172, 41, 54, 159, 8, 185, 232, 113, 196, 231, 47, 146, 120,
+    51, 65, 28, 144, 254, 221, 93, 189, 194, 139, 112, 43, 71, 109, 184, 209,
+];
+
+/// Pearson hash of a salt and three bytes.
+fn b_mapping(salt: u8, i: u8, j: u8, k: u8) -> u8 {
+    let mut h = V_TABLE[salt as usize];
+    h = V_TABLE[(h ^ i) as usize];
+    h = V_TABLE[(h ^ j) as usize];
+    V_TABLE[(h ^ k) as usize]
+}
+
+/// Logarithmic length bucket, as in the reference implementation.
+fn l_capturing(len: u64) -> u8 {
+    const LOG_1_5: f64 = 0.405_465_1;
+    const LOG_1_3: f64 = 0.262_364_26;
+    const LOG_1_1: f64 = 0.095_310_180;
+    let l = (len as f64).ln();
+    let i = if len <= 656 {
+        (l / LOG_1_5).floor()
+    } else if len <= 3199 {
+        (l / LOG_1_3 - 8.727_77).floor()
+    } else {
+        (l / LOG_1_1 - 62.547_2).floor()
+    };
+    (i as u32 & 0xff) as u8
+}
+
+fn swap_nibbles(b: u8) -> u8 {
+    b.rotate_right(4)
+}
+
+/// Incremental TLSH hasher.
+pub struct Tlsh {
+    buckets: [u32; BUCKETS],
+    window: [u8; SLIDING_WND_SIZE],
+    checksum: u8,
+    data_len: u64,
+}
+
+impl Default for Tlsh {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tlsh {
+    pub fn new() -> Tlsh {
+        Tlsh {
+            buckets: [0; BUCKETS],
+            window: [0; SLIDING_WND_SIZE],
+            checksum: 0,
+            data_len: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for c in data {
+            let j = (self.data_len % SLIDING_WND_SIZE as u64) as usize;
+            self.window[j] = *c;
+            if self.data_len >= SLIDING_WND_SIZE as u64 - 1 {
+                let w0 = self.window[j];
+                let w1 = self.window[(j + 4) % SLIDING_WND_SIZE];
+                let w2 = self.window[(j + 3) % SLIDING_WND_SIZE];
+                let w3 = self.window[(j + 2) % SLIDING_WND_SIZE];
+                let w4 = self.window[(j + 1) % SLIDING_WND_SIZE];
+
+                self.checksum = b_mapping(0, w0, w1, self.checksum);
+                self.buckets[b_mapping(2, w0, w1, w2) as usize] += 1;
+                self.buckets[b_mapping(3, w0, w1, w3) as usize] += 1;
+                self.buckets[b_mapping(5, w0, w2, w3) as usize] += 1;
+                self.buckets[b_mapping(7, w0, w2, w4) as usize] += 1;
+                self.buckets[b_mapping(11, w0, w1, w4) as usize] += 1;
+                self.buckets[b_mapping(13, w0, w3, w4) as usize] += 1;
+            }
+            self.data_len += 1;
+        }
+    }
+
+    /// Produce the hex digest. Returns None when the input is too short
+    /// or too uniform to give a valid hash.
+    pub fn finalize(&self) -> Option<String> {
+        if self.data_len < TLSH_MIN_DATA_LENGTH {
+            return None;
+        }
+        let eff = &self.buckets[..EFF_BUCKETS];
+        let nonzero = eff.iter().filter(|b| **b > 0).count();
+        if nonzero <= 4 * CODE_SIZE / 2 {
+            return None;
+        }
+        let mut sorted = [0u32; EFF_BUCKETS];
+        sorted.copy_from_slice(eff);
+        sorted.sort_unstable();
+        let q1 = sorted[EFF_BUCKETS / 4 - 1];
+        let q2 = sorted[EFF_BUCKETS / 2 - 1];
+        let q3 = sorted[EFF_BUCKETS - EFF_BUCKETS / 4 - 1];
+        if q3 == 0 {
+            return None;
+        }
+
+        let mut code = [0u8; CODE_SIZE];
+        for (i, c) in code.iter_mut().enumerate() {
+            let mut h = 0u8;
+            for j in 0..4 {
+                let k = eff[4 * i + j];
+                if q3 < k {
+                    h += 3 << (j * 2);
+                } else if q2 < k {
+                    h += 2 << (j * 2);
+                } else if q1 < k {
+                    h += 1 << (j * 2);
+                }
+            }
+            *c = h;
+        }
+
+        let q1ratio = ((q1 as u64 * 100 / q3 as u64) % 16) as u8;
+        let q2ratio = ((q2 as u64 * 100 / q3 as u64) % 16) as u8;
+
+        let mut out = String::with_capacity(TLSH_HEX_LEN);
+        out.push_str("T1");
+        out.push_str(&format!("{:02X}", swap_nibbles(self.checksum)));
+        out.push_str(&format!("{:02X}", swap_nibbles(l_capturing(self.data_len))));
+        out.push_str(&format!("{:02X}", (q1ratio << 4) | q2ratio));
+        for c in code.iter().rev() {
+            out.push_str(&format!("{:02X}", c));
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn hash(data: &[u8]) -> Option<String> {
+        let mut ctx = Tlsh::new();
+        ctx.update(data);
+        ctx.finalize()
+    }
+
+    #[test]
+    fn test_tlsh_table_is_permutation() {
+        let mut seen = [false; 256];
+        for v in V_TABLE.iter() {
+            assert!(!seen[*v as usize]);
+            seen[*v as usize] = true;
+        }
+    }
+
+    #[test]
+    fn test_tlsh_too_short() {
+        assert_eq!(hash(&sample(49, 1)), None);
+        assert_eq!(hash(&[0u8; 4096]), None);
+    }
+
+    #[test]
+    fn test_tlsh_streaming() {
+        let data = sample(10_000, 2);
+        let mut ctx = Tlsh::new();
+        for chunk in data.chunks(7) {
+            ctx.update(chunk);
+        }
+        let digest = ctx.finalize().unwrap();
+        assert_eq!(digest.len(), TLSH_HEX_LEN);
+        assert!(digest.starts_with("T1"));
+        assert_eq!(Some(digest), hash(&data));
+    }
+
+    #[test]
+    fn test_tlsh_similar() {
+        let data = sample(10_000, 3);
+        let mut modified = data.clone();
+        modified[5_000] ^= 0xff;
+        let a = hash(&data).unwrap();
+        let b = hash(&modified).unwrap();
+        // Body nibbles after the header; only a few buckets may move.
+        let diff = a[8..].bytes().zip(b[8..].bytes()).filter(|(x, y)| x != y).count();
+        assert!(diff < 8);
+    }
+}
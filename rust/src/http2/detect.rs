@@ -19,6 +19,7 @@ use super::http2::{
     HTTP2Event, HTTP2Frame, HTTP2FrameTypeData, HTTP2State, HTTP2Transaction, HTTP2TransactionState,
 };
 use super::parser;
+use crate::applayer::cast_mut;
 use crate::core::{STREAM_TOCLIENT, STREAM_TOSERVER};
 use std::ffi::CStr;
 use std::str::FromStr;
@@ -46,7 +47,7 @@ fn http2_tx_has_frametype(
 pub unsafe extern "C" fn rs_http2_tx_has_frametype(
     tx: *mut std::os::raw::c_void, direction: u8, value: u8,
 ) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, HTTP2Transaction);
+    let tx: &mut HTTP2Transaction = cast_mut(tx);
     return http2_tx_has_frametype(tx, direction, value);
 }
 
@@ -106,7 +107,7 @@ fn http2_tx_has_errorcode(
 pub unsafe extern "C" fn rs_http2_tx_has_errorcode(
     tx: *mut std::os::raw::c_void, direction: u8, code: u32,
 ) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, HTTP2Transaction);
+    let tx: &mut HTTP2Transaction = cast_mut(tx);
     return http2_tx_has_errorcode(tx, direction, code);
 }
 
@@ -179,7 +180,7 @@ fn http2_tx_get_next_priority(
 pub unsafe extern "C" fn rs_http2_tx_get_next_priority(
     tx: *mut std::os::raw::c_void, direction: u8, nb: u32,
 ) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, HTTP2Transaction);
+    let tx: &mut HTTP2Transaction = cast_mut(tx);
     return http2_tx_get_next_priority(tx, direction, nb);
 }
 
@@ -221,7 +222,7 @@ fn http2_tx_get_next_window(
 pub unsafe extern "C" fn rs_http2_tx_get_next_window(
     tx: *mut std::os::raw::c_void, direction: u8, nb: u32,
 ) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, HTTP2Transaction);
+    let tx: &mut HTTP2Transaction = cast_mut(tx);
     return http2_tx_get_next_window(tx, direction, nb);
 }
 
@@ -315,8 +316,8 @@ fn http2_detect_settingsctx_match(
 pub unsafe extern "C" fn rs_http2_detect_settingsctx_match(
     ctx: *const std::os::raw::c_void, tx: *mut std::os::raw::c_void, direction: u8,
 ) -> std::os::raw::c_int {
-    let ctx = cast_pointer!(ctx, parser::DetectHTTP2settingsSigCtx);
-    let tx = cast_pointer!(tx, HTTP2Transaction);
+    let ctx: &mut parser::DetectHTTP2settingsSigCtx = cast_mut(ctx);
+    let tx: &mut HTTP2Transaction = cast_mut(tx);
     return http2_detect_settingsctx_match(ctx, tx, direction);
 }
 
@@ -422,8 +423,8 @@ fn http2_detect_sizeupdatectx_match(
 pub unsafe extern "C" fn rs_http2_detect_sizeupdatectx_match(
     ctx: *const std::os::raw::c_void, tx: *mut std::os::raw::c_void, direction: u8,
 ) -> std::os::raw::c_int {
-    let ctx = cast_pointer!(ctx, parser::DetectU64Data);
-    let tx = cast_pointer!(tx, HTTP2Transaction);
+    let ctx: &mut parser::DetectU64Data = cast_mut(ctx);
+    let tx: &mut HTTP2Transaction = cast_mut(tx);
     return http2_detect_sizeupdatectx_match(ctx, tx, direction);
 }
 
@@ -739,6 +740,38 @@ fn http2_escape_header(blocks: &[parser::HTTP2FrameHeaderBlock], i: u32) -> Vec<
     return vec;
 }
 
+/// Returns the value of the nth header named `name` in the direction.
+fn http2_frames_get_nth_header_value<'a>(
+    tx: &'a HTTP2Transaction, direction: u8, name: &[u8], nb: u32,
+) -> Option<&'a [u8]> {
+    let frames = if direction & STREAM_TOSERVER != 0 {
+        &tx.frames_ts
+    } else {
+        &tx.frames_tc
+    };
+    frames
+        .iter()
+        .filter_map(|frame| http2_header_blocks(frame))
+        .flat_map(|blocks| blocks.iter())
+        .filter(|block| block.name == name)
+        .map(|block| &block.value[..])
+        .nth(nb as usize)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_http2_tx_get_nth_header_value(
+    tx: &mut HTTP2Transaction, direction: u8, name: *const u8, name_len: u32, nb: u32,
+    buffer: *mut *const u8, buffer_len: *mut u32,
+) -> u8 {
+    let name = build_slice!(name, name_len as usize);
+    if let Some(value) = http2_frames_get_nth_header_value(tx, direction, name, nb) {
+        *buffer = value.as_ptr(); //unsafe
+        *buffer_len = value.len() as u32;
+        return 1;
+    }
+    return 0;
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn rs_http2_tx_get_header_names(
     tx: &mut HTTP2Transaction, direction: u8, buffer: *mut *const u8, buffer_len: *mut u32,
@@ -1108,12 +1141,13 @@ pub unsafe extern "C" fn rs_http2_tx_add_header(
 ) {
     let slice_name = build_slice!(name, name_len as usize);
     let slice_value = build_slice!(value, value_len as usize);
-    if slice_name == "HTTP2-Settings".as_bytes() {
+    if http2_caseinsensitive_cmp(slice_name, "http2-settings") {
         http2_tx_set_settings(state, slice_value)
     } else if http2_caseinsensitive_cmp(slice_name, "host") {
         http2_tx_set_header(state, ":authority".as_bytes(), slice_value)
     } else {
-        http2_tx_set_header(state, slice_name, slice_value)
+        // header names are lowercase in HTTP2
+        http2_tx_set_header(state, &slice_name.to_ascii_lowercase(), slice_value)
     }
 }
 
@@ -1210,5 +1244,33 @@ mod tests {
                 panic!("Result should not have been an error: {:?}", e);
             }
         }
+        assert_eq!(
+            http2_frames_get_nth_header_value(&tx, STREAM_TOSERVER, b"Host", 1),
+            Some("efg.net".as_bytes())
+        );
+        assert_eq!(http2_frames_get_nth_header_value(&tx, STREAM_TOSERVER, b"Host", 2), None);
+        assert_eq!(http2_frames_get_nth_header_value(&tx, STREAM_TOCLIENT, b"Host", 0), None);
+    }
+
+    #[test]
+    fn test_http2_upgrade_handoff() {
+        // The files of the state can only be freed by the engine, so never
+        // drop it: a panic in drop during a failed assertion aborts the run.
+        let mut state = std::mem::ManuallyDrop::new(HTTP2State::new());
+        let name = b"User-Agent";
+        let value = b"curl";
+        unsafe {
+            rs_http2_tx_add_header(
+                &mut *state, name.as_ptr(), name.len() as u32, value.as_ptr(), value.len() as u32,
+            );
+        }
+        state.upgrade_handoff();
+        let tx = state.get_tx(0).unwrap();
+        assert_eq!(tx.stream_id, 1);
+        assert_eq!(tx.state, HTTP2TransactionState::HTTP2StateHalfClosedClient);
+        assert_eq!(
+            http2_frames_get_nth_header_value(tx, STREAM_TOSERVER, b"user-agent", 0),
+            Some(&value[..])
+        );
     }
 }
@@ -21,8 +21,9 @@ use super::parser;
 use super::range;
 
 use crate::applayer::{self, *};
+use crate::conf::conf_get_app_layer_node;
 use crate::core::{
-    self, AppProto, Flow, HttpRangeContainerBlock, SuricataFileContext, ALPROTO_FAILED,
+    self, AppProto, Direction, Flow, HttpRangeContainerBlock, SuricataFileContext, ALPROTO_FAILED,
     ALPROTO_UNKNOWN, IPPROTO_TCP, SC, STREAM_TOCLIENT, STREAM_TOSERVER,
 };
 use crate::filecontainer::*;
@@ -64,6 +65,11 @@ const HTTP2_FRAME_WINDOWUPDATE_LEN: usize = 4;
 //TODO make this configurable
 pub const HTTP2_MAX_TABLESIZE: u32 = 0x10000; // 65536
 
+// Limits against header floods, configurable in app-layer.protocols.http2
+static mut HTTP2_MAX_CONTINUATION_FRAMES: u32 = 64;
+static mut HTTP2_MAX_HEADER_BLOCK_SIZE: usize = 0x40000; // 256 KiB
+static mut HTTP2_MAX_TABLE_SIZE_UPDATES: u32 = 16;
+
 #[repr(u8)]
 #[derive(Copy, Clone, PartialOrd, PartialEq, Debug)]
 pub enum HTTP2FrameUnhandledReason {
@@ -134,7 +140,7 @@ pub struct HTTP2Transaction {
     decoder: decompression::HTTP2Decoder,
     pub file_range: *mut HttpRangeContainerBlock,
 
-    de_state: Option<*mut core::DetectEngineState>,
+    de_state: DetectStateHolder,
     events: *mut core::AppLayerDecoderEvents,
     tx_data: AppLayerTxData,
     pub ft_tc: FileTransferTracker,
@@ -143,8 +149,16 @@ pub struct HTTP2Transaction {
     //temporary escaped header for detection
     //must be attached to transaction for memory management (be freed at the right time)
     pub escaped: Vec<Vec<u8>>,
+
+    //header limits tracking, by direction
+    continuation_frames_ts: u32,
+    continuation_frames_tc: u32,
+    size_updates_ts: u32,
+    size_updates_tc: u32,
 }
 
+unsafe impl FfiType for HTTP2Transaction {}
+
 impl HTTP2Transaction {
     pub fn new() -> HTTP2Transaction {
         HTTP2Transaction {
@@ -156,12 +170,16 @@ impl HTTP2Transaction {
             frames_ts: Vec::new(),
             decoder: decompression::HTTP2Decoder::new(),
             file_range: std::ptr::null_mut(),
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: AppLayerTxData::new(),
             ft_tc: FileTransferTracker::new(),
             ft_ts: FileTransferTracker::new(),
             escaped: Vec::with_capacity(16),
+            continuation_frames_ts: 0,
+            continuation_frames_tc: 0,
+            size_updates_ts: 0,
+            size_updates_tc: 0,
         }
     }
 
@@ -169,9 +187,6 @@ impl HTTP2Transaction {
         if self.events != std::ptr::null_mut() {
             core::sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        if let Some(state) = self.de_state {
-            core::sc_detect_engine_state_free(state);
-        }
         if self.file_range != std::ptr::null_mut() {
             match unsafe { SC } {
                 None => panic!("BUG no suricata_config"),
@@ -195,6 +210,52 @@ impl HTTP2Transaction {
         core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
     }
 
+    /// Checks the limits on the header frames of the stream, returns
+    /// false if the frame should not be stored.
+    fn check_header_limits(&mut self, data: &HTTP2FrameTypeData, dir: u8) -> bool {
+        let (continuation_frames, size_updates) = if dir == STREAM_TOSERVER {
+            (&mut self.continuation_frames_ts, &mut self.size_updates_ts)
+        } else {
+            (&mut self.continuation_frames_tc, &mut self.size_updates_tc)
+        };
+        let blocks = match data {
+            HTTP2FrameTypeData::HEADERS(hs) => {
+                *continuation_frames = 0;
+                &hs.blocks
+            }
+            HTTP2FrameTypeData::PUSHPROMISE(hs) => {
+                *continuation_frames = 0;
+                &hs.blocks
+            }
+            HTTP2FrameTypeData::CONTINUATION(hs) => {
+                *continuation_frames += 1;
+                &hs.blocks
+            }
+            _ => {
+                return true;
+            }
+        };
+        let updates = blocks
+            .iter()
+            .filter(|b| b.error == parser::HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeSizeUpdate)
+            .count() as u32;
+        let max_updates = unsafe { HTTP2_MAX_TABLE_SIZE_UPDATES };
+        let mut events = Vec::new();
+        if *size_updates <= max_updates && *size_updates + updates > max_updates {
+            events.push(HTTP2Event::TooManyTableSizeUpdates);
+        }
+        *size_updates = size_updates.saturating_add(updates);
+        let max_continuations = unsafe { HTTP2_MAX_CONTINUATION_FRAMES };
+        let store = *continuation_frames <= max_continuations;
+        if *continuation_frames == max_continuations + 1 {
+            events.push(HTTP2Event::TooManyContinuationFrames);
+        }
+        for event in events {
+            self.set_event(event);
+        }
+        return store;
+    }
+
     fn handle_headers(&mut self, blocks: &Vec<parser::HTTP2FrameHeaderBlock>, dir: u8) {
         for i in 0..blocks.len() {
             if blocks[i].name == "content-encoding".as_bytes().to_vec() {
@@ -373,6 +434,9 @@ pub enum HTTP2Event {
     InvalidHTTP1Settings,
     FailedDecompression,
     InvalidRange,
+    TooManyContinuationFrames,
+    HeaderBlockTooLarge,
+    TooManyTableSizeUpdates,
 }
 
 pub struct HTTP2DynTable {
@@ -380,6 +444,9 @@ pub struct HTTP2DynTable {
     pub current_size: usize,
     pub max_size: usize,
     pub overflow: u8,
+    /// decoded size of the header block being parsed
+    pub block_size: usize,
+    pub max_block_size: usize,
 }
 
 impl HTTP2DynTable {
@@ -389,6 +456,8 @@ impl HTTP2DynTable {
             current_size: 0,
             max_size: 4096, //default value
             overflow: 0,
+            block_size: 0,
+            max_block_size: unsafe { HTTP2_MAX_HEADER_BLOCK_SIZE },
         }
     }
 }
@@ -404,6 +473,8 @@ pub struct HTTP2State {
     pub files: Files,
 }
 
+unsafe impl FfiType for HTTP2State {}
+
 impl HTTP2State {
     pub fn new() -> Self {
         Self {
@@ -539,10 +610,21 @@ impl HTTP2State {
         }
     }
 
+    /// After an HTTP/1.1 upgrade, the request is stream 1, which is
+    /// half closed from the client (RFC 7540 section 3.2).
+    pub fn upgrade_handoff(&mut self) {
+        let index = self.find_tx_index(1);
+        if index > 0 {
+            self.transactions[index - 1].state = HTTP2TransactionState::HTTP2StateHalfClosedClient;
+        }
+    }
+
     fn process_headers(&mut self, blocks: &Vec<parser::HTTP2FrameHeaderBlock>, dir: u8) {
         let (mut update, mut sizeup) = (false, 0);
         for i in 0..blocks.len() {
-            if blocks[i].error >= parser::HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeError {
+            if blocks[i].error == parser::HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeTooLarge {
+                self.set_event(HTTP2Event::HeaderBlockTooLarge);
+            } else if blocks[i].error >= parser::HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeError {
                 self.set_event(HTTP2Event::InvalidHeader);
             } else if blocks[i].error
                 == parser::HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeSizeUpdate
@@ -849,20 +931,24 @@ impl HTTP2State {
                     );
 
                     let tx = self.find_or_create_tx(&head, &txdata, dir);
+                    let store = tx.check_header_limits(&txdata, dir);
                     tx.handle_frame(&head, &txdata, dir);
                     let over = head.flags & parser::HTTP2_FLAG_HEADER_EOS != 0;
                     let ftype = head.ftype;
                     let sid = head.stream_id;
-                    if dir == STREAM_TOSERVER {
-                        tx.frames_ts.push(HTTP2Frame {
-                            header: head,
-                            data: txdata,
-                        });
-                    } else {
-                        tx.frames_tc.push(HTTP2Frame {
-                            header: head,
-                            data: txdata,
-                        });
+                    //on a continuation flood, the frames are not kept
+                    if store {
+                        if dir == STREAM_TOSERVER {
+                            tx.frames_ts.push(HTTP2Frame {
+                                header: head,
+                                data: txdata,
+                            });
+                        } else {
+                            tx.frames_tc.push(HTTP2Frame {
+                                header: head,
+                                data: txdata,
+                            });
+                        }
                     }
                     if ftype == parser::HTTP2FrameType::DATA as u8 {
                         match unsafe { SURICATA_HTTP2_FILE_CONFIG } {
@@ -998,8 +1084,7 @@ impl HTTP2State {
 
 // C exports.
 
-export_tx_get_detect_state!(rs_http2_tx_get_detect_state, HTTP2Transaction);
-export_tx_set_detect_state!(rs_http2_tx_set_detect_state, HTTP2Transaction);
+export_tx_detect_state!(rs_http2_tx_get_detect_state, rs_http2_tx_set_detect_state, HTTP2Transaction);
 
 export_tx_data_get!(rs_http2_get_tx_data, HTTP2Transaction);
 
@@ -1048,14 +1133,15 @@ pub extern "C" fn rs_http2_state_new(
 ) -> *mut std::os::raw::c_void {
     let state = HTTP2State::new();
     let boxed = Box::new(state);
-    let r = Box::into_raw(boxed) as *mut _;
+    let r = Box::into_raw(boxed);
     if orig_state != std::ptr::null_mut() {
         //we could check ALPROTO_HTTP1 == orig_proto
         unsafe {
-            HTTP2MimicHttp1Request(orig_state, r);
+            HTTP2MimicHttp1Request(orig_state, r as *mut _);
+            (*r).upgrade_handoff();
         }
     }
-    return r;
+    return r as *mut _;
 }
 
 #[no_mangle]
@@ -1066,7 +1152,7 @@ pub unsafe extern "C" fn rs_http2_state_free(state: *mut std::os::raw::c_void) {
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_http2_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
-    let state = cast_pointer!(state, HTTP2State);
+    let state: &mut HTTP2State = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -1075,11 +1161,10 @@ pub unsafe extern "C" fn rs_http2_parse_ts(
     flow: *const Flow, state: *mut std::os::raw::c_void, _pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, HTTP2State);
+    let state: &mut HTTP2State = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
 
-    state.files.flags_ts = FileFlowToFlags(flow, STREAM_TOSERVER);
-    state.files.flags_ts = state.files.flags_ts | FILE_USE_DETECT;
+    state.files.update_flags(flow, Direction::ToServer);
     return state.parse_ts(buf, flow);
 }
 
@@ -1088,10 +1173,9 @@ pub unsafe extern "C" fn rs_http2_parse_tc(
     flow: *const Flow, state: *mut std::os::raw::c_void, _pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, HTTP2State);
+    let state: &mut HTTP2State = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
-    state.files.flags_tc = FileFlowToFlags(flow, STREAM_TOCLIENT);
-    state.files.flags_tc = state.files.flags_tc | FILE_USE_DETECT;
+    state.files.update_flags(flow, Direction::ToClient);
     return state.parse_tc(buf, flow);
 }
 
@@ -1099,7 +1183,7 @@ pub unsafe extern "C" fn rs_http2_parse_tc(
 pub unsafe extern "C" fn rs_http2_state_get_tx(
     state: *mut std::os::raw::c_void, tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, HTTP2State);
+    let state: &mut HTTP2State = cast_mut(state);
     match state.get_tx(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -1112,7 +1196,7 @@ pub unsafe extern "C" fn rs_http2_state_get_tx(
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_http2_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
-    let state = cast_pointer!(state, HTTP2State);
+    let state: &mut HTTP2State = cast_mut(state);
     return state.tx_id;
 }
 
@@ -1120,7 +1204,7 @@ pub unsafe extern "C" fn rs_http2_state_get_tx_count(state: *mut std::os::raw::c
 pub unsafe extern "C" fn rs_http2_tx_get_state(
     tx: *mut std::os::raw::c_void,
 ) -> HTTP2TransactionState {
-    let tx = cast_pointer!(tx, HTTP2Transaction);
+    let tx: &mut HTTP2Transaction = cast_mut(tx);
     return tx.state;
 }
 
@@ -1135,7 +1219,7 @@ pub unsafe extern "C" fn rs_http2_tx_get_alstate_progress(
 pub unsafe extern "C" fn rs_http2_state_get_events(
     tx: *mut std::os::raw::c_void,
 ) -> *mut core::AppLayerDecoderEvents {
-    let tx = cast_pointer!(tx, HTTP2Transaction);
+    let tx: &mut HTTP2Transaction = cast_mut(tx);
     return tx.events;
 }
 
@@ -1144,7 +1228,7 @@ pub unsafe extern "C" fn rs_http2_state_get_tx_iterator(
     _ipproto: u8, _alproto: AppProto, state: *mut std::os::raw::c_void, min_tx_id: u64,
     _max_tx_id: u64, istate: &mut u64,
 ) -> applayer::AppLayerGetTxIterTuple {
-    let state = cast_pointer!(state, HTTP2State);
+    let state: &mut HTTP2State = cast_mut(state);
     match state.tx_iterator(min_tx_id, istate) {
         Some((tx, out_tx_id, has_next)) => {
             let c_tx = tx as *const _ as *mut _;
@@ -1157,15 +1241,9 @@ pub unsafe extern "C" fn rs_http2_state_get_tx_iterator(
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_http2_getfiles(
-    state: *mut std::os::raw::c_void, direction: u8,
-) -> *mut FileContainer {
-    let state = cast_pointer!(state, HTTP2State);
-    if direction == STREAM_TOCLIENT {
-        &mut state.files.files_tc as *mut FileContainer
-    } else {
-        &mut state.files.files_ts as *mut FileContainer
+impl FilesState for HTTP2State {
+    fn files(&mut self) -> &mut Files {
+        &mut self.files
     }
 }
 
@@ -1174,6 +1252,29 @@ const PARSER_NAME: &'static [u8] = b"http2\0";
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_http2_register_parser() {
+    if let Some(conf) = conf_get_app_layer_node("http2") {
+        if let Some(val) = conf.get_child_u64("max-continuation-frames") {
+            if val > std::u32::MAX as u64 {
+                SCLogError!("Invalid value for max-continuation-frames: {}", val);
+            } else {
+                HTTP2_MAX_CONTINUATION_FRAMES = val as u32;
+            }
+        }
+        if let Some(val) = conf.get_child_memval("max-header-block-size") {
+            if val == 0 || val > std::u32::MAX as u64 {
+                SCLogError!("Invalid value for max-header-block-size: {}", val);
+            } else {
+                HTTP2_MAX_HEADER_BLOCK_SIZE = val as usize;
+            }
+        }
+        if let Some(val) = conf.get_child_u64("max-table-size-updates") {
+            if val > std::u32::MAX as u64 {
+                SCLogError!("Invalid value for max-table-size-updates: {}", val);
+            } else {
+                HTTP2_MAX_TABLE_SIZE_UPDATES = val as u32;
+            }
+        }
+    }
     let default_port = CString::new("[80]").unwrap();
     let parser = RustParser {
         name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
@@ -1200,12 +1301,13 @@ pub unsafe extern "C" fn rs_http2_register_parser() {
         get_eventinfo_byid: Some(HTTP2Event::get_event_info_by_id),
         localstorage_new: None,
         localstorage_free: None,
-        get_files: Some(rs_http2_getfiles),
+        get_files: Some(state_get_files::<HTTP2State>),
         get_tx_iterator: Some(rs_http2_state_get_tx_iterator),
         get_tx_data: rs_http2_get_tx_data,
         apply_tx_config: None,
         flags: 0,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
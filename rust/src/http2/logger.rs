@@ -17,6 +17,7 @@
 
 use super::http2::{HTTP2Frame, HTTP2FrameTypeData, HTTP2Transaction};
 use super::parser;
+use crate::applayer::cast_mut;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 use std;
 use std::collections::HashMap;
@@ -271,7 +272,7 @@ fn log_http2(tx: &HTTP2Transaction, js: &mut JsonBuilder) -> Result<bool, JsonEr
 pub unsafe extern "C" fn rs_http2_log_json(
     tx: *mut std::os::raw::c_void, js: &mut JsonBuilder,
 ) -> bool {
-    let tx = cast_pointer!(tx, HTTP2Transaction);
+    let tx: &mut HTTP2Transaction = cast_mut(tx);
     if let Ok(x) = log_http2(tx, js) {
         return x;
     }
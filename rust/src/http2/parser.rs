@@ -16,6 +16,7 @@
  */
 
 use super::huffman;
+use crate::applayer::FfiType;
 use crate::http2::http2::{HTTP2DynTable, HTTP2_MAX_TABLESIZE};
 use nom::character::complete::digit1;
 use nom::combinator::rest;
@@ -324,6 +325,7 @@ pub enum HTTP2HeaderDecodeStatus {
     HTTP2HeaderDecodeNotIndexed = 0x81,
     HTTP2HeaderDecodeIntegerOverflow = 0x82,
     HTTP2HeaderDecodeIndex0 = 0x83,
+    HTTP2HeaderDecodeTooLarge = 0x84,
 }
 
 impl fmt::Display for HTTP2HeaderDecodeStatus {
@@ -600,6 +602,53 @@ fn http2_parse_headers_block<'a>(
     }
 }
 
+/// Parses the header fields of a frame. Once the decoded size of the
+/// header block goes over the limit of the table, the fields are still
+/// decoded to keep the dynamic table in sync, but are not returned : a
+/// single block with HTTP2HeaderDecodeTooLarge status marks the truncation.
+fn http2_parse_headers_blocks<'a>(
+    input: &'a [u8], dyn_headers: &mut HTTP2DynTable,
+) -> IResult<&'a [u8], Vec<HTTP2FrameHeaderBlock>> {
+    let mut i3 = input;
+    let mut blocks = Vec::new();
+    while i3.len() > 0 {
+        match http2_parse_headers_block(i3, dyn_headers) {
+            Ok((rem, b)) => {
+                debug_validate_bug_on!(i3.len() == rem.len());
+                if i3.len() == rem.len() {
+                    //infinite loop
+                    return Err(Err::Error((input, ErrorKind::Eof)));
+                }
+                i3 = rem;
+                if b.error == HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeSizeUpdate {
+                    //needed to update the table size
+                    blocks.push(b);
+                    continue;
+                }
+                if dyn_headers.block_size > dyn_headers.max_block_size {
+                    //already truncated
+                    continue;
+                }
+                dyn_headers.block_size += b.name.len() + b.value.len();
+                if dyn_headers.block_size > dyn_headers.max_block_size {
+                    blocks.push(HTTP2FrameHeaderBlock {
+                        name: Vec::new(),
+                        value: Vec::new(),
+                        error: HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeTooLarge,
+                        sizeupdate: 0,
+                    });
+                } else {
+                    blocks.push(b);
+                }
+            }
+            Err(x) => {
+                return Err(x);
+            }
+        }
+    }
+    return Ok((i3, blocks));
+}
+
 #[derive(Clone, Debug)]
 pub struct HTTP2FrameHeaders {
     pub padlength: Option<u8>,
@@ -617,28 +666,14 @@ pub fn http2_parse_frame_headers<'a>(
     input: &'a [u8], flags: u8, dyn_headers: &mut HTTP2DynTable,
 ) -> IResult<&'a [u8], HTTP2FrameHeaders> {
     let (i2, padlength) = cond!(input, flags & HTTP2_FLAG_HEADER_PADDED != 0, be_u8)?;
-    let (mut i3, priority) = cond!(
+    let (i3, priority) = cond!(
         i2,
         flags & HTTP2_FLAG_HEADER_PRIORITY != 0,
         http2_parse_headers_priority
     )?;
-    let mut blocks = Vec::new();
-    while i3.len() > 0 {
-        match http2_parse_headers_block(i3, dyn_headers) {
-            Ok((rem, b)) => {
-                blocks.push(b);
-                debug_validate_bug_on!(i3.len() == rem.len());
-                if i3.len() == rem.len() {
-                    //infinite loop
-                    return Err(Err::Error((input, ErrorKind::Eof)));
-                }
-                i3 = rem;
-            }
-            Err(x) => {
-                return Err(x);
-            }
-        }
-    }
+    //new header block
+    dyn_headers.block_size = 0;
+    let (i3, blocks) = http2_parse_headers_blocks(i3, dyn_headers)?;
     return Ok((
         i3,
         HTTP2FrameHeaders {
@@ -661,24 +696,10 @@ pub fn http2_parse_frame_push_promise<'a>(
     input: &'a [u8], flags: u8, dyn_headers: &mut HTTP2DynTable,
 ) -> IResult<&'a [u8], HTTP2FramePushPromise> {
     let (i2, padlength) = cond!(input, flags & HTTP2_FLAG_HEADER_PADDED != 0, be_u8)?;
-    let (mut i3, stream_id) = bits!(i2, tuple!(take_bits!(1u8), take_bits!(31u32)))?;
-    let mut blocks = Vec::new();
-    while i3.len() > 0 {
-        match http2_parse_headers_block(i3, dyn_headers) {
-            Ok((rem, b)) => {
-                blocks.push(b);
-                debug_validate_bug_on!(i3.len() == rem.len());
-                if i3.len() == rem.len() {
-                    //infinite loop
-                    return Err(Err::Error((input, ErrorKind::Eof)));
-                }
-                i3 = rem;
-            }
-            Err(x) => {
-                return Err(x);
-            }
-        }
-    }
+    let (i3, stream_id) = bits!(i2, tuple!(take_bits!(1u8), take_bits!(31u32)))?;
+    //new header block
+    dyn_headers.block_size = 0;
+    let (i3, blocks) = http2_parse_headers_blocks(i3, dyn_headers)?;
     return Ok((
         i3,
         HTTP2FramePushPromise {
@@ -698,24 +719,7 @@ pub struct HTTP2FrameContinuation {
 pub fn http2_parse_frame_continuation<'a>(
     input: &'a [u8], dyn_headers: &mut HTTP2DynTable,
 ) -> IResult<&'a [u8], HTTP2FrameContinuation> {
-    let mut i3 = input;
-    let mut blocks = Vec::new();
-    while i3.len() > 0 {
-        match http2_parse_headers_block(i3, dyn_headers) {
-            Ok((rem, b)) => {
-                blocks.push(b);
-                debug_validate_bug_on!(i3.len() == rem.len());
-                if i3.len() == rem.len() {
-                    //infinite loop
-                    return Err(Err::Error((input, ErrorKind::Eof)));
-                }
-                i3 = rem;
-            }
-            Err(x) => {
-                return Err(x);
-            }
-        }
-    }
+    let (i3, blocks) = http2_parse_headers_blocks(input, dyn_headers)?;
     return Ok((i3, HTTP2FrameContinuation { blocks }));
 }
 
@@ -774,6 +778,8 @@ pub struct DetectHTTP2settingsSigCtx {
     pub value: Option<DetectU32Data>, //optional value
 }
 
+unsafe impl FfiType for DetectHTTP2settingsSigCtx {}
+
 named!(detect_parse_u32_start_equal<&str,DetectU32Data>,
     do_parse!(
         opt!( is_a!( " " ) ) >>
@@ -844,6 +850,8 @@ pub struct DetectU64Data {
     pub mode: DetectUintMode,
 }
 
+unsafe impl FfiType for DetectU64Data {}
+
 named!(detect_parse_u64_start_equal<&str,DetectU64Data>,
     do_parse!(
         opt!( is_a!( " " ) ) >>
@@ -922,6 +930,25 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_http2_parse_headers_block_size() {
+        let mut dynh = HTTP2DynTable::new();
+        // :method GET is 10 bytes
+        dynh.max_block_size = 10;
+        let buf: &[u8] = &[0x82, 0x82, 0x82];
+        let (_, hs) = http2_parse_frame_headers(buf, 0, &mut dynh).unwrap();
+        assert_eq!(hs.blocks.len(), 2);
+        assert_eq!(hs.blocks[0].error, HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeSuccess);
+        assert_eq!(hs.blocks[1].error, HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeTooLarge);
+        // the continuation belongs to the same truncated header block
+        let (_, hs) = http2_parse_frame_continuation(&[0x82], &mut dynh).unwrap();
+        assert_eq!(hs.blocks.len(), 0);
+        // a new header block
+        let (_, hs) = http2_parse_frame_headers(&[0x82], 0, &mut dynh).unwrap();
+        assert_eq!(hs.blocks.len(), 1);
+        assert_eq!(hs.blocks[0].error, HTTP2HeaderDecodeStatus::HTTP2HeaderDecodeSuccess);
+    }
+
     #[test]
     fn test_http2_parse_header() {
         let buf0: &[u8] = &[0x82];
@@ -0,0 +1,74 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Buffer on the URI of the requests, naming the service of the ICAP
+//! server.
+
+use super::icap::{IcapTransaction, ALPROTO_ICAP};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_ICAP_URI_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn icap_tx_get_uri(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut IcapTransaction = cast_mut(tx);
+    match tx.request {
+        Some(ref request) => {
+            *buf = request.uri.as_ptr();
+            *len = request.uri.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn icap_uri_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, icap_tx_get_uri)
+}
+
+unsafe extern "C" fn icap_uri_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_ICAP, G_ICAP_URI_BUFFER_ID)
+}
+
+/// Register the ICAP keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "icap.uri",
+        desc: "sticky buffer to match on the URI of ICAP requests",
+        url: "/rules/icap-keywords.html#icap-uri",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: icap_uri_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_ICAP_URI_BUFFER_ID = helper_buffer_mpm_register(
+            "icap.uri", "icap request uri", ALPROTO_ICAP, false, true, 0,
+            icap_uri_get_data);
+    }
+}
@@ -0,0 +1,1031 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(icap_stats, "app_layer.icap", {
+    requests,
+    responses,
+    options,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum IcapEvent {
+    MalformedData,
+    HeaderTooLong,
+    InvalidEncapsulated,
+    InvalidChunk,
+    UnauthenticatedOptions,
+    OptionsScan,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static ICAP_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static ICAP_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum length of the head of a message, and of the encapsulated
+/// HTTP headers following it.
+const ICAP_MAX_HEADER_LEN: usize = 65536;
+
+/// Maximum length of a chunk size or trailer line.
+const ICAP_MAX_CHUNK_LINE_LEN: usize = 1024;
+
+/// Number of services probed with unauthenticated OPTIONS requests
+/// after which a flow is reported as a scan.
+const ICAP_OPTIONS_SCAN_THRESHOLD: usize = 3;
+
+/// Number of bytes the probing parser looks into for the first line.
+const ICAP_PROBE_LEN: usize = 1024;
+
+pub static mut ALPROTO_ICAP: AppProto = ALPROTO_UNKNOWN;
+
+/// The encapsulated HTTP message of a request or response.
+#[derive(Debug, Default)]
+pub struct Encapsulated {
+    /// The sections of the Encapsulated header, with their offsets.
+    pub sections: Vec<(Section, u64)>,
+    /// The first line of the encapsulated HTTP request headers.
+    pub http_request_line: Option<Vec<u8>>,
+    /// The first line of the encapsulated HTTP response headers.
+    pub http_status_line: Option<Vec<u8>>,
+    /// The length of the body, without the chunk framing.
+    pub body_len: u64,
+}
+
+#[derive(Debug)]
+pub struct IcapRequest {
+    pub method: String,
+    pub uri: Vec<u8>,
+    pub version: String,
+    pub host: Option<Vec<u8>>,
+    pub user_agent: Option<Vec<u8>>,
+    pub preview: Option<u64>,
+    /// Whether the request has an Authorization or Proxy-Authorization
+    /// header.
+    pub authenticated: bool,
+    pub encapsulated: Encapsulated,
+}
+
+#[derive(Debug)]
+pub struct IcapResponse {
+    pub version: String,
+    pub status: u16,
+    pub reason: Vec<u8>,
+    pub istag: Option<Vec<u8>>,
+    pub service: Option<Vec<u8>>,
+    /// The methods of the service, in a response to OPTIONS.
+    pub methods: Option<Vec<u8>>,
+    pub encapsulated: Encapsulated,
+}
+
+/// A request and its final response. A response without a request,
+/// from a flow picked up in the middle, gets a transaction of its own.
+#[derive(Debug)]
+pub struct IcapTransaction {
+    id: u64,
+    pub request: Option<IcapRequest>,
+    pub response: Option<IcapResponse>,
+    pub request_done: bool,
+    pub response_done: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for IcapTransaction {}
+
+impl IcapTransaction {
+    fn new(id: u64) -> IcapTransaction {
+        IcapTransaction {
+            id,
+            request: None,
+            response: None,
+            request_done: false,
+            response_done: false,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: IcapEvent) {
+        stats_incr!(icap_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    fn encapsulated_mut(&mut self, direction: Direction) -> Option<&mut Encapsulated> {
+        if direction == Direction::ToServer {
+            self.request.as_mut().map(|r| &mut r.encapsulated)
+        } else {
+            self.response.as_mut().map(|r| &mut r.encapsulated)
+        }
+    }
+
+    fn set_done(&mut self, direction: Direction) {
+        if direction == Direction::ToServer {
+            self.request_done = true;
+        } else {
+            self.response_done = true;
+        }
+    }
+}
+
+impl Drop for IcapTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for IcapTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = IcapEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    /// The head of the next message.
+    Head,
+    /// The encapsulated HTTP headers, of `len` bytes, followed by a
+    /// chunked body if `body` is set.
+    Headers { len: usize, body: bool },
+    ChunkSize,
+    /// The data of a chunk, with the number of bytes left.
+    ChunkData(u64),
+    /// The line ending after the data of a chunk.
+    ChunkEnd,
+    /// The trailer after the last chunk, up to an empty line.
+    Trailer,
+}
+
+/// The message being parsed in a direction.
+struct Stream {
+    stage: Stage,
+    /// The id of the transaction of the message.
+    tx_id: u64,
+    /// Whether the body is a preview, after which the client waits for
+    /// the server to ask for the rest.
+    preview: bool,
+    /// Whether the last chunk had the "ieof" extension.
+    ieof: bool,
+}
+
+impl Stream {
+    fn new() -> Stream {
+        Stream {
+            stage: Stage::Head,
+            tx_id: 0,
+            preview: false,
+            ieof: false,
+        }
+    }
+}
+
+pub struct IcapState {
+    transactions: TxTable<IcapTransaction>,
+    ts: Stream,
+    tc: Stream,
+    /// The request whose preview ended without "ieof". The rest of its
+    /// body follows if the server answers with a 100 Continue.
+    preview_tx: Option<u64>,
+    /// The services probed by unauthenticated OPTIONS requests, up to
+    /// the scan threshold.
+    options_uris: Vec<Vec<u8>>,
+}
+
+unsafe impl FfiType for IcapState {}
+
+impl txtable::State for IcapState {
+    type Tx = IcapTransaction;
+
+    fn tx_table(&self) -> &TxTable<IcapTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<IcapTransaction> {
+        &mut self.transactions
+    }
+}
+
+/// The stage following the head of a message with the given
+/// Encapsulated sections, or None if the sections do not end with a
+/// body.
+fn stage_after_head(sections: &[(Section, u64)]) -> Option<Stage> {
+    let (last, offset) = match sections.last() {
+        Some(&last) => last,
+        None => return Some(Stage::Head),
+    };
+    if !last.is_body() {
+        return None;
+    }
+    let body = last != Section::NullBody;
+    if offset > 0 {
+        Some(Stage::Headers { len: offset as usize, body })
+    } else if body {
+        Some(Stage::ChunkSize)
+    } else {
+        Some(Stage::Head)
+    }
+}
+
+/// Split the first line off `input`, without its line ending, along
+/// with the rest of the input.
+fn split_line(input: &[u8]) -> Option<(&[u8], &[u8])> {
+    let pos = input.iter().position(|&b| b == b'\n')?;
+    let line = match pos {
+        0 => &input[..0],
+        _ if input[pos - 1] == b'\r' => &input[..pos - 1],
+        _ => &input[..pos],
+    };
+    Some((line, &input[pos + 1..]))
+}
+
+impl IcapState {
+    pub fn new() -> IcapState {
+        IcapState {
+            transactions: TxTable::with_limit(&ICAP_TX_LIMIT).with_gauges(&ICAP_GAUGES),
+            ts: Stream::new(),
+            tc: Stream::new(),
+            preview_tx: None,
+            options_uris: Vec::new(),
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    fn tx_mut(&mut self, id: u64) -> Option<&mut IcapTransaction> {
+        self.transactions.iter_mut().find(|tx| tx.id == id)
+    }
+
+    fn stream_mut(&mut self, direction: Direction) -> &mut Stream {
+        if direction == Direction::ToServer {
+            &mut self.ts
+        } else {
+            &mut self.tc
+        }
+    }
+
+    /// Set an event on the transaction of the message being parsed, or
+    /// on a new transaction between messages.
+    fn set_event(&mut self, direction: Direction, event: IcapEvent) {
+        let stream = self.stream_mut(direction);
+        let id = if stream.stage == Stage::Head { 0 } else { stream.tx_id };
+        if let Some(tx) = self.tx_mut(id) {
+            tx.set_event(event);
+            return;
+        }
+        let mut tx = IcapTransaction::new(self.transactions.next_id());
+        tx.request_done = true;
+        tx.response_done = true;
+        tx.set_event(event);
+        self.transactions.push(tx);
+    }
+
+    /// The message in `direction` is complete.
+    fn message_done(&mut self, direction: Direction) {
+        let stream = self.stream_mut(direction);
+        let id = stream.tx_id;
+        if direction == Direction::ToServer && stream.preview && !stream.ieof {
+            stream.preview = false;
+            self.preview_tx = Some(id);
+            return;
+        }
+        if let Some(tx) = self.tx_mut(id) {
+            tx.set_done(direction);
+        }
+    }
+
+    /// Start parsing the message of transaction `id` after its head.
+    fn start_message(&mut self, direction: Direction, id: u64, stage: Stage, preview: bool) {
+        let stream = self.stream_mut(direction);
+        stream.tx_id = id;
+        stream.stage = stage;
+        stream.preview = preview && stage != Stage::Head;
+        stream.ieof = false;
+        if stage == Stage::Head {
+            self.message_done(direction);
+        }
+    }
+
+    fn check_options(&mut self, tx: &mut IcapTransaction) {
+        let request = match tx.request {
+            Some(ref request) if request.method == "OPTIONS" => request,
+            _ => return,
+        };
+        stats_incr!(icap_stats::options);
+        if request.authenticated {
+            return;
+        }
+        let uri = request.uri.clone();
+        tx.set_event(IcapEvent::UnauthenticatedOptions);
+        if self.options_uris.len() < ICAP_OPTIONS_SCAN_THRESHOLD
+            && !self.options_uris.contains(&uri)
+        {
+            self.options_uris.push(uri);
+            if self.options_uris.len() == ICAP_OPTIONS_SCAN_THRESHOLD {
+                tx.set_event(IcapEvent::OptionsScan);
+            }
+        }
+    }
+
+    fn handle_request_head(&mut self, head: &[u8]) -> bool {
+        let mut head_lines = lines(head);
+        let request_line = match parse_request_line(head_lines.next().unwrap_or(&[])) {
+            Some(request_line) => request_line,
+            None => {
+                self.set_event(Direction::ToServer, IcapEvent::MalformedData);
+                return false;
+            }
+        };
+        stats_incr!(icap_stats::requests);
+        let mut request = IcapRequest {
+            method: request_line.method,
+            uri: request_line.uri.to_vec(),
+            version: request_line.version,
+            host: None,
+            user_agent: None,
+            preview: None,
+            authenticated: false,
+            encapsulated: Encapsulated::default(),
+        };
+        let mut invalid = false;
+        for (name, value) in head_lines.filter_map(parse_header) {
+            match String::from_utf8_lossy(name).to_ascii_lowercase().as_str() {
+                "host" => request.host = Some(value.to_vec()),
+                "user-agent" => request.user_agent = Some(value.to_vec()),
+                "preview" => {
+                    request.preview = std::str::from_utf8(value).ok().and_then(|v| v.parse().ok());
+                }
+                "authorization" | "proxy-authorization" => request.authenticated = true,
+                "encapsulated" => match parse_encapsulated(value) {
+                    Some(sections) => request.encapsulated.sections = sections,
+                    None => invalid = true,
+                },
+                _ => {}
+            }
+        }
+
+        // a preview that was not continued ends its request
+        if let Some(id) = self.preview_tx.take() {
+            if let Some(tx) = self.tx_mut(id) {
+                tx.request_done = true;
+            }
+        }
+
+        let mut tx = IcapTransaction::new(self.transactions.next_id());
+        let stage = match stage_after_head(&request.encapsulated.sections) {
+            Some(stage) => stage,
+            None => {
+                invalid = true;
+                Stage::Head
+            }
+        };
+        if invalid {
+            request.encapsulated.sections.clear();
+            tx.set_event(IcapEvent::InvalidEncapsulated);
+        }
+        let preview = request.preview.is_some();
+        tx.request = Some(request);
+        self.check_options(&mut tx);
+        let id = tx.id;
+        self.transactions.push(tx);
+        self.start_message(Direction::ToServer, id, stage, preview);
+        true
+    }
+
+    fn handle_response_head(&mut self, head: &[u8]) -> bool {
+        let mut head_lines = lines(head);
+        let status_line = match parse_status_line(head_lines.next().unwrap_or(&[])) {
+            Some(status_line) => status_line,
+            None => {
+                self.set_event(Direction::ToClient, IcapEvent::MalformedData);
+                return false;
+            }
+        };
+        if status_line.code == 100 {
+            // the server asks for the rest of the body after a preview
+            return true;
+        }
+        stats_incr!(icap_stats::responses);
+        let mut response = IcapResponse {
+            version: status_line.version,
+            status: status_line.code,
+            reason: status_line.reason.to_vec(),
+            istag: None,
+            service: None,
+            methods: None,
+            encapsulated: Encapsulated::default(),
+        };
+        let mut invalid = false;
+        for (name, value) in head_lines.filter_map(parse_header) {
+            match String::from_utf8_lossy(name).to_ascii_lowercase().as_str() {
+                "istag" => response.istag = Some(value.to_vec()),
+                "service" => response.service = Some(value.to_vec()),
+                "methods" => response.methods = Some(value.to_vec()),
+                "encapsulated" => match parse_encapsulated(value) {
+                    Some(sections) => response.encapsulated.sections = sections,
+                    None => invalid = true,
+                },
+                _ => {}
+            }
+        }
+
+        let stage = match stage_after_head(&response.encapsulated.sections) {
+            Some(stage) => stage,
+            None => {
+                invalid = true;
+                Stage::Head
+            }
+        };
+        if invalid {
+            response.encapsulated.sections.clear();
+        }
+        let pending = self.transactions.iter()
+            .find(|tx| tx.request.is_some() && tx.response.is_none())
+            .map(|tx| tx.id);
+        let id = match pending {
+            Some(id) => id,
+            None => {
+                let mut tx = IcapTransaction::new(self.transactions.next_id());
+                tx.request_done = true;
+                let id = tx.id;
+                self.transactions.push(tx);
+                id
+            }
+        };
+        if let Some(tx) = self.tx_mut(id) {
+            // a final response also ends a request whose preview was
+            // not continued
+            tx.request_done = true;
+            tx.response = Some(response);
+            if invalid {
+                tx.set_event(IcapEvent::InvalidEncapsulated);
+            }
+        }
+        self.start_message(Direction::ToClient, id, stage, false);
+        true
+    }
+
+    /// Keep the first lines of the encapsulated HTTP headers.
+    fn handle_headers(&mut self, direction: Direction, data: &[u8]) {
+        let id = self.stream_mut(direction).tx_id;
+        let encapsulated = match self.tx_mut(id).and_then(|tx| tx.encapsulated_mut(direction)) {
+            Some(encapsulated) => encapsulated,
+            None => return,
+        };
+        for &(section, offset) in &encapsulated.sections {
+            let start = std::cmp::min(offset as usize, data.len());
+            let line = match split_line(&data[start..]) {
+                Some((line, _)) => line.to_vec(),
+                None => data[start..].to_vec(),
+            };
+            match section {
+                Section::ReqHdr => encapsulated.http_request_line = Some(line),
+                Section::ResHdr => encapsulated.http_status_line = Some(line),
+                _ => {}
+            }
+        }
+    }
+
+    fn add_body_len(&mut self, direction: Direction, len: u64) {
+        let id = self.stream_mut(direction).tx_id;
+        if let Some(encapsulated) = self.tx_mut(id).and_then(|tx| tx.encapsulated_mut(direction)) {
+            encapsulated.body_len += len;
+        }
+    }
+
+    fn parse(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
+        let mut current = input;
+        while !current.is_empty() {
+            let consumed = input.len() - current.len();
+            let stage = self.stream_mut(direction).stage;
+            match stage {
+                Stage::Head => {
+                    // line endings between messages
+                    if current[0] == b'\r' || current[0] == b'\n' {
+                        current = &current[1..];
+                        continue;
+                    }
+                    // after a preview, the client either continues its
+                    // body or sends a new request
+                    if direction == Direction::ToServer && self.preview_tx.is_some() {
+                        let continuation = match split_line(current) {
+                            Some((line, _)) => parse_chunk_size(line).is_some(),
+                            None if current.len() < ICAP_MAX_CHUNK_LINE_LEN => {
+                                return AppLayerResult::incomplete_from(
+                                    input.len(), consumed, current.len() + 1);
+                            }
+                            None => false,
+                        };
+                        if continuation {
+                            let id = self.preview_tx.take().unwrap_or(0);
+                            let stream = self.stream_mut(direction);
+                            stream.tx_id = id;
+                            stream.stage = Stage::ChunkSize;
+                            continue;
+                        }
+                    }
+                    let end = match find_head_end(current) {
+                        Some(end) => end,
+                        None if current.len() >= ICAP_MAX_HEADER_LEN => {
+                            self.set_event(direction, IcapEvent::HeaderTooLong);
+                            return AppLayerResult::err();
+                        }
+                        None => {
+                            return AppLayerResult::incomplete_from(
+                                input.len(), consumed, current.len() + 1);
+                        }
+                    };
+                    let ok = if direction == Direction::ToServer {
+                        self.handle_request_head(&current[..end])
+                    } else {
+                        self.handle_response_head(&current[..end])
+                    };
+                    if !ok {
+                        return AppLayerResult::err();
+                    }
+                    current = &current[end..];
+                }
+                Stage::Headers { len, body } => {
+                    if len > ICAP_MAX_HEADER_LEN {
+                        self.set_event(direction, IcapEvent::HeaderTooLong);
+                        return AppLayerResult::err();
+                    }
+                    if current.len() < len {
+                        return AppLayerResult::incomplete_from(input.len(), consumed, len);
+                    }
+                    self.handle_headers(direction, &current[..len]);
+                    current = &current[len..];
+                    if body {
+                        self.stream_mut(direction).stage = Stage::ChunkSize;
+                    } else {
+                        self.stream_mut(direction).stage = Stage::Head;
+                        self.message_done(direction);
+                    }
+                }
+                Stage::ChunkSize | Stage::Trailer => {
+                    let (line, rest) = match split_line(current) {
+                        Some(split) => split,
+                        None if current.len() >= ICAP_MAX_CHUNK_LINE_LEN => {
+                            self.set_event(direction, IcapEvent::InvalidChunk);
+                            return AppLayerResult::err();
+                        }
+                        None => {
+                            return AppLayerResult::incomplete_from(
+                                input.len(), consumed, current.len() + 1);
+                        }
+                    };
+                    current = rest;
+                    let stream = self.stream_mut(direction);
+                    if stream.stage == Stage::Trailer {
+                        if line.is_empty() {
+                            stream.stage = Stage::Head;
+                            self.message_done(direction);
+                        }
+                        continue;
+                    }
+                    match parse_chunk_size(line) {
+                        Some((0, ieof)) => {
+                            stream.ieof = ieof;
+                            stream.stage = Stage::Trailer;
+                        }
+                        Some((size, _)) => {
+                            stream.stage = Stage::ChunkData(size);
+                        }
+                        None => {
+                            self.set_event(direction, IcapEvent::InvalidChunk);
+                            return AppLayerResult::err();
+                        }
+                    }
+                }
+                Stage::ChunkData(left) => {
+                    let len = std::cmp::min(left, current.len() as u64);
+                    self.add_body_len(direction, len);
+                    current = &current[len as usize..];
+                    self.stream_mut(direction).stage = if len == left {
+                        Stage::ChunkEnd
+                    } else {
+                        Stage::ChunkData(left - len)
+                    };
+                }
+                Stage::ChunkEnd => {
+                    let end = if current.starts_with(b"\n") {
+                        1
+                    } else if current.starts_with(b"\r\n") {
+                        2
+                    } else if current == b"\r" {
+                        return AppLayerResult::incomplete_from(input.len(), consumed, 2);
+                    } else {
+                        self.set_event(direction, IcapEvent::InvalidChunk);
+                        return AppLayerResult::err();
+                    };
+                    current = &current[end..];
+                    self.stream_mut(direction).stage = Stage::ChunkSize;
+                }
+            }
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for a request or status line.
+fn icap_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    let line = match split_line(input) {
+        Some((line, _)) => line,
+        None if input.len() < ICAP_PROBE_LEN => return ProbeResult::Unknown,
+        None => return ProbeResult::Failed,
+    };
+    let actual = if parse_request_line(line).is_some() {
+        Direction::ToServer
+    } else if parse_status_line(line).is_some() {
+        Direction::ToClient
+    } else {
+        return ProbeResult::Failed;
+    };
+    ProbeResult::with_direction(unsafe { ALPROTO_ICAP }, direction, actual)
+}
+
+export_probe!(rs_icap_probing_parser, icap_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_icap_state_new(_orig_state: *mut std::os::raw::c_void,
+                                    _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = IcapState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_icap_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut IcapState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_icap_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut IcapState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_icap_parse_request(_flow: *const Flow,
+                                               state: *mut std::os::raw::c_void,
+                                               pstate: *mut std::os::raw::c_void,
+                                               input: *const u8,
+                                               input_len: u32,
+                                               _data: *const std::os::raw::c_void,
+                                               _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut IcapState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_icap_parse_response(_flow: *const Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut IcapState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_icap_state_get_tx(state: *mut std::os::raw::c_void,
+                                              tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut IcapState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_icap_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut IcapState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// A direction is complete once its message is parsed, body included.
+#[no_mangle]
+pub unsafe extern "C" fn rs_icap_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                         direction: u8) -> std::os::raw::c_int {
+    let tx: &mut IcapTransaction = cast_mut(tx);
+    let done = if Direction::from(direction) == Direction::ToServer {
+        tx.request_done
+    } else {
+        tx.response_done
+    };
+    done as std::os::raw::c_int
+}
+
+export_tx_detect_state!(rs_icap_state_get_tx_detect_state,
+                        rs_icap_state_set_tx_detect_state, IcapTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_icap_state_get_events(tx: *mut std::os::raw::c_void)
+                                                  -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut IcapTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_icap_get_tx_data, IcapTransaction);
+
+fn icap_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    ICAP_GAUGES.log(js)?;
+    js.set_uint("events", icap_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+fn register_pattern_probe() -> i8 {
+    // OPTIONS is shared with HTTP and RTSP, only the scheme of the URI
+    // tells ICAP apart
+    let patterns: [(&[u8], u16, u8); 4] = [
+        (b"REQMOD \0", 7, core::STREAM_TOSERVER),
+        (b"RESPMOD \0", 8, core::STREAM_TOSERVER),
+        (b"OPTIONS icap://\0", 15, core::STREAM_TOSERVER),
+        (b"ICAP/1.\0", 7, core::STREAM_TOCLIENT),
+    ];
+    for &(pattern, depth, direction) in &patterns {
+        unsafe {
+            if AppLayerProtoDetectPMRegisterPatternCSwPP(IPPROTO_TCP as u8, ALPROTO_ICAP,
+                                                         pattern.as_ptr() as *const std::os::raw::c_char,
+                                                         depth, 0, direction,
+                                                         rs_icap_probing_parser, 0, 0) < 0 {
+                SCLogDebug!("AppLayerProtoDetectPMRegisterPatternCSwPP FAILED");
+                return -1;
+            }
+        }
+    }
+    0
+}
+
+const PARSER_NAME: &[u8] = b"icap\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_icap_register_parser() {
+    icap_stats::register();
+    ICAP_TX_LIMIT.configure("icap", txtable::DEFAULT_MAX_TX);
+    introspect::register("icap", icap_introspect);
+    let default_port = CString::new("1344").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_TCP,
+        probe_ts: Some(rs_icap_probing_parser),
+        probe_tc: Some(rs_icap_probing_parser),
+        min_depth: 0,
+        max_depth: ICAP_PROBE_LEN as u16,
+        state_new: rs_icap_state_new,
+        state_free: rs_icap_state_free,
+        tx_free: rs_icap_state_tx_free,
+        parse_ts: rs_icap_parse_request,
+        parse_tc: rs_icap_parse_response,
+        get_tx_count: rs_icap_state_get_tx_count,
+        get_tx: rs_icap_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_icap_tx_get_alstate_progress,
+        get_de_state: rs_icap_state_get_tx_detect_state,
+        set_de_state: rs_icap_state_set_tx_detect_state,
+        get_events: Some(rs_icap_state_get_events),
+        get_eventinfo: Some(IcapEvent::get_event_info),
+        get_eventinfo_byid: Some(IcapEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<IcapState>),
+        get_tx_data: rs_icap_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<IcapState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_ICAP = alproto;
+        if register_pattern_probe() < 0 {
+            return;
+        }
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust icap parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for ICAP.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_icap_state_new,
+            state_free: rs_icap_state_free,
+            parse_ts: rs_icap_parse_request,
+            parse_tc: rs_icap_parse_response,
+            get_tx_count: rs_icap_state_get_tx_count,
+            get_tx: rs_icap_state_get_tx,
+            tx_free: rs_icap_state_tx_free,
+        })
+    }
+
+    const REQMOD: &[u8] = b"REQMOD icap://icap.example.net/reqmod ICAP/1.0\r\n\
+                            Host: icap.example.net\r\n\
+                            Encapsulated: req-hdr=0, req-body=48\r\n\
+                            \r\n\
+                            POST /upload HTTP/1.1\r\n\
+                            Host: www.example.com\r\n\
+                            \r\n\
+                            5\r\n\
+                            hello\r\n\
+                            0\r\n\
+                            \r\n";
+
+    #[test]
+    fn test_icap_reqmod() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, REQMOD));
+        assert!(h.feed(Direction::ToClient,
+                       b"ICAP/1.0 204 No Content\r\n\
+                         ISTag: \"W3E4R7U9-L2E4-2\"\r\n\
+                         Encapsulated: null-body=0\r\n\
+                         \r\n"));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<IcapTransaction>(0).unwrap();
+        assert!(tx.request_done);
+        assert!(tx.response_done);
+        let request = tx.request.as_ref().unwrap();
+        assert_eq!(request.method, "REQMOD");
+        assert_eq!(request.uri, b"icap://icap.example.net/reqmod".to_vec());
+        assert_eq!(request.host, Some(b"icap.example.net".to_vec()));
+        assert_eq!(request.encapsulated.sections,
+                   vec![(Section::ReqHdr, 0), (Section::ReqBody, 48)]);
+        assert_eq!(request.encapsulated.http_request_line,
+                   Some(b"POST /upload HTTP/1.1".to_vec()));
+        assert_eq!(request.encapsulated.body_len, 5);
+        let response = tx.response.as_ref().unwrap();
+        assert_eq!(response.status, 204);
+        assert_eq!(response.istag, Some(b"\"W3E4R7U9-L2E4-2\"".to_vec()));
+    }
+
+    #[test]
+    fn test_icap_byte_by_byte() {
+        let mut h = harness();
+        assert!(h.feed_segments(Direction::ToServer, REQMOD, 1));
+        assert!(h.feed_segments(Direction::ToServer, REQMOD, 7));
+        assert_eq!(h.tx_count(), 2);
+        for tx_id in 0..2 {
+            let tx = h.get_tx::<IcapTransaction>(tx_id).unwrap();
+            assert!(tx.request_done);
+            assert_eq!(tx.request.as_ref().unwrap().encapsulated.body_len, 5);
+        }
+    }
+
+    #[test]
+    fn test_icap_preview() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer,
+                       b"RESPMOD icap://icap.example.net/respmod ICAP/1.0\r\n\
+                         Host: icap.example.net\r\n\
+                         Preview: 4\r\n\
+                         Encapsulated: res-hdr=0, res-body=19\r\n\
+                         \r\n\
+                         HTTP/1.1 200 OK\r\n\
+                         \r\n\
+                         4\r\n\
+                         MZ\x90\x00\r\n\
+                         0\r\n\
+                         \r\n"));
+        let tx = h.get_tx::<IcapTransaction>(0).unwrap();
+        assert!(!tx.request_done);
+        assert!(h.feed(Direction::ToClient, b"ICAP/1.0 100 Continue\r\n\r\n"));
+        assert!(h.feed(Direction::ToServer, b"6\r\n\x03\x00\x00\x00\x04\x00\r\n0\r\n\r\n"));
+        assert!(h.feed(Direction::ToClient,
+                       b"ICAP/1.0 200 OK\r\n\
+                         Encapsulated: res-hdr=0, null-body=26\r\n\
+                         \r\n\
+                         HTTP/1.1 403 Forbidden\r\n\
+                         \r\n"));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<IcapTransaction>(0).unwrap();
+        assert!(tx.request_done);
+        assert!(tx.response_done);
+        let request = tx.request.as_ref().unwrap();
+        assert_eq!(request.preview, Some(4));
+        assert_eq!(request.encapsulated.http_status_line, Some(b"HTTP/1.1 200 OK".to_vec()));
+        assert_eq!(request.encapsulated.body_len, 10);
+        let response = tx.response.as_ref().unwrap();
+        assert_eq!(response.encapsulated.http_status_line,
+                   Some(b"HTTP/1.1 403 Forbidden".to_vec()));
+    }
+
+    #[test]
+    fn test_icap_options_scan() {
+        let mut h = harness();
+        for service in &["reqmod", "respmod", "avscan", "avscan"] {
+            let request = format!("OPTIONS icap://10.0.0.1/{} ICAP/1.0\r\n\
+                                   Host: 10.0.0.1\r\n\
+                                   Encapsulated: null-body=0\r\n\
+                                   \r\n", service);
+            assert!(h.feed(Direction::ToServer, request.as_bytes()));
+        }
+        assert!(h.feed(Direction::ToServer,
+                       b"OPTIONS icap://10.0.0.1/srv_clamav ICAP/1.0\r\n\
+                         Authorization: Basic dXNlcjpwYXNz\r\n\
+                         \r\n"));
+        assert!(h.feed(Direction::ToClient,
+                       b"ICAP/1.0 200 OK\r\n\
+                         Methods: REQMOD, RESPMOD\r\n\
+                         Service: C-ICAP/0.5.6 server - Antivirus service\r\n\
+                         Encapsulated: null-body=0\r\n\
+                         \r\n\
+                         ICAP/1.0 404 ICAP Service not found\r\n\
+                         \r\n"));
+        assert_eq!(h.tx_count(), 5);
+        let state = h.state::<IcapState>();
+        assert_eq!(state.options_uris.len(), ICAP_OPTIONS_SCAN_THRESHOLD);
+        let tx = h.get_tx::<IcapTransaction>(0).unwrap();
+        assert_eq!(tx.response.as_ref().unwrap().methods, Some(b"REQMOD, RESPMOD".to_vec()));
+        let tx = h.get_tx::<IcapTransaction>(1).unwrap();
+        assert_eq!(tx.response.as_ref().unwrap().status, 404);
+        let tx = h.get_tx::<IcapTransaction>(4).unwrap();
+        assert!(tx.request.as_ref().unwrap().authenticated);
+    }
+
+    #[test]
+    fn test_icap_invalid_chunk() {
+        let mut h = harness();
+        assert!(!h.feed(Direction::ToServer,
+                        b"REQMOD icap://icap.example.net/reqmod ICAP/1.0\r\n\
+                          Encapsulated: req-body=0\r\n\
+                          \r\n\
+                          zz\r\n"));
+        assert_eq!(h.tx_count(), 1);
+        assert!(!h.get_tx::<IcapTransaction>(0).unwrap().request_done);
+    }
+
+    #[test]
+    fn test_icap_probe() {
+        unsafe {
+            ALPROTO_ICAP = 42;
+        }
+        let options = b"OPTIONS icap://icap.example.net/avscan ICAP/1.0\r\n";
+        assert_eq!(icap_probe(options, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(icap_probe(options, Direction::ToClient), ProbeResult::Flipped(42));
+        assert_eq!(icap_probe(b"ICAP/1.0 200 OK\r\n", Direction::ToClient),
+                   ProbeResult::Found(42));
+        assert_eq!(icap_probe(b"REQMOD icap://", Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(icap_probe(b"OPTIONS * RTSP/1.0\r\n", Direction::ToServer),
+                   ProbeResult::Failed);
+    }
+}
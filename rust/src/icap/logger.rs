@@ -0,0 +1,85 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::icap::{Encapsulated, IcapTransaction};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log_encapsulated(js: &mut JsonBuilder, encapsulated: &Encapsulated) -> Result<(), JsonError> {
+    if !encapsulated.sections.is_empty() {
+        js.open_object("encapsulated")?;
+        for &(section, offset) in &encapsulated.sections {
+            js.set_uint(section.as_str(), offset)?;
+        }
+        js.close()?;
+    }
+    if let Some(ref line) = encapsulated.http_request_line {
+        js.set_string_from_bytes("http_request_line", line)?;
+    }
+    if let Some(ref line) = encapsulated.http_status_line {
+        js.set_string_from_bytes("http_status_line", line)?;
+    }
+    if encapsulated.body_len > 0 {
+        js.set_uint("body_length", encapsulated.body_len)?;
+    }
+    Ok(())
+}
+
+fn log(tx: &IcapTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("icap")?;
+    if let Some(ref request) = tx.request {
+        js.open_object("request")?;
+        js.set_string("method", &request.method)?;
+        js.set_string_from_bytes("uri", &request.uri)?;
+        js.set_string("version", &request.version)?;
+        if let Some(ref host) = request.host {
+            js.set_string_from_bytes("host", host)?;
+        }
+        if let Some(ref user_agent) = request.user_agent {
+            js.set_string_from_bytes("user_agent", user_agent)?;
+        }
+        if let Some(preview) = request.preview {
+            js.set_uint("preview", preview)?;
+        }
+        js.set_bool("authenticated", request.authenticated)?;
+        log_encapsulated(js, &request.encapsulated)?;
+        js.close()?;
+    }
+    if let Some(ref response) = tx.response {
+        js.open_object("response")?;
+        js.set_string("version", &response.version)?;
+        js.set_uint("status", response.status as u64)?;
+        js.set_string_from_bytes("reason", &response.reason)?;
+        if let Some(ref istag) = response.istag {
+            js.set_string_from_bytes("istag", istag)?;
+        }
+        if let Some(ref service) = response.service {
+            js.set_string_from_bytes("service", service)?;
+        }
+        if let Some(ref methods) = response.methods {
+            js.set_string_from_bytes("methods", methods)?;
+        }
+        log_encapsulated(js, &response.encapsulated)?;
+        js.close()?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_icap_to_json(tx: &mut IcapTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
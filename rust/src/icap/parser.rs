@@ -0,0 +1,258 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! ICAP message heads (RFC 3507). A message is a start line and
+//! headers as in HTTP, followed by the encapsulated HTTP headers and a
+//! chunked body, at the offsets given by the Encapsulated header.
+
+#[derive(Debug, PartialEq)]
+pub struct RequestLine<'a> {
+    pub method: String,
+    pub uri: &'a [u8],
+    pub version: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StatusLine<'a> {
+    pub version: String,
+    pub code: u16,
+    pub reason: &'a [u8],
+}
+
+/// The sections of the Encapsulated header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    ReqHdr,
+    ResHdr,
+    ReqBody,
+    ResBody,
+    OptBody,
+    NullBody,
+}
+
+impl Section {
+    fn from_name(name: &str) -> Option<Section> {
+        match name {
+            "req-hdr" => Some(Section::ReqHdr),
+            "res-hdr" => Some(Section::ResHdr),
+            "req-body" => Some(Section::ReqBody),
+            "res-body" => Some(Section::ResBody),
+            "opt-body" => Some(Section::OptBody),
+            "null-body" => Some(Section::NullBody),
+            _ => None,
+        }
+    }
+
+    /// The name of the section, for logging.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Section::ReqHdr => "req_hdr",
+            Section::ResHdr => "res_hdr",
+            Section::ReqBody => "req_body",
+            Section::ResBody => "res_body",
+            Section::OptBody => "opt_body",
+            Section::NullBody => "null_body",
+        }
+    }
+
+    pub fn is_body(&self) -> bool {
+        match self {
+            Section::ReqHdr | Section::ResHdr => false,
+            _ => true,
+        }
+    }
+}
+
+fn trim(mut value: &[u8]) -> &[u8] {
+    while let Some((first, rest)) = value.split_first() {
+        if !first.is_ascii_whitespace() {
+            break;
+        }
+        value = rest;
+    }
+    while let Some((last, rest)) = value.split_last() {
+        if !last.is_ascii_whitespace() {
+            break;
+        }
+        value = rest;
+    }
+    value
+}
+
+fn is_version(version: &[u8]) -> bool {
+    version.starts_with(b"ICAP/") && version.len() > 5
+        && version[5..].iter().all(|b| b.is_ascii_digit() || *b == b'.')
+}
+
+/// The offset just past the empty line ending the head of a message,
+/// starting at `input`.
+pub fn find_head_end(input: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    while let Some(pos) = input[start..].iter().position(|&b| b == b'\n') {
+        let end = start + pos + 1;
+        let rest = &input[end..];
+        if rest.starts_with(b"\n") {
+            return Some(end + 1);
+        }
+        if rest.starts_with(b"\r\n") {
+            return Some(end + 2);
+        }
+        start = end;
+    }
+    None
+}
+
+/// Split a head into its lines, without their line endings.
+pub fn lines(head: &[u8]) -> impl Iterator<Item = &[u8]> {
+    head.split(|&b| b == b'\n')
+        .map(|line| match line.last() {
+            Some(b'\r') => &line[..line.len() - 1],
+            _ => line,
+        })
+}
+
+/// Parse a request line: "METHOD icap://host/service ICAP/1.0".
+pub fn parse_request_line(line: &[u8]) -> Option<RequestLine<'_>> {
+    let mut fields = line.split(|&b| b == b' ').filter(|f| !f.is_empty());
+    let method = fields.next()?;
+    let uri = fields.next()?;
+    let version = fields.next()?;
+    if fields.next().is_some() || !method.iter().all(|b| b.is_ascii_uppercase())
+        || !is_version(version)
+    {
+        return None;
+    }
+    Some(RequestLine {
+        method: String::from_utf8_lossy(method).into_owned(),
+        uri,
+        version: String::from_utf8_lossy(version).into_owned(),
+    })
+}
+
+/// Parse a status line: "ICAP/1.0 200 OK".
+pub fn parse_status_line(line: &[u8]) -> Option<StatusLine<'_>> {
+    let mut fields = line.splitn(3, |&b| b == b' ');
+    let version = fields.next()?;
+    let code = fields.next()?;
+    if !is_version(version) || code.len() != 3 || !code.iter().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(StatusLine {
+        version: String::from_utf8_lossy(version).into_owned(),
+        code: code.iter().fold(0u16, |code, b| code * 10 + (b - b'0') as u16),
+        reason: fields.next().unwrap_or(&[]),
+    })
+}
+
+/// Parse a header line into its name and value. Returns None for lines
+/// without a colon.
+pub fn parse_header(line: &[u8]) -> Option<(&[u8], &[u8])> {
+    let colon = line.iter().position(|&b| b == b':')?;
+    let name = trim(&line[..colon]);
+    if name.is_empty() {
+        return None;
+    }
+    Some((name, trim(&line[colon + 1..])))
+}
+
+/// Parse the value of an Encapsulated header, "req-hdr=0, req-body=412".
+/// The offsets must be increasing, and only the last section may be a
+/// body.
+pub fn parse_encapsulated(value: &[u8]) -> Option<Vec<(Section, u64)>> {
+    let value = std::str::from_utf8(value).ok()?;
+    let mut sections: Vec<(Section, u64)> = Vec::new();
+    for entry in value.split(',') {
+        let mut parts = entry.trim().splitn(2, '=');
+        let name = parts.next()?;
+        let offset = parts.next()?;
+        let section = Section::from_name(&name.trim().to_ascii_lowercase())?;
+        let offset: u64 = offset.trim().parse().ok()?;
+        if let Some(&(previous, previous_offset)) = sections.last() {
+            if previous.is_body() || offset < previous_offset {
+                return None;
+            }
+        }
+        sections.push((section, offset));
+    }
+    Some(sections)
+}
+
+/// Parse a chunk size line, without its line ending, into the size and
+/// whether the chunk carries the "ieof" extension, closing a preview.
+pub fn parse_chunk_size(line: &[u8]) -> Option<(u64, bool)> {
+    let mut parts = line.split(|&b| b == b';');
+    let size = std::str::from_utf8(trim(parts.next()?)).ok()?;
+    if size.is_empty() || size.len() > 16 {
+        return None;
+    }
+    let size = u64::from_str_radix(size, 16).ok()?;
+    let ieof = parts.any(|ext| trim(ext).eq_ignore_ascii_case(b"ieof"));
+    Some((size, ieof))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_start_lines() {
+        assert_eq!(parse_request_line(b"REQMOD icap://icap.example.net/reqmod ICAP/1.0"),
+                   Some(RequestLine {
+                       method: "REQMOD".to_string(),
+                       uri: b"icap://icap.example.net/reqmod",
+                       version: "ICAP/1.0".to_string(),
+                   }));
+        assert_eq!(parse_request_line(b"GET / HTTP/1.1"), None);
+        assert_eq!(parse_request_line(b"OPTIONS icap://host/avscan"), None);
+        assert_eq!(parse_status_line(b"ICAP/1.0 204 No modifications needed"),
+                   Some(StatusLine {
+                       version: "ICAP/1.0".to_string(),
+                       code: 204,
+                       reason: b"No modifications needed",
+                   }));
+        assert_eq!(parse_status_line(b"ICAP/1.0 20 OK"), None);
+        assert_eq!(parse_status_line(b"HTTP/1.1 200 OK"), None);
+    }
+
+    #[test]
+    fn test_find_head_end() {
+        assert_eq!(find_head_end(b"ICAP/1.0 200 OK\r\nISTag: \"1\"\r\n\r\nGET"), Some(31));
+        assert_eq!(find_head_end(b"ICAP/1.0 200 OK\n\nGET"), Some(17));
+        assert_eq!(find_head_end(b"ICAP/1.0 200 OK\r\nISTag: \"1\"\r\n"), None);
+    }
+
+    #[test]
+    fn test_parse_encapsulated() {
+        assert_eq!(parse_encapsulated(b"req-hdr=0, res-hdr=137, res-body=296"),
+                   Some(vec![(Section::ReqHdr, 0), (Section::ResHdr, 137),
+                             (Section::ResBody, 296)]));
+        assert_eq!(parse_encapsulated(b"null-body=0"), Some(vec![(Section::NullBody, 0)]));
+        assert_eq!(parse_encapsulated(b"req-hdr=10, res-hdr=5"), None);
+        assert_eq!(parse_encapsulated(b"req-body=0, req-hdr=5"), None);
+        assert_eq!(parse_encapsulated(b"foo-hdr=0"), None);
+        assert_eq!(parse_encapsulated(b"req-hdr"), None);
+    }
+
+    #[test]
+    fn test_parse_chunk_size() {
+        assert_eq!(parse_chunk_size(b"1e"), Some((30, false)));
+        assert_eq!(parse_chunk_size(b"0; ieof"), Some((0, true)));
+        assert_eq!(parse_chunk_size(b""), None);
+        assert_eq!(parse_chunk_size(b"REQMOD icap://x ICAP/1.0"), None);
+        assert_eq!(parse_chunk_size(b"fffffffffffffffff"), None);
+    }
+}
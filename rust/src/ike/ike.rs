@@ -22,17 +22,26 @@ use self::ipsec_parser::*;
 
 use crate::applayer;
 use crate::applayer::*;
-use crate::core::{
-    self, AppProto, Flow, ALPROTO_FAILED, ALPROTO_UNKNOWN, STREAM_TOCLIENT, STREAM_TOSERVER,
-};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN};
 use crate::ike::ikev1::{handle_ikev1, IkeV1Header, Ikev1Container};
 use crate::ike::ikev2::{handle_ikev2, Ikev2Container};
 use crate::ike::parser::*;
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use crate::x509::Certificate;
 use nom;
 use std;
 use std::collections::HashSet;
 use std::ffi::CString;
 
+declare_counters!(ike_stats, "app_layer.ike", {
+    ikev1,
+    ikev2,
+    malformed,
+    anomalies,
+});
+
 #[derive(AppLayerEvent)]
 pub enum IkeEvent {
     MalformedData,
@@ -47,8 +56,20 @@ pub enum IkeEvent {
     UnknownProposal,
     PayloadExtraData,
     MultipleServerProposal,
+    InvalidCertificate,
+    TooManyTransactions,
 }
 
+/// Encoding of the CERT payloads holding a X.509 certificate, the same
+/// for IKEv1 (RFC 2408) and IKEv2 (RFC 7296)
+const IKE_CERT_ENCODING_X509_SIGNATURE: u8 = 4;
+
+/// Maximum number of live transactions per flow
+static IKE_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static IKE_GAUGES: StateGauges = StateGauges::new();
+
 pub struct IkeHeaderWrapper {
     pub spi_initiator: String,
     pub spi_responder: String,
@@ -106,12 +127,16 @@ pub struct IKETransaction {
     /// errors seen during exchange
     pub errors: u32,
 
-    logged: LoggerFlags,
-    de_state: Option<*mut core::DetectEngineState>,
+    /// certificates of the CERT payloads
+    pub certificates: Vec<Certificate>,
+
+    de_state: DetectStateHolder,
     events: *mut core::AppLayerDecoderEvents,
     tx_data: applayer::AppLayerTxData,
 }
 
+unsafe impl FfiType for IKETransaction {}
+
 impl IKETransaction {
     pub fn new() -> IKETransaction {
         IKETransaction {
@@ -120,11 +145,11 @@ impl IKETransaction {
             hdr: IkeHeaderWrapper::new(),
             payload_types: Default::default(),
             notify_types: vec![],
-            logged: LoggerFlags::new(),
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: applayer::AppLayerTxData::new(),
             errors: 0,
+            certificates: Vec::new(),
         }
     }
 
@@ -132,16 +157,42 @@ impl IKETransaction {
         if self.events != std::ptr::null_mut() {
             core::sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        if let Some(state) = self.de_state {
-            core::sc_detect_engine_state_free(state);
-        }
     }
 
     /// Set an event.
     pub fn set_event(&mut self, event: IkeEvent) {
+        stats_incr!(ike_stats::anomalies);
         let ev = event as u8;
         core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
     }
+
+    /// Add the certificate of a CERT payload, if it is a X.509 one.
+    pub fn add_certificate(&mut self, encoding: u8, data: &[u8]) {
+        if encoding != IKE_CERT_ENCODING_X509_SIGNATURE {
+            return;
+        }
+        match Certificate::parse(data) {
+            Ok(cert) => self.certificates.push(cert),
+            Err(_e) => {
+                SCLogDebug!("Invalid certificate: {:?}", _e);
+                self.set_event(IkeEvent::InvalidCertificate);
+            }
+        }
+    }
+}
+
+impl Transaction for IKETransaction {
+    fn id(&self) -> u64 {
+        self.tx_id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        self.set_event(IkeEvent::TooManyTransactions);
+    }
 }
 
 impl Drop for IKETransaction {
@@ -152,45 +203,34 @@ impl Drop for IKETransaction {
 
 #[derive(Default)]
 pub struct IKEState {
-    tx_id: u64,
-    pub transactions: Vec<IKETransaction>,
+    pub transactions: TxTable<IKETransaction>,
 
     pub ikev1_container: Ikev1Container,
     pub ikev2_container: Ikev2Container,
 }
 
+unsafe impl FfiType for IKEState {}
+
 impl IKEState {
     // Free a transaction by ID.
     fn free_tx(&mut self, tx_id: u64) {
-        let tx = self
-            .transactions
-            .iter()
-            .position(|tx| tx.tx_id == tx_id + 1);
-        debug_assert!(tx != None);
-        if let Some(idx) = tx {
-            let _ = self.transactions.remove(idx);
-        }
+        self.transactions.free(tx_id);
     }
 
     pub fn get_tx(&mut self, tx_id: u64) -> Option<&mut IKETransaction> {
-        for tx in &mut self.transactions {
-            if tx.tx_id == tx_id + 1 {
-                return Some(tx);
-            }
-        }
-        return None;
+        self.transactions.get_mut(tx_id)
     }
 
     pub fn new_tx(&mut self) -> IKETransaction {
         let mut tx = IKETransaction::new();
-        self.tx_id += 1;
-        tx.tx_id = self.tx_id;
+        tx.tx_id = self.transactions.next_id();
         return tx;
     }
 
     /// Set an event. The event is set on the most recent transaction.
     pub fn set_event(&mut self, event: IkeEvent) {
         if let Some(tx) = self.transactions.last_mut() {
+            stats_incr!(ike_stats::anomalies);
             let ev = event as u8;
             core::sc_app_layer_decoder_events_set_event_raw(&mut tx.events, ev);
         } else {
@@ -201,7 +241,7 @@ impl IKEState {
         }
     }
 
-    fn handle_input(&mut self, input: &[u8], direction: u8) -> AppLayerResult {
+    fn handle_input(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
         // We're not interested in empty requests.
         if input.len() == 0 {
             return AppLayerResult::ok();
@@ -218,8 +258,10 @@ impl IKEState {
                 }
 
                 if isakmp_header.maj_ver == 1 {
+                    stats_incr!(ike_stats::ikev1);
                     handle_ikev1(self, current, isakmp_header, direction);
                 } else if isakmp_header.maj_ver == 2 {
+                    stats_incr!(ike_stats::ikev2);
                     handle_ikev2(self, current, isakmp_header, direction);
                 } else {
                     return AppLayerResult::err();
@@ -228,47 +270,44 @@ impl IKEState {
             }
             Err(nom::Err::Incomplete(_)) => {
                 SCLogDebug!("Insufficient data while parsing IKE");
+                stats_incr!(ike_stats::malformed);
                 return AppLayerResult::err();
             }
             Err(_) => {
                 SCLogDebug!("Error while parsing IKE packet");
+                stats_incr!(ike_stats::malformed);
                 return AppLayerResult::err();
             }
         }
     }
+}
 
-    fn tx_iterator(
-        &mut self, min_tx_id: u64, state: &mut u64,
-    ) -> Option<(&IKETransaction, u64, bool)> {
-        let mut index = *state as usize;
-        let len = self.transactions.len();
-
-        while index < len {
-            let tx = &self.transactions[index];
-            if tx.tx_id < min_tx_id + 1 {
-                index += 1;
-                continue;
-            }
-            *state = index as u64;
+impl txtable::State for IKEState {
+    type Tx = IKETransaction;
 
-            return Some((tx, tx.tx_id - 1, (len - index) > 1));
-        }
+    fn tx_table(&self) -> &TxTable<IKETransaction> {
+        &self.transactions
+    }
 
-        return None;
+    fn tx_table_mut(&mut self) -> &mut TxTable<IKETransaction> {
+        &mut self.transactions
     }
 }
 
 /// Probe to see if this input looks like a request or response.
-fn probe(input: &[u8], direction: u8, rdir: *mut u8) -> bool {
+fn probe(input: &[u8], direction: Direction) -> ProbeResult {
+    if input.len() < 28 {
+        // at least the ISAKMP_HEADER must be there, not ALPROTO_UNKNOWN because over UDP
+        return ProbeResult::Failed;
+    }
+    let alproto = unsafe { ALPROTO_IKE };
     match parse_isakmp_header(input) {
         Ok((_, isakmp_header)) => {
             if isakmp_header.maj_ver == 1 {
-                if isakmp_header.resp_spi == 0 && direction != STREAM_TOSERVER {
-                    unsafe {
-                        *rdir = STREAM_TOSERVER;
-                    }
+                if isakmp_header.resp_spi == 0 {
+                    return ProbeResult::with_direction(alproto, direction, Direction::ToServer);
                 }
-                return true;
+                return ProbeResult::Found(alproto);
             } else if isakmp_header.maj_ver == 2 {
                 if isakmp_header.min_ver != 0 {
                     SCLogDebug!(
@@ -276,60 +315,44 @@ fn probe(input: &[u8], direction: u8, rdir: *mut u8) -> bool {
                         isakmp_header.maj_ver,
                         isakmp_header.min_ver
                     );
-                    return false;
+                    return ProbeResult::Failed;
                 }
                 if isakmp_header.exch_type < 34 || isakmp_header.exch_type > 37 {
                     SCLogDebug!("ipsec_probe: could be ipsec, but with unsupported/invalid exchange type {}",
                            isakmp_header.exch_type);
-                    return false;
+                    return ProbeResult::Failed;
                 }
                 if isakmp_header.length as usize != input.len() {
                     SCLogDebug!("ipsec_probe: could be ipsec, but length does not match");
-                    return false;
+                    return ProbeResult::Failed;
                 }
 
-                if isakmp_header.resp_spi == 0 && direction != STREAM_TOSERVER {
-                    unsafe {
-                        *rdir = STREAM_TOSERVER;
-                    }
+                if isakmp_header.resp_spi == 0 {
+                    return ProbeResult::with_direction(alproto, direction, Direction::ToServer);
                 }
-                return true;
+                return ProbeResult::Found(alproto);
             }
 
-            return false;
+            return ProbeResult::Failed;
         }
-        Err(_) => return false,
+        Err(_) => return ProbeResult::Failed,
     }
 }
 
 // C exports.
-export_tx_get_detect_state!(rs_ike_tx_get_detect_state, IKETransaction);
-export_tx_set_detect_state!(rs_ike_tx_set_detect_state, IKETransaction);
+export_tx_detect_state!(rs_ike_tx_get_detect_state, rs_ike_tx_set_detect_state, IKETransaction);
 
-/// C entry point for a probing parser.
-#[no_mangle]
-pub unsafe extern "C" fn rs_ike_probing_parser(
-    _flow: *const Flow, direction: u8, input: *const u8, input_len: u32, rdir: *mut u8,
-) -> AppProto {
-    if input_len < 28 {
-        // at least the ISAKMP_HEADER must be there, not ALPROTO_UNKNOWN because over UDP
-        return ALPROTO_FAILED;
-    }
-
-    if input != std::ptr::null_mut() {
-        let slice = build_slice!(input, input_len as usize);
-        if probe(slice, direction, rdir) {
-            return ALPROTO_IKE ;
-        }
-    }
-    return ALPROTO_FAILED;
-}
+// C entry point for a probing parser.
+export_probe!(rs_ike_probing_parser, probe);
 
 #[no_mangle]
 pub extern "C" fn rs_ike_state_new(
     _orig_state: *mut std::os::raw::c_void, _orig_proto: AppProto,
 ) -> *mut std::os::raw::c_void {
-    let state = IKEState::default();
+    let state = IKEState {
+        transactions: TxTable::with_limit(&IKE_TX_LIMIT).with_gauges(&IKE_GAUGES),
+        ..Default::default()
+    };
     let boxed = Box::new(state);
     return Box::into_raw(boxed) as *mut _;
 }
@@ -342,7 +365,7 @@ pub unsafe extern "C" fn rs_ike_state_free(state: *mut std::os::raw::c_void) {
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_ike_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
-    let state = cast_pointer!(state, IKEState);
+    let state: &mut IKEState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -351,10 +374,10 @@ pub unsafe extern "C" fn rs_ike_parse_request(
     _flow: *const Flow, state: *mut std::os::raw::c_void, _pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, IKEState);
+    let state: &mut IKEState = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
 
-    return state.handle_input(buf, STREAM_TOSERVER);
+    return state.handle_input(buf, Direction::ToServer);
 }
 
 #[no_mangle]
@@ -362,16 +385,16 @@ pub unsafe extern "C" fn rs_ike_parse_response(
     _flow: *const Flow, state: *mut std::os::raw::c_void, _pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, IKEState);
+    let state: &mut IKEState = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
-    return state.handle_input(buf, STREAM_TOCLIENT);
+    return state.handle_input(buf, Direction::ToClient);
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_ike_state_get_tx(
     state: *mut std::os::raw::c_void, tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, IKEState);
+    let state: &mut IKEState = cast_mut(state);
     match state.get_tx(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -384,8 +407,8 @@ pub unsafe extern "C" fn rs_ike_state_get_tx(
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_ike_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
-    let state = cast_pointer!(state, IKEState);
-    return state.tx_id;
+    let state: &mut IKEState = cast_mut(state);
+    return state.transactions.tx_count();
 }
 
 #[no_mangle]
@@ -401,58 +424,33 @@ pub extern "C" fn rs_ike_tx_get_alstate_progress(
     return 1;
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_ike_tx_get_logged(
-    _state: *mut std::os::raw::c_void, tx: *mut std::os::raw::c_void,
-) -> u32 {
-    let tx = cast_pointer!(tx, IKETransaction);
-    return tx.logged.get();
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_ike_tx_set_logged(
-    _state: *mut std::os::raw::c_void, tx: *mut std::os::raw::c_void, logged: u32,
-) {
-    let tx = cast_pointer!(tx, IKETransaction);
-    tx.logged.set(logged);
-}
-
 #[no_mangle]
 pub unsafe extern "C" fn rs_ike_state_get_events(
     tx: *mut std::os::raw::c_void,
 ) -> *mut core::AppLayerDecoderEvents {
-    let tx = cast_pointer!(tx, IKETransaction);
+    let tx: &mut IKETransaction = cast_mut(tx);
     return tx.events;
 }
 
 static mut ALPROTO_IKE : AppProto = ALPROTO_UNKNOWN;
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_ike_state_get_tx_iterator(
-    _ipproto: u8, _alproto: AppProto, state: *mut std::os::raw::c_void, min_tx_id: u64,
-    _max_tx_id: u64, istate: &mut u64,
-) -> applayer::AppLayerGetTxIterTuple {
-    let state = cast_pointer!(state, IKEState);
-    match state.tx_iterator(min_tx_id, istate) {
-        Some((tx, out_tx_id, has_next)) => {
-            let c_tx = tx as *const _ as *mut _;
-            let ires = applayer::AppLayerGetTxIterTuple::with_values(c_tx, out_tx_id, has_next);
-            return ires;
-        }
-        None => {
-            return applayer::AppLayerGetTxIterTuple::not_found();
-        }
-    }
+// Parser name as a C style string.
+
+fn ike_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    IKE_GAUGES.log(js)?;
+    js.set_uint("events", ike_stats::anomalies::COUNTER.get())?;
+    Ok(())
 }
 
-// Parser name as a C style string.
 const PARSER_NAME: &'static [u8] = b"ike\0";
-const PARSER_ALIAS: &'static [u8] = b"ikev2\0";
 
 export_tx_data_get!(rs_ike_get_tx_data, IKETransaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_ike_register_parser() {
+    ike_stats::register();
+    IKE_TX_LIMIT.configure("ike", txtable::DEFAULT_MAX_TX);
+    introspect::register("ike", ike_introspect);
     let default_port = CString::new("500").unwrap();
     let parser = RustParser {
         name               : PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
@@ -480,11 +478,12 @@ pub unsafe extern "C" fn rs_ike_register_parser() {
         localstorage_new   : None,
         localstorage_free  : None,
         get_files          : None,
-        get_tx_iterator    : None,
+        get_tx_iterator    : Some(txtable::state_get_tx_iterator::<IKEState>),
         get_tx_data        : rs_ike_get_tx_data,
-        apply_tx_config    : None,
+        apply_tx_config    : Some(txtable::state_apply_tx_config::<IKEState>),
         flags              : APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate           : None,
+        state_purge        : None,
     };
 
     let ip_proto_str = CString::new("udp").unwrap();
@@ -496,10 +495,7 @@ pub unsafe extern "C" fn rs_ike_register_parser() {
             let _ = AppLayerRegisterParser(&parser, alproto);
         }
 
-        AppLayerRegisterParserAlias(
-            PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
-            PARSER_ALIAS.as_ptr() as *const std::os::raw::c_char,
-        );
+        register_parser_aliases(&parser, &["ikev2"]);
         SCLogDebug!("Rust IKE parser registered.");
     } else {
         SCLogDebug!("Protocol detector and parser disabled for IKE.");
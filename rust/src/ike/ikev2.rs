@@ -18,7 +18,7 @@
 // written by Pierre Chifflier  <chifflier@wzdftpd.net>
 
 use crate::applayer::*;
-use crate::core::STREAM_TOCLIENT;
+use crate::core::Direction;
 use crate::ike::ipsec_parser::*;
 
 use super::ipsec_parser::IkeV2Transform;
@@ -99,7 +99,7 @@ impl Default for Ikev2Container {
 }
 
 pub fn handle_ikev2(
-    mut state: &mut IKEState, current: &[u8], isakmp_header: IsakmpHeader, direction: u8,
+    mut state: &mut IKEState, current: &[u8], isakmp_header: IsakmpHeader, direction: Direction,
 ) -> AppLayerResult {
     let hdr = IkeV2Header {
         init_spi: isakmp_header.init_spi,
@@ -140,7 +140,7 @@ pub fn handle_ikev2(
                     }
                     IkeV2PayloadContent::KE(ref kex) => {
                         SCLogDebug!("KEX {:?}", kex.dh_group);
-                        if direction == STREAM_TOCLIENT {
+                        if direction == Direction::ToClient {
                             state.ikev2_container.dh_group = kex.dh_group;
                         }
                     }
@@ -154,8 +154,10 @@ pub fn handle_ikev2(
                         }
                         notify_types.push(n.notify_type);
                     }
+                    IkeV2PayloadContent::Certificate(ref cert) => {
+                        tx.add_certificate(cert.cert_encoding.0, cert.cert_data);
+                    }
                     // XXX CertificateRequest
-                    // XXX Certificate
                     // XXX Authentication
                     // XXX TSi
                     // XXX TSr
@@ -181,7 +183,7 @@ pub fn handle_ikev2(
     return AppLayerResult::ok();
 }
 
-fn add_proposals(state: &mut IKEState, tx: &mut IKETransaction, prop: &Vec<IkeV2Proposal>, direction: u8) {
+fn add_proposals(state: &mut IKEState, tx: &mut IKETransaction, prop: &Vec<IkeV2Proposal>, direction: Direction) {
     for p in prop {
         let transforms: Vec<IkeV2Transform> = p.transforms.iter().map(|x| x.into()).collect();
         // Rule 1: warn on weak or unknown transforms
@@ -200,7 +202,7 @@ fn add_proposals(state: &mut IKEState, tx: &mut IKETransaction, prop: &Vec<IkeV2
                         | IkeTransformEncType::ENCR_DES_IV32
                         | IkeTransformEncType::ENCR_NULL => {
                             SCLogDebug!("Weak Encryption: {:?}", enc);
-                            // XXX send event only if direction == STREAM_TOCLIENT ?
+                            // XXX send event only if direction == Direction::ToClient ?
                             tx.set_event(IkeEvent::WeakCryptoEnc);
                         }
                         _ => (),
@@ -286,7 +288,7 @@ fn add_proposals(state: &mut IKEState, tx: &mut IKETransaction, prop: &Vec<IkeV2
             }
         }
         // Finally
-        if direction == STREAM_TOCLIENT {
+        if direction == Direction::ToClient {
             transforms.iter().for_each(|t| match *t {
                 IkeV2Transform::Encryption(ref e) => {
                     state.ikev2_container.alg_enc = *e;
@@ -17,8 +17,10 @@
 
 use super::ike::{IKEState, IKETransaction};
 use super::ipsec_parser::IKEV2_FLAG_INITIATOR;
+use crate::applayer::cast_mut;
 use crate::ike::parser::{ExchangeType, IsakmpPayloadType, SaAttribute};
 use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::x509::log_certificates;
 use std;
 use std::convert::TryFrom;
 use num_traits::FromPrimitive;
@@ -115,6 +117,8 @@ fn log_ike(
     }
     jb.close()?;
 
+    log_certificates(jb, "certificates", &tx.certificates)?;
+
     if tx.ike_version == 1 {
         log_ikev1(state, tx, jb)?;
     } else if tx.ike_version == 2 {
@@ -227,6 +231,6 @@ fn log_ikev2(tx: &IKETransaction, jb: &mut JsonBuilder) -> Result<(), JsonError>
 pub unsafe extern "C" fn rs_ike_logger_log(
     state: &mut IKEState, tx: *mut std::os::raw::c_void, flags: u32, js: &mut JsonBuilder,
 ) -> bool {
-    let tx = cast_pointer!(tx, IKETransaction);
+    let tx: &mut IKETransaction = cast_mut(tx);
     log_ike(state, tx, flags, js).is_ok()
 }
@@ -15,7 +15,7 @@
  * 02110-1301, USA.
  */
 
-use crate::common::to_hex;
+use crate::parsers_util::hex_id;
 use core::fmt;
 use nom::number::streaming::{be_u16, be_u32, be_u64, be_u8};
 use nom::*;
@@ -121,8 +121,8 @@ pub struct NoncePayload<'a> {
 // 12 -> Delete
 
 // 13 -> Vendor ID
-pub struct VendorPayload<'a> {
-    pub vendor_id: &'a [u8],
+pub struct VendorPayload {
+    pub vendor_id: String,
 }
 
 // Attributes inside Transform
@@ -348,7 +348,7 @@ pub fn parse_transform(i: &[u8], length: u16) -> IResult<&[u8], TransformPayload
 }
 
 pub fn parse_vendor_id(i: &[u8], length: u16) -> IResult<&[u8], VendorPayload> {
-    map!(i, take!(length), |v| VendorPayload { vendor_id: v })
+    map!(i, call!(hex_id(length as usize)), |v| VendorPayload { vendor_id: v })
 }
 
 fn get_attribute_type(v: u16) -> AttributeType {
@@ -459,7 +459,7 @@ named! { pub parse_sa_attribute<&[u8], Vec<SaAttribute>>,
                 format: bits!(tuple!(take_bits!(1u8),take_bits!(15u16))) >>
                 attribute_length_or_value: be_u16 >>  // depends on format bit: 1 -> value | 0 -> number of following bytes
                 numeric_variable_value: cond!(format.0 == 0 && attribute_length_or_value == 4, be_u32) >>  // interpret as number
-                variable_attribute_value: cond!(format.0 == 0 && attribute_length_or_value != 4, take!(attribute_length_or_value)) >>
+                variable_attribute_value: cond!(format.0 == 0 && attribute_length_or_value != 4, call!(hex_id(attribute_length_or_value as usize))) >>
                 (
                     SaAttribute {
                         attribute_format: format.0,
@@ -484,9 +484,7 @@ named! { pub parse_sa_attribute<&[u8], Vec<SaAttribute>>,
                             _ => None,
                         },
                         hex_value: match format.0 {
-                            0 => {
-                                variable_attribute_value.map(|_variable_attribute_value| to_hex(_variable_attribute_value))
-                            }
+                            0 => variable_attribute_value,
                             _ => None,
                         }
                     }
@@ -621,7 +619,7 @@ pub fn parse_payload<'a>(
         Some(IsakmpPayloadType::VendorID) => {
             let res = parse_vendor_id(data, data_length);
             if let Ok((_rem, payload)) = res {
-                vendor_ids.push(to_hex(payload.vendor_id));
+                vendor_ids.push(payload.vendor_id);
             }
             Ok(())
         }
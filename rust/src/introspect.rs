@@ -0,0 +1,178 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Live state summaries of the Rust app-layer parsers, for the
+//! `app-layer-state` unix socket command.
+//!
+//! A parser registers a summary function from its register function.
+//! The function adds the fields of its summary to a JSON object, usually
+//! from `StateGauges` counting its live states and transactions, its
+//! memcap use and its stats counters:
+//!
+//! ```ignore
+//! static NTP_GAUGES: StateGauges = StateGauges::new();
+//!
+//! fn ntp_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+//!     NTP_GAUGES.log(js)?;
+//!     js.set_uint("events", ntp_stats::anomalies::COUNTER.get())?;
+//!     Ok(())
+//! }
+//!
+//! // in the parser registration function
+//! introspect::register("ntp", ntp_introspect);
+//! ```
+
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use lazy_static::lazy_static;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Function adding the summary of a parser to a JSON object.
+pub type IntrospectFn = fn(&mut JsonBuilder) -> Result<(), JsonError>;
+
+/// Number of live states and transactions of a parser, shared by all its
+/// states. A `TxTable` created `with_gauges` keeps them up to date.
+#[derive(Debug)]
+pub struct StateGauges {
+    states: AtomicU64,
+    txs: AtomicU64,
+}
+
+impl StateGauges {
+    pub const fn new() -> StateGauges {
+        StateGauges {
+            states: AtomicU64::new(0),
+            txs: AtomicU64::new(0),
+        }
+    }
+
+    pub fn state_new(&self) {
+        self.states.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn state_free(&self) {
+        self.states.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn tx_new(&self) {
+        self.txs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn tx_free(&self, count: u64) {
+        self.txs.fetch_sub(count, Ordering::Relaxed);
+    }
+
+    pub fn states(&self) -> u64 {
+        self.states.load(Ordering::Relaxed)
+    }
+
+    pub fn txs(&self) -> u64 {
+        self.txs.load(Ordering::Relaxed)
+    }
+
+    /// Add the `states` and `txs` fields to a summary.
+    pub fn log(&self, js: &mut JsonBuilder) -> Result<(), JsonError> {
+        js.set_uint("states", self.states())?;
+        js.set_uint("txs", self.txs())?;
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref PROTOCOLS: Mutex<Vec<(&'static str, IntrospectFn)>> = Mutex::new(Vec::new());
+}
+
+/// Register the summary function of a protocol.
+pub fn register(proto: &'static str, func: IntrospectFn) {
+    if let Ok(mut protocols) = PROTOCOLS.lock() {
+        if !protocols.iter().any(|(p, _)| *p == proto) {
+            protocols.push((proto, func));
+        }
+    }
+}
+
+fn introspect(proto: Option<&str>) -> Result<Option<JsonBuilder>, JsonError> {
+    let protocols = match PROTOCOLS.lock() {
+        Ok(protocols) => protocols,
+        Err(_) => return Ok(None),
+    };
+    let mut js = JsonBuilder::new_object();
+    let mut found = false;
+    for (name, func) in protocols.iter() {
+        if proto.map_or(false, |proto| proto != *name) {
+            continue;
+        }
+        js.open_object(name)?;
+        func(&mut js)?;
+        js.close()?;
+        found = true;
+    }
+    js.close()?;
+    if proto.is_some() && !found {
+        return Ok(None);
+    }
+    Ok(Some(js))
+}
+
+/// Return a JSON object with the summary of each registered protocol, or
+/// of `proto` only if not NULL. Returns NULL if `proto` is not a
+/// registered protocol. The result has to be freed with `jb_free`.
+#[no_mangle]
+pub unsafe extern "C" fn rs_app_layer_introspect(proto: *const c_char) -> *mut JsonBuilder {
+    let proto = if proto.is_null() {
+        None
+    } else {
+        match CStr::from_ptr(proto).to_str() {
+            Ok(proto) => Some(proto),
+            Err(_) => return std::ptr::null_mut(),
+        }
+    };
+    match introspect(proto) {
+        Ok(Some(js)) => Box::into_raw(Box::new(js)),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static GAUGES: StateGauges = StateGauges::new();
+
+    fn test_proto_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+        GAUGES.log(js)
+    }
+
+    #[test]
+    fn test_introspect() {
+        GAUGES.state_new();
+        GAUGES.tx_new();
+        GAUGES.tx_new();
+        GAUGES.tx_free(1);
+        register("test-introspect", test_proto_introspect);
+
+        let mut js = introspect(Some("test-introspect")).unwrap().unwrap();
+        let buf = unsafe {
+            std::slice::from_raw_parts(crate::jsonbuilder::jb_ptr(&mut js),
+                                       crate::jsonbuilder::jb_len(&js))
+        };
+        assert_eq!(buf, &br#"{"test-introspect":{"states":1,"txs":1}}"#[..]);
+        assert!(introspect(Some("test-unknown")).unwrap().is_none());
+    }
+}
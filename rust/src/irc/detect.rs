@@ -0,0 +1,119 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Buffers on the text and channels of the messages. A transaction is
+//! a message in a single direction, its buffers are only set in that
+//! direction.
+
+use super::irc::{IrcTransaction, ALPROTO_IRC};
+use crate::applayer::cast_mut;
+use crate::core::Direction;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_multi_buffer_mpm_register, helper_setup_sticky_buffer, DetectKeyword,
+    SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_IRC_MESSAGE_BUFFER_ID: c_int = 0;
+static mut G_IRC_CHANNEL_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn irc_tx_get_message(
+    tx: *mut c_void, flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut IrcTransaction = cast_mut(tx);
+    if tx.direction != Direction::from(flow_flags) {
+        return false;
+    }
+    match tx.message {
+        Some(ref message) => {
+            *buf = message.as_ptr();
+            *len = message.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn irc_tx_get_channel(
+    tx: *mut c_void, flow_flags: u8, local_id: u32, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut IrcTransaction = cast_mut(tx);
+    if tx.direction != Direction::from(flow_flags) {
+        return false;
+    }
+    match tx.channels.get(local_id as usize) {
+        Some(channel) => {
+            *buf = channel.as_ptr();
+            *len = channel.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn irc_message_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, irc_tx_get_message)
+}
+
+unsafe extern "C" fn irc_message_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_IRC, G_IRC_MESSAGE_BUFFER_ID)
+}
+
+unsafe extern "C" fn irc_channel_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_IRC, G_IRC_CHANNEL_BUFFER_ID)
+}
+
+/// Register the IRC keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "irc.message",
+        desc: "sticky buffer to match on the text of IRC messages and topics",
+        url: "/rules/irc-keywords.html#irc-message",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: irc_message_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_IRC_MESSAGE_BUFFER_ID = helper_buffer_mpm_register(
+            "irc.message", "irc message text", ALPROTO_IRC, true, true, 1,
+            irc_message_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "irc.channel",
+        desc: "sticky buffer to match on the channels of IRC messages",
+        url: "/rules/irc-keywords.html#irc-channel",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: irc_channel_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_IRC_CHANNEL_BUFFER_ID = helper_multi_buffer_mpm_register(
+            "irc.channel", "irc channel name", ALPROTO_IRC, true, true, 1,
+            irc_tx_get_channel);
+    }
+}
@@ -0,0 +1,630 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(irc_stats, "app_layer.irc", {
+    join,
+    privmsg,
+    dcc_send,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum IrcEvent {
+    MalformedData,
+    LineTooLong,
+    DccSendOffer,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static IRC_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static IRC_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum length of a line: 512 bytes for the message, and up to 8191
+/// for the IRCv3 message tags.
+const IRC_MAX_LINE_LEN: usize = 8704;
+
+/// Maximum number of channels tracked per flow.
+const IRC_MAX_CHANNELS: usize = 64;
+
+/// Number of bytes the probing parser looks into for the first line.
+const IRC_PROBE_LEN: usize = 1024;
+
+pub static mut ALPROTO_IRC: AppProto = ALPROTO_UNKNOWN;
+
+/// A message of the client, or a message of the server carrying text:
+/// the messages sent to the client and the channel topics.
+#[derive(Debug)]
+pub struct IrcTransaction {
+    id: u64,
+    pub direction: Direction,
+    pub command: String,
+    /// The nick of the sender, for the messages of the server.
+    pub sender: Option<Vec<u8>>,
+    pub nick: Option<Vec<u8>>,
+    pub user: Option<Vec<u8>>,
+    pub realname: Option<Vec<u8>>,
+    /// The target of a PRIVMSG or NOTICE.
+    pub target: Option<Vec<u8>>,
+    /// The channels of a JOIN or PART, or the channel a message or
+    /// topic is for.
+    pub channels: Vec<Vec<u8>>,
+    /// The text of a message or topic.
+    pub message: Option<Vec<u8>>,
+    pub dcc: Option<DccRequest>,
+    /// The channels of the client after a JOIN or PART.
+    pub joined: Option<Vec<Vec<u8>>>,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for IrcTransaction {}
+
+impl IrcTransaction {
+    fn new(id: u64, direction: Direction, command: String) -> IrcTransaction {
+        IrcTransaction {
+            id,
+            direction,
+            command,
+            sender: None,
+            nick: None,
+            user: None,
+            realname: None,
+            target: None,
+            channels: Vec::new(),
+            message: None,
+            dcc: None,
+            joined: None,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: IrcEvent) {
+        stats_incr!(irc_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+}
+
+impl Drop for IrcTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for IrcTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = IrcEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+pub struct IrcState {
+    transactions: TxTable<IrcTransaction>,
+    /// The nick of the client, as last set by the client or the server.
+    nick: Option<Vec<u8>>,
+    /// The channels the client is in.
+    channels: Vec<Vec<u8>>,
+}
+
+unsafe impl FfiType for IrcState {}
+
+impl txtable::State for IrcState {
+    type Tx = IrcTransaction;
+
+    fn tx_table(&self) -> &TxTable<IrcTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<IrcTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl IrcState {
+    pub fn new() -> IrcState {
+        IrcState {
+            transactions: TxTable::with_limit(&IRC_TX_LIMIT).with_gauges(&IRC_GAUGES),
+            nick: None,
+            channels: Vec::new(),
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    fn new_tx(&mut self, direction: Direction, command: &str) -> IrcTransaction {
+        IrcTransaction::new(self.transactions.next_id(), direction, command.to_string())
+    }
+
+    fn set_event(&mut self, direction: Direction, event: IrcEvent) {
+        let mut tx = self.new_tx(direction, "");
+        tx.set_event(event);
+        self.transactions.push(tx);
+    }
+
+    /// Whether a nick is the one of the client. Nicks are case
+    /// insensitive.
+    fn is_own_nick(&self, nick: Option<&[u8]>) -> bool {
+        match (nick, &self.nick) {
+            (Some(nick), Some(own)) => nick.eq_ignore_ascii_case(own),
+            _ => false,
+        }
+    }
+
+    fn join_channel(&mut self, channel: &[u8]) {
+        if self.channels.len() < IRC_MAX_CHANNELS
+            && !self.channels.iter().any(|c| c.eq_ignore_ascii_case(channel))
+        {
+            self.channels.push(channel.to_vec());
+        }
+    }
+
+    fn part_channel(&mut self, channel: &[u8]) {
+        self.channels.retain(|c| !c.eq_ignore_ascii_case(channel));
+    }
+
+    fn handle_text(&mut self, message: &IrcMessage, direction: Direction) {
+        let mut tx = self.new_tx(direction, &message.command);
+        tx.sender = message.prefix_nick().map(|n| n.to_vec());
+        let (target, text) = if message.command == "332" {
+            // RPL_TOPIC: client, channel, topic
+            (message.param(1), message.param(2))
+        } else {
+            (message.param(0), message.param(1))
+        };
+        if let Some(target) = target {
+            if is_channel(target) {
+                tx.channels.push(target.to_vec());
+            }
+            if message.command != "TOPIC" && message.command != "332" {
+                tx.target = Some(target.to_vec());
+            }
+        }
+        if message.command == "PRIVMSG" {
+            stats_incr!(irc_stats::privmsg);
+            if let Some(text) = text {
+                tx.dcc = parse_dcc(text);
+            }
+            let dcc_send = match tx.dcc {
+                Some(ref dcc) => dcc.kind == "SEND",
+                None => false,
+            };
+            if dcc_send {
+                stats_incr!(irc_stats::dcc_send);
+                tx.set_event(IrcEvent::DccSendOffer);
+            }
+        }
+        tx.message = text.map(|t| t.to_vec());
+        self.transactions.push(tx);
+    }
+
+    fn handle_message(&mut self, message: IrcMessage, direction: Direction) {
+        SCLogDebug!("message {:?}", message);
+        match message.command.as_str() {
+            "NICK" => {
+                let nick = message.param(0);
+                if direction == Direction::ToClient && !self.is_own_nick(message.prefix_nick()) {
+                    return;
+                }
+                self.nick = nick.map(|n| n.to_vec());
+                let mut tx = self.new_tx(direction, &message.command);
+                tx.nick = self.nick.clone();
+                self.transactions.push(tx);
+            }
+            "USER" if direction == Direction::ToServer => {
+                let mut tx = self.new_tx(direction, &message.command);
+                tx.user = message.param(0).map(|u| u.to_vec());
+                tx.realname = message.param(3).map(|r| r.to_vec());
+                self.transactions.push(tx);
+            }
+            "JOIN" | "PART" => {
+                let channels = message.param(0).unwrap_or(&[]);
+                let join = message.command == "JOIN";
+                // the server echoes the joins and parts of the client,
+                // and reports the ones of the other members
+                if direction == Direction::ToClient {
+                    if self.is_own_nick(message.prefix_nick()) {
+                        for channel in split_list(channels) {
+                            if join {
+                                self.join_channel(channel);
+                            } else {
+                                self.part_channel(channel);
+                            }
+                        }
+                    }
+                    return;
+                }
+                let mut tx = self.new_tx(direction, &message.command);
+                if join && channels == b"0" {
+                    // leave all the channels
+                    self.channels.clear();
+                } else {
+                    for channel in split_list(channels) {
+                        if join {
+                            self.join_channel(channel);
+                        } else {
+                            self.part_channel(channel);
+                        }
+                        tx.channels.push(channel.to_vec());
+                    }
+                }
+                if join {
+                    stats_incr!(irc_stats::join);
+                }
+                tx.joined = Some(self.channels.clone());
+                self.transactions.push(tx);
+            }
+            "KICK" if direction == Direction::ToClient => {
+                if self.is_own_nick(message.param(1)) {
+                    if let Some(channel) = message.param(0) {
+                        self.part_channel(channel);
+                    }
+                }
+            }
+            "001" if direction == Direction::ToClient => {
+                // RPL_WELCOME confirms the nick
+                self.nick = message.param(0).map(|n| n.to_vec());
+            }
+            "PRIVMSG" | "NOTICE" | "TOPIC" => {
+                self.handle_text(&message, direction);
+            }
+            "332" if direction == Direction::ToClient => {
+                self.handle_text(&message, direction);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_line(&mut self, line: &[u8], direction: Direction) {
+        let line = match line.last() {
+            Some(b'\r') => &line[..line.len() - 1],
+            _ => line,
+        };
+        if line.is_empty() {
+            return;
+        }
+        match parse_message(line) {
+            Some(message) => self.handle_message(message, direction),
+            None => self.set_event(direction, IrcEvent::MalformedData),
+        }
+    }
+
+    fn parse(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
+        let mut current = input;
+        while !current.is_empty() {
+            match current.iter().position(|&b| b == b'\n') {
+                Some(pos) => {
+                    self.handle_line(&current[..pos], direction);
+                    current = &current[pos + 1..];
+                }
+                None => {
+                    if current.len() >= IRC_MAX_LINE_LEN {
+                        self.set_event(direction, IrcEvent::LineTooLong);
+                        return AppLayerResult::err();
+                    }
+                    let consumed = input.len() - current.len();
+                    return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                           current.len() + 1);
+                }
+            }
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for a first line registering the client, or a notice or reply
+/// of the server.
+fn irc_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    let line = match input.iter().position(|&b| b == b'\n') {
+        Some(pos) => &input[..pos],
+        None if input.len() < IRC_PROBE_LEN => return ProbeResult::Unknown,
+        None => return ProbeResult::Failed,
+    };
+    let line = match line.last() {
+        Some(b'\r') => &line[..line.len() - 1],
+        _ => line,
+    };
+    let message = match parse_message(line) {
+        Some(message) => message,
+        None => return ProbeResult::Failed,
+    };
+    let actual = match message.command.as_str() {
+        "NICK" | "USER" | "PASS" | "CAP" if message.prefix.is_none() => Direction::ToServer,
+        "NOTICE" | "PING" => Direction::ToClient,
+        c if c.as_bytes()[0].is_ascii_digit() && message.prefix.is_some() => Direction::ToClient,
+        _ => return ProbeResult::Failed,
+    };
+    ProbeResult::with_direction(unsafe { ALPROTO_IRC }, direction, actual)
+}
+
+export_probe!(rs_irc_probing_parser, irc_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_irc_state_new(_orig_state: *mut std::os::raw::c_void,
+                                   _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = IrcState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_irc_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut IrcState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_irc_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut IrcState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_irc_parse_request(_flow: *const Flow,
+                                              state: *mut std::os::raw::c_void,
+                                              pstate: *mut std::os::raw::c_void,
+                                              input: *const u8,
+                                              input_len: u32,
+                                              _data: *const std::os::raw::c_void,
+                                              _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut IrcState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_irc_parse_response(_flow: *const Flow,
+                                               state: *mut std::os::raw::c_void,
+                                               pstate: *mut std::os::raw::c_void,
+                                               input: *const u8,
+                                               input_len: u32,
+                                               _data: *const std::os::raw::c_void,
+                                               _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut IrcState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_irc_state_get_tx(state: *mut std::os::raw::c_void,
+                                             tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut IrcState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_irc_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut IrcState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// The transactions are single messages, complete once parsed.
+#[no_mangle]
+pub unsafe extern "C" fn rs_irc_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void,
+                                                        _direction: u8) -> std::os::raw::c_int {
+    1
+}
+
+export_tx_detect_state!(rs_irc_state_get_tx_detect_state,
+                        rs_irc_state_set_tx_detect_state, IrcTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_irc_state_get_events(tx: *mut std::os::raw::c_void)
+                                                 -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut IrcTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_irc_get_tx_data, IrcTransaction);
+
+fn irc_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    IRC_GAUGES.log(js)?;
+    js.set_uint("events", irc_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+fn register_pattern_probe() -> i8 {
+    // USER and PASS are left to FTP and POP3
+    for pattern in &[&b"NICK \0"[..], &b"CAP LS\0"[..]] {
+        unsafe {
+            if AppLayerProtoDetectPMRegisterPatternCSwPP(IPPROTO_TCP as u8, ALPROTO_IRC,
+                                                         pattern.as_ptr() as *const std::os::raw::c_char,
+                                                         6, 0, core::STREAM_TOSERVER,
+                                                         rs_irc_probing_parser, 0, 0) < 0 {
+                SCLogDebug!("TOSERVER => AppLayerProtoDetectPMRegisterPatternCSwPP FAILED");
+                return -1;
+            }
+        }
+    }
+    0
+}
+
+const PARSER_NAME: &[u8] = b"irc\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_irc_register_parser() {
+    irc_stats::register();
+    IRC_TX_LIMIT.configure("irc", txtable::DEFAULT_MAX_TX);
+    introspect::register("irc", irc_introspect);
+    let default_port = CString::new("6667").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_TCP,
+        probe_ts: Some(rs_irc_probing_parser),
+        probe_tc: Some(rs_irc_probing_parser),
+        min_depth: 0,
+        max_depth: IRC_PROBE_LEN as u16,
+        state_new: rs_irc_state_new,
+        state_free: rs_irc_state_free,
+        tx_free: rs_irc_state_tx_free,
+        parse_ts: rs_irc_parse_request,
+        parse_tc: rs_irc_parse_response,
+        get_tx_count: rs_irc_state_get_tx_count,
+        get_tx: rs_irc_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_irc_tx_get_alstate_progress,
+        get_de_state: rs_irc_state_get_tx_detect_state,
+        set_de_state: rs_irc_state_set_tx_detect_state,
+        get_events: Some(rs_irc_state_get_events),
+        get_eventinfo: Some(IrcEvent::get_event_info),
+        get_eventinfo_byid: Some(IrcEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<IrcState>),
+        get_tx_data: rs_irc_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<IrcState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_IRC = alproto;
+        if register_pattern_probe() < 0 {
+            return;
+        }
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust irc parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for IRC.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_irc_state_new,
+            state_free: rs_irc_state_free,
+            parse_ts: rs_irc_parse_request,
+            parse_tc: rs_irc_parse_response,
+            get_tx_count: rs_irc_state_get_tx_count,
+            get_tx: rs_irc_state_get_tx,
+            tx_free: rs_irc_state_tx_free,
+        })
+    }
+
+    #[test]
+    fn test_irc_session() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"NICK bot123\r\nUSER x 0 * :Bot Client\r\n"));
+        assert!(h.feed(Direction::ToClient,
+                       b":irc.example.net 001 bot123 :Welcome\r\nPING :irc.example.net\r\n"));
+        // a line split over two segments
+        assert!(h.feed(Direction::ToServer, b"JOIN #cmd,#"));
+        assert!(h.feed(Direction::ToServer, b"logs key\r\n"));
+        assert!(h.feed(Direction::ToClient,
+                       b":bot123!x@host JOIN #cmd\r\n\
+                         :irc.example.net 332 bot123 #cmd :!update http://example.com/a.exe\r\n\
+                         :master!m@host PRIVMSG #cmd :!ddos 10.0.0.1 80\r\n\
+                         :master!m@host KICK #logs bot123 :bye\r\n"));
+        assert!(h.feed(Direction::ToServer, b"PRIVMSG master :done\r\n"));
+
+        assert_eq!(h.tx_count(), 6);
+        let tx = h.get_tx::<IrcTransaction>(0).unwrap();
+        assert_eq!(tx.command, "NICK");
+        assert_eq!(tx.nick, Some(b"bot123".to_vec()));
+        let tx = h.get_tx::<IrcTransaction>(1).unwrap();
+        assert_eq!(tx.user, Some(b"x".to_vec()));
+        assert_eq!(tx.realname, Some(b"Bot Client".to_vec()));
+        let tx = h.get_tx::<IrcTransaction>(2).unwrap();
+        assert_eq!(tx.command, "JOIN");
+        assert_eq!(tx.channels, vec![b"#cmd".to_vec(), b"#logs".to_vec()]);
+        assert_eq!(tx.joined, Some(vec![b"#cmd".to_vec(), b"#logs".to_vec()]));
+        let tx = h.get_tx::<IrcTransaction>(3).unwrap();
+        assert_eq!(tx.command, "332");
+        assert_eq!(tx.direction, Direction::ToClient);
+        assert_eq!(tx.channels, vec![b"#cmd".to_vec()]);
+        assert_eq!(tx.message, Some(b"!update http://example.com/a.exe".to_vec()));
+        let tx = h.get_tx::<IrcTransaction>(4).unwrap();
+        assert_eq!(tx.sender, Some(b"master".to_vec()));
+        assert_eq!(tx.target, Some(b"#cmd".to_vec()));
+        assert_eq!(tx.message, Some(b"!ddos 10.0.0.1 80".to_vec()));
+        let tx = h.get_tx::<IrcTransaction>(5).unwrap();
+        assert_eq!(tx.direction, Direction::ToServer);
+        assert!(tx.channels.is_empty());
+        assert_eq!(h.state::<IrcState>().channels, vec![b"#cmd".to_vec()]);
+    }
+
+    #[test]
+    fn test_irc_dcc_send() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient,
+                       b":master!m@host PRIVMSG bot :\x01DCC SEND a.exe 167772161 5000 1024\x01\r\n"));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<IrcTransaction>(0).unwrap();
+        let dcc = tx.dcc.as_ref().unwrap();
+        assert_eq!(dcc.kind, "SEND");
+        assert_eq!(dcc.address, "10.0.0.1");
+        assert_eq!(dcc.port, 5000);
+    }
+
+    #[test]
+    fn test_irc_probe() {
+        unsafe {
+            ALPROTO_IRC = 42;
+        }
+        let nick = b"NICK bot\r\nUSER bot 0 * :bot\r\n";
+        assert_eq!(irc_probe(nick, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(irc_probe(nick, Direction::ToClient), ProbeResult::Flipped(42));
+        assert_eq!(irc_probe(b":irc.example.net NOTICE * :*** Looking up your hostname\r\n",
+                             Direction::ToClient), ProbeResult::Found(42));
+        assert_eq!(irc_probe(b"NICK bot", Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(irc_probe(b"GET / HTTP/1.1\r\n", Direction::ToServer), ProbeResult::Failed);
+    }
+}
@@ -0,0 +1,80 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::irc::IrcTransaction;
+use crate::core::Direction;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log_channels(js: &mut JsonBuilder, name: &str, channels: &[Vec<u8>]) -> Result<(), JsonError> {
+    js.open_array(name)?;
+    for channel in channels {
+        js.append_string_from_bytes(channel)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+fn log(tx: &IrcTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("irc")?;
+    if !tx.command.is_empty() {
+        js.set_string("command", &tx.command)?;
+        let direction = if tx.direction == Direction::ToServer { "to_server" } else { "to_client" };
+        js.set_string("direction", direction)?;
+    }
+    if let Some(ref sender) = tx.sender {
+        js.set_string_from_bytes("sender", sender)?;
+    }
+    if let Some(ref nick) = tx.nick {
+        js.set_string_from_bytes("nick", nick)?;
+    }
+    if let Some(ref user) = tx.user {
+        js.set_string_from_bytes("user", user)?;
+    }
+    if let Some(ref realname) = tx.realname {
+        js.set_string_from_bytes("realname", realname)?;
+    }
+    if let Some(ref target) = tx.target {
+        js.set_string_from_bytes("target", target)?;
+    }
+    if !tx.channels.is_empty() {
+        log_channels(js, "channels", &tx.channels)?;
+    }
+    if let Some(ref message) = tx.message {
+        js.set_string_from_bytes("message", message)?;
+    }
+    if let Some(ref dcc) = tx.dcc {
+        js.open_object("dcc")?;
+        js.set_string("type", &dcc.kind)?;
+        js.set_string_from_bytes("argument", &dcc.argument)?;
+        js.set_string("address", &dcc.address)?;
+        js.set_uint("port", dcc.port as u64)?;
+        if let Some(size) = dcc.size {
+            js.set_uint("size", size)?;
+        }
+        js.close()?;
+    }
+    if let Some(ref joined) = tx.joined {
+        log_channels(js, "joined", joined)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_irc_to_json(tx: &mut IrcTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
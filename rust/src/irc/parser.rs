@@ -0,0 +1,216 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! IRC message decoding, RFC 1459 and RFC 2812, with the IRCv3 message
+//! tags skipped.
+//!
+//! Parameters are kept as bytes: IRC has no defined encoding.
+
+use std::net::Ipv4Addr;
+
+/// CTCP messages are delimited by this byte in the text of a PRIVMSG.
+const CTCP_DELIM: u8 = 0x01;
+
+#[derive(Debug, PartialEq)]
+pub struct IrcMessage<'a> {
+    /// The origin of the message, nick!user@host or a server name.
+    pub prefix: Option<&'a [u8]>,
+    /// The command, upper case, or a three digit numeric reply.
+    pub command: String,
+    /// The parameters, the trailing one included.
+    pub params: Vec<&'a [u8]>,
+}
+
+impl<'a> IrcMessage<'a> {
+    pub fn param(&self, index: usize) -> Option<&'a [u8]> {
+        self.params.get(index).cloned()
+    }
+
+    /// The nick of the prefix.
+    pub fn prefix_nick(&self) -> Option<&'a [u8]> {
+        let prefix = self.prefix?;
+        prefix.split(|&c| c == b'!' || c == b'@').next()
+    }
+}
+
+fn is_space(c: u8) -> bool {
+    c == b' '
+}
+
+fn skip_spaces(line: &[u8]) -> &[u8] {
+    let start = line.iter().position(|&c| !is_space(c)).unwrap_or(line.len());
+    &line[start..]
+}
+
+/// Split the next space delimited word off `line`.
+fn split_word(line: &[u8]) -> (&[u8], &[u8]) {
+    let end = line.iter().position(|&c| is_space(c)).unwrap_or(line.len());
+    (&line[..end], skip_spaces(&line[end..]))
+}
+
+/// Parse a line, without its line ending. Returns None for lines that
+/// are not IRC messages.
+pub fn parse_message(line: &[u8]) -> Option<IrcMessage> {
+    let mut rem = line;
+    if rem.first() == Some(&b'@') {
+        rem = split_word(rem).1;
+    }
+    let mut prefix = None;
+    if rem.first() == Some(&b':') {
+        let (word, r) = split_word(&rem[1..]);
+        if word.is_empty() {
+            return None;
+        }
+        prefix = Some(word);
+        rem = r;
+    }
+    let (command, mut rem) = split_word(rem);
+    let numeric = command.len() == 3 && command.iter().all(|c| c.is_ascii_digit());
+    if command.is_empty() || (!numeric && !command.iter().all(|c| c.is_ascii_alphabetic())) {
+        return None;
+    }
+    let command = String::from_utf8_lossy(command).to_ascii_uppercase();
+    let mut params = Vec::new();
+    while !rem.is_empty() {
+        if rem[0] == b':' {
+            params.push(&rem[1..]);
+            break;
+        }
+        let (param, r) = split_word(rem);
+        params.push(param);
+        rem = r;
+    }
+    Some(IrcMessage { prefix, command, params })
+}
+
+/// A channel name starts with one of the channel prefixes.
+pub fn is_channel(target: &[u8]) -> bool {
+    match target.first() {
+        Some(b'#') | Some(b'&') | Some(b'+') | Some(b'!') => true,
+        _ => false,
+    }
+}
+
+/// Split a comma separated list of targets.
+pub fn split_list(list: &[u8]) -> impl Iterator<Item = &[u8]> {
+    list.split(|&c| c == b',').filter(|t| !t.is_empty())
+}
+
+/// A DCC request, sent as a CTCP message in a PRIVMSG.
+#[derive(Debug, PartialEq)]
+pub struct DccRequest {
+    /// SEND, CHAT, RESUME...
+    pub kind: String,
+    /// The file name of a SEND, "chat" for a CHAT.
+    pub argument: Vec<u8>,
+    pub address: String,
+    pub port: u16,
+    pub size: Option<u64>,
+}
+
+fn dcc_address(addr: &[u8]) -> Option<String> {
+    let addr = std::str::from_utf8(addr).ok()?;
+    // IPv4 addresses are sent as a number, IPv6 ones as text
+    if let Ok(n) = addr.parse::<u32>() {
+        return Some(Ipv4Addr::from(n).to_string());
+    }
+    if addr.contains(':') {
+        return Some(addr.to_string());
+    }
+    None
+}
+
+/// Parse the DCC request in the text of a PRIVMSG, if any. A file name
+/// with spaces is quoted.
+pub fn parse_dcc(text: &[u8]) -> Option<DccRequest> {
+    let start = text.iter().position(|&c| c == CTCP_DELIM)?;
+    let ctcp = &text[start + 1..];
+    let end = ctcp.iter().position(|&c| c == CTCP_DELIM).unwrap_or(ctcp.len());
+    let ctcp = &ctcp[..end];
+    let (tag, rem) = split_word(ctcp);
+    if !tag.eq_ignore_ascii_case(b"DCC") {
+        return None;
+    }
+    let (kind, rem) = split_word(rem);
+    if kind.is_empty() {
+        return None;
+    }
+    let (argument, rem) = if rem.first() == Some(&b'"') {
+        let close = rem[1..].iter().position(|&c| c == b'"')?;
+        (&rem[1..close + 1], skip_spaces(&rem[close + 2..]))
+    } else {
+        split_word(rem)
+    };
+    let (address, rem) = split_word(rem);
+    let (port, rem) = split_word(rem);
+    let (size, _) = split_word(rem);
+    Some(DccRequest {
+        kind: String::from_utf8_lossy(kind).to_ascii_uppercase(),
+        argument: argument.to_vec(),
+        address: dcc_address(address)?,
+        port: std::str::from_utf8(port).ok()?.parse().ok()?,
+        size: std::str::from_utf8(size).ok().and_then(|s| s.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_message() {
+        let msg = parse_message(b":bot!~u@10.0.0.1 PRIVMSG #chan :!ddos 1.2.3.4 80").unwrap();
+        assert_eq!(msg.prefix, Some(&b"bot!~u@10.0.0.1"[..]));
+        assert_eq!(msg.prefix_nick(), Some(&b"bot"[..]));
+        assert_eq!(msg.command, "PRIVMSG");
+        assert_eq!(msg.params, vec![&b"#chan"[..], &b"!ddos 1.2.3.4 80"[..]]);
+
+        let msg = parse_message(b"@time=2021-01-01T00:00:00Z join  #a,#b key").unwrap();
+        assert_eq!(msg.command, "JOIN");
+        assert_eq!(msg.params, vec![&b"#a,#b"[..], &b"key"[..]]);
+        assert_eq!(split_list(msg.params[0]).collect::<Vec<_>>(), vec![&b"#a"[..], &b"#b"[..]]);
+
+        let msg = parse_message(b":irc.example.net 001 bot :Welcome").unwrap();
+        assert_eq!(msg.command, "001");
+
+        assert_eq!(parse_message(b""), None);
+        assert_eq!(parse_message(b":server"), None);
+        assert_eq!(parse_message(b"HTTP/1.1 200 OK"), None);
+        assert_eq!(parse_message(b"\x16\x03\x01\x02"), None);
+    }
+
+    #[test]
+    fn test_parse_dcc() {
+        let dcc = parse_dcc(b"\x01DCC SEND payload.exe 3232235777 4000 60512\x01").unwrap();
+        assert_eq!(
+            dcc,
+            DccRequest {
+                kind: "SEND".to_string(),
+                argument: b"payload.exe".to_vec(),
+                address: "192.168.1.1".to_string(),
+                port: 4000,
+                size: Some(60512),
+            }
+        );
+        let dcc = parse_dcc(b"\x01DCC SEND \"my file.zip\" fe80::1 5000\x01").unwrap();
+        assert_eq!(dcc.argument, b"my file.zip".to_vec());
+        assert_eq!(dcc.address, "fe80::1");
+        assert_eq!(dcc.size, None);
+        assert_eq!(parse_dcc(b"\x01VERSION\x01"), None);
+        assert_eq!(parse_dcc(b"hello"), None);
+    }
+}
@@ -335,7 +335,26 @@ impl JsonBuilder {
                 return Err(JsonError::InvalidState);
             }
         }
-        self.buf.push_str(&val.to_string());
+        self.encode_float(val);
+        Ok(self)
+    }
+
+    /// Add a value that is already encoded as JSON to an array. The
+    /// value is not validated.
+    pub fn append_raw_json(&mut self, val: &str) -> Result<&mut Self, JsonError> {
+        match self.current_state() {
+            State::ArrayFirst => {
+                self.set_state(State::ArrayNth);
+            }
+            State::ArrayNth => {
+                self.buf.push(',');
+            }
+            _ => {
+                debug_validate_fail!("invalid state");
+                return Err(JsonError::InvalidState);
+            }
+        }
+        self.buf.push_str(val);
         Ok(self)
     }
 
@@ -448,6 +467,8 @@ impl JsonBuilder {
         Ok(self)
     }
 
+    /// Set a key and a float on an object. NaN and infinite values are
+    /// not valid JSON numbers and are set as null.
     pub fn set_float(&mut self, key: &str, val: f64) -> Result<&mut Self, JsonError> {
         match self.current_state() {
             State::ObjectNth => {
@@ -464,7 +485,109 @@ impl JsonBuilder {
         self.buf.push('"');
         self.buf.push_str(key);
         self.buf.push_str("\":");
-        self.buf.push_str(&val.to_string());
+        self.encode_float(val);
+        Ok(self)
+    }
+
+    /// Set a key and bytes as a lowercase hex string on an object.
+    pub fn set_hex(&mut self, key: &str, val: &[u8]) -> Result<&mut Self, JsonError> {
+        match self.current_state() {
+            State::ObjectNth => {
+                self.buf.push(',');
+            }
+            State::ObjectFirst => {
+                self.set_state(State::ObjectNth);
+            }
+            _ => {
+                debug_validate_fail!("invalid state");
+                return Err(JsonError::InvalidState);
+            }
+        }
+        self.buf.push('"');
+        self.buf.push_str(key);
+        self.buf.push_str("\":\"");
+        self.buf.reserve(val.len() * 2 + 1);
+        for &x in val.iter() {
+            self.buf.push(HEX[(x >> 4) as usize] as char);
+            self.buf.push(HEX[(x & 0xf) as usize] as char);
+        }
+        self.buf.push('"');
+        Ok(self)
+    }
+
+    /// Set a key and bytes as a base64 (RFC 4648, with padding) string
+    /// on an object.
+    pub fn set_base64(&mut self, key: &str, val: &[u8]) -> Result<&mut Self, JsonError> {
+        match self.current_state() {
+            State::ObjectNth => {
+                self.buf.push(',');
+            }
+            State::ObjectFirst => {
+                self.set_state(State::ObjectNth);
+            }
+            _ => {
+                debug_validate_fail!("invalid state");
+                return Err(JsonError::InvalidState);
+            }
+        }
+        self.buf.push('"');
+        self.buf.push_str(key);
+        self.buf.push_str("\":\"");
+        self.buf.reserve((val.len() + 2) / 3 * 4 + 1);
+        for chunk in val.chunks(3) {
+            let b0 = chunk[0] as usize;
+            let b1 = chunk.get(1).map_or(0, |&b| b as usize);
+            let b2 = chunk.get(2).map_or(0, |&b| b as usize);
+            self.buf.push(BASE64[b0 >> 2] as char);
+            self.buf.push(BASE64[(b0 & 0x03) << 4 | b1 >> 4] as char);
+            if chunk.len() > 1 {
+                self.buf.push(BASE64[(b1 & 0x0f) << 2 | b2 >> 6] as char);
+            } else {
+                self.buf.push('=');
+            }
+            if chunk.len() > 2 {
+                self.buf.push(BASE64[b2 & 0x3f] as char);
+            } else {
+                self.buf.push('=');
+            }
+        }
+        self.buf.push('"');
+        Ok(self)
+    }
+
+    /// Set a key and a string value on an object, logging at most `max`
+    /// bytes of the string. A longer string is cut at the last character
+    /// boundary before `max`, so the result is still valid UTF-8.
+    pub fn set_string_limited(&mut self, key: &str, val: &str, max: usize) -> Result<&mut Self, JsonError> {
+        if val.len() <= max {
+            return self.set_string(key, val);
+        }
+        let mut end = max;
+        while !val.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.set_string(key, &val[..end])
+    }
+
+    /// Set a key and a value that is already encoded as JSON on an
+    /// object. The value is not validated.
+    pub fn set_raw_json(&mut self, key: &str, val: &str) -> Result<&mut Self, JsonError> {
+        match self.current_state() {
+            State::ObjectNth => {
+                self.buf.push(',');
+            }
+            State::ObjectFirst => {
+                self.set_state(State::ObjectNth);
+            }
+            _ => {
+                debug_validate_fail!("invalid state");
+                return Err(JsonError::InvalidState);
+            }
+        }
+        self.buf.push('"');
+        self.buf.push_str(key);
+        self.buf.push_str("\":");
+        self.buf.push_str(val);
         Ok(self)
     }
 
@@ -495,6 +618,16 @@ impl JsonBuilder {
         self.buf.capacity()
     }
 
+    /// Encode a float into the buffer, as null if it has no JSON
+    /// representation.
+    fn encode_float(&mut self, val: f64) {
+        if val.is_finite() {
+            self.buf.push_str(&val.to_string());
+        } else {
+            self.buf.push_str("null");
+        }
+    }
+
     /// Encode a string into the buffer, escaping as needed.
     ///
     /// The string is encoded into an intermediate vector as its faster
@@ -555,6 +688,169 @@ impl JsonBuilder {
     }
 }
 
+/// A struct that can be logged to EVE, usually implemented with
+/// `#[derive(EveSerialize)]`.
+pub trait EveSerialize {
+    /// Log the fields into the currently open object.
+    fn serialize_eve(&self, js: &mut JsonBuilder) -> Result<(), JsonError>;
+
+    /// Log the fields into a new object.
+    fn to_eve(&self) -> Result<JsonBuilder, JsonError> {
+        let mut js = JsonBuilder::new_object();
+        self.serialize_eve(&mut js)?;
+        js.close()?;
+        Ok(js)
+    }
+}
+
+/// A value that can be logged under a key of an object.
+pub trait EveValue {
+    fn set_eve(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError>;
+}
+
+/// A value that can be logged as an element of an array.
+pub trait EveArrayValue {
+    fn append_eve(&self, js: &mut JsonBuilder) -> Result<(), JsonError>;
+}
+
+/// Bytes that can be logged as a hex string, for `#[eve(hex)]`.
+pub trait EveHex {
+    fn set_eve_hex(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError>;
+}
+
+macro_rules! impl_eve_uint {
+    ($($ty:ty),*) => {
+        $(
+            impl EveValue for $ty {
+                fn set_eve(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+                    js.set_uint(key, *self as u64)?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_eve_uint!(u8, u16, u32, u64);
+
+// No u8, as a Vec<u8> is logged as a string.
+macro_rules! impl_eve_array_uint {
+    ($($ty:ty),*) => {
+        $(
+            impl EveArrayValue for $ty {
+                fn append_eve(&self, js: &mut JsonBuilder) -> Result<(), JsonError> {
+                    js.append_uint(*self as u64)?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_eve_array_uint!(u16, u32, u64);
+
+impl EveValue for bool {
+    fn set_eve(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        js.set_bool(key, *self)?;
+        Ok(())
+    }
+}
+
+impl EveValue for f64 {
+    fn set_eve(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        js.set_float(key, *self)?;
+        Ok(())
+    }
+}
+
+impl EveArrayValue for f64 {
+    fn append_eve(&self, js: &mut JsonBuilder) -> Result<(), JsonError> {
+        js.append_float(*self)?;
+        Ok(())
+    }
+}
+
+impl EveValue for str {
+    fn set_eve(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        js.set_string(key, self)?;
+        Ok(())
+    }
+}
+
+impl EveArrayValue for str {
+    fn append_eve(&self, js: &mut JsonBuilder) -> Result<(), JsonError> {
+        js.append_string(self)?;
+        Ok(())
+    }
+}
+
+impl EveValue for String {
+    fn set_eve(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        js.set_string(key, self)?;
+        Ok(())
+    }
+}
+
+impl EveArrayValue for String {
+    fn append_eve(&self, js: &mut JsonBuilder) -> Result<(), JsonError> {
+        js.append_string(self)?;
+        Ok(())
+    }
+}
+
+impl EveValue for Vec<u8> {
+    fn set_eve(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        js.set_string_from_bytes(key, self)?;
+        Ok(())
+    }
+}
+
+impl<T: EveArrayValue> EveValue for Vec<T> {
+    fn set_eve(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        js.open_array(key)?;
+        for val in self.iter() {
+            val.append_eve(js)?;
+        }
+        js.close()?;
+        Ok(())
+    }
+}
+
+impl<T: EveValue> EveValue for Option<T> {
+    fn set_eve(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        if let Some(val) = self {
+            val.set_eve(js, key)?;
+        }
+        Ok(())
+    }
+}
+
+impl EveHex for [u8] {
+    fn set_eve_hex(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        js.set_hex(key, self)?;
+        Ok(())
+    }
+}
+
+impl EveHex for Vec<u8> {
+    fn set_eve_hex(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        js.set_hex(key, self)?;
+        Ok(())
+    }
+}
+
+impl<T: EveHex> EveHex for Option<T> {
+    fn set_eve_hex(&self, js: &mut JsonBuilder, key: &str) -> Result<(), JsonError> {
+        if let Some(val) = self {
+            val.set_eve_hex(js, key)?;
+        }
+        Ok(())
+    }
+}
+
 /// A Suricata specific function to create a string from bytes when UTF-8 decoding fails.
 ///
 /// For bytes over 0x0f, we encode as hex like "\xf2".
@@ -1053,6 +1349,110 @@ mod test {
         jb.close().unwrap();
         assert_eq!(jb.buf, r#"[1.1,2.2]"#);
     }
+
+    #[test]
+    fn test_float_not_finite() {
+        let mut jb = JsonBuilder::new_object();
+        jb.set_float("nan", std::f64::NAN).unwrap();
+        jb.open_array("inf").unwrap();
+        jb.append_float(std::f64::INFINITY).unwrap();
+        jb.append_float(-0.5).unwrap();
+        jb.close().unwrap();
+        jb.close().unwrap();
+        assert_eq!(jb.buf, r#"{"nan":null,"inf":[null,-0.5]}"#);
+    }
+
+    #[test]
+    fn test_set_hex() {
+        let mut jb = JsonBuilder::new_object();
+        jb.set_hex("empty", &[]).unwrap();
+        jb.set_hex("hex", &[0x00, 0x0f, 0xa5, 0xff]).unwrap();
+        jb.close().unwrap();
+        assert_eq!(jb.buf, r#"{"empty":"","hex":"000fa5ff"}"#);
+    }
+
+    #[test]
+    fn test_set_base64() {
+        let mut jb = JsonBuilder::new_object();
+        jb.set_base64("a", b"").unwrap();
+        jb.set_base64("b", b"f").unwrap();
+        jb.set_base64("c", b"fo").unwrap();
+        jb.set_base64("d", b"foo").unwrap();
+        jb.set_base64("e", b"foobar").unwrap();
+        jb.set_base64("f", &[0xff, 0xfe, 0x00, 0x01]).unwrap();
+        jb.close().unwrap();
+        assert_eq!(
+            jb.buf,
+            r#"{"a":"","b":"Zg==","c":"Zm8=","d":"Zm9v","e":"Zm9vYmFy","f":"//4AAQ=="}"#
+        );
+    }
+
+    #[test]
+    fn test_set_string_limited() {
+        let mut jb = JsonBuilder::new_object();
+        jb.set_string_limited("short", "abc", 3).unwrap();
+        jb.set_string_limited("long", "abcdef", 3).unwrap();
+        // "é" is 2 bytes, so it can't be cut after 2 bytes.
+        jb.set_string_limited("utf8", "aéb", 2).unwrap();
+        jb.set_string_limited("none", "abc", 0).unwrap();
+        jb.close().unwrap();
+        assert_eq!(jb.buf, r#"{"short":"abc","long":"abc","utf8":"a","none":""}"#);
+    }
+
+    #[derive(EveSerialize)]
+    struct EveInner {
+        name: String,
+    }
+
+    #[derive(EveSerialize)]
+    struct EveTest {
+        #[eve(skip)]
+        _id: u64,
+        version: u8,
+        #[eve(rename = "type")]
+        kind: Option<String>,
+        missing: Option<u32>,
+        #[eve(hex)]
+        cookie: Vec<u8>,
+        payload: Vec<u8>,
+        ports: Vec<u16>,
+        empty: Vec<String>,
+        inner: EveInner,
+        items: Vec<EveInner>,
+    }
+
+    #[test]
+    fn test_eve_serialize() {
+        let val = EveTest {
+            _id: 1,
+            version: 2,
+            kind: Some("request".to_string()),
+            missing: None,
+            cookie: vec![0xde, 0xad],
+            payload: b"abc".to_vec(),
+            ports: vec![80, 443],
+            empty: Vec::new(),
+            inner: EveInner { name: "a".to_string() },
+            items: vec![EveInner { name: "b".to_string() }],
+        };
+        let js = val.to_eve().unwrap();
+        assert_eq!(
+            js.buf,
+            r#"{"version":2,"type":"request","cookie":"dead","payload":"abc","ports":[80,443],"inner":{"name":"a"},"items":[{"name":"b"}]}"#
+        );
+    }
+
+    #[test]
+    fn test_raw_json() {
+        let mut jb = JsonBuilder::new_object();
+        jb.set_raw_json("obj", r#"{"a":1}"#).unwrap();
+        jb.open_array("array").unwrap();
+        jb.append_raw_json("1.5e3").unwrap();
+        jb.append_raw_json("[]").unwrap();
+        jb.close().unwrap();
+        jb.close().unwrap();
+        assert_eq!(jb.buf, r#"{"obj":{"a":1},"array":[1.5e3,[]]}"#);
+    }
 }
 
 // Escape table as seen in serde-json (MIT/Apache license)
@@ -1088,6 +1488,8 @@ static ESCAPED: [u8; 256] = [
     __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, __, // F
 ];
 
+static BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
 static HEX: [u8; 16] = [
     b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7', b'8', b'9', b'a', b'b', b'c', b'd', b'e', b'f',
 ];
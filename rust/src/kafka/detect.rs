@@ -0,0 +1,72 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Multi buffer on the topic names of requests and responses.
+
+use super::kafka::{KafkaTransaction, ALPROTO_KAFKA};
+use crate::applayer::cast_mut;
+use crate::core::STREAM_TOSERVER;
+use crate::detect::{
+    helper_keyword_register, helper_multi_buffer_mpm_register, helper_setup_sticky_buffer,
+    DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_KAFKA_TOPIC_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn kafka_tx_get_topic(
+    tx: *mut c_void, flow_flags: u8, local_id: u32, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut KafkaTransaction = cast_mut(tx);
+    let topics = if flow_flags & STREAM_TOSERVER != 0 {
+        &tx.topics
+    } else {
+        &tx.response_topics
+    };
+    match topics.get(local_id as usize) {
+        Some(topic) => {
+            *buf = topic.as_ptr();
+            *len = topic.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn kafka_topic_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_KAFKA, G_KAFKA_TOPIC_BUFFER_ID)
+}
+
+/// Register the Kafka keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "kafka.topic",
+        desc: "sticky buffer to match on the topic names of Kafka requests and responses",
+        url: "/rules/kafka-keywords.html#kafka-topic",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: kafka_topic_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_KAFKA_TOPIC_BUFFER_ID = helper_multi_buffer_mpm_register(
+            "kafka.topic", "kafka topic name", ALPROTO_KAFKA, true, true, 1,
+            kafka_tx_get_topic);
+    }
+}
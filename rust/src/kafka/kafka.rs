@@ -0,0 +1,711 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::conf::conf_get_app_layer_node;
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(kafka_stats, "app_layer.kafka", {
+    produce,
+    fetch,
+    metadata,
+    auth_failures,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum KafkaEvent {
+    MalformedData,
+    UnsolicitedResponse,
+    AuthenticationFailed,
+    UnauthenticatedSensitiveTopic,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static KAFKA_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static KAFKA_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum size of a frame that is decoded. Only the header of larger
+/// frames, like the responses to fetch requests of busy consumers, is
+/// decoded, the rest is skipped.
+const KAFKA_MAX_FRAME_LEN: usize = 4 * 1024 * 1024;
+
+/// Bytes of a large frame looked into for its header.
+const KAFKA_MAX_HEADER_LEN: usize = 512;
+
+/// Maximum number of topics kept per direction of a transaction.
+const KAFKA_MAX_TX_TOPICS: usize = 256;
+
+pub static mut ALPROTO_KAFKA: AppProto = ALPROTO_UNKNOWN;
+
+/// Topics that produce and fetch requests sent before any SASL
+/// authentication raise an event for.
+pub struct KafkaSensitiveTopics {
+    topics: Vec<String>,
+}
+
+impl Default for KafkaSensitiveTopics {
+    fn default() -> Self {
+        Self {
+            topics: vec!["__consumer_offsets".to_string(), "__transaction_state".to_string()],
+        }
+    }
+}
+
+impl KafkaSensitiveTopics {
+    /// Read app-layer.protocols.kafka.sensitive-topics. A list present
+    /// in the configuration replaces the default one.
+    fn from_config() -> Self {
+        let mut sensitive = Self::default();
+        if let Some(conf) = conf_get_app_layer_node("kafka") {
+            if conf.get_child_node("sensitive-topics").is_some() {
+                sensitive.topics = conf.get_child_string_list("sensitive-topics");
+            }
+        }
+        sensitive
+    }
+
+    /// Names ending with a '*' match the topics they prefix.
+    fn contains(&self, topic: &str) -> bool {
+        self.topics.iter().any(|s| {
+            if s.ends_with('*') {
+                topic.starts_with(&s[..s.len() - 1])
+            } else {
+                s == topic
+            }
+        })
+    }
+}
+
+static mut KAFKA_SENSITIVE_TOPICS: Option<KafkaSensitiveTopics> = None;
+
+fn is_sensitive_topic(topic: &str) -> bool {
+    match unsafe { &KAFKA_SENSITIVE_TOPICS } {
+        Some(sensitive) => sensitive.contains(topic),
+        None => KafkaSensitiveTopics::default().contains(topic),
+    }
+}
+
+/// A request and its response, matched by correlation id.
+#[derive(Debug)]
+pub struct KafkaTransaction {
+    id: u64,
+    pub api_key: i16,
+    pub api_version: i16,
+    pub correlation_id: i32,
+    pub client_id: Option<String>,
+    pub transactional_id: Option<String>,
+    pub acks: Option<i16>,
+    pub sasl_mechanism: Option<String>,
+    /// Topics of the request.
+    pub topics: Vec<String>,
+    /// Topics of the response.
+    pub response_topics: Vec<String>,
+    /// The first error of the response.
+    pub error_code: Option<i16>,
+    /// Set once the response is seen, or for requests without one.
+    pub complete: bool,
+    /// Set for the transactions created for an event only.
+    pub anomaly: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for KafkaTransaction {}
+
+impl KafkaTransaction {
+    fn new(id: u64) -> KafkaTransaction {
+        KafkaTransaction {
+            id,
+            api_key: -1,
+            api_version: 0,
+            correlation_id: 0,
+            client_id: None,
+            transactional_id: None,
+            acks: None,
+            sasl_mechanism: None,
+            topics: Vec::new(),
+            response_topics: Vec::new(),
+            error_code: None,
+            complete: false,
+            anomaly: false,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: KafkaEvent) {
+        stats_incr!(kafka_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+}
+
+impl Drop for KafkaTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for KafkaTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = KafkaEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+fn truncate_topics(mut topics: Vec<String>) -> Vec<String> {
+    topics.truncate(KAFKA_MAX_TX_TOPICS);
+    topics
+}
+
+pub struct KafkaState {
+    transactions: TxTable<KafkaTransaction>,
+    /// Set by a successful SASL authentication.
+    authenticated: bool,
+    /// Set once a SaslHandshake v0 succeeds: the SASL tokens follow
+    /// without the Kafka framing and the flow is no longer parsed.
+    sasl_raw: bool,
+    /// Bytes of a large frame left to skip, per direction.
+    skip_ts: usize,
+    skip_tc: usize,
+}
+
+unsafe impl FfiType for KafkaState {}
+
+impl txtable::State for KafkaState {
+    type Tx = KafkaTransaction;
+
+    fn tx_table(&self) -> &TxTable<KafkaTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<KafkaTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl KafkaState {
+    pub fn new() -> KafkaState {
+        KafkaState {
+            transactions: TxTable::with_limit(&KAFKA_TX_LIMIT).with_gauges(&KAFKA_GAUGES),
+            authenticated: false,
+            sasl_raw: false,
+            skip_ts: 0,
+            skip_tc: 0,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    fn set_event(&mut self, event: KafkaEvent) {
+        let mut tx = KafkaTransaction::new(self.transactions.next_id());
+        tx.complete = true;
+        tx.anomaly = true;
+        tx.set_event(event);
+        self.transactions.push(tx);
+    }
+
+    /// Handle a request, `truncated` if only the start of a large frame
+    /// is available.
+    fn handle_request(&mut self, frame: &[u8], truncated: bool) {
+        let (rem, header) = match parse_request_header(frame) {
+            Ok(r) => r,
+            Err(_) => {
+                self.set_event(KafkaEvent::MalformedData);
+                return;
+            }
+        };
+        SCLogDebug!("request {:?}", header);
+        let mut tx = KafkaTransaction::new(self.transactions.next_id());
+        tx.api_key = header.api_key;
+        tx.api_version = header.api_version;
+        tx.correlation_id = header.correlation_id;
+        tx.client_id = header.client_id;
+        match header.api_key {
+            API_PRODUCE => stats_incr!(kafka_stats::produce),
+            API_FETCH => stats_incr!(kafka_stats::fetch),
+            API_METADATA => stats_incr!(kafka_stats::metadata),
+            _ => {}
+        }
+        match parse_request_body(rem, header.api_key, header.api_version) {
+            Ok((_, RequestBody::Produce { transactional_id, acks, topics })) => {
+                tx.transactional_id = transactional_id;
+                tx.acks = Some(acks);
+                tx.topics = truncate_topics(topics);
+                // without acknowledgement there is no response
+                tx.complete = acks == 0;
+            }
+            Ok((_, RequestBody::Fetch { topics })) | Ok((_, RequestBody::Metadata { topics })) => {
+                tx.topics = truncate_topics(topics);
+            }
+            Ok((_, RequestBody::SaslHandshake { mechanism })) => {
+                tx.sasl_mechanism = Some(mechanism);
+            }
+            Ok((_, RequestBody::Other)) => {}
+            Err(_) => {
+                // the records of a large produce request are cut
+                if !truncated {
+                    tx.set_event(KafkaEvent::MalformedData);
+                }
+            }
+        }
+        if !self.authenticated
+            && (header.api_key == API_PRODUCE || header.api_key == API_FETCH)
+            && tx.topics.iter().any(|t| is_sensitive_topic(t))
+        {
+            tx.set_event(KafkaEvent::UnauthenticatedSensitiveTopic);
+        }
+        self.transactions.push(tx);
+    }
+
+    fn handle_response(&mut self, frame: &[u8], truncated: bool) {
+        let (rem, correlation_id) = match parse_response_header(frame) {
+            Ok(r) => r,
+            Err(_) => {
+                self.set_event(KafkaEvent::MalformedData);
+                return;
+            }
+        };
+        let tx = match self
+            .transactions
+            .iter_mut()
+            .find(|tx| !tx.complete && !tx.anomaly && tx.correlation_id == correlation_id)
+        {
+            Some(tx) => tx,
+            None => {
+                self.set_event(KafkaEvent::UnsolicitedResponse);
+                return;
+            }
+        };
+        tx.complete = true;
+        let body = match parse_response_body(rem, tx.api_key, tx.api_version) {
+            Ok((_, body)) => body,
+            Err(_) => {
+                if !truncated {
+                    tx.set_event(KafkaEvent::MalformedData);
+                }
+                return;
+            }
+        };
+        tx.response_topics = truncate_topics(body.topics);
+        tx.error_code = Some(body.error_code);
+        let success = body.error_code == ERROR_NONE;
+        match tx.api_key {
+            API_SASL_HANDSHAKE if success && tx.api_version == 0 => {
+                self.sasl_raw = true;
+            }
+            API_SASL_AUTHENTICATE if success => {
+                self.authenticated = true;
+            }
+            API_SASL_AUTHENTICATE => {
+                stats_incr!(kafka_stats::auth_failures);
+                tx.set_event(KafkaEvent::AuthenticationFailed);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_frame(&mut self, frame: &[u8], direction: Direction, truncated: bool) {
+        if direction == Direction::ToServer {
+            self.handle_request(frame, truncated);
+        } else {
+            self.handle_response(frame, truncated);
+        }
+    }
+
+    fn parse(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
+        if self.sasl_raw {
+            return AppLayerResult::ok();
+        }
+        let mut current = input;
+        let skip = if direction == Direction::ToServer {
+            &mut self.skip_ts
+        } else {
+            &mut self.skip_tc
+        };
+        if *skip > 0 {
+            let n = std::cmp::min(*skip, current.len());
+            *skip -= n;
+            current = &current[n..];
+        }
+        while !current.is_empty() && !self.sasl_raw {
+            let consumed = input.len() - current.len();
+            if current.len() < 4 {
+                return AppLayerResult::incomplete_from(input.len(), consumed, 4);
+            }
+            let size = i32::from_be_bytes([current[0], current[1], current[2], current[3]]);
+            if size < 0 {
+                self.set_event(KafkaEvent::MalformedData);
+                return AppLayerResult::err();
+            }
+            let frame_len = size as usize;
+            if frame_len > KAFKA_MAX_FRAME_LEN {
+                let header_len = std::cmp::min(frame_len, KAFKA_MAX_HEADER_LEN);
+                if current.len() < 4 + header_len {
+                    return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                           4 + header_len);
+                }
+                self.handle_frame(&current[4..4 + header_len], direction, true);
+                if current.len() - 4 < frame_len {
+                    let skip = frame_len - (current.len() - 4);
+                    if direction == Direction::ToServer {
+                        self.skip_ts = skip;
+                    } else {
+                        self.skip_tc = skip;
+                    }
+                    return AppLayerResult::ok();
+                }
+                current = &current[4 + frame_len..];
+                continue;
+            }
+            if current.len() < 4 + frame_len {
+                return AppLayerResult::incomplete_from(input.len(), consumed, 4 + frame_len);
+            }
+            self.handle_frame(&current[4..4 + frame_len], direction, false);
+            current = &current[4 + frame_len..];
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for the header of a request: a sane size, a known API key and
+/// version, and a client id fitting in the frame.
+fn kafka_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    if input.len() < 14 {
+        return ProbeResult::Unknown;
+    }
+    let size = i32::from_be_bytes([input[0], input[1], input[2], input[3]]);
+    let api_key = i16::from_be_bytes([input[4], input[5]]);
+    let api_version = i16::from_be_bytes([input[6], input[7]]);
+    if size < 10
+        || size as usize > KAFKA_MAX_FRAME_LEN
+        || api_key < 0
+        || api_key > API_KEY_MAX
+        || api_version < 0
+        || api_version > 20
+    {
+        return ProbeResult::Failed;
+    }
+    let header = match parse_request_header(&input[4..]) {
+        Ok((_, header)) => header,
+        Err(nom::Err::Incomplete(_)) => return ProbeResult::Unknown,
+        Err(_) => return ProbeResult::Failed,
+    };
+    let client_id_len = header.client_id.as_ref().map(|c| c.len()).unwrap_or(0);
+    if (size as usize) < 10 + client_id_len {
+        return ProbeResult::Failed;
+    }
+    ProbeResult::with_direction(unsafe { ALPROTO_KAFKA }, direction, Direction::ToServer)
+}
+
+export_probe!(rs_kafka_probing_parser, kafka_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_kafka_state_new(_orig_state: *mut std::os::raw::c_void,
+                                     _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = KafkaState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_kafka_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut KafkaState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_kafka_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut KafkaState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_kafka_parse_request(_flow: *const Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut KafkaState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_kafka_parse_response(_flow: *const Flow,
+                                                 state: *mut std::os::raw::c_void,
+                                                 pstate: *mut std::os::raw::c_void,
+                                                 input: *const u8,
+                                                 input_len: u32,
+                                                 _data: *const std::os::raw::c_void,
+                                                 _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut KafkaState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_kafka_state_get_tx(state: *mut std::os::raw::c_void,
+                                               tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut KafkaState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_kafka_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut KafkaState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// The request is complete once seen, the response once matched.
+#[no_mangle]
+pub unsafe extern "C" fn rs_kafka_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                          direction: u8) -> std::os::raw::c_int {
+    let tx: &mut KafkaTransaction = cast_mut(tx);
+    if direction & core::STREAM_TOSERVER != 0 || tx.complete {
+        1
+    } else {
+        0
+    }
+}
+
+export_tx_detect_state!(rs_kafka_state_get_tx_detect_state,
+                        rs_kafka_state_set_tx_detect_state, KafkaTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_kafka_state_get_events(tx: *mut std::os::raw::c_void)
+                                                   -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut KafkaTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_kafka_get_tx_data, KafkaTransaction);
+
+fn kafka_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    KAFKA_GAUGES.log(js)?;
+    js.set_uint("events", kafka_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+const PARSER_NAME: &[u8] = b"kafka\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_kafka_register_parser() {
+    kafka_stats::register();
+    KAFKA_TX_LIMIT.configure("kafka", txtable::DEFAULT_MAX_TX);
+    KAFKA_SENSITIVE_TOPICS = Some(KafkaSensitiveTopics::from_config());
+    introspect::register("kafka", kafka_introspect);
+    let default_port = CString::new("9092").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_TCP,
+        probe_ts: Some(rs_kafka_probing_parser),
+        probe_tc: Some(rs_kafka_probing_parser),
+        min_depth: 0,
+        max_depth: 16,
+        state_new: rs_kafka_state_new,
+        state_free: rs_kafka_state_free,
+        tx_free: rs_kafka_state_tx_free,
+        parse_ts: rs_kafka_parse_request,
+        parse_tc: rs_kafka_parse_response,
+        get_tx_count: rs_kafka_state_get_tx_count,
+        get_tx: rs_kafka_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_kafka_tx_get_alstate_progress,
+        get_de_state: rs_kafka_state_get_tx_detect_state,
+        set_de_state: rs_kafka_state_set_tx_detect_state,
+        get_events: Some(rs_kafka_state_get_events),
+        get_eventinfo: Some(KafkaEvent::get_event_info),
+        get_eventinfo_byid: Some(KafkaEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<KafkaState>),
+        get_tx_data: rs_kafka_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<KafkaState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_KAFKA = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust kafka parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for Kafka.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_kafka_state_new,
+            state_free: rs_kafka_state_free,
+            parse_ts: rs_kafka_parse_request,
+            parse_tc: rs_kafka_parse_response,
+            get_tx_count: rs_kafka_state_get_tx_count,
+            get_tx: rs_kafka_state_get_tx,
+            tx_free: rs_kafka_state_tx_free,
+        })
+    }
+
+    fn frame(body: &[u8]) -> Vec<u8> {
+        let mut v = (body.len() as u32).to_be_bytes().to_vec();
+        v.extend_from_slice(body);
+        v
+    }
+
+    /// Fetch v0 of partition 0 of a topic.
+    fn fetch_request(correlation_id: u8, topic: &[u8]) -> Vec<u8> {
+        let mut body = vec![
+            0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, correlation_id,
+            0x00, 0x01, b'c', // client id
+            0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x01, 0xf4, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x00, 0x00, 0x01, 0x00, topic.len() as u8,
+        ];
+        body.extend_from_slice(topic);
+        body.extend_from_slice(&[
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00,
+        ]);
+        frame(&body)
+    }
+
+    #[test]
+    fn test_kafka_fetch() {
+        let mut h = harness();
+        let request = fetch_request(5, b"__consumer_offsets");
+        // a request split over two segments
+        assert!(h.feed(Direction::ToServer, &request[..10]));
+        assert!(h.feed(Direction::ToServer, &request[10..]));
+        let response = frame(&[
+            0x00, 0x00, 0x00, 0x05, // correlation id
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x01, b't',
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, // unknown topic
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff,
+        ]);
+        assert!(h.feed(Direction::ToClient, &response));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<KafkaTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.api_key, API_FETCH);
+        assert_eq!(tx.client_id, Some("c".to_string()));
+        assert_eq!(tx.topics, vec!["__consumer_offsets".to_string()]);
+        assert_eq!(tx.response_topics, vec!["t".to_string()]);
+        assert_eq!(tx.error_code, Some(3));
+    }
+
+    #[test]
+    fn test_kafka_sasl_authenticate() {
+        let mut h = harness();
+        // SaslAuthenticate v1 with an empty token
+        let request = frame(&[
+            0x00, 0x24, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        assert!(h.feed(Direction::ToServer, &request));
+        let response = frame(&[
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]);
+        assert!(h.feed(Direction::ToClient, &response));
+        assert!(h.state::<KafkaState>().authenticated);
+        assert!(h.feed(Direction::ToServer, &fetch_request(2, b"__consumer_offsets")));
+        assert_eq!(h.tx_count(), 2);
+        let tx = h.get_tx::<KafkaTransaction>(0).unwrap();
+        assert_eq!(tx.error_code, Some(0));
+    }
+
+    #[test]
+    fn test_kafka_large_frame() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, &fetch_request(1, b"t")));
+        let mut response = ((KAFKA_MAX_FRAME_LEN + 8) as u32).to_be_bytes().to_vec();
+        response.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        response.resize(KAFKA_MAX_HEADER_LEN + 4, 0);
+        assert!(h.feed(Direction::ToClient, &response));
+        assert_eq!(h.state::<KafkaState>().skip_tc, KAFKA_MAX_FRAME_LEN + 8 - KAFKA_MAX_HEADER_LEN);
+        let tx = h.get_tx::<KafkaTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert_eq!(h.tx_count(), 1);
+    }
+
+    #[test]
+    fn test_kafka_sensitive_topics() {
+        let sensitive = KafkaSensitiveTopics {
+            topics: vec!["__consumer_offsets".to_string(), "secret-*".to_string()],
+        };
+        assert!(sensitive.contains("__consumer_offsets"));
+        assert!(sensitive.contains("secret-keys"));
+        assert!(!sensitive.contains("secret"));
+        assert!(!sensitive.contains("logs"));
+    }
+
+    #[test]
+    fn test_kafka_probe() {
+        unsafe {
+            ALPROTO_KAFKA = 42;
+        }
+        let request = fetch_request(1, b"t");
+        assert_eq!(kafka_probe(&request, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(kafka_probe(&request, Direction::ToClient), ProbeResult::Flipped(42));
+        assert_eq!(kafka_probe(&request[..8], Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(kafka_probe(b"GET / HTTP/1.1\r\n\r\n", Direction::ToServer),
+                   ProbeResult::Failed);
+    }
+}
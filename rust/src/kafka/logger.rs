@@ -0,0 +1,70 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::kafka::KafkaTransaction;
+use super::parser::api_key_name;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log_topics(js: &mut JsonBuilder, name: &str, topics: &[String]) -> Result<(), JsonError> {
+    if !topics.is_empty() {
+        js.open_array(name)?;
+        for topic in topics {
+            js.append_string(topic)?;
+        }
+        js.close()?;
+    }
+    Ok(())
+}
+
+fn log(tx: &KafkaTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("kafka")?;
+    if tx.api_key >= 0 {
+        js.set_uint("api_key", tx.api_key as u64)?;
+        if let Some(name) = api_key_name(tx.api_key) {
+            js.set_string("api", name)?;
+        }
+        js.set_uint("api_version", tx.api_version as u64)?;
+        js.set_uint("correlation_id", tx.correlation_id as u32 as u64)?;
+    }
+    if let Some(ref client_id) = tx.client_id {
+        js.set_string("client_id", client_id)?;
+    }
+    if let Some(ref transactional_id) = tx.transactional_id {
+        js.set_string("transactional_id", transactional_id)?;
+    }
+    if let Some(acks) = tx.acks {
+        // -1 for all the in-sync replicas
+        js.set_formatted(&format!("\"acks\":{}", acks))?;
+    }
+    if let Some(ref mechanism) = tx.sasl_mechanism {
+        js.set_string("sasl_mechanism", mechanism)?;
+    }
+    log_topics(js, "topics", &tx.topics)?;
+    if let Some(error_code) = tx.error_code {
+        js.open_object("response")?;
+        js.set_formatted(&format!("\"error_code\":{}", error_code))?;
+        log_topics(js, "topics", &tx.response_topics)?;
+        js.close()?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_kafka_to_json(tx: &mut KafkaTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
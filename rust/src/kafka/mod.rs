@@ -1,4 +1,4 @@
-/* Copyright (C) 2018 Open Information Security Foundation
+/* Copyright (C) 2021 Open Information Security Foundation
  *
  * You can copy, redistribute or modify this Program under the terms of
  * the GNU General Public License version 2 as published by the Free
@@ -15,16 +15,13 @@
  * 02110-1301, USA.
  */
 
-/**
- * \file
- *
- * \author FirstName LastName <yourname@domain>
- */
-
-#ifndef __APP_LAYER_TEMPLATE_RUST_H__
-#define __APP_LAYER_TEMPLATE_RUST_H__
-
-void RegisterTemplateRustParsers(void);
-void TemplateRustParserRegisterTests(void);
+//! Kafka application layer, parser and logger module.
+//!
+//! Each request is a transaction, completed by the response with the
+//! same correlation id. Produce requests without acknowledgement have
+//! no response.
 
-#endif /* __APP_LAYER_TEMPLATE_RUST_H__ */
+pub mod detect;
+pub mod kafka;
+pub mod logger;
+pub mod parser;
@@ -0,0 +1,539 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Kafka protocol decoding.
+//!
+//! The parsers work on a complete frame, without its size. The bodies
+//! are decoded for the versions using the classic encoding; the flexible
+//! versions, with compact arrays and tagged fields, only have their
+//! header decoded.
+
+use nom::bytes::streaming::take;
+use nom::error::ErrorKind;
+use nom::number::streaming::{be_i16, be_i32, be_i64, be_i8, be_u8};
+use nom::IResult;
+
+pub const API_PRODUCE: i16 = 0;
+pub const API_FETCH: i16 = 1;
+pub const API_METADATA: i16 = 3;
+pub const API_SASL_HANDSHAKE: i16 = 17;
+pub const API_SASL_AUTHENTICATE: i16 = 36;
+
+pub const ERROR_NONE: i16 = 0;
+
+/// Highest API key the probing parser accepts.
+pub const API_KEY_MAX: i16 = 74;
+
+pub fn api_key_name(api_key: i16) -> Option<&'static str> {
+    let name = match api_key {
+        0 => "produce",
+        1 => "fetch",
+        2 => "list_offsets",
+        3 => "metadata",
+        8 => "offset_commit",
+        9 => "offset_fetch",
+        10 => "find_coordinator",
+        11 => "join_group",
+        12 => "heartbeat",
+        13 => "leave_group",
+        14 => "sync_group",
+        15 => "describe_groups",
+        16 => "list_groups",
+        17 => "sasl_handshake",
+        18 => "api_versions",
+        19 => "create_topics",
+        20 => "delete_topics",
+        21 => "delete_records",
+        22 => "init_producer_id",
+        24 => "add_partitions_to_txn",
+        25 => "add_offsets_to_txn",
+        26 => "end_txn",
+        28 => "txn_offset_commit",
+        29 => "describe_acls",
+        30 => "create_acls",
+        31 => "delete_acls",
+        32 => "describe_configs",
+        33 => "alter_configs",
+        36 => "sasl_authenticate",
+        37 => "create_partitions",
+        42 => "delete_groups",
+        44 => "incremental_alter_configs",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Whether a version of an API uses the flexible encoding, for the APIs
+/// whose bodies are decoded.
+pub fn is_flexible(api_key: i16, api_version: i16) -> bool {
+    let first = match api_key {
+        API_PRODUCE => 9,
+        API_FETCH => 12,
+        API_METADATA => 9,
+        API_SASL_AUTHENTICATE => 2,
+        _ => return false,
+    };
+    api_version >= first
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RequestHeader {
+    pub api_key: i16,
+    pub api_version: i16,
+    pub correlation_id: i32,
+    pub client_id: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RequestBody {
+    Produce {
+        transactional_id: Option<String>,
+        acks: i16,
+        topics: Vec<String>,
+    },
+    Fetch {
+        topics: Vec<String>,
+    },
+    /// The topics asked for, none for all the topics.
+    Metadata {
+        topics: Vec<String>,
+    },
+    SaslHandshake {
+        mechanism: String,
+    },
+    Other,
+}
+
+/// The topics of a response, with the first error of the response or of
+/// its topics and partitions.
+#[derive(Debug, Default, PartialEq)]
+pub struct ResponseBody {
+    pub topics: Vec<String>,
+    pub error_code: i16,
+}
+
+impl ResponseBody {
+    fn add_error(&mut self, error_code: i16) {
+        if self.error_code == ERROR_NONE {
+            self.error_code = error_code;
+        }
+    }
+}
+
+fn error<T>(i: &[u8], kind: ErrorKind) -> IResult<&[u8], T> {
+    Err(nom::Err::Error((i, kind)))
+}
+
+/// A string prefixed with its length as an i16. Invalid UTF-8 sequences
+/// are replaced.
+fn kafka_string(i: &[u8]) -> IResult<&[u8], String> {
+    let (i, len) = be_i16(i)?;
+    if len < 0 {
+        return error(i, ErrorKind::Verify);
+    }
+    let (i, s) = take(len as usize)(i)?;
+    Ok((i, String::from_utf8_lossy(s).into_owned()))
+}
+
+/// A string that is null when its length is -1.
+fn nullable_string(i: &[u8]) -> IResult<&[u8], Option<String>> {
+    let (i, len) = be_i16(i)?;
+    if len < 0 {
+        return Ok((i, None));
+    }
+    let (i, s) = take(len as usize)(i)?;
+    Ok((i, Some(String::from_utf8_lossy(s).into_owned())))
+}
+
+/// Bytes prefixed with their length as an i32, null when the length is
+/// negative.
+fn nullable_bytes(i: &[u8]) -> IResult<&[u8], Option<&[u8]>> {
+    let (i, len) = be_i32(i)?;
+    if len < 0 {
+        return Ok((i, None));
+    }
+    let (i, b) = take(len as usize)(i)?;
+    Ok((i, Some(b)))
+}
+
+/// An array prefixed with its count as an i32. A null array, with a
+/// count of -1, is returned empty.
+fn array<'a, T, F>(f: F) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], Vec<T>>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], T>,
+{
+    move |i: &'a [u8]| {
+        let (mut i, count) = be_i32(i)?;
+        let mut values = Vec::new();
+        if count <= 0 {
+            return Ok((i, values));
+        }
+        // all the elements take at least a byte
+        if count as usize > i.len() {
+            return error(i, ErrorKind::Count);
+        }
+        for _ in 0..count {
+            let (rem, value) = f(i)?;
+            values.push(value);
+            i = rem;
+        }
+        Ok((i, values))
+    }
+}
+
+fn unsigned_varint(i: &[u8]) -> IResult<&[u8], u32> {
+    let mut val = 0u32;
+    let mut rem = i;
+    for n in 0..5 {
+        let (r, b) = be_u8(rem)?;
+        rem = r;
+        val |= ((b & 0x7f) as u32) << (7 * n);
+        if b & 0x80 == 0 {
+            return Ok((rem, val));
+        }
+    }
+    error(i, ErrorKind::TooLarge)
+}
+
+/// Skip the tagged fields of the flexible versions.
+fn tagged_fields(i: &[u8]) -> IResult<&[u8], ()> {
+    let (mut i, count) = unsigned_varint(i)?;
+    for _ in 0..count {
+        let (rem, _tag) = unsigned_varint(i)?;
+        let (rem, size) = unsigned_varint(rem)?;
+        let (rem, _data) = take(size as usize)(rem)?;
+        i = rem;
+    }
+    Ok((i, ()))
+}
+
+pub fn parse_request_header(i: &[u8]) -> IResult<&[u8], RequestHeader> {
+    let (i, api_key) = be_i16(i)?;
+    let (i, api_version) = be_i16(i)?;
+    let (i, correlation_id) = be_i32(i)?;
+    let (i, client_id) = nullable_string(i)?;
+    let (i, _) = if is_flexible(api_key, api_version) { tagged_fields(i)? } else { (i, ()) };
+    Ok((i, RequestHeader { api_key, api_version, correlation_id, client_id }))
+}
+
+/// Parse the header of a response, returning its correlation id. The
+/// tagged fields of the flexible versions are parsed by
+/// `parse_response_body`, which knows the API of the request.
+pub fn parse_response_header(i: &[u8]) -> IResult<&[u8], i32> {
+    be_i32(i)
+}
+
+fn produce_partition(i: &[u8]) -> IResult<&[u8], ()> {
+    let (i, _index) = be_i32(i)?;
+    let (i, _records) = nullable_bytes(i)?;
+    Ok((i, ()))
+}
+
+fn produce_topic(i: &[u8]) -> IResult<&[u8], String> {
+    let (i, name) = kafka_string(i)?;
+    let (i, _) = array(produce_partition)(i)?;
+    Ok((i, name))
+}
+
+fn parse_produce_request(i: &[u8], version: i16) -> IResult<&[u8], RequestBody> {
+    let (i, transactional_id) = if version >= 3 { nullable_string(i)? } else { (i, None) };
+    let (i, acks) = be_i16(i)?;
+    let (i, _timeout_ms) = be_i32(i)?;
+    let (i, topics) = array(produce_topic)(i)?;
+    Ok((i, RequestBody::Produce { transactional_id, acks, topics }))
+}
+
+fn fetch_partition(version: i16) -> impl Fn(&[u8]) -> IResult<&[u8], ()> {
+    move |i: &[u8]| {
+        let (i, _partition) = be_i32(i)?;
+        let (i, _) = if version >= 9 { be_i32(i)? } else { (i, 0) };
+        let (i, _fetch_offset) = be_i64(i)?;
+        let (i, _) = if version >= 5 { be_i64(i)? } else { (i, 0) };
+        let (i, _partition_max_bytes) = be_i32(i)?;
+        Ok((i, ()))
+    }
+}
+
+fn fetch_topic(version: i16) -> impl Fn(&[u8]) -> IResult<&[u8], String> {
+    move |i: &[u8]| {
+        let (i, name) = kafka_string(i)?;
+        let (i, _) = array(fetch_partition(version))(i)?;
+        Ok((i, name))
+    }
+}
+
+fn parse_fetch_request(i: &[u8], version: i16) -> IResult<&[u8], RequestBody> {
+    let (i, _replica_id) = be_i32(i)?;
+    let (i, _max_wait_ms) = be_i32(i)?;
+    let (i, _min_bytes) = be_i32(i)?;
+    let (i, _) = if version >= 3 { be_i32(i)? } else { (i, 0) };
+    let (i, _) = if version >= 4 { be_i8(i)? } else { (i, 0) };
+    let (i, _) = if version >= 7 { be_i64(i)? } else { (i, 0) };
+    let (i, topics) = array(fetch_topic(version))(i)?;
+    Ok((i, RequestBody::Fetch { topics }))
+}
+
+fn parse_metadata_request(i: &[u8]) -> IResult<&[u8], RequestBody> {
+    let (i, topics) = array(kafka_string)(i)?;
+    Ok((i, RequestBody::Metadata { topics }))
+}
+
+fn parse_sasl_handshake_request(i: &[u8]) -> IResult<&[u8], RequestBody> {
+    let (i, mechanism) = kafka_string(i)?;
+    Ok((i, RequestBody::SaslHandshake { mechanism }))
+}
+
+/// Parse the body of a request, after its header.
+pub fn parse_request_body(i: &[u8], api_key: i16, version: i16) -> IResult<&[u8], RequestBody> {
+    if is_flexible(api_key, version) {
+        return Ok((i, RequestBody::Other));
+    }
+    match api_key {
+        API_PRODUCE => parse_produce_request(i, version),
+        API_FETCH => parse_fetch_request(i, version),
+        API_METADATA => parse_metadata_request(i),
+        API_SASL_HANDSHAKE => parse_sasl_handshake_request(i),
+        _ => Ok((i, RequestBody::Other)),
+    }
+}
+
+fn produce_response_partition(version: i16) -> impl Fn(&[u8]) -> IResult<&[u8], i16> {
+    move |i: &[u8]| {
+        let (i, _index) = be_i32(i)?;
+        let (i, error_code) = be_i16(i)?;
+        let (i, _base_offset) = be_i64(i)?;
+        let (i, _) = if version >= 2 { be_i64(i)? } else { (i, 0) };
+        let (i, _) = if version >= 5 { be_i64(i)? } else { (i, 0) };
+        let i = if version >= 8 {
+            let (i, _record_errors) = array(|i| {
+                let (i, _batch_index) = be_i32(i)?;
+                nullable_string(i)
+            })(i)?;
+            let (i, _error_message) = nullable_string(i)?;
+            i
+        } else {
+            i
+        };
+        Ok((i, error_code))
+    }
+}
+
+fn response_topic<'a, F>(partition: F) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], (String, Vec<i16>)>
+where
+    F: Fn(&'a [u8]) -> IResult<&'a [u8], i16>,
+{
+    let partitions = array(partition);
+    move |i: &'a [u8]| {
+        let (i, name) = kafka_string(i)?;
+        let (i, errors) = partitions(i)?;
+        Ok((i, (name, errors)))
+    }
+}
+
+fn add_topics(body: &mut ResponseBody, topics: Vec<(String, Vec<i16>)>) {
+    for (name, errors) in topics {
+        for error_code in errors {
+            body.add_error(error_code);
+        }
+        body.topics.push(name);
+    }
+}
+
+fn parse_produce_response(i: &[u8], version: i16) -> IResult<&[u8], ResponseBody> {
+    let (i, topics) = array(response_topic(produce_response_partition(version)))(i)?;
+    let mut body = ResponseBody::default();
+    add_topics(&mut body, topics);
+    Ok((i, body))
+}
+
+fn fetch_response_partition(version: i16) -> impl Fn(&[u8]) -> IResult<&[u8], i16> {
+    move |i: &[u8]| {
+        let (i, _partition_index) = be_i32(i)?;
+        let (i, error_code) = be_i16(i)?;
+        let (i, _high_watermark) = be_i64(i)?;
+        let (i, _) = if version >= 4 { be_i64(i)? } else { (i, 0) };
+        let (i, _) = if version >= 5 { be_i64(i)? } else { (i, 0) };
+        let (i, _) = if version >= 4 {
+            array(|i| {
+                let (i, _producer_id) = be_i64(i)?;
+                be_i64(i)
+            })(i)?
+        } else {
+            (i, Vec::new())
+        };
+        let (i, _) = if version >= 11 { be_i32(i)? } else { (i, 0) };
+        let (i, _records) = nullable_bytes(i)?;
+        Ok((i, error_code))
+    }
+}
+
+fn parse_fetch_response(i: &[u8], version: i16) -> IResult<&[u8], ResponseBody> {
+    let (i, _) = if version >= 1 { be_i32(i)? } else { (i, 0) };
+    let mut body = ResponseBody::default();
+    let i = if version >= 7 {
+        let (i, error_code) = be_i16(i)?;
+        let (i, _session_id) = be_i32(i)?;
+        body.add_error(error_code);
+        i
+    } else {
+        i
+    };
+    let (i, topics) = array(response_topic(fetch_response_partition(version)))(i)?;
+    add_topics(&mut body, topics);
+    Ok((i, body))
+}
+
+fn metadata_broker(version: i16) -> impl Fn(&[u8]) -> IResult<&[u8], ()> {
+    move |i: &[u8]| {
+        let (i, _node_id) = be_i32(i)?;
+        let (i, _host) = kafka_string(i)?;
+        let (i, _port) = be_i32(i)?;
+        let (i, _) = if version >= 1 { nullable_string(i)? } else { (i, None) };
+        Ok((i, ()))
+    }
+}
+
+fn metadata_partition(version: i16) -> impl Fn(&[u8]) -> IResult<&[u8], i16> {
+    move |i: &[u8]| {
+        let (i, error_code) = be_i16(i)?;
+        let (i, _partition_index) = be_i32(i)?;
+        let (i, _leader_id) = be_i32(i)?;
+        let (i, _) = if version >= 7 { be_i32(i)? } else { (i, 0) };
+        let (i, _replicas) = array(be_i32)(i)?;
+        let (i, _isr) = array(be_i32)(i)?;
+        let (i, _) = if version >= 5 { array(be_i32)(i)? } else { (i, Vec::new()) };
+        Ok((i, error_code))
+    }
+}
+
+fn metadata_topic(version: i16) -> impl Fn(&[u8]) -> IResult<&[u8], (String, Vec<i16>)> {
+    move |i: &[u8]| {
+        let (i, error_code) = be_i16(i)?;
+        let (i, name) = kafka_string(i)?;
+        let (i, _) = if version >= 1 { be_i8(i)? } else { (i, 0) };
+        let (i, mut errors) = array(metadata_partition(version))(i)?;
+        let (i, _) = if version >= 8 { be_i32(i)? } else { (i, 0) };
+        errors.insert(0, error_code);
+        Ok((i, (name, errors)))
+    }
+}
+
+fn parse_metadata_response(i: &[u8], version: i16) -> IResult<&[u8], ResponseBody> {
+    let (i, _) = if version >= 3 { be_i32(i)? } else { (i, 0) };
+    let (i, _brokers) = array(metadata_broker(version))(i)?;
+    let (i, _) = if version >= 2 { nullable_string(i)? } else { (i, None) };
+    let (i, _) = if version >= 1 { be_i32(i)? } else { (i, 0) };
+    let (i, topics) = array(metadata_topic(version))(i)?;
+    let mut body = ResponseBody::default();
+    add_topics(&mut body, topics);
+    Ok((i, body))
+}
+
+fn parse_error_code_response(i: &[u8]) -> IResult<&[u8], ResponseBody> {
+    let (i, error_code) = be_i16(i)?;
+    Ok((i, ResponseBody { topics: Vec::new(), error_code }))
+}
+
+/// Parse the body of a response to a request of `api_key` and
+/// `version`, after its correlation id.
+pub fn parse_response_body(i: &[u8], api_key: i16, version: i16) -> IResult<&[u8], ResponseBody> {
+    if is_flexible(api_key, version) {
+        let (i, _) = tagged_fields(i)?;
+        // the error code comes first, whatever the encoding
+        if api_key == API_SASL_AUTHENTICATE {
+            return parse_error_code_response(i);
+        }
+        return Ok((i, ResponseBody::default()));
+    }
+    match api_key {
+        API_PRODUCE => parse_produce_response(i, version),
+        API_FETCH => parse_fetch_response(i, version),
+        API_METADATA => parse_metadata_response(i, version),
+        API_SASL_HANDSHAKE | API_SASL_AUTHENTICATE => parse_error_code_response(i),
+        _ => Ok((i, ResponseBody::default())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_produce_request() {
+        let buf: &[u8] = &[
+            0x00, 0x00, 0x00, 0x03, // api key, version
+            0x00, 0x00, 0x00, 0x07, // correlation id
+            0x00, 0x04, b'p', b'r', b'o', b'd', // client id
+            0xff, 0xff, // transactional id
+            0x00, 0x01, // acks
+            0x00, 0x00, 0x75, 0x30, // timeout
+            0x00, 0x00, 0x00, 0x01, // topics
+            0x00, 0x04, b'l', b'o', b'g', b's',
+            0x00, 0x00, 0x00, 0x01, // partitions
+            0x00, 0x00, 0x00, 0x00, // index
+            0x00, 0x00, 0x00, 0x02, 0xaa, 0xbb, // records
+        ];
+        let (rem, header) = parse_request_header(buf).unwrap();
+        assert_eq!(
+            header,
+            RequestHeader {
+                api_key: API_PRODUCE,
+                api_version: 3,
+                correlation_id: 7,
+                client_id: Some("prod".to_string()),
+            }
+        );
+        let (rem, body) = parse_request_body(rem, header.api_key, header.api_version).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(
+            body,
+            RequestBody::Produce {
+                transactional_id: None,
+                acks: 1,
+                topics: vec!["logs".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_metadata_response() {
+        let buf: &[u8] = &[
+            0x00, 0x00, 0x00, 0x01, // brokers
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, b'h', 0x00, 0x00, 0x23, 0x84,
+            0x00, 0x00, 0x00, 0x02, // topics
+            0x00, 0x00, 0x00, 0x01, b'a', // no error
+            0x00, 0x00, 0x00, 0x01, // partitions
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // replicas, isr
+            0x00, 0x1d, 0x00, 0x01, b'b', // topic authorization failed
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let (rem, body) = parse_response_body(buf, API_METADATA, 0).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(body.topics, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(body.error_code, 29);
+    }
+
+    #[test]
+    fn test_parse_flexible() {
+        // SaslAuthenticate v2 response, with its empty tagged fields
+        let (_, body) = parse_response_body(&[0x00, 0x00, 0x3a], API_SASL_AUTHENTICATE, 2).unwrap();
+        assert_eq!(body.error_code, 58);
+        assert_eq!(tagged_fields(&[0x01, 0x00, 0x02, 0xaa, 0xbb, 0xcc]), Ok((&[0xcc][..], ())));
+        assert!(array(be_i32)(&[0x7f, 0xff, 0xff, 0xff, 0x00]).is_err());
+    }
+}
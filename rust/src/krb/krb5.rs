@@ -25,32 +25,82 @@ use nom::number::streaming::be_u32;
 use der_parser::der::der_read_element_header;
 use der_parser::ber::BerClass;
 use kerberos_parser::krb5_parser;
-use kerberos_parser::krb5::{EncryptionType,ErrorCode,MessageType,PrincipalName,Realm};
+use kerberos_parser::krb5::{EncryptionType,ErrorCode,MessageType,PAData,PrincipalName,Realm};
 use crate::applayer::{self, *};
+use crate::asn1::ber::{ber_validate, BerConstraints, BerError};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use crate::x509::Certificate;
+use crate::x509::cms::pkinit_certificates;
 use crate::core;
-use crate::core::{AppProto,Flow,ALPROTO_FAILED,ALPROTO_UNKNOWN,STREAM_TOCLIENT,STREAM_TOSERVER,sc_detect_engine_state_free};
+use crate::core::{AppProto,Direction,ALPROTO_UNKNOWN};
+use crate::recorddefrag::RecordDefrag;
+use crate::memcap::AppLayerMemcap;
+
+/// Maximum size of a Kerberos record over TCP
+const KRB5_MAX_RECORD_SIZE: usize = 100000;
+
+/// Memory used by the TCP record reassembly buffers
+pub static KRB5_MEMCAP: AppLayerMemcap = AppLayerMemcap::new();
+
+/// Maximum number of live transactions per flow
+static KRB5_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static KRB5_GAUGES: StateGauges = StateGauges::new();
+
+declare_counters!(krb5_stats, "app_layer.krb5", {
+    as_req,
+    as_rep,
+    tgs_req,
+    tgs_rep,
+    ap_req,
+    ap_rep,
+    error,
+    unknown,
+    anomalies,
+});
+
+/// Kerberos messages are DER encoded (RFC 4120 section 5.1), and do not
+/// nest deeply
+const KRB5_BER_CONSTRAINTS: BerConstraints = BerConstraints {
+    max_depth: 24,
+    max_length: None,
+    der: true,
+};
+
+/// PKINIT pre-authentication data types (RFC 4556)
+const PA_PK_AS_REQ: i32 = 16;
+const PA_PK_AS_REP: i32 = 17;
 
 #[derive(AppLayerEvent)]
 pub enum KRB5Event {
     MalformedData,
     WeakEncryption,
+    InvalidEncoding,
+    InvalidCertificate,
+    TooManyTransactions,
 }
 
 pub struct KRB5State {
     pub req_id: u8,
 
-    pub record_ts: usize,
-    pub defrag_buf_ts: Vec<u8>,
-    pub record_tc: usize,
-    pub defrag_buf_tc: Vec<u8>,
+    /// Record reassembly for the TCP variant
+    pub defrag_ts: RecordDefrag,
+    pub defrag_tc: RecordDefrag,
 
-    /// List of transactions for this session
-    transactions: Vec<KRB5Transaction>,
+    /// Certificates of the PKINIT request, for the transaction of its reply
+    req_certificates: Vec<Certificate>,
+    /// Set if one of the certificates of the request is invalid
+    req_invalid_certificate: bool,
 
-    /// tx counter for assigning incrementing id's to tx's
-    tx_id: u64,
+    /// List of transactions for this session
+    transactions: TxTable<KRB5Transaction>,
 }
 
+unsafe impl FfiType for KRB5State {}
+
 pub struct KRB5Transaction {
     /// The message type: AS-REQ, AS-REP, etc.
     pub msg_type: MessageType,
@@ -68,11 +118,14 @@ pub struct KRB5Transaction {
     /// Error code, if request has failed
     pub error_code: Option<ErrorCode>,
 
+    /// Certificates of the PKINIT request and reply
+    pub certificates: Vec<Certificate>,
+
     /// The internal transaction id
     id: u64,
 
     /// The detection engine state, if present
-    de_state: Option<*mut core::DetectEngineState>,
+    de_state: DetectStateHolder,
 
     /// The events associated with this transaction
     events: *mut core::AppLayerDecoderEvents,
@@ -80,6 +133,8 @@ pub struct KRB5Transaction {
     tx_data: applayer::AppLayerTxData,
 }
 
+unsafe impl FfiType for KRB5Transaction {}
+
 pub fn to_hex_string(bytes: &[u8]) -> String {
     let mut s = String::new();
     for &b in bytes {
@@ -90,30 +145,47 @@ pub fn to_hex_string(bytes: &[u8]) -> String {
 
 impl KRB5State {
     pub fn new() -> KRB5State {
+        let mut defrag_ts = RecordDefrag::new_be_u32(KRB5_MAX_RECORD_SIZE, KRB5_MAX_RECORD_SIZE);
+        defrag_ts.set_memcap(&KRB5_MEMCAP);
+        let mut defrag_tc = RecordDefrag::new_be_u32(KRB5_MAX_RECORD_SIZE, KRB5_MAX_RECORD_SIZE);
+        defrag_tc.set_memcap(&KRB5_MEMCAP);
         KRB5State{
             req_id: 0,
-            record_ts: 0,
-            defrag_buf_ts: Vec::new(),
-            record_tc: 0,
-            defrag_buf_tc: Vec::new(),
-            transactions: Vec::new(),
-            tx_id: 0,
+            defrag_ts: defrag_ts,
+            defrag_tc: defrag_tc,
+            req_certificates: Vec::new(),
+            req_invalid_certificate: false,
+            transactions: TxTable::with_limit(&KRB5_TX_LIMIT).with_gauges(&KRB5_GAUGES),
         }
     }
 
     /// Parse a Kerberos request message
     ///
     /// Returns 0 in case of success, or -1 on error
-    fn parse(&mut self, i: &[u8], _direction: u8) -> i32 {
+    fn parse(&mut self, i: &[u8], _direction: Direction) -> i32 {
+        // malformed messages are left to the message parsers
+        if let Err(BerError::Violation(_v)) = ber_validate(i, &KRB5_BER_CONSTRAINTS) {
+            SCLogDebug!("KRB5 message violates the DER constraints: {:?}", _v);
+            self.set_event(KRB5Event::InvalidEncoding);
+            return -1;
+        }
         match der_read_element_header(i) {
             Ok((_rem,hdr)) => {
                 // Kerberos messages start with an APPLICATION header
                 if hdr.class != BerClass::Application { return 0; }
                 match hdr.tag.0 {
                     10 => {
+                        stats_incr!(krb5_stats::as_req);
                         self.req_id = 10;
+                        self.req_certificates.clear();
+                        self.req_invalid_certificate = false;
+                        if let Ok((_,kdc_req)) = krb5_parser::parse_as_req(i) {
+                            self.req_invalid_certificate = !parse_pkinit_certificates(
+                                &kdc_req.padata, PA_PK_AS_REQ, &mut self.req_certificates);
+                        }
                     },
                     11 => {
+                        stats_incr!(krb5_stats::as_rep);
                         let res = krb5_parser::parse_as_rep(i);
                         if let Ok((_,kdc_rep)) = res {
                             let mut tx = self.new_tx();
@@ -122,17 +194,27 @@ impl KRB5State {
                             tx.realm = Some(kdc_rep.crealm);
                             tx.sname = Some(kdc_rep.ticket.sname);
                             tx.etype = Some(kdc_rep.enc_part.etype);
+                            let valid = self.take_request_certificates(&mut tx)
+                                & parse_pkinit_certificates(
+                                    &kdc_rep.padata, PA_PK_AS_REP, &mut tx.certificates);
                             self.transactions.push(tx);
                             if test_weak_encryption(kdc_rep.enc_part.etype) {
                                 self.set_event(KRB5Event::WeakEncryption);
                             }
+                            if !valid {
+                                self.set_event(KRB5Event::InvalidCertificate);
+                            }
                         };
                         self.req_id = 0;
                     },
                     12 => {
+                        stats_incr!(krb5_stats::tgs_req);
                         self.req_id = 12;
+                        self.req_certificates.clear();
+                        self.req_invalid_certificate = false;
                     },
                     13 => {
+                        stats_incr!(krb5_stats::tgs_rep);
                         let res = krb5_parser::parse_tgs_rep(i);
                         if let Ok((_,kdc_rep)) = res {
                             let mut tx = self.new_tx();
@@ -149,12 +231,15 @@ impl KRB5State {
                         self.req_id = 0;
                     },
                     14 => {
+                        stats_incr!(krb5_stats::ap_req);
                         self.req_id = 14;
                     },
                     15 => {
+                        stats_incr!(krb5_stats::ap_rep);
                         self.req_id = 0;
                     },
                     30 => {
+                        stats_incr!(krb5_stats::error);
                         let res = krb5_parser::parse_krb_error(i);
                         if let Ok((_,error)) = res {
                             let mut tx = self.new_tx();
@@ -163,11 +248,18 @@ impl KRB5State {
                             tx.realm = error.crealm;
                             tx.sname = Some(error.sname);
                             tx.error_code = Some(error.error_code);
+                            let valid = self.take_request_certificates(&mut tx);
                             self.transactions.push(tx);
+                            if !valid {
+                                self.set_event(KRB5Event::InvalidCertificate);
+                            }
                         };
                         self.req_id = 0;
                     },
-                    _ => { SCLogDebug!("unknown/unsupported tag {}", hdr.tag); },
+                    _ => {
+                        stats_incr!(krb5_stats::unknown);
+                        SCLogDebug!("unknown/unsupported tag {}", hdr.tag);
+                    },
                 }
                 0
             },
@@ -191,24 +283,49 @@ impl KRB5State {
     }
 
     fn new_tx(&mut self) -> KRB5Transaction {
-        self.tx_id += 1;
-        KRB5Transaction::new(self.tx_id)
+        KRB5Transaction::new(self.transactions.next_id())
+    }
+
+    /// Move the certificates of the last AS-REQ to the transaction of its
+    /// reply. Returns false if one of them is invalid.
+    fn take_request_certificates(&mut self, tx: &mut KRB5Transaction) -> bool {
+        tx.certificates.append(&mut self.req_certificates);
+        !std::mem::replace(&mut self.req_invalid_certificate, false)
     }
 
     fn get_tx_by_id(&mut self, tx_id: u64) -> Option<&KRB5Transaction> {
-        self.transactions.iter().find(|&tx| tx.id == tx_id + 1)
+        self.transactions.get(tx_id)
     }
 
     fn free_tx(&mut self, tx_id: u64) {
-        let tx = self.transactions.iter().position(|tx| tx.id == tx_id + 1);
-        debug_assert!(tx != None);
-        if let Some(idx) = tx {
-            let _ = self.transactions.remove(idx);
+        self.transactions.free(tx_id);
+    }
+
+    /// Parse Kerberos messages over TCP, each prefixed by a record mark
+    fn parse_tcp(&mut self, i: &[u8], direction: Direction) -> AppLayerResult {
+        let mut defrag = if direction == Direction::ToServer {
+            std::mem::replace(&mut self.defrag_ts, RecordDefrag::new_be_u32(0, 0))
+        } else {
+            std::mem::replace(&mut self.defrag_tc, RecordDefrag::new_be_u32(0, 0))
+        };
+        let r = defrag.feed(i, |record| self.parse(record, direction) >= 0);
+        if direction == Direction::ToServer {
+            self.defrag_ts = defrag;
+        } else {
+            self.defrag_tc = defrag;
+        }
+        match r {
+            Ok(()) => AppLayerResult::ok(),
+            Err(_e) => {
+                SCLogDebug!("KRB5/TCP record reassembly failed: {:?}", _e);
+                AppLayerResult::err()
+            }
         }
     }
 
     /// Set an event. The event is set on the most recent transaction.
     fn set_event(&mut self, event: KRB5Event) {
+        stats_incr!(krb5_stats::anomalies);
         if let Some(tx) = self.transactions.last_mut() {
             let ev = event as u8;
             core::sc_app_layer_decoder_events_set_event_raw(&mut tx.events, ev);
@@ -216,6 +333,44 @@ impl KRB5State {
     }
 }
 
+impl txtable::State for KRB5State {
+    type Tx = KRB5Transaction;
+
+    fn tx_table(&self) -> &TxTable<KRB5Transaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<KRB5Transaction> {
+        &mut self.transactions
+    }
+}
+
+impl Truncate for KRB5State {
+    // Transactions are complete as soon as they are parsed, so only the
+    // partial record buffered in the truncated direction is dropped.
+    fn truncate(&mut self, direction: Direction) {
+        match direction {
+            Direction::ToServer => self.defrag_ts.reset(),
+            Direction::ToClient => self.defrag_tc.reset(),
+        }
+    }
+}
+
+impl Transaction for KRB5Transaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = KRB5Event::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
 impl KRB5Transaction {
     pub fn new(id: u64) -> KRB5Transaction {
         KRB5Transaction{
@@ -225,8 +380,9 @@ impl KRB5Transaction {
             sname: None,
             etype: None,
             error_code: None,
+            certificates: Vec::new(),
             id: id,
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: applayer::AppLayerTxData::new(),
         }
@@ -238,10 +394,28 @@ impl Drop for KRB5Transaction {
         if self.events != std::ptr::null_mut() {
             core::sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        if let Some(state) = self.de_state {
-            sc_detect_engine_state_free(state);
+    }
+}
+
+/// Parse the certificates of the PKINIT pre-authentication data of type
+/// `padata_type` into `certificates`. Returns false if one of them is
+/// invalid.
+fn parse_pkinit_certificates(
+    padata: &[PAData], padata_type: i32, certificates: &mut Vec<Certificate>,
+) -> bool {
+    let mut valid = true;
+    for pa in padata.iter().filter(|pa| pa.padata_type.0 == padata_type) {
+        for der in pkinit_certificates(pa.padata_value) {
+            match Certificate::parse(der) {
+                Ok(cert) => certificates.push(cert),
+                Err(_e) => {
+                    SCLogDebug!("Invalid PKINIT certificate: {:?}", _e);
+                    valid = false;
+                }
+            }
         }
     }
+    valid
 }
 
 /// Return true if Kerberos `EncryptionType` is weak
@@ -282,7 +456,7 @@ pub unsafe extern "C" fn rs_krb5_state_get_tx(state: *mut std::os::raw::c_void,
                                       tx_id: u64)
                                       -> *mut std::os::raw::c_void
 {
-    let state = cast_pointer!(state,KRB5State);
+    let state: &mut KRB5State = cast_mut(state);
     match state.get_tx_by_id(tx_id) {
         Some(tx) => tx as *const _ as *mut _,
         None     => std::ptr::null_mut(),
@@ -293,15 +467,15 @@ pub unsafe extern "C" fn rs_krb5_state_get_tx(state: *mut std::os::raw::c_void,
 pub unsafe extern "C" fn rs_krb5_state_get_tx_count(state: *mut std::os::raw::c_void)
                                             -> u64
 {
-    let state = cast_pointer!(state,KRB5State);
-    state.tx_id
+    let state: &mut KRB5State = cast_mut(state);
+    state.transactions.tx_count()
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_krb5_state_tx_free(state: *mut std::os::raw::c_void,
                                        tx_id: u64)
 {
-    let state = cast_pointer!(state,KRB5State);
+    let state: &mut KRB5State = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -313,100 +487,70 @@ pub extern "C" fn rs_krb5_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void
     1
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_krb5_state_set_tx_detect_state(
-    tx: *mut std::os::raw::c_void,
-    de_state: &mut core::DetectEngineState) -> std::os::raw::c_int
-{
-    let tx = cast_pointer!(tx,KRB5Transaction);
-    tx.de_state = Some(de_state);
-    0
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_krb5_state_get_tx_detect_state(
-    tx: *mut std::os::raw::c_void)
-    -> *mut core::DetectEngineState
-{
-    let tx = cast_pointer!(tx,KRB5Transaction);
-    match tx.de_state {
-        Some(ds) => ds,
-        None => std::ptr::null_mut(),
-    }
-}
+export_tx_detect_state!(rs_krb5_state_get_tx_detect_state, rs_krb5_state_set_tx_detect_state, KRB5Transaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_krb5_state_get_events(tx: *mut std::os::raw::c_void)
                                           -> *mut core::AppLayerDecoderEvents
 {
-    let tx = cast_pointer!(tx, KRB5Transaction);
+    let tx: &mut KRB5Transaction = cast_mut(tx);
     return tx.events;
 }
 
 static mut ALPROTO_KRB5 : AppProto = ALPROTO_UNKNOWN;
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_krb5_probing_parser(_flow: *const Flow,
-        _direction: u8,
-        input:*const u8, input_len: u32,
-        _rdir: *mut u8) -> AppProto
-{
-    let slice = build_slice!(input,input_len as usize);
-    let alproto = ALPROTO_KRB5;
-    if slice.len() <= 10 { return ALPROTO_FAILED; }
+fn krb5_probe(slice: &[u8], _direction: Direction) -> ProbeResult {
+    let alproto = unsafe { ALPROTO_KRB5 };
+    if slice.len() <= 10 { return ProbeResult::Failed; }
     match der_read_element_header(slice) {
         Ok((rem, ref hdr)) => {
             // Kerberos messages start with an APPLICATION header
-            if hdr.class != BerClass::Application { return ALPROTO_FAILED; }
+            if hdr.class != BerClass::Application { return ProbeResult::Failed; }
             // Tag number should be <= 30
-            if hdr.tag.0 > 30 { return ALPROTO_FAILED; }
+            if hdr.tag.0 > 30 { return ProbeResult::Failed; }
             // Kerberos messages contain sequences
-            if rem.is_empty() || rem[0] != 0x30 { return ALPROTO_FAILED; }
+            if rem.is_empty() || rem[0] != 0x30 { return ProbeResult::Failed; }
             // Check kerberos version
             if let Ok((rem,_hdr)) = der_read_element_header(rem) {
                 if rem.len() > 5 {
                     match (rem[2],rem[3],rem[4]) {
                         // Encoding of DER integer 5 (version)
-                        (2,1,5) => { return alproto; },
+                        (2,1,5) => { return ProbeResult::Found(alproto); },
                         _       => (),
                     }
                 }
             }
-            return ALPROTO_FAILED;
+            return ProbeResult::Failed;
         },
         Err(nom::Err::Incomplete(_)) => {
-            return ALPROTO_UNKNOWN;
+            return ProbeResult::Unknown;
         },
         Err(_) => {
-            return ALPROTO_FAILED;
+            return ProbeResult::Failed;
         },
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_krb5_probing_parser_tcp(_flow: *const Flow,
-        direction: u8,
-        input:*const u8, input_len: u32,
-        rdir: *mut u8) -> AppProto
-{
-    let slice = build_slice!(input,input_len as usize);
-    if slice.len() <= 14 { return ALPROTO_FAILED; }
+fn krb5_probe_tcp(slice: &[u8], direction: Direction) -> ProbeResult {
+    if slice.len() <= 14 { return ProbeResult::Failed; }
     match be_u32(slice) as IResult<&[u8],u32> {
         Ok((rem, record_mark)) => {
             // protocol implementations forbid very large requests
-            if record_mark > 16384 { return ALPROTO_FAILED; }
-            return rs_krb5_probing_parser(_flow, direction,
-                    rem.as_ptr(), rem.len() as u32, rdir);
+            if record_mark > 16384 { return ProbeResult::Failed; }
+            return krb5_probe(rem, direction);
         },
         Err(nom::Err::Incomplete(_)) => {
-            return ALPROTO_UNKNOWN;
+            return ProbeResult::Unknown;
         },
         Err(_) => {
-            return ALPROTO_FAILED;
+            return ProbeResult::Failed;
         },
     }
 }
 
+export_probe!(rs_krb5_probing_parser, krb5_probe);
+export_probe!(rs_krb5_probing_parser_tcp, krb5_probe_tcp);
+
 #[no_mangle]
 pub unsafe extern "C" fn rs_krb5_parse_request(_flow: *const core::Flow,
                                        state: *mut std::os::raw::c_void,
@@ -416,8 +560,8 @@ pub unsafe extern "C" fn rs_krb5_parse_request(_flow: *const core::Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8) -> AppLayerResult {
     let buf = build_slice!(input,input_len as usize);
-    let state = cast_pointer!(state,KRB5State);
-    if state.parse(buf, STREAM_TOSERVER) < 0 {
+    let state: &mut KRB5State = cast_mut(state);
+    if state.parse(buf, Direction::ToServer) < 0 {
         return AppLayerResult::err();
     }
     AppLayerResult::ok()
@@ -432,8 +576,8 @@ pub unsafe extern "C" fn rs_krb5_parse_response(_flow: *const core::Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8) -> AppLayerResult {
     let buf = build_slice!(input,input_len as usize);
-    let state = cast_pointer!(state,KRB5State);
-    if state.parse(buf, STREAM_TOCLIENT) < 0 {
+    let state: &mut KRB5State = cast_mut(state);
+    if state.parse(buf, Direction::ToClient) < 0 {
         return AppLayerResult::err();
     }
     AppLayerResult::ok()
@@ -448,54 +592,8 @@ pub unsafe extern "C" fn rs_krb5_parse_request_tcp(_flow: *const core::Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8) -> AppLayerResult {
     let buf = build_slice!(input,input_len as usize);
-    let state = cast_pointer!(state,KRB5State);
-
-    let mut v : Vec<u8>;
-    let tcp_buffer = match state.record_ts {
-        0 => buf,
-        _ => {
-            // sanity check to avoid memory exhaustion
-            if state.defrag_buf_ts.len() + buf.len() > 100000 {
-                SCLogDebug!("rs_krb5_parse_request_tcp: TCP buffer exploded {} {}",
-                            state.defrag_buf_ts.len(), buf.len());
-                return AppLayerResult::err();
-            }
-            v = state.defrag_buf_ts.split_off(0);
-            v.extend_from_slice(buf);
-            v.as_slice()
-        }
-    };
-    let mut cur_i = tcp_buffer;
-    while cur_i.len() > 0 {
-        if state.record_ts == 0 {
-            match be_u32(cur_i) as IResult<&[u8],u32> {
-                Ok((rem,record)) => {
-                    state.record_ts = record as usize;
-                    cur_i = rem;
-                },
-                Err(nom::Err::Incomplete(_)) => {
-                    state.defrag_buf_ts.extend_from_slice(cur_i);
-                    return AppLayerResult::ok();
-                }
-                _ => {
-                    SCLogDebug!("rs_krb5_parse_request_tcp: reading record mark failed!");
-                    return AppLayerResult::err();
-                }
-            }
-        }
-        if cur_i.len() >= state.record_ts {
-            if state.parse(cur_i, STREAM_TOSERVER) < 0 {
-                return AppLayerResult::err();
-            }
-            state.record_ts = 0;
-            cur_i = &cur_i[state.record_ts..];
-        } else {
-            // more fragments required
-            state.defrag_buf_ts.extend_from_slice(cur_i);
-            return AppLayerResult::ok();
-        }
-    }
-    AppLayerResult::ok()
+    let state: &mut KRB5State = cast_mut(state);
+    state.parse_tcp(buf, Direction::ToServer)
 }
 
 #[no_mangle]
@@ -507,58 +605,20 @@ pub unsafe extern "C" fn rs_krb5_parse_response_tcp(_flow: *const core::Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8) -> AppLayerResult {
     let buf = build_slice!(input,input_len as usize);
-    let state = cast_pointer!(state,KRB5State);
-
-    let mut v : Vec<u8>;
-    let tcp_buffer = match state.record_tc {
-        0 => buf,
-        _ => {
-            // sanity check to avoid memory exhaustion
-            if state.defrag_buf_tc.len() + buf.len() > 100000 {
-                SCLogDebug!("rs_krb5_parse_response_tcp: TCP buffer exploded {} {}",
-                            state.defrag_buf_tc.len(), buf.len());
-                return AppLayerResult::err();
-            }
-            v = state.defrag_buf_tc.split_off(0);
-            v.extend_from_slice(buf);
-            v.as_slice()
-        }
-    };
-    let mut cur_i = tcp_buffer;
-    while cur_i.len() > 0 {
-        if state.record_tc == 0 {
-            match be_u32(cur_i) as IResult<&[u8],_> {
-                Ok((rem,record)) => {
-                    state.record_tc = record as usize;
-                    cur_i = rem;
-                },
-                Err(nom::Err::Incomplete(_)) => {
-                    state.defrag_buf_tc.extend_from_slice(cur_i);
-                    return AppLayerResult::ok();
-                }
-                _ => {
-                    SCLogDebug!("reading record mark failed!");
-                    return AppLayerResult::ok();
-                }
-            }
-        }
-        if cur_i.len() >= state.record_tc {
-            if state.parse(cur_i, STREAM_TOCLIENT) < 0 {
-                return AppLayerResult::err();
-            }
-            state.record_tc = 0;
-            cur_i = &cur_i[state.record_tc..];
-        } else {
-            // more fragments required
-            state.defrag_buf_tc.extend_from_slice(cur_i);
-            return AppLayerResult::ok();
-        }
-    }
-    AppLayerResult::ok()
+    let state: &mut KRB5State = cast_mut(state);
+    state.parse_tcp(buf, Direction::ToClient)
 }
 
 export_tx_data_get!(rs_krb5_get_tx_data, KRB5Transaction);
 
+
+fn krb5_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    KRB5_GAUGES.log(js)?;
+    js.set_uint("buffered", KRB5_MEMCAP.get_memuse())?;
+    js.set_uint("events", krb5_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
 const PARSER_NAME : &'static [u8] = b"krb5\0";
 
 #[no_mangle]
@@ -590,12 +650,16 @@ pub unsafe extern "C" fn rs_register_krb5_parser() {
         localstorage_new   : None,
         localstorage_free  : None,
         get_files          : None,
-        get_tx_iterator    : None,
+        get_tx_iterator    : Some(txtable::state_get_tx_iterator::<KRB5State>),
         get_tx_data        : rs_krb5_get_tx_data,
-        apply_tx_config    : None,
+        apply_tx_config    : Some(txtable::state_apply_tx_config::<KRB5State>),
         flags              : APP_LAYER_PARSER_OPT_UNIDIR_TXS,
-        truncate           : None,
+        truncate           : Some(applayer::state_truncate::<KRB5State>),
+        state_purge        : None,
     };
+    krb5_stats::register();
+    KRB5_TX_LIMIT.configure("krb5", txtable::DEFAULT_MAX_TX);
+    introspect::register("krb5", krb5_introspect);
     // register UDP parser
     let ip_proto_str = CString::new("udp").unwrap();
     if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
@@ -609,6 +673,7 @@ pub unsafe extern "C" fn rs_register_krb5_parser() {
         SCLogDebug!("Protocol detector and parser disabled for KRB5/UDP.");
     }
     // register TCP parser
+    KRB5_MEMCAP.configure("krb5", 0);
     parser.ipproto = core::IPPROTO_TCP;
     parser.probe_ts = Some(rs_krb5_probing_parser_tcp);
     parser.probe_tc = Some(rs_krb5_probing_parser_tcp);
@@ -0,0 +1,116 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Sticky buffers on the search requests.
+
+use super::ldap::{LdapTransaction, ALPROTO_LDAP};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_LDAP_SEARCH_BASE_BUFFER_ID: c_int = 0;
+static mut G_LDAP_SEARCH_FILTER_BUFFER_ID: c_int = 0;
+
+unsafe fn set_buffer(data: &[u8], buf: *mut *const u8, len: *mut u32) -> bool {
+    *buf = data.as_ptr();
+    *len = data.len() as u32;
+    true
+}
+
+unsafe extern "C" fn ldap_tx_get_search_base(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut LdapTransaction = cast_mut(tx);
+    match tx.search_request() {
+        Some(search) => set_buffer(search.base_object.as_bytes(), buf, len),
+        None => false,
+    }
+}
+
+unsafe extern "C" fn ldap_tx_get_search_filter(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut LdapTransaction = cast_mut(tx);
+    match tx.search_request() {
+        Some(search) => set_buffer(search.filter.as_bytes(), buf, len),
+        None => false,
+    }
+}
+
+unsafe extern "C" fn ldap_search_base_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ldap_tx_get_search_base)
+}
+
+unsafe extern "C" fn ldap_search_filter_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ldap_tx_get_search_filter)
+}
+
+unsafe extern "C" fn ldap_search_base_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_LDAP, G_LDAP_SEARCH_BASE_BUFFER_ID)
+}
+
+unsafe extern "C" fn ldap_search_filter_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_LDAP, G_LDAP_SEARCH_FILTER_BUFFER_ID)
+}
+
+/// Register the LDAP keywords. The filter buffer holds the string
+/// representation of the filter, as in RFC 4515, e.g.
+/// `(&(objectClass=user)(cn=admin*))`.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "ldap.search.base",
+        desc: "sticky buffer to match on the base object of LDAP search requests",
+        url: "/rules/ldap-keywords.html#ldap-search-base",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ldap_search_base_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_LDAP_SEARCH_BASE_BUFFER_ID = helper_buffer_mpm_register(
+            "ldap.search.base", "ldap search base object", ALPROTO_LDAP, false, true, 0,
+            ldap_search_base_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "ldap.search.filter",
+        desc: "sticky buffer to match on the filter of LDAP search requests",
+        url: "/rules/ldap-keywords.html#ldap-search-filter",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ldap_search_filter_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_LDAP_SEARCH_FILTER_BUFFER_ID = helper_buffer_mpm_register(
+            "ldap.search.filter", "ldap search filter", ALPROTO_LDAP, false, true, 0,
+            ldap_search_filter_get_data);
+    }
+}
@@ -0,0 +1,587 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(ldap_stats, "app_layer.ldap", {
+    bind,
+    search,
+    cleartext_bind,
+    starttls,
+    malformed,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum LdapEvent {
+    MalformedData,
+    MessageTooLarge,
+    UnsolicitedResponse,
+    CleartextBind,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static LDAP_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static LDAP_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum size of a message. Search results can carry large attributes
+/// such as certificates.
+const LDAP_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Maximum number of requests waiting for their response. The oldest
+/// ones are completed without a response beyond that.
+const LDAP_MAX_PENDING: usize = 256;
+
+/// Maximum number of search result entries and references kept per
+/// transaction. Further ones are counted only.
+const LDAP_MAX_TX_RESPONSES: usize = 256;
+
+pub static mut ALPROTO_LDAP: AppProto = ALPROTO_UNKNOWN;
+
+#[derive(Debug)]
+pub struct LdapTransaction {
+    id: u64,
+    pub message_id: u32,
+    pub request: Option<ProtocolOp>,
+    /// Responses, ending with the one completing the operation.
+    pub responses: Vec<ProtocolOp>,
+    /// Responses that were not kept.
+    pub responses_dropped: u32,
+    pub complete: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for LdapTransaction {}
+
+impl LdapTransaction {
+    fn new(id: u64, message_id: u32) -> LdapTransaction {
+        LdapTransaction {
+            id,
+            message_id,
+            request: None,
+            responses: Vec::new(),
+            responses_dropped: 0,
+            complete: false,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: LdapEvent) {
+        stats_incr!(ldap_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    fn add_response(&mut self, op: ProtocolOp) {
+        if self.responses.len() < LDAP_MAX_TX_RESPONSES {
+            self.responses.push(op);
+        } else {
+            self.responses_dropped = self.responses_dropped.saturating_add(1);
+        }
+    }
+
+    /// The search request of the transaction, if it is a search.
+    pub fn search_request(&self) -> Option<&SearchRequest> {
+        match self.request {
+            Some(ProtocolOp::SearchRequest(ref search)) => Some(search),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for LdapTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for LdapTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = LdapEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+pub struct LdapState {
+    transactions: TxTable<LdapTransaction>,
+    /// Requests waiting for a response, by message id.
+    pending: PairingTable<u32>,
+    /// Message id of a StartTLS request waiting for its response.
+    starttls_id: Option<u32>,
+    /// Set once StartTLS succeeded: the rest of the flow is TLS, and is
+    /// not parsed.
+    tls: bool,
+}
+
+unsafe impl FfiType for LdapState {}
+
+impl txtable::State for LdapState {
+    type Tx = LdapTransaction;
+
+    fn tx_table(&self) -> &TxTable<LdapTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<LdapTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl LdapState {
+    pub fn new() -> LdapState {
+        LdapState {
+            transactions: TxTable::with_limit(&LDAP_TX_LIMIT).with_gauges(&LDAP_GAUGES),
+            pending: PairingTable::new(LDAP_MAX_PENDING, 0),
+            starttls_id: None,
+            tls: false,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+        self.pending.remove_tx(tx_id + 1);
+    }
+
+    fn find_tx(&mut self, id: u64) -> Option<&mut LdapTransaction> {
+        self.transactions.iter_mut().find(|tx| tx.id == id)
+    }
+
+    /// Add a transaction holding only an event.
+    fn set_event(&mut self, event: LdapEvent) {
+        let mut tx = LdapTransaction::new(self.transactions.next_id(), 0);
+        tx.complete = true;
+        tx.set_event(event);
+        self.transactions.push(tx);
+    }
+
+    fn handle_request(&mut self, message: LdapMessage) {
+        let mut tx = LdapTransaction::new(self.transactions.next_id(), message.message_id);
+        match message.op {
+            ProtocolOp::BindRequest(ref bind) => {
+                stats_incr!(ldap_stats::bind);
+                if let BindAuth::Simple { password_len } = bind.auth {
+                    if password_len > 0 {
+                        stats_incr!(ldap_stats::cleartext_bind);
+                        tx.set_event(LdapEvent::CleartextBind);
+                    }
+                }
+            }
+            ProtocolOp::SearchRequest(_) => stats_incr!(ldap_stats::search),
+            ProtocolOp::ExtendedRequest { ref name } if name == LDAP_STARTTLS_OID => {
+                self.starttls_id = Some(message.message_id);
+            }
+            _ => {}
+        }
+        let tag = message.op.tag();
+        tx.request = Some(message.op);
+        if tag == LDAP_UNBIND_REQUEST || tag == LDAP_ABANDON_REQUEST {
+            // no response
+            tx.complete = true;
+        } else {
+            for id in self.pending.insert(message.message_id, tx.id, 0) {
+                if let Some(tx) = self.find_tx(id) {
+                    tx.complete = true;
+                }
+            }
+        }
+        self.transactions.push(tx);
+    }
+
+    fn handle_response(&mut self, message: LdapMessage) {
+        let message_id = message.message_id;
+        if let ProtocolOp::Response { tag: LDAP_EXTENDED_RESPONSE, ref result } = message.op {
+            if self.starttls_id == Some(message_id) && result.result_code == 0 {
+                SCLogDebug!("StartTLS, no more parsing");
+                stats_incr!(ldap_stats::starttls);
+                self.tls = true;
+            }
+        }
+        let last = match message.op {
+            ProtocolOp::SearchResultEntry { .. } => false,
+            ProtocolOp::Other { tag } => {
+                tag != LDAP_SEARCH_RESULT_REFERENCE && tag != LDAP_INTERMEDIATE_RESPONSE
+            }
+            _ => true,
+        };
+        let id = if last {
+            self.pending.take(&message_id)
+        } else {
+            self.pending.get(&message_id)
+        };
+        let tx = match id {
+            Some(id) => self.find_tx(id),
+            None => None,
+        };
+        if let Some(tx) = tx {
+            tx.add_response(message.op);
+            tx.complete = last;
+            return;
+        }
+        // a notice of disconnection is unsolicited by design
+        let mut tx = LdapTransaction::new(self.transactions.next_id(), message_id);
+        if message_id != 0 {
+            tx.set_event(LdapEvent::UnsolicitedResponse);
+        }
+        tx.add_response(message.op);
+        tx.complete = true;
+        self.transactions.push(tx);
+    }
+
+    fn parse(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
+        let mut current = input;
+        while !current.is_empty() && !self.tls {
+            match parse_ldap_message(current) {
+                Ok((rem, message)) => {
+                    SCLogDebug!("message {:?}", message);
+                    let response = is_response(message.op.tag());
+                    if response != (direction == Direction::ToClient) {
+                        stats_incr!(ldap_stats::malformed);
+                        self.set_event(LdapEvent::MalformedData);
+                    } else if response {
+                        self.handle_response(message);
+                    } else {
+                        self.handle_request(message);
+                    }
+                    current = rem;
+                }
+                Err(nom::Err::Incomplete(_)) => {
+                    let consumed = input.len() - current.len();
+                    let needed = match ber_header(current) {
+                        Ok((rem, header)) => current.len() - rem.len() + header.len,
+                        Err(_) => current.len() + 1,
+                    };
+                    if needed > LDAP_MAX_MESSAGE_SIZE {
+                        self.set_event(LdapEvent::MessageTooLarge);
+                        return AppLayerResult::err();
+                    }
+                    return AppLayerResult::incomplete_from(input.len(), consumed, needed);
+                }
+                Err(_) => {
+                    stats_incr!(ldap_stats::malformed);
+                    self.set_event(LdapEvent::MalformedData);
+                    return AppLayerResult::err();
+                }
+            }
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for an LDAPMessage: a SEQUENCE starting with the message id.
+fn ldap_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    match parse_ldap_message(input) {
+        Ok((_, message)) => {
+            let actual = if is_response(message.op.tag()) {
+                Direction::ToClient
+            } else {
+                Direction::ToServer
+            };
+            ProbeResult::with_direction(unsafe { ALPROTO_LDAP }, direction, actual)
+        }
+        Err(nom::Err::Incomplete(_)) => {
+            // check what can be checked of the start of the message
+            match ber_header(input) {
+                Ok((rem, header)) if input[0] == 0x30 && header.len <= LDAP_MAX_MESSAGE_SIZE => {
+                    if rem.is_empty() || rem[0] == 0x02 {
+                        ProbeResult::Unknown
+                    } else {
+                        ProbeResult::Failed
+                    }
+                }
+                Err(nom::Err::Incomplete(_)) if input.is_empty() || input[0] == 0x30 => {
+                    ProbeResult::Unknown
+                }
+                _ => ProbeResult::Failed,
+            }
+        }
+        Err(_) => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_ldap_probing_parser, ldap_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_ldap_state_new(_orig_state: *mut std::os::raw::c_void,
+                                    _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = LdapState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ldap_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut LdapState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ldap_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut LdapState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ldap_parse_request(_flow: *const Flow,
+                                               state: *mut std::os::raw::c_void,
+                                               pstate: *mut std::os::raw::c_void,
+                                               input: *const u8,
+                                               input_len: u32,
+                                               _data: *const std::os::raw::c_void,
+                                               _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut LdapState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ldap_parse_response(_flow: *const Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut LdapState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ldap_state_get_tx(state: *mut std::os::raw::c_void,
+                                              tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut LdapState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ldap_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut LdapState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ldap_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                         _direction: u8) -> std::os::raw::c_int {
+    let tx: &mut LdapTransaction = cast_mut(tx);
+    if tx.complete {
+        1
+    } else {
+        0
+    }
+}
+
+export_tx_detect_state!(rs_ldap_state_get_tx_detect_state, rs_ldap_state_set_tx_detect_state,
+                        LdapTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ldap_state_get_events(tx: *mut std::os::raw::c_void)
+                                                  -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut LdapTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_ldap_get_tx_data, LdapTransaction);
+
+fn ldap_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    LDAP_GAUGES.log(js)?;
+    js.set_uint("events", ldap_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+const PARSER_NAME: &[u8] = b"ldap\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_ldap_register_parser() {
+    ldap_stats::register();
+    LDAP_TX_LIMIT.configure("ldap", txtable::DEFAULT_MAX_TX);
+    introspect::register("ldap", ldap_introspect);
+    let default_port = CString::new("389").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_TCP,
+        probe_ts: Some(rs_ldap_probing_parser),
+        probe_tc: Some(rs_ldap_probing_parser),
+        min_depth: 0,
+        max_depth: 16,
+        state_new: rs_ldap_state_new,
+        state_free: rs_ldap_state_free,
+        tx_free: rs_ldap_state_tx_free,
+        parse_ts: rs_ldap_parse_request,
+        parse_tc: rs_ldap_parse_response,
+        get_tx_count: rs_ldap_state_get_tx_count,
+        get_tx: rs_ldap_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_ldap_tx_get_alstate_progress,
+        get_de_state: rs_ldap_state_get_tx_detect_state,
+        set_de_state: rs_ldap_state_set_tx_detect_state,
+        get_events: Some(rs_ldap_state_get_events),
+        get_eventinfo: Some(LdapEvent::get_event_info),
+        get_eventinfo_byid: Some(LdapEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<LdapState>),
+        get_tx_data: rs_ldap_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<LdapState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_LDAP = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust ldap parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for LDAP.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    // bind request with a password, and its successful response
+    const BIND_REQUEST: &[u8] = &[
+        0x30, 0x1c, 0x02, 0x01, 0x01, 0x60, 0x17, 0x02, 0x01, 0x03, 0x04, 0x0a,
+        b'c', b'n', b'=', b'r', b'e', b'a', b'd', b'e', b'r', b's',
+        0x80, 0x06, b's', b'e', b'c', b'r', b'e', b't',
+    ];
+    const BIND_RESPONSE: &[u8] = &[
+        0x30, 0x0c, 0x02, 0x01, 0x01, 0x61, 0x07, 0x0a, 0x01, 0x00, 0x04, 0x00, 0x04, 0x00,
+    ];
+    // search request for (uid=*) under dc=org, its entry and result
+    const SEARCH_REQUEST: &[u8] = &[
+        0x30, 0x23, 0x02, 0x01, 0x02, 0x63, 0x1e, 0x04, 0x06, b'd', b'c', b'=', b'o', b'r',
+        b'g', 0x0a, 0x01, 0x02, 0x0a, 0x01, 0x00, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00, 0x01,
+        0x01, 0x00, 0x87, 0x03, b'u', b'i', b'd', 0x30, 0x00,
+    ];
+    const SEARCH_ENTRY: &[u8] = &[
+        0x30, 0x13, 0x02, 0x01, 0x02, 0x64, 0x0e, 0x04, 0x0a, b'u', b'i', b'd', b'=', b'a',
+        b',', b'd', b'c', b'=', b'o', 0x30, 0x00,
+    ];
+    const SEARCH_DONE: &[u8] = &[
+        0x30, 0x0c, 0x02, 0x01, 0x02, 0x65, 0x07, 0x0a, 0x01, 0x00, 0x04, 0x00, 0x04, 0x00,
+    ];
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_ldap_state_new,
+            state_free: rs_ldap_state_free,
+            parse_ts: rs_ldap_parse_request,
+            parse_tc: rs_ldap_parse_response,
+            get_tx_count: rs_ldap_state_get_tx_count,
+            get_tx: rs_ldap_state_get_tx,
+            tx_free: rs_ldap_state_tx_free,
+        })
+    }
+
+    #[test]
+    fn test_ldap_bind_search() {
+        for segment_size in 1..=BIND_REQUEST.len() {
+            let mut h = harness();
+            let mut request = BIND_REQUEST.to_vec();
+            request.extend_from_slice(SEARCH_REQUEST);
+            let mut response = BIND_RESPONSE.to_vec();
+            response.extend_from_slice(SEARCH_ENTRY);
+            response.extend_from_slice(SEARCH_DONE);
+            assert!(h.feed_segments(Direction::ToServer, &request, segment_size));
+            assert!(h.feed_segments(Direction::ToClient, &response, segment_size));
+            assert_eq!(h.pending(Direction::ToServer), 0);
+            assert_eq!(h.pending(Direction::ToClient), 0);
+
+            assert_eq!(h.tx_count(), 2);
+            let tx = h.get_tx::<LdapTransaction>(0).unwrap();
+            assert!(tx.complete);
+            assert_eq!(tx.responses.len(), 1);
+            let tx = h.get_tx::<LdapTransaction>(1).unwrap();
+            assert!(tx.complete);
+            let search = tx.search_request().unwrap();
+            assert_eq!(search.base_object, "dc=org");
+            assert_eq!(search.filter, "(uid=*)");
+            assert_eq!(tx.responses.len(), 2);
+            assert_eq!(tx.responses[1].tag(), LDAP_SEARCH_RESULT_DONE);
+        }
+    }
+
+    #[test]
+    fn test_ldap_pending() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, SEARCH_REQUEST));
+        assert!(h.feed(Direction::ToClient, SEARCH_ENTRY));
+        assert!(!h.get_tx::<LdapTransaction>(0).unwrap().complete);
+        // unsolicited response
+        assert!(h.feed(Direction::ToClient, BIND_RESPONSE));
+        assert_eq!(h.tx_count(), 2);
+        assert!(h.feed(Direction::ToClient, SEARCH_DONE));
+        assert!(h.get_tx::<LdapTransaction>(0).unwrap().complete);
+    }
+
+    #[test]
+    fn test_ldap_probe() {
+        unsafe {
+            ALPROTO_LDAP = 42;
+        }
+        assert_eq!(ldap_probe(BIND_REQUEST, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(ldap_probe(BIND_RESPONSE, Direction::ToServer), ProbeResult::Flipped(42));
+        assert_eq!(ldap_probe(&BIND_REQUEST[..6], Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(ldap_probe(b"GET / HTTP/1.1\r\n", Direction::ToServer), ProbeResult::Failed);
+    }
+
+    #[test]
+    fn test_ldap_too_large() {
+        let mut state = LdapState::new();
+        let r = state.parse(&[0x30, 0x84, 0x7f, 0xff, 0xff, 0xff, 0x02], Direction::ToServer);
+        assert!(r.is_err());
+    }
+}
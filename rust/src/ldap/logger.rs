@@ -0,0 +1,115 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::ldap::LdapTransaction;
+use super::parser::*;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn scope_name(scope: u32) -> &'static str {
+    match scope {
+        0 => "base",
+        1 => "one",
+        2 => "sub",
+        _ => "unknown",
+    }
+}
+
+fn log_op(op: &ProtocolOp, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.set_string("operation", op_name(op.tag()))?;
+    match op {
+        ProtocolOp::BindRequest(bind) => {
+            js.set_uint("version", bind.version as u64)?;
+            js.set_string("name", &bind.name)?;
+            match bind.auth {
+                BindAuth::Simple { password_len } => {
+                    js.set_string("authentication", "simple")?;
+                    js.set_bool("password", password_len > 0)?;
+                }
+                BindAuth::Sasl { ref mechanism } => {
+                    js.set_string("authentication", "sasl")?;
+                    js.set_string("mechanism", mechanism)?;
+                }
+                BindAuth::Other(_) => {
+                    js.set_string("authentication", "unknown")?;
+                }
+            }
+        }
+        ProtocolOp::SearchRequest(search) => {
+            js.set_string("base_object", &search.base_object)?;
+            js.set_string("scope", scope_name(search.scope))?;
+            js.set_uint("size_limit", search.size_limit as u64)?;
+            js.set_uint("time_limit", search.time_limit as u64)?;
+            js.set_string("filter", &search.filter)?;
+            if !search.attributes.is_empty() {
+                js.open_array("attributes")?;
+                for attribute in &search.attributes {
+                    js.append_string(attribute)?;
+                }
+                js.close()?;
+            }
+        }
+        ProtocolOp::Request { dn, .. } => {
+            js.set_string("dn", dn)?;
+        }
+        ProtocolOp::ExtendedRequest { name } => {
+            js.set_string("name", name)?;
+        }
+        ProtocolOp::Response { result, .. } => {
+            js.set_string("result_code", result_code_name(result.result_code))?;
+            if !result.matched_dn.is_empty() {
+                js.set_string("matched_dn", &result.matched_dn)?;
+            }
+            if !result.diagnostic_message.is_empty() {
+                js.set_string("message", &result.diagnostic_message)?;
+            }
+        }
+        ProtocolOp::SearchResultEntry { object_name } => {
+            js.set_string("object_name", object_name)?;
+        }
+        ProtocolOp::Other { .. } => {}
+    }
+    Ok(())
+}
+
+fn log(tx: &LdapTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("ldap")?;
+    js.set_uint("message_id", tx.message_id as u64)?;
+    if let Some(ref request) = tx.request {
+        js.open_object("request")?;
+        log_op(request, js)?;
+        js.close()?;
+    }
+    if !tx.responses.is_empty() {
+        js.open_array("responses")?;
+        for response in &tx.responses {
+            js.start_object()?;
+            log_op(response, js)?;
+            js.close()?;
+        }
+        js.close()?;
+    }
+    if tx.responses_dropped > 0 {
+        js.set_uint("responses_dropped", tx.responses_dropped as u64)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_ldap_to_json(tx: &mut LdapTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
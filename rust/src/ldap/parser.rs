@@ -0,0 +1,659 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! LDAPv3 messages (RFC 4511), encoded with the BER subset of section
+//! 5.1: definite lengths and low tag numbers only.
+
+use crate::asn1::ber::{self, BerError};
+use nom::bytes::streaming::take;
+use nom::error::ErrorKind;
+use nom::{Err, IResult, Needed};
+
+const BER_CLASS_UNIVERSAL: u8 = 0;
+const BER_CLASS_APPLICATION: u8 = 1;
+const BER_CLASS_CONTEXT: u8 = 2;
+
+const BER_TAG_BOOLEAN: u32 = 1;
+const BER_TAG_INTEGER: u32 = 2;
+const BER_TAG_OCTET_STRING: u32 = 4;
+const BER_TAG_ENUMERATED: u32 = 10;
+const BER_TAG_SEQUENCE: u32 = 16;
+
+/// Maximum number of nested filters.
+const MAX_FILTER_DEPTH: usize = 32;
+
+// Protocol operations, by their APPLICATION tag.
+pub const LDAP_BIND_REQUEST: u32 = 0;
+pub const LDAP_BIND_RESPONSE: u32 = 1;
+pub const LDAP_UNBIND_REQUEST: u32 = 2;
+pub const LDAP_SEARCH_REQUEST: u32 = 3;
+pub const LDAP_SEARCH_RESULT_ENTRY: u32 = 4;
+pub const LDAP_SEARCH_RESULT_DONE: u32 = 5;
+pub const LDAP_MODIFY_REQUEST: u32 = 6;
+pub const LDAP_MODIFY_RESPONSE: u32 = 7;
+pub const LDAP_ADD_REQUEST: u32 = 8;
+pub const LDAP_ADD_RESPONSE: u32 = 9;
+pub const LDAP_DEL_REQUEST: u32 = 10;
+pub const LDAP_DEL_RESPONSE: u32 = 11;
+pub const LDAP_MODDN_REQUEST: u32 = 12;
+pub const LDAP_MODDN_RESPONSE: u32 = 13;
+pub const LDAP_COMPARE_REQUEST: u32 = 14;
+pub const LDAP_COMPARE_RESPONSE: u32 = 15;
+pub const LDAP_ABANDON_REQUEST: u32 = 16;
+pub const LDAP_SEARCH_RESULT_REFERENCE: u32 = 19;
+pub const LDAP_EXTENDED_REQUEST: u32 = 23;
+pub const LDAP_EXTENDED_RESPONSE: u32 = 24;
+pub const LDAP_INTERMEDIATE_RESPONSE: u32 = 25;
+
+/// Name of the StartTLS extended operation (RFC 4511 section 4.14).
+pub const LDAP_STARTTLS_OID: &str = "1.3.6.1.4.1.1466.20037";
+
+pub fn op_name(tag: u32) -> &'static str {
+    match tag {
+        LDAP_BIND_REQUEST => "bind_request",
+        LDAP_BIND_RESPONSE => "bind_response",
+        LDAP_UNBIND_REQUEST => "unbind_request",
+        LDAP_SEARCH_REQUEST => "search_request",
+        LDAP_SEARCH_RESULT_ENTRY => "search_result_entry",
+        LDAP_SEARCH_RESULT_DONE => "search_result_done",
+        LDAP_MODIFY_REQUEST => "modify_request",
+        LDAP_MODIFY_RESPONSE => "modify_response",
+        LDAP_ADD_REQUEST => "add_request",
+        LDAP_ADD_RESPONSE => "add_response",
+        LDAP_DEL_REQUEST => "del_request",
+        LDAP_DEL_RESPONSE => "del_response",
+        LDAP_MODDN_REQUEST => "moddn_request",
+        LDAP_MODDN_RESPONSE => "moddn_response",
+        LDAP_COMPARE_REQUEST => "compare_request",
+        LDAP_COMPARE_RESPONSE => "compare_response",
+        LDAP_ABANDON_REQUEST => "abandon_request",
+        LDAP_SEARCH_RESULT_REFERENCE => "search_result_reference",
+        LDAP_EXTENDED_REQUEST => "extended_request",
+        LDAP_EXTENDED_RESPONSE => "extended_response",
+        LDAP_INTERMEDIATE_RESPONSE => "intermediate_response",
+        _ => "unknown",
+    }
+}
+
+/// Check if an operation is sent by the server.
+pub fn is_response(tag: u32) -> bool {
+    match tag {
+        LDAP_BIND_RESPONSE | LDAP_SEARCH_RESULT_ENTRY | LDAP_SEARCH_RESULT_DONE
+        | LDAP_MODIFY_RESPONSE | LDAP_ADD_RESPONSE | LDAP_DEL_RESPONSE
+        | LDAP_MODDN_RESPONSE | LDAP_COMPARE_RESPONSE | LDAP_SEARCH_RESULT_REFERENCE
+        | LDAP_EXTENDED_RESPONSE | LDAP_INTERMEDIATE_RESPONSE => true,
+        _ => false,
+    }
+}
+
+/// Name of a result code, from RFC 4511 appendix A.
+pub fn result_code_name(code: u32) -> &'static str {
+    match code {
+        0 => "success",
+        1 => "operations_error",
+        2 => "protocol_error",
+        3 => "time_limit_exceeded",
+        4 => "size_limit_exceeded",
+        5 => "compare_false",
+        6 => "compare_true",
+        7 => "auth_method_not_supported",
+        8 => "stronger_auth_required",
+        10 => "referral",
+        11 => "admin_limit_exceeded",
+        12 => "unavailable_critical_extension",
+        13 => "confidentiality_required",
+        14 => "sasl_bind_in_progress",
+        16 => "no_such_attribute",
+        17 => "undefined_attribute_type",
+        18 => "inappropriate_matching",
+        19 => "constraint_violation",
+        20 => "attribute_or_value_exists",
+        21 => "invalid_attribute_syntax",
+        32 => "no_such_object",
+        33 => "alias_problem",
+        34 => "invalid_dn_syntax",
+        36 => "alias_dereferencing_problem",
+        48 => "inappropriate_authentication",
+        49 => "invalid_credentials",
+        50 => "insufficient_access_rights",
+        51 => "busy",
+        52 => "unavailable",
+        53 => "unwilling_to_perform",
+        54 => "loop_detect",
+        64 => "naming_violation",
+        65 => "object_class_violation",
+        66 => "not_allowed_on_non_leaf",
+        67 => "not_allowed_on_rdn",
+        68 => "entry_already_exists",
+        69 => "object_class_mods_prohibited",
+        71 => "affects_multiple_dsas",
+        80 => "other",
+        _ => "unknown",
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BerHeader {
+    pub class: u8,
+    pub constructed: bool,
+    pub tag: u32,
+    pub len: usize,
+}
+
+/// Parse the identifier and length octets of an element.
+pub fn ber_header(i: &[u8]) -> IResult<&[u8], BerHeader> {
+    // LDAP only uses low tag numbers and the definite form of the length
+    if let Some(id) = i.first() {
+        if id & 0x1f == 0x1f {
+            return Err(Err::Error((i, ErrorKind::Tag)));
+        }
+    }
+    let (rem, header) = match ber::ber_header(i) {
+        Ok(r) => r,
+        Err(BerError::Incomplete) => return Err(Err::Incomplete(Needed::Unknown)),
+        Err(_) => return Err(Err::Error((i, ErrorKind::LengthValue))),
+    };
+    let len = match header.len {
+        Some(len) if len <= 0xffff_ffff => len as usize,
+        _ => return Err(Err::Error((i, ErrorKind::LengthValue))),
+    };
+    let header = BerHeader {
+        class: header.class,
+        constructed: header.constructed,
+        tag: header.tag,
+        len,
+    };
+    Ok((rem, header))
+}
+
+fn ber_element(i: &[u8]) -> IResult<&[u8], (BerHeader, &[u8])> {
+    let (rem, header) = ber_header(i)?;
+    let (rem, content) = take(header.len)(rem)?;
+    Ok((rem, (header, content)))
+}
+
+/// Parse an element of the given class and tag, returning its content.
+fn ber_tagged(class: u8, tag: u32) -> impl Fn(&[u8]) -> IResult<&[u8], &[u8]> {
+    move |i: &[u8]| {
+        let (rem, (header, content)) = ber_element(i)?;
+        if header.class != class || header.tag != tag {
+            return Err(Err::Error((i, ErrorKind::Tag)));
+        }
+        Ok((rem, content))
+    }
+}
+
+fn ber_integer(tag: u32) -> impl Fn(&[u8]) -> IResult<&[u8], i64> {
+    move |i: &[u8]| {
+        let (rem, content) = ber_tagged(BER_CLASS_UNIVERSAL, tag)(i)?;
+        if content.is_empty() || content.len() > 8 {
+            return Err(Err::Error((i, ErrorKind::LengthValue)));
+        }
+        let sign = if content[0] & 0x80 != 0 { -1 } else { 0 };
+        let value = content.iter().fold(sign, |value, b| (value << 8) | *b as i64);
+        Ok((rem, value))
+    }
+}
+
+fn ber_octet_string(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    ber_tagged(BER_CLASS_UNIVERSAL, BER_TAG_OCTET_STRING)(i)
+}
+
+/// Parse an LDAPString or LDAPDN. Invalid UTF-8 sequences are replaced.
+fn ber_string(i: &[u8]) -> IResult<&[u8], String> {
+    let (rem, data) = ber_octet_string(i)?;
+    Ok((rem, String::from_utf8_lossy(data).to_string()))
+}
+
+fn ber_boolean(i: &[u8]) -> IResult<&[u8], bool> {
+    let (rem, content) = ber_tagged(BER_CLASS_UNIVERSAL, BER_TAG_BOOLEAN)(i)?;
+    Ok((rem, content.iter().any(|b| *b != 0)))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BindAuth {
+    /// Simple authentication. The password is not kept.
+    Simple { password_len: usize },
+    Sasl { mechanism: String },
+    Other(u32),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BindRequest {
+    pub version: u8,
+    pub name: String,
+    pub auth: BindAuth,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SearchRequest {
+    pub base_object: String,
+    pub scope: u32,
+    pub deref_aliases: u32,
+    pub size_limit: u32,
+    pub time_limit: u32,
+    pub types_only: bool,
+    /// The filter in its string representation (RFC 4515).
+    pub filter: String,
+    pub attributes: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LdapResult {
+    pub result_code: u32,
+    pub matched_dn: String,
+    pub diagnostic_message: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProtocolOp {
+    BindRequest(BindRequest),
+    SearchRequest(SearchRequest),
+    /// Other request applying to an entry, with its DN.
+    Request { tag: u32, dn: String },
+    ExtendedRequest { name: String },
+    /// Response ending an operation.
+    Response { tag: u32, result: LdapResult },
+    SearchResultEntry { object_name: String },
+    /// Operation of which nothing is kept: unbind, abandon, search
+    /// result references and intermediate responses.
+    Other { tag: u32 },
+}
+
+impl ProtocolOp {
+    pub fn tag(&self) -> u32 {
+        match self {
+            ProtocolOp::BindRequest(_) => LDAP_BIND_REQUEST,
+            ProtocolOp::SearchRequest(_) => LDAP_SEARCH_REQUEST,
+            ProtocolOp::Request { tag, .. } => *tag,
+            ProtocolOp::ExtendedRequest { .. } => LDAP_EXTENDED_REQUEST,
+            ProtocolOp::Response { tag, .. } => *tag,
+            ProtocolOp::SearchResultEntry { .. } => LDAP_SEARCH_RESULT_ENTRY,
+            ProtocolOp::Other { tag } => *tag,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LdapMessage {
+    pub message_id: u32,
+    pub op: ProtocolOp,
+}
+
+/// Escape a filter value as in RFC 4515 section 3. Non printable bytes
+/// are escaped as well.
+fn escape_filter_value(value: &[u8]) -> String {
+    let mut s = String::with_capacity(value.len());
+    for b in value {
+        match b {
+            b'*' | b'(' | b')' | b'\\' => s.push_str(&format!("\\{:02x}", b)),
+            0x20..=0x7e => s.push(*b as char),
+            _ => s.push_str(&format!("\\{:02x}", b)),
+        }
+    }
+    s
+}
+
+fn parse_substrings(i: &[u8]) -> IResult<&[u8], String> {
+    let (i, attr) = ber_string(i)?;
+    let (rem, mut substrings) = ber_tagged(BER_CLASS_UNIVERSAL, BER_TAG_SEQUENCE)(i)?;
+    let (mut initial, mut any, mut last) = (&[][..], Vec::new(), &[][..]);
+    while !substrings.is_empty() {
+        let (r, (header, value)) = ber_element(substrings)?;
+        match header.tag {
+            0 => initial = value,
+            1 => any.push(value),
+            2 => last = value,
+            _ => return Err(Err::Error((substrings, ErrorKind::Tag))),
+        }
+        substrings = r;
+    }
+    let mut s = format!("{}={}*", attr, escape_filter_value(initial));
+    for value in any {
+        s.push_str(&escape_filter_value(value));
+        s.push('*');
+    }
+    s.push_str(&escape_filter_value(last));
+    Ok((rem, s))
+}
+
+fn parse_extensible_match(mut i: &[u8]) -> IResult<&[u8], String> {
+    let (mut rule, mut attr, mut value, mut dn) = (None, None, &[][..], false);
+    while !i.is_empty() {
+        let (r, (header, content)) = ber_element(i)?;
+        match header.tag {
+            1 => rule = Some(String::from_utf8_lossy(content)),
+            2 => attr = Some(String::from_utf8_lossy(content)),
+            3 => value = content,
+            4 => dn = content.iter().any(|b| *b != 0),
+            _ => return Err(Err::Error((i, ErrorKind::Tag))),
+        }
+        i = r;
+    }
+    let mut s = String::new();
+    if let Some(attr) = attr {
+        s.push_str(&attr);
+    }
+    if dn {
+        s.push_str(":dn");
+    }
+    if let Some(rule) = rule {
+        s.push(':');
+        s.push_str(&rule);
+    }
+    s.push_str(":=");
+    s.push_str(&escape_filter_value(value));
+    Ok((i, s))
+}
+
+/// Parse a Filter (RFC 4511 section 4.5.1.7) into its string
+/// representation.
+fn parse_filter(i: &[u8], depth: usize) -> IResult<&[u8], String> {
+    if depth >= MAX_FILTER_DEPTH {
+        return Err(Err::Error((i, ErrorKind::TooLarge)));
+    }
+    let (rem, (header, content)) = ber_element(i)?;
+    if header.class != BER_CLASS_CONTEXT {
+        return Err(Err::Error((i, ErrorKind::Tag)));
+    }
+    let filter = match header.tag {
+        // and, or
+        0 | 1 => {
+            let mut s = String::from(if header.tag == 0 { "(&" } else { "(|" });
+            let mut filters = content;
+            while !filters.is_empty() {
+                let (r, filter) = parse_filter(filters, depth + 1)?;
+                s.push_str(&filter);
+                filters = r;
+            }
+            s.push(')');
+            s
+        }
+        // not
+        2 => {
+            let (_, filter) = parse_filter(content, depth + 1)?;
+            format!("(!{})", filter)
+        }
+        // equalityMatch, greaterOrEqual, lessOrEqual, approxMatch
+        3 | 5 | 6 | 8 => {
+            let (r, attr) = ber_string(content)?;
+            let (_, value) = ber_octet_string(r)?;
+            let op = match header.tag {
+                3 => "=",
+                5 => ">=",
+                6 => "<=",
+                _ => "~=",
+            };
+            format!("({}{}{})", attr, op, escape_filter_value(value))
+        }
+        4 => {
+            let (_, s) = parse_substrings(content)?;
+            format!("({})", s)
+        }
+        // present
+        7 => format!("({}=*)", String::from_utf8_lossy(content)),
+        9 => {
+            let (_, s) = parse_extensible_match(content)?;
+            format!("({})", s)
+        }
+        _ => return Err(Err::Error((i, ErrorKind::Tag))),
+    };
+    Ok((rem, filter))
+}
+
+fn parse_bind_request(i: &[u8]) -> IResult<&[u8], ProtocolOp> {
+    let (i, version) = ber_integer(BER_TAG_INTEGER)(i)?;
+    let (i, name) = ber_string(i)?;
+    let (i, (header, content)) = ber_element(i)?;
+    let auth = match (header.class, header.tag) {
+        (BER_CLASS_CONTEXT, 0) => BindAuth::Simple { password_len: content.len() },
+        (BER_CLASS_CONTEXT, 3) => {
+            let (_, mechanism) = ber_string(content)?;
+            BindAuth::Sasl { mechanism }
+        }
+        (_, tag) => BindAuth::Other(tag),
+    };
+    let request = BindRequest {
+        version: version as u8,
+        name,
+        auth,
+    };
+    Ok((i, ProtocolOp::BindRequest(request)))
+}
+
+fn parse_search_request(i: &[u8]) -> IResult<&[u8], ProtocolOp> {
+    let (i, base_object) = ber_string(i)?;
+    let (i, scope) = ber_integer(BER_TAG_ENUMERATED)(i)?;
+    let (i, deref_aliases) = ber_integer(BER_TAG_ENUMERATED)(i)?;
+    let (i, size_limit) = ber_integer(BER_TAG_INTEGER)(i)?;
+    let (i, time_limit) = ber_integer(BER_TAG_INTEGER)(i)?;
+    let (i, types_only) = ber_boolean(i)?;
+    let (i, filter) = parse_filter(i, 0)?;
+    let (i, mut list) = ber_tagged(BER_CLASS_UNIVERSAL, BER_TAG_SEQUENCE)(i)?;
+    let mut attributes = Vec::new();
+    while !list.is_empty() {
+        let (r, attribute) = ber_string(list)?;
+        attributes.push(attribute);
+        list = r;
+    }
+    let request = SearchRequest {
+        base_object,
+        scope: scope as u32,
+        deref_aliases: deref_aliases as u32,
+        size_limit: size_limit as u32,
+        time_limit: time_limit as u32,
+        types_only,
+        filter,
+        attributes,
+    };
+    Ok((i, ProtocolOp::SearchRequest(request)))
+}
+
+fn parse_ldap_result(i: &[u8]) -> IResult<&[u8], LdapResult> {
+    let (i, result_code) = ber_integer(BER_TAG_ENUMERATED)(i)?;
+    let (i, matched_dn) = ber_string(i)?;
+    let (i, diagnostic_message) = ber_string(i)?;
+    let result = LdapResult {
+        result_code: result_code as u32,
+        matched_dn,
+        diagnostic_message,
+    };
+    Ok((i, result))
+}
+
+/// Parse the content of a protocol operation. Trailing optional fields
+/// are not parsed.
+fn parse_protocol_op(tag: u32, i: &[u8]) -> IResult<&[u8], ProtocolOp> {
+    match tag {
+        LDAP_BIND_REQUEST => parse_bind_request(i),
+        LDAP_SEARCH_REQUEST => parse_search_request(i),
+        LDAP_MODIFY_REQUEST | LDAP_ADD_REQUEST | LDAP_MODDN_REQUEST | LDAP_COMPARE_REQUEST => {
+            let (i, dn) = ber_string(i)?;
+            Ok((i, ProtocolOp::Request { tag, dn }))
+        }
+        // the DN is the content of the primitive element
+        LDAP_DEL_REQUEST => {
+            let dn = String::from_utf8_lossy(i).to_string();
+            Ok((&i[i.len()..], ProtocolOp::Request { tag, dn }))
+        }
+        LDAP_EXTENDED_REQUEST => {
+            let (i, name) = ber_tagged(BER_CLASS_CONTEXT, 0)(i)?;
+            let name = String::from_utf8_lossy(name).to_string();
+            Ok((i, ProtocolOp::ExtendedRequest { name }))
+        }
+        LDAP_BIND_RESPONSE | LDAP_SEARCH_RESULT_DONE | LDAP_MODIFY_RESPONSE
+        | LDAP_ADD_RESPONSE | LDAP_DEL_RESPONSE | LDAP_MODDN_RESPONSE
+        | LDAP_COMPARE_RESPONSE | LDAP_EXTENDED_RESPONSE => {
+            let (i, result) = parse_ldap_result(i)?;
+            Ok((i, ProtocolOp::Response { tag, result }))
+        }
+        LDAP_SEARCH_RESULT_ENTRY => {
+            let (i, object_name) = ber_string(i)?;
+            Ok((i, ProtocolOp::SearchResultEntry { object_name }))
+        }
+        _ => Ok((i, ProtocolOp::Other { tag })),
+    }
+}
+
+fn parse_message_content(i: &[u8]) -> IResult<&[u8], LdapMessage> {
+    let (i, message_id) = ber_integer(BER_TAG_INTEGER)(i)?;
+    let (i, (header, content)) = ber_element(i)?;
+    if header.class != BER_CLASS_APPLICATION {
+        return Err(Err::Error((i, ErrorKind::Tag)));
+    }
+    let (_, op) = parse_protocol_op(header.tag, content)?;
+    // the controls are not parsed
+    let message = LdapMessage {
+        message_id: message_id as u32,
+        op,
+    };
+    Ok((&i[i.len()..], message))
+}
+
+/// Parse an LDAPMessage. The message is a SEQUENCE, so its length is
+/// known from its header: an incomplete element inside of it is an
+/// error.
+pub fn parse_ldap_message(i: &[u8]) -> IResult<&[u8], LdapMessage> {
+    let (rem, content) = ber_tagged(BER_CLASS_UNIVERSAL, BER_TAG_SEQUENCE)(i)?;
+    match parse_message_content(content) {
+        Ok((_, message)) => Ok((rem, message)),
+        Err(Err::Incomplete(_)) => Err(Err::Error((i, ErrorKind::Eof))),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // bind request for cn=admin,dc=example,dc=com with password "secret"
+    const BIND_REQUEST: &[u8] = &[
+        0x30, 0x2c, 0x02, 0x01, 0x01, 0x60, 0x27, 0x02, 0x01, 0x03, 0x04, 0x1a,
+        b'c', b'n', b'=', b'a', b'd', b'm', b'i', b'n', b',', b'd', b'c', b'=',
+        b'e', b'x', b'a', b'm', b'p', b'l', b'e', b',', b'd', b'c', b'=', b'c',
+        b'o', b'm', 0x80, 0x06, b's', b'e', b'c', b'r', b'e', b't',
+    ];
+
+    // search request on dc=example,dc=com with the filter
+    // (&(objectClass=person)(cn=j*))
+    const SEARCH_REQUEST: &[u8] = &[
+        0x30, 0x51, 0x02, 0x01, 0x02, 0x63, 0x4c,
+        0x04, 0x11, b'd', b'c', b'=', b'e', b'x', b'a', b'm', b'p', b'l', b'e', b',',
+        b'd', b'c', b'=', b'c', b'o', b'm',
+        0x0a, 0x01, 0x02, 0x0a, 0x01, 0x00, 0x02, 0x01, 0x00, 0x02, 0x01, 0x00,
+        0x01, 0x01, 0x00,
+        0xa0, 0x22,
+        0xa3, 0x15, 0x04, 0x0b, b'o', b'b', b'j', b'e', b'c', b't', b'C', b'l', b'a',
+        b's', b's', 0x04, 0x06, b'p', b'e', b'r', b's', b'o', b'n',
+        0xa4, 0x09, 0x04, 0x02, b'c', b'n', 0x30, 0x03, 0x80, 0x01, b'j',
+        0x30, 0x04, 0x04, 0x02, b'c', b'n',
+    ];
+
+    #[test]
+    fn test_ber_header() {
+        assert_eq!(ber_header(&[0x30, 0x05]).unwrap().1,
+                   BerHeader { class: 0, constructed: true, tag: 16, len: 5 });
+        assert_eq!(ber_header(&[0x63, 0x82, 0x01, 0x00]).unwrap().1,
+                   BerHeader { class: 1, constructed: true, tag: 3, len: 256 });
+        assert!(ber_header(&[0x30, 0x80]).is_err());
+        assert!(ber_header(&[0x1f, 0x01]).is_err());
+        match ber_header(&[0x30, 0x82, 0x01]) {
+            Err(Err::Incomplete(_)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_ber_integer() {
+        assert_eq!(ber_integer(BER_TAG_INTEGER)(&[0x02, 0x01, 0x7f]).unwrap().1, 127);
+        assert_eq!(ber_integer(BER_TAG_INTEGER)(&[0x02, 0x02, 0x00, 0x80]).unwrap().1, 128);
+        assert_eq!(ber_integer(BER_TAG_INTEGER)(&[0x02, 0x01, 0xff]).unwrap().1, -1);
+        assert!(ber_integer(BER_TAG_INTEGER)(&[0x02, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_parse_bind_request() {
+        let (rem, msg) = parse_ldap_message(BIND_REQUEST).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(msg.message_id, 1);
+        assert_eq!(msg.op, ProtocolOp::BindRequest(BindRequest {
+            version: 3,
+            name: "cn=admin,dc=example,dc=com".to_string(),
+            auth: BindAuth::Simple { password_len: 6 },
+        }));
+        match parse_ldap_message(&BIND_REQUEST[..20]) {
+            Err(Err::Incomplete(_)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_parse_search_request() {
+        let (rem, msg) = parse_ldap_message(SEARCH_REQUEST).unwrap();
+        assert!(rem.is_empty());
+        match msg.op {
+            ProtocolOp::SearchRequest(search) => {
+                assert_eq!(search.base_object, "dc=example,dc=com");
+                assert_eq!(search.scope, 2);
+                assert_eq!(search.filter, "(&(objectClass=person)(cn=j*))");
+                assert_eq!(search.attributes, vec!["cn".to_string()]);
+            }
+            op => panic!("unexpected {:?}", op),
+        }
+    }
+
+    #[test]
+    fn test_parse_response() {
+        // bind response: success
+        let buf = [0x30, 0x0c, 0x02, 0x01, 0x01, 0x61, 0x07, 0x0a, 0x01, 0x00,
+                   0x04, 0x00, 0x04, 0x00];
+        let (_, msg) = parse_ldap_message(&buf).unwrap();
+        assert_eq!(msg.op, ProtocolOp::Response {
+            tag: LDAP_BIND_RESPONSE,
+            result: LdapResult {
+                result_code: 0,
+                matched_dn: String::new(),
+                diagnostic_message: String::new(),
+            },
+        });
+        assert!(is_response(msg.op.tag()));
+        // truncated inside of a complete message
+        let buf = [0x30, 0x07, 0x02, 0x01, 0x01, 0x61, 0x07, 0x0a, 0x01];
+        assert!(parse_ldap_message(&buf).is_err());
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        // (!(uid=a\2a))
+        let buf = [0xa2, 0x0a, 0xa3, 0x08, 0x04, 0x03, b'u', b'i', b'd', 0x04, 0x01, b'*'];
+        assert_eq!(parse_filter(&buf, 0).unwrap().1, "(!(uid=\\2a))");
+        // (mail=*)
+        let buf = [0x87, 0x04, b'm', b'a', b'i', b'l'];
+        assert_eq!(parse_filter(&buf, 0).unwrap().1, "(mail=*)");
+        // (cn=*x*y)
+        let buf = [0xa4, 0x0c, 0x04, 0x02, b'c', b'n', 0x30, 0x06, 0x81, 0x01, b'x',
+                   0x82, 0x01, b'y'];
+        assert_eq!(parse_filter(&buf, 0).unwrap().1, "(cn=*x*y)");
+        // nested beyond the limit
+        let mut buf = Vec::new();
+        for n in 0..MAX_FILTER_DEPTH {
+            buf.extend_from_slice(&[0xa2, (2 * (MAX_FILTER_DEPTH - n)) as u8]);
+        }
+        buf.extend_from_slice(&[0x87, 0x00]);
+        assert!(parse_filter(&buf, 0).is_err());
+    }
+}
@@ -100,8 +100,23 @@ pub mod conf;
 pub mod jsonbuilder;
 #[macro_use]
 pub mod applayer;
+pub mod txtable;
+pub mod parsers_util;
 pub mod filecontainer;
 pub mod filetracker;
+pub mod flowstorage;
+pub mod expectation;
+pub mod recorddefrag;
+pub mod memcap;
+#[macro_use]
+pub mod stats;
+pub mod introspect;
+pub mod eventmap;
+pub mod detect;
+#[cfg(any(test, feature = "fuzz"))]
+pub mod testing;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 pub mod kerberos;
 
 #[cfg(feature = "lua")]
@@ -128,8 +143,26 @@ pub mod applayertemplate;
 pub mod rdp;
 pub mod x509;
 pub mod asn1;
+pub mod mime;
 pub mod ssh;
 pub mod http2;
+pub mod quic;
+pub mod ldap;
+pub mod telnet;
+pub mod llmnr;
+pub mod nbns;
+pub mod stratum;
+pub mod kafka;
+pub mod irc;
+pub mod xmpp;
+pub mod openvpn;
+pub mod dtls;
+pub mod rtp;
+pub mod proxy;
+pub mod icap;
+pub mod bittorrent;
+pub mod smtp;
 pub mod plugin;
 pub mod util;
 pub mod ffi;
+pub mod fuzzyhash;
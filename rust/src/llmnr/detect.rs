@@ -0,0 +1,73 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Sticky buffer on the name queried.
+
+use super::llmnr::{LLMNRTransaction, ALPROTO_LLMNR};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_LLMNR_QUERY_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn llmnr_tx_get_query(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut LLMNRTransaction = cast_mut(tx);
+    match tx.query_name() {
+        Some(name) => {
+            *buf = name.as_ptr();
+            *len = name.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn llmnr_query_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, llmnr_tx_get_query)
+}
+
+unsafe extern "C" fn llmnr_query_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_LLMNR, G_LLMNR_QUERY_BUFFER_ID)
+}
+
+/// Register the LLMNR keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "llmnr.query",
+        desc: "sticky buffer to match on the name queried in LLMNR requests and responses",
+        url: "/rules/llmnr-keywords.html#llmnr-query",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: llmnr_query_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_LLMNR_QUERY_BUFFER_ID = helper_buffer_mpm_register(
+            "llmnr.query", "llmnr query name", ALPROTO_LLMNR, true, true, 0,
+            llmnr_query_get_data);
+    }
+}
@@ -0,0 +1,445 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, ALPROTO_UNKNOWN, IPPROTO_UDP};
+use crate::dns::dns::{DNSHeader, DNSRequest, DNSResponse};
+use crate::dns::parser::{dns_parse_header, dns_parse_request, dns_parse_response};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(llmnr_stats, "app_layer.llmnr", {
+    requests,
+    responses,
+    malformed,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum LLMNREvent {
+    MalformedData,
+    NotRequest,
+    NotResponse,
+    InvalidQuestionCount,
+    InvalidOpcode,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static LLMNR_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static LLMNR_GAUGES: StateGauges = StateGauges::new();
+
+pub static mut ALPROTO_LLMNR: AppProto = ALPROTO_UNKNOWN;
+
+const LLMNR_FLAG_RESPONSE: u16 = 0x8000;
+
+/// The opcode of a message. LLMNR only defines the standard query.
+fn llmnr_opcode(header: &DNSHeader) -> u8 {
+    ((header.flags >> 11) & 0xf) as u8
+}
+
+/// A request or a response.
+#[derive(Debug)]
+pub struct LLMNRTransaction {
+    id: u64,
+    pub request: Option<DNSRequest>,
+    pub response: Option<DNSResponse>,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for LLMNRTransaction {}
+
+impl LLMNRTransaction {
+    fn new(id: u64) -> LLMNRTransaction {
+        LLMNRTransaction {
+            id,
+            request: None,
+            response: None,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: LLMNREvent) {
+        stats_incr!(llmnr_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    /// Check the header against the restrictions LLMNR puts on the DNS
+    /// message format.
+    fn check_header(&mut self, header: &DNSHeader) {
+        if header.questions != 1 {
+            self.set_event(LLMNREvent::InvalidQuestionCount);
+        }
+        if llmnr_opcode(header) != 0 {
+            self.set_event(LLMNREvent::InvalidOpcode);
+        }
+    }
+
+    /// The name queried, from the question section of the request or of
+    /// the response.
+    pub fn query_name(&self) -> Option<&[u8]> {
+        let queries = if let Some(ref request) = self.request {
+            &request.queries
+        } else if let Some(ref response) = self.response {
+            &response.queries
+        } else {
+            return None;
+        };
+        queries.first().map(|query| query.name.as_slice())
+    }
+}
+
+impl Drop for LLMNRTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for LLMNRTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = LLMNREvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+pub struct LLMNRState {
+    transactions: TxTable<LLMNRTransaction>,
+}
+
+unsafe impl FfiType for LLMNRState {}
+
+impl txtable::State for LLMNRState {
+    type Tx = LLMNRTransaction;
+
+    fn tx_table(&self) -> &TxTable<LLMNRTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<LLMNRTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl LLMNRState {
+    pub fn new() -> LLMNRState {
+        LLMNRState {
+            transactions: TxTable::with_limit(&LLMNR_TX_LIMIT).with_gauges(&LLMNR_GAUGES),
+        }
+    }
+
+    fn new_tx(&mut self) -> LLMNRTransaction {
+        LLMNRTransaction::new(self.transactions.next_id())
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    /// Parse a datagram. Returns false if it is not a valid message, in
+    /// which case a transaction is still created to hold the event.
+    fn parse(&mut self, input: &[u8], direction: Direction) -> bool {
+        let mut tx = self.new_tx();
+        let valid = if direction == Direction::ToServer {
+            match dns_parse_request(input) {
+                Ok((_, request)) => {
+                    stats_incr!(llmnr_stats::requests);
+                    tx.check_header(&request.header);
+                    if request.header.flags & LLMNR_FLAG_RESPONSE != 0 {
+                        tx.set_event(LLMNREvent::NotRequest);
+                    }
+                    tx.request = Some(request);
+                    true
+                }
+                Err(_) => {
+                    SCLogDebug!("Error while parsing LLMNR request");
+                    stats_incr!(llmnr_stats::malformed);
+                    tx.set_event(LLMNREvent::MalformedData);
+                    false
+                }
+            }
+        } else {
+            match dns_parse_response(input) {
+                Ok((_, response)) => {
+                    stats_incr!(llmnr_stats::responses);
+                    tx.check_header(&response.header);
+                    if response.header.flags & LLMNR_FLAG_RESPONSE == 0 {
+                        tx.set_event(LLMNREvent::NotResponse);
+                    }
+                    tx.response = Some(response);
+                    true
+                }
+                Err(_) => {
+                    SCLogDebug!("Error while parsing LLMNR response");
+                    stats_incr!(llmnr_stats::malformed);
+                    tx.set_event(LLMNREvent::MalformedData);
+                    false
+                }
+            }
+        };
+        self.transactions.push(tx);
+        valid
+    }
+}
+
+/// Probe for a header with a single question and the standard query
+/// opcode, the only one LLMNR defines.
+fn llmnr_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    match dns_parse_header(input) {
+        Ok((_, header)) => {
+            if header.questions != 1 || llmnr_opcode(&header) != 0 {
+                return ProbeResult::Failed;
+            }
+            let actual = if header.flags & LLMNR_FLAG_RESPONSE == 0 {
+                Direction::ToServer
+            } else {
+                Direction::ToClient
+            };
+            ProbeResult::with_direction(unsafe { ALPROTO_LLMNR }, direction, actual)
+        }
+        Err(nom::Err::Incomplete(_)) => ProbeResult::Unknown,
+        Err(_) => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_llmnr_probing_parser, llmnr_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_llmnr_state_new(_orig_state: *mut std::os::raw::c_void,
+                                     _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = LLMNRState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_llmnr_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut LLMNRState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_llmnr_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut LLMNRState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_llmnr_parse_request(_flow: *const core::Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                _pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    let state: &mut LLMNRState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    if state.parse(buf, Direction::ToServer) {
+        AppLayerResult::ok()
+    } else {
+        AppLayerResult::err()
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_llmnr_parse_response(_flow: *const core::Flow,
+                                                 state: *mut std::os::raw::c_void,
+                                                 _pstate: *mut std::os::raw::c_void,
+                                                 input: *const u8,
+                                                 input_len: u32,
+                                                 _data: *const std::os::raw::c_void,
+                                                 _flags: u8) -> AppLayerResult {
+    let state: &mut LLMNRState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    if state.parse(buf, Direction::ToClient) {
+        AppLayerResult::ok()
+    } else {
+        AppLayerResult::err()
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_llmnr_state_get_tx(state: *mut std::os::raw::c_void,
+                                               tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut LLMNRState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_llmnr_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut LLMNRState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+#[no_mangle]
+pub extern "C" fn rs_llmnr_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void,
+                                                   _direction: u8) -> std::os::raw::c_int {
+    1
+}
+
+export_tx_detect_state!(rs_llmnr_state_get_tx_detect_state, rs_llmnr_state_set_tx_detect_state,
+                        LLMNRTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_llmnr_state_get_events(tx: *mut std::os::raw::c_void)
+                                                   -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut LLMNRTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_llmnr_get_tx_data, LLMNRTransaction);
+
+fn llmnr_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    LLMNR_GAUGES.log(js)?;
+    js.set_uint("events", llmnr_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+const PARSER_NAME: &[u8] = b"llmnr\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_llmnr_register_parser() {
+    llmnr_stats::register();
+    LLMNR_TX_LIMIT.configure("llmnr", txtable::DEFAULT_MAX_TX);
+    introspect::register("llmnr", llmnr_introspect);
+    let default_port = CString::new("5355").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_UDP,
+        probe_ts: Some(rs_llmnr_probing_parser),
+        probe_tc: Some(rs_llmnr_probing_parser),
+        min_depth: 0,
+        max_depth: std::mem::size_of::<DNSHeader>() as u16,
+        state_new: rs_llmnr_state_new,
+        state_free: rs_llmnr_state_free,
+        tx_free: rs_llmnr_state_tx_free,
+        parse_ts: rs_llmnr_parse_request,
+        parse_tc: rs_llmnr_parse_response,
+        get_tx_count: rs_llmnr_state_get_tx_count,
+        get_tx: rs_llmnr_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_llmnr_tx_get_alstate_progress,
+        get_de_state: rs_llmnr_state_get_tx_detect_state,
+        set_de_state: rs_llmnr_state_set_tx_detect_state,
+        get_events: Some(rs_llmnr_state_get_events),
+        get_eventinfo: Some(LLMNREvent::get_event_info),
+        get_eventinfo_byid: Some(LLMNREvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<LLMNRState>),
+        get_tx_data: rs_llmnr_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<LLMNRState>),
+        flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("udp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_LLMNR = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust LLMNR parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for LLMNR.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A query for "wpad", type A.
+    const QUERY: &[u8] = &[
+        0x12, 0x34, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x04, 0x77, 0x70, 0x61,
+        0x64, 0x00, 0x00, 0x01, 0x00, 0x01,
+    ];
+
+    /// The response to `QUERY`, with the address 10.0.0.5.
+    const RESPONSE: &[u8] = &[
+        0x12, 0x34, 0x80, 0x00, 0x00, 0x01, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x04, 0x77, 0x70, 0x61,
+        0x64, 0x00, 0x00, 0x01, 0x00, 0x01, 0xc0, 0x0c,
+        0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x1e,
+        0x00, 0x04, 0x0a, 0x00, 0x00, 0x05,
+    ];
+
+    #[test]
+    fn test_llmnr_parse_query() {
+        let mut state = LLMNRState::new();
+        assert!(state.parse(QUERY, Direction::ToServer));
+        let tx = state.transactions.get(0).unwrap();
+        assert_eq!(tx.query_name(), Some(&b"wpad"[..]));
+        assert!(tx.response.is_none());
+    }
+
+    #[test]
+    fn test_llmnr_parse_response() {
+        let mut state = LLMNRState::new();
+        assert!(state.parse(RESPONSE, Direction::ToClient));
+        let tx = state.transactions.get(0).unwrap();
+        assert_eq!(tx.query_name(), Some(&b"wpad"[..]));
+        let response = tx.response.as_ref().unwrap();
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.answers[0].name, b"wpad".to_vec());
+    }
+
+    #[test]
+    fn test_llmnr_parse_malformed() {
+        let mut state = LLMNRState::new();
+        assert!(!state.parse(&QUERY[..16], Direction::ToServer));
+        assert_eq!(state.transactions.tx_count(), 1);
+    }
+
+    #[test]
+    fn test_llmnr_probe() {
+        assert_eq!(llmnr_probe(QUERY, Direction::ToServer),
+                   ProbeResult::Found(unsafe { ALPROTO_LLMNR }));
+        assert_eq!(llmnr_probe(RESPONSE, Direction::ToServer),
+                   ProbeResult::Flipped(unsafe { ALPROTO_LLMNR }));
+        assert_eq!(llmnr_probe(&QUERY[..8], Direction::ToServer), ProbeResult::Unknown);
+
+        // two questions
+        let mut query = QUERY.to_vec();
+        query[5] = 2;
+        assert_eq!(llmnr_probe(&query, Direction::ToServer), ProbeResult::Failed);
+    }
+}
@@ -0,0 +1,78 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::llmnr::LLMNRTransaction;
+use crate::dns::dns::{DNSHeader, DNSQueryEntry};
+use crate::dns::log::{dns_log_json_answer_detail, dns_rcode_string, dns_rrtype_string};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log_header(header: &DNSHeader, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.set_uint("id", header.tx_id as u64)?;
+    if header.flags & 0x0400 != 0 {
+        js.set_bool("conflict", true)?;
+    }
+    if header.flags & 0x0200 != 0 {
+        js.set_bool("truncated", true)?;
+    }
+    if header.flags & 0x0100 != 0 {
+        js.set_bool("tentative", true)?;
+    }
+    Ok(())
+}
+
+fn log_queries(queries: &[DNSQueryEntry], js: &mut JsonBuilder) -> Result<(), JsonError> {
+    if queries.is_empty() {
+        return Ok(());
+    }
+    js.open_array("queries")?;
+    for query in queries {
+        js.start_object()?;
+        js.set_string_from_bytes("rrname", &query.name)?;
+        js.set_string("rrtype", &dns_rrtype_string(query.rrtype))?;
+        js.close()?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+fn log(tx: &LLMNRTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("llmnr")?;
+    if let Some(ref request) = tx.request {
+        js.set_string("type", "request")?;
+        log_header(&request.header, js)?;
+        log_queries(&request.queries, js)?;
+    } else if let Some(ref response) = tx.response {
+        js.set_string("type", "response")?;
+        log_header(&response.header, js)?;
+        js.set_string("rcode", &dns_rcode_string(response.header.flags))?;
+        log_queries(&response.queries, js)?;
+        if !response.answers.is_empty() {
+            js.open_array("answers")?;
+            for answer in &response.answers {
+                js.append_object(&dns_log_json_answer_detail(answer)?)?;
+            }
+            js.close()?;
+        }
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_llmnr_to_json(tx: &mut LLMNRTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
@@ -0,0 +1,136 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Memory use accounting for app-layer parsers.
+//!
+//! A parser declares a static `AppLayerMemcap`, configures it at
+//! registration time from `app-layer.protocols.<proto>.memcap`, and
+//! accounts the buffers it keeps across calls against it. When the
+//! memcap would be exceeded the allocation is refused so the parser can
+//! fail gracefully, for example by setting an event or erroring out.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::conf::conf_get_memval;
+
+pub struct AppLayerMemcap {
+    memuse: AtomicU64,
+    memcap: AtomicU64,
+    memcap_reached: AtomicU64,
+}
+
+impl AppLayerMemcap {
+    /// Create a new memcap. A memcap of 0 means unlimited.
+    pub const fn new() -> AppLayerMemcap {
+        AppLayerMemcap {
+            memuse: AtomicU64::new(0),
+            memcap: AtomicU64::new(0),
+            memcap_reached: AtomicU64::new(0),
+        }
+    }
+
+    /// Set the memcap from `app-layer.protocols.<proto>.memcap`, falling
+    /// back to `default` if not set or invalid.
+    pub fn configure(&self, proto: &str, default: u64) {
+        let key = format!("app-layer.protocols.{}.memcap", proto);
+        let memcap = conf_get_memval(&key).unwrap_or(default);
+        self.set_memcap(memcap);
+    }
+
+    pub fn set_memcap(&self, memcap: u64) {
+        self.memcap.store(memcap, Ordering::Relaxed);
+    }
+
+    pub fn get_memcap(&self) -> u64 {
+        self.memcap.load(Ordering::Relaxed)
+    }
+
+    pub fn get_memuse(&self) -> u64 {
+        self.memuse.load(Ordering::Relaxed)
+    }
+
+    /// Number of times an allocation was refused.
+    pub fn get_memcap_reached(&self) -> u64 {
+        self.memcap_reached.load(Ordering::Relaxed)
+    }
+
+    /// Check if `size` more bytes fit in the memcap, without accounting
+    /// them.
+    pub fn check(&self, size: u64) -> bool {
+        let memcap = self.get_memcap();
+        memcap == 0 || self.get_memuse().saturating_add(size) <= memcap
+    }
+
+    /// Account `size` bytes if they fit in the memcap. Returns false,
+    /// and accounts nothing, if the memcap would be exceeded.
+    pub fn try_add(&self, size: u64) -> bool {
+        let memcap = self.get_memcap();
+        let mut cur = self.memuse.load(Ordering::Relaxed);
+        loop {
+            let new = cur.saturating_add(size);
+            if memcap > 0 && new > memcap {
+                self.memcap_reached.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+            match self.memuse.compare_exchange_weak(cur, new,
+                    Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(v) => cur = v,
+            }
+        }
+    }
+
+    /// Release `size` previously accounted bytes.
+    pub fn sub(&self, size: u64) {
+        let mut cur = self.memuse.load(Ordering::Relaxed);
+        loop {
+            let new = cur.saturating_sub(size);
+            match self.memuse.compare_exchange_weak(cur, new,
+                    Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(v) => cur = v,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memcap_accounting() {
+        let m = AppLayerMemcap::new();
+        m.set_memcap(100);
+        assert!(m.try_add(60));
+        assert!(m.check(40));
+        assert!(!m.check(41));
+        assert!(!m.try_add(41));
+        assert_eq!(m.get_memuse(), 60);
+        assert_eq!(m.get_memcap_reached(), 1);
+        m.sub(60);
+        assert_eq!(m.get_memuse(), 0);
+        assert!(m.try_add(100));
+    }
+
+    #[test]
+    fn test_memcap_unlimited() {
+        let m = AppLayerMemcap::new();
+        assert!(m.try_add(std::u64::MAX));
+        m.sub(std::u64::MAX);
+        assert_eq!(m.get_memuse(), 0);
+    }
+}
@@ -0,0 +1,605 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Streaming MIME decoder, fed the lines of a message as they are
+//! received.
+//!
+//! The decoder follows the entities of the message through their headers
+//! and boundaries, decodes the base64 and quoted-printable bodies, and
+//! returns the content of the attachments to be stored as files. The
+//! anomalies are flags with the values of the C decoder.
+
+use super::{base64_value, hex_value, mime_decode_words, mime_find_header_token,
+            RS_MIME_MAX_TOKEN_LEN};
+
+pub const MIME_ANOM_INVALID_BASE64: u32 = 0x1;
+pub const MIME_ANOM_INVALID_QP: u32 = 0x2;
+pub const MIME_ANOM_LONG_HEADER_NAME: u32 = 0x4;
+pub const MIME_ANOM_LONG_HEADER_VALUE: u32 = 0x8;
+pub const MIME_ANOM_LONG_LINE: u32 = 0x10;
+pub const MIME_ANOM_LONG_ENC_LINE: u32 = 0x20;
+pub const MIME_ANOM_MALFORMED_MSG: u32 = 0x40;
+pub const MIME_ANOM_LONG_BOUNDARY: u32 = 0x80;
+pub const MIME_ANOM_LONG_FILENAME: u32 = 0x100;
+pub const MIME_ANOM_DEEP_NESTING: u32 = 0x200;
+pub const MIME_ANOM_TOO_MANY_HEADERS: u32 = 0x400;
+
+/// Maximum length of a line, RFC 5322.
+const MAX_LINE_LEN: usize = 998;
+/// Maximum length of a line of an encoded body, RFC 2045.
+const MAX_ENC_LINE_LEN: usize = 76;
+const MAX_HEADER_NAME_LEN: usize = 75;
+const MAX_HEADER_VALUE_LEN: usize = 2000;
+/// Maximum number of headers kept per entity.
+const MAX_HEADER_COUNT: usize = 1000;
+/// Maximum depth of the multipart and encapsulated entities.
+const MAX_NESTING_DEPTH: usize = 16;
+/// Maximum length of a boundary, without its leading dashes.
+const MAX_BOUNDARY_LEN: usize = 254;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MimeHeader {
+    pub name: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MimeOutput {
+    /// An attachment starts, with its file name.
+    FileStart(Vec<u8>),
+    /// Decoded content of the attachment.
+    FileData(Vec<u8>),
+    FileEnd,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Encoding {
+    Plain,
+    Base64,
+    QuotedPrintable,
+}
+
+#[derive(Debug)]
+struct Entity {
+    headers: Vec<MimeHeader>,
+    in_headers: bool,
+    /// Whether the body is ignored: the preamble and the epilogue of a
+    /// multipart entity.
+    skip_body: bool,
+    encoding: Encoding,
+    attachment: bool,
+    /// The bits of the base64 characters not yet decoded.
+    b64_acc: u32,
+    b64_bits: u32,
+    /// Whether a line break is due before the next line of the body. The
+    /// line break before a boundary belongs to the boundary.
+    pending_crlf: bool,
+}
+
+impl Entity {
+    fn new() -> Entity {
+        Entity {
+            headers: Vec::new(),
+            in_headers: true,
+            skip_body: false,
+            encoding: Encoding::Plain,
+            attachment: false,
+            b64_acc: 0,
+            b64_bits: 0,
+            pending_crlf: false,
+        }
+    }
+
+    /// The preamble or the epilogue of a multipart entity.
+    fn skipped() -> Entity {
+        let mut entity = Entity::new();
+        entity.in_headers = false;
+        entity.skip_body = true;
+        entity
+    }
+
+    fn header(&self, name: &[u8]) -> Option<&[u8]> {
+        self.headers.iter().find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_slice())
+    }
+}
+
+fn trim(value: &[u8]) -> &[u8] {
+    let start = value.iter().position(|c| !c.is_ascii_whitespace()).unwrap_or(value.len());
+    let end = value.iter().rposition(|c| !c.is_ascii_whitespace()).map_or(start, |i| i + 1);
+    &value[start..end]
+}
+
+/// The media type of a Content-Type value, lowercased.
+fn media_type(value: &[u8]) -> Vec<u8> {
+    let end = value.iter().position(|&c| c == b';').unwrap_or(value.len());
+    trim(&value[..end]).to_ascii_lowercase()
+}
+
+/// Whether `line` is the boundary `boundary`, and if so whether it is
+/// the closing one.
+fn match_boundary(line: &[u8], boundary: &[u8]) -> Option<bool> {
+    if !line.starts_with(b"--") || !line[2..].starts_with(boundary) {
+        return None;
+    }
+    let rest = &line[2 + boundary.len()..];
+    let (close, rest) = if rest.starts_with(b"--") {
+        (true, &rest[2..])
+    } else {
+        (false, rest)
+    };
+    if rest.iter().all(|&c| c == b' ' || c == b'\t') {
+        Some(close)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub struct MimeDecoder {
+    entity: Entity,
+    /// The boundaries of the multipart entities the current one is
+    /// nested in, innermost last, None for an encapsulated message.
+    boundaries: Vec<Option<Vec<u8>>>,
+    /// The header being received, which may continue on folded lines.
+    header: Option<MimeHeader>,
+    /// The headers of the message.
+    pub headers: Vec<MimeHeader>,
+    headers_complete: bool,
+    /// The `MIME_ANOM_` flags of the message.
+    pub anomalies: u32,
+}
+
+impl MimeDecoder {
+    pub fn new() -> MimeDecoder {
+        MimeDecoder {
+            entity: Entity::new(),
+            boundaries: Vec::new(),
+            header: None,
+            headers: Vec::new(),
+            headers_complete: false,
+            anomalies: 0,
+        }
+    }
+
+    /// Whether the headers of the message have all been parsed.
+    pub fn headers_complete(&self) -> bool {
+        self.headers_complete
+    }
+
+    /// Parse a line of the message, without its line break.
+    pub fn parse_line(&mut self, line: &[u8]) -> Vec<MimeOutput> {
+        let mut out = Vec::new();
+        if line.len() > MAX_LINE_LEN {
+            self.anomalies |= MIME_ANOM_LONG_LINE;
+        }
+        if line.starts_with(b"--") && self.parse_boundary(line, &mut out) {
+            return out;
+        }
+        if self.entity.in_headers {
+            self.parse_header_line(line, &mut out);
+        } else {
+            self.parse_body_line(line, &mut out);
+        }
+        out
+    }
+
+    /// End the message, closing the attachment still open.
+    pub fn complete(&mut self) -> Vec<MimeOutput> {
+        let mut out = Vec::new();
+        if self.entity.in_headers {
+            self.end_headers(&mut out);
+        }
+        self.end_entity(&mut out);
+        out
+    }
+
+    fn end_entity(&mut self, out: &mut Vec<MimeOutput>) {
+        if self.entity.attachment && !self.entity.in_headers {
+            out.push(MimeOutput::FileEnd);
+        }
+    }
+
+    fn parse_boundary(&mut self, line: &[u8], out: &mut Vec<MimeOutput>) -> bool {
+        let found = self.boundaries.iter().enumerate().rev().filter_map(|(i, b)| {
+            b.as_ref().and_then(|b| match_boundary(line, b)).map(|close| (i, close))
+        }).next();
+        let (index, close) = match found {
+            Some(found) => found,
+            None => return false,
+        };
+        if self.entity.in_headers {
+            self.finish_header();
+        }
+        self.end_entity(out);
+        if close {
+            self.boundaries.truncate(index);
+            self.entity = Entity::skipped();
+        } else {
+            self.boundaries.truncate(index + 1);
+            self.entity = Entity::new();
+        }
+        true
+    }
+
+    /// Store the header received so far, now that the next line shows it
+    /// is not folded any further.
+    fn finish_header(&mut self) {
+        if let Some(header) = self.header.take() {
+            if self.entity.headers.len() >= MAX_HEADER_COUNT {
+                self.anomalies |= MIME_ANOM_TOO_MANY_HEADERS;
+            } else {
+                self.entity.headers.push(header);
+            }
+        }
+    }
+
+    fn parse_header_line(&mut self, line: &[u8], out: &mut Vec<MimeOutput>) {
+        if line.is_empty() {
+            self.end_headers(out);
+            return;
+        }
+        if line[0] == b' ' || line[0] == b'\t' {
+            match self.header {
+                Some(ref mut header) => {
+                    let room = MAX_HEADER_VALUE_LEN.saturating_sub(header.value.len());
+                    if line.len() > room {
+                        self.anomalies |= MIME_ANOM_LONG_HEADER_VALUE;
+                    }
+                    header.value.extend_from_slice(&line[..std::cmp::min(room, line.len())]);
+                }
+                None => {
+                    self.anomalies |= MIME_ANOM_MALFORMED_MSG;
+                }
+            }
+            return;
+        }
+        self.finish_header();
+        let pos = match line.iter().position(|&c| c == b':') {
+            Some(pos) => pos,
+            None => {
+                // not a header: the body starts without the empty line
+                self.anomalies |= MIME_ANOM_MALFORMED_MSG;
+                self.end_headers(out);
+                self.parse_body_line(line, out);
+                return;
+            }
+        };
+        let mut name = trim(&line[..pos]).to_vec();
+        if name.len() > MAX_HEADER_NAME_LEN {
+            self.anomalies |= MIME_ANOM_LONG_HEADER_NAME;
+            name.truncate(MAX_HEADER_NAME_LEN);
+        }
+        let mut value = trim(&line[pos + 1..]).to_vec();
+        if value.len() > MAX_HEADER_VALUE_LEN {
+            self.anomalies |= MIME_ANOM_LONG_HEADER_VALUE;
+            value.truncate(MAX_HEADER_VALUE_LEN);
+        }
+        self.header = Some(MimeHeader { name, value });
+    }
+
+    fn end_headers(&mut self, out: &mut Vec<MimeOutput>) {
+        self.finish_header();
+        if !self.headers_complete {
+            self.headers = self.entity.headers.clone();
+            self.headers_complete = true;
+        }
+        self.entity.in_headers = false;
+        let content_type = self.entity.header(b"content-type").map(|v| v.to_vec());
+        let mtype = content_type.as_ref().map(|v| media_type(v)).unwrap_or_default();
+
+        if mtype.starts_with(b"multipart/") {
+            let boundary = content_type.as_ref()
+                .and_then(|v| mime_find_header_token(v, b"boundary"));
+            match boundary {
+                Some(ref boundary) if boundary.len() > MAX_BOUNDARY_LEN => {
+                    self.anomalies |= MIME_ANOM_LONG_BOUNDARY;
+                }
+                Some(boundary) => {
+                    if self.boundaries.len() >= MAX_NESTING_DEPTH {
+                        self.anomalies |= MIME_ANOM_DEEP_NESTING;
+                    } else {
+                        self.boundaries.push(Some(boundary));
+                        self.entity = Entity::skipped();
+                    }
+                }
+                None => {
+                    self.anomalies |= MIME_ANOM_MALFORMED_MSG;
+                }
+            }
+            return;
+        }
+
+        let disposition = self.entity.header(b"content-disposition");
+        let filename = disposition.and_then(|v| mime_find_header_token(v, b"filename"))
+            .or_else(|| content_type.as_ref().and_then(|v| mime_find_header_token(v, b"name")));
+
+        if mtype.starts_with(b"message/") && filename.is_none() {
+            if self.boundaries.len() >= MAX_NESTING_DEPTH {
+                self.anomalies |= MIME_ANOM_DEEP_NESTING;
+            } else {
+                // the body is an encapsulated message, with its own headers
+                self.boundaries.push(None);
+                self.entity = Entity::new();
+            }
+            return;
+        }
+
+        if let Some(encoding) = self.entity.header(b"content-transfer-encoding") {
+            let encoding = trim(encoding);
+            if encoding.eq_ignore_ascii_case(b"base64") {
+                self.entity.encoding = Encoding::Base64;
+            } else if encoding.eq_ignore_ascii_case(b"quoted-printable") {
+                self.entity.encoding = Encoding::QuotedPrintable;
+            }
+        }
+        if let Some(filename) = filename {
+            let mut filename = mime_decode_words(&filename);
+            if filename.len() > RS_MIME_MAX_TOKEN_LEN {
+                self.anomalies |= MIME_ANOM_LONG_FILENAME;
+                filename.truncate(RS_MIME_MAX_TOKEN_LEN);
+            }
+            self.entity.attachment = true;
+            out.push(MimeOutput::FileStart(filename));
+        }
+    }
+
+    fn decode_base64_line(&mut self, line: &[u8], data: &mut Vec<u8>) {
+        let entity = &mut self.entity;
+        for &c in line {
+            if c.is_ascii_whitespace() {
+                continue;
+            }
+            if c == b'=' {
+                // padding, the remaining bits are not data
+                entity.b64_acc = 0;
+                entity.b64_bits = 0;
+                continue;
+            }
+            match base64_value(c) {
+                Some(value) => {
+                    entity.b64_acc = (entity.b64_acc << 6) | value;
+                    entity.b64_bits += 6;
+                    if entity.b64_bits >= 8 {
+                        entity.b64_bits -= 8;
+                        data.push((entity.b64_acc >> entity.b64_bits) as u8);
+                        entity.b64_acc &= (1 << entity.b64_bits) - 1;
+                    }
+                }
+                None => {
+                    self.anomalies |= MIME_ANOM_INVALID_BASE64;
+                }
+            }
+        }
+    }
+
+    fn decode_qp_line(&mut self, line: &[u8], data: &mut Vec<u8>) {
+        let end = line.iter().rposition(|&c| c != b' ' && c != b'\t').map_or(0, |i| i + 1);
+        let line = &line[..end];
+        let (line, soft_break) = match line.last() {
+            Some(b'=') => (&line[..line.len() - 1], true),
+            _ => (line, false),
+        };
+        let mut i = 0;
+        while i < line.len() {
+            if line[i] == b'=' {
+                let hex = (line.get(i + 1).and_then(|&c| hex_value(c)),
+                           line.get(i + 2).and_then(|&c| hex_value(c)));
+                if let (Some(h), Some(l)) = hex {
+                    data.push(h << 4 | l);
+                    i += 3;
+                    continue;
+                }
+                self.anomalies |= MIME_ANOM_INVALID_QP;
+            }
+            data.push(line[i]);
+            i += 1;
+        }
+        self.entity.pending_crlf = !soft_break;
+    }
+
+    fn parse_body_line(&mut self, line: &[u8], out: &mut Vec<MimeOutput>) {
+        if self.entity.skip_body {
+            return;
+        }
+        let mut data = Vec::new();
+        match self.entity.encoding {
+            Encoding::Base64 => {
+                if line.len() > MAX_ENC_LINE_LEN {
+                    self.anomalies |= MIME_ANOM_LONG_ENC_LINE;
+                }
+                self.decode_base64_line(line, &mut data);
+            }
+            Encoding::QuotedPrintable => {
+                if line.len() > MAX_ENC_LINE_LEN {
+                    self.anomalies |= MIME_ANOM_LONG_ENC_LINE;
+                }
+                if self.entity.pending_crlf {
+                    data.extend_from_slice(b"\r\n");
+                }
+                self.decode_qp_line(line, &mut data);
+            }
+            Encoding::Plain => {
+                if self.entity.pending_crlf {
+                    data.extend_from_slice(b"\r\n");
+                }
+                data.extend_from_slice(line);
+                self.entity.pending_crlf = true;
+            }
+        }
+        if self.entity.attachment && !data.is_empty() {
+            out.push(MimeOutput::FileData(data));
+        }
+    }
+}
+
+impl Default for MimeDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a message, returning the files found in it.
+    fn parse(decoder: &mut MimeDecoder, message: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut outputs = Vec::new();
+        for line in message.split(|&c| c == b'\n') {
+            let line = match line.last() {
+                Some(b'\r') => &line[..line.len() - 1],
+                _ => line,
+            };
+            outputs.extend(decoder.parse_line(line));
+        }
+        outputs.extend(decoder.complete());
+
+        let mut files = Vec::new();
+        let mut open = false;
+        for output in outputs {
+            match output {
+                MimeOutput::FileStart(name) => {
+                    assert!(!open);
+                    open = true;
+                    files.push((name, Vec::new()));
+                }
+                MimeOutput::FileData(data) => {
+                    assert!(open);
+                    files.last_mut().unwrap().1.extend(data);
+                }
+                MimeOutput::FileEnd => {
+                    assert!(open);
+                    open = false;
+                }
+            }
+        }
+        assert!(!open);
+        files
+    }
+
+    #[test]
+    fn test_mime_decoder_headers() {
+        let mut decoder = MimeDecoder::new();
+        let files = parse(&mut decoder, b"From: alice@example.com\r\n\
+                                          Subject: a long\r\n\
+                                          \tsubject\r\n\
+                                          \r\n\
+                                          Hello\r\n");
+        assert!(files.is_empty());
+        assert!(decoder.headers_complete());
+        assert_eq!(decoder.headers, vec![
+            MimeHeader { name: b"From".to_vec(), value: b"alice@example.com".to_vec() },
+            MimeHeader { name: b"Subject".to_vec(), value: b"a long\tsubject".to_vec() },
+        ]);
+        assert_eq!(decoder.anomalies, 0);
+    }
+
+    #[test]
+    fn test_mime_decoder_attachments() {
+        let mut decoder = MimeDecoder::new();
+        let files = parse(&mut decoder, b"Subject: files\r\n\
+            Content-Type: multipart/mixed; boundary=\"XX\"\r\n\
+            \r\n\
+            preamble\r\n\
+            --XX\r\n\
+            Content-Type: text/plain\r\n\
+            \r\n\
+            the body\r\n\
+            --XX\r\n\
+            Content-Type: application/octet-stream; name=\"a.bin\"\r\n\
+            Content-Transfer-Encoding: base64\r\n\
+            \r\n\
+            aGVsbG8g\r\n\
+            d29ybGQ=\r\n\
+            --XX\r\n\
+            Content-Disposition: attachment; filename=\"b.txt\"\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\
+            \r\n\
+            caf=C3=A9 =\r\n\
+            au lait\r\n\
+            second line\r\n\
+            --XX--\r\n\
+            epilogue\r\n");
+        assert_eq!(files, vec![
+            (b"a.bin".to_vec(), b"hello world".to_vec()),
+            (b"b.txt".to_vec(), b"caf\xc3\xa9 au lait\r\nsecond line".to_vec()),
+        ]);
+        assert_eq!(decoder.headers.len(), 2);
+        assert_eq!(decoder.anomalies, 0);
+    }
+
+    #[test]
+    fn test_mime_decoder_nested() {
+        let mut decoder = MimeDecoder::new();
+        let files = parse(&mut decoder, b"Content-Type: multipart/mixed; boundary=outer\r\n\
+            \r\n\
+            --outer\r\n\
+            Content-Type: message/rfc822\r\n\
+            \r\n\
+            Subject: forwarded\r\n\
+            Content-Type: multipart/mixed; boundary=inner\r\n\
+            \r\n\
+            --inner\r\n\
+            Content-Disposition: attachment; filename=inner.txt\r\n\
+            \r\n\
+            inner file\r\n\
+            --outer\r\n\
+            Content-Disposition: attachment; filename=outer.txt\r\n\
+            \r\n\
+            outer file\r\n\
+            --outer--\r\n");
+        assert_eq!(files, vec![
+            (b"inner.txt".to_vec(), b"inner file".to_vec()),
+            (b"outer.txt".to_vec(), b"outer file".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn test_mime_decoder_anomalies() {
+        let mut decoder = MimeDecoder::new();
+        let mut message = b"Content-Disposition: attachment; filename=x.bin\r\n\
+                            Content-Transfer-Encoding: base64\r\n\
+                            \r\n\
+                            aGVs!bG8=\r\n".to_vec();
+        message.extend(std::iter::repeat(b'A').take(MAX_ENC_LINE_LEN + 4));
+        let files = parse(&mut decoder, &message);
+        assert_eq!(files[0].1[..5], b"hello"[..]);
+        assert_eq!(decoder.anomalies, MIME_ANOM_INVALID_BASE64 | MIME_ANOM_LONG_ENC_LINE);
+
+        let mut decoder = MimeDecoder::new();
+        let mut message = b"X-Long: ".to_vec();
+        message.extend(std::iter::repeat(b'v').take(MAX_HEADER_VALUE_LEN + 1));
+        message.extend_from_slice(b"\r\nContent-Type: multipart/mixed\r\n\r\n");
+        let _ = parse(&mut decoder, &message);
+        assert_eq!(decoder.headers[0].value.len(), MAX_HEADER_VALUE_LEN);
+        assert_eq!(decoder.anomalies,
+                   MIME_ANOM_LONG_LINE | MIME_ANOM_LONG_HEADER_VALUE | MIME_ANOM_MALFORMED_MSG);
+    }
+
+    #[test]
+    fn test_mime_decoder_deep_nesting() {
+        let mut decoder = MimeDecoder::new();
+        let mut message = Vec::new();
+        for i in 0..MAX_NESTING_DEPTH + 1 {
+            message.extend(format!("Content-Type: multipart/mixed; boundary=b{}\r\n\r\n--b{}\r\n",
+                                   i, i).into_bytes());
+        }
+        let _ = parse(&mut decoder, &message);
+        assert_eq!(decoder.anomalies, MIME_ANOM_DEEP_NESTING);
+    }
+}
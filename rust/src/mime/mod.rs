@@ -0,0 +1,442 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Parameters of the MIME headers, for the MIME decoder of the engine.
+//!
+//! The values are decoded following RFC 2231, for the charset and the
+//! continuations of the extended parameters, and for the filenames
+//! following RFC 2047, as many mailers put encoded words in them
+//! although the RFC does not allow it.
+//!
+//! The `decoder` module decodes whole messages, for the Rust parsers.
+
+pub mod decoder;
+
+/// Size of the buffer the tokens are copied into, truncating them.
+pub const RS_MIME_MAX_TOKEN_LEN: usize = 255;
+
+/// A parameter of a header value: "name*1*=value".
+#[derive(Debug)]
+struct Param {
+    name: Vec<u8>,
+    /// The section number of a continuation.
+    section: Option<u32>,
+    /// Whether the value is percent encoded, with a charset.
+    extended: bool,
+    value: Vec<u8>,
+}
+
+fn is_space(c: u8) -> bool {
+    c == b' ' || c == b'\t' || c == b'\r' || c == b'\n'
+}
+
+fn trim(mut value: &[u8]) -> &[u8] {
+    while let Some((&first, rest)) = value.split_first() {
+        if !is_space(first) {
+            break;
+        }
+        value = rest;
+    }
+    while let Some((&last, rest)) = value.split_last() {
+        if !is_space(last) {
+            break;
+        }
+        value = rest;
+    }
+    value
+}
+
+/// Parse a quoted string, starting after the opening quote. An
+/// unterminated string runs to the end of the header.
+fn parse_quoted(input: &[u8]) -> (Vec<u8>, &[u8]) {
+    let mut value = Vec::new();
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'"' => return (value, &input[i + 1..]),
+            b'\\' if i + 1 < input.len() => {
+                value.push(input[i + 1]);
+                i += 2;
+                continue;
+            }
+            c => value.push(c),
+        }
+        i += 1;
+    }
+    (value, &[])
+}
+
+fn parse_name(name: &[u8]) -> (Vec<u8>, Option<u32>, bool) {
+    let name = trim(name).to_ascii_lowercase();
+    let mut parts = name.split(|&b| b == b'*');
+    let base = parts.next().unwrap_or(&[]).to_vec();
+    let mut section = None;
+    let mut extended = false;
+    for part in parts {
+        if part.is_empty() {
+            extended = true;
+        } else if section.is_none() {
+            section = std::str::from_utf8(part).ok().and_then(|s| s.parse().ok());
+        }
+    }
+    (base, section, extended)
+}
+
+/// Split a header value into its parameters, skipping the value before
+/// the first ';', like "attachment" or "text/plain".
+fn parse_params(header: &[u8]) -> Vec<Param> {
+    let mut params = Vec::new();
+    let mut input = match header.iter().position(|&b| b == b';') {
+        Some(pos) => &header[pos + 1..],
+        None => return params,
+    };
+    while !input.is_empty() {
+        let end = input.iter().position(|&b| b == b'=' || b == b';').unwrap_or(input.len());
+        if end == input.len() || input[end] == b';' {
+            // a parameter without a value
+            input = &input[std::cmp::min(end + 1, input.len())..];
+            continue;
+        }
+        let (name, section, extended) = parse_name(&input[..end]);
+        let rest = trim(&input[end + 1..]);
+        let (value, rest) = if rest.first() == Some(&b'"') {
+            let (value, rest) = parse_quoted(&rest[1..]);
+            let next = rest.iter().position(|&b| b == b';').map_or(rest.len(), |p| p + 1);
+            (value, &rest[next..])
+        } else {
+            // some mailers do not quote the values with spaces
+            let next = rest.iter().position(|&b| b == b';').unwrap_or(rest.len());
+            (trim(&rest[..next]).to_vec(), &rest[std::cmp::min(next + 1, rest.len())..])
+        };
+        if !name.is_empty() {
+            params.push(Param { name, section, extended, value });
+        }
+        input = rest;
+    }
+    params
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decode the "%XX" escapes of an extended value. Invalid escapes are
+/// kept as is.
+fn percent_decode(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' && i + 2 < input.len() {
+            if let (Some(h), Some(l)) = (hex_value(input[i + 1]), hex_value(input[i + 2])) {
+                out.push(h << 4 | l);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(input[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Convert a value to UTF-8 from the charsets that map directly. The
+/// values in other charsets are kept as they are.
+fn convert_charset(charset: &[u8], value: Vec<u8>) -> Vec<u8> {
+    let charset = charset.to_ascii_lowercase();
+    match &charset[..] {
+        b"iso-8859-1" | b"latin1" | b"l1" => {
+            value.iter().map(|&b| b as char).collect::<String>().into_bytes()
+        }
+        _ => value,
+    }
+}
+
+/// Split the "charset'language'" prefix from the first section of an
+/// extended value.
+fn split_charset(value: &[u8]) -> (&[u8], &[u8]) {
+    let mut parts = value.splitn(3, |&b| b == b'\'');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(charset), Some(_language), Some(value)) => (charset, value),
+        _ => (&[], value),
+    }
+}
+
+fn base64_value(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+        b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+fn decode_base64(input: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &c in input {
+        if c == b'=' {
+            break;
+        }
+        acc = (acc << 6) | base64_value(c)?;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+fn decode_q(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'_' => out.push(b' '),
+            b'=' if i + 2 < input.len() => {
+                match (hex_value(input[i + 1]), hex_value(input[i + 2])) {
+                    (Some(h), Some(l)) => {
+                        out.push(h << 4 | l);
+                        i += 3;
+                        continue;
+                    }
+                    _ => out.push(b'='),
+                }
+            }
+            c => out.push(c),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Decode an encoded word, "=?charset?B?text?=", at the start of
+/// `input`. Returns the decoded text and the length of the word.
+fn decode_word(input: &[u8]) -> Option<(Vec<u8>, usize)> {
+    if !input.starts_with(b"=?") {
+        return None;
+    }
+    let mut parts = input[2..].splitn(3, |&b| b == b'?');
+    let charset = parts.next()?;
+    let encoding = parts.next()?;
+    let rest = parts.next()?;
+    let text_len = rest.windows(2).position(|w| w == b"?=")?;
+    let text = &rest[..text_len];
+    if encoding.len() != 1 || text.iter().any(|&b| is_space(b)) {
+        return None;
+    }
+    let decoded = match encoding[0] {
+        b'B' | b'b' => decode_base64(text)?,
+        b'Q' | b'q' => decode_q(text),
+        _ => return None,
+    };
+    let len = 2 + charset.len() + 1 + encoding.len() + 1 + text_len + 2;
+    // RFC 2231 allows a language after the charset: "utf-8*en"
+    let charset = charset.split(|&b| b == b'*').next().unwrap_or(&[]);
+    Some((convert_charset(charset, decoded), len))
+}
+
+/// Decode the RFC 2047 encoded words of a value. The whitespace between
+/// two encoded words is dropped.
+pub fn mime_decode_words(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    let mut after_word = false;
+    while i < input.len() {
+        if let Some((decoded, len)) = decode_word(&input[i..]) {
+            out.extend_from_slice(&decoded);
+            i += len;
+            after_word = true;
+            continue;
+        }
+        if after_word && is_space(input[i]) {
+            let spaces = input[i..].iter().take_while(|&&b| is_space(b)).count();
+            if decode_word(&input[i + spaces..]).is_some() {
+                i += spaces;
+                continue;
+            }
+        }
+        after_word = false;
+        out.push(input[i]);
+        i += 1;
+    }
+    out
+}
+
+/// Find the value of the parameter `token` of a header value, like the
+/// filename in "attachment; filename=x.pdf". An extended parameter is
+/// preferred over a plain one with the same name, and the continuations
+/// of a parameter are joined.
+pub fn mime_find_header_token(header: &[u8], token: &[u8]) -> Option<Vec<u8>> {
+    let token = token.to_ascii_lowercase();
+    let params: Vec<Param> = parse_params(header)
+        .into_iter()
+        .filter(|p| p.name == token)
+        .collect();
+
+    let whole = params.iter().filter(|p| p.section.is_none())
+        .max_by_key(|p| p.extended);
+    if let Some(param) = whole {
+        if !param.extended {
+            return Some(param.value.clone());
+        }
+        let (charset, value) = split_charset(&param.value);
+        return Some(convert_charset(charset, percent_decode(value)));
+    }
+
+    // the sections must follow each other from 0
+    let mut value = Vec::new();
+    let mut charset: &[u8] = &[];
+    let mut found = false;
+    for section in 0.. {
+        let param = match params.iter().find(|p| p.section == Some(section)) {
+            Some(param) => param,
+            None => break,
+        };
+        found = true;
+        if !param.extended {
+            value.extend_from_slice(&param.value);
+        } else if section == 0 {
+            let (cs, encoded) = split_charset(&param.value);
+            charset = cs;
+            value.extend_from_slice(&percent_decode(encoded));
+        } else {
+            value.extend_from_slice(&percent_decode(&param.value));
+        }
+    }
+    if !found {
+        return None;
+    }
+    Some(convert_charset(charset, value))
+}
+
+unsafe fn copy_token(
+    value: &[u8], outbuf: &mut [u8; RS_MIME_MAX_TOKEN_LEN], outlen: *mut u32,
+    truncated: *mut bool,
+) {
+    let len = std::cmp::min(value.len(), RS_MIME_MAX_TOKEN_LEN);
+    outbuf[..len].copy_from_slice(&value[..len]);
+    *outlen = len as u32;
+    *truncated = value.len() > RS_MIME_MAX_TOKEN_LEN;
+}
+
+/// Find the value of a parameter of a header, like the boundary of a
+/// Content-Type header, and copy it into `outbuf`.
+///
+/// # Safety
+///
+/// The header, if not NULL, and the token must be valid buffers of the
+/// given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn rs_mime_find_header_token(
+    hinput: *const u8, hlen: u32, tinput: *const u8, tlen: u32,
+    outbuf: &mut [u8; RS_MIME_MAX_TOKEN_LEN], outlen: *mut u32, truncated: *mut bool,
+) -> bool {
+    if hinput.is_null() {
+        return false;
+    }
+    let header = build_slice!(hinput, hlen as usize);
+    let token = build_slice!(tinput, tlen as usize);
+    match mime_find_header_token(header, token) {
+        Some(value) => {
+            copy_token(&value, outbuf, outlen, truncated);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Like `rs_mime_find_header_token`, also decoding the encoded words
+/// of the value, for the filenames.
+///
+/// # Safety
+///
+/// The header, if not NULL, and the token must be valid buffers of the
+/// given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn rs_mime_find_filename(
+    hinput: *const u8, hlen: u32, tinput: *const u8, tlen: u32,
+    outbuf: &mut [u8; RS_MIME_MAX_TOKEN_LEN], outlen: *mut u32, truncated: *mut bool,
+) -> bool {
+    if hinput.is_null() {
+        return false;
+    }
+    let header = build_slice!(hinput, hlen as usize);
+    let token = build_slice!(tinput, tlen as usize);
+    match mime_find_header_token(header, token) {
+        Some(value) => {
+            copy_token(&mime_decode_words(&value), outbuf, outlen, truncated);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mime_find_header_token() {
+        let header = b"attachment; filename=\"report \\\"q3\\\".pdf\"; size=42";
+        assert_eq!(mime_find_header_token(header, b"filename"),
+                   Some(b"report \"q3\".pdf".to_vec()));
+        assert_eq!(mime_find_header_token(header, b"size"), Some(b"42".to_vec()));
+        assert_eq!(mime_find_header_token(header, b"name"), None);
+        assert_eq!(mime_find_header_token(b"multipart/mixed; BOUNDARY=abc", b"boundary"),
+                   Some(b"abc".to_vec()));
+        assert_eq!(mime_find_header_token(b"attachment; filename=my file.doc ", b"filename"),
+                   Some(b"my file.doc".to_vec()));
+        assert_eq!(mime_find_header_token(b"attachment; filename", b"filename"), None);
+    }
+
+    #[test]
+    fn test_mime_rfc2231() {
+        let header = b"attachment; filename=\"fallback.txt\"; \
+                       filename*=iso-8859-1'fr'r%E9sum%E9.txt";
+        assert_eq!(mime_find_header_token(header, b"filename"),
+                   Some("résumé.txt".as_bytes().to_vec()));
+
+        let header = b"attachment;\r\n filename*0*=UTF-8''%E2%82%AC%20;\r\n \
+                       filename*1=\"part two\";\r\n filename*3=lost";
+        assert_eq!(mime_find_header_token(header, b"filename"),
+                   Some("€ part two".as_bytes().to_vec()));
+
+        assert_eq!(mime_find_header_token(b"a; filename*=utf-8''100%", b"filename"),
+                   Some(b"100%".to_vec()));
+    }
+
+    #[test]
+    fn test_mime_decode_words() {
+        assert_eq!(mime_decode_words(b"=?UTF-8?B?w6l0w6kucGRm?="), "été.pdf".as_bytes());
+        assert_eq!(mime_decode_words(b"=?iso-8859-1?Q?caf=E9_menu?= .txt"),
+                   "café menu .txt".as_bytes());
+        assert_eq!(mime_decode_words(b"=?utf-8?q?a?= \r\n =?utf-8?q?b?=.zip"), b"ab.zip");
+        assert_eq!(mime_decode_words(b"=?utf-8*en?Q?x?="), b"x");
+        assert_eq!(mime_decode_words(b"plain =? name.txt"), b"plain =? name.txt");
+        assert_eq!(mime_decode_words(b"=?utf-8?X?abc?="), b"=?utf-8?X?abc?=");
+    }
+}
@@ -15,14 +15,20 @@
  * 02110-1301, USA.
  */
 
-use super::modbus::ModbusTransaction;
+use super::modbus::{ModbusTransaction, ALPROTO_MODBUS};
+use crate::applayer::cast_mut;
 use crate::debug_validate_bug_on;
+use crate::detect::uint::{detect_match_uint, detect_parse_uint, DetectUintData};
+use crate::detect::{
+    helper_buffer_register, helper_free_ctx, helper_keyword_register, helper_setup_tx_match,
+    DetectKeyword,
+};
 use lazy_static::lazy_static;
 use regex::Regex;
 use sawp_modbus::{AccessType, CodeCategory, Data, Flags, FunctionCode, Message};
 use std::ffi::CStr;
 use std::ops::{Range, RangeInclusive};
-use std::os::raw::{c_char, c_void};
+use std::os::raw::{c_char, c_int, c_void};
 use std::str::FromStr;
 
 lazy_static! {
@@ -473,6 +479,118 @@ fn parse_unit_id(unit_str: &str) -> Result<DetectModbusRust, ()> {
     Ok(modbus)
 }
 
+static mut G_MODBUS_FUNCTION_KW_ID: u16 = 0;
+static mut G_MODBUS_FUNCTION_BUFFER_ID: c_int = 0;
+static mut G_MODBUS_EXCEPTION_KW_ID: u16 = 0;
+static mut G_MODBUS_EXCEPTION_BUFFER_ID: c_int = 0;
+
+/// Parse the argument of `modbus.function`, which takes the same values as
+/// the function setting of the `modbus` keyword.
+fn parse_function_keyword(arg: &str) -> Result<DetectModbusRust, ()> {
+    parse_function(&format!("function {}", arg.trim()))
+}
+
+/// Exception code of the response of a transaction, if it is an exception.
+fn tx_exception_code(tx: &ModbusTransaction) -> Option<u8> {
+    match &tx.response {
+        Some(msg) => match &msg.data {
+            Data::Exception(exc) => Some(exc.raw),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+unsafe extern "C" fn modbus_function_match(
+    _det_ctx: *mut c_void, _f: *mut c_void, _flags: u8, _state: *mut c_void, tx: *mut c_void,
+    _s: *const c_void, ctx: *const c_void,
+) -> c_int {
+    let tx: &mut ModbusTransaction = cast_mut(tx);
+    let ctx = &*(ctx as *const DetectModbusRust);
+    rs_modbus_inspect(tx, ctx) as c_int
+}
+
+unsafe extern "C" fn modbus_function_setup(
+    _de: *mut c_void, s: *mut c_void, raw: *const c_char,
+) -> c_int {
+    if raw.is_null() {
+        return -1;
+    }
+    let ctx = match CStr::from_ptr(raw).to_str().map_err(|_| ()).and_then(parse_function_keyword) {
+        Ok(ctx) => ctx,
+        Err(_) => return -1,
+    };
+    helper_setup_tx_match(
+        s, ALPROTO_MODBUS, G_MODBUS_FUNCTION_KW_ID, G_MODBUS_FUNCTION_BUFFER_ID, ctx)
+}
+
+unsafe extern "C" fn modbus_function_free(_de: *mut c_void, ctx: *mut c_void) {
+    helper_free_ctx::<DetectModbusRust>(ctx);
+}
+
+unsafe extern "C" fn modbus_exception_match(
+    _det_ctx: *mut c_void, _f: *mut c_void, _flags: u8, _state: *mut c_void, tx: *mut c_void,
+    _s: *const c_void, ctx: *const c_void,
+) -> c_int {
+    let tx: &mut ModbusTransaction = cast_mut(tx);
+    let ctx = &*(ctx as *const DetectUintData<u8>);
+    match tx_exception_code(tx) {
+        Some(code) => detect_match_uint(ctx, code) as c_int,
+        None => 0,
+    }
+}
+
+unsafe extern "C" fn modbus_exception_setup(
+    _de: *mut c_void, s: *mut c_void, raw: *const c_char,
+) -> c_int {
+    if raw.is_null() {
+        return -1;
+    }
+    let ctx = match CStr::from_ptr(raw).to_str().ok().and_then(detect_parse_uint::<u8>) {
+        Some(ctx) => ctx,
+        None => return -1,
+    };
+    helper_setup_tx_match(
+        s, ALPROTO_MODBUS, G_MODBUS_EXCEPTION_KW_ID, G_MODBUS_EXCEPTION_BUFFER_ID, ctx)
+}
+
+unsafe extern "C" fn modbus_exception_free(_de: *mut c_void, ctx: *mut c_void) {
+    helper_free_ctx::<DetectUintData<u8>>(ctx);
+}
+
+/// Register the Modbus keywords implemented in Rust.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "modbus.function",
+        desc: "match on the function code or function category of a Modbus request",
+        url: "/rules/modbus-keyword.html#modbus-function",
+        flags: 0,
+        setup: modbus_function_setup,
+        free: Some(modbus_function_free),
+        tx_match: Some(modbus_function_match),
+    };
+    if let Some(kw_id) = helper_keyword_register(&kw) {
+        G_MODBUS_FUNCTION_KW_ID = kw_id;
+        G_MODBUS_FUNCTION_BUFFER_ID =
+            helper_buffer_register("modbus.function", ALPROTO_MODBUS, false, true, 0);
+    }
+
+    let kw = DetectKeyword {
+        name: "modbus.exception",
+        desc: "match on the exception code of a Modbus exception response",
+        url: "/rules/modbus-keyword.html#modbus-exception",
+        flags: 0,
+        setup: modbus_exception_setup,
+        free: Some(modbus_exception_free),
+        tx_match: Some(modbus_exception_match),
+    };
+    if let Some(kw_id) = helper_keyword_register(&kw) {
+        G_MODBUS_EXCEPTION_KW_ID = kw_id;
+        G_MODBUS_EXCEPTION_BUFFER_ID =
+            helper_buffer_register("modbus.exception", ALPROTO_MODBUS, true, false, 1);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::super::modbus::ModbusState;
@@ -1462,4 +1580,51 @@ mod test {
             1
         );
     }
+
+    #[test]
+    fn test_function_exception_keywords() {
+        assert_eq!(
+            parse_function_keyword("23"),
+            Ok(DetectModbusRust {
+                function: Some(FunctionCode::RdWrMultRegs),
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_function_keyword(" !reserved"), parse_function("function !reserved"));
+        assert!(parse_function_keyword("0").is_err());
+
+        let mut modbus = ModbusState::new();
+        assert_eq!(
+            modbus.parse(
+                &[
+                    0x00, 0x01, // Transaction ID
+                    0x00, 0x00, // Protocol ID
+                    0x00, 0x06, // Length
+                    0x0a, // Unit ID
+                    0x01, // Function code
+                    0x00, 0x10, // Starting Address
+                    0x00, 0x02, // Quantity of coils
+                ],
+                Direction::ToServer
+            ),
+            AppLayerResult::ok()
+        );
+        assert_eq!(tx_exception_code(&modbus.transactions[0]), None);
+        assert_eq!(
+            modbus.parse(
+                &[
+                    0x00, 0x01, // Transaction ID
+                    0x00, 0x00, // Protocol ID
+                    0x00, 0x03, // Length
+                    0x0a, // Unit ID
+                    0x81, // Exception function code
+                    0x02, // Illegal data address
+                ],
+                Direction::ToClient
+            ),
+            AppLayerResult::ok()
+        );
+        assert_eq!(modbus.transactions.len(), 1);
+        assert_eq!(tx_exception_code(&modbus.transactions[0]), Some(2));
+    }
 }
@@ -28,7 +28,7 @@ use sawp_modbus::{self, AccessType, ErrorFlags, Flags, Message};
 pub const REQUEST_FLOOD: usize = 500; // Default unreplied Modbus requests are considered a flood
 pub const MODBUS_PARSER: sawp_modbus::Modbus = sawp_modbus::Modbus {};
 
-static mut ALPROTO_MODBUS: AppProto = ALPROTO_UNKNOWN;
+pub static mut ALPROTO_MODBUS: AppProto = ALPROTO_UNKNOWN;
 
 #[derive(AppLayerEvent)]
 enum ModbusEvent {
@@ -48,10 +48,12 @@ pub struct ModbusTransaction {
     pub response: Option<Message>,
 
     pub events: *mut core::AppLayerDecoderEvents,
-    pub de_state: Option<*mut core::DetectEngineState>,
+    pub de_state: DetectStateHolder,
     pub tx_data: AppLayerTxData,
 }
 
+unsafe impl FfiType for ModbusTransaction {}
+
 impl ModbusTransaction {
     pub fn new(id: u64) -> Self {
         Self {
@@ -59,7 +61,7 @@ impl ModbusTransaction {
             request: None,
             response: None,
             events: std::ptr::null_mut(),
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             tx_data: AppLayerTxData::new(),
         }
     }
@@ -92,10 +94,6 @@ impl Drop for ModbusTransaction {
         if !self.events.is_null() {
             core::sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-
-        if let Some(state) = self.de_state {
-            core::sc_detect_engine_state_free(state);
-        }
     }
 }
 
@@ -105,6 +103,8 @@ pub struct ModbusState {
     givenup: bool, // Indicates flood
 }
 
+unsafe impl FfiType for ModbusState {}
+
 impl ModbusState {
     pub fn new() -> Self {
         Self {
@@ -324,7 +324,7 @@ pub extern "C" fn rs_modbus_state_free(state: *mut std::os::raw::c_void) {
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_modbus_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
-    let state = cast_pointer!(state, ModbusState);
+    let state: &mut ModbusState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -341,7 +341,7 @@ pub unsafe extern "C" fn rs_modbus_parse_request(
         }
     }
 
-    let state = cast_pointer!(state, ModbusState);
+    let state: &mut ModbusState = cast_mut(state);
     let buf = std::slice::from_raw_parts(input, input_len as usize);
 
     state.parse(buf, Direction::ToServer)
@@ -360,7 +360,7 @@ pub unsafe extern "C" fn rs_modbus_parse_response(
         }
     }
 
-    let state = cast_pointer!(state, ModbusState);
+    let state: &mut ModbusState = cast_mut(state);
     let buf = std::slice::from_raw_parts(input, input_len as usize);
 
     state.parse(buf, Direction::ToClient)
@@ -368,7 +368,7 @@ pub unsafe extern "C" fn rs_modbus_parse_response(
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_modbus_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
-    let state = cast_pointer!(state, ModbusState);
+    let state: &mut ModbusState = cast_mut(state);
     state.tx_id
 }
 
@@ -376,7 +376,7 @@ pub unsafe extern "C" fn rs_modbus_state_get_tx_count(state: *mut std::os::raw::
 pub unsafe extern "C" fn rs_modbus_state_get_tx(
     state: *mut std::os::raw::c_void, tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, ModbusState);
+    let state: &mut ModbusState = cast_mut(state);
     match state.get_tx(tx_id) {
         Some(tx) => (tx as *mut ModbusTransaction) as *mut std::os::raw::c_void,
         None => std::ptr::null_mut(),
@@ -392,7 +392,7 @@ pub unsafe extern "C" fn rs_modbus_state_get_tx_iterator(
     _max_tx_id: u64,
     istate: &mut u64,
 ) -> applayer::AppLayerGetTxIterTuple {
-    let state = cast_pointer!(state, ModbusState);
+    let state: &mut ModbusState = cast_mut(state);
     match state.tx_iterator(min_tx_id, istate) {
         Some((tx, out_tx_id, has_next)) => {
             let c_tx = tx as *const _ as *mut _;
@@ -413,7 +413,7 @@ pub unsafe extern "C" fn rs_modbus_state_get_tx_iterator(
 pub unsafe extern "C" fn rs_modbus_tx_get_alstate_progress(
     tx: *mut std::os::raw::c_void, _direction: u8,
 ) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, ModbusTransaction);
+    let tx: &mut ModbusTransaction = cast_mut(tx);
     tx.response.is_some() as std::os::raw::c_int
 }
 
@@ -421,35 +421,17 @@ pub unsafe extern "C" fn rs_modbus_tx_get_alstate_progress(
 pub unsafe extern "C" fn rs_modbus_state_get_events(
     tx: *mut std::os::raw::c_void,
 ) -> *mut core::AppLayerDecoderEvents {
-    let tx = cast_pointer!(tx, ModbusTransaction);
+    let tx: &mut ModbusTransaction = cast_mut(tx);
     tx.events
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_modbus_state_get_tx_detect_state(
-    tx: *mut std::os::raw::c_void,
-) -> *mut core::DetectEngineState {
-    let tx = cast_pointer!(tx, ModbusTransaction);
-    match tx.de_state {
-        Some(ds) => ds,
-        None => std::ptr::null_mut(),
-    }
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_modbus_state_set_tx_detect_state(
-    tx: *mut std::os::raw::c_void, de_state: &mut core::DetectEngineState,
-) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, ModbusTransaction);
-    tx.de_state = Some(de_state);
-    0
-}
+export_tx_detect_state!(rs_modbus_state_get_tx_detect_state, rs_modbus_state_set_tx_detect_state, ModbusTransaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_modbus_state_get_tx_data(
     tx: *mut std::os::raw::c_void,
 ) -> *mut AppLayerTxData {
-    let tx = cast_pointer!(tx, ModbusTransaction);
+    let tx: &mut ModbusTransaction = cast_mut(tx);
     &mut tx.tx_data
 }
 
@@ -487,6 +469,7 @@ pub unsafe extern "C" fn rs_modbus_register_parser() {
         apply_tx_config: None,
         flags: 0,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
@@ -795,7 +778,7 @@ pub mod test {
     pub unsafe extern "C" fn rs_modbus_state_get_tx_request(
         state: *mut std::os::raw::c_void, tx_id: u64,
     ) -> ModbusMessage {
-        let state = cast_pointer!(state, ModbusState);
+        let state: &mut ModbusState = cast_mut(state);
         if let Some(tx) = state.get_tx(tx_id) {
             if let Some(request) = &tx.request {
                 ModbusMessage((request as *const Message) as *const c_void)
@@ -811,7 +794,7 @@ pub mod test {
     pub unsafe extern "C" fn rs_modbus_state_get_tx_response(
         state: *mut std::os::raw::c_void, tx_id: u64,
     ) -> ModbusMessage {
-        let state = cast_pointer!(state, ModbusState);
+        let state: &mut ModbusState = cast_mut(state);
         if let Some(tx) = state.get_tx(tx_id) {
             if let Some(response) = &tx.response {
                 ModbusMessage((response as *const Message) as *const c_void)
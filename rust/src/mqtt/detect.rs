@@ -17,12 +17,32 @@
 
 // written by Sascha Steinbiss <sascha@steinbiss.name>
 
-use crate::mqtt::mqtt::{MQTTTransaction, MQTTState};
-use crate::mqtt::mqtt_message::{MQTTOperation, MQTTTypeCode};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_buffer_register, helper_free_ctx, helper_get_data,
+    helper_keyword_register, helper_multi_buffer_mpm_register, helper_setup_sticky_buffer,
+    helper_setup_tx_match, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use crate::mqtt::mqtt::{MQTTTransaction, MQTTState, ALPROTO_MQTT};
+use crate::mqtt::mqtt_message::{MQTTConnectData, MQTTOperation, MQTTPublishData, MQTTTypeCode};
 use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 use std::str::FromStr;
 
+static mut G_MQTT_CONNECT_CLIENTID_BUFFER_ID: c_int = 0;
+static mut G_MQTT_CONNECT_USERNAME_BUFFER_ID: c_int = 0;
+static mut G_MQTT_CONNECT_PASSWORD_BUFFER_ID: c_int = 0;
+static mut G_MQTT_CONNECT_WILLTOPIC_BUFFER_ID: c_int = 0;
+static mut G_MQTT_CONNECT_WILLMESSAGE_BUFFER_ID: c_int = 0;
+static mut G_MQTT_AUTH_METHOD_BUFFER_ID: c_int = 0;
+static mut G_MQTT_PUBLISH_TOPIC_BUFFER_ID: c_int = 0;
+static mut G_MQTT_PUBLISH_MESSAGE_BUFFER_ID: c_int = 0;
+static mut G_MQTT_USER_PROPERTY_NAME_BUFFER_ID: c_int = 0;
+static mut G_MQTT_USER_PROPERTY_VALUE_BUFFER_ID: c_int = 0;
+static mut G_MQTT_CONNACK_SESSION_PRESENT_KW_ID: u16 = 0;
+static mut G_MQTT_CONNACK_SESSION_PRESENT_BUFFER_ID: c_int = 0;
+
 #[derive(FromPrimitive, Debug, Copy, Clone, PartialOrd, PartialEq)]
 #[allow(non_camel_case_types)]
 #[repr(u8)]
@@ -142,181 +162,106 @@ pub extern "C" fn rs_mqtt_tx_has_connect_flags(
     return 0;
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_get_connect_clientid(
-    tx: &MQTTTransaction,
-    buffer: *mut *const u8,
-    buffer_len: *mut u32,
-) -> u8 {
-    for msg in tx.msg.iter() {
-        if let MQTTOperation::CONNECT(ref cv) = msg.op {
-            let p = &cv.client_id;
-            if p.len() > 0 {
-                *buffer = p.as_ptr();
-                *buffer_len = p.len() as u32;
-                return 1;
-            }
+/// Set the inspection buffer to `data` if there is one.
+unsafe fn set_buffer(data: Option<&[u8]>, buf: *mut *const u8, len: *mut u32) -> bool {
+    match data {
+        Some(data) => {
+            *buf = data.as_ptr();
+            *len = data.len() as u32;
+            true
         }
+        None => false,
     }
-
-    *buffer = ptr::null();
-    *buffer_len = 0;
-    return 0;
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_get_connect_username(
-    tx: &MQTTTransaction,
-    buffer: *mut *const u8,
-    buffer_len: *mut u32,
-) -> u8 {
+/// A field of the first CONNECT of the transaction which has a non
+/// empty one.
+fn tx_get_connect_field<F>(tx: &MQTTTransaction, field: F) -> Option<&[u8]>
+where
+    F: Fn(&MQTTConnectData) -> Option<&[u8]>,
+{
     for msg in tx.msg.iter() {
         if let MQTTOperation::CONNECT(ref cv) = msg.op {
-            if let Some(p) = &cv.username {
-                if p.len() > 0 {
-                    *buffer = p.as_ptr();
-                    *buffer_len = p.len() as u32;
-                    return 1;
-                }
+            match field(cv) {
+                Some(p) if !p.is_empty() => return Some(p),
+                _ => {}
             }
         }
     }
-
-    *buffer = ptr::null();
-    *buffer_len = 0;
-
-    return 0;
+    None
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_get_connect_password(
-    tx: &MQTTTransaction,
-    buffer: *mut *const u8,
-    buffer_len: *mut u32,
-) -> u8 {
+/// The topic or the message of the first PUBLISH of the transaction
+/// which has a non empty one.
+fn tx_get_publish_field<F>(tx: &MQTTTransaction, field: F) -> Option<&[u8]>
+where
+    F: Fn(&MQTTPublishData) -> &[u8],
+{
     for msg in tx.msg.iter() {
-        if let MQTTOperation::CONNECT(ref cv) = msg.op {
-            if let Some(p) = &cv.password {
-                if p.len() > 0 {
-                    *buffer = p.as_ptr();
-                    *buffer_len = p.len() as u32;
-                    return 1;
-                }
+        if let MQTTOperation::PUBLISH(ref pubv) = msg.op {
+            let p = field(pubv);
+            if !p.is_empty() {
+                return Some(p);
             }
         }
     }
-
-    *buffer = ptr::null();
-    *buffer_len = 0;
-    return 0;
+    None
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_get_connect_willtopic(
-    tx: &MQTTTransaction,
-    buffer: *mut *const u8,
-    buffer_len: *mut u32,
-) -> u8 {
-    for msg in tx.msg.iter() {
-        if let MQTTOperation::CONNECT(ref cv) = msg.op {
-            if let Some(p) = &cv.will_topic {
-                if p.len() > 0 {
-                    *buffer = p.as_ptr();
-                    *buffer_len = p.len() as u32;
-                    return 1;
-                }
-            }
-        }
-    }
-
-    *buffer = ptr::null();
-    *buffer_len = 0;
-
-    return 0;
+unsafe extern "C" fn mqtt_tx_get_connect_clientid(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    set_buffer(tx_get_connect_field(tx, |c| Some(c.client_id.as_bytes())), buf, len)
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_get_connect_willmessage(
-    tx: &MQTTTransaction,
-    buffer: *mut *const u8,
-    buffer_len: *mut u32,
-) -> u8 {
-    for msg in tx.msg.iter() {
-        if let MQTTOperation::CONNECT(ref cv) = msg.op {
-            if let Some(p) = &cv.will_message {
-                if p.len() > 0 {
-                    *buffer = p.as_ptr();
-                    *buffer_len = p.len() as u32;
-                    return 1;
-                }
-            }
-        }
-    }
-
-    *buffer = ptr::null();
-    *buffer_len = 0;
-
-    return 0;
+unsafe extern "C" fn mqtt_tx_get_connect_username(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    set_buffer(tx_get_connect_field(tx, |c| c.username.as_ref().map(|u| u.as_bytes())), buf, len)
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_get_connack_sessionpresent(
-    tx: &MQTTTransaction,
-    session_present: *mut bool,
-) -> u8 {
-    for msg in tx.msg.iter() {
-        if let MQTTOperation::CONNACK(ref ca) = msg.op {
-            *session_present = ca.session_present;
-            return 1;
-        }
-    }
-    return 0;
+unsafe extern "C" fn mqtt_tx_get_connect_password(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    set_buffer(tx_get_connect_field(tx, |c| c.password.as_ref().map(|p| &p[..])), buf, len)
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_get_publish_topic(
-    tx: &MQTTTransaction,
-    buffer: *mut *const u8,
-    buffer_len: *mut u32,
-) -> u8 {
-    for msg in tx.msg.iter() {
-        if let MQTTOperation::PUBLISH(ref pubv) = msg.op {
-            let p = &pubv.topic;
-            if p.len() > 0 {
-                *buffer = p.as_ptr();
-                *buffer_len = p.len() as u32;
-                return 1;
-            }
-        }
-    }
-
-    *buffer = ptr::null();
-    *buffer_len = 0;
+unsafe extern "C" fn mqtt_tx_get_connect_willtopic(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    set_buffer(tx_get_connect_field(tx, |c| c.will_topic.as_ref().map(|t| t.as_bytes())), buf, len)
+}
 
-    return 0;
+unsafe extern "C" fn mqtt_tx_get_connect_willmessage(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    set_buffer(tx_get_connect_field(tx, |c| c.will_message.as_ref().map(|m| &m[..])), buf, len)
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_get_publish_message(
-    tx: &MQTTTransaction,
-    buffer: *mut *const u8,
-    buffer_len: *mut u32,
-) -> u8 {
-    for msg in tx.msg.iter() {
-        if let MQTTOperation::PUBLISH(ref pubv) = msg.op {
-            let p = &pubv.message;
-            if p.len() > 0 {
-                *buffer = p.as_ptr();
-                *buffer_len = p.len() as u32;
-                return 1;
-            }
-        }
-    }
+unsafe extern "C" fn mqtt_tx_get_auth_method(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    set_buffer(tx.auth_method().map(|m| m.as_bytes()), buf, len)
+}
 
-    *buffer = ptr::null();
-    *buffer_len = 0;
+unsafe extern "C" fn mqtt_tx_get_publish_topic(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    set_buffer(tx_get_publish_field(tx, |p| p.topic.as_bytes()), buf, len)
+}
 
-    return 0;
+unsafe extern "C" fn mqtt_tx_get_publish_message(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    set_buffer(tx_get_publish_field(tx, |p| &p.message), buf, len)
 }
 
 #[no_mangle]
@@ -407,7 +352,9 @@ pub unsafe extern "C" fn rs_mqtt_tx_get_reason_code(
                     return 1;
                 }
             }
-            _ => return 0
+            // the request of the transaction, like the CONNECT of a
+            // CONNACK, comes first
+            _ => {}
         }
     }
     return 0;
@@ -432,6 +379,346 @@ pub extern "C" fn rs_mqtt_tx_unsuback_has_reason_code(
     return 0;
 }
 
+unsafe extern "C" fn mqtt_tx_get_user_property_name(
+    tx: *mut c_void, _flow_flags: u8, local_id: u32, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    let prop = tx.user_properties().nth(local_id as usize);
+    set_buffer(prop.map(|p| p.0.as_bytes()), buf, len)
+}
+
+unsafe extern "C" fn mqtt_tx_get_user_property_value(
+    tx: *mut c_void, _flow_flags: u8, local_id: u32, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    let prop = tx.user_properties().nth(local_id as usize);
+    set_buffer(prop.map(|p| p.1.as_bytes()), buf, len)
+}
+
+unsafe extern "C" fn mqtt_connect_clientid_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, mqtt_tx_get_connect_clientid)
+}
+
+unsafe extern "C" fn mqtt_connect_username_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, mqtt_tx_get_connect_username)
+}
+
+unsafe extern "C" fn mqtt_connect_password_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, mqtt_tx_get_connect_password)
+}
+
+unsafe extern "C" fn mqtt_connect_clientid_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_CONNECT_CLIENTID_BUFFER_ID)
+}
+
+unsafe extern "C" fn mqtt_connect_username_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_CONNECT_USERNAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn mqtt_connect_password_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_CONNECT_PASSWORD_BUFFER_ID)
+}
+
+unsafe extern "C" fn mqtt_connect_willtopic_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, mqtt_tx_get_connect_willtopic)
+}
+
+unsafe extern "C" fn mqtt_connect_willmessage_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, mqtt_tx_get_connect_willmessage)
+}
+
+unsafe extern "C" fn mqtt_connect_willtopic_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_CONNECT_WILLTOPIC_BUFFER_ID)
+}
+
+unsafe extern "C" fn mqtt_connect_willmessage_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_CONNECT_WILLMESSAGE_BUFFER_ID)
+}
+
+unsafe extern "C" fn mqtt_auth_method_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, mqtt_tx_get_auth_method)
+}
+
+unsafe extern "C" fn mqtt_auth_method_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_AUTH_METHOD_BUFFER_ID)
+}
+
+unsafe extern "C" fn mqtt_publish_topic_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, mqtt_tx_get_publish_topic)
+}
+
+unsafe extern "C" fn mqtt_publish_message_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, mqtt_tx_get_publish_message)
+}
+
+unsafe extern "C" fn mqtt_publish_topic_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_PUBLISH_TOPIC_BUFFER_ID)
+}
+
+unsafe extern "C" fn mqtt_publish_message_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_PUBLISH_MESSAGE_BUFFER_ID)
+}
+
+unsafe extern "C" fn mqtt_user_property_name_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_USER_PROPERTY_NAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn mqtt_user_property_value_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_MQTT, G_MQTT_USER_PROPERTY_VALUE_BUFFER_ID)
+}
+
+/// Parse the argument of `mqtt.connack.session_present`.
+fn parse_session_present(arg: &str) -> Option<bool> {
+    match arg.trim() {
+        "yes" | "true" => Some(true),
+        "no" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+unsafe extern "C" fn mqtt_connack_session_present_match(
+    _det_ctx: *mut c_void, _f: *mut c_void, _flags: u8, _state: *mut c_void, tx: *mut c_void,
+    _s: *const c_void, ctx: *const c_void,
+) -> c_int {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    let ctx = &*(ctx as *const bool);
+    (tx.session_present() == Some(*ctx)) as c_int
+}
+
+unsafe extern "C" fn mqtt_connack_session_present_setup(
+    _de: *mut c_void, s: *mut c_void, raw: *const c_char,
+) -> c_int {
+    if raw.is_null() {
+        return -1;
+    }
+    let ctx = match CStr::from_ptr(raw).to_str().ok().and_then(parse_session_present) {
+        Some(ctx) => ctx,
+        None => return -1,
+    };
+    helper_setup_tx_match(
+        s, ALPROTO_MQTT, G_MQTT_CONNACK_SESSION_PRESENT_KW_ID,
+        G_MQTT_CONNACK_SESSION_PRESENT_BUFFER_ID, ctx)
+}
+
+unsafe extern "C" fn mqtt_connack_session_present_free(_de: *mut c_void, ctx: *mut c_void) {
+    helper_free_ctx::<bool>(ctx);
+}
+
+/// Register the MQTT keywords implemented in Rust. PUBLISH messages are
+/// sent by the clients, and by the broker to the subscribers, so their
+/// buffers are registered for both directions.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "mqtt.connack.session_present",
+        desc: "match MQTT CONNACK session present flag",
+        url: "/rules/mqtt-keywords.html#mqtt-connack-session-present",
+        flags: 0,
+        setup: mqtt_connack_session_present_setup,
+        free: Some(mqtt_connack_session_present_free),
+        tx_match: Some(mqtt_connack_session_present_match),
+    };
+    if let Some(kw_id) = helper_keyword_register(&kw) {
+        G_MQTT_CONNACK_SESSION_PRESENT_KW_ID = kw_id;
+        // a CONNACK without CONNECT is a transaction to the client
+        G_MQTT_CONNACK_SESSION_PRESENT_BUFFER_ID = helper_buffer_register(
+            "mqtt.connack.session_present", ALPROTO_MQTT, true, true, 1);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.connect.clientid",
+        desc: "sticky buffer to match on the MQTT CONNECT client ID",
+        url: "/rules/mqtt-keywords.html#mqtt-connect-clientid",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_connect_clientid_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_CONNECT_CLIENTID_BUFFER_ID = helper_buffer_mpm_register(
+            "mqtt.connect.clientid", "MQTT CONNECT client ID", ALPROTO_MQTT, false, true, 1,
+            mqtt_connect_clientid_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.connect.username",
+        desc: "sticky buffer to match on the MQTT CONNECT username",
+        url: "/rules/mqtt-keywords.html#mqtt-connect-username",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_connect_username_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_CONNECT_USERNAME_BUFFER_ID = helper_buffer_mpm_register(
+            "mqtt.connect.username", "MQTT CONNECT username", ALPROTO_MQTT, false, true, 1,
+            mqtt_connect_username_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.connect.password",
+        desc: "sticky buffer to match on the MQTT CONNECT password",
+        url: "/rules/mqtt-keywords.html#mqtt-connect-password",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_connect_password_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_CONNECT_PASSWORD_BUFFER_ID = helper_buffer_mpm_register(
+            "mqtt.connect.password", "MQTT CONNECT password", ALPROTO_MQTT, false, true, 1,
+            mqtt_connect_password_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.connect.willtopic",
+        desc: "sticky buffer to match on the MQTT CONNECT will topic",
+        url: "/rules/mqtt-keywords.html#mqtt-connect-willtopic",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_connect_willtopic_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_CONNECT_WILLTOPIC_BUFFER_ID = helper_buffer_mpm_register(
+            "mqtt.connect.willtopic", "MQTT CONNECT will topic", ALPROTO_MQTT, false, true, 1,
+            mqtt_connect_willtopic_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.connect.willmessage",
+        desc: "sticky buffer to match on the MQTT CONNECT will message",
+        url: "/rules/mqtt-keywords.html#mqtt-connect-willmessage",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_connect_willmessage_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_CONNECT_WILLMESSAGE_BUFFER_ID = helper_buffer_mpm_register(
+            "mqtt.connect.willmessage", "MQTT CONNECT will message", ALPROTO_MQTT, false, true, 1,
+            mqtt_connect_willmessage_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.auth.method",
+        desc: "sticky buffer to match on the MQTT 5 authentication method",
+        url: "/rules/mqtt-keywords.html#mqtt-auth-method",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_auth_method_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_AUTH_METHOD_BUFFER_ID = helper_buffer_mpm_register(
+            "mqtt.auth.method", "MQTT authentication method", ALPROTO_MQTT, true, true, 1,
+            mqtt_auth_method_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.publish.topic",
+        desc: "sticky buffer to match on the MQTT PUBLISH topic",
+        url: "/rules/mqtt-keywords.html#mqtt-publish-topic",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_publish_topic_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_PUBLISH_TOPIC_BUFFER_ID = helper_buffer_mpm_register(
+            "mqtt.publish.topic", "MQTT PUBLISH topic", ALPROTO_MQTT, true, true, 1,
+            mqtt_publish_topic_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.publish.message",
+        desc: "sticky buffer to match on the MQTT PUBLISH message",
+        url: "/rules/mqtt-keywords.html#mqtt-publish-message",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_publish_message_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_PUBLISH_MESSAGE_BUFFER_ID = helper_buffer_mpm_register(
+            "mqtt.publish.message", "MQTT PUBLISH message", ALPROTO_MQTT, true, true, 1,
+            mqtt_publish_message_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.user_property.name",
+        desc: "sticky buffer to match on the names of the MQTT 5 user properties",
+        url: "/rules/mqtt-keywords.html#mqtt-user-property-name",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_user_property_name_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_USER_PROPERTY_NAME_BUFFER_ID = helper_multi_buffer_mpm_register(
+            "mqtt.user_property.name", "MQTT user property name", ALPROTO_MQTT, true, true, 1,
+            mqtt_tx_get_user_property_name);
+    }
+
+    let kw = DetectKeyword {
+        name: "mqtt.user_property.value",
+        desc: "sticky buffer to match on the values of the MQTT 5 user properties",
+        url: "/rules/mqtt-keywords.html#mqtt-user-property-value",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: mqtt_user_property_value_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_MQTT_USER_PROPERTY_VALUE_BUFFER_ID = helper_multi_buffer_mpm_register(
+            "mqtt.user_property.value", "MQTT user property value", ALPROTO_MQTT, true, true, 1,
+            mqtt_tx_get_user_property_value);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -453,6 +740,7 @@ mod test {
             op: MQTTOperation::UNSUBSCRIBE(MQTTUnsubscribeData {
                 message_id: 1,
                 topics: vec!["foo".to_string(), "baar".to_string()],
+                invalid_utf8: false,
                 properties: None,
             }),
         });
@@ -467,6 +755,7 @@ mod test {
             op: MQTTOperation::UNSUBSCRIBE(MQTTUnsubscribeData {
                 message_id: 1,
                 topics: vec!["fieee".to_string(), "baaaaz".to_string()],
+                invalid_utf8: false,
                 properties: None,
             }),
         });
@@ -507,11 +796,15 @@ mod test {
                 topics: vec![
                     MQTTSubscribeTopicData {
                         topic_name: "foo".to_string(),
+                        invalid_utf8: false,
                         qos: 0,
+                        options: None,
                     },
                     MQTTSubscribeTopicData {
                         topic_name: "baar".to_string(),
+                        invalid_utf8: false,
                         qos: 1,
+                        options: None,
                     }],
                 properties: None,
             }),
@@ -529,11 +822,15 @@ mod test {
                 topics: vec![
                     MQTTSubscribeTopicData {
                         topic_name: "fieee".to_string(),
+                        invalid_utf8: false,
                         qos: 0,
+                        options: None,
                     },
                     MQTTSubscribeTopicData {
                         topic_name: "baaaaz".to_string(),
+                        invalid_utf8: false,
                         qos: 1,
+                        options: None,
                     }],
                 properties: None,
             }),
@@ -559,4 +856,182 @@ mod test {
         r = unsafe{rs_mqtt_tx_get_subscribe_topic(&t, 4, &mut s, &mut slen)};
         assert_eq!(r, 0);
     }
+
+    #[test]
+    fn test_publish_buffers() {
+        let mut t = MQTTTransaction::new(MQTTMessage {
+            header: FixedHeader {
+                message_type: MQTTTypeCode::PUBLISH,
+                dup_flag: false,
+                qos_level: 0,
+                retain: false,
+                remaining_length: 0,
+            },
+            op: MQTTOperation::PUBLISH(MQTTPublishData {
+                topic: "sensors/temp".to_string(),
+                topic_invalid_utf8: false,
+                message_id: None,
+                message: b"21.5".to_vec(),
+                properties: None,
+                skipped_length: None,
+            }),
+        });
+        let tx = &mut t as *mut MQTTTransaction as *mut c_void;
+        let mut s: *const u8 = std::ptr::null_mut();
+        let mut slen: u32 = 0;
+        assert!(unsafe{mqtt_tx_get_publish_topic(tx, 0, &mut s, &mut slen)});
+        assert_eq!(unsafe{build_slice!(s, slen as usize)}, b"sensors/temp");
+        assert!(unsafe{mqtt_tx_get_publish_message(tx, 0, &mut s, &mut slen)});
+        assert_eq!(unsafe{build_slice!(s, slen as usize)}, b"21.5");
+
+        t.msg.clear();
+        let tx = &mut t as *mut MQTTTransaction as *mut c_void;
+        assert!(!unsafe{mqtt_tx_get_publish_topic(tx, 0, &mut s, &mut slen)});
+        assert!(!unsafe{mqtt_tx_get_publish_message(tx, 0, &mut s, &mut slen)});
+    }
+
+    #[test]
+    fn test_connect_buffers() {
+        let t = MQTTTransaction::new(MQTTMessage {
+            header: FixedHeader {
+                message_type: MQTTTypeCode::CONNECT,
+                dup_flag: false,
+                qos_level: 0,
+                retain: false,
+                remaining_length: 0,
+            },
+            op: MQTTOperation::CONNECT(MQTTConnectData {
+                protocol_string: "MQTT".to_string(),
+                protocol_version: 4,
+                username_flag: true,
+                password_flag: false,
+                will_retain: false,
+                will_qos: 0,
+                will_flag: true,
+                clean_session: true,
+                keepalive: 60,
+                client_id: "sensor-1".to_string(),
+                will_topic: Some("status/sensor-1".to_string()),
+                will_topic_invalid_utf8: false,
+                will_message: Some(b"offline".to_vec()),
+                username: Some("admin".to_string()),
+                password: None,
+                properties: None,
+                will_properties: None,
+            }),
+        });
+        let tx = &t as *const MQTTTransaction as *mut c_void;
+        let mut s: *const u8 = std::ptr::null_mut();
+        let mut slen: u32 = 0;
+        assert!(unsafe{mqtt_tx_get_connect_clientid(tx, 0, &mut s, &mut slen)});
+        assert_eq!(unsafe{build_slice!(s, slen as usize)}, b"sensor-1");
+        assert!(unsafe{mqtt_tx_get_connect_username(tx, 0, &mut s, &mut slen)});
+        assert_eq!(unsafe{build_slice!(s, slen as usize)}, b"admin");
+        assert!(!unsafe{mqtt_tx_get_connect_password(tx, 0, &mut s, &mut slen)});
+        assert!(unsafe{mqtt_tx_get_connect_willtopic(tx, 0, &mut s, &mut slen)});
+        assert_eq!(unsafe{build_slice!(s, slen as usize)}, b"status/sensor-1");
+        assert!(unsafe{mqtt_tx_get_connect_willmessage(tx, 0, &mut s, &mut slen)});
+        assert_eq!(unsafe{build_slice!(s, slen as usize)}, b"offline");
+    }
+
+    #[test]
+    fn test_connack_fields() {
+        let mut t = MQTTTransaction::new(MQTTMessage {
+            header: FixedHeader {
+                message_type: MQTTTypeCode::CONNECT,
+                dup_flag: false,
+                qos_level: 0,
+                retain: false,
+                remaining_length: 0,
+            },
+            op: MQTTOperation::CONNECT(MQTTConnectData {
+                protocol_string: "MQTT".to_string(),
+                protocol_version: 4,
+                username_flag: false,
+                password_flag: false,
+                will_retain: false,
+                will_qos: 0,
+                will_flag: false,
+                clean_session: false,
+                keepalive: 60,
+                client_id: "sensor-1".to_string(),
+                will_topic: None,
+                will_topic_invalid_utf8: false,
+                will_message: None,
+                username: None,
+                password: None,
+                properties: None,
+                will_properties: None,
+            }),
+        });
+        let mut code = 0xff;
+        assert_eq!(t.session_present(), None);
+        assert_eq!(unsafe{rs_mqtt_tx_get_reason_code(&t, &mut code)}, 0);
+
+        t.msg.push(MQTTMessage {
+            header: FixedHeader {
+                message_type: MQTTTypeCode::CONNACK,
+                dup_flag: false,
+                qos_level: 0,
+                retain: false,
+                remaining_length: 2,
+            },
+            op: MQTTOperation::CONNACK(MQTTConnackData {
+                return_code: 0,
+                session_present: true,
+                properties: None,
+            }),
+        });
+        assert_eq!(t.session_present(), Some(true));
+        assert_eq!(t.connack_return_code(), Some(0));
+        // the reason code of the CONNACK, after the CONNECT
+        assert_eq!(unsafe{rs_mqtt_tx_get_reason_code(&t, &mut code)}, 1);
+        assert_eq!(code, 0);
+
+        assert_eq!(parse_session_present("yes"), Some(true));
+        assert_eq!(parse_session_present(" false"), Some(false));
+        assert_eq!(parse_session_present("maybe"), None);
+    }
+
+    #[test]
+    fn test_connack_session_present_match() {
+        let connack = |session_present: bool| MQTTMessage {
+            header: FixedHeader {
+                message_type: MQTTTypeCode::CONNACK,
+                dup_flag: false,
+                qos_level: 0,
+                retain: false,
+                remaining_length: 2,
+            },
+            op: MQTTOperation::CONNACK(MQTTConnackData {
+                return_code: 0,
+                session_present,
+                properties: None,
+            }),
+        };
+        let mut resumed = MQTTTransaction::new(connack(true));
+        let mut clean = MQTTTransaction::new(connack(false));
+        let mut ping = MQTTTransaction::new(MQTTMessage {
+            header: FixedHeader {
+                message_type: MQTTTypeCode::PINGREQ,
+                dup_flag: false,
+                qos_level: 0,
+                retain: false,
+                remaining_length: 0,
+            },
+            op: MQTTOperation::PINGREQ,
+        });
+        let matches = |tx: &mut MQTTTransaction, ctx: bool| unsafe {
+            mqtt_connack_session_present_match(
+                std::ptr::null_mut(), std::ptr::null_mut(), 0, std::ptr::null_mut(),
+                tx as *mut _ as *mut c_void, std::ptr::null(), &ctx as *const _ as *const c_void)
+        };
+        assert_eq!(matches(&mut resumed, true), 1);
+        assert_eq!(matches(&mut resumed, false), 0);
+        assert_eq!(matches(&mut clean, true), 0);
+        assert_eq!(matches(&mut clean, false), 1);
+        // a transaction without CONNACK matches neither
+        assert_eq!(matches(&mut ping, true), 0);
+        assert_eq!(matches(&mut ping, false), 0);
+    }
 }
@@ -20,6 +20,7 @@
 use std;
 use super::mqtt::{MQTTTransaction, MQTTState};
 use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::applayer::cast_mut;
 use crate::mqtt::mqtt_message::{MQTTOperation, MQTTSubscribeTopicData};
 use crate::mqtt::parser::{FixedHeader};
 
@@ -31,6 +32,11 @@ fn log_mqtt_topic(js: &mut JsonBuilder, t: &MQTTSubscribeTopicData) -> Result<()
     js.start_object()?;
     js.set_string("topic", &t.topic_name)?;
     js.set_uint("qos", t.qos as u64)?;
+    if let Some(options) = &t.options {
+        js.set_bool("no_local", options.no_local)?;
+        js.set_bool("retain_as_published", options.retain_as_published)?;
+        js.set_uint("retain_handling", options.retain_handling as u64)?;
+    }
     js.close()?;
     return Ok(());
 }
@@ -44,8 +50,16 @@ fn log_mqtt_header(js: &mut JsonBuilder, hdr: &FixedHeader) -> Result<(), JsonEr
     return Ok(());
 }
 
-fn log_mqtt(tx: &MQTTTransaction, flags: u32, js: &mut JsonBuilder) -> Result<(), JsonError> {
+fn log_mqtt(state: &MQTTState, tx: &MQTTTransaction, flags: u32, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    let log_payload = tx.log_payload();
     js.open_object("mqtt")?;
+    // the session of the flow, to relate its messages to other flows
+    if let Some(client_id) = state.client_id() {
+        js.set_string("client_id", client_id)?;
+    }
+    if let Some(method) = tx.auth_method() {
+        js.set_string("auth_method", method)?;
+    }
     for msg in tx.msg.iter() {
         match msg.op {
             MQTTOperation::CONNECT(ref conn) => {
@@ -75,7 +89,9 @@ fn log_mqtt(tx: &MQTTTransaction, flags: u32, js: &mut JsonBuilder) -> Result<()
                         js.set_string("topic", will_topic)?;
                     }
                     if let Some(will_message) = &conn.will_message {
-                        js.set_string_from_bytes("message", will_message)?;
+                        if log_payload {
+                            js.set_string_from_bytes("message", will_message)?;
+                        }
                     }
                     if let Some(will_properties) = &conn.will_properties {
                         js.open_object("properties")?;
@@ -116,7 +132,13 @@ fn log_mqtt(tx: &MQTTTransaction, flags: u32, js: &mut JsonBuilder) -> Result<()
                 if let Some(message_id) = publish.message_id {
                     js.set_uint("message_id", message_id as u64)?;
                 }
-                js.set_string_from_bytes("message", &publish.message)?;
+                if log_payload {
+                    js.set_string_from_bytes("message", &publish.message)?;
+                }
+                if let Some(skipped_length) = publish.skipped_length {
+                    js.set_bool("truncated", true)?;
+                    js.set_uint("skipped_length", skipped_length as u64)?;
+                }
                 if let Some(properties) = &publish.properties {
                     js.open_object("properties")?;
                     for prop in properties {
@@ -217,6 +239,13 @@ fn log_mqtt(tx: &MQTTTransaction, flags: u32, js: &mut JsonBuilder) -> Result<()
                     js.append_uint(*t as u64)?;
                 }
                 js.close()?; // qos_granted
+                if let Some(properties) = &suback.properties {
+                    js.open_object("properties")?;
+                    for prop in properties {
+                        prop.set_json(js)?;
+                    }
+                    js.close()?; // properties
+                }
                 js.close()?; // suback
             }
             MQTTOperation::UNSUBSCRIBE(ref unsub) => {
@@ -228,6 +257,13 @@ fn log_mqtt(tx: &MQTTTransaction, flags: u32, js: &mut JsonBuilder) -> Result<()
                     js.append_string(t)?;
                 }
                 js.close()?; // topics
+                if let Some(properties) = &unsub.properties {
+                    js.open_object("properties")?;
+                    for prop in properties {
+                        prop.set_json(js)?;
+                    }
+                    js.close()?; // properties
+                }
                 js.close()?; // unsubscribe
             }
             MQTTOperation::UNSUBACK(ref unsuback) => {
@@ -241,6 +277,13 @@ fn log_mqtt(tx: &MQTTTransaction, flags: u32, js: &mut JsonBuilder) -> Result<()
                     }
                     js.close()?; // reason_codes
                 }
+                if let Some(properties) = &unsuback.properties {
+                    js.open_object("properties")?;
+                    for prop in properties {
+                        prop.set_json(js)?;
+                    }
+                    js.close()?; // properties
+                }
                 js.close()?; // unsuback
             }
             MQTTOperation::PINGREQ => {
@@ -297,7 +340,7 @@ fn log_mqtt(tx: &MQTTTransaction, flags: u32, js: &mut JsonBuilder) -> Result<()
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_logger_log(_state: &mut MQTTState, tx: *mut std::os::raw::c_void, flags: u32, js: &mut JsonBuilder) -> bool {
-    let tx = cast_pointer!(tx, MQTTTransaction);
-    log_mqtt(tx, flags, js).is_ok()
+pub unsafe extern "C" fn rs_mqtt_logger_log(state: &mut MQTTState, tx: *mut std::os::raw::c_void, flags: u32, js: &mut JsonBuilder) -> bool {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    log_mqtt(state, tx, flags, js).is_ok()
 }
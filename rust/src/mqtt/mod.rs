@@ -21,3 +21,5 @@ pub mod mqtt;
 pub mod mqtt_message;
 pub mod mqtt_property;
 pub mod parser;
+pub mod topic;
+pub mod websocket;
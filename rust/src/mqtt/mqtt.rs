@@ -18,12 +18,19 @@
 // written by Sascha Steinbiss <sascha@steinbiss.name>
 
 use super::mqtt_message::*;
+use super::mqtt_property::MQTTProperty;
 use super::parser::*;
-use crate::applayer::{self, LoggerFlags};
+use super::topic;
+use super::websocket::{self, WebSocketDeframer};
+use crate::applayer;
 use crate::applayer::*;
-use crate::core::{self, AppProto, Flow, ALPROTO_FAILED, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::conf::conf_get_app_layer_node;
+use crate::core::{self, AppProto, Direction, Flow, SuricataFileContext, ALPROTO_FAILED, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::filecontainer::{Files, FilesState};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
 use nom;
 use std;
+use std::collections::HashMap;
 use std::ffi::CString;
 
 // Used as a special pseudo packet identifier to denote the first CONNECT
@@ -34,10 +41,46 @@ const MQTT_CONNECT_PKT_ID: u32 = std::u32::MAX;
 // Maximum message length in bytes. If the length of a message exceeds
 // this value, it will be truncated. Default: 1MB.
 static mut MAX_MSG_LEN: u32 = 1048576;
+// Number of bytes of the message of a PUBLISH exceeding the maximum
+// message length that are kept for inspection. 0 disables it. Default: 0.
+static mut TRUNCATED_PAYLOAD_LEN: u32 = 0;
+// Time in seconds after which a transaction still waiting for its
+// response is considered complete. 0 disables expiry. Default: 60s.
+static mut PENDING_TIMEOUT: u64 = 60;
+// Number of retransmissions of a PUBLISH, with the DUP flag, tolerated
+// while it waits for its response. Default: 5.
+static mut MAX_RETRANSMISSIONS: u32 = 5;
 
-static mut ALPROTO_MQTT: AppProto = ALPROTO_UNKNOWN;
+// Maximum number of transactions waiting for a response, from max-tx.
+static MQTT_TX_LIMIT: TxLimit = TxLimit::new();
 
-#[derive(FromPrimitive, Debug, AppLayerEvent)]
+pub static mut ALPROTO_MQTT: AppProto = ALPROTO_UNKNOWN;
+
+pub static mut SURICATA_MQTT_FILE_CONFIG: Option<&'static SuricataFileContext> = None;
+
+declare_counters!(mqtt_stats, "app_layer.mqtt", {
+    connect,
+    connack,
+    publish,
+    puback,
+    pubrec,
+    pubrel,
+    pubcomp,
+    subscribe,
+    suback,
+    unsubscribe,
+    unsuback,
+    pingreq,
+    pingresp,
+    disconnect,
+    auth,
+    unassigned,
+    truncated,
+    malformed,
+    anomalies,
+});
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, AppLayerEvent)]
 pub enum MQTTEvent {
     MissingConnect,
     MissingPublish,
@@ -48,6 +91,20 @@ pub enum MQTTEvent {
     InvalidQosLevel,
     MissingMsgId,
     UnassignedMsgType,
+    TooManyTransactions,
+    UnknownTopicAlias,
+    InvalidTopicAlias,
+    MessageIdReuse,
+    TooManyRetransmissions,
+    InvalidSubscriptionOptions,
+    InvalidSharedSubscription,
+    WildcardSubscription,
+    SysTopicSubscription,
+    InvalidTopicUtf8,
+    TopicNulCharacter,
+    InvalidTopicName,
+    InvalidTopicFilter,
+    SessionTakeover,
 }
 
 #[derive(Debug)]
@@ -56,26 +113,28 @@ pub struct MQTTTransaction {
     pkt_id: Option<u32>,
     pub msg: Vec<MQTTMessage>,
     complete: bool,
-    toclient: bool,
-    toserver: bool,
+    direction: Direction,
+    // Number of retransmissions of the PUBLISH while it waits for its
+    // response.
+    retransmissions: u32,
 
-    logged: LoggerFlags,
-    de_state: Option<*mut core::DetectEngineState>,
+    de_state: DetectStateHolder,
     events: *mut core::AppLayerDecoderEvents,
     tx_data: applayer::AppLayerTxData,
 }
 
+unsafe impl FfiType for MQTTTransaction {}
+
 impl MQTTTransaction {
     pub fn new(msg: MQTTMessage) -> MQTTTransaction {
         let mut m = MQTTTransaction {
             tx_id: 0,
             pkt_id: None,
             complete: false,
-            logged: LoggerFlags::new(),
             msg: Vec::new(),
-            toclient: false,
-            toserver: false,
-            de_state: None,
+            direction: Direction::ToServer,
+            retransmissions: 0,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: applayer::AppLayerTxData::new(),
         };
@@ -83,13 +142,67 @@ impl MQTTTransaction {
         return m;
     }
 
+    /// Check if the message payloads of the transaction should be
+    /// logged, as configured through its tx config.
+    pub fn log_payload(&self) -> bool {
+        self.tx_data.config.log_payload(true)
+    }
+
+    /// The method of the enhanced authentication of MQTT 5, from the
+    /// properties of a CONNECT, CONNACK or AUTH.
+    pub fn auth_method(&self) -> Option<&str> {
+        self.properties().filter_map(|p| match p {
+            MQTTProperty::AUTHENTICATION_METHOD(v) => Some(v.as_str()),
+            _ => None,
+        }).next()
+    }
+
+    /// The data of the enhanced authentication, which the method defines.
+    pub fn auth_data(&self) -> Option<&[u8]> {
+        self.properties().filter_map(|p| match p {
+            MQTTProperty::AUTHENTICATION_DATA(v) => Some(&v[..]),
+            _ => None,
+        }).next()
+    }
+
+    /// The CONNACK of the transaction, the response to its CONNECT.
+    pub fn connack(&self) -> Option<&MQTTConnackData> {
+        self.msg.iter().filter_map(|m| match m.op {
+            MQTTOperation::CONNACK(ref connack) => Some(connack),
+            _ => None,
+        }).next()
+    }
+
+    /// Whether the server resumed a session it kept for the client,
+    /// rather than starting a new one.
+    pub fn session_present(&self) -> Option<bool> {
+        self.connack().map(|c| c.session_present)
+    }
+
+    /// The return code of the CONNACK, its reason code in MQTT 5. 0
+    /// accepts the connection.
+    pub fn connack_return_code(&self) -> Option<u8> {
+        self.connack().map(|c| c.return_code)
+    }
+
+    /// The MQTT 5 properties of the messages of the transaction.
+    pub fn properties(&self) -> impl Iterator<Item = &MQTTProperty> + '_ {
+        self.msg.iter().filter_map(|m| m.properties()).flat_map(|p| p.iter())
+    }
+
+    /// The name and value of the user properties of the messages of the
+    /// transaction.
+    pub fn user_properties(&self) -> impl Iterator<Item = &(String, String)> + '_ {
+        self.properties().filter_map(|p| match p {
+            MQTTProperty::USER_PROPERTY(pair) => Some(pair),
+            _ => None,
+        })
+    }
+
     pub fn free(&mut self) {
         if self.events != std::ptr::null_mut() {
             core::sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        if let Some(state) = self.de_state {
-            core::sc_detect_engine_state_free(state);
-        }
     }
 }
 
@@ -99,78 +212,439 @@ impl Drop for MQTTTransaction {
     }
 }
 
+impl Transaction for MQTTTransaction {
+    fn id(&self) -> u64 {
+        self.tx_id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        MQTTState::set_event(self, MQTTEvent::TooManyTransactions);
+    }
+}
+
+/// How the MQTT messages are carried on the connection.
+#[derive(Debug, PartialEq)]
+enum Framing {
+    Unknown,
+    Plain,
+    /// In the binary frames of a WebSocket connection, after an HTTP
+    /// upgrade.
+    WebSocket,
+}
+
+/// The client id and credentials of the first CONNECT of the flow with a
+/// client id, to recognize another client taking over its session.
+#[derive(Debug)]
+struct Session {
+    client_id: String,
+    username: Option<String>,
+    password: Option<Vec<u8>>,
+    clean_session: bool,
+}
+
+impl Session {
+    fn from_connect(conn: &MQTTConnectData) -> Option<Self> {
+        if conn.client_id.is_empty() {
+            // the server assigns the client id
+            return None;
+        }
+        Some(Session {
+            client_id: conn.client_id.clone(),
+            username: conn.username.clone(),
+            password: conn.password.clone(),
+            clean_session: conn.clean_session,
+        })
+    }
+
+    /// Whether a CONNECT reuses the client id with other credentials or
+    /// another clean session flag.
+    fn is_takeover(&self, conn: &MQTTConnectData) -> bool {
+        conn.client_id == self.client_id
+            && (conn.username != self.username
+                || conn.password != self.password
+                || conn.clean_session != self.clean_session)
+    }
+}
+
+/// The topic aliases of MQTT 5 set by the sender of a direction, see
+/// section 3.3.2.3.4 of the specification.
+#[derive(Debug, Default)]
+struct TopicAliases {
+    aliases: HashMap<u16, String>,
+    /// The Topic Alias Maximum announced by the receiver, None if its
+    /// CONNECT or CONNACK was not seen.
+    maximum: Option<u16>,
+}
+
+impl TopicAliases {
+    /// Set the maximum from the properties of a CONNECT or CONNACK. No
+    /// alias is allowed if the property is absent.
+    fn set_maximum(&mut self, properties: &Option<Vec<MQTTProperty>>) {
+        if let Some(ref properties) = properties {
+            let maximum = properties.iter().filter_map(|p| match p {
+                MQTTProperty::TOPIC_ALIAS_MAXIMUM(v) => Some(*v),
+                _ => None,
+            }).next();
+            self.maximum = Some(maximum.unwrap_or(0));
+        }
+    }
+
+    /// Record the alias set by a PUBLISH, or replace an empty topic by
+    /// the topic of its alias.
+    fn resolve(&mut self, publish: &mut MQTTPublishData) -> Result<(), MQTTEvent> {
+        let alias = match publish.properties {
+            Some(ref properties) => properties.iter().filter_map(|p| match p {
+                MQTTProperty::TOPIC_ALIAS(v) => Some(*v),
+                _ => None,
+            }).next(),
+            None => None,
+        };
+        let alias = match alias {
+            Some(alias) => alias,
+            None => return Ok(()),
+        };
+        if alias == 0 || self.maximum.map_or(false, |maximum| alias > maximum) {
+            return Err(MQTTEvent::InvalidTopicAlias);
+        }
+        if publish.topic.is_empty() {
+            match self.aliases.get(&alias) {
+                Some(topic) => publish.topic = topic.clone(),
+                None => return Err(MQTTEvent::UnknownTopicAlias),
+            }
+        } else {
+            self.aliases.insert(alias, publish.topic.clone());
+        }
+        Ok(())
+    }
+}
+
+// The key of a transaction waiting for a response: whether its request
+// was sent to the server, and its packet identifier. Each side numbers
+// its own requests, so the same identifier can be in use in both
+// directions at the same time.
+type PendingKey = (bool, u32);
+
+fn pending_key(direction: Direction, pkt_id: u32) -> PendingKey {
+    (direction.is_to_server(), pkt_id)
+}
+
 pub struct MQTTState {
-    tx_id: u64,
     pub protocol_version: u8,
-    transactions: Vec<MQTTTransaction>,
+    transactions: TxTable<MQTTTransaction>,
+    pending: PairingTable<PendingKey>,
     connected: bool,
     skip_request: usize,
     skip_response: usize,
+    // Set after a gap, until a message is found again.
+    request_gap: bool,
+    response_gap: bool,
     max_msg_len: usize,
+    max_prefix_len: usize,
+    // Time of the last purge, in seconds. Pending transactions are
+    // registered with this time, or with 0 before the first purge.
+    ts: u64,
+    // The payloads of the PUBLISH messages, created with the first one
+    // when file extraction is set up.
+    files: Option<Files>,
+    file_track_id: u32,
+    framing: Framing,
+    ws_ts: WebSocketDeframer,
+    ws_tc: WebSocketDeframer,
+    aliases_ts: TopicAliases,
+    aliases_tc: TopicAliases,
+    session: Option<Session>,
+}
+
+unsafe impl FfiType for MQTTState {}
+
+impl txtable::State for MQTTState {
+    type Tx = MQTTTransaction;
+
+    fn tx_table(&self) -> &TxTable<MQTTTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<MQTTTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl FilesState for MQTTState {
+    fn files(&mut self) -> &mut Files {
+        self.files.get_or_insert_with(Files::default)
+    }
 }
 
 impl MQTTState {
     pub fn new() -> Self {
         Self {
-            tx_id: 0,
             protocol_version: 0,
-            transactions: Vec::new(),
+            transactions: TxTable::new(),
+            pending: PairingTable::new(MQTT_TX_LIMIT.get() as usize,
+                                       unsafe { PENDING_TIMEOUT }),
             connected: false,
             skip_request: 0,
             skip_response: 0,
+            request_gap: false,
+            response_gap: false,
             max_msg_len: unsafe { MAX_MSG_LEN as usize },
+            max_prefix_len: unsafe { TRUNCATED_PAYLOAD_LEN as usize },
+            ts: 0,
+            files: None,
+            file_track_id: 1,
+            framing: Framing::Unknown,
+            ws_ts: WebSocketDeframer::default(),
+            ws_tc: WebSocketDeframer::default(),
+            aliases_ts: TopicAliases::default(),
+            aliases_tc: TopicAliases::default(),
+            session: None,
         }
     }
 
+    /// The client id of the session of the flow, if a CONNECT gave one.
+    pub fn client_id(&self) -> Option<&str> {
+        self.session.as_ref().map(|s| s.client_id.as_str())
+    }
+
     fn free_tx(&mut self, tx_id: u64) {
-        let len = self.transactions.len();
-        let mut found = false;
-        let mut index = 0;
-        for i in 0..len {
-            let tx = &self.transactions[i];
-            if tx.tx_id == tx_id + 1 {
-                found = true;
-                index = i;
-                break;
+        self.transactions.free(tx_id);
+        self.pending.remove_tx(tx_id + 1);
+    }
+
+    pub fn get_tx(&mut self, tx_id: u64) -> Option<&MQTTTransaction> {
+        self.transactions.get(tx_id)
+    }
+
+    // Get the transaction waiting for a response to its request sent in
+    // the given direction with the given packet identifier.
+    pub fn get_tx_by_pkt_id(&mut self, direction: Direction, pkt_id: u32)
+                            -> Option<&mut MQTTTransaction> {
+        let tx_id = self.pending.get(&pending_key(direction, pkt_id))?;
+        self.transactions.get_mut(tx_id - 1)
+    }
+
+    fn push_tx(&mut self, tx: MQTTTransaction) {
+        self.transactions.push(tx);
+    }
+
+    // Add a transaction that waits for a response with the given
+    // packet identifier. A request still waiting for a response with the
+    // same identifier won't get it, so it is complete. If too many
+    // requests are waiting, the oldest one is completed without its
+    // response, so it can be logged and freed.
+    fn push_pending_tx(&mut self, mut tx: MQTTTransaction, pkt_id: u32) {
+        tx.pkt_id = Some(pkt_id);
+        let key = pending_key(tx.direction, pkt_id);
+        let reused = self.pending.get(&key);
+        for tx_id in self.pending.insert(key, tx.tx_id, self.ts) {
+            if let Some(old) = self.transactions.get_mut(tx_id - 1) {
+                old.complete = true;
+                old.pkt_id = None;
+                if Some(tx_id) != reused {
+                    SCLogDebug!("too many transactions, completing tx {}", tx_id);
+                    MQTTState::set_event(old, MQTTEvent::TooManyTransactions);
+                } else if pkt_id != MQTT_CONNECT_PKT_ID {
+                    MQTTState::set_event(&mut tx, MQTTEvent::MessageIdReuse);
+                }
             }
         }
-        if found {
-            self.transactions.remove(index);
+        self.push_tx(tx);
+    }
+
+    // Count a retransmission of the PUBLISH waiting for a response with
+    // the given packet identifier. Returns None if there is none, or
+    // whether it was retransmitted too many times.
+    fn retransmit_pending_tx(&mut self, pkt_id: u32, direction: Direction) -> Option<bool> {
+        let tx = self.get_tx_by_pkt_id(direction, pkt_id)?;
+        match tx.msg.first().map(|m| &m.op) {
+            Some(MQTTOperation::PUBLISH(_)) => {}
+            _ => return None,
         }
+        tx.retransmissions += 1;
+        Some(tx.retransmissions == unsafe { MAX_RETRANSMISSIONS } + 1)
     }
 
-    pub fn get_tx(&mut self, tx_id: u64) -> Option<&MQTTTransaction> {
-        for tx in &mut self.transactions {
-            if tx.tx_id == tx_id + 1 {
-                return Some(tx);
+    // Mark the transaction waiting for a response to its request sent in
+    // the given direction with the given packet identifier as complete.
+    fn complete_pending_tx(&mut self, direction: Direction, pkt_id: u32) {
+        if let Some(tx_id) = self.pending.take(&pending_key(direction, pkt_id)) {
+            if let Some(tx) = self.transactions.get_mut(tx_id - 1) {
+                tx.complete = true;
+                tx.pkt_id = None;
+            }
+        }
+    }
+
+    // Mark the transactions that have been waiting for a response for
+    // longer than the pending timeout as complete, so they can be logged
+    // and freed.
+    fn purge(&mut self, ts: u64) {
+        self.ts = ts;
+        self.pending.set_unknown_ts(ts);
+        let expired = self.pending.expire(ts);
+        if expired.is_empty() {
+            return;
+        }
+        for tx_id in expired {
+            if let Some(tx) = self.transactions.get_mut(tx_id - 1) {
+                SCLogDebug!("expiring pending tx {}", tx_id);
+                tx.complete = true;
+                tx.pkt_id = None;
             }
         }
-        return None;
     }
 
-    pub fn get_tx_by_pkt_id(&mut self, pkt_id: u32) -> Option<&mut MQTTTransaction> {
-        for tx in &mut self.transactions {
-            if !tx.complete {
-                if let Some(mpktid) = tx.pkt_id {
-                    if mpktid == pkt_id {
-                        return Some(tx);
+    fn new_tx(&mut self, msg: MQTTMessage, direction: Direction) -> MQTTTransaction {
+        let mut tx = MQTTTransaction::new(msg);
+        tx.tx_id = self.transactions.next_id();
+        tx.direction = direction;
+        self.resolve_topic_alias(&mut tx);
+        MQTTState::check_topics(&mut tx);
+        self.publish_file(&mut tx);
+        return tx;
+    }
+
+    // Check the topic names and filters of a message for errors, and
+    // the subscriptions for subscribing to more than usual.
+    fn check_topics(tx: &mut MQTTTransaction) {
+        let mut events = Vec::new();
+        match tx.msg[0].op {
+            MQTTOperation::CONNECT(ref connect) => {
+                if let Some(ref will_topic) = connect.will_topic {
+                    MQTTState::check_topic_name(will_topic, connect.will_topic_invalid_utf8,
+                                                &mut events);
+                }
+            }
+            MQTTOperation::PUBLISH(ref publish) => {
+                MQTTState::check_topic_name(&publish.topic, publish.topic_invalid_utf8,
+                                            &mut events);
+            }
+            MQTTOperation::SUBSCRIBE(ref subscribe) => {
+                for t in &subscribe.topics {
+                    MQTTState::check_subscription(t, &mut events);
+                }
+            }
+            MQTTOperation::UNSUBSCRIBE(ref unsubscribe) => {
+                if unsubscribe.invalid_utf8 {
+                    events.push(MQTTEvent::InvalidTopicUtf8);
+                }
+                for t in &unsubscribe.topics {
+                    MQTTState::check_topic_filter(t, &mut events);
+                }
+            }
+            _ => {}
+        }
+        for (i, event) in events.iter().enumerate() {
+            if !events[..i].contains(event) {
+                MQTTState::set_event(tx, *event);
+            }
+        }
+    }
+
+    fn check_topic_name(name: &str, invalid_utf8: bool, events: &mut Vec<MQTTEvent>) {
+        if invalid_utf8 {
+            events.push(MQTTEvent::InvalidTopicUtf8);
+        }
+        if topic::has_nul(name) {
+            events.push(MQTTEvent::TopicNulCharacter);
+        }
+        if !topic::is_valid_name(name) {
+            events.push(MQTTEvent::InvalidTopicName);
+        }
+    }
+
+    fn check_topic_filter(filter: &str, events: &mut Vec<MQTTEvent>) {
+        if topic::has_nul(filter) {
+            events.push(MQTTEvent::TopicNulCharacter);
+        }
+        if !topic::is_valid_filter(filter) {
+            events.push(MQTTEvent::InvalidTopicFilter);
+        }
+    }
+
+    fn check_subscription(t: &MQTTSubscribeTopicData, events: &mut Vec<MQTTEvent>) {
+        if t.invalid_utf8 {
+            events.push(MQTTEvent::InvalidTopicUtf8);
+        }
+        let mut filter = t.topic_name.as_str();
+        // shared subscriptions and the options come with MQTT 5
+        if let Some(ref options) = t.options {
+            if !options.is_valid() {
+                events.push(MQTTEvent::InvalidSubscriptionOptions);
+            }
+            match topic::parse_shared(filter) {
+                Ok(shared) => {
+                    // no local is an error on shared subscriptions
+                    if shared.share_name.is_some() && options.no_local {
+                        events.push(MQTTEvent::InvalidSharedSubscription);
                     }
+                    filter = shared.filter;
                 }
+                Err(_) => events.push(MQTTEvent::InvalidSharedSubscription),
             }
         }
-        return None;
+        MQTTState::check_topic_filter(filter, events);
+        if topic::is_match_all(filter) {
+            events.push(MQTTEvent::WildcardSubscription);
+        }
+        if topic::is_sys(filter) {
+            events.push(MQTTEvent::SysTopicSubscription);
+        }
     }
 
-    fn new_tx(&mut self, msg: MQTTMessage, toclient: bool) -> MQTTTransaction {
-        let mut tx = MQTTTransaction::new(msg);
-        self.tx_id += 1;
-        tx.tx_id = self.tx_id;
-        if toclient {
-            tx.toclient = true;
-        } else {
-            tx.toserver = true;
+    // Resolve the topic alias of a PUBLISH, so it is logged and inspected
+    // with its topic.
+    fn resolve_topic_alias(&mut self, tx: &mut MQTTTransaction) {
+        let aliases = match tx.direction {
+            Direction::ToServer => &mut self.aliases_ts,
+            Direction::ToClient => &mut self.aliases_tc,
+        };
+        if let Some(MQTTOperation::PUBLISH(ref mut publish)) = tx.msg.first_mut().map(|m| &mut m.op) {
+            if let Err(event) = aliases.resolve(publish) {
+                MQTTState::set_event(tx, event);
+            }
+        }
+    }
+
+    // Hand the payload of a PUBLISH to the file API, as a file named
+    // after the topic. The message is complete, so the file is opened
+    // and closed at once.
+    fn publish_file(&mut self, tx: &mut MQTTTransaction) {
+        let cfg = match unsafe { SURICATA_MQTT_FILE_CONFIG } {
+            Some(cfg) => cfg,
+            None => return,
+        };
+        let publish = match tx.msg.first().map(|m| &m.op) {
+            Some(MQTTOperation::PUBLISH(publish)) if !publish.message.is_empty() => publish,
+            _ => return,
+        };
+        let track_id = self.file_track_id;
+        self.file_track_id = self.file_track_id.wrapping_add(1);
+        let direction = tx.direction;
+        let files = self.files.get_or_insert_with(Files::default);
+        if files.open(cfg, direction, track_id, publish.topic.as_bytes()) {
+            files.set_tx(direction, tx.tx_id - 1);
+            files.append(direction, track_id, &publish.message);
+            if publish.skipped_length.is_some() {
+                files.close_truncated(direction, track_id);
+            } else {
+                files.close(direction, track_id);
+            }
+            tx.tx_data.init_files_opened();
+        }
+    }
+
+    // Set the flags of the files opened in a direction from the flow, to
+    // be called before parsing its data.
+    fn update_file_flags(&mut self, flow: *const Flow, direction: Direction) {
+        if unsafe { SURICATA_MQTT_FILE_CONFIG }.is_some() {
+            let files = self.files.get_or_insert_with(Files::default);
+            unsafe { files.update_flags(flow, direction) };
         }
-        return tx;
     }
 
     // Handle a MQTT message depending on the direction and state.
@@ -180,58 +654,82 @@ impl MQTTState {
     // without having to introduce lifetimes etc.
     // This is the reason for the code duplication below. Maybe there is a
     // more concise way to do it, but this works for now.
-    fn handle_msg(&mut self, msg: MQTTMessage, toclient: bool) {
+    fn handle_msg(&mut self, msg: MQTTMessage, direction: Direction) {
+        MQTTState::count_msg(&msg.op);
         match msg.op {
             MQTTOperation::CONNECT(ref conn) => {
                 self.protocol_version = conn.protocol_version;
+                // the client receives the aliases of the server
+                self.aliases_tc.set_maximum(&conn.properties);
+                let takeover = match self.session {
+                    Some(ref session) => session.is_takeover(conn),
+                    None => {
+                        self.session = Session::from_connect(conn);
+                        false
+                    }
+                };
+                let mut tx = self.new_tx(msg, direction);
+                if takeover {
+                    MQTTState::set_event(&mut tx, MQTTEvent::SessionTakeover);
+                }
                 if self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
                     MQTTState::set_event(&mut tx, MQTTEvent::DoubleConnect);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                 } else {
-                    let mut tx = self.new_tx(msg, toclient);
-                    tx.pkt_id = Some(MQTT_CONNECT_PKT_ID);
-                    self.transactions.push(tx);
+                    self.push_pending_tx(tx, MQTT_CONNECT_PKT_ID);
                 }
             },
             MQTTOperation::PUBLISH(ref publish) => {
                 if !self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                     return;
                 }
                 match msg.header.qos_level {
                     0 => {
                         // with QOS level 0, we do not need to wait for a
                         // response
-                        let mut tx = self.new_tx(msg, toclient);
+                        let mut tx = self.new_tx(msg, direction);
                         tx.complete = true;
-                        self.transactions.push(tx);
+                        self.push_tx(tx);
                     },
                     1..=2 => {
                         if let Some(pkt_id) = publish.message_id {
-                            let mut tx = self.new_tx(msg, toclient);
-                            tx.pkt_id = Some(pkt_id as u32);
-                            self.transactions.push(tx);
+                            let pkt_id = pkt_id as u32;
+                            if msg.header.dup_flag {
+                                if let Some(too_many) = self.retransmit_pending_tx(pkt_id, direction) {
+                                    // the original PUBLISH still waits for
+                                    // the response
+                                    let mut tx = self.new_tx(msg, direction);
+                                    tx.complete = true;
+                                    if too_many {
+                                        MQTTState::set_event(&mut tx, MQTTEvent::TooManyRetransmissions);
+                                    }
+                                    self.push_tx(tx);
+                                    return;
+                                }
+                            }
+                            let tx = self.new_tx(msg, direction);
+                            self.push_pending_tx(tx, pkt_id);
                         } else {
-                            let mut tx = self.new_tx(msg, toclient);
+                            let mut tx = self.new_tx(msg, direction);
                             MQTTState::set_event(&mut tx, MQTTEvent::MissingMsgId);
-                            self.transactions.push(tx);
+                            self.push_tx(tx);
                         }
                     },
                     _ => {
-                        let mut tx = self.new_tx(msg, toclient);
+                        let mut tx = self.new_tx(msg, direction);
                         MQTTState::set_event(&mut tx, MQTTEvent::InvalidQosLevel);
-                        self.transactions.push(tx);
+                        self.push_tx(tx);
                     }
                 }
             },
             MQTTOperation::SUBSCRIBE(ref subscribe) => {
                 if !self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                     return;
                 }
                 let pkt_id = subscribe.message_id as u32;
@@ -239,27 +737,26 @@ impl MQTTState {
                     0 => {
                         // with QOS level 0, we do not need to wait for a
                         // response
-                        let mut tx = self.new_tx(msg, toclient);
+                        let mut tx = self.new_tx(msg, direction);
                         tx.complete = true;
-                        self.transactions.push(tx);
+                        self.push_tx(tx);
                     },
                     1..=2 => {
-                        let mut tx = self.new_tx(msg, toclient);
-                        tx.pkt_id = Some(pkt_id);
-                        self.transactions.push(tx);
+                        let tx = self.new_tx(msg, direction);
+                        self.push_pending_tx(tx, pkt_id);
                     },
                     _ => {
-                        let mut tx = self.new_tx(msg, toclient);
+                        let mut tx = self.new_tx(msg, direction);
                         MQTTState::set_event(&mut tx, MQTTEvent::InvalidQosLevel);
-                        self.transactions.push(tx);
+                        self.push_tx(tx);
                     }
                 }
             },
             MQTTOperation::UNSUBSCRIBE(ref unsubscribe) => {
                 if !self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                     return;
                 }
                 let pkt_id = unsubscribe.message_id as u32;
@@ -267,140 +764,221 @@ impl MQTTState {
                     0 => {
                         // with QOS level 0, we do not need to wait for a
                         // response
-                        let mut tx = self.new_tx(msg, toclient);
+                        let mut tx = self.new_tx(msg, direction);
                         tx.complete = true;
-                        self.transactions.push(tx);
+                        self.push_tx(tx);
                     },
                     1..=2 => {
-                        let mut tx = self.new_tx(msg, toclient);
-                        tx.pkt_id = Some(pkt_id);
-                        self.transactions.push(tx);
+                        let tx = self.new_tx(msg, direction);
+                        self.push_pending_tx(tx, pkt_id);
                     },
                     _ => {
-                        let mut tx = self.new_tx(msg, toclient);
+                        let mut tx = self.new_tx(msg, direction);
                         MQTTState::set_event(&mut tx, MQTTEvent::InvalidQosLevel);
-                        self.transactions.push(tx);
+                        self.push_tx(tx);
                     }
                 }
             },
-            MQTTOperation::CONNACK(ref _connack) => {
-                if let Some(tx) = self.get_tx_by_pkt_id(MQTT_CONNECT_PKT_ID) {
+            MQTTOperation::CONNACK(ref connack) => {
+                self.aliases_ts.set_maximum(&connack.properties);
+                if let Some(tx) = self.get_tx_by_pkt_id(direction.reverse(), MQTT_CONNECT_PKT_ID) {
                     (*tx).msg.push(msg);
-                    (*tx).complete = true;
-                    (*tx).pkt_id = None;
+                    self.complete_pending_tx(direction.reverse(), MQTT_CONNECT_PKT_ID);
                     self.connected = true;
                 } else {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::MissingConnect);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                 }
             },
             MQTTOperation::PUBREC(ref v)
             | MQTTOperation::PUBREL(ref v) => {
                 if !self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                     return;
                 }
-                if let Some(tx) = self.get_tx_by_pkt_id(v.message_id as u32) {
+                // the PUBREL is sent by the sender of the PUBLISH
+                let request_direction = match msg.op {
+                    MQTTOperation::PUBREL(_) => direction,
+                    _ => direction.reverse(),
+                };
+                if let Some(tx) = self.get_tx_by_pkt_id(request_direction, v.message_id as u32) {
                     (*tx).msg.push(msg);
                 } else {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::MissingPublish);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                 }
             },
             MQTTOperation::PUBACK(ref v)
             | MQTTOperation::PUBCOMP(ref v) => {
                 if !self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                     return;
                 }
-                if let Some(tx) = self.get_tx_by_pkt_id(v.message_id as u32) {
+                let pkt_id = v.message_id as u32;
+                if let Some(tx) = self.get_tx_by_pkt_id(direction.reverse(), pkt_id) {
                     (*tx).msg.push(msg);
-                    (*tx).complete = true;
-                    (*tx).pkt_id = None;
+                    self.complete_pending_tx(direction.reverse(), pkt_id);
                 } else {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::MissingPublish);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                 }
             },
             MQTTOperation::SUBACK(ref suback) => {
                 if !self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                     return;
                 }
-                if let Some(tx) = self.get_tx_by_pkt_id(suback.message_id as u32) {
+                let pkt_id = suback.message_id as u32;
+                if let Some(tx) = self.get_tx_by_pkt_id(direction.reverse(), pkt_id) {
                     (*tx).msg.push(msg);
-                    (*tx).complete = true;
-                    (*tx).pkt_id = None;
+                    self.complete_pending_tx(direction.reverse(), pkt_id);
                 } else {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::MissingSubscribe);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                 }
             },
             MQTTOperation::UNSUBACK(ref unsuback) => {
                 if !self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                     return;
                 }
-                if let Some(tx) = self.get_tx_by_pkt_id(unsuback.message_id as u32) {
+                let pkt_id = unsuback.message_id as u32;
+                if let Some(tx) = self.get_tx_by_pkt_id(direction.reverse(), pkt_id) {
                     (*tx).msg.push(msg);
-                    (*tx).complete = true;
-                    (*tx).pkt_id = None;
+                    self.complete_pending_tx(direction.reverse(), pkt_id);
                 } else {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::MissingUnsubscribe);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                 }
             },
             MQTTOperation::UNASSIGNED => {
-                let mut tx = self.new_tx(msg, toclient);
+                let mut tx = self.new_tx(msg, direction);
                 tx.complete = true;
                 MQTTState::set_event(&mut tx, MQTTEvent::UnassignedMsgType);
-                self.transactions.push(tx);
+                self.push_tx(tx);
             },
             MQTTOperation::TRUNCATED(_) => {
-                let mut tx = self.new_tx(msg, toclient);
+                let mut tx = self.new_tx(msg, direction);
+                tx.complete = true;
+                self.push_tx(tx);
+            },
+            MQTTOperation::AUTH(_) => {
+                // the enhanced authentication takes place between the
+                // CONNECT and its CONNACK
+                let connecting = [Direction::ToServer, Direction::ToClient].iter()
+                    .any(|d| self.pending.get(&pending_key(*d, MQTT_CONNECT_PKT_ID)).is_some());
+                if !self.connected && !connecting {
+                    let mut tx = self.new_tx(msg, direction);
+                    MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
+                    self.push_tx(tx);
+                    return;
+                }
+                let mut tx = self.new_tx(msg, direction);
                 tx.complete = true;
-                self.transactions.push(tx);
+                self.push_tx(tx);
             },
-            MQTTOperation::AUTH(_)
-            | MQTTOperation::DISCONNECT(_) => {
+            MQTTOperation::DISCONNECT(_) => {
                 if !self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                     return;
                 }
-                let mut tx = self.new_tx(msg, toclient);
+                let mut tx = self.new_tx(msg, direction);
                 tx.complete = true;
-                self.transactions.push(tx);
+                self.push_tx(tx);
             },
             MQTTOperation::PINGREQ
             | MQTTOperation::PINGRESP => {
                 if !self.connected {
-                    let mut tx = self.new_tx(msg, toclient);
+                    let mut tx = self.new_tx(msg, direction);
                     MQTTState::set_event(&mut tx, MQTTEvent::UnintroducedMessage);
-                    self.transactions.push(tx);
+                    self.push_tx(tx);
                     return;
                 }
-                let mut tx = self.new_tx(msg, toclient);
+                let mut tx = self.new_tx(msg, direction);
                 tx.complete = true;
-                self.transactions.push(tx);
+                self.push_tx(tx);
             }
         }
     }
 
+    // Parse the data of a direction, which is WebSocket framed if the
+    // connection was upgraded from HTTP. As MQTT sessions start with a
+    // CONNECT or a CONNACK, a binary frame at the start tells.
+    fn parse(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
+        if self.framing == Framing::Unknown && !input.is_empty() {
+            self.framing = match websocket::probe_payload(input) {
+                Ok(Some(ref payload)) if probe_plain(payload) == Some(false) => Framing::Plain,
+                Ok(_) => Framing::WebSocket,
+                Err(_) => Framing::Plain,
+            };
+        }
+        if self.framing != Framing::WebSocket {
+            return match direction {
+                Direction::ToServer => self.parse_request(input),
+                Direction::ToClient => self.parse_response(input),
+            };
+        }
+        let ws = match direction {
+            Direction::ToServer => &mut self.ws_ts,
+            Direction::ToClient => &mut self.ws_tc,
+        };
+        if ws.feed(input).is_err() {
+            stats_incr!(mqtt_stats::malformed);
+            return AppLayerResult::err();
+        }
+        // the MQTT parser works on the data of the frames, keeping what
+        // it did not consume for the next frames
+        let mut data = std::mem::replace(&mut ws.data, Vec::new());
+        let r = match direction {
+            Direction::ToServer => self.parse_request(&data),
+            Direction::ToClient => self.parse_response(&data),
+        };
+        if r.status == 1 {
+            data.drain(..r.consumed as usize);
+            match direction {
+                Direction::ToServer => self.ws_ts.data = data,
+                Direction::ToClient => self.ws_tc.data = data,
+            }
+            return AppLayerResult::ok();
+        }
+        r
+    }
+
+    // A gap within a message being skipped keeps the direction in sync,
+    // otherwise parsing resumes at the next message found. The frames of
+    // WebSocket can't be found again.
+    fn on_gap(&mut self, gap_len: u32, direction: Direction) -> AppLayerResult {
+        if self.framing == Framing::WebSocket {
+            return AppLayerResult::err();
+        }
+        let (skip, gap) = match direction {
+            Direction::ToServer => (&mut self.skip_request, &mut self.request_gap),
+            Direction::ToClient => (&mut self.skip_response, &mut self.response_gap),
+        };
+        if *skip >= gap_len as usize {
+            *skip -= gap_len as usize;
+        } else {
+            *skip = 0;
+            *gap = true;
+        }
+        AppLayerResult::ok()
+    }
+
     fn parse_request(&mut self, input: &[u8]) -> AppLayerResult {
         let mut current = input;
         if input.len() == 0 {
@@ -422,24 +1000,43 @@ impl MQTTState {
             }
         }
 
+        if self.request_gap {
+            // resume at the next message which looks valid, the data
+            // before it is dropped
+            match find_message_start(current, self.protocol_version, self.max_msg_len) {
+                MessageStart::Found(offset) => {
+                    SCLogDebug!("request resynced after gap at offset {}", offset);
+                    current = &current[offset..];
+                    consumed += offset;
+                    self.request_gap = false;
+                }
+                MessageStart::Incomplete(offset) => {
+                    consumed += offset;
+                    return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                           current.len() - offset + 1);
+                }
+                MessageStart::NotFound => return AppLayerResult::ok(),
+            }
+        }
+
 
         while current.len() > 0 {
             let mut skipped = false;
             SCLogDebug!("request: handling {}", current.len());
-            match parse_message(current, self.protocol_version, self.max_msg_len) {
-                Ok((rem, msg)) => {
+            match parse_message(current, self.protocol_version, self.max_msg_len, self.max_prefix_len) {
+                Ok((mut rem, msg)) => {
                     SCLogDebug!("request msg {:?}", msg);
-                    if let MQTTOperation::TRUNCATED(ref trunc) = msg.op {
-                        SCLogDebug!("found truncated with skipped {} current len {}", trunc.skipped_length, current.len());
-                        if trunc.skipped_length >= current.len() {
+                    if let Some(skipped_length) = msg.skipped_length() {
+                        SCLogDebug!("found truncated with skipped {} current len {}", skipped_length, current.len());
+                        if skipped_length >= current.len() {
                             skipped = true;
-                            self.skip_request = trunc.skipped_length - current.len();
+                            self.skip_request = skipped_length - current.len();
                         } else {
-                            current = &current[trunc.skipped_length..];
+                            rem = &current[skipped_length..];
                             self.skip_request = 0;
                         }
                     }
-                    self.handle_msg(msg, false);
+                    self.handle_msg(msg, Direction::ToServer);
                     if skipped {
                         return AppLayerResult::ok();
                     }
@@ -448,9 +1045,10 @@ impl MQTTState {
                 }
                 Err(nom::Err::Incomplete(_)) => {
                         SCLogDebug!("incomplete request: consumed {} needed {} (input len {})", consumed, (current.len() + 1), input.len());
-                        return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                        return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                 }
                 Err(_) => {
+                    stats_incr!(mqtt_stats::malformed);
                     return AppLayerResult::err();
                 }
             }
@@ -479,24 +1077,43 @@ impl MQTTState {
             }
         }
 
+        if self.response_gap {
+            // resume at the next message which looks valid, the data
+            // before it is dropped
+            match find_message_start(current, self.protocol_version, self.max_msg_len) {
+                MessageStart::Found(offset) => {
+                    SCLogDebug!("response resynced after gap at offset {}", offset);
+                    current = &current[offset..];
+                    consumed += offset;
+                    self.response_gap = false;
+                }
+                MessageStart::Incomplete(offset) => {
+                    consumed += offset;
+                    return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                           current.len() - offset + 1);
+                }
+                MessageStart::NotFound => return AppLayerResult::ok(),
+            }
+        }
+
         while current.len() > 0 {
             let mut skipped = false;
             SCLogDebug!("response: handling {}", current.len());
-            match parse_message(current, self.protocol_version, self.max_msg_len as usize) {
-                Ok((rem, msg)) => {
+            match parse_message(current, self.protocol_version, self.max_msg_len, self.max_prefix_len) {
+                Ok((mut rem, msg)) => {
                     SCLogDebug!("response msg {:?}", msg);
-                    if let MQTTOperation::TRUNCATED(ref trunc) = msg.op {
-                        SCLogDebug!("found truncated with skipped {} current len {}", trunc.skipped_length, current.len());
-                        if trunc.skipped_length >= current.len() {
+                    if let Some(skipped_length) = msg.skipped_length() {
+                        SCLogDebug!("found truncated with skipped {} current len {}", skipped_length, current.len());
+                        if skipped_length >= current.len() {
                             skipped = true;
-                            self.skip_response = trunc.skipped_length - current.len();
+                            self.skip_response = skipped_length - current.len();
                         } else {
-                            current = &current[trunc.skipped_length..];
+                            rem = &current[skipped_length..];
                             self.skip_response = 0;
                         }
                         SCLogDebug!("skip_response now {}", self.skip_response);
                     }
-                    self.handle_msg(msg, true);
+                    self.handle_msg(msg, Direction::ToClient);
                     if skipped {
                         return AppLayerResult::ok();
                     }
@@ -505,9 +1122,10 @@ impl MQTTState {
                 }
                 Err(nom::Err::Incomplete(_)) => {
                     SCLogDebug!("incomplete response: consumed {} needed {} (input len {})", consumed, (current.len() + 1), input.len());
-                    return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                    return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                 }
                 Err(_) => {
+                    stats_incr!(mqtt_stats::malformed);
                     return AppLayerResult::err();
                 }
             }
@@ -516,37 +1134,85 @@ impl MQTTState {
         return AppLayerResult::ok();
     }
 
+    fn count_msg(op: &MQTTOperation) {
+        match op {
+            MQTTOperation::CONNECT(_) => stats_incr!(mqtt_stats::connect),
+            MQTTOperation::CONNACK(_) => stats_incr!(mqtt_stats::connack),
+            MQTTOperation::PUBLISH(ref publish) => {
+                stats_incr!(mqtt_stats::publish);
+                if publish.skipped_length.is_some() {
+                    stats_incr!(mqtt_stats::truncated);
+                }
+            }
+            MQTTOperation::PUBACK(_) => stats_incr!(mqtt_stats::puback),
+            MQTTOperation::PUBREC(_) => stats_incr!(mqtt_stats::pubrec),
+            MQTTOperation::PUBREL(_) => stats_incr!(mqtt_stats::pubrel),
+            MQTTOperation::PUBCOMP(_) => stats_incr!(mqtt_stats::pubcomp),
+            MQTTOperation::SUBSCRIBE(_) => stats_incr!(mqtt_stats::subscribe),
+            MQTTOperation::SUBACK(_) => stats_incr!(mqtt_stats::suback),
+            MQTTOperation::UNSUBSCRIBE(_) => stats_incr!(mqtt_stats::unsubscribe),
+            MQTTOperation::UNSUBACK(_) => stats_incr!(mqtt_stats::unsuback),
+            MQTTOperation::PINGREQ => stats_incr!(mqtt_stats::pingreq),
+            MQTTOperation::PINGRESP => stats_incr!(mqtt_stats::pingresp),
+            MQTTOperation::DISCONNECT(_) => stats_incr!(mqtt_stats::disconnect),
+            MQTTOperation::AUTH(_) => stats_incr!(mqtt_stats::auth),
+            MQTTOperation::UNASSIGNED => stats_incr!(mqtt_stats::unassigned),
+            MQTTOperation::TRUNCATED(_) => stats_incr!(mqtt_stats::truncated),
+        }
+    }
+
     fn set_event(tx: &mut MQTTTransaction, event: MQTTEvent) {
+        stats_incr!(mqtt_stats::anomalies);
         let ev = event as u8;
         core::sc_app_layer_decoder_events_set_event_raw(&mut tx.events, ev);
     }
+}
 
-    fn tx_iterator(
-        &mut self,
-        min_tx_id: u64,
-        state: &mut u64,
-    ) -> Option<(&MQTTTransaction, u64, bool)> {
-        let mut index = *state as usize;
-        let len = self.transactions.len();
-
-        while index < len {
-            let tx = &self.transactions[index];
-            if tx.tx_id < min_tx_id + 1 {
-                index += 1;
-                continue;
+impl Truncate for MQTTState {
+    // Once a direction is truncated the responses expected in it won't
+    // be seen, so complete the transactions still waiting for them.
+    fn truncate(&mut self, direction: Direction) {
+        for tx in self.transactions.iter_mut() {
+            if !tx.complete && tx.direction != direction {
+                SCLogDebug!("completing tx {} on truncation", tx.tx_id);
+                tx.complete = true;
+                if let Some(pkt_id) = tx.pkt_id.take() {
+                    let _ = self.pending.take(&pending_key(tx.direction, pkt_id));
+                }
             }
-            *state = index as u64;
-            return Some((tx, tx.tx_id - 1, (len - index) > 1));
         }
-
-        return None;
     }
 }
 
 // C exports.
 
-export_tx_get_detect_state!(rs_mqtt_tx_get_detect_state, MQTTTransaction);
-export_tx_set_detect_state!(rs_mqtt_tx_set_detect_state, MQTTTransaction);
+export_tx_detect_state!(rs_mqtt_tx_get_detect_state, rs_mqtt_tx_set_detect_state, MQTTTransaction);
+
+// Whether the data starts with an MQTT message, None if more data is
+// needed to tell.
+fn probe_plain(buf: &[u8]) -> Option<bool> {
+    match parse_fixed_header(buf) {
+        Ok((_, hdr)) => {
+            // reject unassigned message type, with 2 being the highest
+            // valid QoS level
+            Some(hdr.message_type != MQTTTypeCode::UNASSIGNED && hdr.qos_level <= 2)
+        },
+        Err(nom::Err::Incomplete(_)) => None,
+        Err(_) => Some(false),
+    }
+}
+
+// Probe plain MQTT, or MQTT in a WebSocket binary frame.
+fn probe(buf: &[u8]) -> Option<bool> {
+    match probe_plain(buf) {
+        Some(false) => match websocket::probe_payload(buf) {
+            Ok(Some(payload)) => probe_plain(&payload),
+            Ok(None) => None,
+            Err(_) => Some(false),
+        },
+        r => r,
+    }
+}
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_mqtt_probing_parser(
@@ -557,20 +1223,10 @@ pub unsafe extern "C" fn rs_mqtt_probing_parser(
     _rdir: *mut u8,
 ) -> AppProto {
     let buf = build_slice!(input, input_len as usize);
-    match parse_fixed_header(buf) {
-        Ok((_, hdr)) => {
-            // reject unassigned message type
-            if hdr.message_type == MQTTTypeCode::UNASSIGNED {
-                return ALPROTO_FAILED;
-            }
-            // with 2 being the highest valid QoS level
-            if hdr.qos_level > 2 {
-                return ALPROTO_FAILED;
-            }
-            return ALPROTO_MQTT;
-        },
-        Err(nom::Err::Incomplete(_)) => ALPROTO_UNKNOWN,
-        Err(_) => ALPROTO_FAILED
+    match probe(buf) {
+        Some(true) => ALPROTO_MQTT,
+        Some(false) => ALPROTO_FAILED,
+        None => ALPROTO_UNKNOWN,
     }
 }
 
@@ -588,13 +1244,13 @@ pub extern "C" fn rs_mqtt_state_free(state: *mut std::os::raw::c_void) {
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_mqtt_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
-    let state = cast_pointer!(state, MQTTState);
+    let state: &mut MQTTState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_mqtt_parse_request(
-    _flow: *const Flow,
+    flow: *const Flow,
     state: *mut std::os::raw::c_void,
     _pstate: *mut std::os::raw::c_void,
     input: *const u8,
@@ -602,14 +1258,18 @@ pub unsafe extern "C" fn rs_mqtt_parse_request(
     _data: *const std::os::raw::c_void,
     _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, MQTTState);
+    let state: &mut MQTTState = cast_mut(state);
+    if input.is_null() && input_len > 0 {
+        return state.on_gap(input_len, Direction::ToServer);
+    }
     let buf = build_slice!(input, input_len as usize);
-    return state.parse_request(buf);
+    state.update_file_flags(flow, Direction::ToServer);
+    return state.parse(buf, Direction::ToServer);
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_mqtt_parse_response(
-    _flow: *const Flow,
+    flow: *const Flow,
     state: *mut std::os::raw::c_void,
     _pstate: *mut std::os::raw::c_void,
     input: *const u8,
@@ -617,9 +1277,13 @@ pub unsafe extern "C" fn rs_mqtt_parse_response(
     _data: *const std::os::raw::c_void,
     _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, MQTTState);
+    let state: &mut MQTTState = cast_mut(state);
+    if input.is_null() && input_len > 0 {
+        return state.on_gap(input_len, Direction::ToClient);
+    }
     let buf = build_slice!(input, input_len as usize);
-    return state.parse_response(buf);
+    state.update_file_flags(flow, Direction::ToClient);
+    return state.parse(buf, Direction::ToClient);
 }
 
 #[no_mangle]
@@ -627,7 +1291,7 @@ pub unsafe extern "C" fn rs_mqtt_state_get_tx(
     state: *mut std::os::raw::c_void,
     tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, MQTTState);
+    let state: &mut MQTTState = cast_mut(state);
     match state.get_tx(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -640,14 +1304,14 @@ pub unsafe extern "C" fn rs_mqtt_state_get_tx(
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_mqtt_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
-    let state = cast_pointer!(state, MQTTState);
-    return state.tx_id;
+    let state: &mut MQTTState = cast_mut(state);
+    return state.transactions.tx_count();
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_mqtt_tx_is_toclient(tx: *const std::os::raw::c_void) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, MQTTTransaction);
-    if tx.toclient {
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    if tx.direction.is_to_client() {
         return 1;
     }
     return 0;
@@ -658,68 +1322,25 @@ pub unsafe extern "C" fn rs_mqtt_tx_get_alstate_progress(
     tx: *mut std::os::raw::c_void,
     direction: u8,
 ) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, MQTTTransaction);
-    if tx.complete {
-        if direction == core::STREAM_TOSERVER {
-            if tx.toserver {
-                return 1;
-            }
-        } else if direction == core::STREAM_TOCLIENT {
-            if tx.toclient {
-                return 1;
-            }
-        }
+    let tx: &mut MQTTTransaction = cast_mut(tx);
+    if tx.complete && tx.direction == Direction::from(direction) {
+        return 1;
     }
     return 0;
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_get_logged(
-    _state: *mut std::os::raw::c_void,
-    tx: *mut std::os::raw::c_void,
-) -> u32 {
-    let tx = cast_pointer!(tx, MQTTTransaction);
-    return tx.logged.get();
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_tx_set_logged(
-    _state: *mut std::os::raw::c_void,
-    tx: *mut std::os::raw::c_void,
-    logged: u32,
-) {
-    let tx = cast_pointer!(tx, MQTTTransaction);
-    tx.logged.set(logged);
-}
-
 #[no_mangle]
 pub unsafe extern "C" fn rs_mqtt_state_get_events(
     tx: *mut std::os::raw::c_void,
 ) -> *mut core::AppLayerDecoderEvents {
-    let tx = cast_pointer!(tx, MQTTTransaction);
+    let tx: &mut MQTTTransaction = cast_mut(tx);
     return tx.events;
 }
 
 #[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_state_get_tx_iterator(
-    _ipproto: u8,
-    _alproto: AppProto,
-    state: *mut std::os::raw::c_void,
-    min_tx_id: u64,
-    _max_tx_id: u64,
-    istate: &mut u64,
-) -> applayer::AppLayerGetTxIterTuple {
-    let state = cast_pointer!(state, MQTTState);
-    match state.tx_iterator(min_tx_id, istate) {
-        Some((tx, out_tx_id, has_next)) => {
-            let c_tx = tx as *const _ as *mut _;
-            let ires = applayer::AppLayerGetTxIterTuple::with_values(c_tx, out_tx_id, has_next);
-            return ires;
-        }
-        None => {
-            return applayer::AppLayerGetTxIterTuple::not_found();
-        }
-    }
+pub unsafe extern "C" fn rs_mqtt_state_purge(state: *mut std::os::raw::c_void, ts: u64) {
+    let state: &mut MQTTState = cast_mut(state);
+    state.purge(ts);
 }
 
 // Parser name as a C style string.
@@ -728,10 +1349,41 @@ const PARSER_NAME: &'static [u8] = b"mqtt\0";
 export_tx_data_get!(rs_mqtt_get_tx_data, MQTTTransaction);
 
 #[no_mangle]
-pub unsafe extern "C" fn rs_mqtt_register_parser(cfg_max_msg_len: u32) {
+pub unsafe extern "C" fn rs_mqtt_init(context: &'static mut SuricataFileContext) {
+    SURICATA_MQTT_FILE_CONFIG = Some(context);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_mqtt_register_parser() {
     let default_port = CString::new("[1883]").unwrap();
-    let max_msg_len = &mut MAX_MSG_LEN;
-    *max_msg_len = cfg_max_msg_len;
+    if let Some(conf) = conf_get_app_layer_node("mqtt") {
+        if let Some(val) = conf.get_child_memval("max-msg-length") {
+            if val > std::u32::MAX as u64 {
+                SCLogError!("Invalid value for max-msg-length: {}", val);
+            } else {
+                MAX_MSG_LEN = val as u32;
+            }
+        }
+        if let Some(val) = conf.get_child_memval("truncated-payload-length") {
+            if val > std::u32::MAX as u64 {
+                SCLogError!("Invalid value for truncated-payload-length: {}", val);
+            } else {
+                TRUNCATED_PAYLOAD_LEN = val as u32;
+            }
+        }
+        if let Some(val) = conf.get_child_u64("pending-timeout") {
+            PENDING_TIMEOUT = val;
+        }
+        if let Some(val) = conf.get_child_u64("max-retransmissions") {
+            if val > std::u32::MAX as u64 {
+                SCLogError!("Invalid value for max-retransmissions: {}", val);
+            } else {
+                MAX_RETRANSMISSIONS = val as u32;
+            }
+        }
+    }
+    MQTT_TX_LIMIT.configure("mqtt", txtable::DEFAULT_MAX_TX);
+    mqtt_stats::register();
     let parser = RustParser {
         name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
         default_port: default_port.as_ptr(),
@@ -757,12 +1409,13 @@ pub unsafe extern "C" fn rs_mqtt_register_parser(cfg_max_msg_len: u32) {
         get_eventinfo_byid: Some(MQTTEvent::get_event_info_by_id),
         localstorage_new: None,
         localstorage_free: None,
-        get_files: None,
-        get_tx_iterator: Some(rs_mqtt_state_get_tx_iterator),
+        get_files: Some(crate::filecontainer::state_get_files::<MQTTState>),
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<MQTTState>),
         get_tx_data: rs_mqtt_get_tx_data,
         apply_tx_config: None,
-        flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
-        truncate: None,
+        flags: APP_LAYER_PARSER_OPT_ACCEPT_GAPS | APP_LAYER_PARSER_OPT_UNIDIR_TXS,
+        truncate: Some(applayer::state_truncate::<MQTTState>),
+        state_purge: Some(rs_mqtt_state_purge),
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
@@ -777,3 +1430,491 @@ pub unsafe extern "C" fn rs_mqtt_register_parser(cfg_max_msg_len: u32) {
         SCLogDebug!("Protocol detector and parser disabled for MQTT.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{decoder_events, ParserFns, ParserHarness};
+
+    // CONNECT, MQTT 3.1.1, empty client id
+    const CONNECT: &[u8] = &[
+        0x10, 0x0c, 0x00, 0x04, 0x4d, 0x51, 0x54, 0x54, 0x04, 0x02, 0x00, 0x3c, 0x00, 0x00,
+    ];
+    const CONNACK: &[u8] = &[0x20, 0x02, 0x00, 0x00];
+    // PUBLISH, QoS 1, topic "a/b", message id 1, payload "hi"
+    const PUBLISH: &[u8] = &[
+        0x32, 0x09, 0x00, 0x03, 0x61, 0x2f, 0x62, 0x00, 0x01, 0x68, 0x69,
+    ];
+    const PUBACK: &[u8] = &[0x40, 0x02, 0x00, 0x01];
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_mqtt_state_new,
+            state_free: rs_mqtt_state_free,
+            parse_ts: rs_mqtt_parse_request,
+            parse_tc: rs_mqtt_parse_response,
+            get_tx_count: rs_mqtt_state_get_tx_count,
+            get_tx: rs_mqtt_state_get_tx,
+            tx_free: rs_mqtt_state_tx_free,
+        })
+    }
+
+    #[test]
+    fn test_mqtt_segmented() {
+        for segment_size in 1..=CONNECT.len() {
+            let mut h = harness();
+            assert!(h.feed_segments(Direction::ToServer, CONNECT, segment_size));
+            assert_eq!(h.pending(Direction::ToServer), 0);
+            assert!(h.feed_segments(Direction::ToClient, CONNACK, segment_size));
+            assert!(h.feed_segments(Direction::ToServer, PUBLISH, segment_size));
+            assert_eq!(h.pending(Direction::ToServer), 0);
+            assert!(h.feed_segments(Direction::ToClient, PUBACK, segment_size));
+
+            assert_eq!(h.tx_count(), 2);
+            let tx = h.get_tx::<MQTTTransaction>(0).unwrap();
+            assert!(tx.complete);
+            assert_eq!(tx.msg.len(), 2);
+            let tx = h.get_tx::<MQTTTransaction>(1).unwrap();
+            assert!(tx.complete);
+            match (&tx.msg[0].op, &tx.msg[1].op) {
+                (MQTTOperation::PUBLISH(_), MQTTOperation::PUBACK(_)) => {}
+                _ => panic!("unexpected messages {:?}", tx.msg),
+            }
+        }
+    }
+
+    #[test]
+    fn test_mqtt_pending_purge() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        assert!(h.feed(Direction::ToServer, PUBLISH));
+        let tx = h.get_tx::<MQTTTransaction>(1).unwrap();
+        assert!(!tx.complete);
+
+        // the first purge only sets the time of the pending transactions
+        h.state_mut::<MQTTState>().purge(1000);
+        let tx = h.get_tx::<MQTTTransaction>(1).unwrap();
+        assert!(!tx.complete);
+
+        h.state_mut::<MQTTState>().purge(1061);
+        let tx = h.get_tx::<MQTTTransaction>(1).unwrap();
+        assert!(tx.complete);
+    }
+
+    #[test]
+    fn test_mqtt_many_pending() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        for id in 1..=1000u16 {
+            let mut publish = PUBLISH.to_vec();
+            publish[7..9].copy_from_slice(&id.to_be_bytes());
+            assert!(h.feed(Direction::ToServer, &publish));
+        }
+        // acknowledged in reverse order, and with the first transactions
+        // freed
+        h.free_tx(0);
+        h.free_tx(1);
+        for id in (1..=1000u16).rev() {
+            let mut puback = PUBACK.to_vec();
+            puback[2..4].copy_from_slice(&id.to_be_bytes());
+            assert!(h.feed(Direction::ToClient, &puback));
+        }
+        assert_eq!(h.tx_count(), 1002);
+        assert!(h.get_tx::<MQTTTransaction>(1).is_none());
+        // the PUBACK of the freed PUBLISH is a transaction of its own
+        let tx = h.get_tx::<MQTTTransaction>(1001).unwrap();
+        assert_eq!(tx.msg.len(), 1);
+        for tx_id in 2..=1000 {
+            let tx = h.get_tx::<MQTTTransaction>(tx_id).unwrap();
+            assert!(tx.complete);
+            assert_eq!(tx.msg.len(), 2);
+        }
+    }
+
+    #[test]
+    fn test_mqtt_too_many_transactions() {
+        let mut h = harness();
+        h.state_mut::<MQTTState>().pending.set_max_pending(3);
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        for id in 1..=4u16 {
+            let mut publish = PUBLISH.to_vec();
+            publish[7..9].copy_from_slice(&id.to_be_bytes());
+            assert!(h.feed(Direction::ToServer, &publish));
+        }
+        assert_eq!(h.tx_count(), 5);
+        // the first PUBLISH is completed without its PUBACK, and kept
+        // until it is logged
+        let tx = h.get_tx::<MQTTTransaction>(1).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.pkt_id, None);
+        assert_eq!(decoder_events(tx.events), vec![MQTTEvent::TooManyTransactions as u8]);
+        assert_eq!(h.state::<MQTTState>().pending.get(&(true, 1)), None);
+        assert_eq!(h.state::<MQTTState>().pending.get(&(true, 2)), Some(3));
+        let tx = h.get_tx::<MQTTTransaction>(4).unwrap();
+        assert!(!tx.complete);
+        assert!(tx.events.is_null());
+        // its PUBACK is a transaction of its own
+        assert!(h.feed(Direction::ToClient, PUBACK));
+        assert_eq!(h.tx_count(), 6);
+        assert_eq!(h.get_tx::<MQTTTransaction>(1).unwrap().msg.len(), 1);
+    }
+
+    #[test]
+    fn test_mqtt_retransmission() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        assert!(h.feed(Direction::ToServer, PUBLISH));
+        let mut dup = PUBLISH.to_vec();
+        dup[0] |= 0x08;
+        for _ in 0..6 {
+            assert!(h.feed(Direction::ToServer, &dup));
+        }
+        assert_eq!(h.tx_count(), 8);
+        for tx_id in 2..8 {
+            assert!(h.get_tx::<MQTTTransaction>(tx_id).unwrap().complete);
+        }
+        let tx = h.get_tx::<MQTTTransaction>(1).unwrap();
+        assert!(!tx.complete);
+        assert_eq!(tx.retransmissions, 6);
+
+        assert!(h.feed(Direction::ToClient, PUBACK));
+        let tx = h.get_tx::<MQTTTransaction>(1).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.msg.len(), 2);
+        // once acknowledged, a retransmission waits for its own response
+        assert!(h.feed(Direction::ToServer, &dup));
+        assert!(!h.get_tx::<MQTTTransaction>(8).unwrap().complete);
+    }
+
+    #[test]
+    fn test_mqtt_message_id_reuse() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        assert!(h.feed(Direction::ToServer, PUBLISH));
+        assert!(h.feed(Direction::ToServer, PUBLISH));
+        // the first PUBLISH won't get a response
+        assert!(h.get_tx::<MQTTTransaction>(1).unwrap().complete);
+        assert!(h.feed(Direction::ToClient, PUBACK));
+        let tx = h.get_tx::<MQTTTransaction>(2).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.msg.len(), 2);
+    }
+
+    #[test]
+    fn test_mqtt_same_id_both_directions() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        // both sides publish with message id 1 before either is
+        // acknowledged
+        assert!(h.feed(Direction::ToServer, PUBLISH));
+        assert!(h.feed(Direction::ToClient, PUBLISH));
+        assert_eq!(h.tx_count(), 3);
+        for tx_id in 1..3 {
+            let tx = h.get_tx::<MQTTTransaction>(tx_id).unwrap();
+            assert!(!tx.complete);
+            assert!(tx.events.is_null());
+        }
+        // the PUBACK of the server acknowledges the PUBLISH of the client
+        assert!(h.feed(Direction::ToClient, PUBACK));
+        let tx = h.get_tx::<MQTTTransaction>(1).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.direction, Direction::ToServer);
+        assert_eq!(tx.msg.len(), 2);
+        assert!(!h.get_tx::<MQTTTransaction>(2).unwrap().complete);
+        assert!(h.feed(Direction::ToServer, PUBACK));
+        let tx = h.get_tx::<MQTTTransaction>(2).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.direction, Direction::ToClient);
+        assert_eq!(tx.msg.len(), 2);
+        assert_eq!(h.tx_count(), 3);
+    }
+
+    #[test]
+    fn test_mqtt_session_takeover() {
+        // client id "c1", with and without a clean session
+        let clean = [
+            0x10, 0x0e, 0x00, 0x04, b'M', b'Q', b'T', b'T', 0x04, 0x02, 0x00, 0x3c,
+            0x00, 0x02, b'c', b'1',
+        ];
+        let mut resume = clean;
+        resume[9] = 0x00;
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        // the server assigns the empty client ids
+        assert_eq!(h.state::<MQTTState>().client_id(), None);
+        assert!(h.feed(Direction::ToServer, &clean));
+        assert!(h.feed(Direction::ToServer, &resume));
+        assert_eq!(h.state::<MQTTState>().client_id(), Some("c1"));
+
+        let connect = |buf: &[u8]| match parse_message(buf, 4, 1024, 0).unwrap().1.op {
+            MQTTOperation::CONNECT(conn) => conn,
+            op => panic!("unexpected message {:?}", op),
+        };
+        let session = h.state::<MQTTState>().session.as_ref().unwrap();
+        assert!(!session.is_takeover(&connect(&clean)));
+        assert!(session.is_takeover(&connect(&resume)));
+        assert!(!session.is_takeover(&connect(CONNECT)));
+    }
+
+    #[test]
+    fn test_mqtt_auth_exchange() {
+        // MQTT 5 with the authentication method "SCRAM-SHA-1", then an AUTH
+        // each way before the CONNACK
+        let connect = [
+            0x10, 0x1b, 0x00, 0x04, b'M', b'Q', b'T', b'T', 0x05, 0x02, 0x00, 0x3c,
+            0x0e, 0x15, 0x00, 0x0b, b'S', b'C', b'R', b'A', b'M', b'-', b'S', b'H', b'A', b'-', b'1',
+            0x00, 0x00,
+        ];
+        let auth = [
+            0xf0, 0x10, 0x18, 0x0e, 0x15, 0x00, 0x0b,
+            b'S', b'C', b'R', b'A', b'M', b'-', b'S', b'H', b'A', b'-', b'1',
+        ];
+        let connack = [0x20, 0x03, 0x00, 0x00, 0x00];
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, &connect));
+        assert!(h.feed(Direction::ToClient, &auth));
+        assert!(h.feed(Direction::ToServer, &auth));
+        assert!(h.feed(Direction::ToClient, &connack));
+        for tx_id in 0..3 {
+            let tx = h.get_tx::<MQTTTransaction>(tx_id).unwrap();
+            assert!(tx.complete);
+            assert_eq!(tx.auth_method(), Some("SCRAM-SHA-1"));
+            assert_eq!(tx.auth_data(), None);
+        }
+        assert!(h.state::<MQTTState>().connected);
+    }
+
+    #[test]
+    fn test_mqtt_truncated_publish() {
+        // QoS 1 PUBLISH on "a/b" with a message of 16 bytes, then PUBLISH
+        let mut publish = vec![0x32, 0x17, 0x00, 0x03, b'a', b'/', b'b', 0x00, 0x02];
+        publish.extend_from_slice(b"0123456789abcdef");
+        publish.extend_from_slice(PUBLISH);
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        h.state_mut::<MQTTState>().max_msg_len = 20;
+        h.state_mut::<MQTTState>().max_prefix_len = 4;
+        for segment_size in &[publish.len(), 1] {
+            assert!(h.feed_segments(Direction::ToServer, &publish, *segment_size));
+        }
+        for tx_id in &[1, 3] {
+            let tx = h.get_tx::<MQTTTransaction>(*tx_id).unwrap();
+            match tx.msg[0].op {
+                MQTTOperation::PUBLISH(ref publish) => {
+                    assert_eq!(publish.topic, "a/b");
+                    assert_eq!(publish.message_id, Some(2));
+                    assert_eq!(publish.message, b"0123");
+                    assert_eq!(publish.skipped_length, Some(25));
+                }
+                _ => panic!("unexpected message {:?}", tx.msg[0]),
+            }
+        }
+        assert_eq!(publish_topic(h.get_tx::<MQTTTransaction>(2).unwrap()), "a/b");
+        assert_eq!(publish_topic(h.get_tx::<MQTTTransaction>(4).unwrap()), "a/b");
+        assert_eq!(h.state::<MQTTState>().transactions.tx_count(), 5);
+
+        // without the prefix, the PUBLISH is truncated as a whole
+        h.state_mut::<MQTTState>().max_prefix_len = 0;
+        assert!(h.feed(Direction::ToServer, &publish));
+        match h.get_tx::<MQTTTransaction>(5).unwrap().msg[0].op {
+            MQTTOperation::TRUNCATED(ref trunc) => assert_eq!(trunc.skipped_length, 25),
+            ref op => panic!("unexpected message {:?}", op),
+        }
+        assert_eq!(h.state::<MQTTState>().transactions.tx_count(), 7);
+    }
+
+    #[test]
+    fn test_mqtt_stats() {
+        let (publish, malformed) = (mqtt_stats::publish::get(), mqtt_stats::malformed::get());
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        assert!(h.feed(Direction::ToServer, PUBLISH));
+        // SUBSCRIBE without topic filters
+        assert!(!h.feed(Direction::ToServer, &[0x82, 0x02, 0x00, 0x01]));
+        // the counters are shared by the tests running in parallel
+        assert!(mqtt_stats::publish::get() > publish);
+        assert!(mqtt_stats::malformed::get() > malformed);
+    }
+
+    #[test]
+    fn test_mqtt_gap() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        // a gap in the middle of a PUBLISH, then the end of a message
+        assert!(h.feed(Direction::ToServer, &PUBLISH[..5]));
+        assert!(h.gap(Direction::ToServer, 10));
+        assert!(h.feed(Direction::ToServer, b"xy"));
+        assert_eq!(h.state::<MQTTState>().transactions.tx_count(), 1);
+        assert!(h.state::<MQTTState>().request_gap);
+        let mut data = b"xy".to_vec();
+        data.extend_from_slice(PUBLISH);
+        assert!(h.feed(Direction::ToServer, &data));
+        assert!(!h.state::<MQTTState>().request_gap);
+        assert_eq!(publish_topic(h.get_tx::<MQTTTransaction>(1).unwrap()), "a/b");
+        assert!(h.feed(Direction::ToClient, PUBACK));
+        assert!(h.get_tx::<MQTTTransaction>(1).unwrap().complete);
+
+        // a gap in a PUBLISH over the maximum length, which is skipped
+        h.state_mut::<MQTTState>().max_msg_len = 5;
+        assert!(h.feed(Direction::ToServer, &PUBLISH[..4]));
+        assert!(h.gap(Direction::ToServer, 3));
+        let mut data = PUBLISH[7..].to_vec();
+        data.extend_from_slice(&[0xc0, 0x00]);
+        assert!(h.feed(Direction::ToServer, &data));
+        assert!(!h.state::<MQTTState>().request_gap);
+        let tx = h.get_tx::<MQTTTransaction>(3).unwrap();
+        assert_eq!(tx.msg[0].header.message_type, MQTTTypeCode::PINGREQ);
+    }
+
+    #[test]
+    fn test_mqtt_gap_split_message() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CONNECT));
+        assert!(h.feed(Direction::ToClient, CONNACK));
+        assert!(h.feed(Direction::ToServer, &PUBLISH[..5]));
+        assert!(h.gap(Direction::ToServer, 10));
+        // the first message after the gap spans two segments
+        let mut data = b"xy".to_vec();
+        data.extend_from_slice(&PUBLISH[..6]);
+        assert!(h.feed(Direction::ToServer, &data));
+        assert!(h.state::<MQTTState>().request_gap);
+        // only the data before it is dropped
+        assert_eq!(h.pending(Direction::ToServer), 6);
+        assert!(h.feed(Direction::ToServer, &PUBLISH[6..]));
+        assert!(!h.state::<MQTTState>().request_gap);
+        assert_eq!(h.tx_count(), 2);
+        assert_eq!(publish_topic(h.get_tx::<MQTTTransaction>(1).unwrap()), "a/b");
+    }
+
+    fn publish_topic(tx: &MQTTTransaction) -> &str {
+        match tx.msg[0].op {
+            MQTTOperation::PUBLISH(ref publish) => &publish.topic,
+            _ => panic!("unexpected message {:?}", tx.msg[0]),
+        }
+    }
+
+    #[test]
+    fn test_mqtt_topic_alias() {
+        // MQTT 5, with a Topic Alias Maximum of 10 and 5
+        let connect = [
+            0x10, 0x10, 0x00, 0x04, b'M', b'Q', b'T', b'T', 0x05, 0x02, 0x00, 0x3c,
+            0x03, 0x22, 0x00, 0x0a, 0x00, 0x00,
+        ];
+        let connack = [0x20, 0x06, 0x00, 0x00, 0x03, 0x22, 0x00, 0x05];
+        // QoS 0, topic "a/b" set as alias 1, then used through it
+        let set = [0x30, 0x0b, 0x00, 0x03, b'a', b'/', b'b', 0x03, 0x23, 0x00, 0x01, b'h', b'i'];
+        let used = [0x30, 0x08, 0x00, 0x00, 0x03, 0x23, 0x00, 0x01, b'h', b'i'];
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, &connect));
+        assert!(h.feed(Direction::ToClient, &connack));
+        assert!(h.feed(Direction::ToServer, &set));
+        assert!(h.feed(Direction::ToServer, &used));
+        // the aliases of a direction are unknown to the other one
+        assert!(h.feed(Direction::ToClient, &used));
+        assert_eq!(publish_topic(h.get_tx::<MQTTTransaction>(2).unwrap()), "a/b");
+        assert_eq!(publish_topic(h.get_tx::<MQTTTransaction>(3).unwrap()), "");
+
+        let state = h.state::<MQTTState>();
+        assert_eq!(state.aliases_ts.maximum, Some(5));
+        assert_eq!(state.aliases_tc.maximum, Some(10));
+    }
+
+    #[test]
+    fn test_mqtt_topic_alias_events() {
+        let publish = |topic: &str, alias| MQTTPublishData {
+            topic: topic.to_string(),
+            topic_invalid_utf8: false,
+            message_id: None,
+            message: Vec::new(),
+            properties: Some(vec![MQTTProperty::TOPIC_ALIAS(alias)]),
+            skipped_length: None,
+        };
+        let mut aliases = TopicAliases::default();
+        // the maximum is unknown without the CONNECT or CONNACK
+        assert!(aliases.resolve(&mut publish("a", 100)).is_ok());
+        aliases.set_maximum(&Some(Vec::new()));
+        assert_eq!(aliases.maximum, Some(0));
+        aliases.set_maximum(&Some(vec![MQTTProperty::TOPIC_ALIAS_MAXIMUM(2)]));
+        assert!(aliases.resolve(&mut publish("a", 1)).is_ok());
+        match aliases.resolve(&mut publish("", 2)) {
+            Err(MQTTEvent::UnknownTopicAlias) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        for alias in &[0, 3] {
+            match aliases.resolve(&mut publish("", *alias)) {
+                Err(MQTTEvent::InvalidTopicAlias) => {}
+                r => panic!("unexpected result {:?}", r),
+            }
+        }
+        let mut p = publish("", 1);
+        assert!(aliases.resolve(&mut p).is_ok());
+        assert_eq!(p.topic, "a");
+    }
+
+    // A binary WebSocket frame, masked by the client.
+    fn ws_frame(payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+        let mut f = vec![0x82];
+        match mask {
+            Some(mask) => {
+                f.push(0x80 | payload.len() as u8);
+                f.extend_from_slice(&mask);
+                f.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+            }
+            None => {
+                f.push(payload.len() as u8);
+                f.extend_from_slice(payload);
+            }
+        }
+        f
+    }
+
+    #[test]
+    fn test_mqtt_websocket() {
+        let mask = Some([0xa1, 0xb2, 0xc3, 0xd4]);
+        let connect = ws_frame(CONNECT, mask);
+        // the PUBLISH split over two frames, with a ping between them
+        let mut publish = ws_frame(&PUBLISH[..4], mask);
+        publish[0] = 0x02; // not the final fragment
+        publish.extend_from_slice(&[0x89, 0x80, 1, 2, 3, 4]);
+        let mut last = ws_frame(&PUBLISH[4..], mask);
+        last[0] = 0x80; // final continuation
+        publish.extend(last);
+        for segment_size in 1..=connect.len() {
+            let mut h = harness();
+            assert!(h.feed_segments(Direction::ToServer, &connect, segment_size));
+            assert!(h.feed_segments(Direction::ToClient, &ws_frame(CONNACK, None), segment_size));
+            assert!(h.feed_segments(Direction::ToServer, &publish, segment_size));
+            assert!(h.feed_segments(Direction::ToClient, &ws_frame(PUBACK, None), segment_size));
+
+            assert_eq!(h.tx_count(), 2);
+            let tx = h.get_tx::<MQTTTransaction>(1).unwrap();
+            assert!(tx.complete);
+            match (&tx.msg[0].op, &tx.msg[1].op) {
+                (MQTTOperation::PUBLISH(ref p), MQTTOperation::PUBACK(_)) => {
+                    assert_eq!(p.topic, "a/b");
+                    assert_eq!(p.message, b"hi");
+                }
+                _ => panic!("unexpected messages {:?}", tx.msg),
+            }
+        }
+    }
+
+    #[test]
+    fn test_mqtt_probe_websocket() {
+        assert_eq!(probe(CONNECT), Some(true));
+        assert_eq!(probe(&ws_frame(CONNECT, Some([1, 2, 3, 4]))), Some(true));
+        // fragments, not plain MQTT messages
+        assert_eq!(probe(&[0x02, 0x84, 1, 2]), None);
+        assert_eq!(probe(&[0x02, 0x02, 0x10, 0x00]), Some(true));
+        assert_eq!(probe(&[0x02, 0x02, 0x00, 0x00]), Some(false));
+    }
+}
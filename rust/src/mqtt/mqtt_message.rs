@@ -28,6 +28,43 @@ pub struct MQTTMessage {
     pub op: MQTTOperation,
 }
 
+impl MQTTMessage {
+    /// The length of a message exceeding the maximum message length,
+    /// which is skipped after the parsed part.
+    pub fn skipped_length(&self) -> Option<usize> {
+        match self.op {
+            MQTTOperation::TRUNCATED(ref trunc) => Some(trunc.skipped_length),
+            MQTTOperation::PUBLISH(ref publish) => publish.skipped_length,
+            _ => None,
+        }
+    }
+
+    /// The MQTT 5 properties of the message. The will properties of a
+    /// CONNECT are not part of them.
+    pub fn properties(&self) -> Option<&[MQTTProperty]> {
+        let properties = match self.op {
+            MQTTOperation::CONNECT(ref d) => &d.properties,
+            MQTTOperation::CONNACK(ref d) => &d.properties,
+            MQTTOperation::PUBLISH(ref d) => &d.properties,
+            MQTTOperation::PUBACK(ref d)
+            | MQTTOperation::PUBREC(ref d)
+            | MQTTOperation::PUBREL(ref d)
+            | MQTTOperation::PUBCOMP(ref d) => &d.properties,
+            MQTTOperation::SUBSCRIBE(ref d) => &d.properties,
+            MQTTOperation::SUBACK(ref d) => &d.properties,
+            MQTTOperation::UNSUBSCRIBE(ref d) => &d.properties,
+            MQTTOperation::UNSUBACK(ref d) => &d.properties,
+            MQTTOperation::AUTH(ref d) => &d.properties,
+            MQTTOperation::DISCONNECT(ref d) => &d.properties,
+            MQTTOperation::UNASSIGNED
+            | MQTTOperation::PINGREQ
+            | MQTTOperation::PINGRESP
+            | MQTTOperation::TRUNCATED(_) => return None,
+        };
+        properties.as_ref().map(|p| &p[..])
+    }
+}
+
 #[derive(Debug)]
 pub enum MQTTOperation {
     UNASSIGNED,
@@ -125,6 +162,8 @@ pub struct MQTTConnectData {
     pub keepalive: u16,
     pub client_id: String,
     pub will_topic: Option<String>,
+    /// Whether the will topic was not valid UTF-8.
+    pub will_topic_invalid_utf8: bool,
     pub will_message: Option<Vec<u8>>,
     pub username: Option<String>,
     pub password: Option<Vec<u8>>,
@@ -142,9 +181,14 @@ pub struct MQTTConnackData {
 #[derive(Debug)]
 pub struct MQTTPublishData {
     pub topic: String,
+    /// Whether the topic was not valid UTF-8.
+    pub topic_invalid_utf8: bool,
     pub message_id: Option<u16>,
     pub message: Vec<u8>,
     pub properties: Option<Vec<MQTTProperty>>, // MQTT 5.0
+    /// The length of the whole PUBLISH if it exceeds the maximum message
+    /// length, of which only the start of the message was kept.
+    pub skipped_length: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -154,10 +198,40 @@ pub struct MQTTMessageIdOnly {
     pub properties: Option<Vec<MQTTProperty>>, // MQTT 5.0
 }
 
+/// The options of a subscription besides its QoS, see section 3.8.3.1
+/// of the MQTT 5.0 specification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MQTTSubscriptionOptions {
+    pub no_local: bool,
+    pub retain_as_published: bool,
+    pub retain_handling: u8,
+    /// The reserved bits, which must be 0.
+    pub reserved: u8,
+}
+
+impl MQTTSubscriptionOptions {
+    pub fn from_byte(b: u8) -> Self {
+        MQTTSubscriptionOptions {
+            no_local: b & 0x04 != 0,
+            retain_as_published: b & 0x08 != 0,
+            retain_handling: (b >> 4) & 0x03,
+            reserved: b >> 6,
+        }
+    }
+
+    /// Retain Handling 3 and the reserved bits are errors.
+    pub fn is_valid(&self) -> bool {
+        self.retain_handling < 3 && self.reserved == 0
+    }
+}
+
 #[derive(Debug)]
 pub struct MQTTSubscribeTopicData {
     pub topic_name: String,
+    /// Whether the topic filter was not valid UTF-8.
+    pub invalid_utf8: bool,
     pub qos: u8,
+    pub options: Option<MQTTSubscriptionOptions>, // MQTT 5.0
 }
 
 #[derive(Debug)]
@@ -178,6 +252,8 @@ pub struct MQTTSubackData {
 pub struct MQTTUnsubscribeData {
     pub message_id: u16,
     pub topics: Vec<String>,
+    /// Whether any of the topic filters was not valid UTF-8.
+    pub invalid_utf8: bool,
     pub properties: Option<Vec<MQTTProperty>>, // MQTT 5.0
 }
 
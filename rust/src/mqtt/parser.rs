@@ -19,6 +19,7 @@
 
 use crate::mqtt::mqtt_message::*;
 use crate::mqtt::mqtt_property::*;
+use crate::parsers_util::{length_data_be_u16, length_string_be_u16, varint_u32};
 use nom::combinator::rest;
 use nom::number::streaming::*;
 use nom::*;
@@ -33,54 +34,22 @@ pub struct FixedHeader {
     pub remaining_length: u32,
 }
 
-// PARSING HELPERS
-
-#[inline]
-fn is_continuation_bit_set(b: u8) -> bool {
-    return (b & 128) != 0;
-}
-
-#[inline]
-fn convert_varint(continued: Vec<u8>, last: u8) -> u32 {
-    let mut multiplier = 1u32;
-    let mut value = 0u32;
-    for val in &continued {
-        value = value + ((val & 127) as u32 * multiplier);
-        multiplier = multiplier * 128u32;
-    }
-    value = value + ((last & 127) as u32 * multiplier);
-    return value;
-}
-
 // DATA TYPES
 
-named!(#[inline], pub parse_mqtt_string<String>,
-       do_parse!(
-           length: be_u16
-           >> content: take!(length)
-           >>  (
-                 String::from_utf8_lossy(content).to_string()
-               )
-       ));
+named!(#[inline], pub parse_mqtt_string<String>, call!(length_string_be_u16));
 
-named!(#[inline], pub parse_mqtt_variable_integer<u32>,
-       do_parse!(
-           // take at most 4 bytes in total, so as not to overflow u32
-           continued_part: take_while_m_n!(0, 3, is_continuation_bit_set)
-           >> non_continued_part: verify!(be_u8, |&val| !is_continuation_bit_set(val))
-           >>  (
-                 convert_varint(continued_part.to_vec(), non_continued_part)
-               )
-       ));
+// Topic names and filters, and whether they are valid UTF-8: the
+// invalid ones are decoded lossily, and set an event.
+named!(#[inline], pub parse_mqtt_topic<(String, bool)>,
+       map!(length_data_be_u16, |data| match std::str::from_utf8(data) {
+           Ok(s) => (s.to_string(), true),
+           Err(_) => (String::from_utf8_lossy(data).into_owned(), false),
+       }));
+
+named!(#[inline], pub parse_mqtt_variable_integer<u32>, call!(varint_u32));
 
 named!(#[inline], pub parse_mqtt_binary_data<Vec<u8>>,
-       do_parse!(
-           length: be_u16
-           >> data: take!(length)
-           >>  (
-                 data.to_vec()
-               )
-       ));
+       map!(length_data_be_u16, |data| data.to_vec()));
 
 named!(#[inline], pub parse_mqtt_string_pair<(String, String)>,
        do_parse!(
@@ -109,30 +78,38 @@ fn parse_properties(input: &[u8], precond: bool) -> IResult<&[u8], Option<Vec<MQ
         return Ok((input, None));
     }
     // parse properties length
-    match parse_mqtt_variable_integer(input) {
-        Ok((rem, mut proplen)) => {
-            if proplen == 0 {
-                // no properties
-                return Ok((rem, None));
+    let (rem, proplen) = parse_mqtt_variable_integer(input)?;
+    if proplen == 0 {
+        // no properties
+        return Ok((rem, None));
+    }
+    // parse properties, which must fit in the announced length: the
+    // message is complete here, so running out of data is an error
+    let proplen = proplen as usize;
+    if rem.len() < proplen {
+        return Err(Err::Error((rem, error::ErrorKind::LengthValue)));
+    }
+    let (mut data, rem) = rem.split_at(proplen);
+    let mut props = Vec::<MQTTProperty>::new();
+    while !data.is_empty() {
+        match parse_property(data) {
+            Ok((_, MQTTProperty::UNKNOWN)) => {
+                // the length of an unknown property is unknown too, so
+                // the remaining properties are skipped
+                props.push(MQTTProperty::UNKNOWN);
+                break;
             }
-            // parse properties
-            let mut props = Vec::<MQTTProperty>::new();
-            let mut newrem = rem;
-            while proplen > 0 {
-                match parse_property(newrem) {
-                    Ok((rem, val)) => {
-                        props.push(val);
-                        let curparselen = (newrem.len() - rem.len()) as u32;
-                        proplen -= curparselen;
-                        newrem = rem;
-                    }
-                    Err(e) => return Err(e),
-                }
+            Ok((newrem, val)) => {
+                props.push(val);
+                data = newrem;
+            }
+            Err(Err::Incomplete(_)) => {
+                return Err(Err::Error((data, error::ErrorKind::LengthValue)));
             }
-            return Ok((newrem, Some(props)));
+            Err(e) => return Err(e),
         }
-        Err(e) => return Err(e),
     }
+    return Ok((rem, Some(props)));
 }
 
 #[inline]
@@ -206,7 +183,7 @@ named!(#[inline], pub parse_connect<MQTTConnectData>,
            >> properties: call!(parse_properties, protocol_version == 5)
            >> client_id: parse_mqtt_string
            >> will_properties: call!(parse_properties, protocol_version == 5 && flags.4 != 0)
-           >> will_topic: cond!(flags.4 != 0, parse_mqtt_string)
+           >> will_topic: cond!(flags.4 != 0, parse_mqtt_topic)
            >> will_message: cond!(flags.4 != 0, parse_mqtt_binary_data)
            >> username: cond!(flags.0 != 0, parse_mqtt_string)
            >> password: cond!(flags.1 != 0, parse_mqtt_binary_data)
@@ -222,7 +199,8 @@ named!(#[inline], pub parse_connect<MQTTConnectData>,
                    clean_session: flags.5 != 0,
                    keepalive: keepalive,
                    client_id: client_id,
-                   will_topic: will_topic,
+                   will_topic_invalid_utf8: will_topic.as_ref().map_or(false, |t| !t.1),
+                   will_topic: will_topic.map(|t| t.0),
                    will_message: will_message,
                    username: username,
                    password: password,
@@ -248,16 +226,18 @@ named_args!(pub parse_connack(protocol_version: u8)<MQTTConnackData>,
 
 named_args!(pub parse_publish(protocol_version: u8, has_id: bool)<MQTTPublishData>,
        do_parse!(
-           topic: parse_mqtt_string
+           topic: parse_mqtt_topic
            >> message_id: cond!(has_id, be_u16)
            >> properties: call!(parse_properties, protocol_version == 5)
            >> message: rest
            >>  (
                  MQTTPublishData {
-                   topic: topic,
+                   topic: topic.0,
+                   topic_invalid_utf8: !topic.1,
                    message_id: message_id,
                    message: message.to_vec(),
                    properties: properties,
+                   skipped_length: None,
                  }
                )
        ));
@@ -337,14 +317,22 @@ named!(#[inline], pub parse_msgidonly_v3<MQTTMessageIdOnly>,
                )
        ));
 
-named!(#[inline], pub parse_subscribe_topic<MQTTSubscribeTopicData>,
+// With MQTT 5 the byte after the topic filter holds the subscription
+// options besides the QoS.
+named_args!(pub parse_subscribe_topic(protocol_version: u8)<MQTTSubscribeTopicData>,
        do_parse!(
-           topic: parse_mqtt_string
-           >> qos: be_u8
+           topic: parse_mqtt_topic
+           >> options: be_u8
            >>  (
                  MQTTSubscribeTopicData {
-                   topic_name: topic,
-                   qos: qos,
+                   topic_name: topic.0,
+                   invalid_utf8: !topic.1,
+                   qos: if protocol_version == 5 { options & 0x03 } else { options },
+                   options: if protocol_version == 5 {
+                       Some(MQTTSubscriptionOptions::from_byte(options))
+                   } else {
+                       None
+                   },
                  }
                )
        ));
@@ -353,7 +341,7 @@ named_args!(pub parse_subscribe(protocol_version: u8)<MQTTSubscribeData>,
        do_parse!(
            message_id: be_u16
            >> properties: call!(parse_properties, protocol_version == 5)
-           >> topics: many1!(complete!(parse_subscribe_topic))
+           >> topics: many1!(complete!(call!(parse_subscribe_topic, protocol_version)))
            >>  (
                  MQTTSubscribeData {
                    message_id: message_id,
@@ -381,11 +369,12 @@ named_args!(pub parse_unsubscribe(protocol_version: u8)<MQTTUnsubscribeData>,
        do_parse!(
            message_id: be_u16
            >> properties: call!(parse_properties, protocol_version == 5)
-           >> topics: many0!(complete!(parse_mqtt_string))
+           >> topics: many0!(complete!(parse_mqtt_topic))
            >>  (
                  MQTTUnsubscribeData {
                    message_id: message_id,
-                   topics: topics,
+                   invalid_utf8: topics.iter().any(|t| !t.1),
+                   topics: topics.into_iter().map(|t| t.0).collect(),
                    properties: properties,
                  }
                )
@@ -463,19 +452,56 @@ fn parse_disconnect(
     }
 }
 
-named!(#[inline], pub parse_auth<MQTTAuthData>,
-       do_parse!(
-           reason_code: be_u8
-           >> properties: call!(parse_properties, true)
-           >>  (
-                 MQTTAuthData {
-                   reason_code: reason_code,
-                   properties: properties,
-                 }
-               )
-       ));
+pub fn parse_auth(input: &[u8]) -> IResult<&[u8], MQTTAuthData> {
+    if input.is_empty() {
+        // The Reason Code and Property Length can be omitted if the Reason
+        // Code is 0x00 (Success) and there are no Properties. In this case
+        // the AUTH has a Remaining Length of 0.
+        return Ok((
+            input,
+            MQTTAuthData {
+                reason_code: 0,
+                properties: None,
+            },
+        ));
+    }
+    let (rem, reason_code) = be_u8(input)?;
+    let (rem, properties) = parse_properties(rem, !rem.is_empty())?;
+    Ok((
+        rem,
+        MQTTAuthData {
+            reason_code: reason_code,
+            properties: properties,
+        },
+    ))
+}
+
+// Keep the start of a PUBLISH exceeding the maximum message length: its
+// topic, message id and properties, and up to `max_prefix` bytes of its
+// message. At most `max_msg_size` bytes are buffered for it, None is
+// returned if they are not enough.
+fn parse_publish_prefix(
+    input: &[u8], protocol_version: u8, has_id: bool, max_msg_size: usize, max_prefix: usize,
+) -> Result<Option<MQTTPublishData>, Needed> {
+    let data = &input[..std::cmp::min(input.len(), max_msg_size)];
+    match parse_publish(data, protocol_version, has_id) {
+        Ok((_, mut publish)) => {
+            let header_len = data.len() - publish.message.len();
+            let wanted = std::cmp::min(header_len + max_prefix, max_msg_size);
+            if data.len() < wanted {
+                return Err(Needed::Size(wanted - data.len()));
+            }
+            publish.message.truncate(max_prefix);
+            Ok(Some(publish))
+        }
+        Err(_) if data.len() == max_msg_size => Ok(None),
+        Err(_) => Err(Needed::Size(1)),
+    }
+}
 
-pub fn parse_message(input: &[u8], protocol_version: u8, max_msg_size: usize) -> IResult<&[u8], MQTTMessage> {
+pub fn parse_message(
+    input: &[u8], protocol_version: u8, max_msg_size: usize, max_prefix: usize,
+) -> IResult<&[u8], MQTTMessage> {
     // Parse the fixed header first. This is identical across versions and can
     // be between 2 and 5 bytes long.
     match parse_fixed_header(input) {
@@ -491,8 +517,23 @@ pub fn parse_message(input: &[u8], protocol_version: u8, max_msg_size: usize) ->
             // If the remaining length (message length) exceeds the specified
             // limit, we return a special truncation message type, containing
             // no parsed metadata but just the skipped length and the message
-            // type.
+            // type. Of a PUBLISH, the start of the message can be kept.
             if len > max_msg_size {
+                if message_type == MQTTTypeCode::PUBLISH && max_prefix > 0 {
+                    match parse_publish_prefix(fullrem, protocol_version, header.qos_level > 0,
+                                               max_msg_size, max_prefix) {
+                        Ok(Some(mut publish)) => {
+                            publish.skipped_length = Some(len + skiplen);
+                            let msg = MQTTMessage {
+                                header: header,
+                                op: MQTTOperation::PUBLISH(publish),
+                            };
+                            return Ok((input, msg));
+                        }
+                        Ok(None) => {}
+                        Err(needed) => return Err(Err::Incomplete(needed)),
+                    }
+                }
                 let msg = MQTTMessage {
                     header: header,
                     op: MQTTOperation::TRUNCATED(MQTTTruncatedData {
@@ -657,6 +698,79 @@ pub fn parse_message(input: &[u8], protocol_version: u8, max_msg_size: usize) ->
     }
 }
 
+// Whether the fixed header has the flags the specification requires for
+// its message type.
+fn is_plausible_header(hdr: &FixedHeader) -> bool {
+    match hdr.message_type {
+        MQTTTypeCode::UNASSIGNED => false,
+        MQTTTypeCode::PUBLISH => hdr.qos_level <= 2 && (hdr.qos_level > 0 || !hdr.dup_flag),
+        MQTTTypeCode::PUBREL | MQTTTypeCode::SUBSCRIBE | MQTTTypeCode::UNSUBSCRIBE => {
+            !hdr.dup_flag && hdr.qos_level == 1 && !hdr.retain
+        }
+        _ => !hdr.dup_flag && hdr.qos_level == 0 && !hdr.retain,
+    }
+}
+
+/// Whether `b` is the first byte of a plausible fixed header.
+fn is_plausible_type(b: u8) -> bool {
+    match parse_fixed_header(&[b, 0]) {
+        Ok((_, hdr)) => is_plausible_header(&hdr),
+        _ => false,
+    }
+}
+
+/// Where parsing can resume after a gap, see `find_message_start`.
+#[derive(Debug, PartialEq)]
+pub enum MessageStart {
+    /// A message which looks valid starts at this offset.
+    Found(usize),
+    /// No message was found, but one with a plausible fixed header
+    /// starting at this offset runs past the end of the input. The data
+    /// before it can be dropped, more is needed to tell.
+    Incomplete(usize),
+    NotFound,
+}
+
+/// Find the first message of `input` which looks valid, to resume
+/// parsing after a gap: its fixed header is plausible, it is complete
+/// and parses, and it is followed by the end of the input or by another
+/// plausible fixed header.
+pub fn find_message_start(input: &[u8], protocol_version: u8, max_msg_size: usize) -> MessageStart {
+    let mut incomplete = None;
+    for i in 0..input.len() {
+        let data = &input[i..];
+        let (rem, hdr) = match parse_fixed_header(data) {
+            Ok((rem, hdr)) if is_plausible_header(&hdr) => (rem, hdr),
+            Err(Err::Incomplete(_)) if is_plausible_type(data[0]) => {
+                // a fixed header cut by the end of the input
+                incomplete = incomplete.or(Some(i));
+                continue;
+            }
+            _ => continue,
+        };
+        let len = hdr.remaining_length as usize;
+        if len > max_msg_size {
+            continue;
+        }
+        if rem.len() < len {
+            incomplete = incomplete.or(Some(i));
+            continue;
+        }
+        match parse_fixed_header(&rem[len..]) {
+            Ok((_, next)) if is_plausible_header(&next) => {}
+            Err(Err::Incomplete(_)) => {}
+            _ => continue,
+        }
+        if parse_message(data, protocol_version, max_msg_size, 0).is_ok() {
+            return MessageStart::Found(i);
+        }
+    }
+    match incomplete {
+        Some(i) => MessageStart::Incomplete(i),
+        None => MessageStart::NotFound,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -707,4 +821,194 @@ mod tests {
     fn test_mqtt_parse_variable_integer_smallest_valid() {
         test_mqtt_parse_variable_check(&[0x0], 0);
     }
+
+    #[test]
+    fn test_mqtt_parse_subscribe_options() {
+        // message id 1, no properties, "a" with QoS 1, no local, retain
+        // as published and retain handling 2, and "b" with reserved bits
+        let buf = [0x00, 0x01, 0x00, 0x00, 0x01, b'a', 0x2d, 0x00, 0x01, b'b', 0xc0];
+        let (rem, subscribe) = parse_subscribe(&buf, 5).unwrap();
+        assert!(rem.is_empty());
+        let t = &subscribe.topics[0];
+        assert_eq!(t.qos, 1);
+        assert_eq!(t.options, Some(MQTTSubscriptionOptions {
+            no_local: true,
+            retain_as_published: true,
+            retain_handling: 2,
+            reserved: 0,
+        }));
+        assert!(t.options.unwrap().is_valid());
+        let t = &subscribe.topics[1];
+        assert_eq!(t.qos, 0);
+        assert!(!t.options.unwrap().is_valid());
+
+        // no options before MQTT 5
+        let (_, subscribe) = parse_subscribe(&[0x00, 0x01, 0x00, 0x01, b'a', 0x01], 4).unwrap();
+        assert_eq!(subscribe.topics[0].qos, 1);
+        assert_eq!(subscribe.topics[0].options, None);
+    }
+
+    #[test]
+    fn test_mqtt_parse_auth() {
+        // success without reason code and properties
+        let (rem, msg) = parse_message(&[0xf0, 0x00, 0xe0, 0x00], 5, 1024, 0).unwrap();
+        assert_eq!(rem, &[0xe0, 0x00]);
+        match msg.op {
+            MQTTOperation::AUTH(ref auth) => {
+                assert_eq!(auth.reason_code, 0);
+                assert!(auth.properties.is_none());
+            }
+            _ => panic!("unexpected message {:?}", msg),
+        }
+        let (_, auth) = parse_auth(&[0x18]).unwrap();
+        assert_eq!(auth.reason_code, 0x18);
+        assert!(auth.properties.is_none());
+        let (_, auth) = parse_auth(&[0x18, 0x05, 0x15, 0x00, 0x02, b'G', b'S']).unwrap();
+        match auth.properties.unwrap()[0] {
+            MQTTProperty::AUTHENTICATION_METHOD(ref m) => assert_eq!(m, "GS"),
+            ref p => panic!("unexpected property {:?}", p),
+        }
+    }
+
+    #[test]
+    fn test_mqtt_parse_publish_prefix() {
+        // QoS 0 PUBLISH on "a/b" with a message of 16 bytes
+        let mut buf = vec![0x30, 0x15, 0x00, 0x03, b'a', b'/', b'b'];
+        buf.extend_from_slice(b"0123456789abcdef");
+        // the prefix is buffered
+        assert!(match parse_message(&buf[..8], 4, 10, 4) {
+            Err(Err::Incomplete(_)) => true,
+            _ => false,
+        });
+        let (rem, msg) = parse_message(&buf, 4, 10, 4).unwrap();
+        assert_eq!(rem.len(), buf.len());
+        assert_eq!(msg.skipped_length(), Some(23));
+        match msg.op {
+            MQTTOperation::PUBLISH(ref publish) => assert_eq!(publish.message, b"0123"),
+            _ => panic!("unexpected message {:?}", msg),
+        }
+        // up to the maximum message length
+        let (_, msg) = parse_message(&buf, 4, 7, 4).unwrap();
+        match msg.op {
+            MQTTOperation::PUBLISH(ref publish) => assert_eq!(publish.message, b"01"),
+            _ => panic!("unexpected message {:?}", msg),
+        }
+        // the topic does not fit
+        let (_, msg) = parse_message(&buf, 4, 4, 4).unwrap();
+        assert!(match msg.op {
+            MQTTOperation::TRUNCATED(_) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn test_mqtt_find_message_start() {
+        // the end of a PUBLISH message, a PUBACK and a PINGREQ
+        let buf = [0x30, b'x', 0x40, 0x02, 0x00, 0x01, 0xc0, 0x00];
+        assert_eq!(find_message_start(&buf, 4, 1024), MessageStart::Found(2));
+        // a PUBLISH without topic does not parse
+        assert_eq!(find_message_start(&[0x30, 0x00, 0xc0, 0x00], 4, 1024),
+                   MessageStart::Found(2));
+        // the PUBLISH and the PUBACK are incomplete, the PUBLISH may be
+        // a message
+        assert_eq!(find_message_start(&buf[..5], 4, 1024), MessageStart::Incomplete(0));
+        assert_eq!(find_message_start(&buf[1..5], 4, 1024), MessageStart::Incomplete(1));
+        // the PINGREQ is cut before its remaining length
+        assert_eq!(find_message_start(&[0x00, 0xc0], 4, 1024), MessageStart::Incomplete(1));
+        // a SUBSCRIBE needs the QoS 1 flags
+        let buf = [0x80, 0x05, 0x00, 0x01, 0x00, 0x00, 0x00, 0xc0, 0x00];
+        assert_eq!(find_message_start(&buf, 4, 1024), MessageStart::Found(7));
+        // a PUBACK not followed by a fixed header
+        assert_eq!(find_message_start(&[0x40, 0x02, 0x00, 0x01, 0x00, 0x00], 4, 1024),
+                   MessageStart::NotFound);
+    }
+
+    #[test]
+    fn test_mqtt_parse_topic_utf8() {
+        let (_, publish) = parse_publish(&[0x00, 0x03, b'a', 0xff, b'b', b'x'], 4, false).unwrap();
+        assert!(publish.topic_invalid_utf8);
+        assert_eq!(publish.topic, "a\u{fffd}b");
+        assert_eq!(publish.message, b"x");
+        let (_, publish) = parse_publish(&[0x00, 0x02, 0xc3, 0xa9], 4, false).unwrap();
+        assert!(!publish.topic_invalid_utf8);
+        assert_eq!(publish.topic, "\u{e9}");
+
+        let buf = [0x00, 0x01, 0x00, 0x01, b'a', 0x00, 0x01, 0xc3];
+        let (_, unsubscribe) = parse_unsubscribe(&buf, 4).unwrap();
+        assert_eq!(unsubscribe.topics.len(), 2);
+        assert!(unsubscribe.invalid_utf8);
+    }
+
+    #[test]
+    fn test_mqtt_parse_publish_properties() {
+        let buf = [
+            0x30, 22, // PUBLISH, remaining length
+            0x00, 0x03, b'a', b'/', b'b', // topic
+            14, // properties length
+            0x26, 0x00, 0x01, b'k', 0x00, 0x01, b'v', // user property
+            0x03, 0x00, 0x04, b't', b'e', b'x', b't', // content type
+            b'h', b'i',
+        ];
+        let (rem, msg) = parse_message(&buf, 5, 1024, 0).unwrap();
+        assert!(rem.is_empty());
+        let props = msg.properties().unwrap();
+        assert_eq!(props.len(), 2);
+        match props[0] {
+            MQTTProperty::USER_PROPERTY((ref k, ref v)) => {
+                assert_eq!(k, "k");
+                assert_eq!(v, "v");
+            }
+            _ => panic!("expected a user property"),
+        }
+        match props[1] {
+            MQTTProperty::CONTENT_TYPE(ref t) => assert_eq!(t, "text"),
+            _ => panic!("expected a content type"),
+        }
+        match msg.op {
+            MQTTOperation::PUBLISH(ref p) => assert_eq!(p.message, b"hi"),
+            _ => panic!("expected a PUBLISH"),
+        }
+    }
+
+    #[test]
+    fn test_mqtt_parse_properties_overflow() {
+        // the content type overflows the properties length
+        let buf = [
+            0x30, 15,
+            0x00, 0x03, b'a', b'/', b'b',
+            5,
+            0x03, 0x00, 0x04, b't', b'e', b'x', b't',
+            b'h', b'i',
+        ];
+        match parse_message(&buf, 5, 1024, 0) {
+            Err(Err::Error(err)) => assert_eq!(err.1, error::ErrorKind::LengthValue),
+            r => panic!("unexpected result {:?}", r),
+        }
+        // the properties length overflows the message
+        let buf = [0x30, 7, 0x00, 0x03, b'a', b'/', b'b', 20, 0x01];
+        match parse_message(&buf, 5, 1024, 0) {
+            Err(Err::Error(err)) => assert_eq!(err.1, error::ErrorKind::LengthValue),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_mqtt_parse_properties_unknown() {
+        // an unknown property identifier ends the properties
+        let buf = [
+            0x30, 12,
+            0x00, 0x03, b'a', b'/', b'b',
+            4,
+            0x01, 0x01, 0x7f, 0x00,
+            b'h', b'i',
+        ];
+        let (rem, msg) = parse_message(&buf, 5, 1024, 0).unwrap();
+        assert!(rem.is_empty());
+        let props = msg.properties().unwrap();
+        assert_eq!(props.len(), 2);
+        match props[1] {
+            MQTTProperty::UNKNOWN => {}
+            _ => panic!("expected an unknown property"),
+        }
+    }
 }
@@ -0,0 +1,129 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Analysis of topic names and of the topic filters of subscriptions,
+//! see section 4.7 of the MQTT 5.0 specification.
+
+const SHARE_PREFIX: &str = "$share/";
+
+/// A topic filter, with the share name of a shared subscription.
+#[derive(Debug, PartialEq)]
+pub struct TopicFilter<'a> {
+    pub share_name: Option<&'a str>,
+    pub filter: &'a str,
+}
+
+/// Split a shared subscription, "$share/{ShareName}/{filter}", of MQTT 5.
+/// Returns an error if the share name is empty or has wildcards, or if
+/// the filter is missing.
+pub fn parse_shared(topic: &str) -> Result<TopicFilter<'_>, ()> {
+    if !topic.starts_with(SHARE_PREFIX) {
+        return Ok(TopicFilter { share_name: None, filter: topic });
+    }
+    let rest = &topic[SHARE_PREFIX.len()..];
+    let sep = rest.find('/').ok_or(())?;
+    let (share_name, filter) = (&rest[..sep], &rest[sep + 1..]);
+    if share_name.is_empty() || share_name.contains(&['+', '#'][..]) || filter.is_empty() {
+        return Err(());
+    }
+    Ok(TopicFilter { share_name: Some(share_name), filter })
+}
+
+/// Whether the topic has a NUL character, which UTF-8 strings of MQTT
+/// must not have.
+pub fn has_nul(topic: &str) -> bool {
+    topic.contains('\0')
+}
+
+/// A topic name, as published to, must not have wildcards.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.contains(&['+', '#'][..])
+}
+
+/// A topic filter must not be empty, and its wildcards must take a whole
+/// level, with "#" only as the last level.
+pub fn is_valid_filter(filter: &str) -> bool {
+    if filter.is_empty() {
+        return false;
+    }
+    let mut levels = filter.split('/').peekable();
+    while let Some(level) = levels.next() {
+        if level == "#" {
+            return levels.peek().is_none();
+        }
+        if level != "+" && level.contains(&['+', '#'][..]) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Whether all the levels of the filter are wildcards, like "#" or
+/// "+/+/#", so it matches any topic of its depth.
+pub fn is_match_all(filter: &str) -> bool {
+    filter.split('/').all(|level| level == "+" || level == "#")
+}
+
+/// Whether the filter is for the $SYS topics of the server, which the
+/// wildcards at the first level don't match.
+pub fn is_sys(filter: &str) -> bool {
+    filter == "$SYS" || filter.starts_with("$SYS/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mqtt_topic_shared() {
+        assert_eq!(parse_shared("a/b"), Ok(TopicFilter { share_name: None, filter: "a/b" }));
+        assert_eq!(parse_shared("$share/g1/a/#"),
+                   Ok(TopicFilter { share_name: Some("g1"), filter: "a/#" }));
+        assert_eq!(parse_shared("$share/g1"), Err(()));
+        assert_eq!(parse_shared("$share//a"), Err(()));
+        assert_eq!(parse_shared("$share/g1/"), Err(()));
+        assert_eq!(parse_shared("$share/g+/a"), Err(()));
+    }
+
+    #[test]
+    fn test_mqtt_topic_wildcards() {
+        assert!(is_match_all("#"));
+        assert!(is_match_all("+/+/#"));
+        assert!(!is_match_all("a/#"));
+        assert!(!is_match_all("+/a"));
+        assert!(is_sys("$SYS/#"));
+        assert!(is_sys("$SYS"));
+        assert!(!is_sys("$SYSTEM/a"));
+    }
+
+    #[test]
+    fn test_mqtt_topic_validity() {
+        assert!(is_valid_name("a/b"));
+        assert!(is_valid_name("/"));
+        assert!(!is_valid_name("a/+"));
+        assert!(!is_valid_name("a#"));
+        assert!(is_valid_filter("#"));
+        assert!(is_valid_filter("a/+/b/#"));
+        assert!(is_valid_filter("+/+"));
+        assert!(!is_valid_filter(""));
+        assert!(!is_valid_filter("a/#/b"));
+        assert!(!is_valid_filter("a#"));
+        assert!(!is_valid_filter("a/b+"));
+        assert!(has_nul("a\0b"));
+        assert!(!has_nul("a/b"));
+    }
+}
@@ -0,0 +1,232 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! WebSocket framing (RFC 6455) of MQTT, as used after an HTTP upgrade
+//! with the "mqtt" subprotocol. The MQTT stream is the concatenation of
+//! the payloads of the binary and continuation frames, the control
+//! frames are skipped.
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_BINARY: u8 = 0x2;
+
+/// Length of the longest frame header: 2 bytes, a 64 bit length and
+/// the masking key.
+const MAX_HEADER_LEN: usize = 14;
+
+#[derive(Debug, PartialEq)]
+pub struct FrameHeader {
+    pub fin: bool,
+    pub opcode: u8,
+    pub mask: Option<[u8; 4]>,
+    pub payload_len: u64,
+}
+
+/// Parse a frame header. Returns None if more data is needed, or an
+/// error if reserved bits are set: MQTT does not negotiate extensions.
+pub fn parse_frame_header(buf: &[u8]) -> Result<Option<(FrameHeader, usize)>, ()> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    if buf[0] & 0x70 != 0 {
+        return Err(());
+    }
+    let fin = buf[0] & 0x80 != 0;
+    let opcode = buf[0] & 0x0f;
+    let masked = buf[1] & 0x80 != 0;
+    let (payload_len, mut len) = match buf[1] & 0x7f {
+        126 => {
+            if buf.len() < 4 {
+                return Ok(None);
+            }
+            (u64::from(buf[2]) << 8 | u64::from(buf[3]), 4)
+        }
+        127 => {
+            if buf.len() < 10 {
+                return Ok(None);
+            }
+            let len = buf[2..10].iter().fold(0u64, |len, &b| len << 8 | u64::from(b));
+            (len, 10)
+        }
+        n => (u64::from(n), 2),
+    };
+    let mask = if masked {
+        if buf.len() < len + 4 {
+            return Ok(None);
+        }
+        let mut mask = [0u8; 4];
+        mask.copy_from_slice(&buf[len..len + 4]);
+        len += 4;
+        Some(mask)
+    } else {
+        None
+    };
+    Ok(Some((FrameHeader { fin, opcode, mask, payload_len }, len)))
+}
+
+fn unmask(data: &mut [u8], mask: [u8; 4], offset: usize) {
+    for (i, b) in data.iter_mut().enumerate() {
+        *b ^= mask[(offset + i) % 4];
+    }
+}
+
+/// The start of the payload of the binary frame at the start of `buf`,
+/// unmasked, for protocol detection. Returns None if more data is
+/// needed, or an error if `buf` does not start with a binary frame.
+pub fn probe_payload(buf: &[u8]) -> Result<Option<Vec<u8>>, ()> {
+    if buf.is_empty() {
+        return Ok(None);
+    }
+    if buf[0] & 0x7f != OPCODE_BINARY {
+        return Err(());
+    }
+    let (hdr, len) = match parse_frame_header(buf)? {
+        Some(r) => r,
+        None => return Ok(None),
+    };
+    let avail = std::cmp::min((buf.len() - len) as u64, hdr.payload_len) as usize;
+    if avail == 0 {
+        return Ok(None);
+    }
+    let mut payload = buf[len..len + avail].to_vec();
+    if let Some(mask) = hdr.mask {
+        unmask(&mut payload, mask, 0);
+    }
+    Ok(Some(payload))
+}
+
+/// Removes the framing of a direction of a WebSocket connection. The
+/// frames can span several calls to `feed`.
+#[derive(Debug, Default)]
+pub struct WebSocketDeframer {
+    /// Bytes of an incomplete frame header.
+    header: Vec<u8>,
+    /// Bytes of the payload of the current frame still to come.
+    remaining: u64,
+    mask: Option<[u8; 4]>,
+    mask_offset: usize,
+    /// Whether the current frame carries MQTT data.
+    is_data: bool,
+    /// The MQTT data, not yet consumed by the parser.
+    pub data: Vec<u8>,
+}
+
+impl WebSocketDeframer {
+    /// Append the payloads of the data frames of `input` to `data`.
+    pub fn feed(&mut self, mut input: &[u8]) -> Result<(), ()> {
+        while !input.is_empty() {
+            if self.remaining == 0 {
+                let take = std::cmp::min(MAX_HEADER_LEN - self.header.len(), input.len());
+                self.header.extend_from_slice(&input[..take]);
+                let (hdr, len) = match parse_frame_header(&self.header)? {
+                    Some(r) => r,
+                    None => {
+                        input = &input[take..];
+                        continue;
+                    }
+                };
+                // the bytes past the header are the start of the payload
+                let extra = self.header.len() - len;
+                input = &input[take - extra..];
+                self.header.clear();
+                self.remaining = hdr.payload_len;
+                self.mask = hdr.mask;
+                self.mask_offset = 0;
+                self.is_data = hdr.opcode == OPCODE_BINARY || hdr.opcode == OPCODE_CONTINUATION;
+                continue;
+            }
+            let n = std::cmp::min(self.remaining, input.len() as u64) as usize;
+            if self.is_data {
+                let start = self.data.len();
+                self.data.extend_from_slice(&input[..n]);
+                if let Some(mask) = self.mask {
+                    unmask(&mut self.data[start..], mask, self.mask_offset);
+                }
+            }
+            self.mask_offset = (self.mask_offset + n) % 4;
+            self.remaining -= n as u64;
+            input = &input[n..];
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(opcode: u8, mask: Option<[u8; 4]>, payload: &[u8]) -> Vec<u8> {
+        let mut f = vec![0x80 | opcode];
+        let masked = if mask.is_some() { 0x80 } else { 0 };
+        if payload.len() < 126 {
+            f.push(masked | payload.len() as u8);
+        } else {
+            f.push(masked | 126);
+            f.push((payload.len() >> 8) as u8);
+            f.push(payload.len() as u8);
+        }
+        let mut payload = payload.to_vec();
+        if let Some(mask) = mask {
+            f.extend_from_slice(&mask);
+            unmask(&mut payload, mask, 0);
+        }
+        f.extend_from_slice(&payload);
+        f
+    }
+
+    #[test]
+    fn test_websocket_header() {
+        assert_eq!(parse_frame_header(&[0x82]), Ok(None));
+        assert_eq!(parse_frame_header(&[0x82, 0xfe, 0x01]), Ok(None));
+        assert_eq!(parse_frame_header(&[0x82, 0x85, 1, 2, 3]), Ok(None));
+        assert_eq!(parse_frame_header(&[0xc2, 0x00]), Err(()));
+        let (hdr, len) = parse_frame_header(&[0x02, 0x7e, 0x01, 0x00]).unwrap().unwrap();
+        assert_eq!(hdr, FrameHeader { fin: false, opcode: 2, mask: None, payload_len: 256 });
+        assert_eq!(len, 4);
+    }
+
+    #[test]
+    fn test_websocket_deframe() {
+        let mask = Some([0x12, 0x34, 0x56, 0x78]);
+        let long = vec![0x42u8; 300];
+        let mut input = frame(OPCODE_BINARY, mask, b"hello");
+        // a ping in the middle of the stream
+        input.extend(frame(0x9, mask, b"ping"));
+        input.extend(frame(OPCODE_CONTINUATION, mask, &long));
+        input.extend(frame(OPCODE_BINARY, None, b""));
+
+        let mut expected = b"hello".to_vec();
+        expected.extend_from_slice(&long);
+        for segment_size in 1..input.len() {
+            let mut ws = WebSocketDeframer::default();
+            for chunk in input.chunks(segment_size) {
+                ws.feed(chunk).unwrap();
+            }
+            assert_eq!(ws.data, expected, "segment size {}", segment_size);
+        }
+    }
+
+    #[test]
+    fn test_websocket_probe() {
+        let mask = Some([1, 2, 3, 4]);
+        assert_eq!(probe_payload(&frame(OPCODE_BINARY, mask, &[0x10, 0x00])),
+                   Ok(Some(vec![0x10, 0x00])));
+        assert_eq!(probe_payload(&frame(OPCODE_BINARY, None, &[0x20, 0x00])[..3]),
+                   Ok(Some(vec![0x20])));
+        assert_eq!(probe_payload(&[0x10, 0x0c, 0x00]), Err(()));
+        assert_eq!(probe_payload(&[0x82, 0x82, 1, 2]), Ok(None));
+    }
+}
@@ -0,0 +1,155 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::nbns::NBNSTransaction;
+use super::parser::*;
+use crate::dns::log::dns_print_addr;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn opcode_name(opcode: u8) -> Option<&'static str> {
+    match opcode {
+        NBNS_OPCODE_QUERY => Some("query"),
+        NBNS_OPCODE_REGISTRATION => Some("registration"),
+        NBNS_OPCODE_RELEASE => Some("release"),
+        NBNS_OPCODE_WACK => Some("wack"),
+        NBNS_OPCODE_REFRESH | NBNS_OPCODE_REFRESH_ALT => Some("refresh"),
+        NBNS_OPCODE_MULTIHOMED => Some("multihomed_registration"),
+        _ => None,
+    }
+}
+
+fn rcode_name(rcode: u8) -> String {
+    match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FMT_ERR".to_string(),
+        2 => "SRV_ERR".to_string(),
+        3 => "NAM_ERR".to_string(),
+        4 => "IMP_ERR".to_string(),
+        5 => "RFS_ERR".to_string(),
+        NBNS_RCODE_ACT_ERR => "ACT_ERR".to_string(),
+        NBNS_RCODE_CFT_ERR => "CFT_ERR".to_string(),
+        _ => rcode.to_string(),
+    }
+}
+
+fn rrtype_name(rrtype: u16) -> String {
+    match rrtype {
+        0x0001 => "A".to_string(),
+        0x0002 => "NS".to_string(),
+        0x000a => "NULL".to_string(),
+        NBNS_RECORD_TYPE_NB => "NB".to_string(),
+        NBNS_RECORD_TYPE_NBSTAT => "NBSTAT".to_string(),
+        _ => rrtype.to_string(),
+    }
+}
+
+/// Log an encoded name decoded, or as is if it is not properly encoded.
+fn log_name(encoded: &[u8], js: &mut JsonBuilder) -> Result<(), JsonError> {
+    match decode_name(encoded) {
+        Some(name) => {
+            js.set_string_from_bytes("name", &name.name)?;
+            js.set_uint("suffix", name.suffix as u64)?;
+            if !name.scope.is_empty() {
+                js.set_string_from_bytes("scope", &name.scope)?;
+            }
+        }
+        None => {
+            js.set_string_from_bytes("name", encoded)?;
+        }
+    }
+    Ok(())
+}
+
+fn log_record(record: &NBNSRecord, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.start_object()?;
+    log_name(&record.name, js)?;
+    js.set_string("rrtype", &rrtype_name(record.rrtype))?;
+    js.set_uint("ttl", record.ttl as u64)?;
+    match record.data {
+        NBNSRData::NB(ref addrs) => {
+            js.open_array("addresses")?;
+            for addr in addrs {
+                js.append_string(&dns_print_addr(&addr.addr))?;
+            }
+            js.close()?;
+        }
+        NBNSRData::NBStat(ref names) => {
+            js.open_array("names")?;
+            for name in names {
+                js.start_object()?;
+                js.set_string_from_bytes("name", &name.name)?;
+                js.set_uint("suffix", name.suffix as u64)?;
+                js.set_bool("group", name.flags & 0x8000 != 0)?;
+                js.close()?;
+            }
+            js.close()?;
+        }
+        NBNSRData::Unknown(_) => {}
+    }
+    js.close()?;
+    Ok(())
+}
+
+fn log_message(msg: &NBNSMessage, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.set_uint("id", msg.header.tx_id as u64)?;
+    match opcode_name(msg.opcode()) {
+        Some(name) => js.set_string("opcode", name)?,
+        None => js.set_uint("opcode", msg.opcode() as u64)?,
+    };
+    if msg.header.flags & NBNS_FLAG_BROADCAST != 0 {
+        js.set_bool("broadcast", true)?;
+    }
+    if msg.header.flags & NBNS_FLAG_RESPONSE != 0 {
+        js.set_string("rcode", &rcode_name(msg.rcode()))?;
+    }
+    if !msg.queries.is_empty() {
+        js.open_array("queries")?;
+        for query in &msg.queries {
+            js.start_object()?;
+            log_name(&query.name, js)?;
+            js.set_string("rrtype", &rrtype_name(query.rrtype))?;
+            js.close()?;
+        }
+        js.close()?;
+    }
+    if !msg.records.is_empty() {
+        js.open_array("records")?;
+        for record in &msg.records {
+            log_record(record, js)?;
+        }
+        js.close()?;
+    }
+    Ok(())
+}
+
+fn log(tx: &NBNSTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("nbns")?;
+    if let Some(ref request) = tx.request {
+        js.set_string("type", "request")?;
+        log_message(request, js)?;
+    } else if let Some(ref response) = tx.response {
+        js.set_string("type", "response")?;
+        log_message(response, js)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_nbns_to_json(tx: &mut NBNSTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
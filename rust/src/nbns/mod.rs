@@ -1,4 +1,4 @@
-/* Copyright (C) 2020 Open Information Security Foundation
+/* Copyright (C) 2021 Open Information Security Foundation
  *
  * You can copy, redistribute or modify this Program under the terms of
  * the GNU General Public License version 2 as published by the Free
@@ -15,15 +15,13 @@
  * 02110-1301, USA.
  */
 
-/**
- * \file
- *
- * \author Sascha Steinbiss <sascha@steinbiss.name>
- */
-
-#ifndef __DETECT_MQTT_CONNACK_SESSIONPRESENT_H__
-#define __DETECT_MQTT_CONNACK_SESSIONPRESENT_H__
-
-void DetectMQTTConnackSessionPresentRegister(void);
+//! NetBIOS Name Service application layer, parser and logger module.
+//!
+//! NBNS messages use the DNS message format. The header, names and
+//! questions are decoded with the DNS parser. Each message is a
+//! transaction.
 
-#endif /* __DETECT_MQTT_CONNACK_SESSIONPRESENT_H__ */
+pub mod detect;
+pub mod logger;
+pub mod nbns;
+pub mod parser;
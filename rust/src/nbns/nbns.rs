@@ -0,0 +1,484 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, ALPROTO_UNKNOWN, IPPROTO_UDP};
+use crate::dns::dns::DNSHeader;
+use crate::dns::parser::dns_parse_header;
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(nbns_stats, "app_layer.nbns", {
+    queries,
+    registrations,
+    releases,
+    other,
+    malformed,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum NBNSEvent {
+    MalformedData,
+    NotRequest,
+    NotResponse,
+    InvalidName,
+    NameConflict,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static NBNS_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static NBNS_GAUGES: StateGauges = StateGauges::new();
+
+pub static mut ALPROTO_NBNS: AppProto = ALPROTO_UNKNOWN;
+
+/// A request or a response.
+#[derive(Debug)]
+pub struct NBNSTransaction {
+    id: u64,
+    pub request: Option<NBNSMessage>,
+    pub response: Option<NBNSMessage>,
+    /// The name the message is about, from the question or, for
+    /// responses without one, from the record.
+    pub name: Option<NBNSName>,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for NBNSTransaction {}
+
+impl NBNSTransaction {
+    fn new(id: u64) -> NBNSTransaction {
+        NBNSTransaction {
+            id,
+            request: None,
+            response: None,
+            name: None,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: NBNSEvent) {
+        stats_incr!(nbns_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    /// Decode the names of the message, keeping the first one.
+    fn set_names(&mut self, msg: &NBNSMessage) {
+        let encoded = msg.queries.iter().map(|query| &query.name)
+            .chain(msg.records.iter().map(|record| &record.name));
+        let mut invalid = false;
+        for name in encoded {
+            match decode_name(name) {
+                Some(name) => {
+                    if self.name.is_none() {
+                        self.name = Some(name);
+                    }
+                }
+                None => invalid = true,
+            }
+        }
+        if invalid {
+            self.set_event(NBNSEvent::InvalidName);
+        }
+    }
+
+    fn message(&self) -> Option<&NBNSMessage> {
+        self.request.as_ref().or_else(|| self.response.as_ref())
+    }
+
+    pub fn opcode(&self) -> Option<u8> {
+        self.message().map(|msg| msg.opcode())
+    }
+}
+
+impl Drop for NBNSTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for NBNSTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = NBNSEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+pub struct NBNSState {
+    transactions: TxTable<NBNSTransaction>,
+}
+
+unsafe impl FfiType for NBNSState {}
+
+impl txtable::State for NBNSState {
+    type Tx = NBNSTransaction;
+
+    fn tx_table(&self) -> &TxTable<NBNSTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<NBNSTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl NBNSState {
+    pub fn new() -> NBNSState {
+        NBNSState {
+            transactions: TxTable::with_limit(&NBNS_TX_LIMIT).with_gauges(&NBNS_GAUGES),
+        }
+    }
+
+    fn new_tx(&mut self) -> NBNSTransaction {
+        NBNSTransaction::new(self.transactions.next_id())
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    fn count_msg(opcode: u8) {
+        match opcode {
+            NBNS_OPCODE_QUERY => stats_incr!(nbns_stats::queries),
+            NBNS_OPCODE_REGISTRATION | NBNS_OPCODE_MULTIHOMED => {
+                stats_incr!(nbns_stats::registrations)
+            }
+            NBNS_OPCODE_RELEASE => stats_incr!(nbns_stats::releases),
+            _ => stats_incr!(nbns_stats::other),
+        }
+    }
+
+    /// Parse a datagram. Returns false if it is not a valid message, in
+    /// which case a transaction is still created to hold the event.
+    fn parse(&mut self, input: &[u8], direction: Direction) -> bool {
+        let mut tx = self.new_tx();
+        let valid = match parse_message(input) {
+            Ok((_, msg)) => {
+                NBNSState::count_msg(msg.opcode());
+                tx.set_names(&msg);
+                let is_response = msg.header.flags & NBNS_FLAG_RESPONSE != 0;
+                if direction == Direction::ToServer {
+                    if is_response {
+                        tx.set_event(NBNSEvent::NotRequest);
+                    }
+                    tx.request = Some(msg);
+                } else {
+                    if !is_response {
+                        tx.set_event(NBNSEvent::NotResponse);
+                    }
+                    let rcode = msg.rcode();
+                    if rcode == NBNS_RCODE_ACT_ERR || rcode == NBNS_RCODE_CFT_ERR {
+                        tx.set_event(NBNSEvent::NameConflict);
+                    }
+                    tx.response = Some(msg);
+                }
+                true
+            }
+            Err(_) => {
+                SCLogDebug!("Error while parsing NBNS message");
+                stats_incr!(nbns_stats::malformed);
+                tx.set_event(NBNSEvent::MalformedData);
+                false
+            }
+        };
+        self.transactions.push(tx);
+        valid
+    }
+}
+
+/// Probe for a header with a known opcode, and the single question or
+/// record NBNS messages have.
+fn nbns_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    match dns_parse_header(input) {
+        Ok((_, header)) => {
+            let opcode = ((header.flags >> 11) & 0xf) as u8;
+            let known = match opcode {
+                NBNS_OPCODE_QUERY | NBNS_OPCODE_REGISTRATION | NBNS_OPCODE_RELEASE
+                | NBNS_OPCODE_WACK | NBNS_OPCODE_REFRESH | NBNS_OPCODE_REFRESH_ALT
+                | NBNS_OPCODE_MULTIHOMED => true,
+                _ => false,
+            };
+            let records = header.answer_rr as u32 + header.authority_rr as u32
+                + header.additional_rr as u32;
+            if !known || header.questions > 1 || records > 1
+                || header.questions as u32 + records == 0
+            {
+                return ProbeResult::Failed;
+            }
+            let actual = if header.flags & NBNS_FLAG_RESPONSE == 0 {
+                Direction::ToServer
+            } else {
+                Direction::ToClient
+            };
+            ProbeResult::with_direction(unsafe { ALPROTO_NBNS }, direction, actual)
+        }
+        Err(nom::Err::Incomplete(_)) => ProbeResult::Unknown,
+        Err(_) => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_nbns_probing_parser, nbns_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_nbns_state_new(_orig_state: *mut std::os::raw::c_void,
+                                     _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = NBNSState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_nbns_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut NBNSState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_nbns_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut NBNSState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_nbns_parse_request(_flow: *const core::Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                _pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    let state: &mut NBNSState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    if state.parse(buf, Direction::ToServer) {
+        AppLayerResult::ok()
+    } else {
+        AppLayerResult::err()
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_nbns_parse_response(_flow: *const core::Flow,
+                                                 state: *mut std::os::raw::c_void,
+                                                 _pstate: *mut std::os::raw::c_void,
+                                                 input: *const u8,
+                                                 input_len: u32,
+                                                 _data: *const std::os::raw::c_void,
+                                                 _flags: u8) -> AppLayerResult {
+    let state: &mut NBNSState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    if state.parse(buf, Direction::ToClient) {
+        AppLayerResult::ok()
+    } else {
+        AppLayerResult::err()
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_nbns_state_get_tx(state: *mut std::os::raw::c_void,
+                                               tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut NBNSState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_nbns_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut NBNSState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+#[no_mangle]
+pub extern "C" fn rs_nbns_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void,
+                                                   _direction: u8) -> std::os::raw::c_int {
+    1
+}
+
+export_tx_detect_state!(rs_nbns_state_get_tx_detect_state, rs_nbns_state_set_tx_detect_state,
+                        NBNSTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_nbns_state_get_events(tx: *mut std::os::raw::c_void)
+                                                   -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut NBNSTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_nbns_get_tx_data, NBNSTransaction);
+
+fn nbns_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    NBNS_GAUGES.log(js)?;
+    js.set_uint("events", nbns_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+const PARSER_NAME: &[u8] = b"nbns\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_nbns_register_parser() {
+    nbns_stats::register();
+    NBNS_TX_LIMIT.configure("nbns", txtable::DEFAULT_MAX_TX);
+    introspect::register("nbns", nbns_introspect);
+    let default_port = CString::new("137").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_UDP,
+        probe_ts: Some(rs_nbns_probing_parser),
+        probe_tc: Some(rs_nbns_probing_parser),
+        min_depth: 0,
+        max_depth: std::mem::size_of::<DNSHeader>() as u16,
+        state_new: rs_nbns_state_new,
+        state_free: rs_nbns_state_free,
+        tx_free: rs_nbns_state_tx_free,
+        parse_ts: rs_nbns_parse_request,
+        parse_tc: rs_nbns_parse_response,
+        get_tx_count: rs_nbns_state_get_tx_count,
+        get_tx: rs_nbns_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_nbns_tx_get_alstate_progress,
+        get_de_state: rs_nbns_state_get_tx_detect_state,
+        set_de_state: rs_nbns_state_set_tx_detect_state,
+        get_events: Some(rs_nbns_state_get_events),
+        get_eventinfo: Some(NBNSEvent::get_event_info),
+        get_eventinfo_byid: Some(NBNSEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<NBNSState>),
+        get_tx_data: rs_nbns_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<NBNSState>),
+        flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("udp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_NBNS = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust NBNS parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for NBNS.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A broadcast name query for "WPAD<00>".
+    const QUERY: &[u8] = &[
+        0x80, 0x01, 0x01, 0x10, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x20, 0x46, 0x48, 0x46,
+        0x41, 0x45, 0x42, 0x45, 0x45, 0x43, 0x41, 0x43,
+        0x41, 0x43, 0x41, 0x43, 0x41, 0x43, 0x41, 0x43,
+        0x41, 0x43, 0x41, 0x43, 0x41, 0x43, 0x41, 0x43,
+        0x41, 0x43, 0x41, 0x41, 0x41, 0x00, 0x00, 0x20,
+        0x00, 0x01,
+    ];
+
+    /// The positive response to `QUERY`, with the address 10.0.0.5.
+    const RESPONSE: &[u8] = &[
+        0x80, 0x01, 0x85, 0x00, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x20, 0x46, 0x48, 0x46,
+        0x41, 0x45, 0x42, 0x45, 0x45, 0x43, 0x41, 0x43,
+        0x41, 0x43, 0x41, 0x43, 0x41, 0x43, 0x41, 0x43,
+        0x41, 0x43, 0x41, 0x43, 0x41, 0x43, 0x41, 0x43,
+        0x41, 0x43, 0x41, 0x41, 0x41, 0x00, 0x00, 0x20,
+        0x00, 0x01, 0x00, 0x04, 0x93, 0xe0, 0x00, 0x06,
+        0x00, 0x00, 0x0a, 0x00, 0x00, 0x05,
+    ];
+
+    #[test]
+    fn test_nbns_parse_query() {
+        let mut state = NBNSState::new();
+        assert!(state.parse(QUERY, Direction::ToServer));
+        let tx = state.transactions.get(0).unwrap();
+        assert_eq!(tx.name.as_ref().unwrap().name, b"WPAD".to_vec());
+        assert_eq!(tx.opcode(), Some(NBNS_OPCODE_QUERY));
+        assert!(tx.response.is_none());
+    }
+
+    #[test]
+    fn test_nbns_parse_response() {
+        let mut state = NBNSState::new();
+        assert!(state.parse(RESPONSE, Direction::ToClient));
+        let tx = state.transactions.get(0).unwrap();
+        // no question, the name is taken from the answer
+        assert_eq!(tx.name.as_ref().unwrap().name, b"WPAD".to_vec());
+        let response = tx.response.as_ref().unwrap();
+        assert_eq!(response.records.len(), 1);
+        assert_eq!(response.records[0].data, NBNSRData::NB(vec![NBNSAddress {
+            flags: 0,
+            addr: vec![10, 0, 0, 5],
+        }]));
+    }
+
+    #[test]
+    fn test_nbns_parse_invalid_name() {
+        let mut query = QUERY.to_vec();
+        query[13] = b'Z';
+        let mut state = NBNSState::new();
+        assert!(state.parse(&query, Direction::ToServer));
+        assert!(state.transactions.get(0).unwrap().name.is_none());
+    }
+
+    #[test]
+    fn test_nbns_parse_malformed() {
+        let mut state = NBNSState::new();
+        assert!(!state.parse(&QUERY[..20], Direction::ToServer));
+        assert_eq!(state.transactions.tx_count(), 1);
+    }
+
+    #[test]
+    fn test_nbns_probe() {
+        assert_eq!(nbns_probe(QUERY, Direction::ToServer),
+                   ProbeResult::Found(unsafe { ALPROTO_NBNS }));
+        assert_eq!(nbns_probe(RESPONSE, Direction::ToServer),
+                   ProbeResult::Flipped(unsafe { ALPROTO_NBNS }));
+        assert_eq!(nbns_probe(&QUERY[..8], Direction::ToServer), ProbeResult::Unknown);
+
+        // a DNS style query, with an unassigned NBNS opcode
+        let mut query = QUERY.to_vec();
+        query[2] = 0x11;
+        assert_eq!(nbns_probe(&query, Direction::ToServer), ProbeResult::Failed);
+    }
+}
@@ -0,0 +1,290 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! NBNS message decoding. The header, the names and the questions have
+//! the DNS format and are decoded with the DNS parser, the resource
+//! records are decoded here as their types differ from DNS.
+
+use crate::dns::dns::{DNSHeader, DNSQueryEntry};
+use crate::dns::parser::{dns_parse_header, dns_parse_name, dns_parse_query};
+use nom::bytes::streaming::take;
+use nom::combinator::complete;
+use nom::multi::{count, length_data, many0};
+use nom::number::streaming::{be_u16, be_u32, be_u8};
+use nom::IResult;
+
+pub const NBNS_RECORD_TYPE_NB: u16 = 0x20;
+pub const NBNS_RECORD_TYPE_NBSTAT: u16 = 0x21;
+
+pub const NBNS_OPCODE_QUERY: u8 = 0;
+pub const NBNS_OPCODE_REGISTRATION: u8 = 5;
+pub const NBNS_OPCODE_RELEASE: u8 = 6;
+pub const NBNS_OPCODE_WACK: u8 = 7;
+pub const NBNS_OPCODE_REFRESH: u8 = 8;
+pub const NBNS_OPCODE_REFRESH_ALT: u8 = 9;
+pub const NBNS_OPCODE_MULTIHOMED: u8 = 15;
+
+pub const NBNS_RCODE_ACT_ERR: u8 = 6;
+pub const NBNS_RCODE_CFT_ERR: u8 = 7;
+
+pub const NBNS_FLAG_RESPONSE: u16 = 0x8000;
+pub const NBNS_FLAG_BROADCAST: u16 = 0x0010;
+
+/// A NetBIOS name, decoded from its first level encoding.
+#[derive(Debug, PartialEq)]
+pub struct NBNSName {
+    /// The name, without the padding spaces.
+    pub name: Vec<u8>,
+    /// The last byte of the name, giving the type of service.
+    pub suffix: u8,
+    /// The NetBIOS scope, if any.
+    pub scope: Vec<u8>,
+}
+
+/// An entry of the rdata of a NB record.
+#[derive(Debug, PartialEq)]
+pub struct NBNSAddress {
+    pub flags: u16,
+    pub addr: Vec<u8>,
+}
+
+/// A name of the rdata of a NBSTAT record.
+#[derive(Debug, PartialEq)]
+pub struct NBNSNodeName {
+    pub name: Vec<u8>,
+    pub suffix: u8,
+    pub flags: u16,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum NBNSRData {
+    NB(Vec<NBNSAddress>),
+    NBStat(Vec<NBNSNodeName>),
+    Unknown(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NBNSRecord {
+    /// The name, still encoded.
+    pub name: Vec<u8>,
+    pub rrtype: u16,
+    pub rrclass: u16,
+    pub ttl: u32,
+    pub data: NBNSRData,
+}
+
+/// A message. The records of all the sections are kept together: a
+/// message has at most one, in the answer section of responses and the
+/// additional section of requests.
+#[derive(Debug)]
+pub struct NBNSMessage {
+    pub header: DNSHeader,
+    pub queries: Vec<DNSQueryEntry>,
+    pub records: Vec<NBNSRecord>,
+}
+
+impl NBNSMessage {
+    pub fn opcode(&self) -> u8 {
+        ((self.header.flags >> 11) & 0xf) as u8
+    }
+
+    pub fn rcode(&self) -> u8 {
+        (self.header.flags & 0xf) as u8
+    }
+}
+
+/// Decode a name from its first level encoding, each half byte of the
+/// 16 byte name being sent as a letter from 'A' to 'P'. Returns None if
+/// the name is not properly encoded.
+pub fn decode_name(encoded: &[u8]) -> Option<NBNSName> {
+    let (label, scope) = match encoded.iter().position(|&c| c == b'.') {
+        Some(dot) => (&encoded[..dot], &encoded[dot + 1..]),
+        None => (encoded, &encoded[encoded.len()..]),
+    };
+    if label.len() != 32 {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(16);
+    for pair in label.chunks(2) {
+        if pair.iter().any(|&c| c < b'A' || c > b'P') {
+            return None;
+        }
+        decoded.push(((pair[0] - b'A') << 4) | (pair[1] - b'A'));
+    }
+    let suffix = decoded.pop().unwrap_or(0);
+    while decoded.last().map_or(false, |&c| c == b' ' || c == 0) {
+        decoded.pop();
+    }
+    Some(NBNSName {
+        name: decoded,
+        suffix,
+        scope: scope.to_vec(),
+    })
+}
+
+fn parse_nb_address(i: &[u8]) -> IResult<&[u8], NBNSAddress> {
+    let (i, flags) = be_u16(i)?;
+    let (i, addr) = take(4usize)(i)?;
+    Ok((i, NBNSAddress { flags, addr: addr.to_vec() }))
+}
+
+fn parse_node_name(i: &[u8]) -> IResult<&[u8], NBNSNodeName> {
+    let (i, name) = take(15usize)(i)?;
+    let (i, suffix) = be_u8(i)?;
+    let (i, flags) = be_u16(i)?;
+    let mut name = name.to_vec();
+    while name.last().map_or(false, |&c| c == b' ' || c == 0) {
+        name.pop();
+    }
+    Ok((i, NBNSNodeName { name, suffix, flags }))
+}
+
+fn parse_nbstat(i: &[u8]) -> IResult<&[u8], Vec<NBNSNodeName>> {
+    let (i, num_names) = be_u8(i)?;
+    // the statistics following the names are ignored
+    count(parse_node_name, num_names as usize)(i)
+}
+
+fn parse_rdata(data: &[u8], rrtype: u16) -> NBNSRData {
+    let result = match rrtype {
+        NBNS_RECORD_TYPE_NB => {
+            many0(complete(parse_nb_address))(data).map(|(_, addrs)| NBNSRData::NB(addrs))
+        }
+        NBNS_RECORD_TYPE_NBSTAT => {
+            parse_nbstat(data).map(|(_, names)| NBNSRData::NBStat(names))
+        }
+        _ => Err(nom::Err::Error((data, nom::error::ErrorKind::Switch))),
+    };
+    result.unwrap_or_else(|_| NBNSRData::Unknown(data.to_vec()))
+}
+
+fn parse_record<'a>(i: &'a [u8], message: &'a [u8]) -> IResult<&'a [u8], NBNSRecord> {
+    let (i, name) = dns_parse_name(i, message)?;
+    let (i, rrtype) = be_u16(i)?;
+    let (i, rrclass) = be_u16(i)?;
+    let (i, ttl) = be_u32(i)?;
+    let (i, data) = length_data(be_u16)(i)?;
+    Ok((i, NBNSRecord {
+        name,
+        rrtype,
+        rrclass,
+        ttl,
+        data: parse_rdata(data, rrtype),
+    }))
+}
+
+/// Parse a message, requests and responses having the same format.
+pub fn parse_message(message: &[u8]) -> IResult<&[u8], NBNSMessage> {
+    let (i, header) = dns_parse_header(message)?;
+    let (i, queries) = count(|i| dns_parse_query(i, message), header.questions as usize)(i)?;
+    let num_records = header.answer_rr as usize + header.authority_rr as usize
+        + header.additional_rr as usize;
+    let (i, records) = count(|i| parse_record(i, message), num_records)(i)?;
+    Ok((i, NBNSMessage { header, queries, records }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A broadcast name query for "WPAD<00>".
+    const QUERY: &[u8] = &[
+        0x80, 0x01, 0x01, 0x10, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x20, 0x46, 0x48, 0x46,
+        0x41, 0x45, 0x42, 0x45, 0x45, 0x43, 0x41, 0x43,
+        0x41, 0x43, 0x41, 0x43, 0x41, 0x43, 0x41, 0x43,
+        0x41, 0x43, 0x41, 0x43, 0x41, 0x43, 0x41, 0x43,
+        0x41, 0x43, 0x41, 0x41, 0x41, 0x00, 0x00, 0x20,
+        0x00, 0x01,
+    ];
+
+    #[test]
+    fn test_nbns_decode_name() {
+        let name = decode_name(b"FHFAEBEECACACACACACACACACACACAAA").unwrap();
+        assert_eq!(name.name, b"WPAD".to_vec());
+        assert_eq!(name.suffix, 0);
+        assert!(name.scope.is_empty());
+
+        let name = decode_name(b"EHFCEPFFFACACACACACACACACACACABN.example").unwrap();
+        assert_eq!(name.name, b"GROUP".to_vec());
+        assert_eq!(name.suffix, 0x1d);
+        assert_eq!(name.scope, b"example".to_vec());
+
+        // wildcard of the node status requests
+        let name = decode_name(b"CKAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA").unwrap();
+        assert_eq!(name.name, b"*".to_vec());
+
+        assert_eq!(decode_name(b"WPAD"), None);
+        assert_eq!(decode_name(b"FHFAEBEECACACACACACACACACACACAAZ"), None);
+    }
+
+    #[test]
+    fn test_nbns_parse_query() {
+        let (rem, msg) = parse_message(QUERY).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(msg.opcode(), NBNS_OPCODE_QUERY);
+        assert!(msg.header.flags & NBNS_FLAG_BROADCAST != 0);
+        assert_eq!(msg.queries.len(), 1);
+        assert_eq!(msg.queries[0].rrtype, NBNS_RECORD_TYPE_NB);
+        assert!(msg.records.is_empty());
+    }
+
+    #[test]
+    fn test_nbns_parse_registration() {
+        let mut buf = QUERY.to_vec();
+        // registration, with the record in the additional section
+        buf[2] = 0x29;
+        buf[11] = 0x01;
+        buf.extend_from_slice(&[
+            0xc0, 0x0c, 0x00, 0x20, 0x00, 0x01, 0x00, 0x04,
+            0x93, 0xe0, 0x00, 0x06, 0x00, 0x00, 0x0a, 0x00,
+            0x00, 0x05,
+        ]);
+        let (_, msg) = parse_message(&buf).unwrap();
+        assert_eq!(msg.opcode(), NBNS_OPCODE_REGISTRATION);
+        assert_eq!(msg.records.len(), 1);
+        let record = &msg.records[0];
+        assert_eq!(record.ttl, 300000);
+        assert_eq!(record.data, NBNSRData::NB(vec![NBNSAddress {
+            flags: 0,
+            addr: vec![10, 0, 0, 5],
+        }]));
+        assert_eq!(decode_name(&record.name).unwrap().name, b"WPAD".to_vec());
+    }
+
+    #[test]
+    fn test_nbns_parse_nbstat() {
+        let mut rdata = vec![2];
+        rdata.extend_from_slice(b"HOST           \x00\x04\x00");
+        rdata.extend_from_slice(b"WORKGROUP      \x00\x84\x00");
+        rdata.extend_from_slice(&[0u8; 46]);
+        match parse_rdata(&rdata, NBNS_RECORD_TYPE_NBSTAT) {
+            NBNSRData::NBStat(names) => {
+                assert_eq!(names.len(), 2);
+                assert_eq!(names[0].name, b"HOST".to_vec());
+                assert_eq!(names[1].name, b"WORKGROUP".to_vec());
+                assert_eq!(names[1].flags, 0x8400);
+            }
+            _ => panic!("NBSTAT rdata not decoded"),
+        }
+    }
+
+    #[test]
+    fn test_nbns_parse_truncated() {
+        assert!(parse_message(&QUERY[..40]).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Sticky buffers on the file names and NFSv4.1 sessions of transactions.
+
+use super::nfs::{NFSTransaction, NFSTransactionSession, NFSTransactionTypeData, ALPROTO_NFS};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_NFS_FILENAME_BUFFER_ID: c_int = 0;
+static mut G_NFS_NEW_FILENAME_BUFFER_ID: c_int = 0;
+static mut G_NFS_CLIENT_OWNER_BUFFER_ID: c_int = 0;
+static mut G_NFS_SESSION_ID_BUFFER_ID: c_int = 0;
+
+fn nfs_tx_session(tx: &NFSTransaction) -> Option<&NFSTransactionSession> {
+    match tx.type_data {
+        Some(NFSTransactionTypeData::SESSION(ref tds)) => Some(tds),
+        _ => None,
+    }
+}
+
+unsafe extern "C" fn nfs_tx_get_filename(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut NFSTransaction = cast_mut(tx);
+    if tx.file_name.is_empty() {
+        return false;
+    }
+    *buf = tx.file_name.as_ptr();
+    *len = tx.file_name.len() as u32;
+    true
+}
+
+unsafe extern "C" fn nfs_tx_get_new_filename(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut NFSTransaction = cast_mut(tx);
+    match tx.type_data {
+        Some(NFSTransactionTypeData::RENAME(ref name))
+        | Some(NFSTransactionTypeData::LINK(ref name)) if !name.is_empty() => {
+            *buf = name.as_ptr();
+            *len = name.len() as u32;
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn nfs_tx_get_client_owner(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut NFSTransaction = cast_mut(tx);
+    match nfs_tx_session(tx) {
+        Some(tds) if !tds.client_owner.is_empty() => {
+            *buf = tds.client_owner.as_ptr();
+            *len = tds.client_owner.len() as u32;
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn nfs_tx_get_session_id(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut NFSTransaction = cast_mut(tx);
+    match nfs_tx_session(tx) {
+        Some(tds) if !tds.session_id.is_empty() => {
+            *buf = tds.session_id.as_ptr();
+            *len = tds.session_id.len() as u32;
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn nfs_filename_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, nfs_tx_get_filename)
+}
+
+unsafe extern "C" fn nfs_new_filename_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, nfs_tx_get_new_filename)
+}
+
+unsafe extern "C" fn nfs_client_owner_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, nfs_tx_get_client_owner)
+}
+
+unsafe extern "C" fn nfs_session_id_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, nfs_tx_get_session_id)
+}
+
+unsafe extern "C" fn nfs_filename_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_NFS, G_NFS_FILENAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn nfs_new_filename_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_NFS, G_NFS_NEW_FILENAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn nfs_client_owner_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_NFS, G_NFS_CLIENT_OWNER_BUFFER_ID)
+}
+
+unsafe extern "C" fn nfs_session_id_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_NFS, G_NFS_SESSION_ID_BUFFER_ID)
+}
+
+/// Register the NFS keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "nfs.filename",
+        desc: "sticky buffer to match on the file name of NFS transactions, the old name of renames",
+        url: "/rules/nfs-keywords.html#nfs-filename",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: nfs_filename_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_NFS_FILENAME_BUFFER_ID = helper_buffer_mpm_register(
+            "nfs.filename", "nfs file name", ALPROTO_NFS, false, true, 1,
+            nfs_filename_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "nfs.new_filename",
+        desc: "sticky buffer to match on the new name of NFS RENAME and LINK transactions",
+        url: "/rules/nfs-keywords.html#nfs-new-filename",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: nfs_new_filename_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_NFS_NEW_FILENAME_BUFFER_ID = helper_buffer_mpm_register(
+            "nfs.new_filename", "nfs new file name", ALPROTO_NFS, false, true, 1,
+            nfs_new_filename_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "nfs.client_owner",
+        desc: "sticky buffer to match on the client owner of NFSv4.1 EXCHANGE_ID requests",
+        url: "/rules/nfs-keywords.html#nfs-client-owner",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: nfs_client_owner_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_NFS_CLIENT_OWNER_BUFFER_ID = helper_buffer_mpm_register(
+            "nfs.client_owner", "nfs client owner", ALPROTO_NFS, false, true, 1,
+            nfs_client_owner_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "nfs.session_id",
+        desc: "sticky buffer to match on the session id of NFSv4.1 session transactions",
+        url: "/rules/nfs-keywords.html#nfs-session-id",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: nfs_session_id_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_NFS_SESSION_ID_BUFFER_ID = helper_buffer_mpm_register(
+            "nfs.session_id", "nfs session id", ALPROTO_NFS, true, true, 1,
+            nfs_session_id_get_data);
+    }
+}
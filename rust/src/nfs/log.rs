@@ -51,6 +51,26 @@ fn nfs_rename_object(tx: &NFSTransaction, js: &mut JsonBuilder)
     Ok(())
 }
 
+fn nfs_link_object(tx: &NFSTransaction, js: &mut JsonBuilder)
+    -> Result<(), JsonError>
+{
+    let from_str = String::from_utf8_lossy(&tx.file_name);
+    js.set_string("from", &from_str)?;
+
+    if let Some(NFSTransactionTypeData::LINK(ref x)) = tx.type_data {
+        js.set_string("to", &String::from_utf8_lossy(x))?;
+    }
+    Ok(())
+}
+
+fn nfs_is_remove(tx: &NFSTransaction) -> bool {
+    match tx.nfs_version {
+        2 => tx.procedure == NFSPROC2_REMOVE,
+        3 => tx.procedure == NFSPROC3_REMOVE,
+        _ => tx.procedure == NFSPROC4_REMOVE,
+    }
+}
+
 fn nfs_creds_object(tx: &NFSTransaction, js: &mut JsonBuilder)
     -> Result<(), JsonError>
 {
@@ -73,6 +93,53 @@ fn nfs_file_object(tx: &NFSTransaction, js: &mut JsonBuilder)
     }
     Ok(())
 }
+
+fn nfs_session_object(tds: &NFSTransactionSession, js: &mut JsonBuilder)
+    -> Result<(), JsonError>
+{
+    if !tds.client_owner.is_empty() {
+        js.set_string("client_owner", &String::from_utf8_lossy(&tds.client_owner))?;
+    }
+    if tds.client_id != 0 {
+        js.set_string("client_id", &format!("{:016x}", tds.client_id))?;
+    }
+    if !tds.session_id.is_empty() {
+        let strings: Vec<String> = tds.session_id.iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        js.set_string("session_id", &strings.join(""))?;
+    }
+    Ok(())
+}
+
+fn nfs_range_object(tx: &NFSTransaction, tdr: &NFSTransactionRange, js: &mut JsonBuilder)
+    -> Result<(), JsonError>
+{
+    match tx.procedure {
+        NFSPROC4_COPY => {
+            js.set_uint("src_offset", tdr.src_offset)?;
+            js.set_uint("dst_offset", tdr.offset)?;
+            js.set_uint("count", tdr.length)?;
+            if tx.nfs_response_status == NFS4_OK {
+                js.set_uint("copied", tdr.result)?;
+                js.set_bool("async", tdr.result_flag)?;
+            }
+        },
+        NFSPROC4_SEEK => {
+            js.set_uint("offset", tdr.offset)?;
+            js.set_string("what", if tdr.what == 0 { "data" } else { "hole" })?;
+            if tx.nfs_response_status == NFS4_OK {
+                js.set_uint("result", tdr.result)?;
+                js.set_bool("eof", tdr.result_flag)?;
+            }
+        },
+        _ => {
+            js.set_uint("offset", tdr.offset)?;
+            js.set_uint("length", tdr.length)?;
+        },
+    }
+    Ok(())
+}
 /*
 fn nfs_handle2hex(bytes: &Vec<u8>) -> String {
     let strings: Vec<String> = bytes.iter()
@@ -90,7 +157,9 @@ fn nfs_common_header(state: &NFSState, tx: &NFSTransaction, js: &mut JsonBuilder
     -> Result<(), JsonError>
 {
     js.set_uint("version", state.nfs_version as u64)?;
-    let proc_string = if state.nfs_version < 4 {
+    let proc_string = if state.nfs_version == 2 {
+        nfs2_procedure_string(tx.procedure)
+    } else if state.nfs_version < 4 {
         nfs3_procedure_string(tx.procedure)
     } else {
         nfs4_procedure_string(tx.procedure)
@@ -142,11 +211,42 @@ fn nfs_log_response(state: &NFSState, tx: &NFSTransaction, js: &mut JsonBuilder)
             js.open_object("write")?;
             nfs_file_object(tx, js)?;
             js.close()?;
-        } else if tx.procedure == NFSPROC3_RENAME {
+        }
+    }
+    match tx.type_data {
+        Some(NFSTransactionTypeData::RENAME(_)) => {
             js.open_object("rename")?;
             nfs_rename_object(tx, js)?;
             js.close()?;
-        }
+        },
+        Some(NFSTransactionTypeData::LINK(_)) => {
+            js.open_object("link")?;
+            nfs_link_object(tx, js)?;
+            js.close()?;
+        },
+        Some(NFSTransactionTypeData::SESSION(ref tds)) => {
+            js.open_object("session")?;
+            nfs_session_object(tds, js)?;
+            js.close()?;
+        },
+        Some(NFSTransactionTypeData::RANGE(ref tdr)) => {
+            let name = match tx.procedure {
+                NFSPROC4_COPY => "copy",
+                NFSPROC4_SEEK => "seek",
+                NFSPROC4_DEALLOCATE => "deallocate",
+                _ => "allocate",
+            };
+            js.open_object(name)?;
+            nfs_range_object(tx, tdr, js)?;
+            js.close()?;
+        },
+        _ => {
+            if nfs_is_remove(tx) {
+                js.open_object("remove")?;
+                js.set_string("name", &String::from_utf8_lossy(&tx.file_name))?;
+                js.close()?;
+            }
+        },
     }
     Ok(())
 }
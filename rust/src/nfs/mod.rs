@@ -26,6 +26,7 @@ pub mod nfs2;
 pub mod nfs3;
 pub mod nfs4;
 pub mod log;
+pub mod detect;
 
 //#[cfg(feature = "lua")]
 //pub mod lua;
@@ -41,7 +41,7 @@ pub static mut SURICATA_NFS_FILE_CONFIG: Option<&'static SuricataFileContext> =
 
 pub const NFS_MIN_FRAME_LEN: u16 = 32;
 
-static mut ALPROTO_NFS: AppProto = ALPROTO_UNKNOWN;
+pub static mut ALPROTO_NFS: AppProto = ALPROTO_UNKNOWN;
 /*
  * Record parsing.
  *
@@ -107,7 +107,37 @@ impl NFSEvent {
 #[derive(Debug)]
 pub enum NFSTransactionTypeData {
     RENAME(Vec<u8>),
+    /// name of the new link, the existing file is the tx file name
+    LINK(Vec<u8>),
     FILE(NFSTransactionFile),
+    SESSION(NFSTransactionSession),
+    RANGE(NFSTransactionRange),
+}
+
+/// NFSv4.1 session management: EXCHANGE_ID, CREATE_SESSION,
+/// DESTROY_SESSION and SEQUENCE
+#[derive(Default, Debug)]
+pub struct NFSTransactionSession {
+    /// co_ownerid of EXCHANGE_ID
+    pub client_owner: Vec<u8>,
+    pub client_id: u64,
+    pub session_id: Vec<u8>,
+}
+
+/// NFSv4.2 operations on a byte range of a file: COPY, SEEK, ALLOCATE
+/// and DEALLOCATE
+#[derive(Default, Debug)]
+pub struct NFSTransactionRange {
+    pub offset: u64,
+    pub length: u64,
+    /// COPY: offset in the source file
+    pub src_offset: u64,
+    /// SEEK: data_content4 looked for
+    pub what: u32,
+    /// from the response: COPY bytes copied, SEEK offset found
+    pub result: u64,
+    /// from the response: COPY is asynchronous, SEEK hit the end of file
+    pub result_flag: bool,
 }
 
 #[derive(Default, Debug)]
@@ -145,7 +175,8 @@ pub struct NFSTransaction {
     pub xid: u32,   /// nfs req/reply pair id
     pub procedure: u32,
     /// file name of the object we're dealing with. In case of RENAME
-    /// this is the 'from' or original name.
+    /// this is the 'from' or original name, in case of LINK the name
+    /// of the existing file.
     pub file_name: Vec<u8>,
 
     pub auth_type: u32,
@@ -179,12 +210,14 @@ pub struct NFSTransaction {
     /// attempt failed.
     pub type_data: Option<NFSTransactionTypeData>,
 
-    pub de_state: Option<*mut DetectEngineState>,
+    pub de_state: DetectStateHolder,
     pub events: *mut AppLayerDecoderEvents,
 
     pub tx_data: AppLayerTxData,
 }
 
+unsafe impl FfiType for NFSTransaction {}
+
 impl NFSTransaction {
     pub fn new() -> Self {
         return Self {
@@ -208,7 +241,7 @@ impl NFSTransaction {
             file_tx_direction: 0,
             file_handle:Vec::new(),
             type_data: None,
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: AppLayerTxData::new(),
         }
@@ -220,12 +253,6 @@ impl NFSTransaction {
         if self.events != std::ptr::null_mut() {
             sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        match self.de_state {
-            Some(state) => {
-                sc_detect_engine_state_free(state);
-            }
-            _ => {}
-        }
     }
 }
 
@@ -323,6 +350,8 @@ pub struct NFSState {
     ts: u64,
 }
 
+unsafe impl FfiType for NFSState {}
+
 impl NFSState {
     /// Allocation function for a new TLS parser instance
     pub fn new() -> NFSState {
@@ -1041,7 +1070,7 @@ impl NFSState {
                     0 => {
                         SCLogDebug!("incomplete, queue and retry with the next block (input {}). Looped {} times.",
                                 cur_i.len(), _cnt);
-                        return AppLayerResult::incomplete((i.len() - cur_i.len()) as u32, (cur_i.len() + 1) as u32);
+                        return AppLayerResult::incomplete_from(i.len(), i.len() - cur_i.len(), cur_i.len() + 1);
                     },
                     -1 => {
                         cur_i = &cur_i[1..];
@@ -1110,7 +1139,7 @@ impl NFSState {
                         // but lower than the record size
                         let n1 = cmp::max(cur_i.len(), 1024);
                         let n2 = cmp::min(n1, rec_size);
-                        return AppLayerResult::incomplete((i.len() - cur_i.len()) as u32, n2 as u32);
+                        return AppLayerResult::incomplete_from(i.len(), i.len() - cur_i.len(), n2);
                     }
 
                     // we have the full records size worth of data,
@@ -1142,7 +1171,7 @@ impl NFSState {
                         // 28 is the partial RPC header size parse_rpc_request_partial
                         // looks for.
                         let need = if n > 28 { n } else { 28 };
-                        return AppLayerResult::incomplete((i.len() - cur_i.len()) as u32, need as u32);
+                        return AppLayerResult::incomplete_from(i.len(), i.len() - cur_i.len(), need);
                     }
                     return AppLayerResult::err();
                 },
@@ -1194,7 +1223,7 @@ impl NFSState {
                     0 => {
                         SCLogDebug!("incomplete, queue and retry with the next block (input {}). Looped {} times.",
                                 cur_i.len(), _cnt);
-                        return AppLayerResult::incomplete((i.len() - cur_i.len()) as u32, (cur_i.len() + 1) as u32);
+                        return AppLayerResult::incomplete_from(i.len(), i.len() - cur_i.len(), cur_i.len() + 1);
                     },
                     -1 => {
                         cur_i = &cur_i[1..];
@@ -1263,7 +1292,7 @@ impl NFSState {
                         // but lower than the record size
                         let n1 = cmp::max(cur_i.len(), 1024);
                         let n2 = cmp::min(n1, rec_size);
-                        return AppLayerResult::incomplete((i.len() - cur_i.len()) as u32, n2 as u32);
+                        return AppLayerResult::incomplete_from(i.len(), i.len() - cur_i.len(), n2);
                     }
 
                     // we have the full data of the record, lets parse
@@ -1294,7 +1323,7 @@ impl NFSState {
                         // 12 is the partial RPC header size parse_rpc_packet_header
                         // looks for.
                         let need = if n > 12 { n } else { 12 };
-                        return AppLayerResult::incomplete((i.len() - cur_i.len()) as u32, need as u32);
+                        return AppLayerResult::incomplete_from(i.len(), i.len() - cur_i.len(), need);
                     }
                     return AppLayerResult::err();
                 },
@@ -1361,22 +1390,6 @@ impl NFSState {
         AppLayerResult::ok()
     }
 
-    fn getfiles(&mut self, direction: u8) -> * mut FileContainer {
-        //SCLogDebug!("direction: {}", direction);
-        if direction == STREAM_TOCLIENT {
-            &mut self.files.files_tc as *mut FileContainer
-        } else {
-            &mut self.files.files_ts as *mut FileContainer
-        }
-    }
-    fn setfileflags(&mut self, direction: u8, flags: u16) {
-        SCLogDebug!("direction: {}, flags: {}", direction, flags);
-        if direction == STREAM_TOCLIENT {
-            self.files.flags_tc = flags;
-        } else {
-            self.files.flags_ts = flags;
-        }
-    }
 }
 
 /// Returns *mut NFSState
@@ -1408,10 +1421,9 @@ pub unsafe extern "C" fn rs_nfs_parse_request(flow: *const Flow,
                                        _flags: u8,
                                        ) -> AppLayerResult
 {
-    let state = cast_pointer!(state, NFSState);
-    let flow = cast_pointer!(flow, Flow);
-    let file_flags = FileFlowToFlags(flow, STREAM_TOSERVER);
-    rs_nfs_setfileflags(STREAM_TOSERVER, state, file_flags);
+    let state: &mut NFSState = cast_mut(state);
+    let flow: &mut Flow = cast_mut(flow);
+    state.files.set_flags(Direction::ToServer, FileFlowToFlags(flow, STREAM_TOSERVER));
 
     if input.is_null() == true && input_len > 0 {
         return rs_nfs_parse_request_tcp_gap(state, input_len);
@@ -1442,10 +1454,9 @@ pub unsafe extern "C" fn rs_nfs_parse_response(flow: *const Flow,
                                         _flags: u8,
                                         ) -> AppLayerResult
 {
-    let state = cast_pointer!(state, NFSState);
-    let flow = cast_pointer!(flow, Flow);
-    let file_flags = FileFlowToFlags(flow, STREAM_TOCLIENT);
-    rs_nfs_setfileflags(STREAM_TOCLIENT, state, file_flags);
+    let state: &mut NFSState = cast_mut(state);
+    let flow: &mut Flow = cast_mut(flow);
+    state.files.set_flags(Direction::ToClient, FileFlowToFlags(flow, STREAM_TOCLIENT));
 
     if input.is_null() == true && input_len > 0 {
         return rs_nfs_parse_response_tcp_gap(state, input_len);
@@ -1476,9 +1487,8 @@ pub unsafe extern "C" fn rs_nfs_parse_request_udp(f: *const Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8) -> AppLayerResult
 {
-    let state = cast_pointer!(state, NFSState);
-    let file_flags = FileFlowToFlags(f, STREAM_TOSERVER);
-    rs_nfs_setfileflags(STREAM_TOSERVER, state, file_flags);
+    let state: &mut NFSState = cast_mut(state);
+    state.files.set_flags(Direction::ToServer, FileFlowToFlags(f, STREAM_TOSERVER));
 
     let buf = std::slice::from_raw_parts(input, input_len as usize);
     SCLogDebug!("parsing {} bytes of request data", input_len);
@@ -1494,9 +1504,8 @@ pub unsafe extern "C" fn rs_nfs_parse_response_udp(f: *const Flow,
                                         _data: *const std::os::raw::c_void,
                                         _flags: u8) -> AppLayerResult
 {
-    let state = cast_pointer!(state, NFSState);
-    let file_flags = FileFlowToFlags(f, STREAM_TOCLIENT);
-    rs_nfs_setfileflags(STREAM_TOCLIENT, state, file_flags);
+    let state: &mut NFSState = cast_mut(state);
+    state.files.set_flags(Direction::ToClient, FileFlowToFlags(f, STREAM_TOCLIENT));
     SCLogDebug!("parsing {} bytes of response data", input_len);
     let buf = std::slice::from_raw_parts(input, input_len as usize);
     state.parse_udp_tc(buf)
@@ -1506,7 +1515,7 @@ pub unsafe extern "C" fn rs_nfs_parse_response_udp(f: *const Flow,
 pub unsafe extern "C" fn rs_nfs_state_get_tx_count(state: *mut std::os::raw::c_void)
                                             -> u64
 {
-    let state = cast_pointer!(state, NFSState);
+    let state: &mut NFSState = cast_mut(state);
     SCLogDebug!("rs_nfs_state_get_tx_count: returning {}", state.tx_id);
     return state.tx_id;
 }
@@ -1516,7 +1525,7 @@ pub unsafe extern "C" fn rs_nfs_state_get_tx(state: *mut std::os::raw::c_void,
                                       tx_id: u64)
                                       -> *mut std::os::raw::c_void
 {
-    let state = cast_pointer!(state, NFSState);
+    let state: &mut NFSState = cast_mut(state);
     match state.get_tx_by_id(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -1538,7 +1547,7 @@ pub unsafe extern "C" fn rs_nfs_state_get_tx_iterator(
                                       istate: &mut u64)
                                       -> applayer::AppLayerGetTxIterTuple
 {
-    let state = cast_pointer!(state, NFSState);
+    let state: &mut NFSState = cast_mut(state);
     match state.get_tx_iterator(min_tx_id, istate) {
         Some((tx, out_tx_id, has_next)) => {
             let c_tx = tx as *const _ as *mut _;
@@ -1555,7 +1564,7 @@ pub unsafe extern "C" fn rs_nfs_state_get_tx_iterator(
 pub unsafe extern "C" fn rs_nfs_state_tx_free(state: *mut std::os::raw::c_void,
                                        tx_id: u64)
 {
-    let state = cast_pointer!(state, NFSState);
+    let state: &mut NFSState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -1564,7 +1573,7 @@ pub unsafe extern "C" fn rs_nfs_tx_get_alstate_progress(tx: *mut std::os::raw::c
                                                   direction: u8)
                                                   -> std::os::raw::c_int
 {
-    let tx = cast_pointer!(tx, NFSTransaction);
+    let tx: &mut NFSTransaction = cast_mut(tx);
     if direction == STREAM_TOSERVER && tx.request_done {
         //SCLogNotice!("TOSERVER progress 1");
         return 1;
@@ -1582,43 +1591,17 @@ pub unsafe extern "C" fn rs_nfs_get_tx_data(
     tx: *mut std::os::raw::c_void)
     -> *mut AppLayerTxData
 {
-    let tx = cast_pointer!(tx, NFSTransaction);
+    let tx: &mut NFSTransaction = cast_mut(tx);
     return &mut tx.tx_data;
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_nfs_state_set_tx_detect_state(
-    tx: *mut std::os::raw::c_void,
-    de_state: &mut DetectEngineState) -> i32
-{
-    let tx = cast_pointer!(tx, NFSTransaction);
-    tx.de_state = Some(de_state);
-    0
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_nfs_state_get_tx_detect_state(
-    tx: *mut std::os::raw::c_void)
-    -> *mut DetectEngineState
-{
-    let tx = cast_pointer!(tx, NFSTransaction);
-    match tx.de_state {
-        Some(ds) => {
-            SCLogDebug!("{}: getting de_state", tx.id);
-            return ds;
-        },
-        None => {
-            SCLogDebug!("{}: getting de_state: have none", tx.id);
-            return std::ptr::null_mut();
-        }
-    }
-}
+export_tx_detect_state!(rs_nfs_state_get_tx_detect_state, rs_nfs_state_set_tx_detect_state, NFSTransaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_nfs_state_get_events(tx: *mut std::os::raw::c_void)
                                           -> *mut AppLayerDecoderEvents
 {
-    let tx = cast_pointer!(tx, NFSTransaction);
+    let tx: &mut NFSTransaction = cast_mut(tx);
     return tx.events;
 }
 
@@ -1910,18 +1893,10 @@ pub unsafe extern "C" fn rs_nfs_probe_udp_tc(_f: *const Flow,
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_nfs_getfiles(ptr: *mut std::ffi::c_void, direction: u8) -> * mut FileContainer {
-    if ptr.is_null() { panic!("NULL ptr"); };
-    let parser = cast_pointer!(ptr, NFSState);
-    parser.getfiles(direction)
-}
-#[no_mangle]
-pub unsafe extern "C" fn rs_nfs_setfileflags(direction: u8, ptr: *mut NFSState, flags: u16) {
-    if ptr.is_null() { panic!("NULL ptr"); };
-    let parser = &mut *ptr;
-    SCLogDebug!("direction {} flags {}", direction, flags);
-    parser.setfileflags(direction, flags)
+impl FilesState for NFSState {
+    fn files(&mut self) -> &mut Files {
+        &mut self.files
+    }
 }
 
 // Parser name as a C style string.
@@ -1955,12 +1930,13 @@ pub unsafe extern "C" fn rs_nfs_register_parser() {
         get_eventinfo_byid : Some(rs_nfs_state_get_event_info_by_id),
         localstorage_new: None,
         localstorage_free: None,
-        get_files: Some(rs_nfs_getfiles),
+        get_files: Some(state_get_files::<NFSState>),
         get_tx_iterator: Some(rs_nfs_state_get_tx_iterator),
         get_tx_data: rs_nfs_get_tx_data,
         apply_tx_config: None,
         flags: APP_LAYER_PARSER_OPT_ACCEPT_GAPS,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
@@ -2034,12 +2010,13 @@ pub unsafe extern "C" fn rs_nfs_udp_register_parser() {
         get_eventinfo_byid : None,
         localstorage_new: None,
         localstorage_free: None,
-        get_files: Some(rs_nfs_getfiles),
+        get_files: Some(state_get_files::<NFSState>),
         get_tx_iterator: Some(rs_nfs_state_get_tx_iterator),
         get_tx_data: rs_nfs_get_tx_data,
         apply_tx_config: None,
         flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("udp").unwrap();
@@ -32,9 +32,9 @@ impl NFSState {
                 r.hdr.xid, r.procedure, self.requestmap.len(), r.prog_data.len());
 
         let mut xidmap = NFSRequestXidMap::new(r.progver, r.procedure, 0);
-        let aux_file_name = Vec::new();
+        let mut aux_file_name = Vec::new();
 
-        if r.procedure == NFSPROC3_LOOKUP {
+        if r.procedure == NFSPROC2_LOOKUP {
             match parse_nfs2_request_lookup(r.prog_data) {
                 Ok((_, ar)) => {
                     xidmap.file_handle = ar.handle.value.to_vec();
@@ -44,7 +44,7 @@ impl NFSState {
                     self.set_event(NFSEvent::MalformedData);
                 },
             };
-        } else if r.procedure == NFSPROC3_READ {
+        } else if r.procedure == NFSPROC2_READ {
             match parse_nfs2_request_read(r.prog_data) {
                 Ok((_, read_record)) => {
                     xidmap.chunk_offset = read_record.offset as u64;
@@ -55,6 +55,38 @@ impl NFSState {
                     self.set_event(NFSEvent::MalformedData);
                 },
             };
+        } else if r.procedure == NFSPROC2_REMOVE {
+            match parse_nfs2_diropargs(r.prog_data) {
+                Ok((_, rd)) => {
+                    xidmap.file_handle = rd.handle.value.to_vec();
+                    xidmap.file_name = rd.name_vec;
+                },
+                _ => {
+                    self.set_event(NFSEvent::MalformedData);
+                },
+            };
+        } else if r.procedure == NFSPROC2_RENAME {
+            match parse_nfs2_request_rename(r.prog_data) {
+                Ok((_, rd)) => {
+                    xidmap.file_handle = rd.from.handle.value.to_vec();
+                    xidmap.file_name = rd.from.name_vec;
+                    aux_file_name = rd.to.name_vec;
+                },
+                _ => {
+                    self.set_event(NFSEvent::MalformedData);
+                },
+            };
+        } else if r.procedure == NFSPROC2_LINK {
+            match parse_nfs2_request_link(r.prog_data) {
+                Ok((_, rd)) => {
+                    xidmap.file_handle = rd.handle.value.to_vec();
+                    self.xidmap_handle2name(&mut xidmap);
+                    aux_file_name = rd.to.name_vec;
+                },
+                _ => {
+                    self.set_event(NFSEvent::MalformedData);
+                },
+            };
         }
 
         if !(r.procedure == NFSPROC3_COMMIT || // commit handled separately
@@ -69,8 +101,10 @@ impl NFSState {
             tx.file_handle = xidmap.file_handle.to_vec();
             tx.nfs_version = r.progver as u16;
 
-            if r.procedure == NFSPROC3_RENAME {
+            if r.procedure == NFSPROC2_RENAME {
                 tx.type_data = Some(NFSTransactionTypeData::RENAME(aux_file_name));
+            } else if r.procedure == NFSPROC2_LINK {
+                tx.type_data = Some(NFSTransactionTypeData::LINK(aux_file_name));
             }
 
             tx.auth_type = r.creds_flavor;
@@ -95,7 +129,7 @@ impl NFSState {
         let mut nfs_status = 0;
         let resp_handle = Vec::new();
 
-        if xidmap.procedure == NFSPROC3_READ {
+        if xidmap.procedure == NFSPROC2_READ {
             match parse_nfs2_reply_read(r.prog_data) {
                 Ok((_, ref reply)) => {
                     SCLogDebug!("NFSv2: READ reply record");
@@ -55,6 +55,63 @@ named!(pub parse_nfs2_request_lookup<Nfs2RequestLookup>,
         ))
 );
 
+/// diropargs: directory handle and name of an entry
+#[derive(Debug,PartialEq)]
+pub struct Nfs2DirOpArgs<'a> {
+    pub handle: Nfs2Handle<'a>,
+    pub name_vec: Vec<u8>,
+}
+
+named!(pub parse_nfs2_diropargs<Nfs2DirOpArgs>,
+    do_parse!(
+            handle: parse_nfs2_handle
+        >>  name_len: be_u32
+        >>  name_contents: take!(name_len)
+        >>  _name_padding: cond!(name_len % 4 != 0, take!(4 - name_len % 4))
+        >> (
+            Nfs2DirOpArgs {
+                handle,
+                name_vec:name_contents.to_vec(),
+            }
+        ))
+);
+
+#[derive(Debug,PartialEq)]
+pub struct Nfs2RequestRename<'a> {
+    pub from: Nfs2DirOpArgs<'a>,
+    pub to: Nfs2DirOpArgs<'a>,
+}
+
+named!(pub parse_nfs2_request_rename<Nfs2RequestRename>,
+    do_parse!(
+            from: parse_nfs2_diropargs
+        >>  to: parse_nfs2_diropargs
+        >> (
+            Nfs2RequestRename {
+                from,
+                to,
+            }
+        ))
+);
+
+#[derive(Debug,PartialEq)]
+pub struct Nfs2RequestLink<'a> {
+    pub handle: Nfs2Handle<'a>,
+    pub to: Nfs2DirOpArgs<'a>,
+}
+
+named!(pub parse_nfs2_request_link<Nfs2RequestLink>,
+    do_parse!(
+            handle: parse_nfs2_handle
+        >>  to: parse_nfs2_diropargs
+        >> (
+            Nfs2RequestLink {
+                handle,
+                to,
+            }
+        ))
+);
+
 #[derive(Debug,PartialEq)]
 pub struct Nfs2RequestRead<'a> {
     pub handle: Nfs2Handle<'a>,
@@ -100,6 +100,14 @@ impl NFSState {
             } else {
                 self.set_event(NFSEvent::MalformedData);
             };
+        } else if r.procedure == NFSPROC3_LINK {
+            if let Ok((_, rd)) = parse_nfs3_request_link(r.prog_data) {
+                xidmap.file_handle = rd.handle.value.to_vec();
+                self.xidmap_handle2name(&mut xidmap);
+                aux_file_name = rd.link_name_vec;
+            } else {
+                self.set_event(NFSEvent::MalformedData);
+            };
         } else if r.procedure == NFSPROC3_MKDIR {
             if let Ok((_, rd)) = parse_nfs3_request_mkdir(r.prog_data) {
                 xidmap.file_handle = rd.handle.value.to_vec();
@@ -148,6 +156,8 @@ impl NFSState {
 
             if r.procedure == NFSPROC3_RENAME {
                 tx.type_data = Some(NFSTransactionTypeData::RENAME(aux_file_name));
+            } else if r.procedure == NFSPROC3_LINK {
+                tx.type_data = Some(NFSTransactionTypeData::LINK(aux_file_name));
             }
 
             tx.auth_type = r.creds_flavor;
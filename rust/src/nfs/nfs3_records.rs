@@ -195,6 +195,29 @@ named!(pub parse_nfs3_request_rename<Nfs3RequestRename>,
         ))
 );
 
+#[derive(Debug,PartialEq)]
+pub struct Nfs3RequestLink<'a> {
+    pub handle: Nfs3Handle<'a>,
+    pub link_dir_handle: Nfs3Handle<'a>,
+    pub link_name_vec: Vec<u8>,
+}
+
+named!(pub parse_nfs3_request_link<Nfs3RequestLink>,
+    do_parse!(
+            handle: parse_nfs3_handle
+        >>  link_dir_handle: parse_nfs3_handle
+        >>  link_name_len: be_u32
+        >>  link_name: take!(link_name_len)
+        >>  _fill_bytes: rest
+        >> (
+            Nfs3RequestLink {
+                handle,
+                link_dir_handle,
+                link_name_vec:link_name.to_vec(),
+            }
+        ))
+);
+
 #[derive(Debug,PartialEq)]
 pub struct Nfs3RequestGetAttr<'a> {
     pub handle: Nfs3Handle<'a>,
@@ -443,3 +466,24 @@ named!(pub parse_nfs3_reply_read<NfsReplyRead>,
             }
         ))
 );
+
+#[cfg(test)]
+mod tests {
+    use crate::nfs::nfs3_records::*;
+
+    #[test]
+    fn test_nfs3_request_link() {
+        let buf: &[u8] = &[
+            0x00, 0x00, 0x00, 0x08, // file handle
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+            0x00, 0x00, 0x00, 0x04, // link dir handle
+            0x0a, 0x0b, 0x0c, 0x0d,
+            0x00, 0x00, 0x00, 0x05, // link name
+            0x62, 0x2e, 0x74, 0x78, 0x74, 0x00, 0x00, 0x00,
+        ];
+        let (_, rd) = parse_nfs3_request_link(buf).unwrap();
+        assert_eq!(rd.handle.value, &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        assert_eq!(rd.link_dir_handle.value, &[0x0a, 0x0b, 0x0c, 0x0d]);
+        assert_eq!(rd.link_name_vec, b"b.txt".to_vec());
+    }
+}
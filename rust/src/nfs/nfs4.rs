@@ -123,7 +123,7 @@ impl NFSState {
 
     fn new_tx_v4<'b>(&mut self, r: &RpcPacket<'b>,
             xidmap: &NFSRequestXidMap, procedure: u32,
-            _aux_opcodes: &Vec<u32>)
+            _aux_opcodes: &Vec<u32>, type_data: Option<NFSTransactionTypeData>)
     {
         let mut tx = self.new_tx();
         tx.xid = r.hdr.xid;
@@ -132,6 +132,7 @@ impl NFSState {
         tx.file_name = xidmap.file_name.to_vec();
         tx.nfs_version = r.progver as u16;
         tx.file_handle = xidmap.file_handle.to_vec();
+        tx.type_data = type_data;
 
         tx.auth_type = r.creds_flavor;
         match r.creds {
@@ -155,8 +156,11 @@ impl NFSState {
             xidmap: &mut NFSRequestXidMap)
     {
         let mut last_putfh : Option<&'b[u8]> = None;
+        let mut saved_fh : Option<&'b[u8]> = None;
         let mut main_opcode : u32 = 0;
         let mut aux_opcodes : Vec<u32> = Vec::new();
+        let mut type_data : Option<NFSTransactionTypeData> = None;
+        let mut ssn_id : Option<&'b[u8]> = None;
 
         for c in &cr.commands {
             SCLogDebug!("c {:?}", c);
@@ -212,18 +216,129 @@ impl NFSState {
                     xidmap.file_name = rd.to_vec();
                     main_opcode = NFSPROC4_REMOVE;
                 }
+                &Nfs4RequestContent::SaveFH => {
+                    saved_fh = last_putfh;
+                }
+                &Nfs4RequestContent::Rename(ref rd) => {
+                    SCLogDebug!("RENAMEv4: {:?}", rd);
+                    // PUTFH (src dir) SAVEFH PUTFH (dst dir) RENAME
+                    if let Some(fh) = saved_fh {
+                        xidmap.file_handle = fh.to_vec();
+                    }
+                    xidmap.file_name = rd.oldname.to_vec();
+                    type_data = Some(NFSTransactionTypeData::RENAME(rd.newname.to_vec()));
+                    main_opcode = NFSPROC4_RENAME;
+                }
+                &Nfs4RequestContent::Link(rd) => {
+                    SCLogDebug!("LINKv4: {:?}", rd);
+                    // PUTFH (file) SAVEFH PUTFH (dir) LINK
+                    if let Some(fh) = saved_fh {
+                        xidmap.file_handle = fh.to_vec();
+                        self.xidmap_handle2name(xidmap);
+                    }
+                    type_data = Some(NFSTransactionTypeData::LINK(rd.to_vec()));
+                    main_opcode = NFSPROC4_LINK;
+                }
                 &Nfs4RequestContent::SetClientId(ref _rd) => {
                     SCLogDebug!("SETCLIENTIDv4: client id {} r_netid {} r_addr {}",
                             String::from_utf8_lossy(&_rd.client_id),
                             String::from_utf8_lossy(&_rd.r_netid),
                             String::from_utf8_lossy(&_rd.r_addr));
                 }
+                &Nfs4RequestContent::Sequence(ref rd) => {
+                    SCLogDebug!("SEQUENCEv4: {:?}", rd);
+                    ssn_id = Some(rd.ssn_id);
+                    aux_opcodes.push(NFSPROC4_SEQUENCE);
+                }
+                &Nfs4RequestContent::ExchangeId(ref rd) => {
+                    SCLogDebug!("EXCHANGE_IDv4: {:?}", rd);
+                    type_data = Some(NFSTransactionTypeData::SESSION(NFSTransactionSession {
+                        client_owner: rd.client_string.to_vec(),
+                        ..Default::default()
+                    }));
+                    main_opcode = NFSPROC4_EXCHANGE_ID;
+                }
+                &Nfs4RequestContent::CreateSession(ref rd) => {
+                    SCLogDebug!("CREATE_SESSIONv4: {:?}", rd);
+                    type_data = Some(NFSTransactionTypeData::SESSION(NFSTransactionSession {
+                        client_id: rd.client_id,
+                        ..Default::default()
+                    }));
+                    main_opcode = NFSPROC4_CREATE_SESSION;
+                }
+                &Nfs4RequestContent::DestroySession(rd) => {
+                    SCLogDebug!("DESTROY_SESSIONv4: {:?}", rd);
+                    type_data = Some(NFSTransactionTypeData::SESSION(NFSTransactionSession {
+                        session_id: rd.to_vec(),
+                        ..Default::default()
+                    }));
+                    main_opcode = NFSPROC4_DESTROY_SESSION;
+                }
+                &Nfs4RequestContent::Copy(ref rd) => {
+                    SCLogDebug!("COPYv4: {:?}", rd);
+                    // PUTFH (src) SAVEFH PUTFH (dst) COPY: the last handle
+                    // is the destination file
+                    if let Some(fh) = last_putfh {
+                        xidmap.file_handle = fh.to_vec();
+                        self.xidmap_handle2name(xidmap);
+                    }
+                    type_data = Some(NFSTransactionTypeData::RANGE(NFSTransactionRange {
+                        offset: rd.dst_offset,
+                        length: rd.count,
+                        src_offset: rd.src_offset,
+                        ..Default::default()
+                    }));
+                    main_opcode = NFSPROC4_COPY;
+                }
+                &Nfs4RequestContent::Seek(ref rd) => {
+                    SCLogDebug!("SEEKv4: {:?}", rd);
+                    if let Some(fh) = last_putfh {
+                        xidmap.file_handle = fh.to_vec();
+                        self.xidmap_handle2name(xidmap);
+                    }
+                    type_data = Some(NFSTransactionTypeData::RANGE(NFSTransactionRange {
+                        offset: rd.offset,
+                        what: rd.what,
+                        ..Default::default()
+                    }));
+                    main_opcode = NFSPROC4_SEEK;
+                }
+                &Nfs4RequestContent::Allocate(ref rd) |
+                &Nfs4RequestContent::Deallocate(ref rd) => {
+                    SCLogDebug!("(DE)ALLOCATEv4: {:?}", rd);
+                    if let Some(fh) = last_putfh {
+                        xidmap.file_handle = fh.to_vec();
+                        self.xidmap_handle2name(xidmap);
+                    }
+                    type_data = Some(NFSTransactionTypeData::RANGE(NFSTransactionRange {
+                        offset: rd.offset,
+                        length: rd.length,
+                        ..Default::default()
+                    }));
+                    main_opcode = if let &Nfs4RequestContent::Allocate(_) = c {
+                        NFSPROC4_ALLOCATE
+                    } else {
+                        NFSPROC4_DEALLOCATE
+                    };
+                }
                 &_ => { },
             }
         }
 
+        /* a compound with just a SEQUENCE is the v4.1 replacement of RENEW,
+         * keeping the session and the lease alive. */
+        if main_opcode == 0 && cr.commands.len() == 1 {
+            if let Some(ssn_id) = ssn_id {
+                type_data = Some(NFSTransactionTypeData::SESSION(NFSTransactionSession {
+                    session_id: ssn_id.to_vec(),
+                    ..Default::default()
+                }));
+                main_opcode = NFSPROC4_SEQUENCE;
+            }
+        }
+
         if main_opcode != 0 {
-            self.new_tx_v4(r, xidmap, main_opcode, &aux_opcodes);
+            self.new_tx_v4(r, xidmap, main_opcode, &aux_opcodes, type_data);
         }
     }
 
@@ -297,6 +412,9 @@ impl NFSState {
         let mut insert_filename_with_getfh = false;
         let mut main_opcode_status : u32 = 0;
         let mut main_opcode_status_set : bool = false;
+        let mut client_id : Option<u64> = None;
+        let mut ssn_id : Option<&'b[u8]> = None;
+        let mut range_result : Option<(u64, bool)> = None;
 
         for c in &cr.commands {
             SCLogDebug!("c {:?}", c);
@@ -359,6 +477,51 @@ impl NFSState {
                         SCLogDebug!("filename {:?}", xidmap.file_name);
                     }
                 },
+                &Nfs4ResponseContent::Sequence(s, _) => {
+                    SCLogDebug!("SEQUENCE4: status {}", s);
+                    main_opcode_status = s;
+                    main_opcode_status_set = true;
+                },
+                &Nfs4ResponseContent::ExchangeId(s, ref rd) => {
+                    SCLogDebug!("EXCHANGE_ID4: status {} {:?}", s, rd);
+                    if let &Some(ref rd) = rd {
+                        client_id = Some(rd.client_id);
+                    }
+                    main_opcode_status = s;
+                    main_opcode_status_set = true;
+                },
+                &Nfs4ResponseContent::CreateSession(s, ref rd) => {
+                    SCLogDebug!("CREATE_SESSION4: status {} {:?}", s, rd);
+                    if let &Some(ref rd) = rd {
+                        ssn_id = Some(rd.ssn_id);
+                    }
+                    main_opcode_status = s;
+                    main_opcode_status_set = true;
+                },
+                &Nfs4ResponseContent::Copy(s, ref rd) => {
+                    SCLogDebug!("COPY4: status {} {:?}", s, rd);
+                    if let &Some(ref rd) = rd {
+                        range_result = Some((rd.count, rd.asynchronous));
+                    }
+                    main_opcode_status = s;
+                    main_opcode_status_set = true;
+                },
+                &Nfs4ResponseContent::Seek(s, ref rd) => {
+                    SCLogDebug!("SEEK4: status {} {:?}", s, rd);
+                    if let &Some(ref rd) = rd {
+                        range_result = Some((rd.offset, rd.eof));
+                    }
+                    main_opcode_status = s;
+                    main_opcode_status_set = true;
+                },
+                &Nfs4ResponseContent::Rename(s) |
+                &Nfs4ResponseContent::Link(s) |
+                &Nfs4ResponseContent::DestroySession(s) |
+                &Nfs4ResponseContent::Allocate(s) |
+                &Nfs4ResponseContent::Deallocate(s) => {
+                    main_opcode_status = s;
+                    main_opcode_status_set = true;
+                },
                 &_ => { },
             }
         }
@@ -366,6 +529,26 @@ impl NFSState {
         if main_opcode_status_set {
             let resp_handle = Vec::new();
             self.mark_response_tx_done(r.hdr.xid, r.reply_state, main_opcode_status, &resp_handle);
+
+            if let Some(tx) = self.get_tx_by_xid(r.hdr.xid) {
+                match tx.type_data {
+                    Some(NFSTransactionTypeData::SESSION(ref mut tds)) => {
+                        if let Some(client_id) = client_id {
+                            tds.client_id = client_id;
+                        }
+                        if let Some(ssn_id) = ssn_id {
+                            tds.session_id = ssn_id.to_vec();
+                        }
+                    },
+                    Some(NFSTransactionTypeData::RANGE(ref mut tdr)) => {
+                        if let Some((result, flag)) = range_result {
+                            tdr.result = result;
+                            tdr.result_flag = flag;
+                        }
+                    },
+                    _ => { },
+                }
+            }
         }
     }
 
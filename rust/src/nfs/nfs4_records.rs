@@ -41,11 +41,19 @@ pub enum Nfs4RequestContent<'a> {
     SetAttr(Nfs4RequestSetAttr<'a>),
     Renew(u64),
     Remove(&'a[u8]),
+    Link(&'a[u8]),
     DelegReturn(Nfs4StateId<'a>),
     SetClientId(Nfs4RequestSetClientId<'a>),
     SetClientIdConfirm,
     ExchangeId(Nfs4RequestExchangeId<'a>),
     Sequence(Nfs4RequestSequence<'a>),
+    CreateSession(Nfs4RequestCreateSession),
+    DestroySession(&'a[u8]),
+    ReclaimComplete(u32),
+    Copy(Nfs4RequestCopy<'a>),
+    Seek(Nfs4RequestSeek<'a>),
+    Allocate(Nfs4RequestAllocate<'a>),
+    Deallocate(Nfs4RequestAllocate<'a>),
 }
 
 #[derive(Debug,PartialEq)]
@@ -271,6 +279,12 @@ named!(nfs4_req_rename<Nfs4RequestContent>,
         ))
 );
 
+named!(nfs4_req_link<Nfs4RequestContent>,
+    do_parse!(
+            newname: nfs4_parse_nfsstring
+        >> ( Nfs4RequestContent::Link(newname) )
+));
+
 #[derive(Debug,PartialEq)]
 pub struct Nfs4RequestLookup<'a> {
     pub filename: &'a[u8],
@@ -425,22 +439,45 @@ pub struct Nfs4RequestExchangeId<'a> {
     pub nii_name: &'a[u8],
 }
 
+// nfs_impl_id4: implementation domain and name, plus build date
+named!(nfs4_parse_impl_id<(&[u8], &[u8])>,
+    do_parse!(
+        nii_domain: nfs4_parse_nfsstring
+    >>  nii_name: nfs4_parse_nfsstring
+    >>  _nii_data_sec: be_u64
+    >>  _nii_data_nsec: be_u32
+    >> ( (nii_domain, nii_name) )
+));
+
+named!(nfs4_parse_bitmap<Vec<u32>>,
+    do_parse!(
+        cnt: verify!(be_u32, |&v| v <= 8)
+    >>  words: count!(be_u32, cnt as usize)
+    >> ( words )
+));
+
+// state_protect4_a/state_protect4_r: only SP4_NONE and SP4_MACH_CRED are
+// supported. SP4_SSV is rare and makes the parser bail.
+named!(nfs4_parse_state_protect<u32>,
+    do_parse!(
+        how: verify!(be_u32, |&v| v <= 1)
+    >>  _mach_ops: cond!(how == 1, tuple!(nfs4_parse_bitmap, nfs4_parse_bitmap))
+    >> ( how )
+));
+
 named!(nfs4_req_exchangeid<Nfs4RequestContent>,
     do_parse!(
         _verifier: take!(8)
     >>  eia_clientstring: nfs4_parse_nfsstring
     >>  _eia_clientflags: be_u32
-    >>  _eia_state_protect: be_u32
-    >>  _eia_client_impl_id: be_u32
-    >>  nii_domain: nfs4_parse_nfsstring
-    >>  nii_name: nfs4_parse_nfsstring
-    >>  _nii_data_sec: be_u64
-    >>  _nii_data_nsec: be_u32
+    >>  _eia_state_protect: nfs4_parse_state_protect
+    >>  eia_client_impl_id_cnt: verify!(be_u32, |&v| v <= 1)
+    >>  impl_id: cond!(eia_client_impl_id_cnt == 1, nfs4_parse_impl_id)
     >> (Nfs4RequestContent::ExchangeId(
             Nfs4RequestExchangeId {
                 client_string: eia_clientstring,
-                nii_domain,
-                nii_name
+                nii_domain: impl_id.map(|i| i.0).unwrap_or(&[]),
+                nii_name: impl_id.map(|i| i.1).unwrap_or(&[]),
             }
         ))
 ));
@@ -464,6 +501,176 @@ named!(nfs4_req_sequence<Nfs4RequestContent>,
         ))
 ));
 
+// channel_attrs4, the limits of the fore and back channels of a session
+named!(nfs4_parse_channel_attrs<u32>,
+    do_parse!(
+        _headerpadsize: be_u32
+    >>  _maxrequestsize: be_u32
+    >>  _maxresponsesize: be_u32
+    >>  _maxresponsesize_cached: be_u32
+    >>  _maxoperations: be_u32
+    >>  maxrequests: be_u32
+    >>  rdma_ird_cnt: verify!(be_u32, |&v| v <= 1)
+    >>  _rdma_ird: cond!(rdma_ird_cnt == 1, be_u32)
+    >> ( maxrequests )
+));
+
+// callback_sec_parms4: AUTH_NONE, AUTH_SYS or RPCSEC_GSS
+named!(nfs4_parse_cb_sec_parms<u32>,
+    do_parse!(
+        flavor: be_u32
+    >>  _data: switch!(value!(flavor),
+            RPCAUTH_NULL => value!(()) |
+            RPCAUTH_UNIX => do_parse!(
+                    _stamp: be_u32
+                >>  _machine_name: nfs4_parse_nfsstring
+                >>  _uid: be_u32
+                >>  _gid: be_u32
+                >>  gids_cnt: verify!(be_u32, |&v| v <= 16)
+                >>  _gids: count!(be_u32, gids_cnt as usize)
+                >> ( () )) |
+            RPCAUTH_GSS => do_parse!(
+                    _service: be_u32
+                >>  _handle_from_server: nfs4_parse_nfsstring
+                >>  _handle_from_client: nfs4_parse_nfsstring
+                >> ( () ))
+            )
+    >> ( flavor )
+));
+
+#[derive(Debug,PartialEq)]
+pub struct Nfs4RequestCreateSession {
+    pub client_id: u64,
+    pub seqid: u32,
+    pub flags: u32,
+}
+
+named!(nfs4_req_create_session<Nfs4RequestContent>,
+    do_parse!(
+        client_id: be_u64
+    >>  seqid: be_u32
+    >>  flags: be_u32
+    >>  _fore_chan_attrs: nfs4_parse_channel_attrs
+    >>  _back_chan_attrs: nfs4_parse_channel_attrs
+    >>  _cb_program: be_u32
+    >>  sec_parms_cnt: verify!(be_u32, |&v| v <= 16)
+    >>  _sec_parms: count!(nfs4_parse_cb_sec_parms, sec_parms_cnt as usize)
+    >> (Nfs4RequestContent::CreateSession(
+            Nfs4RequestCreateSession {
+                client_id,
+                seqid,
+                flags,
+            }
+        ))
+));
+
+named!(nfs4_req_destroy_session<Nfs4RequestContent>,
+    do_parse!(
+        ssn_id: take!(16)
+    >> ( Nfs4RequestContent::DestroySession(ssn_id) )
+));
+
+named!(nfs4_req_reclaim_complete<Nfs4RequestContent>,
+    do_parse!(
+        one_fs: be_u32
+    >> ( Nfs4RequestContent::ReclaimComplete(one_fs) )
+));
+
+// netloc4, the location of the source server of an inter server COPY
+named!(nfs4_parse_netloc<&[u8]>,
+    do_parse!(
+        nl_type: be_u32
+    >>  loc: switch!(value!(nl_type),
+            1 => call!(nfs4_parse_nfsstring) | // NL4_NAME
+            2 => call!(nfs4_parse_nfsstring) | // NL4_URL
+            3 => do_parse!(                    // NL4_NETADDR
+                    _netid: nfs4_parse_nfsstring
+                >>  addr: nfs4_parse_nfsstring
+                >> ( addr ))
+            )
+    >> ( loc )
+));
+
+#[derive(Debug,PartialEq)]
+pub struct Nfs4RequestCopy<'a> {
+    pub src_stateid: Nfs4StateId<'a>,
+    pub dst_stateid: Nfs4StateId<'a>,
+    pub src_offset: u64,
+    pub dst_offset: u64,
+    pub count: u64,
+    pub synchronous: bool,
+    /// locations of the source server, empty for intra server copies
+    pub source_servers: Vec<&'a[u8]>,
+}
+
+named!(nfs4_req_copy<Nfs4RequestContent>,
+    do_parse!(
+        src_stateid: nfs4_parse_stateid
+    >>  dst_stateid: nfs4_parse_stateid
+    >>  src_offset: be_u64
+    >>  dst_offset: be_u64
+    >>  count: be_u64
+    >>  _consecutive: be_u32
+    >>  synchronous: be_u32
+    >>  source_server_cnt: verify!(be_u32, |&v| v <= 16)
+    >>  source_servers: count!(nfs4_parse_netloc, source_server_cnt as usize)
+    >> (Nfs4RequestContent::Copy(Nfs4RequestCopy {
+            src_stateid,
+            dst_stateid,
+            src_offset,
+            dst_offset,
+            count,
+            synchronous: synchronous != 0,
+            source_servers,
+        }))
+));
+
+#[derive(Debug,PartialEq)]
+pub struct Nfs4RequestSeek<'a> {
+    pub stateid: Nfs4StateId<'a>,
+    pub offset: u64,
+    /// data_content4: NFS4_CONTENT_DATA (0) or NFS4_CONTENT_HOLE (1)
+    pub what: u32,
+}
+
+named!(nfs4_req_seek<Nfs4RequestContent>,
+    do_parse!(
+        stateid: nfs4_parse_stateid
+    >>  offset: be_u64
+    >>  what: be_u32
+    >> (Nfs4RequestContent::Seek(Nfs4RequestSeek {
+            stateid,
+            offset,
+            what,
+        }))
+));
+
+/// ALLOCATE and DEALLOCATE arguments
+#[derive(Debug,PartialEq)]
+pub struct Nfs4RequestAllocate<'a> {
+    pub stateid: Nfs4StateId<'a>,
+    pub offset: u64,
+    pub length: u64,
+}
+
+named!(nfs4_parse_allocate<Nfs4RequestAllocate>,
+    do_parse!(
+        stateid: nfs4_parse_stateid
+    >>  offset: be_u64
+    >>  length: be_u64
+    >> (Nfs4RequestAllocate {
+            stateid,
+            offset,
+            length,
+        })
+));
+
+named!(nfs4_req_allocate<Nfs4RequestContent>,
+    map!(nfs4_parse_allocate, Nfs4RequestContent::Allocate));
+
+named!(nfs4_req_deallocate<Nfs4RequestContent>,
+    map!(nfs4_parse_allocate, Nfs4RequestContent::Deallocate));
+
 named!(parse_request_compound_command<Nfs4RequestContent>,
     do_parse!(
         cmd: be_u32
@@ -484,6 +691,7 @@ named!(parse_request_compound_command<Nfs4RequestContent>,
             NFSPROC4_OPEN_CONFIRM           => call!(nfs4_req_open_confirm)         |
             NFSPROC4_REMOVE                 => call!(nfs4_req_remove)               |
             NFSPROC4_RENAME                 => call!(nfs4_req_rename)               |
+            NFSPROC4_LINK                   => call!(nfs4_req_link)                 |
             NFSPROC4_CREATE                 => call!(nfs4_req_create)               |
             NFSPROC4_DELEGRETURN            => call!(nfs4_req_delegreturn)          |
             NFSPROC4_SETATTR                => call!(nfs4_req_setattr)              |
@@ -491,7 +699,14 @@ named!(parse_request_compound_command<Nfs4RequestContent>,
             NFSPROC4_SETCLIENTID            => call!(nfs4_req_setclientid)          |
             NFSPROC4_SETCLIENTID_CONFIRM    => call!(nfs4_req_setclientid_confirm)  |
             NFSPROC4_SEQUENCE               => call!(nfs4_req_sequence)             |
-            NFSPROC4_EXCHANGE_ID            => call!(nfs4_req_exchangeid)
+            NFSPROC4_EXCHANGE_ID            => call!(nfs4_req_exchangeid)           |
+            NFSPROC4_CREATE_SESSION         => call!(nfs4_req_create_session)       |
+            NFSPROC4_DESTROY_SESSION        => call!(nfs4_req_destroy_session)      |
+            NFSPROC4_RECLAIM_COMPLETE       => call!(nfs4_req_reclaim_complete)     |
+            NFSPROC4_COPY                   => call!(nfs4_req_copy)                 |
+            NFSPROC4_SEEK                   => call!(nfs4_req_seek)                 |
+            NFSPROC4_ALLOCATE               => call!(nfs4_req_allocate)             |
+            NFSPROC4_DEALLOCATE             => call!(nfs4_req_deallocate)
             )
         >> ( cmd_data )
 ));
@@ -521,6 +736,7 @@ pub enum Nfs4ResponseContent<'a> {
     Lookup(u32),
     SaveFH(u32),
     Rename(u32),
+    Link(u32),
     Write(u32, Option<Nfs4ResponseWrite>),
     Read(u32, Option<Nfs4ResponseRead<'a>>),
     Renew(u32),
@@ -538,6 +754,14 @@ pub enum Nfs4ResponseContent<'a> {
     Create(u32),
     Commit(u32),
     Sequence(u32, Option<Nfs4ResponseSequence<'a>>),
+    ExchangeId(u32, Option<Nfs4ResponseExchangeId<'a>>),
+    CreateSession(u32, Option<Nfs4ResponseCreateSession<'a>>),
+    DestroySession(u32),
+    ReclaimComplete(u32),
+    Copy(u32, Option<Nfs4ResponseCopy>),
+    Seek(u32, Option<Nfs4ResponseSeek>),
+    Allocate(u32),
+    Deallocate(u32),
 }
 
 #[derive(Debug,PartialEq)]
@@ -750,9 +974,17 @@ named!(nfs4_res_remove<Nfs4ResponseContent>,
 named!(nfs4_res_rename<Nfs4ResponseContent>,
     do_parse!(
             status: be_u32
+        >>  cond!(status == 0, take!(40))   // source and target change_info
         >> ( Nfs4ResponseContent::Rename(status) )
 ));
 
+named!(nfs4_res_link<Nfs4ResponseContent>,
+    do_parse!(
+            status: be_u32
+        >>  cond!(status == 0, take!(20))   // change_info
+        >> ( Nfs4ResponseContent::Link(status) )
+));
+
 named!(nfs4_res_savefh<Nfs4ResponseContent>,
     do_parse!(
             status: be_u32
@@ -864,6 +1096,151 @@ named!(nfs4_res_sequence<Nfs4ResponseContent>,
         >> ( Nfs4ResponseContent::Sequence(status, seq) )
 ));
 
+#[derive(Debug,PartialEq)]
+pub struct Nfs4ResponseExchangeId<'a> {
+    pub client_id: u64,
+    pub seqid: u32,
+    pub flags: u32,
+    pub server_owner: &'a[u8],
+    pub server_scope: &'a[u8],
+}
+
+named!(nfs4_res_exchangeid_ok<Nfs4ResponseExchangeId>,
+    do_parse!(
+            client_id: be_u64
+        >>  seqid: be_u32
+        >>  flags: be_u32
+        >>  _state_protect: nfs4_parse_state_protect
+        >>  _so_minor_id: be_u64
+        >>  server_owner: nfs4_parse_nfsstring
+        >>  server_scope: nfs4_parse_nfsstring
+        >>  impl_id_cnt: verify!(be_u32, |&v| v <= 1)
+        >>  _impl_id: cond!(impl_id_cnt == 1, nfs4_parse_impl_id)
+        >> ( Nfs4ResponseExchangeId {
+                client_id,
+                seqid,
+                flags,
+                server_owner,
+                server_scope,
+            })
+));
+
+named!(nfs4_res_exchangeid<Nfs4ResponseContent>,
+    do_parse!(
+            status: be_u32
+        >>  eid: cond!(status == 0, nfs4_res_exchangeid_ok)
+        >> ( Nfs4ResponseContent::ExchangeId(status, eid) )
+));
+
+#[derive(Debug,PartialEq)]
+pub struct Nfs4ResponseCreateSession<'a> {
+    pub ssn_id: &'a[u8],
+    pub seqid: u32,
+    pub flags: u32,
+}
+
+named!(nfs4_res_create_session_ok<Nfs4ResponseCreateSession>,
+    do_parse!(
+            ssn_id: take!(16)
+        >>  seqid: be_u32
+        >>  flags: be_u32
+        >>  _fore_chan_attrs: nfs4_parse_channel_attrs
+        >>  _back_chan_attrs: nfs4_parse_channel_attrs
+        >> ( Nfs4ResponseCreateSession {
+                ssn_id,
+                seqid,
+                flags,
+            })
+));
+
+named!(nfs4_res_create_session<Nfs4ResponseContent>,
+    do_parse!(
+            status: be_u32
+        >>  cs: cond!(status == 0, nfs4_res_create_session_ok)
+        >> ( Nfs4ResponseContent::CreateSession(status, cs) )
+));
+
+named!(nfs4_res_destroy_session<Nfs4ResponseContent>,
+    do_parse!(
+            status: be_u32
+        >> ( Nfs4ResponseContent::DestroySession(status) )
+));
+
+named!(nfs4_res_reclaim_complete<Nfs4ResponseContent>,
+    do_parse!(
+            status: be_u32
+        >> ( Nfs4ResponseContent::ReclaimComplete(status) )
+));
+
+#[derive(Debug,PartialEq)]
+pub struct Nfs4ResponseCopy {
+    /// bytes copied, for synchronous copies
+    pub count: u64,
+    /// the copy continues in the background
+    pub asynchronous: bool,
+    pub committed: u32,
+}
+
+named!(nfs4_res_copy_ok<Nfs4ResponseCopy>,
+    do_parse!(
+            callback_id_cnt: verify!(be_u32, |&v| v <= 1)
+        >>  _callback_id: cond!(callback_id_cnt == 1, nfs4_parse_stateid)
+        >>  count: be_u64
+        >>  committed: be_u32
+        >>  _verifier: take!(8)
+        >>  _consecutive: be_u32
+        >>  _synchronous: be_u32
+        >> ( Nfs4ResponseCopy {
+                count,
+                asynchronous: callback_id_cnt == 1,
+                committed,
+            })
+));
+
+named!(nfs4_res_copy<Nfs4ResponseContent>,
+    do_parse!(
+            status: be_u32
+        >>  cd: cond!(status == 0, nfs4_res_copy_ok)
+            // NFS4ERR_OFFLOAD_NO_REQS comes with copy_requirements4
+        >>  _reqs: cond!(status == NFS4ERR_OFFLOAD_NO_REQS, tuple!(be_u32, be_u32))
+        >> ( Nfs4ResponseContent::Copy(status, cd) )
+));
+
+#[derive(Debug,PartialEq)]
+pub struct Nfs4ResponseSeek {
+    pub eof: bool,
+    pub offset: u64,
+}
+
+named!(nfs4_res_seek_ok<Nfs4ResponseSeek>,
+    do_parse!(
+            eof: be_u32
+        >>  offset: be_u64
+        >> ( Nfs4ResponseSeek {
+                eof: eof != 0,
+                offset,
+            })
+));
+
+named!(nfs4_res_seek<Nfs4ResponseContent>,
+    do_parse!(
+            status: be_u32
+        >>  sd: cond!(status == 0, nfs4_res_seek_ok)
+        >> ( Nfs4ResponseContent::Seek(status, sd) )
+));
+
+named!(nfs4_res_allocate<Nfs4ResponseContent>,
+    do_parse!(
+            status: be_u32
+        >> ( Nfs4ResponseContent::Allocate(status) )
+));
+
+named!(nfs4_res_deallocate<Nfs4ResponseContent>,
+    do_parse!(
+            status: be_u32
+        >> ( Nfs4ResponseContent::Deallocate(status) )
+));
+
 named!(nfs4_res_compound_command<Nfs4ResponseContent>,
     do_parse!(
         cmd: be_u32
@@ -876,6 +1253,7 @@ named!(nfs4_res_compound_command<Nfs4ResponseContent>,
             NFSPROC4_PUTFH                  => call!(nfs4_res_putfh)               |
             NFSPROC4_SAVEFH                 => call!(nfs4_res_savefh)              |
             NFSPROC4_RENAME                 => call!(nfs4_res_rename)              |
+            NFSPROC4_LINK                   => call!(nfs4_res_link)                |
             NFSPROC4_READDIR                => call!(nfs4_res_readdir)             |
             NFSPROC4_GETATTR                => call!(nfs4_res_getattr)             |
             NFSPROC4_SETATTR                => call!(nfs4_res_setattr)             |
@@ -890,6 +1268,14 @@ named!(nfs4_res_compound_command<Nfs4ResponseContent>,
             NFSPROC4_SETCLIENTID_CONFIRM    => call!(nfs4_res_setclientid_confirm) |
             NFSPROC4_PUTROOTFH              => call!(nfs4_res_putrootfh)           |
             NFSPROC4_SEQUENCE               => call!(nfs4_res_sequence)            |
+            NFSPROC4_EXCHANGE_ID            => call!(nfs4_res_exchangeid)          |
+            NFSPROC4_CREATE_SESSION         => call!(nfs4_res_create_session)      |
+            NFSPROC4_DESTROY_SESSION        => call!(nfs4_res_destroy_session)     |
+            NFSPROC4_RECLAIM_COMPLETE       => call!(nfs4_res_reclaim_complete)    |
+            NFSPROC4_COPY                   => call!(nfs4_res_copy)                |
+            NFSPROC4_SEEK                   => call!(nfs4_res_seek)                |
+            NFSPROC4_ALLOCATE               => call!(nfs4_res_allocate)            |
+            NFSPROC4_DEALLOCATE             => call!(nfs4_res_deallocate)          |
             NFSPROC4_RENEW                  => call!(nfs4_res_renew))
     >> (cmd_data)
 ));
@@ -912,3 +1298,109 @@ named!(pub parse_nfs4_response_compound<Nfs4ResponseCompoundRecord>,
                 commands: commands,
             })
 ));
+
+#[cfg(test)]
+mod tests {
+    use crate::nfs::nfs4_records::*;
+
+    #[test]
+    fn test_nfs4_request_create_session() {
+        let buf: &[u8] = &[
+            0x00, 0x00, 0x00, 0x2b, // CREATE_SESSION
+            0x5f, 0x0b, 0x16, 0x4a, 0x00, 0x00, 0x00, 0x01, // client id
+            0x00, 0x00, 0x00, 0x01, // seqid
+            0x00, 0x00, 0x00, 0x03, // flags
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, // fore channel
+            0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00,
+            0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x40,
+            0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, // back channel
+            0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x10,
+            0x00, 0x00, 0x00, 0x00,
+            0x40, 0x00, 0x00, 0x00, // cb program
+            0x00, 0x00, 0x00, 0x01, // sec parms
+            0x00, 0x00, 0x00, 0x01, // AUTH_SYS
+            0x00, 0x00, 0x00, 0x00, // stamp
+            0x00, 0x00, 0x00, 0x02, 0x61, 0x62, 0x00, 0x00, // machine name
+            0x00, 0x00, 0x00, 0x00, // uid
+            0x00, 0x00, 0x00, 0x00, // gid
+            0x00, 0x00, 0x00, 0x00, // gids
+        ];
+        let (rem, cmd) = parse_request_compound_command(buf).unwrap();
+        assert_eq!(rem.len(), 0);
+        assert_eq!(cmd, Nfs4RequestContent::CreateSession(Nfs4RequestCreateSession {
+            client_id: 0x5f0b164a00000001,
+            seqid: 1,
+            flags: 3,
+        }));
+    }
+
+    #[test]
+    fn test_nfs4_request_seek() {
+        let buf: &[u8] = &[
+            0x00, 0x00, 0x00, 0x45, // SEEK
+            0x00, 0x00, 0x00, 0x01, // stateid seqid
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+            0x09, 0x0a, 0x0b, 0x0c,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, // offset
+            0x00, 0x00, 0x00, 0x01, // NFS4_CONTENT_HOLE
+        ];
+        let (rem, cmd) = parse_request_compound_command(buf).unwrap();
+        assert_eq!(rem.len(), 0);
+        match cmd {
+            Nfs4RequestContent::Seek(seek) => {
+                assert_eq!(seek.stateid.seqid, 1);
+                assert_eq!(seek.offset, 0x10000);
+                assert_eq!(seek.what, 1);
+            },
+            _ => { panic!("unexpected {:?}", cmd); }
+        }
+    }
+
+    #[test]
+    fn test_nfs4_response_exchange_id() {
+        let buf: &[u8] = &[
+            0x00, 0x00, 0x00, 0x2a, // EXCHANGE_ID
+            0x00, 0x00, 0x00, 0x00, // status
+            0x5f, 0x0b, 0x16, 0x4a, 0x00, 0x00, 0x00, 0x01, // client id
+            0x00, 0x00, 0x00, 0x01, // seqid
+            0x00, 0x01, 0x00, 0x01, // flags
+            0x00, 0x00, 0x00, 0x00, // SP4_NONE
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // minor id
+            0x00, 0x00, 0x00, 0x04, 0x73, 0x72, 0x76, 0x31, // major id
+            0x00, 0x00, 0x00, 0x03, 0x73, 0x72, 0x76, 0x00, // scope
+            0x00, 0x00, 0x00, 0x00, // impl id
+        ];
+        let (rem, cmd) = nfs4_res_compound_command(buf).unwrap();
+        assert_eq!(rem.len(), 0);
+        assert_eq!(cmd, Nfs4ResponseContent::ExchangeId(0, Some(Nfs4ResponseExchangeId {
+            client_id: 0x5f0b164a00000001,
+            seqid: 1,
+            flags: 0x00010001,
+            server_owner: b"srv1",
+            server_scope: b"srv",
+        })));
+    }
+
+    #[test]
+    fn test_nfs4_response_copy() {
+        let buf: &[u8] = &[
+            0x00, 0x00, 0x00, 0x3c, // COPY
+            0x00, 0x00, 0x00, 0x00, // status
+            0x00, 0x00, 0x00, 0x00, // callback id
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, // count
+            0x00, 0x00, 0x00, 0x02, // FILE_SYNC4
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, // verifier
+            0x00, 0x00, 0x00, 0x01, // consecutive
+            0x00, 0x00, 0x00, 0x01, // synchronous
+        ];
+        let (rem, cmd) = nfs4_res_compound_command(buf).unwrap();
+        assert_eq!(rem.len(), 0);
+        assert_eq!(cmd, Nfs4ResponseContent::Copy(0, Some(Nfs4ResponseCopy {
+            count: 0x2000,
+            asynchronous: false,
+            committed: 2,
+        })));
+    }
+}
@@ -69,6 +69,52 @@ pub fn nfs3_procedure_string(procedure: u32) -> String {
     }.to_string()
 }
 
+/* RFC 1094, section '2.2. Server Procedures' */
+pub const NFSPROC2_NULL:        u32 = 0;
+pub const NFSPROC2_GETATTR:     u32 = 1;
+pub const NFSPROC2_SETATTR:     u32 = 2;
+pub const NFSPROC2_ROOT:        u32 = 3;
+pub const NFSPROC2_LOOKUP:      u32 = 4;
+pub const NFSPROC2_READLINK:    u32 = 5;
+pub const NFSPROC2_READ:        u32 = 6;
+pub const NFSPROC2_WRITECACHE:  u32 = 7;
+pub const NFSPROC2_WRITE:       u32 = 8;
+pub const NFSPROC2_CREATE:      u32 = 9;
+pub const NFSPROC2_REMOVE:      u32 = 10;
+pub const NFSPROC2_RENAME:      u32 = 11;
+pub const NFSPROC2_LINK:        u32 = 12;
+pub const NFSPROC2_SYMLINK:     u32 = 13;
+pub const NFSPROC2_MKDIR:       u32 = 14;
+pub const NFSPROC2_RMDIR:       u32 = 15;
+pub const NFSPROC2_READDIR:     u32 = 16;
+pub const NFSPROC2_STATFS:      u32 = 17;
+
+pub fn nfs2_procedure_string(procedure: u32) -> String {
+    match procedure {
+        NFSPROC2_NULL           => "NULL",
+        NFSPROC2_GETATTR        => "GETATTR",
+        NFSPROC2_SETATTR        => "SETATTR",
+        NFSPROC2_ROOT           => "ROOT",
+        NFSPROC2_LOOKUP         => "LOOKUP",
+        NFSPROC2_READLINK       => "READLINK",
+        NFSPROC2_READ           => "READ",
+        NFSPROC2_WRITECACHE     => "WRITECACHE",
+        NFSPROC2_WRITE          => "WRITE",
+        NFSPROC2_CREATE         => "CREATE",
+        NFSPROC2_REMOVE         => "REMOVE",
+        NFSPROC2_RENAME         => "RENAME",
+        NFSPROC2_LINK           => "LINK",
+        NFSPROC2_SYMLINK        => "SYMLINK",
+        NFSPROC2_MKDIR          => "MKDIR",
+        NFSPROC2_RMDIR          => "RMDIR",
+        NFSPROC2_READDIR        => "READDIR",
+        NFSPROC2_STATFS         => "STATFS",
+        _ => {
+            return (procedure).to_string();
+        }
+    }.to_string()
+}
+
 /* RFC 1813, section '2.6 Defined Error Numbers' */
 pub const NFS3_OK:              u32 = 0;
 pub const NFS3ERR_PERM:         u32 = 1;
@@ -273,10 +319,42 @@ pub const NFSPROC4_SETCLIENTID_CONFIRM: u32 = 36;
 pub const NFSPROC4_VERIFY:              u32 = 37;
 pub const NFSPROC4_WRITE:               u32 = 38;
 pub const NFSPROC4_RELEASE_LOCKOWNER:   u32 = 39;
-pub const NFSPROC4_SEQUENCE:            u32 = 53;
-
 
+/* NFSv4.1 (RFC 5661) */
+pub const NFSPROC4_BACKCHANNEL_CTL:     u32 = 40;
+pub const NFSPROC4_BIND_CONN_TO_SESSION: u32 = 41;
 pub const NFSPROC4_EXCHANGE_ID:         u32 = 42;
+pub const NFSPROC4_CREATE_SESSION:      u32 = 43;
+pub const NFSPROC4_DESTROY_SESSION:     u32 = 44;
+pub const NFSPROC4_FREE_STATEID:        u32 = 45;
+pub const NFSPROC4_GET_DIR_DELEGATION:  u32 = 46;
+pub const NFSPROC4_GETDEVICEINFO:       u32 = 47;
+pub const NFSPROC4_GETDEVICELIST:       u32 = 48;
+pub const NFSPROC4_LAYOUTCOMMIT:        u32 = 49;
+pub const NFSPROC4_LAYOUTGET:           u32 = 50;
+pub const NFSPROC4_LAYOUTRETURN:        u32 = 51;
+pub const NFSPROC4_SECINFO_NO_NAME:     u32 = 52;
+pub const NFSPROC4_SEQUENCE:            u32 = 53;
+pub const NFSPROC4_SET_SSV:             u32 = 54;
+pub const NFSPROC4_TEST_STATEID:        u32 = 55;
+pub const NFSPROC4_WANT_DELEGATION:     u32 = 56;
+pub const NFSPROC4_DESTROY_CLIENTID:    u32 = 57;
+pub const NFSPROC4_RECLAIM_COMPLETE:    u32 = 58;
+
+/* NFSv4.2 (RFC 7862) */
+pub const NFSPROC4_ALLOCATE:            u32 = 59;
+pub const NFSPROC4_COPY:                u32 = 60;
+pub const NFSPROC4_COPY_NOTIFY:         u32 = 61;
+pub const NFSPROC4_DEALLOCATE:          u32 = 62;
+pub const NFSPROC4_IO_ADVISE:           u32 = 63;
+pub const NFSPROC4_LAYOUTERROR:         u32 = 64;
+pub const NFSPROC4_LAYOUTSTATS:         u32 = 65;
+pub const NFSPROC4_OFFLOAD_CANCEL:      u32 = 66;
+pub const NFSPROC4_OFFLOAD_STATUS:      u32 = 67;
+pub const NFSPROC4_READ_PLUS:           u32 = 68;
+pub const NFSPROC4_SEEK:                u32 = 69;
+pub const NFSPROC4_WRITE_SAME:          u32 = 70;
+pub const NFSPROC4_CLONE:               u32 = 71;
 
 pub const NFSPROC4_ILLEGAL:             u32 = 10044;
 
@@ -323,6 +401,38 @@ pub fn nfs4_procedure_string(procedure: u32) -> String {
         NFSPROC4_VERIFY                 => "VERIFY",
         NFSPROC4_WRITE                  => "WRITE",
         NFSPROC4_RELEASE_LOCKOWNER      => "RELEASE_LOCKOWNER",
+        NFSPROC4_BACKCHANNEL_CTL        => "BACKCHANNEL_CTL",
+        NFSPROC4_BIND_CONN_TO_SESSION   => "BIND_CONN_TO_SESSION",
+        NFSPROC4_EXCHANGE_ID            => "EXCHANGE_ID",
+        NFSPROC4_CREATE_SESSION         => "CREATE_SESSION",
+        NFSPROC4_DESTROY_SESSION        => "DESTROY_SESSION",
+        NFSPROC4_FREE_STATEID           => "FREE_STATEID",
+        NFSPROC4_GET_DIR_DELEGATION     => "GET_DIR_DELEGATION",
+        NFSPROC4_GETDEVICEINFO          => "GETDEVICEINFO",
+        NFSPROC4_GETDEVICELIST          => "GETDEVICELIST",
+        NFSPROC4_LAYOUTCOMMIT           => "LAYOUTCOMMIT",
+        NFSPROC4_LAYOUTGET              => "LAYOUTGET",
+        NFSPROC4_LAYOUTRETURN           => "LAYOUTRETURN",
+        NFSPROC4_SECINFO_NO_NAME        => "SECINFO_NO_NAME",
+        NFSPROC4_SEQUENCE               => "SEQUENCE",
+        NFSPROC4_SET_SSV                => "SET_SSV",
+        NFSPROC4_TEST_STATEID           => "TEST_STATEID",
+        NFSPROC4_WANT_DELEGATION        => "WANT_DELEGATION",
+        NFSPROC4_DESTROY_CLIENTID       => "DESTROY_CLIENTID",
+        NFSPROC4_RECLAIM_COMPLETE       => "RECLAIM_COMPLETE",
+        NFSPROC4_ALLOCATE               => "ALLOCATE",
+        NFSPROC4_COPY                   => "COPY",
+        NFSPROC4_COPY_NOTIFY            => "COPY_NOTIFY",
+        NFSPROC4_DEALLOCATE             => "DEALLOCATE",
+        NFSPROC4_IO_ADVISE              => "IO_ADVISE",
+        NFSPROC4_LAYOUTERROR            => "LAYOUTERROR",
+        NFSPROC4_LAYOUTSTATS            => "LAYOUTSTATS",
+        NFSPROC4_OFFLOAD_CANCEL         => "OFFLOAD_CANCEL",
+        NFSPROC4_OFFLOAD_STATUS         => "OFFLOAD_STATUS",
+        NFSPROC4_READ_PLUS              => "READ_PLUS",
+        NFSPROC4_SEEK                   => "SEEK",
+        NFSPROC4_WRITE_SAME             => "WRITE_SAME",
+        NFSPROC4_CLONE                  => "CLONE",
         NFSPROC4_ILLEGAL                => "ILLEGAL",
         _ => {
             return (procedure).to_string();
@@ -331,4 +441,5 @@ pub fn nfs4_procedure_string(procedure: u32) -> String {
 }
 
 pub const NFS4_OK:              u32 = 0;
+pub const NFS4ERR_OFFLOAD_NO_REQS: u32 = 10094;
 
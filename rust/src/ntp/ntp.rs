@@ -20,32 +20,52 @@
 extern crate ntp_parser;
 use self::ntp_parser::*;
 use crate::core;
-use crate::core::{AppProto,Flow,ALPROTO_UNKNOWN,ALPROTO_FAILED};
+use crate::core::{AppProto,Direction,Flow,ALPROTO_UNKNOWN,ALPROTO_FAILED};
 use crate::applayer::{self, *};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
 use std;
 use std::ffi::CString;
 
 use nom;
 
+declare_counters!(ntp_stats, "app_layer.ntp", {
+    client,
+    server,
+    symmetric,
+    broadcast,
+    control,
+    other,
+    malformed,
+    anomalies,
+});
+
 #[derive(AppLayerEvent)]
 pub enum NTPEvent {
     UnsolicitedResponse ,
     MalformedData,
     NotRequest,
     NotResponse,
+    TooManyTransactions,
 }
 
+/// Maximum number of live transactions per flow
+static NTP_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static NTP_GAUGES: StateGauges = StateGauges::new();
+
 pub struct NTPState {
     /// List of transactions for this session
-    transactions: Vec<NTPTransaction>,
+    transactions: TxTable<NTPTransaction>,
 
     /// Events counter
     events: u16,
-
-    /// tx counter for assigning incrementing id's to tx's
-    tx_id: u64,
 }
 
+unsafe impl FfiType for NTPState {}
+
 #[derive(Debug)]
 pub struct NTPTransaction {
     /// The NTP reference ID
@@ -55,7 +75,7 @@ pub struct NTPTransaction {
     id: u64,
 
     /// The detection engine state, if present
-    de_state: Option<*mut core::DetectEngineState>,
+    de_state: DetectStateHolder,
 
     /// The events associated with this transaction
     events: *mut core::AppLayerDecoderEvents,
@@ -63,14 +83,15 @@ pub struct NTPTransaction {
     tx_data: applayer::AppLayerTxData,
 }
 
+unsafe impl FfiType for NTPTransaction {}
+
 
 
 impl NTPState {
     pub fn new() -> NTPState {
         NTPState{
-            transactions: Vec::new(),
+            transactions: TxTable::with_limit(&NTP_TX_LIMIT).with_gauges(&NTP_GAUGES),
             events: 0,
-            tx_id: 0,
         }
     }
 }
@@ -79,10 +100,11 @@ impl NTPState {
     /// Parse an NTP request message
     ///
     /// Returns 0 if successful, or -1 on error
-    fn parse(&mut self, i: &[u8], _direction: u8) -> i32 {
+    fn parse(&mut self, i: &[u8], _direction: Direction) -> i32 {
         match parse_ntp(i) {
             Ok((_,ref msg)) => {
                 // SCLogDebug!("parse_ntp: {:?}",msg);
+                NTPState::count_msg(msg.mode);
                 if msg.mode == NtpMode::SymmetricActive || msg.mode == NtpMode::Client {
                     let mut tx = self.new_tx();
                     // use the reference id as identifier
@@ -93,17 +115,35 @@ impl NTPState {
             },
             Err(nom::Err::Incomplete(_)) => {
                 SCLogDebug!("Insufficient data while parsing NTP data");
+                stats_incr!(ntp_stats::malformed);
                 self.set_event(NTPEvent::MalformedData);
                 -1
             },
             Err(_) => {
                 SCLogDebug!("Error while parsing NTP data");
+                stats_incr!(ntp_stats::malformed);
                 self.set_event(NTPEvent::MalformedData);
                 -1
             },
         }
     }
 
+    fn count_msg(mode: NtpMode) {
+        if mode == NtpMode::Client {
+            stats_incr!(ntp_stats::client);
+        } else if mode == NtpMode::Server {
+            stats_incr!(ntp_stats::server);
+        } else if mode == NtpMode::SymmetricActive || mode == NtpMode::SymmetricPassive {
+            stats_incr!(ntp_stats::symmetric);
+        } else if mode == NtpMode::Broadcast {
+            stats_incr!(ntp_stats::broadcast);
+        } else if mode == NtpMode::NtpControlMessage {
+            stats_incr!(ntp_stats::control);
+        } else {
+            stats_incr!(ntp_stats::other);
+        }
+    }
+
     fn free(&mut self) {
         // All transactions are freed when the `transactions` object is freed.
         // But let's be explicit
@@ -111,25 +151,21 @@ impl NTPState {
     }
 
     fn new_tx(&mut self) -> NTPTransaction {
-        self.tx_id += 1;
-        NTPTransaction::new(self.tx_id)
+        NTPTransaction::new(self.transactions.next_id())
     }
 
     pub fn get_tx_by_id(&mut self, tx_id: u64) -> Option<&NTPTransaction> {
-        self.transactions.iter().find(|&tx| tx.id == tx_id + 1)
+        self.transactions.get(tx_id)
     }
 
     fn free_tx(&mut self, tx_id: u64) {
-        let tx = self.transactions.iter().position(|tx| tx.id == tx_id + 1);
-        debug_assert!(tx != None);
-        if let Some(idx) = tx {
-            let _ = self.transactions.remove(idx);
-        }
+        self.transactions.free(tx_id);
     }
 
     /// Set an event. The event is set on the most recent transaction.
     pub fn set_event(&mut self, event: NTPEvent) {
         if let Some(tx) = self.transactions.last_mut() {
+            stats_incr!(ntp_stats::anomalies);
             let ev = event as u8;
             core::sc_app_layer_decoder_events_set_event_raw(&mut tx.events, ev);
             self.events += 1;
@@ -137,12 +173,39 @@ impl NTPState {
     }
 }
 
+impl txtable::State for NTPState {
+    type Tx = NTPTransaction;
+
+    fn tx_table(&self) -> &TxTable<NTPTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<NTPTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl Transaction for NTPTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = NTPEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
 impl NTPTransaction {
     pub fn new(id: u64) -> NTPTransaction {
         NTPTransaction {
             xid: 0,
             id: id,
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: applayer::AppLayerTxData::new(),
         }
@@ -186,8 +249,8 @@ pub unsafe extern "C" fn rs_ntp_parse_request(_flow: *const core::Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8) -> AppLayerResult {
     let buf = build_slice!(input,input_len as usize);
-    let state = cast_pointer!(state,NTPState);
-    if state.parse(buf, 0) < 0 {
+    let state: &mut NTPState = cast_mut(state);
+    if state.parse(buf, Direction::ToServer) < 0 {
         return AppLayerResult::err();
     }
     AppLayerResult::ok()
@@ -202,8 +265,8 @@ pub unsafe extern "C" fn rs_ntp_parse_response(_flow: *const core::Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8) -> AppLayerResult {
     let buf = build_slice!(input,input_len as usize);
-    let state = cast_pointer!(state,NTPState);
-    if state.parse(buf, 1) < 0 {
+    let state: &mut NTPState = cast_mut(state);
+    if state.parse(buf, Direction::ToClient) < 0 {
         return AppLayerResult::err();
     }
     AppLayerResult::ok()
@@ -214,7 +277,7 @@ pub unsafe extern "C" fn rs_ntp_state_get_tx(state: *mut std::os::raw::c_void,
                                       tx_id: u64)
                                       -> *mut std::os::raw::c_void
 {
-    let state = cast_pointer!(state,NTPState);
+    let state: &mut NTPState = cast_mut(state);
     match state.get_tx_by_id(tx_id) {
         Some(tx) => tx as *const _ as *mut _,
         None     => std::ptr::null_mut(),
@@ -225,15 +288,15 @@ pub unsafe extern "C" fn rs_ntp_state_get_tx(state: *mut std::os::raw::c_void,
 pub unsafe extern "C" fn rs_ntp_state_get_tx_count(state: *mut std::os::raw::c_void)
                                             -> u64
 {
-    let state = cast_pointer!(state,NTPState);
-    state.tx_id
+    let state: &mut NTPState = cast_mut(state);
+    state.transactions.tx_count()
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_ntp_state_tx_free(state: *mut std::os::raw::c_void,
                                        tx_id: u64)
 {
-    let state = cast_pointer!(state,NTPState);
+    let state: &mut NTPState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -245,33 +308,13 @@ pub extern "C" fn rs_ntp_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void,
     1
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_ntp_state_set_tx_detect_state(
-    tx: *mut std::os::raw::c_void,
-    de_state: &mut core::DetectEngineState) -> std::os::raw::c_int
-{
-    let tx = cast_pointer!(tx,NTPTransaction);
-    tx.de_state = Some(de_state);
-    0
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_ntp_state_get_tx_detect_state(
-    tx: *mut std::os::raw::c_void)
-    -> *mut core::DetectEngineState
-{
-    let tx = cast_pointer!(tx,NTPTransaction);
-    match tx.de_state {
-        Some(ds) => ds,
-        None => std::ptr::null_mut(),
-    }
-}
+export_tx_detect_state!(rs_ntp_state_get_tx_detect_state, rs_ntp_state_set_tx_detect_state, NTPTransaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_ntp_state_get_events(tx: *mut std::os::raw::c_void)
                                           -> *mut core::AppLayerDecoderEvents
 {
-    let tx = cast_pointer!(tx, NTPTransaction);
+    let tx: &mut NTPTransaction = cast_mut(tx);
     return tx.events;
 }
 
@@ -304,10 +347,20 @@ pub extern "C" fn ntp_probing_parser(_flow: *const Flow,
 
 export_tx_data_get!(rs_ntp_get_tx_data, NTPTransaction);
 
+
+fn ntp_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    NTP_GAUGES.log(js)?;
+    js.set_uint("events", ntp_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
 const PARSER_NAME : &'static [u8] = b"ntp\0";
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_register_ntp_parser() {
+    ntp_stats::register();
+    NTP_TX_LIMIT.configure("ntp", txtable::DEFAULT_MAX_TX);
+    introspect::register("ntp", ntp_introspect);
     let default_port = CString::new("123").unwrap();
     let parser = RustParser {
         name               : PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
@@ -335,11 +388,12 @@ pub unsafe extern "C" fn rs_register_ntp_parser() {
         localstorage_new   : None,
         localstorage_free  : None,
         get_files          : None,
-        get_tx_iterator    : None,
+        get_tx_iterator    : Some(txtable::state_get_tx_iterator::<NTPState>),
         get_tx_data        : rs_ntp_get_tx_data,
-        apply_tx_config    : None,
+        apply_tx_config    : Some(txtable::state_apply_tx_config::<NTPState>),
         flags              : APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate           : None,
+        state_purge        : None,
     };
 
     let ip_proto_str = CString::new("udp").unwrap();
@@ -373,6 +427,6 @@ mod tests {
         ];
 
         let mut state = NTPState::new();
-        assert_eq!(0, state.parse(REQ, 0));
+        assert_eq!(0, state.parse(REQ, Direction::ToServer));
     }
 }
@@ -0,0 +1,48 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::openvpn::OpenVpnTransaction;
+use super::parser::{opcode_name, Protection};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log(tx: &OpenVpnTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("openvpn")?;
+    if let Some(opcode) = tx.client_opcode {
+        js.open_object("client")?;
+        js.set_string("opcode", opcode_name(opcode))?;
+        js.set_string("session_id", &format!("{:016x}", tx.client_session_id))?;
+        js.close()?;
+        js.set_uint("key_id", tx.key_id as u64)?;
+        js.set_string("protection", tx.protection.to_str())?;
+        if let Protection::TlsAuth(size) = tx.protection {
+            js.set_uint("hmac_size", size as u64)?;
+        }
+    }
+    if let (Some(opcode), Some(session_id)) = (tx.server_opcode, tx.server_session_id) {
+        js.open_object("server")?;
+        js.set_string("opcode", opcode_name(opcode))?;
+        js.set_string("session_id", &format!("{:016x}", session_id))?;
+        js.close()?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_openvpn_to_json(tx: &mut OpenVpnTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
@@ -0,0 +1,556 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP, IPPROTO_UDP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(openvpn_stats, "app_layer.openvpn", {
+    handshakes,
+    unprotected_handshakes,
+    control_packets,
+    acks,
+    soft_resets,
+    data_packets,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum OpenVpnEvent {
+    MalformedData,
+    NoTlsAuth,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static OPENVPN_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static OPENVPN_GAUGES: StateGauges = StateGauges::new();
+
+/// Smallest hard reset: the opcode, the session id and the reliability
+/// layer header.
+const OPENVPN_MIN_RESET_LEN: usize = 14;
+
+/// Largest hard reset the probing parsers accept. The tls-crypt-v2
+/// resets carry the wrapped key of the client.
+const OPENVPN_MAX_RESET_LEN: usize = 2048;
+
+pub static mut ALPROTO_OPENVPN: AppProto = ALPROTO_UNKNOWN;
+
+#[derive(Debug)]
+pub struct OpenVpnTransaction {
+    id: u64,
+    /// The opcode of the hard reset of the client, None for the
+    /// transactions only carrying an event.
+    pub client_opcode: Option<u8>,
+    pub key_id: u8,
+    pub client_session_id: u64,
+    pub protection: Protection,
+    pub server_opcode: Option<u8>,
+    pub server_session_id: Option<u64>,
+    /// Set once the server replied, or data is exchanged.
+    pub complete: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for OpenVpnTransaction {}
+
+impl OpenVpnTransaction {
+    fn new(id: u64) -> OpenVpnTransaction {
+        OpenVpnTransaction {
+            id,
+            client_opcode: None,
+            key_id: 0,
+            client_session_id: 0,
+            protection: Protection::Unknown,
+            server_opcode: None,
+            server_session_id: None,
+            complete: true,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: OpenVpnEvent) {
+        stats_incr!(openvpn_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+}
+
+impl Drop for OpenVpnTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for OpenVpnTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = OpenVpnEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+pub struct OpenVpnState {
+    transactions: TxTable<OpenVpnTransaction>,
+    /// Set once a malformed packet was seen: the event is only raised
+    /// once per flow.
+    malformed: bool,
+}
+
+unsafe impl FfiType for OpenVpnState {}
+
+impl txtable::State for OpenVpnState {
+    type Tx = OpenVpnTransaction;
+
+    fn tx_table(&self) -> &TxTable<OpenVpnTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<OpenVpnTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl OpenVpnState {
+    pub fn new() -> OpenVpnState {
+        OpenVpnState {
+            transactions: TxTable::with_limit(&OPENVPN_TX_LIMIT).with_gauges(&OPENVPN_GAUGES),
+            malformed: false,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    fn new_tx(&mut self) -> OpenVpnTransaction {
+        OpenVpnTransaction::new(self.transactions.next_id())
+    }
+
+    fn set_malformed(&mut self) {
+        if self.malformed {
+            return;
+        }
+        self.malformed = true;
+        let mut tx = self.new_tx();
+        tx.set_event(OpenVpnEvent::MalformedData);
+        self.transactions.push(tx);
+    }
+
+    /// The handshake waiting for the reply of the server.
+    fn pending_handshake(&mut self) -> Option<&mut OpenVpnTransaction> {
+        self.transactions
+            .iter_mut()
+            .rev()
+            .find(|tx| tx.client_opcode.is_some() && !tx.complete)
+    }
+
+    fn handle_client_reset(&mut self, packet: &Packet, session_id: u64) {
+        // the reset is retransmitted until the server acknowledges it
+        let retransmission = self
+            .transactions
+            .iter()
+            .rev()
+            .any(|tx| tx.client_opcode.is_some() && tx.client_session_id == session_id);
+        if retransmission {
+            return;
+        }
+        stats_incr!(openvpn_stats::handshakes);
+        let mut tx = self.new_tx();
+        tx.client_opcode = Some(packet.opcode);
+        tx.key_id = packet.key_id;
+        tx.client_session_id = session_id;
+        tx.protection = client_reset_protection(packet.opcode, packet.payload);
+        tx.complete = false;
+        if tx.protection == Protection::None {
+            stats_incr!(openvpn_stats::unprotected_handshakes);
+            tx.set_event(OpenVpnEvent::NoTlsAuth);
+        }
+        self.transactions.push(tx);
+    }
+
+    fn handle_packet(&mut self, input: &[u8]) -> bool {
+        let packet = match parse_packet(input) {
+            Some(packet) => packet,
+            None => {
+                self.set_malformed();
+                return false;
+            }
+        };
+        match (packet.opcode, packet.session_id) {
+            (opcode, Some(session_id)) if is_client_reset(opcode) => {
+                self.handle_client_reset(&packet, session_id);
+            }
+            (opcode, Some(session_id)) if is_server_reset(opcode) => {
+                if let Some(tx) = self.pending_handshake() {
+                    tx.server_opcode = Some(opcode);
+                    tx.server_session_id = Some(session_id);
+                    tx.complete = true;
+                }
+            }
+            (P_CONTROL_SOFT_RESET_V1, _) => {
+                stats_incr!(openvpn_stats::soft_resets);
+            }
+            (P_ACK_V1, _) => {
+                stats_incr!(openvpn_stats::acks);
+            }
+            (opcode, _) if is_data(opcode) => {
+                stats_incr!(openvpn_stats::data_packets);
+                // the reply of the server was missed
+                if let Some(tx) = self.pending_handshake() {
+                    tx.complete = true;
+                }
+            }
+            _ => {
+                stats_incr!(openvpn_stats::control_packets);
+            }
+        }
+        true
+    }
+
+    fn parse_udp(&mut self, input: &[u8]) -> AppLayerResult {
+        let _ = self.handle_packet(input);
+        AppLayerResult::ok()
+    }
+
+    /// Parse the length prefixed packets of a stream.
+    fn parse_tcp(&mut self, input: &[u8]) -> AppLayerResult {
+        let mut start = input;
+        while !start.is_empty() {
+            let consumed = input.len() - start.len();
+            let len = match tcp_packet_len(start) {
+                Some(len) => len,
+                None => return AppLayerResult::incomplete_from(input.len(), consumed, 2),
+            };
+            if start.len() < len + 2 {
+                return AppLayerResult::incomplete_from(input.len(), consumed, len + 2);
+            }
+            if !self.handle_packet(&start[2..len + 2]) {
+                return AppLayerResult::err();
+            }
+            start = &start[len + 2..];
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for a hard reset, of the client or of the server.
+fn openvpn_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    if input.len() < OPENVPN_MIN_RESET_LEN || input.len() > OPENVPN_MAX_RESET_LEN {
+        return ProbeResult::Failed;
+    }
+    let packet = match parse_packet(input) {
+        Some(packet) => packet,
+        None => return ProbeResult::Failed,
+    };
+    if packet.key_id != 0 {
+        return ProbeResult::Failed;
+    }
+    let actual = if is_client_reset(packet.opcode) {
+        Direction::ToServer
+    } else if is_server_reset(packet.opcode) {
+        Direction::ToClient
+    } else {
+        return ProbeResult::Failed;
+    };
+    ProbeResult::with_direction(unsafe { ALPROTO_OPENVPN }, direction, actual)
+}
+
+/// Probe for a hard reset behind its length prefix.
+fn openvpn_probe_tcp(input: &[u8], direction: Direction) -> ProbeResult {
+    let len = match tcp_packet_len(input) {
+        Some(len) => len,
+        None => return ProbeResult::Unknown,
+    };
+    if input.len() < len + 2 {
+        if input.len() < OPENVPN_MIN_RESET_LEN + 2 {
+            return ProbeResult::Unknown;
+        }
+        if len > OPENVPN_MAX_RESET_LEN {
+            return ProbeResult::Failed;
+        }
+        return openvpn_probe(&input[2..OPENVPN_MIN_RESET_LEN + 2], direction);
+    }
+    openvpn_probe(&input[2..len + 2], direction)
+}
+
+export_probe!(rs_openvpn_probing_parser, openvpn_probe);
+export_probe!(rs_openvpn_probing_parser_tcp, openvpn_probe_tcp);
+
+#[no_mangle]
+pub extern "C" fn rs_openvpn_state_new(_orig_state: *mut std::os::raw::c_void,
+                                       _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = OpenVpnState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut OpenVpnState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut OpenVpnState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_parse(_flow: *const Flow,
+                                          state: *mut std::os::raw::c_void,
+                                          _pstate: *mut std::os::raw::c_void,
+                                          input: *const u8,
+                                          input_len: u32,
+                                          _data: *const std::os::raw::c_void,
+                                          _flags: u8) -> AppLayerResult {
+    let state: &mut OpenVpnState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse_udp(buf)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_parse_request_tcp(_flow: *const Flow,
+                                                      state: *mut std::os::raw::c_void,
+                                                      pstate: *mut std::os::raw::c_void,
+                                                      input: *const u8,
+                                                      input_len: u32,
+                                                      _data: *const std::os::raw::c_void,
+                                                      _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut OpenVpnState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse_tcp(buf)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_parse_response_tcp(_flow: *const Flow,
+                                                       state: *mut std::os::raw::c_void,
+                                                       pstate: *mut std::os::raw::c_void,
+                                                       input: *const u8,
+                                                       input_len: u32,
+                                                       _data: *const std::os::raw::c_void,
+                                                       _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut OpenVpnState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse_tcp(buf)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_state_get_tx(state: *mut std::os::raw::c_void,
+                                                 tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut OpenVpnState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut OpenVpnState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// A handshake is complete once the server replied to the reset of the
+/// client.
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                            _direction: u8) -> std::os::raw::c_int {
+    let tx: &mut OpenVpnTransaction = cast_mut(tx);
+    tx.complete as std::os::raw::c_int
+}
+
+export_tx_detect_state!(rs_openvpn_state_get_tx_detect_state,
+                        rs_openvpn_state_set_tx_detect_state, OpenVpnTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_state_get_events(tx: *mut std::os::raw::c_void)
+                                                     -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut OpenVpnTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_openvpn_get_tx_data, OpenVpnTransaction);
+
+fn openvpn_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    OPENVPN_GAUGES.log(js)?;
+    js.set_uint("events", openvpn_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+const PARSER_NAME: &[u8] = b"openvpn\0";
+
+fn parser(ipproto: i32) -> RustParser {
+    let tcp = ipproto == IPPROTO_TCP;
+    RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: std::ptr::null(),
+        ipproto,
+        probe_ts: Some(if tcp { rs_openvpn_probing_parser_tcp } else { rs_openvpn_probing_parser }),
+        probe_tc: Some(if tcp { rs_openvpn_probing_parser_tcp } else { rs_openvpn_probing_parser }),
+        min_depth: 0,
+        max_depth: (OPENVPN_MAX_RESET_LEN + 2) as u16,
+        state_new: rs_openvpn_state_new,
+        state_free: rs_openvpn_state_free,
+        tx_free: rs_openvpn_state_tx_free,
+        parse_ts: if tcp { rs_openvpn_parse_request_tcp } else { rs_openvpn_parse },
+        parse_tc: if tcp { rs_openvpn_parse_response_tcp } else { rs_openvpn_parse },
+        get_tx_count: rs_openvpn_state_get_tx_count,
+        get_tx: rs_openvpn_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_openvpn_tx_get_alstate_progress,
+        get_de_state: rs_openvpn_state_get_tx_detect_state,
+        set_de_state: rs_openvpn_state_set_tx_detect_state,
+        get_events: Some(rs_openvpn_state_get_events),
+        get_eventinfo: Some(OpenVpnEvent::get_event_info),
+        get_eventinfo_byid: Some(OpenVpnEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<OpenVpnState>),
+        get_tx_data: rs_openvpn_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<OpenVpnState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    }
+}
+
+unsafe fn register(ipproto: i32, ip_proto_str: &str) {
+    let default_port = CString::new("1194").unwrap();
+    let mut parser = parser(ipproto);
+    parser.default_port = default_port.as_ptr();
+
+    let ip_proto_str = CString::new(ip_proto_str).unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_OPENVPN = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust openvpn parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for OpenVPN.");
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_udp_register_parser() {
+    openvpn_stats::register();
+    OPENVPN_TX_LIMIT.configure("openvpn", txtable::DEFAULT_MAX_TX);
+    introspect::register("openvpn", openvpn_introspect);
+    register(IPPROTO_UDP, "udp");
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_openvpn_tcp_register_parser() {
+    register(IPPROTO_TCP, "tcp");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_openvpn_state_new,
+            state_free: rs_openvpn_state_free,
+            parse_ts: rs_openvpn_parse_request_tcp,
+            parse_tc: rs_openvpn_parse_response_tcp,
+            get_tx_count: rs_openvpn_state_get_tx_count,
+            get_tx: rs_openvpn_state_get_tx,
+            tx_free: rs_openvpn_state_tx_free,
+        })
+    }
+
+    /// A hard reset of the client without tls-auth, over TCP.
+    const CLIENT_RESET: &[u8] = b"\x00\x0e\x38\x11\x22\x33\x44\x55\x66\x77\x88\
+        \x00\x00\x00\x00\x00";
+
+    /// The hard reset of the server, acknowledging the one of the client.
+    const SERVER_RESET: &[u8] = b"\x00\x1a\x40\xa1\xa2\xa3\xa4\xa5\xa6\xa7\xa8\
+        \x01\x00\x00\x00\x00\x11\x22\x33\x44\x55\x66\x77\x88\x00\x00\x00\x00";
+
+    #[test]
+    fn test_openvpn_tcp_handshake() {
+        let mut h = harness();
+        assert!(h.feed_segments(Direction::ToServer, CLIENT_RESET, 5));
+        // the retransmission is not a new handshake
+        assert!(h.feed(Direction::ToServer, CLIENT_RESET));
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<OpenVpnTransaction>(0).unwrap();
+        assert_eq!(tx.client_opcode, Some(P_CONTROL_HARD_RESET_CLIENT_V2));
+        assert_eq!(tx.client_session_id, 0x1122334455667788);
+        assert_eq!(tx.protection, Protection::None);
+        assert!(!tx.complete);
+
+        let mut stream = SERVER_RESET.to_vec();
+        // a P_CONTROL_V1 split with the next segment
+        stream.extend_from_slice(b"\x00\x18\x20\xa1\xa2\xa3\xa4");
+        assert!(h.feed(Direction::ToClient, &stream));
+        assert!(h.feed(Direction::ToClient,
+                       b"\xa5\xa6\xa7\xa8\x00\x00\x00\x00\x01\x16\x03\x01\x00\x05\x02\x00\x00\x01\x00"));
+        let tx = h.get_tx::<OpenVpnTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.server_opcode, Some(P_CONTROL_HARD_RESET_SERVER_V2));
+        assert_eq!(tx.server_session_id, Some(0xa1a2a3a4a5a6a7a8));
+        assert_eq!(h.pending(Direction::ToClient), 0);
+
+        assert!(!h.feed(Direction::ToServer, b"\x00\x03\xf8\x00\x00"));
+        assert_eq!(h.tx_count(), 2);
+    }
+
+    #[test]
+    fn test_openvpn_probe() {
+        unsafe {
+            ALPROTO_OPENVPN = 42;
+        }
+        assert_eq!(openvpn_probe_tcp(CLIENT_RESET, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(openvpn_probe_tcp(SERVER_RESET, Direction::ToServer), ProbeResult::Flipped(42));
+        assert_eq!(openvpn_probe(&CLIENT_RESET[2..], Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(openvpn_probe_tcp(&CLIENT_RESET[..8], Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(openvpn_probe_tcp(b"GET / HTTP/1.1\r\n\r\n", Direction::ToServer), ProbeResult::Failed);
+        // a P_DATA_V2 is not the start of a flow
+        assert_eq!(openvpn_probe(b"\x48\x00\x00\x01\x00\x00\x00\x01\x02\x03\x04\x05\x06\x07",
+                                 Direction::ToServer), ProbeResult::Failed);
+    }
+}
@@ -0,0 +1,273 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! OpenVPN packet decoding. The control channel carries a TLS session
+//! over a reliability layer of acknowledged, numbered packets; the data
+//! channel is encrypted with the keys negotiated over it.
+//!
+//! A packet starts with its opcode, in the 5 high bits, and its key id.
+//! Over TCP each packet is prefixed with its length.
+
+pub const P_CONTROL_HARD_RESET_CLIENT_V1: u8 = 1;
+pub const P_CONTROL_HARD_RESET_SERVER_V1: u8 = 2;
+pub const P_CONTROL_SOFT_RESET_V1: u8 = 3;
+pub const P_CONTROL_V1: u8 = 4;
+pub const P_ACK_V1: u8 = 5;
+pub const P_DATA_V1: u8 = 6;
+pub const P_CONTROL_HARD_RESET_CLIENT_V2: u8 = 7;
+pub const P_CONTROL_HARD_RESET_SERVER_V2: u8 = 8;
+pub const P_DATA_V2: u8 = 9;
+pub const P_CONTROL_HARD_RESET_CLIENT_V3: u8 = 10;
+pub const P_CONTROL_WKC_V1: u8 = 11;
+
+/// Length of a session id.
+const SESSION_ID_LEN: usize = 8;
+
+/// The HMAC sizes of the digests usable with tls-auth, SHA1 and SHA256
+/// first.
+const TLS_AUTH_HMAC_SIZES: &[usize] = &[20, 32, 64, 16, 28, 48];
+
+/// HMAC size of tls-crypt, always SHA256.
+const TLS_CRYPT_HMAC_SIZE: usize = 32;
+
+/// The replay protection packet ids of the first packets are small,
+/// retransmissions of the reset included.
+const MAX_FIRST_PACKET_ID: u32 = 16;
+
+pub fn opcode_name(opcode: u8) -> &'static str {
+    match opcode {
+        P_CONTROL_HARD_RESET_CLIENT_V1 => "P_CONTROL_HARD_RESET_CLIENT_V1",
+        P_CONTROL_HARD_RESET_SERVER_V1 => "P_CONTROL_HARD_RESET_SERVER_V1",
+        P_CONTROL_SOFT_RESET_V1 => "P_CONTROL_SOFT_RESET_V1",
+        P_CONTROL_V1 => "P_CONTROL_V1",
+        P_ACK_V1 => "P_ACK_V1",
+        P_DATA_V1 => "P_DATA_V1",
+        P_CONTROL_HARD_RESET_CLIENT_V2 => "P_CONTROL_HARD_RESET_CLIENT_V2",
+        P_CONTROL_HARD_RESET_SERVER_V2 => "P_CONTROL_HARD_RESET_SERVER_V2",
+        P_DATA_V2 => "P_DATA_V2",
+        P_CONTROL_HARD_RESET_CLIENT_V3 => "P_CONTROL_HARD_RESET_CLIENT_V3",
+        P_CONTROL_WKC_V1 => "P_CONTROL_WKC_V1",
+        _ => "unknown",
+    }
+}
+
+pub fn is_client_reset(opcode: u8) -> bool {
+    match opcode {
+        P_CONTROL_HARD_RESET_CLIENT_V1
+        | P_CONTROL_HARD_RESET_CLIENT_V2
+        | P_CONTROL_HARD_RESET_CLIENT_V3 => true,
+        _ => false,
+    }
+}
+
+pub fn is_server_reset(opcode: u8) -> bool {
+    opcode == P_CONTROL_HARD_RESET_SERVER_V1 || opcode == P_CONTROL_HARD_RESET_SERVER_V2
+}
+
+pub fn is_data(opcode: u8) -> bool {
+    opcode == P_DATA_V1 || opcode == P_DATA_V2
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Packet<'a> {
+    pub opcode: u8,
+    pub key_id: u8,
+    /// The session id of the sender, for the control channel packets.
+    pub session_id: Option<u64>,
+    /// The rest of the packet, after the session id of a control
+    /// channel packet or after the opcode of a data channel one.
+    pub payload: &'a [u8],
+}
+
+fn be_u32(input: &[u8]) -> u32 {
+    (input[0] as u32) << 24 | (input[1] as u32) << 16 | (input[2] as u32) << 8 | input[3] as u32
+}
+
+fn be_u64(input: &[u8]) -> u64 {
+    (be_u32(input) as u64) << 32 | be_u32(&input[4..]) as u64
+}
+
+/// Parse a packet, a datagram or a packet without its length prefix.
+pub fn parse_packet(input: &[u8]) -> Option<Packet> {
+    let (&first, rest) = input.split_first()?;
+    let opcode = first >> 3;
+    let key_id = first & 0x07;
+    match opcode {
+        P_DATA_V1 | P_DATA_V2 => Some(Packet {
+            opcode,
+            key_id,
+            session_id: None,
+            payload: rest,
+        }),
+        P_CONTROL_HARD_RESET_CLIENT_V1..=P_ACK_V1
+        | P_CONTROL_HARD_RESET_CLIENT_V2
+        | P_CONTROL_HARD_RESET_SERVER_V2
+        | P_CONTROL_HARD_RESET_CLIENT_V3
+        | P_CONTROL_WKC_V1 => {
+            if rest.len() < SESSION_ID_LEN {
+                return None;
+            }
+            Some(Packet {
+                opcode,
+                key_id,
+                session_id: Some(be_u64(rest)),
+                payload: &rest[SESSION_ID_LEN..],
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The length of the TCP packet at the start of `input`, its length
+/// prefix excluded.
+pub fn tcp_packet_len(input: &[u8]) -> Option<usize> {
+    if input.len() < 2 {
+        return None;
+    }
+    Some((input[0] as usize) << 8 | input[1] as usize)
+}
+
+/// How the control channel is protected.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protection {
+    /// No tls-auth nor tls-crypt key: anyone can start a TLS handshake
+    /// with the server.
+    None,
+    /// tls-auth, with the HMAC size of its digest.
+    TlsAuth(usize),
+    /// tls-crypt.
+    TlsCrypt,
+    /// tls-crypt-v2, with a per client key.
+    TlsCryptV2,
+    /// The layout of the packet matches none of the above.
+    Unknown,
+}
+
+impl Protection {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Protection::None => "none",
+            Protection::TlsAuth(_) => "tls-auth",
+            Protection::TlsCrypt => "tls-crypt",
+            Protection::TlsCryptV2 => "tls-crypt-v2",
+            Protection::Unknown => "unknown",
+        }
+    }
+}
+
+/// The reliability layer header of a reset, an empty acknowledgement
+/// array and the message packet id 0.
+fn is_first_reliable(input: &[u8]) -> bool {
+    input.len() >= 5 && input[0] == 0 && be_u32(&input[1..]) == 0
+}
+
+/// A replay protection packet id and timestamp.
+fn is_first_replay(input: &[u8]) -> bool {
+    if input.len() < 8 {
+        return false;
+    }
+    let packet_id = be_u32(input);
+    packet_id != 0 && packet_id <= MAX_FIRST_PACKET_ID && be_u32(&input[4..]) != 0
+}
+
+/// Find the protection of the control channel from the layout of the
+/// hard reset of the client, `payload` following its session id.
+///
+/// Without protection the reliability layer header follows the session
+/// id. With tls-auth a HMAC and a replay protection header come first.
+/// With tls-crypt the replay protection header follows the session id,
+/// then the HMAC, and the rest is encrypted.
+pub fn client_reset_protection(opcode: u8, payload: &[u8]) -> Protection {
+    if opcode == P_CONTROL_HARD_RESET_CLIENT_V3 {
+        return Protection::TlsCryptV2;
+    }
+    if is_first_reliable(payload) {
+        return Protection::None;
+    }
+    for &size in TLS_AUTH_HMAC_SIZES {
+        if payload.len() > size
+            && is_first_replay(&payload[size..])
+            && is_first_reliable(&payload[size + 8..])
+        {
+            return Protection::TlsAuth(size);
+        }
+    }
+    if payload.len() > 8 + TLS_CRYPT_HMAC_SIZE && is_first_replay(payload) {
+        return Protection::TlsCrypt;
+    }
+    Protection::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION_ID: &[u8] = b"\x11\x22\x33\x44\x55\x66\x77\x88";
+
+    fn reset(opcode: u8, body: &[u8]) -> Vec<u8> {
+        let mut packet = vec![opcode << 3];
+        packet.extend_from_slice(SESSION_ID);
+        packet.extend_from_slice(body);
+        packet
+    }
+
+    #[test]
+    fn test_parse_packet() {
+        let packet = reset(P_CONTROL_HARD_RESET_CLIENT_V2, b"\x00\x00\x00\x00\x00");
+        let packet = parse_packet(&packet).unwrap();
+        assert_eq!(packet.opcode, P_CONTROL_HARD_RESET_CLIENT_V2);
+        assert_eq!(packet.key_id, 0);
+        assert_eq!(packet.session_id, Some(0x1122334455667788));
+        assert_eq!(packet.payload.len(), 5);
+
+        let packet = parse_packet(b"\x4a\x00\x00\x01\xde\xad").unwrap();
+        assert_eq!(packet.opcode, P_DATA_V2);
+        assert_eq!(packet.key_id, 2);
+        assert_eq!(packet.session_id, None);
+
+        assert_eq!(parse_packet(b""), None);
+        assert_eq!(parse_packet(b"\x38\x11\x22"), None);
+        assert_eq!(parse_packet(b"\xf8\x11\x22\x33\x44\x55\x66\x77\x88"), None);
+        assert_eq!(tcp_packet_len(b"\x00\x0e\x38"), Some(14));
+        assert_eq!(tcp_packet_len(b"\x00"), None);
+    }
+
+    #[test]
+    fn test_client_reset_protection() {
+        let v2 = P_CONTROL_HARD_RESET_CLIENT_V2;
+        // with the early negotiation TLV of OpenVPN 2.6
+        let packet = reset(v2, b"\x00\x00\x00\x00\x00\x00\x01\x00\x02\x00\x01");
+        let packet = parse_packet(&packet).unwrap();
+        assert_eq!(client_reset_protection(v2, packet.payload), Protection::None);
+
+        let mut body = vec![0xa5; 20];
+        body.extend_from_slice(b"\x00\x00\x00\x01\x60\x70\x80\x90\x00\x00\x00\x00\x00");
+        assert_eq!(client_reset_protection(v2, &body), Protection::TlsAuth(20));
+
+        let mut body = vec![0x5a; 32];
+        body.extend_from_slice(b"\x00\x00\x00\x02\x60\x70\x80\x90\x00\x00\x00\x00\x00");
+        assert_eq!(client_reset_protection(v2, &body), Protection::TlsAuth(32));
+
+        let mut body = b"\x00\x00\x00\x01\x60\x70\x80\x90".to_vec();
+        body.extend_from_slice(&[0xa5; 37]);
+        assert_eq!(client_reset_protection(v2, &body), Protection::TlsCrypt);
+
+        let v3 = P_CONTROL_HARD_RESET_CLIENT_V3;
+        assert_eq!(client_reset_protection(v3, &body), Protection::TlsCryptV2);
+        assert_eq!(client_reset_protection(v2, &[0xa5; 40]), Protection::Unknown);
+    }
+}
@@ -0,0 +1,194 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Parsing combinators shared by the protocol parsers.
+//!
+//! All the parsers are streaming: they return `Err::Incomplete` when the
+//! input is too short, so they can be used with data that may be
+//! truncated or segmented.
+
+use crate::common::to_hex;
+use nom::bytes::streaming::{take, take_until};
+use nom::error::ErrorKind;
+use nom::multi::length_data;
+use nom::number::streaming::{be_u16, be_u8};
+use nom::{Err, IResult, Needed};
+
+/// Maximum number of bytes of a variable length integer, so that its
+/// value fits in 28 bits.
+const VARINT_MAX_LEN: usize = 4;
+
+/// Parse an MQTT style variable length integer: 7 bits per byte, least
+/// significant group first, with the high bit set on all the bytes but
+/// the last. At most 4 bytes are accepted.
+pub fn varint_u32(i: &[u8]) -> IResult<&[u8], u32> {
+    let mut value = 0u32;
+    for (n, b) in i.iter().take(VARINT_MAX_LEN).enumerate() {
+        value |= ((b & 0x7f) as u32) << (7 * n);
+        if b & 0x80 == 0 {
+            return Ok((&i[n + 1..], value));
+        }
+    }
+    if i.len() >= VARINT_MAX_LEN {
+        return Err(Err::Error((&i[VARINT_MAX_LEN - 1..], ErrorKind::Verify)));
+    }
+    Err(Err::Incomplete(Needed::Size(1)))
+}
+
+/// Parse bytes prefixed with their length as an u8.
+pub fn length_data_be_u8(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    length_data(be_u8)(i)
+}
+
+/// Parse bytes prefixed with their length as a big endian u16.
+pub fn length_data_be_u16(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    length_data(be_u16)(i)
+}
+
+/// Parse bytes prefixed with their length as a big endian u16, failing
+/// if the length is more than `max`.
+pub fn length_data_be_u16_max(max: usize) -> impl Fn(&[u8]) -> IResult<&[u8], &[u8]> {
+    move |i: &[u8]| {
+        let (rem, len) = be_u16(i)?;
+        if len as usize > max {
+            return Err(Err::Error((i, ErrorKind::TooLarge)));
+        }
+        take(len)(rem)
+    }
+}
+
+/// Parse a string prefixed with its length as a big endian u16. Invalid
+/// UTF-8 sequences are replaced.
+pub fn length_string_be_u16(i: &[u8]) -> IResult<&[u8], String> {
+    let (rem, data) = length_data_be_u16(i)?;
+    Ok((rem, String::from_utf8_lossy(data).to_string()))
+}
+
+/// Parse an identifier of `size` bytes, returned as a hex string.
+pub fn hex_id(size: usize) -> impl Fn(&[u8]) -> IResult<&[u8], String> {
+    move |i: &[u8]| {
+        let (rem, id) = take(size)(i)?;
+        Ok((rem, to_hex(id)))
+    }
+}
+
+/// Parse a null terminated string, returning it without the terminator.
+pub fn null_terminated(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (rem, data) = take_until(&b"\0"[..])(i)?;
+    Ok((&rem[1..], data))
+}
+
+/// Parse a field of `size` bytes padded with nulls, returning the bytes
+/// before the first null.
+pub fn null_padded(size: usize) -> impl Fn(&[u8]) -> IResult<&[u8], &[u8]> {
+    move |i: &[u8]| {
+        let (rem, field) = take(size)(i)?;
+        let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+        Ok((rem, &field[..len]))
+    }
+}
+
+/// Run all the parsers on `buf`, checking that they never return more
+/// than their input. Used by the `parsers_util` fuzz target.
+#[cfg(any(test, feature = "fuzz"))]
+pub fn fuzz_parsers(buf: &[u8]) {
+    if let Ok((rem, _)) = varint_u32(buf) {
+        assert!(rem.len() < buf.len());
+    }
+    if let Ok((rem, data)) = length_data_be_u16(buf) {
+        assert_eq!(rem.len() + data.len() + 2, buf.len());
+    }
+    if let Ok((rem, data)) = length_data_be_u16_max(8)(buf) {
+        assert!(data.len() <= 8);
+        assert_eq!(rem.len() + data.len() + 2, buf.len());
+    }
+    if let Ok((rem, data)) = null_terminated(buf) {
+        assert_eq!(rem.len() + data.len() + 1, buf.len());
+        assert!(!data.contains(&0));
+    }
+    if let Ok((rem, id)) = hex_id(4)(buf) {
+        assert_eq!(id.len(), 8);
+        assert_eq!(rem.len() + 4, buf.len());
+    }
+    let _ = length_string_be_u16(buf);
+    let _ = null_padded(6)(buf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_u32() {
+        assert_eq!(varint_u32(&[0x00]), Ok((&[][..], 0)));
+        assert_eq!(varint_u32(&[0x7f, 0x01]), Ok((&[0x01][..], 127)));
+        assert_eq!(varint_u32(&[0x80, 0x01]), Ok((&[][..], 128)));
+        assert_eq!(varint_u32(&[0xff, 0xff, 0xff, 0x7f]), Ok((&[][..], 268435455)));
+        assert_eq!(varint_u32(&[0xff, 0xff]), Err(Err::Incomplete(Needed::Size(1))));
+        match varint_u32(&[0xff, 0xff, 0xff, 0x80]) {
+            Err(Err::Error((_, kind))) => assert_eq!(kind, ErrorKind::Verify),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_length_data() {
+        let buf = [0x00, 0x03, b'a', b'b', b'c', b'd'];
+        assert_eq!(length_data_be_u16(&buf), Ok((&buf[5..], &buf[2..5])));
+        assert_eq!(length_string_be_u16(&buf), Ok((&buf[5..], "abc".to_string())));
+        assert!(length_data_be_u16_max(3)(&buf).is_ok());
+        assert!(length_data_be_u16_max(2)(&buf).is_err());
+        assert!(length_data_be_u16(&buf[..4]).unwrap_err().is_incomplete());
+        assert_eq!(length_data_be_u8(&[0x01, 0xaa]), Ok((&[][..], &[0xaa][..])));
+    }
+
+    #[test]
+    fn test_hex_id() {
+        let buf = [0x01, 0xab, 0xff];
+        assert_eq!(hex_id(2)(&buf), Ok((&buf[2..], "01ab".to_string())));
+        assert!(hex_id(4)(&buf).is_err());
+    }
+
+    #[test]
+    fn test_null_terminated() {
+        assert_eq!(null_terminated(b"abc\0def"), Ok((&b"def"[..], &b"abc"[..])));
+        assert!(null_terminated(b"abc").is_err());
+        assert_eq!(null_padded(4)(b"ab\0\0cd"), Ok((&b"cd"[..], &b"ab"[..])));
+        assert_eq!(null_padded(2)(b"abcd"), Ok((&b"cd"[..], &b"ab"[..])));
+    }
+
+    /// Run all the parsers on pseudo random inputs, with the checks of
+    /// the `parsers_util` fuzz target.
+    #[test]
+    fn test_parsers_random_input() {
+        let mut seed = 0x2545_f491u32;
+        let mut buf = Vec::new();
+        for _ in 0..2000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let len = (seed % 24) as usize;
+            buf.clear();
+            for n in 0..len {
+                // small values every third byte, to get plausible lengths
+                let mask = if n % 3 == 0 { 0x03 } else { 0xff };
+                buf.push(seed.rotate_left(n as u32 * 3) as u8 & mask);
+            }
+            fuzz_parsers(&buf);
+        }
+    }
+}
@@ -0,0 +1,85 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::{Command, ProxyHeader};
+use super::proxy::PROXY_HEADERS;
+use crate::core::Flow;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log(header: &ProxyHeader, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("proxy")?;
+    js.set_uint("version", header.version as u64)?;
+    js.set_string("command", match header.command {
+        Command::Local => "local",
+        Command::Proxy => "proxy",
+    })?;
+    js.set_string("transport", header.transport.to_str())?;
+    if let Some((ip, port)) = &header.source {
+        js.set_string("src_ip", &ip.to_string())?;
+        js.set_uint("src_port", *port as u64)?;
+    }
+    if let Some((ip, port)) = &header.destination {
+        js.set_string("dest_ip", &ip.to_string())?;
+        js.set_uint("dest_port", *port as u64)?;
+    }
+    if let Some(alpn) = &header.alpn {
+        js.set_string("alpn", alpn)?;
+    }
+    if let Some(authority) = &header.authority {
+        js.set_string("authority", authority)?;
+    }
+    if let Some(unique_id) = &header.unique_id {
+        js.set_hex("unique_id", unique_id)?;
+    }
+    if let Some(ssl) = &header.ssl {
+        js.open_object("ssl")?;
+        js.set_bool("client_ssl", ssl.client_ssl)?;
+        js.set_bool("client_cert", ssl.client_cert)?;
+        if let Some(version) = &ssl.version {
+            js.set_string("version", version)?;
+        }
+        if let Some(cn) = &ssl.cn {
+            js.set_string("cn", cn)?;
+        }
+        if let Some(cipher) = &ssl.cipher {
+            js.set_string("cipher", cipher)?;
+        }
+        js.close()?;
+    }
+    if let Some(netns) = &header.netns {
+        js.set_string("netns", netns)?;
+    }
+    if let Some(vpce_id) = &header.aws_vpce_id {
+        js.set_string("aws_vpce_id", vpce_id)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+/// Add the PROXY header of the flow, if it had one, to a record of the
+/// flow. Returns false only if logging it failed.
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_flow_to_json(flow: *const Flow, js: &mut JsonBuilder) -> bool {
+    if flow.is_null() {
+        return true;
+    }
+    match PROXY_HEADERS.get(&*flow) {
+        Some(header) => log(header, js).is_ok(),
+        None => true,
+    }
+}
+
@@ -0,0 +1,27 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! PROXY protocol application layer, parser and logger module.
+//!
+//! The header a load balancer sends ahead of the stream of the client
+//! carries the addresses of the real client. Once parsed, it is kept on
+//! the flow, added to every EVE record of the flow, and the rest of the
+//! stream goes through the protocol detection again.
+
+pub mod logger;
+pub mod parser;
+pub mod proxy;
@@ -0,0 +1,409 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! PROXY protocol header decoding, version 1 (a text line) and 2
+//! (binary, with TLVs), as specified by HAProxy.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Start of a version 1 header.
+pub const V1_PREFIX: &[u8] = b"PROXY ";
+
+/// Largest version 1 header, its CRLF included.
+pub const V1_MAX_LEN: usize = 107;
+
+/// Signature starting a version 2 header.
+pub const V2_SIGNATURE: &[u8] = b"\r\n\r\n\x00\r\nQUIT\n";
+
+/// Length of the fixed part of a version 2 header.
+pub const V2_HEADER_LEN: usize = 16;
+
+const PP2_TYPE_ALPN: u8 = 0x01;
+const PP2_TYPE_AUTHORITY: u8 = 0x02;
+const PP2_TYPE_UNIQUE_ID: u8 = 0x05;
+const PP2_TYPE_SSL: u8 = 0x20;
+const PP2_SUBTYPE_SSL_VERSION: u8 = 0x21;
+const PP2_SUBTYPE_SSL_CN: u8 = 0x22;
+const PP2_SUBTYPE_SSL_CIPHER: u8 = 0x23;
+const PP2_TYPE_NETNS: u8 = 0x30;
+/// Endpoint id of the AWS VPC endpoint services.
+const PP2_TYPE_AWS: u8 = 0xea;
+const PP2_SUBTYPE_AWS_VPCE_ID: u8 = 0x01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Sent by the proxy for its own connections, such as health
+    /// checks: the addresses of the flow are the real ones.
+    Local,
+    Proxy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transport {
+    Unspec,
+    Tcp,
+    Udp,
+    Unix,
+}
+
+impl Transport {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Transport::Unspec => "unspec",
+            Transport::Tcp => "tcp",
+            Transport::Udp => "udp",
+            Transport::Unix => "unix",
+        }
+    }
+}
+
+/// The TLS details of the connection of the client to the proxy.
+#[derive(Debug, Default, PartialEq)]
+pub struct SslInfo {
+    /// Set when the client connected over TLS.
+    pub client_ssl: bool,
+    /// Set when the client presented a certificate.
+    pub client_cert: bool,
+    pub version: Option<String>,
+    pub cn: Option<String>,
+    pub cipher: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProxyHeader {
+    pub version: u8,
+    pub command: Command,
+    pub transport: Transport,
+    /// The address and port of the client, and the ones it connected
+    /// to, for IPv4 and IPv6.
+    pub source: Option<(IpAddr, u16)>,
+    pub destination: Option<(IpAddr, u16)>,
+    pub alpn: Option<String>,
+    pub authority: Option<String>,
+    pub unique_id: Option<Vec<u8>>,
+    pub ssl: Option<SslInfo>,
+    pub netns: Option<String>,
+    pub aws_vpce_id: Option<String>,
+}
+
+impl ProxyHeader {
+    fn new(version: u8, command: Command, transport: Transport) -> ProxyHeader {
+        ProxyHeader {
+            version,
+            command,
+            transport,
+            source: None,
+            destination: None,
+            alpn: None,
+            authority: None,
+            unique_id: None,
+            ssl: None,
+            netns: None,
+            aws_vpce_id: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseResult {
+    /// The header, and its length.
+    Header(ProxyHeader, usize),
+    /// More data is needed: the length of the header if known, else
+    /// one more byte than received.
+    Incomplete(usize),
+    Invalid,
+}
+
+/// Whether `input` may start a header, checking as much of the prefix or
+/// signature as received.
+pub fn is_header_start(input: &[u8]) -> bool {
+    let v1 = &V1_PREFIX[..std::cmp::min(input.len(), V1_PREFIX.len())];
+    let v2 = &V2_SIGNATURE[..std::cmp::min(input.len(), V2_SIGNATURE.len())];
+    input.starts_with(v1) || input.starts_with(v2)
+}
+
+fn lossy(input: &[u8]) -> String {
+    String::from_utf8_lossy(input).to_string()
+}
+
+/// "PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\n", or
+/// "PROXY UNKNOWN ...\r\n" when the proxy could not tell.
+fn parse_v1(input: &[u8]) -> ParseResult {
+    let end = match input.windows(2).position(|w| w == b"\r\n") {
+        Some(end) if end + 2 <= V1_MAX_LEN => end,
+        Some(_) => return ParseResult::Invalid,
+        None if input.len() < V1_MAX_LEN => return ParseResult::Incomplete(input.len() + 1),
+        None => return ParseResult::Invalid,
+    };
+    let line = match std::str::from_utf8(&input[V1_PREFIX.len()..end]) {
+        Ok(line) => line,
+        Err(_) => return ParseResult::Invalid,
+    };
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields[0] {
+        "UNKNOWN" => {
+            let header = ProxyHeader::new(1, Command::Proxy, Transport::Unspec);
+            return ParseResult::Header(header, end + 2);
+        }
+        "TCP4" | "TCP6" if fields.len() == 5 => {}
+        _ => return ParseResult::Invalid,
+    }
+    let v6 = fields[0] == "TCP6";
+    let address = |s: &str| -> Option<IpAddr> {
+        if v6 {
+            s.parse::<Ipv6Addr>().ok().map(IpAddr::V6)
+        } else {
+            s.parse::<Ipv4Addr>().ok().map(IpAddr::V4)
+        }
+    };
+    // ports are in decimal without leading zeros
+    let port = |s: &str| -> Option<u16> {
+        if s.len() > 1 && s.starts_with('0') {
+            return None;
+        }
+        s.parse::<u16>().ok()
+    };
+    match (address(fields[1]), address(fields[2]), port(fields[3]), port(fields[4])) {
+        (Some(src), Some(dst), Some(sp), Some(dp)) => {
+            let mut header = ProxyHeader::new(1, Command::Proxy, Transport::Tcp);
+            header.source = Some((src, sp));
+            header.destination = Some((dst, dp));
+            ParseResult::Header(header, end + 2)
+        }
+        _ => ParseResult::Invalid,
+    }
+}
+
+fn be_u16(input: &[u8]) -> u16 {
+    (input[0] as u16) << 8 | input[1] as u16
+}
+
+/// Iterate over the type, value pairs of a TLV list. Returns None if a
+/// value runs past the end of the list.
+fn parse_tlvs(mut input: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut tlvs = Vec::new();
+    while !input.is_empty() {
+        if input.len() < 3 {
+            return None;
+        }
+        let len = be_u16(&input[1..]) as usize;
+        let value = input.get(3..3 + len)?;
+        tlvs.push((input[0], value));
+        input = &input[3 + len..];
+    }
+    Some(tlvs)
+}
+
+/// The value of a PP2_TYPE_SSL TLV: the client flags, the verification
+/// result and sub-TLVs.
+fn parse_ssl(value: &[u8]) -> Option<SslInfo> {
+    if value.len() < 5 {
+        return None;
+    }
+    let mut ssl = SslInfo {
+        client_ssl: value[0] & 0x01 != 0,
+        client_cert: value[0] & 0x06 != 0,
+        ..Default::default()
+    };
+    for (subtype, value) in parse_tlvs(&value[5..])? {
+        match subtype {
+            PP2_SUBTYPE_SSL_VERSION => ssl.version = Some(lossy(value)),
+            PP2_SUBTYPE_SSL_CN => ssl.cn = Some(lossy(value)),
+            PP2_SUBTYPE_SSL_CIPHER => ssl.cipher = Some(lossy(value)),
+            _ => {}
+        }
+    }
+    Some(ssl)
+}
+
+fn parse_v2(input: &[u8]) -> ParseResult {
+    if input.len() < V2_HEADER_LEN {
+        return ParseResult::Incomplete(V2_HEADER_LEN);
+    }
+    let len = V2_HEADER_LEN + be_u16(&input[14..]) as usize;
+    if input.len() < len {
+        return ParseResult::Incomplete(len);
+    }
+    if input[12] >> 4 != 2 {
+        return ParseResult::Invalid;
+    }
+    let command = match input[12] & 0x0f {
+        0 => Command::Local,
+        1 => Command::Proxy,
+        _ => return ParseResult::Invalid,
+    };
+    let transport = match input[13] & 0x0f {
+        0 => Transport::Unspec,
+        1 => Transport::Tcp,
+        2 => Transport::Udp,
+        _ => return ParseResult::Invalid,
+    };
+    let body = &input[V2_HEADER_LEN..len];
+    let mut header = ProxyHeader::new(2, command, transport);
+    let addresses_len = match input[13] >> 4 {
+        0 => 0,
+        1 => {
+            if body.len() < 12 {
+                return ParseResult::Invalid;
+            }
+            let src = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let dst = Ipv4Addr::new(body[4], body[5], body[6], body[7]);
+            header.source = Some((IpAddr::V4(src), be_u16(&body[8..])));
+            header.destination = Some((IpAddr::V4(dst), be_u16(&body[10..])));
+            12
+        }
+        2 => {
+            if body.len() < 36 {
+                return ParseResult::Invalid;
+            }
+            let mut src = [0u8; 16];
+            let mut dst = [0u8; 16];
+            src.copy_from_slice(&body[..16]);
+            dst.copy_from_slice(&body[16..32]);
+            header.source = Some((IpAddr::V6(Ipv6Addr::from(src)), be_u16(&body[32..])));
+            header.destination = Some((IpAddr::V6(Ipv6Addr::from(dst)), be_u16(&body[34..])));
+            36
+        }
+        3 => {
+            // the paths of the unix sockets are not logged
+            header.transport = Transport::Unix;
+            216
+        }
+        _ => return ParseResult::Invalid,
+    };
+    if body.len() < addresses_len {
+        return ParseResult::Invalid;
+    }
+    let tlvs = match parse_tlvs(&body[addresses_len..]) {
+        Some(tlvs) => tlvs,
+        None => return ParseResult::Invalid,
+    };
+    for (tlv_type, value) in tlvs {
+        match tlv_type {
+            PP2_TYPE_ALPN => header.alpn = Some(lossy(value)),
+            PP2_TYPE_AUTHORITY => header.authority = Some(lossy(value)),
+            PP2_TYPE_UNIQUE_ID => header.unique_id = Some(value.to_vec()),
+            PP2_TYPE_SSL => header.ssl = parse_ssl(value),
+            PP2_TYPE_NETNS => header.netns = Some(lossy(value)),
+            PP2_TYPE_AWS if value.first() == Some(&PP2_SUBTYPE_AWS_VPCE_ID) => {
+                header.aws_vpce_id = Some(lossy(&value[1..]));
+            }
+            _ => {}
+        }
+    }
+    ParseResult::Header(header, len)
+}
+
+/// Parse the header at the start of a stream.
+pub fn parse_header(input: &[u8]) -> ParseResult {
+    if !is_header_start(input) {
+        return ParseResult::Invalid;
+    }
+    if V1_PREFIX.starts_with(&input[..std::cmp::min(input.len(), V1_PREFIX.len())]) {
+        if input.len() < V1_PREFIX.len() {
+            return ParseResult::Incomplete(input.len() + 1);
+        }
+        parse_v1(input)
+    } else {
+        parse_v2(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_v1() {
+        let input = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n";
+        let header = match parse_header(input) {
+            ParseResult::Header(header, 45) => header,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!(header.version, 1);
+        assert_eq!(header.transport, Transport::Tcp);
+        assert_eq!(header.source, Some(("192.0.2.1".parse().unwrap(), 56324)));
+        assert_eq!(header.destination, Some(("198.51.100.1".parse().unwrap(), 443)));
+
+        let input = b"PROXY TCP6 2001:db8::1 2001:db8::2 1 80\r\n";
+        match parse_header(input) {
+            ParseResult::Header(header, _) => {
+                assert_eq!(header.source, Some(("2001:db8::1".parse().unwrap(), 1)));
+            }
+            r => panic!("{:?}", r),
+        }
+        match parse_header(b"PROXY UNKNOWN\r\n") {
+            ParseResult::Header(header, 15) => assert_eq!(header.transport, Transport::Unspec),
+            r => panic!("{:?}", r),
+        }
+        assert_eq!(parse_header(b"PROXY TCP4 192.0.2.1"), ParseResult::Incomplete(21));
+        assert_eq!(parse_header(b"PRO"), ParseResult::Incomplete(4));
+        assert_eq!(parse_header(b"PROXY TCP4 192.0.2.1 2001:db8::2 1 80\r\n"), ParseResult::Invalid);
+        assert_eq!(parse_header(b"PROXY TCP4 192.0.2.1 192.0.2.2 01 80\r\n"), ParseResult::Invalid);
+        let mut input = V1_PREFIX.to_vec();
+        input.extend_from_slice(&[b'A'; 200]);
+        assert_eq!(parse_header(&input), ParseResult::Invalid);
+        assert_eq!(parse_header(b"GET / HTTP/1.1\r\n"), ParseResult::Invalid);
+    }
+
+    fn v2_header(command: u8, family: u8, body: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.push(0x20 | command);
+        header.push(family);
+        header.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        header.extend_from_slice(body);
+        header
+    }
+
+    #[test]
+    fn test_parse_v2() {
+        let mut body = vec![192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb];
+        // ALPN, an empty NOOP and the SSL TLV with a version
+        body.extend_from_slice(b"\x01\x00\x02h2\x04\x00\x00");
+        body.extend_from_slice(b"\x20\x00\x0c\x01\x00\x00\x00\x00\x21\x00\x04TLS3");
+        let input = v2_header(1, 0x11, &body);
+        let header = match parse_header(&input) {
+            ParseResult::Header(header, len) if len == input.len() => header,
+            r => panic!("{:?}", r),
+        };
+        assert_eq!(header.version, 2);
+        assert_eq!(header.command, Command::Proxy);
+        assert_eq!(header.source, Some(("192.0.2.1".parse().unwrap(), 56324)));
+        assert_eq!(header.destination, Some(("198.51.100.1".parse().unwrap(), 443)));
+        assert_eq!(header.alpn.as_ref().unwrap(), "h2");
+        let ssl = header.ssl.unwrap();
+        assert!(ssl.client_ssl);
+        assert_eq!(ssl.version.as_ref().unwrap(), "TLS3");
+
+        let input = v2_header(0, 0x00, b"");
+        match parse_header(&input) {
+            ParseResult::Header(header, 16) => {
+                assert_eq!(header.command, Command::Local);
+                assert_eq!(header.source, None);
+            }
+            r => panic!("{:?}", r),
+        }
+
+        assert_eq!(parse_header(&input[..10]), ParseResult::Incomplete(16));
+        assert_eq!(parse_header(&v2_header(1, 0x11, &body)[..20]),
+                   ParseResult::Incomplete(16 + body.len()));
+        // a TLV running past the header
+        assert_eq!(parse_header(&v2_header(1, 0x11, &body[..body.len() - 1])), ParseResult::Invalid);
+        // an unknown command
+        assert_eq!(parse_header(&v2_header(2, 0x11, &body)), ParseResult::Invalid);
+    }
+}
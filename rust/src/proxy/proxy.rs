@@ -0,0 +1,399 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::flowstorage::FlowStorage;
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(proxy_stats, "app_layer.proxy", {
+    headers_v1,
+    headers_v2,
+    local,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum ProxyEvent {
+    InvalidHeader,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static PROXY_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static PROXY_GAUGES: StateGauges = StateGauges::new();
+
+/// The header of the flow, kept after the parser handed the stream off.
+pub static PROXY_HEADERS: FlowStorage<ProxyHeader> = FlowStorage::new();
+
+pub static mut ALPROTO_PROXY: AppProto = ALPROTO_UNKNOWN;
+
+/// A transaction only carries the event of an invalid header: a valid
+/// one is kept on the flow and logged with every record of the flow.
+#[derive(Debug)]
+pub struct ProxyTransaction {
+    id: u64,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for ProxyTransaction {}
+
+impl ProxyTransaction {
+    fn new(id: u64) -> ProxyTransaction {
+        ProxyTransaction {
+            id,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: ProxyEvent) {
+        stats_incr!(proxy_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+}
+
+impl Drop for ProxyTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for ProxyTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = ProxyEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+pub struct ProxyState {
+    transactions: TxTable<ProxyTransaction>,
+    /// Set once the header is parsed and the stream handed off to the
+    /// protocol detection.
+    handed_off: bool,
+}
+
+unsafe impl FfiType for ProxyState {}
+
+impl txtable::State for ProxyState {
+    type Tx = ProxyTransaction;
+
+    fn tx_table(&self) -> &TxTable<ProxyTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<ProxyTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl ProxyState {
+    pub fn new() -> ProxyState {
+        ProxyState {
+            transactions: TxTable::with_limit(&PROXY_TX_LIMIT).with_gauges(&PROXY_GAUGES),
+            handed_off: false,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    /// Parse the header at the start of the stream of the client. Once
+    /// parsed, the header is stored on the flow and the rest of the
+    /// stream is left unconsumed for the protocol detection to run on.
+    fn parse_request(&mut self, flow: *const Flow, input: &[u8]) -> AppLayerResult {
+        if self.handed_off {
+            return AppLayerResult::ok();
+        }
+        let (header, len) = match parse_header(input) {
+            ParseResult::Header(header, len) => (header, len),
+            ParseResult::Incomplete(needed) => {
+                return AppLayerResult::incomplete_from(input.len(), 0, needed);
+            }
+            ParseResult::Invalid => {
+                let mut tx = ProxyTransaction::new(self.transactions.next_id());
+                tx.set_event(ProxyEvent::InvalidHeader);
+                self.transactions.push(tx);
+                return AppLayerResult::err();
+            }
+        };
+        if header.version == 1 {
+            stats_incr!(proxy_stats::headers_v1);
+        } else {
+            stats_incr!(proxy_stats::headers_v2);
+        }
+        if header.command == Command::Local {
+            stats_incr!(proxy_stats::local);
+        }
+        self.handed_off = true;
+        if !flow.is_null() {
+            let flow = unsafe { &mut *(flow as *mut Flow) };
+            let _ = PROXY_HEADERS.set(flow, header);
+            // any protocol may follow the header
+            unsafe { AppLayerRequestProtocolChange(flow, 0, ALPROTO_UNKNOWN) };
+        }
+        if len == input.len() {
+            AppLayerResult::ok()
+        } else {
+            // not an incomplete message, but the data following the
+            // header, left for the protocol detection
+            AppLayerResult::incomplete(len as u32, (input.len() - len) as u32)
+        }
+    }
+}
+
+/// Validate the header found by the pattern matcher.
+fn proxy_probe(input: &[u8], _direction: Direction) -> ProbeResult {
+    match parse_header(input) {
+        ParseResult::Header(..) => ProbeResult::Found(unsafe { ALPROTO_PROXY }),
+        ParseResult::Incomplete(_) => ProbeResult::Unknown,
+        ParseResult::Invalid => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_proxy_probing_parser, proxy_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_proxy_state_new(_orig_state: *mut std::os::raw::c_void,
+                                     _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = ProxyState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut ProxyState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut ProxyState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_parse_request(flow: *const Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut ProxyState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse_request(flow, buf)
+}
+
+/// The server does not send a header: its data is left to the protocol
+/// detection run once the one of the client is parsed.
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_parse_response(_flow: *const Flow,
+                                                 _state: *mut std::os::raw::c_void,
+                                                 _pstate: *mut std::os::raw::c_void,
+                                                 _input: *const u8,
+                                                 _input_len: u32,
+                                                 _data: *const std::os::raw::c_void,
+                                                 _flags: u8) -> AppLayerResult {
+    AppLayerResult::ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_state_get_tx(state: *mut std::os::raw::c_void,
+                                               tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut ProxyState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut ProxyState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void,
+                                                          _direction: u8) -> std::os::raw::c_int {
+    1
+}
+
+export_tx_detect_state!(rs_proxy_state_get_tx_detect_state,
+                        rs_proxy_state_set_tx_detect_state, ProxyTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_state_get_events(tx: *mut std::os::raw::c_void)
+                                                   -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut ProxyTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_proxy_get_tx_data, ProxyTransaction);
+
+fn proxy_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    PROXY_GAUGES.log(js)?;
+    js.set_uint("events", proxy_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+fn register_pattern_probe() -> i8 {
+    let patterns: &[(&[u8], u16)] = &[
+        (b"PROXY \0", 6),
+        (b"|0d 0a 0d 0a 00 0d 0a|QUIT|0a|\0", 12),
+    ];
+    for (pattern, depth) in patterns {
+        unsafe {
+            if AppLayerProtoDetectPMRegisterPatternCSwPP(IPPROTO_TCP as u8, ALPROTO_PROXY,
+                                                         pattern.as_ptr() as *const std::os::raw::c_char,
+                                                         *depth, 0, core::STREAM_TOSERVER,
+                                                         rs_proxy_probing_parser, 0,
+                                                         V1_MAX_LEN as u16) < 0 {
+                SCLogDebug!("TOSERVER => AppLayerProtoDetectPMRegisterPatternCSwPP FAILED");
+                return -1;
+            }
+        }
+    }
+    0
+}
+
+const PARSER_NAME: &[u8] = b"proxy\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_proxy_register_parser() {
+    proxy_stats::register();
+    PROXY_TX_LIMIT.configure("proxy", txtable::DEFAULT_MAX_TX);
+    introspect::register("proxy", proxy_introspect);
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: std::ptr::null(),
+        ipproto: IPPROTO_TCP,
+        probe_ts: None,
+        probe_tc: None,
+        min_depth: 0,
+        max_depth: 0,
+        state_new: rs_proxy_state_new,
+        state_free: rs_proxy_state_free,
+        tx_free: rs_proxy_state_tx_free,
+        parse_ts: rs_proxy_parse_request,
+        parse_tc: rs_proxy_parse_response,
+        get_tx_count: rs_proxy_state_get_tx_count,
+        get_tx: rs_proxy_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_proxy_tx_get_alstate_progress,
+        get_de_state: rs_proxy_state_get_tx_detect_state,
+        set_de_state: rs_proxy_state_set_tx_detect_state,
+        get_events: Some(rs_proxy_state_get_events),
+        get_eventinfo: Some(ProxyEvent::get_event_info),
+        get_eventinfo_byid: Some(ProxyEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<ProxyState>),
+        get_tx_data: rs_proxy_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<ProxyState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_PROXY = alproto;
+        if register_pattern_probe() < 0 {
+            return;
+        }
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = PROXY_HEADERS.register("proxy-header");
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust proxy parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for PROXY.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_proxy_state_new,
+            state_free: rs_proxy_state_free,
+            parse_ts: rs_proxy_parse_request,
+            parse_tc: rs_proxy_parse_response,
+            get_tx_count: rs_proxy_state_get_tx_count,
+            get_tx: rs_proxy_state_get_tx,
+            tx_free: rs_proxy_state_tx_free,
+        })
+    }
+
+    #[test]
+    fn test_proxy_v1_handoff() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, b"PROXY TCP4 192.0.2.1 198.51.100.1 "));
+        assert_eq!(h.pending(Direction::ToServer), 34);
+        assert!(h.feed(Direction::ToServer, b"56324 443\r\nGET / HTTP/1.1\r\n\r\n"));
+        assert_eq!(h.pending(Direction::ToServer), 0);
+        assert!(h.state::<ProxyState>().handed_off);
+        assert_eq!(h.tx_count(), 0);
+    }
+
+    #[test]
+    fn test_proxy_invalid() {
+        let mut h = harness();
+        assert!(!h.feed(Direction::ToServer, b"PROXY TCP4 localhost 192.0.2.1 1 2\r\n"));
+        assert_eq!(h.tx_count(), 1);
+    }
+
+    #[test]
+    fn test_proxy_probe() {
+        unsafe {
+            ALPROTO_PROXY = 42;
+        }
+        assert_eq!(proxy_probe(b"PROXY UNKNOWN\r\n", Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(proxy_probe(b"PROXY TCP4 ", Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(proxy_probe(b"PROXY HTTP/1.1\r\n", Direction::ToServer), ProbeResult::Failed);
+    }
+}
@@ -0,0 +1,186 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Protection of the Initial packets, as defined in RFC 9001 section 5.
+//! Their keys are derived from the destination connection id of the
+//! first Initial packet of the client, so they can be removed by anyone.
+
+use super::parser::{QUIC_VERSION_1, QUIC_VERSION_DRAFT29};
+use crate::core::Direction;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockEncrypt, NewBlockCipher};
+use aes::Aes128;
+use aes_gcm::aead::{AeadInPlace, NewAead};
+use aes_gcm::{Aes128Gcm, Key, Nonce, Tag};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+const INITIAL_SALT_V1: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17,
+    0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad, 0xcc, 0xbb, 0x7f, 0x0a,
+];
+
+const INITIAL_SALT_DRAFT29: [u8; 20] = [
+    0xaf, 0xbf, 0xec, 0x28, 0x99, 0x93, 0xd2, 0x4c, 0x9e, 0x97,
+    0x86, 0xf1, 0x9c, 0x61, 0x11, 0xe0, 0x43, 0x90, 0xa8, 0x99,
+];
+
+const TAG_LEN: usize = 16;
+const SAMPLE_LEN: usize = 16;
+
+/// Keys of the Initial packets in one direction.
+struct KeyMaterial {
+    key: [u8; 16],
+    iv: [u8; 12],
+    hp: [u8; 16],
+}
+
+/// HKDF-Expand-Label of TLS 1.3 (RFC 8446 section 7.1), with an empty
+/// context.
+fn hkdf_expand_label(hk: &Hkdf<Sha256>, label: &[u8], out: &mut [u8]) -> Option<()> {
+    let mut info = Vec::with_capacity(10 + label.len());
+    info.extend_from_slice(&(out.len() as u16).to_be_bytes());
+    info.push((6 + label.len()) as u8);
+    info.extend_from_slice(b"tls13 ");
+    info.extend_from_slice(label);
+    info.push(0);
+    hk.expand(&info, out).ok()
+}
+
+fn initial_key_material(version: u32, dcid: &[u8], direction: Direction) -> Option<KeyMaterial> {
+    let salt = match version {
+        QUIC_VERSION_1 => &INITIAL_SALT_V1,
+        QUIC_VERSION_DRAFT29 => &INITIAL_SALT_DRAFT29,
+        _ => return None,
+    };
+    let (_, hk) = Hkdf::<Sha256>::extract(Some(salt), dcid);
+    let label: &[u8] = match direction {
+        Direction::ToServer => b"client in",
+        Direction::ToClient => b"server in",
+    };
+    let mut secret = [0u8; 32];
+    hkdf_expand_label(&hk, label, &mut secret)?;
+    let hk = Hkdf::<Sha256>::from_prk(&secret).ok()?;
+    let mut keys = KeyMaterial {
+        key: [0; 16],
+        iv: [0; 12],
+        hp: [0; 16],
+    };
+    hkdf_expand_label(&hk, b"quic key", &mut keys.key)?;
+    hkdf_expand_label(&hk, b"quic iv", &mut keys.iv)?;
+    hkdf_expand_label(&hk, b"quic hp", &mut keys.hp)?;
+    Some(keys)
+}
+
+/// Keys protecting the Initial packets sent in one direction.
+pub struct PacketKeys {
+    hp: Aes128,
+    aead: Aes128Gcm,
+    iv: [u8; 12],
+}
+
+impl PacketKeys {
+    /// Derive the keys of the Initial packets sent in `direction`, from
+    /// the first destination connection id of the client. Returns None
+    /// if the version is not supported.
+    pub fn initial(version: u32, dcid: &[u8], direction: Direction) -> Option<PacketKeys> {
+        let keys = initial_key_material(version, dcid, direction)?;
+        Some(PacketKeys {
+            hp: Aes128::new(GenericArray::from_slice(&keys.hp)),
+            aead: Aes128Gcm::new(Key::from_slice(&keys.key)),
+            iv: keys.iv,
+        })
+    }
+
+    /// Remove the header protection of the long header `packet` and
+    /// decrypt its payload. `pn_offset` and `length` are the offset of
+    /// the packet number and the length of the packet number and
+    /// payload, from the header. Returns None if the packet can't be
+    /// decrypted.
+    pub fn decrypt(&self, packet: &[u8], pn_offset: usize, length: usize) -> Option<Vec<u8>> {
+        let end = pn_offset.checked_add(length)?;
+        let sample_offset = pn_offset + 4;
+        if end > packet.len() || sample_offset + SAMPLE_LEN > end {
+            return None;
+        }
+        let mut mask = GenericArray::clone_from_slice(
+            &packet[sample_offset..sample_offset + SAMPLE_LEN]);
+        self.hp.encrypt_block(&mut mask);
+
+        let mut header = packet[..sample_offset].to_vec();
+        header[0] ^= mask[0] & 0x0f;
+        let pn_len = (header[0] & 0x03) as usize + 1;
+        header.truncate(pn_offset + pn_len);
+        // The packet number is truncated to its pn_len least significant
+        // bytes. The Initial packets of interest are the first ones of the
+        // flow, with small packet numbers, so it is used as is.
+        let mut pn = 0u64;
+        for n in 0..pn_len {
+            header[pn_offset + n] ^= mask[1 + n];
+            pn = (pn << 8) | header[pn_offset + n] as u64;
+        }
+        if pn_offset + pn_len + TAG_LEN > end {
+            return None;
+        }
+        let mut nonce = self.iv;
+        for (n, b) in pn.to_be_bytes().iter().enumerate() {
+            nonce[4 + n] ^= b;
+        }
+        let mut payload = packet[pn_offset + pn_len..end - TAG_LEN].to_vec();
+        let tag = Tag::from_slice(&packet[end - TAG_LEN..end]);
+        self.aead
+            .decrypt_in_place_detached(Nonce::from_slice(&nonce), &header, &mut payload, tag)
+            .ok()?;
+        Some(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 9001 appendix A.1
+    const DCID: &[u8] = &[0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+
+    #[test]
+    fn test_initial_keys() {
+        let keys = initial_key_material(QUIC_VERSION_1, DCID, Direction::ToServer).unwrap();
+        assert_eq!(keys.key, [0x1f, 0x36, 0x96, 0x13, 0xdd, 0x76, 0xd5, 0x46,
+                              0x77, 0x30, 0xef, 0xcb, 0xe3, 0xb1, 0xa2, 0x2d]);
+        assert_eq!(keys.iv, [0xfa, 0x04, 0x4b, 0x2f, 0x42, 0xa3, 0xfd, 0x3b,
+                             0x46, 0xfb, 0x25, 0x5c]);
+        assert_eq!(keys.hp, [0x9f, 0x50, 0x44, 0x9e, 0x04, 0xa0, 0xe8, 0x10,
+                             0x28, 0x3a, 0x1e, 0x99, 0x33, 0xad, 0xed, 0xd2]);
+
+        let keys = initial_key_material(QUIC_VERSION_1, DCID, Direction::ToClient).unwrap();
+        assert_eq!(keys.key, [0xcf, 0x3a, 0x53, 0x31, 0x65, 0x3c, 0x36, 0x4c,
+                              0x88, 0xf0, 0xf3, 0x79, 0xb6, 0x06, 0x7e, 0x37]);
+        assert_eq!(keys.iv, [0x0a, 0xc1, 0x49, 0x3c, 0xa1, 0x90, 0x58, 0x53,
+                             0xb0, 0xbb, 0xa0, 0x3e]);
+        assert_eq!(keys.hp, [0xc2, 0x06, 0xb8, 0xd9, 0xb9, 0xf0, 0xf3, 0x76,
+                             0x44, 0x43, 0x0b, 0x49, 0x0e, 0xea, 0xa3, 0x14]);
+
+        assert!(initial_key_material(0x1234, DCID, Direction::ToServer).is_none());
+    }
+
+    #[test]
+    fn test_decrypt_short_packet() {
+        let keys = PacketKeys::initial(QUIC_VERSION_1, DCID, Direction::ToServer).unwrap();
+        assert!(keys.decrypt(&[0xc0; 30], 10, 10).is_none());
+        assert!(keys.decrypt(&[0xc0; 64], 10, 40).is_none());
+    }
+}
@@ -0,0 +1,78 @@
+/* Copyright (C) 2024 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Sticky buffer on the JA4 fingerprint of the client hello.
+
+use super::quic::{QuicTransaction, ALPROTO_QUIC};
+use crate::applayer::cast_mut;
+use crate::core::ALPROTO_UNKNOWN;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_QUIC_JA4_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn quic_tx_get_ja4(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut QuicTransaction = cast_mut(tx);
+    match tx.ja4 {
+        Some(ref ja4) => {
+            *buf = ja4.as_ptr();
+            *len = ja4.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn quic_ja4_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, quic_tx_get_ja4)
+}
+
+unsafe extern "C" fn quic_ja4_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_QUIC, G_QUIC_JA4_BUFFER_ID)
+}
+
+/// Register the QUIC keywords.
+pub unsafe fn register_keywords() {
+    // The parser is only registered if enabled in config.
+    if ALPROTO_QUIC == ALPROTO_UNKNOWN {
+        return;
+    }
+    let kw = DetectKeyword {
+        name: "quic.ja4",
+        desc: "sticky buffer to match on the JA4 fingerprint of the QUIC client hello",
+        url: "/rules/quic-keywords.html#quic-ja4",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: quic_ja4_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_QUIC_JA4_BUFFER_ID = helper_buffer_mpm_register(
+            "quic.ja4", "quic ja4 fingerprint", ALPROTO_QUIC, false, true, 0,
+            quic_ja4_get_data);
+    }
+}
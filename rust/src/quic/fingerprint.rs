@@ -0,0 +1,286 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! TLS hellos carried in the CRYPTO frames, and their JA3 and JA4
+//! fingerprints.
+
+use digest::Digest;
+use md5::Md5;
+use nom::number::streaming::be_u16;
+use nom::IResult;
+use tls_parser::{parse_tls_extensions, parse_tls_message_handshake, TlsExtension, TlsMessage,
+                 TlsMessageHandshake};
+use crate::common::ja4_hash;
+use crate::parsers_util::length_data_be_u16;
+
+const EXT_SNI: u16 = 0x0000;
+const EXT_ALPN: u16 = 0x0010;
+const EXT_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// Check for the GREASE values of RFC 8701, which are left out of the
+/// fingerprints.
+fn is_grease(v: u16) -> bool {
+    v & 0x0f0f == 0x0a0a && v >> 8 == v & 0xff
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ClientHello {
+    pub version: u16,
+    pub ciphers: Vec<u16>,
+    pub extensions: Vec<u16>,
+    pub sni: Option<Vec<u8>>,
+    pub alpn: Vec<Vec<u8>>,
+    pub groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+    pub sig_algs: Vec<u16>,
+    pub supported_versions: Vec<u16>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ServerHello {
+    pub version: u16,
+    pub cipher: u16,
+    pub extensions: Vec<u16>,
+    /// Version selected through the supported_versions extension.
+    pub selected_version: Option<u16>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Hello {
+    Client(ClientHello),
+    Server(ServerHello),
+    /// Another handshake message.
+    Other,
+}
+
+/// Return the type and data of the extensions of a hello.
+fn raw_extensions(mut i: &[u8]) -> IResult<&[u8], Vec<(u16, &[u8])>> {
+    let mut exts = Vec::new();
+    while !i.is_empty() {
+        let (rem, ty) = be_u16(i)?;
+        let (rem, data) = length_data_be_u16(rem)?;
+        exts.push((ty, data));
+        i = rem;
+    }
+    Ok((i, exts))
+}
+
+/// Build a ClientHello from its fields, also used for the hellos of DTLS.
+pub(crate) fn client_hello(version: u16, ciphers: Vec<u16>, ext: &[u8]) -> ClientHello {
+    let mut hello = ClientHello {
+        version,
+        ciphers,
+        ..Default::default()
+    };
+    if let Ok((_, exts)) = raw_extensions(ext) {
+        hello.extensions = exts.iter().map(|(ty, _)| *ty).collect();
+    }
+    if let Ok((_, exts)) = parse_tls_extensions(ext) {
+        for ext in exts {
+            match ext {
+                TlsExtension::SNI(names) => {
+                    if let Some((_, name)) = names.first() {
+                        hello.sni = Some(name.to_vec());
+                    }
+                }
+                TlsExtension::ALPN(protocols) => {
+                    hello.alpn = protocols.iter().map(|p| p.to_vec()).collect();
+                }
+                TlsExtension::EllipticCurves(groups) => {
+                    hello.groups = groups.iter().map(|g| g.0).collect();
+                }
+                TlsExtension::EcPointFormats(formats) => {
+                    hello.ec_point_formats = formats.to_vec();
+                }
+                TlsExtension::SignatureAlgorithms(algs) => {
+                    hello.sig_algs = algs;
+                }
+                TlsExtension::SupportedVersions(versions) => {
+                    hello.supported_versions = versions.iter().map(|v| v.0).collect();
+                }
+                _ => {}
+            }
+        }
+    }
+    hello
+}
+
+/// Build a ServerHello from its fields, also used for the hellos of DTLS.
+pub(crate) fn server_hello(version: u16, cipher: u16, ext: &[u8]) -> ServerHello {
+    let mut hello = ServerHello {
+        version,
+        cipher,
+        ..Default::default()
+    };
+    if let Ok((_, exts)) = raw_extensions(ext) {
+        for (ty, data) in exts {
+            // a single version, unlike the list of the client
+            if ty == EXT_SUPPORTED_VERSIONS && data.len() == 2 {
+                hello.selected_version = Some(u16::from_be_bytes([data[0], data[1]]));
+            }
+            hello.extensions.push(ty);
+        }
+    }
+    hello
+}
+
+/// Parse a handshake message from the reassembled CRYPTO data.
+pub fn parse_hello(i: &[u8]) -> IResult<&[u8], Hello> {
+    let (rem, msg) = parse_tls_message_handshake(i)?;
+    let hello = match msg {
+        TlsMessage::Handshake(TlsMessageHandshake::ClientHello(ch)) => {
+            let ciphers = ch.ciphers.iter().map(|c| c.0).collect();
+            Hello::Client(client_hello(ch.version.0, ciphers, ch.ext.unwrap_or(&[])))
+        }
+        TlsMessage::Handshake(TlsMessageHandshake::ServerHello(sh)) => {
+            Hello::Server(server_hello(sh.version.0, sh.cipher.0, sh.ext.unwrap_or(&[])))
+        }
+        _ => Hello::Other,
+    };
+    Ok((rem, hello))
+}
+
+fn join<T: ToString>(values: &[T], sep: &str) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(sep)
+}
+
+fn without_grease(values: &[u16]) -> Vec<u16> {
+    values.iter().cloned().filter(|v| !is_grease(*v)).collect()
+}
+
+pub fn ja3_hash(ja3: &str) -> String {
+    format!("{:x}", Md5::new().chain(ja3).finalize())
+}
+
+impl ClientHello {
+    pub fn ja3(&self) -> String {
+        format!("{},{},{},{},{}",
+                self.version,
+                join(&without_grease(&self.ciphers), "-"),
+                join(&without_grease(&self.extensions), "-"),
+                join(&without_grease(&self.groups), "-"),
+                join(&self.ec_point_formats, "-"))
+    }
+
+    pub fn ja4(&self) -> String {
+        let ciphers = without_grease(&self.ciphers);
+        let extensions = without_grease(&self.extensions);
+        let version = without_grease(&self.supported_versions).into_iter().max()
+            .unwrap_or(self.version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            _ => "00",
+        };
+        let sni = if self.sni.is_some() { 'd' } else { 'i' };
+        let alpn = match self.alpn.first() {
+            Some(p) if !p.is_empty() => {
+                let (first, last) = (p[0] as char, p[p.len() - 1] as char);
+                if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                    format!("{}{}", first, last)
+                } else {
+                    format!("{:x}{:x}", p[0] >> 4, p[p.len() - 1] & 0x0f)
+                }
+            }
+            _ => "00".to_string(),
+        };
+
+        let mut sorted_ciphers = ciphers.clone();
+        sorted_ciphers.sort_unstable();
+        let mut sorted_exts: Vec<u16> = extensions.iter().cloned()
+            .filter(|e| *e != EXT_SNI && *e != EXT_ALPN).collect();
+        sorted_exts.sort_unstable();
+        let hex = |values: &[u16]| {
+            values.iter().map(|v| format!("{:04x}", v)).collect::<Vec<_>>().join(",")
+        };
+        let mut exts = hex(&sorted_exts);
+        if !exts.is_empty() && !self.sig_algs.is_empty() {
+            exts.push('_');
+            exts.push_str(&hex(&self.sig_algs));
+        }
+        format!("q{}{}{:02}{:02}{}_{}_{}",
+                version, sni,
+                std::cmp::min(ciphers.len(), 99), std::cmp::min(extensions.len(), 99),
+                alpn, ja4_hash(&hex(&sorted_ciphers)), ja4_hash(&exts))
+    }
+}
+
+impl ServerHello {
+    pub fn ja3s(&self) -> String {
+        format!("{},{},{}",
+                self.version, self.cipher, join(&without_grease(&self.extensions), "-"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello() -> ClientHello {
+        ClientHello {
+            version: 0x0303,
+            ciphers: vec![0x0a0a, 0x1301, 0x1302],
+            extensions: vec![0x0000, 0x0010, 0x002b, 0x000d, 0x1a1a],
+            sni: Some(b"example.com".to_vec()),
+            alpn: vec![b"h3".to_vec()],
+            groups: vec![0x001d],
+            ec_point_formats: Vec::new(),
+            sig_algs: vec![0x0403],
+            supported_versions: vec![0x0304],
+        }
+    }
+
+    #[test]
+    fn test_grease() {
+        assert!(is_grease(0x0a0a));
+        assert!(is_grease(0xfafa));
+        assert!(!is_grease(0x0a1a));
+        assert!(!is_grease(0x1301));
+    }
+
+    #[test]
+    fn test_ja3() {
+        assert_eq!(hello().ja3(), "771,4865-4866,0-16-43-13,29,");
+        assert_eq!(ja3_hash(""), "d41d8cd98f00b204e9800998ecf8427e");
+    }
+
+    #[test]
+    fn test_ja4() {
+        let ja4 = hello().ja4();
+        assert!(ja4.starts_with("q13d0204h3_"), "{}", ja4);
+        assert_eq!(ja4.len(), "q13d0204h3_".len() + 12 + 1 + 12);
+
+        let mut hello = hello();
+        hello.sni = None;
+        hello.alpn.clear();
+        hello.ciphers.clear();
+        assert!(hello.ja4().starts_with("q13i000400_000000000000_"));
+    }
+
+    #[test]
+    fn test_raw_extensions() {
+        let buf = [0x00, 0x2b, 0x00, 0x02, 0x03, 0x04, 0x00, 0x10, 0x00, 0x00];
+        let (_, exts) = raw_extensions(&buf).unwrap();
+        assert_eq!(exts, vec![(0x002b, &[0x03, 0x04][..]), (0x0010, &[][..])]);
+        let hello = server_hello(0x0303, 0x1301, &buf);
+        assert_eq!(hello.selected_version, Some(0x0304));
+        assert_eq!(hello.ja3s(), "771,4865,43-16");
+    }
+}
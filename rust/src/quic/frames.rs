@@ -0,0 +1,147 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Frames of the Initial and Handshake packets (RFC 9000 section 19).
+
+use super::parser::{quic_varint, quic_varint_data};
+use nom::IResult;
+
+const FRAME_PADDING: u64 = 0x00;
+const FRAME_PING: u64 = 0x01;
+const FRAME_ACK: u64 = 0x02;
+const FRAME_ACK_ECN: u64 = 0x03;
+const FRAME_CRYPTO: u64 = 0x06;
+const FRAME_CONNECTION_CLOSE: u64 = 0x1c;
+const FRAME_CONNECTION_CLOSE_APP: u64 = 0x1d;
+
+#[derive(Debug, PartialEq)]
+pub enum Frame {
+    /// A run of padding bytes.
+    Padding,
+    Ping,
+    Ack,
+    Crypto { offset: u64, data: Vec<u8> },
+    ConnectionClose { error_code: u64, reason: Vec<u8> },
+    /// A frame that is not expected in the Initial and Handshake
+    /// packets. The rest of the payload is not parsed.
+    Unknown(u64),
+}
+
+impl Frame {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Frame::Padding => "padding",
+            Frame::Ping => "ping",
+            Frame::Ack => "ack",
+            Frame::Crypto { .. } => "crypto",
+            Frame::ConnectionClose { .. } => "connection_close",
+            Frame::Unknown(_) => "unknown",
+        }
+    }
+}
+
+fn parse_ack(i: &[u8], ecn: bool) -> IResult<&[u8], Frame> {
+    let (i, _largest) = quic_varint(i)?;
+    let (i, _delay) = quic_varint(i)?;
+    let (i, range_count) = quic_varint(i)?;
+    let (mut i, _first_range) = quic_varint(i)?;
+    // each range is at least 2 bytes, so this stops at the end of the
+    // input for bogus counts
+    for _ in 0..range_count {
+        let (r, _gap) = quic_varint(i)?;
+        let (r, _len) = quic_varint(r)?;
+        i = r;
+    }
+    if ecn {
+        for _ in 0..3 {
+            let (r, _count) = quic_varint(i)?;
+            i = r;
+        }
+    }
+    Ok((i, Frame::Ack))
+}
+
+fn parse_frame(i: &[u8]) -> IResult<&[u8], Frame> {
+    let (rem, ty) = quic_varint(i)?;
+    match ty {
+        FRAME_PADDING => {
+            let len = rem.iter().position(|&b| b != 0).unwrap_or(rem.len());
+            Ok((&rem[len..], Frame::Padding))
+        }
+        FRAME_PING => Ok((rem, Frame::Ping)),
+        FRAME_ACK | FRAME_ACK_ECN => parse_ack(rem, ty == FRAME_ACK_ECN),
+        FRAME_CRYPTO => {
+            let (rem, offset) = quic_varint(rem)?;
+            let (rem, data) = quic_varint_data(rem)?;
+            Ok((rem, Frame::Crypto { offset, data: data.to_vec() }))
+        }
+        FRAME_CONNECTION_CLOSE | FRAME_CONNECTION_CLOSE_APP => {
+            let (mut rem, error_code) = quic_varint(rem)?;
+            if ty == FRAME_CONNECTION_CLOSE {
+                let (r, _frame_type) = quic_varint(rem)?;
+                rem = r;
+            }
+            let (rem, reason) = quic_varint_data(rem)?;
+            Ok((rem, Frame::ConnectionClose { error_code, reason: reason.to_vec() }))
+        }
+        _ => Ok((&rem[rem.len()..], Frame::Unknown(ty))),
+    }
+}
+
+/// Parse the frames of a decrypted payload.
+pub fn parse_frames(mut i: &[u8]) -> IResult<&[u8], Vec<Frame>> {
+    let mut frames = Vec::new();
+    while !i.is_empty() {
+        let (rem, frame) = parse_frame(i)?;
+        frames.push(frame);
+        i = rem;
+    }
+    Ok((i, frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frames() {
+        let buf = [
+            0x02, 0x05, 0x00, 0x01, 0x00, 0x02, 0x01, // ack with one range
+            0x06, 0x00, 0x03, b'a', b'b', b'c', // crypto
+            0x01, // ping
+            0x00, 0x00, 0x00, // padding
+        ];
+        let (rem, frames) = parse_frames(&buf).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(frames, vec![
+            Frame::Ack,
+            Frame::Crypto { offset: 0, data: b"abc".to_vec() },
+            Frame::Ping,
+            Frame::Padding,
+        ]);
+    }
+
+    #[test]
+    fn test_parse_frames_unknown() {
+        let (_, frames) = parse_frames(&[0x1c, 0x0a, 0x06, 0x02, b'n', b'o', 0x08, 0x01]).unwrap();
+        assert_eq!(frames, vec![
+            Frame::ConnectionClose { error_code: 10, reason: b"no".to_vec() },
+            Frame::Unknown(8),
+        ]);
+        assert!(parse_frames(&[0x06, 0x00, 0x05, b'a']).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::fingerprint::ja3_hash;
+use super::frames::Frame;
+use super::quic::QuicTransaction;
+use crate::core::Direction;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log_ja3(js: &mut JsonBuilder, key: &str, ja3: &str) -> Result<(), JsonError> {
+    js.open_object(key)?;
+    js.set_string("string", ja3)?;
+    js.set_string("hash", &ja3_hash(ja3))?;
+    js.close()?;
+    Ok(())
+}
+
+fn log(tx: &QuicTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("quic")?;
+    let direction = match tx.direction {
+        Direction::ToServer => "toserver",
+        Direction::ToClient => "toclient",
+    };
+    js.set_string("direction", direction)?;
+    if let Some(ref header) = tx.header {
+        js.set_string("packet_type", header.ty.to_str())?;
+        js.set_string("version", &format!("{:08x}", header.version))?;
+        js.set_hex("dcid", &header.dcid)?;
+        js.set_hex("scid", &header.scid)?;
+        if !header.versions.is_empty() {
+            js.open_array("versions")?;
+            for version in &header.versions {
+                js.append_string(&format!("{:08x}", version))?;
+            }
+            js.close()?;
+        }
+    }
+    if let Some(ref hello) = tx.client_hello {
+        if let Some(ref sni) = hello.sni {
+            js.set_string_from_bytes("sni", sni)?;
+        }
+        if !hello.alpn.is_empty() {
+            js.open_array("alpn")?;
+            for protocol in &hello.alpn {
+                js.append_string_from_bytes(protocol)?;
+            }
+            js.close()?;
+        }
+        log_ja3(js, "ja3", &hello.ja3())?;
+    }
+    if let Some(ref ja4) = tx.ja4 {
+        js.set_string("ja4", ja4)?;
+    }
+    if let Some(ref hello) = tx.server_hello {
+        if let Some(version) = hello.selected_version {
+            js.set_string("tls_version", &format!("{:04x}", version))?;
+        }
+        js.set_string("cipher_suite", &format!("{:04x}", hello.cipher))?;
+        log_ja3(js, "ja3s", &hello.ja3s())?;
+    }
+    if !tx.frames.is_empty() {
+        js.open_array("frames")?;
+        for frame in &tx.frames {
+            js.start_object()?;
+            js.set_string("type", frame.to_str())?;
+            match frame {
+                Frame::Crypto { offset, data } => {
+                    js.set_uint("offset", *offset)?;
+                    js.set_uint("length", data.len() as u64)?;
+                }
+                Frame::ConnectionClose { error_code, reason } => {
+                    js.set_uint("error_code", *error_code)?;
+                    js.set_string_from_bytes("reason", reason)?;
+                }
+                Frame::Unknown(ty) => {
+                    js.set_uint("frame_type", *ty)?;
+                }
+                _ => {}
+            }
+            js.close()?;
+        }
+        js.close()?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_quic_to_json(tx: &mut QuicTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
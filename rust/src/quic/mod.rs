@@ -1,4 +1,4 @@
-/* Copyright (C) 2020 Open Information Security Foundation
+/* Copyright (C) 2021 Open Information Security Foundation
  *
  * You can copy, redistribute or modify this Program under the terms of
  * the GNU General Public License version 2 as published by the Free
@@ -15,15 +15,14 @@
  * 02110-1301, USA.
  */
 
-/**
- * \file
- *
- * \author Sascha Steinbiss <sascha@steinbiss.name>
- */
-
-#ifndef __DETECT_MQTT_CONNECT_WILLMESSAGE_H__
-#define __DETECT_MQTT_CONNECT_WILLMESSAGE_H__
-
-void DetectMQTTConnectWillMessageRegister(void);
+//! QUIC parser: the long header packets are parsed, and the CRYPTO
+//! frames of the Initial packets are decrypted to get the TLS client and
+//! server hellos.
 
-#endif /* __DETECT_MQTT_CONNECT_WILLMESSAGE_H__ */
+mod crypto;
+pub mod detect;
+pub(crate) mod fingerprint;
+mod frames;
+pub mod logger;
+mod parser;
+pub mod quic;
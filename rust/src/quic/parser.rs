@@ -0,0 +1,216 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! QUIC packet headers, as defined in RFC 9000 section 17.
+
+use crate::parsers_util::length_data_be_u8;
+use nom::bytes::streaming::take;
+use nom::error::ErrorKind;
+use nom::number::streaming::{be_u32, be_u8};
+use nom::{Err, IResult};
+
+pub const QUIC_VERSION_1: u32 = 0x0000_0001;
+pub const QUIC_VERSION_DRAFT29: u32 = 0xff00_001d;
+
+/// Version of the version negotiation packets.
+const QUIC_VERSION_NEGOTIATION: u32 = 0;
+
+const QUIC_FLAG_LONG_HEADER: u8 = 0x80;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QuicPacketType {
+    Initial,
+    ZeroRtt,
+    Handshake,
+    Retry,
+    VersionNegotiation,
+    Short,
+}
+
+impl QuicPacketType {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            QuicPacketType::Initial => "initial",
+            QuicPacketType::ZeroRtt => "0rtt",
+            QuicPacketType::Handshake => "handshake",
+            QuicPacketType::Retry => "retry",
+            QuicPacketType::VersionNegotiation => "version_negotiation",
+            QuicPacketType::Short => "short",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct QuicHeader {
+    pub flags: u8,
+    pub ty: QuicPacketType,
+    pub version: u32,
+    pub dcid: Vec<u8>,
+    pub scid: Vec<u8>,
+    /// Versions offered by a version negotiation packet.
+    pub versions: Vec<u32>,
+    /// Offset of the packet number from the start of the packet.
+    pub pn_offset: usize,
+    /// Length of the packet number and the payload.
+    pub length: usize,
+}
+
+impl QuicHeader {
+    /// Length of the packet, from the start of the header. Long header
+    /// packets can be followed by other packets in the same datagram.
+    pub fn packet_len(&self) -> usize {
+        self.pn_offset + self.length
+    }
+}
+
+/// Parse a variable length integer (RFC 9000 section 16): the 2 most
+/// significant bits of the first byte give its length.
+pub fn quic_varint(i: &[u8]) -> IResult<&[u8], u64> {
+    let (_, first) = be_u8(i)?;
+    let (rem, bytes) = take(1usize << (first >> 6))(i)?;
+    let mut value = (bytes[0] & 0x3f) as u64;
+    for b in &bytes[1..] {
+        value = (value << 8) | *b as u64;
+    }
+    Ok((rem, value))
+}
+
+/// Take the number of bytes given by a variable length integer.
+pub fn quic_varint_data(i: &[u8]) -> IResult<&[u8], &[u8]> {
+    let (rem, len) = quic_varint(i)?;
+    if len > rem.len() as u64 {
+        return Err(Err::Error((i, ErrorKind::TooLarge)));
+    }
+    take(len as usize)(rem)
+}
+
+/// Parse a packet header. The connection id of short header packets is
+/// not prefixed by its length, which has to be known from the long
+/// header packets: `short_dcid_len`. The rest of the input is the packet
+/// number and the payload.
+pub fn parse_quic_header(i: &[u8], short_dcid_len: usize) -> IResult<&[u8], QuicHeader> {
+    let (rem, flags) = be_u8(i)?;
+    if flags & QUIC_FLAG_LONG_HEADER == 0 {
+        let (rem, dcid) = take(short_dcid_len)(rem)?;
+        let header = QuicHeader {
+            flags,
+            ty: QuicPacketType::Short,
+            version: 0,
+            dcid: dcid.to_vec(),
+            scid: Vec::new(),
+            versions: Vec::new(),
+            pn_offset: i.len() - rem.len(),
+            length: rem.len(),
+        };
+        return Ok((rem, header));
+    }
+    let (rem, version) = be_u32(rem)?;
+    let (rem, dcid) = length_data_be_u8(rem)?;
+    let (mut rem, scid) = length_data_be_u8(rem)?;
+    let mut versions = Vec::new();
+    let ty = if version == QUIC_VERSION_NEGOTIATION {
+        while !rem.is_empty() {
+            let (r, v) = be_u32(rem)?;
+            versions.push(v);
+            rem = r;
+        }
+        QuicPacketType::VersionNegotiation
+    } else {
+        match (flags >> 4) & 0x03 {
+            0 => QuicPacketType::Initial,
+            1 => QuicPacketType::ZeroRtt,
+            2 => QuicPacketType::Handshake,
+            _ => QuicPacketType::Retry,
+        }
+    };
+    let length = match ty {
+        QuicPacketType::Initial | QuicPacketType::ZeroRtt | QuicPacketType::Handshake => {
+            if ty == QuicPacketType::Initial {
+                let (r, _token) = quic_varint_data(rem)?;
+                rem = r;
+            }
+            let (r, length) = quic_varint(rem)?;
+            if length > r.len() as u64 {
+                return Err(Err::Error((r, ErrorKind::TooLarge)));
+            }
+            rem = r;
+            length as usize
+        }
+        _ => rem.len(),
+    };
+    let header = QuicHeader {
+        flags,
+        ty,
+        version,
+        dcid: dcid.to_vec(),
+        scid: scid.to_vec(),
+        versions,
+        pn_offset: i.len() - rem.len(),
+        length,
+    };
+    Ok((rem, header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quic_varint() {
+        // RFC 9000 appendix A.1
+        assert_eq!(quic_varint(&[0x25]), Ok((&[][..], 37)));
+        assert_eq!(quic_varint(&[0x7b, 0xbd]), Ok((&[][..], 15293)));
+        assert_eq!(quic_varint(&[0x9d, 0x7f, 0x3e, 0x7d]), Ok((&[][..], 494878333)));
+        assert_eq!(quic_varint(&[0xc2, 0x19, 0x7c, 0x5e, 0xff, 0x14, 0xe8, 0x8c]),
+                   Ok((&[][..], 151288809941952652)));
+        assert!(quic_varint(&[0x7b]).is_err());
+    }
+
+    #[test]
+    fn test_parse_initial_header() {
+        let buf = [
+            0xc3, 0x00, 0x00, 0x00, 0x01, // flags, version
+            0x04, 0x01, 0x02, 0x03, 0x04, // dcid
+            0x00, // scid
+            0x00, // token
+            0x05, // length
+            0xaa, 0xbb, 0xcc, 0xdd, 0xee, // pn and payload
+            0xc0, // next packet
+        ];
+        let (rem, header) = parse_quic_header(&buf, 0).unwrap();
+        assert_eq!(header.ty, QuicPacketType::Initial);
+        assert_eq!(header.version, QUIC_VERSION_1);
+        assert_eq!(header.dcid, vec![1, 2, 3, 4]);
+        assert!(header.scid.is_empty());
+        assert_eq!(header.pn_offset, 13);
+        assert_eq!(header.length, 5);
+        assert_eq!(header.packet_len(), 18);
+        assert_eq!(rem.len(), 6);
+    }
+
+    #[test]
+    fn test_parse_version_negotiation() {
+        let buf = [
+            0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x07,
+            0x00, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x1d,
+        ];
+        let (_, header) = parse_quic_header(&buf, 0).unwrap();
+        assert_eq!(header.ty, QuicPacketType::VersionNegotiation);
+        assert_eq!(header.scid, vec![7]);
+        assert_eq!(header.versions, vec![QUIC_VERSION_1, QUIC_VERSION_DRAFT29]);
+    }
+}
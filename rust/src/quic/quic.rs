@@ -0,0 +1,554 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::crypto::PacketKeys;
+use super::fingerprint::{parse_hello, ClientHello, Hello, ServerHello};
+use super::frames::{parse_frames, Frame};
+use super::parser::{parse_quic_header, QuicHeader, QuicPacketType, QUIC_VERSION_1,
+                    QUIC_VERSION_DRAFT29};
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std;
+use std::ffi::CString;
+
+declare_counters!(quic_stats, "app_layer.quic", {
+    initial,
+    handshake,
+    zero_rtt,
+    retry,
+    version_negotiation,
+    short,
+    hello,
+    decrypt_failed,
+    malformed,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum QuicEvent {
+    MalformedData,
+    FailedDecrypt,
+    CryptoFragmentLimit,
+    InvalidHandshake,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static QUIC_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static QUIC_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum size of the TLS handshake data reassembled per direction. The
+/// hellos are expected well below this.
+const QUIC_MAX_CRYPTO_LEN: usize = 65536;
+
+/// Maximum number of out of order CRYPTO frames kept per direction.
+const QUIC_MAX_CRYPTO_FRAGMENTS: usize = 32;
+
+/// Reassembly of the CRYPTO frames of one direction, which carry the TLS
+/// handshake.
+#[derive(Default)]
+struct CryptoStream {
+    /// Data received in order, from offset 0.
+    data: Vec<u8>,
+    /// Frames received ahead of `data`.
+    fragments: Vec<(u64, Vec<u8>)>,
+    /// Set once the hello is parsed, or failed to parse.
+    done: bool,
+}
+
+impl CryptoStream {
+    /// Add the data of a CRYPTO frame. Returns false if it goes over
+    /// the limits, in which case it is dropped.
+    fn add(&mut self, offset: u64, data: Vec<u8>) -> bool {
+        if offset.saturating_add(data.len() as u64) > QUIC_MAX_CRYPTO_LEN as u64
+            || self.fragments.len() >= QUIC_MAX_CRYPTO_FRAGMENTS {
+            return false;
+        }
+        self.fragments.push((offset, data));
+        self.fragments.sort_by_key(|(offset, _)| *offset);
+        let mut ahead = Vec::new();
+        for (offset, data) in self.fragments.drain(..) {
+            let offset = offset as usize;
+            if offset > self.data.len() {
+                ahead.push((offset as u64, data));
+            } else if offset + data.len() > self.data.len() {
+                let skip = self.data.len() - offset;
+                self.data.extend_from_slice(&data[skip..]);
+            }
+        }
+        self.fragments = ahead;
+        true
+    }
+}
+
+pub struct QuicState {
+    transactions: TxTable<QuicTransaction>,
+    /// Version and destination connection id of the first Initial packet
+    /// of the client, from which the Initial keys are derived.
+    version: u32,
+    initial_dcid: Option<Vec<u8>>,
+    keys_ts: Option<PacketKeys>,
+    keys_tc: Option<PacketKeys>,
+    crypto_ts: CryptoStream,
+    crypto_tc: CryptoStream,
+    /// Length of the connection ids chosen by the client and the server,
+    /// used as destination of the short header packets.
+    client_cid_len: usize,
+    server_cid_len: usize,
+}
+
+unsafe impl FfiType for QuicState {}
+
+#[derive(Debug)]
+pub struct QuicTransaction {
+    id: u64,
+    pub direction: Direction,
+    /// Header of the packet, None if it could not be parsed.
+    pub header: Option<QuicHeader>,
+    /// Frames of the decrypted Initial packets.
+    pub frames: Vec<Frame>,
+    pub client_hello: Option<ClientHello>,
+    pub server_hello: Option<ServerHello>,
+    /// JA4 fingerprint of the client hello.
+    pub ja4: Option<String>,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for QuicTransaction {}
+
+impl QuicTransaction {
+    fn new(id: u64, direction: Direction) -> QuicTransaction {
+        QuicTransaction {
+            id,
+            direction,
+            header: None,
+            frames: Vec::new(),
+            client_hello: None,
+            server_hello: None,
+            ja4: None,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: QuicEvent) {
+        stats_incr!(quic_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+}
+
+impl Drop for QuicTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for QuicTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = QuicEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+impl txtable::State for QuicState {
+    type Tx = QuicTransaction;
+
+    fn tx_table(&self) -> &TxTable<QuicTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<QuicTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl QuicState {
+    pub fn new() -> QuicState {
+        QuicState {
+            transactions: TxTable::with_limit(&QUIC_TX_LIMIT).with_gauges(&QUIC_GAUGES),
+            version: 0,
+            initial_dcid: None,
+            keys_ts: None,
+            keys_tc: None,
+            crypto_ts: CryptoStream::default(),
+            crypto_tc: CryptoStream::default(),
+            client_cid_len: 0,
+            server_cid_len: 0,
+        }
+    }
+
+    /// Parse a datagram, which can hold several long header packets
+    /// followed by a short header packet.
+    fn parse(&mut self, mut input: &[u8], direction: Direction) {
+        while !input.is_empty() {
+            let short_dcid_len = match direction {
+                Direction::ToServer => self.server_cid_len,
+                Direction::ToClient => self.client_cid_len,
+            };
+            let header = match parse_quic_header(input, short_dcid_len) {
+                Ok((_, header)) => header,
+                Err(_) => {
+                    stats_incr!(quic_stats::malformed);
+                    let mut tx = QuicTransaction::new(self.transactions.next_id(), direction);
+                    tx.set_event(QuicEvent::MalformedData);
+                    self.transactions.push(tx);
+                    return;
+                }
+            };
+            if header.ty == QuicPacketType::Short {
+                // protected by the 1-RTT keys, and extends to the end of
+                // the datagram
+                stats_incr!(quic_stats::short);
+                return;
+            }
+            let packet_len = header.packet_len();
+            let packet = &input[..packet_len];
+            input = &input[packet_len..];
+            self.handle_packet(packet, header, direction);
+        }
+    }
+
+    fn handle_packet(&mut self, packet: &[u8], header: QuicHeader, direction: Direction) {
+        let mut tx = QuicTransaction::new(self.transactions.next_id(), direction);
+        match direction {
+            Direction::ToServer => self.client_cid_len = header.scid.len(),
+            Direction::ToClient => self.server_cid_len = header.scid.len(),
+        }
+        match header.ty {
+            QuicPacketType::Initial => {
+                stats_incr!(quic_stats::initial);
+                self.handle_initial(&mut tx, packet, &header, direction);
+            }
+            QuicPacketType::Handshake => stats_incr!(quic_stats::handshake),
+            QuicPacketType::ZeroRtt => stats_incr!(quic_stats::zero_rtt),
+            QuicPacketType::Retry => {
+                stats_incr!(quic_stats::retry);
+                // the client starts over with the connection id chosen
+                // by the server, and new keys
+                self.initial_dcid = None;
+                self.keys_ts = None;
+                self.keys_tc = None;
+                self.crypto_ts = CryptoStream::default();
+            }
+            QuicPacketType::VersionNegotiation => stats_incr!(quic_stats::version_negotiation),
+            QuicPacketType::Short => {}
+        }
+        tx.header = Some(header);
+        self.transactions.push(tx);
+    }
+
+    fn handle_initial(&mut self, tx: &mut QuicTransaction, packet: &[u8], header: &QuicHeader,
+                      direction: Direction) {
+        if self.initial_dcid.is_none() {
+            if direction == Direction::ToClient {
+                // the keys can't be derived without the first Initial
+                // packet of the client
+                return;
+            }
+            self.version = header.version;
+            self.initial_dcid = Some(header.dcid.clone());
+        }
+        let (version, dcid) = (self.version, self.initial_dcid.as_ref().unwrap());
+        let keys = match direction {
+            Direction::ToServer => &mut self.keys_ts,
+            Direction::ToClient => &mut self.keys_tc,
+        };
+        if keys.is_none() {
+            *keys = PacketKeys::initial(version, dcid, direction);
+        }
+        let payload = match keys.as_ref().and_then(|k| k.decrypt(packet, header.pn_offset,
+                                                                  header.length)) {
+            Some(payload) => payload,
+            None => {
+                stats_incr!(quic_stats::decrypt_failed);
+                tx.set_event(QuicEvent::FailedDecrypt);
+                return;
+            }
+        };
+        let frames = match parse_frames(&payload) {
+            Ok((_, frames)) => frames,
+            Err(_) => {
+                stats_incr!(quic_stats::malformed);
+                tx.set_event(QuicEvent::MalformedData);
+                return;
+            }
+        };
+        let crypto = match direction {
+            Direction::ToServer => &mut self.crypto_ts,
+            Direction::ToClient => &mut self.crypto_tc,
+        };
+        for frame in &frames {
+            if let Frame::Crypto { offset, data } = frame {
+                if !crypto.done && !crypto.add(*offset, data.clone()) {
+                    tx.set_event(QuicEvent::CryptoFragmentLimit);
+                }
+            }
+        }
+        tx.frames = frames;
+        if crypto.done || crypto.data.is_empty() {
+            return;
+        }
+        match parse_hello(&crypto.data) {
+            Ok((_, hello)) => {
+                crypto.done = true;
+                match hello {
+                    Hello::Client(hello) => {
+                        stats_incr!(quic_stats::hello);
+                        tx.ja4 = Some(hello.ja4());
+                        tx.client_hello = Some(hello);
+                    }
+                    Hello::Server(hello) => {
+                        stats_incr!(quic_stats::hello);
+                        tx.server_hello = Some(hello);
+                    }
+                    Hello::Other => {}
+                }
+            }
+            Err(nom::Err::Incomplete(_)) => {}
+            Err(_) => {
+                crypto.done = true;
+                tx.set_event(QuicEvent::InvalidHandshake);
+            }
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn rs_quic_state_new(_orig_state: *mut std::os::raw::c_void,
+                                    _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = QuicState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_quic_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut QuicState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_quic_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut QuicState = cast_mut(state);
+    state.transactions.free(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_quic_parse_request(_flow: *const Flow,
+                                               state: *mut std::os::raw::c_void,
+                                               _pstate: *mut std::os::raw::c_void,
+                                               input: *const u8,
+                                               input_len: u32,
+                                               _data: *const std::os::raw::c_void,
+                                               _flags: u8) -> AppLayerResult {
+    let buf = build_slice!(input, input_len as usize);
+    let state: &mut QuicState = cast_mut(state);
+    state.parse(buf, Direction::ToServer);
+    AppLayerResult::ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_quic_parse_response(_flow: *const Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                _pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    let buf = build_slice!(input, input_len as usize);
+    let state: &mut QuicState = cast_mut(state);
+    state.parse(buf, Direction::ToClient);
+    AppLayerResult::ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_quic_state_get_tx(state: *mut std::os::raw::c_void,
+                                              tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut QuicState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_quic_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut QuicState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+#[no_mangle]
+pub extern "C" fn rs_quic_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void,
+                                                  _direction: u8) -> std::os::raw::c_int {
+    1
+}
+
+export_tx_detect_state!(rs_quic_state_get_tx_detect_state, rs_quic_state_set_tx_detect_state,
+                        QuicTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_quic_state_get_events(tx: *mut std::os::raw::c_void)
+                                                  -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut QuicTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_quic_get_tx_data, QuicTransaction);
+
+pub static mut ALPROTO_QUIC: AppProto = ALPROTO_UNKNOWN;
+
+/// Detect the Initial packets of the supported versions. The other long
+/// header packets can't start a flow, and the short header packets can't
+/// be told apart from random data.
+fn quic_probe(input: &[u8], _direction: Direction) -> ProbeResult {
+    match parse_quic_header(input, 0) {
+        Ok((_, header)) => {
+            if header.ty == QuicPacketType::Initial
+                && (header.version == QUIC_VERSION_1 || header.version == QUIC_VERSION_DRAFT29) {
+                ProbeResult::Found(unsafe { ALPROTO_QUIC })
+            } else {
+                ProbeResult::Failed
+            }
+        }
+        Err(nom::Err::Incomplete(_)) => ProbeResult::Unknown,
+        Err(_) => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_quic_probing_parser, quic_probe);
+
+fn quic_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    QUIC_GAUGES.log(js)?;
+    js.set_uint("events", quic_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+const PARSER_NAME: &[u8] = b"quic\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_quic_register_parser() {
+    quic_stats::register();
+    QUIC_TX_LIMIT.configure("quic", txtable::DEFAULT_MAX_TX);
+    introspect::register("quic", quic_introspect);
+    let default_port = CString::new("443").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: core::IPPROTO_UDP,
+        probe_ts: Some(rs_quic_probing_parser),
+        probe_tc: Some(rs_quic_probing_parser),
+        min_depth: 0,
+        max_depth: 16,
+        state_new: rs_quic_state_new,
+        state_free: rs_quic_state_free,
+        tx_free: rs_quic_state_tx_free,
+        parse_ts: rs_quic_parse_request,
+        parse_tc: rs_quic_parse_response,
+        get_tx_count: rs_quic_state_get_tx_count,
+        get_tx: rs_quic_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_quic_tx_get_alstate_progress,
+        get_de_state: rs_quic_state_get_tx_detect_state,
+        set_de_state: rs_quic_state_set_tx_detect_state,
+        get_events: Some(rs_quic_state_get_events),
+        get_eventinfo: Some(QuicEvent::get_event_info),
+        get_eventinfo_byid: Some(QuicEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<QuicState>),
+        get_tx_data: rs_quic_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<QuicState>),
+        flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("udp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_QUIC = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust quic parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for QUIC.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crypto_stream() {
+        let mut crypto = CryptoStream::default();
+        assert!(crypto.add(3, b"def".to_vec()));
+        assert!(crypto.data.is_empty());
+        assert!(crypto.add(0, b"abc".to_vec()));
+        assert_eq!(crypto.data, b"abcdef");
+        assert!(crypto.fragments.is_empty());
+        // overlapping retransmission
+        assert!(crypto.add(4, b"efgh".to_vec()));
+        assert_eq!(crypto.data, b"abcdefgh");
+        assert!(!crypto.add(QUIC_MAX_CRYPTO_LEN as u64, b"x".to_vec()));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let mut state = QuicState::new();
+        // long header cut in the connection ids
+        state.parse(&[0xc0, 0x00, 0x00, 0x00, 0x01, 0x08, 0x01], Direction::ToServer);
+        assert_eq!(state.transactions.tx_count(), 1);
+        let tx = state.transactions.get(0).unwrap();
+        assert!(tx.header.is_none());
+    }
+
+    #[test]
+    fn test_parse_version_negotiation() {
+        let mut state = QuicState::new();
+        let buf = [
+            0x80, 0x00, 0x00, 0x00, 0x00, // version 0
+            0x01, 0xaa, 0x01, 0xbb, // connection ids
+            0x00, 0x00, 0x00, 0x01, 0xff, 0x00, 0x00, 0x1d,
+        ];
+        state.parse(&buf, Direction::ToClient);
+        let tx = state.transactions.get(0).unwrap();
+        let header = tx.header.as_ref().unwrap();
+        assert_eq!(header.ty, QuicPacketType::VersionNegotiation);
+        assert_eq!(header.versions, vec![QUIC_VERSION_1, QUIC_VERSION_DRAFT29]);
+        assert_eq!(state.server_cid_len, 1);
+    }
+}
@@ -0,0 +1,130 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Sticky buffers on the connection request of the client.
+
+use super::rdp::{RdpTransaction, RdpTransactionItem, ALPROTO_RDP};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_RDP_COOKIE_BUFFER_ID: c_int = 0;
+static mut G_RDP_PROTOCOLS_BUFFER_ID: c_int = 0;
+
+unsafe fn set_buffer(data: Option<&String>, buf: *mut *const u8, len: *mut u32) -> bool {
+    match data {
+        Some(data) => {
+            *buf = data.as_ptr();
+            *len = data.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn rdp_tx_get_cookie(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut RdpTransaction = cast_mut(tx);
+    match tx.item {
+        RdpTransactionItem::X224ConnectionRequest(ref x224) => {
+            set_buffer(x224.cookie.as_ref().map(|c| &c.mstshash), buf, len)
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn rdp_tx_get_protocols(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut RdpTransaction = cast_mut(tx);
+    set_buffer(tx.requested_protocols.as_ref(), buf, len)
+}
+
+unsafe extern "C" fn rdp_cookie_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, rdp_tx_get_cookie)
+}
+
+unsafe extern "C" fn rdp_protocols_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, rdp_tx_get_protocols)
+}
+
+unsafe extern "C" fn rdp_cookie_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_RDP, G_RDP_COOKIE_BUFFER_ID)
+}
+
+unsafe extern "C" fn rdp_protocols_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_RDP, G_RDP_PROTOCOLS_BUFFER_ID)
+}
+
+/// Register the RDP keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "rdp.cookie",
+        desc: "sticky buffer to match on the mstshash cookie of the RDP connection request",
+        url: "/rules/rdp-keywords.html#rdp-cookie",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: rdp_cookie_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_RDP_COOKIE_BUFFER_ID = helper_buffer_mpm_register(
+            "rdp.cookie",
+            "rdp connection request cookie",
+            ALPROTO_RDP,
+            false,
+            true,
+            0,
+            rdp_cookie_get_data,
+        );
+    }
+
+    let kw = DetectKeyword {
+        name: "rdp.protocols",
+        desc: "sticky buffer to match on the security protocols requested by the RDP client",
+        url: "/rules/rdp-keywords.html#rdp-protocols",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: rdp_protocols_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_RDP_PROTOCOLS_BUFFER_ID = helper_buffer_mpm_register(
+            "rdp.protocols",
+            "rdp requested security protocols",
+            ALPROTO_RDP,
+            false,
+            true,
+            0,
+            rdp_protocols_get_data,
+        );
+    }
+}
@@ -48,6 +48,9 @@ fn log(tx: &RdpTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
 
         RdpTransactionItem::TlsCertificateChain(chain) => {
             js.set_string("event_type", "tls_handshake")?;
+            if let Some(ref security) = tx.security {
+                js.set_string("security", security.name())?;
+            }
             js.open_array("x509_serials")?;
             for blob in chain {
                 match parse_x509_der(&blob.data) {
@@ -90,6 +93,11 @@ fn x224_req_to_json(x224: &X224ConnectionRequest, js: &mut JsonBuilder) -> Resul
             }
             js.close()?;
         }
+        js.open_array("protocols")?;
+        for name in req.protocols.names() {
+            js.append_string(name)?;
+        }
+        js.close()?;
     }
 
     Ok(())
@@ -126,14 +134,7 @@ fn x224_conf_to_json(x224: &X224ConnectionConfirm, js: &mut JsonBuilder) -> Resu
                     js.close()?;
                 }
 
-                let protocol = match resp.protocol {
-                    Protocol::ProtocolRdp => "rdp",
-                    Protocol::ProtocolSsl => "ssl",
-                    Protocol::ProtocolHybrid => "hybrid",
-                    Protocol::ProtocolRdsTls => "rds_tls",
-                    Protocol::ProtocolHybridEx => "hybrid_ex",
-                };
-                js.set_string("protocol", protocol)?;
+                js.set_string("protocol", resp.protocol.name())?;
             }
 
             NegotiationFromServer::Failure(ref fail) => match fail.code {
@@ -19,6 +19,7 @@
 //!
 //! written by Zach Kelly <zach.kelly@lmco.com>
 
+pub mod detect;
 pub mod error;
 pub mod log;
 pub mod parser;
@@ -152,6 +152,18 @@ pub enum Protocol {
     ProtocolHybridEx = 0x8,
 }
 
+impl Protocol {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Protocol::ProtocolRdp => "rdp",
+            Protocol::ProtocolSsl => "ssl",
+            Protocol::ProtocolHybrid => "hybrid",
+            Protocol::ProtocolRdsTls => "rds_tls",
+            Protocol::ProtocolHybridEx => "hybrid_ex",
+        }
+    }
+}
+
 // rdp-spec, section 2.2.1.1.1
 bitflags! {
     pub struct ProtocolFlags: u32 {
@@ -163,6 +175,27 @@ bitflags! {
     }
 }
 
+impl ProtocolFlags {
+    /// Names of the protocols, "rdp" alone for the standard RDP security
+    pub fn names(&self) -> Vec<&'static str> {
+        if self.is_empty() {
+            return vec![Protocol::ProtocolRdp.name()];
+        }
+        let mut names = Vec::new();
+        for &(flag, ref protocol) in &[
+            (ProtocolFlags::PROTOCOL_SSL, Protocol::ProtocolSsl),
+            (ProtocolFlags::PROTOCOL_HYBRID, Protocol::ProtocolHybrid),
+            (ProtocolFlags::PROTOCOL_RDSTLS, Protocol::ProtocolRdsTls),
+            (ProtocolFlags::PROTOCOL_HYBRID_EX, Protocol::ProtocolHybridEx),
+        ] {
+            if self.contains(flag) {
+                names.push(protocol.name());
+            }
+        }
+        names
+    }
+}
+
 /// rdp-spec, section 2.2.1.2
 /// x.224-spec, section 13.3
 #[derive(Clone, Debug, PartialEq)]
@@ -247,6 +280,19 @@ pub struct McsConnectRequest {
     pub children: Vec<McsConnectRequestChild>,
 }
 
+impl McsConnectRequest {
+    /// names of the static virtual channels requested by the client
+    pub fn channels(&self) -> Vec<&str> {
+        let mut channels = Vec::new();
+        for child in &self.children {
+            if let McsConnectRequestChild::CsNet(net) = child {
+                channels.extend(net.channels.iter().map(|c| c.as_str()));
+            }
+        }
+        channels
+    }
+}
+
 /// variants that an mcs connection message can hold
 #[derive(Clone, Debug, PartialEq)]
 pub enum McsConnectRequestChild {
@@ -1244,6 +1290,18 @@ mod tests_core_49350 {
             parse_t123_tpkt(t123_bytes)
         );
     }
+
+    #[test]
+    fn test_mcs_connect_channels() {
+        match parse_t123_tpkt(&BYTES[TPKT_BEGIN..]) {
+            Ok((_, T123Tpkt { child: T123TpktChild::Data(X223Data {
+                child: X223DataChild::McsConnectRequest(mcs),
+            }) })) => {
+                assert_eq!(vec!["rdpdr", "rdpsnd", "drdynvc", "cliprdr"], mcs.channels());
+            }
+            _ => panic!("Result should have been a mcs connect request."),
+        }
+    }
 }
 
 #[cfg(test)]
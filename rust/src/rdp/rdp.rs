@@ -20,13 +20,23 @@
 //! RDP application layer
 
 use crate::applayer::*;
-use crate::core::{self, AppProto, DetectEngineState, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::core::{self, AppProto, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
 use crate::rdp::parser::*;
 use nom;
 use std;
 use tls_parser::{parse_tls_plaintext, TlsMessage, TlsMessageHandshake, TlsRecordType};
 
-static mut ALPROTO_RDP: AppProto = ALPROTO_UNKNOWN;
+pub static mut ALPROTO_RDP: AppProto = ALPROTO_UNKNOWN;
+
+#[derive(AppLayerEvent)]
+pub enum RdpEvent {
+    NlaNotNegotiated,
+    /// the client requested the clipboard redirection channel, cliprdr
+    ClipboardRedirection,
+    /// the client requested the device redirection channel, rdpdr, which
+    /// carries the redirected drives, printers, ports and smart cards
+    DeviceRedirection,
+}
 
 //
 // transactions
@@ -50,31 +60,41 @@ pub enum RdpTransactionItem {
 pub struct RdpTransaction {
     pub id: u64,
     pub item: RdpTransactionItem,
-    // managed by macros `export_tx_get_detect_state!` and `export_tx_set_detect_state!`
-    de_state: Option<*mut DetectEngineState>,
+    /// protocols requested by the client, comma separated
+    pub requested_protocols: Option<String>,
+    /// security protocol selected by the server, set on the tls certificate chain
+    pub security: Option<Protocol>,
+    // managed by macro `export_tx_detect_state!`
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
     tx_data: AppLayerTxData,
 }
 
+unsafe impl FfiType for RdpTransaction {}
+
 impl RdpTransaction {
     fn new(id: u64, item: RdpTransactionItem) -> Self {
         Self {
             id,
             item,
-            de_state: None,
+            requested_protocols: None,
+            security: None,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
             tx_data: AppLayerTxData::new(),
         }
     }
 
-    fn free(&mut self) {
-        if let Some(de_state) = self.de_state {
-            core::sc_detect_engine_state_free(de_state);
-        }
+    fn set_event(&mut self, event: RdpEvent) {
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
     }
 }
 
 impl Drop for RdpTransaction {
     fn drop(&mut self) {
-        self.free();
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
     }
 }
 
@@ -82,7 +102,7 @@ impl Drop for RdpTransaction {
 pub unsafe extern "C" fn rs_rdp_state_get_tx(
     state: *mut std::os::raw::c_void, tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, RdpState);
+    let state: &mut RdpState = cast_mut(state);
     match state.get_tx(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -95,7 +115,7 @@ pub unsafe extern "C" fn rs_rdp_state_get_tx(
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_rdp_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
-    let state = cast_pointer!(state, RdpState);
+    let state: &mut RdpState = cast_mut(state);
     return state.next_id;
 }
 
@@ -118,8 +138,12 @@ pub struct RdpState {
     transactions: Vec<RdpTransaction>,
     tls_parsing: bool,
     bypass_parsing: bool,
+    /// security protocol selected by the server in the connection confirm
+    selected_protocol: Option<Protocol>,
 }
 
+unsafe impl FfiType for RdpState {}
+
 impl RdpState {
     fn new() -> Self {
         Self {
@@ -127,6 +151,7 @@ impl RdpState {
             transactions: Vec::new(),
             tls_parsing: false,
             bypass_parsing: false,
+            selected_protocol: None,
         }
     }
 
@@ -204,8 +229,13 @@ impl RdpState {
                         match t123.child {
                             // X.224 connection request
                             T123TpktChild::X224ConnectionRequest(x224) => {
-                                let tx =
+                                let requested_protocols = x224
+                                    .negotiation_request
+                                    .as_ref()
+                                    .map(|req| req.protocols.names().join(","));
+                                let mut tx =
                                     self.new_tx(RdpTransactionItem::X224ConnectionRequest(x224));
+                                tx.requested_protocols = requested_protocols;
                                 self.transactions.push(tx);
                             }
 
@@ -213,8 +243,19 @@ impl RdpState {
                             T123TpktChild::Data(x223) => {
                                 match x223.child {
                                     X223DataChild::McsConnectRequest(mcs) => {
-                                        let tx =
+                                        let mut events = Vec::new();
+                                        for channel in mcs.channels() {
+                                            if channel.eq_ignore_ascii_case("cliprdr") {
+                                                events.push(RdpEvent::ClipboardRedirection);
+                                            } else if channel.eq_ignore_ascii_case("rdpdr") {
+                                                events.push(RdpEvent::DeviceRedirection);
+                                            }
+                                        }
+                                        let mut tx =
                                             self.new_tx(RdpTransactionItem::McsConnectRequest(mcs));
+                                        for event in events {
+                                            tx.set_event(event);
+                                        }
                                         self.transactions.push(tx);
                                     }
                                     // unknown message in X.223, skip
@@ -283,8 +324,9 @@ impl RdpState {
                                             data: cert.data.to_vec(),
                                         });
                                     }
-                                    let tx =
+                                    let mut tx =
                                         self.new_tx(RdpTransactionItem::TlsCertificateChain(chain));
+                                    tx.security = self.selected_protocol.clone();
                                     self.transactions.push(tx);
                                     self.bypass_parsing = true;
                                 }
@@ -316,8 +358,22 @@ impl RdpState {
                         match t123.child {
                             // X.224 connection confirm
                             T123TpktChild::X224ConnectionConfirm(x224) => {
-                                let tx =
+                                self.selected_protocol = match x224.negotiation_from_server {
+                                    Some(NegotiationFromServer::Response(ref response)) => {
+                                        Some(response.protocol.clone())
+                                    }
+                                    _ => None,
+                                };
+                                let mut tx =
                                     self.new_tx(RdpTransactionItem::X224ConnectionConfirm(x224));
+                                // network level authentication is done through CredSSP
+                                match self.selected_protocol {
+                                    Some(Protocol::ProtocolHybrid)
+                                    | Some(Protocol::ProtocolHybridEx) => {}
+                                    _ => {
+                                        tx.set_event(RdpEvent::NlaNotNegotiated);
+                                    }
+                                }
                                 self.transactions.push(tx);
                             }
 
@@ -385,7 +441,7 @@ pub extern "C" fn rs_rdp_state_free(state: *mut std::os::raw::c_void) {
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_rdp_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
-    let state = cast_pointer!(state, RdpState);
+    let state: &mut RdpState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -393,8 +449,7 @@ pub unsafe extern "C" fn rs_rdp_state_tx_free(state: *mut std::os::raw::c_void,
 // detection state
 //
 
-export_tx_get_detect_state!(rs_rdp_tx_get_detect_state, RdpTransaction);
-export_tx_set_detect_state!(rs_rdp_tx_set_detect_state, RdpTransaction);
+export_tx_detect_state!(rs_rdp_tx_get_detect_state, rs_rdp_tx_set_detect_state, RdpTransaction);
 
 //
 // probe
@@ -438,7 +493,7 @@ pub unsafe extern "C" fn rs_rdp_parse_ts(
     _flow: *const Flow, state: *mut std::os::raw::c_void, _pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, RdpState);
+    let state: &mut RdpState = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
     // attempt to parse bytes as `rdp` protocol
     return state.parse_ts(buf);
@@ -449,12 +504,20 @@ pub unsafe extern "C" fn rs_rdp_parse_tc(
     _flow: *const Flow, state: *mut std::os::raw::c_void, _pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, RdpState);
+    let state: &mut RdpState = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
     // attempt to parse bytes as `rdp` protocol
     return state.parse_tc(buf);
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn rs_rdp_state_get_events(
+    tx: *mut std::os::raw::c_void,
+) -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut RdpTransaction = cast_mut(tx);
+    return tx.events;
+}
+
 export_tx_data_get!(rs_rdp_get_tx_data, RdpTransaction);
 
 //
@@ -486,9 +549,9 @@ pub unsafe extern "C" fn rs_rdp_register_parser() {
         tx_get_progress: rs_rdp_tx_get_progress,
         get_de_state: rs_rdp_tx_get_detect_state,
         set_de_state: rs_rdp_tx_set_detect_state,
-        get_events: None,
-        get_eventinfo: None,
-        get_eventinfo_byid: None,
+        get_events: Some(rs_rdp_state_get_events),
+        get_eventinfo: Some(RdpEvent::get_event_info),
+        get_eventinfo_byid: Some(RdpEvent::get_event_info_by_id),
         localstorage_new: None,
         localstorage_free: None,
         get_files: None,
@@ -497,6 +560,7 @@ pub unsafe extern "C" fn rs_rdp_register_parser() {
         apply_tx_config: None,
         flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = std::ffi::CString::new("tcp").unwrap();
@@ -598,6 +662,45 @@ mod tests {
         assert_eq!(AppLayerResult::err(), state.parse_tc(buf));
     }
 
+    #[test]
+    fn test_parse_ts_requested_protocols() {
+        let buf: &[u8] = &[
+            0x03, 0x00, 0x00, 0x13, 0x0e, 0xe0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08,
+            0x00, 0x03, 0x00, 0x00, 0x00,
+        ];
+        let mut state = RdpState::new();
+        assert_eq!(AppLayerResult::ok(), state.parse_ts(buf));
+        assert_eq!(1, state.transactions.len());
+        assert_eq!(
+            Some(String::from("ssl,hybrid")),
+            state.transactions[0].requested_protocols
+        );
+    }
+
+    #[test]
+    fn test_parse_tc_selected_hybrid() {
+        let buf: &[u8] = &[
+            0x03, 0x00, 0x00, 0x13, 0x0e, 0xd0, 0x00, 0x00, 0x12, 0x34, 0x00, 0x02, 0x00, 0x08,
+            0x00, 0x02, 0x00, 0x00, 0x00,
+        ];
+        let mut state = RdpState::new();
+        assert_eq!(AppLayerResult::ok(), state.parse_tc(buf));
+        assert_eq!(1, state.transactions.len());
+        assert_eq!(Some(Protocol::ProtocolHybrid), state.selected_protocol);
+    }
+
+    #[test]
+    fn test_parse_tc_selected_ssl() {
+        let buf: &[u8] = &[
+            0x03, 0x00, 0x00, 0x13, 0x0e, 0xd0, 0x00, 0x00, 0x12, 0x34, 0x00, 0x02, 0x00, 0x08,
+            0x00, 0x01, 0x00, 0x00, 0x00,
+        ];
+        let mut state = RdpState::new();
+        assert_eq!(AppLayerResult::ok(), state.parse_tc(buf));
+        assert_eq!(1, state.transactions.len());
+        assert_eq!(Some(Protocol::ProtocolSsl), state.selected_protocol);
+    }
+
     #[test]
     fn test_state_new_tx() {
         let mut state = RdpState::new();
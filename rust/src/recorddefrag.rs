@@ -0,0 +1,281 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Reassembly of length prefixed records over TCP.
+//!
+//! Many protocols frame their messages on TCP as a fixed size length
+//! field followed by the payload (Kerberos, LDAP, TDS, ...). RecordDefrag
+//! takes the stream data as it is handed to the parser, and calls a
+//! handler for each complete record, buffering incomplete records
+//! between calls. Both the record size and the amount of buffered data
+//! are bounded, and the buffered data can be accounted against an
+//! app-layer memcap.
+
+use crate::memcap::AppLayerMemcap;
+
+/// Function returning the payload length of a record from its header.
+pub type RecordLenFn = fn(&[u8]) -> usize;
+
+#[derive(Debug, PartialEq)]
+pub enum RecordDefragError {
+    /// The record announced a length larger than the maximum record size.
+    RecordTooLarge(usize),
+    /// Buffering the incomplete record would exceed the buffer limit.
+    BufferTooLarge(usize),
+    /// Buffering the incomplete record would exceed the memcap.
+    MemcapReached,
+    /// The record handler rejected a record.
+    HandlerFailed,
+}
+
+pub struct RecordDefrag {
+    hdr_len: usize,
+    len_fn: RecordLenFn,
+    max_record_size: usize,
+    max_buffered: usize,
+    buf: Vec<u8>,
+    memcap: Option<&'static AppLayerMemcap>,
+    accounted: usize,
+}
+
+/// Record length as a 32 bit big endian integer.
+pub fn record_len_be_u32(hdr: &[u8]) -> usize {
+    ((hdr[0] as usize) << 24) | ((hdr[1] as usize) << 16) |
+        ((hdr[2] as usize) << 8) | (hdr[3] as usize)
+}
+
+/// Record length as a 16 bit big endian integer.
+pub fn record_len_be_u16(hdr: &[u8]) -> usize {
+    ((hdr[0] as usize) << 8) | (hdr[1] as usize)
+}
+
+impl RecordDefrag {
+    /// Create a new record reassembler for records with a header of
+    /// `hdr_len` bytes, from which `len_fn` returns the payload length.
+    pub fn new(hdr_len: usize, len_fn: RecordLenFn,
+            max_record_size: usize, max_buffered: usize) -> RecordDefrag {
+        RecordDefrag {
+            hdr_len: hdr_len,
+            len_fn: len_fn,
+            max_record_size: max_record_size,
+            max_buffered: max_buffered,
+            buf: Vec::new(),
+            memcap: None,
+            accounted: 0,
+        }
+    }
+
+    /// Records prefixed by a 32 bit big endian length.
+    pub fn new_be_u32(max_record_size: usize, max_buffered: usize) -> RecordDefrag {
+        RecordDefrag::new(4, record_len_be_u32, max_record_size, max_buffered)
+    }
+
+    /// Records prefixed by a 16 bit big endian length.
+    pub fn new_be_u16(max_record_size: usize, max_buffered: usize) -> RecordDefrag {
+        RecordDefrag::new(2, record_len_be_u16, max_record_size, max_buffered)
+    }
+
+    /// Account the buffered data against `memcap`.
+    pub fn set_memcap(&mut self, memcap: &'static AppLayerMemcap) {
+        self.release();
+        self.memcap = Some(memcap);
+    }
+
+    /// Number of bytes currently buffered.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Drop any partially received record.
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.buf.shrink_to_fit();
+        self.release();
+    }
+
+    fn release(&mut self) {
+        if let Some(memcap) = self.memcap {
+            memcap.sub(self.accounted as u64);
+        }
+        self.accounted = 0;
+    }
+
+    fn check_record_len(&self, len: usize) -> Result<(), RecordDefragError> {
+        if len > self.max_record_size {
+            return Err(RecordDefragError::RecordTooLarge(len));
+        }
+        Ok(())
+    }
+
+    fn buffer(&mut self, data: &[u8]) -> Result<(), RecordDefragError> {
+        if self.buf.len() + data.len() > self.max_buffered {
+            let len = self.buf.len() + data.len();
+            self.reset();
+            return Err(RecordDefragError::BufferTooLarge(len));
+        }
+        if let Some(memcap) = self.memcap {
+            if !memcap.try_add(data.len() as u64) {
+                self.reset();
+                return Err(RecordDefragError::MemcapReached);
+            }
+            self.accounted += data.len();
+        }
+        self.buf.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Feed stream data. `handler` is called with the payload of each
+    /// complete record, and returns false if the record could not be
+    /// processed, in which case an error is returned.
+    ///
+    /// On error the buffered data is discarded.
+    pub fn feed<F>(&mut self, input: &[u8], mut handler: F) -> Result<(), RecordDefragError>
+        where F: FnMut(&[u8]) -> bool
+    {
+        let mut input = input;
+
+        // first finish the record we have started to buffer
+        if !self.buf.is_empty() {
+            if self.buf.len() < self.hdr_len {
+                let n = std::cmp::min(self.hdr_len - self.buf.len(), input.len());
+                self.buffer(&input[..n])?;
+                input = &input[n..];
+                if self.buf.len() < self.hdr_len {
+                    return Ok(());
+                }
+            }
+            let rec_len = (self.len_fn)(&self.buf[..self.hdr_len]);
+            if let Err(e) = self.check_record_len(rec_len) {
+                self.reset();
+                return Err(e);
+            }
+            let total = self.hdr_len + rec_len;
+            let n = std::cmp::min(total - self.buf.len(), input.len());
+            self.buffer(&input[..n])?;
+            input = &input[n..];
+            if self.buf.len() < total {
+                return Ok(());
+            }
+            let record = std::mem::replace(&mut self.buf, Vec::new());
+            self.release();
+            if !handler(&record[self.hdr_len..]) {
+                return Err(RecordDefragError::HandlerFailed);
+            }
+        }
+
+        while !input.is_empty() {
+            if input.len() < self.hdr_len {
+                return self.buffer(input);
+            }
+            let rec_len = (self.len_fn)(&input[..self.hdr_len]);
+            self.check_record_len(rec_len)?;
+            let total = self.hdr_len + rec_len;
+            if input.len() < total {
+                if total > self.max_buffered {
+                    return Err(RecordDefragError::BufferTooLarge(total));
+                }
+                return self.buffer(input);
+            }
+            if !handler(&input[self.hdr_len..total]) {
+                return Err(RecordDefragError::HandlerFailed);
+            }
+            input = &input[total..];
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RecordDefrag {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(defrag: &mut RecordDefrag, input: &[u8], out: &mut Vec<Vec<u8>>)
+        -> Result<(), RecordDefragError>
+    {
+        defrag.feed(input, |r| { out.push(r.to_vec()); true })
+    }
+
+    #[test]
+    fn test_record_defrag_single() {
+        let mut d = RecordDefrag::new_be_u32(100, 100);
+        let mut out = Vec::new();
+        let buf = [0, 0, 0, 2, 0xaa, 0xbb, 0, 0, 0, 1, 0xcc];
+        assert_eq!(collect(&mut d, &buf, &mut out), Ok(()));
+        assert_eq!(out, vec![vec![0xaa, 0xbb], vec![0xcc]]);
+        assert_eq!(d.buffered(), 0);
+    }
+
+    #[test]
+    fn test_record_defrag_split() {
+        let buf = [0, 0, 0, 3, 1, 2, 3, 0, 0, 0, 1, 4];
+        // feed the records split at every possible offset
+        for i in 0..buf.len() {
+            let mut d = RecordDefrag::new_be_u32(100, 100);
+            let mut out = Vec::new();
+            assert_eq!(collect(&mut d, &buf[..i], &mut out), Ok(()));
+            assert_eq!(collect(&mut d, &buf[i..], &mut out), Ok(()));
+            assert_eq!(out, vec![vec![1, 2, 3], vec![4]]);
+            assert_eq!(d.buffered(), 0);
+        }
+    }
+
+    #[test]
+    fn test_record_defrag_limits() {
+        let mut d = RecordDefrag::new_be_u16(4, 100);
+        let mut out = Vec::new();
+        assert_eq!(collect(&mut d, &[0, 5, 1], &mut out),
+                   Err(RecordDefragError::RecordTooLarge(5)));
+
+        let mut d = RecordDefrag::new_be_u16(100, 4);
+        assert_eq!(collect(&mut d, &[0, 5, 1], &mut out),
+                   Err(RecordDefragError::BufferTooLarge(7)));
+        assert_eq!(d.buffered(), 0);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_record_defrag_memcap() {
+        static MEMCAP: AppLayerMemcap = AppLayerMemcap::new();
+        MEMCAP.set_memcap(6);
+        let mut d = RecordDefrag::new_be_u16(100, 100);
+        d.set_memcap(&MEMCAP);
+        let mut out = Vec::new();
+        assert_eq!(collect(&mut d, &[0, 8, 1, 2], &mut out), Ok(()));
+        assert_eq!(MEMCAP.get_memuse(), 4);
+        assert_eq!(collect(&mut d, &[3, 4, 5], &mut out),
+                   Err(RecordDefragError::MemcapReached));
+        assert_eq!(MEMCAP.get_memuse(), 0);
+        assert_eq!(collect(&mut d, &[0, 1, 1], &mut out), Ok(()));
+        assert_eq!(collect(&mut d, &[0, 2, 1], &mut out), Ok(()));
+        assert_eq!(MEMCAP.get_memuse(), 3);
+        drop(d);
+        assert_eq!(MEMCAP.get_memuse(), 0);
+    }
+
+    #[test]
+    fn test_record_defrag_handler_error() {
+        let mut d = RecordDefrag::new_be_u16(100, 100);
+        let r = d.feed(&[0, 1, 1], |_| false);
+        assert_eq!(r, Err(RecordDefragError::HandlerFailed));
+    }
+}
@@ -17,9 +17,17 @@
 
 // Author: Frank Honza <frank.honza@dcso.de>
 
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
 use crate::rfb::rfb::*;
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 
+static mut G_RFB_SECTYPE_NAME_BUFFER_ID: c_int = 0;
+
 #[no_mangle]
 pub unsafe extern "C" fn rs_rfb_tx_get_name(
     tx: &mut RFBTransaction,
@@ -67,4 +75,50 @@ pub unsafe extern "C" fn rs_rfb_tx_get_secresult(
     }
 
     return 0;
-}
\ No newline at end of file
+}
+
+unsafe extern "C" fn rfb_tx_get_sectype_name(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut RFBTransaction = cast_mut(tx);
+    match tx.chosen_security_type.and_then(security_type_name) {
+        Some(name) => {
+            *buf = name.as_ptr();
+            *len = name.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn rfb_sectype_name_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, rfb_tx_get_sectype_name)
+}
+
+unsafe extern "C" fn rfb_sectype_name_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_RFB, G_RFB_SECTYPE_NAME_BUFFER_ID)
+}
+
+/// Register the RFB keywords implemented in Rust.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "rfb.sectype_name",
+        desc: "sticky buffer to match on the name of the RFB security type",
+        url: "/rules/rfb-keywords.html#rfb-sectype-name",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: rfb_sectype_name_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        // the security type is set by the server or selected by the client
+        G_RFB_SECTYPE_NAME_BUFFER_ID = helper_buffer_mpm_register(
+            "rfb.sectype_name", "rfb security type name", ALPROTO_RFB, true, true, 1,
+            rfb_sectype_name_get_data);
+    }
+}
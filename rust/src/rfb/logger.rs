@@ -20,6 +20,7 @@
 use std;
 use std::fmt::Write;
 use super::rfb::{RFBState, RFBTransaction};
+use crate::applayer::cast_mut;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 
 fn log_rfb(tx: &RFBTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
@@ -68,7 +69,7 @@ fn log_rfb(tx: &RFBTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
     if let Some(security_result) = &tx.tc_security_result {
         let _ = match security_result.status {
             0 => js.set_string("security_result", "OK")?,
-            1 => js.set_string("security-result", "FAIL")?,
+            1 => js.set_string("security_result", "FAIL")?,
             2 => js.set_string("security_result", "TOOMANY")?,
             _ => js.set_string("security_result",
                     &format!("UNKNOWN ({})", security_result.status))?,
@@ -116,6 +117,6 @@ fn log_rfb(tx: &RFBTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
 pub unsafe extern "C" fn rs_rfb_logger_log(_state: &mut RFBState,
                                     tx: *mut std::os::raw::c_void,
                                     js: &mut JsonBuilder) -> bool {
-    let tx = cast_pointer!(tx, RFBTransaction);
+    let tx: &mut RFBTransaction = cast_mut(tx);
     log_rfb(tx, js).is_ok()
 }
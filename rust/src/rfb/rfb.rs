@@ -25,7 +25,47 @@ use crate::applayer::*;
 use nom;
 use super::parser;
 
-static mut ALPROTO_RFB: AppProto = ALPROTO_UNKNOWN;
+pub static mut ALPROTO_RFB: AppProto = ALPROTO_UNKNOWN;
+
+#[derive(AppLayerEvent)]
+pub enum RFBEvent {
+    UnimplementedSecurityType,
+    UnknownSecurityResult,
+    AuthFailure,
+    NoneAuthAccepted,
+    RepeatedAuthFailures,
+}
+
+/// Security types of RFB (RFC 6143 and the IANA registry)
+pub const RFB_SECURITY_TYPE_INVALID: u32 = 0;
+pub const RFB_SECURITY_TYPE_NONE: u32 = 1;
+pub const RFB_SECURITY_TYPE_VNC: u32 = 2;
+
+/// Security results
+pub const RFB_SECURITY_RESULT_OK: u32 = 0;
+pub const RFB_SECURITY_RESULT_FAIL: u32 = 1;
+pub const RFB_SECURITY_RESULT_TOOMANY: u32 = 2;
+
+/// Name of a security type, for the rfb.sectype_name buffer.
+pub fn security_type_name(security_type: u32) -> Option<&'static str> {
+    let name = match security_type {
+        0 => "invalid",
+        1 => "none",
+        2 => "vnc",
+        5 => "ra2",
+        6 => "ra2ne",
+        16 => "tight",
+        17 => "ultra",
+        18 => "tls",
+        19 => "vencrypt",
+        20 => "sasl",
+        21 => "md5",
+        22 => "xvp",
+        30 => "ard",
+        _ => return None,
+    };
+    Some(name)
+}
 
 pub struct RFBTransaction {
     tx_id: u64,
@@ -44,11 +84,13 @@ pub struct RFBTransaction {
     pub tc_failure_reason: Option<parser::FailureReason>,
     pub tc_server_init: Option<parser::ServerInit>,
 
-    de_state: Option<*mut core::DetectEngineState>,
+    de_state: DetectStateHolder,
     events: *mut core::AppLayerDecoderEvents,
     tx_data: applayer::AppLayerTxData,
 }
 
+unsafe impl FfiType for RFBTransaction {}
+
 impl RFBTransaction {
     pub fn new() -> RFBTransaction {
         RFBTransaction {
@@ -68,7 +110,7 @@ impl RFBTransaction {
             tc_failure_reason: None,
             tc_server_init: None,
 
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: applayer::AppLayerTxData::new(),
         }
@@ -78,9 +120,10 @@ impl RFBTransaction {
         if self.events != std::ptr::null_mut() {
             core::sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        if let Some(state) = self.de_state {
-            core::sc_detect_engine_state_free(state);
-        }
+    }
+
+    fn set_event(&mut self, event: RFBEvent) {
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
     }
 }
 
@@ -93,15 +136,22 @@ impl Drop for RFBTransaction {
 pub struct RFBState {
     tx_id: u64,
     transactions: Vec<RFBTransaction>,
-    state: parser::RFBGlobalState
+    state: parser::RFBGlobalState,
+    /// Minor version of the protocol chosen by the client. From 3.8 on,
+    /// a security result is also sent for the None security type, and a
+    /// failure reason follows a failed security result.
+    minor_version: u32,
 }
 
+unsafe impl FfiType for RFBState {}
+
 impl RFBState {
     pub fn new() -> Self {
         Self {
             tx_id: 0,
             transactions: Vec::new(),
-            state: parser::RFBGlobalState::TCServerProtocolVersion
+            state: parser::RFBGlobalState::TCServerProtocolVersion,
+            minor_version: 0,
         }
     }
 
@@ -148,6 +198,22 @@ impl RFBState {
         return None;
     }
 
+    fn set_event(&mut self, event: RFBEvent) {
+        if let Some(tx) = self.get_current_tx() {
+            tx.set_event(event);
+        }
+    }
+
+    /// The security handshake passed: the client init is next.
+    fn security_accepted(&mut self) {
+        self.state = parser::RFBGlobalState::TSClientInit;
+        if let Some(tx) = self.get_current_tx() {
+            if tx.chosen_security_type == Some(RFB_SECURITY_TYPE_NONE) {
+                tx.set_event(RFBEvent::NoneAuthAccepted);
+            }
+        }
+    }
+
     fn parse_request(&mut self, input: &[u8]) -> AppLayerResult {
         // We're not interested in empty requests.
         if input.len() == 0 {
@@ -168,6 +234,7 @@ impl RFBState {
                             consumed += current.len() - rem.len();
                             current = rem;
 
+                            self.minor_version = request.minor.parse::<u32>().unwrap_or(0);
                             if request.major == "003" && request.minor == "003" {
                                 // in version 3.3 the server decided security type
                                 self.state = parser::RFBGlobalState::TCServerSecurityType;
@@ -182,7 +249,7 @@ impl RFBState {
                             }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -195,22 +262,31 @@ impl RFBState {
                             consumed += current.len() - rem.len();
                             current = rem;
 
-                            let chosen_security_type = request.security_type;
-                            match chosen_security_type {
-                                2 => self.state = parser::RFBGlobalState::TCVncChallenge,
-                                1 => self.state = parser::RFBGlobalState::TSClientInit,
-                                _ => return AppLayerResult::err(),
-                            }
-
+                            let chosen_security_type = request.security_type as u32;
                             if let Some(current_transaction) = self.get_current_tx() {
                                 current_transaction.ts_security_type_selection = Some(request);
-                                current_transaction.chosen_security_type = Some(chosen_security_type as u32);
+                                current_transaction.chosen_security_type = Some(chosen_security_type);
                             } else {
                                 return AppLayerResult::err();
                             }
+
+                            match chosen_security_type {
+                                RFB_SECURITY_TYPE_VNC => self.state = parser::RFBGlobalState::TCVncChallenge,
+                                RFB_SECURITY_TYPE_NONE => {
+                                    if self.minor_version >= 8 {
+                                        self.state = parser::RFBGlobalState::TCSecurityResult;
+                                    } else {
+                                        self.security_accepted();
+                                    }
+                                }
+                                _ => {
+                                    self.set_event(RFBEvent::UnimplementedSecurityType);
+                                    return AppLayerResult::err();
+                                }
+                            }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -232,7 +308,7 @@ impl RFBState {
                             }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -254,7 +330,7 @@ impl RFBState {
                             }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -308,7 +384,7 @@ impl RFBState {
                             }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -338,7 +414,7 @@ impl RFBState {
                             }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -354,25 +430,25 @@ impl RFBState {
 
                             let chosen_security_type = request.security_type;
                             SCLogDebug!("chosen_security_type: {}", chosen_security_type);
-                            match chosen_security_type {
-                                0 => self.state = parser::RFBGlobalState::TCFailureReason,
-                                1 => self.state = parser::RFBGlobalState::TSClientInit,
-                                2 => self.state = parser::RFBGlobalState::TCVncChallenge,
-                                _ => {
-                                    // TODO Event unknown security type
-                                    return AppLayerResult::err();
-                                }
-                            }
-
                             if let Some(current_transaction) = self.get_current_tx() {
                                 current_transaction.tc_server_security_type = Some(request);
                                 current_transaction.chosen_security_type = Some(chosen_security_type);
                             } else {
                                 return AppLayerResult::err();
                             }
+
+                            match chosen_security_type {
+                                RFB_SECURITY_TYPE_INVALID => self.state = parser::RFBGlobalState::TCFailureReason,
+                                RFB_SECURITY_TYPE_NONE => self.security_accepted(),
+                                RFB_SECURITY_TYPE_VNC => self.state = parser::RFBGlobalState::TCVncChallenge,
+                                _ => {
+                                    self.set_event(RFBEvent::UnimplementedSecurityType);
+                                    return AppLayerResult::err();
+                                }
+                            }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -394,7 +470,7 @@ impl RFBState {
                             }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -407,22 +483,44 @@ impl RFBState {
                             consumed += current.len() - rem.len();
                             current = rem;
 
-                            if request.status == 0 {
-                                self.state = parser::RFBGlobalState::TSClientInit;
+                            let status = request.status;
+                            let minor_version = self.minor_version;
+                            if let Some(current_transaction) = self.get_current_tx() {
+                                current_transaction.tc_security_result = Some(request);
+                                match status {
+                                    RFB_SECURITY_RESULT_OK => {}
+                                    RFB_SECURITY_RESULT_FAIL | RFB_SECURITY_RESULT_TOOMANY => {
+                                        current_transaction.set_event(RFBEvent::AuthFailure);
+                                        if status == RFB_SECURITY_RESULT_TOOMANY {
+                                            current_transaction.set_event(RFBEvent::RepeatedAuthFailures);
+                                        }
+                                        // before 3.8, the server closes the connection
+                                        // without a reason
+                                        if minor_version < 8 {
+                                            current_transaction.complete = true;
+                                        }
+                                    }
+                                    _ => current_transaction.set_event(RFBEvent::UnknownSecurityResult),
+                                }
+                            } else {
+                                return AppLayerResult::err();
+                            }
 
-                                if let Some(current_transaction) = self.get_current_tx() {
-                                    current_transaction.tc_security_result = Some(request);
-                                } else {
+                            match status {
+                                RFB_SECURITY_RESULT_OK => self.security_accepted(),
+                                RFB_SECURITY_RESULT_FAIL | RFB_SECURITY_RESULT_TOOMANY
+                                    if minor_version >= 8 =>
+                                {
+                                    self.state = parser::RFBGlobalState::TCFailureReason;
+                                }
+                                _ => {
+                                    // the handshake is over
                                     return AppLayerResult::err();
                                 }
-                            } else if request.status == 1 {
-                                self.state = parser::RFBGlobalState::TCFailureReason;
-                            } else {
-                                // TODO: Event: unknown security result value
                             }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -434,13 +532,14 @@ impl RFBState {
                         Ok((_rem, request)) => {
                             if let Some(current_transaction) = self.get_current_tx() {
                                 current_transaction.tc_failure_reason = Some(request);
+                                current_transaction.complete = true;
                             } else {
                                 return AppLayerResult::err();
                             }
                             return AppLayerResult::err();
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -464,7 +563,7 @@ impl RFBState {
                             }
                         }
                         Err(nom::Err::Incomplete(_)) => {
-                            return AppLayerResult::incomplete(consumed as u32, (current.len() + 1) as u32);
+                            return AppLayerResult::incomplete_from(input.len(), consumed, current.len() + 1);
                         }
                         Err(_) => {
                             return AppLayerResult::err();
@@ -507,14 +606,7 @@ impl RFBState {
 
 // C exports.
 
-export_tx_get_detect_state!(
-    rs_rfb_tx_get_detect_state,
-    RFBTransaction
-);
-export_tx_set_detect_state!(
-    rs_rfb_tx_set_detect_state,
-    RFBTransaction
-);
+export_tx_detect_state!(rs_rfb_tx_get_detect_state, rs_rfb_tx_set_detect_state, RFBTransaction);
 
 #[no_mangle]
 pub extern "C" fn rs_rfb_state_new(_orig_state: *mut std::os::raw::c_void, _orig_proto: AppProto) -> *mut std::os::raw::c_void {
@@ -534,7 +626,7 @@ pub unsafe extern "C" fn rs_rfb_state_tx_free(
     state: *mut std::os::raw::c_void,
     tx_id: u64,
 ) {
-    let state = cast_pointer!(state, RFBState);
+    let state: &mut RFBState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -548,7 +640,7 @@ pub unsafe extern "C" fn rs_rfb_parse_request(
     _data: *const std::os::raw::c_void,
     _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, RFBState);
+    let state: &mut RFBState = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
     return state.parse_request(buf);
 }
@@ -563,7 +655,7 @@ pub unsafe extern "C" fn rs_rfb_parse_response(
     _data: *const std::os::raw::c_void,
     _flags: u8,
 ) -> AppLayerResult {
-    let state = cast_pointer!(state, RFBState);
+    let state: &mut RFBState = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
     return state.parse_response(buf);
 }
@@ -573,7 +665,7 @@ pub unsafe extern "C" fn rs_rfb_state_get_tx(
     state: *mut std::os::raw::c_void,
     tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, RFBState);
+    let state: &mut RFBState = cast_mut(state);
     match state.get_tx(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -588,7 +680,7 @@ pub unsafe extern "C" fn rs_rfb_state_get_tx(
 pub unsafe extern "C" fn rs_rfb_state_get_tx_count(
     state: *mut std::os::raw::c_void,
 ) -> u64 {
-    let state = cast_pointer!(state, RFBState);
+    let state: &mut RFBState = cast_mut(state);
     return state.tx_id;
 }
 
@@ -597,7 +689,7 @@ pub unsafe extern "C" fn rs_rfb_tx_get_alstate_progress(
     tx: *mut std::os::raw::c_void,
     _direction: u8,
 ) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, RFBTransaction);
+    let tx: &mut RFBTransaction = cast_mut(tx);
     if tx.complete {
         return 1;
     }
@@ -608,7 +700,7 @@ pub unsafe extern "C" fn rs_rfb_tx_get_alstate_progress(
 pub unsafe extern "C" fn rs_rfb_state_get_events(
     tx: *mut std::os::raw::c_void
 ) -> *mut core::AppLayerDecoderEvents {
-    let tx = cast_pointer!(tx, RFBTransaction);
+    let tx: &mut RFBTransaction = cast_mut(tx);
     return tx.events;
 }
 
@@ -621,7 +713,7 @@ pub unsafe extern "C" fn rs_rfb_state_get_tx_iterator(
     _max_tx_id: u64,
     istate: &mut u64,
 ) -> applayer::AppLayerGetTxIterTuple {
-    let state = cast_pointer!(state, RFBState);
+    let state: &mut RFBState = cast_mut(state);
     match state.tx_iterator(min_tx_id, istate) {
         Some((tx, out_tx_id, has_next)) => {
             let c_tx = tx as *const _ as *mut _;
@@ -666,8 +758,8 @@ pub unsafe extern "C" fn rs_rfb_register_parser() {
         get_de_state: rs_rfb_tx_get_detect_state,
         set_de_state: rs_rfb_tx_set_detect_state,
         get_events: Some(rs_rfb_state_get_events),
-        get_eventinfo: None,
-        get_eventinfo_byid: None,
+        get_eventinfo: Some(RFBEvent::get_event_info),
+        get_eventinfo_byid: Some(RFBEvent::get_event_info_by_id),
         localstorage_new: None,
         localstorage_free: None,
         get_files: None,
@@ -676,6 +768,7 @@ pub unsafe extern "C" fn rs_rfb_register_parser() {
         apply_tx_config: None,
         flags: 0,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
@@ -699,3 +792,92 @@ pub unsafe extern "C" fn rs_rfb_register_parser() {
         SCLogDebug!("Protocol detector and parser disabled for RFB.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Direction;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_rfb_state_new,
+            state_free: rs_rfb_state_free,
+            parse_ts: rs_rfb_parse_request,
+            parse_tc: rs_rfb_parse_response,
+            get_tx_count: rs_rfb_state_get_tx_count,
+            get_tx: rs_rfb_state_get_tx,
+            tx_free: rs_rfb_state_tx_free,
+        })
+    }
+
+    fn handshake(h: &mut ParserHarness, version: &[u8], security_type: u8) {
+        assert!(h.feed(Direction::ToClient, version));
+        assert!(h.feed(Direction::ToServer, version));
+        assert!(h.feed(Direction::ToClient, &[2, 1, 2]));
+        assert!(h.feed(Direction::ToServer, &[security_type]));
+    }
+
+    #[test]
+    fn test_rfb_vnc_auth_failure() {
+        let mut h = harness();
+        handshake(&mut h, b"RFB 003.008\n", 2);
+        assert!(h.feed(Direction::ToClient, &[0x11; 16]));
+        assert!(h.feed(Direction::ToServer, &[0x22; 16]));
+        assert!(h.feed(Direction::ToClient, &[0, 0, 0, 1]));
+        // parsing stops after the failure reason
+        assert!(!h.feed(Direction::ToClient, b"\x00\x00\x00\x16Authentication failure"));
+
+        let tx = h.get_tx::<RFBTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.chosen_security_type, Some(RFB_SECURITY_TYPE_VNC));
+        assert_eq!(tx.tc_security_result.as_ref().map(|r| r.status), Some(RFB_SECURITY_RESULT_FAIL));
+        assert_eq!(
+            tx.tc_failure_reason.as_ref().map(|r| r.reason_string.as_str()),
+            Some("Authentication failure")
+        );
+    }
+
+    #[test]
+    fn test_rfb_none_auth() {
+        // from 3.8, the server sends a security result for None
+        let mut h = harness();
+        handshake(&mut h, b"RFB 003.008\n", 1);
+        assert!(h.feed(Direction::ToClient, &[0, 0, 0, 0]));
+        assert!(h.feed(Direction::ToServer, &[1]));
+        assert_eq!(h.state::<RFBState>().state.to_string(), "TCServerInit");
+        assert_eq!(
+            h.get_tx::<RFBTransaction>(0).unwrap().tc_security_result.as_ref().map(|r| r.status),
+            Some(RFB_SECURITY_RESULT_OK)
+        );
+
+        // before 3.8, the client init follows the selection
+        let mut h = harness();
+        handshake(&mut h, b"RFB 003.007\n", 1);
+        assert!(h.feed(Direction::ToServer, &[1]));
+        assert_eq!(h.state::<RFBState>().state.to_string(), "TCServerInit");
+    }
+
+    #[test]
+    fn test_rfb_too_many_failures() {
+        // RFB 3.3, where the server chooses the security type
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"RFB 003.003\n"));
+        assert!(h.feed(Direction::ToServer, b"RFB 003.003\n"));
+        assert!(h.feed(Direction::ToClient, &[0, 0, 0, 2]));
+        assert!(h.feed(Direction::ToClient, &[0x11; 16]));
+        assert!(h.feed(Direction::ToServer, &[0x22; 16]));
+        assert!(!h.feed(Direction::ToClient, &[0, 0, 0, 2]));
+
+        let tx = h.get_tx::<RFBTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.tc_security_result.as_ref().map(|r| r.status), Some(RFB_SECURITY_RESULT_TOOMANY));
+    }
+
+    #[test]
+    fn test_rfb_sectype_name() {
+        assert_eq!(security_type_name(RFB_SECURITY_TYPE_VNC), Some("vnc"));
+        assert_eq!(security_type_name(19), Some("vencrypt"));
+        assert_eq!(security_type_name(200), None);
+    }
+}
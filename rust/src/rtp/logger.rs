@@ -0,0 +1,73 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::payload_type_name;
+use super::rtp::RtpTransaction;
+use crate::core::Direction;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log(tx: &RtpTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("rtp")?;
+    js.set_string("ssrc", &format!("{:08x}", tx.ssrc))?;
+    js.set_string("direction", match tx.direction {
+        Direction::ToServer => "to_server",
+        Direction::ToClient => "to_client",
+    })?;
+    if let Some(pt) = tx.payload_type {
+        js.set_uint("payload_type", pt as u64)?;
+        if let Some(name) = payload_type_name(pt) {
+            js.set_string("encoding", name)?;
+        }
+        if tx.payload_type_changes > 0 {
+            js.set_uint("payload_type_changes", tx.payload_type_changes)?;
+        }
+        js.set_uint("packets", tx.stats.received)?;
+        js.set_uint("bytes", tx.stats.octets)?;
+        js.set_uint("expected", tx.stats.expected())?;
+        js.set_uint("lost", tx.stats.lost())?;
+        js.set_uint("misordered", tx.stats.misordered)?;
+        if tx.stats.sequence_jumps > 0 {
+            js.set_uint("sequence_jumps", tx.stats.sequence_jumps)?;
+        }
+        if let Some(jitter) = tx.jitter_ms() {
+            js.set_float("jitter_ms", jitter)?;
+        }
+    }
+    if tx.sender_reports > 0 || tx.receiver_reports > 0 || tx.cname.is_some() || tx.goodbye {
+        js.open_object("rtcp")?;
+        js.set_uint("sender_reports", tx.sender_reports)?;
+        js.set_uint("receiver_reports", tx.receiver_reports)?;
+        if let Some(cname) = &tx.cname {
+            js.set_string("cname", cname)?;
+        }
+        js.set_bool("bye", tx.goodbye)?;
+        js.close()?;
+    }
+    if let Some(sip) = &tx.sip {
+        js.open_object("sip")?;
+        js.set_string("call_id", &sip.call_id)?;
+        js.set_string("media", &sip.media)?;
+        js.close()?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_rtp_to_json(tx: &mut RtpTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
@@ -0,0 +1,27 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! RTP and RTCP application layer, parser and logger module.
+//!
+//! A transaction is a media stream, identified by its SSRC. It
+//! accumulates the packet, loss and jitter statistics of the stream, and
+//! is logged once the source said goodbye with a RTCP BYE, or once it
+//! has been idle for the stream timeout.
+
+pub mod logger;
+pub mod parser;
+pub mod rtp;
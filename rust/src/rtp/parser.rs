@@ -0,0 +1,286 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! RTP and RTCP packet decoding (RFC 3550). Both may share a flow
+//! (RFC 5761): the second byte of a RTCP packet, its packet type, falls
+//! in the range of the RTP payload types 64 to 95 with the marker bit
+//! set, which are not used by RTP.
+
+pub const RTP_VERSION: u8 = 2;
+
+/// Length of the fixed header of a RTP packet.
+pub const RTP_HEADER_LEN: usize = 12;
+
+pub const RTCP_SR: u8 = 200;
+pub const RTCP_RR: u8 = 201;
+pub const RTCP_SDES: u8 = 202;
+pub const RTCP_BYE: u8 = 203;
+pub const RTCP_APP: u8 = 204;
+
+/// SDES item carrying the canonical name of a source.
+const SDES_CNAME: u8 = 1;
+
+#[derive(Debug, PartialEq)]
+pub struct RtpHeader {
+    pub marker: bool,
+    pub payload_type: u8,
+    pub sequence: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    /// Length of the payload, without the CSRCs, the header extension
+    /// and the padding.
+    pub payload_len: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RtcpPacket {
+    SenderReport { ssrc: u32 },
+    ReceiverReport { ssrc: u32 },
+    /// The canonical names of the sources described.
+    SourceDescription { cnames: Vec<(u32, String)> },
+    Goodbye { ssrcs: Vec<u32> },
+    /// APP and the feedback messages, only counted.
+    Other { packet_type: u8 },
+}
+
+fn be_u16(input: &[u8]) -> u16 {
+    (input[0] as u16) << 8 | input[1] as u16
+}
+
+fn be_u32(input: &[u8]) -> u32 {
+    (input[0] as u32) << 24 | (input[1] as u32) << 16 | (input[2] as u32) << 8 | input[3] as u32
+}
+
+/// Whether a packet of the flow is RTCP rather than RTP.
+pub fn is_rtcp(input: &[u8]) -> bool {
+    input.len() >= 2 && input[1] >= 192 && input[1] <= 223
+}
+
+/// Parse the header of a RTP packet.
+pub fn parse_rtp(input: &[u8]) -> Option<RtpHeader> {
+    if input.len() < RTP_HEADER_LEN || input[0] >> 6 != RTP_VERSION {
+        return None;
+    }
+    let padding = input[0] & 0x20 != 0;
+    let extension = input[0] & 0x10 != 0;
+    let csrc_count = (input[0] & 0x0f) as usize;
+    let mut header_len = RTP_HEADER_LEN + 4 * csrc_count;
+    if extension {
+        if input.len() < header_len + 4 {
+            return None;
+        }
+        header_len += 4 + 4 * be_u16(&input[header_len + 2..]) as usize;
+    }
+    if input.len() < header_len {
+        return None;
+    }
+    let mut payload_len = input.len() - header_len;
+    if padding {
+        let padding_len = *input.last()? as usize;
+        if padding_len == 0 || padding_len > payload_len {
+            return None;
+        }
+        payload_len -= padding_len;
+    }
+    Some(RtpHeader {
+        marker: input[1] & 0x80 != 0,
+        payload_type: input[1] & 0x7f,
+        sequence: be_u16(&input[2..]),
+        timestamp: be_u32(&input[4..]),
+        ssrc: be_u32(&input[8..]),
+        payload_len,
+    })
+}
+
+/// The canonical names of the chunks of a SDES packet. Each chunk is a
+/// SSRC followed by items ending with a null item, padded to 32 bits.
+fn parse_sdes(mut input: &[u8], count: usize) -> Option<Vec<(u32, String)>> {
+    let mut cnames = Vec::new();
+    for _ in 0..count {
+        if input.len() < 4 {
+            return None;
+        }
+        let ssrc = be_u32(input);
+        let mut offset = 4;
+        loop {
+            let item_type = *input.get(offset)?;
+            if item_type == 0 {
+                offset += 1;
+                break;
+            }
+            let len = *input.get(offset + 1)? as usize;
+            let value = input.get(offset + 2..offset + 2 + len)?;
+            if item_type == SDES_CNAME {
+                cnames.push((ssrc, String::from_utf8_lossy(value).to_string()));
+            }
+            offset += 2 + len;
+        }
+        let chunk_len = (offset + 3) & !3;
+        input = input.get(chunk_len..)?;
+    }
+    Some(cnames)
+}
+
+/// Parse a compound RTCP packet. Returns None if one of the packets is
+/// malformed.
+pub fn parse_rtcp(mut input: &[u8]) -> Option<Vec<RtcpPacket>> {
+    let mut packets = Vec::new();
+    while !input.is_empty() {
+        if input.len() < 4 || input[0] >> 6 != RTP_VERSION {
+            return None;
+        }
+        let count = (input[0] & 0x1f) as usize;
+        let packet_type = input[1];
+        let len = 4 * (be_u16(&input[2..]) as usize + 1);
+        if input.len() < len {
+            return None;
+        }
+        let body = &input[4..len];
+        let packet = match packet_type {
+            RTCP_SR | RTCP_RR => {
+                if body.len() < 4 {
+                    return None;
+                }
+                let ssrc = be_u32(body);
+                if packet_type == RTCP_SR {
+                    RtcpPacket::SenderReport { ssrc }
+                } else {
+                    RtcpPacket::ReceiverReport { ssrc }
+                }
+            }
+            RTCP_SDES => RtcpPacket::SourceDescription {
+                cnames: parse_sdes(body, count)?,
+            },
+            RTCP_BYE => {
+                if body.len() < 4 * count {
+                    return None;
+                }
+                RtcpPacket::Goodbye {
+                    ssrcs: body[..4 * count].chunks(4).map(be_u32).collect(),
+                }
+            }
+            192..=223 => RtcpPacket::Other { packet_type },
+            _ => return None,
+        };
+        packets.push(packet);
+        input = &input[len..];
+    }
+    Some(packets)
+}
+
+/// Clock rate of the static payload types (RFC 3551). The rate of the
+/// dynamic ones is only known from the session description.
+pub fn clock_rate(payload_type: u8) -> Option<u32> {
+    match payload_type {
+        0..=5 | 7..=9 | 12 | 13 | 15 | 18 => Some(8000),
+        6 => Some(16000),
+        10 | 11 => Some(44100),
+        16 => Some(11025),
+        17 => Some(22050),
+        14 | 25 | 26 | 28 | 31..=34 => Some(90000),
+        _ => None,
+    }
+}
+
+/// Encoding name of the static payload types.
+pub fn payload_type_name(payload_type: u8) -> Option<&'static str> {
+    let name = match payload_type {
+        0 => "PCMU",
+        3 => "GSM",
+        4 => "G723",
+        5 | 6 => "DVI4",
+        7 => "LPC",
+        8 => "PCMA",
+        9 => "G722",
+        10 | 11 => "L16",
+        12 => "QCELP",
+        13 => "CN",
+        14 => "MPA",
+        15 => "G728",
+        16 | 17 => "DVI4",
+        18 => "G729",
+        25 => "CelB",
+        26 => "JPEG",
+        28 => "nv",
+        31 => "H261",
+        32 => "MPV",
+        33 => "MP2T",
+        34 => "H263",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rtp() {
+        let packet = b"\x80\x08\x12\x34\x00\x00\x01\x40\xde\xad\xbe\xef\xd5\xd5\xd5\xd5";
+        let header = parse_rtp(packet).unwrap();
+        assert_eq!(header.payload_type, 8);
+        assert!(!header.marker);
+        assert_eq!(header.sequence, 0x1234);
+        assert_eq!(header.timestamp, 320);
+        assert_eq!(header.ssrc, 0xdeadbeef);
+        assert_eq!(header.payload_len, 4);
+
+        // one CSRC, a header extension of one word and 2 bytes of padding
+        let packet = b"\xb1\xe0\x00\x01\x00\x00\x00\x00\x00\x00\x00\x01\
+            \x00\x00\x00\x02\xbe\xde\x00\x01\x10\xff\x00\x00\xaa\xbb\x00\x02";
+        let header = parse_rtp(packet).unwrap();
+        assert!(header.marker);
+        assert_eq!(header.payload_type, 96);
+        assert_eq!(header.payload_len, 2);
+
+        assert_eq!(parse_rtp(b"\x40\x08\x12\x34\x00\x00\x01\x40\xde\xad\xbe\xef"), None);
+        assert_eq!(parse_rtp(b"\x83\x08\x12\x34\x00\x00\x01\x40\xde\xad\xbe\xef"), None);
+        assert_eq!(parse_rtp(b"\xa0\x08\x12\x34\x00\x00\x01\x40\xde\xad\xbe\xef\x05"), None);
+    }
+
+    #[test]
+    fn test_parse_rtcp() {
+        // a receiver report without report blocks, a SDES with the
+        // CNAME of the source, and a BYE
+        let packet = b"\x80\xc9\x00\x01\xde\xad\xbe\xef\
+            \x81\xca\x00\x03\xde\xad\xbe\xef\x01\x05alice\x00\
+            \x81\xcb\x00\x01\xde\xad\xbe\xef";
+        assert!(is_rtcp(packet));
+        let packets = parse_rtcp(packet).unwrap();
+        assert_eq!(packets, vec![
+            RtcpPacket::ReceiverReport { ssrc: 0xdeadbeef },
+            RtcpPacket::SourceDescription { cnames: vec![(0xdeadbeef, "alice".to_string())] },
+            RtcpPacket::Goodbye { ssrcs: vec![0xdeadbeef] },
+        ]);
+
+        assert!(!is_rtcp(b"\x80\x08\x12\x34"));
+        // length beyond the datagram
+        assert_eq!(parse_rtcp(b"\x80\xc9\x00\x02\xde\xad\xbe\xef"), None);
+        // unterminated SDES items
+        assert_eq!(parse_rtcp(b"\x81\xca\x00\x02\xde\xad\xbe\xef\x01\x02ab"), None);
+    }
+
+    #[test]
+    fn test_clock_rate() {
+        assert_eq!(clock_rate(0), Some(8000));
+        assert_eq!(clock_rate(31), Some(90000));
+        assert_eq!(clock_rate(111), None);
+        assert_eq!(payload_type_name(8), Some("PCMA"));
+        assert_eq!(payload_type_name(96), None);
+    }
+}
@@ -0,0 +1,694 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::conf::conf_get_u64;
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_UDP};
+use crate::expectation;
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+use std::time::Duration;
+
+declare_counters!(rtp_stats, "app_layer.rtp", {
+    streams,
+    packets,
+    rtcp_packets,
+    goodbyes,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum RtpEvent {
+    InvalidPacket,
+    SsrcChange,
+    SequenceJump,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static RTP_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static RTP_GAUGES: StateGauges = StateGauges::new();
+
+/// Seconds without packets after which a stream is over.
+static mut RTP_STREAM_TIMEOUT: u64 = 60;
+
+/// Largest forward jump of the sequence numbers accounted as loss, and
+/// largest backward one accounted as misordering (RFC 3550, A.1).
+const MAX_DROPOUT: u16 = 3000;
+const MAX_MISORDER: u16 = 100;
+
+pub static mut ALPROTO_RTP: AppProto = ALPROTO_UNKNOWN;
+
+/// Sequence number and jitter accounting of a stream (RFC 3550, A.1
+/// and A.8).
+#[derive(Debug, Default)]
+pub struct StreamStats {
+    /// Sequence numbers since the last resynchronisation.
+    base_seq: u32,
+    max_seq: u16,
+    cycles: u32,
+    /// Packets expected before the last resynchronisation.
+    expected_prior: u64,
+    pub received: u64,
+    pub octets: u64,
+    pub misordered: u64,
+    pub sequence_jumps: u64,
+    /// Transit time of the previous packet, in timestamp units.
+    transit: Option<i64>,
+    /// Interarrival jitter estimate, in timestamp units.
+    jitter: f64,
+}
+
+impl StreamStats {
+    fn start(&mut self, sequence: u16) {
+        self.base_seq = sequence as u32;
+        self.max_seq = sequence;
+        self.cycles = 0;
+    }
+
+    /// Account a packet. Returns false on a jump of the sequence
+    /// numbers, after which the accounting restarts from the packet.
+    fn update(&mut self, header: &RtpHeader, arrival: Duration, clock_rate: Option<u32>) -> bool {
+        let mut in_sequence = true;
+        if self.received == 0 {
+            self.start(header.sequence);
+        } else {
+            let delta = header.sequence.wrapping_sub(self.max_seq);
+            if delta < MAX_DROPOUT {
+                if header.sequence < self.max_seq {
+                    self.cycles += 1 << 16;
+                }
+                self.max_seq = header.sequence;
+            } else if delta <= std::u16::MAX - MAX_MISORDER {
+                self.expected_prior = self.expected();
+                self.start(header.sequence);
+                self.sequence_jumps += 1;
+                self.transit = None;
+                in_sequence = false;
+            } else {
+                self.misordered += 1;
+            }
+        }
+        self.received += 1;
+        self.octets += header.payload_len as u64;
+
+        if let Some(rate) = clock_rate {
+            let arrival = (arrival.as_micros() as u64 * rate as u64 / 1_000_000) as i64;
+            let transit = arrival.wrapping_sub(header.timestamp as i64);
+            if let Some(previous) = self.transit {
+                let d = (transit - previous).abs() as f64;
+                self.jitter += (d - self.jitter) / 16.0;
+            }
+            self.transit = Some(transit);
+        }
+        in_sequence
+    }
+
+    /// Number of packets expected from the sequence numbers.
+    pub fn expected(&self) -> u64 {
+        if self.received == 0 {
+            return self.expected_prior;
+        }
+        let extended_max = self.cycles as u64 + self.max_seq as u64;
+        self.expected_prior + extended_max + 1 - self.base_seq as u64
+    }
+
+    /// Number of packets lost, duplicates making up for losses.
+    pub fn lost(&self) -> u64 {
+        self.expected().saturating_sub(self.received)
+    }
+}
+
+/// Media of a SIP call the flow was negotiated for, attached by the SIP
+/// parser to the expectation of the flow.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SipMedia {
+    pub call_id: String,
+    /// "audio", "video", ...
+    pub media: String,
+}
+
+#[derive(Debug)]
+pub struct RtpTransaction {
+    id: u64,
+    pub ssrc: u32,
+    /// The direction of the flow the stream is sent in.
+    pub direction: Direction,
+    /// The payload type of the first packet, None for the sources only
+    /// seen in RTCP.
+    pub payload_type: Option<u8>,
+    pub payload_type_changes: u64,
+    pub stats: StreamStats,
+    pub sender_reports: u64,
+    pub receiver_reports: u64,
+    pub cname: Option<String>,
+    /// Set when the source left with a RTCP BYE.
+    pub goodbye: bool,
+    /// The SIP call of the flow, when it was expected from one.
+    pub sip: Option<SipMedia>,
+    /// Time of the last packet of the stream.
+    last_seen: Duration,
+    pub complete: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for RtpTransaction {}
+
+impl RtpTransaction {
+    fn new(id: u64, ssrc: u32, direction: Direction, now: Duration) -> RtpTransaction {
+        RtpTransaction {
+            id,
+            ssrc,
+            direction,
+            payload_type: None,
+            payload_type_changes: 0,
+            stats: StreamStats::default(),
+            sender_reports: 0,
+            receiver_reports: 0,
+            cname: None,
+            goodbye: false,
+            sip: None,
+            last_seen: now,
+            complete: false,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: RtpEvent) {
+        stats_incr!(rtp_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    /// Interarrival jitter in milliseconds, when the clock rate of the
+    /// payload type is known.
+    pub fn jitter_ms(&self) -> Option<f64> {
+        let rate = clock_rate(self.payload_type?)?;
+        Some(self.stats.jitter * 1000.0 / rate as f64)
+    }
+}
+
+impl Drop for RtpTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for RtpTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = RtpEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+pub struct RtpState {
+    transactions: TxTable<RtpTransaction>,
+    /// Set once an invalid packet was seen: the event is only raised
+    /// once per flow.
+    invalid: bool,
+    /// Set once the flow was checked for the expectation it may have
+    /// been created from.
+    expectation_checked: bool,
+    sip: Option<SipMedia>,
+}
+
+unsafe impl FfiType for RtpState {}
+
+impl txtable::State for RtpState {
+    type Tx = RtpTransaction;
+
+    fn tx_table(&self) -> &TxTable<RtpTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<RtpTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl RtpState {
+    pub fn new() -> RtpState {
+        RtpState {
+            transactions: TxTable::with_limit(&RTP_TX_LIMIT).with_gauges(&RTP_GAUGES),
+            invalid: false,
+            expectation_checked: false,
+            sip: None,
+        }
+    }
+
+    /// Link the flow to the SIP call it was negotiated in, if it was
+    /// expected from one.
+    fn check_expectation(&mut self, flow: *const Flow) {
+        if !self.expectation_checked {
+            self.expectation_checked = true;
+            self.sip = unsafe { expectation::take::<SipMedia>(flow) };
+        }
+    }
+
+    fn push_tx(&mut self, mut tx: RtpTransaction) {
+        tx.sip = self.sip.clone();
+        self.transactions.push(tx);
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    fn set_invalid(&mut self, now: Duration) {
+        if self.invalid {
+            return;
+        }
+        self.invalid = true;
+        let id = self.transactions.next_id();
+        let mut tx = RtpTransaction::new(id, 0, Direction::ToServer, now);
+        tx.complete = true;
+        tx.set_event(RtpEvent::InvalidPacket);
+        self.push_tx(tx);
+    }
+
+    fn find_stream(&mut self, ssrc: u32) -> Option<&mut RtpTransaction> {
+        self.transactions
+            .iter_mut()
+            .rev()
+            .find(|tx| !tx.complete && tx.ssrc == ssrc)
+    }
+
+    /// Get the stream of `ssrc`, starting a new one if needed. A new
+    /// source while another one of the direction is still sending media
+    /// breaks the continuity of the stream.
+    fn get_stream(&mut self, ssrc: u32, direction: Direction, now: Duration, media: bool)
+                  -> &mut RtpTransaction {
+        if self.find_stream(ssrc).is_none() {
+            stats_incr!(rtp_stats::streams);
+            let change = media
+                && self.transactions.iter().any(|tx| {
+                    !tx.complete && tx.direction == direction && tx.payload_type.is_some()
+                });
+            let id = self.transactions.next_id();
+            let mut tx = RtpTransaction::new(id, ssrc, direction, now);
+            if change {
+                tx.set_event(RtpEvent::SsrcChange);
+            }
+            self.push_tx(tx);
+        }
+        let tx = self.find_stream(ssrc).unwrap();
+        tx.last_seen = now;
+        tx
+    }
+
+    fn handle_rtp(&mut self, header: &RtpHeader, direction: Direction, now: Duration) {
+        stats_incr!(rtp_stats::packets);
+        let tx = self.get_stream(header.ssrc, direction, now, true);
+        match tx.payload_type {
+            None => tx.payload_type = Some(header.payload_type),
+            Some(pt) if pt != header.payload_type => tx.payload_type_changes += 1,
+            _ => {}
+        }
+        // the jitter is computed with the clock of the payload type of
+        // the stream, not the one of eventual DTMF or comfort noise
+        let rate = if Some(header.payload_type) == tx.payload_type {
+            clock_rate(header.payload_type)
+        } else {
+            None
+        };
+        if !tx.stats.update(header, now, rate) {
+            tx.set_event(RtpEvent::SequenceJump);
+        }
+    }
+
+    fn handle_rtcp(&mut self, packets: Vec<RtcpPacket>, direction: Direction, now: Duration) {
+        stats_incr!(rtp_stats::rtcp_packets);
+        for packet in packets {
+            match packet {
+                RtcpPacket::SenderReport { ssrc } => {
+                    self.get_stream(ssrc, direction, now, false).sender_reports += 1;
+                }
+                RtcpPacket::ReceiverReport { ssrc } => {
+                    self.get_stream(ssrc, direction, now, false).receiver_reports += 1;
+                }
+                RtcpPacket::SourceDescription { cnames } => {
+                    for (ssrc, cname) in cnames {
+                        self.get_stream(ssrc, direction, now, false).cname = Some(cname);
+                    }
+                }
+                RtcpPacket::Goodbye { ssrcs } => {
+                    for ssrc in ssrcs {
+                        if let Some(tx) = self.find_stream(ssrc) {
+                            stats_incr!(rtp_stats::goodbyes);
+                            tx.goodbye = true;
+                            tx.complete = true;
+                        }
+                    }
+                }
+                RtcpPacket::Other { .. } => {}
+            }
+        }
+    }
+
+    /// Complete the streams idle for the stream timeout.
+    fn expire(&mut self, now: Duration) {
+        let timeout = Duration::from_secs(unsafe { RTP_STREAM_TIMEOUT });
+        for tx in self.transactions.iter_mut() {
+            if !tx.complete && now > tx.last_seen + timeout {
+                tx.complete = true;
+            }
+        }
+    }
+
+    fn parse(&mut self, input: &[u8], direction: Direction, now: Duration) -> AppLayerResult {
+        self.expire(now);
+        if is_rtcp(input) {
+            match parse_rtcp(input) {
+                Some(packets) => self.handle_rtcp(packets, direction, now),
+                None => self.set_invalid(now),
+            }
+        } else {
+            match parse_rtp(input) {
+                Some(header) => self.handle_rtp(&header, direction, now),
+                None => self.set_invalid(now),
+            }
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for a RTP packet, or a compound RTCP packet starting with a
+/// sender or receiver report as all of them do.
+fn rtp_probe(input: &[u8], _direction: Direction) -> ProbeResult {
+    if input.len() < RTP_HEADER_LEN {
+        return ProbeResult::Unknown;
+    }
+    let alproto = unsafe { ALPROTO_RTP };
+    if is_rtcp(input) {
+        if input[0] >> 6 == RTP_VERSION && (input[1] == RTCP_SR || input[1] == RTCP_RR) {
+            return ProbeResult::Found(alproto);
+        }
+        return ProbeResult::Failed;
+    }
+    match parse_rtp(input) {
+        Some(header) if header.payload_type < 72 || header.payload_type > 76 => {
+            ProbeResult::Found(alproto)
+        }
+        _ => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_rtp_probing_parser, rtp_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_rtp_state_new(_orig_state: *mut std::os::raw::c_void,
+                                   _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = RtpState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut RtpState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut RtpState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+/// Arrival time of the packet being parsed.
+unsafe fn packet_time(flow: *const Flow) -> Duration {
+    if flow.is_null() {
+        return Duration::default();
+    }
+    let flow: &mut Flow = cast_mut(flow);
+    flow.get_last_time()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_parse_request(flow: *const Flow,
+                                              state: *mut std::os::raw::c_void,
+                                              _pstate: *mut std::os::raw::c_void,
+                                              input: *const u8,
+                                              input_len: u32,
+                                              _data: *const std::os::raw::c_void,
+                                              _flags: u8) -> AppLayerResult {
+    let state: &mut RtpState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.check_expectation(flow);
+    state.parse(buf, Direction::ToServer, packet_time(flow))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_parse_response(flow: *const Flow,
+                                               state: *mut std::os::raw::c_void,
+                                               _pstate: *mut std::os::raw::c_void,
+                                               input: *const u8,
+                                               input_len: u32,
+                                               _data: *const std::os::raw::c_void,
+                                               _flags: u8) -> AppLayerResult {
+    let state: &mut RtpState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.check_expectation(flow);
+    state.parse(buf, Direction::ToClient, packet_time(flow))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_state_get_tx(state: *mut std::os::raw::c_void,
+                                             tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut RtpState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut RtpState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// A stream is complete once its source said goodbye or went idle.
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                        _direction: u8) -> std::os::raw::c_int {
+    let tx: &mut RtpTransaction = cast_mut(tx);
+    tx.complete as std::os::raw::c_int
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_state_purge(state: *mut std::os::raw::c_void, ts: u64) {
+    let state: &mut RtpState = cast_mut(state);
+    state.expire(Duration::from_secs(ts));
+}
+
+export_tx_detect_state!(rs_rtp_state_get_tx_detect_state,
+                        rs_rtp_state_set_tx_detect_state, RtpTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_state_get_events(tx: *mut std::os::raw::c_void)
+                                                 -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut RtpTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_rtp_get_tx_data, RtpTransaction);
+
+fn rtp_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    RTP_GAUGES.log(js)?;
+    js.set_uint("events", rtp_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+const PARSER_NAME: &[u8] = b"rtp\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_rtp_register_parser() {
+    rtp_stats::register();
+    RTP_TX_LIMIT.configure("rtp", txtable::DEFAULT_MAX_TX);
+    if let Some(timeout) = conf_get_u64("app-layer.protocols.rtp.stream-timeout") {
+        RTP_STREAM_TIMEOUT = timeout;
+    }
+    introspect::register("rtp", rtp_introspect);
+    let default_port = CString::new("[5004,5005]").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_UDP,
+        probe_ts: Some(rs_rtp_probing_parser),
+        probe_tc: Some(rs_rtp_probing_parser),
+        min_depth: 0,
+        max_depth: RTP_HEADER_LEN as u16,
+        state_new: rs_rtp_state_new,
+        state_free: rs_rtp_state_free,
+        tx_free: rs_rtp_state_tx_free,
+        parse_ts: rs_rtp_parse_request,
+        parse_tc: rs_rtp_parse_response,
+        get_tx_count: rs_rtp_state_get_tx_count,
+        get_tx: rs_rtp_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_rtp_tx_get_alstate_progress,
+        get_de_state: rs_rtp_state_get_tx_detect_state,
+        set_de_state: rs_rtp_state_set_tx_detect_state,
+        get_events: Some(rs_rtp_state_get_events),
+        get_eventinfo: Some(RtpEvent::get_event_info),
+        get_eventinfo_byid: Some(RtpEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<RtpState>),
+        get_tx_data: rs_rtp_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<RtpState>),
+        flags: 0,
+        truncate: None,
+        state_purge: Some(rs_rtp_state_purge),
+    };
+
+    let ip_proto_str = CString::new("udp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_RTP = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust rtp parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for RTP.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rtp(payload_type: u8, sequence: u16, timestamp: u32, ssrc: u32) -> Vec<u8> {
+        let mut packet = vec![0x80, payload_type];
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(&timestamp.to_be_bytes());
+        packet.extend_from_slice(&ssrc.to_be_bytes());
+        packet.extend_from_slice(&[0xd5; 160]);
+        packet
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_rtp_stream_stats() {
+        let mut state = RtpState::new();
+        // 20ms PCMA packets, the 3rd lost, the 5th arriving late and
+        // the 6th before it
+        for &(seq, at) in &[(100u16, 0u64), (101, 20), (103, 60), (105, 100), (104, 110)] {
+            let ts = seq as u32 * 160;
+            state.parse(&rtp(8, seq, ts, 0x1234), Direction::ToServer, ms(at));
+        }
+        assert_eq!(state.transactions.tx_count(), 1);
+        let tx = state.transactions.get(0).unwrap();
+        assert_eq!(tx.payload_type, Some(8));
+        assert_eq!(tx.stats.received, 5);
+        assert_eq!(tx.stats.expected(), 6);
+        assert_eq!(tx.stats.lost(), 1);
+        assert_eq!(tx.stats.misordered, 1);
+        assert!(tx.jitter_ms().unwrap() > 0.0);
+        assert!(!tx.complete);
+
+        // the source leaves
+        state.parse(b"\x81\xcb\x00\x01\x00\x00\x12\x34", Direction::ToServer, ms(120));
+        assert!(state.transactions.get(0).unwrap().goodbye);
+        assert!(state.transactions.get(0).unwrap().complete);
+    }
+
+    #[test]
+    fn test_rtp_sequence_wrap_and_jump() {
+        let mut stats = StreamStats::default();
+        for &seq in &[65534u16, 65535, 0, 1] {
+            let header = parse_rtp(&rtp(0, seq, 0, 1)).unwrap();
+            assert!(stats.update(&header, ms(0), None));
+        }
+        assert_eq!(stats.expected(), 4);
+        assert_eq!(stats.lost(), 0);
+
+        let header = parse_rtp(&rtp(0, 20000, 0, 1)).unwrap();
+        assert!(!stats.update(&header, ms(0), None));
+        assert_eq!(stats.sequence_jumps, 1);
+        assert_eq!(stats.expected(), 5);
+    }
+
+    #[test]
+    fn test_rtp_ssrc_change_and_timeout() {
+        let mut state = RtpState::new();
+        state.parse(&rtp(0, 1, 0, 0xaaaa), Direction::ToServer, ms(0));
+        state.parse(&rtp(0, 1, 0, 0xbbbb), Direction::ToClient, ms(10));
+        state.parse(&rtp(0, 500, 0, 0xcccc), Direction::ToServer, ms(20));
+        assert_eq!(state.transactions.tx_count(), 3);
+        assert_eq!(state.transactions.get(1).unwrap().direction, Direction::ToClient);
+        assert_eq!(state.transactions.get(2).unwrap().ssrc, 0xcccc);
+
+        state.expire(Duration::from_secs(120));
+        assert!(state.transactions.iter().all(|tx| tx.complete));
+
+        state.parse(b"\x90\x08", Direction::ToServer, Duration::from_secs(121));
+        assert_eq!(state.transactions.tx_count(), 4);
+    }
+
+    #[test]
+    fn test_rtp_sip_media() {
+        let mut state = RtpState::new();
+        state.sip = Some(SipMedia {
+            call_id: "a84b4c76e66710".to_string(),
+            media: "audio".to_string(),
+        });
+        state.parse(&rtp(0, 1, 0, 0xaaaa), Direction::ToServer, ms(0));
+        state.parse(b"\x80\xc9\x00\x01\x00\x00\xbb\xbb", Direction::ToClient, ms(10));
+        assert_eq!(state.transactions.tx_count(), 2);
+        for tx in state.transactions.iter() {
+            assert_eq!(tx.sip.as_ref().unwrap().call_id, "a84b4c76e66710");
+        }
+    }
+
+    #[test]
+    fn test_rtp_probe() {
+        unsafe {
+            ALPROTO_RTP = 42;
+        }
+        assert_eq!(rtp_probe(&rtp(0, 1, 0, 1), Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(rtp_probe(b"\x80\xc8\x00\x06\x00\x00\x12\x34\x00\x00\x00\x00",
+                             Direction::ToClient), ProbeResult::Found(42));
+        assert_eq!(rtp_probe(b"\x80\xcb\x00\x01\x00\x00\x12\x34\x00\x00\x00\x00",
+                             Direction::ToServer), ProbeResult::Failed);
+        assert_eq!(rtp_probe(b"\x80\x08\x00", Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(rtp_probe(b"GET / HTTP/1.1\r\n", Direction::ToServer), ProbeResult::Failed);
+    }
+}
@@ -17,10 +17,18 @@
 
 // written by Giuseppe Longo <giuseppe@glongo.it>
 
+use crate::applayer::cast_mut;
 use crate::core::{STREAM_TOCLIENT, STREAM_TOSERVER};
-use crate::sip::sip::SIPTransaction;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use crate::sip::sip::{SIPTransaction, ALPROTO_SIP};
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 
+static mut G_SIP_HEADER_BUFFER_ID: c_int = 0;
+
 #[no_mangle]
 pub unsafe extern "C" fn rs_sip_tx_get_method(
     tx: &mut SIPTransaction,
@@ -181,3 +189,48 @@ pub unsafe extern "C" fn rs_sip_tx_get_response_line(
 
     return 0;
 }
+
+unsafe extern "C" fn sip_tx_get_header(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SIPTransaction = cast_mut(tx);
+    if tx.header_buffer.is_empty() {
+        return false;
+    }
+    *buf = tx.header_buffer.as_ptr();
+    *len = tx.header_buffer.len() as u32;
+    true
+}
+
+unsafe extern "C" fn sip_header_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, sip_tx_get_header)
+}
+
+unsafe extern "C" fn sip_header_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SIP, G_SIP_HEADER_BUFFER_ID)
+}
+
+/// Register the SIP keywords implemented in rust. The header buffer has
+/// a "Name: value\r\n" line per header, the compact names being
+/// expanded, so that rules can key on the header name whatever form the
+/// sender used.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "sip.header",
+        desc: "sticky buffer to match on the headers of SIP messages",
+        url: "/rules/sip-keywords.html#sip-header",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: sip_header_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SIP_HEADER_BUFFER_ID = helper_buffer_mpm_register(
+            "sip.header", "sip headers", ALPROTO_SIP, true, true, 0, sip_header_get_data);
+    }
+}
@@ -43,6 +43,41 @@ fn log(tx: &SIPTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
         js.set_string("response_line", resp_line)?;
     }
 
+    if let Some(sdp) = &tx.sdp {
+        js.open_object("sdp")?;
+        if let Some(origin) = &sdp.origin {
+            js.set_string("origin", origin)?;
+        }
+        if let Some(session_name) = &sdp.session_name {
+            js.set_string("session_name", session_name)?;
+        }
+        if let Some(connection) = &sdp.connection {
+            js.set_string("connection", connection)?;
+        }
+        if !sdp.media.is_empty() {
+            js.open_array("media")?;
+            for media in &sdp.media {
+                js.start_object()?;
+                js.set_string("type", &media.media)?;
+                js.set_uint("port", media.port as u64)?;
+                js.set_string("protocol", &media.proto)?;
+                if let Some((address, _)) = sdp.media_endpoint(media) {
+                    js.set_string("address", address)?;
+                }
+                if !media.formats.is_empty() {
+                    js.open_array("formats")?;
+                    for format in &media.formats {
+                        js.append_string(format)?;
+                    }
+                    js.close()?;
+                }
+                js.close()?;
+            }
+            js.close()?;
+        }
+        js.close()?;
+    }
+
     js.close()?;
 
     Ok(())
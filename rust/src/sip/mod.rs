@@ -20,4 +20,5 @@
 pub mod detect;
 pub mod log;
 pub mod parser;
+pub mod sdp;
 pub mod sip;
@@ -19,6 +19,7 @@
 
 use nom::*;
 use nom::IResult;
+use nom::error::ErrorKind;
 use nom::character::{is_alphabetic, is_alphanumeric, is_space};
 use nom::character::streaming::crlf;
 use std;
@@ -36,6 +37,8 @@ pub struct Request {
     pub path: String,
     pub version: String,
     pub headers: HashMap<String, String>,
+    /// All the headers, in order
+    pub header_list: Vec<Header>,
 }
 
 #[derive(Debug)]
@@ -43,6 +46,8 @@ pub struct Response {
     pub version: String,
     pub code: String,
     pub reason: String,
+    /// All the headers, in order. Empty if they could not be parsed.
+    pub header_list: Vec<Header>,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -91,7 +96,11 @@ named!(pub sip_parse_request<&[u8], Request>,
         version: parse_version >> crlf >>
         headers: parse_headers >>
         crlf >>
-        (Request { method: method.into(), path: path.into(), version: version.into(), headers: headers})
+        (Request {
+            method: method.into(), path: path.into(), version: version.into(),
+            headers: headers.iter().map(|h| (h.name.clone(), h.value.clone())).collect(),
+            header_list: headers,
+        })
     )
 );
 
@@ -100,7 +109,32 @@ named!(pub sip_parse_response<&[u8], Response>,
         version: parse_version >> char!(' ') >>
         code: parse_code >> char!(' ') >>
         reason: parse_reason >> crlf >>
-        (Response { version: version.into(), code: code.into(), reason: reason.into() })
+        headers: opt!(complete!(terminated!(parse_headers, crlf))) >>
+        (Response {
+            version: version.into(), code: code.into(), reason: reason.into(),
+            header_list: headers.unwrap_or_default(),
+        })
+    )
+);
+
+// Request line, returning the version, to tell SIP from other text
+// protocols on a stream.
+named!(pub sip_parse_request_line<&[u8], &str>,
+    do_parse!(
+        take_while1!(is_method_char) >> char!(' ') >>
+        parse_request_uri >> char!(' ') >>
+        version: parse_version >> crlf >>
+        (version)
+    )
+);
+
+// Status line, returning the version.
+named!(pub sip_parse_status_line<&[u8], &str>,
+    do_parse!(
+        version: parse_version >> char!(' ') >>
+        parse_code >> char!(' ') >>
+        parse_reason >> crlf >>
+        (version)
     )
 );
 
@@ -158,8 +192,8 @@ named!(pub sip_take_line<&[u8], Option<String> >,
     )
 );
 
-pub fn parse_headers(mut input: &[u8]) -> IResult<&[u8], HashMap<String, String>> {
-    let mut headers_map: HashMap<String, String> = HashMap::new();
+pub fn parse_headers(mut input: &[u8]) -> IResult<&[u8], Vec<Header>> {
+    let mut headers = Vec::new();
     loop {
         match crlf(input) as IResult<&[u8],_> {
             Ok((_, _)) => {
@@ -170,11 +204,93 @@ pub fn parse_headers(mut input: &[u8]) -> IResult<&[u8], HashMap<String, String>
             Err(Err::Incomplete(e)) => return Err(Err::Incomplete(e)),
         };
         let (rest, header) = try_parse!(input, message_header);
-        headers_map.insert(header.name, header.value);
+        headers.push(header);
         input = rest;
     }
 
-    Ok((input, headers_map))
+    Ok((input, headers))
+}
+
+/// Full name of a header, for the compact forms of RFC 3261, 7.3.3 and
+/// of the later extensions.
+pub fn header_full_name(name: &str) -> &str {
+    if name.len() != 1 {
+        return name;
+    }
+    match name.as_bytes()[0].to_ascii_lowercase() {
+        b'a' => "Accept-Contact",
+        b'b' => "Referred-By",
+        b'c' => "Content-Type",
+        b'd' => "Request-Disposition",
+        b'e' => "Content-Encoding",
+        b'f' => "From",
+        b'i' => "Call-ID",
+        b'j' => "Reject-Contact",
+        b'k' => "Supported",
+        b'l' => "Content-Length",
+        b'm' => "Contact",
+        b'o' => "Event",
+        b'r' => "Refer-To",
+        b's' => "Subject",
+        b't' => "To",
+        b'u' => "Allow-Events",
+        b'v' => "Via",
+        b'x' => "Session-Expires",
+        _ => name,
+    }
+}
+
+/// Length of the start line and headers, up to the empty line, and the
+/// Content-Length, if present, of the message starting `input`.
+fn sip_message_lengths(input: &[u8]) -> IResult<&[u8], (usize, Option<usize>)> {
+    let headers_len = match input.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(pos) => pos + 4,
+        None => return Err(Err::Incomplete(Needed::Unknown)),
+    };
+    let mut body_len = None;
+    // skip the start line
+    for line in input[..headers_len].split(|&c| c == b'\n').skip(1) {
+        let sep = match line.iter().position(|&c| c == b':') {
+            Some(sep) => sep,
+            None => continue,
+        };
+        let name = std::str::from_utf8(&line[..sep]).unwrap_or("").trim();
+        if header_full_name(name).eq_ignore_ascii_case("content-length") {
+            body_len = match std::str::from_utf8(&line[sep + 1..]) {
+                Ok(value) => match value.trim().parse::<usize>() {
+                    Ok(len) => Some(len),
+                    Err(_) => return Err(Err::Error(error_position!(input, ErrorKind::Verify))),
+                },
+                Err(_) => return Err(Err::Error(error_position!(input, ErrorKind::Verify))),
+            };
+        }
+    }
+    Ok((input, (headers_len, body_len)))
+}
+
+/// Length of the message starting `input` on a stream transport: its
+/// start line and headers, and the body of the length given by
+/// Content-Length (RFC 3261, 18.3). The input is not consumed.
+pub fn sip_message_len(input: &[u8]) -> IResult<&[u8], usize> {
+    let (_, (headers_len, body_len)) = sip_message_lengths(input)?;
+    match headers_len.checked_add(body_len.unwrap_or(0)) {
+        Some(len) => Ok((input, len)),
+        None => Err(Err::Error(error_position!(input, ErrorKind::Verify))),
+    }
+}
+
+/// Body of a message: what follows the empty line after the headers, up
+/// to Content-Length if present, else up to the end of the message as
+/// on datagram transports.
+pub fn sip_message_body(input: &[u8]) -> &[u8] {
+    match sip_message_lengths(input) {
+        Ok((_, (headers_len, Some(body_len)))) => {
+            let end = std::cmp::min(input.len(), headers_len.saturating_add(body_len));
+            &input[headers_len..end]
+        }
+        Ok((_, (headers_len, None))) => &input[headers_len..],
+        Err(_) => &[],
+    }
 }
 
 fn parse_header_value(buf: &[u8]) -> IResult<&[u8], &[u8]> {
@@ -264,6 +380,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sip_message_len() {
+        let buf: &[u8] = "INVITE sip:bob@example.com SIP/2.0\r\n\
+                          Call-ID: a84b4c76e66710\r\n\
+                          content-length: 5\r\n\
+                          \r\n\
+                          v=0\r\n\
+                          OPTIONS"
+            .as_bytes();
+        match sip_message_len(buf) {
+            Ok((_, len)) => {
+                assert_eq!(&buf[len..], b"OPTIONS");
+            }
+            _ => {
+                assert!(false);
+            }
+        }
+
+        let buf: &[u8] = b"SIP/2.0 200 OK\r\nl: 0\r\n\r\n";
+        match sip_message_len(buf) {
+            Ok((_, len)) => {
+                assert_eq!(len, buf.len());
+            }
+            _ => {
+                assert!(false);
+            }
+        }
+
+        let buf: &[u8] = b"SIP/2.0 200 OK\r\nContent-Length: 0\r\n";
+        assert!(sip_message_len(buf).is_err());
+        let buf: &[u8] = b"SIP/2.0 200 OK\r\nContent-Length: x\r\n\r\n";
+        assert!(sip_message_len(buf).is_err());
+    }
+
+    #[test]
+    fn test_parse_request_header_list() {
+        let buf: &[u8] = "INVITE sip:bob@example.com SIP/2.0\r\n\
+                          v: SIP/2.0/UDP pc33.example.com\r\n\
+                          Via: SIP/2.0/UDP proxy.example.com\r\n\
+                          c: application/sdp\r\n\
+                          \r\n"
+            .as_bytes();
+
+        match sip_parse_request(buf) {
+            Ok((_, req)) => {
+                assert_eq!(req.header_list.len(), 3);
+                assert_eq!(header_full_name(&req.header_list[0].name), "Via");
+                assert_eq!(req.header_list[1].value, "SIP/2.0/UDP proxy.example.com");
+                assert_eq!(header_full_name(&req.header_list[2].name), "Content-Type");
+            }
+            _ => {
+                assert!(false);
+            }
+        }
+    }
+
     #[test]
     fn test_parse_response() {
         let buf: &[u8] = "SIP/2.0 401 Unauthorized\r\n\
@@ -275,6 +447,7 @@ mod tests {
                 assert_eq!(resp.version, "SIP/2.0");
                 assert_eq!(resp.code, "401");
                 assert_eq!(resp.reason, "Unauthorized");
+                assert!(resp.header_list.is_empty());
             }
             _ => {
                 assert!(false);
@@ -0,0 +1,183 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Session descriptions (SDP, RFC 4566) of the SIP offers and answers,
+//! giving the media streams negotiated by a call.
+
+/// A media description, "m=" line
+#[derive(Debug, PartialEq)]
+pub struct SdpMedia {
+    /// "audio", "video", ...
+    pub media: String,
+    pub port: u16,
+    /// "RTP/AVP", ...
+    pub proto: String,
+    pub formats: Vec<String>,
+    /// Connection address of the media, if it has its own "c=" line
+    pub connection: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SdpMessage {
+    /// "o=" line
+    pub origin: Option<String>,
+    /// "s=" line
+    pub session_name: Option<String>,
+    /// Connection address of the session
+    pub connection: Option<String>,
+    pub media: Vec<SdpMedia>,
+}
+
+impl SdpMessage {
+    /// Address and port the media is to be sent to.
+    pub fn media_endpoint<'a>(&'a self, media: &'a SdpMedia) -> Option<(&'a str, u16)> {
+        let address = media.connection.as_ref().or_else(|| self.connection.as_ref())?;
+        Some((address.as_str(), media.port))
+    }
+
+    /// Media sent over RTP, "RTP/AVP", "RTP/SAVPF", "UDP/TLS/RTP/SAVPF"
+    /// and the like, leaving out the streams rejected with a port 0.
+    pub fn rtp_media(&self) -> impl Iterator<Item = &SdpMedia> {
+        self.media
+            .iter()
+            .filter(|media| media.port != 0 && media.proto.split('/').any(|p| p == "RTP"))
+    }
+}
+
+/// Address of a "c=" line: "IN IP4 192.0.2.1", without the TTL and
+/// number of addresses of the multicast ones.
+fn parse_connection(value: &str) -> Option<String> {
+    let mut fields = value.split_whitespace();
+    if fields.next() != Some("IN") {
+        return None;
+    }
+    match fields.next() {
+        Some("IP4") | Some("IP6") => {}
+        _ => return None,
+    }
+    let address = fields.next()?;
+    Some(address.split('/').next().unwrap_or(address).to_string())
+}
+
+/// "m=" line: "audio 49170 RTP/AVP 0 8". A port "49170/2" for a range
+/// keeps the first port.
+fn parse_media(value: &str) -> Option<SdpMedia> {
+    let mut fields = value.split_whitespace();
+    let media = fields.next()?.to_string();
+    let port = fields.next()?;
+    let port = port.split('/').next().unwrap_or(port).parse::<u16>().ok()?;
+    let proto = fields.next()?.to_string();
+    let formats = fields.map(|f| f.to_string()).collect();
+    Some(SdpMedia {
+        media,
+        port,
+        proto,
+        formats,
+        connection: None,
+    })
+}
+
+/// Parse a session description. Returns None if the body does not
+/// start with the version line.
+pub fn parse_sdp(input: &[u8]) -> Option<SdpMessage> {
+    let text = String::from_utf8_lossy(input);
+    let mut lines = text
+        .split('\n')
+        .map(|l| l.trim_end_matches('\r'))
+        .filter(|l| !l.is_empty());
+    if lines.next() != Some("v=0") {
+        return None;
+    }
+    let mut sdp = SdpMessage::default();
+    for line in lines {
+        if line.len() < 2 || line.as_bytes()[1] != b'=' {
+            // not a type=value line
+            continue;
+        }
+        let value = &line[2..];
+        match line.as_bytes()[0] {
+            b'o' => {
+                sdp.origin = Some(value.to_string());
+            }
+            b's' => {
+                sdp.session_name = Some(value.to_string());
+            }
+            b'c' => {
+                let connection = parse_connection(value);
+                // after a media description, the line is its own
+                match sdp.media.last_mut() {
+                    Some(media) => media.connection = connection,
+                    None => sdp.connection = connection,
+                }
+            }
+            b'm' => {
+                if let Some(media) = parse_media(value) {
+                    sdp.media.push(media);
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(sdp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sdp() {
+        let buf = b"v=0\r\n\
+                    o=alice 2890844526 2890844526 IN IP4 atlanta.example.com\r\n\
+                    s=-\r\n\
+                    c=IN IP4 192.0.2.101\r\n\
+                    t=0 0\r\n\
+                    m=audio 49172 RTP/AVP 0 8\r\n\
+                    a=rtpmap:0 PCMU/8000\r\n\
+                    m=video 51372/2 RTP/AVP 31\r\n\
+                    c=IN IP4 224.2.1.1/127\r\n";
+        let sdp = parse_sdp(buf).unwrap();
+        assert_eq!(sdp.origin.as_ref().unwrap(),
+                   "alice 2890844526 2890844526 IN IP4 atlanta.example.com");
+        assert_eq!(sdp.session_name.as_ref().unwrap(), "-");
+        assert_eq!(sdp.media.len(), 2);
+        assert_eq!(sdp.media[0].media, "audio");
+        assert_eq!(sdp.media[0].formats, vec!["0", "8"]);
+        assert_eq!(sdp.media_endpoint(&sdp.media[0]), Some(("192.0.2.101", 49172)));
+        assert_eq!(sdp.media_endpoint(&sdp.media[1]), Some(("224.2.1.1", 51372)));
+    }
+
+    #[test]
+    fn test_sdp_rtp_media() {
+        let buf = b"v=0\r\n\
+                    c=IN IP4 192.0.2.101\r\n\
+                    m=audio 49172 RTP/AVP 0\r\n\
+                    m=video 0 RTP/AVP 31\r\n\
+                    m=application 9 TCP/BFCP *\r\n\
+                    m=video 51372 UDP/TLS/RTP/SAVPF 96\r\n";
+        let sdp = parse_sdp(buf).unwrap();
+        let ports: Vec<u16> = sdp.rtp_media().map(|media| media.port).collect();
+        assert_eq!(ports, vec![49172, 51372]);
+    }
+
+    #[test]
+    fn test_parse_sdp_invalid() {
+        assert_eq!(parse_sdp(b"<xml/>"), None);
+        let sdp = parse_sdp(b"v=0\r\nm=audio x RTP/AVP 0\r\n").unwrap();
+        assert!(sdp.media.is_empty());
+    }
+}
@@ -21,8 +21,11 @@ extern crate nom;
 
 use crate::applayer::{self, *};
 use crate::core;
-use crate::core::{sc_detect_engine_state_free, AppProto, Flow, ALPROTO_UNKNOWN};
+use crate::core::{AppProto, Direction, Flow, ALPROTO_UNKNOWN, STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::expectation;
+use crate::rtp::rtp::{SipMedia, ALPROTO_RTP};
 use crate::sip::parser::*;
+use crate::sip::sdp::{parse_sdp, SdpMessage};
 use std;
 use std::ffi::CString;
 
@@ -32,22 +35,36 @@ pub enum SIPEvent {
     InvalidData,
 }
 
+/// Maximum length of the start line and headers of a message on TCP.
+/// The stream is considered invalid if no end of headers is found
+/// within it.
+const SIP_MAX_HEADERS_LEN: usize = 65536;
+
 pub struct SIPState {
     transactions: Vec<SIPTransaction>,
     tx_id: u64,
 }
 
+unsafe impl FfiType for SIPState {}
+
 pub struct SIPTransaction {
     id: u64,
     pub request: Option<Request>,
     pub response: Option<Response>,
     pub request_line: Option<String>,
     pub response_line: Option<String>,
-    de_state: Option<*mut core::DetectEngineState>,
+    /// Session description of the offer or answer in the body
+    pub sdp: Option<SdpMessage>,
+    /// Headers of the message, one "Name: value\r\n" line each, with
+    /// the names in their full form
+    pub header_buffer: Vec<u8>,
+    de_state: DetectStateHolder,
     events: *mut core::AppLayerDecoderEvents,
     tx_data: applayer::AppLayerTxData,
 }
 
+unsafe impl FfiType for SIPTransaction {}
+
 impl SIPState {
     pub fn new() -> SIPState {
         SIPState {
@@ -87,14 +104,48 @@ impl SIPState {
         }
     }
 
-    fn parse_request(&mut self, input: &[u8]) -> bool {
+    /// Declare the RTP flows of the media negotiated by the session
+    /// description of `tx`, for them to be classified as RTP without
+    /// probing and linked to the call.
+    ///
+    /// The expectations only match flows between the hosts of the SIP
+    /// flow, media relayed through another host is left to probing.
+    /// The flow may start from either side with symmetric RTP, so the
+    /// port is expected as the source and the destination.
+    fn expect_media(&self, flow: *const Flow, tx: &SIPTransaction) {
+        if flow.is_null() || unsafe { ALPROTO_RTP } == ALPROTO_UNKNOWN {
+            return;
+        }
+        let sdp = match tx.sdp {
+            Some(ref sdp) => sdp,
+            None => return,
+        };
+        let call_id = match tx.call_id() {
+            Some(call_id) => call_id,
+            None => return,
+        };
+        for media in sdp.rtp_media() {
+            let data = SipMedia {
+                call_id: call_id.to_string(),
+                media: media.media.clone(),
+            };
+            for &(sp, dp) in &[(0, media.port), (media.port, 0)] {
+                expectation::create(flow, STREAM_TOSERVER | STREAM_TOCLIENT, sp, dp,
+                                    unsafe { ALPROTO_RTP }, data.clone());
+            }
+        }
+    }
+
+    fn parse_request(&mut self, flow: *const Flow, input: &[u8]) -> bool {
         match sip_parse_request(input) {
             Ok((_, request)) => {
                 let mut tx = self.new_tx();
+                tx.set_headers(&request.header_list, input);
                 tx.request = Some(request);
                 if let Ok((_, req_line)) = sip_take_line(input) {
                     tx.request_line = req_line;
                 }
+                self.expect_media(flow, &tx);
                 self.transactions.push(tx);
                 return true;
             }
@@ -109,14 +160,16 @@ impl SIPState {
         }
     }
 
-    fn parse_response(&mut self, input: &[u8]) -> bool {
+    fn parse_response(&mut self, flow: *const Flow, input: &[u8]) -> bool {
         match sip_parse_response(input) {
             Ok((_, response)) => {
                 let mut tx = self.new_tx();
+                tx.set_headers(&response.header_list, input);
                 tx.response = Some(response);
                 if let Ok((_, resp_line)) = sip_take_line(input) {
                     tx.response_line = resp_line;
                 }
+                self.expect_media(flow, &tx);
                 self.transactions.push(tx);
                 return true;
             }
@@ -130,21 +183,99 @@ impl SIPState {
             }
         }
     }
+
+    /// Parse the messages of a stream. They are framed by the end of
+    /// their headers and their Content-Length, and may be pipelined or
+    /// split over several segments.
+    fn parse_tcp(&mut self, flow: *const Flow, input: &[u8], direction: Direction)
+                 -> AppLayerResult {
+        let mut start = input;
+        while !start.is_empty() {
+            // keep-alives between messages, RFC 5626
+            if start[0] == b'\r' || start[0] == b'\n' {
+                start = &start[1..];
+                continue;
+            }
+            let consumed = input.len() - start.len();
+            match sip_message_len(start) {
+                Ok((_, len)) => {
+                    if start.len() < len {
+                        return AppLayerResult::incomplete_from(input.len(), consumed, len);
+                    }
+                    let msg = &start[..len];
+                    // a message failing to parse is skipped, the
+                    // framing is still known
+                    let _ = match direction {
+                        Direction::ToServer => self.parse_request(flow, msg),
+                        Direction::ToClient => self.parse_response(flow, msg),
+                    };
+                    start = &start[len..];
+                }
+                Err(nom::Err::Incomplete(_)) => {
+                    if start.len() > SIP_MAX_HEADERS_LEN {
+                        self.set_event(SIPEvent::InvalidData);
+                        return AppLayerResult::err();
+                    }
+                    return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                           start.len() + 1);
+                }
+                Err(_) => {
+                    self.set_event(SIPEvent::InvalidData);
+                    return AppLayerResult::err();
+                }
+            }
+        }
+        AppLayerResult::ok()
+    }
 }
 
 impl SIPTransaction {
     pub fn new(id: u64) -> SIPTransaction {
         SIPTransaction {
             id: id,
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             request: None,
             response: None,
             request_line: None,
             response_line: None,
+            sdp: None,
+            header_buffer: Vec::new(),
             events: std::ptr::null_mut(),
             tx_data: applayer::AppLayerTxData::new(),
         }
     }
+
+    /// Call-ID of the message, the compact form included.
+    pub fn call_id(&self) -> Option<&str> {
+        let headers = match (&self.request, &self.response) {
+            (Some(request), _) => &request.header_list,
+            (None, Some(response)) => &response.header_list,
+            (None, None) => return None,
+        };
+        headers
+            .iter()
+            .find(|header| header_full_name(&header.name).eq_ignore_ascii_case("call-id"))
+            .map(|header| header.value.as_str())
+    }
+
+    /// Set up the header buffer, and parse the body of `message` if it
+    /// is a session description.
+    fn set_headers(&mut self, headers: &[Header], message: &[u8]) {
+        let mut is_sdp = false;
+        for header in headers {
+            let name = header_full_name(&header.name);
+            if name.eq_ignore_ascii_case("content-type") {
+                is_sdp = header.value.to_ascii_lowercase().starts_with("application/sdp");
+            }
+            self.header_buffer.extend_from_slice(name.as_bytes());
+            self.header_buffer.extend_from_slice(b": ");
+            self.header_buffer.extend_from_slice(header.value.as_bytes());
+            self.header_buffer.extend_from_slice(b"\r\n");
+        }
+        if is_sdp {
+            self.sdp = parse_sdp(sip_message_body(message));
+        }
+    }
 }
 
 impl Drop for SIPTransaction {
@@ -152,9 +283,6 @@ impl Drop for SIPTransaction {
         if self.events != std::ptr::null_mut() {
             core::sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        if let Some(state) = self.de_state {
-            sc_detect_engine_state_free(state);
-        }
     }
 }
 
@@ -176,7 +304,7 @@ pub unsafe extern "C" fn rs_sip_state_get_tx(
     state: *mut std::os::raw::c_void,
     tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, SIPState);
+    let state: &mut SIPState = cast_mut(state);
     match state.get_tx_by_id(tx_id) {
         Some(tx) => tx as *const _ as *mut _,
         None => std::ptr::null_mut(),
@@ -185,13 +313,13 @@ pub unsafe extern "C" fn rs_sip_state_get_tx(
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_sip_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
-    let state = cast_pointer!(state, SIPState);
+    let state: &mut SIPState = cast_mut(state);
     state.tx_id
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_sip_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
-    let state = cast_pointer!(state, SIPState);
+    let state: &mut SIPState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -203,36 +331,17 @@ pub extern "C" fn rs_sip_tx_get_alstate_progress(
     1
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_sip_state_set_tx_detect_state(
-    tx: *mut std::os::raw::c_void,
-    de_state: &mut core::DetectEngineState,
-) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, SIPTransaction);
-    tx.de_state = Some(de_state);
-    0
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_sip_state_get_tx_detect_state(
-    tx: *mut std::os::raw::c_void,
-) -> *mut core::DetectEngineState {
-    let tx = cast_pointer!(tx, SIPTransaction);
-    match tx.de_state {
-        Some(ds) => ds,
-        None => std::ptr::null_mut(),
-    }
-}
+export_tx_detect_state!(rs_sip_state_get_tx_detect_state, rs_sip_state_set_tx_detect_state, SIPTransaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_sip_state_get_events(
     tx: *mut std::os::raw::c_void,
 ) -> *mut core::AppLayerDecoderEvents {
-    let tx = cast_pointer!(tx, SIPTransaction);
+    let tx: &mut SIPTransaction = cast_mut(tx);
     return tx.events;
 }
 
-static mut ALPROTO_SIP: AppProto = ALPROTO_UNKNOWN;
+pub static mut ALPROTO_SIP: AppProto = ALPROTO_UNKNOWN;
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_sip_probing_parser_ts(
@@ -266,7 +375,7 @@ pub unsafe extern "C" fn rs_sip_probing_parser_tc(
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_sip_parse_request(
-    _flow: *const core::Flow,
+    flow: *const core::Flow,
     state: *mut std::os::raw::c_void,
     _pstate: *mut std::os::raw::c_void,
     input: *const u8,
@@ -275,13 +384,13 @@ pub unsafe extern "C" fn rs_sip_parse_request(
     _flags: u8,
 ) -> AppLayerResult {
     let buf = build_slice!(input, input_len as usize);
-    let state = cast_pointer!(state, SIPState);
-    state.parse_request(buf).into()
+    let state: &mut SIPState = cast_mut(state);
+    state.parse_request(flow, buf).into()
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_sip_parse_response(
-    _flow: *const core::Flow,
+    flow: *const core::Flow,
     state: *mut std::os::raw::c_void,
     _pstate: *mut std::os::raw::c_void,
     input: *const u8,
@@ -290,8 +399,74 @@ pub unsafe extern "C" fn rs_sip_parse_response(
     _flags: u8,
 ) -> AppLayerResult {
     let buf = build_slice!(input, input_len as usize);
-    let state = cast_pointer!(state, SIPState);
-    state.parse_response(buf).into()
+    let state: &mut SIPState = cast_mut(state);
+    state.parse_response(flow, buf).into()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_sip_probing_parser_tcp_ts(
+    _flow: *const Flow,
+    _direction: u8,
+    input: *const u8,
+    input_len: u32,
+    _rdir: *mut u8,
+) -> AppProto {
+    let buf = build_slice!(input, input_len as usize);
+    match sip_parse_request_line(buf) {
+        Ok((_, version)) if version.starts_with("SIP/") => ALPROTO_SIP,
+        _ => ALPROTO_UNKNOWN,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_sip_probing_parser_tcp_tc(
+    _flow: *const Flow,
+    _direction: u8,
+    input: *const u8,
+    input_len: u32,
+    _rdir: *mut u8,
+) -> AppProto {
+    let buf = build_slice!(input, input_len as usize);
+    match sip_parse_status_line(buf) {
+        Ok((_, version)) if version.starts_with("SIP/") => ALPROTO_SIP,
+        _ => ALPROTO_UNKNOWN,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_sip_parse_request_tcp(
+    flow: *const core::Flow,
+    state: *mut std::os::raw::c_void,
+    pstate: *mut std::os::raw::c_void,
+    input: *const u8,
+    input_len: u32,
+    _data: *const std::os::raw::c_void,
+    _flags: u8,
+) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let buf = build_slice!(input, input_len as usize);
+    let state: &mut SIPState = cast_mut(state);
+    state.parse_tcp(flow, buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_sip_parse_response_tcp(
+    flow: *const core::Flow,
+    state: *mut std::os::raw::c_void,
+    pstate: *mut std::os::raw::c_void,
+    input: *const u8,
+    input_len: u32,
+    _data: *const std::os::raw::c_void,
+    _flags: u8,
+) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let buf = build_slice!(input, input_len as usize);
+    let state: &mut SIPState = cast_mut(state);
+    state.parse_tcp(flow, buf, Direction::ToClient)
 }
 
 export_tx_data_get!(rs_sip_get_tx_data, SIPTransaction);
@@ -332,6 +507,7 @@ pub unsafe extern "C" fn rs_sip_register_parser() {
         apply_tx_config: None,
         flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("udp").unwrap();
@@ -345,3 +521,130 @@ pub unsafe extern "C" fn rs_sip_register_parser() {
         SCLogDebug!("Protocol detecter and parser disabled for SIP/UDP.");
     }
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_sip_tcp_register_parser() {
+    let default_port = CString::new("5060").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: core::IPPROTO_TCP,
+        probe_ts: Some(rs_sip_probing_parser_tcp_ts),
+        probe_tc: Some(rs_sip_probing_parser_tcp_tc),
+        min_depth: 0,
+        max_depth: 1024,
+        state_new: rs_sip_state_new,
+        state_free: rs_sip_state_free,
+        tx_free: rs_sip_state_tx_free,
+        parse_ts: rs_sip_parse_request_tcp,
+        parse_tc: rs_sip_parse_response_tcp,
+        get_tx_count: rs_sip_state_get_tx_count,
+        get_tx: rs_sip_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_sip_tx_get_alstate_progress,
+        get_de_state: rs_sip_state_get_tx_detect_state,
+        set_de_state: rs_sip_state_set_tx_detect_state,
+        get_events: Some(rs_sip_state_get_events),
+        get_eventinfo: Some(SIPEvent::get_event_info),
+        get_eventinfo_byid: Some(SIPEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: None,
+        get_tx_data: rs_sip_get_tx_data,
+        apply_tx_config: None,
+        flags: APP_LAYER_PARSER_OPT_UNIDIR_TXS,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_SIP = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+    } else {
+        SCLogDebug!("Protocol detecter and parser disabled for SIP/TCP.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_sip_state_new,
+            state_free: rs_sip_state_free,
+            parse_ts: rs_sip_parse_request_tcp,
+            parse_tc: rs_sip_parse_response_tcp,
+            get_tx_count: rs_sip_state_get_tx_count,
+            get_tx: rs_sip_state_get_tx,
+            tx_free: rs_sip_state_tx_free,
+        })
+    }
+
+    const INVITE: &[u8] = b"INVITE sip:bob@example.com SIP/2.0\r\n\
+        Call-ID: a84b4c76e66710\r\n\
+        Content-Type: application/sdp\r\n\
+        Content-Length: 10\r\n\
+        \r\n\
+        v=0\r\ns=-\r\n";
+
+    const BYE: &[u8] = b"BYE sip:bob@example.com SIP/2.0\r\n\
+        Call-ID: a84b4c76e66710\r\n\
+        l: 0\r\n\
+        \r\n";
+
+    #[test]
+    fn test_sip_tcp_segments() {
+        let mut h = harness();
+        assert!(h.feed_segments(Direction::ToServer, INVITE, 7));
+        assert_eq!(h.tx_count(), 1);
+        assert_eq!(h.pending(Direction::ToServer), 0);
+        let tx = h.get_tx::<SIPTransaction>(0).unwrap();
+        assert_eq!(tx.request.as_ref().unwrap().method, "INVITE");
+        assert_eq!(tx.sdp.as_ref().unwrap().session_name.as_ref().unwrap(), "-");
+        assert_eq!(tx.call_id(), Some("a84b4c76e66710"));
+    }
+
+    #[test]
+    fn test_sip_header_buffer() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, BYE));
+        let tx = h.get_tx::<SIPTransaction>(0).unwrap();
+        assert_eq!(&tx.header_buffer[..],
+                   &b"Call-ID: a84b4c76e66710\r\nContent-Length: 0\r\n"[..]);
+        assert!(tx.sdp.is_none());
+    }
+
+    #[test]
+    fn test_sip_tcp_pipelined() {
+        let mut h = harness();
+        let mut buf = INVITE.to_vec();
+        buf.extend_from_slice(b"\r\n\r\n");
+        buf.extend_from_slice(BYE);
+        buf.extend_from_slice(&BYE[..20]);
+        assert!(h.feed(Direction::ToServer, &buf));
+        assert_eq!(h.tx_count(), 2);
+        assert!(h.feed(Direction::ToServer, &BYE[20..]));
+        assert_eq!(h.tx_count(), 3);
+        let tx = h.get_tx::<SIPTransaction>(2).unwrap();
+        assert_eq!(tx.request.as_ref().unwrap().method, "BYE");
+
+        assert!(h.feed(Direction::ToClient, b"SIP/2.0 200 OK\r\nContent-Length: 0\r\n\r\n"));
+        assert_eq!(h.tx_count(), 4);
+        let tx = h.get_tx::<SIPTransaction>(3).unwrap();
+        assert_eq!(tx.response.as_ref().unwrap().code, "200");
+    }
+
+    #[test]
+    fn test_sip_tcp_invalid_length() {
+        let mut h = harness();
+        assert!(!h.feed(Direction::ToServer, b"BYE sip:bob@example.com SIP/2.0\r\nContent-Length: -1\r\n\r\n"));
+    }
+}
@@ -151,10 +151,45 @@ fn parse_secblob_spnego(blob: &[u8]) -> Option<SpnegoRequest>
 
 #[derive(Debug,PartialEq)]
 pub struct NtlmsspData {
+    pub msg_type: u32,
     pub host: Vec<u8>,
     pub user: Vec<u8>,
     pub domain: Vec<u8>,
     pub version: Option<NTLMSSPVersion>,
+    pub flags: u32,
+    /// NTLM version of the AUTHENTICATE response, 0 if there is none
+    pub ntlm_version: u8,
+    pub anonymous: bool,
+}
+
+/// strip the UTF-16 encoding of plain ascii names
+fn ntlmssp_string(s: &[u8]) -> Vec<u8> {
+    let mut v = s.to_vec();
+    v.retain(|&i|i != 0x00);
+    v
+}
+
+/// The NT response of NTLMv1 is 24 bytes, NTLMv2 responses are longer.
+/// Anonymous authentication has an empty user name and NT response.
+fn ntlmssp_auth_data(ad: NTLMSSPAuthRecord) -> NtlmsspData
+{
+    let ntlm_version = match ad.nt_response.len() {
+        0 => 0,
+        24 => 1,
+        _ => 2,
+    };
+    let anonymous = ad.flags & NTLMSSP_NEGOTIATE_ANONYMOUS != 0 ||
+        (ad.user.is_empty() && ad.nt_response.is_empty() && ad.lm_response.len() <= 1);
+    NtlmsspData {
+        msg_type: NTLMSSP_AUTH,
+        host: ntlmssp_string(ad.host),
+        user: ntlmssp_string(ad.user),
+        domain: ntlmssp_string(ad.domain),
+        version: ad.version,
+        flags: ad.flags,
+        ntlm_version: ntlm_version,
+        anonymous: anonymous,
+    }
 }
 
 /// take in blob, search for the header and parse it
@@ -169,25 +204,29 @@ fn parse_ntlmssp_blob(blob: &[u8]) -> Option<NtlmsspData>
                     nd.msg_type, &ntlmssp_type_string(nd.msg_type), nd);
             match nd.msg_type {
                 NTLMSSP_NEGOTIATE => {
+                    match parse_ntlm_negotiate_record(nd.data) {
+                        Ok((_, nr)) => {
+                            SCLogDebug!("negotiate data {:?}", nr);
+                            let d = NtlmsspData {
+                                msg_type: NTLMSSP_NEGOTIATE,
+                                host: ntlmssp_string(nr.workstation),
+                                user: Vec::new(),
+                                domain: ntlmssp_string(nr.domain),
+                                version: nr.version,
+                                flags: nr.flags,
+                                ntlm_version: 0,
+                                anonymous: false,
+                            };
+                            ntlmssp_data = Some(d);
+                        },
+                        _ => {},
+                    }
                 },
                 NTLMSSP_AUTH => {
                     match parse_ntlm_auth_record(nd.data) {
                         Ok((_, ad)) => {
                             SCLogDebug!("auth data {:?}", ad);
-                            let mut host = ad.host.to_vec();
-                            host.retain(|&i|i != 0x00);
-                            let mut user = ad.user.to_vec();
-                            user.retain(|&i|i != 0x00);
-                            let mut domain = ad.domain.to_vec();
-                            domain.retain(|&i|i != 0x00);
-
-                            let d = NtlmsspData {
-                                host: host,
-                                user: user,
-                                domain: domain,
-                                version: ad.version,
-                            };
-                            ntlmssp_data = Some(d);
+                            ntlmssp_data = Some(ntlmssp_auth_data(ad));
                         },
                         _ => {},
                     }
@@ -200,6 +239,53 @@ fn parse_ntlmssp_blob(blob: &[u8]) -> Option<NtlmsspData>
     return ntlmssp_data;
 }
 
+#[derive(Debug,PartialEq)]
+pub struct NtlmsspChallenge {
+    pub target_name: Vec<u8>,
+    pub nb_domain: Vec<u8>,
+    pub nb_computer: Vec<u8>,
+    pub dns_domain: Vec<u8>,
+    pub dns_computer: Vec<u8>,
+    pub version: Option<NTLMSSPVersion>,
+    pub flags: u32,
+}
+
+/// parse the CHALLENGE from the security blob of a session setup
+/// response. The SPNEGO wrapping is skipped by searching for the header.
+pub fn parse_ntlmssp_challenge_blob(blob: &[u8]) -> Option<NtlmsspChallenge>
+{
+    match parse_ntlmssp(blob) {
+        Ok((_, ref nd)) if nd.msg_type == NTLMSSP_CHALLENGE => {
+            match parse_ntlm_challenge_record(nd.data) {
+                Ok((_, cr)) => {
+                    SCLogDebug!("challenge data {:?}", cr);
+                    let mut c = NtlmsspChallenge {
+                        target_name: ntlmssp_string(cr.target_name),
+                        nb_domain: Vec::new(),
+                        nb_computer: Vec::new(),
+                        dns_domain: Vec::new(),
+                        dns_computer: Vec::new(),
+                        version: cr.version,
+                        flags: cr.flags,
+                    };
+                    for av in cr.target_info {
+                        match av.av_id {
+                            NTLMSSP_AV_NB_DOMAIN_NAME => { c.nb_domain = ntlmssp_string(av.value); },
+                            NTLMSSP_AV_NB_COMPUTER_NAME => { c.nb_computer = ntlmssp_string(av.value); },
+                            NTLMSSP_AV_DNS_DOMAIN_NAME => { c.dns_domain = ntlmssp_string(av.value); },
+                            NTLMSSP_AV_DNS_COMPUTER_NAME => { c.dns_computer = ntlmssp_string(av.value); },
+                            _ => {},
+                        }
+                    }
+                    Some(c)
+                },
+                _ => { None },
+            }
+        },
+        _ => { None },
+    }
+}
+
 // if spnego parsing fails try to fall back to ntlmssp
 pub fn parse_secblob(blob: &[u8]) -> Option<SpnegoRequest>
 {
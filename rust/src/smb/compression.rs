@@ -0,0 +1,539 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Decompression of SMB 3.1.1 COMPRESSION_TRANSFORM records.
+//!
+//! The algorithms are described in MS-XCA: LZNT1, plain LZ77 and
+//! LZ77+Huffman, plus LZ4 and the Pattern_V1 run length encoding of
+//! MS-SMB2. Decompressed buffers are accounted against a global memcap.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::smb::smb3::*;
+
+pub const SMB3_COMPRESSION_NONE:            u16 = 0;
+pub const SMB3_COMPRESSION_LZNT1:           u16 = 1;
+pub const SMB3_COMPRESSION_LZ77:            u16 = 2;
+pub const SMB3_COMPRESSION_LZ77_HUFFMAN:    u16 = 3;
+pub const SMB3_COMPRESSION_PATTERN_V1:      u16 = 4;
+pub const SMB3_COMPRESSION_LZ4:             u16 = 5;
+
+/// max size of a decompressed record
+pub static mut SMB3_DECOMPRESS_MAX_SIZE: usize = 8 * 1024 * 1024;
+/// max memory used by decompressed records in all flows
+pub static mut SMB3_DECOMPRESS_MEMCAP: usize = 64 * 1024 * 1024;
+static SMB3_DECOMPRESS_MEMUSE: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug,PartialEq)]
+pub enum DecompressError {
+    /// algorithm we don't implement
+    Unsupported,
+    /// invalid compressed data
+    Malformed,
+    /// record larger than the max size or memcap reached
+    TooLarge,
+}
+
+/// Decompressed record. Its memory is accounted against the memcap
+/// until it is dropped.
+#[derive(Debug)]
+pub struct Smb3DecompressedRecord {
+    pub data: Vec<u8>,
+    reserved: usize,
+}
+
+impl Drop for Smb3DecompressedRecord {
+    fn drop(&mut self) {
+        SMB3_DECOMPRESS_MEMUSE.fetch_sub(self.reserved, Ordering::Relaxed);
+    }
+}
+
+fn read_u16(input: &[u8], pos: usize) -> Result<u16, DecompressError> {
+    if pos + 2 > input.len() {
+        return Err(DecompressError::Malformed);
+    }
+    Ok(u16::from_le_bytes([input[pos], input[pos + 1]]))
+}
+
+fn read_u32(input: &[u8], pos: usize) -> Result<u32, DecompressError> {
+    if pos + 4 > input.len() {
+        return Err(DecompressError::Malformed);
+    }
+    Ok(u32::from_le_bytes([input[pos], input[pos + 1], input[pos + 2], input[pos + 3]]))
+}
+
+fn read_u8(input: &[u8], pos: usize) -> Result<u8, DecompressError> {
+    input.get(pos).cloned().ok_or(DecompressError::Malformed)
+}
+
+fn push_literal(out: &mut Vec<u8>, b: u8, limit: usize) -> Result<(), DecompressError> {
+    if out.len() >= limit {
+        return Err(DecompressError::TooLarge);
+    }
+    out.push(b);
+    Ok(())
+}
+
+/// Copy a match of `length` bytes starting `offset` bytes back. Matches
+/// can't reference data before `base`, the start of the payload, and can
+/// overlap with the bytes they produce.
+fn copy_match(out: &mut Vec<u8>, base: usize, offset: usize, length: usize, limit: usize)
+    -> Result<(), DecompressError>
+{
+    if offset == 0 || offset > out.len() - base {
+        return Err(DecompressError::Malformed);
+    }
+    if out.len() + length > limit {
+        return Err(DecompressError::TooLarge);
+    }
+    let from = out.len() - offset;
+    for i in 0..length {
+        let b = out[from + i];
+        out.push(b);
+    }
+    Ok(())
+}
+
+/// MS-XCA 2.5 LZNT1
+fn lznt1_decompress(input: &[u8], out: &mut Vec<u8>, limit: usize)
+    -> Result<(), DecompressError>
+{
+    let mut pos = 0;
+    while pos + 2 <= input.len() {
+        let hdr = read_u16(input, pos)?;
+        pos += 2;
+        if hdr == 0 {
+            break;
+        }
+        let size = (hdr & 0x0fff) as usize + 1;
+        if pos + size > input.len() {
+            return Err(DecompressError::Malformed);
+        }
+        let chunk = &input[pos..pos + size];
+        pos += size;
+
+        if hdr & 0x8000 == 0 {
+            if out.len() + size > limit {
+                return Err(DecompressError::TooLarge);
+            }
+            out.extend_from_slice(chunk);
+            continue;
+        }
+
+        let chunk_start = out.len();
+        let mut cpos = 0;
+        while cpos < chunk.len() {
+            let flags = chunk[cpos];
+            cpos += 1;
+            for bit in 0..8 {
+                if cpos >= chunk.len() {
+                    break;
+                }
+                if flags & (1 << bit) == 0 {
+                    push_literal(out, chunk[cpos], limit)?;
+                    cpos += 1;
+                    continue;
+                }
+                let token = read_u16(chunk, cpos)?;
+                cpos += 2;
+                let decoded = out.len() - chunk_start;
+                if decoded == 0 {
+                    return Err(DecompressError::Malformed);
+                }
+                // the split between offset and length depends on the
+                // position in the chunk
+                let mut length_mask: u16 = 0x0fff;
+                let mut offset_shift = 12;
+                let mut n = decoded - 1;
+                while n >= 0x10 {
+                    length_mask >>= 1;
+                    offset_shift -= 1;
+                    n >>= 1;
+                }
+                let length = (token & length_mask) as usize + 3;
+                let offset = (token >> offset_shift) as usize + 1;
+                copy_match(out, chunk_start, offset, length, limit)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// MS-XCA 2.4 plain LZ77
+fn lz77_decompress(input: &[u8], out: &mut Vec<u8>, limit: usize)
+    -> Result<(), DecompressError>
+{
+    let base = out.len();
+    let mut pos = 0;
+    let mut flags: u32 = 0;
+    let mut flag_count = 0;
+    let mut last_length_half_byte = 0;
+
+    while out.len() < limit {
+        if flag_count == 0 {
+            if pos + 4 > input.len() {
+                break;
+            }
+            flags = read_u32(input, pos)?;
+            pos += 4;
+            flag_count = 32;
+        }
+        flag_count -= 1;
+
+        if flags & (1 << flag_count) == 0 {
+            if pos >= input.len() {
+                break;
+            }
+            push_literal(out, input[pos], limit)?;
+            pos += 1;
+            continue;
+        }
+
+        if pos == input.len() {
+            break;
+        }
+        let match_bytes = read_u16(input, pos)?;
+        pos += 2;
+        let mut length = (match_bytes % 8) as usize;
+        let offset = (match_bytes / 8) as usize + 1;
+        if length == 7 {
+            // lengths of 7 and more use half bytes, shared by two matches
+            if last_length_half_byte == 0 {
+                length = (read_u8(input, pos)? % 16) as usize;
+                last_length_half_byte = pos;
+                pos += 1;
+            } else {
+                length = (input[last_length_half_byte] / 16) as usize;
+                last_length_half_byte = 0;
+            }
+            if length == 15 {
+                length = read_u8(input, pos)? as usize;
+                pos += 1;
+                if length == 255 {
+                    length = read_u16(input, pos)? as usize;
+                    pos += 2;
+                    if length == 0 {
+                        length = read_u32(input, pos)? as usize;
+                        pos += 4;
+                    }
+                    if length < 15 + 7 {
+                        return Err(DecompressError::Malformed);
+                    }
+                    length -= 15 + 7;
+                }
+                length += 15;
+            }
+            length += 7;
+        }
+        length += 3;
+        copy_match(out, base, offset, length, limit)?;
+    }
+    Ok(())
+}
+
+const HUFFMAN_TABLE_BITS: u32 = 15;
+
+/// Build the decoding table of a LZ77+Huffman block from the 4 bit code
+/// lengths of its 512 symbols. Entries hold the symbol in the low 9 bits
+/// and the code length above.
+fn huffman_build_table(lengths: &[u8], table: &mut Vec<u16>) -> Result<(), DecompressError> {
+    for e in table.iter_mut() {
+        *e = 0;
+    }
+    let mut entry = 0;
+    for bit_len in 1..=HUFFMAN_TABLE_BITS {
+        for symbol in 0..512 {
+            let b = lengths[symbol / 2];
+            let len = (if symbol % 2 == 0 { b & 0x0f } else { b >> 4 }) as u32;
+            if len != bit_len {
+                continue;
+            }
+            let n = 1 << (HUFFMAN_TABLE_BITS - bit_len);
+            if entry + n > table.len() {
+                return Err(DecompressError::Malformed);
+            }
+            for e in &mut table[entry..entry + n] {
+                *e = ((bit_len as u16) << 9) | symbol as u16;
+            }
+            entry += n;
+        }
+    }
+    Ok(())
+}
+
+/// MS-XCA 2.2 LZ77+Huffman
+fn lz77_huffman_decompress(input: &[u8], out: &mut Vec<u8>, limit: usize)
+    -> Result<(), DecompressError>
+{
+    let base = out.len();
+    let mut pos = 0;
+    let mut table = vec![0u16; 1 << HUFFMAN_TABLE_BITS];
+
+    while pos < input.len() && out.len() < limit {
+        if pos + 256 > input.len() {
+            return Err(DecompressError::Malformed);
+        }
+        huffman_build_table(&input[pos..pos + 256], &mut table)?;
+        pos += 256;
+
+        let mut next_bits = (read_u16(input, pos)? as u32) << 16;
+        next_bits |= read_u16(input, pos + 2)? as u32;
+        pos += 4;
+        let mut extra_bits: i32 = 16;
+        let block_end = out.len() + 65536;
+
+        while out.len() < block_end && out.len() < limit {
+            let e = table[(next_bits >> (32 - HUFFMAN_TABLE_BITS)) as usize];
+            let bit_len = (e >> 9) as u32;
+            if bit_len == 0 {
+                return Err(DecompressError::Malformed);
+            }
+            let symbol = (e & 0x1ff) as usize;
+            next_bits <<= bit_len;
+            extra_bits -= bit_len as i32;
+            if extra_bits < 0 {
+                // the bit stream can end before the last symbols
+                let w = read_u16(input, pos).unwrap_or(0);
+                pos += 2;
+                next_bits |= (w as u32) << (-extra_bits);
+                extra_bits += 16;
+            }
+
+            if symbol < 256 {
+                push_literal(out, symbol as u8, limit)?;
+                continue;
+            }
+            if symbol == 256 && pos >= input.len() {
+                // end of stream marker
+                return Ok(());
+            }
+            let symbol = symbol - 256;
+            let mut length = symbol & 0x0f;
+            let offset_bits = (symbol >> 4) as u32;
+            if length == 15 {
+                length = read_u8(input, pos)? as usize;
+                pos += 1;
+                if length == 255 {
+                    length = read_u16(input, pos)? as usize;
+                    pos += 2;
+                    if length < 15 {
+                        return Err(DecompressError::Malformed);
+                    }
+                    length -= 15;
+                }
+                length += 15;
+            }
+            length += 3;
+
+            let mut offset = 1 << offset_bits;
+            if offset_bits > 0 {
+                offset += (next_bits >> (32 - offset_bits)) as usize;
+                next_bits <<= offset_bits;
+                extra_bits -= offset_bits as i32;
+                if extra_bits < 0 {
+                    let w = read_u16(input, pos).unwrap_or(0);
+                    pos += 2;
+                    next_bits |= (w as u32) << (-extra_bits);
+                    extra_bits += 16;
+                }
+            }
+            copy_match(out, base, offset, length, limit)?;
+        }
+    }
+    Ok(())
+}
+
+fn lz4_length(input: &[u8], pos: &mut usize, mut length: usize) -> Result<usize, DecompressError> {
+    if length == 15 {
+        loop {
+            let b = read_u8(input, *pos)?;
+            *pos += 1;
+            length += b as usize;
+            if b != 255 {
+                break;
+            }
+        }
+    }
+    Ok(length)
+}
+
+/// LZ4 block format
+fn lz4_decompress(input: &[u8], out: &mut Vec<u8>, limit: usize)
+    -> Result<(), DecompressError>
+{
+    let base = out.len();
+    let mut pos = 0;
+    while pos < input.len() {
+        let token = input[pos];
+        pos += 1;
+        let literals = lz4_length(input, &mut pos, (token >> 4) as usize)?;
+        if pos + literals > input.len() {
+            return Err(DecompressError::Malformed);
+        }
+        if out.len() + literals > limit {
+            return Err(DecompressError::TooLarge);
+        }
+        out.extend_from_slice(&input[pos..pos + literals]);
+        pos += literals;
+        // the last sequence only has literals
+        if pos >= input.len() {
+            break;
+        }
+        let offset = read_u16(input, pos)? as usize;
+        pos += 2;
+        let length = lz4_length(input, &mut pos, (token & 0x0f) as usize)? + 4;
+        copy_match(out, base, offset, length, limit)?;
+    }
+    Ok(())
+}
+
+/// Pattern_V1: a byte repeated a number of times
+fn pattern_v1_decompress(input: &[u8], out: &mut Vec<u8>, limit: usize)
+    -> Result<(), DecompressError>
+{
+    let pattern = read_u8(input, 0)?;
+    let repetitions = read_u32(input, 4)? as usize;
+    if out.len() + repetitions > limit {
+        return Err(DecompressError::TooLarge);
+    }
+    out.resize(out.len() + repetitions, pattern);
+    Ok(())
+}
+
+fn decompress_payload(payload: &Smb3CompressedPayload, out: &mut Vec<u8>, limit: usize)
+    -> Result<(), DecompressError>
+{
+    match payload.algorithm {
+        SMB3_COMPRESSION_NONE => {
+            if out.len() + payload.data.len() > limit {
+                return Err(DecompressError::TooLarge);
+            }
+            out.extend_from_slice(payload.data);
+            Ok(())
+        },
+        SMB3_COMPRESSION_PATTERN_V1 => pattern_v1_decompress(payload.data, out, limit),
+        _ => {
+            let size = payload.original_size.unwrap_or(0) as usize;
+            if out.len() + size > limit {
+                return Err(DecompressError::TooLarge);
+            }
+            let limit = out.len() + size;
+            match payload.algorithm {
+                SMB3_COMPRESSION_LZNT1 => lznt1_decompress(payload.data, out, limit),
+                SMB3_COMPRESSION_LZ77 => lz77_decompress(payload.data, out, limit),
+                SMB3_COMPRESSION_LZ77_HUFFMAN => lz77_huffman_decompress(payload.data, out, limit),
+                SMB3_COMPRESSION_LZ4 => lz4_decompress(payload.data, out, limit),
+                _ => Err(DecompressError::Unsupported),
+            }
+        },
+    }
+}
+
+/// Decompress a COMPRESSION_TRANSFORM record into the SMB2 records it
+/// carries.
+pub fn smb3_decompress_record(rec: &Smb3CompressionTransformRecord)
+    -> Result<Smb3DecompressedRecord, DecompressError>
+{
+    let size = rec.uncompressed.len() + rec.original_size as usize;
+    let (max_size, memcap) = unsafe { (SMB3_DECOMPRESS_MAX_SIZE, SMB3_DECOMPRESS_MEMCAP) };
+    if size > max_size {
+        return Err(DecompressError::TooLarge);
+    }
+    let memuse = SMB3_DECOMPRESS_MEMUSE.fetch_add(size, Ordering::Relaxed);
+    // from here the reservation is released when the record is dropped
+    let mut record = Smb3DecompressedRecord {
+        data: Vec::new(),
+        reserved: size,
+    };
+    if memuse + size > memcap {
+        return Err(DecompressError::TooLarge);
+    }
+
+    record.data.reserve_exact(size);
+    record.data.extend_from_slice(rec.uncompressed);
+    for payload in &rec.payloads {
+        decompress_payload(payload, &mut record.data, size)?;
+    }
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decompress(algorithm: u16, input: &[u8], size: usize) -> Result<Vec<u8>, DecompressError> {
+        let payload = Smb3CompressedPayload {
+            algorithm,
+            flags: 0,
+            original_size: Some(size as u32),
+            data: input,
+        };
+        let mut out = Vec::new();
+        decompress_payload(&payload, &mut out, size)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_lznt1() {
+        // "abcabcabcabc": 3 literals and a match of 9 at offset 3
+        let input: &[u8] = &[
+            0x05, 0xb0, // compressed chunk of 6 bytes
+            0x08, 0x61, 0x62, 0x63, 0x06, 0x20,
+        ];
+        assert_eq!(decompress(SMB3_COMPRESSION_LZNT1, input, 12).unwrap(),
+                b"abcabcabcabc".to_vec());
+    }
+
+    #[test]
+    fn test_lz77() {
+        // 3 literals and a match of 9 at offset 3, then end of input
+        let input: &[u8] = &[
+            0xff, 0xff, 0xff, 0x1f, // flags: 0001 then all ones
+            0x61, 0x62, 0x63,
+            0x16, 0x00, // offset 3, length 6 + 3
+        ];
+        assert_eq!(decompress(SMB3_COMPRESSION_LZ77, input, 12).unwrap(),
+                b"abcabcabcabc".to_vec());
+    }
+
+    #[test]
+    fn test_lz4() {
+        // 3 literals then a match of 9 at offset 3, then 1 literal
+        let input: &[u8] = &[
+            0x35, 0x61, 0x62, 0x63, 0x03, 0x00,
+            0x10, 0x64,
+        ];
+        assert_eq!(decompress(SMB3_COMPRESSION_LZ4, input, 13).unwrap(),
+                b"abcabcabcabcd".to_vec());
+    }
+
+    #[test]
+    fn test_pattern_v1() {
+        let input: &[u8] = &[0x41, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00];
+        assert_eq!(decompress(SMB3_COMPRESSION_PATTERN_V1, input, 4).unwrap(),
+                b"AAAA".to_vec());
+        assert_eq!(decompress(SMB3_COMPRESSION_PATTERN_V1, input, 3),
+                Err(DecompressError::TooLarge));
+    }
+
+    #[test]
+    fn test_match_before_start() {
+        let input: &[u8] = &[0x05, 0xb0, 0x08, 0x61, 0x62, 0x63, 0x06, 0x40];
+        assert_eq!(decompress(SMB3_COMPRESSION_LZNT1, input, 12),
+                Err(DecompressError::Malformed));
+    }
+}
@@ -19,6 +19,13 @@ use std::ptr;
 use crate::core::*;
 use crate::smb::smb::*;
 use crate::dcerpc::detect::{DCEIfaceData, DCEOpnumData, DETECT_DCE_OPNUM_RANGE_UNINITIALIZED};
+use crate::smb::auth::NtlmsspData;
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_smb_tx_get_share(tx: &mut SMBTransaction,
@@ -198,3 +205,104 @@ pub extern "C" fn rs_smb_tx_get_dce_iface(state: &mut SMBState,
     }
     return 0;
 }
+
+static mut G_SMB_NTLMSSP_USER_BUFFER_ID: c_int = 0;
+static mut G_SMB_NTLMSSP_DOMAIN_BUFFER_ID: c_int = 0;
+
+/// NTLMSSP AUTHENTICATE message of a session setup tx
+fn smb_tx_ntlmssp_auth(tx: &SMBTransaction) -> Option<&NtlmsspData> {
+    match tx.type_data {
+        Some(SMBTransactionTypeData::SESSIONSETUP(ref x)) => match x.ntlmssp {
+            Some(ref n) if n.msg_type == NTLMSSP_AUTH => Some(n),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+unsafe extern "C" fn smb_tx_get_ntlmssp_user(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SMBTransaction = cast_mut(tx);
+    match smb_tx_ntlmssp_auth(tx) {
+        Some(n) if !n.user.is_empty() => {
+            *buf = n.user.as_ptr();
+            *len = n.user.len() as u32;
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn smb_tx_get_ntlmssp_domain(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SMBTransaction = cast_mut(tx);
+    match smb_tx_ntlmssp_auth(tx) {
+        Some(n) if !n.domain.is_empty() => {
+            *buf = n.domain.as_ptr();
+            *len = n.domain.len() as u32;
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn smb_ntlmssp_user_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, smb_tx_get_ntlmssp_user)
+}
+
+unsafe extern "C" fn smb_ntlmssp_domain_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, smb_tx_get_ntlmssp_domain)
+}
+
+unsafe extern "C" fn smb_ntlmssp_user_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SMB, G_SMB_NTLMSSP_USER_BUFFER_ID)
+}
+
+unsafe extern "C" fn smb_ntlmssp_domain_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SMB, G_SMB_NTLMSSP_DOMAIN_BUFFER_ID)
+}
+
+/// Register the SMB keywords implemented in Rust.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "smb.ntlmssp_user",
+        desc: "sticky buffer to match on the user name of NTLMSSP authentication in SMB session setups",
+        url: "/rules/smb-keywords.html#smb-ntlmssp-user",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: smb_ntlmssp_user_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SMB_NTLMSSP_USER_BUFFER_ID = helper_buffer_mpm_register(
+            "smb.ntlmssp_user", "smb ntlmssp user", ALPROTO_SMB, false, true, 1,
+            smb_ntlmssp_user_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "smb.ntlmssp_domain",
+        desc: "sticky buffer to match on the domain of NTLMSSP authentication in SMB session setups",
+        url: "/rules/smb-keywords.html#smb-ntlmssp-domain",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: smb_ntlmssp_domain_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SMB_NTLMSSP_DOMAIN_BUFFER_ID = helper_buffer_mpm_register(
+            "smb.ntlmssp_domain", "smb ntlmssp domain", ALPROTO_SMB, false, true, 1,
+            smb_ntlmssp_domain_get_data);
+    }
+}
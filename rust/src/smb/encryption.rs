@@ -0,0 +1,272 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use crate::core::*;
+use crate::conf::conf_get_app_layer_node;
+use crate::smb::smb::*;
+use crate::smb::smb2::*;
+use crate::smb::smb2_records::*;
+use crate::smb::smb3::*;
+
+const SMB2_HEADER_SIZE: usize = 64;
+
+pub const SMB2_GLOBAL_CAP_ENCRYPTION: u32 = 0x0000_0040;
+pub const SMB2_SHAREFLAG_ENCRYPT_DATA: u32 = 0x0000_8000;
+
+pub const SMB2_ENCRYPTION_AES128_CCM: u16 = 0x0001;
+pub const SMB2_ENCRYPTION_AES128_GCM: u16 = 0x0002;
+pub const SMB2_ENCRYPTION_AES256_CCM: u16 = 0x0003;
+pub const SMB2_ENCRYPTION_AES256_GCM: u16 = 0x0004;
+
+pub fn smb3_cipher_string(c: u16) -> String {
+    match c {
+        SMB2_ENCRYPTION_AES128_CCM => "AES-128-CCM",
+        SMB2_ENCRYPTION_AES128_GCM => "AES-128-GCM",
+        SMB2_ENCRYPTION_AES256_CCM => "AES-256-CCM",
+        SMB2_ENCRYPTION_AES256_GCM => "AES-256-GCM",
+        _ => { return (c).to_string(); },
+    }.to_string()
+}
+
+/// Get the cipher selected by the server from a NEGOTIATE response.
+/// SMB 3.0 and 3.0.2 only support AES-128-CCM, SMB 3.1.1 announces
+/// the cipher in the encryption capabilities context. Returns 0 if
+/// encryption was not negotiated.
+pub fn smb2_negotiate_response_cipher(r: &Smb2Record, rd: &Smb2NegotiateProtocolResponseRecord) -> u16
+{
+    if rd.dialect < 0x0300 {
+        return 0;
+    }
+    if rd.dialect < 0x0311 {
+        if rd.capabilities & SMB2_GLOBAL_CAP_ENCRYPTION != 0 {
+            return SMB2_ENCRYPTION_AES128_CCM;
+        }
+        return 0;
+    }
+    // the context offset is relative to the start of the SMB2 header
+    let offset = (rd.ctx_offset as usize).saturating_sub(SMB2_HEADER_SIZE);
+    if rd.ctx_cnt == 0 || offset == 0 || offset >= r.data.len() {
+        return 0;
+    }
+    if let Ok((_, contexts)) = parse_smb2_negotiate_contexts(&r.data[offset..], rd.ctx_cnt) {
+        for ctx in contexts {
+            if ctx.ctx_type != SMB2_ENCRYPTION_CAPABILITIES {
+                continue;
+            }
+            // the server response holds the single selected cipher
+            if let Ok((_, ciphers)) = parse_smb2_encryption_capabilities(ctx.data) {
+                if let Some(c) = ciphers.first() {
+                    return *c;
+                }
+            }
+        }
+    }
+    return 0;
+}
+
+#[derive(Default, Debug)]
+pub struct SMBTransactionEncryption {
+    pub cipher: u16,
+    pub ts_records: u64,
+    pub ts_bytes: u64,
+    pub tc_records: u64,
+    pub tc_bytes: u64,
+}
+
+impl SMBTransactionEncryption {
+    pub fn new(cipher: u16) -> Self {
+        return Self {
+            cipher: cipher,
+            ..Default::default()
+        }
+    }
+}
+
+impl SMBState {
+    /// The encryption tx tracks all TRANSFORM records of a session. It is
+    /// kept open so that it is logged with the final counts at the end
+    /// of the flow.
+    pub fn new_encryption_tx(&mut self, hdr: SMBCommonHdr, cipher: u16)
+        -> &mut SMBTransaction
+    {
+        let mut tx = self.new_tx();
+        tx.hdr = hdr;
+        tx.type_data = Some(SMBTransactionTypeData::ENCRYPTION(
+                    SMBTransactionEncryption::new(cipher)));
+        tx.request_done = false;
+        tx.response_done = false;
+
+        SCLogDebug!("SMB: TX ENCRYPTION created: ID {} SSN {}", tx.id, tx.hdr.ssn_id);
+        self.transactions.push(tx);
+        let tx_ref = self.transactions.last_mut();
+        return tx_ref.unwrap();
+    }
+
+    pub fn get_encryption_tx(&mut self, ssn_id: u64)
+        -> Option<&mut SMBTransaction>
+    {
+        for tx in &mut self.transactions {
+            let hit = tx.hdr.ssn_id == ssn_id && match tx.type_data {
+                Some(SMBTransactionTypeData::ENCRYPTION(_)) => { true },
+                _ => { false },
+            };
+            if hit {
+                return Some(tx);
+            }
+        }
+        return None;
+    }
+}
+
+/// Account a TRANSFORM record. The payload itself is opaque to us.
+pub fn smb3_transform_record(state: &mut SMBState, r: &Smb3TransformRecord, direction: u8)
+{
+    // SMB 3.0.x uses the field for the algorithm, SMB 3.1.1 as flags
+    let cipher = if state.cipher != 0 {
+        state.cipher
+    } else if state.dialect < 0x0311 && r.enc_algo == SMB2_ENCRYPTION_AES128_CCM {
+        SMB2_ENCRYPTION_AES128_CCM
+    } else {
+        0
+    };
+    let tx = match state.get_encryption_tx(r.session_id) {
+        Some(tx) => tx,
+        None => {
+            let hdr = SMBCommonHdr::new(SMBHDR_TYPE_HEADER, r.session_id, 0, 0);
+            state.new_encryption_tx(hdr, cipher)
+        },
+    };
+    if let Some(SMBTransactionTypeData::ENCRYPTION(ref mut td)) = tx.type_data {
+        if td.cipher == 0 {
+            td.cipher = cipher;
+        }
+        if direction == STREAM_TOSERVER {
+            td.ts_records += 1;
+            td.ts_bytes += r.enc_data.len() as u64;
+        } else {
+            td.tc_records += 1;
+            td.tc_bytes += r.enc_data.len() as u64;
+        }
+    }
+}
+
+/// Shares for which plaintext access raises an event, in addition to
+/// the shares the server flags as requiring encryption.
+pub struct SMBEncryptionPolicy {
+    shares: Vec<String>,
+}
+
+impl SMBEncryptionPolicy {
+    /// Read app-layer.protocols.smb.encryption-policy. Returns None if
+    /// the policy is disabled.
+    fn from_config() -> Option<Self> {
+        let mut policy = Self { shares: Vec::new() };
+        let conf = match conf_get_app_layer_node("smb") {
+            Some(conf) => conf,
+            None => return Some(policy),
+        };
+        let node = match conf.get_child_node("encryption-policy") {
+            Some(node) => node,
+            None => return Some(policy),
+        };
+        if node.get_child_value("enabled").is_some() && !node.get_child_bool("enabled") {
+            return None;
+        }
+        policy.shares = node.get_child_string_list("shares");
+        Some(policy)
+    }
+
+    /// Share names are in the form \\server\share, configured names
+    /// are compared to the last component.
+    fn requires_encryption(&self, name: &[u8], share_flags: u32) -> bool {
+        if share_flags & SMB2_SHAREFLAG_ENCRYPT_DATA != 0 {
+            return true;
+        }
+        let share = match name.rsplit(|&c| c == b'\\').next() {
+            Some(share) => share,
+            None => return false,
+        };
+        self.shares.iter().any(|s| s.as_bytes().eq_ignore_ascii_case(share))
+    }
+}
+
+static mut SMB_ENCRYPTION_POLICY: Option<SMBEncryptionPolicy> = None;
+
+pub fn smb_encryption_policy_init() {
+    unsafe {
+        SMB_ENCRYPTION_POLICY = SMBEncryptionPolicy::from_config();
+    }
+}
+
+pub fn smb2_share_requires_encryption(name: &[u8], share_flags: u32) -> bool {
+    match unsafe { &SMB_ENCRYPTION_POLICY } {
+        Some(policy) => policy.requires_encryption(name, share_flags),
+        None => false,
+    }
+}
+
+/// Check a plaintext request against the encryption requirement of its
+/// tree. Returns true the first time a tree requiring encryption is
+/// accessed without it.
+pub fn smb2_check_encryption_policy(state: &mut SMBState, r: &Smb2Record) -> bool
+{
+    if r.tree_id == 0 || r.command == SMB2_COMMAND_TREE_CONNECT {
+        return false;
+    }
+    let tree_key = SMBCommonHdr::from2(r, SMBHDR_TYPE_SHARE);
+    match state.ssn2tree_map.get_mut(&tree_key) {
+        Some(tree) => {
+            if tree.encrypt_data && !tree.unencrypted_access {
+                tree.unencrypted_access = true;
+                return true;
+            }
+            false
+        },
+        None => { false },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smb3_encryption_policy_share_name() {
+        let policy = SMBEncryptionPolicy { shares: vec!["Finance".to_string()] };
+        assert!(policy.requires_encryption(b"\\srv\\finance", 0));
+        assert!(!policy.requires_encryption(b"\\srv\\public", 0));
+        assert!(policy.requires_encryption(b"\\srv\\public", SMB2_SHAREFLAG_ENCRYPT_DATA));
+    }
+
+    #[test]
+    fn test_smb2_negotiate_contexts() {
+        let buf: &[u8] = &[
+            // preauth integrity context, 6 bytes data + 2 padding
+            0x01, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+            // encryption capabilities, AES-128-GCM
+            0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x02, 0x00,
+        ];
+        let (rem, contexts) = parse_smb2_negotiate_contexts(buf, 2).unwrap();
+        assert_eq!(rem.len(), 0);
+        assert_eq!(contexts.len(), 2);
+        assert_eq!(contexts[1].ctx_type, SMB2_ENCRYPTION_CAPABILITIES);
+        let (_, ciphers) = parse_smb2_encryption_capabilities(contexts[1].data).unwrap();
+        assert_eq!(ciphers, vec![SMB2_ENCRYPTION_AES128_GCM]);
+    }
+}
@@ -17,6 +17,7 @@
 
 use crate::core::*;
 use crate::smb::smb::*;
+use crate::smb::compression::DecompressError;
 
 #[derive(AppLayerEvent)]
 pub enum SMBEvent {
@@ -28,6 +29,12 @@ pub enum SMBEvent {
     DuplicateNegotiate,
     NegotiateMalformedDialects,
     FileOverlap,
+    UnsupportedCompression,
+    DecompressionFailed,
+    DecompressionLimit,
+    UnencryptedShareAccess,
+    Ntlmv1Authentication,
+    AnonymousSession,
 }
 
 impl SMBTransaction {
@@ -44,6 +51,16 @@ impl SMBTransaction {
     }
 }
 
+impl From<DecompressError> for SMBEvent {
+    fn from(e: DecompressError) -> Self {
+        match e {
+            DecompressError::Unsupported => SMBEvent::UnsupportedCompression,
+            DecompressError::Malformed => SMBEvent::DecompressionFailed,
+            DecompressError::TooLarge => SMBEvent::DecompressionLimit,
+        }
+    }
+}
+
 impl SMBState {
     /// Set an event. The event is set on the most recent transaction.
     pub fn set_event(&mut self, event: SMBEvent) {
@@ -16,6 +16,7 @@
  */
 
 use std;
+use crate::applayer::cast_mut;
 use crate::core::*;
 use crate::filetracker::*;
 use crate::filecontainer::*;
@@ -103,23 +104,6 @@ impl SMBState {
         return None;
     }
 
-    fn getfiles(&mut self, direction: u8) -> * mut FileContainer {
-        //SCLogDebug!("direction: {}", direction);
-        if direction == STREAM_TOCLIENT {
-            &mut self.files.files_tc as *mut FileContainer
-        } else {
-            &mut self.files.files_ts as *mut FileContainer
-        }
-    }
-    fn setfileflags(&mut self, direction: u8, flags: u16) {
-        SCLogDebug!("direction: {}, flags: {}", direction, flags);
-        if direction == STREAM_TOCLIENT {
-            self.files.flags_tc = flags;
-        } else {
-            self.files.flags_ts = flags;
-        }
-    }
-
     // update in progress chunks for file transfers
     // return how much data we consumed
     pub fn filetracker_update(&mut self, direction: u8, data: &[u8], gap_size: u32) -> u32 {
@@ -190,17 +174,8 @@ impl SMBState {
     }
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_smb_getfiles(ptr: *mut std::ffi::c_void, direction: u8) -> * mut FileContainer {
-    if ptr.is_null() { panic!("NULL ptr"); };
-    let parser = cast_pointer!(ptr, SMBState);
-    parser.getfiles(direction)
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_smb_setfileflags(direction: u8, ptr: *mut SMBState, flags: u16) {
-    if ptr.is_null() { panic!("NULL ptr"); };
-    let parser = &mut *ptr;
-    SCLogDebug!("direction {} flags {}", direction, flags);
-    parser.setfileflags(direction, flags)
+impl FilesState for SMBState {
+    fn files(&mut self) -> &mut Files {
+        &mut self.files
+    }
 }
@@ -24,6 +24,7 @@ use crate::smb::smb1::*;
 use crate::smb::smb2::*;
 use crate::dcerpc::dcerpc::*;
 use crate::smb::funcs::*;
+use crate::smb::encryption::*;
 
 #[cfg(not(feature = "debug"))]
 fn debug_add_progress(_js: &mut JsonBuilder, _tx: &SMBTransaction) -> Result<(), JsonError> { Ok(()) }
@@ -138,22 +139,65 @@ fn smb_common_header(jsb: &mut JsonBuilder, state: &SMBState, tx: &SMBTransactio
         Some(SMBTransactionTypeData::SESSIONSETUP(ref x)) => {
             if let Some(ref ntlmssp) = x.ntlmssp {
                 jsb.open_object("ntlmssp")?;
-                let domain = String::from_utf8_lossy(&ntlmssp.domain);
-                jsb.set_string("domain", &domain)?;
+                jsb.set_string("type", &ntlmssp_type_string(ntlmssp.msg_type))?;
+                if ntlmssp.msg_type == NTLMSSP_AUTH {
+                    let domain = String::from_utf8_lossy(&ntlmssp.domain);
+                    jsb.set_string("domain", &domain)?;
 
-                let user = String::from_utf8_lossy(&ntlmssp.user);
-                jsb.set_string("user", &user)?;
+                    let user = String::from_utf8_lossy(&ntlmssp.user);
+                    jsb.set_string("user", &user)?;
 
-                let host = String::from_utf8_lossy(&ntlmssp.host);
-                jsb.set_string("host", &host)?;
+                    let host = String::from_utf8_lossy(&ntlmssp.host);
+                    jsb.set_string("host", &host)?;
+
+                    match ntlmssp.ntlm_version {
+                        1 => { jsb.set_string("ntlm_version", "NTLMv1")?; },
+                        2 => { jsb.set_string("ntlm_version", "NTLMv2")?; },
+                        _ => { },
+                    }
+                    jsb.set_bool("anonymous", ntlmssp.anonymous)?;
+                } else {
+                    if !ntlmssp.domain.is_empty() {
+                        let domain = String::from_utf8_lossy(&ntlmssp.domain);
+                        jsb.set_string("domain", &domain)?;
+                    }
+                    if !ntlmssp.host.is_empty() {
+                        let host = String::from_utf8_lossy(&ntlmssp.host);
+                        jsb.set_string("host", &host)?;
+                    }
+                }
 
                 if let Some(ref v) = ntlmssp.version {
                     jsb.set_string("version", v.to_string().as_str())?;
                 }
+                jsb.set_string("flags", &format!("0x{:08x}", ntlmssp.flags))?;
 
                 jsb.close()?;
             }
 
+            if let Some(ref c) = x.ntlmssp_challenge {
+                jsb.open_object("ntlmssp_challenge")?;
+                let target = String::from_utf8_lossy(&c.target_name);
+                jsb.set_string("target_name", &target)?;
+                if !c.nb_domain.is_empty() {
+                    jsb.set_string("netbios_domain", &String::from_utf8_lossy(&c.nb_domain))?;
+                }
+                if !c.nb_computer.is_empty() {
+                    jsb.set_string("netbios_computer", &String::from_utf8_lossy(&c.nb_computer))?;
+                }
+                if !c.dns_domain.is_empty() {
+                    jsb.set_string("dns_domain", &String::from_utf8_lossy(&c.dns_domain))?;
+                }
+                if !c.dns_computer.is_empty() {
+                    jsb.set_string("dns_computer", &String::from_utf8_lossy(&c.dns_computer))?;
+                }
+                if let Some(ref v) = c.version {
+                    jsb.set_string("version", v.to_string().as_str())?;
+                }
+                jsb.set_string("flags", &format!("0x{:08x}", c.flags))?;
+                jsb.close()?;
+            }
+
             if let Some(ref ticket) = x.krb_ticket {
                 jsb.open_object("kerberos")?;
                 jsb.set_string("realm", &ticket.realm.0)?;
@@ -249,6 +293,10 @@ fn smb_common_header(jsb: &mut JsonBuilder, state: &SMBState, tx: &SMBTransactio
             }
 
             jsb.set_string("server_guid", &guid_to_string(&x.server_guid))?;
+
+            if x.cipher != 0 {
+                jsb.set_string("cipher", &smb3_cipher_string(x.cipher))?;
+            }
         },
         Some(SMBTransactionTypeData::TREECONNECT(ref x)) => {
             jsb.set_uint("tree_id", x.tree_id as u64)?;
@@ -371,6 +419,17 @@ fn smb_common_header(jsb: &mut JsonBuilder, state: &SMBState, tx: &SMBTransactio
             jsb.set_uint("call_id", x.call_id as u64)?;
             jsb.close()?;
         }
+        Some(SMBTransactionTypeData::ENCRYPTION(ref x)) => {
+            jsb.open_object("encryption")?;
+            if x.cipher != 0 {
+                jsb.set_string("cipher", &smb3_cipher_string(x.cipher))?;
+            }
+            jsb.set_uint("records_toserver", x.ts_records)?;
+            jsb.set_uint("bytes_toserver", x.ts_bytes)?;
+            jsb.set_uint("records_toclient", x.tc_records)?;
+            jsb.set_uint("bytes_toclient", x.tc_bytes)?;
+            jsb.close()?;
+        },
         Some(SMBTransactionTypeData::IOCTL(ref x)) => {
             jsb.set_string("function", &fsctl_func_to_string(x.func))?;
         },
@@ -30,6 +30,8 @@ pub mod smb2;
 pub mod smb2_session;
 pub mod smb2_ioctl;
 pub mod smb3;
+pub mod compression;
+pub mod encryption;
 pub mod dcerpc;
 pub mod session;
 pub mod log;
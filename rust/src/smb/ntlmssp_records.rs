@@ -16,6 +16,7 @@
  */
 
 use nom::IResult;
+use nom::error::ErrorKind;
 use nom::combinator::rest;
 use nom::number::streaming::{le_u8, le_u16, le_u32};
 
@@ -50,65 +51,174 @@ named!(parse_ntlm_auth_version<NTLMSSPVersion>,
              })
 ));
 
-#[derive(Debug,PartialEq)]
-pub struct NTLMSSPAuthRecord<'a> {
-    pub domain: &'a[u8],
-    pub user: &'a[u8],
-    pub host: &'a[u8],
-    pub version: Option<NTLMSSPVersion>,
-}
+pub const NTLMSSP_NEGOTIATE_UNICODE:                  u32 = 0x0000_0001;
+pub const NTLMSSP_NEGOTIATE_ANONYMOUS:                u32 = 0x0000_0800;
+pub const NTLMSSP_NEGOTIATE_EXTENDED_SESSIONSECURITY: u32 = 0x0008_0000;
+pub const NTLMSSP_NEGOTIATE_TARGET_INFO:              u32 = 0x0080_0000;
+pub const NTLMSSP_NEGOTIATE_VERSION:                  u32 = 0x0200_0000;
+
+/// size of the identifier (8) and type (4) that are cut before the
+/// message parsers are called. Field offsets include them.
+const NTLMSSP_HEADER_SIZE: u32 = 12;
 
-fn parse_ntlm_auth_nego_flags(i:&[u8]) -> IResult<&[u8],(u8,u8,u32)> {
-    bits!(i, tuple!(take_bits!(6u8),take_bits!(1u8),take_bits!(25u32)))
+#[derive(Debug,PartialEq)]
+struct NTLMSSPField {
+    len: u16,
+    offset: u32,
 }
 
-named!(pub parse_ntlm_auth_record<NTLMSSPAuthRecord>,
+named!(parse_ntlmssp_field<NTLMSSPField>,
     do_parse!(
-            _lm_blob_len: le_u16
-         >> _lm_blob_maxlen: le_u16
-         >> _lm_blob_offset: le_u32
+            len: le_u16
+         >> _maxlen: le_u16
+         >> offset: le_u32
+         >> ( NTLMSSPField {
+                len: len,
+                offset: offset,
+             })
+));
 
-         >> _ntlmresp_blob_len: le_u16
-         >> _ntlmresp_blob_maxlen: le_u16
-         >> _ntlmresp_blob_offset: le_u32
+/// get the payload of a field from the message
+fn ntlmssp_field_data<'a>(msg: &'a[u8], field: &NTLMSSPField) -> IResult<&'a[u8], &'a[u8]> {
+    if field.len == 0 {
+        return Ok((msg, &[]));
+    }
+    let start = field.offset.saturating_sub(NTLMSSP_HEADER_SIZE) as usize;
+    match msg.get(start..start + field.len as usize) {
+        Some(data) => Ok((msg, data)),
+        None => Err(nom::Err::Error((msg, ErrorKind::Eof))),
+    }
+}
 
-         >> domain_blob_len: le_u16
-         >> _domain_blob_maxlen: le_u16
-         >> domain_blob_offset: le_u32
+#[derive(Debug,PartialEq)]
+pub struct NTLMSSPNegotiateRecord<'a> {
+    pub flags: u32,
+    pub domain: &'a[u8],
+    pub workstation: &'a[u8],
+    pub version: Option<NTLMSSPVersion>,
+}
 
-         >> user_blob_len: le_u16
-         >> _user_blob_maxlen: le_u16
-         >> _user_blob_offset: le_u32
+pub fn parse_ntlm_negotiate_record(i: &[u8]) -> IResult<&[u8], NTLMSSPNegotiateRecord> {
+    let (rem, flags) = le_u32(i)?;
+    let (rem, domain) = parse_ntlmssp_field(rem)?;
+    let (rem, workstation) = parse_ntlmssp_field(rem)?;
+    let (rem, version) = cond!(rem, flags & NTLMSSP_NEGOTIATE_VERSION != 0, parse_ntlm_auth_version)?;
+    let (_, domain) = ntlmssp_field_data(i, &domain)?;
+    let (_, workstation) = ntlmssp_field_data(i, &workstation)?;
+    Ok((rem, NTLMSSPNegotiateRecord {
+        flags: flags,
+        domain: domain,
+        workstation: workstation,
+        version: version,
+    }))
+}
 
-         >> host_blob_len: le_u16
-         >> _host_blob_maxlen: le_u16
-         >> _host_blob_offset: le_u32
+pub const NTLMSSP_AV_EOL:               u16 = 0;
+pub const NTLMSSP_AV_NB_COMPUTER_NAME:  u16 = 1;
+pub const NTLMSSP_AV_NB_DOMAIN_NAME:    u16 = 2;
+pub const NTLMSSP_AV_DNS_COMPUTER_NAME: u16 = 3;
+pub const NTLMSSP_AV_DNS_DOMAIN_NAME:   u16 = 4;
+pub const NTLMSSP_AV_DNS_TREE_NAME:     u16 = 5;
 
-         >> _ssnkey_blob_len: le_u16
-         >> _ssnkey_blob_maxlen: le_u16
-         >> _ssnkey_blob_offset: le_u32
+#[derive(Debug,PartialEq)]
+pub struct NTLMSSPAvPair<'a> {
+    pub av_id: u16,
+    pub value: &'a[u8],
+}
 
-         >> nego_flags: parse_ntlm_auth_nego_flags
-         >> version: cond!(nego_flags.1==1, parse_ntlm_auth_version)
+named!(parse_ntlmssp_av_pair<NTLMSSPAvPair>,
+    do_parse!(
+            av_id: le_u16
+         >> av_len: le_u16
+         >> value: take!(av_len)
+         >> ( NTLMSSPAvPair {
+                av_id: av_id,
+                value: value,
+             })
+));
 
-         // subtrack 12 as idenfier (8) and type (4) are cut before we are called
-         // subtract 60 for the len/offset/maxlen fields above
-         >> cond!(nego_flags.1==1, take!(domain_blob_offset - (12 + 60)))
-         // or 52 if we have no version
-         >> cond!(nego_flags.1==0, take!(domain_blob_offset - (12 + 52)))
+/// parse the AV_PAIR list up to MsvAvEOL
+fn parse_ntlmssp_av_pairs(i: &[u8]) -> IResult<&[u8], Vec<NTLMSSPAvPair>> {
+    let mut pairs = Vec::new();
+    let mut input = i;
+    loop {
+        let (rem, pair) = parse_ntlmssp_av_pair(input)?;
+        input = rem;
+        if pair.av_id == NTLMSSP_AV_EOL {
+            break;
+        }
+        pairs.push(pair);
+    }
+    Ok((input, pairs))
+}
 
-         >> domain_blob: take!(domain_blob_len)
-         >> user_blob: take!(user_blob_len)
-         >> host_blob: take!(host_blob_len)
+#[derive(Debug,PartialEq)]
+pub struct NTLMSSPChallengeRecord<'a> {
+    pub target_name: &'a[u8],
+    pub flags: u32,
+    pub server_challenge: &'a[u8],
+    pub target_info: Vec<NTLMSSPAvPair<'a>>,
+    pub version: Option<NTLMSSPVersion>,
+}
 
-         >> ( NTLMSSPAuthRecord {
-                domain: domain_blob,
-                user: user_blob,
-                host: host_blob,
+pub fn parse_ntlm_challenge_record(i: &[u8]) -> IResult<&[u8], NTLMSSPChallengeRecord> {
+    let (rem, target_name) = parse_ntlmssp_field(i)?;
+    let (rem, flags) = le_u32(rem)?;
+    let (rem, server_challenge) = take!(rem, 8)?;
+    let (rem, _reserved) = take!(rem, 8)?;
+    let (rem, target_info) = parse_ntlmssp_field(rem)?;
+    let (rem, version) = cond!(rem, flags & NTLMSSP_NEGOTIATE_VERSION != 0, parse_ntlm_auth_version)?;
+    let (_, target_name) = ntlmssp_field_data(i, &target_name)?;
+    let (_, target_info) = ntlmssp_field_data(i, &target_info)?;
+    let target_info = if flags & NTLMSSP_NEGOTIATE_TARGET_INFO != 0 && !target_info.is_empty() {
+        parse_ntlmssp_av_pairs(target_info)?.1
+    } else {
+        Vec::new()
+    };
+    Ok((rem, NTLMSSPChallengeRecord {
+        target_name: target_name,
+        flags: flags,
+        server_challenge: server_challenge,
+        target_info: target_info,
+        version: version,
+    }))
+}
 
-                version: version,
-            })
-));
+#[derive(Debug,PartialEq)]
+pub struct NTLMSSPAuthRecord<'a> {
+    pub domain: &'a[u8],
+    pub user: &'a[u8],
+    pub host: &'a[u8],
+    pub lm_response: &'a[u8],
+    pub nt_response: &'a[u8],
+    pub flags: u32,
+    pub version: Option<NTLMSSPVersion>,
+}
+
+pub fn parse_ntlm_auth_record(i: &[u8]) -> IResult<&[u8], NTLMSSPAuthRecord> {
+    let (rem, lm_response) = parse_ntlmssp_field(i)?;
+    let (rem, nt_response) = parse_ntlmssp_field(rem)?;
+    let (rem, domain) = parse_ntlmssp_field(rem)?;
+    let (rem, user) = parse_ntlmssp_field(rem)?;
+    let (rem, host) = parse_ntlmssp_field(rem)?;
+    let (rem, _ssnkey) = parse_ntlmssp_field(rem)?;
+    let (rem, flags) = le_u32(rem)?;
+    let (rem, version) = cond!(rem, flags & NTLMSSP_NEGOTIATE_VERSION != 0, parse_ntlm_auth_version)?;
+    let (_, lm_response) = ntlmssp_field_data(i, &lm_response)?;
+    let (_, nt_response) = ntlmssp_field_data(i, &nt_response)?;
+    let (_, domain) = ntlmssp_field_data(i, &domain)?;
+    let (_, user) = ntlmssp_field_data(i, &user)?;
+    let (_, host) = ntlmssp_field_data(i, &host)?;
+    Ok((rem, NTLMSSPAuthRecord {
+        domain: domain,
+        user: user,
+        host: host,
+        lm_response: lm_response,
+        nt_response: nt_response,
+        flags: flags,
+        version: version,
+    }))
+}
 
 #[derive(Debug,PartialEq)]
 pub struct NTLMSSPRecord<'a> {
@@ -127,3 +237,49 @@ named!(pub parse_ntlmssp<NTLMSSPRecord>,
                 data:data,
             })
 ));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ntlm_auth_record() {
+        let mut buf: Vec<u8> = vec![
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // lm response
+            0x18, 0x00, 0x18, 0x00, 0x4e, 0x00, 0x00, 0x00, // nt response
+            0x02, 0x00, 0x02, 0x00, 0x48, 0x00, 0x00, 0x00, // domain
+            0x02, 0x00, 0x02, 0x00, 0x4a, 0x00, 0x00, 0x00, // user
+            0x02, 0x00, 0x02, 0x00, 0x4c, 0x00, 0x00, 0x00, // host
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // session key
+            0x01, 0x00, 0x00, 0x02,                         // flags
+            0x0a, 0x00, 0x63, 0x45, 0x00, 0x00, 0x00, 0x0f, // version
+            b'D', 0x00, b'U', 0x00, b'H', 0x00,
+        ];
+        buf.extend_from_slice(&[0xaa; 24]);
+        let (_, ar) = parse_ntlm_auth_record(&buf).unwrap();
+        assert_eq!(ar.domain, b"D\x00");
+        assert_eq!(ar.user, b"U\x00");
+        assert_eq!(ar.host, b"H\x00");
+        assert_eq!(ar.lm_response.len(), 0);
+        assert_eq!(ar.nt_response.len(), 24);
+        assert_eq!(ar.flags, NTLMSSP_NEGOTIATE_VERSION | NTLMSSP_NEGOTIATE_UNICODE);
+        let version = ar.version.unwrap();
+        assert_eq!(version.ver_major, 10);
+        assert_eq!(version.ver_build, 17763);
+        assert_eq!(version.ver_ntlm_rev, 15);
+    }
+
+    #[test]
+    fn test_parse_ntlm_auth_record_bad_offset() {
+        let buf: &[u8] = &[
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x02, 0x00, 0x02, 0x00, 0xff, 0x00, 0x00, 0x00, // domain past the end
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x01, 0x00, 0x00, 0x00,
+        ];
+        assert!(parse_ntlm_auth_record(buf).is_err());
+    }
+}
@@ -19,12 +19,15 @@ use crate::kerberos::*;
 use crate::smb::smb::*;
 use crate::smb::smb1_session::*;
 use crate::smb::auth::*;
+use crate::smb::events::*;
 
 #[derive(Default, Debug)]
 pub struct SMBTransactionSessionSetup {
     pub request_host: Option<SessionSetupRequest>,
     pub response_host: Option<SessionSetupResponse>,
     pub ntlmssp: Option<NtlmsspData>,
+    /// NTLMSSP CHALLENGE of the response
+    pub ntlmssp_challenge: Option<NtlmsspChallenge>,
     pub krb_ticket: Option<Kerberos5Ticket>,
 }
 
@@ -67,3 +70,19 @@ impl SMBState {
         return None;
     }
 }
+
+/// Events for the NTLMSSP AUTHENTICATE message of a session setup request.
+pub fn smb_ntlmssp_events(ntlmssp: &Option<NtlmsspData>) -> Vec<SMBEvent>
+{
+    let mut events = Vec::new();
+    if let Some(ref n) = *ntlmssp {
+        if n.msg_type == NTLMSSP_AUTH {
+            if n.anonymous {
+                events.push(SMBEvent::AnonymousSession);
+            } else if n.ntlm_version == 1 {
+                events.push(SMBEvent::Ntlmv1Authentication);
+            }
+        }
+    }
+    events
+}
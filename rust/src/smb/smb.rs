@@ -47,6 +47,8 @@ use crate::smb::smb2_records::*;
 use crate::smb::smb1::*;
 use crate::smb::smb2::*;
 use crate::smb::smb3::*;
+use crate::smb::compression::*;
+use crate::smb::encryption::*;
 use crate::smb::dcerpc::*;
 use crate::smb::session::*;
 use crate::smb::events::*;
@@ -56,7 +58,7 @@ use crate::smb::smb2_ioctl::*;
 pub const MIN_REC_SIZE: u16 = 32 + 4; // SMB hdr + nbss hdr
 pub const SMB_CONFIG_DEFAULT_STREAM_DEPTH: u32 = 0;
 
-static mut ALPROTO_SMB: AppProto = ALPROTO_UNKNOWN;
+pub static mut ALPROTO_SMB: AppProto = ALPROTO_UNKNOWN;
 
 pub static mut SURICATA_SMB_FILE_CONFIG: Option<&'static SuricataFileContext> = None;
 
@@ -352,6 +354,7 @@ pub enum SMBTransactionTypeData {
     IOCTL(SMBTransactionIoctl),
     RENAME(SMBTransactionRename),
     SETFILEPATHINFO(SMBTransactionSetFilePathInfo),
+    ENCRYPTION(SMBTransactionEncryption),
 }
 
 // Used for Trans2 SET_PATH_INFO and SET_FILE_INFO
@@ -486,6 +489,8 @@ pub struct SMBTransactionNegotiate {
     // SMB1 doesn't have the client GUID
     pub client_guid: Option<Vec<u8>>,
     pub server_guid: Vec<u8>,
+    /// SMB3 cipher selected by the server
+    pub cipher: u16,
 }
 
 impl SMBTransactionNegotiate {
@@ -536,11 +541,13 @@ pub struct SMBTransaction {
     /// Command specific data
     pub type_data: Option<SMBTransactionTypeData>,
 
-    pub de_state: Option<*mut DetectEngineState>,
+    pub de_state: DetectStateHolder,
     pub events: *mut AppLayerDecoderEvents,
     pub tx_data: AppLayerTxData,
 }
 
+unsafe impl FfiType for SMBTransaction {}
+
 impl SMBTransaction {
     pub fn new() -> Self {
         return Self {
@@ -550,7 +557,7 @@ impl SMBTransaction {
               request_done: false,
               response_done: false,
               type_data: None,
-              de_state: None,
+              de_state: DetectStateHolder::new(),
               events: std::ptr::null_mut(),
               tx_data: AppLayerTxData::new(),
         }
@@ -571,12 +578,6 @@ impl SMBTransaction {
         if self.events != std::ptr::null_mut() {
             sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        match self.de_state {
-            Some(state) => {
-                sc_detect_engine_state_free(state);
-            }
-            _ => {}
-        }
     }
 }
 
@@ -695,6 +696,10 @@ impl SMBHashKeyHdrGuid {
 pub struct SMBTree {
     pub name: Vec<u8>,
     pub is_pipe: bool,
+    /// share requires encryption per share flags or policy
+    pub encrypt_data: bool,
+    /// plaintext access to a share requiring encryption was seen
+    pub unencrypted_access: bool,
 }
 
 impl SMBTree {
@@ -702,6 +707,8 @@ impl SMBTree {
         Self {
             name:name,
             is_pipe:is_pipe,
+            encrypt_data: false,
+            unencrypted_access: false,
         }
     }
 }
@@ -762,6 +769,8 @@ pub struct SMBState<> {
     pub dialect: u16,
     /// contains name of SMB1 dialect
     pub dialect_vec: Option<Vec<u8>>, // used if dialect == 0
+    /// SMB3 cipher selected by the server or 0 if not negotiated
+    pub cipher: u16,
 
     /// dcerpc interfaces, stored here to be able to match
     /// them while inspecting DCERPC REQUEST txs
@@ -772,6 +781,8 @@ pub struct SMBState<> {
     ts: u64,
 }
 
+unsafe impl FfiType for SMBState {}
+
 impl SMBState {
     /// Allocation function for a new TLS parser instance
     pub fn new() -> Self {
@@ -800,6 +811,7 @@ impl SMBState {
             tx_id:0,
             dialect:0,
             dialect_vec: None,
+            cipher: 0,
             dcerpc_ifaces: None,
             ts: 0,
         }
@@ -1425,7 +1437,8 @@ impl SMBState {
                                     while nbss_data.len() > 0 {
                                         SCLogDebug!("SMBv3 transform record");
                                         match parse_smb3_transform_record(nbss_data) {
-                                            Ok((nbss_data_rem, ref _smb3_record)) => {
+                                            Ok((nbss_data_rem, ref smb3_record)) => {
+                                                smb3_transform_record(self, smb3_record, STREAM_TOSERVER);
                                                 nbss_data = nbss_data_rem;
                                             },
                                             _ => {
@@ -1434,6 +1447,39 @@ impl SMBState {
                                             },
                                         }
                                     }
+                                } else if smb.version == 0xfc_u8 { // SMB3 compression transform
+                                    SCLogDebug!("SMBv3 compression transform record");
+                                    match parse_smb3_compression_transform_record(nbss_hdr.data) {
+                                        Ok((_, ref smb3_record)) => {
+                                            match smb3_decompress_record(smb3_record) {
+                                                Ok(decompressed) => {
+                                                    // feed the decompressed SMB2 records to the normal parser
+                                                    let mut nbss_data = &decompressed.data[..];
+                                                    while nbss_data.len() > 0 {
+                                                        SCLogDebug!("SMBv2 record (decompressed)");
+                                                        match parse_smb2_request_record(nbss_data) {
+                                                            Ok((nbss_data_rem, ref smb_record)) => {
+                                                                smb2_request_record(self, smb_record);
+                                                                nbss_data = nbss_data_rem;
+                                                            },
+                                                            _ => {
+                                                                self.set_event(SMBEvent::MalformedData);
+                                                                break;
+                                                            },
+                                                        }
+                                                    }
+                                                },
+                                                Err(e) => {
+                                                    SCLogDebug!("SMBv3 decompression failed: {:?}", e);
+                                                    self.set_event(SMBEvent::from(e));
+                                                },
+                                            }
+                                        },
+                                        _ => {
+                                            self.set_event(SMBEvent::MalformedData);
+                                            return AppLayerResult::err();
+                                        },
+                                    }
                                 }
                             },
                             _ => {
@@ -1662,7 +1708,8 @@ impl SMBState {
                                     while nbss_data.len() > 0 {
                                         SCLogDebug!("SMBv3 transform record");
                                         match parse_smb3_transform_record(nbss_data) {
-                                            Ok((nbss_data_rem, ref _smb3_record)) => {
+                                            Ok((nbss_data_rem, ref smb3_record)) => {
+                                                smb3_transform_record(self, smb3_record, STREAM_TOCLIENT);
                                                 nbss_data = nbss_data_rem;
                                             },
                                             _ => {
@@ -1671,6 +1718,39 @@ impl SMBState {
                                             },
                                         }
                                     }
+                                } else if smb.version == 0xfc_u8 { // SMB3 compression transform
+                                    SCLogDebug!("SMBv3 compression transform record");
+                                    match parse_smb3_compression_transform_record(nbss_hdr.data) {
+                                        Ok((_, ref smb3_record)) => {
+                                            match smb3_decompress_record(smb3_record) {
+                                                Ok(decompressed) => {
+                                                    // feed the decompressed SMB2 records to the normal parser
+                                                    let mut nbss_data = &decompressed.data[..];
+                                                    while nbss_data.len() > 0 {
+                                                        SCLogDebug!("SMBv2 record (decompressed)");
+                                                        match parse_smb2_response_record(nbss_data) {
+                                                            Ok((nbss_data_rem, ref smb_record)) => {
+                                                                smb2_response_record(self, smb_record);
+                                                                nbss_data = nbss_data_rem;
+                                                            },
+                                                            _ => {
+                                                                self.set_event(SMBEvent::MalformedData);
+                                                                break;
+                                                            },
+                                                        }
+                                                    }
+                                                },
+                                                Err(e) => {
+                                                    SCLogDebug!("SMBv3 decompression failed: {:?}", e);
+                                                    self.set_event(SMBEvent::from(e));
+                                                },
+                                            }
+                                        },
+                                        _ => {
+                                            self.set_event(SMBEvent::MalformedData);
+                                            return AppLayerResult::err();
+                                        },
+                                    }
                                 }
                             },
                             Err(nom::Err::Incomplete(_)) => {
@@ -1824,10 +1904,9 @@ pub unsafe extern "C" fn rs_smb_parse_request_tcp(flow: *const Flow,
                                        -> AppLayerResult
 {
     let buf = std::slice::from_raw_parts(input, input_len as usize);
-    let mut state = cast_pointer!(state, SMBState);
-    let flow = cast_pointer!(flow, Flow);
-    let file_flags = FileFlowToFlags(flow, STREAM_TOSERVER);
-    rs_smb_setfileflags(STREAM_TOSERVER, state, file_flags|FILE_USE_DETECT);
+    let mut state: &mut SMBState = cast_mut(state);
+    let flow: &mut Flow = cast_mut(flow);
+    state.files.update_flags(flow, Direction::ToServer);
     SCLogDebug!("parsing {} bytes of request data", input_len);
 
     if input.is_null() && input_len > 0 {
@@ -1862,10 +1941,9 @@ pub unsafe extern "C" fn rs_smb_parse_response_tcp(flow: *const Flow,
                                         flags: u8)
                                         -> AppLayerResult
 {
-    let mut state = cast_pointer!(state, SMBState);
-    let flow = cast_pointer!(flow, Flow);
-    let file_flags = FileFlowToFlags(flow, STREAM_TOCLIENT);
-    rs_smb_setfileflags(STREAM_TOCLIENT, state, file_flags|FILE_USE_DETECT);
+    let mut state: &mut SMBState = cast_mut(state);
+    let flow: &mut Flow = cast_mut(flow);
+    state.files.update_flags(flow, Direction::ToClient);
 
     if input.is_null() && input_len > 0 {
         return rs_smb_parse_response_tcp_gap(state, input_len);
@@ -2009,7 +2087,7 @@ pub unsafe extern "C" fn rs_smb_probe_tcp(_f: *const Flow,
 pub unsafe extern "C" fn rs_smb_state_get_tx_count(state: *mut ffi::c_void)
                                             -> u64
 {
-    let state = cast_pointer!(state, SMBState);
+    let state: &mut SMBState = cast_mut(state);
     SCLogDebug!("rs_smb_state_get_tx_count: returning {}", state.tx_id);
     return state.tx_id;
 }
@@ -2019,7 +2097,7 @@ pub unsafe extern "C" fn rs_smb_state_get_tx(state: *mut ffi::c_void,
                                       tx_id: u64)
                                       -> *mut ffi::c_void
 {
-    let state = cast_pointer!(state, SMBState);
+    let state: &mut SMBState = cast_mut(state);
     match state.get_tx_by_id(tx_id) {
         Some(tx) => {
             return tx as *const _ as *mut _;
@@ -2041,7 +2119,7 @@ pub unsafe extern "C" fn rs_smb_state_get_tx_iterator(
                                                istate: &mut u64,
                                                ) -> applayer::AppLayerGetTxIterTuple
 {
-    let state = cast_pointer!(state, SMBState);
+    let state: &mut SMBState = cast_mut(state);
     match state.get_tx_iterator(min_tx_id, istate) {
         Some((tx, out_tx_id, has_next)) => {
             let c_tx = tx as *const _ as *mut _;
@@ -2058,7 +2136,7 @@ pub unsafe extern "C" fn rs_smb_state_get_tx_iterator(
 pub unsafe extern "C" fn rs_smb_state_tx_free(state: *mut ffi::c_void,
                                        tx_id: u64)
 {
-    let state = cast_pointer!(state, SMBState);
+    let state: &mut SMBState = cast_mut(state);
     SCLogDebug!("freeing tx {}", tx_id as u64);
     state.free_tx(tx_id);
 }
@@ -2068,7 +2146,7 @@ pub unsafe extern "C" fn rs_smb_tx_get_alstate_progress(tx: *mut ffi::c_void,
                                                   direction: u8)
                                                   -> i32
 {
-    let tx = cast_pointer!(tx, SMBTransaction);
+    let tx: &mut SMBTransaction = cast_mut(tx);
 
     if direction == STREAM_TOSERVER && tx.request_done {
         SCLogDebug!("tx {} TOSERVER progress 1 => {:?}", tx.id, tx);
@@ -2088,42 +2166,18 @@ pub unsafe extern "C" fn rs_smb_get_tx_data(
     tx: *mut std::os::raw::c_void)
     -> *mut AppLayerTxData
 {
-    let tx = cast_pointer!(tx, SMBTransaction);
+    let tx: &mut SMBTransaction = cast_mut(tx);
     return &mut tx.tx_data;
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_smb_state_get_tx_detect_state(
-    tx: *mut std::os::raw::c_void)
-    -> *mut DetectEngineState
-{
-    let tx = cast_pointer!(tx, SMBTransaction);
-    match tx.de_state {
-        Some(ds) => {
-            return ds;
-        },
-        None => {
-            return std::ptr::null_mut();
-        }
-    }
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_smb_state_set_tx_detect_state(
-    tx: *mut std::os::raw::c_void,
-    de_state: &mut DetectEngineState) -> std::os::raw::c_int
-{
-    let tx = cast_pointer!(tx, SMBTransaction);
-    tx.de_state = Some(de_state);
-    0
-}
+export_tx_detect_state!(rs_smb_state_get_tx_detect_state, rs_smb_state_set_tx_detect_state, SMBTransaction);
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_smb_state_truncate(
         state: *mut std::ffi::c_void,
         direction: u8)
 {
-    let state = cast_pointer!(state, SMBState);
+    let state: &mut SMBState = cast_mut(state);
     if (direction & STREAM_TOSERVER) != 0 {
         state.trunc_ts();
     } else {
@@ -2135,7 +2189,7 @@ pub unsafe extern "C" fn rs_smb_state_truncate(
 pub unsafe extern "C" fn rs_smb_state_get_events(tx: *mut std::os::raw::c_void)
                                           -> *mut AppLayerDecoderEvents
 {
-    let tx = cast_pointer!(tx, SMBTransaction);
+    let tx: &mut SMBTransaction = cast_mut(tx);
     return tx.events;
 }
 
@@ -2159,7 +2213,7 @@ pub unsafe extern "C" fn rs_smb_state_get_event_info(
 
 pub unsafe extern "C" fn smb3_probe_tcp(f: *const Flow, dir: u8, input: *const u8, len: u32, rdir: *mut u8) -> u16 {
     let retval = rs_smb_probe_tcp(f, dir, input, len, rdir);
-    let f = cast_pointer!(f, Flow);
+    let f: &mut Flow = cast_mut(f);
     if retval != ALPROTO_SMB {
         return retval;
     }
@@ -2242,12 +2296,13 @@ pub unsafe extern "C" fn rs_smb_register_parser() {
         get_eventinfo_byid : Some(rs_smb_state_get_event_info_by_id),
         localstorage_new: None,
         localstorage_free: None,
-        get_files: Some(rs_smb_getfiles),
+        get_files: Some(state_get_files::<SMBState>),
         get_tx_iterator: Some(rs_smb_state_get_tx_iterator),
         get_tx_data: rs_smb_get_tx_data,
         apply_tx_config: None,
         flags: APP_LAYER_PARSER_OPT_ACCEPT_GAPS,
         truncate: Some(rs_smb_state_truncate),
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
@@ -2286,8 +2341,23 @@ pub unsafe extern "C" fn rs_smb_register_parser() {
                 Ok(retval) => { stream_depth = retval as u32; }
                 Err(_) => { SCLogError!("Invalid depth value"); }
            }
-            AppLayerParserSetStreamDepth(IPPROTO_TCP as u8, ALPROTO_SMB, stream_depth);
+            set_stream_depth(IPPROTO_TCP as u8, ALPROTO_SMB, stream_depth);
+        }
+        let retval = conf_get("app-layer.protocols.smb.max-decompressed-size");
+        if let Some(val) = retval {
+            match get_memval(val) {
+                Ok(retval) => { SMB3_DECOMPRESS_MAX_SIZE = retval as usize; }
+                Err(_) => { SCLogError!("Invalid max-decompressed-size value"); }
+            }
+        }
+        let retval = conf_get("app-layer.protocols.smb.decompression-memcap");
+        if let Some(val) = retval {
+            match get_memval(val) {
+                Ok(retval) => { SMB3_DECOMPRESS_MEMCAP = retval as usize; }
+                Err(_) => { SCLogError!("Invalid decompression-memcap value"); }
+            }
         }
+        smb_encryption_policy_init();
     } else {
         SCLogDebug!("Protocol detector and parser disabled for SMB.");
     }
@@ -20,6 +20,7 @@ use crate::smb::smb1_records::*;
 use crate::smb::smb::*;
 use crate::smb::events::*;
 use crate::smb::auth::*;
+use crate::smb::session::*;
 
 #[derive(Debug)]
 pub struct SessionSetupRequest {
@@ -133,16 +134,19 @@ pub fn smb1_session_setup_request(state: &mut SMBState, r: &SmbRecord, andx_offs
             let tx = state.new_sessionsetup_tx(hdr);
             tx.vercmd.set_smb1_cmd(r.command);
 
+            let mut events = Vec::new();
             if let Some(SMBTransactionTypeData::SESSIONSETUP(ref mut td)) = tx.type_data {
                 match parse_secblob(setup.sec_blob) {
                     Some(s) => {
                         td.ntlmssp = s.ntlmssp;
                         td.krb_ticket = s.krb;
+                        events = smb_ntlmssp_events(&td.ntlmssp);
                     },
                     None => { },
                 }
                 td.request_host = Some(smb1_session_setup_request_host_info(r, rem));
             }
+            tx.set_events(events);
         },
         _ => {
             // events.push(SMBEvent::MalformedData);
@@ -153,9 +157,10 @@ pub fn smb1_session_setup_request(state: &mut SMBState, r: &SmbRecord, andx_offs
 fn smb1_session_setup_update_tx(tx: &mut SMBTransaction, r: &SmbRecord, andx_offset: usize)
 {
     match parse_smb_response_setup_andx_record(&r.data[andx_offset-SMB1_HEADER_SIZE..]) {
-        Ok((rem, _setup)) => {
+        Ok((rem, setup)) => {
             if let Some(SMBTransactionTypeData::SESSIONSETUP(ref mut td)) = tx.type_data {
                 td.response_host = Some(smb1_session_setup_response_host_info(r, rem));
+                td.ntlmssp_challenge = parse_ntlmssp_challenge_blob(setup.sec_blob);
             }
         },
         _ => {
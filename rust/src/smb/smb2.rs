@@ -25,6 +25,7 @@ use crate::smb::smb2_session::*;
 use crate::smb::smb2_ioctl::*;
 use crate::smb::dcerpc::*;
 use crate::smb::events::*;
+use crate::smb::encryption::*;
 use crate::smb::files::*;
 
 pub const SMB2_COMMAND_NEGOTIATE_PROTOCOL:      u16 = 0;
@@ -350,6 +351,7 @@ pub fn smb2_request_record<'b>(state: &mut SMBState, r: &Smb2Record<'b>)
             &smb2_command_string(r.command), r.tree_id, r.session_id);
 
     let mut events : Vec<SMBEvent> = Vec::new();
+    let unencrypted_access = smb2_check_encryption_policy(state, r);
 
     let have_tx = match r.command {
         SMB2_COMMAND_SET_INFO => {
@@ -577,6 +579,10 @@ pub fn smb2_request_record<'b>(state: &mut SMBState, r: &Smb2Record<'b>)
             tx.set_events(events);
         }
     }
+    if unencrypted_access {
+        SCLogDebug!("SMBv2 plaintext access to tree {} requiring encryption", r.tree_id);
+        state.set_event(SMBEvent::UnencryptedShareAccess);
+    }
 }
 
 pub fn smb2_response_record<'b>(state: &mut SMBState, r: &Smb2Record<'b>)
@@ -732,7 +738,9 @@ pub fn smb2_response_record<'b>(state: &mut SMBState, r: &Smb2Record<'b>)
                             None => { false },
                         };
                         if found {
-                            let tree = SMBTree::new(share_name.to_vec(), is_pipe);
+                            let mut tree = SMBTree::new(share_name.to_vec(), is_pipe);
+                            tree.encrypt_data = !is_pipe &&
+                                smb2_share_requires_encryption(&share_name, tr.share_flags);
                             let tree_key = SMBCommonHdr::from2(r, SMBHDR_TYPE_SHARE);
                             state.ssn2tree_map.insert(tree_key, tree);
                         }
@@ -767,10 +775,13 @@ pub fn smb2_response_record<'b>(state: &mut SMBState, r: &Smb2Record<'b>)
                     SCLogDebug!("SERVER dialect => {}", &smb2_dialect_string(rd.dialect));
 
                     state.dialect = rd.dialect;
+                    let cipher = smb2_negotiate_response_cipher(r, &rd);
+                    state.cipher = cipher;
                     let found2 = match state.get_negotiate_tx(2) {
                         Some(tx) => {
                             if let Some(SMBTransactionTypeData::NEGOTIATE(ref mut tdn)) = tx.type_data {
                                 tdn.server_guid = rd.server_guid.to_vec();
+                                tdn.cipher = cipher;
                             }
                             tx.set_status(r.nt_status, false);
                             tx.response_done = true;
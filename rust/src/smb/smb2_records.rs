@@ -140,6 +140,10 @@ named!(pub parse_smb2_request_negotiate_protocol<Smb2NegotiateProtocolRequestRec
 pub struct Smb2NegotiateProtocolResponseRecord<'a> {
     pub dialect: u16,
     pub server_guid: &'a[u8],
+    pub capabilities: u32,
+    /// offset of the negotiate contexts from the start of the SMB2 header
+    pub ctx_offset: u32,
+    pub ctx_cnt: u16,
 }
 
 named!(pub parse_smb2_response_negotiate_protocol<Smb2NegotiateProtocolResponseRecord>,
@@ -147,11 +151,23 @@ named!(pub parse_smb2_response_negotiate_protocol<Smb2NegotiateProtocolResponseR
             _struct_size: take!(2)
         >>  _skip1: take!(2)
         >>  dialect: le_u16
-        >>  _ctx_cnt: le_u16
+        >>  ctx_cnt: le_u16
         >>  server_guid: take!(16)
+        >>  capabilities: le_u32
+        >>  _max_trans_size: le_u32
+        >>  _max_read_size: le_u32
+        >>  _max_write_size: le_u32
+        >>  _system_time: le_u64
+        >>  _server_start_time: le_u64
+        >>  _sec_offset: le_u16
+        >>  _sec_len: le_u16
+        >>  ctx_offset: le_u32
         >>  (Smb2NegotiateProtocolResponseRecord {
                 dialect,
-                server_guid
+                server_guid,
+                capabilities,
+                ctx_offset,
+                ctx_cnt,
             })
 ));
 
@@ -162,9 +178,56 @@ named!(pub parse_smb2_response_negotiate_protocol_error<Smb2NegotiateProtocolRes
         >>  (Smb2NegotiateProtocolResponseRecord {
                 dialect: 0,
                 server_guid: &[],
+                capabilities: 0,
+                ctx_offset: 0,
+                ctx_cnt: 0,
             })
 ));
 
+pub const SMB2_ENCRYPTION_CAPABILITIES: u16 = 0x0002;
+
+#[derive(Debug,PartialEq)]
+pub struct Smb2NegotiateContext<'a> {
+    pub ctx_type: u16,
+    pub data: &'a[u8],
+}
+
+named!(pub parse_smb2_negotiate_context<Smb2NegotiateContext>,
+    do_parse!(
+            ctx_type: le_u16
+        >>  data_len: le_u16
+        >>  _reserved: le_u32
+        >>  data: take!(data_len)
+        >>  (Smb2NegotiateContext {
+                ctx_type,
+                data,
+            })
+));
+
+/// Parse `cnt` negotiate contexts. Each context but the last is
+/// padded to an 8 byte boundary.
+pub fn parse_smb2_negotiate_contexts(i: &[u8], cnt: u16) -> IResult<&[u8], Vec<Smb2NegotiateContext>> {
+    let mut contexts = Vec::new();
+    let mut input = i;
+    for n in 0..cnt {
+        let (rem, ctx) = parse_smb2_negotiate_context(input)?;
+        contexts.push(ctx);
+        input = rem;
+        if n + 1 < cnt {
+            let pad = (8 - (i.len() - input.len()) % 8) % 8;
+            let (rem, _) = take!(input, pad)?;
+            input = rem;
+        }
+    }
+    Ok((input, contexts))
+}
+
+named!(pub parse_smb2_encryption_capabilities<Vec<u16>>,
+    do_parse!(
+            cipher_cnt: le_u16
+        >>  ciphers: count!(le_u16, cipher_cnt as usize)
+        >>  (ciphers)
+));
 
 #[derive(Debug,PartialEq)]
 pub struct Smb2SessionSetupRequestRecord<'a> {
@@ -188,6 +251,25 @@ named!(pub parse_smb2_request_session_setup<Smb2SessionSetupRequestRecord>,
 ));
 
 
+#[derive(Debug,PartialEq)]
+pub struct Smb2SessionSetupResponseRecord<'a> {
+    pub session_flags: u16,
+    pub data: &'a[u8],
+}
+
+named!(pub parse_smb2_response_session_setup<Smb2SessionSetupResponseRecord>,
+    do_parse!(
+            _struct_size: take!(2)
+        >>  session_flags: le_u16
+        >>  _sec_offset: le_u16
+        >>  _sec_len: le_u16
+        >>  data: rest
+        >>  (Smb2SessionSetupResponseRecord {
+                session_flags,
+                data,
+            })
+));
+
 #[derive(Debug,PartialEq)]
 pub struct Smb2TreeConnectRequestRecord<'a> {
     pub share_name: &'a[u8],
@@ -206,17 +288,20 @@ named!(pub parse_smb2_request_tree_connect<Smb2TreeConnectRequestRecord>,
 #[derive(Debug,PartialEq)]
 pub struct Smb2TreeConnectResponseRecord<> {
     pub share_type: u8,
+    pub share_flags: u32,
 }
 
 named!(pub parse_smb2_response_tree_connect<Smb2TreeConnectResponseRecord>,
     do_parse!(
             _struct_size: take!(2)
         >>  share_type: le_u8
-        >>  _share_flags: le_u32
+        >>  _reserved: le_u8
+        >>  share_flags: le_u32
         >>  _share_caps: le_u32
         >>  _access_mask: le_u32
         >>  (Smb2TreeConnectResponseRecord {
-                share_type
+                share_type,
+                share_flags,
             })
 ));
 
@@ -577,7 +662,7 @@ pub fn search_smb_record<'a>(i: &'a [u8]) -> nom::IResult<&'a [u8], &'a [u8]> {
     let mut d = i;
     while d.len() >= 4 {
         if &d[1..4] == b"SMB" &&
-            (d[0] == 0xfe || d[0] == 0xff || d[0] == 0xfd)
+            (d[0] == 0xfe || d[0] == 0xff || d[0] == 0xfd || d[0] == 0xfc)
         {
             return Ok((&d[4..], d));
         }
@@ -19,6 +19,7 @@ use crate::smb::smb2_records::*;
 use crate::smb::smb::*;
 //use smb::events::*;
 use crate::smb::auth::*;
+use crate::smb::session::*;
 
 pub fn smb2_session_setup_request(state: &mut SMBState, r: &Smb2Record)
 {
@@ -29,12 +30,15 @@ pub fn smb2_session_setup_request(state: &mut SMBState, r: &Smb2Record)
             let tx = state.new_sessionsetup_tx(hdr);
             tx.vercmd.set_smb2_cmd(r.command);
 
+            let mut events = Vec::new();
             if let Some(SMBTransactionTypeData::SESSIONSETUP(ref mut td)) = tx.type_data {
                 if let Some(s) = parse_secblob(setup.data) {
                     td.ntlmssp = s.ntlmssp;
                     td.krb_ticket = s.krb;
+                    events = smb_ntlmssp_events(&td.ntlmssp);
                 }
             }
+            tx.set_events(events);
         },
             _ => {
 //                events.push(SMBEvent::MalformedData);
@@ -44,6 +48,11 @@ pub fn smb2_session_setup_request(state: &mut SMBState, r: &Smb2Record)
 
 fn smb2_session_setup_update_tx(tx: &mut SMBTransaction, r: &Smb2Record)
 {
+    if let Ok((_, setup)) = parse_smb2_response_session_setup(r.data) {
+        if let Some(SMBTransactionTypeData::SESSIONSETUP(ref mut td)) = tx.type_data {
+            td.ntlmssp_challenge = parse_ntlmssp_challenge_blob(setup.data);
+        }
+    }
     tx.hdr = SMBCommonHdr::from2(r, SMBHDR_TYPE_HEADER); // to overwrite ssn_id 0
     tx.set_status(r.nt_status, false);
     tx.response_done = true;
@@ -15,7 +15,9 @@
  * 02110-1301, USA.
  */
 
+use nom::combinator::rest;
 use nom::number::streaming::{le_u16, le_u32, le_u64};
+use crate::smb::compression::*;
 
 #[derive(Debug,PartialEq)]
 pub struct Smb3TransformRecord<'a> {
@@ -40,3 +42,85 @@ named!(pub parse_smb3_transform_record<Smb3TransformRecord>,
                 enc_data,
             })
 ));
+
+pub const SMB3_COMPRESSION_FLAG_CHAINED: u16 = 0x0001;
+
+/// Algorithms for which chained payloads have an OriginalPayloadSize
+fn smb3_compression_has_size(algorithm: u16) -> bool {
+    algorithm != SMB3_COMPRESSION_NONE && algorithm != SMB3_COMPRESSION_PATTERN_V1
+}
+
+#[derive(Debug,PartialEq)]
+pub struct Smb3CompressedPayload<'a> {
+    pub algorithm: u16,
+    pub flags: u16,
+    /// size of the payload once decompressed
+    pub original_size: Option<u32>,
+    pub data: &'a[u8],
+}
+
+#[derive(Debug,PartialEq)]
+pub struct Smb3CompressionTransformRecord<'a> {
+    /// size of the decompressed data, not counting `uncompressed`
+    pub original_size: u32,
+    /// data in front of the compressed payload of unchained records
+    pub uncompressed: &'a[u8],
+    pub payloads: Vec<Smb3CompressedPayload<'a>>,
+}
+
+named!(parse_smb3_chained_payload<Smb3CompressedPayload>,
+    do_parse!(
+            algorithm: le_u16
+        >>  flags: le_u16
+        >>  length: verify!(le_u32, |&v| v >= 4 || !smb3_compression_has_size(algorithm))
+        >>  original_size: cond!(smb3_compression_has_size(algorithm), le_u32)
+            // the length includes the OriginalPayloadSize field
+        >>  data: take!(if original_size.is_some() { length - 4 } else { length })
+        >> ( Smb3CompressedPayload {
+                algorithm,
+                flags,
+                original_size,
+                data,
+            })
+));
+
+named_args!(parse_smb3_unchained(original_size: u32)<Smb3CompressionTransformRecord>,
+    do_parse!(
+            algorithm: le_u16
+        >>  flags: le_u16
+        >>  offset: le_u32
+        >>  uncompressed: take!(offset)
+        >>  data: rest
+        >> ( Smb3CompressionTransformRecord {
+                original_size,
+                uncompressed,
+                payloads: vec![Smb3CompressedPayload {
+                    algorithm,
+                    flags,
+                    original_size: Some(original_size),
+                    data,
+                }],
+            })
+));
+
+named_args!(parse_smb3_chained(original_size: u32)<Smb3CompressionTransformRecord>,
+    do_parse!(
+            payloads: many1!(complete!(parse_smb3_chained_payload))
+        >>  eof!()
+        >> ( Smb3CompressionTransformRecord {
+                original_size,
+                uncompressed: &[],
+                payloads,
+            })
+));
+
+named!(pub parse_smb3_compression_transform_record<Smb3CompressionTransformRecord>,
+    do_parse!(
+            tag!(b"\xfcSMB")
+        >>  original_size: le_u32
+        >>  flags: peek!(preceded!(take!(2), le_u16))
+        >>  record: switch!(value!(flags & SMB3_COMPRESSION_FLAG_CHAINED),
+                0 => call!(parse_smb3_unchained, original_size) |
+                _ => call!(parse_smb3_chained, original_size))
+        >> ( record )
+));
@@ -0,0 +1,29 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! SMTP parser module, meant to replace the C parser of
+//! app-layer-smtp.c.
+//!
+//! It follows the behaviour of the C parser: the commands are matched
+//! with their replies in order, the message of a DATA command is decoded
+//! by the MIME decoder with its attachments stored as files, and a
+//! transaction is a mail, up to the reply to its message. The parser is
+//! not registered yet, as the SMTP logging and keywords use the C
+//! transactions.
+
+pub mod parser;
+pub mod smtp;
@@ -0,0 +1,137 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Command and reply lines, RFC 5321.
+
+/// A command, recognized by its prefix like the C parser does.
+#[derive(Debug, PartialEq)]
+pub enum Command<'a> {
+    StartTls,
+    Data,
+    /// BDAT and the size of its chunk, None if it is not a number.
+    Bdat(Option<u64>),
+    /// HELO or EHLO and the client name.
+    Helo(&'a [u8]),
+    MailFrom(&'a [u8]),
+    RcptTo(&'a [u8]),
+    Rset,
+    Quit,
+    Other,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Reply<'a> {
+    pub code: u16,
+    /// Whether more lines of the reply follow, as in "250-PIPELINING".
+    pub more: bool,
+    pub text: &'a [u8],
+}
+
+fn starts_with_ci(line: &[u8], prefix: &[u8]) -> bool {
+    line.len() >= prefix.len() && line[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// The parameter of a command, after the prefix and its separator, up to
+/// the next space: the address of a MAIL FROM can be followed by the
+/// options of RFC 1870.
+fn parameter(line: &[u8], prefix_len: usize) -> &[u8] {
+    let rest = line.get(prefix_len + 1..).unwrap_or(&[]);
+    let start = rest.iter().position(|&c| c != b' ').unwrap_or(rest.len());
+    let rest = &rest[start..];
+    let end = rest.iter().position(|&c| c == b' ').unwrap_or(rest.len());
+    &rest[..end]
+}
+
+/// The chunk size of a BDAT command, which must follow the command
+/// after spaces.
+fn bdat_size(line: &[u8]) -> Option<u64> {
+    let rest = &line[4..];
+    let start = rest.iter().position(|&c| c != b' ')?;
+    if start == 0 {
+        return None;
+    }
+    let digits = &rest[start..];
+    let end = digits.iter().position(|c| !c.is_ascii_digit()).unwrap_or(digits.len());
+    std::str::from_utf8(&digits[..end]).ok()?.parse().ok()
+}
+
+pub fn parse_command(line: &[u8]) -> Command {
+    if starts_with_ci(line, b"starttls") {
+        Command::StartTls
+    } else if starts_with_ci(line, b"data") {
+        Command::Data
+    } else if starts_with_ci(line, b"bdat") {
+        Command::Bdat(bdat_size(line))
+    } else if starts_with_ci(line, b"helo") || starts_with_ci(line, b"ehlo") {
+        Command::Helo(parameter(line, 4))
+    } else if starts_with_ci(line, b"mail from") {
+        Command::MailFrom(parameter(line, 9))
+    } else if starts_with_ci(line, b"rcpt to") {
+        Command::RcptTo(parameter(line, 7))
+    } else if starts_with_ci(line, b"rset") {
+        Command::Rset
+    } else if starts_with_ci(line, b"quit") {
+        Command::Quit
+    } else {
+        Command::Other
+    }
+}
+
+/// Parse a reply line, which must start with a 3 digit code.
+pub fn parse_reply(line: &[u8]) -> Option<Reply> {
+    if line.len() < 3 || line[0] < b'2' || line[0] > b'5'
+        || !line[1..3].iter().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code = line[..3].iter().fold(0u16, |code, &c| code * 10 + (c - b'0') as u16);
+    let more = line.get(3) == Some(&b'-');
+    let text = line.get(4..).unwrap_or(&[]);
+    Some(Reply { code, more, text })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smtp_parse_command() {
+        assert_eq!(parse_command(b"EHLO mx.example.com"), Command::Helo(b"mx.example.com"));
+        assert_eq!(parse_command(b"MAIL FROM:<a@example.com> SIZE=100"),
+                   Command::MailFrom(b"<a@example.com>"));
+        assert_eq!(parse_command(b"rcpt to: <b@example.com>"),
+                   Command::RcptTo(b"<b@example.com>"));
+        assert_eq!(parse_command(b"MAIL FROM"), Command::MailFrom(b""));
+        assert_eq!(parse_command(b"BDAT 1024 LAST"), Command::Bdat(Some(1024)));
+        assert_eq!(parse_command(b"BDAT1024"), Command::Bdat(None));
+        assert_eq!(parse_command(b"BDAT x"), Command::Bdat(None));
+        assert_eq!(parse_command(b"StartTLS"), Command::StartTls);
+        assert_eq!(parse_command(b"DATA"), Command::Data);
+        assert_eq!(parse_command(b"NOOP"), Command::Other);
+    }
+
+    #[test]
+    fn test_smtp_parse_reply() {
+        assert_eq!(parse_reply(b"250-PIPELINING"),
+                   Some(Reply { code: 250, more: true, text: b"PIPELINING" }));
+        assert_eq!(parse_reply(b"354 go ahead"),
+                   Some(Reply { code: 354, more: false, text: b"go ahead" }));
+        assert_eq!(parse_reply(b"221"), Some(Reply { code: 221, more: false, text: b"" }));
+        assert_eq!(parse_reply(b"25"), None);
+        assert_eq!(parse_reply(b"100 no"), None);
+        assert_eq!(parse_reply(b"2x0 no"), None);
+    }
+}
@@ -0,0 +1,869 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, SuricataFileContext};
+use crate::filecontainer::{Files, FilesState};
+use crate::mime::decoder::*;
+use crate::txtable::{self, Transaction, TxTable};
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, AppLayerEvent)]
+pub enum SmtpEvent {
+    InvalidReply,
+    UnableToMatchReplyWithRequest,
+    MaxCommandLineLenExceeded,
+    MaxReplyLineLenExceeded,
+    InvalidPipelinedSequence,
+    BdatChunkLenExceeded,
+    NoServerWelcomeMessage,
+    TlsRejected,
+    DataCommandRejected,
+    MimeParseFailed,
+    MimeMalformedMsg,
+    MimeInvalidBase64,
+    MimeInvalidQp,
+    MimeLongLine,
+    MimeLongEncLine,
+    MimeLongHeaderName,
+    MimeLongHeaderValue,
+    MimeLongBoundary,
+    MimeLongFilename,
+    MimeDeepNesting,
+    MimeTooManyHeaders,
+    DuplicateFields,
+    UnparsableContent,
+    TooManyTransactions,
+}
+
+/// The events for the anomalies found by the MIME decoder.
+const MIME_EVENTS: &[(u32, SmtpEvent)] = &[
+    (MIME_ANOM_INVALID_BASE64, SmtpEvent::MimeInvalidBase64),
+    (MIME_ANOM_INVALID_QP, SmtpEvent::MimeInvalidQp),
+    (MIME_ANOM_LONG_LINE, SmtpEvent::MimeLongLine),
+    (MIME_ANOM_LONG_ENC_LINE, SmtpEvent::MimeLongEncLine),
+    (MIME_ANOM_LONG_HEADER_NAME, SmtpEvent::MimeLongHeaderName),
+    (MIME_ANOM_LONG_HEADER_VALUE, SmtpEvent::MimeLongHeaderValue),
+    (MIME_ANOM_MALFORMED_MSG, SmtpEvent::MimeMalformedMsg),
+    (MIME_ANOM_LONG_BOUNDARY, SmtpEvent::MimeLongBoundary),
+    (MIME_ANOM_LONG_FILENAME, SmtpEvent::MimeLongFilename),
+    (MIME_ANOM_DEEP_NESTING, SmtpEvent::MimeDeepNesting),
+    (MIME_ANOM_TOO_MANY_HEADERS, SmtpEvent::MimeTooManyHeaders),
+];
+
+/// Maximum length of a line. Longer lines are discarded up to their
+/// end.
+const SMTP_MAX_LINE_LEN: usize = 4096;
+
+pub static mut SURICATA_SMTP_FILE_CONFIG: Option<&'static SuricataFileContext> = None;
+
+/// A command waiting for its reply.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PendingCommand {
+    StartTls,
+    Data,
+    Bdat,
+    /// The end of a message, acknowledged like a command.
+    DataEnd,
+    Rset,
+    Other,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Command,
+    /// The lines of a message, up to the "." line.
+    Data,
+    /// A BDAT chunk of `size` bytes, RFC 3030.
+    Bdat { size: u64, received: u64 },
+    /// The rest of the flow is TLS, after a STARTTLS.
+    Tls,
+}
+
+/// A mail, from its first command to the reply to its message. The
+/// commands of the session before the mail, like EHLO, are part of its
+/// transaction.
+#[derive(Debug)]
+pub struct SmtpTransaction {
+    id: u64,
+    pub mail_from: Option<Vec<u8>>,
+    pub rcpt_to: Vec<Vec<u8>>,
+    /// The headers of the message, set once they are all received.
+    pub headers: Vec<MimeHeader>,
+    pub done: bool,
+    mime: Option<MimeDecoder>,
+    /// The track id of the attachment being stored.
+    file_track_id: Option<u32>,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for SmtpTransaction {}
+
+impl SmtpTransaction {
+    fn new(id: u64) -> SmtpTransaction {
+        SmtpTransaction {
+            id,
+            mail_from: None,
+            rcpt_to: Vec::new(),
+            headers: Vec::new(),
+            done: false,
+            mime: None,
+            file_track_id: None,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: SmtpEvent) {
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    /// The value of the first header of the message named `name`.
+    pub fn header(&self, name: &[u8]) -> Option<&[u8]> {
+        self.headers.iter().find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_slice())
+    }
+}
+
+impl Drop for SmtpTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for SmtpTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        self.set_event(SmtpEvent::TooManyTransactions);
+    }
+}
+
+pub struct SmtpState {
+    transactions: TxTable<SmtpTransaction>,
+    /// The client name of the HELO or EHLO command.
+    pub helo: Option<Vec<u8>>,
+    /// The commands waiting for their reply, oldest first.
+    commands: VecDeque<PendingCommand>,
+    mode: Mode,
+    first_reply_seen: bool,
+    /// Set while the lines of a multiline reply are received.
+    multiline: bool,
+    /// Whether the server supports pipelining, RFC 2920. The message is
+    /// then expected right after the DATA command.
+    pipelining: bool,
+    file_track_id: u32,
+    /// The attachments, created with the first one as the state cannot
+    /// free them without the engine.
+    files: Option<Files>,
+    /// Set while the rest of an overlong line is discarded, per
+    /// direction.
+    discard_ts: bool,
+    discard_tc: bool,
+}
+
+unsafe impl FfiType for SmtpState {}
+
+impl txtable::State for SmtpState {
+    type Tx = SmtpTransaction;
+
+    fn tx_table(&self) -> &TxTable<SmtpTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<SmtpTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl FilesState for SmtpState {
+    fn files(&mut self) -> &mut Files {
+        self.files.get_or_insert_with(Files::default)
+    }
+}
+
+impl SmtpState {
+    pub fn new() -> SmtpState {
+        SmtpState {
+            transactions: TxTable::new(),
+            helo: None,
+            commands: VecDeque::new(),
+            mode: Mode::Command,
+            first_reply_seen: false,
+            multiline: false,
+            pipelining: false,
+            file_track_id: 1,
+            files: None,
+            discard_ts: false,
+            discard_tc: false,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    fn new_tx(&mut self) {
+        let tx = SmtpTransaction::new(self.transactions.next_id());
+        self.transactions.push(tx);
+    }
+
+    /// The transaction of the mail in progress, unless freed.
+    fn current_tx(&mut self) -> Option<&mut SmtpTransaction> {
+        let id = self.transactions.tx_count();
+        self.transactions.last_mut().filter(|tx| tx.id == id)
+    }
+
+    fn set_event(&mut self, event: SmtpEvent) {
+        if self.current_tx().is_none() {
+            self.new_tx();
+        }
+        if let Some(tx) = self.current_tx() {
+            tx.set_event(event);
+        }
+    }
+
+    fn complete_tx(&mut self) {
+        if let Some(tx) = self.current_tx() {
+            tx.done = true;
+        }
+    }
+
+    fn push_command(&mut self, command: PendingCommand) {
+        match self.commands.back() {
+            // the commands after which the client must wait for the reply
+            Some(PendingCommand::StartTls) | Some(PendingCommand::Data) => {
+                self.set_event(SmtpEvent::InvalidPipelinedSequence);
+            }
+            _ => {}
+        }
+        self.commands.push_back(command);
+    }
+
+    /// Store the attachments of the message of the current transaction.
+    fn handle_mime_outputs(&mut self, flow: *const Flow, outputs: Vec<MimeOutput>) {
+        let cfg = match unsafe { SURICATA_SMTP_FILE_CONFIG } {
+            Some(cfg) => cfg,
+            None => return,
+        };
+        let tx_id = self.transactions.tx_count();
+        for output in outputs {
+            let open = self.current_tx().and_then(|tx| tx.file_track_id);
+            match (output, open) {
+                (MimeOutput::FileStart(name), _) => {
+                    let track_id = self.file_track_id;
+                    self.file_track_id = self.file_track_id.wrapping_add(1);
+                    let files = self.files.get_or_insert_with(Files::default);
+                    unsafe { files.update_flags(flow, Direction::ToServer) };
+                    if !files.open(cfg, Direction::ToServer, track_id, &name) {
+                        continue;
+                    }
+                    files.set_tx(Direction::ToServer, tx_id - 1);
+                    if let Some(tx) = self.current_tx() {
+                        tx.tx_data.init_files_opened();
+                        tx.file_track_id = Some(track_id);
+                    }
+                }
+                (MimeOutput::FileData(data), Some(track_id)) => {
+                    if let Some(files) = self.files.as_mut() {
+                        files.append(Direction::ToServer, track_id, &data);
+                    }
+                }
+                (MimeOutput::FileEnd, Some(track_id)) => {
+                    if let Some(files) = self.files.as_mut() {
+                        files.close(Direction::ToServer, track_id);
+                    }
+                    if let Some(tx) = self.current_tx() {
+                        tx.file_track_id = None;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Close the attachment left open by a message that did not end.
+    fn truncate_file(&mut self) {
+        let open = self.current_tx().and_then(|tx| tx.file_track_id.take());
+        if let (Some(track_id), Some(files)) = (open, self.files.as_mut()) {
+            files.close_truncated(Direction::ToServer, track_id);
+        }
+    }
+
+    fn handle_command(&mut self, command: Command) -> bool {
+        let pending = match command {
+            Command::StartTls => PendingCommand::StartTls,
+            Command::Data => {
+                if self.current_tx().map_or(false, |tx| tx.mime.is_some()) {
+                    // a second message, the end of the first one was not
+                    // seen
+                    self.set_event(SmtpEvent::UnparsableContent);
+                    self.truncate_file();
+                    self.new_tx();
+                }
+                if let Some(tx) = self.current_tx() {
+                    tx.mime = Some(MimeDecoder::new());
+                }
+                if self.pipelining {
+                    self.mode = Mode::Data;
+                }
+                PendingCommand::Data
+            }
+            Command::Bdat(size) => {
+                let size = match size {
+                    Some(size) => size,
+                    None => return false,
+                };
+                self.mode = Mode::Bdat { size, received: 0 };
+                PendingCommand::Bdat
+            }
+            Command::Helo(name) => {
+                if self.helo.is_some() {
+                    self.set_event(SmtpEvent::DuplicateFields);
+                } else {
+                    self.helo = Some(name.to_vec());
+                }
+                PendingCommand::Other
+            }
+            Command::MailFrom(address) => {
+                let duplicate = match self.current_tx() {
+                    Some(tx) if tx.mail_from.is_none() => {
+                        tx.mail_from = Some(address.to_vec());
+                        false
+                    }
+                    _ => true,
+                };
+                if duplicate {
+                    self.set_event(SmtpEvent::DuplicateFields);
+                }
+                PendingCommand::Other
+            }
+            Command::RcptTo(address) => {
+                if let Some(tx) = self.current_tx() {
+                    tx.rcpt_to.push(address.to_vec());
+                }
+                PendingCommand::Other
+            }
+            Command::Rset => PendingCommand::Rset,
+            Command::Quit | Command::Other => PendingCommand::Other,
+        };
+        self.push_command(pending);
+        true
+    }
+
+    fn handle_message_line(&mut self, flow: *const Flow, line: &[u8]) {
+        if line == b"." {
+            self.mode = Mode::Command;
+            self.push_command(PendingCommand::DataEnd);
+            let (outputs, anomalies) = match self.current_tx().and_then(|tx| tx.mime.as_mut()) {
+                Some(mime) => (mime.complete(), mime.anomalies),
+                None => (Vec::new(), 0),
+            };
+            self.handle_mime_outputs(flow, outputs);
+            if let Some(tx) = self.current_tx() {
+                for &(flag, event) in MIME_EVENTS {
+                    if anomalies & flag != 0 {
+                        tx.set_event(event);
+                    }
+                }
+                if tx.headers.is_empty() {
+                    if let Some(ref mime) = tx.mime {
+                        tx.headers = mime.headers.clone();
+                    }
+                }
+                tx.done = true;
+            }
+            return;
+        }
+        // the dot stuffing of the lines starting with a dot, RFC 5321
+        let line = if line.starts_with(b"..") { &line[1..] } else { line };
+        let outputs = match self.current_tx() {
+            Some(tx) => match tx.mime {
+                Some(ref mut mime) => {
+                    let outputs = mime.parse_line(line);
+                    if tx.headers.is_empty() && mime.headers_complete() {
+                        tx.headers = mime.headers.clone();
+                    }
+                    outputs
+                }
+                None => return,
+            },
+            None => return,
+        };
+        self.handle_mime_outputs(flow, outputs);
+    }
+
+    /// Account `len` bytes of a BDAT chunk.
+    fn handle_chunk_data(&mut self, len: usize) -> bool {
+        if let Mode::Bdat { size, received } = self.mode {
+            let received = received + len as u64;
+            if received > size {
+                self.mode = Mode::Command;
+                self.set_event(SmtpEvent::BdatChunkLenExceeded);
+                return false;
+            }
+            self.mode = if received == size {
+                Mode::Command
+            } else {
+                Mode::Bdat { size, received }
+            };
+        }
+        true
+    }
+
+    fn handle_request(&mut self, flow: *const Flow, line: &[u8], eol_len: usize) -> bool {
+        let command = if self.mode == Mode::Command {
+            Some(parse_command(line))
+        } else {
+            None
+        };
+        // RSET and QUIT belong to the mail before them
+        let new_tx = match command {
+            Some(Command::Rset) | Some(Command::Quit) => false,
+            _ => true,
+        };
+        let current_done = self.current_tx().map(|tx| tx.done);
+        if current_done.is_none() || (current_done == Some(true) && new_tx) {
+            self.new_tx();
+        }
+        if !self.first_reply_seen {
+            self.set_event(SmtpEvent::NoServerWelcomeMessage);
+        }
+        match (command, self.mode) {
+            (Some(command), _) => self.handle_command(command),
+            (None, Mode::Data) => {
+                self.handle_message_line(flow, line);
+                true
+            }
+            (None, Mode::Bdat { .. }) => self.handle_chunk_data(line.len() + eol_len),
+            (None, _) => true,
+        }
+    }
+
+    fn handle_reply(&mut self, flow: *const Flow, line: &[u8]) -> bool {
+        let reply = match parse_reply(line) {
+            Some(reply) => reply,
+            None => {
+                SCLogDebug!("invalid reply: {:?}", line);
+                self.multiline = line.get(3) == Some(&b'-');
+                self.set_event(SmtpEvent::InvalidReply);
+                return false;
+            }
+        };
+        self.multiline = reply.more;
+        let command = match self.commands.front() {
+            Some(&command) => command,
+            None => {
+                if !self.first_reply_seen {
+                    // the greeting can be a multiline reply
+                    if !self.multiline {
+                        self.first_reply_seen = true;
+                    }
+                    if reply.code != 220 {
+                        self.set_event(SmtpEvent::InvalidReply);
+                    }
+                } else {
+                    SCLogDebug!("unable to match reply with request");
+                }
+                return true;
+            }
+        };
+        match command {
+            PendingCommand::StartTls => {
+                if reply.code == 220 {
+                    self.mode = Mode::Tls;
+                    core::sc_app_layer_request_protocol_tls_upgrade(flow);
+                    self.complete_tx();
+                } else {
+                    self.set_event(SmtpEvent::TlsRejected);
+                }
+            }
+            PendingCommand::Data => {
+                if reply.code == 354 {
+                    // unless the message was pipelined and already ended
+                    if !self.current_tx().map_or(true, |tx| tx.done) {
+                        self.mode = Mode::Data;
+                    }
+                } else {
+                    self.set_event(SmtpEvent::DataCommandRejected);
+                    if self.mode == Mode::Data {
+                        self.mode = Mode::Command;
+                    }
+                }
+            }
+            PendingCommand::Rset => {
+                if reply.code == 250 {
+                    self.complete_tx();
+                }
+            }
+            PendingCommand::Bdat | PendingCommand::DataEnd | PendingCommand::Other => {}
+        }
+        if !self.multiline {
+            self.commands.pop_front();
+        } else if self.first_reply_seen && reply.code == 250
+            && reply.text.eq_ignore_ascii_case(b"pipelining") {
+            self.pipelining = true;
+        }
+        true
+    }
+
+    fn set_line_too_long(&mut self, direction: Direction, len: usize) -> bool {
+        let event = match (direction, self.mode) {
+            (Direction::ToClient, _) => SmtpEvent::MaxReplyLineLenExceeded,
+            (Direction::ToServer, Mode::Command) => SmtpEvent::MaxCommandLineLenExceeded,
+            (Direction::ToServer, _) => SmtpEvent::MimeLongLine,
+        };
+        self.set_event(event);
+        direction == Direction::ToClient || self.handle_chunk_data(len)
+    }
+
+    fn parse(&mut self, flow: *const Flow, input: &[u8], direction: Direction) -> AppLayerResult {
+        let mut current = input;
+        while !current.is_empty() && self.mode != Mode::Tls {
+            let discard = match direction {
+                Direction::ToServer => &mut self.discard_ts,
+                Direction::ToClient => &mut self.discard_tc,
+            };
+            let ok = match current.iter().position(|&b| b == b'\n') {
+                Some(pos) if *discard => {
+                    *discard = false;
+                    current = &current[pos + 1..];
+                    direction == Direction::ToClient || self.handle_chunk_data(pos + 1)
+                }
+                Some(pos) => {
+                    let (line, eol_len) = match current[..pos].last() {
+                        Some(b'\r') => (&current[..pos - 1], 2),
+                        _ => (&current[..pos], 1),
+                    };
+                    current = &current[pos + 1..];
+                    match direction {
+                        Direction::ToServer => self.handle_request(flow, line, eol_len),
+                        Direction::ToClient => self.handle_reply(flow, line),
+                    }
+                }
+                None if *discard => {
+                    let len = current.len();
+                    current = &[];
+                    direction == Direction::ToClient || self.handle_chunk_data(len)
+                }
+                None => {
+                    if current.len() >= SMTP_MAX_LINE_LEN {
+                        *discard = true;
+                        let len = current.len();
+                        current = &[];
+                        self.set_line_too_long(direction, len)
+                    } else {
+                        let consumed = input.len() - current.len();
+                        return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                               current.len() + 1);
+                    }
+                }
+            };
+            if !ok {
+                return AppLayerResult::err();
+            }
+        }
+        AppLayerResult::ok()
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_init(context: &'static mut SuricataFileContext) {
+    SURICATA_SMTP_FILE_CONFIG = Some(context);
+}
+
+#[no_mangle]
+pub extern "C" fn rs_smtp_state_new(_orig_state: *mut std::os::raw::c_void,
+                                    _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = SmtpState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut SmtpState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut SmtpState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_parse_request(flow: *const Flow,
+                                               state: *mut std::os::raw::c_void,
+                                               pstate: *mut std::os::raw::c_void,
+                                               input: *const u8,
+                                               input_len: u32,
+                                               _data: *const std::os::raw::c_void,
+                                               _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut SmtpState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(flow, buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_parse_response(flow: *const Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut SmtpState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(flow, buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_state_get_tx(state: *mut std::os::raw::c_void,
+                                              tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut SmtpState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut SmtpState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// A transaction is complete in both directions once its message is
+/// acknowledged, or it is ended by a RSET or STARTTLS.
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                         _direction: u8) -> std::os::raw::c_int {
+    let tx: &mut SmtpTransaction = cast_mut(tx);
+    tx.done as std::os::raw::c_int
+}
+
+export_tx_detect_state!(rs_smtp_state_get_tx_detect_state,
+                        rs_smtp_state_set_tx_detect_state, SmtpTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_state_get_events(tx: *mut std::os::raw::c_void)
+                                                  -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut SmtpTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_smtp_get_tx_data, SmtpTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_smtp_getfiles(state: *mut std::os::raw::c_void,
+                                          direction: u8) -> *mut crate::filecontainer::FileContainer {
+    crate::filecontainer::state_get_files::<SmtpState>(state, direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_smtp_state_new,
+            state_free: rs_smtp_state_free,
+            parse_ts: rs_smtp_parse_request,
+            parse_tc: rs_smtp_parse_response,
+            get_tx_count: rs_smtp_state_get_tx_count,
+            get_tx: rs_smtp_state_get_tx,
+            tx_free: rs_smtp_state_tx_free,
+        })
+    }
+
+    #[test]
+    fn test_smtp_mail() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx.example.com ESMTP\r\n"));
+        assert!(h.feed(Direction::ToServer, b"EHLO client.example.com\r\n"));
+        assert!(h.feed(Direction::ToClient, b"250-mx.example.com\r\n250 SIZE 1000000\r\n"));
+        assert!(h.feed(Direction::ToServer,
+                       b"MAIL FROM:<alice@example.com> SIZE=100\r\n\
+                         RCPT TO:<bob@example.com>\r\n\
+                         RCPT TO:<carol@example.com>\r\n\
+                         DATA\r\n"));
+        assert!(h.feed(Direction::ToClient, b"250 OK\r\n250 OK\r\n250 OK\r\n354 Go ahead\r\n"));
+        assert_eq!(h.state::<SmtpState>().mode, Mode::Data);
+        assert!(h.feed(Direction::ToServer,
+                       b"From: alice@example.com\r\n\
+                         Subject: hello\r\n\
+                         \r\n\
+                         Hi Bob\r\n\
+                         ..dot stuffed\r\n\
+                         .\r\n"));
+        let tx = h.get_tx::<SmtpTransaction>(0).unwrap();
+        assert!(tx.done);
+        assert_eq!(tx.mail_from.as_ref().unwrap(), b"<alice@example.com>");
+        assert_eq!(tx.rcpt_to, vec![b"<bob@example.com>".to_vec(),
+                                    b"<carol@example.com>".to_vec()]);
+        assert_eq!(tx.header(b"subject"), Some(&b"hello"[..]));
+        assert_eq!(h.state::<SmtpState>().helo.as_ref().unwrap(), b"client.example.com");
+
+        assert!(h.feed(Direction::ToClient, b"250 Queued\r\n"));
+        assert!(h.feed(Direction::ToServer, b"QUIT\r\n"));
+        assert!(h.feed(Direction::ToClient, b"221 Bye\r\n"));
+        assert_eq!(h.tx_count(), 1);
+        assert!(h.state::<SmtpState>().commands.is_empty());
+    }
+
+    #[test]
+    fn test_smtp_two_mails() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx\r\n"));
+        for _ in 0..2 {
+            assert!(h.feed(Direction::ToServer, b"MAIL FROM:<a@example.com>\r\nDATA\r\n"));
+            assert!(h.feed(Direction::ToClient, b"250 OK\r\n354 Go ahead\r\n"));
+            assert!(h.feed(Direction::ToServer, b"Subject: x\r\n\r\nbody\r\n.\r\n"));
+            assert!(h.feed(Direction::ToClient, b"250 Queued\r\n"));
+        }
+        assert_eq!(h.tx_count(), 2);
+        assert!(h.get_tx::<SmtpTransaction>(1).unwrap().done);
+    }
+
+    #[test]
+    fn test_smtp_pipelining() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx\r\n"));
+        assert!(h.feed(Direction::ToServer, b"EHLO client\r\n"));
+        assert!(h.feed(Direction::ToClient, b"250-mx\r\n250-PIPELINING\r\n250 8BITMIME\r\n"));
+        assert!(h.state::<SmtpState>().pipelining);
+        // the message is expected without waiting for the reply to DATA
+        assert!(h.feed(Direction::ToServer,
+                       b"MAIL FROM:<a@example.com>\r\nRCPT TO:<b@example.com>\r\nDATA\r\n"));
+        assert_eq!(h.state::<SmtpState>().mode, Mode::Data);
+        assert!(h.feed(Direction::ToServer, b"Subject: piped\r\n\r\nbody\r\n.\r\n"));
+        let tx = h.get_tx::<SmtpTransaction>(0).unwrap();
+        assert!(tx.done);
+        assert_eq!(tx.header(b"Subject"), Some(&b"piped"[..]));
+        assert!(h.feed(Direction::ToClient,
+                       b"250 OK\r\n250 OK\r\n354 Go ahead\r\n250 Queued\r\n"));
+        assert!(h.state::<SmtpState>().commands.is_empty());
+    }
+
+    #[test]
+    fn test_smtp_starttls() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx\r\n"));
+        assert!(h.feed(Direction::ToServer, b"STARTTLS\r\n"));
+        assert!(h.feed(Direction::ToClient, b"220 Ready to start TLS\r\n"));
+        assert_eq!(h.state::<SmtpState>().mode, Mode::Tls);
+        assert!(h.get_tx::<SmtpTransaction>(0).unwrap().done);
+        // the handshake is not parsed as commands
+        assert!(h.feed(Direction::ToServer, b"\x16\x03\x01\x00\x05hello\n"));
+        assert_eq!(h.tx_count(), 1);
+    }
+
+    #[test]
+    fn test_smtp_starttls_rejected() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx\r\n"));
+        assert!(h.feed(Direction::ToServer, b"STARTTLS\r\n"));
+        assert!(h.feed(Direction::ToClient, b"454 TLS not available\r\n"));
+        assert_eq!(h.state::<SmtpState>().mode, Mode::Command);
+        assert!(!h.get_tx::<SmtpTransaction>(0).unwrap().done);
+    }
+
+    #[test]
+    fn test_smtp_data_rejected() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx\r\n"));
+        assert!(h.feed(Direction::ToServer, b"DATA\r\n"));
+        assert!(h.feed(Direction::ToClient, b"503 Need MAIL command\r\n"));
+        assert_eq!(h.state::<SmtpState>().mode, Mode::Command);
+        // a new DATA while the first message did not end starts a mail
+        assert!(h.feed(Direction::ToServer, b"DATA\r\n"));
+        assert_eq!(h.tx_count(), 2);
+    }
+
+    #[test]
+    fn test_smtp_rset() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx\r\n"));
+        assert!(h.feed(Direction::ToServer, b"MAIL FROM:<a@example.com>\r\nRSET\r\n"));
+        assert!(h.feed(Direction::ToClient, b"250 OK\r\n250 Reset\r\n"));
+        assert!(h.get_tx::<SmtpTransaction>(0).unwrap().done);
+        assert!(h.feed(Direction::ToServer, b"MAIL FROM:<b@example.com>\r\n"));
+        assert_eq!(h.tx_count(), 2);
+        let tx = h.get_tx::<SmtpTransaction>(1).unwrap();
+        assert_eq!(tx.mail_from.as_ref().unwrap(), b"<b@example.com>");
+    }
+
+    #[test]
+    fn test_smtp_bdat() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx\r\n"));
+        assert!(h.feed(Direction::ToServer, b"BDAT 13 LAST\r\nhello\r\nabcd\r\nQUIT\r\n"));
+        assert_eq!(h.state::<SmtpState>().mode, Mode::Command);
+        assert_eq!(h.state::<SmtpState>().commands,
+                   vec![PendingCommand::Bdat, PendingCommand::Other]);
+
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx\r\n"));
+        assert!(!h.feed(Direction::ToServer, b"BDAT 5\r\nhello world\r\n"));
+        assert!(!h.feed(Direction::ToServer, b"BDAT\r\n"));
+    }
+
+    #[test]
+    fn test_smtp_invalid_reply() {
+        let mut h = harness();
+        assert!(!h.feed(Direction::ToClient, b"hello\r\n"));
+        assert_eq!(h.tx_count(), 1);
+    }
+
+    #[test]
+    fn test_smtp_split_lines() {
+        let mut h = harness();
+        assert!(h.feed_segments(Direction::ToClient, b"220-mx\r\n220 ready\r\n", 1));
+        assert!(h.state::<SmtpState>().first_reply_seen);
+        assert!(h.feed_segments(Direction::ToServer, b"HELO client\r\n", 1));
+        assert_eq!(h.state::<SmtpState>().helo.as_ref().unwrap(), b"client");
+    }
+
+    #[test]
+    fn test_smtp_line_too_long() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, b"220 mx\r\n"));
+        let mut input = b"HELO ".to_vec();
+        input.extend(std::iter::repeat(b'A').take(SMTP_MAX_LINE_LEN));
+        assert!(h.feed(Direction::ToServer, &input));
+        assert!(h.feed(Direction::ToServer, b"AAAA\r\nHELO client\r\n"));
+        assert_eq!(h.state::<SmtpState>().helo.as_ref().unwrap(), b"client");
+    }
+}
@@ -17,7 +17,15 @@
 
 // written by Pierre Chifflier  <chifflier@wzdftpd.net>
 
-use crate::snmp::snmp::SNMPTransaction;
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use crate::snmp::snmp::{SNMPTransaction, ALPROTO_SNMP};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_SNMP_USM_USER_BUFFER_ID: c_int = 0;
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_snmp_tx_get_version(tx: &mut SNMPTransaction,
@@ -54,3 +62,49 @@ pub unsafe extern "C" fn rs_snmp_tx_get_pdu_type(tx: &mut SNMPTransaction,
         }
     }
 }
+
+unsafe extern "C" fn snmp_tx_get_usm_user(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SNMPTransaction = cast_mut(tx);
+    match tx.usm {
+        Some(ref user) if !user.is_empty() => {
+            *buf = user.as_ptr();
+            *len = user.len() as u32;
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn snmp_usm_user_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, snmp_tx_get_usm_user)
+}
+
+unsafe extern "C" fn snmp_usm_user_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SNMP, G_SNMP_USM_USER_BUFFER_ID)
+}
+
+/// Register the SNMP keywords implemented in rust. The user name of the
+/// USM security parameters is in the clear, even for encrypted messages.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "snmp.usm.user",
+        desc: "sticky buffer to match on the USM user name of SNMPv3 messages",
+        url: "/rules/snmp-keywords.html#snmp-usm-user",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: snmp_usm_user_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SNMP_USM_USER_BUFFER_ID = helper_buffer_mpm_register(
+            "snmp.usm.user", "SNMP USM user name", ALPROTO_SNMP, true, true, 0,
+            snmp_usm_user_get_data);
+    }
+}
@@ -17,6 +17,7 @@
 
 // written by Pierre Chifflier  <chifflier@wzdftpd.net>
 
+use crate::common::to_hex;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
 use crate::snmp::snmp::{SNMPState,SNMPTransaction};
 use crate::snmp::snmp_parser::{NetworkAddress,PduType};
@@ -72,9 +73,18 @@ fn snmp_log_response(jsb: &mut JsonBuilder, state: &mut SNMPState, tx: &mut SNMP
         if let Some(community) = &tx.community {
             jsb.set_string("community", community)?;
         }
-        if let Some(usm) = &tx.usm {
-            jsb.set_string("usm", usm)?;
+    }
+    // the security parameters are not encrypted
+    if let Some(usm) = &tx.usm {
+        jsb.set_string("usm", usm)?;
+    }
+    if let Some(info) = &tx.usm_info {
+        jsb.set_string("security_level", info.security_level())?;
+        if !info.engine_id.is_empty() {
+            jsb.set_string("engine_id", &to_hex(&info.engine_id))?;
         }
+        jsb.set_uint("engine_boots", info.engine_boots as u64)?;
+        jsb.set_uint("engine_time", info.engine_time as u64)?;
     }
 
     return Ok(());
@@ -19,8 +19,9 @@
 
 use crate::snmp::snmp_parser::*;
 use crate::core;
-use crate::core::{AppProto,Flow,ALPROTO_UNKNOWN,ALPROTO_FAILED,STREAM_TOSERVER,STREAM_TOCLIENT};
+use crate::core::{AppProto,Direction,Flow,ALPROTO_UNKNOWN,ALPROTO_FAILED};
 use crate::applayer::{self, *};
+use crate::asn1::ber::{ber_validate, BerConstraints, BerError};
 use std;
 use std::ffi::CString;
 
@@ -36,8 +37,21 @@ pub enum SNMPEvent {
     MalformedData,
     UnknownSecurityModel,
     VersionMismatch,
+    NoAuthNoPriv,
+    InvalidEncoding,
 }
 
+/// SNMP messages are BER encoded, and do not nest deeply
+const SNMP_BER_CONSTRAINTS: BerConstraints = BerConstraints {
+    max_depth: 16,
+    max_length: None,
+    der: false,
+};
+
+/// SNMPv3 msgFlags, RFC 3412
+const SNMP_MSG_FLAG_AUTH: u8 = 0x01;
+const SNMP_MSG_FLAG_PRIV: u8 = 0x02;
+
 pub struct SNMPState<'a> {
     /// SNMP protocol version
     pub version: u32,
@@ -49,6 +63,8 @@ pub struct SNMPState<'a> {
     tx_id: u64,
 }
 
+unsafe impl<'a> FfiType for SNMPState<'a> {}
+
 pub struct SNMPPduInfo<'a> {
     pub pdu_type: PduType,
 
@@ -59,6 +75,26 @@ pub struct SNMPPduInfo<'a> {
     pub vars: Vec<Oid<'a>>,
 }
 
+/// USM security parameters, RFC 3414, and security level of a SNMPv3
+/// message
+pub struct SNMPUsmInfo {
+    pub engine_id: Vec<u8>,
+    pub engine_boots: u32,
+    pub engine_time: u32,
+    pub auth: bool,
+    pub privacy: bool,
+}
+
+impl SNMPUsmInfo {
+    pub fn security_level(&self) -> &'static str {
+        match (self.auth, self.privacy) {
+            (true, true) => "authPriv",
+            (true, false) => "authNoPriv",
+            _ => "noAuthNoPriv",
+        }
+    }
+}
+
 pub struct SNMPTransaction<'a> {
     /// PDU version
     pub version: u32,
@@ -69,9 +105,12 @@ pub struct SNMPTransaction<'a> {
     /// Community, if present (SNMPv2)
     pub community: Option<String>,
 
-    /// USM info, if present (SNMPv3)
+    /// USM user name, if present (SNMPv3)
     pub usm: Option<String>,
 
+    /// USM parameters, if present (SNMPv3)
+    pub usm_info: Option<SNMPUsmInfo>,
+
     /// True if transaction was encrypted
     pub encrypted: bool,
 
@@ -79,7 +118,7 @@ pub struct SNMPTransaction<'a> {
     id: u64,
 
     /// The detection engine state, if present
-    de_state: Option<*mut core::DetectEngineState>,
+    de_state: DetectStateHolder,
 
     /// The events associated with this transaction
     events: *mut core::AppLayerDecoderEvents,
@@ -87,6 +126,8 @@ pub struct SNMPTransaction<'a> {
     tx_data: applayer::AppLayerTxData,
 }
 
+unsafe impl<'a> FfiType for SNMPTransaction<'a> {}
+
 
 
 impl<'a> SNMPState<'a> {
@@ -131,7 +172,7 @@ impl<'a> SNMPState<'a> {
         tx.info = Some(pdu_info);
     }
 
-    fn handle_snmp_v12(&mut self, msg: SnmpMessage<'a>, _direction: u8) -> i32 {
+    fn handle_snmp_v12(&mut self, msg: SnmpMessage<'a>, _direction: Direction) -> i32 {
         let mut tx = self.new_tx();
         // in the message, version is encoded as 0 (version 1) or 1 (version 2)
         if self.version != msg.version + 1 {
@@ -144,7 +185,7 @@ impl<'a> SNMPState<'a> {
         0
     }
 
-    fn handle_snmp_v3(&mut self, msg: SnmpV3Message<'a>, _direction: u8) -> i32 {
+    fn handle_snmp_v3(&mut self, msg: SnmpV3Message<'a>, direction: Direction) -> i32 {
         let mut tx = self.new_tx();
         if self.version != msg.version {
             SCLogDebug!("SNMP version mismatch: expected {}, received {}", self.version, msg.version);
@@ -160,7 +201,23 @@ impl<'a> SNMPState<'a> {
         }
         match msg.security_params {
             SecurityParameters::USM(usm) => {
+                let flags = msg.header_data.msg_flags;
+                let info = SNMPUsmInfo {
+                    engine_id: usm.msg_authoritative_engine_id.to_vec(),
+                    engine_boots: usm.msg_authoritative_engine_boots,
+                    engine_time: usm.msg_authoritative_engine_time,
+                    auth: flags & SNMP_MSG_FLAG_AUTH != 0,
+                    privacy: flags & SNMP_MSG_FLAG_PRIV != 0,
+                };
+                // unauthenticated messages of a user, except the engine
+                // discovery which has no user name
+                if direction == Direction::ToServer && !info.auth
+                    && !usm.msg_user_name.is_empty()
+                {
+                    self.set_event_tx(&mut tx, SNMPEvent::NoAuthNoPriv);
+                }
                 tx.usm = Some(usm.msg_user_name);
+                tx.usm_info = Some(info);
             },
             _                            => {
                 self.set_event_tx(&mut tx, SNMPEvent::UnknownSecurityModel);
@@ -173,7 +230,12 @@ impl<'a> SNMPState<'a> {
     /// Parse an SNMP request message
     ///
     /// Returns 0 if successful, or -1 on error
-    fn parse(&mut self, i: &'a [u8], direction: u8) -> i32 {
+    fn parse(&mut self, i: &'a [u8], direction: Direction) -> i32 {
+        if let Err(BerError::Violation(_v)) = ber_validate(i, &SNMP_BER_CONSTRAINTS) {
+            SCLogDebug!("SNMP message violates the BER constraints: {:?}", _v);
+            self.set_event(SNMPEvent::InvalidEncoding);
+            return -1;
+        }
         if self.version == 0 {
             match parse_pdu_enveloppe_version(i) {
                 Ok((_,x)) => self.version = x,
@@ -258,9 +320,10 @@ impl<'a> SNMPTransaction<'a> {
             info: None,
             community: None,
             usm: None,
+            usm_info: None,
             encrypted: false,
             id: id,
-            de_state: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: applayer::AppLayerTxData::new(),
         }
@@ -309,8 +372,8 @@ pub unsafe extern "C" fn rs_snmp_parse_request(_flow: *const core::Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8) -> AppLayerResult {
     let buf = build_slice!(input,input_len as usize);
-    let state = cast_pointer!(state,SNMPState);
-    state.parse(buf, STREAM_TOSERVER).into()
+    let state: &mut SNMPState = cast_mut(state);
+    state.parse(buf, Direction::ToServer).into()
 }
 
 #[no_mangle]
@@ -322,8 +385,8 @@ pub unsafe extern "C" fn rs_snmp_parse_response(_flow: *const core::Flow,
                                        _data: *const std::os::raw::c_void,
                                        _flags: u8) -> AppLayerResult {
     let buf = build_slice!(input,input_len as usize);
-    let state = cast_pointer!(state,SNMPState);
-    state.parse(buf, STREAM_TOCLIENT).into()
+    let state: &mut SNMPState = cast_mut(state);
+    state.parse(buf, Direction::ToClient).into()
 }
 
 #[no_mangle]
@@ -331,7 +394,7 @@ pub unsafe extern "C" fn rs_snmp_state_get_tx(state: *mut std::os::raw::c_void,
                                       tx_id: u64)
                                       -> *mut std::os::raw::c_void
 {
-    let state = cast_pointer!(state,SNMPState);
+    let state: &mut SNMPState = cast_mut(state);
     match state.get_tx_by_id(tx_id) {
         Some(tx) => tx as *const _ as *mut _,
         None     => std::ptr::null_mut(),
@@ -342,7 +405,7 @@ pub unsafe extern "C" fn rs_snmp_state_get_tx(state: *mut std::os::raw::c_void,
 pub unsafe extern "C" fn rs_snmp_state_get_tx_count(state: *mut std::os::raw::c_void)
                                             -> u64
 {
-    let state = cast_pointer!(state,SNMPState);
+    let state: &mut SNMPState = cast_mut(state);
     state.tx_id
 }
 
@@ -350,7 +413,7 @@ pub unsafe extern "C" fn rs_snmp_state_get_tx_count(state: *mut std::os::raw::c_
 pub unsafe extern "C" fn rs_snmp_state_tx_free(state: *mut std::os::raw::c_void,
                                        tx_id: u64)
 {
-    let state = cast_pointer!(state,SNMPState);
+    let state: &mut SNMPState = cast_mut(state);
     state.free_tx(tx_id);
 }
 
@@ -362,34 +425,14 @@ pub extern "C" fn rs_snmp_tx_get_alstate_progress(_tx: *mut std::os::raw::c_void
     1
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn rs_snmp_state_set_tx_detect_state(
-    tx: *mut std::os::raw::c_void,
-    de_state: &mut core::DetectEngineState) -> std::os::raw::c_int
-{
-    let tx = cast_pointer!(tx,SNMPTransaction);
-    tx.de_state = Some(de_state);
-    0
-}
-
-#[no_mangle]
-pub unsafe extern "C" fn rs_snmp_state_get_tx_detect_state(
-    tx: *mut std::os::raw::c_void)
-    -> *mut core::DetectEngineState
-{
-    let tx = cast_pointer!(tx,SNMPTransaction);
-    match tx.de_state {
-        Some(ds) => ds,
-        None => std::ptr::null_mut(),
-    }
-}
+export_tx_detect_state!(rs_snmp_state_get_tx_detect_state, rs_snmp_state_set_tx_detect_state, SNMPTransaction);
 
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_snmp_state_get_events(tx: *mut std::os::raw::c_void)
                                            -> *mut core::AppLayerDecoderEvents
 {
-    let tx = cast_pointer!(tx, SNMPTransaction);
+    let tx: &mut SNMPTransaction = cast_mut(tx);
     return tx.events;
 }
 
@@ -422,7 +465,7 @@ pub unsafe extern "C" fn rs_snmp_get_tx_iterator(_ipproto: u8,
                                           _max_tx_id: u64,
                                           istate: &mut u64) -> applayer::AppLayerGetTxIterTuple
 {
-    let state = cast_pointer!(alstate,SNMPState);
+    let state: &mut SNMPState = cast_mut(alstate);
     match state.get_tx_iterator(min_tx_id, istate) {
         Some((tx, out_tx_id, has_next)) => {
             let c_tx = tx as *const _ as *mut _;
@@ -437,7 +480,7 @@ pub unsafe extern "C" fn rs_snmp_get_tx_iterator(_ipproto: u8,
 
 
 
-static mut ALPROTO_SNMP : AppProto = ALPROTO_UNKNOWN;
+pub static mut ALPROTO_SNMP : AppProto = ALPROTO_UNKNOWN;
 
 // Read PDU sequence and extract version, if similar to SNMP definition
 fn parse_pdu_enveloppe_version(i:&[u8]) -> IResult<&[u8],u32> {
@@ -519,6 +562,7 @@ pub unsafe extern "C" fn rs_register_snmp_parser() {
         apply_tx_config    : None,
         flags              : APP_LAYER_PARSER_OPT_UNIDIR_TXS,
         truncate           : None,
+        state_purge        : None,
     };
     let ip_proto_str = CString::new("udp").unwrap();
     if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
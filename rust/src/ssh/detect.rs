@@ -15,15 +15,28 @@
  * 02110-1301, USA.
  */
 
-use super::ssh::SSHTransaction;
+use super::ssh::{SSHConnectionState, SSHTransaction, ALPROTO_SSH};
 use crate::core::{STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 
+static mut G_SSH_CLIENT_SOFTWARE_BUFFER_ID: c_int = 0;
+static mut G_SSH_SERVER_SOFTWARE_BUFFER_ID: c_int = 0;
+static mut G_SSH_CLIENT_COMMENTS_BUFFER_ID: c_int = 0;
+static mut G_SSH_SERVER_COMMENTS_BUFFER_ID: c_int = 0;
+static mut G_SSH_CLIENT_JA4_BUFFER_ID: c_int = 0;
+static mut G_SSH_SERVER_JA4_BUFFER_ID: c_int = 0;
+
 #[no_mangle]
 pub unsafe extern "C" fn rs_ssh_tx_get_protocol(
     tx: *mut std::os::raw::c_void, buffer: *mut *const u8, buffer_len: *mut u32, direction: u8,
 ) -> u8 {
-    let tx = cast_pointer!(tx, SSHTransaction);
+    let tx: &mut SSHTransaction = cast_mut(tx);
     match direction {
         STREAM_TOSERVER => {
             let m = &tx.cli_hdr.protover;
@@ -53,7 +66,7 @@ pub unsafe extern "C" fn rs_ssh_tx_get_protocol(
 pub unsafe extern "C" fn rs_ssh_tx_get_software(
     tx: *mut std::os::raw::c_void, buffer: *mut *const u8, buffer_len: *mut u32, direction: u8,
 ) -> u8 {
-    let tx = cast_pointer!(tx, SSHTransaction);
+    let tx: &mut SSHTransaction = cast_mut(tx);
     match direction {
         STREAM_TOSERVER => {
             let m = &tx.cli_hdr.swver;
@@ -86,7 +99,7 @@ pub unsafe extern "C" fn rs_ssh_tx_get_hassh(
     buffer_len: *mut u32,
     direction: u8,
 ) -> u8 {
-    let tx = cast_pointer!(tx, SSHTransaction);
+    let tx: &mut SSHTransaction = cast_mut(tx);
     match direction {
         STREAM_TOSERVER => {
             let m = &tx.cli_hdr.hassh;
@@ -119,7 +132,7 @@ pub unsafe extern "C" fn rs_ssh_tx_get_hassh_string(
     buffer_len: *mut u32,
     direction: u8,
 ) -> u8 {
-    let tx = cast_pointer!(tx, SSHTransaction);
+    let tx: &mut SSHTransaction = cast_mut(tx);
     match direction {
         STREAM_TOSERVER => {
             let m = &tx.cli_hdr.hassh_string;
@@ -144,3 +157,228 @@ pub unsafe extern "C" fn rs_ssh_tx_get_hassh_string(
 
     return 0;
 }
+
+unsafe fn set_buffer(data: &[u8], buf: *mut *const u8, len: *mut u32) -> bool {
+    if data.len() == 0 {
+        return false;
+    }
+    *buf = data.as_ptr();
+    *len = data.len() as u32;
+    true
+}
+
+unsafe extern "C" fn ssh_tx_get_client_software(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SSHTransaction = cast_mut(tx);
+    set_buffer(&tx.cli_hdr.swver, buf, len)
+}
+
+unsafe extern "C" fn ssh_tx_get_server_software(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SSHTransaction = cast_mut(tx);
+    set_buffer(&tx.srv_hdr.swver, buf, len)
+}
+
+unsafe extern "C" fn ssh_tx_get_client_comments(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SSHTransaction = cast_mut(tx);
+    set_buffer(&tx.cli_hdr.comments, buf, len)
+}
+
+unsafe extern "C" fn ssh_tx_get_server_comments(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SSHTransaction = cast_mut(tx);
+    set_buffer(&tx.srv_hdr.comments, buf, len)
+}
+
+unsafe extern "C" fn ssh_tx_get_client_ja4(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SSHTransaction = cast_mut(tx);
+    set_buffer(&tx.cli_hdr.ja4, buf, len)
+}
+
+unsafe extern "C" fn ssh_tx_get_server_ja4(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut SSHTransaction = cast_mut(tx);
+    set_buffer(&tx.srv_hdr.ja4, buf, len)
+}
+
+unsafe extern "C" fn ssh_client_software_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ssh_tx_get_client_software)
+}
+
+unsafe extern "C" fn ssh_server_software_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ssh_tx_get_server_software)
+}
+
+unsafe extern "C" fn ssh_client_comments_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ssh_tx_get_client_comments)
+}
+
+unsafe extern "C" fn ssh_server_comments_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ssh_tx_get_server_comments)
+}
+
+unsafe extern "C" fn ssh_client_ja4_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ssh_tx_get_client_ja4)
+}
+
+unsafe extern "C" fn ssh_server_ja4_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, ssh_tx_get_server_ja4)
+}
+
+unsafe extern "C" fn ssh_client_software_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SSH, G_SSH_CLIENT_SOFTWARE_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_server_software_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SSH, G_SSH_SERVER_SOFTWARE_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_client_comments_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SSH, G_SSH_CLIENT_COMMENTS_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_server_comments_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SSH, G_SSH_SERVER_COMMENTS_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_client_ja4_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SSH, G_SSH_CLIENT_JA4_BUFFER_ID)
+}
+
+unsafe extern "C" fn ssh_server_ja4_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_SSH, G_SSH_SERVER_JA4_BUFFER_ID)
+}
+
+/// Register the keywords on the identification strings and on the
+/// fingerprints of the key exchange.
+pub unsafe fn register_keywords() {
+    let progress = SSHConnectionState::SshStateBannerDone as c_int;
+
+    let kw = DetectKeyword {
+        name: "ssh.client.software",
+        desc: "sticky buffer to match on the software version of the SSH client banner",
+        url: "/rules/ssh-keywords.html#ssh-client-software",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ssh_client_software_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SSH_CLIENT_SOFTWARE_BUFFER_ID = helper_buffer_mpm_register(
+            "ssh.client.software", "ssh client software version", ALPROTO_SSH, false, true,
+            progress, ssh_client_software_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "ssh.server.software",
+        desc: "sticky buffer to match on the software version of the SSH server banner",
+        url: "/rules/ssh-keywords.html#ssh-server-software",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ssh_server_software_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SSH_SERVER_SOFTWARE_BUFFER_ID = helper_buffer_mpm_register(
+            "ssh.server.software", "ssh server software version", ALPROTO_SSH, true, false,
+            progress, ssh_server_software_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "ssh.client.comments",
+        desc: "sticky buffer to match on the comments of the SSH client banner",
+        url: "/rules/ssh-keywords.html#ssh-client-comments",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ssh_client_comments_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SSH_CLIENT_COMMENTS_BUFFER_ID = helper_buffer_mpm_register(
+            "ssh.client.comments", "ssh client banner comments", ALPROTO_SSH, false, true,
+            progress, ssh_client_comments_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "ssh.server.comments",
+        desc: "sticky buffer to match on the comments of the SSH server banner",
+        url: "/rules/ssh-keywords.html#ssh-server-comments",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ssh_server_comments_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SSH_SERVER_COMMENTS_BUFFER_ID = helper_buffer_mpm_register(
+            "ssh.server.comments", "ssh server banner comments", ALPROTO_SSH, true, false,
+            progress, ssh_server_comments_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "ssh.client.ja4",
+        desc: "sticky buffer to match on the JA4 style fingerprint of the SSH client key exchange",
+        url: "/rules/ssh-keywords.html#ssh-client-ja4",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ssh_client_ja4_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SSH_CLIENT_JA4_BUFFER_ID = helper_buffer_mpm_register(
+            "ssh.client.ja4", "ssh client ja4 fingerprint", ALPROTO_SSH, false, true,
+            progress, ssh_client_ja4_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "ssh.server.ja4",
+        desc: "sticky buffer to match on the JA4 style fingerprint of the SSH server key exchange",
+        url: "/rules/ssh-keywords.html#ssh-server-ja4",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: ssh_server_ja4_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_SSH_SERVER_JA4_BUFFER_ID = helper_buffer_mpm_register(
+            "ssh.server.ja4", "ssh server ja4 fingerprint", ALPROTO_SSH, true, false,
+            progress, ssh_server_ja4_get_data);
+    }
+}
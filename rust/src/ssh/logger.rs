@@ -15,8 +15,10 @@
  * 02110-1301, USA.
  */
 
+use super::parser::SshNegotiatedAlgorithms;
 use super::ssh::SSHTransaction;
 use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::applayer::cast_mut;
 
 fn log_ssh(tx: &SSHTransaction, js: &mut JsonBuilder) -> Result<bool, JsonError> {
     if tx.cli_hdr.protover.len() == 0 && tx.srv_hdr.protover.len() == 0 {
@@ -28,6 +30,9 @@ fn log_ssh(tx: &SSHTransaction, js: &mut JsonBuilder) -> Result<bool, JsonError>
         if tx.cli_hdr.swver.len() > 0 {
             js.set_string_from_bytes("software_version", &tx.cli_hdr.swver)?;
         }
+        if tx.cli_hdr.comments.len() > 0 {
+            js.set_string_from_bytes("comments", &tx.cli_hdr.comments)?;
+        }
         if tx.cli_hdr.hassh.len() > 0 || tx.cli_hdr.hassh_string.len() > 0 {
             js.open_object("hassh")?;
             if tx.cli_hdr.hassh.len() > 0 {
@@ -38,6 +43,9 @@ fn log_ssh(tx: &SSHTransaction, js: &mut JsonBuilder) -> Result<bool, JsonError>
             }
             js.close()?;
         }
+        if tx.cli_hdr.ja4.len() > 0 {
+            js.set_string_from_bytes("ja4", &tx.cli_hdr.ja4)?;
+        }
         js.close()?;
     }
     if tx.srv_hdr.protover.len() > 0 {
@@ -46,6 +54,9 @@ fn log_ssh(tx: &SSHTransaction, js: &mut JsonBuilder) -> Result<bool, JsonError>
         if tx.srv_hdr.swver.len() > 0 {
             js.set_string_from_bytes("software_version", &tx.srv_hdr.swver)?;
         }
+        if tx.srv_hdr.comments.len() > 0 {
+            js.set_string_from_bytes("comments", &tx.srv_hdr.comments)?;
+        }
         if tx.srv_hdr.hassh.len() > 0 || tx.srv_hdr.hassh_string.len() > 0 {
             js.open_object("hassh")?;
             if tx.srv_hdr.hassh.len() > 0 {
@@ -56,14 +67,41 @@ fn log_ssh(tx: &SSHTransaction, js: &mut JsonBuilder) -> Result<bool, JsonError>
             }
             js.close()?;
         }
+        if tx.srv_hdr.ja4.len() > 0 {
+            js.set_string_from_bytes("ja4", &tx.srv_hdr.ja4)?;
+        }
         js.close()?;
     }
+    if let Some(ref negotiated) = tx.negotiated {
+        log_negotiated(negotiated, js)?;
+    }
     return Ok(true);
 }
 
+fn log_negotiated(negotiated: &SshNegotiatedAlgorithms, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("algorithms")?;
+    let fields = [
+        ("kex", &negotiated.kex),
+        ("host_key", &negotiated.server_host_key),
+        ("encryption_client_to_server", &negotiated.encr_client_to_server),
+        ("encryption_server_to_client", &negotiated.encr_server_to_client),
+        ("mac_client_to_server", &negotiated.mac_client_to_server),
+        ("mac_server_to_client", &negotiated.mac_server_to_client),
+        ("compression_client_to_server", &negotiated.comp_client_to_server),
+        ("compression_server_to_client", &negotiated.comp_server_to_client),
+    ];
+    for (key, alg) in fields.iter() {
+        if let Some(alg) = alg {
+            js.set_string(key, alg)?;
+        }
+    }
+    js.close()?;
+    Ok(())
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn rs_ssh_log_json(tx: *mut std::os::raw::c_void, js: &mut JsonBuilder) -> bool {
-    let tx = cast_pointer!(tx, SSHTransaction);
+    let tx: &mut SSHTransaction = cast_mut(tx);
     if let Ok(x) = log_ssh(tx, js) {
         return x;
     }
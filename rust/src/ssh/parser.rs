@@ -17,6 +17,7 @@
 
 use nom::combinator::rest;
 use nom::number::streaming::{be_u32, be_u8};
+use crate::common::ja4_hash;
 use md5::Md5;
 use digest::Digest;
 use std::fmt;
@@ -80,6 +81,27 @@ named!(pub ssh_parse_line<&[u8], &[u8]>,
 pub struct SshBanner<'a> {
     pub protover: &'a [u8],
     pub swver: &'a [u8],
+    pub comments: &'a [u8],
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let mut end = line.len();
+    while end > 0 && (line[end - 1] == b'\r' || line[end - 1] == b'\n') {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// Major version of the protocol announced in a banner, 1.99 meaning
+/// a server compatible with both 1 and 2.
+pub fn ssh_major_version(protover: &[u8]) -> Option<u8> {
+    if protover == b"1.99" || protover.starts_with(b"2.") {
+        Some(2)
+    } else if protover.starts_with(b"1.") {
+        Some(1)
+    } else {
+        None
+    }
 }
 
 // Could be simplified adding dummy \n at the end
@@ -91,7 +113,8 @@ named!(pub ssh_parse_banner<SshBanner>,
         char!('-') >>
         swver: alt!( complete!( is_not!(" \r\n") ) | rest ) >>
         //remaining after space is comments
-        (SshBanner{protover, swver})
+        comments: opt!( complete!( preceded!(char!(' '), rest) ) ) >>
+        (SshBanner{protover, swver, comments: comments.map(trim_line_end).unwrap_or(&b""[..])})
     )
 );
 
@@ -176,6 +199,146 @@ impl<'a> SshPacketKeyExchange<'a> {
     }
 }
 
+/// Algorithm name-lists of a KEXINIT message, in order of preference.
+#[derive(Debug, Default, PartialEq)]
+pub struct SshKexAlgorithms {
+    pub kex: Vec<String>,
+    pub server_host_key: Vec<String>,
+    pub encr_client_to_server: Vec<String>,
+    pub encr_server_to_client: Vec<String>,
+    pub mac_client_to_server: Vec<String>,
+    pub mac_server_to_client: Vec<String>,
+    pub comp_client_to_server: Vec<String>,
+    pub comp_server_to_client: Vec<String>,
+}
+
+/// Algorithms both sides settle on, see RFC 4253 section 7.1.
+#[derive(Debug, Default, PartialEq)]
+pub struct SshNegotiatedAlgorithms {
+    pub kex: Option<String>,
+    pub server_host_key: Option<String>,
+    pub encr_client_to_server: Option<String>,
+    pub encr_server_to_client: Option<String>,
+    pub mac_client_to_server: Option<String>,
+    pub mac_server_to_client: Option<String>,
+    pub comp_client_to_server: Option<String>,
+    pub comp_server_to_client: Option<String>,
+}
+
+fn parse_name_list(list: &[u8]) -> Vec<String> {
+    list.split(|&c| c == b',')
+        .filter(|name| name.len() > 0)
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect()
+}
+
+// the chosen algorithm is the first one of the client the server supports
+fn negotiate_algorithm(client: &[String], server: &[String]) -> Option<String> {
+    client.iter().find(|alg| server.contains(alg)).cloned()
+}
+
+// authenticated ciphers do not use the negotiated MAC
+fn is_aead_cipher(cipher: &Option<String>) -> bool {
+    match cipher {
+        Some(ref cipher) => {
+            cipher == "chacha20-poly1305@openssh.com"
+                || cipher == "aes128-gcm@openssh.com"
+                || cipher == "aes256-gcm@openssh.com"
+        }
+        None => false,
+    }
+}
+
+impl SshKexAlgorithms {
+    pub fn negotiate(client: &SshKexAlgorithms, server: &SshKexAlgorithms)
+                     -> SshNegotiatedAlgorithms {
+        let encr_client_to_server = negotiate_algorithm(&client.encr_client_to_server,
+                                                        &server.encr_client_to_server);
+        let encr_server_to_client = negotiate_algorithm(&client.encr_server_to_client,
+                                                        &server.encr_server_to_client);
+        let mac_client_to_server = if is_aead_cipher(&encr_client_to_server) {
+            None
+        } else {
+            negotiate_algorithm(&client.mac_client_to_server, &server.mac_client_to_server)
+        };
+        let mac_server_to_client = if is_aead_cipher(&encr_server_to_client) {
+            None
+        } else {
+            negotiate_algorithm(&client.mac_server_to_client, &server.mac_server_to_client)
+        };
+        SshNegotiatedAlgorithms {
+            kex: negotiate_algorithm(&client.kex, &server.kex),
+            server_host_key: negotiate_algorithm(&client.server_host_key,
+                                                 &server.server_host_key),
+            encr_client_to_server,
+            encr_server_to_client,
+            mac_client_to_server,
+            mac_server_to_client,
+            comp_client_to_server: negotiate_algorithm(&client.comp_client_to_server,
+                                                       &server.comp_client_to_server),
+            comp_server_to_client: negotiate_algorithm(&client.comp_server_to_client,
+                                                       &server.comp_server_to_client),
+        }
+    }
+}
+
+impl SshKexAlgorithms {
+    /// A JA4 style fingerprint of the algorithms offered by one side:
+    /// 'c' or 's' for the side, the protocol version, the number of key
+    /// exchange, host key, cipher and MAC algorithms, 'z' or 'n' for
+    /// whether compression is offered, then the truncated hashes of the
+    /// key exchange, host key, and cipher and MAC algorithms. The names
+    /// are sorted, so that the fingerprint is of the set offered rather
+    /// than of the order of preference. As for hassh, the lists of the
+    /// direction the side sends in are used.
+    pub fn ja4(&self, protover: &[u8], server: bool) -> String {
+        let (side, encr, mac, comp) = if server {
+            ('s', &self.encr_server_to_client, &self.mac_server_to_client,
+             &self.comp_server_to_client)
+        } else {
+            ('c', &self.encr_client_to_server, &self.mac_client_to_server,
+             &self.comp_client_to_server)
+        };
+        let version: String = protover.iter()
+            .filter(|c| c.is_ascii_digit())
+            .take(2)
+            .map(|&c| c as char)
+            .collect();
+        let sorted = |names: &[String]| {
+            let mut names = names.to_vec();
+            names.sort_unstable();
+            names.join(",")
+        };
+        let count = |names: &[String]| std::cmp::min(names.len(), 99);
+        let compression = if comp.iter().any(|alg| alg != "none") { 'z' } else { 'n' };
+        let mut ciphers = sorted(encr);
+        if !mac.is_empty() {
+            ciphers.push('_');
+            ciphers.push_str(&sorted(mac));
+        }
+        format!("{}{:0<2}{:02}{:02}{:02}{:02}{}_{}_{}_{}",
+                side, version, count(&self.kex), count(&self.server_host_key),
+                count(encr), count(mac), compression,
+                ja4_hash(&sorted(&self.kex)), ja4_hash(&sorted(&self.server_host_key)),
+                ja4_hash(&ciphers))
+    }
+}
+
+impl<'a> SshPacketKeyExchange<'a> {
+    pub fn algorithms(&self) -> SshKexAlgorithms {
+        SshKexAlgorithms {
+            kex: parse_name_list(self.kex_algs),
+            server_host_key: parse_name_list(self.server_host_key_algs),
+            encr_client_to_server: parse_name_list(self.encr_algs_client_to_server),
+            encr_server_to_client: parse_name_list(self.encr_algs_server_to_client),
+            mac_client_to_server: parse_name_list(self.mac_algs_client_to_server),
+            mac_server_to_client: parse_name_list(self.mac_algs_server_to_client),
+            comp_client_to_server: parse_name_list(self.comp_algs_client_to_server),
+            comp_server_to_client: parse_name_list(self.comp_algs_server_to_client),
+        }
+    }
+}
+
 named!(parse_string<&[u8]>, do_parse!(
     len: be_u32 >>
     string: take!(len) >>
@@ -240,6 +403,19 @@ mod tests {
                 // Check the first message.
                 assert_eq!(message.protover, b"2.0");
                 assert_eq!(message.swver, b"Soft");
+                assert_eq!(message.comments, b"");
+            }
+            Err(err) => {
+                panic!("Result should not be an error: {:?}.", err);
+            }
+        }
+        let buf3 = b"SSH-2.0-OpenSSH_8.2p1 Ubuntu-4ubuntu0.2\r";
+        let result3 = ssh_parse_banner(buf3);
+        match result3 {
+            Ok((_, message)) => {
+                assert_eq!(message.protover, b"2.0");
+                assert_eq!(message.swver, b"OpenSSH_8.2p1");
+                assert_eq!(message.comments, b"Ubuntu-4ubuntu0.2");
             }
             Err(err) => {
                 panic!("Result should not be an error: {:?}.", err);
@@ -247,6 +423,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_ssh_major_version() {
+        assert_eq!(ssh_major_version(b"2.0"), Some(2));
+        assert_eq!(ssh_major_version(b"1.99"), Some(2));
+        assert_eq!(ssh_major_version(b"1.5"), Some(1));
+        assert_eq!(ssh_major_version(b"3"), None);
+    }
+
     #[test]
     fn test_parse_line() {
         let buf = b"SSH-Single\n";
@@ -701,4 +885,77 @@ mod tests {
             panic!("ssh_parse_key_exchange() parsed malicious key_exchange");
         }
 }
+
+    fn name_list(names: &str) -> Vec<String> {
+        parse_name_list(names.as_bytes())
+    }
+
+    #[test]
+    fn test_parse_name_list() {
+        assert_eq!(name_list("aes128-ctr,aes256-ctr"),
+                   vec!["aes128-ctr".to_string(), "aes256-ctr".to_string()]);
+        assert_eq!(name_list("none,"), vec!["none".to_string()]);
+        assert!(name_list("").is_empty());
+    }
+
+    #[test]
+    fn test_negotiate_algorithms() {
+        let client = SshKexAlgorithms {
+            kex: name_list("curve25519-sha256,diffie-hellman-group1-sha1,ext-info-c"),
+            server_host_key: name_list("ssh-ed25519,ssh-rsa"),
+            encr_client_to_server: name_list("chacha20-poly1305@openssh.com,aes128-cbc"),
+            encr_server_to_client: name_list("aes128-cbc,chacha20-poly1305@openssh.com"),
+            mac_client_to_server: name_list("hmac-sha2-256,hmac-sha1"),
+            mac_server_to_client: name_list("hmac-sha2-256,hmac-sha1"),
+            comp_client_to_server: name_list("none"),
+            comp_server_to_client: name_list("none"),
+        };
+        let server = SshKexAlgorithms {
+            kex: name_list("diffie-hellman-group1-sha1,curve25519-sha256"),
+            server_host_key: name_list("ssh-rsa"),
+            encr_client_to_server: name_list("aes128-cbc,chacha20-poly1305@openssh.com"),
+            encr_server_to_client: name_list("aes128-cbc,chacha20-poly1305@openssh.com"),
+            mac_client_to_server: name_list("hmac-sha1"),
+            mac_server_to_client: name_list("hmac-sha1"),
+            comp_client_to_server: name_list("zlib"),
+            comp_server_to_client: name_list("none"),
+        };
+        let negotiated = SshKexAlgorithms::negotiate(&client, &server);
+        assert_eq!(negotiated.kex, Some("curve25519-sha256".to_string()));
+        assert_eq!(negotiated.server_host_key, Some("ssh-rsa".to_string()));
+        assert_eq!(negotiated.encr_client_to_server,
+                   Some("chacha20-poly1305@openssh.com".to_string()));
+        assert_eq!(negotiated.encr_server_to_client, Some("aes128-cbc".to_string()));
+        assert_eq!(negotiated.mac_client_to_server, None);
+        assert_eq!(negotiated.mac_server_to_client, Some("hmac-sha1".to_string()));
+        assert_eq!(negotiated.comp_client_to_server, None);
+        assert_eq!(negotiated.comp_server_to_client, Some("none".to_string()));
+    }
+
+    #[test]
+    fn test_ja4() {
+        let mut client = SshKexAlgorithms {
+            kex: name_list("curve25519-sha256,diffie-hellman-group14-sha256,ext-info-c"),
+            server_host_key: name_list("ssh-ed25519,rsa-sha2-512"),
+            encr_client_to_server: name_list("chacha20-poly1305@openssh.com,aes128-ctr"),
+            encr_server_to_client: name_list("aes128-ctr"),
+            mac_client_to_server: name_list("hmac-sha2-256"),
+            mac_server_to_client: name_list("hmac-sha2-256"),
+            comp_client_to_server: name_list("none,zlib@openssh.com"),
+            comp_server_to_client: name_list("none"),
+        };
+        let ja4 = client.ja4(b"2.0", false);
+        assert!(ja4.starts_with("c2003020201z_"), "{}", ja4);
+        assert_eq!(ja4.len(), "c2003020201z_".len() + 3 * 12 + 2);
+        assert!(client.ja4(b"1.99", true).starts_with("s1903020101n_"));
+
+        // the order of preference does not count
+        client.kex.reverse();
+        client.encr_client_to_server.reverse();
+        assert_eq!(client.ja4(b"2.0", false), ja4);
+
+        let empty = SshKexAlgorithms::default();
+        assert_eq!(empty.ja4(b"", false),
+                   "c0000000000n_000000000000_000000000000_000000000000");
+    }
 }
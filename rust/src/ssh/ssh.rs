@@ -17,12 +17,13 @@
 
 use super::parser;
 use crate::applayer::*;
+use crate::conf::conf_get_app_layer_node;
 use crate::core::STREAM_TOSERVER;
 use crate::core::{self, AppProto, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
 use std::ffi::CString;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-static mut ALPROTO_SSH: AppProto = ALPROTO_UNKNOWN;
+pub static mut ALPROTO_SSH: AppProto = ALPROTO_UNKNOWN;
 static HASSH_ENABLED: AtomicBool = AtomicBool::new(false);
 
 fn hassh_is_enabled() -> bool {
@@ -35,8 +36,107 @@ pub enum SSHEvent {
     LongBanner,
     InvalidRecord,
     LongKexRecord,
+    WeakKexAlgorithm,
+    WeakHostKeyAlgorithm,
+    WeakCipher,
+    WeakMac,
+    PreBannerData,
+    ProtocolDowngrade,
 }
 
+/// Deprecated algorithms for which an event is set when they are
+/// negotiated.
+pub struct SshWeakAlgorithms {
+    pub kex: Vec<String>,
+    pub host_key: Vec<String>,
+    pub cipher: Vec<String>,
+    pub mac: Vec<String>,
+}
+
+fn string_list(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+impl Default for SshWeakAlgorithms {
+    fn default() -> Self {
+        Self {
+            kex: string_list(&["diffie-hellman-group1-sha1"]),
+            host_key: string_list(&["ssh-rsa", "ssh-dss"]),
+            cipher: string_list(&[
+                "3des-cbc",
+                "aes128-cbc",
+                "aes192-cbc",
+                "aes256-cbc",
+                "blowfish-cbc",
+                "cast128-cbc",
+                "rijndael-cbc@lysator.liu.se",
+            ]),
+            mac: string_list(&["hmac-sha1", "hmac-sha1-96", "hmac-md5", "hmac-md5-96"]),
+        }
+    }
+}
+
+impl SshWeakAlgorithms {
+    /// Read the lists from app-layer.protocols.ssh.weak-algorithms. A
+    /// list present in the configuration replaces the default one.
+    fn from_config() -> Option<Self> {
+        let mut weak = Self::default();
+        let conf = match conf_get_app_layer_node("ssh") {
+            Some(conf) => conf,
+            None => return Some(weak),
+        };
+        let node = match conf.get_child_node("weak-algorithms") {
+            Some(node) => node,
+            None => return Some(weak),
+        };
+        if node.get_child_value("enabled").is_some() && !node.get_child_bool("enabled") {
+            return None;
+        }
+        if node.get_child_node("kex").is_some() {
+            weak.kex = node.get_child_string_list("kex");
+        }
+        if node.get_child_node("host-key").is_some() {
+            weak.host_key = node.get_child_string_list("host-key");
+        }
+        if node.get_child_node("cipher").is_some() {
+            weak.cipher = node.get_child_string_list("cipher");
+        }
+        if node.get_child_node("mac").is_some() {
+            weak.mac = node.get_child_string_list("mac");
+        }
+        Some(weak)
+    }
+
+    /// Return the events for the weak algorithms of a negotiation.
+    pub fn check(&self, negotiated: &parser::SshNegotiatedAlgorithms) -> Vec<SSHEvent> {
+        let is_weak = |list: &Vec<String>, alg: &Option<String>| match alg {
+            Some(alg) => list.contains(alg),
+            None => false,
+        };
+        let mut events = Vec::new();
+        if is_weak(&self.kex, &negotiated.kex) {
+            events.push(SSHEvent::WeakKexAlgorithm);
+        }
+        if is_weak(&self.host_key, &negotiated.server_host_key) {
+            events.push(SSHEvent::WeakHostKeyAlgorithm);
+        }
+        if is_weak(&self.cipher, &negotiated.encr_client_to_server)
+            || is_weak(&self.cipher, &negotiated.encr_server_to_client)
+        {
+            events.push(SSHEvent::WeakCipher);
+        }
+        if is_weak(&self.mac, &negotiated.mac_client_to_server)
+            || is_weak(&self.mac, &negotiated.mac_server_to_client)
+        {
+            events.push(SSHEvent::WeakMac);
+        }
+        events
+    }
+}
+
+/// Weak algorithm lists, None when the events are disabled.
+static mut SSH_WEAK_ALGORITHMS: Option<SshWeakAlgorithms> = None;
+
 #[repr(u8)]
 #[derive(Copy, Clone, PartialOrd, PartialEq)]
 pub enum SSHConnectionState {
@@ -47,6 +147,8 @@ pub enum SSHConnectionState {
 }
 
 const SSH_MAX_BANNER_LEN: usize = 256;
+// lines a peer may send before its banner, a server being allowed to by RFC 4253
+const SSH_MAX_PRE_BANNER_LINES: u32 = 16;
 const SSH_RECORD_HEADER_LEN: usize = 6;
 const SSH_MAX_REASSEMBLED_RECORD_LEN: usize = 65535;
 
@@ -57,9 +159,16 @@ pub struct SshHeader {
     flags: SSHConnectionState,
     pub protover: Vec<u8>,
     pub swver: Vec<u8>,
+    pub comments: Vec<u8>,
+    pre_banner_lines: u32,
 
     pub hassh: Vec<u8>,
     pub hassh_string: Vec<u8>,
+    /// JA4 style fingerprint of the first KEXINIT
+    pub ja4: Vec<u8>,
+
+    /// algorithm lists of the first KEXINIT
+    pub kex: Option<parser::SshKexAlgorithms>,
 }
 
 impl SshHeader {
@@ -71,9 +180,37 @@ impl SshHeader {
             flags: SSHConnectionState::SshStateInProgress,
             protover: Vec::new(),
             swver: Vec::new(),
+            comments: Vec::new(),
+            pre_banner_lines: 0,
 
             hassh: Vec::new(),
             hassh_string: Vec::new(),
+            ja4: Vec::new(),
+
+            kex: None,
+        }
+    }
+
+    fn set_banner(&mut self, banner: &parser::SshBanner) {
+        self.protover.extend(banner.protover);
+        if banner.swver.len() > 0 {
+            self.swver.extend(banner.swver);
+        }
+        if banner.comments.len() > 0 {
+            self.comments.extend(banner.comments);
+        }
+    }
+
+    fn handle_kexinit(&mut self, input: &[u8], resp: bool) {
+        if let Ok((_, key_exchange)) = parser::ssh_parse_key_exchange(input) {
+            if hassh_is_enabled() {
+                key_exchange.generate_hassh(&mut self.hassh_string, &mut self.hassh, &resp);
+            }
+            if self.kex.is_none() {
+                let kex = key_exchange.algorithms();
+                self.ja4 = kex.ja4(&self.protover, resp).into_bytes();
+                self.kex = Some(kex);
+            }
         }
     }
 }
@@ -81,18 +218,22 @@ impl SshHeader {
 pub struct SSHTransaction {
     pub srv_hdr: SshHeader,
     pub cli_hdr: SshHeader,
+    pub negotiated: Option<parser::SshNegotiatedAlgorithms>,
 
-    de_state: Option<*mut core::DetectEngineState>,
+    de_state: DetectStateHolder,
     events: *mut core::AppLayerDecoderEvents,
     tx_data: AppLayerTxData,
 }
 
+unsafe impl FfiType for SSHTransaction {}
+
 impl SSHTransaction {
     pub fn new() -> SSHTransaction {
         SSHTransaction {
             srv_hdr: SshHeader::new(),
             cli_hdr: SshHeader::new(),
-            de_state: None,
+            negotiated: None,
+            de_state: DetectStateHolder::new(),
             events: std::ptr::null_mut(),
             tx_data: AppLayerTxData::new(),
         }
@@ -102,9 +243,6 @@ impl SSHTransaction {
         if self.events != std::ptr::null_mut() {
             core::sc_app_layer_decoder_events_free_events(&mut self.events);
         }
-        if let Some(state) = self.de_state {
-            core::sc_detect_engine_state_free(state);
-        }
     }
 }
 
@@ -118,6 +256,8 @@ pub struct SSHState {
     transaction: SSHTransaction,
 }
 
+unsafe impl FfiType for SSHState {}
+
 impl SSHState {
     pub fn new() -> Self {
         Self {
@@ -130,7 +270,47 @@ impl SSHState {
         core::sc_app_layer_decoder_events_set_event_raw(&mut self.transaction.events, ev);
     }
 
+    /// Derive the negotiated algorithms once both KEXINIT were seen.
+    fn negotiate(&mut self) {
+        if self.transaction.negotiated.is_some() {
+            return;
+        }
+        let negotiated = match (&self.transaction.cli_hdr.kex, &self.transaction.srv_hdr.kex) {
+            (Some(client), Some(server)) => parser::SshKexAlgorithms::negotiate(client, server),
+            _ => return,
+        };
+        let events = match unsafe { &SSH_WEAK_ALGORITHMS } {
+            Some(weak) => weak.check(&negotiated),
+            None => Vec::new(),
+        };
+        for event in events {
+            self.set_event(event);
+        }
+        self.transaction.negotiated = Some(negotiated);
+    }
+
+    /// Set an event when one side only speaks SSH 1 while the other
+    /// supports SSH 2.
+    fn check_protocol_versions(&mut self) {
+        let client = parser::ssh_major_version(&self.transaction.cli_hdr.protover);
+        let server = parser::ssh_major_version(&self.transaction.srv_hdr.protover);
+        match (client, server) {
+            (Some(1), Some(2)) | (Some(2), Some(1)) => {
+                self.set_event(SSHEvent::ProtocolDowngrade);
+            }
+            _ => {}
+        }
+    }
+
     fn parse_record(
+        &mut self, input: &[u8], resp: bool, pstate: *mut std::os::raw::c_void,
+    ) -> AppLayerResult {
+        let r = self.parse_records(input, resp, pstate);
+        self.negotiate();
+        return r;
+    }
+
+    fn parse_records(
         &mut self, mut input: &[u8], resp: bool, pstate: *mut std::os::raw::c_void,
     ) -> AppLayerResult {
         let (mut hdr, ohdr) = if !resp {
@@ -150,16 +330,8 @@ impl SSHState {
                 let start = hdr.record_left as usize;
                 match hdr.record_left_msg {
                     // parse reassembled tcp segments
-                    parser::MessageCode::SshMsgKexinit if hassh_is_enabled() => {
-                        if let Ok((_rem, key_exchange)) =
-                            parser::ssh_parse_key_exchange(&input[..start])
-                        {
-                            key_exchange.generate_hassh(
-                                &mut hdr.hassh_string,
-                                &mut hdr.hassh,
-                                &resp,
-                            );
-                        }
+                    parser::MessageCode::SshMsgKexinit => {
+                        hdr.handle_kexinit(&input[..start], resp);
                         hdr.record_left_msg = parser::MessageCode::SshMsgUndefined(0);
                     }
                     _ => {}
@@ -174,23 +346,16 @@ impl SSHState {
                 Ok((rem, head)) => {
                     SCLogDebug!("SSH valid record {}", head);
                     match head.msg_code {
-                        parser::MessageCode::SshMsgKexinit if hassh_is_enabled() => {
+                        parser::MessageCode::SshMsgKexinit => {
                             //let endkex = SSH_RECORD_HEADER_LEN + head.pkt_len - 2;
                             let endkex = input.len() - rem.len();
-                            if let Ok((_, key_exchange)) = parser::ssh_parse_key_exchange(&input[SSH_RECORD_HEADER_LEN..endkex]) {
-                                key_exchange.generate_hassh(&mut hdr.hassh_string, &mut hdr.hassh, &resp);
-                            }
+                            hdr.handle_kexinit(&input[SSH_RECORD_HEADER_LEN..endkex], resp);
                         }
                         parser::MessageCode::SshMsgNewKeys => {
                             hdr.flags = SSHConnectionState::SshStateFinished;
                             if ohdr.flags >= SSHConnectionState::SshStateFinished {
                                 unsafe {
-                                    AppLayerParserStateSetFlag(
-                                        pstate,
-                                        APP_LAYER_PARSER_NO_INSPECTION
-                                        | APP_LAYER_PARSER_NO_REASSEMBLY
-                                        | APP_LAYER_PARSER_BYPASS_READY,
-                                    );
+                                    parser_state_set_bypass_ready(pstate);
                                 }
                             }
                         }
@@ -211,7 +376,7 @@ impl SSHState {
                                 parser::MessageCode::SshMsgNewKeys => {
                                     hdr.flags = SSHConnectionState::SshStateFinished;
                                 }
-                                parser::MessageCode::SshMsgKexinit if hassh_is_enabled() => {
+                                parser::MessageCode::SshMsgKexinit => {
                                     // check if buffer is bigger than maximum reassembled packet size
                                     hdr.record_left = head.pkt_len - 2;
                                     if hdr.record_left < SSH_MAX_REASSEMBLED_RECORD_LEN as u32 {
@@ -279,7 +444,7 @@ impl SSHState {
                     return r;
                 }
                 Err(nom::Err::Incomplete(_)) => {
-                    return AppLayerResult::incomplete(0 as u32, (input.len() + 1) as u32);
+                    return AppLayerResult::incomplete_from(input.len(), 0, input.len() + 1);
                 }
                 Err(_e) => {
                     SCLogDebug!("SSH invalid banner {}", _e);
@@ -290,11 +455,24 @@ impl SSHState {
         }
         match parser::ssh_parse_line(input) {
             Ok((rem, line)) => {
-                if let Ok((_, banner)) = parser::ssh_parse_banner(line) {
-                    hdr.protover.extend(banner.protover);
-                    if banner.swver.len() > 0 {
-                        hdr.swver.extend(banner.swver);
+                if !line.starts_with(b"SSH-") && hdr.pre_banner_lines < SSH_MAX_PRE_BANNER_LINES {
+                    SCLogDebug!("SSH data before banner");
+                    hdr.pre_banner_lines += 1;
+                    if hdr.pre_banner_lines == 1 {
+                        self.set_event(SSHEvent::PreBannerData);
+                    }
+                    if line.len() >= SSH_MAX_BANNER_LEN {
+                        self.set_event(SSHEvent::LongBanner);
+                    }
+                    let mut r = self.parse_banner(rem, resp, pstate);
+                    if r.is_incomplete() {
+                        //adds bytes consumed by the line to incomplete result
+                        r.consumed += (input.len() - rem.len()) as u32;
                     }
+                    return r;
+                }
+                if let Ok((_, banner)) = parser::ssh_parse_banner(line) {
+                    hdr.set_banner(&banner);
                     hdr.flags = SSHConnectionState::SshStateBannerDone;
                 } else {
                     SCLogDebug!("SSH invalid banner");
@@ -309,6 +487,7 @@ impl SSHState {
                     );
                     self.set_event(SSHEvent::LongBanner);
                 }
+                self.check_protocol_versions();
                 let mut r = self.parse_record(rem, resp, pstate);
                 if r.is_incomplete() {
                     //adds bytes consumed by banner to incomplete result
@@ -319,7 +498,7 @@ impl SSHState {
             Err(nom::Err::Incomplete(_)) => {
                 if input.len() < SSH_MAX_BANNER_LEN {
                     //0 consumed, needs at least one more byte
-                    return AppLayerResult::incomplete(0 as u32, (input.len() + 1) as u32);
+                    return AppLayerResult::incomplete_from(input.len(), 0, input.len() + 1);
                 } else {
                     SCLogDebug!(
                         "SSH banner too long {} vs {} and waiting for eol",
@@ -327,12 +506,10 @@ impl SSHState {
                         SSH_MAX_BANNER_LEN
                     );
                     if let Ok((_, banner)) = parser::ssh_parse_banner(input) {
-                        hdr.protover.extend(banner.protover);
-                        if banner.swver.len() > 0 {
-                            hdr.swver.extend(banner.swver);
-                        }
+                        hdr.set_banner(&banner);
                         hdr.flags = SSHConnectionState::SshStateBannerWaitEol;
                         self.set_event(SSHEvent::LongBanner);
+                        self.check_protocol_versions();
                         return AppLayerResult::ok();
                     } else {
                         self.set_event(SSHEvent::InvalidBanner);
@@ -351,8 +528,7 @@ impl SSHState {
 
 // C exports.
 
-export_tx_get_detect_state!(rs_ssh_tx_get_detect_state, SSHTransaction);
-export_tx_set_detect_state!(rs_ssh_tx_set_detect_state, SSHTransaction);
+export_tx_detect_state!(rs_ssh_tx_get_detect_state, rs_ssh_tx_set_detect_state, SSHTransaction);
 
 export_tx_data_get!(rs_ssh_get_tx_data, SSHTransaction);
 
@@ -360,7 +536,7 @@ export_tx_data_get!(rs_ssh_get_tx_data, SSHTransaction);
 pub unsafe extern "C" fn rs_ssh_state_get_events(
     tx: *mut std::os::raw::c_void,
 ) -> *mut core::AppLayerDecoderEvents {
-    let tx = cast_pointer!(tx, SSHTransaction);
+    let tx: &mut SSHTransaction = cast_mut(tx);
     return tx.events;
 }
 
@@ -386,7 +562,7 @@ pub unsafe extern "C" fn rs_ssh_parse_request(
     _flow: *const Flow, state: *mut std::os::raw::c_void, pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, _flags: u8,
 ) -> AppLayerResult {
-    let state = &mut cast_pointer!(state, SSHState);
+    let state: &mut SSHState = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
     let hdr = &mut state.transaction.cli_hdr;
     if hdr.flags < SSHConnectionState::SshStateBannerDone {
@@ -401,7 +577,7 @@ pub unsafe extern "C" fn rs_ssh_parse_response(
     _flow: *const Flow, state: *mut std::os::raw::c_void, pstate: *mut std::os::raw::c_void,
     input: *const u8, input_len: u32, _data: *const std::os::raw::c_void, _flags: u8,
 ) -> AppLayerResult {
-    let state = &mut cast_pointer!(state, SSHState);
+    let state: &mut SSHState = cast_mut(state);
     let buf = build_slice!(input, input_len as usize);
     let hdr = &mut state.transaction.srv_hdr;
     if hdr.flags < SSHConnectionState::SshStateBannerDone {
@@ -415,7 +591,7 @@ pub unsafe extern "C" fn rs_ssh_parse_response(
 pub unsafe extern "C" fn rs_ssh_state_get_tx(
     state: *mut std::os::raw::c_void, _tx_id: u64,
 ) -> *mut std::os::raw::c_void {
-    let state = cast_pointer!(state, SSHState);
+    let state: &mut SSHState = cast_mut(state);
     return &state.transaction as *const _ as *mut _;
 }
 
@@ -428,7 +604,7 @@ pub extern "C" fn rs_ssh_state_get_tx_count(_state: *mut std::os::raw::c_void) -
 pub unsafe extern "C" fn rs_ssh_tx_get_flags(
     tx: *mut std::os::raw::c_void, direction: u8,
 ) -> SSHConnectionState {
-    let tx = cast_pointer!(tx, SSHTransaction);
+    let tx: &mut SSHTransaction = cast_mut(tx);
     if direction == STREAM_TOSERVER {
         return tx.cli_hdr.flags;
     } else {
@@ -440,7 +616,7 @@ pub unsafe extern "C" fn rs_ssh_tx_get_flags(
 pub unsafe extern "C" fn rs_ssh_tx_get_alstate_progress(
     tx: *mut std::os::raw::c_void, direction: u8,
 ) -> std::os::raw::c_int {
-    let tx = cast_pointer!(tx, SSHTransaction);
+    let tx: &mut SSHTransaction = cast_mut(tx);
 
     if tx.cli_hdr.flags >= SSHConnectionState::SshStateFinished
         && tx.srv_hdr.flags >= SSHConnectionState::SshStateFinished
@@ -498,6 +674,7 @@ pub unsafe extern "C" fn rs_ssh_register_parser() {
         apply_tx_config: None,
         flags: 0,
         truncate: None,
+        state_purge: None,
     };
 
     let ip_proto_str = CString::new("tcp").unwrap();
@@ -505,6 +682,7 @@ pub unsafe extern "C" fn rs_ssh_register_parser() {
     if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
         let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
         ALPROTO_SSH = alproto;
+        SSH_WEAK_ALGORITHMS = SshWeakAlgorithms::from_config();
         if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
             let _ = AppLayerRegisterParser(&parser, alproto);
         }
@@ -526,7 +704,7 @@ pub extern "C" fn rs_ssh_hassh_is_enabled() -> bool {
 
 #[no_mangle]
 pub unsafe extern "C" fn rs_ssh_tx_get_log_condition( tx: *mut std::os::raw::c_void) -> bool {
-    let tx = cast_pointer!(tx, SSHTransaction);
+    let tx: &mut SSHTransaction = cast_mut(tx);
     
     if rs_ssh_hassh_is_enabled() {
         if  tx.cli_hdr.flags == SSHConnectionState::SshStateFinished &&
@@ -535,10 +713,43 @@ pub unsafe extern "C" fn rs_ssh_tx_get_log_condition( tx: *mut std::os::raw::c_v
         }
     }
     else {
-        if  tx.cli_hdr.flags == SSHConnectionState::SshStateBannerDone && 
-            tx.srv_hdr.flags == SSHConnectionState::SshStateBannerDone {
+        // wait for both KEXINIT to log the negotiated algorithms
+        if  tx.cli_hdr.flags >= SSHConnectionState::SshStateBannerDone &&
+            tx.srv_hdr.flags >= SSHConnectionState::SshStateBannerDone &&
+            tx.negotiated.is_some() {
             return true;
         }
     }
     return false;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_algorithms_check() {
+        let weak = SshWeakAlgorithms::default();
+        let mut negotiated = parser::SshNegotiatedAlgorithms {
+            kex: Some("curve25519-sha256".to_string()),
+            server_host_key: Some("ssh-ed25519".to_string()),
+            encr_client_to_server: Some("chacha20-poly1305@openssh.com".to_string()),
+            encr_server_to_client: Some("chacha20-poly1305@openssh.com".to_string()),
+            ..Default::default()
+        };
+        assert!(weak.check(&negotiated).is_empty());
+
+        negotiated.server_host_key = Some("ssh-rsa".to_string());
+        negotiated.encr_server_to_client = Some("aes128-cbc".to_string());
+        negotiated.mac_server_to_client = Some("hmac-sha1".to_string());
+        let events: Vec<u8> = weak.check(&negotiated).into_iter().map(|e| e as u8).collect();
+        assert_eq!(
+            events,
+            vec![
+                SSHEvent::WeakHostKeyAlgorithm as u8,
+                SSHEvent::WeakCipher as u8,
+                SSHEvent::WeakMac as u8
+            ]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Stats counters for Rust app-layer parsers.
+//!
+//! The parser callbacks do not have access to the ThreadVars, so the
+//! counters are global atomics exposed to the stats subsystem through
+//! `StatsRegisterGlobalCounter`. A parser declares its counters with
+//! `declare_counters!`, queues them for registration from its register
+//! function, and the C side registers all queued counters once the stats
+//! subsystem is up, in `AppLayerRegisterGlobalCounters`.
+//!
+//! ```ignore
+//! declare_counters!(mqtt_stats, "app_layer.mqtt", {
+//!     connect,
+//!     publish,
+//! });
+//!
+//! // in the parser registration function
+//! mqtt_stats::register();
+//!
+//! // in the parser
+//! stats_incr!(mqtt_stats::publish);
+//! ```
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use lazy_static::lazy_static;
+
+/// Function returning the current value of a global counter.
+pub type StatsCounterFn = extern "C" fn() -> u64;
+
+extern {
+    fn StatsRegisterGlobalCounter(name: *const c_char, func: StatsCounterFn) -> u16;
+}
+
+pub struct StatsCounter {
+    value: AtomicU64,
+}
+
+impl StatsCounter {
+    pub const fn new() -> StatsCounter {
+        StatsCounter {
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn incr(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add(&self, val: u64) {
+        self.value.fetch_add(val, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, val: u64) {
+        self.value.store(val, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+lazy_static! {
+    // The stats subsystem keeps a pointer to the counter name, so the
+    // names are kept here for the lifetime of the process.
+    static ref COUNTERS: Mutex<Vec<(CString, StatsCounterFn)>> = Mutex::new(Vec::new());
+}
+
+/// Queue a global counter for registration with the stats subsystem.
+pub fn register_counter(name: &str, func: StatsCounterFn) {
+    let name = match CString::new(name) {
+        Ok(name) => name,
+        Err(_) => {
+            SCLogError!("Invalid stats counter name: {}", name);
+            return;
+        }
+    };
+    if let Ok(mut counters) = COUNTERS.lock() {
+        if !counters.iter().any(|(n, _)| *n == name) {
+            counters.push((name, func));
+        }
+    }
+}
+
+/// Register the counters declared by the Rust parsers. Called from
+/// `AppLayerRegisterGlobalCounters`, possibly more than once; the stats
+/// subsystem ignores counters that are already registered.
+#[no_mangle]
+pub extern "C" fn rs_stats_register_global_counters() {
+    if let Ok(counters) = COUNTERS.lock() {
+        for (name, func) in counters.iter() {
+            unsafe {
+                StatsRegisterGlobalCounter(name.as_ptr(), *func);
+            }
+        }
+    }
+}
+
+/// Declare a set of global counters in module `$name`. Each counter is
+/// named `<prefix>.<counter>` in the stats output. `$name::register()`
+/// has to be called from the parser registration function.
+#[macro_export]
+macro_rules! declare_counters {
+    ($name:ident, $prefix:expr, { $($counter:ident),* $(,)? }) => {
+        #[allow(non_snake_case, dead_code)]
+        pub mod $name {
+            $(
+                pub mod $counter {
+                    pub static COUNTER: $crate::stats::StatsCounter =
+                        $crate::stats::StatsCounter::new();
+                    pub extern "C" fn get() -> u64 {
+                        COUNTER.get()
+                    }
+                }
+            )*
+
+            pub fn register() {
+                $(
+                    $crate::stats::register_counter(
+                        concat!($prefix, ".", stringify!($counter)), $counter::get);
+                )*
+            }
+        }
+    };
+}
+
+/// Increment a counter declared with `declare_counters!`, optionally by
+/// a given value.
+#[macro_export]
+macro_rules! stats_incr {
+    ($($counter:ident)::+) => {
+        $($counter)::+::COUNTER.incr()
+    };
+    ($($counter:ident)::+, $val:expr) => {
+        $($counter)::+::COUNTER.add($val as u64)
+    };
+}
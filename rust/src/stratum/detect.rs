@@ -0,0 +1,113 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Sticky buffers on the requests of the miners.
+
+use super::stratum::{StratumTransaction, ALPROTO_STRATUM};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_STRATUM_METHOD_BUFFER_ID: c_int = 0;
+static mut G_STRATUM_WORKER_BUFFER_ID: c_int = 0;
+
+unsafe fn set_buffer(data: &Option<String>, buf: *mut *const u8, len: *mut u32) -> bool {
+    match data {
+        Some(data) => {
+            *buf = data.as_ptr();
+            *len = data.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn stratum_tx_get_method(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut StratumTransaction = cast_mut(tx);
+    set_buffer(&tx.method, buf, len)
+}
+
+unsafe extern "C" fn stratum_tx_get_worker(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut StratumTransaction = cast_mut(tx);
+    set_buffer(&tx.worker, buf, len)
+}
+
+unsafe extern "C" fn stratum_method_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, stratum_tx_get_method)
+}
+
+unsafe extern "C" fn stratum_worker_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, stratum_tx_get_worker)
+}
+
+unsafe extern "C" fn stratum_method_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_STRATUM, G_STRATUM_METHOD_BUFFER_ID)
+}
+
+unsafe extern "C" fn stratum_worker_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_STRATUM, G_STRATUM_WORKER_BUFFER_ID)
+}
+
+/// Register the Stratum keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "stratum.method",
+        desc: "sticky buffer to match on the method of Stratum requests",
+        url: "/rules/stratum-keywords.html#stratum-method",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: stratum_method_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_STRATUM_METHOD_BUFFER_ID = helper_buffer_mpm_register(
+            "stratum.method", "stratum request method", ALPROTO_STRATUM, false, true, 0,
+            stratum_method_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "stratum.worker",
+        desc: "sticky buffer to match on the worker name of Stratum requests",
+        url: "/rules/stratum-keywords.html#stratum-worker",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: stratum_worker_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_STRATUM_WORKER_BUFFER_ID = helper_buffer_mpm_register(
+            "stratum.worker", "stratum worker name", ALPROTO_STRATUM, false, true, 0,
+            stratum_worker_get_data);
+    }
+}
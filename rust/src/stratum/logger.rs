@@ -0,0 +1,66 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::stratum::StratumTransaction;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log(tx: &StratumTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("stratum")?;
+    if let Some(ref method) = tx.method {
+        js.set_string("method", method)?;
+    }
+    if let Some(ref id) = tx.request_id {
+        js.set_string("id", id)?;
+    }
+    if tx.notification {
+        js.set_bool("notification", true)?;
+    }
+    if let Some(ref agent) = tx.agent {
+        js.set_string("agent", agent)?;
+    }
+    if let Some(ref worker) = tx.worker {
+        js.set_string("worker", worker)?;
+    }
+    if let Some(ref job_id) = tx.job_id {
+        js.set_string("job_id", job_id)?;
+    }
+    if let Some(ref extranonce1) = tx.extranonce1 {
+        js.set_string("extranonce1", extranonce1)?;
+    }
+    if let Some(extranonce2_size) = tx.extranonce2_size {
+        js.set_uint("extranonce2_size", extranonce2_size)?;
+    }
+    if let Some(ref extranonce2) = tx.extranonce2 {
+        js.set_string("extranonce2", extranonce2)?;
+    }
+    if let Some(ref difficulty) = tx.difficulty {
+        js.set_string("difficulty", difficulty)?;
+    }
+    if let Some(result) = tx.result {
+        js.set_bool("result", result)?;
+    }
+    if let Some(ref error) = tx.error {
+        js.set_string("error", error)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_stratum_to_json(tx: &mut StratumTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
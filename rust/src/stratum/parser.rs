@@ -0,0 +1,395 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Stratum message decoding.
+//!
+//! Stratum messages are JSON-RPC objects, one per line. The decoder
+//! handles a single line: the framing is done by the parser.
+
+/// Maximum nesting of arrays and objects in a message.
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    /// A number, kept as its text.
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// The value of member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => {
+                members.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            JsonValue::Number(n) => n.parse::<u64>().ok(),
+            _ => None,
+        }
+    }
+
+    /// The text of a string or number, as used for identifiers and
+    /// values that pools send either way.
+    pub fn as_text(&self) -> Option<String> {
+        match self {
+            JsonValue::String(s) => Some(s.clone()),
+            JsonValue::Number(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+}
+
+struct JsonDecoder<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> JsonDecoder<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Result<u8, ()> {
+        let b = self.peek().ok_or(())?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, b: u8) -> Result<(), ()> {
+        if self.next()? == b {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    fn literal(&mut self, literal: &[u8], value: JsonValue) -> Result<JsonValue, ()> {
+        if self.input[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(value)
+        } else {
+            Err(())
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<JsonValue, ()> {
+        self.skip_whitespace();
+        match self.peek().ok_or(())? {
+            b'{' => self.object(depth + 1),
+            b'[' => self.array(depth + 1),
+            b'"' => Ok(JsonValue::String(self.string()?)),
+            b'n' => self.literal(b"null", JsonValue::Null),
+            b't' => self.literal(b"true", JsonValue::Bool(true)),
+            b'f' => self.literal(b"false", JsonValue::Bool(false)),
+            b'-' | b'0'..=b'9' => Ok(JsonValue::Number(self.number()?)),
+            _ => Err(()),
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<JsonValue, ()> {
+        if depth > MAX_DEPTH {
+            return Err(());
+        }
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            let value = self.value(depth)?;
+            members.push((key, value));
+            self.skip_whitespace();
+            match self.next()? {
+                b',' => {}
+                b'}' => return Ok(JsonValue::Object(members)),
+                _ => return Err(()),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<JsonValue, ()> {
+        if depth > MAX_DEPTH {
+            return Err(());
+        }
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(JsonValue::Array(values));
+        }
+        loop {
+            values.push(self.value(depth)?);
+            self.skip_whitespace();
+            match self.next()? {
+                b',' => {}
+                b']' => return Ok(JsonValue::Array(values)),
+                _ => return Err(()),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, ()> {
+        let mut val = 0;
+        for _ in 0..4 {
+            let digit = (self.next()? as char).to_digit(16).ok_or(())?;
+            val = (val << 4) | digit;
+        }
+        Ok(val)
+    }
+
+    /// A `\u` escape, with the surrogate pairs combined. Invalid code
+    /// points are replaced.
+    fn unicode_escape(&mut self) -> Result<char, ()> {
+        let high = self.hex4()?;
+        if high >= 0xd800 && high < 0xdc00 && self.input[self.pos..].starts_with(b"\\u") {
+            self.pos += 2;
+            let low = self.hex4()?;
+            if low >= 0xdc00 && low < 0xe000 {
+                let c = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                return Ok(std::char::from_u32(c).unwrap_or('\u{fffd}'));
+            }
+            return Ok('\u{fffd}');
+        }
+        Ok(std::char::from_u32(high).unwrap_or('\u{fffd}'))
+    }
+
+    /// A string, with its escapes decoded. Invalid UTF-8 sequences are
+    /// replaced.
+    fn string(&mut self) -> Result<String, ()> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return Err(()),
+                    };
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                b => bytes.push(b),
+            }
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn digits(&mut self) -> Result<(), ()> {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        if self.pos > start {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
+    fn number(&mut self) -> Result<String, ()> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        self.digits()?;
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            self.digits()?;
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.pos += 1;
+            }
+            self.digits()?;
+        }
+        Ok(String::from_utf8_lossy(&self.input[start..self.pos]).into_owned())
+    }
+}
+
+/// Decode a JSON value taking all of `input`, but for whitespace.
+pub fn parse_json(input: &[u8]) -> Result<JsonValue, ()> {
+    let mut decoder = JsonDecoder { input, pos: 0 };
+    let value = decoder.value(0)?;
+    decoder.skip_whitespace();
+    if decoder.pos != input.len() {
+        return Err(());
+    }
+    Ok(value)
+}
+
+/// A JSON-RPC message: a request or notification when it has a method, a
+/// response otherwise.
+#[derive(Debug, PartialEq)]
+pub struct StratumMessage {
+    /// The id of the message, absent or null for the notifications.
+    pub id: Option<String>,
+    pub method: Option<String>,
+    pub params: Vec<JsonValue>,
+    pub result: Option<JsonValue>,
+    pub error: Option<JsonValue>,
+}
+
+impl StratumMessage {
+    pub fn is_response(&self) -> bool {
+        self.method.is_none()
+    }
+
+    /// The string or number parameter at `index`.
+    pub fn param_text(&self, index: usize) -> Option<String> {
+        self.params.get(index).and_then(|p| p.as_text())
+    }
+
+    /// The message of the error of a response. Pools send either an
+    /// array `[code, message, data]` or an object with a `message`.
+    pub fn error_message(&self) -> Option<String> {
+        match self.error {
+            None | Some(JsonValue::Null) => None,
+            Some(JsonValue::Array(ref values)) => values.get(1).and_then(|v| v.as_text()),
+            Some(ref error @ JsonValue::Object(_)) => {
+                error.get("message").and_then(|v| v.as_text())
+            }
+            Some(ref error) => error.as_text(),
+        }
+    }
+}
+
+/// Decode a line holding a message.
+pub fn parse_message(line: &[u8]) -> Result<StratumMessage, ()> {
+    let value = parse_json(line)?;
+    match value {
+        JsonValue::Object(_) => {}
+        _ => return Err(()),
+    }
+    let id = value.get("id").and_then(|id| id.as_text());
+    let method = match value.get("method") {
+        Some(JsonValue::String(method)) => Some(method.clone()),
+        None | Some(JsonValue::Null) => None,
+        Some(_) => return Err(()),
+    };
+    let params = match value.get("params") {
+        Some(JsonValue::Array(params)) => params.clone(),
+        _ => Vec::new(),
+    };
+    let result = value.get("result").cloned();
+    let error = value.get("error").cloned();
+    if method.is_none() && result.is_none() && error.is_none() {
+        return Err(());
+    }
+    Ok(StratumMessage { id, method, params, result, error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        assert_eq!(
+            parse_json(b" {\"a\": [1, -2.5e3, true, null], \"b\\u00e9\\ud83d\\ude00\": \"x\\\"y\"} "),
+            Ok(JsonValue::Object(vec![
+                (
+                    "a".to_string(),
+                    JsonValue::Array(vec![
+                        JsonValue::Number("1".to_string()),
+                        JsonValue::Number("-2.5e3".to_string()),
+                        JsonValue::Bool(true),
+                        JsonValue::Null,
+                    ])
+                ),
+                ("b\u{e9}\u{1f600}".to_string(), JsonValue::String("x\"y".to_string())),
+            ]))
+        );
+        assert!(parse_json(b"{\"a\": 1,}").is_err());
+        assert!(parse_json(b"[1] 2").is_err());
+        assert!(parse_json(b"\"abc").is_err());
+        assert!(parse_json(&[b'['; 32]).is_err());
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let msg = parse_message(
+            br#"{"id": 2, "method": "mining.authorize", "params": ["wallet.rig1", "x"]}"#,
+        )
+        .unwrap();
+        assert_eq!(msg.id, Some("2".to_string()));
+        assert_eq!(msg.method, Some("mining.authorize".to_string()));
+        assert_eq!(msg.param_text(0), Some("wallet.rig1".to_string()));
+        assert!(!msg.is_response());
+
+        let msg = parse_message(br#"{"id":4,"result":null,"error":[23,"Low difficulty share",null]}"#)
+            .unwrap();
+        assert!(msg.is_response());
+        assert_eq!(msg.result, Some(JsonValue::Null));
+        assert_eq!(msg.error_message(), Some("Low difficulty share".to_string()));
+
+        let msg = parse_message(br#"{"id":null,"method":"mining.set_difficulty","params":[16]}"#)
+            .unwrap();
+        assert_eq!(msg.id, None);
+        assert_eq!(msg.param_text(0), Some("16".to_string()));
+
+        assert!(parse_message(b"[1, 2]").is_err());
+        assert!(parse_message(br#"{"id": 1}"#).is_err());
+        assert!(parse_message(br#"{"id": 1, "method": 5}"#).is_err());
+    }
+}
@@ -0,0 +1,590 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(stratum_stats, "app_layer.stratum", {
+    subscribe,
+    authorize,
+    submit,
+    rejected_shares,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum StratumEvent {
+    MalformedData,
+    LineTooLong,
+    UnsolicitedResponse,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static STRATUM_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static STRATUM_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum length of a line. A job notification with its merkle branches
+/// is a few kilobytes.
+const STRATUM_MAX_LINE_LEN: usize = 65536;
+
+/// Number of bytes the probing parser looks into for the first line.
+const STRATUM_PROBE_LEN: usize = 1024;
+
+pub static mut ALPROTO_STRATUM: AppProto = ALPROTO_UNKNOWN;
+
+/// A request of the miner and its response, or a notification of the
+/// pool setting the extranonce.
+#[derive(Debug)]
+pub struct StratumTransaction {
+    id: u64,
+    pub method: Option<String>,
+    pub request_id: Option<String>,
+    /// Set for the notifications of the pool.
+    pub notification: bool,
+    /// The software of the miner, sent in the subscribe request.
+    pub agent: Option<String>,
+    pub worker: Option<String>,
+    pub job_id: Option<String>,
+    pub extranonce1: Option<String>,
+    pub extranonce2_size: Option<u64>,
+    pub extranonce2: Option<String>,
+    /// The difficulty set by the pool when a share is submitted.
+    pub difficulty: Option<String>,
+    pub result: Option<bool>,
+    pub error: Option<String>,
+    pub complete: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for StratumTransaction {}
+
+impl StratumTransaction {
+    fn new(id: u64) -> StratumTransaction {
+        StratumTransaction {
+            id,
+            method: None,
+            request_id: None,
+            notification: false,
+            agent: None,
+            worker: None,
+            job_id: None,
+            extranonce1: None,
+            extranonce2_size: None,
+            extranonce2: None,
+            difficulty: None,
+            result: None,
+            error: None,
+            complete: false,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: StratumEvent) {
+        stats_incr!(stratum_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+}
+
+impl Drop for StratumTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for StratumTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = StratumEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+pub struct StratumState {
+    transactions: TxTable<StratumTransaction>,
+    /// The difficulty last set by the pool.
+    difficulty: Option<String>,
+}
+
+unsafe impl FfiType for StratumState {}
+
+impl txtable::State for StratumState {
+    type Tx = StratumTransaction;
+
+    fn tx_table(&self) -> &TxTable<StratumTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<StratumTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl StratumState {
+    pub fn new() -> StratumState {
+        StratumState {
+            transactions: TxTable::with_limit(&STRATUM_TX_LIMIT).with_gauges(&STRATUM_GAUGES),
+            difficulty: None,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    fn set_event(&mut self, event: StratumEvent) {
+        let mut tx = StratumTransaction::new(self.transactions.next_id());
+        tx.complete = true;
+        tx.set_event(event);
+        self.transactions.push(tx);
+    }
+
+    fn handle_request(&mut self, message: StratumMessage) {
+        let mut tx = StratumTransaction::new(self.transactions.next_id());
+        match message.method.as_ref().map(|m| m.as_str()) {
+            Some("mining.subscribe") => {
+                stats_incr!(stratum_stats::subscribe);
+                tx.agent = message.param_text(0);
+            }
+            Some("mining.authorize") => {
+                stats_incr!(stratum_stats::authorize);
+                tx.worker = message.param_text(0);
+            }
+            Some("mining.submit") => {
+                stats_incr!(stratum_stats::submit);
+                tx.worker = message.param_text(0);
+                tx.job_id = message.param_text(1);
+                tx.extranonce2 = message.param_text(2);
+                tx.difficulty = self.difficulty.clone();
+            }
+            _ => {}
+        }
+        tx.method = message.method;
+        tx.request_id = message.id;
+        self.transactions.push(tx);
+    }
+
+    fn handle_response(&mut self, message: StratumMessage) {
+        let request_id = match message.id {
+            Some(ref id) => id,
+            None => {
+                self.set_event(StratumEvent::UnsolicitedResponse);
+                return;
+            }
+        };
+        let tx = match self
+            .transactions
+            .iter_mut()
+            .find(|tx| !tx.complete && tx.request_id.as_ref() == Some(request_id))
+        {
+            Some(tx) => tx,
+            None => {
+                self.set_event(StratumEvent::UnsolicitedResponse);
+                return;
+            }
+        };
+        tx.complete = true;
+        tx.error = message.error_message();
+        match message.result {
+            Some(JsonValue::Bool(result)) => tx.result = Some(result),
+            Some(JsonValue::Array(ref result))
+                if tx.method.as_ref().map(|m| m.as_str()) == Some("mining.subscribe") =>
+            {
+                // [subscriptions, extranonce1, extranonce2_size]
+                tx.extranonce1 = result.get(1).and_then(|v| v.as_text());
+                tx.extranonce2_size = result.get(2).and_then(|v| v.as_u64());
+            }
+            _ => {}
+        }
+        if tx.method.as_ref().map(|m| m.as_str()) == Some("mining.submit")
+            && (tx.result == Some(false) || tx.error.is_some())
+        {
+            stats_incr!(stratum_stats::rejected_shares);
+        }
+    }
+
+    fn handle_notification(&mut self, message: StratumMessage) {
+        match message.method.as_ref().map(|m| m.as_str()) {
+            Some("mining.set_difficulty") => {
+                self.difficulty = message.param_text(0);
+            }
+            Some("mining.set_extranonce") => {
+                let mut tx = StratumTransaction::new(self.transactions.next_id());
+                tx.notification = true;
+                tx.complete = true;
+                tx.extranonce1 = message.param_text(0);
+                tx.extranonce2_size = message.params.get(1).and_then(|v| v.as_u64());
+                tx.method = message.method;
+                self.transactions.push(tx);
+            }
+            // job notifications come every few seconds, they are not
+            // transactions
+            _ => {}
+        }
+    }
+
+    fn handle_line(&mut self, line: &[u8], direction: Direction) {
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            return;
+        }
+        let message = match parse_message(line) {
+            Ok(message) => message,
+            Err(_) => {
+                self.set_event(StratumEvent::MalformedData);
+                return;
+            }
+        };
+        SCLogDebug!("message {:?}", message);
+        if direction == Direction::ToServer {
+            if message.is_response() {
+                self.set_event(StratumEvent::MalformedData);
+            } else {
+                self.handle_request(message);
+            }
+        } else if message.is_response() {
+            self.handle_response(message);
+        } else {
+            self.handle_notification(message);
+        }
+    }
+
+    fn parse(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
+        let mut current = input;
+        while !current.is_empty() {
+            match current.iter().position(|&b| b == b'\n') {
+                Some(pos) => {
+                    self.handle_line(&current[..pos], direction);
+                    current = &current[pos + 1..];
+                }
+                None => {
+                    if current.len() >= STRATUM_MAX_LINE_LEN {
+                        self.set_event(StratumEvent::LineTooLong);
+                        return AppLayerResult::err();
+                    }
+                    let consumed = input.len() - current.len();
+                    return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                           current.len() + 1);
+                }
+            }
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for a first line holding a mining request of the miner, or a
+/// mining notification of the pool.
+fn stratum_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    let line = match input.iter().position(|&b| b == b'\n') {
+        Some(pos) => &input[..pos],
+        None if input.len() < STRATUM_PROBE_LEN => return ProbeResult::Unknown,
+        None => return ProbeResult::Failed,
+    };
+    let message = match parse_message(line) {
+        Ok(message) => message,
+        Err(_) => return ProbeResult::Failed,
+    };
+    match message.method {
+        Some(ref method) if method.starts_with("mining.") => {
+            let actual = if message.id.is_some() {
+                Direction::ToServer
+            } else {
+                Direction::ToClient
+            };
+            ProbeResult::with_direction(unsafe { ALPROTO_STRATUM }, direction, actual)
+        }
+        _ => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_stratum_probing_parser, stratum_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_stratum_state_new(_orig_state: *mut std::os::raw::c_void,
+                                       _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = StratumState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_stratum_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut StratumState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_stratum_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut StratumState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_stratum_parse_request(_flow: *const Flow,
+                                                  state: *mut std::os::raw::c_void,
+                                                  pstate: *mut std::os::raw::c_void,
+                                                  input: *const u8,
+                                                  input_len: u32,
+                                                  _data: *const std::os::raw::c_void,
+                                                  _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut StratumState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_stratum_parse_response(_flow: *const Flow,
+                                                   state: *mut std::os::raw::c_void,
+                                                   pstate: *mut std::os::raw::c_void,
+                                                   input: *const u8,
+                                                   input_len: u32,
+                                                   _data: *const std::os::raw::c_void,
+                                                   _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut StratumState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_stratum_state_get_tx(state: *mut std::os::raw::c_void,
+                                                 tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut StratumState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_stratum_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut StratumState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_stratum_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                            _direction: u8) -> std::os::raw::c_int {
+    let tx: &mut StratumTransaction = cast_mut(tx);
+    if tx.complete {
+        1
+    } else {
+        0
+    }
+}
+
+export_tx_detect_state!(rs_stratum_state_get_tx_detect_state,
+                        rs_stratum_state_set_tx_detect_state, StratumTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_stratum_state_get_events(tx: *mut std::os::raw::c_void)
+                                                     -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut StratumTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_stratum_get_tx_data, StratumTransaction);
+
+fn stratum_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    STRATUM_GAUGES.log(js)?;
+    js.set_uint("events", stratum_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+fn register_pattern_probe() -> i8 {
+    for pattern in &[&b"mining.subscribe\0"[..], &b"mining.authorize\0"[..]] {
+        unsafe {
+            if AppLayerProtoDetectPMRegisterPatternCSwPP(IPPROTO_TCP as u8, ALPROTO_STRATUM,
+                                                         pattern.as_ptr() as *const std::os::raw::c_char,
+                                                         128, 0, core::STREAM_TOSERVER,
+                                                         rs_stratum_probing_parser, 0, 0) < 0 {
+                SCLogDebug!("TOSERVER => AppLayerProtoDetectPMRegisterPatternCSwPP FAILED");
+                return -1;
+            }
+        }
+    }
+    0
+}
+
+const PARSER_NAME: &[u8] = b"stratum\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_stratum_register_parser() {
+    stratum_stats::register();
+    STRATUM_TX_LIMIT.configure("stratum", txtable::DEFAULT_MAX_TX);
+    introspect::register("stratum", stratum_introspect);
+    let default_port = CString::new("3333").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_TCP,
+        probe_ts: Some(rs_stratum_probing_parser),
+        probe_tc: Some(rs_stratum_probing_parser),
+        min_depth: 0,
+        max_depth: STRATUM_PROBE_LEN as u16,
+        state_new: rs_stratum_state_new,
+        state_free: rs_stratum_state_free,
+        tx_free: rs_stratum_state_tx_free,
+        parse_ts: rs_stratum_parse_request,
+        parse_tc: rs_stratum_parse_response,
+        get_tx_count: rs_stratum_state_get_tx_count,
+        get_tx: rs_stratum_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_stratum_tx_get_alstate_progress,
+        get_de_state: rs_stratum_state_get_tx_detect_state,
+        set_de_state: rs_stratum_state_set_tx_detect_state,
+        get_events: Some(rs_stratum_state_get_events),
+        get_eventinfo: Some(StratumEvent::get_event_info),
+        get_eventinfo_byid: Some(StratumEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<StratumState>),
+        get_tx_data: rs_stratum_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<StratumState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_STRATUM = alproto;
+        if register_pattern_probe() < 0 {
+            return;
+        }
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust stratum parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for Stratum.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_stratum_state_new,
+            state_free: rs_stratum_state_free,
+            parse_ts: rs_stratum_parse_request,
+            parse_tc: rs_stratum_parse_response,
+            get_tx_count: rs_stratum_state_get_tx_count,
+            get_tx: rs_stratum_state_get_tx,
+            tx_free: rs_stratum_state_tx_free,
+        })
+    }
+
+    #[test]
+    fn test_stratum_session() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer,
+                       b"{\"id\": 1, \"method\": \"mining.subscribe\", \"params\": [\"cgminer/4.10.0\"]}\n"));
+        // a response split over two segments
+        assert!(h.feed(Direction::ToClient,
+                       b"{\"id\":1,\"result\":[[[\"mining.set_difficulty\",\"1\"],[\"mining.notify\",\"1\"]],"));
+        assert!(h.feed(Direction::ToClient, b"\"08000002\",4],\"error\":null}\n"));
+        assert!(h.feed(Direction::ToServer,
+                       b"{\"id\": 2, \"method\": \"mining.authorize\", \"params\": [\"wallet.rig1\", \"x\"]}\n"));
+        assert!(h.feed(Direction::ToClient,
+                       b"{\"id\":2,\"result\":true,\"error\":null}\n\
+                         {\"id\":null,\"method\":\"mining.set_difficulty\",\"params\":[16]}\n\
+                         {\"id\":null,\"method\":\"mining.notify\",\"params\":[\"bf\",\"4d16\",\"01\",\"02\",[],\"00000002\",\"1c2ac4af\",\"504e86b9\",false]}\n"));
+        assert!(h.feed(Direction::ToServer,
+                       b"{\"params\": [\"wallet.rig1\", \"bf\", \"00000001\", \"504e86ed\", \"b2957c02\"], \"id\": 4, \"method\": \"mining.submit\"}\n"));
+        assert!(h.feed(Direction::ToClient,
+                       b"{\"id\":4,\"result\":null,\"error\":[23,\"Low difficulty share\",null]}\n"));
+
+        assert_eq!(h.tx_count(), 3);
+        let tx = h.get_tx::<StratumTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.method, Some("mining.subscribe".to_string()));
+        assert_eq!(tx.agent, Some("cgminer/4.10.0".to_string()));
+        assert_eq!(tx.extranonce1, Some("08000002".to_string()));
+        assert_eq!(tx.extranonce2_size, Some(4));
+        let tx = h.get_tx::<StratumTransaction>(1).unwrap();
+        assert_eq!(tx.worker, Some("wallet.rig1".to_string()));
+        assert_eq!(tx.result, Some(true));
+        let tx = h.get_tx::<StratumTransaction>(2).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.request_id, Some("4".to_string()));
+        assert_eq!(tx.job_id, Some("bf".to_string()));
+        assert_eq!(tx.extranonce2, Some("00000001".to_string()));
+        assert_eq!(tx.difficulty, Some("16".to_string()));
+        assert_eq!(tx.result, None);
+        assert_eq!(tx.error, Some("Low difficulty share".to_string()));
+    }
+
+    #[test]
+    fn test_stratum_set_extranonce() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient,
+                       b"{\"id\":null,\"method\":\"mining.set_extranonce\",\"params\":[\"af4c\",6]}\r\n"));
+        assert!(h.feed(Direction::ToClient, b"{\"id\":9,\"result\":true}\n"));
+        assert_eq!(h.tx_count(), 2);
+        let tx = h.get_tx::<StratumTransaction>(0).unwrap();
+        assert!(tx.notification);
+        assert_eq!(tx.extranonce1, Some("af4c".to_string()));
+        assert_eq!(tx.extranonce2_size, Some(6));
+        // a response without a request gets a transaction for its event
+        let tx = h.get_tx::<StratumTransaction>(1).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.method, None);
+    }
+
+    #[test]
+    fn test_stratum_probe() {
+        unsafe {
+            ALPROTO_STRATUM = 42;
+        }
+        let subscribe = b"{\"id\": 1, \"method\": \"mining.subscribe\", \"params\": []}\n";
+        assert_eq!(stratum_probe(subscribe, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(stratum_probe(subscribe, Direction::ToClient), ProbeResult::Flipped(42));
+        assert_eq!(stratum_probe(&subscribe[..20], Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(stratum_probe(b"{\"id\": 1, \"method\": \"getwork\"}\n", Direction::ToServer),
+                   ProbeResult::Failed);
+        assert_eq!(stratum_probe(b"GET / HTTP/1.1\r\n", Direction::ToServer), ProbeResult::Failed);
+    }
+}
@@ -0,0 +1,113 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Sticky buffers on the credentials of the login attempts.
+
+use super::telnet::{TelnetTransaction, ALPROTO_TELNET};
+use crate::applayer::cast_mut;
+use crate::detect::{
+    helper_buffer_mpm_register, helper_get_data, helper_keyword_register,
+    helper_setup_sticky_buffer, DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_TELNET_USERNAME_BUFFER_ID: c_int = 0;
+static mut G_TELNET_PASSWORD_BUFFER_ID: c_int = 0;
+
+unsafe fn set_buffer(data: &Option<Vec<u8>>, buf: *mut *const u8, len: *mut u32) -> bool {
+    match data {
+        Some(data) => {
+            *buf = data.as_ptr();
+            *len = data.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn telnet_tx_get_username(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut TelnetTransaction = cast_mut(tx);
+    set_buffer(&tx.username, buf, len)
+}
+
+unsafe extern "C" fn telnet_tx_get_password(
+    tx: *mut c_void, _flow_flags: u8, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut TelnetTransaction = cast_mut(tx);
+    set_buffer(&tx.password, buf, len)
+}
+
+unsafe extern "C" fn telnet_username_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, telnet_tx_get_username)
+}
+
+unsafe extern "C" fn telnet_password_get_data(
+    det_ctx: *mut c_void, transforms: *const c_void, f: *mut c_void, flow_flags: u8,
+    tx: *mut c_void, list_id: c_int,
+) -> *mut c_void {
+    helper_get_data(det_ctx, transforms, f, flow_flags, tx, list_id, telnet_tx_get_password)
+}
+
+unsafe extern "C" fn telnet_username_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_TELNET, G_TELNET_USERNAME_BUFFER_ID)
+}
+
+unsafe extern "C" fn telnet_password_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_TELNET, G_TELNET_PASSWORD_BUFFER_ID)
+}
+
+/// Register the Telnet keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "telnet.username",
+        desc: "sticky buffer to match on the username of Telnet logins",
+        url: "/rules/telnet-keywords.html#telnet-username",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: telnet_username_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_TELNET_USERNAME_BUFFER_ID = helper_buffer_mpm_register(
+            "telnet.username", "telnet login username", ALPROTO_TELNET, false, true, 0,
+            telnet_username_get_data);
+    }
+
+    let kw = DetectKeyword {
+        name: "telnet.password",
+        desc: "sticky buffer to match on the password of Telnet logins",
+        url: "/rules/telnet-keywords.html#telnet-password",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: telnet_password_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_TELNET_PASSWORD_BUFFER_ID = helper_buffer_mpm_register(
+            "telnet.password", "telnet login password", ALPROTO_TELNET, false, true, 0,
+            telnet_password_get_data);
+    }
+}
@@ -0,0 +1,74 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use super::telnet::{LoginResult, TelnetTransaction};
+use crate::core::Direction;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+pub const TELNET_LOG_PASSWORDS: u32 = BIT_U32!(0);
+
+fn log(tx: &TelnetTransaction, flags: u32, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("telnet")?;
+    if !tx.options.is_empty() {
+        js.open_array("options")?;
+        for option in &tx.options {
+            js.start_object()?;
+            let direction = if option.direction == Direction::ToServer {
+                "toserver"
+            } else {
+                "toclient"
+            };
+            js.set_string("direction", direction)?;
+            js.set_string("command", command_name(option.command))?;
+            match option_name(option.option) {
+                Some(name) => js.set_string("option", name)?,
+                None => js.set_uint("option", option.option as u64)?,
+            };
+            js.close()?;
+        }
+        js.close()?;
+    }
+    if let Some(ref terminal_type) = tx.terminal_type {
+        js.set_string_from_bytes("terminal_type", terminal_type)?;
+    }
+    if let Some(ref username) = tx.username {
+        js.set_string_from_bytes("username", username)?;
+    }
+    if let Some(ref password) = tx.password {
+        if flags & TELNET_LOG_PASSWORDS != 0 {
+            js.set_string_from_bytes("password", password)?;
+        } else {
+            js.set_bool("password", true)?;
+        }
+    }
+    let result = match tx.result {
+        LoginResult::Unknown => None,
+        LoginResult::Success => Some("success"),
+        LoginResult::Failure => Some("failure"),
+    };
+    if let Some(result) = result {
+        js.set_string("login", result)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_telnet_to_json(tx: &mut TelnetTransaction, flags: u32, js: &mut JsonBuilder) -> bool {
+    log(tx, flags, js).is_ok()
+}
@@ -0,0 +1,234 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Telnet command decoding (RFC 854, RFC 855).
+//!
+//! The decoder is fed the data of a direction as it arrives, and splits
+//! it into data and commands. A command may be split over several
+//! calls.
+
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+pub const SB: u8 = 250;
+pub const SE: u8 = 240;
+
+pub const OPTION_ECHO: u8 = 1;
+pub const OPTION_TERMINAL_TYPE: u8 = 24;
+
+/// Subnegotiation command sending a value (RFC 1091).
+pub const SB_IS: u8 = 0;
+
+/// Maximum length of the data of a subnegotiation. Longer ones are
+/// truncated.
+const MAX_SB_LEN: usize = 256;
+
+pub fn command_name(command: u8) -> &'static str {
+    match command {
+        DONT => "dont",
+        DO => "do",
+        WONT => "wont",
+        WILL => "will",
+        241 => "nop",
+        242 => "data_mark",
+        243 => "break",
+        244 => "interrupt_process",
+        245 => "abort_output",
+        246 => "are_you_there",
+        247 => "erase_character",
+        248 => "erase_line",
+        249 => "go_ahead",
+        _ => "unknown",
+    }
+}
+
+pub fn option_name(option: u8) -> Option<&'static str> {
+    let name = match option {
+        0 => "binary",
+        OPTION_ECHO => "echo",
+        3 => "suppress_go_ahead",
+        5 => "status",
+        6 => "timing_mark",
+        OPTION_TERMINAL_TYPE => "terminal_type",
+        31 => "naws",
+        32 => "terminal_speed",
+        33 => "remote_flow_control",
+        34 => "linemode",
+        35 => "x_display_location",
+        36 => "environ",
+        37 => "authentication",
+        38 => "encryption",
+        39 => "new_environ",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TelnetItem {
+    Data(Vec<u8>),
+    /// WILL, WONT, DO or DONT of an option.
+    Negotiation { command: u8, option: u8 },
+    Subnegotiation { option: u8, data: Vec<u8> },
+    Command(u8),
+    /// IAC followed by a byte that is not a command.
+    Invalid(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DecoderState {
+    Data,
+    Iac,
+    Option(u8),
+    SbOption,
+    SbData(u8),
+    SbIac(u8),
+}
+
+#[derive(Debug)]
+pub struct TelnetDecoder {
+    state: DecoderState,
+    sb: Vec<u8>,
+}
+
+impl TelnetDecoder {
+    pub fn new() -> TelnetDecoder {
+        TelnetDecoder {
+            state: DecoderState::Data,
+            sb: Vec::new(),
+        }
+    }
+
+    /// Decode `input`, continuing a command started in a previous call.
+    pub fn decode(&mut self, input: &[u8]) -> Vec<TelnetItem> {
+        let mut items = Vec::new();
+        let mut data = Vec::new();
+        for &b in input {
+            self.state = match self.state {
+                DecoderState::Data if b == IAC => DecoderState::Iac,
+                DecoderState::Data => {
+                    data.push(b);
+                    DecoderState::Data
+                }
+                DecoderState::Iac => {
+                    if b == IAC {
+                        // escaped 0xff data byte
+                        data.push(b);
+                        DecoderState::Data
+                    } else {
+                        if !data.is_empty() {
+                            items.push(TelnetItem::Data(std::mem::replace(&mut data, Vec::new())));
+                        }
+                        match b {
+                            WILL | WONT | DO | DONT => DecoderState::Option(b),
+                            SB => DecoderState::SbOption,
+                            241..=249 => {
+                                items.push(TelnetItem::Command(b));
+                                DecoderState::Data
+                            }
+                            _ => {
+                                items.push(TelnetItem::Invalid(b));
+                                DecoderState::Data
+                            }
+                        }
+                    }
+                }
+                DecoderState::Option(command) => {
+                    items.push(TelnetItem::Negotiation { command, option: b });
+                    DecoderState::Data
+                }
+                DecoderState::SbOption => {
+                    self.sb.clear();
+                    DecoderState::SbData(b)
+                }
+                DecoderState::SbData(option) if b == IAC => DecoderState::SbIac(option),
+                DecoderState::SbData(option) => {
+                    self.push_sb(b);
+                    DecoderState::SbData(option)
+                }
+                DecoderState::SbIac(option) => match b {
+                    SE => {
+                        let data = std::mem::replace(&mut self.sb, Vec::new());
+                        items.push(TelnetItem::Subnegotiation { option, data });
+                        DecoderState::Data
+                    }
+                    IAC => {
+                        self.push_sb(b);
+                        DecoderState::SbData(option)
+                    }
+                    _ => {
+                        // not a valid end of subnegotiation, but what
+                        // follows is a command and not more of it
+                        self.sb.clear();
+                        items.push(TelnetItem::Invalid(b));
+                        DecoderState::Data
+                    }
+                },
+            };
+        }
+        if !data.is_empty() {
+            items.push(TelnetItem::Data(data));
+        }
+        items
+    }
+
+    fn push_sb(&mut self, b: u8) {
+        if self.sb.len() < MAX_SB_LEN {
+            self.sb.push(b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_negotiation() {
+        let mut decoder = TelnetDecoder::new();
+        let items = decoder.decode(&[IAC, DO, 24, IAC, WILL, 1, b'h', b'i']);
+        assert_eq!(
+            items,
+            vec![
+                TelnetItem::Negotiation { command: DO, option: 24 },
+                TelnetItem::Negotiation { command: WILL, option: 1 },
+                TelnetItem::Data(b"hi".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_split() {
+        let mut decoder = TelnetDecoder::new();
+        assert_eq!(decoder.decode(b"ab\xff"), vec![TelnetItem::Data(b"ab".to_vec())]);
+        assert_eq!(decoder.decode(&[IAC, b'c']), vec![TelnetItem::Data(vec![IAC, b'c'])]);
+        assert!(decoder.decode(&[IAC, SB, 24, SB_IS, b'x']).is_empty());
+        assert_eq!(
+            decoder.decode(&[b't', IAC, SE]),
+            vec![TelnetItem::Subnegotiation { option: 24, data: vec![SB_IS, b'x', b't'] }]
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        let mut decoder = TelnetDecoder::new();
+        let items = decoder.decode(&[IAC, 0x41, IAC, 246]);
+        assert_eq!(items, vec![TelnetItem::Invalid(0x41), TelnetItem::Command(246)]);
+    }
+}
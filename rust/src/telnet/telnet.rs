@@ -0,0 +1,721 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::conf::conf_get_app_layer_node;
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(telnet_stats, "app_layer.telnet", {
+    logins,
+    login_failures,
+    brute_force,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum TelnetEvent {
+    MalformedData,
+    PlaintextLogin,
+    BruteForce,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static TELNET_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static TELNET_GAUGES: StateGauges = StateGauges::new();
+
+/// Number of failed logins on a flow at which the brute force event is
+/// set.
+static mut TELNET_BRUTE_FORCE_THRESHOLD: u32 = 3;
+
+/// Maximum length of a line kept for the prompts and credentials. The
+/// rest of a longer line is dropped.
+const TELNET_MAX_LINE_LEN: usize = 256;
+
+/// Maximum number of option negotiations kept per transaction.
+const TELNET_MAX_TX_OPTIONS: usize = 64;
+
+pub static mut ALPROTO_TELNET: AppProto = ALPROTO_UNKNOWN;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoginResult {
+    Unknown,
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TelnetOption {
+    pub direction: Direction,
+    pub command: u8,
+    pub option: u8,
+}
+
+/// A login attempt, with the option negotiation seen while it was open.
+#[derive(Debug)]
+pub struct TelnetTransaction {
+    id: u64,
+    pub options: Vec<TelnetOption>,
+    pub terminal_type: Option<Vec<u8>>,
+    pub username: Option<Vec<u8>>,
+    pub password: Option<Vec<u8>>,
+    pub result: LoginResult,
+    pub complete: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for TelnetTransaction {}
+
+impl TelnetTransaction {
+    fn new(id: u64) -> TelnetTransaction {
+        TelnetTransaction {
+            id,
+            options: Vec::new(),
+            terminal_type: None,
+            username: None,
+            password: None,
+            result: LoginResult::Unknown,
+            complete: false,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: TelnetEvent) {
+        stats_incr!(telnet_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+}
+
+impl Drop for TelnetTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for TelnetTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = TelnetEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+/// Assembles the lines of a direction. Telnet ends lines with CR LF or
+/// CR NUL, and a client in character mode sends the erase characters.
+#[derive(Debug, Default)]
+struct LineBuffer {
+    line: Vec<u8>,
+    last_cr: bool,
+}
+
+impl LineBuffer {
+    /// Add `data`, returning the lines it completes.
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut lines = Vec::new();
+        for &b in data {
+            let last_cr = self.last_cr;
+            self.last_cr = false;
+            match b {
+                b'\r' => {
+                    lines.push(std::mem::replace(&mut self.line, Vec::new()));
+                    self.last_cr = true;
+                }
+                b'\n' | 0 if last_cr => {}
+                b'\n' => lines.push(std::mem::replace(&mut self.line, Vec::new())),
+                0x08 | 0x7f => {
+                    self.line.pop();
+                }
+                _ => {
+                    if self.line.len() < TELNET_MAX_LINE_LEN {
+                        self.line.push(b);
+                    }
+                }
+            }
+        }
+        lines
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ServerText {
+    LoginPrompt,
+    PasswordPrompt,
+    ShellPrompt,
+    Failure,
+    Success,
+    Other,
+}
+
+/// Classify a line, or the start of a line, sent by the server.
+fn classify_server_text(text: &[u8]) -> ServerText {
+    let text = String::from_utf8_lossy(text).to_lowercase();
+    let text = text.trim_end();
+    if ["login:", "username:", "user name:", "user:"].iter().any(|p| text.ends_with(p)) {
+        ServerText::LoginPrompt
+    } else if ["password:", "passcode:"].iter().any(|p| text.ends_with(p)) {
+        ServerText::PasswordPrompt
+    } else if ["incorrect", "failed", "failure", "denied", "invalid", "bad password"]
+        .iter()
+        .any(|p| text.contains(p))
+    {
+        ServerText::Failure
+    } else if text.contains("last login") || text.contains("welcome") {
+        ServerText::Success
+    } else if text.ends_with(|c: char| c == '$' || c == '#' || c == '>' || c == '%') {
+        ServerText::ShellPrompt
+    } else {
+        ServerText::Other
+    }
+}
+
+/// What the parser waits for in the login dialog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Expect {
+    Nothing,
+    Username,
+    Password,
+    Result,
+}
+
+pub struct TelnetState {
+    transactions: TxTable<TelnetTransaction>,
+    decoder_ts: TelnetDecoder,
+    decoder_tc: TelnetDecoder,
+    lines_ts: LineBuffer,
+    lines_tc: LineBuffer,
+    /// Id of the open login transaction.
+    current: Option<u64>,
+    expect: Expect,
+    failed_logins: u32,
+    /// Set after a successful login: the rest of the flow is the session,
+    /// and is not parsed.
+    logged_in: bool,
+}
+
+unsafe impl FfiType for TelnetState {}
+
+impl txtable::State for TelnetState {
+    type Tx = TelnetTransaction;
+
+    fn tx_table(&self) -> &TxTable<TelnetTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<TelnetTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl TelnetState {
+    pub fn new() -> TelnetState {
+        TelnetState {
+            transactions: TxTable::with_limit(&TELNET_TX_LIMIT).with_gauges(&TELNET_GAUGES),
+            decoder_ts: TelnetDecoder::new(),
+            decoder_tc: TelnetDecoder::new(),
+            lines_ts: LineBuffer::default(),
+            lines_tc: LineBuffer::default(),
+            current: None,
+            expect: Expect::Nothing,
+            failed_logins: 0,
+            logged_in: false,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    /// The open login transaction, created if there is none.
+    fn open_tx(&mut self) -> &mut TelnetTransaction {
+        // the open transaction is the last one, unless the transaction
+        // limit dropped it
+        let open = match (self.current, self.transactions.last_mut()) {
+            (Some(id), Some(tx)) => tx.id == id,
+            _ => false,
+        };
+        if !open {
+            let id = self.transactions.next_id();
+            self.transactions.push(TelnetTransaction::new(id));
+            self.current = Some(id);
+        }
+        self.transactions.last_mut().unwrap()
+    }
+
+    fn complete_login(&mut self, result: LoginResult) {
+        if self.current.is_none() {
+            return;
+        }
+        let failed_logins = if result == LoginResult::Failure {
+            stats_incr!(telnet_stats::login_failures);
+            self.failed_logins += 1;
+            self.failed_logins
+        } else {
+            self.failed_logins
+        };
+        let threshold = unsafe { TELNET_BRUTE_FORCE_THRESHOLD };
+        let tx = self.open_tx();
+        tx.result = result;
+        tx.complete = true;
+        if result == LoginResult::Failure && failed_logins == threshold {
+            stats_incr!(telnet_stats::brute_force);
+            tx.set_event(TelnetEvent::BruteForce);
+        }
+        self.current = None;
+        self.expect = Expect::Nothing;
+        if result == LoginResult::Success {
+            SCLogDebug!("logged in, no more parsing");
+            self.logged_in = true;
+        }
+    }
+
+    fn handle_server_text(&mut self, text: ServerText) {
+        match (text, self.expect) {
+            (ServerText::LoginPrompt, expect) => {
+                if expect == Expect::Result {
+                    // prompted again
+                    self.complete_login(LoginResult::Failure);
+                }
+                let tx = self.open_tx();
+                if tx.username.is_some() || tx.password.is_some() {
+                    // an attempt that was abandoned
+                    tx.complete = true;
+                    self.current = None;
+                }
+                self.open_tx();
+                self.expect = Expect::Username;
+            }
+            (ServerText::PasswordPrompt, Expect::Result) => {
+                self.complete_login(LoginResult::Failure);
+                self.open_tx();
+                self.expect = Expect::Password;
+            }
+            (ServerText::PasswordPrompt, _) => {
+                self.open_tx();
+                self.expect = Expect::Password;
+            }
+            (ServerText::Failure, Expect::Result) => self.complete_login(LoginResult::Failure),
+            (ServerText::Success, Expect::Result) | (ServerText::ShellPrompt, Expect::Result) => {
+                self.complete_login(LoginResult::Success)
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_server_data(&mut self, data: &[u8]) {
+        for line in self.lines_tc.push(data) {
+            // prompts are expected at the end of the data, but a server
+            // may also end them with a new line
+            self.handle_server_text(classify_server_text(&line));
+            if self.logged_in {
+                return;
+            }
+        }
+        if self.lines_tc.line.is_empty() {
+            return;
+        }
+        // a prompt does not end with a new line
+        let text = classify_server_text(&self.lines_tc.line);
+        match text {
+            ServerText::LoginPrompt | ServerText::PasswordPrompt | ServerText::ShellPrompt => {
+                self.lines_tc.line.clear();
+                self.handle_server_text(text);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_client_data(&mut self, data: &[u8]) {
+        for line in self.lines_ts.push(data) {
+            match self.expect {
+                Expect::Username if !line.is_empty() => {
+                    self.open_tx().username = Some(line);
+                    self.expect = Expect::Nothing;
+                }
+                Expect::Password => {
+                    stats_incr!(telnet_stats::logins);
+                    let tx = self.open_tx();
+                    tx.password = Some(line);
+                    tx.set_event(TelnetEvent::PlaintextLogin);
+                    self.expect = Expect::Result;
+                }
+                _ => {}
+            }
+            if self.logged_in {
+                break;
+            }
+        }
+    }
+
+    fn handle_item(&mut self, item: TelnetItem, direction: Direction) {
+        match item {
+            TelnetItem::Data(data) => {
+                if direction == Direction::ToServer {
+                    self.handle_client_data(&data);
+                } else {
+                    self.handle_server_data(&data);
+                }
+            }
+            TelnetItem::Negotiation { command, option } => {
+                let tx = self.open_tx();
+                if tx.options.len() < TELNET_MAX_TX_OPTIONS {
+                    tx.options.push(TelnetOption { direction, command, option });
+                }
+            }
+            TelnetItem::Subnegotiation { option, data } => {
+                if option == OPTION_TERMINAL_TYPE
+                    && direction == Direction::ToServer
+                    && data.first() == Some(&SB_IS)
+                {
+                    self.open_tx().terminal_type = Some(data[1..].to_vec());
+                }
+            }
+            TelnetItem::Command(_) => {}
+            TelnetItem::Invalid(_) => {
+                self.open_tx().set_event(TelnetEvent::MalformedData);
+            }
+        }
+    }
+
+    fn parse(&mut self, input: &[u8], direction: Direction) -> AppLayerResult {
+        if self.logged_in {
+            return AppLayerResult::ok();
+        }
+        let items = if direction == Direction::ToServer {
+            self.decoder_ts.decode(input)
+        } else {
+            self.decoder_tc.decode(input)
+        };
+        for item in items {
+            self.handle_item(item, direction);
+            if self.logged_in {
+                break;
+            }
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for option negotiation, which both sides start with.
+fn telnet_probe(input: &[u8], _direction: Direction) -> ProbeResult {
+    if input.len() < 2 {
+        return ProbeResult::Unknown;
+    }
+    match (input[0], input[1]) {
+        (IAC, WILL) | (IAC, WONT) | (IAC, DO) | (IAC, DONT) | (IAC, SB) => {
+            ProbeResult::Found(unsafe { ALPROTO_TELNET })
+        }
+        _ => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_telnet_probing_parser, telnet_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_telnet_state_new(_orig_state: *mut std::os::raw::c_void,
+                                      _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = TelnetState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_telnet_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut TelnetState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_telnet_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut TelnetState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_telnet_parse_request(_flow: *const Flow,
+                                                 state: *mut std::os::raw::c_void,
+                                                 pstate: *mut std::os::raw::c_void,
+                                                 input: *const u8,
+                                                 input_len: u32,
+                                                 _data: *const std::os::raw::c_void,
+                                                 _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut TelnetState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_telnet_parse_response(_flow: *const Flow,
+                                                  state: *mut std::os::raw::c_void,
+                                                  pstate: *mut std::os::raw::c_void,
+                                                  input: *const u8,
+                                                  input_len: u32,
+                                                  _data: *const std::os::raw::c_void,
+                                                  _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut TelnetState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_telnet_state_get_tx(state: *mut std::os::raw::c_void,
+                                                tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut TelnetState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_telnet_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut TelnetState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_telnet_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                           _direction: u8) -> std::os::raw::c_int {
+    let tx: &mut TelnetTransaction = cast_mut(tx);
+    if tx.complete {
+        1
+    } else {
+        0
+    }
+}
+
+export_tx_detect_state!(rs_telnet_state_get_tx_detect_state, rs_telnet_state_set_tx_detect_state,
+                        TelnetTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_telnet_state_get_events(tx: *mut std::os::raw::c_void)
+                                                    -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut TelnetTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_telnet_get_tx_data, TelnetTransaction);
+
+fn telnet_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    TELNET_GAUGES.log(js)?;
+    js.set_uint("events", telnet_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+const PARSER_NAME: &[u8] = b"telnet\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_telnet_register_parser() {
+    if let Some(conf) = conf_get_app_layer_node("telnet") {
+        if let Some(val) = conf.get_child_u64("brute-force-threshold") {
+            if val == 0 || val > std::u32::MAX as u64 {
+                SCLogError!("Invalid value for brute-force-threshold: {}", val);
+            } else {
+                TELNET_BRUTE_FORCE_THRESHOLD = val as u32;
+            }
+        }
+    }
+    telnet_stats::register();
+    TELNET_TX_LIMIT.configure("telnet", txtable::DEFAULT_MAX_TX);
+    introspect::register("telnet", telnet_introspect);
+    let default_port = CString::new("23").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_TCP,
+        probe_ts: Some(rs_telnet_probing_parser),
+        probe_tc: Some(rs_telnet_probing_parser),
+        min_depth: 0,
+        max_depth: 16,
+        state_new: rs_telnet_state_new,
+        state_free: rs_telnet_state_free,
+        tx_free: rs_telnet_state_tx_free,
+        parse_ts: rs_telnet_parse_request,
+        parse_tc: rs_telnet_parse_response,
+        get_tx_count: rs_telnet_state_get_tx_count,
+        get_tx: rs_telnet_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_telnet_tx_get_alstate_progress,
+        get_de_state: rs_telnet_state_get_tx_detect_state,
+        set_de_state: rs_telnet_state_set_tx_detect_state,
+        get_events: Some(rs_telnet_state_get_events),
+        get_eventinfo: Some(TelnetEvent::get_event_info),
+        get_eventinfo_byid: Some(TelnetEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<TelnetState>),
+        get_tx_data: rs_telnet_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<TelnetState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_TELNET = alproto;
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust telnet parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for Telnet.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_telnet_state_new,
+            state_free: rs_telnet_state_free,
+            parse_ts: rs_telnet_parse_request,
+            parse_tc: rs_telnet_parse_response,
+            get_tx_count: rs_telnet_state_get_tx_count,
+            get_tx: rs_telnet_state_get_tx,
+            tx_free: rs_telnet_state_tx_free,
+        })
+    }
+
+    /// Send a line the way a client in character mode does.
+    fn type_line(h: &mut ParserHarness, line: &[u8], echo: bool) {
+        for &c in line {
+            assert!(h.feed(Direction::ToServer, &[c]));
+            if echo {
+                assert!(h.feed(Direction::ToClient, &[c]));
+            }
+        }
+        assert!(h.feed(Direction::ToServer, b"\r\0"));
+        assert!(h.feed(Direction::ToClient, b"\r\n"));
+    }
+
+    #[test]
+    fn test_telnet_login() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToClient, &[IAC, DO, OPTION_TERMINAL_TYPE, IAC, WILL, OPTION_ECHO]));
+        assert!(h.feed(Direction::ToServer, &[IAC, WILL, OPTION_TERMINAL_TYPE]));
+        let mut sb = vec![IAC, SB, OPTION_TERMINAL_TYPE, SB_IS];
+        sb.extend_from_slice(b"XTERM");
+        sb.extend_from_slice(&[IAC, SE]);
+        assert!(h.feed(Direction::ToServer, &sb));
+        assert!(h.feed(Direction::ToClient, b"Ubuntu 20.04\r\nhost lo"));
+        assert!(h.feed(Direction::ToClient, b"gin: "));
+        type_line(&mut h, b"roor\x7ft", true);
+        assert!(h.feed(Direction::ToClient, b"Password: "));
+        type_line(&mut h, b"s3cret", false);
+        assert!(h.feed(Direction::ToClient, b"Last login: Thu Oct 15 10:00:00\r\n"));
+        assert!(h.feed(Direction::ToClient, b"root@host:~# "));
+        assert!(h.feed(Direction::ToServer, b"ls\r\n"));
+
+        assert_eq!(h.tx_count(), 1);
+        let tx = h.get_tx::<TelnetTransaction>(0).unwrap();
+        assert!(tx.complete);
+        assert_eq!(tx.result, LoginResult::Success);
+        assert_eq!(tx.username, Some(b"root".to_vec()));
+        assert_eq!(tx.password, Some(b"s3cret".to_vec()));
+        assert_eq!(tx.terminal_type, Some(b"XTERM".to_vec()));
+        assert_eq!(tx.options.len(), 3);
+        assert_eq!(
+            tx.options[2],
+            TelnetOption { direction: Direction::ToServer, command: WILL, option: 24 }
+        );
+    }
+
+    #[test]
+    fn test_telnet_failed_logins() {
+        let mut h = harness();
+        for _ in 0..3 {
+            assert!(h.feed(Direction::ToClient, b"login: "));
+            type_line(&mut h, b"admin", true);
+            assert!(h.feed(Direction::ToClient, b"Password:"));
+            type_line(&mut h, b"admin", false);
+            assert!(h.feed(Direction::ToClient, b"\r\nLogin incorrect\r\n"));
+        }
+        // prompted again without a failure message
+        assert!(h.feed(Direction::ToClient, b"login: "));
+        type_line(&mut h, b"guest", true);
+        assert!(h.feed(Direction::ToClient, b"Password:"));
+        type_line(&mut h, b"guest", false);
+        assert!(h.feed(Direction::ToClient, b"login: "));
+
+        assert_eq!(h.tx_count(), 5);
+        for id in 0..4 {
+            let tx = h.get_tx::<TelnetTransaction>(id).unwrap();
+            assert!(tx.complete);
+            assert_eq!(tx.result, LoginResult::Failure);
+        }
+        assert_eq!(h.get_tx::<TelnetTransaction>(3).unwrap().username, Some(b"guest".to_vec()));
+        assert!(!h.get_tx::<TelnetTransaction>(4).unwrap().complete);
+        assert_eq!(h.state::<TelnetState>().failed_logins, 4);
+    }
+
+    #[test]
+    fn test_telnet_lines() {
+        let mut lines = LineBuffer::default();
+        assert!(lines.push(b"ab").is_empty());
+        assert_eq!(lines.push(b"c\x08d\r"), vec![b"abd".to_vec()]);
+        assert_eq!(lines.push(b"\n\nx\r\0"), vec![b"".to_vec(), b"x".to_vec()]);
+    }
+
+    #[test]
+    fn test_telnet_classify() {
+        assert_eq!(classify_server_text(b"myhost login: "), ServerText::LoginPrompt);
+        assert_eq!(classify_server_text(b"Password:"), ServerText::PasswordPrompt);
+        assert_eq!(classify_server_text(b"Login incorrect"), ServerText::Failure);
+        assert_eq!(classify_server_text(b"Router>"), ServerText::ShellPrompt);
+        assert_eq!(classify_server_text(b"Ubuntu 20.04"), ServerText::Other);
+    }
+
+    #[test]
+    fn test_telnet_probe() {
+        unsafe {
+            ALPROTO_TELNET = 42;
+        }
+        assert_eq!(telnet_probe(&[IAC, DO, 24], Direction::ToClient), ProbeResult::Found(42));
+        assert_eq!(telnet_probe(&[IAC], Direction::ToClient), ProbeResult::Unknown);
+        assert_eq!(telnet_probe(b"SSH-2.0-", Direction::ToClient), ProbeResult::Failed);
+    }
+}
@@ -0,0 +1,454 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Test support for the Rust app-layer parsers.
+//!
+//! `ParserHarness` drives a parser through the same C ABI functions the
+//! engine uses, without the engine: data that a parser reports as
+//! incomplete is buffered and passed again once enough data is available,
+//! like the stream engine does. Data can be fed in arbitrary segments to
+//! check that a parser copes with any segmentation.
+//!
+//! `pcap_payloads` extracts the app-layer payloads of a single flow from a
+//! pcap file, so captured traffic can be replayed through a parser.
+//!
+//! Decoder events are stored by the C engine. Without it they are kept
+//! as a list of event ids, which `decoder_events` returns, so the events
+//! of a transaction can be checked along with the rest of it through
+//! `get_tx`.
+
+use crate::applayer::*;
+use crate::core::{AppLayerDecoderEvents, AppProto, Direction, Flow, STREAM_GAP, STREAM_START, STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::flowstorage::FlowStorageId;
+use std::os::raw::{c_int, c_void};
+
+// The parsers only use the parser state flags to get the EOF flags and
+// to disable inspection, which do not matter here.
+#[no_mangle]
+pub extern "C" fn AppLayerParserStateIssetFlag(_state: *mut c_void, _flag: u8) -> c_int {
+    0
+}
+
+#[no_mangle]
+pub extern "C" fn AppLayerParserStateSetFlag(_state: *mut c_void, _flag: u8) {}
+
+// FTP only sets up data channel expectations with a flow, which the
+// harness does not have.
+#[no_mangle]
+pub extern "C" fn FTPDataExpectationCreate(
+    _flow: *const c_void, _direction: u8, _dyn_port: u16, _file_name: *const u8,
+    _file_name_len: u32, _store: bool,
+) -> c_int {
+    -1
+}
+
+// SIP sets up media expectations with a flow, which the harness does
+// not have.
+#[no_mangle]
+pub extern "C" fn AppLayerExpectationCreate(
+    _flow: *mut Flow, _direction: c_int, _src: u16, _dst: u16, _alproto: AppProto,
+    _data: *mut c_void,
+) -> c_int {
+    -1
+}
+
+// The harness has no flows, so nothing is ever stored on one.
+#[no_mangle]
+pub(crate) extern "C" fn FlowGetStorageById(_flow: *mut Flow, _id: FlowStorageId) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+#[no_mangle]
+pub(crate) extern "C" fn FlowSetStorageById(
+    _flow: *mut Flow, _id: FlowStorageId, _ptr: *mut c_void,
+) -> c_int {
+    -1
+}
+
+// The PROXY protocol hands the flow back to protocol detection, which
+// is up to the engine.
+#[no_mangle]
+pub extern "C" fn AppLayerRequestProtocolChange(
+    _flow: *const Flow, _dp: u16, _expect_proto: AppProto,
+) {
+}
+
+// The files are only opened with the file config of the engine, so
+// the flags are never used.
+#[no_mangle]
+pub extern "C" fn FileFlowToFlags(_flow: *const Flow, _direction: u8) -> u16 {
+    0
+}
+
+/// Stand-in for AppLayerDecoderEventsSetEventRaw: the events are a
+/// boxed list of event ids.
+pub(crate) fn set_event_raw(events: *mut *mut AppLayerDecoderEvents, event: u8) {
+    unsafe {
+        if (*events).is_null() {
+            *events = Box::into_raw(Box::new(Vec::<u8>::new())) as *mut AppLayerDecoderEvents;
+        }
+        (*(*events as *mut Vec<u8>)).push(event);
+    }
+}
+
+/// Stand-in for AppLayerDecoderEventsFreeEvents.
+pub(crate) fn free_events(events: *mut *mut AppLayerDecoderEvents) {
+    unsafe {
+        if !(*events).is_null() {
+            std::mem::drop(Box::from_raw(*events as *mut Vec<u8>));
+            *events = std::ptr::null_mut();
+        }
+    }
+}
+
+/// The ids of the decoder events set on a transaction, in the order
+/// they were set.
+pub fn decoder_events(events: *const AppLayerDecoderEvents) -> Vec<u8> {
+    if events.is_null() {
+        return Vec::new();
+    }
+    unsafe { (*(events as *const Vec<u8>)).clone() }
+}
+
+/// The parser functions used by the harness.
+pub struct ParserFns {
+    pub state_new: StateAllocFn,
+    pub state_free: StateFreeFn,
+    pub parse_ts: ParseFn,
+    pub parse_tc: ParseFn,
+    pub get_tx_count: StateGetTxCntFn,
+    pub get_tx: StateGetTxFn,
+    pub tx_free: StateTxFreeFn,
+}
+
+#[derive(Default)]
+struct StreamBuffer {
+    data: Vec<u8>,
+    needed: usize,
+    started: bool,
+}
+
+pub struct ParserHarness {
+    fns: ParserFns,
+    state: *mut c_void,
+    ts: StreamBuffer,
+    tc: StreamBuffer,
+}
+
+impl ParserHarness {
+    pub fn new(fns: ParserFns) -> ParserHarness {
+        let state = (fns.state_new)(std::ptr::null_mut(), 0);
+        assert!(!state.is_null());
+        ParserHarness {
+            fns,
+            state,
+            ts: StreamBuffer::default(),
+            tc: StreamBuffer::default(),
+        }
+    }
+
+    /// Feed data in one direction. Returns false if the parser returned an
+    /// error.
+    pub fn feed(&mut self, direction: Direction, data: &[u8]) -> bool {
+        let (buffer, parse, flags) = match direction {
+            Direction::ToServer => (&mut self.ts, self.fns.parse_ts, STREAM_TOSERVER),
+            Direction::ToClient => (&mut self.tc, self.fns.parse_tc, STREAM_TOCLIENT),
+        };
+        buffer.data.extend_from_slice(data);
+        while !buffer.data.is_empty() && buffer.data.len() >= buffer.needed {
+            let flags = if buffer.started {
+                flags
+            } else {
+                buffer.started = true;
+                flags | STREAM_START
+            };
+            let r = unsafe {
+                parse(
+                    std::ptr::null(),
+                    self.state,
+                    std::ptr::null_mut(),
+                    buffer.data.as_ptr(),
+                    buffer.data.len() as u32,
+                    std::ptr::null(),
+                    flags,
+                )
+            };
+            if r.is_err() {
+                return false;
+            }
+            if !r.is_incomplete() {
+                buffer.data.clear();
+                buffer.needed = 0;
+                break;
+            }
+            // Same checks as AppLayerParserParse.
+            assert!(r.consumed as usize <= buffer.data.len());
+            assert!(r.needed > 0);
+            assert!((r.consumed + r.needed) as usize >= buffer.data.len());
+            buffer.data.drain(..r.consumed as usize);
+            buffer.needed = r.needed as usize;
+        }
+        true
+    }
+
+    /// Feed data in one direction, in segments of at most `segment_size`
+    /// bytes.
+    pub fn feed_segments(&mut self, direction: Direction, data: &[u8], segment_size: usize) -> bool {
+        assert!(segment_size > 0);
+        data.chunks(segment_size).all(|chunk| self.feed(direction, chunk))
+    }
+
+    /// Signal a gap of `len` bytes in one direction, with a null input as
+    /// the engine does. The data not consumed yet is lost with it.
+    pub fn gap(&mut self, direction: Direction, len: u32) -> bool {
+        let (buffer, parse, flags) = match direction {
+            Direction::ToServer => (&mut self.ts, self.fns.parse_ts, STREAM_TOSERVER),
+            Direction::ToClient => (&mut self.tc, self.fns.parse_tc, STREAM_TOCLIENT),
+        };
+        buffer.data.clear();
+        buffer.needed = 0;
+        let r = unsafe {
+            parse(
+                std::ptr::null(),
+                self.state,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                len,
+                std::ptr::null(),
+                flags | STREAM_GAP,
+            )
+        };
+        !r.is_err()
+    }
+
+    /// Replay a list of payloads, such as returned by `pcap_payloads`.
+    pub fn replay(&mut self, payloads: &[(Direction, Vec<u8>)]) -> bool {
+        payloads.iter().all(|(direction, data)| self.feed(*direction, data))
+    }
+
+    /// Number of bytes received but not yet passed to the parser.
+    pub fn pending(&self, direction: Direction) -> usize {
+        match direction {
+            Direction::ToServer => self.ts.data.len(),
+            Direction::ToClient => self.tc.data.len(),
+        }
+    }
+
+    pub fn tx_count(&self) -> u64 {
+        unsafe { (self.fns.get_tx_count)(self.state) }
+    }
+
+    /// Get the transaction with the given (0 based) id. `T` has to be the
+    /// transaction type of the parser.
+    pub fn get_tx<T>(&self, tx_id: u64) -> Option<&T> {
+        let tx = unsafe { (self.fns.get_tx)(self.state, tx_id) };
+        unsafe { (tx as *const T).as_ref() }
+    }
+
+    /// Free the transaction with the given (0 based) id, like the engine
+    /// does once it has been inspected and logged.
+    pub fn free_tx(&mut self, tx_id: u64) {
+        unsafe { (self.fns.tx_free)(self.state, tx_id) };
+    }
+
+    /// Get the parser state. `T` has to be the state type of the parser.
+    pub fn state<T>(&self) -> &T {
+        unsafe { &*(self.state as *const T) }
+    }
+
+    pub fn state_mut<T>(&mut self) -> &mut T {
+        unsafe { &mut *(self.state as *mut T) }
+    }
+}
+
+impl Drop for ParserHarness {
+    fn drop(&mut self) {
+        unsafe { (self.fns.state_free)(self.state) };
+    }
+}
+
+fn read_u16(i: &[u8], be: bool) -> u16 {
+    let v = [i[0], i[1]];
+    if be { u16::from_be_bytes(v) } else { u16::from_le_bytes(v) }
+}
+
+fn read_u32(i: &[u8], be: bool) -> u32 {
+    let v = [i[0], i[1], i[2], i[3]];
+    if be { u32::from_be_bytes(v) } else { u32::from_le_bytes(v) }
+}
+
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+
+/// Extract the TCP or UDP payloads to or from `server_port` from a pcap
+/// file, in capture order. Packets going to `server_port` are to server.
+///
+/// There is no TCP reassembly: the capture is expected to be in order and
+/// without retransmissions. Only Ethernet and raw IP link types, and IP
+/// packets without IPv6 extension headers are handled.
+pub fn pcap_payloads(pcap: &[u8], server_port: u16) -> Result<Vec<(Direction, Vec<u8>)>, &'static str> {
+    if pcap.len() < 24 {
+        return Err("pcap too short");
+    }
+    let be = match &pcap[0..4] {
+        [0xd4, 0xc3, 0xb2, 0xa1] | [0x4d, 0x3c, 0xb2, 0xa1] => false,
+        [0xa1, 0xb2, 0xc3, 0xd4] | [0xa1, 0xb2, 0x3c, 0x4d] => true,
+        _ => return Err("not a pcap file"),
+    };
+    let linktype = read_u32(&pcap[20..], be);
+    if linktype != LINKTYPE_ETHERNET && linktype != LINKTYPE_RAW {
+        return Err("unsupported link type");
+    }
+
+    let mut payloads = Vec::new();
+    let mut rem = &pcap[24..];
+    while !rem.is_empty() {
+        if rem.len() < 16 {
+            return Err("truncated record header");
+        }
+        let caplen = read_u32(&rem[8..], be) as usize;
+        if rem.len() < 16 + caplen {
+            return Err("truncated record");
+        }
+        let packet = &rem[16..16 + caplen];
+        rem = &rem[16 + caplen..];
+
+        let ip = if linktype == LINKTYPE_ETHERNET {
+            if packet.len() < 14 {
+                continue;
+            }
+            let ethertype = read_u16(&packet[12..], true);
+            if ethertype != 0x0800 && ethertype != 0x86dd {
+                continue;
+            }
+            &packet[14..]
+        } else {
+            packet
+        };
+        if let Some((src_port, dst_port, payload)) = ip_payload(ip) {
+            if payload.is_empty() {
+                continue;
+            }
+            if dst_port == server_port {
+                payloads.push((Direction::ToServer, payload.to_vec()));
+            } else if src_port == server_port {
+                payloads.push((Direction::ToClient, payload.to_vec()));
+            }
+        }
+    }
+    Ok(payloads)
+}
+
+/// Return the ports and payload of a TCP or UDP packet.
+fn ip_payload(ip: &[u8]) -> Option<(u16, u16, &[u8])> {
+    if ip.is_empty() {
+        return None;
+    }
+    let (proto, l4) = match ip[0] >> 4 {
+        4 => {
+            if ip.len() < 20 {
+                return None;
+            }
+            let hlen = ((ip[0] & 0x0f) as usize) * 4;
+            let total = read_u16(&ip[2..], true) as usize;
+            if hlen < 20 || total < hlen || ip.len() < total {
+                return None;
+            }
+            (ip[9], &ip[hlen..total])
+        }
+        6 => {
+            if ip.len() < 40 {
+                return None;
+            }
+            let plen = read_u16(&ip[4..], true) as usize;
+            if ip.len() < 40 + plen {
+                return None;
+            }
+            (ip[6], &ip[40..40 + plen])
+        }
+        _ => return None,
+    };
+    match proto {
+        6 => {
+            if l4.len() < 20 {
+                return None;
+            }
+            let hlen = ((l4[12] >> 4) as usize) * 4;
+            if hlen < 20 || l4.len() < hlen {
+                return None;
+            }
+            Some((read_u16(l4, true), read_u16(&l4[2..], true), &l4[hlen..]))
+        }
+        17 => {
+            if l4.len() < 8 {
+                return None;
+            }
+            Some((read_u16(l4, true), read_u16(&l4[2..], true), &l4[8..]))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pcap_header(linktype: u8) -> Vec<u8> {
+        let mut v = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        v.extend_from_slice(&[0; 8]);
+        v.extend_from_slice(&[0xff, 0xff, 0, 0, linktype, 0, 0, 0]);
+        v
+    }
+
+    fn pcap_record(packet: &[u8]) -> Vec<u8> {
+        let len = (packet.len() as u32).to_le_bytes();
+        let mut v = vec![0; 8];
+        v.extend_from_slice(&len);
+        v.extend_from_slice(&len);
+        v.extend_from_slice(packet);
+        v
+    }
+
+    fn ipv4_udp(sp: u16, dp: u16, payload: &[u8]) -> Vec<u8> {
+        let total = (28 + payload.len()) as u16;
+        let mut v = vec![0x45, 0];
+        v.extend_from_slice(&total.to_be_bytes());
+        v.extend_from_slice(&[0, 0, 0, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+        v.extend_from_slice(&sp.to_be_bytes());
+        v.extend_from_slice(&dp.to_be_bytes());
+        v.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        v.extend_from_slice(&[0, 0]);
+        v.extend_from_slice(payload);
+        v
+    }
+
+    #[test]
+    fn test_pcap_payloads() {
+        let mut pcap = pcap_header(101);
+        pcap.extend(pcap_record(&ipv4_udp(40000, 53, b"request")));
+        pcap.extend(pcap_record(&ipv4_udp(53, 40000, b"response")));
+        pcap.extend(pcap_record(&ipv4_udp(40001, 123, b"other")));
+        let payloads = pcap_payloads(&pcap, 53).unwrap();
+        assert_eq!(payloads, vec![
+            (Direction::ToServer, b"request".to_vec()),
+            (Direction::ToClient, b"response".to_vec()),
+        ]);
+
+        assert!(pcap_payloads(&pcap[..30], 53).is_err());
+        assert!(pcap_payloads(b"not a pcap file at all..", 53).is_err());
+    }
+}
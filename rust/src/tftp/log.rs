@@ -27,10 +27,35 @@ fn tftp_log_request(tx: &mut TFTPTransaction,
     match tx.opcode {
         1 => jb.set_string("packet", "read")?,
         2 => jb.set_string("packet", "write")?,
+        3 => jb.set_string("packet", "data")?,
+        4 => jb.set_string("packet", "ack")?,
+        6 => jb.set_string("packet", "oack")?,
         _ => jb.set_string("packet", "error")?
     };
-    jb.set_string("file", tx.filename.as_str())?;
-    jb.set_string("mode", tx.mode.as_str())?;
+    if !tx.filename.is_empty() {
+        jb.set_string("file", tx.filename.as_str())?;
+        jb.set_string("mode", tx.mode.as_str())?;
+    }
+    if !tx.options.is_empty() {
+        jb.open_object("options")?;
+        for &(ref name, ref value) in &tx.options {
+            jb.set_string(name, value)?;
+        }
+        jb.close()?;
+    }
+    if let Some(ref transfer) = tx.transfer {
+        jb.open_object("transfer")?;
+        jb.set_string("state", transfer.state.as_str())?;
+        jb.set_uint("blocks", transfer.blocks)?;
+        jb.set_uint("size", transfer.size)?;
+        if let Some(code) = transfer.error_code {
+            jb.set_uint("error_code", code as u64)?;
+        }
+        if let Some(ref message) = transfer.error_message {
+            jb.set_string("error_message", message)?;
+        }
+        jb.close()?;
+    }
     Ok(())
 }
 
@@ -22,31 +22,245 @@ extern crate nom;
 use std::str;
 use std;
 use nom::*;
+use nom::combinator::rest;
+use nom::number::streaming::be_u16;
 
-use crate::applayer::AppLayerTxData;
+use crate::applayer::{cast_mut, AppLayerEvent, AppLayerTxData, DetectStateHolder, FfiType};
+use crate::core::{self, AppProto, Direction, Flow, SuricataFileContext, ALPROTO_UNKNOWN,
+                  STREAM_TOCLIENT, STREAM_TOSERVER};
+use crate::expectation;
+use crate::filecontainer::{Files, FilesState};
 
 const READREQUEST:  u8 = 1;
 const WRITEREQUEST: u8 = 2;
 const DATA:         u8 = 3;
 const ACK:          u8 = 4;
 const ERROR:        u8 = 5;
+const OACK:         u8 = 6;
+
+/// Block size of the transfers not negotiating one, RFC 1350
+const DEFAULT_BLKSIZE: usize = 512;
+
+pub static mut SURICATA_TFTP_FILE_CONFIG: Option<&'static SuricataFileContext> = None;
+
+static mut ALPROTO_TFTP: AppProto = ALPROTO_UNKNOWN;
+
+#[derive(AppLayerEvent)]
+pub enum TFTPEvent {
+    BlockOutOfOrder,
+    TransferAborted,
+}
+
+/// A read or write request, with its RFC 2347 options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TFTPRequest {
+    pub opcode : u8,
+    pub filename : String,
+    pub mode : String,
+    pub options : Vec<(String, String)>,
+}
+
+impl TFTPRequest {
+    pub fn new(opcode : u8, filename : String, mode : String,
+               options : Vec<(String, String)>) -> TFTPRequest {
+        TFTPRequest {
+            opcode : opcode,
+            filename : filename,
+            mode : mode.to_lowercase(),
+            options : options,
+        }
+    }
+    pub fn is_mode_ok(&self) -> bool {
+        match self.mode.as_str() {
+            "netascii" | "mail" | "octet" => true,
+            _ => false
+        }
+    }
+    pub fn is_opcode_ok(&self) -> bool {
+        match self.opcode {
+            READREQUEST | WRITEREQUEST => true,
+            _ => false
+        }
+    }
+}
 
 #[derive(Debug, PartialEq)]
+pub enum TFTPPacket<'a> {
+    Request(TFTPRequest),
+    Data { block: u16, data: &'a [u8] },
+    Ack { block: u16 },
+    Error { code: u16, message: String },
+    OptionAck { options: Vec<(String, String)> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferState {
+    InProgress,
+    Complete,
+    /// blocks are missing
+    Truncated,
+    /// aborted by an ERROR packet
+    Error,
+}
+
+impl TransferState {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            TransferState::InProgress => "in_progress",
+            TransferState::Complete => "complete",
+            TransferState::Truncated => "truncated",
+            TransferState::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum BlockResult {
+    Next,
+    /// short block ending the transfer
+    Last,
+    /// retransmission of the last block
+    Duplicate,
+    OutOfOrder,
+}
+
+/// The DATA/ACK exchange following a request.
+#[derive(Debug)]
+pub struct TFTPTransfer {
+    pub state: TransferState,
+    /// last DATA block received in sequence
+    pub block: u16,
+    /// last block acknowledged
+    pub acked: Option<u16>,
+    pub blksize: usize,
+    pub blocks: u64,
+    pub size: u64,
+    pub error_code: Option<u16>,
+    pub error_message: Option<String>,
+    /// direction of the DATA packets, once a file is opened
+    file_direction: Option<Direction>,
+}
+
+impl TFTPTransfer {
+    fn new() -> TFTPTransfer {
+        TFTPTransfer {
+            state: TransferState::InProgress,
+            block: 0,
+            acked: None,
+            blksize: DEFAULT_BLKSIZE,
+            blocks: 0,
+            size: 0,
+            error_code: None,
+            error_message: None,
+            file_direction: None,
+        }
+    }
+
+    fn set_options(&mut self, options: &[(String, String)]) {
+        for &(ref name, ref value) in options {
+            if name == "blksize" {
+                if let Ok(blksize) = value.parse::<usize>() {
+                    self.blksize = blksize;
+                }
+            }
+        }
+    }
+
+    /// Account a DATA block of `len` bytes. The block numbers roll over
+    /// to 0 or to 1 after 65535, depending on the implementation.
+    fn data(&mut self, block: u16, len: usize) -> BlockResult {
+        let next = self.block.wrapping_add(1);
+        if block == next || (self.block == std::u16::MAX && block == 1) {
+            // servers ignoring the blksize option, or sending a
+            // larger block than we saw negotiated
+            if self.blocks == 0 && len > self.blksize {
+                self.blksize = len;
+            }
+            self.block = block;
+            self.blocks += 1;
+            self.size += len as u64;
+            if len < self.blksize {
+                BlockResult::Last
+            } else {
+                BlockResult::Next
+            }
+        } else if self.blocks > 0 && block == self.block {
+            BlockResult::Duplicate
+        } else {
+            BlockResult::OutOfOrder
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct TFTPTransaction {
     pub opcode : u8,
     pub filename : String,
     pub mode : String,
+    pub options : Vec<(String, String)>,
+    /// DATA/ACK exchange, for the transactions of the transfer flows
+    pub transfer : Option<TFTPTransfer>,
     id: u64,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
     tx_data: AppLayerTxData,
 }
 
+unsafe impl FfiType for TFTPTransaction {}
+
+impl TFTPTransaction {
+    fn new(request: TFTPRequest) -> TFTPTransaction {
+        TFTPTransaction {
+            opcode : request.opcode,
+            filename : request.filename,
+            mode : request.mode,
+            options : request.options,
+            transfer : None,
+            id : 0,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: TFTPEvent) {
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+}
+
+impl Drop for TFTPTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
 pub struct TFTPState {
     pub transactions : Vec<TFTPTransaction>,
     /// tx counter for assigning incrementing id's to tx's
     tx_id: u64,
+    files: Files,
+    /// Request of the transfer on this flow, attached by the request
+    /// flow to its expectation.
+    request: Option<TFTPRequest>,
+    request_checked: bool,
+    /// Transaction of the transfer on this flow
+    transfer_tx: Option<u64>,
 }
 
 impl TFTPState {
+    fn new() -> TFTPState {
+        TFTPState {
+            transactions: Vec::new(),
+            tx_id: 0,
+            files: Files::default(),
+            request: None,
+            request_checked: false,
+            transfer_tx: None,
+        }
+    }
+
     fn get_tx_by_id(&mut self, tx_id: u64) -> Option<&TFTPTransaction> {
         self.transactions.iter().find(|&tx| tx.id == tx_id + 1)
     }
@@ -58,35 +272,186 @@ impl TFTPState {
             let _ = self.transactions.remove(idx);
         }
     }
-}
 
-impl TFTPTransaction {
-    pub fn new(opcode : u8, filename : String, mode : String) -> TFTPTransaction {
-        TFTPTransaction {
-            opcode : opcode,
-            filename : filename,
-            mode : mode.to_lowercase(),
-            id : 0,
-            tx_data: AppLayerTxData::new(),
+    fn new_tx(&mut self, request: TFTPRequest) -> usize {
+        let mut tx = TFTPTransaction::new(request);
+        self.tx_id += 1;
+        tx.id = self.tx_id;
+        self.transactions.push(tx);
+        self.transactions.len() - 1
+    }
+
+    /// Return the index of the transaction of the transfer, creating
+    /// it on the first packet of the transfer.
+    fn transfer_tx(&mut self, opcode: u8) -> usize {
+        if let Some(id) = self.transfer_tx {
+            if let Some(idx) = self.transactions.iter().position(|tx| tx.id == id) {
+                return idx;
+            }
         }
+        // the request is unknown if its flow was not seen
+        let request = match self.request.clone() {
+            Some(request) => request,
+            None => TFTPRequest::new(opcode, String::new(), String::new(), Vec::new()),
+        };
+        let idx = self.new_tx(request);
+        let tx = &mut self.transactions[idx];
+        let mut transfer = TFTPTransfer::new();
+        transfer.set_options(&tx.options);
+        tx.transfer = Some(transfer);
+        self.transfer_tx = Some(tx.id);
+        idx
     }
-    pub fn is_mode_ok(&self) -> bool {
-        match self.mode.as_str() {
-            "netascii" | "mail" | "octet" => true,
-            _ => false
+
+    /// End the transfer on the flow, truncating its file.
+    fn end_transfer(&mut self, idx: usize, state: TransferState) {
+        let tx = &mut self.transactions[idx];
+        if let Some(ref mut transfer) = tx.transfer {
+            if let Some(direction) = transfer.file_direction.take() {
+                self.files.close_truncated(direction, tx.id as u32);
+            }
+            transfer.state = state;
         }
+        self.transfer_tx = None;
     }
-    pub fn is_opcode_ok(&self) -> bool {
-        match self.opcode {
-            READREQUEST | WRITEREQUEST | ACK | DATA | ERROR => true,
-            _ => false
+
+    fn handle_request(&mut self, flow: *const Flow, request: TFTPRequest,
+                      direction: Direction) {
+        if let Some(id) = self.transfer_tx {
+            if let Some(idx) = self.transactions.iter().position(|tx| tx.id == id) {
+                self.end_transfer(idx, TransferState::Truncated);
+            }
         }
+        // the transfer comes from a new port of the server to the port
+        // the client sent the request from
+        if !flow.is_null() && unsafe { ALPROTO_TFTP } != ALPROTO_UNKNOWN {
+            let (sp, dp) = unsafe { (*flow).get_ports() };
+            let client_port = if direction == Direction::ToServer { sp } else { dp };
+            expectation::create(flow, STREAM_TOSERVER | STREAM_TOCLIENT, 0, client_port,
+                                unsafe { ALPROTO_TFTP }, request.clone());
+        }
+        self.new_tx(request);
+    }
+
+    fn handle_data(&mut self, block: u16, data: &[u8], direction: Direction) {
+        let idx = self.transfer_tx(DATA);
+        let tx = &mut self.transactions[idx];
+        let track_id = tx.id as u32;
+        let transfer = match tx.transfer {
+            Some(ref mut transfer) => transfer,
+            None => return,
+        };
+        if transfer.state != TransferState::InProgress {
+            return;
+        }
+        let result = transfer.data(block, data.len());
+        match result {
+            BlockResult::Duplicate => {
+                return;
+            }
+            BlockResult::OutOfOrder => {
+                tx.set_event(TFTPEvent::BlockOutOfOrder);
+                self.end_transfer(idx, TransferState::Truncated);
+                return;
+            }
+            BlockResult::Next | BlockResult::Last => {}
+        }
+        if let Some(cfg) = unsafe { SURICATA_TFTP_FILE_CONFIG } {
+            if transfer.file_direction.is_none() && transfer.blocks == 1 {
+                if self.files.open(cfg, direction, track_id, tx.filename.as_bytes()) {
+                    self.files.set_tx(direction, tx.id - 1);
+                    tx.tx_data.init_files_opened();
+                    transfer.file_direction = Some(direction);
+                }
+            }
+            if transfer.file_direction.is_some() {
+                self.files.append(direction, track_id, data);
+            }
+        }
+        if result == BlockResult::Last {
+            if let Some(direction) = transfer.file_direction.take() {
+                self.files.close(direction, track_id);
+            }
+            transfer.state = TransferState::Complete;
+            self.transfer_tx = None;
+        }
+    }
+
+    fn handle_ack(&mut self, block: u16) {
+        let idx = self.transfer_tx(ACK);
+        if let Some(ref mut transfer) = self.transactions[idx].transfer {
+            transfer.acked = Some(block);
+        }
+    }
+
+    fn handle_error(&mut self, code: u16, message: String) {
+        let idx = self.transfer_tx(ERROR);
+        let tx = &mut self.transactions[idx];
+        if let Some(ref mut transfer) = tx.transfer {
+            transfer.error_code = Some(code);
+            transfer.error_message = Some(message);
+            if transfer.blocks > 0 {
+                tx.set_event(TFTPEvent::TransferAborted);
+            }
+        }
+        self.end_transfer(idx, TransferState::Error);
+    }
+
+    fn handle_option_ack(&mut self, options: Vec<(String, String)>) {
+        let idx = self.transfer_tx(OACK);
+        if let Some(ref mut transfer) = self.transactions[idx].transfer {
+            transfer.set_options(&options);
+        }
+    }
+
+    fn parse(&mut self, flow: *const Flow, input: &[u8], direction: Direction) -> i64 {
+        if !self.request_checked {
+            self.request_checked = true;
+            self.request = unsafe { expectation::take::<TFTPRequest>(flow) };
+        }
+        unsafe { self.files.update_flags(flow, direction) };
+        match parse_tftp_packet(input) {
+            Some(TFTPPacket::Request(request)) => {
+                self.handle_request(flow, request, direction);
+            }
+            Some(TFTPPacket::Data { block, data }) => {
+                self.handle_data(block, data, direction);
+            }
+            Some(TFTPPacket::Ack { block }) => {
+                self.handle_ack(block);
+            }
+            Some(TFTPPacket::Error { code, message }) => {
+                self.handle_error(code, message);
+            }
+            Some(TFTPPacket::OptionAck { options }) => {
+                self.handle_option_ack(options);
+            }
+            None => {
+                return -1;
+            }
+        }
+        0
+    }
+}
+
+impl FilesState for TFTPState {
+    fn files(&mut self) -> &mut Files {
+        &mut self.files
     }
 }
 
+unsafe impl FfiType for TFTPState {}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_tftp_init(context: &'static mut SuricataFileContext,
+                                      alproto: AppProto) {
+    SURICATA_TFTP_FILE_CONFIG = Some(context);
+    ALPROTO_TFTP = alproto;
+}
+
 #[no_mangle]
 pub extern "C" fn rs_tftp_state_alloc() -> *mut std::os::raw::c_void {
-    let state = TFTPState { transactions : Vec::new(), tx_id: 0, };
+    let state = TFTPState::new();
     let boxed = Box::new(state);
     return Box::into_raw(boxed) as *mut _;
 }
@@ -116,35 +481,61 @@ pub extern "C" fn rs_tftp_get_tx_cnt(state: &mut TFTPState) -> u64 {
     return state.tx_id as u64;
 }
 
+/// A request is done once seen, a transfer once it ended.
+#[no_mangle]
+pub unsafe extern "C" fn rs_tftp_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                         _direction: u8)
+                                                         -> std::os::raw::c_int {
+    let tx: &mut TFTPTransaction = cast_mut(tx);
+    match tx.transfer {
+        Some(ref transfer) if transfer.state == TransferState::InProgress => 0,
+        _ => 1,
+    }
+}
+
 named!(getstr<&str>, map_res!(
         take_while!(call!(|c| c != 0)),
         str::from_utf8
     )
 );
 
-fn tftp_request<'a>(slice: &'a [u8]) -> IResult<&[u8], TFTPTransaction> {
+named!(tftp_option<(String, String)>,
+       do_parse!(
+           name: getstr >>
+           tag!([0]) >>
+           value: getstr >>
+           tag!([0]) >>
+           (
+               (name.to_lowercase(), String::from(value))
+           )
+       )
+);
+
+fn tftp_request<'a>(slice: &'a [u8]) -> IResult<&[u8], TFTPRequest> {
        do_parse!(slice,
            tag!([0]) >>
            opcode: take!(1) >>
            filename: getstr >>
            tag!([0]) >>
            mode: getstr >>
+           tag!([0]) >>
+           options: many0!(complete!(tftp_option)) >>
            (
-               TFTPTransaction::new(opcode[0], String::from(filename), String::from(mode))
+               TFTPRequest::new(opcode[0], String::from(filename), String::from(mode), options)
             )
        )
 }
 
-fn parse_tftp_request(input: &[u8]) -> Option<TFTPTransaction> {
+fn parse_tftp_request(input: &[u8]) -> Option<TFTPRequest> {
     match tftp_request(input) {
-        Ok((_, tx)) => {
-            if !tx.is_mode_ok() {
+        Ok((_, request)) => {
+            if !request.is_mode_ok() {
                 return None;
             }
-            if !tx.is_opcode_ok() {
+            if !request.is_opcode_ok() {
                 return None;
             }
-            return Some(tx);
+            return Some(request);
         }
         Err(_) => {
             return None;
@@ -152,22 +543,82 @@ fn parse_tftp_request(input: &[u8]) -> Option<TFTPTransaction> {
     }
 }
 
+named!(tftp_data<TFTPPacket>,
+       do_parse!(
+           block: be_u16 >>
+           data: rest >>
+           ( TFTPPacket::Data { block, data } )
+       )
+);
+
+named!(tftp_ack<TFTPPacket>,
+       do_parse!(
+           block: be_u16 >>
+           ( TFTPPacket::Ack { block } )
+       )
+);
+
+named!(tftp_error<TFTPPacket>,
+       do_parse!(
+           code: be_u16 >>
+           // the message terminator is sometimes missing
+           message: alt!(complete!(terminated!(take_while!(call!(|c| c != 0)), tag!([0])))
+                         | rest) >>
+           (
+               TFTPPacket::Error {
+                   code,
+                   message: String::from_utf8_lossy(message).into_owned(),
+               }
+           )
+       )
+);
+
+named!(tftp_oack<TFTPPacket>,
+       do_parse!(
+           options: many0!(complete!(tftp_option)) >>
+           ( TFTPPacket::OptionAck { options } )
+       )
+);
+
+pub fn parse_tftp_packet(input: &[u8]) -> Option<TFTPPacket> {
+    if input.len() < 2 || input[0] != 0 {
+        return None;
+    }
+    let body = &input[2..];
+    let r = match input[1] {
+        READREQUEST | WRITEREQUEST => {
+            return parse_tftp_request(input).map(TFTPPacket::Request);
+        }
+        DATA => tftp_data(body),
+        ACK => tftp_ack(body),
+        ERROR => tftp_error(body),
+        OACK => tftp_oack(body),
+        _ => {
+            return None;
+        }
+    };
+    match r {
+        Ok((_, packet)) => Some(packet),
+        Err(_) => None,
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn rs_tftp_request(state: &mut TFTPState,
+                                  flow: *const Flow,
                                   input: *const u8,
                                   len: u32) -> i64 {
     let buf = std::slice::from_raw_parts(input, len as usize);
-    match parse_tftp_request(buf) {
-        Some(mut tx) => {
-            state.tx_id += 1;
-            tx.id = state.tx_id;
-            state.transactions.push(tx);
-            0
-        },
-        None => {
-           -1
-        }
-    }
+    state.parse(flow, buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_tftp_response(state: &mut TFTPState,
+                                  flow: *const Flow,
+                                  input: *const u8,
+                                  len: u32) -> i64 {
+    let buf = std::slice::from_raw_parts(input, len as usize);
+    state.parse(flow, buf, Direction::ToClient)
 }
 
 #[no_mangle]
@@ -175,10 +626,45 @@ pub unsafe extern "C" fn rs_tftp_get_tx_data(
     tx: *mut std::os::raw::c_void)
     -> *mut AppLayerTxData
 {
-    let tx = cast_pointer!(tx, TFTPTransaction);
+    let tx: &mut TFTPTransaction = cast_mut(tx);
     return &mut tx.tx_data;
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn rs_tftp_getfiles(state: *mut std::os::raw::c_void,
+                                          direction: u8)
+                                          -> *mut crate::filecontainer::FileContainer {
+    crate::filecontainer::state_get_files::<TFTPState>(state, direction)
+}
+
+export_tx_detect_state!(rs_tftp_get_tx_detect_state, rs_tftp_set_tx_detect_state,
+                        TFTPTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_tftp_get_events(tx: *mut std::os::raw::c_void)
+                                            -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut TFTPTransaction = cast_mut(tx);
+    tx.events
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_tftp_state_get_event_info(
+    event_name: *const std::os::raw::c_char,
+    event_id: *mut std::os::raw::c_int,
+    event_type: *mut core::AppLayerEventType,
+) -> std::os::raw::c_int {
+    TFTPEvent::get_event_info(event_name, event_id, event_type)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_tftp_state_get_event_info_by_id(
+    event_id: std::os::raw::c_int,
+    event_name: *mut *const std::os::raw::c_char,
+    event_type: *mut core::AppLayerEventType,
+) -> i8 {
+    TFTPEvent::get_event_info_by_id(event_id, event_name, event_type)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -206,12 +692,11 @@ mod test {
 
     #[test]
     pub fn test_parse_tftp_read_request_1() {
-        let tx = TFTPTransaction {
+        let tx = TFTPRequest {
             opcode: READREQUEST,
             filename: String::from("rfc1350.txt"),
             mode: String::from("octet"),
-            id: 0,
-            tx_data: AppLayerTxData::new(),
+            options: Vec::new(),
         };
 
         match parse_tftp_request(&READ_REQUEST[..]) {
@@ -226,12 +711,11 @@ mod test {
 
     #[test]
     pub fn test_parse_tftp_write_request_1() {
-        let tx = TFTPTransaction {
+        let tx = TFTPRequest {
             opcode: WRITEREQUEST,
             filename: String::from("rfc1350.txt"),
             mode: String::from("octet"),
-            id: 0,
-            tx_data: AppLayerTxData::new(),
+            options: Vec::new(),
         };
 
         match parse_tftp_request(&WRITE_REQUEST[..]) {
@@ -266,4 +750,72 @@ mod test {
 
         assert_eq!(None, parse_tftp_request(&INVALID_MODE[..]));
     }
+
+    #[test]
+    pub fn test_parse_tftp_request_options() {
+        let buf = b"\x00\x01fw.bin\x00octet\x00BLKSIZE\x001428\x00tsize\x000\x00";
+        let request = parse_tftp_request(buf).unwrap();
+        assert_eq!(request.filename, "fw.bin");
+        assert_eq!(request.options, vec![
+            (String::from("blksize"), String::from("1428")),
+            (String::from("tsize"), String::from("0")),
+        ]);
+    }
+
+    #[test]
+    pub fn test_parse_tftp_packets() {
+        assert_eq!(parse_tftp_packet(b"\x00\x03\x00\x01abc"),
+                   Some(TFTPPacket::Data { block: 1, data: b"abc" }));
+        assert_eq!(parse_tftp_packet(b"\x00\x04\x00\x02"),
+                   Some(TFTPPacket::Ack { block: 2 }));
+        assert_eq!(parse_tftp_packet(b"\x00\x05\x00\x01not found\x00"),
+                   Some(TFTPPacket::Error { code: 1, message: String::from("not found") }));
+        assert_eq!(parse_tftp_packet(b"\x00\x05\x00\x02denied"),
+                   Some(TFTPPacket::Error { code: 2, message: String::from("denied") }));
+        assert_eq!(parse_tftp_packet(b"\x00\x06blksize\x001024\x00"),
+                   Some(TFTPPacket::OptionAck {
+                       options: vec![(String::from("blksize"), String::from("1024"))],
+                   }));
+        assert_eq!(parse_tftp_packet(b"\x00\x03\x00"), None);
+        assert_eq!(parse_tftp_packet(b"\x00\x07\x00\x01"), None);
+    }
+
+    #[test]
+    pub fn test_tftp_transfer_sequencing() {
+        let mut transfer = TFTPTransfer::new();
+        assert_eq!(transfer.data(1, 512), BlockResult::Next);
+        assert_eq!(transfer.data(1, 512), BlockResult::Duplicate);
+        assert_eq!(transfer.data(2, 512), BlockResult::Next);
+        assert_eq!(transfer.data(4, 512), BlockResult::OutOfOrder);
+        assert_eq!(transfer.data(3, 10), BlockResult::Last);
+        assert_eq!(transfer.blocks, 3);
+        assert_eq!(transfer.size, 1034);
+    }
+
+    #[test]
+    pub fn test_tftp_transfer_blksize() {
+        let mut transfer = TFTPTransfer::new();
+        transfer.set_options(&[(String::from("blksize"), String::from("1428"))]);
+        assert_eq!(transfer.data(1, 1428), BlockResult::Next);
+        assert_eq!(transfer.data(2, 512), BlockResult::Last);
+
+        // block size not negotiated by us, adopted from the first block
+        let mut transfer = TFTPTransfer::new();
+        assert_eq!(transfer.data(1, 1024), BlockResult::Next);
+        assert_eq!(transfer.blksize, 1024);
+        assert_eq!(transfer.data(2, 512), BlockResult::Last);
+    }
+
+    #[test]
+    pub fn test_tftp_transfer_rollover() {
+        let mut transfer = TFTPTransfer::new();
+        transfer.block = std::u16::MAX;
+        transfer.blocks = std::u16::MAX as u64;
+        assert_eq!(transfer.data(0, 512), BlockResult::Next);
+
+        let mut transfer = TFTPTransfer::new();
+        transfer.block = std::u16::MAX;
+        transfer.blocks = std::u16::MAX as u64;
+        assert_eq!(transfer.data(1, 512), BlockResult::Next);
+    }
 }
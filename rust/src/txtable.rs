@@ -0,0 +1,391 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Transaction storage shared by the app-layer parsers.
+//!
+//! A parser keeping its transactions in a `TxTable` and implementing
+//! `State` can register the generic `state_get_tx_iterator` as its
+//! transaction iterator, instead of having the engine probe every
+//! transaction id up to the transaction count.
+//!
+//! The number of live transactions of a table can be limited with a
+//! `TxLimit`, configured from `app-layer.protocols.<proto>.max-tx`. Once
+//! the limit is reached the oldest transaction is dropped for each new
+//! one, and the new one gets the `too_many_transactions` event, so a flow
+//! can't make a parser hold an unbounded number of transactions.
+//!
+//! Parsers with unidirectional transactions can register the generic
+//! `state_apply_tx_config` as their `apply_tx_config`: the config is
+//! applied to the transaction and carried over to the next one, usually
+//! the response.
+
+use crate::applayer::{cast_mut, AppLayerGetTxIterTuple, AppLayerTxConfig, AppLayerTxData,
+                      FfiType};
+use crate::conf::conf_get_u64;
+use crate::core::AppProto;
+use crate::introspect::StateGauges;
+use std::os::raw::{c_int, c_void};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Default number of live transactions per flow.
+pub const DEFAULT_MAX_TX: u64 = 256;
+
+/// A transaction stored in a `TxTable`.
+pub trait Transaction {
+    /// The id of the transaction, starting at 1. The engine uses 0 based
+    /// ids, so a transaction is known to it by `id() - 1`.
+    fn id(&self) -> u64;
+
+    fn tx_data(&mut self) -> &mut AppLayerTxData;
+
+    /// Set the `too_many_transactions` event on the transaction. Every
+    /// parser event enum using a `TxTable` has a `TooManyTransactions`
+    /// variant for this.
+    fn set_too_many_transactions(&mut self);
+}
+
+/// The configured transaction limit of a parser, shared by all its
+/// states.
+pub struct TxLimit {
+    max_tx: AtomicU64,
+}
+
+impl TxLimit {
+    /// Create a new limit. A limit of 0 means unlimited.
+    pub const fn new() -> TxLimit {
+        TxLimit {
+            max_tx: AtomicU64::new(0),
+        }
+    }
+
+    /// Set the limit from `app-layer.protocols.<proto>.max-tx`, falling
+    /// back to `default` if not set or invalid.
+    pub fn configure(&self, proto: &str, default: u64) {
+        let key = format!("app-layer.protocols.{}.max-tx", proto);
+        let max_tx = conf_get_u64(&key).unwrap_or(default);
+        self.set(max_tx);
+    }
+
+    pub fn set(&self, max_tx: u64) {
+        self.max_tx.store(max_tx, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.max_tx.load(Ordering::Relaxed)
+    }
+}
+
+/// A parser state keeping its transactions in a `TxTable`.
+pub trait State {
+    type Tx: Transaction;
+
+    fn tx_table(&self) -> &TxTable<Self::Tx>;
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<Self::Tx>;
+}
+
+/// The transactions of a parser state, ordered by id.
+#[derive(Debug)]
+pub struct TxTable<Tx> {
+    txs: Vec<Tx>,
+    /// Id of the last transaction created.
+    tx_id: u64,
+    /// Maximum number of live transactions, 0 for unlimited.
+    max_tx: u64,
+    gauges: Option<&'static StateGauges>,
+    /// Config to apply to the next transaction, with its mode.
+    pending_config: Option<(c_int, AppLayerTxConfig)>,
+}
+
+impl<Tx: Transaction> TxTable<Tx> {
+    pub fn new() -> Self {
+        Self {
+            txs: Vec::new(),
+            tx_id: 0,
+            max_tx: 0,
+            gauges: None,
+            pending_config: None,
+        }
+    }
+
+    /// Create a table holding at most the number of transactions allowed
+    /// by `limit`.
+    pub fn with_limit(limit: &TxLimit) -> Self {
+        let mut table = Self::new();
+        table.max_tx = limit.get();
+        table
+    }
+
+    /// Account the state owning this new table, and the transactions
+    /// added to it, in `gauges`.
+    pub fn with_gauges(mut self, gauges: &'static StateGauges) -> Self {
+        gauges.state_new();
+        self.gauges = Some(gauges);
+        self
+    }
+
+    fn account_free(&self, count: usize) {
+        if let Some(gauges) = self.gauges {
+            gauges.tx_free(count as u64);
+        }
+    }
+
+    /// Allocate the id for a new transaction.
+    pub fn next_id(&mut self) -> u64 {
+        self.tx_id += 1;
+        self.tx_id
+    }
+
+    /// Add a transaction, which must have the id returned by the last
+    /// call to `next_id`. If the table is full the oldest transaction is
+    /// dropped and the `too_many_transactions` event is set on `tx`.
+    pub fn push(&mut self, mut tx: Tx) {
+        debug_assert!(tx.id() == self.tx_id);
+        if self.max_tx > 0 && self.txs.len() as u64 >= self.max_tx {
+            let _ = self.txs.remove(0);
+            self.account_free(1);
+            tx.set_too_many_transactions();
+        }
+        if let Some((mode, config)) = self.pending_config.take() {
+            tx.tx_data().config.apply(mode, &config);
+        }
+        self.txs.push(tx);
+        if let Some(gauges) = self.gauges {
+            gauges.tx_new();
+        }
+    }
+
+    /// Number of transactions created, including the freed ones. This is
+    /// the transaction count reported to the engine.
+    pub fn tx_count(&self) -> u64 {
+        self.tx_id
+    }
+
+    /// Number of transactions currently stored.
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<Tx> {
+        self.txs.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<Tx> {
+        self.txs.iter_mut()
+    }
+
+    /// The most recent transaction.
+    pub fn last_mut(&mut self) -> Option<&mut Tx> {
+        self.txs.last_mut()
+    }
+
+    /// Index of a transaction by its engine (0 based) id. The
+    /// transactions are ordered by id, so this is a binary search: flows
+    /// with many transactions in flight don't make lookups linear.
+    fn index(&self, tx_id: u64) -> Option<usize> {
+        self.txs.binary_search_by_key(&(tx_id + 1), |tx| tx.id()).ok()
+    }
+
+    /// Get a transaction by its engine (0 based) id.
+    pub fn get(&self, tx_id: u64) -> Option<&Tx> {
+        let index = self.index(tx_id)?;
+        Some(&self.txs[index])
+    }
+
+    /// Get a transaction by its engine (0 based) id.
+    pub fn get_mut(&mut self, tx_id: u64) -> Option<&mut Tx> {
+        let index = self.index(tx_id)?;
+        Some(&mut self.txs[index])
+    }
+
+    /// Free a transaction by its engine (0 based) id.
+    pub fn free(&mut self, tx_id: u64) {
+        let index = self.index(tx_id);
+        debug_assert!(index != None);
+        if let Some(index) = index {
+            let _ = self.txs.remove(index);
+            self.account_free(1);
+        }
+    }
+
+    /// Set a config to apply to the next transaction added.
+    pub fn apply_config_next(&mut self, mode: c_int, config: AppLayerTxConfig) {
+        self.pending_config = Some((mode, config));
+    }
+
+    /// Free all transactions. The ids keep increasing.
+    pub fn clear(&mut self) {
+        self.account_free(self.txs.len());
+        self.txs.clear();
+    }
+
+    /// Return the first transaction with an engine id of at least
+    /// `min_tx_id`. `istate` is the iterator state, the index to start
+    /// searching at; it is 0 on the first call of an iteration.
+    pub fn get_iter(&self, min_tx_id: u64, istate: &mut u64) -> AppLayerGetTxIterTuple {
+        let len = self.txs.len();
+        let mut index = *istate as usize;
+        while index < len {
+            let tx = &self.txs[index];
+            if tx.id() < min_tx_id + 1 {
+                index += 1;
+                continue;
+            }
+            *istate = index as u64;
+            return AppLayerGetTxIterTuple::with_values(
+                tx as *const _ as *mut _,
+                tx.id() - 1,
+                len - index > 1,
+            );
+        }
+        AppLayerGetTxIterTuple::not_found()
+    }
+}
+
+impl<Tx: Transaction> Default for TxTable<Tx> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Tx> Drop for TxTable<Tx> {
+    fn drop(&mut self) {
+        if let Some(gauges) = self.gauges {
+            gauges.tx_free(self.txs.len() as u64);
+            gauges.state_free();
+        }
+    }
+}
+
+/// Transaction iterator for any parser state implementing `State`, to
+/// be registered as `get_tx_iterator: Some(state_get_tx_iterator::<S>)`.
+pub unsafe extern "C" fn state_get_tx_iterator<S: State + FfiType>(
+    _ipproto: u8, _alproto: AppProto, state: *mut c_void, min_tx_id: u64, _max_tx_id: u64,
+    istate: &mut u64,
+) -> AppLayerGetTxIterTuple {
+    let state: &mut S = cast_mut(state);
+    state.tx_table().get_iter(min_tx_id, istate)
+}
+
+/// `apply_tx_config` for any parser state implementing `State`, to be
+/// registered as `apply_tx_config: Some(state_apply_tx_config::<S>)`.
+/// The config is applied to `tx` and to the next transaction created.
+pub unsafe extern "C" fn state_apply_tx_config<S: State + FfiType>(
+    state: *mut c_void, tx: *mut c_void, mode: c_int, config: AppLayerTxConfig,
+) where
+    S::Tx: FfiType,
+{
+    let state: &mut S = cast_mut(state);
+    let tx: &mut S::Tx = cast_mut(tx);
+    tx.tx_data().config.apply(mode, &config);
+    state.tx_table_mut().apply_config_next(mode, config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Tx(u64, bool, AppLayerTxData);
+
+    impl Transaction for Tx {
+        fn id(&self) -> u64 {
+            self.0
+        }
+
+        fn tx_data(&mut self) -> &mut AppLayerTxData {
+            &mut self.2
+        }
+
+        fn set_too_many_transactions(&mut self) {
+            self.1 = true;
+        }
+    }
+
+    fn next(table: &TxTable<Tx>, min_tx_id: u64, istate: &mut u64) -> Option<(u64, bool)> {
+        let r = table.get_iter(min_tx_id, istate);
+        if r.tx_ptr.is_null() {
+            None
+        } else {
+            Some((r.tx_id, r.has_next))
+        }
+    }
+
+    #[test]
+    fn test_tx_table_iter() {
+        let mut table = TxTable::new();
+        for _ in 0..4 {
+            let id = table.next_id();
+            table.push(Tx(id, false, AppLayerTxData::new()));
+        }
+        table.free(1);
+        assert_eq!(table.tx_count(), 4);
+        assert_eq!(table.len(), 3);
+        assert!(table.get(1).is_none());
+        assert_eq!(table.get(2).map(|tx| tx.id()), Some(3));
+
+        let mut istate = 0;
+        assert_eq!(next(&table, 0, &mut istate), Some((0, true)));
+        assert_eq!(next(&table, 1, &mut istate), Some((2, true)));
+        assert_eq!(next(&table, 3, &mut istate), Some((3, false)));
+        assert_eq!(next(&table, 4, &mut istate), None);
+
+        // Freeing the returned transaction during the iteration.
+        let mut istate = 0;
+        assert_eq!(next(&table, 0, &mut istate), Some((0, true)));
+        table.free(0);
+        assert_eq!(next(&table, 1, &mut istate), Some((2, true)));
+        table.free(2);
+        assert_eq!(next(&table, 3, &mut istate), Some((3, false)));
+    }
+
+    #[test]
+    fn test_tx_table_limit() {
+        let limit = TxLimit::new();
+        limit.set(2);
+        let mut table = TxTable::with_limit(&limit);
+        for _ in 0..3 {
+            let id = table.next_id();
+            table.push(Tx(id, false, AppLayerTxData::new()));
+        }
+        assert_eq!(table.tx_count(), 3);
+        assert_eq!(table.len(), 2);
+        assert!(table.get(0).is_none());
+        assert!(!table.get(1).unwrap().1);
+        assert!(table.get(2).unwrap().1);
+    }
+
+    #[test]
+    fn test_tx_table_apply_config() {
+        let mut table = TxTable::new();
+        let mut config = AppLayerTxConfig::new();
+        config.set_payload_log(crate::applayer::TX_PAYLOAD_LOG_NONE);
+        table.apply_config_next(1, config);
+        for _ in 0..2 {
+            let id = table.next_id();
+            table.push(Tx(id, false, AppLayerTxData::new()));
+        }
+        let payload_log = |table: &mut TxTable<Tx>, tx_id| {
+            table.get_mut(tx_id).unwrap().2.config.get_payload_log()
+        };
+        assert_eq!(payload_log(&mut table, 0), crate::applayer::TX_PAYLOAD_LOG_NONE);
+        assert_eq!(payload_log(&mut table, 1), crate::applayer::TX_PAYLOAD_LOG_DEFAULT);
+    }
+}
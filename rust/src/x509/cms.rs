@@ -0,0 +1,173 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Certificates carried in a CMS SignedData (RFC 5652), as the ones of
+//! the Kerberos PKINIT pre-authentication data (RFC 4556).
+
+use crate::asn1::ber::{ber_header, BerHeader, BER_CLASS_CONTEXT, BER_CLASS_UNIVERSAL};
+
+const BER_TAG_OID: u32 = 6;
+const BER_TAG_SEQUENCE: u32 = 16;
+
+/// id-signedData, 1.2.840.113549.1.7.2
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+
+struct Element<'a> {
+    header: BerHeader,
+    content: &'a [u8],
+    /// The whole encoding of the element.
+    raw: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    fn is(&self, class: u8, constructed: bool, tag: u32) -> bool {
+        self.header.class == class && self.header.constructed == constructed
+            && self.header.tag == tag
+    }
+}
+
+/// Parse an element of definite length, returning the data after it.
+fn element(i: &[u8]) -> Option<(&[u8], Element<'_>)> {
+    let (rem, header) = ber_header(i).ok()?;
+    let len = header.len?;
+    if len > rem.len() as u64 {
+        return None;
+    }
+    let len = len as usize;
+    let raw_len = i.len() - rem.len() + len;
+    let element = Element {
+        header,
+        content: &rem[..len],
+        raw: &i[..raw_len],
+    };
+    Some((&rem[len..], element))
+}
+
+/// The content of the first element of `i`, if it has the given class,
+/// form and tag.
+fn content(i: &[u8], class: u8, constructed: bool, tag: u32) -> Option<&[u8]> {
+    let (_, e) = element(i)?;
+    if e.is(class, constructed, tag) {
+        Some(e.content)
+    } else {
+        None
+    }
+}
+
+fn signed_data_certificates_opt(content_info: &[u8]) -> Option<Vec<&[u8]>> {
+    // ContentInfo ::= SEQUENCE { contentType, content [0] EXPLICIT ANY }
+    let i = content(content_info, BER_CLASS_UNIVERSAL, true, BER_TAG_SEQUENCE)?;
+    let (rem, oid) = element(i)?;
+    if !oid.is(BER_CLASS_UNIVERSAL, false, BER_TAG_OID) || oid.content != OID_SIGNED_DATA {
+        return None;
+    }
+    let i = content(rem, BER_CLASS_CONTEXT, true, 0)?;
+    // SignedData ::= SEQUENCE { version, digestAlgorithms,
+    //   encapContentInfo, certificates [0] IMPLICIT CertificateSet OPTIONAL,
+    //   ... }
+    let mut i = content(i, BER_CLASS_UNIVERSAL, true, BER_TAG_SEQUENCE)?;
+    for _ in 0..3 {
+        i = element(i)?.0;
+    }
+    let mut certificates = Vec::new();
+    if let Some(mut set) = content(i, BER_CLASS_CONTEXT, true, 0) {
+        while !set.is_empty() {
+            let (rem, e) = element(set)?;
+            // the other choices are tagged
+            if e.is(BER_CLASS_UNIVERSAL, true, BER_TAG_SEQUENCE) {
+                certificates.push(e.raw);
+            }
+            set = rem;
+        }
+    }
+    Some(certificates)
+}
+
+/// Return the DER encoding of the certificates of a ContentInfo holding a
+/// SignedData. Invalid data has none.
+pub fn signed_data_certificates(content_info: &[u8]) -> Vec<&[u8]> {
+    signed_data_certificates_opt(content_info).unwrap_or_default()
+}
+
+/// Return the DER encoding of the certificates of the value of a
+/// PA-PK-AS-REQ or PA-PK-AS-REP pre-authentication data.
+///
+/// The signedAuthPack of the request, and the dhSignedData of the Diffie
+/// Hellman reply, are both the first element of a structure, tagged [0].
+/// The reply encrypting the key with the public key of the client does
+/// not hold a SignedData.
+pub fn pkinit_certificates(padata_value: &[u8]) -> Vec<&[u8]> {
+    let outer = match element(padata_value) {
+        Some((_, e)) => e,
+        None => return Vec::new(),
+    };
+    if !outer.is(BER_CLASS_UNIVERSAL, true, BER_TAG_SEQUENCE)
+        && !outer.is(BER_CLASS_CONTEXT, true, 0)
+    {
+        return Vec::new();
+    }
+    match content(outer.content, BER_CLASS_CONTEXT, false, 0) {
+        Some(content_info) => signed_data_certificates(content_info),
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wrap content in an element with a short length.
+    fn tlv(id: u8, content: &[u8]) -> Vec<u8> {
+        assert!(content.len() < 0x80);
+        let mut v = vec![id, content.len() as u8];
+        v.extend_from_slice(content);
+        v
+    }
+
+    fn signed_data(certificates: &[&[u8]]) -> Vec<u8> {
+        let mut sd = tlv(0x02, &[3]);
+        sd.extend(tlv(0x31, &[]));
+        sd.extend(tlv(0x30, &tlv(0x06, &[0x2b, 0x06, 0x01, 0x05, 0x02, 0x03, 0x01])));
+        let set: Vec<u8> = certificates.iter().flat_map(|c| c.iter().cloned()).collect();
+        sd.extend(tlv(0xa0, &set));
+        sd.extend(tlv(0x31, &[]));
+        let mut ci = tlv(0x06, OID_SIGNED_DATA);
+        ci.extend(tlv(0xa0, &tlv(0x30, &sd)));
+        tlv(0x30, &ci)
+    }
+
+    #[test]
+    fn test_signed_data_certificates() {
+        let cert1 = tlv(0x30, &tlv(0x30, b"first"));
+        let cert2 = tlv(0x30, &tlv(0x30, b"second"));
+        // an attribute certificate, which is skipped
+        let other = tlv(0xa2, b"other");
+        let ci = signed_data(&[&cert1, &other, &cert2]);
+        assert_eq!(signed_data_certificates(&ci), vec![&cert1[..], &cert2[..]]);
+        assert!(signed_data_certificates(&ci[..ci.len() - 1]).is_empty());
+
+        // PA-PK-AS-REQ
+        let req = tlv(0x30, &tlv(0x80, &ci));
+        assert_eq!(pkinit_certificates(&req), vec![&cert1[..], &cert2[..]]);
+        // PA-PK-AS-REP, dhInfo
+        let rep = tlv(0xa0, &tlv(0x80, &ci));
+        assert_eq!(pkinit_certificates(&rep), vec![&cert1[..], &cert2[..]]);
+        // PA-PK-AS-REP, encKeyPack
+        let rep = tlv(0x81, &ci);
+        assert!(pkinit_certificates(&rep).is_empty());
+    }
+}
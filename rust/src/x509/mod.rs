@@ -17,12 +17,25 @@
 
 // written by Pierre Chifflier  <chifflier@wzdftpd.net>
 
+//! X.509 certificates, for the TLS parser in C and the Rust parsers
+//! carrying certificates: DTLS, IKE and Kerberos PKINIT.
+//!
+//! The names, serials, fingerprints and validity dates are formatted
+//! the same way for all of them, as in the TLS logs.
+
+use crate::applayer::{cast_mut, FfiType};
 use crate::common::rust_string_to_c;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use digest::Digest;
 use nom;
+use sha1::Sha1;
 use std;
 use std::os::raw::c_char;
 use x509_parser::{error::X509Error, parse_x509_der, X509Certificate};
 
+pub mod cms;
+
+#[derive(Debug)]
 #[repr(u32)]
 pub enum X509DecodeError {
     Success = 0,
@@ -42,6 +55,123 @@ pub enum X509DecodeError {
 
 pub struct X509(X509Certificate<'static>);
 
+unsafe impl FfiType for X509 {}
+
+/// A certificate, as logged by the parsers carrying it.
+#[derive(Debug, PartialEq)]
+pub struct Certificate {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    /// SHA1 of the DER encoding.
+    pub fingerprint: String,
+    /// Validity period, in seconds since the epoch.
+    pub not_before: i64,
+    pub not_after: i64,
+}
+
+impl Certificate {
+    pub fn parse(der: &[u8]) -> Result<Certificate, X509DecodeError> {
+        let (_, cert) = parse_x509_der(der).map_err(|e| x509_parse_error_to_errcode(&e))?;
+        let tbs = &cert.tbs_certificate;
+        Ok(Certificate {
+            subject: normalize_name(&tbs.subject.to_string()),
+            issuer: normalize_name(&tbs.issuer.to_string()),
+            serial: format_serial(tbs.raw_serial()),
+            fingerprint: format_fingerprint(der),
+            not_before: tbs.validity.not_before.to_timespec().sec,
+            not_after: tbs.validity.not_after.to_timespec().sec,
+        })
+    }
+
+    /// Whether `ts`, in seconds since the epoch, is in the validity period.
+    pub fn is_valid_at(&self, ts: i64) -> bool {
+        self.not_before <= ts && ts <= self.not_after
+    }
+
+    /// Log the certificate as an object of an array, in the fields of the
+    /// TLS logs.
+    pub fn log(&self, js: &mut JsonBuilder) -> Result<(), JsonError> {
+        js.start_object()?;
+        js.set_string("subject", &self.subject)?;
+        js.set_string("issuerdn", &self.issuer)?;
+        js.set_string("serial", &self.serial)?;
+        js.set_string("fingerprint", &self.fingerprint)?;
+        js.set_string("notbefore", &format_time(self.not_before))?;
+        js.set_string("notafter", &format_time(self.not_after))?;
+        js.close()?;
+        Ok(())
+    }
+}
+
+/// Log certificates in an array named `key`, if there are any.
+pub fn log_certificates(
+    js: &mut JsonBuilder, key: &str, certificates: &[Certificate],
+) -> Result<(), JsonError> {
+    if !certificates.is_empty() {
+        js.open_array(key)?;
+        for cert in certificates {
+            cert.log(js)?;
+        }
+        js.close()?;
+    }
+    Ok(())
+}
+
+/// Escape the control characters of a distinguished name, which would
+/// otherwise end up in the logs and the buffers the rules inspect.
+fn normalize_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_control() {
+            out.push_str(&format!("\\x{:02x}", c as u32));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn colon_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+/// Format a serial as uppercase hex octets separated by colons.
+fn format_serial(raw: &[u8]) -> String {
+    colon_hex(raw).to_uppercase()
+}
+
+/// Format the SHA1 of the DER encoding as lowercase hex octets separated
+/// by colons.
+fn format_fingerprint(der: &[u8]) -> String {
+    colon_hex(&Sha1::digest(der))
+}
+
+/// Format seconds since the epoch as an ISO 8601 UTC time, without the
+/// time zone, as `CreateUtcIsoTimeString` does.
+fn format_time(ts: i64) -> String {
+    let mut days = ts / 86400;
+    let mut secs = ts % 86400;
+    if secs < 0 {
+        secs += 86400;
+        days -= 1;
+    }
+    // civil from days, proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year, month, day, secs / 3600, secs / 60 % 60, secs % 60
+    )
+}
+
 /// Attempt to parse a X.509 from input, and return a pointer to the parsed object if successful.
 ///
 /// # Safety
@@ -70,8 +200,8 @@ pub unsafe extern "C" fn rs_x509_get_subject(ptr: *const X509) -> *mut c_char {
     if ptr.is_null() {
         return std::ptr::null_mut();
     }
-    let x509 = cast_pointer! {ptr, X509};
-    let subject = x509.0.tbs_certificate.subject.to_string();
+    let x509: &mut X509 = cast_mut(ptr);
+    let subject = normalize_name(&x509.0.tbs_certificate.subject.to_string());
     rust_string_to_c(subject)
 }
 
@@ -80,8 +210,8 @@ pub unsafe extern "C" fn rs_x509_get_issuer(ptr: *const X509) -> *mut c_char {
     if ptr.is_null() {
         return std::ptr::null_mut();
     }
-    let x509 = cast_pointer! {ptr, X509};
-    let issuer = x509.0.tbs_certificate.issuer.to_string();
+    let x509: &mut X509 = cast_mut(ptr);
+    let issuer = normalize_name(&x509.0.tbs_certificate.issuer.to_string());
     rust_string_to_c(issuer)
 }
 
@@ -90,10 +220,8 @@ pub unsafe extern "C" fn rs_x509_get_serial(ptr: *const X509) -> *mut c_char {
     if ptr.is_null() {
         return std::ptr::null_mut();
     }
-    let x509 = cast_pointer! {ptr, X509};
-    let raw_serial = x509.0.tbs_certificate.raw_serial();
-    let v: Vec<_> = raw_serial.iter().map(|x| format!("{:02X}", x)).collect();
-    let serial = v.join(":");
+    let x509: &mut X509 = cast_mut(ptr);
+    let serial = format_serial(x509.0.tbs_certificate.raw_serial());
     rust_string_to_c(serial)
 }
 
@@ -147,3 +275,24 @@ fn x509_parse_error_to_errcode(e: &nom::Err<X509Error>) -> X509DecodeError {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "1970-01-01T00:00:00");
+        assert_eq!(format_time(951_782_400), "2000-02-29T00:00:00");
+        assert_eq!(format_time(1_609_459_199), "2020-12-31T23:59:59");
+        assert_eq!(format_time(-1), "1969-12-31T23:59:59");
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_serial(&[0x01, 0x5a, 0x3c]), "01:5A:3C");
+        assert_eq!(format_fingerprint(b"abc"),
+                   "a9:99:3e:36:47:06:81:6a:ba:3e:25:71:78:50:c2:6c:9c:d0:d8:9d");
+        assert_eq!(normalize_name("CN=a\nb, O=c"), "CN=a\\x0ab, O=c");
+    }
+}
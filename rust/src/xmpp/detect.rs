@@ -0,0 +1,72 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! Buffer on the JIDs of the stream headers and stanzas: the to and
+//! from addresses and the JID bound by the server. A transaction is
+//! sent in a single direction, the buffer is only set in that
+//! direction.
+
+use super::xmpp::{XmppTransaction, ALPROTO_XMPP};
+use crate::applayer::cast_mut;
+use crate::core::Direction;
+use crate::detect::{
+    helper_keyword_register, helper_multi_buffer_mpm_register, helper_setup_sticky_buffer,
+    DetectKeyword, SIGMATCH_INFO_STICKY_BUFFER, SIGMATCH_NOOPT,
+};
+use std::os::raw::{c_char, c_int, c_void};
+
+static mut G_XMPP_JID_BUFFER_ID: c_int = 0;
+
+unsafe extern "C" fn xmpp_tx_get_jid(
+    tx: *mut c_void, flow_flags: u8, local_id: u32, buf: *mut *const u8, len: *mut u32,
+) -> bool {
+    let tx: &mut XmppTransaction = cast_mut(tx);
+    if tx.direction != Direction::from(flow_flags) {
+        return false;
+    }
+    match tx.jids().nth(local_id as usize) {
+        Some(jid) => {
+            *buf = jid.as_ptr();
+            *len = jid.len() as u32;
+            true
+        }
+        None => false,
+    }
+}
+
+unsafe extern "C" fn xmpp_jid_setup(
+    _de: *mut c_void, s: *mut c_void, _raw: *const c_char,
+) -> c_int {
+    helper_setup_sticky_buffer(s, ALPROTO_XMPP, G_XMPP_JID_BUFFER_ID)
+}
+
+/// Register the XMPP keywords.
+pub unsafe fn register_keywords() {
+    let kw = DetectKeyword {
+        name: "xmpp.jid",
+        desc: "sticky buffer to match on the JIDs of XMPP streams and stanzas",
+        url: "/rules/xmpp-keywords.html#xmpp-jid",
+        flags: SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER,
+        setup: xmpp_jid_setup,
+        free: None,
+        tx_match: None,
+    };
+    if helper_keyword_register(&kw).is_some() {
+        G_XMPP_JID_BUFFER_ID = helper_multi_buffer_mpm_register(
+            "xmpp.jid", "xmpp jid", ALPROTO_XMPP, true, true, 1, xmpp_tx_get_jid);
+    }
+}
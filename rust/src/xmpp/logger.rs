@@ -0,0 +1,79 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::xmpp::XmppTransaction;
+use crate::core::Direction;
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+
+fn log(tx: &XmppTransaction, js: &mut JsonBuilder) -> Result<(), JsonError> {
+    js.open_object("xmpp")?;
+    js.set_string("type", tx.tx_type.to_str())?;
+    let direction = if tx.direction == Direction::ToServer { "to_server" } else { "to_client" };
+    js.set_string("direction", direction)?;
+    if let Some(ref name) = tx.name {
+        js.set_string("name", name)?;
+    }
+    if let Some(ref to) = tx.to {
+        js.set_string("to", to)?;
+    }
+    if let Some(ref from) = tx.from {
+        js.set_string("from", from)?;
+    }
+    if let Some(ref stanza_type) = tx.stanza_type {
+        js.set_string("stanza_type", stanza_type)?;
+    }
+    if let Some(ref id) = tx.stanza_id {
+        js.set_string("id", id)?;
+    }
+    if let Some(ref version) = tx.version {
+        js.set_string("version", version)?;
+    }
+    if tx.starttls_offered {
+        js.open_object("starttls")?;
+        js.set_bool("required", tx.starttls_required)?;
+        js.close()?;
+    }
+    if !tx.mechanisms.is_empty() {
+        js.open_array("mechanisms")?;
+        for mechanism in &tx.mechanisms {
+            js.append_string(mechanism)?;
+        }
+        js.close()?;
+    }
+    if let Some(ref mechanism) = tx.mechanism {
+        js.set_string("mechanism", mechanism)?;
+    }
+    if let Some(ref username) = tx.username {
+        js.set_string("username", username)?;
+    }
+    if let Some(ref jid) = tx.jid {
+        js.set_string("jid", jid)?;
+    }
+    if let Some(success) = tx.success {
+        js.set_bool("success", success)?;
+    }
+    if let Some(ref condition) = tx.condition {
+        js.set_string("condition", condition)?;
+    }
+    js.close()?;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn rs_xmpp_to_json(tx: &mut XmppTransaction, js: &mut JsonBuilder) -> bool {
+    log(tx, js).is_ok()
+}
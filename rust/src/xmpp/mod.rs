@@ -0,0 +1,27 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! XMPP application layer, parser and logger module.
+//!
+//! The transactions are the stream headers, the stream features, the
+//! STARTTLS and SASL negotiations and the stanzas. Parsing stops once
+//! the server accepts STARTTLS: the flow is handed to the TLS parser.
+
+pub mod detect;
+pub mod logger;
+pub mod parser;
+pub mod xmpp;
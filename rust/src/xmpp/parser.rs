@@ -0,0 +1,410 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+//! XMPP stream decoding, RFC 6120.
+//!
+//! An XMPP stream is a single XML document, opened by the stream header
+//! and closed at the end of the session. It is decoded a unit at a time:
+//! the stream header and footer, and the complete elements at the first
+//! level of the stream, the stanzas and negotiation elements. Only the
+//! XML used by XMPP is supported: no DTD, no entity but the predefined
+//! and numeric ones.
+
+/// Maximum depth of the elements in a unit.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Element {
+    /// The name, with its namespace prefix if any.
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    /// The name without its namespace prefix.
+    pub fn local_name(&self) -> &str {
+        local_name(&self.name)
+    }
+
+    /// The value of an attribute, by its local name.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| local_name(n) == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The first child of a local name.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.local_name() == name)
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    match name.rfind(':') {
+        Some(pos) => &name[pos + 1..],
+        None => name,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Unit {
+    /// The XML declaration, or whitespace between the elements.
+    Ignored,
+    /// The stream header, the start tag of the stream element.
+    StreamStart(Element),
+    /// The end tag of the stream element.
+    StreamEnd,
+    Element(Element),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum XmlError {
+    Incomplete,
+    Invalid,
+}
+
+fn is_space(c: u8) -> bool {
+    c == b' ' || c == b'\t' || c == b'\r' || c == b'\n'
+}
+
+fn find(input: &[u8], pos: usize, pattern: &[u8]) -> Result<usize, XmlError> {
+    if pos > input.len() {
+        return Err(XmlError::Incomplete);
+    }
+    input[pos..]
+        .windows(pattern.len())
+        .position(|w| w == pattern)
+        .map(|p| pos + p)
+        .ok_or(XmlError::Incomplete)
+}
+
+fn skip_spaces(input: &[u8], mut pos: usize) -> usize {
+    while pos < input.len() && is_space(input[pos]) {
+        pos += 1;
+    }
+    pos
+}
+
+fn byte_at(input: &[u8], pos: usize) -> Result<u8, XmlError> {
+    input.get(pos).cloned().ok_or(XmlError::Incomplete)
+}
+
+/// Replace the predefined and numeric entities.
+fn unescape(raw: &[u8]) -> String {
+    let text = String::from_utf8_lossy(raw);
+    if !text.contains('&') {
+        return text.into_owned();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rem: &str = &text;
+    while let Some(start) = rem.find('&') {
+        out.push_str(&rem[..start]);
+        rem = &rem[start..];
+        let end = match rem.find(';') {
+            Some(end) if end <= 10 => end,
+            _ => break,
+        };
+        let entity = &rem[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => {
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(std::char::from_u32)
+            }
+            _ if entity.starts_with('#') => {
+                entity[1..].parse().ok().and_then(std::char::from_u32)
+            }
+            _ => None,
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rem = &rem[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rem = &rem[1..];
+            }
+        }
+    }
+    out.push_str(rem);
+    out
+}
+
+/// Parse a start tag from its '<'. Returns the position after the tag,
+/// the element without content, and whether the tag is self closing.
+fn parse_start_tag(input: &[u8], pos: usize) -> Result<(usize, Element, bool), XmlError> {
+    let mut pos = pos + 1;
+    let start = pos;
+    while pos < input.len() && !is_space(input[pos]) && input[pos] != b'/' && input[pos] != b'>'
+    {
+        pos += 1;
+    }
+    if pos >= input.len() {
+        return Err(XmlError::Incomplete);
+    }
+    if pos == start {
+        return Err(XmlError::Invalid);
+    }
+    let mut element = Element {
+        name: String::from_utf8_lossy(&input[start..pos]).into_owned(),
+        ..Default::default()
+    };
+    loop {
+        pos = skip_spaces(input, pos);
+        match byte_at(input, pos)? {
+            b'>' => return Ok((pos + 1, element, false)),
+            b'/' => {
+                if byte_at(input, pos + 1)? != b'>' {
+                    return Err(XmlError::Invalid);
+                }
+                return Ok((pos + 2, element, true));
+            }
+            _ => {}
+        }
+        let name_start = pos;
+        while pos < input.len() && !is_space(input[pos]) && input[pos] != b'=' {
+            if input[pos] == b'>' || input[pos] == b'/' {
+                return Err(XmlError::Invalid);
+            }
+            pos += 1;
+        }
+        let name = String::from_utf8_lossy(&input[name_start..pos]).into_owned();
+        pos = skip_spaces(input, pos);
+        if byte_at(input, pos)? != b'=' {
+            return Err(XmlError::Invalid);
+        }
+        pos = skip_spaces(input, pos + 1);
+        let quote = byte_at(input, pos)?;
+        if quote != b'\'' && quote != b'"' {
+            return Err(XmlError::Invalid);
+        }
+        let end = find(input, pos + 1, &[quote])?;
+        element.attrs.push((name, unescape(&input[pos + 1..end])));
+        pos = end + 1;
+    }
+}
+
+/// Parse the content and end tag of an element, from the end of its
+/// start tag.
+fn parse_content(input: &[u8], mut pos: usize, element: &mut Element, depth: usize)
+                 -> Result<usize, XmlError> {
+    if depth > MAX_DEPTH {
+        return Err(XmlError::Invalid);
+    }
+    loop {
+        let lt = find(input, pos, b"<")?;
+        element.text.push_str(&unescape(&input[pos..lt]));
+        let rem = &input[lt..];
+        // enough to tell an end tag, a comment or a CDATA section from a
+        // start tag
+        if rem.len() < 2 || (rem[1] == b'!' && rem.len() < 9) {
+            return Err(XmlError::Incomplete);
+        }
+        if rem[1] == b'/' {
+            let end = find(input, lt, b">")?;
+            let name = &input[lt + 2..end];
+            let name = &name[..name.iter().position(|&c| is_space(c)).unwrap_or(name.len())];
+            if name != element.name.as_bytes() {
+                return Err(XmlError::Invalid);
+            }
+            return Ok(end + 1);
+        }
+        if rem.starts_with(b"<!--") {
+            pos = find(input, lt + 4, b"-->")? + 3;
+        } else if rem.starts_with(b"<![CDATA[") {
+            let end = find(input, lt + 9, b"]]>")?;
+            element.text.push_str(&String::from_utf8_lossy(&input[lt + 9..end]));
+            pos = end + 3;
+        } else {
+            let (end, mut child, empty) = parse_start_tag(input, lt)?;
+            pos = if empty { end } else { parse_content(input, end, &mut child, depth + 1)? };
+            element.children.push(child);
+        }
+    }
+}
+
+/// Parse the next unit of a stream. Returns the number of bytes of the
+/// unit and the unit.
+pub fn parse_unit(input: &[u8]) -> Result<(usize, Unit), XmlError> {
+    let first = match input.first() {
+        Some(&c) => c,
+        None => return Err(XmlError::Incomplete),
+    };
+    if is_space(first) {
+        return Ok((skip_spaces(input, 0), Unit::Ignored));
+    }
+    if first != b'<' {
+        return Err(XmlError::Invalid);
+    }
+    match byte_at(input, 1)? {
+        b'?' => {
+            let end = find(input, 2, b"?>")?;
+            Ok((end + 2, Unit::Ignored))
+        }
+        b'/' => {
+            let end = find(input, 2, b">")?;
+            Ok((end + 1, Unit::StreamEnd))
+        }
+        _ => {
+            let (end, mut element, empty) = parse_start_tag(input, 0)?;
+            if element.local_name() == "stream" {
+                return Ok((end, Unit::StreamStart(element)));
+            }
+            let end = if empty { end } else { parse_content(input, end, &mut element, 1)? };
+            Ok((end, Unit::Element(element)))
+        }
+    }
+}
+
+/// Parse the start of a stream up to the stream header, skipping the
+/// XML declaration and whitespace. Unlike `parse_unit`, another first
+/// element is invalid as soon as its start tag is seen.
+pub fn parse_stream_header(input: &[u8]) -> Result<Element, XmlError> {
+    let mut pos = 0;
+    loop {
+        match byte_at(input, pos)? {
+            c if is_space(c) => pos = skip_spaces(input, pos),
+            b'<' if byte_at(input, pos + 1)? == b'?' => {
+                pos = find(input, pos + 2, b"?>")? + 2;
+            }
+            b'<' => {
+                let (_, element, _) = parse_start_tag(input, pos)?;
+                if element.local_name() != "stream" {
+                    return Err(XmlError::Invalid);
+                }
+                return Ok(element);
+            }
+            _ => return Err(XmlError::Invalid),
+        }
+    }
+}
+
+fn base64_value(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decode the base64 of the SASL data, padding optional.
+pub fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for &c in input.trim_end_matches('=').as_bytes() {
+        if is_space(c) {
+            continue;
+        }
+        acc = (acc << 6) | base64_value(c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// The authentication identity of the initial response of the PLAIN
+/// mechanism: authzid NUL authcid NUL passwd.
+pub fn plain_authcid(response: &str) -> Option<String> {
+    let data = decode_base64(response.trim())?;
+    let mut fields = data.split(|&c| c == 0);
+    let _authzid = fields.next()?;
+    let authcid = fields.next()?;
+    fields.next()?;
+    Some(String::from_utf8_lossy(authcid).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stream() {
+        let buf = b"<?xml version='1.0'?><stream:stream to='example.com' \
+                    xmlns='jabber:client' xmlns:stream='http://etherx.jabber.org/streams' \
+                    version='1.0'>";
+        let (n, unit) = parse_unit(buf).unwrap();
+        assert_eq!(unit, Unit::Ignored);
+        let (m, unit) = parse_unit(&buf[n..]).unwrap();
+        assert_eq!(n + m, buf.len());
+        match unit {
+            Unit::StreamStart(header) => {
+                assert_eq!(header.attr("to"), Some("example.com"));
+                assert_eq!(header.attr("version"), Some("1.0"));
+            }
+            _ => panic!("unexpected {:?}", unit),
+        }
+        assert_eq!(parse_unit(b"</stream:stream>"), Ok((16, Unit::StreamEnd)));
+    }
+
+    #[test]
+    fn test_parse_element() {
+        let buf = b"<stream:features><starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'>\
+                    <required/></starttls><mechanisms xmlns='urn:ietf:params:xml:ns:xmpp-sasl'>\
+                    <mechanism>SCRAM-SHA-1</mechanism><mechanism>PLAIN</mechanism>\
+                    </mechanisms><!-- c --></stream:features><iq";
+        let (n, unit) = parse_unit(buf).unwrap();
+        assert_eq!(&buf[n..], b"<iq");
+        let features = match unit {
+            Unit::Element(e) => e,
+            _ => panic!("unexpected {:?}", unit),
+        };
+        assert_eq!(features.local_name(), "features");
+        assert!(features.child("starttls").unwrap().child("required").is_some());
+        let mechanisms: Vec<&str> = features
+            .child("mechanisms")
+            .unwrap()
+            .children
+            .iter()
+            .map(|m| m.text.as_str())
+            .collect();
+        assert_eq!(mechanisms, vec!["SCRAM-SHA-1", "PLAIN"]);
+
+        for i in 1..n {
+            assert_eq!(parse_unit(&buf[..i]), Err(XmlError::Incomplete));
+        }
+
+        let (_, unit) = parse_unit(b"<body>a &lt;b&gt; &#x41;<![CDATA[<c>]]></body>").unwrap();
+        match unit {
+            Unit::Element(e) => assert_eq!(e.text, "a <b> A<c>"),
+            _ => panic!("unexpected {:?}", unit),
+        }
+        assert_eq!(parse_unit(b"<a></b>"), Err(XmlError::Invalid));
+        assert_eq!(parse_unit(b"hello"), Err(XmlError::Invalid));
+    }
+
+    #[test]
+    fn test_plain_authcid() {
+        // "\0juliet\0r0m30"
+        assert_eq!(plain_authcid("AGp1bGlldAByMG0zMA=="), Some("juliet".to_string()));
+        assert_eq!(plain_authcid("="), None);
+        assert_eq!(plain_authcid("!!"), None);
+    }
+}
@@ -0,0 +1,708 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+use super::parser::*;
+use crate::applayer::{self, *};
+use crate::core::{self, AppProto, Direction, Flow, ALPROTO_UNKNOWN, IPPROTO_TCP};
+use crate::introspect::{self, StateGauges};
+use crate::jsonbuilder::{JsonBuilder, JsonError};
+use crate::txtable::{self, Transaction, TxLimit, TxTable};
+use std::ffi::CString;
+
+declare_counters!(xmpp_stats, "app_layer.xmpp", {
+    starttls,
+    auth,
+    auth_failures,
+    stanzas,
+    anomalies,
+});
+
+#[derive(AppLayerEvent)]
+pub enum XmppEvent {
+    MalformedData,
+    ElementTooLarge,
+    PlainAuthWithoutTls,
+    UnsolicitedResponse,
+    TooManyTransactions,
+}
+
+/// Maximum number of live transactions per flow
+static XMPP_TX_LIMIT: TxLimit = TxLimit::new();
+
+/// Live states and transactions
+static XMPP_GAUGES: StateGauges = StateGauges::new();
+
+/// Maximum size of a stanza or negotiation element. Servers commonly
+/// limit stanzas to 64 KiB or less.
+const XMPP_MAX_ELEMENT_LEN: usize = 65536;
+
+/// Number of bytes the probing parser looks into for the stream header.
+const XMPP_PROBE_LEN: usize = 1024;
+
+pub static mut ALPROTO_XMPP: AppProto = ALPROTO_UNKNOWN;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum XmppTxType {
+    /// A stream header.
+    Stream,
+    /// The features announced by the server.
+    Features,
+    /// A STARTTLS request and its outcome.
+    StartTls,
+    /// A SASL authentication and its outcome.
+    Auth,
+    /// A message, presence or iq stanza.
+    Stanza,
+    /// A stream error.
+    Error,
+}
+
+impl XmppTxType {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            XmppTxType::Stream => "stream",
+            XmppTxType::Features => "features",
+            XmppTxType::StartTls => "starttls",
+            XmppTxType::Auth => "auth",
+            XmppTxType::Stanza => "stanza",
+            XmppTxType::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct XmppTransaction {
+    id: u64,
+    pub tx_type: XmppTxType,
+    pub direction: Direction,
+    /// The local name of a stanza.
+    pub name: Option<String>,
+    pub to: Option<String>,
+    pub from: Option<String>,
+    /// The type attribute of a stanza.
+    pub stanza_type: Option<String>,
+    /// The id of a stream or stanza.
+    pub stanza_id: Option<String>,
+    pub version: Option<String>,
+    /// The SASL mechanisms offered, for the features.
+    pub mechanisms: Vec<String>,
+    /// Whether STARTTLS is offered and required, for the features.
+    pub starttls_offered: bool,
+    pub starttls_required: bool,
+    /// The SASL mechanism of an authentication.
+    pub mechanism: Option<String>,
+    /// The authentication identity of a PLAIN authentication.
+    pub username: Option<String>,
+    /// The JID the server bound the resource of the client to.
+    pub jid: Option<String>,
+    /// The outcome of STARTTLS or of an authentication.
+    pub success: Option<bool>,
+    /// The condition of a failure or stream error.
+    pub condition: Option<String>,
+    pub complete: bool,
+    de_state: DetectStateHolder,
+    events: *mut core::AppLayerDecoderEvents,
+    tx_data: applayer::AppLayerTxData,
+}
+
+unsafe impl FfiType for XmppTransaction {}
+
+impl XmppTransaction {
+    fn new(id: u64, tx_type: XmppTxType, direction: Direction) -> XmppTransaction {
+        XmppTransaction {
+            id,
+            tx_type,
+            direction,
+            name: None,
+            to: None,
+            from: None,
+            stanza_type: None,
+            stanza_id: None,
+            version: None,
+            mechanisms: Vec::new(),
+            starttls_offered: false,
+            starttls_required: false,
+            mechanism: None,
+            username: None,
+            jid: None,
+            success: None,
+            condition: None,
+            complete: true,
+            de_state: DetectStateHolder::new(),
+            events: std::ptr::null_mut(),
+            tx_data: applayer::AppLayerTxData::new(),
+        }
+    }
+
+    fn set_event(&mut self, event: XmppEvent) {
+        stats_incr!(xmpp_stats::anomalies);
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, event as u8);
+    }
+
+    /// The JIDs of the transaction, for the xmpp.jid buffer.
+    pub fn jids(&self) -> impl Iterator<Item = &String> {
+        self.to.iter().chain(self.from.iter()).chain(self.jid.iter())
+    }
+}
+
+impl Drop for XmppTransaction {
+    fn drop(&mut self) {
+        if !self.events.is_null() {
+            core::sc_app_layer_decoder_events_free_events(&mut self.events);
+        }
+    }
+}
+
+impl Transaction for XmppTransaction {
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn tx_data(&mut self) -> &mut applayer::AppLayerTxData {
+        &mut self.tx_data
+    }
+
+    fn set_too_many_transactions(&mut self) {
+        let ev = XmppEvent::TooManyTransactions as u8;
+        core::sc_app_layer_decoder_events_set_event_raw(&mut self.events, ev);
+    }
+}
+
+fn attr_string(element: &Element, name: &str) -> Option<String> {
+    element.attr(name).map(|v| v.to_string())
+}
+
+/// The name of the first child, the condition of a failure or error.
+fn condition(element: &Element) -> Option<String> {
+    element
+        .children
+        .iter()
+        .find(|c| c.local_name() != "text")
+        .map(|c| c.local_name().to_string())
+}
+
+pub struct XmppState {
+    transactions: TxTable<XmppTransaction>,
+    /// Set once the server accepts STARTTLS: the rest of the flow is
+    /// TLS.
+    tls: bool,
+    authenticated: bool,
+}
+
+unsafe impl FfiType for XmppState {}
+
+impl txtable::State for XmppState {
+    type Tx = XmppTransaction;
+
+    fn tx_table(&self) -> &TxTable<XmppTransaction> {
+        &self.transactions
+    }
+
+    fn tx_table_mut(&mut self) -> &mut TxTable<XmppTransaction> {
+        &mut self.transactions
+    }
+}
+
+impl XmppState {
+    pub fn new() -> XmppState {
+        XmppState {
+            transactions: TxTable::with_limit(&XMPP_TX_LIMIT).with_gauges(&XMPP_GAUGES),
+            tls: false,
+            authenticated: false,
+        }
+    }
+
+    fn free_tx(&mut self, tx_id: u64) {
+        self.transactions.free(tx_id);
+    }
+
+    fn new_tx(&mut self, tx_type: XmppTxType, direction: Direction) -> XmppTransaction {
+        XmppTransaction::new(self.transactions.next_id(), tx_type, direction)
+    }
+
+    fn set_event(&mut self, direction: Direction, event: XmppEvent) {
+        let mut tx = self.new_tx(XmppTxType::Error, direction);
+        tx.set_event(event);
+        self.transactions.push(tx);
+    }
+
+    fn handle_stream_start(&mut self, header: Element, direction: Direction) {
+        let mut tx = self.new_tx(XmppTxType::Stream, direction);
+        tx.to = attr_string(&header, "to");
+        tx.from = attr_string(&header, "from");
+        tx.stanza_id = attr_string(&header, "id");
+        tx.version = attr_string(&header, "version");
+        self.transactions.push(tx);
+    }
+
+    fn handle_request(&mut self, element: Element) {
+        match element.local_name() {
+            "starttls" => {
+                stats_incr!(xmpp_stats::starttls);
+                let mut tx = self.new_tx(XmppTxType::StartTls, Direction::ToServer);
+                tx.complete = false;
+                self.transactions.push(tx);
+            }
+            "auth" => {
+                stats_incr!(xmpp_stats::auth);
+                let mut tx = self.new_tx(XmppTxType::Auth, Direction::ToServer);
+                tx.complete = false;
+                tx.mechanism = attr_string(&element, "mechanism");
+                if tx.mechanism.as_ref().map(|m| m.as_str()) == Some("PLAIN") {
+                    tx.username = plain_authcid(&element.text);
+                    if !self.tls {
+                        tx.set_event(XmppEvent::PlainAuthWithoutTls);
+                    }
+                }
+                self.transactions.push(tx);
+            }
+            "message" | "presence" | "iq" => self.handle_stanza(element, Direction::ToServer),
+            // the SASL responses to the challenges of the server
+            _ => {}
+        }
+    }
+
+    fn handle_stanza(&mut self, element: Element, direction: Direction) {
+        stats_incr!(xmpp_stats::stanzas);
+        let mut tx = self.new_tx(XmppTxType::Stanza, direction);
+        tx.name = Some(element.local_name().to_string());
+        tx.to = attr_string(&element, "to");
+        tx.from = attr_string(&element, "from");
+        tx.stanza_type = attr_string(&element, "type");
+        tx.stanza_id = attr_string(&element, "id");
+        if let Some(jid) = element.child("bind").and_then(|b| b.child("jid")) {
+            tx.jid = Some(jid.text.trim().to_string());
+        }
+        self.transactions.push(tx);
+    }
+
+    /// Complete the pending STARTTLS or authentication transaction with
+    /// the outcome sent by the server.
+    fn handle_outcome(&mut self, element: &Element, success: bool) {
+        let tx = match self
+            .transactions
+            .iter_mut()
+            .rev()
+            .find(|tx| !tx.complete)
+        {
+            Some(tx) => tx,
+            None => {
+                self.set_event(Direction::ToClient, XmppEvent::UnsolicitedResponse);
+                return;
+            }
+        };
+        tx.complete = true;
+        tx.success = Some(success);
+        if !success {
+            tx.condition = condition(element);
+        }
+        match tx.tx_type {
+            XmppTxType::StartTls if success => {
+                self.tls = true;
+            }
+            XmppTxType::Auth if success => {
+                self.authenticated = true;
+            }
+            XmppTxType::Auth => {
+                stats_incr!(xmpp_stats::auth_failures);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_response(&mut self, element: Element) {
+        match element.local_name() {
+            "features" => {
+                let mut tx = self.new_tx(XmppTxType::Features, Direction::ToClient);
+                if let Some(starttls) = element.child("starttls") {
+                    tx.starttls_offered = true;
+                    tx.starttls_required = starttls.child("required").is_some();
+                }
+                if let Some(mechanisms) = element.child("mechanisms") {
+                    tx.mechanisms = mechanisms
+                        .children
+                        .iter()
+                        .filter(|m| m.local_name() == "mechanism")
+                        .map(|m| m.text.trim().to_string())
+                        .collect();
+                }
+                self.transactions.push(tx);
+            }
+            "proceed" | "success" => self.handle_outcome(&element, true),
+            "failure" => self.handle_outcome(&element, false),
+            "message" | "presence" | "iq" => self.handle_stanza(element, Direction::ToClient),
+            "error" => {
+                let mut tx = self.new_tx(XmppTxType::Error, Direction::ToClient);
+                tx.condition = condition(&element);
+                self.transactions.push(tx);
+            }
+            _ => {}
+        }
+    }
+
+    fn parse(&mut self, flow: *const Flow, input: &[u8], direction: Direction) -> AppLayerResult {
+        if self.tls {
+            return AppLayerResult::ok();
+        }
+        let mut current = input;
+        while !current.is_empty() && !self.tls {
+            match parse_unit(current) {
+                Ok((len, unit)) => {
+                    match unit {
+                        Unit::StreamStart(header) => self.handle_stream_start(header, direction),
+                        Unit::Element(element) => match direction {
+                            Direction::ToServer => self.handle_request(element),
+                            Direction::ToClient => self.handle_response(element),
+                        },
+                        Unit::StreamEnd | Unit::Ignored => {}
+                    }
+                    current = &current[len..];
+                }
+                Err(XmlError::Incomplete) => {
+                    if current.len() >= XMPP_MAX_ELEMENT_LEN {
+                        self.set_event(direction, XmppEvent::ElementTooLarge);
+                        return AppLayerResult::err();
+                    }
+                    let consumed = input.len() - current.len();
+                    return AppLayerResult::incomplete_from(input.len(), consumed,
+                                                           current.len() + 1);
+                }
+                Err(XmlError::Invalid) => {
+                    self.set_event(direction, XmppEvent::MalformedData);
+                    return AppLayerResult::err();
+                }
+            }
+        }
+        if self.tls && !flow.is_null() {
+            // the TLS parser takes over after the proceed of the server
+            core::sc_app_layer_request_protocol_tls_upgrade(flow);
+        }
+        AppLayerResult::ok()
+    }
+}
+
+/// Probe for a stream header, after an optional XML declaration.
+fn xmpp_probe(input: &[u8], direction: Direction) -> ProbeResult {
+    match parse_stream_header(input) {
+        Ok(header) => {
+            let xmlns = header.attr("xmlns").unwrap_or("");
+            if !xmlns.starts_with("jabber:") {
+                return ProbeResult::Failed;
+            }
+            // the stream header of the server has an id
+            let actual = if header.attr("id").is_some() {
+                Direction::ToClient
+            } else {
+                Direction::ToServer
+            };
+            ProbeResult::with_direction(unsafe { ALPROTO_XMPP }, direction, actual)
+        }
+        Err(XmlError::Incomplete) if input.len() < XMPP_PROBE_LEN => ProbeResult::Unknown,
+        _ => ProbeResult::Failed,
+    }
+}
+
+export_probe!(rs_xmpp_probing_parser, xmpp_probe);
+
+#[no_mangle]
+pub extern "C" fn rs_xmpp_state_new(_orig_state: *mut std::os::raw::c_void,
+                                    _orig_proto: AppProto) -> *mut std::os::raw::c_void {
+    let state = XmppState::new();
+    Box::into_raw(Box::new(state)) as *mut _
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_xmpp_state_free(state: *mut std::os::raw::c_void) {
+    std::mem::drop(Box::from_raw(state as *mut XmppState));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_xmpp_state_tx_free(state: *mut std::os::raw::c_void, tx_id: u64) {
+    let state: &mut XmppState = cast_mut(state);
+    state.free_tx(tx_id);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_xmpp_parse_request(flow: *const Flow,
+                                               state: *mut std::os::raw::c_void,
+                                               pstate: *mut std::os::raw::c_void,
+                                               input: *const u8,
+                                               input_len: u32,
+                                               _data: *const std::os::raw::c_void,
+                                               _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToServer) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut XmppState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(flow, buf, Direction::ToServer)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_xmpp_parse_response(flow: *const Flow,
+                                                state: *mut std::os::raw::c_void,
+                                                pstate: *mut std::os::raw::c_void,
+                                                input: *const u8,
+                                                input_len: u32,
+                                                _data: *const std::os::raw::c_void,
+                                                _flags: u8) -> AppLayerResult {
+    if input.is_null() && parser_state_is_eof(pstate, Direction::ToClient) {
+        return AppLayerResult::ok();
+    }
+    let state: &mut XmppState = cast_mut(state);
+    let buf = build_slice!(input, input_len as usize);
+    state.parse(flow, buf, Direction::ToClient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_xmpp_state_get_tx(state: *mut std::os::raw::c_void,
+                                              tx_id: u64) -> *mut std::os::raw::c_void {
+    let state: &mut XmppState = cast_mut(state);
+    match state.transactions.get(tx_id) {
+        Some(tx) => tx as *const _ as *mut _,
+        None => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_xmpp_state_get_tx_count(state: *mut std::os::raw::c_void) -> u64 {
+    let state: &mut XmppState = cast_mut(state);
+    state.transactions.tx_count()
+}
+
+/// STARTTLS and authentications are complete with the outcome sent by
+/// the server, the other transactions once parsed.
+#[no_mangle]
+pub unsafe extern "C" fn rs_xmpp_tx_get_alstate_progress(tx: *mut std::os::raw::c_void,
+                                                         direction: u8) -> std::os::raw::c_int {
+    let tx: &mut XmppTransaction = cast_mut(tx);
+    if direction & core::STREAM_TOSERVER != 0 || tx.complete {
+        1
+    } else {
+        0
+    }
+}
+
+export_tx_detect_state!(rs_xmpp_state_get_tx_detect_state,
+                        rs_xmpp_state_set_tx_detect_state, XmppTransaction);
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_xmpp_state_get_events(tx: *mut std::os::raw::c_void)
+                                                  -> *mut core::AppLayerDecoderEvents {
+    let tx: &mut XmppTransaction = cast_mut(tx);
+    tx.events
+}
+
+export_tx_data_get!(rs_xmpp_get_tx_data, XmppTransaction);
+
+fn xmpp_introspect(js: &mut JsonBuilder) -> Result<(), JsonError> {
+    XMPP_GAUGES.log(js)?;
+    js.set_uint("events", xmpp_stats::anomalies::COUNTER.get())?;
+    Ok(())
+}
+
+fn register_pattern_probe() -> i8 {
+    for pattern in &[&b"jabber:client\0"[..], &b"jabber:server\0"[..]] {
+        unsafe {
+            if AppLayerProtoDetectPMRegisterPatternCSwPP(IPPROTO_TCP as u8, ALPROTO_XMPP,
+                                                         pattern.as_ptr() as *const std::os::raw::c_char,
+                                                         XMPP_PROBE_LEN as u16, 0,
+                                                         core::STREAM_TOSERVER,
+                                                         rs_xmpp_probing_parser, 0, 0) < 0 {
+                SCLogDebug!("TOSERVER => AppLayerProtoDetectPMRegisterPatternCSwPP FAILED");
+                return -1;
+            }
+        }
+    }
+    0
+}
+
+const PARSER_NAME: &[u8] = b"xmpp\0";
+
+#[no_mangle]
+pub unsafe extern "C" fn rs_xmpp_register_parser() {
+    xmpp_stats::register();
+    XMPP_TX_LIMIT.configure("xmpp", txtable::DEFAULT_MAX_TX);
+    introspect::register("xmpp", xmpp_introspect);
+    let default_port = CString::new("[5222,5269]").unwrap();
+    let parser = RustParser {
+        name: PARSER_NAME.as_ptr() as *const std::os::raw::c_char,
+        default_port: default_port.as_ptr(),
+        ipproto: IPPROTO_TCP,
+        probe_ts: Some(rs_xmpp_probing_parser),
+        probe_tc: Some(rs_xmpp_probing_parser),
+        min_depth: 0,
+        max_depth: XMPP_PROBE_LEN as u16,
+        state_new: rs_xmpp_state_new,
+        state_free: rs_xmpp_state_free,
+        tx_free: rs_xmpp_state_tx_free,
+        parse_ts: rs_xmpp_parse_request,
+        parse_tc: rs_xmpp_parse_response,
+        get_tx_count: rs_xmpp_state_get_tx_count,
+        get_tx: rs_xmpp_state_get_tx,
+        tx_comp_st_ts: 1,
+        tx_comp_st_tc: 1,
+        tx_get_progress: rs_xmpp_tx_get_alstate_progress,
+        get_de_state: rs_xmpp_state_get_tx_detect_state,
+        set_de_state: rs_xmpp_state_set_tx_detect_state,
+        get_events: Some(rs_xmpp_state_get_events),
+        get_eventinfo: Some(XmppEvent::get_event_info),
+        get_eventinfo_byid: Some(XmppEvent::get_event_info_by_id),
+        localstorage_new: None,
+        localstorage_free: None,
+        get_files: None,
+        get_tx_iterator: Some(txtable::state_get_tx_iterator::<XmppState>),
+        get_tx_data: rs_xmpp_get_tx_data,
+        apply_tx_config: Some(txtable::state_apply_tx_config::<XmppState>),
+        flags: 0,
+        truncate: None,
+        state_purge: None,
+    };
+
+    let ip_proto_str = CString::new("tcp").unwrap();
+    if AppLayerProtoDetectConfProtoDetectionEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+        let alproto = AppLayerRegisterProtocolDetection(&parser, 1);
+        ALPROTO_XMPP = alproto;
+        if register_pattern_probe() < 0 {
+            return;
+        }
+        if AppLayerParserConfParserEnabled(ip_proto_str.as_ptr(), parser.name) != 0 {
+            let _ = AppLayerRegisterParser(&parser, alproto);
+        }
+        SCLogDebug!("Rust xmpp parser registered.");
+    } else {
+        SCLogDebug!("Protocol detector and parser disabled for XMPP.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ParserFns, ParserHarness};
+
+    fn harness() -> ParserHarness {
+        ParserHarness::new(ParserFns {
+            state_new: rs_xmpp_state_new,
+            state_free: rs_xmpp_state_free,
+            parse_ts: rs_xmpp_parse_request,
+            parse_tc: rs_xmpp_parse_response,
+            get_tx_count: rs_xmpp_state_get_tx_count,
+            get_tx: rs_xmpp_state_get_tx,
+            tx_free: rs_xmpp_state_tx_free,
+        })
+    }
+
+    const CLIENT_HEADER: &[u8] = b"<?xml version='1.0'?><stream:stream to='example.com' \
+        xmlns='jabber:client' xmlns:stream='http://etherx.jabber.org/streams' version='1.0'>";
+    const SERVER_HEADER: &[u8] = b"<?xml version='1.0'?><stream:stream from='example.com' \
+        id='c2s_1' xmlns='jabber:client' xmlns:stream='http://etherx.jabber.org/streams' \
+        version='1.0'>";
+
+    #[test]
+    fn test_xmpp_plain_auth() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CLIENT_HEADER));
+        assert!(h.feed(Direction::ToClient, SERVER_HEADER));
+        // features split over two segments
+        assert!(h.feed(Direction::ToClient,
+                       b"<stream:features><mechanisms xmlns='urn:ietf:params:xml:ns:xmpp-sasl'>\
+                         <mechanism>PLAIN</mech"));
+        assert!(h.feed(Direction::ToClient, b"anism></mechanisms></stream:features>"));
+        assert!(h.feed(Direction::ToServer,
+                       b"<auth xmlns='urn:ietf:params:xml:ns:xmpp-sasl' \
+                         mechanism='PLAIN'>AGp1bGlldAByMG0zMA==</auth>"));
+        assert!(h.feed(Direction::ToClient,
+                       b"<success xmlns='urn:ietf:params:xml:ns:xmpp-sasl'/>"));
+        assert!(h.feed(Direction::ToServer,
+                       b"<iq type='set' id='b1'><bind xmlns='urn:ietf:params:xml:ns:xmpp-bind'>\
+                         <resource>home</resource></bind></iq>"));
+        assert!(h.feed(Direction::ToClient,
+                       b"<iq type='result' id='b1'><bind xmlns='urn:ietf:params:xml:ns:xmpp-bind'>\
+                         <jid>juliet@example.com/home</jid></bind></iq>\n"));
+        assert!(h.feed(Direction::ToServer,
+                       b"<message to='romeo@example.net' type='chat'><body>hi</body></message>"));
+
+        assert_eq!(h.tx_count(), 7);
+        let tx = h.get_tx::<XmppTransaction>(0).unwrap();
+        assert_eq!(tx.tx_type, XmppTxType::Stream);
+        assert_eq!(tx.to, Some("example.com".to_string()));
+        let tx = h.get_tx::<XmppTransaction>(2).unwrap();
+        assert_eq!(tx.mechanisms, vec!["PLAIN".to_string()]);
+        assert!(!tx.starttls_offered);
+        let tx = h.get_tx::<XmppTransaction>(3).unwrap();
+        assert_eq!(tx.tx_type, XmppTxType::Auth);
+        assert_eq!(tx.username, Some("juliet".to_string()));
+        assert_eq!(tx.success, Some(true));
+        assert!(h.state::<XmppState>().authenticated);
+        let tx = h.get_tx::<XmppTransaction>(5).unwrap();
+        assert_eq!(tx.jid, Some("juliet@example.com/home".to_string()));
+        let tx = h.get_tx::<XmppTransaction>(6).unwrap();
+        assert_eq!(tx.name, Some("message".to_string()));
+        assert_eq!(tx.jids().collect::<Vec<_>>(), vec!["romeo@example.net"]);
+    }
+
+    #[test]
+    fn test_xmpp_starttls() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer, CLIENT_HEADER));
+        assert!(h.feed(Direction::ToClient, SERVER_HEADER));
+        assert!(h.feed(Direction::ToClient,
+                       b"<stream:features><starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'>\
+                         <required/></starttls></stream:features>"));
+        assert!(h.feed(Direction::ToServer,
+                       b"<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>"));
+        assert!(h.feed(Direction::ToClient,
+                       b"<proceed xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>"));
+        // the TLS handshake is not parsed
+        assert!(h.feed(Direction::ToServer, b"\x16\x03\x01\x00\xa5\x01\x00\x00\xa1\x03\x03"));
+        assert_eq!(h.tx_count(), 4);
+        let tx = h.get_tx::<XmppTransaction>(2).unwrap();
+        assert!(tx.starttls_offered);
+        assert!(tx.starttls_required);
+        let tx = h.get_tx::<XmppTransaction>(3).unwrap();
+        assert_eq!(tx.tx_type, XmppTxType::StartTls);
+        assert_eq!(tx.success, Some(true));
+    }
+
+    #[test]
+    fn test_xmpp_auth_failure() {
+        let mut h = harness();
+        assert!(h.feed(Direction::ToServer,
+                       b"<auth xmlns='urn:ietf:params:xml:ns:xmpp-sasl' mechanism='SCRAM-SHA-1'>\
+                         biwsbj1qdWxpZXQscj1vTXNUQUF3QUFBQU1BQUFBTlAwVEFBQUFBQUJQVTBBQQ==</auth>"));
+        assert!(h.feed(Direction::ToClient,
+                       b"<failure xmlns='urn:ietf:params:xml:ns:xmpp-sasl'>\
+                         <not-authorized/><text>bad</text></failure>"));
+        let tx = h.get_tx::<XmppTransaction>(0).unwrap();
+        assert_eq!(tx.success, Some(false));
+        assert_eq!(tx.username, None);
+        assert_eq!(tx.condition, Some("not-authorized".to_string()));
+        assert!(!h.feed(Direction::ToServer, b"not xml"));
+    }
+
+    #[test]
+    fn test_xmpp_probe() {
+        unsafe {
+            ALPROTO_XMPP = 42;
+        }
+        assert_eq!(xmpp_probe(CLIENT_HEADER, Direction::ToServer), ProbeResult::Found(42));
+        assert_eq!(xmpp_probe(SERVER_HEADER, Direction::ToServer), ProbeResult::Flipped(42));
+        assert_eq!(xmpp_probe(&CLIENT_HEADER[..40], Direction::ToServer), ProbeResult::Unknown);
+        assert_eq!(xmpp_probe(b"<html><body>", Direction::ToServer), ProbeResult::Failed);
+        assert_eq!(xmpp_probe(b"GET / HTTP/1.1\r\n", Direction::ToServer), ProbeResult::Failed);
+    }
+}
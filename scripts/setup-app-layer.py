@@ -63,17 +63,17 @@ def copy_app_layer_templates(proto, rust):
     upper = proto.upper()
 
     if rust:
+        # Rust parsers are registered from app-layer-parser.c directly,
+        # and get their detection buffers in detect.rs.
         pairs = (
-            ("src/app-layer-template-rust.c",
-             "src/app-layer-%s.c" % (lower)),
-            ("src/app-layer-template-rust.h",
-             "src/app-layer-%s.h" % (lower)),
             ("rust/src/applayertemplate/mod.rs",
-             "rust/src/applayer%s/mod.rs" % (lower)),
+             "rust/src/%s/mod.rs" % (lower)),
             ("rust/src/applayertemplate/template.rs",
-             "rust/src/applayer%s/%s.rs" % (lower, lower)),
+             "rust/src/%s/%s.rs" % (lower, lower)),
             ("rust/src/applayertemplate/parser.rs",
-             "rust/src/applayer%s/parser.rs" % (lower)),
+             "rust/src/%s/parser.rs" % (lower)),
+            ("rust/src/applayertemplate/detect.rs",
+             "rust/src/%s/detect.rs" % (lower)),
         )
     else:
         pairs = (
@@ -102,13 +102,24 @@ def patch_rust_lib_rs(protoname):
     with open(filename) as infile:
         for line in infile:
             if line.startswith("pub mod applayertemplate;"):
-                output.write(line.replace("template", protoname.lower()))
+                output.write(line.replace("applayertemplate", protoname.lower()))
+            output.write(line)
+    open(filename, "w").write(output.getvalue())
+
+def patch_rust_detect_mod_rs(protoname):
+    filename = "rust/src/detect/mod.rs"
+    print("Patching %s." % (filename))
+    output = io.StringIO()
+    with open(filename) as infile:
+        for line in infile:
+            if line.find("crate::applayertemplate::detect::register_keywords();") > -1:
+                output.write(line.replace("applayertemplate", protoname.lower()))
             output.write(line)
     open(filename, "w").write(output.getvalue())
 
 def patch_rust_applayer_mod_rs(protoname):
     lower = protoname.lower()
-    filename = "rust/src/applayer%s/mod.rs" % (lower)
+    filename = "rust/src/%s/mod.rs" % (lower)
     print("Patching %s." % (filename))
     output = io.StringIO()
     done = False
@@ -170,16 +181,20 @@ def patch_app_layer_detect_proto_c(proto):
         output.write(line)
     open(filename, "w").write(output.getvalue())
 
-def patch_app_layer_parser_c(proto):
+def patch_app_layer_parser_c(proto, rust):
     filename = "src/app-layer-parser.c"
     print("Patching %s." % (filename))
     output = io.StringIO()
     inlines = open(filename).readlines()
     for line in inlines:
-        if line.find("app-layer-template.h") > -1:
-            output.write(line.replace("template", proto.lower()))
-        if line.find("RegisterTemplateParsers()") > -1:
-            output.write(line.replace("Template", proto))
+        if rust:
+            if line.find("rs_template_register_parser()") > -1:
+                output.write(line.replace("template", proto.lower()))
+        else:
+            if line.find("app-layer-template.h") > -1:
+                output.write(line.replace("template", proto.lower()))
+            if line.find("RegisterTemplateParsers()") > -1:
+                output.write(line.replace("Template", proto))
         output.write(line)
     open(filename, "w").write(output.getvalue())
 
@@ -249,6 +264,24 @@ def logger_patch_output_c(proto):
         output.write(line)
     open(filename, "w").write(output.getvalue())
 
+def logger_patch_output_json_alert_c(proto):
+    filename = "src/output-json-alert.c"
+    print("Patching %s." % (filename))
+    output = io.StringIO()
+    inlines = open(filename).readlines()
+    for i, line in enumerate(inlines):
+        if line.find("output-json-template-rust.h") > -1:
+            output.write(line.replace("template-rust", proto.lower()))
+        if line.find("case ALPROTO_TEMPLATE_RUST:") > -1:
+            # Duplicate the case, up to and including its break.
+            for j in range(i, i + 6):
+                temp = inlines[j]
+                temp = temp.replace("TEMPLATE_RUST", proto.upper())
+                temp = temp.replace("TemplateRust", proto)
+                output.write(temp)
+        output.write(line)
+    open(filename, "w").write(output.getvalue())
+
 def logger_copy_templates(proto, rust):
     lower = proto.lower()
 
@@ -259,7 +292,7 @@ def logger_copy_templates(proto, rust):
             ("src/output-json-template-rust.c",
              "src/output-json-%s.c" % (lower)),
             ("rust/src/applayertemplate/logger.rs",
-             "rust/src/applayer%s/logger.rs" % (lower)),
+             "rust/src/%s/logger.rs" % (lower)),
         )
     else:
         pairs = (
@@ -400,8 +433,12 @@ Examples:
     %(progname)s --logger DNP3
     %(progname)s --parser Gopher
 
-This script can also setup a detect buffer. This is a separate
-operation that must be done after creating the parser.
+With --rust, the parser is generated with its transaction table,
+counters, unit tests and the detection buffers of its requests and
+responses, and the logger also adds its metadata to alerts.
+
+For C parsers, this script can also setup a detect buffer. This is a
+separate operation that must be done after creating the parser.
 
 Examples:
 
@@ -446,6 +483,9 @@ def main():
         detect = args.detect
 
     if detect:
+        if args.rust:
+            raise SetupError(
+                "Rust parsers get their detection buffers in detect.rs")
         if args.buffer is None:
             raise SetupError("--detect requires a buffer name")
 
@@ -462,11 +502,13 @@ def main():
         copy_app_layer_templates(proto, args.rust)
         if args.rust:
             patch_rust_lib_rs(proto)
-        patch_makefile_am(proto)
+            patch_rust_detect_mod_rs(proto)
+        else:
+            patch_makefile_am(proto)
         patch_app_layer_protos_h(proto)
         patch_app_layer_protos_c(proto)
         patch_app_layer_detect_proto_c(proto)
-        patch_app_layer_parser_c(proto)
+        patch_app_layer_parser_c(proto, args.rust)
         patch_suricata_yaml_in(proto)
 
     if logger:
@@ -475,6 +517,7 @@ def main():
         logger_copy_templates(proto, args.rust)
         if args.rust:
             patch_rust_applayer_mod_rs(proto)
+            logger_patch_output_json_alert_c(proto)
         logger_patch_makefile_am(proto)
         logger_patch_suricata_common_h(proto)
         logger_patch_output_c(proto)
@@ -492,11 +535,13 @@ def main():
     if parser:
         if args.rust:
             print("""
-An application detector and parser for the protocol %(proto)s have
-now been setup in the files:
+An application detector, parser and detection buffers for the
+protocol %(proto)s have now been setup in the files:
 
-    rust/src/applayer%(proto_lower)s/mod.rs
-    rust/src/applayer%(proto_lower)s/parser.rs""" % {
+    rust/src/%(proto_lower)s/mod.rs
+    rust/src/%(proto_lower)s/%(proto_lower)s.rs
+    rust/src/%(proto_lower)s/parser.rs
+    rust/src/%(proto_lower)s/detect.rs""" % {
             "proto": proto,
             "proto_lower": proto.lower(),
         })
@@ -515,9 +560,11 @@ now been setup in the files:
         if args.rust:
             print("""
 A JSON application layer transaction logger for the protocol
-%(proto)s has now been set in the file:
+%(proto)s has now been set in the files:
 
-    rust/src/applayer%(proto_lower)s/logger.rs""" % {
+    src/output-json-%(proto_lower)s.h
+    src/output-json-%(proto_lower)s.c
+    rust/src/%(proto_lower)s/logger.rs""" % {
             "proto": proto,
             "proto_lower": proto.lower(),
         })
@@ -32,9 +32,112 @@
 #include "util-byte.h"
 #include "pkt-var.h"
 #include "util-profiling.h"
+#include "util-atomic.h"
+#include "counters.h"
 
 #include "app-layer-enip-common.h"
 
+/* Per service counters of the CIP requests, registered as global stats
+ * counters as the parser has no access to the thread's stats. */
+#define CIP_SERVICE_COUNTER(name)                                                                  \
+    static SC_ATOMIC_DECLARE(uint64_t, cip_service_##name);                                       \
+    static uint64_t CIPServiceCounter_##name(void)                                                 \
+    {                                                                                              \
+        return SC_ATOMIC_GET(cip_service_##name);                                                  \
+    }
+
+CIP_SERVICE_COUNTER(get_attr_all)
+CIP_SERVICE_COUNTER(get_attr_list)
+CIP_SERVICE_COUNTER(set_attr_list)
+CIP_SERVICE_COUNTER(reset)
+CIP_SERVICE_COUNTER(start)
+CIP_SERVICE_COUNTER(stop)
+CIP_SERVICE_COUNTER(create)
+CIP_SERVICE_COUNTER(delete)
+CIP_SERVICE_COUNTER(msp)
+CIP_SERVICE_COUNTER(get_attr_single)
+CIP_SERVICE_COUNTER(set_attr_single)
+CIP_SERVICE_COUNTER(forward_close)
+CIP_SERVICE_COUNTER(unconnected_send)
+CIP_SERVICE_COUNTER(forward_open)
+CIP_SERVICE_COUNTER(large_forward_open)
+CIP_SERVICE_COUNTER(other)
+
+void ENIPRegisterGlobalCounters(void)
+{
+    StatsRegisterGlobalCounter("enip.cip.get_attr_all", CIPServiceCounter_get_attr_all);
+    StatsRegisterGlobalCounter("enip.cip.get_attr_list", CIPServiceCounter_get_attr_list);
+    StatsRegisterGlobalCounter("enip.cip.set_attr_list", CIPServiceCounter_set_attr_list);
+    StatsRegisterGlobalCounter("enip.cip.reset", CIPServiceCounter_reset);
+    StatsRegisterGlobalCounter("enip.cip.start", CIPServiceCounter_start);
+    StatsRegisterGlobalCounter("enip.cip.stop", CIPServiceCounter_stop);
+    StatsRegisterGlobalCounter("enip.cip.create", CIPServiceCounter_create);
+    StatsRegisterGlobalCounter("enip.cip.delete", CIPServiceCounter_delete);
+    StatsRegisterGlobalCounter("enip.cip.multiple_service", CIPServiceCounter_msp);
+    StatsRegisterGlobalCounter("enip.cip.get_attr_single", CIPServiceCounter_get_attr_single);
+    StatsRegisterGlobalCounter("enip.cip.set_attr_single", CIPServiceCounter_set_attr_single);
+    StatsRegisterGlobalCounter("enip.cip.forward_close", CIPServiceCounter_forward_close);
+    StatsRegisterGlobalCounter("enip.cip.unconnected_send", CIPServiceCounter_unconnected_send);
+    StatsRegisterGlobalCounter("enip.cip.forward_open", CIPServiceCounter_forward_open);
+    StatsRegisterGlobalCounter(
+            "enip.cip.large_forward_open", CIPServiceCounter_large_forward_open);
+    StatsRegisterGlobalCounter("enip.cip.other", CIPServiceCounter_other);
+}
+
+static void CIPServiceCount(uint8_t service)
+{
+    switch (service) {
+        case CIP_GET_ATTR_ALL:
+            SC_ATOMIC_ADD(cip_service_get_attr_all, 1);
+            break;
+        case CIP_GET_ATTR_LIST:
+            SC_ATOMIC_ADD(cip_service_get_attr_list, 1);
+            break;
+        case CIP_SET_ATTR_LIST:
+            SC_ATOMIC_ADD(cip_service_set_attr_list, 1);
+            break;
+        case CIP_RESET:
+            SC_ATOMIC_ADD(cip_service_reset, 1);
+            break;
+        case CIP_START:
+            SC_ATOMIC_ADD(cip_service_start, 1);
+            break;
+        case CIP_STOP:
+            SC_ATOMIC_ADD(cip_service_stop, 1);
+            break;
+        case CIP_CREATE:
+            SC_ATOMIC_ADD(cip_service_create, 1);
+            break;
+        case CIP_DELETE:
+            SC_ATOMIC_ADD(cip_service_delete, 1);
+            break;
+        case CIP_MSP:
+            SC_ATOMIC_ADD(cip_service_msp, 1);
+            break;
+        case CIP_GET_ATTR_SINGLE:
+            SC_ATOMIC_ADD(cip_service_get_attr_single, 1);
+            break;
+        case CIP_SET_ATTR_SINGLE:
+            SC_ATOMIC_ADD(cip_service_set_attr_single, 1);
+            break;
+        case CIP_FORWARD_CLOSE:
+            SC_ATOMIC_ADD(cip_service_forward_close, 1);
+            break;
+        case CIP_UNCONNECTED_SEND:
+            SC_ATOMIC_ADD(cip_service_unconnected_send, 1);
+            break;
+        case CIP_FORWARD_OPEN:
+            SC_ATOMIC_ADD(cip_service_forward_open, 1);
+            break;
+        case CIP_LARGE_FORWARD_OPEN:
+            SC_ATOMIC_ADD(cip_service_large_forward_open, 1);
+            break;
+        default:
+            SC_ATOMIC_ADD(cip_service_other, 1);
+            break;
+    }
+}
+
 /**
  * \brief Extract 8 bits and move up the offset
  * @param res
@@ -440,16 +543,41 @@ int DecodeCIPPDU(const uint8_t *input, uint32_t input_len,
 
 
 
+/**
+ * \brief Get the class of the request path of a service
+ * @return 1 if the path has a class
+ */
+static int CIPServiceGetClass(CIPServiceEntry *node, uint16_t *class)
+{
+    SegmentEntry *seg = NULL;
+    TAILQ_FOREACH(seg, &node->segment_list, next)
+    {
+        if (seg->segment == PATH_CLASS_8BIT || seg->segment == PATH_CLASS_16BIT) {
+            *class = seg->value;
+            return 1;
+        }
+    }
+    return 0;
+}
+
+/**
+ * \brief Decode the request embedded in an Unconnected Send
+ * @param offset start of the Unconnected Send request data
+ */
+static int DecodeCIPUnconnectedSendPDU(const uint8_t *input, uint32_t input_len,
+        ENIPTransaction *enip_data, uint16_t offset);
+
 /**
  * \brief Decode CIP Request
  * @param input, input_len data stream
  * @param enip_data stores data from Packet
  * @param offset current point in the packet
+ * @param embedded 1 if the request is embedded in an Unconnected Send
  * @return 1 Packet ok
  * @return 0 Packet has errors
  */
-int DecodeCIPRequestPDU(const uint8_t *input, uint32_t input_len,
-        ENIPTransaction *enip_data, uint16_t offset)
+static int DecodeCIPRequest(const uint8_t *input, uint32_t input_len,
+        ENIPTransaction *enip_data, uint16_t offset, uint8_t embedded)
 {
     int ret = 1;
 
@@ -495,6 +623,7 @@ int DecodeCIPRequestPDU(const uint8_t *input, uint32_t input_len,
     node->service = service;
     node->request.path_size = path_size;
     node->request.path_offset = offset;
+    node->request.embedded = embedded;
     // SCLogDebug("DecodeCIPRequestPDU: service 0x%x size %d", node->service,
     //         node->request.path_size);
 
@@ -502,6 +631,11 @@ int DecodeCIPRequestPDU(const uint8_t *input, uint32_t input_len,
 
     offset += path_size * sizeof(uint16_t); //move offset past pathsize
 
+    CIPServiceCount(service);
+
+    uint16_t class = 0;
+    int has_class = CIPServiceGetClass(node, &class);
+
     //list of CIP services is large and can be vendor specific, store CIP service  anyways and let the rule decide the action
     switch (service)
     {
@@ -551,6 +685,22 @@ int DecodeCIPRequestPDU(const uint8_t *input, uint32_t input_len,
         case CIP_GET_STATUS:
             SCLogDebug("DecodeCIPRequest - CIP_GET_STATUS");
             break;
+        case CIP_UNCONNECTED_SEND:
+            SCLogDebug("DecodeCIPRequest - CIP_UNCONNECTED_SEND");
+            if (has_class && class == CIP_CLASS_CONNECTION_MANAGER) {
+                DecodeCIPUnconnectedSendPDU(input, input_len, enip_data, offset);
+            }
+            break;
+        case CIP_FORWARD_OPEN:
+        case CIP_LARGE_FORWARD_OPEN:
+            SCLogDebug("DecodeCIPRequest - CIP_FORWARD_OPEN");
+            // connections are opened through the Connection Manager only
+            if (!has_class || class != CIP_CLASS_CONNECTION_MANAGER) {
+                AppLayerDecoderEventsSetEventRaw(&enip_data->decoder_events,
+                        ENIP_DECODER_EVENT_FORWARD_OPEN_UNUSUAL_CLASS);
+                enip_data->enip->events++;
+            }
+            break;
         default:
             SCLogDebug("DecodeCIPRequest - CIP SERVICE 0x%x", service);
     }
@@ -559,6 +709,59 @@ int DecodeCIPRequestPDU(const uint8_t *input, uint32_t input_len,
 }
 
 
+int DecodeCIPRequestPDU(const uint8_t *input, uint32_t input_len,
+        ENIPTransaction *enip_data, uint16_t offset)
+{
+    return DecodeCIPRequest(input, input_len, enip_data, offset, 0);
+}
+
+static int DecodeCIPUnconnectedSendPDU(const uint8_t *input, uint32_t input_len,
+        ENIPTransaction *enip_data, uint16_t offset)
+{
+    uint8_t priority; //priority and time tick
+    uint8_t timeout_ticks;
+    uint16_t msg_size;
+
+    if (ENIPExtractUint8(&priority, input, &offset, input_len) != 1)
+    {
+        return 0;
+    }
+    if (ENIPExtractUint8(&timeout_ticks, input, &offset, input_len) != 1)
+    {
+        return 0;
+    }
+    if (ENIPExtractUint16(&msg_size, input, &offset, input_len) != 1)
+    {
+        return 0;
+    }
+    if (msg_size < sizeof(CIPReqHdr) || (uint32_t)offset + msg_size > input_len)
+    {
+        SCLogDebug("DecodeCIPUnconnectedSend: Parsing beyond payload length");
+        return 0;
+    }
+
+    //the embedded request is bounded by its size
+    return DecodeCIPRequest(input, offset + msg_size, enip_data, offset, 1);
+}
+
+
+/**
+ * \brief Append a path segment to the string form of the request path,
+ *        used by the enip.cip_path buffer
+ */
+static void CIPPathStrAppend(CIPServiceEntry *node, const char *name, uint16_t value)
+{
+    uint8_t len = node->request.path_str_len;
+    int r = snprintf(node->request.path_str + len, CIP_PATH_STR_MAX - len, "%s%s %u",
+            len > 0 ? " " : "", name, value);
+    if (r > 0 && len + r < CIP_PATH_STR_MAX) {
+        node->request.path_str_len = len + r;
+    } else {
+        //truncated, keep the complete segments only
+        node->request.path_str[len] = '\0';
+    }
+}
+
 /**
  * \brief Deocde CIP Request Path
  * @param input, input_len data stream
@@ -596,6 +799,8 @@ int DecodeCIPRequestPathPDU(const uint8_t *input, uint32_t input_len,
 
     SegmentEntry *seg = NULL;
 
+    node->request.path_str_len = 0;
+
     while (bytes_remain > 0)
     {
         uint8_t segment = 0;
@@ -619,6 +824,7 @@ int DecodeCIPRequestPathPDU(const uint8_t *input, uint32_t input_len,
                 seg->segment = segment;
                 seg->value = class;
                 TAILQ_INSERT_TAIL(&node->segment_list, seg, next);
+                CIPPathStrAppend(node, "class", class);
 
                 bytes_remain--;
                 break;
@@ -629,6 +835,7 @@ int DecodeCIPRequestPathPDU(const uint8_t *input, uint32_t input_len,
                     return 0;
                 }
                 //skip instance, don't need to store
+                CIPPathStrAppend(node, "instance", req_path_instance8);
                 bytes_remain--;
                 break;
             case PATH_ATTR_8BIT: //single attribute
@@ -645,6 +852,7 @@ int DecodeCIPRequestPathPDU(const uint8_t *input, uint32_t input_len,
                 seg->segment = segment;
                 seg->value = class;
                 TAILQ_INSERT_TAIL(&node->segment_list, seg, next);
+                CIPPathStrAppend(node, "attribute", req_path_attr8);
 
                 bytes_remain--;
                 break;
@@ -666,6 +874,7 @@ int DecodeCIPRequestPathPDU(const uint8_t *input, uint32_t input_len,
                 seg->segment = segment;
                 seg->value = class;
                 TAILQ_INSERT_TAIL(&node->segment_list, seg, next);
+                CIPPathStrAppend(node, "class", class);
                 if (bytes_remain >= 2)
                 {
                     bytes_remain = bytes_remain - 2;
@@ -684,6 +893,7 @@ int DecodeCIPRequestPathPDU(const uint8_t *input, uint32_t input_len,
                     return 0;
                 }
                 //skip instance, don't need to store
+                CIPPathStrAppend(node, "instance", req_path_instance16);
                 if (bytes_remain >= 2)
                 {
                     bytes_remain = bytes_remain - 2;
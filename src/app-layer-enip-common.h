@@ -79,8 +79,22 @@
 #define CIP_SET_ATTR_SINGLE 0x10
 #define CIP_KICK_TIMER      0x4b
 #define CIP_OPEN_CONNECTION 0x4c
+#define CIP_FORWARD_CLOSE   0x4e
 #define CIP_CHANGE_START    0x4f
 #define CIP_GET_STATUS      0x50
+#define CIP_UNCONNECTED_SEND 0x52
+#define CIP_FORWARD_OPEN    0x54
+#define CIP_LARGE_FORWARD_OPEN 0x5b
+
+// CIP object classes
+#define CIP_CLASS_CONNECTION_MANAGER 0x06
+
+/** max length of the string form of a request path */
+#define CIP_PATH_STR_MAX    64
+
+enum {
+    ENIP_DECODER_EVENT_FORWARD_OPEN_UNUSUAL_CLASS,
+};
 
 //PATH sizing codes
 #define PATH_CLASS_8BIT         0x20
@@ -179,6 +193,9 @@ typedef struct CIPServiceEntry_
         {
             uint8_t path_size;                  /**< cip path size */
             uint16_t path_offset;               /**< offset to cip path */
+            uint8_t embedded;                   /**< request embedded in an unconnected send */
+            uint8_t path_str_len;
+            char path_str[CIP_PATH_STR_MAX];    /**< "class 6 instance 1 attribute 3" */
         } request;
         struct
         {
@@ -248,4 +265,6 @@ int DecodeCIPRequestMSPPDU(const uint8_t *input, uint32_t input_len,
 int DecodeCIPResponseMSPPDU(const uint8_t *input, uint32_t input_len,
         ENIPTransaction *enip_data, uint16_t offset);
 
+void ENIPRegisterGlobalCounters(void);
+
 #endif /* __APP_LAYER_ENIP_COMMON_H__ */
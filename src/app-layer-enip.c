@@ -54,6 +54,7 @@
 
 
 SCEnumCharMap enip_decoder_event_table[ ] = {
+    { "FORWARD_OPEN_UNUSUAL_CLASS", ENIP_DECODER_EVENT_FORWARD_OPEN_UNUSUAL_CLASS },
     { NULL,                         -1 },
 };
 
@@ -681,11 +682,83 @@ static int ALDecodeENIPTest(void)
     PASS;
 }
 
+/* Unconnected Send to the Connection Manager with an embedded
+ * Get Attribute Single of class 1 instance 1 attribute 7 */
+static uint8_t unconnectedSend[] = {/* Send RR Data */ 0x6f, 0x00,
+                                    /* Length */       0x26, 0x00,
+                                    /* Session */      0x01, 0x00, 0x00, 0x00,
+                                    /* Status */       0x00, 0x00, 0x00, 0x00,
+                                    /* Context */      0x00, 0x00, 0x00, 0x00,
+                                                       0x00, 0x00, 0x00, 0x00,
+                                    /* Options */      0x00, 0x00, 0x00, 0x00,
+                                    /* Handle */       0x00, 0x00, 0x00, 0x00,
+                                    /* Timeout */      0x00, 0x00,
+                                    /* Item count */   0x02, 0x00,
+                                    /* Null address */ 0x00, 0x00, 0x00, 0x00,
+                                    /* Unconnected data item */ 0xb2, 0x00, 0x16, 0x00,
+                                    /* Service, path */ 0x52, 0x02, 0x20, 0x06, 0x24, 0x01,
+                                    /* Priority, timeout */ 0x0a, 0x0e,
+                                    /* Message size */ 0x08, 0x00,
+                                    /* Embedded request */ 0x0e, 0x03, 0x20, 0x01, 0x24, 0x01,
+                                                       0x30, 0x07,
+                                    /* Route path */   0x01, 0x00, 0x01, 0x00};
+
+/**
+ * \brief Test the decoding of the request embedded in an Unconnected Send
+ */
+static int ALDecodeENIPUnconnectedSendTest(void)
+{
+    AppLayerParserThreadCtx *alp_tctx = AppLayerParserThreadCtxAlloc();
+    Flow f;
+    TcpSession ssn;
+
+    memset(&f, 0, sizeof(f));
+    memset(&ssn, 0, sizeof(ssn));
+
+    f.protoctx  = (void *)&ssn;
+    f.proto     = IPPROTO_TCP;
+    f.alproto   = ALPROTO_ENIP;
+
+    StreamTcpInitConfig(true);
+
+    int r = AppLayerParserParse(NULL, alp_tctx, &f, ALPROTO_ENIP, STREAM_TOSERVER,
+            unconnectedSend, sizeof(unconnectedSend));
+    FAIL_IF(r != 0);
+
+    ENIPState    *enip_state = f.alstate;
+    FAIL_IF_NULL(enip_state);
+
+    ENIPTransaction *tx = ENIPGetTx(enip_state, 0);
+    FAIL_IF_NULL(tx);
+    FAIL_IF(tx->service_count != 2);
+
+    CIPServiceEntry *svc = TAILQ_FIRST(&tx->service_list);
+    FAIL_IF_NULL(svc);
+    FAIL_IF(svc->service != CIP_UNCONNECTED_SEND);
+    FAIL_IF(svc->request.embedded != 0);
+    FAIL_IF(strcmp(svc->request.path_str, "class 6 instance 1") != 0);
+
+    svc = TAILQ_NEXT(svc, next);
+    FAIL_IF_NULL(svc);
+    FAIL_IF(svc->service != CIP_GET_ATTR_SINGLE);
+    FAIL_IF(svc->request.embedded != 1);
+    FAIL_IF(strcmp(svc->request.path_str, "class 1 instance 1 attribute 7") != 0);
+
+    FAIL_IF_NOT_NULL(tx->decoder_events);
+
+    AppLayerParserThreadCtxFree(alp_tctx);
+    StreamTcpFreeConfig(true);
+    FLOW_DESTROY(&f);
+
+    PASS;
+}
+
 #endif /* UNITTESTS */
 
 void ENIPParserRegisterTests(void)
 {
 #ifdef UNITTESTS
       UtRegisterTest("ALDecodeENIPTest", ALDecodeENIPTest);
+      UtRegisterTest("ALDecodeENIPUnconnectedSendTest", ALDecodeENIPUnconnectedSendTest);
 #endif /* UNITTESTS */
 }
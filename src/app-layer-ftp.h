@@ -27,12 +27,6 @@
 
 #include "rust.h"
 
-enum {
-    FTP_STATE_IN_PROGRESS,
-    FTP_STATE_PORT_DONE,
-    FTP_STATE_FINISHED,
-};
-
 typedef enum {
     FTP_COMMAND_UNKNOWN = 0,
     FTP_COMMAND_ABOR,
@@ -89,108 +83,6 @@ typedef enum {
     /** \todo more if missing.. */
 } FtpRequestCommand;
 
-typedef struct FtpCommand_ {
-    FtpRequestCommand command;
-    const char *command_name;
-    const uint8_t command_length;
-} FtpCommand;
-extern const FtpCommand FtpCommands[FTP_COMMAND_MAX + 1];
-
-typedef uint32_t FtpRequestCommandArgOfs;
-
-typedef uint16_t FtpResponseCode;
-
-enum {
-    FTP_FIELD_NONE = 0,
-
-    FTP_FIELD_REQUEST_LINE,
-    FTP_FIELD_REQUEST_COMMAND,
-    FTP_FIELD_REQUEST_ARGS,
-
-    FTP_FIELD_RESPONSE_LINE,
-    FTP_FIELD_REPONSE_CODE,
-
-    /* must be last */
-    FTP_FIELD_MAX,
-};
-
-/** used to hold the line state when we have fragmentation. */
-typedef struct FtpLineState_ {
-    /** used to indicate if the current_line buffer is a malloced buffer.  We
-     * use a malloced buffer, if a line is fragmented */
-    uint8_t *db;
-    uint32_t db_len;
-    uint8_t current_line_db;
-    /** we have see LF for the currently parsed line */
-    uint8_t current_line_lf_seen;
-} FtpLineState;
-
-typedef struct FTPString_ {
-    uint8_t *str;
-    uint16_t len;
-    TAILQ_ENTRY(FTPString_) next;
-} FTPString;
-
-typedef struct FTPTransaction_  {
-    /** id of this tx, starting at 0 */
-    uint64_t tx_id;
-
-    AppLayerTxData tx_data;
-
-    /* for the request */
-    uint32_t request_length;
-    uint8_t *request;
-
-    /* for the command description */
-    const FtpCommand *command_descriptor;
-
-    uint16_t dyn_port; /* dynamic port, if applicable */
-    bool done; /* transaction complete? */
-    bool active; /* active or passive mode */
-
-    uint8_t direction;
-
-    /* Handle multiple responses */
-    TAILQ_HEAD(, FTPString_) response_list;
-
-    DetectEngineState *de_state;
-
-    TAILQ_ENTRY(FTPTransaction_) next;
-} FTPTransaction;
-
-/** FTP State for app layer parser */
-typedef struct FtpState_ {
-    const uint8_t *input;
-    int32_t input_len;
-    uint8_t direction;
-    bool active;
-
-    FTPTransaction *curr_tx;
-    TAILQ_HEAD(, FTPTransaction_) tx_list;  /**< transaction list */
-    uint64_t tx_cnt;
-
-    /* --parser details-- */
-    /** current line extracted by the parser from the call to FTPGetline() */
-    const uint8_t *current_line;
-    /** length of the line in current_line.  Doesn't include the delimiter */
-    uint32_t current_line_len;
-    uint8_t current_line_delimiter_len;
-
-    /* 0 for toserver, 1 for toclient */
-    FtpLineState line_state[2];
-
-    FtpRequestCommand command;
-    FtpRequestCommandArgOfs arg_offset;
-    uint32_t port_line_len;
-    uint32_t port_line_size;
-    uint8_t *port_line;
-
-    uint16_t dyn_port;
-    /* specifies which loggers are done logging */
-    uint32_t logged;
-
-} FtpState;
-
 enum {
     FTPDATA_STATE_IN_PROGRESS,
     FTPDATA_STATE_FINISHED,
@@ -211,13 +103,11 @@ typedef struct FtpDataState_ {
 } FtpDataState;
 
 void RegisterFTPParsers(void);
-void FTPParserRegisterTests(void);
-void FTPAtExitPrintStats(void);
-void FTPParserCleanup(void);
 uint64_t FTPMemuseGlobalCounter(void);
 uint64_t FTPMemcapGlobalCounter(void);
 
-uint16_t JsonGetNextLineFromBuffer(const char *buffer, const uint16_t len);
+int FTPDataExpectationCreate(Flow *f, uint8_t direction, uint16_t dyn_port,
+        const uint8_t *file_name, uint32_t file_name_len, bool store);
 void EveFTPDataAddMetadata(const Flow *f, JsonBuilder *jb);
 
 #endif /* __APP_LAYER_FTP_H__ */
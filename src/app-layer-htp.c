@@ -878,6 +878,20 @@ static AppLayerResult HTPHandleRequestData(Flow *f, void *htp_state,
     SCReturnStruct(APP_LAYER_OK);
 }
 
+/** Default port of MQTT, to detect it after a WebSocket upgrade. */
+#define MQTT_DEFAULT_PORT 1883
+
+/**
+ *  \brief Check if the server accepted a WebSocket upgrade with an MQTT
+ *         subprotocol, "mqtt" or the "mqttv3.1" of older clients.
+ */
+static bool HTPUpgradeIsMQTT(htp_tx_t *tx)
+{
+    htp_header_t *h =
+            (htp_header_t *)htp_table_get_c(tx->response_headers, "Sec-WebSocket-Protocol");
+    return h != NULL && bstr_begins_with_c_nocase(h->value, "mqtt");
+}
+
 /**
  *  \brief  Function to handle the reassembled data from server and feed it to
  *          the HTP library to process it.
@@ -925,19 +939,31 @@ static AppLayerResult HTPHandleResponseData(Flow *f, void *htp_state,
                 if (tx != NULL && tx->response_status_number == 101) {
                     htp_header_t *h =
                             (htp_header_t *)htp_table_get_c(tx->response_headers, "Upgrade");
-                    if (h == NULL || bstr_cmp_c(h->value, "h2c") != 0) {
+                    if (h == NULL) {
                         break;
                     }
-                    if (AppLayerProtoDetectGetProtoName(ALPROTO_HTTP2) == NULL) {
-                        // if HTTP2 is disabled, keep the HTP_STREAM_TUNNEL mode
+                    AppProto upgrade_proto;
+                    uint16_t dp = 0;
+                    if (bstr_cmp_c_nocase(h->value, "h2c") == 0) {
+                        upgrade_proto = ALPROTO_HTTP2;
+                        if (tx->request_port_number != -1) {
+                            dp = (uint16_t)tx->request_port_number;
+                        }
+                    } else if (bstr_cmp_c_nocase(h->value, "websocket") == 0 &&
+                               HTPUpgradeIsMQTT(tx)) {
+                        upgrade_proto = ALPROTO_MQTT;
+                        /* MQTT is detected by its probing parser, use its
+                         * default port like STARTTLS does for TLS */
+                        dp = MQTT_DEFAULT_PORT;
+                    } else {
                         break;
                     }
-                    uint16_t dp = 0;
-                    if (tx->request_port_number != -1) {
-                        dp = (uint16_t)tx->request_port_number;
+                    if (AppLayerProtoDetectGetProtoName(upgrade_proto) == NULL) {
+                        // if the protocol is disabled, keep the HTP_STREAM_TUNNEL mode
+                        break;
                     }
                     consumed = htp_connp_res_data_consumed(hstate->connp);
-                    AppLayerRequestProtocolChange(hstate->f, dp, ALPROTO_HTTP2);
+                    AppLayerRequestProtocolChange(hstate->f, dp, upgrade_proto);
                     // During HTTP2 upgrade, we may consume the HTTP1 part of the data
                     // and we need to parser the remaining part with HTTP2
                     if (consumed > 0 && consumed < input_len) {
@@ -25,7 +25,6 @@
 #include "stream.h"
 #include "conf.h"
 
-#include "util-misc.h"
 #include "util-unittest.h"
 
 #include "app-layer-detect-proto.h"
@@ -34,22 +33,16 @@
 #include "app-layer-mqtt.h"
 #include "rust.h"
 
+static StreamingBufferConfig sbcfg = STREAMING_BUFFER_CONFIG_INITIALIZER;
+static SuricataFileContext sfc = { &sbcfg };
+
 void RegisterMQTTParsers(void)
 {
     SCLogDebug("Registering Rust mqtt parser.");
-    uint32_t max_msg_len = 1048576; /* default: 1MB */
 
     if (AppLayerParserConfParserEnabled("tcp", "mqtt")) {
-        ConfNode *p = ConfGetNode("app-layer.protocols.mqtt.max-msg-length");
-        if (p != NULL) {
-            uint32_t value;
-            if (ParseSizeStringU32(p->val, &value) < 0) {
-                SCLogError(SC_ERR_SIZE_PARSE, "invalid value for max-msg-length: %s", p->val);
-            } else {
-                max_msg_len = value;
-            }
-        }
-        rs_mqtt_register_parser(max_msg_len);
+        rs_mqtt_init(&sfc);
+        rs_mqtt_register_parser();
     }
 #ifdef UNITTESTS
     AppLayerParserRegisterProtocolUnittests(IPPROTO_TCP, ALPROTO_MQTT,
@@ -66,7 +66,6 @@
 #include "app-layer-rfb.h"
 #include "app-layer-mqtt.h"
 #include "app-layer-template.h"
-#include "app-layer-template-rust.h"
 #include "app-layer-rdp.h"
 #include "app-layer-http2.h"
 
@@ -95,7 +94,7 @@ typedef struct AppLayerParserProtoCtx_
     /* 0 - to_server, 1 - to_client. */
     AppLayerParserFPtr Parser[2];
     bool logger;
-    uint32_t logger_bits;   /**< registered loggers for this proto */
+    uint64_t logger_bits;   /**< registered loggers for this proto */
 
     void *(*StateAlloc)(void *, AppProto);
     void (*StateFree)(void *);
@@ -104,6 +103,7 @@ typedef struct AppLayerParserProtoCtx_
     void (*LocalStorageFree)(void *);
 
     void (*Truncate)(void *, uint8_t);
+    void (*StatePurge)(void *, uint64_t);
     FileContainer *(*StateGetFiles)(void *, uint8_t);
     AppLayerDecoderEvents *(*StateGetEvents)(void *);
 
@@ -166,6 +166,9 @@ struct AppLayerParserState_ {
 
     /* Used to store decoder events. */
     AppLayerDecoderEvents *decoder_events;
+
+    /* Time in seconds of the last StatePurge call. */
+    uint64_t last_purge;
 };
 
 #ifdef UNITTESTS
@@ -242,7 +245,6 @@ int AppLayerParserDeSetup(void)
 {
     SCEnter();
 
-    FTPParserCleanup();
     SMTPParserCleanup();
 
     SCReturnInt(0);
@@ -443,7 +445,7 @@ void AppLayerParserRegisterGetEventsFunc(uint8_t ipproto, AppProto alproto,
     SCReturn;
 }
 
-void AppLayerParserRegisterLoggerBits(uint8_t ipproto, AppProto alproto, LoggerId bits)
+void AppLayerParserRegisterLoggerBits(uint8_t ipproto, AppProto alproto, uint64_t bits)
 {
     SCEnter();
 
@@ -461,6 +463,16 @@ void AppLayerParserRegisterLogger(uint8_t ipproto, AppProto alproto)
     SCReturn;
 }
 
+void AppLayerParserRegisterStatePurgeFunc(uint8_t ipproto, AppProto alproto,
+                                          void (*StatePurge)(void *, uint64_t))
+{
+    SCEnter();
+
+    alp_ctx.ctxs[FlowGetProtoMapping(ipproto)][alproto].StatePurge = StatePurge;
+
+    SCReturn;
+}
+
 void AppLayerParserRegisterTruncateFunc(uint8_t ipproto, AppProto alproto,
                                         void (*Truncate)(void *, uint8_t))
 {
@@ -722,7 +734,7 @@ static inline void SetTxDetectFlags(AppLayerTxData *txd, const uint8_t dir, cons
     }
 }
 
-static inline uint32_t GetTxLogged(AppLayerTxData *txd)
+static inline uint64_t GetTxLogged(AppLayerTxData *txd)
 {
     return txd->logged.flags;
 }
@@ -879,6 +891,34 @@ extern int g_detect_disabled;
 extern bool g_file_logger_enabled;
 extern bool g_filedata_logger_enabled;
 
+/**
+ * \brief let the parser drop stale state, at most once per second
+ *
+ * \param ts time of the packet being processed, or the current time
+ *           for flow timeout packets
+ */
+void AppLayerParserStatePurge(Flow *f, const struct timeval *ts)
+{
+    SCEnter();
+    DEBUG_ASSERT_FLOW_LOCKED(f);
+
+    AppLayerParserProtoCtx *p = &alp_ctx.ctxs[f->protomap][f->alproto];
+    if (likely(p->StatePurge == NULL))
+        SCReturn;
+
+    AppLayerParserState * const alparser = f->alparser;
+    if (f->alstate == NULL || alparser == NULL)
+        SCReturn;
+
+    const uint64_t now = (uint64_t)ts->tv_sec;
+    if (now <= alparser->last_purge)
+        SCReturn;
+    alparser->last_purge = now;
+
+    p->StatePurge(f->alstate, now);
+    SCReturn;
+}
+
 /**
  * \brief remove obsolete (inspected and logged) transactions
  */
@@ -902,7 +942,7 @@ void AppLayerParserTransactionsCleanup(Flow *f)
 
     const uint64_t min = alparser->min_id;
     const uint64_t total_txs = AppLayerParserGetTxCnt(f, alstate);
-    const LoggerId logger_expectation = AppLayerParserProtocolGetLoggerBits(ipproto, alproto);
+    const uint64_t logger_expectation = AppLayerParserProtocolGetLoggerBits(ipproto, alproto);
     const int tx_end_state_ts = AppLayerParserGetStateProgressCompletionStatus(alproto, STREAM_TOSERVER);
     const int tx_end_state_tc = AppLayerParserGetStateProgressCompletionStatus(alproto, STREAM_TOCLIENT);
     const uint8_t ts_disrupt_flags = FlowGetDisruptionFlags(f, STREAM_TOSERVER);
@@ -988,9 +1028,9 @@ void AppLayerParserTransactionsCleanup(Flow *f)
         }
 
         if (txd && logger_expectation != 0) {
-            LoggerId tx_logged = GetTxLogged(txd);
+            uint64_t tx_logged = GetTxLogged(txd);
             if (tx_logged != logger_expectation) {
-                SCLogDebug("%p/%"PRIu64" skipping: logging not done: want:%"PRIx32", have:%"PRIx32,
+                SCLogDebug("%p/%"PRIu64" skipping: logging not done: want:%"PRIx64", have:%"PRIx64,
                         tx, i, logger_expectation, tx_logged);
                 skipped = true;
                 goto next;
@@ -1435,11 +1475,11 @@ int AppLayerParserProtocolHasLogger(uint8_t ipproto, AppProto alproto)
     SCReturnInt(r);
 }
 
-LoggerId AppLayerParserProtocolGetLoggerBits(uint8_t ipproto, AppProto alproto)
+uint64_t AppLayerParserProtocolGetLoggerBits(uint8_t ipproto, AppProto alproto)
 {
     SCEnter();
     const int ipproto_map = FlowGetProtoMapping(ipproto);
-    LoggerId r = alp_ctx.ctxs[ipproto_map][alproto].logger_bits;
+    uint64_t r = alp_ctx.ctxs[ipproto_map][alproto].logger_bits;
     SCReturnUInt(r);
 }
 
@@ -1598,6 +1638,7 @@ void AppLayerParserRegisterProtocolParsers(void)
     rs_dcerpc_register_parser();
     rs_dcerpc_udp_register_parser();
     RegisterSMBParsers();
+    rs_ftp_register_parser();
     RegisterFTPParsers();
     RegisterSSHParsers();
     RegisterSMTPParsers();
@@ -1616,12 +1657,29 @@ void AppLayerParserRegisterProtocolParsers(void)
     rs_dhcp_register_parser();
     RegisterSNMPParsers();
     RegisterSIPParsers();
-    RegisterTemplateRustParsers();
+    rs_template_register_parser();
     RegisterRFBParsers();
     RegisterMQTTParsers();
     RegisterTemplateParsers();
     RegisterRdpParsers();
     RegisterHTTP2Parsers();
+    rs_quic_register_parser();
+    rs_ldap_register_parser();
+    rs_telnet_register_parser();
+    rs_llmnr_register_parser();
+    rs_nbns_register_parser();
+    rs_stratum_register_parser();
+    rs_kafka_register_parser();
+    rs_irc_register_parser();
+    rs_xmpp_register_parser();
+    rs_openvpn_udp_register_parser();
+    rs_openvpn_tcp_register_parser();
+    rs_dtls_register_parser();
+    rs_rtp_register_parser();
+    rs_proxy_register_parser();
+    rs_icap_register_parser();
+    rs_bittorrent_tcp_register_parser();
+    rs_bittorrent_udp_register_parser();
 
     /** IMAP */
     AppLayerProtoDetectRegisterProtocol(ALPROTO_IMAP, "imap");
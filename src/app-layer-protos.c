@@ -120,6 +120,48 @@ const char *AppProtoToString(AppProto alproto)
         case ALPROTO_HTTP2:
             proto_name = "http2";
             break;
+        case ALPROTO_QUIC:
+            proto_name = "quic";
+            break;
+        case ALPROTO_LDAP:
+            proto_name = "ldap";
+            break;
+        case ALPROTO_TELNET:
+            proto_name = "telnet";
+            break;
+        case ALPROTO_LLMNR:
+            proto_name = "llmnr";
+            break;
+        case ALPROTO_NBNS:
+            proto_name = "nbns";
+            break;
+        case ALPROTO_STRATUM:
+            proto_name = "stratum";
+            break;
+        case ALPROTO_KAFKA:
+            proto_name = "kafka";
+            break;
+        case ALPROTO_XMPP:
+            proto_name = "xmpp";
+            break;
+        case ALPROTO_OPENVPN:
+            proto_name = "openvpn";
+            break;
+        case ALPROTO_DTLS:
+            proto_name = "dtls";
+            break;
+        case ALPROTO_RTP:
+            proto_name = "rtp";
+            break;
+        case ALPROTO_PROXY:
+            proto_name = "proxy";
+            break;
+        case ALPROTO_ICAP:
+            proto_name = "icap";
+            break;
+        case ALPROTO_BITTORRENT:
+            proto_name = "bittorrent";
+            break;
         case ALPROTO_HTTP:
             proto_name = "http_any";
             break;
@@ -174,6 +216,20 @@ AppProto StringToAppProto(const char *proto_name)
     if (strcmp(proto_name,"template-rust")==0) return ALPROTO_TEMPLATE_RUST;
     if (strcmp(proto_name,"rdp")==0) return ALPROTO_RDP;
     if (strcmp(proto_name,"http2")==0) return ALPROTO_HTTP2;
+    if (strcmp(proto_name,"quic")==0) return ALPROTO_QUIC;
+    if (strcmp(proto_name,"ldap")==0) return ALPROTO_LDAP;
+    if (strcmp(proto_name,"telnet")==0) return ALPROTO_TELNET;
+    if (strcmp(proto_name,"llmnr")==0) return ALPROTO_LLMNR;
+    if (strcmp(proto_name,"nbns")==0) return ALPROTO_NBNS;
+    if (strcmp(proto_name,"stratum")==0) return ALPROTO_STRATUM;
+    if (strcmp(proto_name,"kafka")==0) return ALPROTO_KAFKA;
+    if (strcmp(proto_name,"xmpp")==0) return ALPROTO_XMPP;
+    if (strcmp(proto_name,"openvpn")==0) return ALPROTO_OPENVPN;
+    if (strcmp(proto_name,"dtls")==0) return ALPROTO_DTLS;
+    if (strcmp(proto_name,"rtp")==0) return ALPROTO_RTP;
+    if (strcmp(proto_name,"proxy")==0) return ALPROTO_PROXY;
+    if (strcmp(proto_name,"icap")==0) return ALPROTO_ICAP;
+    if (strcmp(proto_name,"bittorrent")==0) return ALPROTO_BITTORRENT;
     if (strcmp(proto_name,"failed")==0) return ALPROTO_FAILED;
 
     return ALPROTO_UNKNOWN;
@@ -57,6 +57,20 @@ enum AppProtoEnum {
     ALPROTO_TEMPLATE_RUST,
     ALPROTO_RDP,
     ALPROTO_HTTP2,
+    ALPROTO_QUIC,
+    ALPROTO_LDAP,
+    ALPROTO_TELNET,
+    ALPROTO_LLMNR,
+    ALPROTO_NBNS,
+    ALPROTO_STRATUM,
+    ALPROTO_KAFKA,
+    ALPROTO_XMPP,
+    ALPROTO_OPENVPN,
+    ALPROTO_DTLS,
+    ALPROTO_RTP,
+    ALPROTO_PROXY,
+    ALPROTO_ICAP,
+    ALPROTO_BITTORRENT,
 
     // signature-only (ie not seen in flow)
     // HTTP for any version (ALPROTO_HTTP1 (version 1) or ALPROTO_HTTP2)
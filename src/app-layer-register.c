@@ -187,6 +187,10 @@ int AppLayerRegisterParser(const struct AppLayerParser *p, AppProto alproto)
         AppLayerParserRegisterTruncateFunc(p->ip_proto, alproto, p->Truncate);
     }
 
+    if (p->StatePurge) {
+        AppLayerParserRegisterStatePurgeFunc(p->ip_proto, alproto, p->StatePurge);
+    }
+
     return 0;
 }
 
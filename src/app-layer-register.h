@@ -74,6 +74,8 @@ typedef struct AppLayerParser {
 
     void (*Truncate)(void *state, uint8_t direction);
 
+    void (*StatePurge)(void *state, uint64_t ts);
+
 } AppLayerParser;
 
 /**
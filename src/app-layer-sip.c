@@ -20,7 +20,7 @@
  *
  * \author Giuseppe Longo <giuseppe@glongo.it>
  *
- * Parser for SIP application layer running on UDP port 5060.
+ * Parser for SIP application layer running on UDP and TCP port 5060.
  */
 
 #include "suricata-common.h"
@@ -30,4 +30,5 @@
 void RegisterSIPParsers(void)
 {
     rs_sip_register_parser();
+    rs_sip_tcp_register_parser();
 }
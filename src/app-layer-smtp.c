@@ -150,6 +150,10 @@ SCEnumCharMap smtp_decoder_event_table[ ] = {
       SMTP_DECODER_EVENT_MIME_BOUNDARY_TOO_LONG },
     { "MIME_LONG_FILENAME",
       SMTP_DECODER_EVENT_MIME_LONG_FILENAME },
+    { "MIME_DEEP_NESTING",
+      SMTP_DECODER_EVENT_MIME_DEEP_NESTING },
+    { "MIME_TOO_MANY_HEADERS",
+      SMTP_DECODER_EVENT_MIME_TOO_MANY_HEADERS },
 
     /* Invalid behavior or content */
     { "DUPLICATE_FIELDS",
@@ -237,7 +241,7 @@ SCEnumCharMap smtp_reply_map[ ] = {
 };
 
 /* Create SMTP config structure */
-SMTPConfig smtp_config = { 0, { 0, 0, 0, 0, 0 }, 0, 0, 0, 0, STREAMING_BUFFER_CONFIG_INITIALIZER};
+SMTPConfig smtp_config = { 0, { 0, 0, 0, 0, 0, 0, 0 }, 0, 0, 0, 0, STREAMING_BUFFER_CONFIG_INITIALIZER};
 
 static SMTPString *SMTPStringAlloc(void);
 
@@ -279,6 +283,16 @@ static void SMTPConfigure(void) {
             smtp_config.mime_config.header_value_depth = (uint32_t) imval;
         }
 
+        ret = ConfGetChildValueInt(config, "max-nesting-depth", &imval);
+        if (ret) {
+            smtp_config.mime_config.max_nesting_depth = (uint32_t) imval;
+        }
+
+        ret = ConfGetChildValueInt(config, "max-header-count", &imval);
+        if (ret) {
+            smtp_config.mime_config.max_header_count = (uint32_t) imval;
+        }
+
         ret = ConfGetChildValueBool(config, "extract-urls", &val);
         if (ret) {
             smtp_config.mime_config.extract_urls = val;
@@ -880,6 +894,12 @@ static void SetMimeEvents(SMTPState *state)
     if (msg->anomaly_flags & ANOM_LONG_FILENAME) {
         SMTPSetEvent(state, SMTP_DECODER_EVENT_MIME_LONG_FILENAME);
     }
+    if (msg->anomaly_flags & ANOM_DEEP_NESTING) {
+        SMTPSetEvent(state, SMTP_DECODER_EVENT_MIME_DEEP_NESTING);
+    }
+    if (msg->anomaly_flags & ANOM_TOO_MANY_HEADERS) {
+        SMTPSetEvent(state, SMTP_DECODER_EVENT_MIME_TOO_MANY_HEADERS);
+    }
 }
 
 static inline void SMTPTransactionComplete(SMTPState *state)
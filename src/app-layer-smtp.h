@@ -52,6 +52,8 @@ enum {
     SMTP_DECODER_EVENT_MIME_LONG_HEADER_VALUE,
     SMTP_DECODER_EVENT_MIME_BOUNDARY_TOO_LONG,
     SMTP_DECODER_EVENT_MIME_LONG_FILENAME,
+    SMTP_DECODER_EVENT_MIME_DEEP_NESTING,
+    SMTP_DECODER_EVENT_MIME_TOO_MANY_HEADERS,
 
     /* Invalid behavior or content */
     SMTP_DECODER_EVENT_DUPLICATE_FIELDS,
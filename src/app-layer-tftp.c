@@ -44,6 +44,9 @@
  * be the size of a header. */
 #define TFTP_MIN_FRAME_LEN 4
 
+static StreamingBufferConfig sbcfg = STREAMING_BUFFER_CONFIG_INITIALIZER;
+static SuricataFileContext sfc = { &sbcfg };
+
 static void *TFTPStateAlloc(void *orig_state, AppProto proto_orig)
 {
     return rs_tftp_state_alloc();
@@ -65,17 +68,6 @@ static void TFTPStateTxFree(void *state, uint64_t tx_id)
     rs_tftp_state_tx_free(state, tx_id);
 }
 
-static int TFTPStateGetEventInfo(const char *event_name, int *event_id,
-    AppLayerEventType *event_type)
-{
-    return -1;
-}
-
-static AppLayerDecoderEvents *TFTPGetEvents(void *tx)
-{
-    return NULL;
-}
-
 /**
  * \brief Probe the input to see if it looks like echo.
  *
@@ -114,7 +106,7 @@ static AppLayerResult TFTPParseRequest(Flow *f, void *state,
         SCReturnStruct(APP_LAYER_OK);
     }
 
-    int res = rs_tftp_request(state, input, input_len);
+    int res = rs_tftp_request(state, f, input, input_len);
     if (res < 0) {
         SCReturnStruct(APP_LAYER_ERROR);
     }
@@ -122,12 +114,21 @@ static AppLayerResult TFTPParseRequest(Flow *f, void *state,
 }
 
 /**
- * \brief Response parsing is not implemented
+ * \brief Parse the packets of the server, the DATA blocks of a read
+ *        or the ACKs of a write.
  */
 static AppLayerResult TFTPParseResponse(Flow *f, void *state, AppLayerParserState *pstate,
     const uint8_t *input, uint32_t input_len, void *local_data,
     const uint8_t flags)
 {
+    if (input == NULL || input_len == 0) {
+        SCReturnStruct(APP_LAYER_OK);
+    }
+
+    int res = rs_tftp_response(state, f, input, input_len);
+    if (res < 0) {
+        SCReturnStruct(APP_LAYER_ERROR);
+    }
     SCReturnStruct(APP_LAYER_OK);
 }
 
@@ -141,35 +142,6 @@ static void *TFTPGetTx(void *state, uint64_t tx_id)
     return rs_tftp_get_tx(state, tx_id);
 }
 
-/**
- * \brief Return the state of a transaction in a given direction.
- *
- * In the case of the echo protocol, the existence of a transaction
- * means that the request is done. However, some protocols that may
- * need multiple chunks of data to complete the request may need more
- * than just the existence of a transaction for the request to be
- * considered complete.
- *
- * For the response to be considered done, the response for a request
- * needs to be seen.  The response_done flag is set on response for
- * checking here.
- */
-static int TFTPGetStateProgress(void *tx, uint8_t direction)
-{
-    return 1;
-}
-
-static DetectEngineState *TFTPGetTxDetectState(void *vtx)
-{
-    return NULL;
-}
-
-static int TFTPSetTxDetectState(void *vtx,
-    DetectEngineState *s)
-{
-    return 0;
-}
-
 void RegisterTFTPParsers(void)
 {
     const char *proto_name = "tftp";
@@ -238,19 +210,26 @@ void RegisterTFTPParsers(void)
         AppLayerParserRegisterStateProgressCompletionStatus(ALPROTO_TFTP, 1, 1);
         AppLayerParserRegisterGetStateProgressFunc(IPPROTO_UDP,
                                                    ALPROTO_TFTP,
-                                                   TFTPGetStateProgress);
+                                                   rs_tftp_tx_get_alstate_progress);
         AppLayerParserRegisterGetTx(IPPROTO_UDP, ALPROTO_TFTP,
                                     TFTPGetTx);
 
-        /* What is this being registered for? */
         AppLayerParserRegisterDetectStateFuncs(IPPROTO_UDP, ALPROTO_TFTP,
-                                               TFTPGetTxDetectState,
-                                               TFTPSetTxDetectState);
+                                               rs_tftp_get_tx_detect_state,
+                                               rs_tftp_set_tx_detect_state);
 
         AppLayerParserRegisterGetEventInfo(IPPROTO_UDP, ALPROTO_TFTP,
-                                           TFTPStateGetEventInfo);
+                                           rs_tftp_state_get_event_info);
+        AppLayerParserRegisterGetEventInfoById(IPPROTO_UDP, ALPROTO_TFTP,
+                                               rs_tftp_state_get_event_info_by_id);
         AppLayerParserRegisterGetEventsFunc(IPPROTO_UDP, ALPROTO_TFTP,
-                                            TFTPGetEvents);
+                                            rs_tftp_get_events);
+
+        /* The transfers on the flows expected by the requests are
+         * handed to the file API. */
+        rs_tftp_init(&sfc, ALPROTO_TFTP);
+        AppLayerParserRegisterGetFilesFunc(IPPROTO_UDP, ALPROTO_TFTP,
+                                           rs_tftp_getfiles);
 
         AppLayerParserRegisterTxDataFunc(IPPROTO_UDP, ALPROTO_TFTP,
                                          rs_tftp_get_tx_data);
@@ -31,6 +31,7 @@
 
 #include "detect-cipservice.h"
 #include "detect-engine-enip.h"
+#include "detect-engine-helper.h"
 
 /*
  * CIP SERVICE CODE
@@ -458,3 +459,58 @@ static void DetectEnipCommandRegisterTests(void)
             DetectEnipCommandSignatureTest01);
 }
 #endif /* UNITTESTS */
+
+/*
+ * CIP REQUEST PATH
+ */
+
+static int g_cip_path_buffer_id = 0;
+
+/**
+ * \brief Get the request path of the local_id'th CIP request of the tx
+ */
+static bool CipPathGetBuffer(void *txv, const uint8_t flow_flags, const uint32_t local_id,
+        const uint8_t **buf, uint32_t *buf_len)
+{
+    ENIPTransaction *tx = (ENIPTransaction *)txv;
+    uint32_t idx = 0;
+
+    CIPServiceEntry *svc = NULL;
+    TAILQ_FOREACH (svc, &tx->service_list, next) {
+        if (svc->direction != 0 || svc->request.path_str_len == 0)
+            continue;
+        if (idx == local_id) {
+            *buf = (const uint8_t *)svc->request.path_str;
+            *buf_len = svc->request.path_str_len;
+            return true;
+        }
+        idx++;
+    }
+    return false;
+}
+
+static int DetectCipPathSetup(DetectEngineCtx *de_ctx, Signature *s, const char *str)
+{
+    if (DetectBufferSetActiveList(s, g_cip_path_buffer_id) < 0)
+        return -1;
+    if (DetectSignatureSetAppProto(s, ALPROTO_ENIP) < 0)
+        return -1;
+    return 0;
+}
+
+/**
+ * \brief Registration function for the enip.cip_path sticky buffer
+ */
+void DetectCipPathRegister(void)
+{
+    sigmatch_table[DETECT_ENIP_CIP_PATH].name = "enip.cip_path";
+    sigmatch_table[DETECT_ENIP_CIP_PATH].desc =
+            "sticky buffer to match on the request path of CIP requests";
+    sigmatch_table[DETECT_ENIP_CIP_PATH].url = "/rules/enip-keyword.html#enip-cip-path";
+    sigmatch_table[DETECT_ENIP_CIP_PATH].Setup = DetectCipPathSetup;
+    sigmatch_table[DETECT_ENIP_CIP_PATH].flags |= SIGMATCH_NOOPT;
+    sigmatch_table[DETECT_ENIP_CIP_PATH].flags |= SIGMATCH_INFO_STICKY_BUFFER;
+
+    g_cip_path_buffer_id = DetectHelperMultiBufferMpmRegister("enip.cip_path",
+            "enip cip request path", ALPROTO_ENIP, false, true, 1, CipPathGetBuffer);
+}
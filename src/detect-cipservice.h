@@ -54,6 +54,7 @@ typedef struct DetectEnipCommandData_
 
 void DetectCipServiceRegister(void);
 void DetectEnipCommandRegister(void);
+void DetectCipPathRegister(void);
 
 /**
  * link list node for storing CIP service data
@@ -123,6 +123,9 @@ static int DetectDceIfaceMatchRust(DetectEngineThreadCtx *det_ctx,
     SCEnter();
 
     if (f->alproto == ALPROTO_DCERPC) {
+        if (f->proto == IPPROTO_UDP) {
+            return rs_dcerpc_udp_iface_match(txv, (void *)m);
+        }
         // TODO check if state is NULL
         return rs_dcerpc_iface_match(txv, state, (void *)m);
     }
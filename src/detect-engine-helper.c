@@ -0,0 +1,296 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+/**
+ * \file
+ *
+ * Helpers to register detection keywords and buffers implemented in Rust.
+ *
+ * Keywords registered through these helpers get an id from the dynamic
+ * part of the sigmatch_table, so they don't need an entry in
+ * DetectKeywordId.
+ */
+
+#include "suricata-common.h"
+#include "detect.h"
+#include "detect-parse.h"
+#include "detect-engine.h"
+#include "detect-engine-mpm.h"
+#include "detect-engine-content-inspection.h"
+#include "detect-engine-prefilter.h"
+#include "detect-engine-helper.h"
+
+/** next free slot in the dynamic part of the sigmatch_table */
+static int g_keyword_next_id = DETECT_TBLSIZE_STATIC;
+
+/**
+ * \brief Register a keyword in the sigmatch_table.
+ *
+ * Running out of slots is fatal: DETECT_TBLSIZE_DYNAMIC has to be raised
+ * rather than having the keyword silently missing from the engine.
+ *
+ * \retval id of the keyword
+ */
+int DetectHelperKeywordRegister(const SCSigTableElmt *kw)
+{
+    if (g_keyword_next_id >= DETECT_TBLSIZE) {
+        FatalError(SC_ERR_FATAL,
+                "no room left to register keyword %s, DETECT_TBLSIZE_DYNAMIC (%d) is too small",
+                kw->name, DETECT_TBLSIZE_DYNAMIC);
+    }
+    const int id = g_keyword_next_id++;
+
+    sigmatch_table[id].name = kw->name;
+    sigmatch_table[id].desc = kw->desc;
+    sigmatch_table[id].url = kw->url;
+    sigmatch_table[id].flags = kw->flags;
+    sigmatch_table[id].Setup = (int (*)(DetectEngineCtx *, Signature *, const char *))kw->Setup;
+    sigmatch_table[id].Free = (void (*)(DetectEngineCtx *, void *))kw->Free;
+    sigmatch_table[id].AppLayerTxMatch = (int (*)(DetectEngineThreadCtx *, Flow *, uint8_t,
+            void *, void *, const Signature *, const SigMatchCtx *))kw->AppLayerTxMatch;
+
+    return id;
+}
+
+static int DetectHelperInspectGenericList(DetectEngineCtx *de_ctx,
+        DetectEngineThreadCtx *det_ctx, const struct DetectEngineAppInspectionEngine_ *engine,
+        const Signature *s, Flow *f, uint8_t flags, void *alstate, void *txv, uint64_t tx_id)
+{
+    return DetectEngineInspectGenericList(
+            de_ctx, det_ctx, s, engine->smd, f, flags, alstate, txv, tx_id);
+}
+
+/**
+ * \brief Register a list for keywords matching on the transaction
+ *        through their AppLayerTxMatch callback.
+ *
+ * \retval list id
+ */
+int DetectHelperBufferRegister(const char *name, AppProto alproto, bool toclient, bool toserver,
+        int progress)
+{
+    if (toserver) {
+        DetectAppLayerInspectEngineRegister2(name, alproto, SIG_FLAG_TOSERVER, progress,
+                DetectHelperInspectGenericList, NULL);
+    }
+    if (toclient) {
+        DetectAppLayerInspectEngineRegister2(name, alproto, SIG_FLAG_TOCLIENT, progress,
+                DetectHelperInspectGenericList, NULL);
+    }
+    return DetectBufferTypeGetByName(name);
+}
+
+/**
+ * \brief Register a sticky buffer with inspection and mpm engines.
+ *
+ * \retval list id
+ */
+int DetectHelperBufferMpmRegister(const char *name, const char *desc, AppProto alproto,
+        bool toclient, bool toserver, int progress, InspectionBufferGetDataPtr GetData)
+{
+    if (toserver) {
+        DetectAppLayerInspectEngineRegister2(name, alproto, SIG_FLAG_TOSERVER, progress,
+                DetectEngineInspectBufferGeneric, GetData);
+        DetectAppLayerMpmRegister2(name, SIG_FLAG_TOSERVER, 2, PrefilterGenericMpmRegister,
+                GetData, alproto, progress);
+    }
+    if (toclient) {
+        DetectAppLayerInspectEngineRegister2(name, alproto, SIG_FLAG_TOCLIENT, progress,
+                DetectEngineInspectBufferGeneric, GetData);
+        DetectAppLayerMpmRegister2(name, SIG_FLAG_TOCLIENT, 2, PrefilterGenericMpmRegister,
+                GetData, alproto, progress);
+    }
+    DetectBufferTypeSetDescriptionByName(name, desc);
+    return DetectBufferTypeGetByName(name);
+}
+
+/**
+ * \brief Get the inspection buffer for a transaction, filling it from
+ *        the GetBuf callback if needed.
+ */
+InspectionBuffer *DetectHelperGetData(struct DetectEngineThreadCtx_ *det_ctx,
+        const DetectEngineTransforms *transforms, Flow *f, const uint8_t flow_flags, void *txv,
+        const int list_id,
+        bool (*GetBuf)(void *txv, const uint8_t flow_flags, const uint8_t **buf,
+                uint32_t *buf_len))
+{
+    InspectionBuffer *buffer = InspectionBufferGet(det_ctx, list_id);
+    if (buffer->inspect == NULL) {
+        const uint8_t *b = NULL;
+        uint32_t b_len = 0;
+
+        if (!GetBuf(txv, flow_flags, &b, &b_len))
+            return NULL;
+        if (b == NULL || b_len == 0)
+            return NULL;
+
+        InspectionBufferSetup(det_ctx, list_id, buffer, b, b_len);
+        InspectionBufferApplyTransforms(buffer, transforms);
+    }
+    return buffer;
+}
+
+/**
+ * \brief Get the inspection buffer number local_id of a transaction for
+ *        a multi buffer, filling it from the GetBuf callback if needed.
+ *
+ * \retval buffer or NULL if the transaction has no buffer local_id
+ */
+static InspectionBuffer *DetectHelperGetMultiData(DetectEngineThreadCtx *det_ctx,
+        const DetectEngineTransforms *transforms, const uint8_t flow_flags, void *txv,
+        const int list_id, const uint32_t local_id, const bool first,
+        DetectHelperGetMultiBufFn GetBuf)
+{
+    InspectionBuffer *buffer = InspectionBufferMultipleForListGet(det_ctx, list_id, local_id);
+    if (buffer == NULL)
+        return NULL;
+    if (!first && buffer->inspect != NULL)
+        return buffer;
+
+    const uint8_t *b = NULL;
+    uint32_t b_len = 0;
+    if (!GetBuf(txv, flow_flags, local_id, &b, &b_len))
+        return NULL;
+
+    InspectionBufferSetupMulti(buffer, transforms, b, b_len);
+    return buffer;
+}
+
+/* The GetBuf callback of a multi buffer is registered in place of the
+ * GetData callback of its engines, to be cast back by them. */
+
+static int DetectHelperInspectMultiBuffer(DetectEngineCtx *de_ctx,
+        DetectEngineThreadCtx *det_ctx, const struct DetectEngineAppInspectionEngine_ *engine,
+        const Signature *s, Flow *f, uint8_t flags, void *alstate, void *txv, uint64_t tx_id)
+{
+    DetectHelperGetMultiBufFn GetBuf = (DetectHelperGetMultiBufFn)engine->v2.GetData;
+    const DetectEngineTransforms *transforms = NULL;
+    if (!engine->mpm) {
+        transforms = engine->v2.transforms;
+    }
+
+    for (uint32_t local_id = 0;; local_id++) {
+        InspectionBuffer *buffer = DetectHelperGetMultiData(
+                det_ctx, transforms, flags, txv, engine->sm_list, local_id, false, GetBuf);
+        if (buffer == NULL || buffer->inspect == NULL)
+            break;
+
+        det_ctx->buffer_offset = 0;
+        det_ctx->discontinue_matching = 0;
+        det_ctx->inspection_recursion_counter = 0;
+
+        const int match = DetectEngineContentInspection(de_ctx, det_ctx, s, engine->smd, NULL, f,
+                buffer->inspect, buffer->inspect_len, buffer->inspect_offset,
+                DETECT_CI_FLAGS_SINGLE, DETECT_ENGINE_CONTENT_INSPECTION_MODE_STATE);
+        if (match == 1) {
+            return DETECT_ENGINE_INSPECT_SIG_MATCH;
+        }
+    }
+    return DETECT_ENGINE_INSPECT_SIG_NO_MATCH;
+}
+
+typedef struct PrefilterMpmHelperMultiBuf {
+    int list_id;
+    const MpmCtx *mpm_ctx;
+    const DetectEngineTransforms *transforms;
+    DetectHelperGetMultiBufFn GetBuf;
+} PrefilterMpmHelperMultiBuf;
+
+static void PrefilterTxHelperMultiBuf(DetectEngineThreadCtx *det_ctx, const void *pectx,
+        Packet *p, Flow *f, void *txv, const uint64_t idx, const uint8_t flags)
+{
+    const PrefilterMpmHelperMultiBuf *ctx = (const PrefilterMpmHelperMultiBuf *)pectx;
+    const MpmCtx *mpm_ctx = ctx->mpm_ctx;
+
+    for (uint32_t local_id = 0;; local_id++) {
+        InspectionBuffer *buffer = DetectHelperGetMultiData(
+                det_ctx, ctx->transforms, flags, txv, ctx->list_id, local_id, true, ctx->GetBuf);
+        if (buffer == NULL)
+            break;
+
+        if (buffer->inspect_len >= mpm_ctx->minlen) {
+            (void)mpm_table[mpm_ctx->mpm_type].Search(mpm_ctx, &det_ctx->mtcu, &det_ctx->pmq,
+                    buffer->inspect, buffer->inspect_len);
+        }
+    }
+}
+
+static void PrefilterMpmHelperMultiBufFree(void *ptr)
+{
+    SCFree(ptr);
+}
+
+static int PrefilterMpmHelperMultiBufRegister(DetectEngineCtx *de_ctx, SigGroupHead *sgh,
+        MpmCtx *mpm_ctx, const DetectBufferMpmRegistery *mpm_reg, int list_id)
+{
+    PrefilterMpmHelperMultiBuf *pectx = SCCalloc(1, sizeof(*pectx));
+    if (pectx == NULL)
+        return -1;
+    pectx->list_id = list_id;
+    pectx->mpm_ctx = mpm_ctx;
+    pectx->transforms = &mpm_reg->transforms;
+    pectx->GetBuf = (DetectHelperGetMultiBufFn)mpm_reg->app_v2.GetData;
+
+    return PrefilterAppendTxEngine(de_ctx, sgh, PrefilterTxHelperMultiBuf,
+            mpm_reg->app_v2.alproto, mpm_reg->app_v2.tx_min_progress, pectx,
+            PrefilterMpmHelperMultiBufFree, mpm_reg->pname);
+}
+
+/**
+ * \brief Register a sticky buffer with inspection and mpm engines, for
+ *        which a transaction can have several buffers, inspected one by
+ *        one. GetBuf returns the buffers by index, from 0, and false once
+ *        past the last one.
+ *
+ * \retval list id
+ */
+int DetectHelperMultiBufferMpmRegister(const char *name, const char *desc, AppProto alproto,
+        bool toclient, bool toserver, int progress, DetectHelperGetMultiBufFn GetBuf)
+{
+    InspectionBufferGetDataPtr GetData = (InspectionBufferGetDataPtr)GetBuf;
+    if (toserver) {
+        DetectAppLayerInspectEngineRegister2(name, alproto, SIG_FLAG_TOSERVER, progress,
+                DetectHelperInspectMultiBuffer, GetData);
+        DetectAppLayerMpmRegister2(name, SIG_FLAG_TOSERVER, 2, PrefilterMpmHelperMultiBufRegister,
+                GetData, alproto, progress);
+    }
+    if (toclient) {
+        DetectAppLayerInspectEngineRegister2(name, alproto, SIG_FLAG_TOCLIENT, progress,
+                DetectHelperInspectMultiBuffer, GetData);
+        DetectAppLayerMpmRegister2(name, SIG_FLAG_TOCLIENT, 2, PrefilterMpmHelperMultiBufRegister,
+                GetData, alproto, progress);
+    }
+    DetectBufferTypeSetDescriptionByName(name, desc);
+    return DetectBufferTypeGetByName(name);
+}
+
+/**
+ * \brief Append a match with context ctx for keyword kw_id to list list_id.
+ *
+ * On failure the caller keeps ownership of ctx.
+ */
+int DetectHelperSigMatchAppend(Signature *s, uint16_t kw_id, void *ctx, int list_id)
+{
+    SigMatch *sm = SigMatchAlloc();
+    if (sm == NULL)
+        return -1;
+
+    sm->type = kw_id;
+    sm->ctx = (SigMatchCtx *)ctx;
+    SigMatchAppendSMToList(s, sm, list_id);
+    return 0;
+}
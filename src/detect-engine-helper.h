@@ -0,0 +1,47 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+/**
+ * \file
+ *
+ * Helpers to register detection keywords and buffers implemented in Rust.
+ */
+
+#ifndef __DETECT_ENGINE_HELPER_H__
+#define __DETECT_ENGINE_HELPER_H__
+
+#include "app-layer-protos.h"
+#include "detect.h"
+#include "rust.h"
+
+int DetectHelperKeywordRegister(const SCSigTableElmt *kw);
+int DetectHelperBufferRegister(const char *name, AppProto alproto, bool toclient, bool toserver,
+        int progress);
+int DetectHelperBufferMpmRegister(const char *name, const char *desc, AppProto alproto,
+        bool toclient, bool toserver, int progress, InspectionBufferGetDataPtr GetData);
+InspectionBuffer *DetectHelperGetData(struct DetectEngineThreadCtx_ *det_ctx,
+        const DetectEngineTransforms *transforms, Flow *f, const uint8_t flow_flags, void *txv,
+        const int list_id,
+        bool (*GetBuf)(void *txv, const uint8_t flow_flags, const uint8_t **buf,
+                uint32_t *buf_len));
+typedef bool (*DetectHelperGetMultiBufFn)(void *txv, const uint8_t flow_flags,
+        const uint32_t local_id, const uint8_t **buf, uint32_t *buf_len);
+int DetectHelperMultiBufferMpmRegister(const char *name, const char *desc, AppProto alproto,
+        bool toclient, bool toserver, int progress, DetectHelperGetMultiBufFn GetBuf);
+int DetectHelperSigMatchAppend(Signature *s, uint16_t kw_id, void *ctx, int list_id);
+
+#endif /* __DETECT_ENGINE_HELPER_H__ */
@@ -197,7 +197,6 @@
 #include "detect-rfb-sectype.h"
 #include "detect-rfb-name.h"
 #include "detect-target.h"
-#include "detect-template-rust-buffer.h"
 #include "detect-snmp-version.h"
 #include "detect-snmp-community.h"
 #include "detect-snmp-pdu_type.h"
@@ -207,14 +206,6 @@
 #include "detect-mqtt-protocol-version.h"
 #include "detect-mqtt-reason-code.h"
 #include "detect-mqtt-connect-flags.h"
-#include "detect-mqtt-connect-clientid.h"
-#include "detect-mqtt-connect-username.h"
-#include "detect-mqtt-connect-password.h"
-#include "detect-mqtt-connect-willtopic.h"
-#include "detect-mqtt-connect-willmessage.h"
-#include "detect-mqtt-connack-sessionpresent.h"
-#include "detect-mqtt-publish-topic.h"
-#include "detect-mqtt-publish-message.h"
 #include "detect-mqtt-subscribe-topic.h"
 #include "detect-mqtt-unsubscribe-topic.h"
 
@@ -293,6 +284,8 @@
 #include "util-mpm-ac.h"
 #include "runmodes.h"
 
+#include "rust.h"
+
 static void PrintFeatureList(const SigTableElmt *e, char sep)
 {
     const uint16_t flags = e->flags;
@@ -492,6 +485,7 @@ void SigTableSetup(void)
     DetectModbusRegister();
     DetectCipServiceRegister();
     DetectEnipCommandRegister();
+    DetectCipPathRegister();
     DetectDNP3Register();
 
     DetectIkeExchTypeRegister();
@@ -623,7 +617,6 @@ void SigTableSetup(void)
     DetectRfbSectypeRegister();
     DetectRfbNameRegister();
     DetectTargetRegister();
-    DetectTemplateRustBufferRegister();
     DetectSNMPVersionRegister();
     DetectSNMPCommunityRegister();
     DetectSNMPPduTypeRegister();
@@ -633,14 +626,6 @@ void SigTableSetup(void)
     DetectMQTTProtocolVersionRegister();
     DetectMQTTReasonCodeRegister();
     DetectMQTTConnectFlagsRegister();
-    DetectMQTTConnectClientIDRegister();
-    DetectMQTTConnectUsernameRegister();
-    DetectMQTTConnectPasswordRegister();
-    DetectMQTTConnectWillTopicRegister();
-    DetectMQTTConnectWillMessageRegister();
-    DetectMQTTConnackSessionPresentRegister();
-    DetectMQTTPublishTopicRegister();
-    DetectMQTTPublishMessageRegister();
     DetectMQTTSubscribeTopicRegister();
     DetectMQTTUnsubscribeTopicRegister();
 
@@ -657,6 +642,9 @@ void SigTableSetup(void)
     DetectTransformPcrexformRegister();
     DetectTransformUrlDecodeRegister();
 
+    /* keywords implemented in Rust */
+    rs_detect_register_keywords();
+
     /* close keyword registration */
     DetectBufferTypeCloseRegistration();
 }
@@ -228,6 +228,7 @@ enum DetectKeywordId {
     DETECT_AL_MODBUS,
     DETECT_CIPSERVICE,
     DETECT_ENIPCOMMAND,
+    DETECT_ENIP_CIP_PATH,
 
     DETECT_AL_DNP3DATA,
     DETECT_AL_DNP3FUNC,
@@ -264,7 +265,6 @@ enum DetectKeywordId {
     DETECT_TCPMSS,
     DETECT_FTPDATA,
     DETECT_TARGET,
-    DETECT_AL_TEMPLATE_RUST_BUFFER,
     DETECT_AL_SNMP_VERSION,
     DETECT_AL_SNMP_COMMUNITY,
     DETECT_AL_SNMP_PDU_TYPE,
@@ -274,14 +274,6 @@ enum DetectKeywordId {
     DETECT_AL_MQTT_PROTOCOL_VERSION,
     DETECT_AL_MQTT_REASON_CODE,
     DETECT_AL_MQTT_CONNECT_FLAGS,
-    DETECT_AL_MQTT_CONNECT_CLIENTID,
-    DETECT_AL_MQTT_CONNECT_USERNAME,
-    DETECT_AL_MQTT_CONNECT_PASSWORD,
-    DETECT_AL_MQTT_CONNECT_WILLTOPIC,
-    DETECT_AL_MQTT_CONNECT_WILLMESSAGE,
-    DETECT_AL_MQTT_CONNACK_SESSION_PRESENT,
-    DETECT_AL_MQTT_PUBLISH_TOPIC,
-    DETECT_AL_MQTT_PUBLISH_MESSAGE,
     DETECT_AL_MQTT_SUBSCRIBE_TOPIC,
     DETECT_AL_MQTT_UNSUBSCRIBE_TOPIC,
     DETECT_AL_TEMPLATE_BUFFER,
@@ -310,9 +302,14 @@ enum DetectKeywordId {
     DETECT_AL_IKE_KEY_EXCHANGE,
 
     /* make sure this stays last */
-    DETECT_TBLSIZE,
+    DETECT_TBLSIZE_STATIC,
 };
 
+/** number of sigmatch_table slots for keywords registered at runtime,
+ *  see detect-engine-helper.c */
+#define DETECT_TBLSIZE_DYNAMIC 128
+#define DETECT_TBLSIZE (DETECT_TBLSIZE_STATIC + DETECT_TBLSIZE_DYNAMIC)
+
 int SigTableList(const char *keyword);
 void SigTableSetup(void);
 void SigTableRegisterTests(void);
@@ -127,9 +127,9 @@ void DetectFilemagicRegister(void)
     g_file_match_list_id = DetectBufferTypeRegister("files");
 
     AppProto protos_ts[] = { ALPROTO_HTTP1, ALPROTO_SMTP, ALPROTO_FTP, ALPROTO_SMB, ALPROTO_NFS,
-        ALPROTO_HTTP2, 0 };
+        ALPROTO_HTTP2, ALPROTO_TFTP, ALPROTO_MQTT, 0 };
     AppProto protos_tc[] = { ALPROTO_HTTP1, ALPROTO_FTP, ALPROTO_SMB, ALPROTO_NFS, ALPROTO_HTTP2,
-        0 };
+        ALPROTO_TFTP, ALPROTO_MQTT, 0 };
 
     for (int i = 0; protos_ts[i] != 0; i++) {
         DetectAppLayerInspectEngineRegister2("file.magic", protos_ts[i],
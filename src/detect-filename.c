@@ -120,6 +120,16 @@ void DetectFilenameRegister(void)
     DetectAppLayerInspectEngineRegister2(
             "files", ALPROTO_SMB, SIG_FLAG_TOCLIENT, 0, DetectFileInspectGeneric, NULL);
 
+    DetectAppLayerInspectEngineRegister2(
+            "files", ALPROTO_TFTP, SIG_FLAG_TOSERVER, 0, DetectFileInspectGeneric, NULL);
+    DetectAppLayerInspectEngineRegister2(
+            "files", ALPROTO_TFTP, SIG_FLAG_TOCLIENT, 0, DetectFileInspectGeneric, NULL);
+
+    DetectAppLayerInspectEngineRegister2(
+            "files", ALPROTO_MQTT, SIG_FLAG_TOSERVER, 0, DetectFileInspectGeneric, NULL);
+    DetectAppLayerInspectEngineRegister2(
+            "files", ALPROTO_MQTT, SIG_FLAG_TOCLIENT, 0, DetectFileInspectGeneric, NULL);
+
     //this is used by filestore
     DetectAppLayerInspectEngineRegister2("files", ALPROTO_HTTP2, SIG_FLAG_TOSERVER,
             HTTP2StateDataClient, DetectFileInspectGeneric, NULL);
@@ -129,9 +139,9 @@ void DetectFilenameRegister(void)
     g_file_match_list_id = DetectBufferTypeGetByName("files");
 
     AppProto protos_ts[] = { ALPROTO_HTTP1, ALPROTO_SMTP, ALPROTO_FTP, ALPROTO_FTPDATA, ALPROTO_SMB,
-        ALPROTO_NFS, 0 };
+        ALPROTO_NFS, ALPROTO_TFTP, ALPROTO_MQTT, 0 };
     AppProto protos_tc[] = { ALPROTO_HTTP1, ALPROTO_FTP, ALPROTO_FTPDATA, ALPROTO_SMB, ALPROTO_NFS,
-        0 };
+        ALPROTO_TFTP, ALPROTO_MQTT, 0 };
 
     for (int i = 0; protos_ts[i] != 0; i++) {
         DetectAppLayerInspectEngineRegister2("file.name", protos_ts[i],
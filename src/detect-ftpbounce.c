@@ -45,7 +45,6 @@
 #include "threads.h"
 #include "detect-ftpbounce.h"
 #include "stream-tcp.h"
-#include "util-byte.h"
 
 static int DetectFtpbounceALMatch(DetectEngineThreadCtx *,
         Flow *, uint8_t, void *, void *,
@@ -91,90 +90,6 @@ static int InspectFtpRequest(DetectEngineCtx *de_ctx, DetectEngineThreadCtx *det
             de_ctx, det_ctx, s, engine->smd, f, flags, alstate, txv, tx_id);
 }
 
-/**
- * \brief This function is used to match ftpbounce attacks
- *
- * \param payload Payload of the PORT command
- * \param payload_len Length of the payload
- * \param ip_orig IP source to check the ftpbounce condition
- * \param offset offset to the arguments of the PORT command
- *
- * \retval 1 if ftpbounce detected, 0 if not
- */
-static int DetectFtpbounceMatchArgs(uint8_t *payload, uint16_t payload_len,
-                             uint32_t ip_orig, uint16_t offset)
-{
-    SCEnter();
-    SCLogDebug("Checking ftpbounce condition");
-    char *c = NULL;
-    uint16_t i = 0;
-    int octet = 0;
-    int octet_ascii_len = 0;
-    int noctet = 0;
-    uint32_t ip = 0;
-    /* PrintRawDataFp(stdout, payload, payload_len); */
-
-    if (payload_len < 7) {
-        /* we need at least a differet ip address
-         * in the format 1,2,3,4,x,y where x,y is the port
-         * in two byte representation so let's look at
-         * least for the IP octets in comma separated */
-        return 0;
-    }
-
-    if (offset + 7 >= payload_len)
-        return 0;
-
-    c =(char*) payload;
-    if (c == NULL) {
-        SCLogDebug("No payload to check");
-        return 0;
-    }
-
-    i = offset;
-    /* Search for the first IP octect(Skips "PORT ") */
-    while (i < payload_len && !isdigit((unsigned char)c[i])) i++;
-
-    for (;i < payload_len && octet_ascii_len < 4 ;i++) {
-        if (isdigit((unsigned char)c[i])) {
-            octet =(c[i] - '0') + octet * 10;
-            octet_ascii_len++;
-        } else {
-            if (octet > 256) {
-                SCLogDebug("Octet not in ip format");
-                return 0;
-            }
-
-            if (isspace((unsigned char)c[i]))
-                while (i < payload_len && isspace((unsigned char)c[i]) ) i++;
-
-            if (i < payload_len && c[i] == ',') { /* we have an octet */
-                noctet++;
-                octet_ascii_len = 0;
-                ip =(ip << 8) + octet;
-                octet = 0;
-            } else {
-                SCLogDebug("Unrecognized character '%c'", c[i]);
-                return 0;
-            }
-            if (noctet == 4) {
-                /* Different IP than src, ftp bounce scan */
-                ip = SCByteSwap32(ip);
-
-                if (ip != ip_orig) {
-                    SCLogDebug("Different ip, so Matched ip:%d <-> ip_orig:%d",
-                               ip, ip_orig);
-                    return 1;
-                }
-                SCLogDebug("Same ip, so no match here");
-                return 0;
-            }
-        }
-    }
-    SCLogDebug("No match");
-    return 0;
-}
-
 /**
  * \brief This function is used to check matches from the FTP App Layer Parser
  *
@@ -193,20 +108,20 @@ static int DetectFtpbounceALMatch(DetectEngineThreadCtx *det_ctx,
 {
     SCEnter();
 
-    FtpState *ftp_state = (FtpState *)state;
-    if (ftp_state == NULL) {
-        SCLogDebug("no ftp state, no match");
+    uint32_t ip = 0;
+    if (!rs_ftp_tx_get_port_ipv4(txv, &ip)) {
+        SCLogDebug("no PORT command, no match");
         SCReturnInt(0);
     }
 
-    int ret = 0;
-    if (ftp_state->command == FTP_COMMAND_PORT) {
-        ret = DetectFtpbounceMatchArgs(ftp_state->port_line,
-                  ftp_state->port_line_len, f->src.address.address_un_data32[0],
-                  ftp_state->arg_offset);
+    /* Different IP than src, ftp bounce scan */
+    if (ip != f->src.address.address_un_data32[0]) {
+        SCLogDebug("Different ip, so Matched ip:%d <-> ip_orig:%d",
+                   ip, f->src.address.address_un_data32[0]);
+        SCReturnInt(1);
     }
-
-    SCReturnInt(ret);
+    SCLogDebug("Same ip, so no match here");
+    SCReturnInt(0);
 }
 
 /**
@@ -375,14 +290,13 @@ static int DetectFtpbounceTestALMatch02(void)
 
     FLOWLOCK_UNLOCK(&f);
 
-    FtpState *ftp_state = f.alstate;
-    if (ftp_state == NULL) {
+    if (f.alstate == NULL) {
         SCLogDebug("no ftp state: ");
         result = 0;
         goto end;
     }
 
-    if (ftp_state->command != FTP_COMMAND_PORT) {
+    if (rs_ftp_state_get_tx_count(f.alstate) != 1) {
         SCLogDebug("expected command port not detected");
         result = 0;
         goto end;
@@ -519,14 +433,13 @@ static int DetectFtpbounceTestALMatch03(void)
     }
     FLOWLOCK_UNLOCK(&f);
 
-    FtpState *ftp_state = f.alstate;
-    if (ftp_state == NULL) {
+    if (f.alstate == NULL) {
         SCLogDebug("no ftp state: ");
         result = 0;
         goto end;
     }
 
-    if (ftp_state->command != FTP_COMMAND_PORT) {
+    if (rs_ftp_state_get_tx_count(f.alstate) != 1) {
         SCLogDebug("expected command port not detected");
         result = 0;
         goto end;
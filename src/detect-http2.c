@@ -43,6 +43,7 @@ void DetectHTTP2priorityRegisterTests (void);
 void DetectHTTP2windowRegisterTests (void);
 void DetectHTTP2settingsRegisterTests (void);
 void DetectHTTP2sizeUpdateRegisterTests (void);
+void DetectHTTP2headerRegisterTests (void);
 #endif
 
 /* prototypes */
@@ -93,6 +94,8 @@ static int DetectEngineInspectHttp2HeaderName(
                                               Flow *f, uint8_t flags, void *alstate, void *txv, uint64_t tx_id);
 
 static int DetectHTTP2headerSetup(DetectEngineCtx *de_ctx, Signature *s, const char *arg);
+static void DetectHTTP2headerNameFilter(InspectionBuffer *buffer, void *options);
+static void DetectHTTP2headerNameFree(DetectEngineCtx *de_ctx, void *ptr);
 static int PrefilterMpmHttp2HeaderRegister(DetectEngineCtx *de_ctx,
                                                SigGroupHead *sgh, MpmCtx *mpm_ctx,
                                                const DetectBufferMpmRegistery *mpm_reg, int list_id);
@@ -215,10 +218,17 @@ void DetectHttp2Register(void)
     g_http2_header_name_buffer_id = DetectBufferTypeGetByName("http2_header_name");
 
     sigmatch_table[DETECT_HTTP2_HEADER].name = "http2.header";
-    sigmatch_table[DETECT_HTTP2_HEADER].desc = "sticky buffer to match on one HTTP2 header name and value";
+    sigmatch_table[DETECT_HTTP2_HEADER].desc = "sticky buffer to match on one HTTP2 header name and value, "
+                                               "or on the values of the header named by the option";
     sigmatch_table[DETECT_HTTP2_HEADER].url = "/rules/http2-keywords.html#header";
     sigmatch_table[DETECT_HTTP2_HEADER].Setup = DetectHTTP2headerSetup;
-    sigmatch_table[DETECT_HTTP2_HEADER].flags |= SIGMATCH_NOOPT | SIGMATCH_INFO_STICKY_BUFFER;
+    /* the header name option is carried as a transform of the buffer */
+    sigmatch_table[DETECT_HTTP2_HEADER].Transform = DetectHTTP2headerNameFilter;
+    sigmatch_table[DETECT_HTTP2_HEADER].Free = DetectHTTP2headerNameFree;
+    sigmatch_table[DETECT_HTTP2_HEADER].flags |= SIGMATCH_OPTIONAL_OPT | SIGMATCH_INFO_STICKY_BUFFER;
+#ifdef UNITTESTS
+    sigmatch_table[DETECT_HTTP2_HEADER].RegisterTests = DetectHTTP2headerRegisterTests;
+#endif
 
     DetectAppLayerMpmRegister2("http2_header", SIG_FLAG_TOCLIENT, 2,
                                PrefilterMpmHttp2HeaderRegister, NULL,
@@ -802,6 +812,35 @@ static int DetectEngineInspectHttp2HeaderName(
     return DETECT_ENGINE_INSPECT_SIG_NO_MATCH;
 }
 
+/**
+ * \brief The header name option of http2.header is stored as the first
+ *        transform of the buffer, giving each such signature its own list.
+ *        The buffer is already restricted to the values of the header when
+ *        it is set up, so the transform itself does nothing.
+ */
+static void DetectHTTP2headerNameFilter(InspectionBuffer *buffer, void *options)
+{
+}
+
+static void DetectHTTP2headerNameFree(DetectEngineCtx *de_ctx, void *ptr)
+{
+    SCFree(ptr);
+}
+
+/**
+ * \brief Get the header name of a http2.header buffer with a name option
+ *
+ * \retval name or NULL if the buffer has all the headers
+ */
+static const char *DetectHTTP2headerGetName(const DetectEngineTransforms *transforms)
+{
+    if (transforms != NULL && transforms->cnt > 0 &&
+            transforms->transforms[0].transform == DETECT_HTTP2_HEADER) {
+        return (const char *)transforms->transforms[0].options;
+    }
+    return NULL;
+}
+
 static int DetectHTTP2headerSetup(DetectEngineCtx *de_ctx, Signature *s, const char *arg)
 {
     if (DetectBufferSetActiveList(s, g_http2_header_buffer_id) < 0)
@@ -810,6 +849,21 @@ static int DetectHTTP2headerSetup(DetectEngineCtx *de_ctx, Signature *s, const c
     if (DetectSignatureSetAppProto(s, ALPROTO_HTTP2) != 0)
         return -1;
 
+    if (arg == NULL || strlen(arg) == 0)
+        return 0;
+
+    /* header names are lowercase in HTTP2 */
+    char *name = SCStrdup(arg);
+    if (name == NULL)
+        return -1;
+    for (char *c = name; *c != '\0'; c++) {
+        *c = u8_tolower(*c);
+    }
+    if (DetectSignatureAddTransform(s, DETECT_HTTP2_HEADER, name) < 0) {
+        SCFree(name);
+        return -1;
+    }
+
     return 0;
 }
 
@@ -821,7 +875,7 @@ static void PrefilterMpmHttp2HeaderFree(void *ptr)
 static InspectionBuffer *GetHttp2HeaderData(DetectEngineThreadCtx *det_ctx,
         const uint8_t flags, const DetectEngineTransforms *transforms,
         Flow *_f, const struct MpmListIdDataArgs *cbdata,
-        int list_id, bool first)
+        int list_id, bool first, const char *name)
 {
     SCEnter();
 
@@ -835,8 +889,13 @@ static InspectionBuffer *GetHttp2HeaderData(DetectEngineThreadCtx *det_ctx,
     uint32_t b_len = 0;
     const uint8_t *b = NULL;
 
-    if (rs_http2_tx_get_header(cbdata->txv, flags, cbdata->local_id, &b, &b_len) != 1)
+    if (name != NULL) {
+        if (rs_http2_tx_get_nth_header_value(cbdata->txv, flags, (const uint8_t *)name,
+                    strlen(name), cbdata->local_id, &b, &b_len) != 1)
+            return NULL;
+    } else if (rs_http2_tx_get_header(cbdata->txv, flags, cbdata->local_id, &b, &b_len) != 1) {
         return NULL;
+    }
     if (b == NULL || b_len == 0)
         return NULL;
 
@@ -855,6 +914,7 @@ static void PrefilterTxHttp2Header(DetectEngineThreadCtx *det_ctx,
     const PrefilterMpmListId *ctx = (const PrefilterMpmListId *)pectx;
     const MpmCtx *mpm_ctx = ctx->mpm_ctx;
     const int list_id = ctx->list_id;
+    const char *name = DetectHTTP2headerGetName(ctx->transforms);
 
     uint32_t local_id = 0;
 
@@ -863,7 +923,7 @@ static void PrefilterTxHttp2Header(DetectEngineThreadCtx *det_ctx,
 
         struct MpmListIdDataArgs cbdata = { local_id, txv };
         InspectionBuffer *buffer = GetHttp2HeaderData(det_ctx, flags, ctx->transforms,
-                f, &cbdata, list_id, true);
+                f, &cbdata, list_id, true, name);
         if (buffer == NULL)
             break;
 
@@ -905,11 +965,12 @@ static int DetectEngineInspectHttp2Header(
     if (!engine->mpm) {
         transforms = engine->v2.transforms;
     }
+    const char *name = DetectHTTP2headerGetName(engine->v2.transforms);
 
     while (1) {
         struct MpmListIdDataArgs cbdata = { local_id, txv, };
         InspectionBuffer *buffer = GetHttp2HeaderData(det_ctx, flags,
-                transforms, f, &cbdata, engine->sm_list, false);
+                transforms, f, &cbdata, engine->sm_list, false, name);
 
         if (buffer == NULL || buffer->inspect == NULL)
             break;
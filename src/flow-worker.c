@@ -434,6 +434,7 @@ static void FlowWorkerFlowTimeout(ThreadVars *tv, Packet *p, FlowWorkerThreadDat
     FLOWWORKER_PROFILING_END(p, PROFILE_FLOWWORKER_TCPPRUNE);
 
     /* run tx cleanup last */
+    AppLayerParserStatePurge(p->flow, &p->ts);
     AppLayerParserTransactionsCleanup(p->flow);
 
     FlowDeReference(&p->flow);
@@ -570,6 +571,7 @@ static TmEcode FlowWorker(ThreadVars *tv, Packet *p, void *data)
         }
 
         /* run tx cleanup last */
+        AppLayerParserStatePurge(p->flow, &p->ts);
         AppLayerParserTransactionsCleanup(p->flow);
 
         Flow *f = p->flow;
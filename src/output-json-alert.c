@@ -71,6 +71,21 @@
 #include "output-json-sip.h"
 #include "output-json-rfb.h"
 #include "output-json-mqtt.h"
+#include "output-json-quic.h"
+#include "output-json-ldap.h"
+#include "output-json-telnet.h"
+#include "output-json-llmnr.h"
+#include "output-json-nbns.h"
+#include "output-json-stratum.h"
+#include "output-json-kafka.h"
+#include "output-json-irc.h"
+#include "output-json-xmpp.h"
+#include "output-json-openvpn.h"
+#include "output-json-dtls.h"
+#include "output-json-rtp.h"
+#include "output-json-icap.h"
+#include "output-json-bittorrent.h"
+#include "output-json-template-rust.h"
 #include "output-json-ike.h"
 #include "output-json-modbus.h"
 
@@ -554,6 +569,96 @@ static void AlertAddAppLayer(const Packet *p, JsonBuilder *jb,
                 jb_restore_mark(jb, &mark);
             }
             break;
+        case ALPROTO_QUIC:
+            jb_get_mark(jb, &mark);
+            if (!JsonQuicAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_LDAP:
+            jb_get_mark(jb, &mark);
+            if (!JsonLdapAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_TELNET:
+            jb_get_mark(jb, &mark);
+            if (!JsonTelnetAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_LLMNR:
+            jb_get_mark(jb, &mark);
+            if (!JsonLlmnrAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_NBNS:
+            jb_get_mark(jb, &mark);
+            if (!JsonNbnsAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_STRATUM:
+            jb_get_mark(jb, &mark);
+            if (!JsonStratumAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_KAFKA:
+            jb_get_mark(jb, &mark);
+            if (!JsonKafkaAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_IRC:
+            jb_get_mark(jb, &mark);
+            if (!JsonIrcAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_XMPP:
+            jb_get_mark(jb, &mark);
+            if (!JsonXmppAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_OPENVPN:
+            jb_get_mark(jb, &mark);
+            if (!JsonOpenVpnAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_DTLS:
+            jb_get_mark(jb, &mark);
+            if (!JsonDtlsAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_RTP:
+            jb_get_mark(jb, &mark);
+            if (!JsonRtpAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_ICAP:
+            jb_get_mark(jb, &mark);
+            if (!JsonIcapAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_BITTORRENT:
+            jb_get_mark(jb, &mark);
+            if (!JsonBitTorrentAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
+        case ALPROTO_TEMPLATE_RUST:
+            jb_get_mark(jb, &mark);
+            if (!JsonTemplateRustAddMetadata(p->flow, tx_id, jb)) {
+                jb_restore_mark(jb, &mark);
+            }
+            break;
         default:
             break;
     }
@@ -0,0 +1,102 @@
+/* Copyright (C) 2024 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+
+/**
+ * \file
+ *
+ * Implement JSON/eve logging app-layer BitTorrent.
+ */
+
+#include "suricata-common.h"
+#include "debug.h"
+#include "detect.h"
+#include "pkt-var.h"
+#include "conf.h"
+
+#include "threads.h"
+#include "threadvars.h"
+#include "tm-threads.h"
+
+#include "util-unittest.h"
+#include "util-buffer.h"
+#include "util-debug.h"
+#include "util-byte.h"
+
+#include "output.h"
+#include "output-json.h"
+
+#include "app-layer.h"
+#include "app-layer-parser.h"
+
+#include "output-json-bittorrent.h"
+
+#include "rust.h"
+
+bool JsonBitTorrentAddMetadata(const Flow *f, uint64_t tx_id, JsonBuilder *js)
+{
+    void *state = FlowGetAppState(f);
+    if (state) {
+        BitTorrentTransaction *tx = AppLayerParserGetTx(f->proto, ALPROTO_BITTORRENT, state, tx_id);
+        if (tx) {
+            return rs_bittorrent_to_json(tx, js);
+        }
+    }
+
+    return false;
+}
+
+static int JsonBitTorrentLogger(ThreadVars *tv, void *thread_data,
+    const Packet *p, Flow *f, void *state, void *tx, uint64_t tx_id)
+{
+    OutputJsonThreadCtx *thread = thread_data;
+
+    JsonBuilder *js = CreateEveHeader((Packet *)p, LOG_DIR_FLOW, "bittorrent", NULL, thread->ctx);
+    if (unlikely(js == NULL)) {
+        return TM_ECODE_OK;
+    }
+
+    if (!rs_bittorrent_to_json(tx, js)) {
+        goto error;
+    }
+
+    OutputJsonBuilderBuffer(js, thread);
+    jb_free(js);
+
+    return TM_ECODE_OK;
+
+error:
+    jb_free(js);
+    return TM_ECODE_FAILED;
+}
+
+static OutputInitResult OutputBitTorrentLogInitSub(ConfNode *conf,
+    OutputCtx *parent_ctx)
+{
+    AppLayerParserRegisterLogger(IPPROTO_UDP, ALPROTO_BITTORRENT);
+    AppLayerParserRegisterLogger(IPPROTO_TCP, ALPROTO_BITTORRENT);
+    return OutputJsonLogInitSub(conf, parent_ctx);
+}
+
+void JsonBitTorrentLogRegister(void)
+{
+    /* Register as an eve sub-module. */
+    OutputRegisterTxSubModule(LOGGER_JSON_BITTORRENT, "eve-log", "JsonBitTorrentLog", "eve-log.bittorrent",
+            OutputBitTorrentLogInitSub, ALPROTO_BITTORRENT, JsonBitTorrentLogger, JsonLogThreadInit,
+            JsonLogThreadDeinit, NULL);
+
+    SCLogDebug("BitTorrent JSON logger registered.");
+}
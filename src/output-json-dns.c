@@ -120,8 +120,12 @@
 #define LOG_FORMAT_GROUPED     BIT_U64(60)
 #define LOG_FORMAT_DETAILED    BIT_U64(61)
 
+#define LOG_AUTHORITIES        BIT_U64(62)
+#define LOG_ADDITIONALS        BIT_U64(63)
+
 #define LOG_FORMAT_ALL (LOG_FORMAT_GROUPED|LOG_FORMAT_DETAILED)
-#define LOG_ALL_RRTYPES (~(uint64_t)(LOG_QUERIES|LOG_ANSWERS|LOG_FORMAT_DETAILED|LOG_FORMAT_GROUPED))
+#define LOG_SECTIONS_ALL (LOG_AUTHORITIES|LOG_ADDITIONALS)
+#define LOG_ALL_RRTYPES (~(uint64_t)(LOG_QUERIES|LOG_ANSWERS|LOG_FORMAT_DETAILED|LOG_FORMAT_GROUPED|LOG_SECTIONS_ALL))
 
 typedef enum {
     DNS_RRTYPE_A = 0,
@@ -594,6 +598,24 @@ static void JsonDnsLogInitFilters(LogDnsFileCtx *dnslog_ctx, ConfNode *conf)
                 } else {
                     dnslog_ctx->flags |= LOG_FORMAT_ALL;
                 }
+
+                ConfNode *sections;
+                if ((sections = ConfNodeLookupChild(conf, "sections")) != NULL) {
+                    dnslog_ctx->flags &= ~LOG_SECTIONS_ALL;
+                    ConfNode *field;
+                    TAILQ_FOREACH(field, &sections->head, next) {
+                        if (strcasecmp(field->val, "authorities") == 0) {
+                            dnslog_ctx->flags |= LOG_AUTHORITIES;
+                        } else if (strcasecmp(field->val, "additionals") == 0) {
+                            dnslog_ctx->flags |= LOG_ADDITIONALS;
+                        } else {
+                            SCLogWarning(SC_ERR_INVALID_ARGUMENT,
+                                    "eve-log dns: unknown section %s", field->val);
+                        }
+                    }
+                } else {
+                    dnslog_ctx->flags |= LOG_SECTIONS_ALL;
+                }
             }
         }
     }
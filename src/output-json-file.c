@@ -63,6 +63,7 @@
 #include "output-json-nfs.h"
 #include "output-json-smb.h"
 #include "output-json-http2.h"
+#include "output-json-mqtt.h"
 
 #include "app-layer-htp.h"
 #include "app-layer-htp-xff.h"
@@ -181,6 +182,12 @@ JsonBuilder *JsonBuildFileInfoRecord(const Packet *p, const File *ff, const bool
                 jb_restore_mark(js, &mark);
             }
             break;
+        case ALPROTO_MQTT:
+            jb_get_mark(js, &mark);
+            if (!JsonMQTTAddMetadata(p->flow, ff->txid, js)) {
+                jb_restore_mark(js, &mark);
+            }
+            break;
     }
 
     jb_set_string(js, "app_proto", AppProtoToString(p->flow->alproto));
@@ -162,6 +162,10 @@ static JsonBuilder *CreateEveHeaderFromFlow(const Flow *f)
             jb_set_uint(jb, "spi", f->esp.spi);
             break;
     }
+
+    /* real client and server of a flow behind a PROXY protocol proxy */
+    rs_proxy_flow_to_json(f, jb);
+
     return jb;
 }
 
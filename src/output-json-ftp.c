@@ -48,93 +48,6 @@
 #include "app-layer-ftp.h"
 #include "output-json-ftp.h"
 
-static void EveFTPLogCommand(Flow *f, FTPTransaction *tx, JsonBuilder *jb)
-{
-    /* Preallocate array objects to simplify failure case */
-    JsonBuilder *js_resplist = NULL;
-    if (!TAILQ_EMPTY(&tx->response_list)) {
-        js_resplist = jb_new_array();
-
-        if (unlikely(js_resplist == NULL)) {
-            return;
-        }
-    }
-    jb_set_string(jb, "command", tx->command_descriptor->command_name);
-    uint32_t min_length = tx->command_descriptor->command_length + 1; /* command + space */
-    if (tx->request_length > min_length) {
-        jb_set_string_from_bytes(jb,
-                "command_data",
-                (const uint8_t *)tx->request + min_length,
-                tx->request_length - min_length - 1);
-    }
-
-    if (!TAILQ_EMPTY(&tx->response_list)) {
-        int resp_code_cnt = 0;
-        int resp_cnt = 0;
-        FTPString *response;
-        bool is_cc_array_open = false;
-        TAILQ_FOREACH(response, &tx->response_list, next) {
-            /* handle multiple lines within the response, \r\n delimited */
-            uint8_t *where = response->str;
-            uint16_t length = response->len ? response->len -1 : 0;
-            uint16_t pos;
-            while ((pos = JsonGetNextLineFromBuffer((const char *)where, length)) != UINT16_MAX) {
-                uint16_t offset = 0;
-                /* Try to find a completion code for this line */
-                if (pos >= 3)  {
-                    /* Gather the completion code if present */
-                    if (isdigit(where[0]) && isdigit(where[1]) && isdigit(where[2])) {
-                        if (!is_cc_array_open) {
-                            jb_open_array(jb, "completion_code");
-                            is_cc_array_open = true;
-                        }
-                        jb_append_string_from_bytes(jb, (const uint8_t *)where, 3);
-                        resp_code_cnt++;
-                        offset = 4;
-                    }
-                }
-                /* move past 3 character completion code */
-                if (pos >= offset) {
-                    jb_append_string_from_bytes(js_resplist, (const uint8_t *)where + offset, pos - offset);
-                    resp_cnt++;
-                }
-
-                where += pos;
-                length -= pos;
-            }
-        }
-
-        if (is_cc_array_open) {
-            jb_close(jb);
-        }
-        if (resp_cnt) {
-            jb_close(js_resplist);
-            jb_set_object(jb, "reply", js_resplist);
-        }
-        jb_free(js_resplist);
-    }
-
-    if (tx->dyn_port) {
-        jb_set_uint(jb, "dynamic_port", tx->dyn_port);
-    }
-
-    if (tx->command_descriptor->command == FTP_COMMAND_PORT ||
-        tx->command_descriptor->command == FTP_COMMAND_EPRT) {
-        if (tx->active) {
-            JB_SET_STRING(jb, "mode", "active");
-        } else {
-            JB_SET_STRING(jb, "mode", "passive");
-        }
-    }
-
-    if (tx->done) {
-        JB_SET_STRING(jb, "reply_received", "yes");
-    } else {
-        JB_SET_STRING(jb, "reply_received", "no");
-    }
-}
-
-
 static int JsonFTPLogger(ThreadVars *tv, void *thread_data,
     const Packet *p, Flow *f, void *state, void *vtx, uint64_t tx_id)
 {
@@ -147,7 +60,6 @@ static int JsonFTPLogger(ThreadVars *tv, void *thread_data,
     } else {
         event_type = "ftp";
     }
-    FTPTransaction *tx = vtx;
 
     JsonBuilder *jb =
             CreateEveHeaderWithTxId(p, LOG_DIR_FLOW, event_type, NULL, tx_id, thread->ctx);
@@ -156,7 +68,7 @@ static int JsonFTPLogger(ThreadVars *tv, void *thread_data,
         if (f->alproto == ALPROTO_FTPDATA) {
             EveFTPDataAddMetadata(f, jb);
         } else {
-            EveFTPLogCommand(f, tx, jb);
+            rs_ftp_log_json(vtx, jb);
         }
 
         if (!jb_close(jb)) {
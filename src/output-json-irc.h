@@ -1,4 +1,4 @@
-/* Copyright (C) 2020 Open Information Security Foundation
+/* Copyright (C) 2021 Open Information Security Foundation
  *
  * You can copy, redistribute or modify this Program under the terms of
  * the GNU General Public License version 2 as published by the Free
@@ -17,13 +17,13 @@
 
 /**
  * \file
- *
- * \author Sascha Steinbiss <sascha@steinbiss.name>
  */
 
-#ifndef __DETECT_MQTT_CONNECT_PASSWORD_H__
-#define __DETECT_MQTT_CONNECT_PASSWORD_H__
+#ifndef __OUTPUT_JSON_IRC_H__
+#define __OUTPUT_JSON_IRC_H__
+
+void JsonIrcLogRegister(void);
 
-void DetectMQTTConnectPasswordRegister(void);
+bool JsonIrcAddMetadata(const Flow *f, uint64_t tx_id, JsonBuilder *js);
 
-#endif /* __DETECT_MQTT_CONNECT_PASSWORD_H__ */
+#endif /* __OUTPUT_JSON_IRC_H__ */
@@ -0,0 +1,188 @@
+/* Copyright (C) 2021 Open Information Security Foundation
+ *
+ * You can copy, redistribute or modify this Program under the terms of
+ * the GNU General Public License version 2 as published by the Free
+ * Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * version 2 along with this program; if not, write to the Free Software
+ * Foundation, Inc., 51 Franklin Street, Fifth Floor, Boston, MA
+ * 02110-1301, USA.
+ */
+/**
+ * \file
+ *
+ * Implement JSON/eve logging app-layer Telnet.
+ */
+
+#include "suricata-common.h"
+#include "debug.h"
+#include "detect.h"
+#include "pkt-var.h"
+#include "conf.h"
+
+#include "threads.h"
+#include "threadvars.h"
+#include "tm-threads.h"
+
+#include "util-unittest.h"
+#include "util-buffer.h"
+#include "util-debug.h"
+#include "util-byte.h"
+
+#include "output.h"
+#include "output-json.h"
+
+#include "app-layer.h"
+#include "app-layer-parser.h"
+
+#include "output-json-telnet.h"
+
+#include "rust.h"
+
+#define TELNET_LOG_PASSWORDS BIT_U32(0)
+#define TELNET_DEFAULTS 0
+
+typedef struct LogTelnetFileCtx_ {
+    uint32_t flags;
+    OutputJsonCtx *eve_ctx;
+} LogTelnetFileCtx;
+
+typedef struct LogTelnetLogThread_ {
+    LogTelnetFileCtx *telnetlog_ctx;
+    OutputJsonThreadCtx *ctx;
+} LogTelnetLogThread;
+
+bool JsonTelnetAddMetadata(const Flow *f, uint64_t tx_id, JsonBuilder *js)
+{
+    void *state = FlowGetAppState(f);
+    if (state) {
+        TelnetTransaction *tx = AppLayerParserGetTx(f->proto, ALPROTO_TELNET, state, tx_id);
+        if (tx) {
+            return rs_telnet_to_json(tx, TELNET_DEFAULTS, js);
+        }
+    }
+
+    return false;
+}
+
+static int JsonTelnetLogger(ThreadVars *tv, void *thread_data,
+    const Packet *p, Flow *f, void *state, void *tx, uint64_t tx_id)
+{
+    LogTelnetLogThread *thread = thread_data;
+
+    JsonBuilder *js = CreateEveHeader(p, LOG_DIR_FLOW, "telnet", NULL,
+            thread->telnetlog_ctx->eve_ctx);
+    if (unlikely(js == NULL)) {
+        return TM_ECODE_FAILED;
+    }
+
+    if (!rs_telnet_to_json(tx, thread->telnetlog_ctx->flags, js)) {
+        goto error;
+    }
+
+    OutputJsonBuilderBuffer(js, thread->ctx);
+    jb_free(js);
+
+    return TM_ECODE_OK;
+
+error:
+    jb_free(js);
+    return TM_ECODE_FAILED;
+}
+
+static void OutputTelnetLogDeInitCtxSub(OutputCtx *output_ctx)
+{
+    LogTelnetFileCtx *telnetlog_ctx = (LogTelnetFileCtx *)output_ctx->data;
+    SCFree(telnetlog_ctx);
+    SCFree(output_ctx);
+}
+
+static void JsonTelnetLogParseConfig(ConfNode *conf, LogTelnetFileCtx *telnetlog_ctx)
+{
+    telnetlog_ctx->flags = TELNET_DEFAULTS;
+    const char *query = ConfNodeLookupChildValue(conf, "passwords");
+    if (query != NULL && ConfValIsTrue(query)) {
+        telnetlog_ctx->flags |= TELNET_LOG_PASSWORDS;
+    }
+}
+
+static OutputInitResult OutputTelnetLogInitSub(ConfNode *conf,
+    OutputCtx *parent_ctx)
+{
+    OutputInitResult result = { NULL, false };
+    OutputJsonCtx *ajt = parent_ctx->data;
+
+    LogTelnetFileCtx *telnetlog_ctx = SCCalloc(1, sizeof(*telnetlog_ctx));
+    if (unlikely(telnetlog_ctx == NULL)) {
+        return result;
+    }
+    telnetlog_ctx->eve_ctx = ajt;
+
+    OutputCtx *output_ctx = SCCalloc(1, sizeof(*output_ctx));
+    if (unlikely(output_ctx == NULL)) {
+        SCFree(telnetlog_ctx);
+        return result;
+    }
+    output_ctx->data = telnetlog_ctx;
+    output_ctx->DeInit = OutputTelnetLogDeInitCtxSub;
+
+    JsonTelnetLogParseConfig(conf, telnetlog_ctx);
+
+    AppLayerParserRegisterLogger(IPPROTO_TCP, ALPROTO_TELNET);
+
+    result.ctx = output_ctx;
+    result.ok = true;
+    return result;
+}
+
+static TmEcode JsonTelnetLogThreadInit(ThreadVars *t, const void *initdata, void **data)
+{
+    LogTelnetLogThread *thread = SCCalloc(1, sizeof(*thread));
+    if (unlikely(thread == NULL)) {
+        return TM_ECODE_FAILED;
+    }
+
+    if (initdata == NULL) {
+        SCLogDebug("Error getting context for EveLogTelnet.  \"initdata\" is NULL.");
+        SCFree(thread);
+        return TM_ECODE_FAILED;
+    }
+
+    thread->telnetlog_ctx = ((OutputCtx *)initdata)->data;
+    thread->ctx = CreateEveThreadCtx(t, thread->telnetlog_ctx->eve_ctx);
+    if (unlikely(thread->ctx == NULL)) {
+        SCFree(thread);
+        return TM_ECODE_FAILED;
+    }
+
+    *data = (void *)thread;
+
+    return TM_ECODE_OK;
+}
+
+static TmEcode JsonTelnetLogThreadDeinit(ThreadVars *t, void *data)
+{
+    LogTelnetLogThread *thread = (LogTelnetLogThread *)data;
+    if (thread == NULL) {
+        return TM_ECODE_OK;
+    }
+    FreeEveThreadCtx(thread->ctx);
+    SCFree(thread);
+    return TM_ECODE_OK;
+}
+
+void JsonTelnetLogRegister(void)
+{
+    /* Register as an eve sub-module. */
+    OutputRegisterTxSubModule(LOGGER_JSON_TELNET, "eve-log", "JsonTelnetLog", "eve-log.telnet",
+            OutputTelnetLogInitSub, ALPROTO_TELNET, JsonTelnetLogger, JsonTelnetLogThreadInit,
+            JsonTelnetLogThreadDeinit, NULL);
+
+    SCLogDebug("Telnet JSON logger registered.");
+}
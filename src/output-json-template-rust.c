@@ -16,9 +16,8 @@
  */
 
 /*
- * TODO: Update \author in this file and in output-json-template.h.
- * TODO: Remove SCLogNotice statements, or convert to debug.
- * TODO: Implement your app-layers logging.
+ * TODO: Update \author in this file and in output-json-template-rust.h.
+ * TODO: Implement your app-layers logging in the logger.rs of the parser.
  */
 
 /**
@@ -26,7 +25,7 @@
  *
  * \author FirstName LastName <yourname@domain>
  *
- * Implement JSON/eve logging app-layer Template.
+ * Implement JSON/eve logging app-layer TemplateRust.
  */
 
 #include "suricata-common.h"
@@ -50,39 +49,38 @@
 #include "app-layer.h"
 #include "app-layer-parser.h"
 
-#include "app-layer-template-rust.h"
 #include "output-json-template-rust.h"
+
 #include "rust.h"
 
-typedef struct LogTemplateFileCtx_ {
-    uint32_t    flags;
-    OutputJsonCtx *eve_ctx;
-} LogTemplateFileCtx;
+bool JsonTemplateRustAddMetadata(const Flow *f, uint64_t tx_id, JsonBuilder *js)
+{
+    void *state = FlowGetAppState(f);
+    if (state) {
+        TemplateTransaction *tx = AppLayerParserGetTx(f->proto, ALPROTO_TEMPLATE_RUST, state, tx_id);
+        if (tx) {
+            return rs_template_to_json(tx, js);
+        }
+    }
 
-typedef struct LogTemplateLogThread_ {
-    LogTemplateFileCtx *templatelog_ctx;
-    OutputJsonThreadCtx *ctx;
-} LogTemplateLogThread;
+    return false;
+}
 
-static int JsonTemplateLogger(ThreadVars *tv, void *thread_data,
+static int JsonTemplateRustLogger(ThreadVars *tv, void *thread_data,
     const Packet *p, Flow *f, void *state, void *tx, uint64_t tx_id)
 {
-    SCLogNotice("JsonTemplateLogger");
-    LogTemplateLogThread *thread = thread_data;
+    OutputJsonThreadCtx *thread = thread_data;
 
-    JsonBuilder *js = CreateEveHeader(
-            p, LOG_DIR_PACKET, "template-rust", NULL, thread->templatelog_ctx->eve_ctx);
+    JsonBuilder *js = CreateEveHeader((Packet *)p, LOG_DIR_FLOW, "template-rust", NULL, thread->ctx);
     if (unlikely(js == NULL)) {
-        return TM_ECODE_FAILED;
+        return TM_ECODE_OK;
     }
 
-    jb_open_object(js, "template");
-    if (!rs_template_logger_log(tx, js)) {
+    if (!rs_template_to_json(tx, js)) {
         goto error;
     }
-    jb_close(js);
 
-    OutputJsonBuilderBuffer(js, thread->ctx);
+    OutputJsonBuilderBuffer(js, thread);
     jb_free(js);
 
     return TM_ECODE_OK;
@@ -92,77 +90,11 @@ error:
     return TM_ECODE_FAILED;
 }
 
-static void OutputTemplateLogDeInitCtxSub(OutputCtx *output_ctx)
-{
-    LogTemplateFileCtx *templatelog_ctx = (LogTemplateFileCtx *)output_ctx->data;
-    SCFree(templatelog_ctx);
-    SCFree(output_ctx);
-}
-
-static OutputInitResult OutputTemplateLogInitSub(ConfNode *conf,
+static OutputInitResult OutputTemplateRustLogInitSub(ConfNode *conf,
     OutputCtx *parent_ctx)
 {
-    OutputInitResult result = { NULL, false };
-    OutputJsonCtx *ajt = parent_ctx->data;
-
-    LogTemplateFileCtx *templatelog_ctx = SCCalloc(1, sizeof(*templatelog_ctx));
-    if (unlikely(templatelog_ctx == NULL)) {
-        return result;
-    }
-    templatelog_ctx->eve_ctx = ajt;
-
-    OutputCtx *output_ctx = SCCalloc(1, sizeof(*output_ctx));
-    if (unlikely(output_ctx == NULL)) {
-        SCFree(templatelog_ctx);
-        return result;
-    }
-    output_ctx->data = templatelog_ctx;
-    output_ctx->DeInit = OutputTemplateLogDeInitCtxSub;
-
-    SCLogNotice("Template log sub-module initialized.");
-
     AppLayerParserRegisterLogger(IPPROTO_TCP, ALPROTO_TEMPLATE_RUST);
-
-    result.ctx = output_ctx;
-    result.ok = true;
-    return result;
-}
-
-static TmEcode JsonTemplateLogThreadInit(ThreadVars *t, const void *initdata, void **data)
-{
-    LogTemplateLogThread *thread = SCCalloc(1, sizeof(*thread));
-    if (unlikely(thread == NULL)) {
-        return TM_ECODE_FAILED;
-    }
-
-    if (initdata == NULL) {
-        SCLogDebug("Error getting context for EveLogTemplate.  \"initdata\" is NULL.");
-        goto error_exit;
-    }
-
-    thread->templatelog_ctx = ((OutputCtx *)initdata)->data;
-    thread->ctx = CreateEveThreadCtx(t, thread->templatelog_ctx->eve_ctx);
-    if (!thread->ctx) {
-        goto error_exit;
-    }
-    *data = (void *)thread;
-
-    return TM_ECODE_OK;
-
-error_exit:
-    SCFree(thread);
-    return TM_ECODE_FAILED;
-}
-
-static TmEcode JsonTemplateLogThreadDeinit(ThreadVars *t, void *data)
-{
-    LogTemplateLogThread *thread = (LogTemplateLogThread *)data;
-    if (thread == NULL) {
-        return TM_ECODE_OK;
-    }
-    FreeEveThreadCtx(thread->ctx);
-    SCFree(thread);
-    return TM_ECODE_OK;
+    return OutputJsonLogInitSub(conf, parent_ctx);
 }
 
 void JsonTemplateRustLogRegister(void)
@@ -175,8 +107,8 @@ void JsonTemplateRustLogRegister(void)
     /* Register as an eve sub-module. */
     OutputRegisterTxSubModule(LOGGER_JSON_TEMPLATE_RUST, "eve-log",
         "JsonTemplateRustLog", "eve-log.template-rust",
-        OutputTemplateLogInitSub, ALPROTO_TEMPLATE_RUST, JsonTemplateLogger,
-        JsonTemplateLogThreadInit, JsonTemplateLogThreadDeinit, NULL);
+        OutputTemplateRustLogInitSub, ALPROTO_TEMPLATE_RUST, JsonTemplateRustLogger,
+        JsonLogThreadInit, JsonLogThreadDeinit, NULL);
 
-    SCLogNotice("Template JSON logger registered.");
+    SCLogDebug("TemplateRust JSON logger registered.");
 }
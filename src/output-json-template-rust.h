@@ -26,4 +26,6 @@
 
 void JsonTemplateRustLogRegister(void);
 
+bool JsonTemplateRustAddMetadata(const Flow *f, uint64_t tx_id, JsonBuilder *js);
+
 #endif /* __OUTPUT_JSON_TEMPLATE_RUST_H__ */
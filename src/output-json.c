@@ -161,6 +161,21 @@ void EveFileInfo(JsonBuilder *jb, const File *ff, const bool stored)
                 }
                 jb_set_string(jb, "sha1", str);
             }
+            if (ff->flags & FILE_SHA3_256) {
+                size_t x;
+                int i;
+                char str[256];
+                for (i = 0, x = 0; x < sizeof(ff->sha3_256); x++) {
+                    i += snprintf(&str[i], 255-i, "%02x", ff->sha3_256[x]);
+                }
+                jb_set_string(jb, "sha3_256", str);
+            }
+            if (ff->ssdeep != NULL) {
+                jb_set_string(jb, "ssdeep", ff->ssdeep);
+            }
+            if (ff->tlsh != NULL) {
+                jb_set_string(jb, "tlsh", ff->tlsh);
+            }
             break;
         case FILE_STATE_TRUNCATED:
             JB_SET_STRING(jb, "state", "TRUNCATED");
@@ -910,6 +925,11 @@ JsonBuilder *CreateEveHeader(const Packet *p, enum OutputJsonLogDirection dir,
             break;
     }
 
+    /* real client and server of a flow behind a PROXY protocol proxy */
+    if (f != NULL) {
+        rs_proxy_flow_to_json(f, js);
+    }
+
     if (eve_ctx != NULL) {
         EveAddCommonOptions(&eve_ctx->cfg, p, f, js);
     }
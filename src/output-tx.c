@@ -179,7 +179,7 @@ static TmEcode OutputTxLog(ThreadVars *tv, Packet *p, void *thread_data)
 
     if (AppLayerParserProtocolHasLogger(p->proto, alproto) == 0)
         goto end;
-    const LoggerId logger_expectation = AppLayerParserProtocolGetLoggerBits(p->proto, alproto);
+    const uint64_t logger_expectation = AppLayerParserProtocolGetLoggerBits(p->proto, alproto);
     if (logger_expectation == 0)
         goto end;
 
@@ -231,9 +231,9 @@ static TmEcode OutputTxLog(ThreadVars *tv, Packet *p, void *thread_data)
             goto next_tx;
         }
 
-        LoggerId tx_logged = txd->logged.flags;
-        const LoggerId tx_logged_old = tx_logged;
-        SCLogDebug("logger: expect %08x, have %08x", logger_expectation, tx_logged);
+        uint64_t tx_logged = txd->logged.flags;
+        const uint64_t tx_logged_old = tx_logged;
+        SCLogDebug("logger: expect %016"PRIx64", have %016"PRIx64, logger_expectation, tx_logged);
         if (tx_logged == logger_expectation) {
             /* tx already fully logged */
             goto next_tx;
@@ -260,7 +260,7 @@ static TmEcode OutputTxLog(ThreadVars *tv, Packet *p, void *thread_data)
             SCLogDebug("logger %p, Alproto %d LogCondition %p, ts_log_progress %d "
                     "tc_log_progress %d", logger, logger->alproto, logger->LogCondition,
                     logger->ts_log_progress, logger->tc_log_progress);
-            if ((tx_logged_old & BIT_U32(logger->logger_id)) == 0) {
+            if ((tx_logged_old & BIT_U64(logger->logger_id)) == 0) {
                 SCLogDebug("alproto match %d, logging tx_id %"PRIu64, logger->alproto, tx_id);
 
                 SCLogDebug("pcap_cnt %"PRIu64", tx_id %"PRIu64" logger %d. "
@@ -296,7 +296,7 @@ static TmEcode OutputTxLog(ThreadVars *tv, Packet *p, void *thread_data)
                 logger->LogFunc(tv, store->thread_data, p, f, alstate, tx, tx_id);
                 PACKET_PROFILING_LOGGER_END(p, logger->logger_id);
 
-                tx_logged |= BIT_U32(logger->logger_id);
+                tx_logged |= BIT_U64(logger->logger_id);
             }
 
 next_logger:
@@ -308,7 +308,7 @@ next_logger:
         }
 
         if (tx_logged != tx_logged_old) {
-            SCLogDebug("logger: storing %08x (was %08x)",
+            SCLogDebug("logger: storing %016"PRIx64" (was %016"PRIx64")",
                 tx_logged, tx_logged_old);
             DEBUG_VALIDATE_BUG_ON(txd == NULL);
             txd->logged.flags |= tx_logged;
@@ -80,6 +80,20 @@
 #include "output-json-template-rust.h"
 #include "output-json-rdp.h"
 #include "output-json-http2.h"
+#include "output-json-quic.h"
+#include "output-json-ldap.h"
+#include "output-json-telnet.h"
+#include "output-json-llmnr.h"
+#include "output-json-nbns.h"
+#include "output-json-stratum.h"
+#include "output-json-kafka.h"
+#include "output-json-irc.h"
+#include "output-json-xmpp.h"
+#include "output-json-openvpn.h"
+#include "output-json-dtls.h"
+#include "output-json-rtp.h"
+#include "output-json-icap.h"
+#include "output-json-bittorrent.h"
 #include "output-lua.h"
 #include "output-json-dnp3.h"
 #include "output-json-metadata.h"
@@ -1118,4 +1132,32 @@ void OutputRegisterLoggers(void)
     JsonRdpLogRegister();
     /* DCERPC JSON logger. */
     JsonDCERPCLogRegister();
+    /* QUIC JSON logger. */
+    JsonQuicLogRegister();
+    /* LDAP JSON logger. */
+    JsonLdapLogRegister();
+    /* Telnet JSON logger. */
+    JsonTelnetLogRegister();
+    /* LLMNR JSON logger. */
+    JsonLlmnrLogRegister();
+    /* NBNS JSON logger. */
+    JsonNbnsLogRegister();
+    /* Stratum JSON logger. */
+    JsonStratumLogRegister();
+    /* Kafka JSON logger. */
+    JsonKafkaLogRegister();
+    /* IRC JSON logger. */
+    JsonIrcLogRegister();
+    /* XMPP JSON logger. */
+    JsonXmppLogRegister();
+    /* OpenVPN JSON logger. */
+    JsonOpenVpnLogRegister();
+    /* DTLS JSON logger. */
+    JsonDtlsLogRegister();
+    /* RTP JSON logger. */
+    JsonRtpLogRegister();
+    /* ICAP JSON logger. */
+    JsonIcapLogRegister();
+    /* BitTorrent JSON logger. */
+    JsonBitTorrentLogRegister();
 }
@@ -1523,6 +1523,42 @@ TmEcode UnixSocketShowAllMemcap(json_t *cmd, json_t *answer, void *data)
     json_object_set_new(answer, "message", jmemcaps);
     SCReturnInt(TM_ECODE_OK);
 }
+
+/**
+ * \brief Command to dump the live state summaries of the Rust app-layer
+ *        parsers: live states and transactions, buffered bytes and
+ *        events, for all protocols or for the given one.
+ */
+TmEcode UnixSocketShowAppLayerState(json_t *cmd, json_t *answer, void *data)
+{
+    const char *proto = NULL;
+
+    json_t *jarg = json_object_get(cmd, "protocol");
+    if (jarg != NULL) {
+        if (!json_is_string(jarg)) {
+            json_object_set_new(answer, "message", json_string("protocol is not a string"));
+            return TM_ECODE_FAILED;
+        }
+        proto = json_string_value(jarg);
+    }
+
+    JsonBuilder *js = rs_app_layer_introspect(proto);
+    if (js == NULL) {
+        json_object_set_new(answer, "message", json_string("unknown protocol"));
+        return TM_ECODE_FAILED;
+    }
+
+    json_t *jstate = json_loadb((const char *)jb_ptr(js), jb_len(js), 0, NULL);
+    jb_free(js);
+    if (jstate == NULL) {
+        json_object_set_new(answer, "message",
+                            json_string("internal error at json object creation"));
+        return TM_ECODE_FAILED;
+    }
+
+    json_object_set_new(answer, "message", jstate);
+    SCReturnInt(TM_ECODE_OK);
+}
 #endif /* BUILD_UNIX_SOCKET */
 
 #ifdef BUILD_UNIX_SOCKET
@@ -489,7 +489,7 @@ static void RunOutputFreeList(void)
 
 static int file_logger_count = 0;
 static int filedata_logger_count = 0;
-static LoggerId logger_bits[ALPROTO_MAX];
+static uint64_t logger_bits[ALPROTO_MAX];
 
 int RunModeOutputFileEnabled(void)
 {
@@ -601,7 +601,7 @@ static void SetupOutput(const char *name, OutputModule *module, OutputCtx *outpu
                 module->ThreadExitPrintStats);
         /* Not used with wild card loggers */
         if (module->alproto != ALPROTO_UNKNOWN) {
-            logger_bits[module->alproto] |= (1<<module->logger_id);
+            logger_bits[module->alproto] |= BIT_U64(module->logger_id);
         }
     } else if (module->FiledataLogFunc) {
         SCLogDebug("%s is a filedata logger", module->name);
@@ -900,7 +900,7 @@ void RunModeInitializeOutputs(void)
         SCLogDebug("logger for %s: %s %s", AppProtoToString(a),
                 tcp ? "true" : "false", udp ? "true" : "false");
 
-        SCLogDebug("logger bits for %s: %08x", AppProtoToString(a), logger_bits[a]);
+        SCLogDebug("logger bits for %s: %016"PRIx64, AppProtoToString(a), logger_bits[a]);
         if (tcp)
             AppLayerParserRegisterLoggerBits(IPPROTO_TCP, a, logger_bits[a]);
         if (udp)
@@ -38,6 +38,7 @@ typedef struct SuricataContext_ {
             uint8_t);
     void (*AppLayerDecoderEventsFreeEvents)(AppLayerDecoderEvents **);
     void (*AppLayerParserTriggerRawStreamReassembly)(Flow *, int direction);
+    void (*AppLayerRequestProtocolTLSUpgrade)(Flow *);
 
     void (*HttpRangeFreeBlock)(HttpRangeContainerBlock *);
     void (*HTPFileCloseHandleRange)(
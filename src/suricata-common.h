@@ -460,9 +460,23 @@ typedef enum {
     LOGGER_JSON_RDP,
     LOGGER_JSON_DCERPC,
     LOGGER_JSON_HTTP2,
-
-    /** \warning when we exceed what we can express as a u32 flag here we need to update
-     *           LoggerFlags::flags (u32) and `tx_logged` in src/output-tx.c */
+    LOGGER_JSON_QUIC,
+    LOGGER_JSON_LDAP,
+    LOGGER_JSON_TELNET,
+    LOGGER_JSON_LLMNR,
+    LOGGER_JSON_NBNS,
+    LOGGER_JSON_STRATUM,
+    LOGGER_JSON_KAFKA,
+    LOGGER_JSON_IRC,
+    LOGGER_JSON_XMPP,
+    LOGGER_JSON_OPENVPN,
+    LOGGER_JSON_DTLS,
+    LOGGER_JSON_RTP,
+    LOGGER_JSON_ICAP,
+    LOGGER_JSON_BITTORRENT,
+
+    /** \warning when we exceed what we can express as a u64 flag here we need to update
+     *           LoggerFlags::flags (u64) and `tx_logged` in src/output-tx.c */
 
     /* non-tx loggers below */
 
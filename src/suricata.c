@@ -115,6 +115,7 @@
 #include "app-layer.h"
 #include "app-layer-parser.h"
 #include "app-layer-register.h"
+#include "app-layer-detect-proto.h"
 #include "app-layer-htp.h"
 #include "app-layer-ssl.h"
 #include "app-layer-ssh.h"
@@ -2666,6 +2667,7 @@ int InitGlobal(void) {
     suricata_context.AppLayerDecoderEventsFreeEvents = AppLayerDecoderEventsFreeEvents;
     suricata_context.AppLayerParserTriggerRawStreamReassembly =
             AppLayerParserTriggerRawStreamReassembly;
+    suricata_context.AppLayerRequestProtocolTLSUpgrade = AppLayerRequestProtocolTLSUpgrade;
 
     suricata_context.HttpRangeFreeBlock = HttpRangeFreeBlock;
     suricata_context.HTPFileCloseHandleRange = HTPFileCloseHandleRange;
@@ -159,3 +159,31 @@ void DetectHTTP2sizeUpdateRegisterTests(void)
 {
     UtRegisterTest("DetectHTTP2sizeUpdateParseTest01", DetectHTTP2sizeUpdateParseTest01);
 }
+
+/**
+* \test signatures with http2.header, with and without header name.
+*/
+
+static int DetectHTTP2headerParseTest01 (void)
+{
+    DetectEngineCtx *de_ctx = DetectEngineCtxInit();
+    FAIL_IF_NULL(de_ctx);
+
+    Signature *sig = DetectEngineAppendSig(de_ctx,
+                                           "alert http2 any any -> any any (http2.header; content:\"user-agent: curl\"; sid:1; rev:1;)");
+    FAIL_IF_NULL(sig);
+    sig = DetectEngineAppendSig(de_ctx,
+                                "alert http2 any any -> any any (http2.header:X-Custom; content:\"http://\"; sid:2; rev:1;)");
+    FAIL_IF_NULL(sig);
+    sig = DetectEngineAppendSig(de_ctx,
+                                "alert http2 any any -> any any (http2.header:x-custom; to_lowercase; content:\"abc\"; sid:3; rev:1;)");
+    FAIL_IF_NULL(sig);
+
+    DetectEngineCtxFree(de_ctx);
+    PASS;
+}
+
+void DetectHTTP2headerRegisterTests(void)
+{
+    UtRegisterTest("DetectHTTP2headerParseTest01", DetectHTTP2headerParseTest01);
+}
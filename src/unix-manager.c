@@ -503,6 +503,12 @@ static int UnixCommandExecute(UnixCommand * this, char *command, UnixClient *cli
                     SCLogInfo("error: argument is not an object");
                     goto error_cmd;
                 }
+            } else if (lcmd->flags & UNIX_CMD_OPTIONAL_ARGS) {
+                cmd = json_object_get(jsoncmd, "arguments");
+                if (cmd != NULL && !json_is_object(cmd)) {
+                    SCLogInfo("error: argument is not an object");
+                    goto error_cmd;
+                }
             }
             fret = lcmd->Func(cmd, server_msg, lcmd->data);
             if (fret != TM_ECODE_OK) {
@@ -1080,6 +1086,8 @@ int UnixManagerInit(void)
     UnixManagerRegisterCommand("memcap-set", UnixSocketSetMemcap, &command, UNIX_CMD_TAKE_ARGS);
     UnixManagerRegisterCommand("memcap-show", UnixSocketShowMemcap, &command, UNIX_CMD_TAKE_ARGS);
     UnixManagerRegisterCommand("memcap-list", UnixSocketShowAllMemcap, NULL, 0);
+    UnixManagerRegisterCommand("app-layer-state", UnixSocketShowAppLayerState, NULL,
+            UNIX_CMD_OPTIONAL_ARGS);
 
     UnixManagerRegisterCommand("dataset-add", UnixSocketDatasetAdd, &command, UNIX_CMD_TAKE_ARGS);
     UnixManagerRegisterCommand("dataset-remove", UnixSocketDatasetRemove, &command, UNIX_CMD_TAKE_ARGS);
@@ -25,6 +25,8 @@
 #define UNIX_MANAGER_H
 
 #define UNIX_CMD_TAKE_ARGS 1
+/* arguments are passed if present, NULL otherwise */
+#define UNIX_CMD_OPTIONAL_ARGS 2
 
 extern SCCtrlCondT unix_manager_ctrl_cond;
 extern SCCtrlMutex unix_manager_ctrl_mutex;
@@ -60,13 +60,16 @@
 #define MAX_ENC_LINE_LEN    76 /* Def in RFC 2045, excluding CRLF sequence */
 #define MAX_HEADER_NAME     75 /* 75 + ":" = 76 */
 #define MAX_HEADER_VALUE  2000 /* Default - arbitrary limit */
+#define MAX_NESTING_DEPTH   16 /* Default - arbitrary limit */
+#define MAX_HEADER_COUNT  1000 /* Default - arbitrary limit */
 #define BOUNDARY_BUF       256
 #define CTNT_TYPE_STR     "content-type"
 #define CTNT_DISP_STR     "content-disposition"
 #define CTNT_TRAN_STR     "content-transfer-encoding"
 #define MSG_ID_STR        "message-id"
-#define BND_START_STR     "boundary="
-#define TOK_END_STR       "\""
+#define BND_STR           "boundary"
+#define FILENAME_STR      "filename"
+#define NAME_STR          "name"
 #define MSG_STR           "message/"
 #define MULTIPART_STR     "multipart/"
 #define QP_STR            "quoted-printable"
@@ -82,7 +85,8 @@
 #define MAX_IP6_CHARS  39
 
 /* Globally hold configuration data */
-static MimeDecConfig mime_dec_config = { 1, 1, 1, 0, MAX_HEADER_VALUE };
+static MimeDecConfig mime_dec_config = { 1, 1, 1, 0, MAX_HEADER_VALUE,
+    MAX_NESTING_DEPTH, MAX_HEADER_COUNT };
 
 /* Mime Parser String translation */
 static const char *StateFlags[] = { "NONE",
@@ -141,6 +145,12 @@ void MimeDecSetConfig(MimeDecConfig *config)
         if (mime_dec_config.header_value_depth == 0) {
             mime_dec_config.header_value_depth = MAX_HEADER_VALUE;
         }
+        if (mime_dec_config.max_nesting_depth == 0) {
+            mime_dec_config.max_nesting_depth = MAX_NESTING_DEPTH;
+        }
+        if (mime_dec_config.max_header_count == 0) {
+            mime_dec_config.max_header_count = MAX_HEADER_COUNT;
+        }
     } else {
         SCLogWarning(SC_ERR_MISSING_CONFIG_PARAM, "Invalid null configuration parameters");
     }
@@ -296,6 +306,7 @@ MimeDecField * MimeDecAddField(MimeDecEntity *entity)
         node->next = entity->field_list;
         entity->field_list = node;
     }
+    entity->field_cnt++;
 
     return node;
 }
@@ -491,6 +502,7 @@ static MimeDecStackNode * PushStack(MimeDecStack *stack)
     /* Push to top of stack */
     node->next = stack->top;
     stack->top = node;
+    stack->depth++;
 
     /* Return a pointer to the top of the stack */
     return node;
@@ -523,6 +535,7 @@ static MimeDecStackNode * PopStack(MimeDecStack *stack)
         SCFree(stack->top);
     }
     stack->top = curr;
+    stack->depth--;
 
     /* Return a pointer to the top of the stack */
     return curr;
@@ -838,8 +851,14 @@ static int StoreMimeHeader(MimeDecParseState *state)
                         " name");
                 ret = MIME_DEC_ERR_PARSE;
             } else if (state->stack->top != NULL) {
-                /* Store each header name and value */
-                if (MimeDecFillField(state->stack->top->data, state->hname,
+                MimeDecEntity *entity = state->stack->top->data;
+                if (entity->field_cnt >= MimeDecGetConfig()->max_header_count) {
+                    /* Flag it and drop the header */
+                    SCLogDebug("Error: Too many header fields (%u)",
+                            entity->field_cnt);
+                    entity->anomaly_flags |= ANOM_TOO_MANY_HEADERS;
+                    state->msg->anomaly_flags |= ANOM_TOO_MANY_HEADERS;
+                } else if (MimeDecFillField(entity, state->hname,
                             state->hlen, val, vlen) == NULL) {
                     SCLogError(SC_ERR_MEM_ALLOC, "MimeDecFillField() function failed");
                     ret = MIME_DEC_ERR_MEM;
@@ -1827,67 +1846,69 @@ static int FindMimeHeader(const uint8_t *buf, uint32_t blen,
 }
 
 /**
- * \brief Finds a mime header token within the specified field
+ * \brief Stores the file name of an attachment, from a parameter of the
+ * specified field, with the RFC 2231 and RFC 2047 encodings decoded
  *
+ * \param state The current parser state
+ * \param entity The current entity
  * \param field The current field
- * \param search_start The start of the search (ie. boundary=\")
- * \param search_end The end of the search (ie. \")
- * \param tlen The output length of the token (if found)
- * \param max_len The maximum offset in which to search
- * \param toolong Set if the field value was truncated to max_len.
+ * \param name The name of the parameter (ie. filename)
  *
- * \return A pointer to the token if found, otherwise NULL if not found
+ * \return MIME_DEC_OK on success, otherwise < 0 on failure
  */
-static uint8_t * FindMimeHeaderTokenRestrict(MimeDecField *field, const char *search_start,
-        const char *search_end, uint32_t *tlen, uint32_t max_len, bool *toolong)
+static int StoreMimeFilename(MimeDecParseState *state, MimeDecEntity *entity,
+        MimeDecField *field, const char *name)
 {
-    uint8_t *fptr, *tptr = NULL, *tok = NULL;
-
-    if (toolong)
-        *toolong = false;
+    uint8_t buf[RS_MIME_MAX_TOKEN_LEN];
+    uint32_t len = 0;
+    bool truncated = false;
 
-    SCLogDebug("Looking for token: %s", search_start);
+    if (!rs_mime_find_filename(field->value, field->value_len, (const uint8_t *)name,
+                strlen(name), &buf, &len, &truncated)) {
+        return MIME_DEC_OK;
+    }
+    SCLogDebug("File attachment found");
+    entity->ctnt_flags |= CTNT_IS_ATTACHMENT;
 
-    /* Check for token definition */
-    size_t ss_len = strlen(search_start);
-    fptr = FindBuffer(field->value, field->value_len, (const uint8_t *)search_start, ss_len);
-    if (fptr != NULL) {
-        fptr += ss_len; /* Start at end of start string */
-        uint32_t offset = fptr - field->value;
-        if (offset > field->value_len) {
-            return tok;
-        }
-        tok = GetToken(fptr, field->value_len - offset, search_end, &tptr, tlen);
-        if (tok == NULL) {
-            return tok;
+    if (len > 0) {
+        /* Copy over using dynamic memory */
+        entity->filename = SCMalloc(len);
+        if (unlikely(entity->filename == NULL)) {
+            SCLogError(SC_ERR_MEM_ALLOC, "memory allocation failed");
+            return MIME_DEC_ERR_MEM;
         }
-        SCLogDebug("Found mime token");
+        memcpy(entity->filename, buf, len);
+        entity->filename_len = len;
+    }
 
-        /* Compare the actual token length against the maximum */
-        if (toolong && max_len && *tlen > max_len) {
-            SCLogDebug("Token length %d exceeds length restriction %d; truncating", *tlen, max_len);
-            *toolong = true;
-            *tlen = max_len;
-        }
+    if (truncated) {
+        SCLogDebug("File name exceeds length restriction %d; truncating",
+                RS_MIME_MAX_TOKEN_LEN);
+        entity->anomaly_flags |= ANOM_LONG_FILENAME;
+        state->msg->anomaly_flags |= ANOM_LONG_FILENAME;
     }
 
-    return tok;
+    return MIME_DEC_OK;
 }
 
 /**
- * \brief Finds a mime header token within the specified field
+ * \brief Checks whether the current entity may have child entities, which
+ * is not the case once the stack reaches the maximum nesting depth
  *
- * \param field The current field
- * \param search_start The start of the search (ie. boundary=\")
- * \param search_end The end of the search (ie. \")
- * \param tlen The output length of the token (if found)
+ * \param state The current parser state
  *
- * \return A pointer to the token if found, otherwise NULL if not found
+ * \return true if child entities may be created, otherwise false
  */
-static uint8_t * FindMimeHeaderToken(MimeDecField *field, const char *search_start,
-        const char *search_end, uint32_t *tlen)
+static bool MimeDecChildAllowed(MimeDecParseState *state)
 {
-    return FindMimeHeaderTokenRestrict(field, search_start, search_end, tlen, 0, NULL);
+    if (state->stack->depth < MimeDecGetConfig()->max_nesting_depth) {
+        return true;
+    }
+
+    SCLogDebug("Error: Entities nested too deep (%u)", state->stack->depth);
+    state->stack->top->data->anomaly_flags |= ANOM_DEEP_NESTING;
+    state->msg->anomaly_flags |= ANOM_DEEP_NESTING;
+    return false;
 }
 
 /**
@@ -1905,8 +1926,7 @@ static int ProcessMimeHeaders(const uint8_t *buf, uint32_t len,
 {
     int ret = MIME_DEC_OK;
     MimeDecField *field;
-    uint8_t *bptr = NULL, *rptr = NULL;
-    uint32_t blen = 0;
+    uint8_t *rptr = NULL;
     MimeDecEntity *entity = (MimeDecEntity *) state->stack->top->data;
 
     /* Look for mime header in current line */
@@ -1935,25 +1955,9 @@ static int ProcessMimeHeaders(const uint8_t *buf, uint32_t len,
         /* Check for file attachment in content disposition */
         field = MimeDecFindField(entity, CTNT_DISP_STR);
         if (field != NULL) {
-            bool truncated_name = false;
-            bptr = FindMimeHeaderTokenRestrict(field, "filename=", TOK_END_STR, &blen, NAME_MAX, &truncated_name);
-            if (bptr != NULL) {
-                SCLogDebug("File attachment found in disposition");
-                entity->ctnt_flags |= CTNT_IS_ATTACHMENT;
-
-                /* Copy over using dynamic memory */
-                entity->filename = SCMalloc(blen);
-                if (unlikely(entity->filename == NULL)) {
-                    SCLogError(SC_ERR_MEM_ALLOC, "memory allocation failed");
-                    return MIME_DEC_ERR_MEM;
-                }
-                memcpy(entity->filename, bptr, blen);
-                entity->filename_len = blen;
-
-                if (truncated_name) {
-                    state->stack->top->data->anomaly_flags |= ANOM_LONG_FILENAME;
-                    state->msg->anomaly_flags |= ANOM_LONG_FILENAME;
-                }
+            ret = StoreMimeFilename(state, entity, field, FILENAME_STR);
+            if (ret != MIME_DEC_OK) {
+                return ret;
             }
         }
 
@@ -1961,47 +1965,35 @@ static int ProcessMimeHeaders(const uint8_t *buf, uint32_t len,
         field = MimeDecFindField(entity, CTNT_TYPE_STR);
         if (field != NULL) {
             /* Check if child entity boundary definition found */
-            bptr = FindMimeHeaderToken(field, BND_START_STR, TOK_END_STR, &blen);
-            if (bptr != NULL) {
+            uint8_t bdef[RS_MIME_MAX_TOKEN_LEN];
+            uint32_t bdef_len = 0;
+            bool truncated = false;
+            if (rs_mime_find_header_token(field->value, field->value_len,
+                        (const uint8_t *)BND_STR, strlen(BND_STR), &bdef, &bdef_len,
+                        &truncated) && bdef_len > 0 && MimeDecChildAllowed(state)) {
                 state->found_child = 1;
                 entity->ctnt_flags |= CTNT_IS_MULTIPART;
 
-                if (blen > (BOUNDARY_BUF - 2)) {
+                if (truncated || bdef_len > (BOUNDARY_BUF - 2)) {
                     state->stack->top->data->anomaly_flags |= ANOM_LONG_BOUNDARY;
                     return MIME_DEC_ERR_PARSE;
                 }
 
                 /* Store boundary in parent node */
-                state->stack->top->bdef = SCMalloc(blen);
+                state->stack->top->bdef = SCMalloc(bdef_len);
                 if (unlikely(state->stack->top->bdef == NULL)) {
                     SCLogError(SC_ERR_MEM_ALLOC, "Memory allocation failed");
                     return MIME_DEC_ERR_MEM;
                 }
-                memcpy(state->stack->top->bdef, bptr, blen);
-                state->stack->top->bdef_len = blen;
+                memcpy(state->stack->top->bdef, bdef, bdef_len);
+                state->stack->top->bdef_len = bdef_len;
             }
 
             /* Look for file name (if not already found) */
             if (!(entity->ctnt_flags & CTNT_IS_ATTACHMENT)) {
-                bool truncated_name = false;
-                bptr = FindMimeHeaderTokenRestrict(field, "name=", TOK_END_STR, &blen, NAME_MAX, &truncated_name);
-                if (bptr != NULL) {
-                    SCLogDebug("File attachment found");
-                    entity->ctnt_flags |= CTNT_IS_ATTACHMENT;
-
-                    /* Copy over using dynamic memory */
-                    entity->filename = SCMalloc(blen);
-                    if (unlikely(entity->filename == NULL)) {
-                        SCLogError(SC_ERR_MEM_ALLOC, "memory allocation failed");
-                        return MIME_DEC_ERR_MEM;
-                    }
-                    memcpy(entity->filename, bptr, blen);
-                    entity->filename_len = blen;
-
-                    if (truncated_name) {
-                        state->stack->top->data->anomaly_flags |= ANOM_LONG_FILENAME;
-                        state->msg->anomaly_flags |= ANOM_LONG_FILENAME;
-                    }
+                ret = StoreMimeFilename(state, entity, field, NAME_STR);
+                if (ret != MIME_DEC_OK) {
+                    return ret;
                 }
             }
 
@@ -2011,7 +2003,8 @@ static int ProcessMimeHeaders(const uint8_t *buf, uint32_t len,
             if (entity->ctnt_type != NULL) {
                 /* Check for encapsulated message */
                 if (FindBuffer(entity->ctnt_type, entity->ctnt_type_len,
-                            (const uint8_t *)MSG_STR, strlen(MSG_STR)))
+                            (const uint8_t *)MSG_STR, strlen(MSG_STR)) &&
+                        MimeDecChildAllowed(state))
                 {
                     SCLogDebug("Found encapsulated message entity");
 
@@ -3140,6 +3133,175 @@ static int MimeDecParseLongFilename02(void)
     PASS;
 }
 
+static int MimeDecParseEncodedFilename01(void)
+{
+    uint32_t line_count = 0;
+
+    /* Init parser */
+    MimeDecParseState *state = MimeDecInitParser(&line_count,
+            TestDataChunkCallback);
+
+    const char *str = "Content-Type: multipart/mixed; boundary=\"XYZ\"";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    /* RFC 2231 continuations, with a charset */
+    str = "--XYZ";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "Content-Disposition: attachment;";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = " filename*0*=iso-8859-1''r%E9sum%E9;";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = " filename*1=\".pdf\"";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "A simple message line 1";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    /* RFC 2047 encoded word in the name of the Content-Type */
+    str = "--XYZ";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "Content-Type: application/zip; name=\"=?UTF-8?B?w6l0w6kuemlw?=\"";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "A simple message line 2";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "--XYZ--";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    /* Completed */
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseComplete(state));
+
+    MimeDecEntity *msg = state->msg;
+    FAIL_IF_NOT(msg);
+
+    MimeDecEntity *part = msg->child;
+    FAIL_IF_NOT(part);
+    FAIL_IF_NOT(part->ctnt_flags & CTNT_IS_ATTACHMENT);
+    FAIL_IF_NOT(part->filename_len == 12);
+    FAIL_IF(memcmp(part->filename, "r\xc3\xa9sum\xc3\xa9.pdf", 12) != 0);
+
+    part = part->next;
+    FAIL_IF_NOT(part);
+    FAIL_IF_NOT(part->ctnt_flags & CTNT_IS_ATTACHMENT);
+    FAIL_IF_NOT(part->filename_len == 9);
+    FAIL_IF(memcmp(part->filename, "\xc3\xa9t\xc3\xa9.zip", 9) != 0);
+
+    MimeDecFreeEntity(msg);
+
+    /* De Init parser */
+    MimeDecDeInitParser(state);
+
+    PASS;
+}
+
+static int MimeDecParseNestingDepth01(void)
+{
+    uint32_t line_count = 0;
+    const char *lines[] = { "Content-Type: multipart/mixed; boundary=\"outer\"",
+            "",
+            "--outer",
+            "Content-Type: multipart/mixed; boundary=\"inner\"",
+            "",
+            "--inner",
+            "Content-Type: text/plain",
+            "",
+            "A simple message line 1",
+            "--inner--",
+            "--outer--",
+            NULL };
+
+    MimeDecGetConfig()->max_nesting_depth = 2;
+
+    /* Init parser */
+    MimeDecParseState *state = MimeDecInitParser(&line_count,
+            TestDataChunkCallback);
+
+    for (int i = 0; lines[i] != NULL; i++) {
+        FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)lines[i],
+                    strlen(lines[i]), 1, state));
+    }
+
+    /* Completed */
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseComplete(state));
+
+    MimeDecEntity *msg = state->msg;
+    FAIL_IF_NOT(msg);
+
+    /* The inner parts are part of the body of the first part */
+    FAIL_IF_NOT(msg->anomaly_flags & ANOM_DEEP_NESTING);
+    FAIL_IF_NOT(msg->child);
+    FAIL_IF_NOT(msg->child->anomaly_flags & ANOM_DEEP_NESTING);
+    FAIL_IF(msg->child->child);
+
+    MimeDecFreeEntity(msg);
+
+    /* De Init parser */
+    MimeDecDeInitParser(state);
+
+    MimeDecGetConfig()->max_nesting_depth = MAX_NESTING_DEPTH;
+
+    PASS;
+}
+
+static int MimeDecParseHeaderCount01(void)
+{
+    uint32_t line_count = 0;
+
+    MimeDecGetConfig()->max_header_count = 2;
+
+    /* Init parser */
+    MimeDecParseState *state = MimeDecInitParser(&line_count,
+            TestDataChunkCallback);
+
+    const char *str = "From: Sender1";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "To: Recipient1";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "Subject: Test";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    str = "A simple message line 1";
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseLine((uint8_t *)str, strlen(str), 1, state));
+
+    /* Completed */
+    FAIL_IF_NOT(MIME_DEC_OK == MimeDecParseComplete(state));
+
+    MimeDecEntity *msg = state->msg;
+    FAIL_IF_NOT(msg);
+
+    FAIL_IF_NOT(msg->anomaly_flags & ANOM_TOO_MANY_HEADERS);
+    FAIL_IF_NOT(msg->field_cnt == 2);
+    FAIL_IF(MimeDecFindField(msg, "subject"));
+
+    MimeDecFreeEntity(msg);
+
+    /* De Init parser */
+    MimeDecDeInitParser(state);
+
+    MimeDecGetConfig()->max_header_count = MAX_HEADER_COUNT;
+
+    PASS;
+}
+
 #endif /* UNITTESTS */
 
 void MimeDecRegisterTests(void)
@@ -3155,5 +3317,8 @@ void MimeDecRegisterTests(void)
     UtRegisterTest("MimeIsIpv6HostTest01", MimeIsIpv6HostTest01);
     UtRegisterTest("MimeDecParseLongFilename01", MimeDecParseLongFilename01);
     UtRegisterTest("MimeDecParseLongFilename02", MimeDecParseLongFilename02);
+    UtRegisterTest("MimeDecParseEncodedFilename01", MimeDecParseEncodedFilename01);
+    UtRegisterTest("MimeDecParseNestingDepth01", MimeDecParseNestingDepth01);
+    UtRegisterTest("MimeDecParseHeaderCount01", MimeDecParseHeaderCount01);
 #endif /* UNITTESTS */
 }
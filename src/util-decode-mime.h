@@ -61,6 +61,8 @@
 #define ANOM_MALFORMED_MSG      64  /* Misc msg format errors found */
 #define ANOM_LONG_BOUNDARY     128  /* Boundary too long */
 #define ANOM_LONG_FILENAME     256  /* filename truncated */
+#define ANOM_DEEP_NESTING      512  /* entities nested too deep */
+#define ANOM_TOO_MANY_HEADERS 1024  /* too many header fields in an entity */
 
 /* Publicly exposed size constants */
 #define DATA_CHUNK_SIZE  3072  /* Should be divisible by 3 */
@@ -99,6 +101,11 @@ typedef struct MimeDecConfig {
     int body_md5;  /**< Compute md5 sum of body */
     uint32_t header_value_depth;  /**< Depth of which to store header values
                                        (Default is 2000) */
+    uint32_t max_nesting_depth;  /**< Depth beyond which multipart and
+                                      encapsulated entities are not
+                                      decoded (Default is 16) */
+    uint32_t max_header_count;  /**< Number of header fields stored per
+                                     entity (Default is 1000) */
 } MimeDecConfig;
 
 /**
@@ -138,6 +145,7 @@ typedef struct MimeDecEntity {
     uint32_t header_flags; /**< Flags indicating header characteristics */
     uint32_t ctnt_flags;  /**< Flags indicating type of content */
     uint32_t anomaly_flags;  /**< Flags indicating an anomaly in the message */
+    uint32_t field_cnt;  /**< Number of header fields in the list */
     uint32_t filename_len;  /**< Length of file attachment name */
     uint8_t *filename;  /**< Name of file attachment */
     uint8_t *ctnt_type;  /**< Quick access pointer to short-hand content type field */
@@ -169,6 +177,7 @@ typedef struct MimeDecStack {
     MimeDecStackNode *top;  /**< Pointer to the top of the stack */
     MimeDecStackNode *free_nodes;  /**< Pointer to the list of free nodes */
     uint32_t free_nodes_cnt;  /**< Count of free nodes in the list */
+    uint32_t depth;  /**< Count of nodes on the stack */
 } MimeDecStack;
 
 /**
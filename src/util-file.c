@@ -71,6 +71,21 @@ static int g_file_force_sha1 = 0;
  */
 static int g_file_force_sha256 = 0;
 
+/** \brief switch to force sha3-256 calculation on all files
+ *         regardless of the rules.
+ */
+static int g_file_force_sha3_256 = 0;
+
+/** \brief switch to force ssdeep calculation on all files
+ *         regardless of the rules.
+ */
+static int g_file_force_ssdeep = 0;
+
+/** \brief switch to force tlsh calculation on all files
+ *         regardless of the rules.
+ */
+static int g_file_force_tlsh = 0;
+
 /** \brief switch to force tracking off all files
  *         regardless of the rules.
  */
@@ -120,6 +135,23 @@ void FileForceSha256Enable(void)
     g_file_flow_mask |= (FLOWFILE_NO_SHA256_TS|FLOWFILE_NO_SHA256_TC);
 }
 
+/* sha3-256, ssdeep and tlsh are never requested by rules, so there are
+ * no flow flags to mask for them. */
+void FileForceSha3_256Enable(void)
+{
+    g_file_force_sha3_256 = 1;
+}
+
+void FileForceSsdeepEnable(void)
+{
+    g_file_force_ssdeep = 1;
+}
+
+void FileForceTlshEnable(void)
+{
+    g_file_force_tlsh = 1;
+}
+
 int FileForceFilestore(void)
 {
     return g_file_force_filestore;
@@ -159,6 +191,21 @@ int FileForceSha256(void)
     return g_file_force_sha256;
 }
 
+int FileForceSha3_256(void)
+{
+    return g_file_force_sha3_256;
+}
+
+int FileForceSsdeep(void)
+{
+    return g_file_force_ssdeep;
+}
+
+int FileForceTlsh(void)
+{
+    return g_file_force_tlsh;
+}
+
 void FileForceTrackingEnable(void)
 {
     g_file_force_tracking = 1;
@@ -227,6 +274,36 @@ void FileForceHashParseCfg(ConfNode *conf)
                     SCLogConfig("forcing sha256 calculation for logged or stored files");
                 }
             }
+
+            if (strcasecmp("sha3-256", field->val) == 0) {
+                if (g_disable_hashing) {
+                    SCLogInfo("not forcing sha3-256 calculation for logged files: hashing "
+                              "globally disabled");
+                } else {
+                    FileForceSha3_256Enable();
+                    SCLogConfig("forcing sha3-256 calculation for logged or stored files");
+                }
+            }
+
+            if (strcasecmp("ssdeep", field->val) == 0) {
+                if (g_disable_hashing) {
+                    SCLogInfo("not forcing ssdeep calculation for logged files: hashing globally "
+                              "disabled");
+                } else {
+                    FileForceSsdeepEnable();
+                    SCLogConfig("forcing ssdeep calculation for logged or stored files");
+                }
+            }
+
+            if (strcasecmp("tlsh", field->val) == 0) {
+                if (g_disable_hashing) {
+                    SCLogInfo("not forcing tlsh calculation for logged files: hashing globally "
+                              "disabled");
+                } else {
+                    FileForceTlshEnable();
+                    SCLogConfig("forcing tlsh calculation for logged or stored files");
+                }
+            }
         }
     }
 }
@@ -534,6 +611,16 @@ static void FileFree(File *ff)
         SCSha1Free(ff->sha1_ctx);
     if (ff->sha256_ctx)
         SCSha256Free(ff->sha256_ctx);
+    if (ff->sha3_256_ctx)
+        SCSha3_256Free(ff->sha3_256_ctx);
+    if (ff->ssdeep_ctx)
+        SCSsdeepFree(ff->ssdeep_ctx);
+    if (ff->ssdeep)
+        SCFree(ff->ssdeep);
+    if (ff->tlsh_ctx)
+        SCTlshFree(ff->tlsh_ctx);
+    if (ff->tlsh)
+        SCFree(ff->tlsh);
     SCFree(ff);
 }
 
@@ -621,6 +708,15 @@ static int AppendData(File *file, const uint8_t *data, uint32_t data_len)
     if (file->sha256_ctx) {
         SCSha256Update(file->sha256_ctx, data, data_len);
     }
+    if (file->sha3_256_ctx) {
+        SCSha3_256Update(file->sha3_256_ctx, data, data_len);
+    }
+    if (file->ssdeep_ctx) {
+        SCSsdeepUpdate(file->ssdeep_ctx, data, data_len);
+    }
+    if (file->tlsh_ctx) {
+        SCTlshUpdate(file->tlsh_ctx, data, data_len);
+    }
     SCReturnInt(0);
 }
 
@@ -632,7 +728,7 @@ static int AppendData(File *file, const uint8_t *data, uint32_t data_len)
 static void FileFlagGap(File *ff) {
     ff->flags |= FILE_HAS_GAPS;
     ff->flags |= (FILE_NOMD5|FILE_NOSHA1|FILE_NOSHA256);
-    ff->flags &= ~(FILE_MD5|FILE_SHA1|FILE_SHA256);
+    ff->flags &= ~(FILE_MD5|FILE_SHA1|FILE_SHA256|FILE_SHA3_256);
 }
 
 /** \internal
@@ -682,6 +778,18 @@ static int FileAppendDataDo(File *ff, const uint8_t *data, uint32_t data_len)
             SCSha256Update(ff->sha256_ctx, data, data_len);
             hash_done = 1;
         }
+        if (ff->sha3_256_ctx) {
+            SCSha3_256Update(ff->sha3_256_ctx, data, data_len);
+            hash_done = 1;
+        }
+        if (ff->ssdeep_ctx) {
+            SCSsdeepUpdate(ff->ssdeep_ctx, data, data_len);
+            hash_done = 1;
+        }
+        if (ff->tlsh_ctx) {
+            SCTlshUpdate(ff->tlsh_ctx, data, data_len);
+            hash_done = 1;
+        }
 
         if (hash_done)
             SCReturnInt(0);
@@ -892,6 +1000,15 @@ static File *FileOpenFile(FileContainer *ffc, const StreamingBufferConfig *sbcfg
     if (!(ff->flags & FILE_NOSHA256) || g_file_force_sha256) {
         ff->sha256_ctx = SCSha256New();
     }
+    if (g_file_force_sha3_256) {
+        ff->sha3_256_ctx = SCSha3_256New();
+    }
+    if (g_file_force_ssdeep) {
+        ff->ssdeep_ctx = SCSsdeepNew();
+    }
+    if (g_file_force_tlsh) {
+        ff->tlsh_ctx = SCTlshNew();
+    }
 
     ff->state = FILE_STATE_OPENED;
     SCLogDebug("flowfile state transitioned to FILE_STATE_OPENED");
@@ -952,6 +1069,12 @@ int FileCloseFilePtr(File *ff, const uint8_t *data,
                 SCSha1Update(ff->sha1_ctx, data, data_len);
             if (ff->sha256_ctx)
                 SCSha256Update(ff->sha256_ctx, data, data_len);
+            if (ff->sha3_256_ctx)
+                SCSha3_256Update(ff->sha3_256_ctx, data, data_len);
+            if (ff->ssdeep_ctx)
+                SCSsdeepUpdate(ff->ssdeep_ctx, data, data_len);
+            if (ff->tlsh_ctx)
+                SCTlshUpdate(ff->tlsh_ctx, data, data_len);
         } else {
             if (AppendData(ff, data, data_len) != 0) {
                 ff->state = FILE_STATE_ERROR;
@@ -989,6 +1112,26 @@ int FileCloseFilePtr(File *ff, const uint8_t *data,
         if (ff->sha256_ctx) {
             FileEndSha256(ff);
         }
+        if (ff->sha3_256_ctx) {
+            SCSha3_256Finalize(ff->sha3_256_ctx, ff->sha3_256, sizeof(ff->sha3_256));
+            ff->sha3_256_ctx = NULL;
+            ff->flags |= FILE_SHA3_256;
+        }
+        if (ff->ssdeep_ctx) {
+            char str[SC_SSDEEP_MAX_LEN];
+            if (SCSsdeepFinalize(ff->ssdeep_ctx, str, sizeof(str))) {
+                ff->ssdeep = SCStrdup(str);
+            }
+            ff->ssdeep_ctx = NULL;
+        }
+        if (ff->tlsh_ctx) {
+            char str[SC_TLSH_LEN];
+            /* no digest for files that are too small or too uniform */
+            if (SCTlshFinalize(ff->tlsh_ctx, str, sizeof(str))) {
+                ff->tlsh = SCStrdup(str);
+            }
+            ff->tlsh_ctx = NULL;
+        }
     }
 
     SCReturnInt(0);
@@ -1128,8 +1271,9 @@ static void FileDisableStoringForFile(File *ff)
     ff->flags |= FILE_NOSTORE;
 
     if (ff->state == FILE_STATE_OPENED && FileDataSize(ff) >= (uint64_t)FileMagicSize()) {
-        if (g_file_force_md5 == 0 && g_file_force_sha1 == 0 && g_file_force_sha256 == 0
-                && g_file_force_tracking == 0) {
+        if (g_file_force_md5 == 0 && g_file_force_sha1 == 0 && g_file_force_sha256 == 0 &&
+                g_file_force_sha3_256 == 0 && g_file_force_ssdeep == 0 &&
+                g_file_force_tlsh == 0 && g_file_force_tracking == 0) {
             (void)FileCloseFilePtr(ff, NULL, 0,
                     (FILE_TRUNCATED|FILE_NOSTORE));
         }
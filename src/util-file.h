@@ -42,6 +42,15 @@ typedef struct SCSha1 SCSha1;
 typedef struct SCMd5 SCMd5;
 #define SC_MD5_LEN 16
 
+typedef struct SCSha3_256 SCSha3_256;
+#define SC_SHA3_256_LEN 32
+
+typedef struct SCSsdeep SCSsdeep;
+#define SC_SSDEEP_MAX_LEN 148
+
+typedef struct SCTlsh SCTlsh;
+#define SC_TLSH_LEN 73
+
 #define FILE_TRUNCATED  BIT_U16(0)
 #define FILE_NOMAGIC    BIT_U16(1)
 #define FILE_NOMD5      BIT_U16(2)
@@ -56,6 +65,7 @@ typedef struct SCMd5 SCMd5;
 #define FILE_STORED     BIT_U16(11)
 #define FILE_NOTRACK    BIT_U16(12) /**< track size of file */
 #define FILE_USE_DETECT BIT_U16(13) /**< use content_inspected tracker */
+#define FILE_SHA3_256   BIT_U16(14)
 #define FILE_HAS_GAPS   BIT_U16(15)
 
 typedef enum FileState_ {
@@ -90,6 +100,13 @@ typedef struct File_ {
     uint8_t sha1[SC_SHA1_LEN];
     SCSha256 *sha256_ctx;
     uint8_t sha256[SC_SHA256_LEN];
+    /* the hashes below are only calculated when forced by an output */
+    SCSha3_256 *sha3_256_ctx;
+    uint8_t sha3_256[SC_SHA3_256_LEN];
+    SCSsdeep *ssdeep_ctx;
+    char *ssdeep;                   /**< digest string, NULL if not set */
+    SCTlsh *tlsh_ctx;
+    char *tlsh;                     /**< digest string, NULL if not set */
     uint64_t content_inspected;     /**< used in pruning if FILE_USE_DETECT
                                      *   flag is set */
     uint64_t content_stored;
@@ -233,6 +250,15 @@ int FileForceSha1(void);
 void FileForceSha256Enable(void);
 int FileForceSha256(void);
 
+void FileForceSha3_256Enable(void);
+int FileForceSha3_256(void);
+
+void FileForceSsdeepEnable(void);
+int FileForceSsdeep(void);
+
+void FileForceTlshEnable(void);
+int FileForceTlsh(void);
+
 void FileUpdateFlowFileFlags(Flow *f, uint16_t set_file_flags, uint8_t direction);
 
 void FileForceHashParseCfg(ConfNode *);
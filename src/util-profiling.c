@@ -1319,6 +1319,20 @@ const char * PacketProfileLoggertIdToString(LoggerId id)
         CASE_CODE (LOGGER_JSON_RDP);
         CASE_CODE (LOGGER_JSON_DCERPC);
         CASE_CODE (LOGGER_JSON_HTTP2);
+        CASE_CODE (LOGGER_JSON_QUIC);
+        CASE_CODE (LOGGER_JSON_LDAP);
+        CASE_CODE (LOGGER_JSON_TELNET);
+        CASE_CODE (LOGGER_JSON_LLMNR);
+        CASE_CODE (LOGGER_JSON_NBNS);
+        CASE_CODE (LOGGER_JSON_STRATUM);
+        CASE_CODE (LOGGER_JSON_KAFKA);
+        CASE_CODE (LOGGER_JSON_IRC);
+        CASE_CODE (LOGGER_JSON_XMPP);
+        CASE_CODE (LOGGER_JSON_OPENVPN);
+        CASE_CODE (LOGGER_JSON_DTLS);
+        CASE_CODE (LOGGER_JSON_RTP);
+        CASE_CODE (LOGGER_JSON_ICAP);
+        CASE_CODE (LOGGER_JSON_BITTORRENT);
         CASE_CODE (LOGGER_TLS_STORE);
         CASE_CODE (LOGGER_TLS);
         CASE_CODE (LOGGER_FILE_STORE);